@@ -0,0 +1,213 @@
+//keeps a pool's permissionless maintenance instructions going from a single process
+//
+//every task runs at its own interval (tasks without one are off) and right after the crank starts. the pool's state is
+// reloaded before each round, tasks that have nothing to do for the pool (e.g. CheckPeg without oracles) are skipped
+// and failed transactions are reported without stopping the crank, which only ends on errors reading the pool
+
+use crate::{describe, user_accounts, CliResult, Config};
+use clap::Args;
+use pool::{client::pool::PoolClient, decimal::DecimalU64, state::InvariantKind};
+use solana_program::{clock::UnixTimestamp, instruction::Instruction, pubkey::Pubkey};
+use std::{
+    thread,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+#[derive(Args)]
+pub struct CrankArgs {
+    /// Address of the pool
+    pool: Pubkey,
+    /// Seconds between CheckPegs, which pause depegged tokens and advance the amp damping
+    #[clap(long)]
+    check_peg_interval: Option<UnixTimestamp>,
+    /// Seconds between PublishVirtualPrices, which integrators read the lp token's price from
+    #[clap(long)]
+    virtual_price_interval: Option<UnixTimestamp>,
+    /// Seconds between SnapshotLpSupplys, which record the lp supply for time weighted distributions
+    #[clap(long)]
+    lp_supply_interval: Option<UnixTimestamp>,
+    /// Seconds between CheckpointFees of the keypair's lp token account
+    #[clap(long)]
+    fee_checkpoint_interval: Option<UnixTimestamp>,
+    /// Skips fee checkpoints until the lp account earned more than this many lp tokens' worth of fees
+    #[clap(long, default_value = "0")]
+    fee_dust: u64,
+    /// Seconds between Repegs of a hybrid pool
+    #[clap(long)]
+    repeg_interval: Option<UnixTimestamp>,
+    /// Runs every task once and exits
+    #[clap(long)]
+    once: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Task {
+    CheckPeg,
+    PublishVirtualPrice,
+    SnapshotLpSupply,
+    CheckpointFees,
+    Repeg,
+}
+
+impl Task {
+    fn name(&self) -> &'static str {
+        match self {
+            Task::CheckPeg => "check peg",
+            Task::PublishVirtualPrice => "publish virtual price",
+            Task::SnapshotLpSupply => "snapshot lp supply",
+            Task::CheckpointFees => "checkpoint fees",
+            Task::Repeg => "repeg",
+        }
+    }
+}
+
+//when each task is due next, in seconds of the local clock
+struct Schedule {
+    tasks: Vec<(Task, UnixTimestamp, UnixTimestamp)>,
+}
+
+impl Schedule {
+    //every task with an interval is due right away
+    fn new(intervals: &[(Task, Option<UnixTimestamp>)]) -> CliResult<Self> {
+        let mut tasks = vec![];
+        for &(task, interval) in intervals {
+            match interval {
+                Some(interval) if interval <= 0 => {
+                    return Err(format!("the interval of {} has to be positive", task.name()).into())
+                }
+                Some(interval) => tasks.push((task, interval, 0)),
+                None => (),
+            }
+        }
+        if tasks.is_empty() {
+            return Err("no task has an interval, nothing to crank".into());
+        }
+        Ok(Self { tasks })
+    }
+
+    //the tasks due at `now`, which are then scheduled an interval later
+    fn take_due(&mut self, now: UnixTimestamp) -> Vec<Task> {
+        let mut due = vec![];
+        for (task, interval, next_ts) in self.tasks.iter_mut() {
+            if *next_ts <= now {
+                due.push(*task);
+                *next_ts = now + *interval;
+            }
+        }
+        due
+    }
+
+    fn next_ts(&self) -> UnixTimestamp {
+        self.tasks.iter().map(|&(_, _, next_ts)| next_ts).min().unwrap()
+    }
+}
+
+fn local_now() -> UnixTimestamp {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs() as UnixTimestamp
+}
+
+pub fn run(config: Config, args: CrankArgs) -> CliResult<()> {
+    let (mut pool_client, sender) = config.load_pool(args.pool)?;
+    if sender.is_exporting() {
+        return Err("crank sends its own transactions, it can't run with --offline or --dump-base64".into());
+    }
+    let mut schedule = Schedule::new(&[
+        (Task::CheckPeg, args.check_peg_interval),
+        (Task::PublishVirtualPrice, args.virtual_price_interval),
+        (Task::SnapshotLpSupply, args.lp_supply_interval),
+        (Task::CheckpointFees, args.fee_checkpoint_interval),
+        (Task::Repeg, args.repeg_interval),
+    ])?;
+    loop {
+        let due = schedule.take_due(local_now());
+        pool_client.reload()?;
+        for task in due {
+            let instruction = match crank_ix(&pool_client, &sender.pubkey(), task, args.fee_dust)? {
+                Ok(instruction) => instruction,
+                Err(reason) => {
+                    println!("{}: skipped, {}", task.name(), reason);
+                    continue;
+                }
+            };
+            println!("{}:", task.name());
+            if let Err(err) = sender.submit(&pool_client.rpc_client, &[instruction], &[]) {
+                println!("{}: failed, {}", task.name(), describe(err.as_ref()));
+            }
+        }
+        if args.once {
+            return Ok(());
+        }
+        let wait = (schedule.next_ts() - local_now()).max(0);
+        thread::sleep(Duration::from_secs(wait as u64));
+    }
+}
+
+//the task's instruction for the pool in its current state, or why there is nothing to do
+fn crank_ix(
+    pool_client: &PoolClient,
+    payer: &Pubkey,
+    task: Task,
+    fee_dust: u64,
+) -> CliResult<Result<Instruction, String>> {
+    let state = &pool_client.state;
+    Ok(Ok(match task {
+        Task::CheckPeg => {
+            if !state.has_token_oracles() {
+                return Ok(Err("not every token has an oracle".to_string()));
+            }
+            if state.peg_band.get() == DecimalU64::from(0) && !state.amp_damping.is_enabled() {
+                return Ok(Err("the peg band and amp damping are off".to_string()));
+            }
+            pool_client.check_peg_ix()?
+        }
+        Task::PublishVirtualPrice => pool_client.publish_virtual_price_ix(payer)?,
+        Task::SnapshotLpSupply => pool_client.snapshot_lp_supply_ix(payer)?,
+        Task::CheckpointFees => {
+            let lp_account = user_accounts(pool_client, payer).1;
+            if pool_client.rpc_client.get_balance(&lp_account)? == 0 {
+                return Ok(Err(format!("the lp account {} doesn't exist", lp_account)));
+            }
+            if let Some(checkpoint) = pool_client.fee_checkpoint(&lp_account)? {
+                let earned = checkpoint.lp_fees_earned(state.lp_fee_growth);
+                if earned <= fee_dust {
+                    return Ok(Err(format!("{} lp tokens' worth of fees earned so far", earned)));
+                }
+            }
+            pool_client.checkpoint_fees_ix(&lp_account, payer, payer)?
+        }
+        Task::Repeg => {
+            if state.invariant_kind != InvariantKind::Hybrid {
+                return Ok(Err("the pool isn't a hybrid pool".to_string()));
+            }
+            if state.is_paused {
+                return Ok(Err("the pool is paused".to_string()));
+            }
+            pool_client.repeg_ix()?
+        }
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn schedule() {
+        let mut schedule = Schedule::new(&[
+            (Task::CheckPeg, Some(10)),
+            (Task::PublishVirtualPrice, None),
+            (Task::Repeg, Some(25)),
+        ])
+        .unwrap();
+        assert_eq!(schedule.take_due(100), vec![Task::CheckPeg, Task::Repeg]);
+        assert_eq!(schedule.next_ts(), 110);
+        assert!(schedule.take_due(109).is_empty());
+        assert_eq!(schedule.take_due(110), vec![Task::CheckPeg]);
+        //a late round runs each task once and counts its interval from then
+        assert_eq!(schedule.take_due(140), vec![Task::CheckPeg, Task::Repeg]);
+        assert_eq!(schedule.next_ts(), 150);
+
+        assert!(Schedule::new(&[(Task::CheckPeg, None)]).is_err());
+        assert!(Schedule::new(&[(Task::CheckPeg, Some(0))]).is_err());
+    }
+}
@@ -0,0 +1,254 @@
+use crate::{
+    check_index, create_missing_accounts, create_missing_launch_allocation, create_missing_rebate, send_as_user,
+    time::format_timestamp, token_amounts, user_accounts, CliResult, Config,
+};
+use clap::{Args, Subcommand};
+use pool::{
+    instruction::{find_rebate_campaign_address, DeFiInstruction},
+    TOKEN_COUNT,
+};
+use solana_program::pubkey::Pubkey;
+use spl_associated_token_account::get_associated_token_address;
+
+#[derive(Args)]
+pub struct AddArgs {
+    /// Address of the pool
+    pool: Pubkey,
+    /// Amount of every token to add (in atomic units and the pool's token order)
+    #[clap(required = true)]
+    amounts: Vec<u64>,
+    /// Fail if fewer lp tokens would be minted
+    #[clap(long, default_value = "0")]
+    minimum_mint_amount: u64,
+}
+
+#[derive(Args)]
+pub struct SwapArgs {
+    /// Address of the pool
+    pool: Pubkey,
+    /// Index of the token that is paid
+    #[clap(long)]
+    input_index: u8,
+    /// Index of the token that is received
+    #[clap(long)]
+    output_index: u8,
+    /// Exact amount that is paid or, with --exact-output, exact amount that is received
+    amount: u64,
+    /// Receive exactly `amount` instead of paying exactly `amount`
+    #[clap(long)]
+    exact_output: bool,
+    /// Minimum amount received or, with --exact-output, maximum amount paid [default: no limit]
+    #[clap(long)]
+    limit: Option<u64>,
+}
+
+#[derive(Args)]
+pub struct RemoveArgs {
+    /// Address of the pool
+    pool: Pubkey,
+    #[clap(subcommand)]
+    kind: RemoveKind,
+}
+
+#[derive(Args)]
+pub struct AnnounceWithdrawalArgs {
+    /// Address of the pool
+    pool: Pubkey,
+    /// Most lp tokens to burn in removes above the pool's withdrawal cooldown threshold, 0 withdraws the announcement
+    lp_amount: u64,
+}
+
+#[derive(Args)]
+pub struct ClaimRebateArgs {
+    /// Address of the pool
+    pool: Pubkey,
+    /// Id of the rebate campaign
+    campaign_id: u64,
+}
+
+#[derive(Args)]
+pub struct RedeemSunsetArgs {
+    /// Address of the pool
+    pool: Pubkey,
+}
+
+#[derive(Subcommand)]
+enum RemoveKind {
+    /// Burns lp tokens in exchange for a proportional share of every token
+    Uniform {
+        burn_amount: u64,
+        /// Minimum amount of every token to receive, comma separated [default: no limit]
+        #[clap(long, use_value_delimiter = true)]
+        minimum_output_amounts: Vec<u64>,
+    },
+    /// Burns lp tokens in exchange for a single token
+    ExactBurn {
+        burn_amount: u64,
+        /// Index of the token that is received
+        #[clap(long)]
+        output_index: u8,
+        #[clap(long, default_value = "0")]
+        minimum_output_amount: u64,
+    },
+    /// Removes exact amounts of tokens in exchange for lp tokens
+    ExactOutput {
+        /// Amount of every token to remove (in atomic units and the pool's token order)
+        #[clap(required = true)]
+        amounts: Vec<u64>,
+        /// Maximum amount of lp tokens to burn [default: no limit]
+        #[clap(long)]
+        maximum_burn_amount: Option<u64>,
+    },
+}
+
+pub fn add(config: Config, args: AddArgs) -> CliResult<()> {
+    execute(
+        config,
+        args.pool,
+        DeFiInstruction::Add {
+            input_amounts: token_amounts(&args.amounts)?,
+            minimum_mint_amount: args.minimum_mint_amount,
+        },
+    )
+}
+
+pub fn swap(config: Config, args: SwapArgs) -> CliResult<()> {
+    for &index in [args.input_index, args.output_index].iter() {
+        check_index(index)?;
+    }
+    if args.input_index == args.output_index {
+        return Err("input and output token must differ".into());
+    }
+    let mut amounts = [0; TOKEN_COUNT];
+    let defi_instruction = if args.exact_output {
+        amounts[args.output_index as usize] = args.amount;
+        DeFiInstruction::SwapExactOutput {
+            maximum_input_amount: args.limit.unwrap_or(u64::MAX),
+            input_token_index: args.input_index,
+            exact_output_amounts: amounts,
+        }
+    } else {
+        amounts[args.input_index as usize] = args.amount;
+        DeFiInstruction::SwapExactInput {
+            exact_input_amounts: amounts,
+            output_token_index: args.output_index,
+            minimum_output_amount: args.limit.unwrap_or(0),
+        }
+    };
+    execute(config, args.pool, defi_instruction)
+}
+
+pub fn remove(config: Config, args: RemoveArgs) -> CliResult<()> {
+    let defi_instruction = match args.kind {
+        RemoveKind::Uniform {
+            burn_amount,
+            minimum_output_amounts,
+        } => DeFiInstruction::RemoveUniform {
+            exact_burn_amount: burn_amount,
+            minimum_output_amounts: if minimum_output_amounts.is_empty() {
+                [0; TOKEN_COUNT]
+            } else {
+                token_amounts(&minimum_output_amounts)?
+            },
+        },
+        RemoveKind::ExactBurn {
+            burn_amount,
+            output_index,
+            minimum_output_amount,
+        } => {
+            check_index(output_index)?;
+            DeFiInstruction::RemoveExactBurn {
+                exact_burn_amount: burn_amount,
+                output_token_index: output_index,
+                minimum_output_amount,
+            }
+        }
+        RemoveKind::ExactOutput {
+            amounts,
+            maximum_burn_amount,
+        } => DeFiInstruction::RemoveExactOutput {
+            maximum_burn_amount: maximum_burn_amount.unwrap_or(u64::MAX),
+            exact_output_amounts: token_amounts(&amounts)?,
+        },
+    };
+    execute(config, args.pool, defi_instruction)
+}
+
+pub fn announce_withdrawal(config: Config, args: AnnounceWithdrawalArgs) -> CliResult<()> {
+    let (pool_client, sender) = config.load_pool(args.pool)?;
+    let state = &pool_client.state;
+    if !state.has_withdrawal_cooldown() {
+        println!("the pool has no withdrawal cooldown, removes don't need to be announced");
+    } else if args.lp_amount > 0 {
+        let now = pool_client.now()?;
+        println!(
+            "removes beyond the {} lp tokens that a slot lets go unannounced can burn up to {} lp tokens from {}",
+            state.withdrawal_cooldown_threshold,
+            args.lp_amount,
+            format_timestamp(now + state.withdrawal_cooldown, now)
+        );
+    }
+    let ix = pool_client.announce_withdrawal_ix(&sender.pubkey(), args.lp_amount)?;
+    sender.submit(&pool_client.rpc_client, &[ix], &[])
+}
+
+pub fn claim_rebate(config: Config, args: ClaimRebateArgs) -> CliResult<()> {
+    let (pool_client, sender) = config.load_pool(args.pool)?;
+    let owner = sender.pubkey();
+    let campaign = pool_client
+        .rebate_campaign(args.campaign_id)?
+        .ok_or_else(|| format!("the pool has no rebate campaign {}", args.campaign_id))?;
+    let now = pool_client.now()?;
+    if campaign.end_ts > now {
+        return Err(format!("rebates can be claimed from {}", format_timestamp(campaign.end_ts, now)).into());
+    }
+    let campaign_key = find_rebate_campaign_address(&pool_client.program_id, &args.pool, args.campaign_id).0;
+    let rebate = pool_client
+        .rebate(&campaign_key, &owner)?
+        .ok_or("no rebate to claim, swaps record their fees once their swapper joined")?;
+    println!("claiming {} of {}", campaign.rebate(rebate.fees), campaign.rebate_mint);
+    let mut instructions = create_missing_accounts(&pool_client.rpc_client, &owner, &[campaign.rebate_mint])?;
+    instructions.push(pool_client.claim_rebate_ix(
+        args.campaign_id,
+        &owner,
+        &get_associated_token_address(&owner, &campaign.rebate_mint),
+    )?);
+    sender.submit(&pool_client.rpc_client, &instructions, &[])
+}
+
+pub fn redeem_sunset(config: Config, args: RedeemSunsetArgs) -> CliResult<()> {
+    let (pool_client, sender) = config.load_pool(args.pool)?;
+    let state = &pool_client.state;
+    if !state.is_sunset() {
+        return Err("the pool isn't being wound down".into());
+    }
+    let now = pool_client.now()?;
+    if state.sunset_ts > now {
+        return Err(format!(
+            "lp tokens can be redeemed from {}, until then they can be removed",
+            format_timestamp(state.sunset_ts, now)
+        )
+        .into());
+    }
+    let (token_accounts, lp_account) = user_accounts(&pool_client, &sender.pubkey());
+    let ix = pool_client.redeem_sunset_ix(&lp_account, &sender.pubkey(), &token_accounts)?;
+    send_as_user(&pool_client, &sender, &[ix])
+}
+
+fn execute(config: Config, pool: Pubkey, defi_instruction: DeFiInstruction<TOKEN_COUNT>) -> CliResult<()> {
+    let (pool_client, sender) = config.load_pool(pool)?;
+    let (token_accounts, lp_account) = user_accounts(&pool_client, &sender.pubkey());
+    let is_swap = defi_instruction.is_swap();
+    let mut instructions = vec![];
+    if let DeFiInstruction::Add { .. } = defi_instruction {
+        instructions.extend(create_missing_launch_allocation(&pool_client, &sender.pubkey())?);
+    }
+    let mut instruction = pool_client.defi_ix(defi_instruction, &sender.pubkey(), &token_accounts, &lp_account)?;
+    //swaps earn rebates while the pool's rebate campaign is open
+    if is_swap && pool_client.state.rebate_end_ts > pool_client.now()? {
+        instructions.extend(create_missing_rebate(&pool_client, &sender.pubkey())?);
+        pool_client.record_swap_rebate(&mut instruction, &sender.pubkey());
+    }
+    instructions.push(instruction);
+    send_as_user(&pool_client, &sender, &instructions)
+}
@@ -0,0 +1,44 @@
+//deploys the pools of a manifest (see pool::client::deploy) one transaction each, skipping the pools that exist
+// already, so a deployment that failed halfway is finished by running it again
+
+use crate::{CliResult, Config};
+use clap::Args;
+use pool::client::deploy::{DeployManifest, StepStatus};
+use solana_program::pubkey::Pubkey;
+use std::{path::PathBuf, slice};
+
+#[derive(Args)]
+pub struct DeployArgs {
+    /// Manifest of the pools to deploy, read as json if the file name ends with .json and as toml otherwise
+    manifest: PathBuf,
+    /// Only print the plan and which of its pools exist already
+    #[clap(long)]
+    dry_run: bool,
+    /// Id of the deployed pool program
+    #[clap(long, default_value_t = pool::id())]
+    program_id: Pubkey,
+}
+
+pub fn run(config: Config, args: DeployArgs) -> CliResult<()> {
+    let Config { rpc_client, sender } = config;
+    let plan = DeployManifest::load(&args.manifest)?.plan(&args.program_id, &sender.pubkey())?;
+    let status = plan.status(&rpc_client)?;
+    for (step, status) in plan.steps.iter().zip(status.iter()) {
+        let status = match status {
+            StepStatus::Pending => "pending",
+            StepStatus::Deployed => "deployed",
+        };
+        println!("{}: {} ({})", step.name, step.pool, status);
+    }
+    if args.dry_run {
+        return Ok(());
+    }
+
+    for (step, status) in plan.steps.iter().zip(status) {
+        if status == StepStatus::Pending {
+            println!("deploying {}", step.name);
+            sender.submit(&rpc_client, slice::from_ref(&step.instruction), &[])?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,50 @@
+//emergency responders
+//
+//responders can't change any setting, they can only put the pool into withdrawal-only mode (i.e. pause it) once enough
+// of them confirmed an emergency. like governance commands, the transaction can be printed for signing elsewhere
+// (--offline or --dump-base64)
+
+use crate::{CliResult, Config};
+use clap::Args;
+use pool::state::EMERGENCY_CONFIRMATION_WINDOW;
+use solana_program::pubkey::Pubkey;
+
+#[derive(Args)]
+pub struct ConfirmEmergencyArgs {
+    /// Address of the pool
+    pool: Pubkey,
+}
+
+pub fn confirm_emergency(config: Config, args: ConfirmEmergencyArgs) -> CliResult<()> {
+    let (pool_client, sender) = config.load_pool(args.pool)?;
+    let state = &pool_client.state;
+    if state.is_paused {
+        return Err("the pool is paused already".into());
+    }
+    let responder_index = state
+        .emergency_responder_index(&sender.pubkey())
+        .ok_or("the keypair isn't one of the pool's emergency responders")?;
+    let now = pool_client.now()?;
+    //the keypair's own earlier confirmation is replaced
+    let mut confirmation_ts = state.emergency_confirmation_ts;
+    confirmation_ts[responder_index] = 0;
+    let others = confirmation_ts
+        .iter()
+        .filter(|&&ts| ts != 0 && now - ts < EMERGENCY_CONFIRMATION_WINDOW)
+        .count();
+    if others + 1 >= state.emergency_threshold as usize {
+        println!(
+            "confirmation {} of {} pauses the pool",
+            others + 1,
+            state.emergency_threshold
+        );
+    } else {
+        println!(
+            "confirmation {} of {}, the others have to confirm within the hour",
+            others + 1,
+            state.emergency_threshold
+        );
+    }
+    let ix = pool_client.confirm_emergency_ix(&sender.pubkey())?;
+    sender.submit(&pool_client.rpc_client, &[ix], &[])
+}
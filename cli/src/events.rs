@@ -0,0 +1,187 @@
+//exports a pool's history (see src/client/history.rs) for accounting and analytics, one row or json line per event in
+// chronological order. amounts are atomic and signed from the perspective of the respective account, e.g. an add shows
+// positive pool balance changes and a positive user lp change
+
+use crate::{CliResult, Config};
+use clap::{ArgEnum, Args};
+use pool::{client::history::PoolEvent, TOKEN_COUNT};
+use serde_json::json;
+use solana_program::{clock::Slot, pubkey::Pubkey};
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::PathBuf,
+};
+
+#[derive(Args)]
+pub struct ExportEventsArgs {
+    /// Address of the pool
+    pool: Pubkey,
+    /// Oldest slot to export
+    #[clap(long, default_value = "0")]
+    from_slot: Slot,
+    #[clap(long, arg_enum, default_value = "csv")]
+    format: Format,
+    /// File to write to [default: stdout]
+    #[clap(long, short = 'o')]
+    output: Option<PathBuf>,
+}
+
+#[derive(ArgEnum, Clone, Copy)]
+enum Format {
+    Csv,
+    Jsonl,
+}
+
+pub fn run(config: Config, args: ExportEventsArgs) -> CliResult<()> {
+    let (pool_client, _) = config.load_pool(args.pool)?;
+    let mut writer: Box<dyn Write> = match &args.output {
+        Some(path) => Box::new(BufWriter::new(File::create(path)?)),
+        None => Box::new(BufWriter::new(io::stdout())),
+    };
+    if let Format::Csv = args.format {
+        writeln!(writer, "{}", csv_header())?;
+    }
+    let mut count = 0;
+    pool_client.events(args.from_slot, |event| {
+        let line = match args.format {
+            Format::Csv => csv_row(&event),
+            Format::Jsonl => json_line(&event),
+        };
+        writeln!(writer, "{}", line)?;
+        count += 1;
+        Ok(())
+    })?;
+    writer.flush()?;
+    if args.output.is_some() {
+        println!("exported {} events", count);
+    }
+    Ok(())
+}
+
+fn csv_header() -> String {
+    let mut columns = vec![
+        "signature".to_string(),
+        "slot".into(),
+        "block_time".into(),
+        "kind".into(),
+        "authority".into(),
+    ];
+    columns.extend((0..TOKEN_COUNT).map(|i| format!("pool_balance_change_{}", i)));
+    columns.push("user_lp_change".into());
+    columns.push("governance_fee_change".into());
+    columns.push("lp_supply".into());
+    columns.join(",")
+}
+
+fn csv_row(event: &PoolEvent) -> String {
+    let transaction = &event.transaction;
+    let mut fields = vec![
+        transaction.signature.to_string(),
+        transaction.slot.to_string(),
+        transaction.block_time.map(|time| time.to_string()).unwrap_or_default(),
+        event.kind.to_string(),
+        event.authority.to_string(),
+    ];
+    fields.extend(event.pool_balance_changes.iter().map(|change| change.to_string()));
+    fields.push(event.user_lp_change.to_string());
+    fields.push(event.governance_fee_change.to_string());
+    fields.push(lp_supply(event).map(|supply| supply.to_string()).unwrap_or_default());
+    fields.join(",")
+}
+
+//amounts are strings like in `show --json`
+fn json_line(event: &PoolEvent) -> String {
+    let transaction = &event.transaction;
+    json!({
+        "signature": transaction.signature.to_string(),
+        "slot": transaction.slot,
+        "block_time": transaction.block_time,
+        "kind": event.kind,
+        "authority": event.authority.to_string(),
+        "pool_balance_changes": event.pool_balance_changes.iter().map(|change| change.to_string()).collect::<Vec<_>>(),
+        "user_lp_change": event.user_lp_change.to_string(),
+        "governance_fee_change": event.governance_fee_change.to_string(),
+        "lp_supply": lp_supply(event).map(|supply| supply.to_string()),
+    })
+    .to_string()
+}
+
+//the lp supply after the event, only known for defi instructions
+fn lp_supply(event: &PoolEvent) -> Option<u64> {
+    event.lp_supply_event.map(|lp_supply_event| lp_supply_event.lp_supply)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pool::{client::history::EventTransaction, state::LpSupplyEvent};
+    use serde_json::Value;
+    use solana_sdk::signature::Signature;
+
+    #[test]
+    fn formats() {
+        let mut pool_balance_changes = [0; TOKEN_COUNT];
+        pool_balance_changes[0] = -5;
+        let event = PoolEvent {
+            transaction: EventTransaction {
+                signature: Signature::default(),
+                slot: 7,
+                block_time: None,
+            },
+            kind: "swap_exact_input",
+            authority: Pubkey::default(),
+            pool_balance_changes,
+            user_lp_change: 0,
+            governance_fee_change: 1,
+            lp_supply_event: Some(LpSupplyEvent {
+                user_lp_change: 0,
+                governance_mint_amount: 1,
+                lp_supply: 1001,
+            }),
+        };
+
+        let row = csv_row(&event);
+        assert_eq!(row.split(',').count(), csv_header().split(',').count());
+        assert!(row.contains(",7,,swap_exact_input,"));
+        assert!(row.ends_with(&format!(",-5{},0,1,1001", ",0".repeat(TOKEN_COUNT - 1))));
+
+        let value: Value = serde_json::from_str(&json_line(&event)).unwrap();
+        assert_eq!(value["slot"], 7);
+        assert_eq!(value["block_time"], Value::Null);
+        assert_eq!(value["pool_balance_changes"][0], "-5");
+        assert_eq!(value["governance_fee_change"], "1");
+        assert_eq!(value["lp_supply"], "1001");
+    }
+}
+
+#[derive(Args)]
+pub struct RejectionsArgs {
+    /// Address of the pool
+    pool: Pubkey,
+    /// Oldest slot to count
+    #[clap(long, default_value = "0")]
+    from_slot: Slot,
+}
+
+pub fn rejections(config: Config, args: RejectionsArgs) -> CliResult<()> {
+    let (pool_client, _) = config.load_pool(args.pool)?;
+    let counts = pool_client.rejections(args.from_slot)?;
+    let total = counts.succeeded + counts.rejected();
+    println!("transactions:           {}", total);
+    println!("succeeded:              {}", counts.succeeded);
+    for (class, count) in [
+        ("slippage", counts.slippage),
+        ("paused", counts.paused),
+        ("validation", counts.validation),
+        ("other", counts.other),
+    ] {
+        let share = if total == 0 {
+            0.0
+        } else {
+            count as f64 / total as f64 * 100.0
+        };
+        println!("{:<24}{} ({:.2}%)", format!("{}:", class), count, share);
+    }
+    Ok(())
+}
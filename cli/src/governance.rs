@@ -0,0 +1,1651 @@
+//governance instructions
+//
+//every command prints the change it is about to make and can be simulated with --dry-run first (which doesn't require
+// the governance keypair), prepare commands report when the prepared change becomes enactable. with --offline or
+// --dump-base64 the transaction is printed for the governance (e.g. a multisig) to sign instead
+
+use crate::{
+    check_index, parse_decimal, parse_fee,
+    time::{format_duration, format_time_of_day, format_timestamp, parse_time_of_day},
+    CliResult, Config, Sender,
+};
+use clap::{ArgEnum, Args, Subcommand};
+use pool::{
+    amp_factor::MIN_ADJUSTMENT_WINDOW,
+    client::pool::PoolClient,
+    decimal::DecimalU64,
+    instruction::{find_allowed_depositor_address, find_allowed_user_address, GovernanceInstruction},
+    oracle::{OracleFallback, OracleKind, OracleLimits, RateSource, BPS_ONE, EMA_DECIMALS, RATE_DECIMALS},
+    processor::ENACT_DELAY,
+    state::{
+        CallerMode, InvariantKind, MaintenanceWindow, PegKeeper, PriceBand, Role, TokenFlags, TokenParticipation,
+        TradingHours, UserGate, YieldAdapter, GAUGE_CLAIM_PERIOD, MAX_ALLOWED_CALLERS, MAX_EMERGENCY_RESPONDERS,
+        MAX_GAUGE_REWARDS, MAX_HOOK_PROGRAMS, MAX_MAINTENANCE_WINDOWS, MAX_REBATE_WINDOW, MAX_RFQ_DEVIATION_BPS,
+        MAX_WITHDRAWAL_COOLDOWN, REBATE_CLAIM_PERIOD,
+    },
+    TOKEN_COUNT,
+};
+use solana_program::{
+    clock::{Slot, UnixTimestamp},
+    pubkey::Pubkey,
+};
+use spl_associated_token_account::get_associated_token_address;
+
+#[derive(Args)]
+pub struct GovernanceArgs {
+    /// Address of the pool
+    pool: Pubkey,
+    /// Only simulate the transaction, the signing keypair doesn't have to be the pool's governance
+    #[clap(long)]
+    dry_run: bool,
+    #[clap(subcommand)]
+    command: GovernanceCommand,
+}
+
+#[derive(Subcommand)]
+enum GovernanceCommand {
+    /// Prepares new fees that can be enacted once the enact delay has passed
+    PrepareFee {
+        /// e.g. 3bps, 0.03% or 0.0003
+        #[clap(long, parse(try_from_str = parse_fee))]
+        lp_fee: DecimalU64,
+        #[clap(long, parse(try_from_str = parse_fee))]
+        governance_fee: DecimalU64,
+    },
+    /// Enacts the prepared fees
+    EnactFee,
+    /// Hands governance of the pool over to another key (prepare, then enact once the enact delay has passed)
+    Transfer {
+        #[clap(subcommand)]
+        step: TransferStep,
+    },
+    /// Pauses or unpauses the pool (only uniform removes remain possible while it's paused)
+    SetPaused {
+        #[clap(parse(try_from_str))]
+        paused: bool,
+    },
+    /// Gradually adjusts the amp factor towards a new value
+    AdjustAmp {
+        #[clap(parse(try_from_str = parse_decimal))]
+        target_value: DecimalU64,
+        /// Duration of the adjustment in seconds [default: the minimum adjustment window]
+        #[clap(long)]
+        duration: Option<UnixTimestamp>,
+    },
+    /// Checks swaps against the tokens' oracles (bound with token-oracle) or turns the check off
+    SetOracleGuard {
+        /// Largest deviation of a swap's execution price from the oracle cross-rate, e.g. 1% or 0.01
+        #[clap(parse(try_from_str = parse_fee), required_unless_present = "off", conflicts_with = "off")]
+        max_deviation: Option<DecimalU64>,
+        /// Turns the oracle guard off (the tokens keep their oracles)
+        #[clap(long)]
+        off: bool,
+    },
+    /// Sets how far a token's oracle price may be off its peg before anyone can pause its swaps (0 turns this off),
+    /// also resumes swaps of all tokens that were paused
+    SetPegBand {
+        /// e.g. 1% or 0.01
+        #[clap(parse(try_from_str = parse_fee))]
+        band: DecimalU64,
+    },
+    /// Sets where the exchange rate of a token comes from (pause the pool first, its balances are revalued at once)
+    SetRateSource {
+        token_index: u8,
+        #[clap(arg_enum)]
+        source: RateSourceArg,
+        /// The stake pool, pyth price, switchboard aggregator or wrapper exchange rate account, or the base pool whose
+        /// lp token the token is (not for `none` and `fixed`)
+        #[clap(
+            long,
+            required_if_eq_any = &[
+                ("source", "spl-stake-pool"),
+                ("source", "pyth"),
+                ("source", "switchboard"),
+                ("source", "base-pool"),
+                ("source", "wrapper")
+            ]
+        )]
+        account: Option<Pubkey>,
+        /// The rate of a `fixed` source, e.g. 1.05 for a token worth 1.05 of the asset
+        #[clap(long, parse(try_from_str = parse_decimal), required_if_eq("source", "fixed"))]
+        rate: Option<DecimalU64>,
+    },
+    /// Sets what guarded swaps do while a guard oracle is unavailable (see token-oracle for the limits on its readings)
+    SetOracleFallback {
+        #[clap(arg_enum)]
+        fallback: OracleFallbackArg,
+    },
+    /// Scales the amp factor down while an oracle price is off its peg for longer than the delay, until all prices are
+    /// back within the release band (requires an oracle for every token, advanced by check-peg)
+    SetAmpDamping {
+        /// Scale of the damped amp factor, e.g. 0.1 (1 turns damping off)
+        #[clap(long, parse(try_from_str = parse_decimal), required_unless_present = "off")]
+        factor: Option<DecimalU64>,
+        /// How far an oracle price has to be off its peg to trigger damping, e.g. 2% or 0.02
+        #[clap(long, parse(try_from_str = parse_fee), required_unless_present = "off")]
+        trigger_band: Option<DecimalU64>,
+        /// How close all oracle prices have to be back to their peg to release damping [default: the trigger band]
+        #[clap(long, parse(try_from_str = parse_fee))]
+        release_band: Option<DecimalU64>,
+        /// Seconds a price has to stay outside of the trigger band
+        #[clap(long, default_value = "0")]
+        delay: UnixTimestamp,
+        /// Turns damping off
+        #[clap(long, conflicts_with_all = &["factor", "trigger-band", "release-band"])]
+        off: bool,
+    },
+    /// Binds a guard oracle to a single token (prepare, then enact once the enact delay has passed)
+    TokenOracle {
+        #[clap(subcommand)]
+        step: TokenOracleStep,
+    },
+    /// Sets the programs that swaps may call back once they executed, replacing the current ones (none clears them)
+    SetHookPrograms {
+        #[clap(max_values = MAX_HOOK_PROGRAMS)]
+        programs: Vec<Pubkey>,
+    },
+    /// Sets who may call the pool's defi instructions and the programs allowed to call them via cpi, replacing the
+    /// current ones (uniform removes are never restricted)
+    SetCallerAllowlist {
+        #[clap(arg_enum)]
+        mode: CallerModeArg,
+        #[clap(max_values = MAX_ALLOWED_CALLERS)]
+        programs: Vec<Pubkey>,
+    },
+    /// Winds the pool down: stops adds and swaps and allows removes until the deadline (a unix timestamp at least the
+    /// enact delay away), after which governance pays out what's left to the lp token holders. 0 calls it off
+    SetSunset { deadline: UnixTimestamp },
+    /// Gives up governance of the pool for good, freezing its parameters; with --lock-amp a pending amp factor
+    /// adjustment stops at its current value
+    Renounce {
+        #[clap(long)]
+        lock_amp: bool,
+    },
+    /// Switches the governance fee account, moving the lp tokens of the current one (which has to be owned by the
+    /// governance) over to the new one
+    MigrateFeeAccount { new_account: Pubkey },
+    /// Sets the share of the governance fee that goes to the pool's lp token locks
+    SetLockFeeShare {
+        /// e.g. 50% or 0.5
+        #[clap(parse(try_from_str = parse_fee))]
+        share: DecimalU64,
+    },
+    /// Sets the least lp tokens that an add has to mint and that a partial remove has to leave behind (0 for none)
+    SetMinLpPosition {
+        #[clap(long, default_value = "0")]
+        min_mint: u64,
+        #[clap(long, default_value = "0")]
+        min_residual: u64,
+    },
+    /// Burns lp tokens of the governance fee account (which has to be owned by the governance), leaving the fees they
+    /// are worth to the remaining lp token holders
+    BurnFees {
+        /// All of the account's lp tokens by default
+        #[clap(long)]
+        amount: Option<u64>,
+    },
+    /// Caps the lp tokens that adds may mint to each wallet until the launch window ends, the cap lifts by itself
+    /// afterwards. 0 lifts it right away
+    SetLaunchCap {
+        max_lp_per_wallet: u64,
+        /// End of the launch window (a unix timestamp)
+        #[clap(long)]
+        end: Option<UnixTimestamp>,
+    },
+    /// Delegates a role's instructions to a key of its own, which may then sign them in place of the governance
+    SetRole {
+        #[clap(arg_enum)]
+        role: RoleArg,
+        /// Revokes the role if omitted
+        key: Option<Pubkey>,
+    },
+    /// Disables a token as an input and/or output of adds, swaps and single token removes, without flags enables it
+    /// again (uniform removes always give all tokens)
+    SetTokenFlags {
+        token_index: u8,
+        #[clap(long)]
+        disable_input: bool,
+        #[clap(long)]
+        disable_output: bool,
+    },
+    /// Sets which users may add, swap and remove (uniform removes always stay open)
+    SetUserGate {
+        #[clap(arg_enum)]
+        gate: UserGateArg,
+        /// The token that users have to hold (only for token)
+        #[clap(long, required_if_eq("gate", "token"))]
+        mint: Option<Pubkey>,
+    },
+    /// Allows a user while the pool's user gate is an allowlist, the signer pays for its allowed user account
+    SetUserAllowed {
+        user: Pubkey,
+        /// Disallows the user instead, refunding the account's rent to the signer
+        #[clap(long)]
+        disallow: bool,
+    },
+    /// Sets which users may also add and remove other than uniformly, on top of the user gate
+    SetDepositorGate {
+        #[clap(arg_enum)]
+        gate: UserGateArg,
+        /// The token that depositors have to hold (only for token)
+        #[clap(long, required_if_eq("gate", "token"))]
+        mint: Option<Pubkey>,
+    },
+    /// Allows a depositor while the pool's depositor gate is an allowlist, the signer pays for its account
+    SetDepositorAllowed {
+        depositor: Pubkey,
+        /// Disallows the depositor instead, refunding the account's rent to the signer
+        #[clap(long)]
+        disallow: bool,
+    },
+    /// Prepares the key whose signed quotes rfq swaps execute or turning rfq swaps off, enactable after the enact delay
+    /// with enact-rfq-quoter
+    SetRfqQuoter {
+        #[clap(required_unless_present = "off", conflicts_with = "off")]
+        quoter: Option<Pubkey>,
+        /// Largest deviation of a quote's price from the internal oracle's, e.g. 0.5% or 0.005, at most
+        /// MAX_RFQ_DEVIATION_BPS
+        #[clap(long, parse(try_from_str = parse_fee), required_unless_present = "off")]
+        max_deviation: Option<DecimalU64>,
+        /// Turns rfq swaps off
+        #[clap(long)]
+        off: bool,
+    },
+    /// Sets the program that defi instructions call before they execute, which can veto them, or removes it
+    SetComplianceProgram {
+        #[clap(required_unless_present = "off", conflicts_with = "off")]
+        program: Option<Pubkey>,
+        /// Removes the compliance program
+        #[clap(long)]
+        off: bool,
+    },
+    /// Sets the daily trading hours and the maintenance windows in which the pool is closed like while paused (but for
+    /// uniform removes), replacing the current ones
+    SetTradingSchedule {
+        /// UTC, e.g. 13:30-20:00 (past midnight if the close is earlier than the open), open around the clock if omitted
+        #[clap(long, parse(try_from_str = parse_trading_hours))]
+        hours: Option<TradingHours>,
+        /// Unix timestamps START-END, comma separated
+        #[clap(
+            long,
+            use_value_delimiter = true,
+            max_values = MAX_MAINTENANCE_WINDOWS,
+            parse(try_from_str = parse_maintenance_window)
+        )]
+        maintenance: Vec<MaintenanceWindow>,
+    },
+    /// Makes removes (but uniform ones) that burn more than THRESHOLD lp tokens require an announcement made COOLDOWN
+    /// seconds ahead (see announce-withdrawal), a COOLDOWN of 0 turns this off
+    SetWithdrawalCooldown {
+        threshold: u64,
+        /// In seconds, at most MAX_WITHDRAWAL_COOLDOWN
+        cooldown: UnixTimestamp,
+    },
+    /// Replaces the keys that can pause the pool (but do nothing else) once THRESHOLD of them confirmed an emergency
+    /// within an hour (see confirm-emergency), without keys removes them
+    SetEmergencyResponders {
+        #[clap(max_values = MAX_EMERGENCY_RESPONDERS)]
+        responders: Vec<Pubkey>,
+        /// How many responders have to confirm [default: all of them]
+        #[clap(long)]
+        threshold: Option<u8>,
+    },
+    /// Restricts trades of a two token pool to those that keep the price of its second token (in units of its first,
+    /// at their rates) within MIN and MAX, without either turns the band off
+    SetPriceBand {
+        #[clap(long, parse(try_from_str = parse_decimal), requires = "max")]
+        min: Option<DecimalU64>,
+        #[clap(long, parse(try_from_str = parse_decimal), requires = "min")]
+        max: Option<DecimalU64>,
+    },
+    /// Onboards or offboards a token: deposit-only tokens can only be added, withdraw-only tokens only swapped out and
+    /// removed (uniform removes always give all tokens)
+    SetTokenParticipation {
+        token_index: u8,
+        #[clap(arg_enum)]
+        participation: TokenParticipationArg,
+    },
+    /// Prepares letting KEEPER (usually a program's address) lend the pool up to CREDIT_LIMIT (in the token's smallest
+    /// units) of the token at TOKEN_INDEX to keep it at its peg, without a keeper its removal, enactable after the
+    /// enact delay with enact-peg-keeper
+    SetPegKeeper {
+        keeper: Option<Pubkey>,
+        #[clap(long, default_value = "0")]
+        token_index: u8,
+        #[clap(long, default_value = "0")]
+        credit_limit: u64,
+    },
+    /// Prepares letting anyone deploy what the pool holds of the token at TOKEN_INDEX beyond BUFFER (in the token's
+    /// smallest units) to the yield adapter program ADAPTER, without an adapter its removal, enactable after the enact
+    /// delay with enact-yield-adapter
+    SetYieldAdapter {
+        token_index: u8,
+        adapter: Option<Pubkey>,
+        #[clap(long, default_value = "0")]
+        buffer: u64,
+    },
+    /// Lets anyone repeg a hybrid pool's price for its volatile token once the token's rate source is more than
+    /// THRESHOLD_BPS away, by at most MAX_STEP_BPS of the price and at most every MIN_INTERVAL seconds, a step of 0
+    /// turns repegging off
+    SetRepegParams {
+        #[clap(long)]
+        threshold_bps: u16,
+        #[clap(long)]
+        max_step_bps: u16,
+        #[clap(long, default_value = "0")]
+        min_interval: UnixTimestamp,
+    },
+    /// Adds REWARD_MINT as a reward of the pool's liquidity mining gauge, which only FUNDER can fund (or sets the
+    /// reward's funder). With --retire the reward stops emitting and what it was yet to emit goes back to its funder's
+    /// associated token account, stakes can still claim what they earned
+    SetGaugeReward {
+        reward_mint: Pubkey,
+        #[clap(long, required_unless_present = "retire")]
+        funder: Option<Pubkey>,
+        #[clap(long, conflicts_with = "funder")]
+        retire: bool,
+    },
+    /// Starts rebate campaign ID (any id the pool didn't use yet), which escrows BUDGET tokens of REBATE_MINT from the
+    /// governance's associated token account and splits them among the swappers whose swaps record their fees with it
+    /// between --start and --end (unix timestamps) by fees, claimable once it ended. Swaps of this cli record their
+    /// fees by themselves
+    StartRebateCampaign {
+        id: u64,
+        rebate_mint: Pubkey,
+        budget: u64,
+        #[clap(long)]
+        start: UnixTimestamp,
+        #[clap(long)]
+        end: UnixTimestamp,
+    },
+    /// Returns what's left of the budget of rebate campaign ID to the governance's associated token account, once its
+    /// rebates can no longer be claimed
+    ReclaimRebateBudget { id: u64 },
+    /// Prepares the voting escrow program that boosts the gauge's stakes by their owners' vote-escrowed balances,
+    /// enactable after the enact delay with enact-gauge-ve-program. Stakes keep their boost until they are updated
+    SetGaugeVeProgram {
+        #[clap(required_unless_present = "off", conflicts_with = "off")]
+        program: Option<Pubkey>,
+        /// Removes the ve program, so stakes earn by their amount again
+        #[clap(long)]
+        off: bool,
+    },
+    /// Enacts the prepared ve program of the gauge
+    EnactGaugeVeProgram,
+    /// Enacts the prepared rfq quoter
+    EnactRfqQuoter,
+    /// Enacts the prepared peg keeper
+    EnactPegKeeper,
+    /// Enacts the prepared yield adapter
+    EnactYieldAdapter,
+}
+
+#[derive(ArgEnum, Clone, Copy)]
+enum RateSourceArg {
+    None,
+    SplStakePool,
+    Pyth,
+    Switchboard,
+    Fixed,
+    BasePool,
+    Wrapper,
+}
+
+#[derive(ArgEnum, Clone, Copy)]
+enum OracleKindArg {
+    Pyth,
+    Switchboard,
+}
+
+#[derive(ArgEnum, Clone, Copy)]
+enum CallerModeArg {
+    Unrestricted,
+    AllowedPrograms,
+    AllowedProgramsOnly,
+}
+
+#[derive(ArgEnum, Clone, Copy)]
+enum RoleArg {
+    /// prepare-fee and enact-fee
+    FeeAdmin,
+    /// adjust-amp
+    AmpAdmin,
+    /// set-paused and set-token-flags
+    PauseAdmin,
+}
+
+#[derive(ArgEnum, Clone, Copy)]
+enum TokenParticipationArg {
+    Active,
+    DepositOnly,
+    WithdrawOnly,
+}
+
+#[derive(ArgEnum, Clone, Copy)]
+enum UserGateArg {
+    Open,
+    Allowlist,
+    Token,
+}
+
+#[derive(ArgEnum, Clone, Copy)]
+enum OracleFallbackArg {
+    Reject,
+    IgnoreGuard,
+    InternalEma,
+}
+
+//a decimal rate scaled by 10^RATE_DECIMALS
+pub fn to_fixed_rate(rate: DecimalU64) -> CliResult<u64> {
+    RATE_DECIMALS
+        .checked_sub(rate.get_decimals() as u32)
+        .and_then(|shift| rate.get_raw().checked_mul(10u64.checked_pow(shift)?))
+        .filter(|&rate| rate > 0)
+        .ok_or_else(|| format!("{} isn't a positive rate with at most {} decimals", rate, RATE_DECIMALS).into())
+}
+
+//a decimal price scaled by 10^EMA_DECIMALS like the internal oracle's
+fn to_price(price: DecimalU64) -> CliResult<u64> {
+    EMA_DECIMALS
+        .checked_sub(price.get_decimals() as u32)
+        .and_then(|shift| price.get_raw().checked_mul(10u64.checked_pow(shift)?))
+        .ok_or_else(|| format!("{} isn't a price with at most {} decimals", price, EMA_DECIMALS).into())
+}
+
+fn format_price(price: u64) -> String {
+    DecimalU64::new(price, EMA_DECIMALS as u8).unwrap().to_string()
+}
+
+#[derive(Subcommand)]
+enum TransferStep {
+    /// Prepares the transition to a new governance key
+    Prepare { new_governance: Pubkey },
+    /// Enacts the prepared transition
+    Enact,
+}
+
+#[derive(Subcommand)]
+enum TokenOracleStep {
+    /// Prepares the token's new oracle and the limits on its readings, which apply to its rate feed as well
+    Prepare {
+        token_index: u8,
+        /// Pyth price account or switchboard aggregator
+        #[clap(long, required_unless_present = "remove", conflicts_with = "remove")]
+        oracle: Option<Pubkey>,
+        #[clap(long, arg_enum, default_value = "pyth")]
+        kind: OracleKindArg,
+        /// Maximum age of a reading in slots
+        #[clap(long)]
+        max_age: Slot,
+        /// Maximum confidence interval of a reading in basis points, at most 10000 (0 turns the check off)
+        #[clap(long, default_value = "0")]
+        max_conf_bps: u16,
+        /// Removes the token's oracle, which turns the oracle guard off
+        #[clap(long)]
+        remove: bool,
+    },
+    /// Enacts the prepared oracle
+    Enact,
+}
+
+pub fn run(config: Config, args: GovernanceArgs) -> CliResult<()> {
+    let (mut pool_client, sender) = config.load_pool(args.pool)?;
+    let now = pool_client.now()?;
+    let state = &pool_client.state;
+
+    let gov_instruction = match args.command {
+        GovernanceCommand::PrepareFee { lp_fee, governance_fee } => {
+            println!(
+                "fees: lp fee {} -> {}, governance fee {} -> {}",
+                state.lp_fee.get(),
+                lp_fee,
+                state.governance_fee.get(),
+                governance_fee
+            );
+            if state.fee_transition_ts != 0 {
+                println!("replaces the fee change that is currently prepared and restarts the enact delay");
+            }
+            GovernanceInstruction::PrepareFeeChange { lp_fee, governance_fee }
+        }
+        GovernanceCommand::EnactFee => {
+            check_enactable("fee change", state.fee_transition_ts, now)?;
+            println!(
+                "fees: lp fee {} -> {}, governance fee {} -> {}",
+                state.lp_fee.get(),
+                state.prepared_lp_fee.get(),
+                state.governance_fee.get(),
+                state.prepared_governance_fee.get()
+            );
+            GovernanceInstruction::EnactFeeChange {}
+        }
+        GovernanceCommand::Transfer {
+            step: TransferStep::Prepare { new_governance },
+        } => {
+            println!("governance: {} -> {}", state.governance_key, new_governance);
+            if state.governance_transition_ts != 0 {
+                println!("replaces the transition that is currently prepared and restarts the enact delay");
+            }
+            GovernanceInstruction::PrepareGovernanceTransition {
+                upcoming_governance_key: new_governance,
+            }
+        }
+        GovernanceCommand::Transfer {
+            step: TransferStep::Enact,
+        } => {
+            check_enactable("governance transition", state.governance_transition_ts, now)?;
+            println!(
+                "governance: {} -> {}",
+                state.governance_key, state.prepared_governance_key
+            );
+            GovernanceInstruction::EnactGovernanceTransition {}
+        }
+        GovernanceCommand::SetPaused { paused } => {
+            println!("paused: {} -> {}", state.is_paused, paused);
+            GovernanceInstruction::SetPaused { paused }
+        }
+        GovernanceCommand::AdjustAmp { target_value, duration } => {
+            let duration = duration.unwrap_or(MIN_ADJUSTMENT_WINDOW);
+            let target_ts = now + duration;
+            println!(
+                "amp factor: {} -> {} over {}, reaching its target at {}",
+                state.amp_factor.get(now),
+                target_value,
+                format_duration(duration),
+                format_timestamp(target_ts, now)
+            );
+            GovernanceInstruction::AdjustAmpFactor {
+                target_ts,
+                target_value,
+            }
+        }
+        GovernanceCommand::SetOracleGuard { max_deviation, off } => {
+            let current = if state.is_oracle_guarded() {
+                format!("max deviation {}", state.max_oracle_deviation.get())
+            } else {
+                "off".to_string()
+            };
+            if off {
+                println!("oracle guard: {} -> off", current);
+                GovernanceInstruction::SetOracleGuard {
+                    max_deviation: DecimalU64::from(0),
+                }
+            } else {
+                if !state.has_token_oracles() {
+                    return Err("not every token has an oracle, bind them with token-oracle first".into());
+                }
+                let max_deviation = max_deviation.unwrap();
+                println!("oracle guard: {} -> max deviation {}", current, max_deviation);
+                GovernanceInstruction::SetOracleGuard { max_deviation }
+            }
+        }
+        GovernanceCommand::SetRateSource {
+            token_index,
+            source,
+            account,
+            rate,
+        } => {
+            check_index(token_index)?;
+            let source = match source {
+                RateSourceArg::None => RateSource::None,
+                RateSourceArg::SplStakePool => RateSource::SplStakePool,
+                RateSourceArg::Pyth => RateSource::Pyth,
+                RateSourceArg::Switchboard => RateSource::Switchboard,
+                RateSourceArg::Fixed => RateSource::Fixed {
+                    rate: to_fixed_rate(rate.unwrap())?,
+                },
+                RateSourceArg::BasePool => RateSource::BasePool,
+                RateSourceArg::Wrapper => RateSource::Wrapper,
+            };
+            let i = token_index as usize;
+            println!(
+                "rate source of token {}: {:?} {} -> {:?} {}",
+                i,
+                state.rate_sources[i],
+                state.rate_keys[i],
+                source,
+                account.unwrap_or_default()
+            );
+            if !state.is_paused && state.previous_depth != 0 {
+                return Err("the pool holds balances that the new rate revalues immediately, pause it first".into());
+            }
+            GovernanceInstruction::SetRateSource {
+                token_index,
+                source,
+                rate_key: account.unwrap_or_default(),
+            }
+        }
+        GovernanceCommand::SetOracleFallback { fallback } => {
+            let fallback = match fallback {
+                OracleFallbackArg::Reject => OracleFallback::Reject,
+                OracleFallbackArg::IgnoreGuard => OracleFallback::IgnoreGuard,
+                OracleFallbackArg::InternalEma => OracleFallback::InternalEma,
+            };
+            println!("oracle fallback: {:?} -> {:?}", state.oracle_fallback, fallback);
+            GovernanceInstruction::SetOracleFallback { fallback }
+        }
+        GovernanceCommand::SetAmpDamping {
+            factor,
+            trigger_band,
+            release_band,
+            delay,
+            off,
+        } => {
+            let damping = &state.amp_damping;
+            let current = if damping.is_enabled() {
+                format!(
+                    "trigger band {}, release band {} (damping: {})",
+                    damping.trigger_band(),
+                    damping.release_band(),
+                    damping.is_damping()
+                )
+            } else {
+                "off".to_string()
+            };
+            if off {
+                println!("amp damping: {} -> off", current);
+                GovernanceInstruction::SetAmpDamping {
+                    factor: DecimalU64::from(1),
+                    trigger_band: DecimalU64::from(0),
+                    release_band: DecimalU64::from(0),
+                    delay: 0,
+                }
+            } else {
+                let (factor, trigger_band) = (factor.unwrap(), trigger_band.unwrap());
+                let release_band = release_band.unwrap_or(trigger_band);
+                println!(
+                    "amp damping: {} -> factor {}, trigger band {}, release band {}, delay {}",
+                    current,
+                    factor,
+                    trigger_band,
+                    release_band,
+                    format_duration(delay)
+                );
+                GovernanceInstruction::SetAmpDamping {
+                    factor,
+                    trigger_band,
+                    release_band,
+                    delay,
+                }
+            }
+        }
+        GovernanceCommand::TokenOracle {
+            step:
+                TokenOracleStep::Prepare {
+                    token_index,
+                    oracle,
+                    kind,
+                    max_age,
+                    max_conf_bps,
+                    remove: _,
+                },
+        } => {
+            check_index(token_index)?;
+            let oracle_kind = match kind {
+                OracleKindArg::Pyth => OracleKind::Pyth,
+                OracleKindArg::Switchboard => OracleKind::Switchboard,
+            };
+            let oracle_key = oracle.unwrap_or_default();
+            let limits = OracleLimits { max_age, max_conf_bps };
+            if !limits.is_valid() {
+                return Err("--max-age has to be nonzero and --max-conf-bps at most 10000".into());
+            }
+            let i = token_index as usize;
+            println!(
+                "oracle of token {}: {:?} {} {:?} -> {:?} {} {:?}",
+                i, state.oracle_kinds[i], state.oracle_keys[i], state.oracle_limits[i], oracle_kind, oracle_key, limits
+            );
+            if state.oracle_transition_ts != 0 {
+                println!("replaces the oracle that is currently prepared and restarts the enact delay");
+            }
+            GovernanceInstruction::SetTokenOracle {
+                token_index,
+                oracle_kind,
+                oracle_key,
+                max_age,
+                max_conf_bps,
+            }
+        }
+        GovernanceCommand::TokenOracle {
+            step: TokenOracleStep::Enact,
+        } => {
+            check_enactable("token oracle", state.oracle_transition_ts, now)?;
+            let i = state.prepared_oracle_index as usize;
+            let prepared = &state.prepared_token_oracle;
+            println!(
+                "oracle of token {}: {:?} {} {:?} -> {:?} {} {:?}",
+                i,
+                state.oracle_kinds[i],
+                state.oracle_keys[i],
+                state.oracle_limits[i],
+                prepared.kind,
+                prepared.key,
+                prepared.limits
+            );
+            GovernanceInstruction::EnactTokenOracle {}
+        }
+        GovernanceCommand::SetPegBand { band } => {
+            println!("peg band: {} -> {}", state.peg_band.get(), band);
+            let depegged: Vec<_> = (0..TOKEN_COUNT).filter(|&i| state.depegged_tokens[i]).collect();
+            if !depegged.is_empty() {
+                println!("resumes swaps of tokens {:?}", depegged);
+            }
+            GovernanceInstruction::SetPegBand { band }
+        }
+        GovernanceCommand::SetHookPrograms { programs } => {
+            let mut hook_programs = [Pubkey::default(); MAX_HOOK_PROGRAMS];
+            hook_programs[..programs.len()].copy_from_slice(&programs);
+            let allowed = |hook_programs: &[Pubkey]| -> Vec<Pubkey> {
+                hook_programs
+                    .iter()
+                    .filter(|key| **key != Pubkey::default())
+                    .copied()
+                    .collect()
+            };
+            println!(
+                "hook programs: {:?} -> {:?}",
+                allowed(&state.hook_programs),
+                allowed(&hook_programs)
+            );
+            GovernanceInstruction::SetHookPrograms { hook_programs }
+        }
+        GovernanceCommand::SetCallerAllowlist { mode, programs } => {
+            let caller_mode = match mode {
+                CallerModeArg::Unrestricted => CallerMode::Unrestricted,
+                CallerModeArg::AllowedPrograms => CallerMode::AllowedPrograms,
+                CallerModeArg::AllowedProgramsOnly => CallerMode::AllowedProgramsOnly,
+            };
+            let mut allowed_callers = [Pubkey::default(); MAX_ALLOWED_CALLERS];
+            allowed_callers[..programs.len()].copy_from_slice(&programs);
+            let allowed = |callers: &[Pubkey]| -> Vec<Pubkey> {
+                callers
+                    .iter()
+                    .filter(|key| **key != Pubkey::default())
+                    .copied()
+                    .collect()
+            };
+            println!(
+                "callers: {:?} {:?} -> {:?} {:?}",
+                state.caller_mode,
+                allowed(&state.allowed_callers),
+                caller_mode,
+                allowed(&allowed_callers)
+            );
+            GovernanceInstruction::SetCallerAllowlist {
+                caller_mode,
+                allowed_callers,
+            }
+        }
+        GovernanceCommand::SetSunset { deadline } => {
+            if deadline != 0 && deadline < now + ENACT_DELAY {
+                return Err(format!(
+                    "the sunset deadline has to be at least {} away",
+                    format_duration(ENACT_DELAY)
+                )
+                .into());
+            }
+            let format_sunset = |ts| match ts {
+                0 => "none".to_string(),
+                ts => format_timestamp(ts, now),
+            };
+            println!(
+                "sunset: {} -> {}",
+                format_sunset(state.sunset_ts),
+                format_sunset(deadline)
+            );
+            GovernanceInstruction::SetSunset { deadline }
+        }
+        GovernanceCommand::Renounce { lock_amp } => {
+            println!("governance: {} -> none (renounced for good)", state.governance_key);
+            if lock_amp {
+                println!("amp factor: locked at {}", state.amp_factor.get(now));
+            }
+            GovernanceInstruction::RenounceGovernance {
+                lock_amp_factor: lock_amp,
+            }
+        }
+        GovernanceCommand::MigrateFeeAccount { new_account } => {
+            println!(
+                "governance fee account: {} -> {}",
+                state.governance_fee_key, new_account
+            );
+            GovernanceInstruction::MigrateGovernanceFeeAccount {
+                governance_fee_key: new_account,
+            }
+        }
+        GovernanceCommand::SetLockFeeShare { share } => {
+            println!("lock fee share: {} -> {}", state.lock_fee_share.get(), share);
+            GovernanceInstruction::SetLockFeeShare { share }
+        }
+        GovernanceCommand::SetMinLpPosition { min_mint, min_residual } => {
+            println!(
+                "min lp mint: {} -> {}, min lp residual: {} -> {}",
+                state.min_lp_mint_amount, min_mint, state.min_lp_residual_amount, min_residual
+            );
+            GovernanceInstruction::SetMinLpPosition {
+                min_mint_amount: min_mint,
+                min_residual_amount: min_residual,
+            }
+        }
+        GovernanceCommand::BurnFees { amount } => {
+            let balance = pool_client
+                .rpc_client
+                .get_token_account_balance(&state.governance_fee_key)?
+                .amount
+                .parse::<u64>()?;
+            let burn_amount = amount.unwrap_or(balance);
+            if burn_amount == 0 || burn_amount > balance {
+                return Err(format!(
+                    "the governance fee account holds {} lp tokens, can't burn {}",
+                    balance, burn_amount
+                )
+                .into());
+            }
+            let lp_supply = pool_client.lp_supply()?;
+            println!(
+                "governance fee account: {} -> {}, lp supply: {} -> {}",
+                balance,
+                balance - burn_amount,
+                lp_supply,
+                lp_supply - burn_amount
+            );
+            GovernanceInstruction::BurnGovernanceFees { burn_amount }
+        }
+        GovernanceCommand::SetLaunchCap { max_lp_per_wallet, end } => {
+            let end_ts = end.unwrap_or(0);
+            if max_lp_per_wallet != 0 && end_ts <= now {
+                return Err("the launch window has to end in the future (--end)".into());
+            }
+            let format_cap = |cap, end_ts| match cap {
+                0 => "none".to_string(),
+                cap => format!("{} per wallet until {}", cap, format_timestamp(end_ts, now)),
+            };
+            println!(
+                "launch cap: {} -> {}",
+                format_cap(state.launch_lp_cap, state.launch_end_ts),
+                format_cap(max_lp_per_wallet, end_ts)
+            );
+            GovernanceInstruction::SetLaunchCap {
+                max_lp_per_wallet,
+                end_ts,
+            }
+        }
+        GovernanceCommand::SetRole { role, key } => {
+            let (role, name) = match role {
+                RoleArg::FeeAdmin => (Role::FeeAdmin, "fee admin"),
+                RoleArg::AmpAdmin => (Role::AmpAdmin, "amp admin"),
+                RoleArg::PauseAdmin => (Role::PauseAdmin, "pause admin"),
+            };
+            let format_key = |key: Option<Pubkey>| key.map_or("none".to_string(), |key| key.to_string());
+            println!("{}: {} -> {}", name, format_key(state.role_key(role)), format_key(key));
+            GovernanceInstruction::SetRole {
+                role,
+                key: key.unwrap_or_default(),
+            }
+        }
+        GovernanceCommand::SetTokenFlags {
+            token_index,
+            disable_input,
+            disable_output,
+        } => {
+            check_index(token_index)?;
+            let flags = TokenFlags {
+                input_disabled: disable_input,
+                output_disabled: disable_output,
+            };
+            let format_flags = |flags: &TokenFlags| match (flags.input_disabled, flags.output_disabled) {
+                (false, false) => "enabled",
+                (true, false) => "input disabled",
+                (false, true) => "output disabled",
+                (true, true) => "input and output disabled",
+            };
+            println!(
+                "token {}: {} -> {}",
+                token_index,
+                format_flags(&state.token_flags[token_index as usize]),
+                format_flags(&flags)
+            );
+            GovernanceInstruction::SetTokenFlags { token_index, flags }
+        }
+        GovernanceCommand::SetUserGate { gate, mint } => {
+            let user_gate = to_user_gate(gate, mint);
+            println!(
+                "user gate: {} -> {}",
+                format_gate(&state.user_gate),
+                format_gate(&user_gate)
+            );
+            GovernanceInstruction::SetUserGate { user_gate }
+        }
+        GovernanceCommand::SetUserAllowed { user, disallow } => {
+            let allowed_user = find_allowed_user_address(&pool_client.program_id, &args.pool, &user).0;
+            print_allowed_change(&pool_client, "user", &user, &allowed_user, !disallow, &state.user_gate)?;
+            GovernanceInstruction::SetUserAllowed {
+                user,
+                allowed: !disallow,
+            }
+        }
+        GovernanceCommand::SetDepositorGate { gate, mint } => {
+            let depositor_gate = to_user_gate(gate, mint);
+            println!(
+                "depositor gate: {} -> {}",
+                format_gate(&state.depositor_gate),
+                format_gate(&depositor_gate)
+            );
+            GovernanceInstruction::SetDepositorGate { depositor_gate }
+        }
+        GovernanceCommand::SetDepositorAllowed { depositor, disallow } => {
+            let allowed_depositor = find_allowed_depositor_address(&pool_client.program_id, &args.pool, &depositor).0;
+            print_allowed_change(
+                &pool_client,
+                "depositor",
+                &depositor,
+                &allowed_depositor,
+                !disallow,
+                &state.depositor_gate,
+            )?;
+            GovernanceInstruction::SetDepositorAllowed {
+                depositor,
+                allowed: !disallow,
+            }
+        }
+        GovernanceCommand::SetRfqQuoter {
+            quoter,
+            max_deviation,
+            off,
+        } => {
+            let (quoter_key, max_deviation) = if off {
+                (Pubkey::default(), DecimalU64::from(0))
+            } else {
+                (quoter.unwrap(), max_deviation.unwrap())
+            };
+            if max_deviation * BPS_ONE > MAX_RFQ_DEVIATION_BPS {
+                return Err(format!("max deviation exceeds {} bps", MAX_RFQ_DEVIATION_BPS).into());
+            }
+            println!(
+                "rfq quoter: {} -> {}",
+                format_quoter(state.rfq_quoter_key, state.rfq_max_deviation.get()),
+                format_quoter(quoter_key, max_deviation)
+            );
+            if state.rfq_quoter_transition_ts != 0 {
+                println!("replaces the rfq quoter that is currently prepared and restarts the enact delay");
+            }
+            GovernanceInstruction::SetRfqQuoter {
+                quoter_key,
+                max_deviation,
+            }
+        }
+        GovernanceCommand::SetComplianceProgram { program, off } => {
+            let compliance_program = if off { Pubkey::default() } else { program.unwrap() };
+            let format_program = |program: Pubkey| {
+                if program == Pubkey::default() {
+                    "none".to_string()
+                } else {
+                    program.to_string()
+                }
+            };
+            println!(
+                "compliance program: {} -> {}",
+                format_program(state.compliance_program),
+                format_program(compliance_program)
+            );
+            GovernanceInstruction::SetComplianceProgram { compliance_program }
+        }
+        GovernanceCommand::SetTradingSchedule { hours, maintenance } => {
+            let trading_hours = hours.unwrap_or_default();
+            let format_hours = |hours: &TradingHours| {
+                if hours.open_secs == hours.close_secs {
+                    "around the clock".to_string()
+                } else {
+                    format!(
+                        "{}-{} UTC",
+                        format_time_of_day(hours.open_secs),
+                        format_time_of_day(hours.close_secs)
+                    )
+                }
+            };
+            let format_windows = |windows: &[MaintenanceWindow]| -> Vec<String> {
+                windows
+                    .iter()
+                    .filter(|window| window.is_set())
+                    .map(|window| {
+                        format!(
+                            "{} until {}",
+                            format_timestamp(window.start_ts, now),
+                            format_timestamp(window.end_ts, now)
+                        )
+                    })
+                    .collect()
+            };
+            let mut maintenance_windows = [MaintenanceWindow::default(); MAX_MAINTENANCE_WINDOWS];
+            maintenance_windows[..maintenance.len()].copy_from_slice(&maintenance);
+            println!(
+                "trading hours: {} -> {}",
+                format_hours(&state.trading_hours),
+                format_hours(&trading_hours)
+            );
+            println!(
+                "maintenance windows: {:?} -> {:?}",
+                format_windows(&state.maintenance_windows),
+                format_windows(&maintenance_windows)
+            );
+            GovernanceInstruction::SetTradingSchedule {
+                trading_hours,
+                maintenance_windows,
+            }
+        }
+        GovernanceCommand::SetWithdrawalCooldown { threshold, cooldown } => {
+            if cooldown > MAX_WITHDRAWAL_COOLDOWN {
+                return Err(format!("cooldown exceeds {}", format_duration(MAX_WITHDRAWAL_COOLDOWN)).into());
+            }
+            let format_cooldown = |threshold: u64, cooldown: UnixTimestamp| {
+                if cooldown == 0 {
+                    "none".to_string()
+                } else {
+                    format!("{} above {} lp tokens", format_duration(cooldown), threshold)
+                }
+            };
+            println!(
+                "withdrawal cooldown: {} -> {}",
+                format_cooldown(state.withdrawal_cooldown_threshold, state.withdrawal_cooldown),
+                format_cooldown(threshold, cooldown)
+            );
+            GovernanceInstruction::SetWithdrawalCooldown { threshold, cooldown }
+        }
+        GovernanceCommand::SetEmergencyResponders { responders, threshold } => {
+            let threshold = threshold.unwrap_or(responders.len() as u8);
+            if threshold as usize > responders.len() || (threshold == 0 && !responders.is_empty()) {
+                return Err(format!("threshold has to be between 1 and the {} responders", responders.len()).into());
+            }
+            let mut responder_keys = [Pubkey::default(); MAX_EMERGENCY_RESPONDERS];
+            responder_keys[..responders.len()].copy_from_slice(&responders);
+            let format_responders = |responders: &[Pubkey], threshold: u8| -> String {
+                let keys: Vec<_> = responders.iter().filter(|key| **key != Pubkey::default()).collect();
+                if keys.is_empty() {
+                    "none".to_string()
+                } else {
+                    format!("{} of {:?}", threshold, keys)
+                }
+            };
+            println!(
+                "emergency responders: {} -> {}",
+                format_responders(&state.emergency_responders, state.emergency_threshold),
+                format_responders(&responder_keys, threshold)
+            );
+            GovernanceInstruction::SetEmergencyResponders {
+                responders: responder_keys,
+                threshold,
+            }
+        }
+        GovernanceCommand::SetPriceBand { min, max } => {
+            if TOKEN_COUNT != 2 {
+                return Err("only two token pools can have a price band".into());
+            }
+            let price_band = match (min, max) {
+                (Some(min), Some(max)) => PriceBand {
+                    min_price: to_price(min)?,
+                    max_price: to_price(max)?,
+                },
+                _ => PriceBand::default(),
+            };
+            if !price_band.is_valid() {
+                return Err("min has to be below max".into());
+            }
+            let format_band = |band: &PriceBand| {
+                if band.is_set() {
+                    format!("{} to {}", format_price(band.min_price), format_price(band.max_price))
+                } else {
+                    "none".to_string()
+                }
+            };
+            println!(
+                "price band: {} -> {}",
+                format_band(&state.price_band),
+                format_band(&price_band)
+            );
+            GovernanceInstruction::SetPriceBand { price_band }
+        }
+        GovernanceCommand::SetTokenParticipation {
+            token_index,
+            participation,
+        } => {
+            check_index(token_index)?;
+            let participation = match participation {
+                TokenParticipationArg::Active => TokenParticipation::Active,
+                TokenParticipationArg::DepositOnly => TokenParticipation::DepositOnly,
+                TokenParticipationArg::WithdrawOnly => TokenParticipation::WithdrawOnly,
+            };
+            println!(
+                "token {}: {:?} -> {:?}",
+                token_index, state.token_participation[token_index as usize], participation
+            );
+            GovernanceInstruction::SetTokenParticipation {
+                token_index,
+                participation,
+            }
+        }
+        GovernanceCommand::SetPegKeeper {
+            keeper,
+            token_index,
+            credit_limit,
+        } => {
+            check_index(token_index)?;
+            let peg_keeper = &state.peg_keeper;
+            if peg_keeper.debt > 0 && (keeper.is_none() || token_index != peg_keeper.token_index) {
+                return Err(format!(
+                    "the pool owes its keeper {} of token {}, which it has to absorb first",
+                    peg_keeper.debt, peg_keeper.token_index
+                )
+                .into());
+            }
+            println!(
+                "peg keeper: {} -> {}",
+                format_peg_keeper(peg_keeper),
+                format_peg_keeper(&PegKeeper {
+                    keeper_key: keeper.unwrap_or_default(),
+                    token_index,
+                    credit_limit,
+                    ..PegKeeper::default()
+                })
+            );
+            if state.peg_keeper_transition_ts != 0 {
+                println!("replaces the peg keeper that is currently prepared and restarts the enact delay");
+            }
+            GovernanceInstruction::SetPegKeeper {
+                keeper_key: keeper.unwrap_or_default(),
+                token_index,
+                credit_limit,
+            }
+        }
+        GovernanceCommand::SetYieldAdapter {
+            token_index,
+            adapter,
+            buffer,
+        } => {
+            check_index(token_index)?;
+            let yield_adapter = &state.yield_adapters[token_index as usize];
+            check_yield_adapter_change(yield_adapter, adapter.unwrap_or_default(), token_index)?;
+            println!(
+                "yield adapter of token {}: {} -> {}",
+                token_index,
+                format_yield_adapter(yield_adapter),
+                format_yield_adapter(&YieldAdapter {
+                    program: adapter.unwrap_or_default(),
+                    buffer,
+                    deployed: 0,
+                })
+            );
+            if state.yield_adapter_transition_ts != 0 {
+                println!("replaces the yield adapter that is currently prepared and restarts the enact delay");
+            }
+            GovernanceInstruction::SetYieldAdapter {
+                token_index,
+                adapter_program: adapter.unwrap_or_default(),
+                buffer,
+            }
+        }
+        GovernanceCommand::SetRepegParams {
+            threshold_bps,
+            max_step_bps,
+            min_interval,
+        } => {
+            if state.invariant_kind != InvariantKind::Hybrid {
+                return Err("only hybrid pools repeg".into());
+            }
+            if threshold_bps > 10_000 || max_step_bps > 10_000 || min_interval < 0 {
+                return Err("basis points go up to 10000 and the interval can't be negative".into());
+            }
+            let price_scale = &state.price_scale;
+            println!(
+                "repeg of token {} (price {}): threshold {}bps, step {}bps, interval {}s -> {}bps, {}bps, {}s",
+                price_scale.token_index,
+                price_scale.rate,
+                price_scale.threshold_bps,
+                price_scale.max_step_bps,
+                price_scale.min_interval,
+                threshold_bps,
+                max_step_bps,
+                min_interval
+            );
+            GovernanceInstruction::SetRepegParams {
+                threshold_bps,
+                max_step_bps,
+                min_interval,
+            }
+        }
+        GovernanceCommand::SetGaugeReward {
+            reward_mint,
+            funder,
+            retire,
+        } => {
+            let gauge = pool_client.gauge()?.unwrap_or_default();
+            let funder = funder.unwrap_or_default();
+            match gauge.reward_index(&reward_mint).map(|index| gauge.rewards[index]) {
+                Some(reward) if !reward.is_retired() => {
+                    if retire {
+                        println!(
+                            "gauge reward {}: retired, refunding {} to {}",
+                            reward_mint,
+                            reward.unemitted(now),
+                            reward.funder
+                        );
+                    } else {
+                        println!(
+                            "funder of gauge reward {}: {} -> {}",
+                            reward_mint, reward.funder, funder
+                        );
+                    }
+                }
+                _ if retire => return Err(format!("{} is not an active reward of the gauge", reward_mint).into()),
+                Some(_) => println!("gauge reward {}: added again, funded by {}", reward_mint, funder),
+                None => match gauge.free_index(now) {
+                    None => {
+                        let reusable_ts = gauge
+                            .rewards
+                            .iter()
+                            .filter(|reward| reward.is_retired())
+                            .map(|reward| reward.emission_end_ts + GAUGE_CLAIM_PERIOD)
+                            .min();
+                        return Err(match reusable_ts {
+                            Some(ts) => format!("retired rewards can be claimed until {}", format_timestamp(ts, now)),
+                            None => format!("the gauge already has {} rewards", MAX_GAUGE_REWARDS),
+                        }
+                        .into());
+                    }
+                    Some(index) if gauge.rewards[index].mint != Pubkey::default() => println!(
+                        "gauge reward {}: added in place of retired reward {}, funded by {}",
+                        reward_mint, gauge.rewards[index].mint, funder
+                    ),
+                    Some(_) => println!("gauge reward {}: added, funded by {}", reward_mint, funder),
+                },
+            }
+            GovernanceInstruction::SetGaugeReward { reward_mint, funder }
+        }
+        GovernanceCommand::StartRebateCampaign {
+            id,
+            rebate_mint,
+            budget,
+            start,
+            end,
+        } => {
+            if pool_client.rebate_campaign(id)?.is_some() {
+                return Err(format!("the pool already has a rebate campaign {}", id).into());
+            }
+            if state.rebate_end_ts > now {
+                return Err(format!(
+                    "the pool's rebate campaign runs until {}",
+                    format_timestamp(state.rebate_end_ts, now)
+                )
+                .into());
+            }
+            if budget == 0 || start < now || end <= start || end - start > MAX_REBATE_WINDOW {
+                return Err(format!(
+                    "the budget can't be empty and the window has to start in the future and last at most {}",
+                    format_duration(MAX_REBATE_WINDOW)
+                )
+                .into());
+            }
+            println!(
+                "rebate campaign {}: {} of {} from {} until {}",
+                id,
+                budget,
+                rebate_mint,
+                format_timestamp(start, now),
+                format_timestamp(end, now)
+            );
+            GovernanceInstruction::StartRebateCampaign {
+                campaign_id: id,
+                rebate_mint,
+                budget,
+                start_ts: start,
+                end_ts: end,
+            }
+        }
+        GovernanceCommand::ReclaimRebateBudget { id } => {
+            let campaign = pool_client
+                .rebate_campaign(id)?
+                .ok_or_else(|| format!("the pool has no rebate campaign {}", id))?;
+            if campaign.reclaimed {
+                return Err(format!("the budget of rebate campaign {} was reclaimed already", id).into());
+            }
+            //right after the window if no swap recorded its fees
+            let reclaimable_ts = if campaign.total_fees == 0 {
+                campaign.end_ts
+            } else {
+                campaign.end_ts + REBATE_CLAIM_PERIOD
+            };
+            if reclaimable_ts > now {
+                return Err(format!("rebates can be claimed until {}", format_timestamp(reclaimable_ts, now)).into());
+            }
+            println!(
+                "rebate campaign {}: reclaiming what's left of {} of {}",
+                id, campaign.budget, campaign.rebate_mint
+            );
+            GovernanceInstruction::ReclaimRebateBudget { campaign_id: id }
+        }
+        GovernanceCommand::SetGaugeVeProgram { program, off } => {
+            let gauge = pool_client.gauge()?.ok_or("the pool has no gauge")?;
+            let ve_program = if off { Pubkey::default() } else { program.unwrap() };
+            println!(
+                "gauge ve program: {} -> {}",
+                format_ve_program(gauge.ve_program),
+                format_ve_program(ve_program)
+            );
+            if gauge.ve_program_transition_ts != 0 {
+                println!("replaces the ve program that is currently prepared and restarts the enact delay");
+            }
+            GovernanceInstruction::SetGaugeVeProgram { ve_program }
+        }
+        GovernanceCommand::EnactGaugeVeProgram => {
+            let gauge = pool_client.gauge()?.ok_or("the pool has no gauge")?;
+            check_enactable("gauge ve program", gauge.ve_program_transition_ts, now)?;
+            println!(
+                "gauge ve program: {} -> {}",
+                format_ve_program(gauge.ve_program),
+                format_ve_program(gauge.prepared_ve_program)
+            );
+            GovernanceInstruction::EnactGaugeVeProgram {}
+        }
+        GovernanceCommand::EnactRfqQuoter => {
+            check_enactable("rfq quoter", state.rfq_quoter_transition_ts, now)?;
+            println!(
+                "rfq quoter: {} -> {}",
+                format_quoter(state.rfq_quoter_key, state.rfq_max_deviation.get()),
+                format_quoter(state.prepared_rfq_quoter_key, state.prepared_rfq_max_deviation.get())
+            );
+            GovernanceInstruction::EnactRfqQuoter {}
+        }
+        GovernanceCommand::EnactPegKeeper => {
+            check_enactable("peg keeper", state.peg_keeper_transition_ts, now)?;
+            let (peg_keeper, prepared) = (&state.peg_keeper, &state.prepared_peg_keeper);
+            if peg_keeper.debt > 0 && (!prepared.is_set() || prepared.token_index != peg_keeper.token_index) {
+                return Err(format!(
+                    "the pool owes its keeper {} of token {}, which it has to absorb first",
+                    peg_keeper.debt, peg_keeper.token_index
+                )
+                .into());
+            }
+            println!(
+                "peg keeper: {} -> {}",
+                format_peg_keeper(peg_keeper),
+                format_peg_keeper(prepared)
+            );
+            GovernanceInstruction::EnactPegKeeper {}
+        }
+        GovernanceCommand::EnactYieldAdapter => {
+            check_enactable("yield adapter", state.yield_adapter_transition_ts, now)?;
+            let token_index = state.prepared_yield_adapter_index;
+            let (yield_adapter, prepared) = (
+                &state.yield_adapters[token_index as usize],
+                &state.prepared_yield_adapter,
+            );
+            check_yield_adapter_change(yield_adapter, prepared.program, token_index)?;
+            println!(
+                "yield adapter of token {}: {} -> {}",
+                token_index,
+                format_yield_adapter(yield_adapter),
+                format_yield_adapter(prepared)
+            );
+            GovernanceInstruction::EnactYieldAdapter {}
+        }
+    };
+    let is_prepare = matches!(
+        gov_instruction,
+        GovernanceInstruction::PrepareFeeChange { .. }
+            | GovernanceInstruction::PrepareGovernanceTransition { .. }
+            | GovernanceInstruction::SetTokenOracle { .. }
+            | GovernanceInstruction::SetGaugeVeProgram { .. }
+            | GovernanceInstruction::SetRfqQuoter { .. }
+            | GovernanceInstruction::SetPegKeeper { .. }
+            | GovernanceInstruction::SetYieldAdapter { .. }
+    );
+
+    execute(&pool_client, &sender, gov_instruction, args.dry_run)?;
+
+    if is_prepare {
+        let enactable_ts = if args.dry_run || sender.is_exporting() {
+            //the program uses the cluster's time at execution, which will be later than now
+            now + ENACT_DELAY
+        } else {
+            pool_client.reload()?;
+            let ve_program_transition_ts = pool_client.gauge()?.map_or(0, |gauge| gauge.ve_program_transition_ts);
+            let state = &pool_client.state;
+            state
+                .fee_transition_ts
+                .max(state.governance_transition_ts)
+                .max(state.oracle_transition_ts)
+                .max(state.rfq_quoter_transition_ts)
+                .max(state.peg_keeper_transition_ts)
+                .max(state.yield_adapter_transition_ts)
+                .max(ve_program_transition_ts)
+        };
+        println!("enactable at {}", format_timestamp(enactable_ts, now));
+    }
+
+    Ok(())
+}
+
+fn format_quoter(key: Pubkey, max_deviation: DecimalU64) -> String {
+    if key == Pubkey::default() {
+        "off".to_string()
+    } else {
+        format!("{} (max deviation {})", key, max_deviation)
+    }
+}
+
+fn format_peg_keeper(peg_keeper: &PegKeeper) -> String {
+    if peg_keeper.is_set() {
+        format!(
+            "{} with {} of token {}",
+            peg_keeper.keeper_key, peg_keeper.credit_limit, peg_keeper.token_index
+        )
+    } else {
+        "none".to_string()
+    }
+}
+
+fn check_yield_adapter_change(yield_adapter: &YieldAdapter, program: Pubkey, token_index: u8) -> CliResult<()> {
+    if yield_adapter.deployed > 0 && program != yield_adapter.program {
+        return Err(format!(
+            "{} of token {} are deployed to its adapter, which have to be recalled first",
+            yield_adapter.deployed, token_index
+        )
+        .into());
+    }
+    Ok(())
+}
+
+fn format_yield_adapter(yield_adapter: &YieldAdapter) -> String {
+    if yield_adapter.is_set() {
+        format!("{} beyond {}", yield_adapter.program, yield_adapter.buffer)
+    } else {
+        "none".to_string()
+    }
+}
+
+fn format_ve_program(ve_program: Pubkey) -> String {
+    if ve_program == Pubkey::default() {
+        "none".to_string()
+    } else {
+        ve_program.to_string()
+    }
+}
+
+//OPEN-CLOSE, e.g. 13:30-20:00
+fn parse_trading_hours(s: &str) -> Result<TradingHours, String> {
+    let (open, close) = s
+        .split_once('-')
+        .ok_or_else(|| format!("{} isn't a range of times like 13:30-20:00", s))?;
+    Ok(TradingHours {
+        open_secs: parse_time_of_day(open)?,
+        close_secs: parse_time_of_day(close)?,
+    })
+}
+
+//START-END unix timestamps
+fn parse_maintenance_window(s: &str) -> Result<MaintenanceWindow, String> {
+    let invalid = || format!("{} isn't a range of unix timestamps like 1700000000-1700003600", s);
+    let (start, end) = s.split_once('-').ok_or_else(invalid)?;
+    let window = MaintenanceWindow {
+        start_ts: start.parse().map_err(|_| invalid())?,
+        end_ts: end.parse().map_err(|_| invalid())?,
+    };
+    if window.start_ts >= window.end_ts {
+        return Err(invalid());
+    }
+    Ok(window)
+}
+
+fn to_user_gate(gate: UserGateArg, mint: Option<Pubkey>) -> UserGate {
+    match gate {
+        UserGateArg::Open => UserGate::Open,
+        UserGateArg::Allowlist => UserGate::Allowlist,
+        UserGateArg::Token => UserGate::GatingToken { mint: mint.unwrap() },
+    }
+}
+
+fn format_gate(gate: &UserGate) -> String {
+    match gate {
+        UserGate::Open => "open".to_string(),
+        UserGate::Allowlist => "allowlist".to_string(),
+        UserGate::GatingToken { mint } => format!("holders of {}", mint),
+    }
+}
+
+//whether `user` is on an allowlist is whether its account at `allowed_key` exists
+fn print_allowed_change(
+    pool_client: &PoolClient,
+    list: &str,
+    user: &Pubkey,
+    allowed_key: &Pubkey,
+    allowed: bool,
+    gate: &UserGate,
+) -> CliResult<()> {
+    let was_allowed = pool_client
+        .rpc_client
+        .get_account_with_commitment(allowed_key, pool_client.rpc_client.commitment())?
+        .value
+        .is_some();
+    let format_allowed = |allowed| if allowed { "allowed" } else { "not allowed" };
+    println!(
+        "{}: {} -> {}",
+        user,
+        format_allowed(was_allowed),
+        format_allowed(allowed)
+    );
+    if *gate != UserGate::Allowlist {
+        println!(
+            "the pool's {} gate isn't an allowlist, the change takes effect once it is",
+            list
+        );
+    }
+    Ok(())
+}
+
+fn check_enactable(change: &str, transition_ts: UnixTimestamp, now: UnixTimestamp) -> CliResult<()> {
+    if transition_ts == 0 {
+        return Err(format!("no {} has been prepared", change).into());
+    }
+    if transition_ts > now {
+        return Err(format!(
+            "the prepared {} is enactable at {}",
+            change,
+            format_timestamp(transition_ts, now)
+        )
+        .into());
+    }
+    Ok(())
+}
+
+fn execute(
+    pool_client: &PoolClient,
+    sender: &Sender,
+    gov_instruction: GovernanceInstruction<TOKEN_COUNT>,
+    dry_run: bool,
+) -> CliResult<()> {
+    //the holder of the instruction's role signs in place of the governance
+    let role_holder = gov_instruction
+        .role()
+        .and_then(|role| pool_client.state.role_key(role))
+        .filter(|key| *key == sender.pubkey());
+    let instruction = match gov_instruction {
+        GovernanceInstruction::MigrateGovernanceFeeAccount { .. } => {
+            pool_client.governance_ix(gov_instruction, Some(&pool_client.state.governance_fee_key))?
+        }
+        GovernanceInstruction::BurnGovernanceFees { burn_amount } => {
+            pool_client.burn_governance_fees_ix(burn_amount)?
+        }
+        GovernanceInstruction::SetUserAllowed { user, allowed } => {
+            pool_client.set_user_allowed_ix(&user, allowed, &sender.pubkey())?
+        }
+        GovernanceInstruction::SetDepositorAllowed { depositor, allowed } => {
+            pool_client.set_depositor_allowed_ix(&depositor, allowed, &sender.pubkey())?
+        }
+        //what the retired reward was yet to emit goes to its funder's associated token account
+        GovernanceInstruction::SetGaugeReward { reward_mint, funder } if funder == Pubkey::default() => {
+            let gauge = pool_client.gauge()?.unwrap_or_default();
+            let reward_funder = gauge
+                .reward_index(&reward_mint)
+                .map(|index| gauge.rewards[index].funder)
+                .unwrap_or_default();
+            pool_client.retire_gauge_reward_ix(
+                &reward_mint,
+                &get_associated_token_address(&reward_funder, &reward_mint),
+            )?
+        }
+        GovernanceInstruction::SetGaugeReward { reward_mint, funder } => {
+            pool_client.add_gauge_reward_ix(&reward_mint, &funder, &sender.pubkey())?
+        }
+        //the budget comes from and goes back to the governance's associated token account
+        GovernanceInstruction::StartRebateCampaign {
+            campaign_id,
+            rebate_mint,
+            budget,
+            start_ts,
+            end_ts,
+        } => pool_client.start_rebate_campaign_ix(
+            campaign_id,
+            &rebate_mint,
+            &get_associated_token_address(&pool_client.state.governance_key, &rebate_mint),
+            budget,
+            start_ts,
+            end_ts,
+            &sender.pubkey(),
+        )?,
+        GovernanceInstruction::ReclaimRebateBudget { campaign_id } => {
+            let rebate_mint = pool_client
+                .rebate_campaign(campaign_id)?
+                .map(|campaign| campaign.rebate_mint)
+                .unwrap_or_default();
+            pool_client.reclaim_rebate_budget_ix(
+                campaign_id,
+                &get_associated_token_address(&pool_client.state.governance_key, &rebate_mint),
+            )?
+        }
+        //the rate key of a base pool source is the base pool here, the instruction's is its virtual price account
+        GovernanceInstruction::SetRateSource {
+            token_index,
+            source: RateSource::BasePool,
+            rate_key,
+        } => pool_client.set_base_pool_rate_ix(token_index, &rate_key)?,
+        _ => match role_holder {
+            Some(role_holder) => pool_client.role_ix(gov_instruction, &role_holder)?,
+            None => pool_client.governance_ix(gov_instruction, None)?,
+        },
+    };
+    if dry_run {
+        for log in pool_client.simulate(&[instruction], &sender.pubkey())? {
+            println!("  {}", log);
+        }
+        println!("simulation succeeded, nothing was sent");
+        return Ok(());
+    }
+
+    if sender.pubkey() != pool_client.state.governance_key && role_holder.is_none() {
+        return Err(format!(
+            "{} is not the pool's governance ({})",
+            sender.pubkey(),
+            pool_client.state.governance_key
+        )
+        .into());
+    }
+    sender.submit(&pool_client.rpc_client, &[instruction], &[])
+}
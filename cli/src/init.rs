@@ -0,0 +1,476 @@
+//creates a pool along with all the accounts it needs: the lp mint, the pool's token accounts (associated token accounts
+// of the pool authority), the governance fee account (the governance's associated token account of the lp mint) and the
+// pool account itself
+//
+//with --deposit the pool is initialized with InitAndAdd, adding the keypair's initial deposit in the same instruction
+//
+//with --non-transferable-lp the lp mint is a Token-2022 mint with the NonTransferable extension (see
+// PoolInstruction::Init), the governance fee account is then the governance's Token-2022 associated token account
+//
+//with --weights the pool uses the weighted constant product invariant instead of the stable swap one (see
+// PoolInstruction::InitWeighted), it then has no amp factor. likewise with --fixed-rate the pool trades its tokens at a
+// fixed rate (see PoolInstruction::InitFixedRate), and with --hybrid it pairs stablecoins with the volatile token at
+// the given index, which its stable swap curve values at --initial-rate until repegs move it (see
+// PoolInstruction::InitHybrid)
+//
+//with --like the pool gets the parameters of an existing pool (see PoolInstruction::ClonePoolConfig) rather than those
+// given on the command line
+//
+//all parameters are checked against the program's rules before anything is sent. the instructions are packed into as
+// few transactions as fit, the last of which creates and initializes the pool account, so the whole init is atomic
+// whenever it fits into a single transaction
+
+use crate::{check_index, governance::to_fixed_rate, pack, parse_decimal, parse_fee, CliResult, Config};
+use clap::Args;
+use pool::{
+    amp_factor::{AmpFactor, MAX_AMP_VALUE, MIN_AMP_VALUE},
+    client::pool::PoolClient,
+    common::create_array,
+    decimal::DecimalU64,
+    instruction::{
+        create_defi_ix, create_init_and_add_ix, create_init_fixed_rate_ix, create_init_hybrid_ix, create_init_ix,
+        create_init_weighted_ix, DeFiInstruction,
+    },
+    pool_fee::PoolFee,
+    processor::MAX_DECIMAL_DIFFERENCE,
+    state::PoolState,
+    token_2022,
+    weighted::{is_valid_weights, MIN_WEIGHT, WEIGHT_ONE},
+    TOKEN_COUNT,
+};
+use solana_client::rpc_client::RpcClient;
+use solana_program::{instruction::AccountMeta, program_pack::Pack, pubkey::Pubkey, system_instruction};
+use solana_sdk::signature::{Keypair, Signer};
+use spl_associated_token_account::{create_associated_token_account, get_associated_token_address};
+use spl_token::state::Mint as MintState;
+use std::convert::TryInto;
+
+#[derive(Args)]
+pub struct InitArgs {
+    /// Mints of the pool's tokens in order, comma separated
+    #[clap(long, required = true, use_value_delimiter = true)]
+    mints: Vec<Pubkey>,
+    /// Decimals of the lp token [default: the largest number of decimals of the pool's tokens]
+    #[clap(long)]
+    lp_decimals: Option<u8>,
+    #[clap(
+        long = "amp",
+        alias = "amp-factor",
+        required_unless_present_any = &["like", "weights", "fixed-rate"],
+        parse(try_from_str = parse_decimal)
+    )]
+    amp_factor: Option<DecimalU64>,
+    /// e.g. 4bps, 0.04% or 0.0004
+    #[clap(long, required_unless_present = "like", parse(try_from_str = parse_fee))]
+    lp_fee: Option<DecimalU64>,
+    /// [default: 0]
+    #[clap(long = "gov-fee", alias = "governance-fee", parse(try_from_str = parse_fee))]
+    governance_fee: Option<DecimalU64>,
+    /// Initialize a weighted pool that holds these shares of its value in its tokens (in parts of 1000000 and the
+    /// pool's token order, comma separated) instead of a stable swap pool
+    #[clap(long, use_value_delimiter = true, conflicts_with_all = &["amp-factor", "like", "deposit"])]
+    weights: Option<Vec<u32>>,
+    /// Initialize a fixed rate pool that trades its tokens one for one at their exchange rates (set with
+    /// `governance set-rate-source`) instead of a stable swap pool, e.g. to migrate one mint to another
+    #[clap(long, conflicts_with_all = &["amp-factor", "weights", "like", "deposit"])]
+    fixed_rate: bool,
+    /// Initialize a hybrid pool of stablecoins and the volatile token at this index, which the pool values at
+    /// --initial-rate until `repeg` moves it towards the token's rate source (set with `governance set-rate-source`)
+    #[clap(
+        long,
+        requires = "initial-rate",
+        conflicts_with_all = &["weights", "fixed-rate", "like", "deposit"]
+    )]
+    hybrid: Option<u8>,
+    /// The volatile token's initial price in units of the stablecoins, e.g. 1850.5
+    #[clap(long, requires = "hybrid", parse(try_from_str = parse_decimal))]
+    initial_rate: Option<DecimalU64>,
+    /// Copy the amp factor, fees, oracle guard deviation and limits, peg band, amp damping, hook programs and caller
+    /// allowlist of an existing pool of the program instead of giving them
+    #[clap(long, conflicts_with_all = &["amp-factor", "lp-fee", "governance-fee"])]
+    like: Option<Pubkey>,
+    /// Amount of every token to add from the keypair's token accounts as part of the init (in atomic units and the
+    /// pool's token order, comma separated), so the keypair holds the pool's first lp tokens
+    #[clap(long, use_value_delimiter = true, conflicts_with = "like")]
+    deposit: Option<Vec<u64>>,
+    /// Create the lp mint as a Token-2022 mint with the NonTransferable extension, so lp tokens can only be minted to
+    /// and burned from their first owner's account
+    #[clap(long)]
+    non_transferable_lp: bool,
+    /// Governance of the pool [default: the signing keypair]
+    #[clap(long)]
+    governance: Option<Pubkey>,
+    /// Id of the deployed pool program
+    #[clap(long, default_value_t = pool::id())]
+    program_id: Pubkey,
+}
+
+pub fn run(config: Config, args: InitArgs) -> CliResult<()> {
+    if args.mints.len() != TOKEN_COUNT {
+        return Err(format!("expected {} mints, got {}", TOKEN_COUNT, args.mints.len()).into());
+    }
+    for (i, mint) in args.mints.iter().enumerate() {
+        if args.mints[..i].contains(mint) {
+            return Err(format!("mint {} is given more than once", mint).into());
+        }
+    }
+    if let Some(deposit) = &args.deposit {
+        if deposit.len() != TOKEN_COUNT || deposit.contains(&0) {
+            return Err(format!("the deposit needs an amount of each of the {} tokens", TOKEN_COUNT).into());
+        }
+    }
+    let weights = match &args.weights {
+        Some(weights) => {
+            let weights: [u32; TOKEN_COUNT] = weights
+                .as_slice()
+                .try_into()
+                .map_err(|_| format!("expected {} weights, got {}", TOKEN_COUNT, weights.len()))?;
+            if !is_valid_weights(&weights) {
+                return Err(format!(
+                    "the weights have to be at least {} each and add up to {}",
+                    MIN_WEIGHT, WEIGHT_ONE
+                )
+                .into());
+            }
+            Some(weights)
+        }
+        None => None,
+    };
+    let hybrid = match args.hybrid {
+        Some(volatile_index) => {
+            check_index(volatile_index)?;
+            Some((volatile_index, to_fixed_rate(args.initial_rate.unwrap())?))
+        }
+        None => None,
+    };
+    let Config { rpc_client, sender } = config;
+    let template = match args.like {
+        Some(template) => {
+            let template_client = RpcClient::new_with_commitment(rpc_client.url(), rpc_client.commitment());
+            let template = PoolClient::load(template_client, template)?;
+            if template.program_id != args.program_id {
+                return Err(format!("{} is not a pool of program {}", template.pool, args.program_id).into());
+            }
+            Some(template)
+        }
+        None => None,
+    };
+    //a template that is adjusting its amp factor passes on the value at the time the new pool is initialized
+    let (amp_factor, lp_fee, governance_fee) = match &template {
+        Some(template) => (
+            template.state.amp_factor.target_value(),
+            template.state.lp_fee.get(),
+            template.state.governance_fee.get(),
+        ),
+        None => (
+            args.amp_factor.unwrap_or_else(|| DecimalU64::from(1)),
+            args.lp_fee.unwrap(),
+            args.governance_fee.unwrap_or_default(),
+        ),
+    };
+    let token_decimals = args
+        .mints
+        .iter()
+        .zip(rpc_client.get_multiple_accounts(&args.mints)?)
+        .map(|(mint, account)| match account {
+            Some(account) if account.owner == spl_token::id() => MintState::unpack(&account.data)
+                .map(|mint_state| mint_state.decimals)
+                .map_err(|_| format!("{} is not an initialized mint", mint).into()),
+            _ => Err(format!("mint {} does not exist", mint).into()),
+        })
+        .collect::<CliResult<Vec<_>>>()?;
+    let lp_decimals = validate(&token_decimals, args.lp_decimals, amp_factor, lp_fee, governance_fee)?;
+
+    let payer = sender.pubkey();
+    let governance = args.governance.unwrap_or(payer);
+    let pool_keypair = Keypair::new();
+    let pool = pool_keypair.pubkey();
+    let (authority, nonce) = Pubkey::find_program_address(&[&pool.to_bytes()[..32]], &args.program_id);
+    let lp_mint_keypair = Keypair::new();
+    let lp_mint = lp_mint_keypair.pubkey();
+    let lp_account_address = |owner: &Pubkey| {
+        if args.non_transferable_lp {
+            token_2022::get_associated_token_address(owner, &lp_mint)
+        } else {
+            get_associated_token_address(owner, &lp_mint)
+        }
+    };
+    let create_lp_account = |owner: &Pubkey| {
+        if args.non_transferable_lp {
+            token_2022::create_associated_token_account(&payer, owner, &lp_mint)
+        } else {
+            create_associated_token_account(&payer, owner, &lp_mint)
+        }
+    };
+    let governance_fee_account = lp_account_address(&governance);
+    let mut token_mints = [Pubkey::default(); TOKEN_COUNT];
+    let mut token_accounts = [Pubkey::default(); TOKEN_COUNT];
+    for i in 0..TOKEN_COUNT {
+        token_mints[i] = args.mints[i];
+        token_accounts[i] = get_associated_token_address(&authority, &token_mints[i]);
+    }
+
+    //the lp mint is initialized by the transaction that creates it so nobody else can initialize it in between
+    let mut lp_mint_unit = if args.non_transferable_lp {
+        vec![
+            system_instruction::create_account(
+                &payer,
+                &lp_mint,
+                rpc_client.get_minimum_balance_for_rent_exemption(token_2022::NON_TRANSFERABLE_MINT_LEN)?,
+                token_2022::NON_TRANSFERABLE_MINT_LEN as u64,
+                &token_2022::id(),
+            ),
+            token_2022::initialize_non_transferable_mint(&lp_mint),
+            token_2022::initialize_mint(&lp_mint, &authority, lp_decimals),
+        ]
+    } else {
+        vec![
+            system_instruction::create_account(
+                &payer,
+                &lp_mint,
+                rpc_client.get_minimum_balance_for_rent_exemption(MintState::LEN)?,
+                MintState::LEN as u64,
+                &spl_token::id(),
+            ),
+            spl_token::instruction::initialize_mint(&spl_token::id(), &lp_mint, &authority, None, lp_decimals)?,
+        ]
+    };
+    lp_mint_unit.push(create_lp_account(&governance));
+    let mut units = vec![lp_mint_unit];
+    for token_mint in token_mints.iter() {
+        units.push(vec![create_associated_token_account(&payer, &authority, token_mint)]);
+    }
+    let pool_len = solana_program::borsh::get_packed_len::<PoolState<TOKEN_COUNT>>();
+    let mut init_unit = vec![system_instruction::create_account(
+        &payer,
+        &pool,
+        rpc_client.get_minimum_balance_for_rent_exemption(pool_len)?,
+        pool_len as u64,
+        &args.program_id,
+    )];
+    let init_ix = match &template {
+        Some(template) => template.clone_config_ix(
+            &pool,
+            &lp_mint,
+            &token_mints,
+            &token_accounts,
+            &governance,
+            &governance_fee_account,
+            nonce,
+        )?,
+        None if args.fixed_rate => create_init_fixed_rate_ix::<TOKEN_COUNT>(
+            &args.program_id,
+            &pool,
+            &lp_mint,
+            &token_mints,
+            &token_accounts,
+            &governance,
+            &governance_fee_account,
+            nonce,
+            lp_fee,
+            governance_fee,
+        )?,
+        None if hybrid.is_some() => {
+            let (volatile_index, initial_rate) = hybrid.unwrap();
+            create_init_hybrid_ix::<TOKEN_COUNT>(
+                &args.program_id,
+                &pool,
+                &lp_mint,
+                &token_mints,
+                &token_accounts,
+                &governance,
+                &governance_fee_account,
+                nonce,
+                amp_factor,
+                lp_fee,
+                governance_fee,
+                volatile_index,
+                initial_rate,
+            )?
+        }
+        None => match weights {
+            Some(weights) => create_init_weighted_ix::<TOKEN_COUNT>(
+                &args.program_id,
+                &pool,
+                &lp_mint,
+                &token_mints,
+                &token_accounts,
+                &governance,
+                &governance_fee_account,
+                nonce,
+                weights,
+                lp_fee,
+                governance_fee,
+            )?,
+            None => create_init_ix::<TOKEN_COUNT>(
+                &args.program_id,
+                &pool,
+                &lp_mint,
+                &token_mints,
+                &token_accounts,
+                &governance,
+                &governance_fee_account,
+                nonce,
+                amp_factor,
+                lp_fee,
+                governance_fee,
+            )?,
+        },
+    };
+    match &args.deposit {
+        Some(deposit) => {
+            let user_lp = lp_account_address(&payer);
+            let user_token_accounts = create_array(|i| get_associated_token_address(&payer, &token_mints[i]));
+            let mut add_ix = create_defi_ix(
+                DeFiInstruction::Add {
+                    input_amounts: create_array(|i| deposit[i]),
+                    minimum_mint_amount: 0,
+                },
+                &args.program_id,
+                &pool,
+                &authority,
+                &token_accounts,
+                &lp_mint,
+                &governance_fee_account,
+                &payer,
+                &user_token_accounts,
+                &spl_token::id(),
+                Some(&user_lp),
+            )?;
+            if args.non_transferable_lp {
+                add_ix.accounts.push(AccountMeta::new_readonly(token_2022::id(), false));
+            }
+            //the governance fee account if the keypair is the governance
+            if user_lp != governance_fee_account {
+                init_unit.push(create_lp_account(&payer));
+            }
+            init_unit.push(create_init_and_add_ix::<TOKEN_COUNT>(init_ix, add_ix)?);
+        }
+        None => init_unit.push(init_ix),
+    }
+    units.push(init_unit);
+
+    println!("pool: {}", pool);
+    println!("authority: {}", authority);
+    println!("lp mint: {} ({} decimals)", lp_mint, lp_decimals);
+    if args.non_transferable_lp {
+        println!("lp token is non-transferable (Token-2022)");
+    }
+    println!("governance: {}", governance);
+    println!("governance fee account: {}", governance_fee_account);
+    for i in 0..TOKEN_COUNT {
+        println!(
+            "token account {}: {} (mint {}, {} decimals)",
+            i, token_accounts[i], token_mints[i], token_decimals[i]
+        );
+    }
+    match weights {
+        Some(weights) => println!(
+            "weights {:?}, lp fee {}, governance fee {}",
+            weights, lp_fee, governance_fee
+        ),
+        None if args.fixed_rate => println!("fixed rate, lp fee {}, governance fee {}", lp_fee, governance_fee),
+        None if hybrid.is_some() => println!(
+            "hybrid with volatile token {} at {}, amp factor {}, lp fee {}, governance fee {}",
+            args.hybrid.unwrap(),
+            args.initial_rate.unwrap(),
+            amp_factor,
+            lp_fee,
+            governance_fee
+        ),
+        None => println!(
+            "amp factor {}, lp fee {}, governance fee {}",
+            amp_factor, lp_fee, governance_fee
+        ),
+    }
+    if let Some(template) = &template {
+        println!("parameters copied from pool {}", template.pool);
+    }
+    if let Some(deposit) = &args.deposit {
+        println!("initial deposit: {:?}", deposit);
+    }
+
+    let transactions = pack(units, &payer)?;
+    if transactions.len() > 1 {
+        println!("the init needs {} transactions", transactions.len());
+    }
+    for instructions in transactions.iter() {
+        sender.submit(&rpc_client, instructions, &[&lp_mint_keypair, &pool_keypair])?;
+    }
+    Ok(())
+}
+
+//checks the parameters like the program does on init and returns the lp decimals
+fn validate(
+    token_decimals: &[u8],
+    lp_decimals: Option<u8>,
+    amp_factor: DecimalU64,
+    lp_fee: DecimalU64,
+    governance_fee: DecimalU64,
+) -> CliResult<u8> {
+    if AmpFactor::new(amp_factor).is_err() {
+        return Err(format!(
+            "the amp factor has to be between {} and {}",
+            MIN_AMP_VALUE, MAX_AMP_VALUE
+        )
+        .into());
+    }
+    for &(name, fee) in [("lp fee", lp_fee), ("governance fee", governance_fee)].iter() {
+        if PoolFee::new(fee).is_err() {
+            return Err(format!(
+                "invalid {} {}: fees have to be less than 100% with a resolution of a hundredth of a basis point",
+                name, fee
+            )
+            .into());
+        }
+    }
+    if lp_fee + governance_fee >= DecimalU64::from(1) {
+        return Err("lp fee and governance fee have to add up to less than 100%".into());
+    }
+
+    let max_decimals = token_decimals.iter().copied().max().unwrap_or(0);
+    let lp_decimals = lp_decimals.unwrap_or(max_decimals);
+    let min_decimals = token_decimals.iter().copied().min().unwrap_or(0);
+    if max_decimals - min_decimals > MAX_DECIMAL_DIFFERENCE {
+        return Err(format!(
+            "the decimals of the tokens range from {} to {}, which is more than {} apart",
+            min_decimals, max_decimals, MAX_DECIMAL_DIFFERENCE
+        )
+        .into());
+    }
+    if (max_decimals as i16 - lp_decimals as i16).abs() > MAX_DECIMAL_DIFFERENCE as i16 {
+        return Err(format!(
+            "the lp token's {} decimals are more than {} apart from the tokens' {}",
+            lp_decimals, MAX_DECIMAL_DIFFERENCE, max_decimals
+        )
+        .into());
+    }
+    Ok(lp_decimals)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn validation() {
+        let fee = |s| parse_fee(s).unwrap();
+        let amp = DecimalU64::from(1000);
+        assert_eq!(validate(&[6, 9, 8], None, amp, fee("4bps"), fee("1bp")).unwrap(), 9);
+        assert_eq!(validate(&[6, 9, 8], Some(6), amp, fee("4bps"), fee("0")).unwrap(), 6);
+
+        //the lp token may have more or fewer decimals than the tokens, up to the same difference
+        assert!(validate(&[0, 8], None, amp, fee("4bps"), fee("1bp")).is_ok());
+        assert!(validate(&[0, 9], None, amp, fee("4bps"), fee("1bp")).is_err());
+        assert!(validate(&[2, 6], Some(15), amp, fee("4bps"), fee("1bp")).is_err());
+        assert_eq!(validate(&[9, 9], Some(6), amp, fee("4bps"), fee("1bp")).unwrap(), 6);
+        assert_eq!(validate(&[2, 6], Some(11), amp, fee("4bps"), fee("1bp")).unwrap(), 11);
+        assert!(validate(&[9, 9], Some(0), amp, fee("4bps"), fee("1bp")).is_err());
+
+        assert!(validate(&[6, 6], None, DecimalU64::from(0), fee("4bps"), fee("1bp")).is_err());
+        assert!(validate(&[6, 6], None, DecimalU64::from(1_000_001), fee("4bps"), fee("1bp")).is_err());
+        assert!(validate(&[6, 6], None, amp, fee("0.001bps"), fee("0")).is_err());
+        assert!(validate(&[6, 6], None, amp, fee("100%"), fee("0")).is_err());
+        assert!(validate(&[6, 6], None, amp, fee("60%"), fee("40%")).is_err());
+    }
+}
@@ -0,0 +1,451 @@
+//command line interface for pool operators, built on the pool's client module (see src/client)
+//
+//the RPC URL and the signing keypair default to those of the Solana CLI config, user token accounts are always the
+// associated token accounts of the signing keypair
+//
+//with --offline or --dump-base64 write commands print their transactions instead of sending them, so they can be
+// signed elsewhere (e.g. by a multisig that is the pool's governance), --keypair may then be a public key
+
+mod crank;
+mod defi;
+mod deploy;
+mod emergency;
+mod events;
+mod governance;
+mod init;
+mod migrate;
+mod quote;
+mod show;
+mod time;
+mod watch;
+
+use clap::{Parser, Subcommand};
+use pool::{
+    client::{
+        explain_error,
+        pool::{send_instructions, unsigned_transaction, PoolClient},
+        ClientError,
+    },
+    decimal::DecimalU64,
+    token_2022, TOKEN_COUNT,
+};
+use solana_client::rpc_client::RpcClient;
+use solana_program::{instruction::Instruction, message::Message, pubkey::Pubkey};
+use solana_sdk::{
+    commitment_config::CommitmentConfig,
+    packet::PACKET_DATA_SIZE,
+    signature::{read_keypair_file, Signature, Signer},
+    signer::null_signer::NullSigner,
+};
+use spl_associated_token_account::{create_associated_token_account, get_associated_token_address};
+use std::{error::Error, process, str::FromStr};
+
+pub use pool::client::{parse_decimal, parse_fee};
+
+pub type CliResult<T> = Result<T, Box<dyn Error>>;
+
+#[derive(Parser)]
+#[clap(name = "pool-cli", version, about = "Operates pools of the pool program")]
+struct Cli {
+    /// RPC URL of the cluster [default: json_rpc_url of the Solana CLI config]
+    #[clap(long, short = 'u', global = true)]
+    url: Option<String>,
+    /// Keypair that signs and pays for all transactions [default: keypair_path of the Solana CLI config]
+    #[clap(long, short = 'k', global = true)]
+    keypair: Option<String>,
+    /// Print the message of every transaction base58 encoded (as imported by multisig tools) instead of sending it
+    #[clap(long, global = true)]
+    offline: bool,
+    /// Print every transaction unsigned and base64 encoded instead of sending it
+    #[clap(long, global = true, conflicts_with = "offline")]
+    dump_base64: bool,
+    #[clap(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Creates the accounts of a new pool and initializes it
+    Init(init::InitArgs),
+    /// Creates the pools of a manifest that don't exist yet
+    Deploy(deploy::DeployArgs),
+    /// Adds tokens to a pool in exchange for lp tokens
+    Add(defi::AddArgs),
+    /// Swaps one token for another
+    Swap(defi::SwapArgs),
+    /// Burns lp tokens in exchange for tokens of the pool
+    Remove(defi::RemoveArgs),
+    /// Announces removes above the pool's withdrawal cooldown threshold, which can be made once the cooldown passed
+    AnnounceWithdrawal(defi::AnnounceWithdrawalArgs),
+    /// Claims the rebate that swaps earned with one of the pool's rebate campaigns once its window ended
+    ClaimRebate(defi::ClaimRebateArgs),
+    /// Burns all of the user's lp tokens for their share of a wound down pool once its sunset deadline passed
+    RedeemSunset(defi::RedeemSunsetArgs),
+    /// Prints the state of a pool
+    Show(show::ShowArgs),
+    /// Quotes a swap exactly like the program would execute it
+    Quote(quote::QuoteArgs),
+    /// Follows a pool's updates and alerts (with exit status 2) when thresholds are breached
+    Watch(watch::WatchArgs),
+    /// Submits the pool's permissionless maintenance instructions, each at its own interval
+    Crank(crank::CrankArgs),
+    /// Exports the pool's events (its instructions along with the resulting balance changes) as csv or json lines
+    ExportEvents(events::ExportEventsArgs),
+    /// Counts the pool's failed transactions by why they failed (slippage, paused, validation, other)
+    Rejections(events::RejectionsArgs),
+    /// Moves liquidity from one pool to another
+    Migrate(migrate::MigrateArgs),
+    /// Changes the pool's settings (requires the governance keypair unless run with --dry-run)
+    Governance(governance::GovernanceArgs),
+    /// Confirms an emergency as one of the pool's emergency responders, pausing the pool once enough of them did
+    ConfirmEmergency(emergency::ConfirmEmergencyArgs),
+}
+
+pub struct Config {
+    pub rpc_client: RpcClient,
+    pub sender: Sender,
+}
+
+impl Config {
+    fn new(url: Option<String>, keypair: Option<String>, export: Option<Export>) -> CliResult<Self> {
+        let cli_config = solana_cli_config::CONFIG_FILE
+            .as_ref()
+            .and_then(|path| solana_cli_config::Config::load(path).ok())
+            .unwrap_or_default();
+        let url = url.unwrap_or(cli_config.json_rpc_url);
+        let keypair_path = keypair.unwrap_or(cli_config.keypair_path);
+        let signer: Box<dyn Signer> = match Pubkey::from_str(&keypair_path) {
+            Ok(pubkey) if export.is_some() => Box::new(NullSigner::new(&pubkey)),
+            _ => Box::new(
+                read_keypair_file(&keypair_path)
+                    .map_err(|err| format!("failed to read keypair {}: {}", keypair_path, err))?,
+            ),
+        };
+        Ok(Self {
+            rpc_client: RpcClient::new_with_commitment(url, CommitmentConfig::confirmed()),
+            sender: Sender { signer, export },
+        })
+    }
+
+    pub fn load_pool(self, pool: Pubkey) -> CliResult<(PoolClient, Sender)> {
+        Ok((PoolClient::load(self.rpc_client, pool)?, self.sender))
+    }
+}
+
+#[derive(Clone, Copy)]
+pub enum Export {
+    /// The transaction's message, base58 encoded
+    Message,
+    /// The unsigned transaction, base64 encoded
+    Transaction,
+}
+
+/// The signing keypair (or, when exporting, possibly just its public key) along with how to hand over transactions
+pub struct Sender {
+    signer: Box<dyn Signer>,
+    export: Option<Export>,
+}
+
+impl Sender {
+    pub fn pubkey(&self) -> Pubkey {
+        self.signer.pubkey()
+    }
+
+    pub fn is_exporting(&self) -> bool {
+        self.export.is_some()
+    }
+
+    /// Sends the instructions within a single transaction signed by `signers` as well or, when exporting, prints the
+    /// transaction (carrying only the signatures of `signers`) instead. `signers` the instructions don't require are
+    /// skipped, so the same signers can be passed for every transaction of a batch
+    pub fn submit(
+        &self,
+        rpc_client: &RpcClient,
+        instructions: &[Instruction],
+        signers: &[&dyn Signer],
+    ) -> CliResult<()> {
+        let message = Message::new(instructions, Some(&self.pubkey()));
+        let required = message.signer_keys();
+        let signers: Vec<&dyn Signer> = signers
+            .iter()
+            .copied()
+            .filter(|signer| required.contains(&&signer.pubkey()))
+            .collect();
+        let signers = signers.as_slice();
+        let export = match self.export {
+            Some(export) => export,
+            None => {
+                let signature = send_instructions(rpc_client, instructions, self.signer.as_ref(), signers)?;
+                println!("signature: {}", signature);
+                return Ok(());
+            }
+        };
+        let transaction = unsigned_transaction(rpc_client, instructions, &self.pubkey(), signers)?;
+        let missing_signers: Vec<_> = transaction
+            .message
+            .account_keys
+            .iter()
+            .zip(transaction.signatures.iter())
+            .filter(|(_, signature)| **signature == Signature::default())
+            .map(|(key, _)| key.to_string())
+            .collect();
+        println!(
+            "transaction requiring the signatures of {}:",
+            missing_signers.join(", ")
+        );
+        match export {
+            Export::Message => println!("{}", bs58::encode(transaction.message_data()).into_string()),
+            Export::Transaction => println!("{}", base64::encode(bincode::serialize(&transaction)?)),
+        }
+        Ok(())
+    }
+}
+
+fn main() {
+    let cli = Cli::parse();
+    if let Err(err) = run(cli) {
+        eprintln!("error: {}", describe(err.as_ref()));
+        process::exit(1);
+    }
+}
+
+fn run(cli: Cli) -> CliResult<()> {
+    let export = if cli.offline {
+        Some(Export::Message)
+    } else if cli.dump_base64 {
+        Some(Export::Transaction)
+    } else {
+        None
+    };
+    let config = Config::new(cli.url, cli.keypair, export)?;
+    match cli.command {
+        Command::Init(args) => init::run(config, args),
+        Command::Deploy(args) => deploy::run(config, args),
+        Command::Add(args) => defi::add(config, args),
+        Command::Swap(args) => defi::swap(config, args),
+        Command::Remove(args) => defi::remove(config, args),
+        Command::AnnounceWithdrawal(args) => defi::announce_withdrawal(config, args),
+        Command::ClaimRebate(args) => defi::claim_rebate(config, args),
+        Command::RedeemSunset(args) => defi::redeem_sunset(config, args),
+        Command::Show(args) => show::run(config, args),
+        Command::Quote(args) => quote::run(config, args),
+        Command::Watch(args) => watch::run(config, args),
+        Command::Crank(args) => crank::run(config, args),
+        Command::ExportEvents(args) => events::run(config, args),
+        Command::Rejections(args) => events::rejections(config, args),
+        Command::Migrate(args) => migrate::run(config, args),
+        Command::Governance(args) => governance::run(config, args),
+        Command::ConfirmEmergency(args) => emergency::confirm_emergency(config, args),
+    }
+}
+
+//translates failed transactions into the pool's (or the token program's) error messages, with hints what to do
+fn describe(err: &(dyn Error + 'static)) -> String {
+    let transaction_error = match err.downcast_ref::<ClientError>() {
+        Some(ClientError::Rpc(err)) => err.get_transaction_error(),
+        Some(ClientError::SimulationFailed(err)) | Some(ClientError::TransactionFailed(err)) => Some(err.clone()),
+        _ => None,
+    };
+    match transaction_error {
+        Some(transaction_error) => explain_error(transaction_error).to_string(),
+        None => err.to_string(),
+    }
+}
+
+/// Parses fractions given either as percentage (`20%`) or as decimal number (`0.2`)
+pub fn parse_percentage(s: &str) -> Result<f64, String> {
+    let (number, scale) = match s.strip_suffix('%') {
+        Some(number) => (number, 100.0),
+        None => (s, 1.0),
+    };
+    let decimal = parse_decimal(number)?;
+    Ok(decimal.get_raw() as f64 / 10f64.powi(decimal.get_decimals() as i32) / scale)
+}
+
+pub fn check_index(index: u8) -> CliResult<()> {
+    if index as usize >= TOKEN_COUNT {
+        return Err(format!(
+            "token index {} is out of range, the pool has {} tokens",
+            index, TOKEN_COUNT
+        )
+        .into());
+    }
+    Ok(())
+}
+
+/// Converts amounts given on the command line (one per token of the pool) into an array
+pub fn token_amounts(amounts: &[u64]) -> CliResult<[u64; TOKEN_COUNT]> {
+    if amounts.len() != TOKEN_COUNT {
+        return Err(format!(
+            "expected {} amounts (one per token), got {}",
+            TOKEN_COUNT,
+            amounts.len()
+        )
+        .into());
+    }
+    let mut array = [0; TOKEN_COUNT];
+    array.copy_from_slice(amounts);
+    Ok(array)
+}
+
+/// The associated token accounts of `owner` for all tokens of the pool and its lp token
+pub fn user_accounts(pool_client: &PoolClient, owner: &Pubkey) -> ([Pubkey; TOKEN_COUNT], Pubkey) {
+    let mut token_accounts = [Pubkey::default(); TOKEN_COUNT];
+    for i in 0..TOKEN_COUNT {
+        token_accounts[i] = get_associated_token_address(owner, &pool_client.state.token_mint_keys[i]);
+    }
+    let lp_account = if pool_client.state.lp_non_transferable {
+        token_2022::get_associated_token_address(owner, &pool_client.state.lp_mint_key)
+    } else {
+        get_associated_token_address(owner, &pool_client.state.lp_mint_key)
+    };
+    (token_accounts, lp_account)
+}
+
+/// Submits `instruction` on behalf of the sender, creating any of its associated token accounts that don't exist yet
+pub fn send_as_user(pool_client: &PoolClient, sender: &Sender, pool_instructions: &[Instruction]) -> CliResult<()> {
+    let state = &pool_client.state;
+    let mut mints = state.token_mint_keys.to_vec();
+    if !state.lp_non_transferable {
+        mints.push(state.lp_mint_key);
+    }
+    let mut instructions = create_missing_accounts(&pool_client.rpc_client, &sender.pubkey(), &mints)?;
+    if state.lp_non_transferable {
+        let owner = sender.pubkey();
+        let lp_account = user_accounts(pool_client, &owner).1;
+        if pool_client
+            .rpc_client
+            .get_account_with_commitment(&lp_account, pool_client.rpc_client.commitment())?
+            .value
+            .is_none()
+        {
+            instructions.push(token_2022::create_associated_token_account(
+                &owner,
+                &owner,
+                &state.lp_mint_key,
+            ));
+        }
+    }
+    instructions.extend_from_slice(pool_instructions);
+    sender.submit(&pool_client.rpc_client, &instructions, &[])
+}
+
+/// `CreateLaunchAllocation` for `owner` if the pool's launch window is open and `owner` has no launch allocation yet,
+/// which its adds need
+pub fn create_missing_launch_allocation(pool_client: &PoolClient, owner: &Pubkey) -> CliResult<Option<Instruction>> {
+    if !pool_client.state.is_launch_capped(pool_client.now()?) || pool_client.launch_allocation(owner)?.is_some() {
+        return Ok(None);
+    }
+    Ok(Some(pool_client.create_launch_allocation_ix(owner, owner)?))
+}
+
+/// `JoinRebateCampaign` for `owner` if `owner` didn't join the pool's rebate campaign yet, which its swaps need to
+/// record their fees with it
+pub fn create_missing_rebate(pool_client: &PoolClient, owner: &Pubkey) -> CliResult<Option<Instruction>> {
+    if pool_client.rebate(&pool_client.state.rebate_campaign, owner)?.is_some() {
+        return Ok(None);
+    }
+    Ok(Some(pool_client.join_rebate_campaign_ix(owner, owner)?))
+}
+
+/// Instructions that create the associated token accounts of `owner` for those of `mints` that don't exist yet
+pub fn create_missing_accounts(
+    rpc_client: &RpcClient,
+    owner: &Pubkey,
+    mints: &[Pubkey],
+) -> CliResult<Vec<Instruction>> {
+    let mut mints = mints.to_vec();
+    mints.sort();
+    mints.dedup();
+    let accounts: Vec<_> = mints
+        .iter()
+        .map(|mint| get_associated_token_address(owner, mint))
+        .collect();
+    let existing = rpc_client.get_multiple_accounts(&accounts)?;
+    Ok(mints
+        .iter()
+        .zip(existing.iter())
+        .filter(|(_, account)| account.is_none())
+        .map(|(mint, _)| create_associated_token_account(owner, owner, mint))
+        .collect())
+}
+
+/// Whether the instructions fit into a single transaction paid for by `payer`
+pub fn fits(instructions: &[Instruction], payer: &Pubkey) -> bool {
+    let message = Message::new(instructions, Some(payer));
+    //signatures are prefixed by their (compact-u16 encoded) count, a single byte for up to 127 signatures
+    1 + 64 * message.header.num_required_signatures as usize + message.serialize().len() <= PACKET_DATA_SIZE
+}
+
+/// Groups units of instructions (which mustn't be split up) into as few consecutive transactions as fit
+pub fn pack(units: Vec<Vec<Instruction>>, payer: &Pubkey) -> CliResult<Vec<Vec<Instruction>>> {
+    let mut transactions: Vec<Vec<Instruction>> = vec![];
+    for unit in units {
+        if !fits(&unit, payer) {
+            return Err("instructions don't fit into a single transaction".into());
+        }
+        match transactions.last_mut() {
+            Some(last) if fits(&[last.as_slice(), unit.as_slice()].concat(), payer) => last.extend(unit),
+            _ => transactions.push(unit),
+        }
+    }
+    Ok(transactions)
+}
+
+/// The limit for a swap (or an add) that tolerates `slippage`: rounded down for a minimum output, up for a maximum input
+pub fn apply_slippage(amount: u64, slippage: DecimalU64, is_input: bool) -> CliResult<u64> {
+    let factor = if is_input {
+        DecimalU64::from(1) + slippage
+    } else {
+        DecimalU64::from(1) - slippage
+    };
+    let limit = DecimalU64::from(amount)
+        .checked_mul(factor)
+        .ok_or("amount is too large to apply the slippage to")?;
+    Ok(if is_input { limit.ceil(0) } else { limit }.trunc())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_decimals() {
+        assert_eq!(parse_decimal("1000").unwrap(), DecimalU64::new(1000, 0).unwrap());
+        assert_eq!(parse_decimal("0.0003").unwrap(), DecimalU64::new(3, 4).unwrap());
+        assert_eq!(parse_decimal("12.50").unwrap(), DecimalU64::new(1250, 2).unwrap());
+        for invalid in ["", ".5", "1.2.3", "-1", "1e3", "18446744073709551616"].iter() {
+            assert!(parse_decimal(invalid).is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn parse_percentages() {
+        assert_eq!(parse_percentage("20%").unwrap(), 0.2);
+        assert_eq!(parse_percentage("0.2").unwrap(), 0.2);
+        assert_eq!(parse_percentage("0.5%").unwrap(), 0.005);
+        assert!(parse_percentage("%").is_err());
+        assert!(parse_percentage("20 %").is_err());
+    }
+
+    #[test]
+    fn parse_fees() {
+        assert_eq!(parse_fee("4bps").unwrap(), DecimalU64::new(4, 4).unwrap());
+        assert_eq!(parse_fee("1bp").unwrap(), DecimalU64::new(1, 4).unwrap());
+        assert_eq!(parse_fee("0.5bps").unwrap(), DecimalU64::new(5, 5).unwrap());
+        assert_eq!(parse_fee("0.04%").unwrap(), DecimalU64::new(4, 4).unwrap());
+        assert_eq!(parse_fee("0.0004").unwrap(), DecimalU64::new(4, 4).unwrap());
+        assert!(parse_fee("bps").is_err());
+        assert!(parse_fee("4 bps").is_err());
+    }
+
+    #[test]
+    fn slippage() {
+        let slippage = parse_decimal("0.005").unwrap();
+        assert_eq!(apply_slippage(1000, slippage, false).unwrap(), 995);
+        assert_eq!(apply_slippage(1001, slippage, false).unwrap(), 995);
+        assert_eq!(apply_slippage(1000, slippage, true).unwrap(), 1005);
+        assert_eq!(apply_slippage(1001, slippage, true).unwrap(), 1007);
+        assert_eq!(apply_slippage(0, slippage, true).unwrap(), 0);
+    }
+}
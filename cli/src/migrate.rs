@@ -0,0 +1,210 @@
+//moves liquidity from one pool to another: a uniform remove from the old pool followed by an add to the new one
+//
+//both instructions go into a single transaction whenever it fits, in which case the remove has to return exactly the
+// quoted amounts (otherwise the whole migration fails and nothing changes) and the slippage only bounds the lp tokens
+// minted by the new pool. if the transaction is too large, the missing token accounts are created first and, failing
+// that, the remove and the add are sent separately (with the add depositing whatever the remove actually returned)
+
+use crate::{
+    apply_slippage, create_missing_accounts, create_missing_launch_allocation, fits, parse_decimal, user_accounts,
+    CliResult, Config,
+};
+use clap::Args;
+use pool::{client::pool::PoolClient, decimal::DecimalU64, instruction::DeFiInstruction, quote, TOKEN_COUNT};
+use solana_client::rpc_client::RpcClient;
+use solana_program::{instruction::Instruction, pubkey::Pubkey};
+
+#[derive(Args)]
+pub struct MigrateArgs {
+    /// Pool to withdraw from
+    #[clap(long)]
+    from: Pubkey,
+    /// Pool to deposit into
+    #[clap(long)]
+    to: Pubkey,
+    /// Lp tokens of the old pool to burn [default: all]
+    #[clap(long)]
+    amount: Option<u64>,
+    /// Tolerated shortfall of the minted lp tokens compared to the quote
+    #[clap(long, default_value = "0.005", parse(try_from_str = parse_decimal))]
+    slippage: DecimalU64,
+}
+
+pub fn run(config: Config, args: MigrateArgs) -> CliResult<()> {
+    if args.from == args.to {
+        return Err("--from and --to must be different pools".into());
+    }
+    if args.slippage >= DecimalU64::from(1) {
+        return Err("slippage must be less than 1".into());
+    }
+    let rpc_client = RpcClient::new_with_commitment(config.rpc_client.url(), config.rpc_client.commitment());
+    let (from, sender) = config.load_pool(args.from)?;
+    let to = PoolClient::load(rpc_client, args.to)?;
+    let owner = sender.pubkey();
+    let (from_token_accounts, from_lp_account) = user_accounts(&from, &owner);
+    let (to_token_accounts, to_lp_account) = user_accounts(&to, &owner);
+
+    let from_snapshot = from.snapshot()?;
+    let lp_balance = from
+        .rpc_client
+        .get_token_account_balance(&from_lp_account)
+        .map_err(|_| format!("{} holds no lp tokens of {}", owner, args.from))?
+        .amount
+        .parse::<u64>()?;
+    let burn_amount = args.amount.unwrap_or(lp_balance);
+    if burn_amount == 0 || burn_amount > lp_balance {
+        return Err(format!("can't burn {} lp tokens, {} holds {}", burn_amount, owner, lp_balance).into());
+    }
+    let (output_amounts, _) = quote::remove_uniform(
+        &from_snapshot.state,
+        &from_snapshot.balances,
+        from_snapshot.lp_supply,
+        burn_amount,
+    );
+    let input_amounts = deposit_amounts(
+        &from_snapshot.state.token_mint_keys,
+        &output_amounts,
+        &to.state.token_mint_keys,
+    );
+    check_deposit(&to, &input_amounts)?;
+
+    println!("burning {} lp tokens of {}", burn_amount, args.from);
+    for i in 0..TOKEN_COUNT {
+        let mint = from_snapshot.state.token_mint_keys[i];
+        let migrated = to.state.token_mint_keys.contains(&mint);
+        println!(
+            "  {} of {}{}",
+            output_amounts[i],
+            mint,
+            if migrated { "" } else { " (stays in the wallet)" }
+        );
+    }
+
+    let mut mints = from.state.token_mint_keys.to_vec();
+    mints.extend_from_slice(&to.state.token_mint_keys);
+    mints.push(to.state.lp_mint_key);
+    let mut account_ixs = create_missing_accounts(&from.rpc_client, &owner, &mints)?;
+    account_ixs.extend(create_missing_launch_allocation(&to, &owner)?);
+    let remove_ix = |minimum_output_amounts| {
+        from.defi_ix(
+            DeFiInstruction::RemoveUniform {
+                exact_burn_amount: burn_amount,
+                minimum_output_amounts,
+            },
+            &owner,
+            &from_token_accounts,
+            &from_lp_account,
+        )
+    };
+    let add_ix = |input_amounts: [u64; TOKEN_COUNT]| -> CliResult<Instruction> {
+        let minimum_mint_amount = quote_add(&to, &input_amounts, args.slippage)?;
+        Ok(to.defi_ix(
+            DeFiInstruction::Add {
+                input_amounts,
+                minimum_mint_amount,
+            },
+            &owner,
+            &to_token_accounts,
+            &to_lp_account,
+        )?)
+    };
+    let send = |instructions: &[Instruction]| sender.submit(&from.rpc_client, instructions, &[]);
+
+    let migration_ixs = vec![remove_ix(output_amounts)?, add_ix(input_amounts)?];
+    let all_ixs: Vec<_> = account_ixs.iter().chain(migration_ixs.iter()).cloned().collect();
+    if fits(&all_ixs, &owner) {
+        return send(&all_ixs);
+    }
+    let migration_fits = fits(&migration_ixs, &owner);
+    if !migration_fits && sender.is_exporting() {
+        //the add of a separate transaction depends on the outcome of the remove, so it can't be built in advance
+        return Err("the migration doesn't fit into a single transaction and can't be exported".into());
+    }
+    if !account_ixs.is_empty() {
+        send(&account_ixs)?;
+    }
+    if migration_fits {
+        return send(&migration_ixs);
+    }
+
+    println!("the migration doesn't fit into a single transaction, removing and adding separately");
+    let balances_before = token_balances(&from.rpc_client, &to_token_accounts)?;
+    let mut minimum_output_amounts = [0; TOKEN_COUNT];
+    for i in 0..TOKEN_COUNT {
+        minimum_output_amounts[i] = apply_slippage(output_amounts[i], args.slippage, false)?;
+    }
+    send(&[remove_ix(minimum_output_amounts)?])?;
+    let balances_after = token_balances(&from.rpc_client, &to_token_accounts)?;
+    let mut received = [0; TOKEN_COUNT];
+    for i in 0..TOKEN_COUNT {
+        received[i] = balances_after[i].saturating_sub(balances_before[i]);
+    }
+    send(&[add_ix(received)?])
+}
+
+//the amounts to deposit into the new pool: the removed amounts of the tokens both pools share (in the new pool's order)
+fn deposit_amounts(
+    from_mints: &[Pubkey; TOKEN_COUNT],
+    output_amounts: &[u64; TOKEN_COUNT],
+    to_mints: &[Pubkey; TOKEN_COUNT],
+) -> [u64; TOKEN_COUNT] {
+    let mut input_amounts = [0; TOKEN_COUNT];
+    for (input_amount, to_mint) in input_amounts.iter_mut().zip(to_mints.iter()) {
+        if let Some(i) = from_mints.iter().position(|from_mint| from_mint == to_mint) {
+            *input_amount = output_amounts[i];
+        }
+    }
+    input_amounts
+}
+
+fn check_deposit(to: &PoolClient, input_amounts: &[u64; TOKEN_COUNT]) -> CliResult<()> {
+    if to.state.is_paused {
+        return Err(format!("{} is paused", to.pool).into());
+    }
+    if input_amounts.iter().all(|amount| *amount == 0) {
+        return Err("the pools share no tokens (or the removed amounts are all zero)".into());
+    }
+    if input_amounts.iter().any(|amount| *amount == 0) && to.lp_supply()? == 0 {
+        return Err(format!("{} is empty and its first add requires all of its tokens", to.pool).into());
+    }
+    Ok(())
+}
+
+//the minimum lp amount for adding `input_amounts` to the pool with the given slippage
+fn quote_add(to: &PoolClient, input_amounts: &[u64; TOKEN_COUNT], slippage: DecimalU64) -> CliResult<u64> {
+    let snapshot = to.snapshot()?;
+    let quote = snapshot.quoter().add(input_amounts)?;
+    println!("expecting {} lp tokens of {}", quote.amount, to.pool);
+    apply_slippage(quote.amount, slippage, false)
+}
+
+fn token_balances(rpc_client: &RpcClient, accounts: &[Pubkey; TOKEN_COUNT]) -> CliResult<[u64; TOKEN_COUNT]> {
+    let mut balances = [0; TOKEN_COUNT];
+    for i in 0..TOKEN_COUNT {
+        balances[i] = rpc_client.get_token_account_balance(&accounts[i])?.amount.parse()?;
+    }
+    Ok(balances)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn deposit_shared_tokens() {
+        let from_mints = [(); TOKEN_COUNT].map(|_| Pubkey::new_unique());
+        let mut to_mints = [(); TOKEN_COUNT].map(|_| Pubkey::new_unique());
+        to_mints[0] = from_mints[1];
+        to_mints[1] = from_mints[0];
+        let mut output_amounts = [0; TOKEN_COUNT];
+        output_amounts[0] = 10;
+        output_amounts[1] = 20;
+        output_amounts[2] = 30;
+
+        let input_amounts = deposit_amounts(&from_mints, &output_amounts, &to_mints);
+        let mut expected = [0; TOKEN_COUNT];
+        expected[0] = 20;
+        expected[1] = 10;
+        assert_eq!(input_amounts, expected);
+    }
+}
@@ -0,0 +1,203 @@
+//quotes swaps with the same code the program executes them with (see src/quote.rs), so the printed amounts are exact
+// unless the pool changes before the swap lands (which the printed limit for the given slippage protects against)
+
+use crate::{apply_slippage, check_index, parse_decimal, CliResult, Config};
+use clap::{Args, Subcommand};
+use pool::{client::snapshot::PoolSnapshot, decimal::DecimalU64, TOKEN_COUNT};
+use solana_program::pubkey::Pubkey;
+use std::str::FromStr;
+
+#[derive(Args)]
+pub struct QuoteArgs {
+    /// Address of the pool
+    pool: Pubkey,
+    /// Tolerated change of the quoted amount until the swap executes, used for the printed limit
+    #[clap(long, default_value = "0.005", parse(try_from_str = parse_decimal))]
+    slippage: DecimalU64,
+    #[clap(subcommand)]
+    kind: QuoteKind,
+}
+
+#[derive(Subcommand)]
+enum QuoteKind {
+    /// Quotes paying exactly `amount` of the input token
+    SwapExactIn(SwapTokens),
+    /// Quotes receiving exactly `amount` of the output token
+    SwapExactOut(SwapTokens),
+    /// Suggests a price for an over-the-counter sale of `amount` lp tokens, paid in one of the pool's tokens
+    LpPrice(LpPriceTokens),
+}
+
+#[derive(Args)]
+struct LpPriceTokens {
+    /// Token that the lp tokens are paid with, given as index or mint
+    #[clap(long = "in")]
+    payment: String,
+    /// Amount of lp tokens in atomic units
+    #[clap(long)]
+    amount: u64,
+}
+
+#[derive(Args)]
+struct SwapTokens {
+    /// Token that is paid, given as index or mint
+    #[clap(long = "in")]
+    input: String,
+    /// Token that is received, given as index or mint
+    #[clap(long = "out")]
+    output: String,
+    /// Amount in atomic units
+    #[clap(long)]
+    amount: u64,
+}
+
+pub fn run(config: Config, args: QuoteArgs) -> CliResult<()> {
+    if args.slippage >= DecimalU64::from(1) {
+        return Err("slippage must be less than 1".into());
+    }
+    let (pool_client, _) = config.load_pool(args.pool)?;
+    let snapshot = pool_client.snapshot()?;
+    if snapshot.lp_supply == 0 {
+        return Err("the pool is empty".into());
+    }
+    let (exact_input, tokens) = match args.kind {
+        QuoteKind::SwapExactIn(tokens) => (true, tokens),
+        QuoteKind::SwapExactOut(tokens) => (false, tokens),
+        QuoteKind::LpPrice(lp_tokens) => return lp_price(&snapshot, lp_tokens),
+    };
+    let mints = &snapshot.state.token_mint_keys;
+    let input_index = token_index(&tokens.input, mints)?;
+    let output_index = token_index(&tokens.output, mints)?;
+    if input_index == output_index {
+        return Err("input and output token must differ".into());
+    }
+    if tokens.amount == 0 {
+        return Err("amount must be positive".into());
+    }
+
+    let quoter = snapshot.swap_quoter()?;
+    let fee_free = quoter.clone().without_fees();
+    let mut amounts = [0; TOKEN_COUNT];
+    let (input_amount, output_amount, fee, governance_mint_amount) = if exact_input {
+        amounts[input_index] = tokens.amount;
+        let quote = quoter.swap_exact_input(&amounts, output_index)?;
+        let without_fees = fee_free.swap_exact_input(&amounts, output_index)?;
+        (
+            tokens.amount,
+            quote.amount,
+            without_fees.amount.saturating_sub(quote.amount),
+            quote.governance_mint_amount,
+        )
+    } else {
+        if tokens.amount >= snapshot.balances[output_index] {
+            return Err(format!(
+                "the pool only holds {} of the output token",
+                snapshot.balances[output_index]
+            )
+            .into());
+        }
+        amounts[output_index] = tokens.amount;
+        let quote = quoter.swap_exact_output(input_index, &amounts)?;
+        let without_fees = fee_free.swap_exact_output(input_index, &amounts)?;
+        (
+            quote.amount,
+            tokens.amount,
+            quote.amount.saturating_sub(without_fees.amount),
+            quote.governance_mint_amount,
+        )
+    };
+
+    //the marginal price is approximated by the price of a fee-less swap of a millionth of the input token's balance
+    let marginal_input = (snapshot.balances[input_index] / 1_000_000).max(1);
+    let mut marginal_amounts = [0; TOKEN_COUNT];
+    marginal_amounts[input_index] = marginal_input;
+    let marginal_output = fee_free.swap_exact_input(&marginal_amounts, output_index)?.amount;
+    let equalizers = &snapshot.state.token_decimal_equalizers;
+    let price = |input: u64, output: u64| {
+        (output as f64 * 10f64.powi(equalizers[output_index] as i32))
+            / (input as f64 * 10f64.powi(equalizers[input_index] as i32))
+    };
+    let fee_free_output = if exact_input {
+        output_amount + fee
+    } else {
+        output_amount
+    };
+    let fee_free_input = if exact_input { input_amount } else { input_amount - fee };
+    let price_impact = 1.0 - price(fee_free_input, fee_free_output) / price(marginal_input, marginal_output);
+
+    let fee_token = if exact_input { output_index } else { input_index };
+    println!(
+        "input:          {} of token {} ({})",
+        input_amount, input_index, mints[input_index]
+    );
+    println!(
+        "output:         {} of token {} ({})",
+        output_amount, output_index, mints[output_index]
+    );
+    println!("fee:            {} of token {}", fee, fee_token);
+    println!(
+        "governance fee: {} lp tokens minted to the governance fee account",
+        governance_mint_amount
+    );
+    println!("price impact:   {:.4}%", price_impact.max(0.0) * 100.0);
+    println!("amp factor:     {} at slot {}", snapshot.amp_factor, snapshot.slot);
+    if exact_input {
+        let minimum_output = apply_slippage(output_amount, args.slippage, false)?;
+        println!("minimum output: {} (slippage {})", minimum_output, args.slippage);
+        println!(
+            "swap:           pool-cli swap {} --input-index {} --output-index {} {} --limit {}",
+            args.pool, input_index, output_index, input_amount, minimum_output
+        );
+    } else {
+        let maximum_input = apply_slippage(input_amount, args.slippage, true)?;
+        println!("maximum input:  {} (slippage {})", maximum_input, args.slippage);
+        println!(
+            "swap:           pool-cli swap {} --input-index {} --output-index {} {} --exact-output --limit {}",
+            args.pool, input_index, output_index, output_amount, maximum_input
+        );
+    }
+    Ok(())
+}
+
+fn lp_price(snapshot: &PoolSnapshot, lp_tokens: LpPriceTokens) -> CliResult<()> {
+    let payment_index = token_index(&lp_tokens.payment, &snapshot.state.token_mint_keys)?;
+    let price = snapshot.suggest_lp_price(lp_tokens.amount, payment_index)?;
+    println!("lp tokens:      {}", lp_tokens.amount);
+    println!(
+        "fair price:     {} of token {} ({}) at the virtual price",
+        price.fair_amount, payment_index, snapshot.state.token_mint_keys[payment_index]
+    );
+    println!(
+        "redeem value:   {} of token {} by removing the lp tokens into it",
+        price.redeem_amount, payment_index
+    );
+    Ok(())
+}
+
+fn token_index(token: &str, mints: &[Pubkey; TOKEN_COUNT]) -> CliResult<usize> {
+    if let Ok(index) = token.parse::<u8>() {
+        check_index(index)?;
+        return Ok(index as usize);
+    }
+    let mint = Pubkey::from_str(token).map_err(|_| format!("{} is neither a token index nor a mint", token))?;
+    mints
+        .iter()
+        .position(|pool_mint| *pool_mint == mint)
+        .ok_or_else(|| format!("{} is not a token of the pool", mint).into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tokens() {
+        let mut mints = [Pubkey::default(); TOKEN_COUNT];
+        mints[1] = Pubkey::new_unique();
+        assert_eq!(token_index("1", &mints).unwrap(), 1);
+        assert_eq!(token_index(&mints[1].to_string(), &mints).unwrap(), 1);
+        assert!(token_index(&Pubkey::new_unique().to_string(), &mints).is_err());
+        assert!(token_index(&TOKEN_COUNT.to_string(), &mints).is_err());
+        assert!(token_index("usdc", &mints).is_err());
+    }
+}
@@ -0,0 +1,155 @@
+//prints a snapshot of the pool, either as a table or with --json for scripting (amounts and decimals are encoded as
+// strings in the latter, since they don't fit into the double precision numbers of most json parsers)
+
+use crate::{time::format_timestamp, CliResult, Config};
+use clap::Args;
+use pool::{client::snapshot::PoolSnapshot, TOKEN_COUNT};
+use serde_json::{json, Value};
+use solana_program::pubkey::Pubkey;
+
+#[derive(Args)]
+pub struct ShowArgs {
+    /// Address of the pool
+    pool: Pubkey,
+    /// Print the pool as json
+    #[clap(long)]
+    json: bool,
+}
+
+pub fn run(config: Config, args: ShowArgs) -> CliResult<()> {
+    let (pool_client, _) = config.load_pool(args.pool)?;
+    let snapshot = pool_client.snapshot()?;
+    let authority = pool_client.authority();
+    if args.json {
+        let value = to_json(&snapshot, &pool_client.program_id, &authority);
+        println!("{}", serde_json::to_string_pretty(&value)?);
+    } else {
+        print_table(&snapshot, &pool_client.program_id, &authority);
+    }
+    Ok(())
+}
+
+fn to_json(snapshot: &PoolSnapshot, program_id: &Pubkey, authority: &Pubkey) -> Value {
+    let state = &snapshot.state;
+    let tokens: Vec<_> = (0..TOKEN_COUNT)
+        .map(|i| {
+            json!({
+                "mint": state.token_mint_keys[i].to_string(),
+                "account": state.token_keys[i].to_string(),
+                "decimal_equalizer": state.token_decimal_equalizers[i],
+                "balance": snapshot.balances[i].to_string(),
+            })
+        })
+        .collect();
+    let amp_adjustment = if state.amp_factor.target_ts() > snapshot.unix_timestamp {
+        json!({
+            "target_value": state.amp_factor.target_value().to_string(),
+            "target_ts": state.amp_factor.target_ts(),
+        })
+    } else {
+        Value::Null
+    };
+    let prepared_fee_change = if state.fee_transition_ts != 0 {
+        json!({
+            "lp_fee": state.prepared_lp_fee.get().to_string(),
+            "governance_fee": state.prepared_governance_fee.get().to_string(),
+            "enactable_ts": state.fee_transition_ts,
+        })
+    } else {
+        Value::Null
+    };
+    let prepared_governance_transition = if state.governance_transition_ts != 0 {
+        json!({
+            "governance": state.prepared_governance_key.to_string(),
+            "enactable_ts": state.governance_transition_ts,
+        })
+    } else {
+        Value::Null
+    };
+
+    json!({
+        "pool": snapshot.pool.to_string(),
+        "program_id": program_id.to_string(),
+        "authority": authority.to_string(),
+        "slot": snapshot.slot,
+        "unix_timestamp": snapshot.unix_timestamp,
+        "paused": state.is_paused,
+        "amp_factor": snapshot.amp_factor.to_string(),
+        "amp_adjustment": amp_adjustment,
+        "lp_fee": state.lp_fee.get().to_string(),
+        "governance_fee": state.governance_fee.get().to_string(),
+        "lp_mint": state.lp_mint_key.to_string(),
+        "lp_decimal_equalizer": state.lp_decimal_equalizer,
+        "lp_supply": snapshot.lp_supply.to_string(),
+        "depth": snapshot.depth.to_string(),
+        "previous_depth": state.previous_depth.to_string(),
+        "virtual_price": snapshot.virtual_price().map(|price| price.to_string()),
+        "governance": state.governance_key.to_string(),
+        "governance_fee_account": state.governance_fee_key.to_string(),
+        "prepared_fee_change": prepared_fee_change,
+        "prepared_governance_transition": prepared_governance_transition,
+        "tokens": tokens,
+    })
+}
+
+fn print_table(snapshot: &PoolSnapshot, program_id: &Pubkey, authority: &Pubkey) {
+    let state = &snapshot.state;
+    let now = snapshot.unix_timestamp;
+
+    println!("pool:                   {}", snapshot.pool);
+    println!("program:                {}", program_id);
+    println!("authority:              {}", authority);
+    println!(
+        "slot:                   {} at {}",
+        snapshot.slot,
+        format_timestamp(now, now)
+    );
+    println!("paused:                 {}", state.is_paused);
+    println!("amp factor:             {}", snapshot.amp_factor);
+    if state.amp_factor.target_ts() > now {
+        println!(
+            "amp adjustment:         towards {}, reached at {}",
+            state.amp_factor.target_value(),
+            format_timestamp(state.amp_factor.target_ts(), now)
+        );
+    }
+    println!("lp fee:                 {}", state.lp_fee.get());
+    println!("governance fee:         {}", state.governance_fee.get());
+    println!("lp mint:                {}", state.lp_mint_key);
+    println!("lp supply:              {}", snapshot.lp_supply);
+    println!("depth:                  {}", snapshot.depth);
+    println!("previous depth:         {}", state.previous_depth);
+    match snapshot.virtual_price() {
+        Some(price) => println!("virtual price:          {:.9}", price),
+        None => println!("virtual price:          - (empty pool)"),
+    }
+    println!("governance:             {}", state.governance_key);
+    println!("governance fee account: {}", state.governance_fee_key);
+    if state.fee_transition_ts != 0 {
+        println!(
+            "prepared fee change:    lp fee {} governance fee {}, enactable at {}",
+            state.prepared_lp_fee.get(),
+            state.prepared_governance_fee.get(),
+            format_timestamp(state.fee_transition_ts, now)
+        );
+    }
+    if state.governance_transition_ts != 0 {
+        println!(
+            "prepared transition:    {}, enactable at {}",
+            state.prepared_governance_key,
+            format_timestamp(state.governance_transition_ts, now)
+        );
+    }
+
+    println!();
+    println!("{:>2}  {:<44}  {:<44}  {:>20}", "#", "mint", "account", "balance");
+    for i in 0..TOKEN_COUNT {
+        println!(
+            "{:>2}  {:<44}  {:<44}  {:>20}",
+            i,
+            state.token_mint_keys[i].to_string(),
+            state.token_keys[i].to_string(),
+            snapshot.balances[i]
+        );
+    }
+}
@@ -0,0 +1,108 @@
+//human readable timestamps for timelocks and amp factor adjustments (without pulling in a date time crate)
+
+use solana_program::clock::UnixTimestamp;
+
+/// Formats `ts` as UTC date and time along with how far it lies in the future (or past) relative to `now`
+pub fn format_timestamp(ts: UnixTimestamp, now: UnixTimestamp) -> String {
+    let relative = if ts > now {
+        format!("in {}", format_duration(ts - now))
+    } else if ts < now {
+        format!("{} ago", format_duration(now - ts))
+    } else {
+        "now".to_string()
+    };
+    format!("{} ({})", format_utc(ts), relative)
+}
+
+pub fn format_duration(seconds: UnixTimestamp) -> String {
+    let (days, hours, minutes) = (seconds / 86400, seconds % 86400 / 3600, seconds % 3600 / 60);
+    if days > 0 {
+        format!("{}d {}h {}m", days, hours, minutes)
+    } else if hours > 0 {
+        format!("{}h {}m", hours, minutes)
+    } else if minutes > 0 {
+        format!("{}m {}s", minutes, seconds % 60)
+    } else {
+        format!("{}s", seconds)
+    }
+}
+
+pub fn format_utc(ts: UnixTimestamp) -> String {
+    let (days, seconds) = (ts.div_euclid(86400), ts.rem_euclid(86400));
+    let (year, month, day) = civil_from_days(days);
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02} UTC",
+        year,
+        month,
+        day,
+        seconds / 3600,
+        seconds % 3600 / 60,
+        seconds % 60
+    )
+}
+
+/// Formats seconds since midnight as `HH:MM`, the precision of `parse_time_of_day`
+pub fn format_time_of_day(secs: u32) -> String {
+    format!("{:02}:{:02}", secs / 3600, secs % 3600 / 60)
+}
+
+/// Parses a UTC time of day like `13:30` into seconds since midnight
+pub fn parse_time_of_day(s: &str) -> Result<u32, String> {
+    let invalid = || format!("{} isn't a time of day like 13:30", s);
+    let (hours, minutes) = s.split_once(':').ok_or_else(invalid)?;
+    let hours: u32 = hours.parse().map_err(|_| invalid())?;
+    let minutes: u32 = minutes.parse().map_err(|_| invalid())?;
+    if hours >= 24 || minutes >= 60 {
+        return Err(invalid());
+    }
+    Ok(hours * 3600 + minutes * 60)
+}
+
+//days since 1970-01-01 to (year, month, day) in the proleptic gregorian calendar
+// see http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let day_of_era = z.rem_euclid(146097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn utc() {
+        assert_eq!(format_utc(0), "1970-01-01 00:00:00 UTC");
+        assert_eq!(format_utc(951782400), "2000-02-29 00:00:00 UTC");
+        assert_eq!(format_utc(1639353600 + 3661), "2021-12-13 01:01:01 UTC");
+        assert_eq!(format_utc(-1), "1969-12-31 23:59:59 UTC");
+    }
+
+    #[test]
+    fn time_of_day() {
+        assert_eq!(parse_time_of_day("13:30"), Ok(13 * 3600 + 30 * 60));
+        assert_eq!(parse_time_of_day("00:00"), Ok(0));
+        assert!(parse_time_of_day("24:00").is_err());
+        assert!(parse_time_of_day("9").is_err());
+        assert_eq!(format_time_of_day(9 * 3600 + 5 * 60), "09:05");
+    }
+
+    #[test]
+    fn relative() {
+        assert_eq!(format_timestamp(100, 100), "1970-01-01 00:01:40 UTC (now)");
+        assert_eq!(format_duration(3 * 86400 + 7200 + 60), "3d 2h 1m");
+        assert_eq!(format_duration(61), "1m 1s");
+        assert!(format_timestamp(0, 90).ends_with("(1m 30s ago)"));
+    }
+}
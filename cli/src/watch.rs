@@ -0,0 +1,133 @@
+//follows a pool through websocket account notifications and alerts on threshold breaches
+//
+//every defi instruction writes the pool's state, so each notification triggers a fresh snapshot of the pool. a breached
+// threshold is reported on stderr and ends the command with exit status 2 (any other failure exits with 1), which makes
+// it easy to wrap in alerting scripts
+
+use crate::{parse_percentage, time::format_utc, CliResult, Config};
+use clap::Args;
+use pool::{client::snapshot::PoolSnapshot, decimal::U128};
+use solana_client::{pubsub_client::PubsubClient, rpc_config::RpcAccountInfoConfig};
+use solana_program::pubkey::Pubkey;
+use std::process;
+
+pub const ALERT_EXIT_CODE: i32 = 2;
+
+#[derive(Args)]
+pub struct WatchArgs {
+    /// Address of the pool
+    pool: Pubkey,
+    /// Alert once a token's balance deviates from the average balance by more than this (e.g. 20% or 0.2)
+    #[clap(long, parse(try_from_str = parse_percentage))]
+    imbalance_alert: Option<f64>,
+    /// Alert once the depth drops by more than this below the highest depth seen while watching
+    #[clap(long, parse(try_from_str = parse_percentage))]
+    depth_drop_alert: Option<f64>,
+    /// Websocket URL of the cluster [default: derived from the RPC URL]
+    #[clap(long)]
+    ws_url: Option<String>,
+}
+
+pub fn run(config: Config, args: WatchArgs) -> CliResult<()> {
+    let ws_url = args
+        .ws_url
+        .clone()
+        .unwrap_or_else(|| solana_cli_config::Config::compute_websocket_url(&config.rpc_client.url()));
+    let (pool_client, _) = config.load_pool(args.pool)?;
+    let account_config = RpcAccountInfoConfig {
+        commitment: Some(pool_client.rpc_client.commitment()),
+        ..RpcAccountInfoConfig::default()
+    };
+    //unsubscribes when dropped
+    let (_subscription, notifications) = PubsubClient::account_subscribe(&ws_url, &args.pool, Some(account_config))?;
+
+    let mut thresholds = Thresholds::new(args.imbalance_alert, args.depth_drop_alert);
+    report(&mut thresholds, &pool_client.snapshot()?);
+    for _ in notifications.iter() {
+        report(&mut thresholds, &pool_client.snapshot()?);
+    }
+    Err("the websocket subscription was closed".into())
+}
+
+fn report(thresholds: &mut Thresholds, snapshot: &PoolSnapshot) {
+    let imbalance = snapshot.imbalance();
+    let virtual_price = snapshot
+        .virtual_price()
+        .map_or_else(|| "-".to_string(), |price| format!("{:.9}", price));
+    println!(
+        "slot {} at {}: depth {} lp supply {} virtual price {} imbalance {:.2}%",
+        snapshot.slot,
+        format_utc(snapshot.unix_timestamp),
+        snapshot.depth,
+        snapshot.lp_supply,
+        virtual_price,
+        imbalance * 100.0
+    );
+    if let Some(alert) = thresholds.check(snapshot.depth, imbalance) {
+        eprintln!("alert: {}", alert);
+        process::exit(ALERT_EXIT_CODE);
+    }
+}
+
+struct Thresholds {
+    imbalance: Option<f64>,
+    depth_drop: Option<f64>,
+    max_depth: U128,
+}
+
+impl Thresholds {
+    fn new(imbalance: Option<f64>, depth_drop: Option<f64>) -> Self {
+        Self {
+            imbalance,
+            depth_drop,
+            max_depth: U128::zero(),
+        }
+    }
+
+    fn check(&mut self, depth: U128, imbalance: f64) -> Option<String> {
+        self.max_depth = self.max_depth.max(depth);
+        if let Some(threshold) = self.imbalance {
+            if imbalance > threshold {
+                return Some(format!(
+                    "imbalance of {:.2}% exceeds {:.2}%",
+                    imbalance * 100.0,
+                    threshold * 100.0
+                ));
+            }
+        }
+        if let Some(threshold) = self.depth_drop {
+            if !self.max_depth.is_zero() {
+                let drop = 1.0 - depth.as_u128() as f64 / self.max_depth.as_u128() as f64;
+                if drop > threshold {
+                    return Some(format!(
+                        "depth dropped by {:.2}% (from {} to {}), more than {:.2}%",
+                        drop * 100.0,
+                        self.max_depth,
+                        depth,
+                        threshold * 100.0
+                    ));
+                }
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn thresholds() {
+        let mut thresholds = Thresholds::new(Some(0.2), Some(0.05));
+        assert!(thresholds.check(U128::from(1000), 0.1).is_none());
+        assert!(thresholds.check(U128::from(1000), 0.21).is_some());
+        assert!(thresholds.check(U128::from(2000), 0.0).is_none());
+        assert!(thresholds.check(U128::from(1901), 0.0).is_none());
+        assert!(thresholds.check(U128::from(1899), 0.0).is_some());
+
+        let mut disabled = Thresholds::new(None, None);
+        assert!(disabled.check(U128::from(1000), 1.0).is_none());
+        assert!(disabled.check(U128::from(0), 1.0).is_none());
+    }
+}
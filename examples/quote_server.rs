@@ -0,0 +1,245 @@
+//a quote server built on the client module: serves quotes and route plans over JSON-RPC (HTTP POST) from a
+// `RouteCache` that follows the pools of a program through websocket subscriptions, so answering takes no requests
+//
+//    cargo run --example quote_server --features client -- <rpc url> <websocket url> <program id> [bind address]
+//
+//    curl -s localhost:8080 -d '{"jsonrpc":"2.0","id":1,"method":"getQuote",
+//        "params":{"inputMint":"...","outputMint":"...","amount":1000000}}'
+//
+//methods:
+// getQuote     {inputMint, outputMint, amount} -> the best route (see `Router::find_best_route`) and its price impact
+// getRoutePlan {inputMint, outputMint, amount, slippageBps = 50, validForSlots = 150} -> a `RoutePlan` for an executor
+//
+//requests are served one at a time on a single thread, which is plenty for a cache lookup per request. a deployment
+// would put this behind a proper http server and reverse proxy; the point here is how to embed the cache
+
+use pool::client::{
+    route_cache::RouteCache,
+    route_plan::RoutePlan,
+    router::{Route, Router},
+    ClientError,
+};
+use serde_json::{json, Value};
+use solana_client::rpc_client::RpcClient;
+use solana_program::pubkey::Pubkey;
+use solana_sdk::commitment_config::CommitmentConfig;
+use std::{
+    env,
+    io::{self, BufRead, BufReader, Read, Write},
+    net::{TcpListener, TcpStream},
+    process,
+    str::FromStr,
+};
+
+const DEFAULT_BIND_ADDRESS: &str = "127.0.0.1:8080";
+const DEFAULT_SLIPPAGE_BPS: u64 = 50;
+//about a minute
+const DEFAULT_VALID_FOR_SLOTS: u64 = 150;
+const MAX_BODY_LENGTH: usize = 64 * 1024;
+
+//json-rpc error codes
+const PARSE_ERROR: i64 = -32700;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+const NO_ROUTE: i64 = -32000;
+
+struct RpcError {
+    code: i64,
+    message: String,
+}
+
+impl RpcError {
+    fn invalid_params(message: &str) -> Self {
+        Self {
+            code: INVALID_PARAMS,
+            message: message.to_string(),
+        }
+    }
+}
+
+impl From<ClientError> for RpcError {
+    fn from(error: ClientError) -> Self {
+        Self {
+            code: NO_ROUTE,
+            message: error.to_string(),
+        }
+    }
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    if args.len() < 3 {
+        eprintln!("usage: quote_server <rpc url> <websocket url> <program id> [bind address]");
+        process::exit(2);
+    }
+    let program_id = Pubkey::from_str(&args[2]).unwrap_or_else(|_| {
+        eprintln!("error: invalid program id {}", args[2]);
+        process::exit(2);
+    });
+    let bind_address = args.get(3).map_or(DEFAULT_BIND_ADDRESS, String::as_str);
+    if let Err(error) = run(&args[0], &args[1], &program_id, bind_address) {
+        eprintln!("error: {}", error);
+        process::exit(1);
+    }
+}
+
+fn run(rpc_url: &str, ws_url: &str, program_id: &Pubkey, bind_address: &str) -> Result<(), ClientError> {
+    let rpc_client = RpcClient::new_with_commitment(rpc_url.to_string(), CommitmentConfig::confirmed());
+    let mut cache = RouteCache::subscribe(&rpc_client, ws_url, program_id)?;
+    println!(
+        "following {} pools of {}, listening on {}",
+        cache.router().pools().len(),
+        program_id,
+        bind_address
+    );
+    let listener = TcpListener::bind(bind_address)?;
+    for stream in listener.incoming() {
+        //a broken connection only concerns its own request
+        if let Err(error) = stream.and_then(|stream| serve(&mut cache, stream)) {
+            eprintln!("connection error: {}", error);
+        }
+    }
+    Ok(())
+}
+
+fn serve(cache: &mut RouteCache, mut stream: TcpStream) -> io::Result<()> {
+    let body = match read_body(&mut stream)? {
+        Some(body) => body,
+        None => return respond(&mut stream, "400 Bad Request", &json!({})),
+    };
+    let response = match serde_json::from_slice::<Value>(&body) {
+        Ok(request) => {
+            let id = request.get("id").cloned().unwrap_or(Value::Null);
+            let method = request.get("method").and_then(Value::as_str).unwrap_or_default();
+            let params = request.get("params").cloned().unwrap_or(Value::Null);
+            match handle(cache, method, &params) {
+                Ok(result) => json!({"jsonrpc": "2.0", "id": id, "result": result}),
+                Err(error) => json!({
+                    "jsonrpc": "2.0",
+                    "id": id,
+                    "error": {"code": error.code, "message": error.message},
+                }),
+            }
+        }
+        Err(error) => json!({
+            "jsonrpc": "2.0",
+            "id": Value::Null,
+            "error": {"code": PARSE_ERROR, "message": error.to_string()},
+        }),
+    };
+    respond(&mut stream, "200 OK", &response)
+}
+
+//the body of a POST request, `None` for anything else
+fn read_body(stream: &mut TcpStream) -> io::Result<Option<Vec<u8>>> {
+    let mut reader = BufReader::new(stream);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    let mut content_length = None;
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header)? == 0 || header.trim().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.eq_ignore_ascii_case("content-length") {
+                content_length = value.trim().parse::<usize>().ok();
+            }
+        }
+    }
+    let content_length = match content_length {
+        Some(content_length) if request_line.starts_with("POST ") && content_length <= MAX_BODY_LENGTH => {
+            content_length
+        }
+        _ => return Ok(None),
+    };
+    let mut body = vec![0; content_length];
+    reader.read_exact(&mut body)?;
+    Ok(Some(body))
+}
+
+fn respond(stream: &mut TcpStream, status: &str, body: &Value) -> io::Result<()> {
+    let body = body.to_string();
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    )
+}
+
+fn handle(cache: &mut RouteCache, method: &str, params: &Value) -> Result<Value, RpcError> {
+    //whatever changed since the last request
+    cache.refresh()?;
+    match method {
+        "getQuote" => {
+            let route = find_route(cache, params)?;
+            let price_impact = cache.router().price_impact(&route)?;
+            Ok(json!({
+                "inAmount": route.in_amount(),
+                "outAmount": route.out_amount(),
+                "priceImpact": price_impact,
+                "hops": route.hops.iter().map(|hop| json!({
+                    "pool": hop.leg.pool.to_string(),
+                    "inputMint": hop.input_mint.to_string(),
+                    "outputMint": hop.output_mint.to_string(),
+                    "inAmount": hop.in_amount,
+                    "outAmount": hop.out_amount,
+                })).collect::<Vec<_>>(),
+            }))
+        }
+        "getRoutePlan" => {
+            let route = find_route(cache, params)?;
+            let slippage_bps = optional_u64(params, "slippageBps", DEFAULT_SLIPPAGE_BPS)?;
+            let valid_for_slots = optional_u64(params, "validForSlots", DEFAULT_VALID_FOR_SLOTS)?;
+            if slippage_bps > 10_000 {
+                return Err(RpcError::invalid_params("slippageBps must be at most 10000"));
+            }
+            let minimum_output_amount = (route.out_amount() as u128 * (10_000 - slippage_bps) as u128 / 10_000) as u64;
+            let expiry_slot = quoted_slot(cache.router(), &route) + valid_for_slots;
+            let plan = RoutePlan::new(&route, minimum_output_amount, expiry_slot);
+            Ok(serde_json::to_value(&plan).unwrap())
+        }
+        _ => Err(RpcError {
+            code: METHOD_NOT_FOUND,
+            message: format!("unknown method {}", method),
+        }),
+    }
+}
+
+fn find_route(cache: &mut RouteCache, params: &Value) -> Result<Route, RpcError> {
+    let mint = |name: &str| {
+        params
+            .get(name)
+            .and_then(Value::as_str)
+            .and_then(|mint| Pubkey::from_str(mint).ok())
+            .ok_or_else(|| RpcError::invalid_params(&format!("{} must be a base58 public key", name)))
+    };
+    let (input_mint, output_mint) = (mint("inputMint")?, mint("outputMint")?);
+    let amount = params
+        .get("amount")
+        .and_then(Value::as_u64)
+        .ok_or_else(|| RpcError::invalid_params("amount must be an integer"))?;
+    Ok(cache.find_best_route(&input_mint, &output_mint, amount)?)
+}
+
+fn optional_u64(params: &Value, name: &str, default: u64) -> Result<u64, RpcError> {
+    match params.get(name) {
+        None => Ok(default),
+        Some(value) => value
+            .as_u64()
+            .ok_or_else(|| RpcError::invalid_params(&format!("{} must be an integer", name))),
+    }
+}
+
+//the slot of the clock the route's pools were last updated with
+fn quoted_slot(router: &Router, route: &Route) -> u64 {
+    router
+        .pools()
+        .iter()
+        .filter(|amm| route.hops.iter().any(|hop| hop.leg.pool == amm.key()))
+        .filter_map(|amm| amm.snapshot().map(|snapshot| snapshot.slot))
+        .max()
+        .unwrap_or_default()
+}
@@ -422,6 +422,7 @@ async fn run_fuzz_instructions<const TOKEN_COUNT: usize>(
             let kp_vec = vec![];
             (ix_vec, kp_vec)
         }
+        DeFiInstruction::Simulate(_) => (vec![], vec![]),
     }
 }
 
@@ -14,6 +14,9 @@ pub const MAX_AMP_VALUE: ValueT = ValueT::const_from(10u64.pow(6));
 pub const MIN_ADJUSTMENT_WINDOW: TimestampT = 60 * 60 * 24;
 pub const MAX_RELATIVE_ADJUSTMENT: ValueT = ValueT::const_from(10);
 
+/// How long the amp damping takes to fully engage or release
+pub const DAMPING_WINDOW: TimestampT = 60 * 60;
+
 #[derive(BorshSerialize, BorshDeserialize, BorshSchema, Debug)]
 pub struct AmpFactor {
     //invariants:
@@ -28,7 +31,12 @@ pub struct AmpFactor {
 
 impl Default for AmpFactor {
     fn default() -> Self {
-        AmpFactor::new(MIN_AMP_VALUE).unwrap()
+        AmpFactor {
+            initial_value: MIN_AMP_VALUE,
+            initial_ts: 0,
+            target_value: MIN_AMP_VALUE,
+            target_ts: 0,
+        }
     }
 }
 
@@ -51,9 +59,10 @@ impl AmpFactor {
             //check if we are inside an adjustment window
             //not in an adjustment window
             self.target_value
+        } else if current_ts <= self.initial_ts {
+            //adjustments start at the time they are set, so this is only ever their start
+            self.initial_value
         } else {
-            assert!(current_ts >= self.initial_ts);
-
             //we are within an adjustment window and hence need to interpolate the amp factor
             //
             //mathematically speaking we ought to use exponential interpolation
@@ -82,6 +91,16 @@ impl AmpFactor {
         }
     }
 
+    /// The value that the amp factor is adjusting towards (or has settled on)
+    pub fn target_value(&self) -> ValueT {
+        self.target_value
+    }
+
+    /// When the current adjustment completes (in the past if there is none in progress)
+    pub fn target_ts(&self) -> TimestampT {
+        self.target_ts
+    }
+
     pub fn set_target(
         &mut self,
         current_ts: TimestampT,
@@ -112,6 +131,155 @@ impl AmpFactor {
     }
 }
 
+/// Scales the amp factor down while the pool's oracles report a sustained depeg (as tracked by `CheckPeg`), which
+/// flattens the curve so the pool gives up less of its other tokens for the depegged one. Damping starts once an oracle
+/// price has been outside of the trigger band for `delay` seconds and only ends once all prices are back within the
+/// narrower release band, so prices hovering around a band don't make it flap. Like amp factor adjustments, the scale
+/// moves gradually (over `DAMPING_WINDOW`)
+#[derive(BorshSerialize, BorshDeserialize, BorshSchema, Debug)]
+pub struct AmpDamping {
+    //invariants:
+    // 0 < factor <= 1 (1 turns damping off)
+    // release_band <= trigger_band
+    factor: ValueT,
+    trigger_band: ValueT,
+    release_band: ValueT,
+    delay: TimestampT,
+    //when an oracle price first left the trigger band, 0 while all are within it
+    depeg_ts: TimestampT,
+    is_damping: bool,
+    //the scale moves from initial_scale towards factor (while damping) or 1 (otherwise) starting at switch_ts
+    initial_scale: ValueT,
+    switch_ts: TimestampT,
+}
+
+impl Default for AmpDamping {
+    fn default() -> Self {
+        AmpDamping {
+            factor: ValueT::from(1),
+            trigger_band: ValueT::from(0),
+            release_band: ValueT::from(0),
+            delay: 0,
+            depeg_ts: 0,
+            is_damping: false,
+            initial_scale: ValueT::from(1),
+            switch_ts: 0,
+        }
+    }
+}
+
+impl AmpDamping {
+    pub fn is_enabled(&self) -> bool {
+        self.factor < ValueT::from(1)
+    }
+
+    /// Whether the damping is engaged (though it might still be ramping up)
+    pub fn is_damping(&self) -> bool {
+        self.is_damping
+    }
+
+    pub fn factor(&self) -> ValueT {
+        self.factor
+    }
+
+    pub fn trigger_band(&self) -> ValueT {
+        self.trigger_band
+    }
+
+    pub fn release_band(&self) -> ValueT {
+        self.release_band
+    }
+
+    pub fn delay(&self) -> TimestampT {
+        self.delay
+    }
+
+    pub fn set_params(
+        &mut self,
+        current_ts: TimestampT,
+        factor: ValueT,
+        trigger_band: ValueT,
+        release_band: ValueT,
+        delay: TimestampT,
+    ) -> Result<(), PoolError> {
+        if factor == ValueT::from(0)
+            || factor > ValueT::from(1)
+            || release_band > trigger_band
+            || (factor < ValueT::from(1) && trigger_band == ValueT::from(0))
+            || delay < 0
+        {
+            return Err(PoolError::InvalidAmpFactorValue);
+        }
+
+        //the scale continues from where it is towards the new factor (or 1 once damping is off)
+        self.initial_scale = self.scale(current_ts);
+        self.switch_ts = current_ts;
+        self.factor = factor;
+        self.trigger_band = trigger_band;
+        self.release_band = release_band;
+        self.delay = delay;
+        if !self.is_enabled() {
+            self.depeg_ts = 0;
+            self.is_damping = false;
+        }
+
+        Ok(())
+    }
+
+    /// Advances the damping given whether any oracle price is outside of the trigger band and whether any is outside of
+    /// the release band at `current_ts`
+    pub fn update(&mut self, beyond_trigger: bool, beyond_release: bool, current_ts: TimestampT) {
+        if !beyond_trigger {
+            self.depeg_ts = 0;
+        } else if self.depeg_ts == 0 {
+            self.depeg_ts = current_ts;
+        }
+
+        if !self.is_damping && beyond_trigger && self.is_enabled() && current_ts - self.depeg_ts >= self.delay {
+            self.switch(true, current_ts);
+        } else if self.is_damping && !beyond_release {
+            self.switch(false, current_ts);
+        }
+    }
+
+    /// `amp_factor` (i.e. the value of `AmpFactor::get`) scaled down by the damping at `current_ts`
+    pub fn apply(&self, amp_factor: ValueT, current_ts: TimestampT) -> ValueT {
+        let scale = self.scale(current_ts);
+        if scale == ValueT::from(1) {
+            return amp_factor;
+        }
+        //never below the smallest valid amp factor, in particular it can't be 0
+        let damped = amp_factor * scale;
+        if damped < MIN_AMP_VALUE {
+            MIN_AMP_VALUE
+        } else {
+            damped
+        }
+    }
+
+    fn switch(&mut self, is_damping: bool, current_ts: TimestampT) {
+        self.initial_scale = self.scale(current_ts);
+        self.is_damping = is_damping;
+        self.switch_ts = current_ts;
+    }
+
+    fn scale(&self, current_ts: TimestampT) -> ValueT {
+        let target_scale = if self.is_damping { self.factor } else { ValueT::from(1) };
+        let elapsed = current_ts - self.switch_ts;
+        if elapsed >= DAMPING_WINDOW || self.initial_scale == target_scale {
+            return target_scale;
+        }
+
+        //linear interpolation, as in AmpFactor::get
+        let progress = ValueT::from(elapsed.max(0) as u64) / ValueT::from(DAMPING_WINDOW as u64);
+        if target_scale > self.initial_scale {
+            self.initial_scale + (target_scale - self.initial_scale) * progress
+        } else {
+            self.initial_scale - (self.initial_scale - target_scale) * progress
+        }
+    }
+}
+
 #[cfg(all(test, not(feature = "test-bpf")))]
 mod tests {
     use super::*;
@@ -177,4 +345,70 @@ mod tests {
         let mut amp = AmpFactor::new(ValueT::from(10000)).unwrap();
         amp.set_target(20000, ValueT::from(20000), 50000).unwrap();
     }
+
+    #[test]
+    fn damping_hysteresis() {
+        let amp = ValueT::from(1000);
+        let mut damping = AmpDamping::default();
+        assert!(!damping.is_enabled());
+        assert_eq!(damping.apply(amp, 100), amp);
+
+        let (half, two_percent, one_percent) = (new_u64(5, 1), new_u64(2, 2), new_u64(1, 2));
+        assert!(damping
+            .set_params(100, ValueT::from(0), two_percent, one_percent, 60)
+            .is_err());
+        assert!(damping
+            .set_params(100, ValueT::from(2), two_percent, one_percent, 60)
+            .is_err());
+        assert!(damping.set_params(100, half, one_percent, two_percent, 60).is_err());
+        assert!(damping
+            .set_params(100, half, ValueT::from(0), ValueT::from(0), 60)
+            .is_err());
+        damping.set_params(100, half, two_percent, one_percent, 60).unwrap();
+        assert_eq!(damping.apply(amp, 100), amp);
+
+        //the depeg has to last for the delay
+        damping.update(true, true, 1000);
+        damping.update(true, true, 1059);
+        assert!(!damping.is_damping());
+        damping.update(false, true, 1060);
+        damping.update(true, true, 1100);
+        damping.update(true, true, 1159);
+        assert!(!damping.is_damping());
+        damping.update(true, true, 1160);
+        assert!(damping.is_damping());
+
+        //the scale ramps down over the damping window
+        assert_eq!(damping.apply(amp, 1160), amp);
+        assert_eq!(damping.apply(amp, 1160 + DAMPING_WINDOW / 2), ValueT::from(750));
+        assert_eq!(damping.apply(amp, 1160 + DAMPING_WINDOW), ValueT::from(500));
+
+        //back within the trigger but not the release band keeps damping
+        let damped_ts = 1160 + DAMPING_WINDOW;
+        damping.update(false, true, damped_ts);
+        assert!(damping.is_damping());
+        damping.update(false, false, damped_ts);
+        assert!(!damping.is_damping());
+        assert_eq!(damping.apply(amp, damped_ts + DAMPING_WINDOW / 2), ValueT::from(750));
+        assert_eq!(damping.apply(amp, damped_ts + DAMPING_WINDOW), amp);
+
+        //never below the smallest amp factor
+        assert_eq!(damping.apply(MIN_AMP_VALUE, 1160 + DAMPING_WINDOW), MIN_AMP_VALUE);
+
+        //turning it off releases the damping gradually
+        damping.update(true, true, 10_000);
+        damping.update(true, true, 10_060);
+        damping
+            .set_params(
+                10_060 + DAMPING_WINDOW,
+                ValueT::from(1),
+                ValueT::from(0),
+                ValueT::from(0),
+                0,
+            )
+            .unwrap();
+        assert!(!damping.is_damping());
+        assert_eq!(damping.apply(amp, 10_060 + DAMPING_WINDOW), ValueT::from(500));
+        assert_eq!(damping.apply(amp, 10_060 + 2 * DAMPING_WINDOW), amp);
+    }
 }
@@ -0,0 +1,325 @@
+//a pool behind the interface that swap aggregators list AMMs with (the `Amm` trait of `jupiter-amm-interface`): the
+// aggregator fetches the accounts the AMM asks for, feeds them back through `update` and then quotes and builds swaps
+// without the AMM making any requests of its own
+//
+//the interface crate requires a newer solana sdk than the pinned 1.8 release, so the trait itself isn't implemented
+// here. `PoolAmm` mirrors its methods one to one, implementing it once the sdk is upgraded only takes forwarding
+// calls and converting between the parameter and quote types
+
+use super::{snapshot::PoolSnapshot, ClientError, ClientResult};
+use crate::{
+    error::PoolError,
+    instruction::{create_defi_ix, find_user_gate_account, DeFiInstruction, RouteLeg},
+    state::PoolState,
+    token_2022, validation, TOKEN_COUNT,
+};
+use borsh::BorshDeserialize;
+use solana_program::{
+    clock::Clock,
+    instruction::{AccountMeta, Instruction},
+    program_error::ProgramError,
+    pubkey::Pubkey,
+    sysvar,
+};
+use solana_sdk::account::{from_account, Account};
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SwapMode {
+    ExactIn,
+    ExactOut,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AmmQuoteParams {
+    pub input_mint: Pubkey,
+    pub output_mint: Pubkey,
+    /// The input amount for `SwapMode::ExactIn`, the output amount for `SwapMode::ExactOut`
+    pub amount: u64,
+    pub swap_mode: SwapMode,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AmmQuote {
+    pub in_amount: u64,
+    pub out_amount: u64,
+    /// The difference to a swap without fees, paid in `fee_mint`: the output token for exact input swaps, the input
+    /// token for exact output swaps
+    pub fee_amount: u64,
+    pub fee_mint: Pubkey,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AmmSwapParams {
+    pub input_mint: Pubkey,
+    pub output_mint: Pubkey,
+    /// The exact input amount for `SwapMode::ExactIn`, the maximum input amount for `SwapMode::ExactOut`
+    pub in_amount: u64,
+    /// The minimum output amount for `SwapMode::ExactIn`, the exact output amount for `SwapMode::ExactOut`
+    pub out_amount: u64,
+    pub swap_mode: SwapMode,
+    pub source_token_account: Pubkey,
+    pub destination_token_account: Pubkey,
+    pub token_transfer_authority: Pubkey,
+}
+
+pub struct PoolAmm {
+    program_id: Pubkey,
+    pool: Pubkey,
+    state: PoolState<TOKEN_COUNT>,
+    //`None` until the first `update`
+    snapshot: Option<PoolSnapshot>,
+}
+
+impl PoolAmm {
+    /// Decodes the pool account, the program id is taken from its owner
+    pub fn from_keyed_account(pool: &Pubkey, account: &Account) -> ClientResult<Self> {
+        let state = PoolState::<TOKEN_COUNT>::deserialize(&mut account.data.as_slice())
+            .ok()
+            .filter(|state| state.is_initialized())
+            .ok_or(ClientError::InvalidAccountData(*pool))?;
+        Ok(Self {
+            program_id: account.owner,
+            pool: *pool,
+            state,
+            snapshot: None,
+        })
+    }
+
+    pub fn label(&self) -> String {
+        "Swim".to_string()
+    }
+
+    pub fn program_id(&self) -> Pubkey {
+        self.program_id
+    }
+
+    pub fn key(&self) -> Pubkey {
+        self.pool
+    }
+
+    pub fn get_reserve_mints(&self) -> Vec<Pubkey> {
+        self.current_state().token_mint_keys.to_vec()
+    }
+
+    /// The pool account, the clock, the lp mint, the pool's token accounts and its rate and oracle accounts (see
+    /// `PoolSnapshot::account_keys`), which change along with the pool's state
+    pub fn get_accounts_to_update(&self) -> Vec<Pubkey> {
+        PoolSnapshot::account_keys(&self.pool, self.current_state())
+    }
+
+    /// Takes the accounts of `get_accounts_to_update` from `accounts`
+    pub fn update(&mut self, accounts: &HashMap<Pubkey, Account>) -> ClientResult<()> {
+        let accounts = self
+            .get_accounts_to_update()
+            .iter()
+            .map(|key| accounts.get(key).cloned().ok_or(ClientError::AccountNotFound(*key)))
+            .collect::<ClientResult<Vec<_>>>()?;
+        let slot = from_account::<Clock, _>(&accounts[1])
+            .ok_or(ClientError::InvalidAccountData(sysvar::clock::id()))?
+            .slot;
+        self.snapshot = Some(PoolSnapshot::from_accounts(
+            &self.pool,
+            self.current_state(),
+            slot,
+            &accounts,
+        )?);
+        Ok(())
+    }
+
+    /// Quotes the swap like the program executes it at the slot of the last `update`
+    pub fn quote(&self, params: &AmmQuoteParams) -> ClientResult<AmmQuote> {
+        let snapshot = self.snapshot.as_ref().ok_or(ClientError::AccountNotFound(self.pool))?;
+        let (input_index, output_index) = self.token_indices(&params.input_mint, &params.output_mint)?;
+        if params.amount == 0 {
+            return Err(ProgramError::InvalidInstructionData.into());
+        }
+        let state = &snapshot.state;
+        if state.is_paused {
+            return Err(PoolError::PoolIsPaused.into());
+        }
+        if state.is_sunset() {
+            return Err(PoolError::PoolIsSunset.into());
+        }
+        if snapshot.lp_supply == 0 {
+            return Err(PoolError::PoolIsEmpty.into());
+        }
+        if state.depegged_tokens[input_index] || state.depegged_tokens[output_index] {
+            return Err(PoolError::TokenDepegged.into());
+        }
+        if state.token_flags[input_index].input_disabled {
+            return Err(PoolError::TokenInputDisabled.into());
+        }
+        if state.token_flags[output_index].output_disabled {
+            return Err(PoolError::TokenOutputDisabled.into());
+        }
+        if !state.token_participation[input_index].allows_input(false) {
+            return Err(validation::participation_error(state.token_participation[input_index]).into());
+        }
+        if !state.token_participation[output_index].allows_output() {
+            return Err(validation::participation_error(state.token_participation[output_index]).into());
+        }
+
+        let quoter = snapshot.swap_quoter()?;
+        let fee_free = quoter.clone().without_fees();
+        let mut amounts = [0; TOKEN_COUNT];
+        match params.swap_mode {
+            SwapMode::ExactIn => {
+                amounts[input_index] = params.amount;
+                let out_amount = quoter.swap_exact_input(&amounts, output_index)?.amount;
+                //the tokens deployed to a yield adapter are priced in but can't be paid out (see check_liquidity)
+                if out_amount > snapshot.balances[output_index] {
+                    return Err(PoolError::YieldBufferShort.into());
+                }
+                let fee_free_amount = fee_free.swap_exact_input(&amounts, output_index)?.amount;
+                Ok(AmmQuote {
+                    in_amount: params.amount,
+                    out_amount,
+                    fee_amount: fee_free_amount.saturating_sub(out_amount),
+                    fee_mint: params.output_mint,
+                })
+            }
+            SwapMode::ExactOut => {
+                if params.amount >= state.total_balances(&snapshot.balances)[output_index] {
+                    return Err(ProgramError::InvalidInstructionData.into());
+                }
+                if params.amount > snapshot.balances[output_index] {
+                    return Err(PoolError::YieldBufferShort.into());
+                }
+                amounts[output_index] = params.amount;
+                let in_amount = quoter.swap_exact_output(input_index, &amounts)?.amount;
+                let fee_free_amount = fee_free.swap_exact_output(input_index, &amounts)?.amount;
+                Ok(AmmQuote {
+                    in_amount,
+                    out_amount: params.amount,
+                    fee_amount: in_amount.saturating_sub(fee_free_amount),
+                    fee_mint: params.input_mint,
+                })
+            }
+        }
+    }
+
+    /// The pool's swap instruction, the source token account fills the user token account slots of the tokens that
+    /// aren't swapped (the program doesn't touch those)
+    pub fn get_swap_ix(&self, params: &AmmSwapParams) -> ClientResult<Instruction> {
+        let (input_index, output_index) = self.token_indices(&params.input_mint, &params.output_mint)?;
+        let mut amounts = [0; TOKEN_COUNT];
+        let defi_instruction = match params.swap_mode {
+            SwapMode::ExactIn => {
+                amounts[input_index] = params.in_amount;
+                DeFiInstruction::SwapExactInput {
+                    exact_input_amounts: amounts,
+                    output_token_index: output_index as u8,
+                    minimum_output_amount: params.out_amount,
+                }
+            }
+            SwapMode::ExactOut => {
+                amounts[output_index] = params.out_amount;
+                DeFiInstruction::SwapExactOutput {
+                    maximum_input_amount: params.in_amount,
+                    input_token_index: input_index as u8,
+                    exact_output_amounts: amounts,
+                }
+            }
+        };
+        let mut user_token_accounts = [params.source_token_account; TOKEN_COUNT];
+        user_token_accounts[output_index] = params.destination_token_account;
+
+        let state = self.current_state();
+        let mut instruction = create_defi_ix(
+            defi_instruction,
+            &self.program_id,
+            &self.pool,
+            &self.authority()?,
+            &state.token_keys,
+            &state.lp_mint_key,
+            &state.governance_fee_key,
+            &params.token_transfer_authority,
+            &user_token_accounts,
+            &spl_token::id(),
+            None,
+        )?;
+        instruction.accounts.extend(
+            self.swap_accounts()
+                .iter()
+                .map(|key| AccountMeta::new_readonly(*key, false)),
+        );
+        if let Some(gate_account) = find_user_gate_account(
+            &self.program_id,
+            &self.pool,
+            &state.user_gate,
+            &params.token_transfer_authority,
+        ) {
+            instruction
+                .accounts
+                .push(AccountMeta::new_readonly(gate_account, false));
+        }
+        Ok(instruction)
+    }
+
+    /// The state of the pool as of the last `update`
+    pub fn snapshot(&self) -> Option<&PoolSnapshot> {
+        self.snapshot.as_ref()
+    }
+
+    /// The pool's accounts for a leg of a `RoutedSwap`. Fails for pools that gate their users, whose swaps need the
+    /// user's gate account, so the router doesn't route through them
+    pub fn route_leg(&self) -> ClientResult<RouteLeg<TOKEN_COUNT>> {
+        let state = self.current_state();
+        if state.gates_users() {
+            return Err(PoolError::UserNotAllowed.into());
+        }
+        Ok(RouteLeg {
+            pool: self.pool,
+            authority: self.authority()?,
+            token_accounts: state.token_keys,
+            lp_mint: state.lp_mint_key,
+            governance_fee_account: state.governance_fee_key,
+            swap_accounts: self.swap_accounts(),
+        })
+    }
+
+    fn authority(&self) -> ClientResult<Pubkey> {
+        Pubkey::create_program_address(
+            &[&self.pool.to_bytes()[..32], &[self.current_state().nonce]],
+            &self.program_id,
+        )
+        .map_err(|_| ClientError::InvalidAccountData(self.pool))
+    }
+
+    //the accounts that swaps pass after the user's accounts (see `RouteLeg::swap_accounts`)
+    fn swap_accounts(&self) -> Vec<Pubkey> {
+        let state = self.current_state();
+        let mut swap_accounts = vec![];
+        if state.lp_non_transferable {
+            swap_accounts.push(token_2022::id());
+        }
+        if state.is_oracle_guarded() {
+            swap_accounts.extend_from_slice(&state.oracle_keys);
+        }
+        swap_accounts.extend(state.rate_account_keys());
+        if state.restricts_callers() {
+            swap_accounts.push(sysvar::instructions::id());
+        }
+        swap_accounts
+    }
+
+    fn current_state(&self) -> &PoolState<TOKEN_COUNT> {
+        self.snapshot.as_ref().map_or(&self.state, |snapshot| &snapshot.state)
+    }
+
+    fn token_indices(&self, input_mint: &Pubkey, output_mint: &Pubkey) -> ClientResult<(usize, usize)> {
+        let mints = &self.current_state().token_mint_keys;
+        let index = |mint: &Pubkey| {
+            mints
+                .iter()
+                .position(|pool_mint| pool_mint == mint)
+                .ok_or(ClientError::Instruction(ProgramError::InvalidArgument))
+        };
+        let (input_index, output_index) = (index(input_mint)?, index(output_mint)?);
+        if input_index == output_index {
+            return Err(ProgramError::InvalidArgument.into());
+        }
+        Ok((input_index, output_index))
+    }
+}
@@ -0,0 +1,121 @@
+//finds swaps that profit from a pool's prices deviating from oracle prices, which is what keeps pools pegged
+//
+//for every pair of tokens with an oracle price, the input amount that makes the most profit at the oracle prices is
+// searched for with exact quotes (see `PoolAmm::quote`). at that amount the pool's marginal price is back at the
+// oracle price (up to fees), so executing an opportunity pushes the pool back towards parity
+
+use super::{
+    amm::PoolAmm,
+    router::{quote_hop, Route},
+};
+use solana_program::pubkey::Pubkey;
+use std::collections::HashMap;
+
+/// A profitable swap in a single pool, `route.out_amount()` is worth `profit_bps` more than `route.in_amount()` at the
+/// oracle prices
+#[derive(Debug, Clone, PartialEq)]
+pub struct ArbOpportunity {
+    pub route: Route,
+    /// The value of the output divided by the value of the input minus one, in basis points
+    pub profit_bps: u64,
+    /// The value of the output minus the input amount, in the input token
+    pub profit_amount: u64,
+    //in equalized units of the oracle prices, to compare opportunities in different tokens
+    profit: f64,
+}
+
+/// The most profitable swap of each pool that makes at least `min_profit_bps`, most profitable first.
+/// `oracle_prices` are the prices of a whole token by mint, in any common unit (e.g. USD); pairs with a token without
+/// a price are skipped. The opportunities are quoted independently of one another, so executing one changes the
+/// others of the same tokens
+pub fn find_opportunities(
+    pools: &[PoolAmm],
+    oracle_prices: &HashMap<Pubkey, f64>,
+    min_profit_bps: u64,
+) -> Vec<ArbOpportunity> {
+    let mut opportunities: Vec<ArbOpportunity> = pools
+        .iter()
+        .filter_map(|amm| {
+            let mints = amm.get_reserve_mints();
+            let mut best: Option<ArbOpportunity> = None;
+            for input_index in 0..mints.len() {
+                for output_index in (0..mints.len()).filter(|output_index| *output_index != input_index) {
+                    let opportunity = match find_pair_opportunity(amm, oracle_prices, input_index, output_index) {
+                        Some(opportunity) => opportunity,
+                        None => continue,
+                    };
+                    let is_better = match &best {
+                        Some(best) => opportunity.profit > best.profit,
+                        None => true,
+                    };
+                    if opportunity.profit_bps >= min_profit_bps && is_better {
+                        best = Some(opportunity);
+                    }
+                }
+            }
+            best
+        })
+        .collect();
+    opportunities.sort_by(|a, b| b.profit.partial_cmp(&a.profit).unwrap());
+    opportunities
+}
+
+fn find_pair_opportunity(
+    amm: &PoolAmm,
+    oracle_prices: &HashMap<Pubkey, f64>,
+    input_index: usize,
+    output_index: usize,
+) -> Option<ArbOpportunity> {
+    let snapshot = amm.snapshot()?;
+    let mints = &snapshot.state.token_mint_keys;
+    let (input_mint, output_mint) = (&mints[input_index], &mints[output_index]);
+    //the value of a single unit of each token, equalizing scales all tokens to the same decimals
+    let unit_value = |index: usize| {
+        let price = oracle_prices.get(&mints[index]).filter(|price| **price > 0.0)?;
+        Some(price * 10f64.powi(snapshot.state.token_decimal_equalizers[index] as i32))
+    };
+    let (input_value, output_value) = (unit_value(input_index)?, unit_value(output_index)?);
+    let profit = |amount: u64| {
+        quote_hop(amm, input_mint, output_mint, amount)
+            .ok()
+            .map(|hop| (hop.out_amount as f64 * output_value - amount as f64 * input_value, hop))
+    };
+
+    //the profit is concave in the input amount and zero without input, so there's none if a tiny swap makes none
+    let marginal_amount = (snapshot.balances[input_index] / 1_000_000).max(1);
+    if !matches!(profit(marginal_amount), Some((profit, _)) if profit > 0.0) {
+        return None;
+    }
+    //swapping more than the output balance's worth never pays
+    let (mut low, mut high) = (
+        1u64,
+        (snapshot.balances[output_index] as f64 * output_value / input_value) as u64,
+    );
+    while high > low + 2 {
+        let third = (high - low) / 3;
+        let (first, second) = (low + third, high - third);
+        let first_profit = profit(first).map_or(f64::MIN, |(profit, _)| profit);
+        let second_profit = profit(second).map_or(f64::MIN, |(profit, _)| profit);
+        if first_profit < second_profit {
+            low = first;
+        } else {
+            high = second;
+        }
+    }
+    let (profit, hop) = (low..=high)
+        .filter_map(profit)
+        .max_by(|(a, _), (b, _)| a.partial_cmp(b).unwrap())?;
+    if profit <= 0.0 {
+        return None;
+    }
+    let profit_bps = (profit / (hop.in_amount as f64 * input_value) * 10_000.0) as u64;
+    Some(ArbOpportunity {
+        route: Route {
+            program_id: amm.program_id(),
+            hops: vec![hop],
+        },
+        profit_bps,
+        profit_amount: (profit / input_value) as u64,
+        profit,
+    })
+}
@@ -0,0 +1,494 @@
+//captures the accounts touched by a single pool instruction together with their state after execution
+// so that the instruction can later be replayed against the local processor (see tests/replay.rs)
+//
+//the post-execution state is obtained by simulating the instruction against the cluster, hence nothing
+// is ever sent and no signatures are required
+
+use super::{amm::PoolAmm, router::Router, ClientError, ClientResult};
+use crate::{state::PoolState, token_2022, TOKEN_COUNT};
+use borsh::{BorshDeserialize, BorshSerialize};
+use solana_account_decoder::UiAccountEncoding;
+use solana_client::{
+    rpc_client::RpcClient,
+    rpc_config::{RpcSimulateTransactionAccountsConfig, RpcSimulateTransactionConfig},
+};
+use solana_program::{
+    clock::{Clock, Slot, UnixTimestamp},
+    instruction::{AccountMeta, Instruction},
+    program_option::COption,
+    program_pack::Pack,
+    pubkey::Pubkey,
+    sysvar,
+};
+use solana_sdk::{
+    account::{from_account, Account},
+    message::Message,
+    transaction::Transaction,
+};
+use spl_token::state::{Account as TokenState, Mint as MintState};
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+pub const FIXTURE_VERSION: u8 = 1;
+pub const FIXTURE_EXTENSION: &str = "fixture";
+pub const FORK_EXTENSION: &str = "fork";
+
+//the pool's accounts can change between fetching them and simulating (somebody else trades), in which case
+// the capture is retried
+const MAX_CAPTURE_ATTEMPTS: usize = 5;
+
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq)]
+pub struct FixtureAccountMeta {
+    pub pubkey: Pubkey,
+    pub is_signer: bool,
+    pub is_writable: bool,
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq)]
+pub struct FixtureAccount {
+    pub pubkey: Pubkey,
+    pub owner: Pubkey,
+    pub lamports: u64,
+    pub data: Vec<u8>,
+    pub executable: bool,
+    pub rent_epoch: u64,
+}
+
+impl FixtureAccount {
+    fn new(pubkey: Pubkey, account: Account) -> Self {
+        Self {
+            pubkey,
+            owner: account.owner,
+            lamports: account.lamports,
+            data: account.data,
+            executable: account.executable,
+            rent_epoch: account.rent_epoch,
+        }
+    }
+
+    pub fn to_account(&self) -> Account {
+        Account {
+            lamports: self.lamports,
+            data: self.data.clone(),
+            owner: self.owner,
+            executable: self.executable,
+            rent_epoch: self.rent_epoch,
+        }
+    }
+}
+
+/// A single recorded pool instruction: the state of all accounts it references before and after execution
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq)]
+pub struct ReplayFixture {
+    pub version: u8,
+    pub description: String,
+    pub slot: Slot,
+    pub unix_timestamp: UnixTimestamp,
+    pub program_id: Pubkey,
+    pub account_metas: Vec<FixtureAccountMeta>,
+    pub instruction_data: Vec<u8>,
+    //executable accounts (i.e. the token program) are never recorded, they are provided by the replay environment
+    pub pre_accounts: Vec<FixtureAccount>,
+    pub post_accounts: Vec<FixtureAccount>,
+}
+
+impl ReplayFixture {
+    pub fn instruction(&self) -> Instruction {
+        Instruction {
+            program_id: self.program_id,
+            accounts: self
+                .account_metas
+                .iter()
+                .map(|meta| AccountMeta {
+                    pubkey: meta.pubkey,
+                    is_signer: meta.is_signer,
+                    is_writable: meta.is_writable,
+                })
+                .collect(),
+            data: self.instruction_data.clone(),
+        }
+    }
+
+    pub fn signers(&self) -> Vec<Pubkey> {
+        let mut signers: Vec<Pubkey> = vec![];
+        for meta in self.account_metas.iter().filter(|meta| meta.is_signer) {
+            if !signers.contains(&meta.pubkey) {
+                signers.push(meta.pubkey);
+            }
+        }
+        signers
+    }
+
+    /// Replaces `old` with `new` in the account metas, account addresses and owners and in the fields of the
+    /// recorded pool states, token accounts and mints that hold keys (see `substitute_in_data`)
+    ///
+    /// Replaying requires the signatures of the original signers, so the replay swaps them for keys it controls.
+    /// Since the same substitution is applied to the expected post state, outputs remain comparable byte-for-byte.
+    /// Other data is left alone, an amount or hash that happens to contain the key's bytes stays what it was
+    pub fn substitute_key(&mut self, old: &Pubkey, new: &Pubkey) {
+        let program_id = self.program_id;
+        for meta in self.account_metas.iter_mut() {
+            if meta.pubkey == *old {
+                meta.pubkey = *new;
+            }
+        }
+        for account in self.pre_accounts.iter_mut().chain(self.post_accounts.iter_mut()) {
+            if account.pubkey == *old {
+                account.pubkey = *new;
+            }
+            substitute_in_data(account, &program_id, old, new);
+            if account.owner == *old {
+                account.owner = *new;
+            }
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        fs::write(path, self.try_to_vec()?)
+    }
+
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let fixture = Self::try_from_slice(&fs::read(path)?)?;
+        if fixture.version != FIXTURE_VERSION {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("unsupported fixture version {} in {}", fixture.version, path.display()),
+            ));
+        }
+        Ok(fixture)
+    }
+
+    /// Loads all fixtures of the given directory, ordered by file name
+    pub fn load_dir(dir: &Path) -> io::Result<Vec<(PathBuf, Self)>> {
+        fixture_paths(dir, FIXTURE_EXTENSION)?
+            .into_iter()
+            .map(|path| Self::load(&path).map(|fixture| (path, fixture)))
+            .collect()
+    }
+}
+
+/// The accounts that a set of pools quote from at a single slot, to fork a local bank from and dry-run routes
+/// through them against the local processor (see tests/fork.rs)
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq)]
+pub struct ForkFixture {
+    pub version: u8,
+    pub description: String,
+    pub slot: Slot,
+    pub unix_timestamp: UnixTimestamp,
+    pub program_id: Pubkey,
+    pub pools: Vec<Pubkey>,
+    //all but the clock, which the fork sets from `slot` and `unix_timestamp`
+    pub accounts: Vec<FixtureAccount>,
+}
+
+impl ForkFixture {
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        fs::write(path, self.try_to_vec()?)
+    }
+
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let fixture = Self::try_from_slice(&fs::read(path)?)?;
+        if fixture.version != FIXTURE_VERSION {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("unsupported fixture version {} in {}", fixture.version, path.display()),
+            ));
+        }
+        Ok(fixture)
+    }
+
+    /// Loads all fork fixtures of the given directory, ordered by file name
+    pub fn load_dir(dir: &Path) -> io::Result<Vec<(PathBuf, Self)>> {
+        fixture_paths(dir, FORK_EXTENSION)?
+            .into_iter()
+            .map(|path| Self::load(&path).map(|fixture| (path, fixture)))
+            .collect()
+    }
+}
+
+fn fixture_paths(dir: &Path, extension: &str) -> io::Result<Vec<PathBuf>> {
+    let mut paths = fs::read_dir(dir)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<Result<Vec<_>, _>>()?;
+    paths.retain(|path| {
+        path.extension()
+            .map_or(false, |path_extension| path_extension == extension)
+    });
+    paths.sort();
+    Ok(paths)
+}
+
+/// The accounts of `amm` that a fork needs to quote and swap in it: those it quotes from but the clock, and the
+/// governance fee account that swaps mint fees to. Programs and sysvars are provided by the forked bank
+pub fn fork_account_keys(amm: &PoolAmm) -> Vec<Pubkey> {
+    let mut keys: Vec<Pubkey> = amm
+        .get_accounts_to_update()
+        .into_iter()
+        .filter(|key| *key != sysvar::clock::id())
+        .collect();
+    if let Some(snapshot) = amm.snapshot() {
+        if snapshot.state.governance_fee_key != Pubkey::default() {
+            keys.push(snapshot.state.governance_fee_key);
+        }
+    }
+    keys
+}
+
+/// Records the current state of `pools` and of all accounts they quote from
+pub fn capture_fork(
+    rpc_client: &RpcClient,
+    program_id: &Pubkey,
+    pools: &[Pubkey],
+    description: &str,
+) -> ClientResult<ForkFixture> {
+    let router = Router::load_pools(rpc_client, program_id, pools)?;
+    let mut keys: Vec<Pubkey> = vec![];
+    for key in router.pools().iter().flat_map(fork_account_keys) {
+        if !keys.contains(&key) {
+            keys.push(key);
+        }
+    }
+    let (slot, clock, accounts) = fetch_accounts(rpc_client, &keys)?;
+    Ok(ForkFixture {
+        version: FIXTURE_VERSION,
+        description: description.to_string(),
+        slot,
+        unix_timestamp: clock.unix_timestamp,
+        program_id: *program_id,
+        pools: pools.to_vec(),
+        accounts,
+    })
+}
+
+/// Records `instruction` together with the current and post-execution state of all accounts it references
+///
+/// `fee_payer` must be an existing system account on the cluster (it's only used for the simulation).
+pub fn capture_fixture(
+    rpc_client: &RpcClient,
+    instruction: &Instruction,
+    fee_payer: &Pubkey,
+    description: &str,
+) -> ClientResult<ReplayFixture> {
+    let mut keys: Vec<Pubkey> = vec![];
+    for meta in &instruction.accounts {
+        if !keys.contains(&meta.pubkey) {
+            keys.push(meta.pubkey);
+        }
+    }
+
+    for _ in 0..MAX_CAPTURE_ATTEMPTS {
+        let (slot, clock, pre_accounts) = fetch_accounts(rpc_client, &keys)?;
+        let post_accounts = simulate(rpc_client, instruction, fee_payer, &keys)?;
+        //make sure that nothing happened in between, otherwise the post state doesn't follow from the pre state
+        let (_, _, pre_accounts_after) = fetch_accounts(rpc_client, &keys)?;
+        if pre_accounts != pre_accounts_after {
+            continue;
+        }
+
+        return Ok(ReplayFixture {
+            version: FIXTURE_VERSION,
+            description: description.to_string(),
+            slot,
+            unix_timestamp: clock.unix_timestamp,
+            program_id: instruction.program_id,
+            account_metas: instruction
+                .accounts
+                .iter()
+                .map(|meta| FixtureAccountMeta {
+                    pubkey: meta.pubkey,
+                    is_signer: meta.is_signer,
+                    is_writable: meta.is_writable,
+                })
+                .collect(),
+            instruction_data: instruction.data.clone(),
+            pre_accounts,
+            post_accounts,
+        });
+    }
+
+    Err(ClientError::StateChanged)
+}
+
+fn fetch_accounts(rpc_client: &RpcClient, keys: &[Pubkey]) -> ClientResult<(Slot, Clock, Vec<FixtureAccount>)> {
+    let mut keys_and_clock = keys.to_vec();
+    keys_and_clock.push(sysvar::clock::id());
+    let response = rpc_client.get_multiple_accounts_with_commitment(&keys_and_clock, rpc_client.commitment())?;
+    let mut accounts = response.value;
+
+    let clock_account = accounts
+        .pop()
+        .flatten()
+        .ok_or(ClientError::AccountNotFound(sysvar::clock::id()))?;
+    let clock = from_account::<Clock, _>(&clock_account).ok_or(ClientError::InvalidAccountData(sysvar::clock::id()))?;
+
+    let accounts = keys
+        .iter()
+        .zip(accounts.into_iter())
+        .filter_map(|(key, account)| account.map(|account| FixtureAccount::new(*key, account)))
+        .filter(|account| !account.executable)
+        .collect();
+
+    Ok((response.context.slot, clock, accounts))
+}
+
+fn simulate(
+    rpc_client: &RpcClient,
+    instruction: &Instruction,
+    fee_payer: &Pubkey,
+    keys: &[Pubkey],
+) -> ClientResult<Vec<FixtureAccount>> {
+    let transaction = Transaction::new_unsigned(Message::new(&[instruction.clone()], Some(fee_payer)));
+    let config = RpcSimulateTransactionConfig {
+        sig_verify: false,
+        replace_recent_blockhash: true,
+        commitment: Some(rpc_client.commitment()),
+        accounts: Some(RpcSimulateTransactionAccountsConfig {
+            encoding: Some(UiAccountEncoding::Base64),
+            addresses: keys.iter().map(|key| key.to_string()).collect(),
+        }),
+        ..RpcSimulateTransactionConfig::default()
+    };
+    let result = rpc_client.simulate_transaction_with_config(&transaction, config)?.value;
+    if let Some(err) = result.err {
+        return Err(ClientError::SimulationFailed(err));
+    }
+
+    let accounts = result.accounts.unwrap_or_default();
+    Ok(keys
+        .iter()
+        .zip(accounts.into_iter())
+        .filter_map(|(key, account)| {
+            account
+                .and_then(|account| account.decode::<Account>())
+                .map(|account| FixtureAccount::new(*key, account))
+        })
+        .filter(|account| !account.executable)
+        .collect())
+}
+
+//replaces `old` in the fields that can hold a signer's key: the governance, roles, emergency responders, peg keeper
+// and rfq quoter of a pool state, the owner, delegate and close authority of a token account and the authorities of a
+// mint (Token-2022 extensions are left alone). accounts derived from a signer's key (e.g. a withdrawal announcement)
+// keep the key they were derived from, fixtures of instructions that take them can't be replayed
+fn substitute_in_data(account: &mut FixtureAccount, program_id: &Pubkey, old: &Pubkey, new: &Pubkey) {
+    let substitute = |key: &mut Pubkey| {
+        if *key == *old {
+            *key = *new;
+        }
+    };
+    let substitute_option = |key: &mut COption<Pubkey>| {
+        if *key == COption::Some(*old) {
+            *key = COption::Some(*new);
+        }
+    };
+    if account.owner == *program_id {
+        if let Ok(mut state) = PoolState::<TOKEN_COUNT>::try_from_slice(&account.data) {
+            substitute(&mut state.governance_key);
+            substitute(&mut state.prepared_governance_key);
+            state.roles.iter_mut().for_each(substitute);
+            state.emergency_responders.iter_mut().for_each(substitute);
+            substitute(&mut state.peg_keeper.keeper_key);
+            substitute(&mut state.prepared_peg_keeper.keeper_key);
+            substitute(&mut state.rfq_quoter_key);
+            substitute(&mut state.prepared_rfq_quoter_key);
+            account.data = state.try_to_vec().unwrap();
+        }
+    } else if account.owner == spl_token::id() || account.owner == token_2022::id() {
+        if let Ok(mut token_state) = token_2022::unpack_account(&account.data) {
+            substitute(&mut token_state.owner);
+            substitute_option(&mut token_state.delegate);
+            substitute_option(&mut token_state.close_authority);
+            token_state.pack_into_slice(&mut account.data[..TokenState::LEN]);
+        } else if let Ok(mut mint_state) = token_2022::unpack_mint(&account.data) {
+            substitute_option(&mut mint_state.mint_authority);
+            substitute_option(&mut mint_state.freeze_authority);
+            mint_state.pack_into_slice(&mut account.data[..MintState::LEN]);
+        }
+    }
+}
+
+#[cfg(all(test, not(feature = "test-bpf")))]
+mod tests {
+    use super::*;
+
+    fn fixture_account(owner: Pubkey, data: Vec<u8>) -> FixtureAccount {
+        FixtureAccount {
+            pubkey: Pubkey::new_unique(),
+            owner,
+            lamports: 10,
+            data,
+            executable: false,
+            rent_epoch: 0,
+        }
+    }
+
+    fn sample_fixture(signer: Pubkey) -> ReplayFixture {
+        let mut token_data = vec![0u8; TokenState::LEN];
+        TokenState {
+            mint: Pubkey::new_unique(),
+            owner: signer,
+            amount: 5,
+            delegate: COption::Some(signer),
+            state: spl_token::state::AccountState::Initialized,
+            ..TokenState::default()
+        }
+        .pack_into_slice(&mut token_data);
+        let mut mint_data = vec![0u8; MintState::LEN];
+        MintState {
+            mint_authority: COption::Some(signer),
+            supply: 5,
+            is_initialized: true,
+            ..MintState::default()
+        }
+        .pack_into_slice(&mut mint_data);
+        //data of an unknown layout that happens to contain the signer's key
+        let mut other_data = vec![7u8; 3];
+        other_data.extend_from_slice(&signer.to_bytes());
+        ReplayFixture {
+            version: FIXTURE_VERSION,
+            description: "sample".to_string(),
+            slot: 1,
+            unix_timestamp: 2,
+            program_id: Pubkey::new_unique(),
+            account_metas: vec![FixtureAccountMeta {
+                pubkey: signer,
+                is_signer: true,
+                is_writable: false,
+            }],
+            instruction_data: vec![1, 2, 3],
+            pre_accounts: vec![
+                fixture_account(spl_token::id(), token_data),
+                fixture_account(token_2022::id(), mint_data),
+                fixture_account(Pubkey::new_unique(), other_data),
+            ],
+            post_accounts: vec![],
+        }
+    }
+
+    #[test]
+    fn substitute_key() {
+        let (old, new) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let mut fixture = sample_fixture(old);
+        let other_data = fixture.pre_accounts[2].data.clone();
+        fixture.substitute_key(&old, &new);
+
+        assert_eq!(fixture.signers(), vec![new]);
+        let token_state = TokenState::unpack(&fixture.pre_accounts[0].data).unwrap();
+        assert_eq!(
+            (token_state.owner, token_state.delegate, token_state.amount),
+            (new, COption::Some(new), 5)
+        );
+        let mint_state = MintState::unpack(&fixture.pre_accounts[1].data).unwrap();
+        assert_eq!(mint_state.mint_authority, COption::Some(new));
+        assert_eq!(fixture.pre_accounts[2].data, other_data);
+    }
+
+    #[test]
+    fn serialization_roundtrip() {
+        let fixture = sample_fixture(Pubkey::new_unique());
+        let serialized = fixture.try_to_vec().unwrap();
+        assert_eq!(ReplayFixture::try_from_slice(&serialized).unwrap(), fixture);
+    }
+}
@@ -0,0 +1,294 @@
+//deploys several pools at once from a manifest (toml or json) that describes each of them
+//
+//every pool is created with CreatePool, so all of its accounts are program addresses derived from its mints and fee
+// tier: the plan only depends on the manifest, the program and the payer, and a deployment that failed halfway is
+// resumed by running the same manifest again, which skips the pools that exist already
+
+use super::{parse_decimal, parse_fee, pool::send_instructions, ClientError, ClientResult};
+use crate::{
+    amp_factor::AmpFactor,
+    decimal::DecimalU64,
+    instruction::{create_pool_ix, find_pool_address},
+    pool_fee::PoolFee,
+    state::PoolState,
+    TOKEN_COUNT,
+};
+use serde::Deserialize;
+use solana_client::rpc_client::RpcClient;
+use solana_program::{instruction::Instruction, pubkey::Pubkey};
+use solana_sdk::signature::{Signature, Signer};
+use std::{collections::HashSet, convert::TryInto, fs, path::Path, slice, str::FromStr};
+
+/// The pools to deploy, in order, e.g.
+///
+/// ```toml
+/// [[pools]]
+/// name = "usd"
+/// mints = ["<mint>", "<mint>"]
+/// amp_factor = "1000"
+/// lp_fee = "4bps"
+/// governance_fee = "1bps"
+/// governance = "<pubkey>"
+/// ```
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct DeployManifest {
+    pub pools: Vec<PoolManifest>,
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct PoolManifest {
+    /// Only tells the pools of the manifest apart
+    pub name: String,
+    /// In any order, the pool's tokens are in ascending order of their mints
+    pub mints: Vec<String>,
+    /// Tells pools of the same tokens apart [default: 0]
+    #[serde(default)]
+    pub fee_tier: u16,
+    pub amp_factor: String,
+    /// e.g. "4bps", "0.04%" or "0.0004"
+    pub lp_fee: String,
+    /// Like `lp_fee` [default: 0]
+    #[serde(default)]
+    pub governance_fee: Option<String>,
+    pub governance: String,
+}
+
+/// The transaction that creates one pool of the manifest
+pub struct DeployStep {
+    pub name: String,
+    pub pool: Pubkey,
+    pub governance: Pubkey,
+    pub instruction: Instruction,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StepStatus {
+    Pending,
+    Deployed,
+}
+
+pub struct DeployPlan {
+    pub program_id: Pubkey,
+    pub steps: Vec<DeployStep>,
+}
+
+impl DeployManifest {
+    /// Reads a manifest, as json if the file name ends with .json and as toml otherwise
+    pub fn load(path: &Path) -> ClientResult<Self> {
+        let manifest = fs::read_to_string(path)?;
+        if path.extension().map_or(false, |extension| extension == "json") {
+            Self::from_json(&manifest)
+        } else {
+            Self::from_toml(&manifest)
+        }
+    }
+
+    pub fn from_toml(manifest: &str) -> ClientResult<Self> {
+        toml::from_str(manifest).map_err(|err| ClientError::InvalidManifest(err.to_string()))
+    }
+
+    pub fn from_json(manifest: &str) -> ClientResult<Self> {
+        serde_json::from_str(manifest).map_err(|err| ClientError::InvalidManifest(err.to_string()))
+    }
+
+    /// Checks every pool against the program's rules and builds the instructions that create them, in the order of
+    /// the manifest. Nothing is fetched, so mismatched mint decimals only fail on chain
+    pub fn plan(&self, program_id: &Pubkey, payer: &Pubkey) -> ClientResult<DeployPlan> {
+        let mut names = HashSet::new();
+        let mut pools = HashSet::new();
+        let mut steps = vec![];
+        for pool in self.pools.iter() {
+            let invalid = |reason: String| ClientError::InvalidManifest(format!("pool {}: {}", pool.name, reason));
+            if !names.insert(pool.name.as_str()) {
+                return Err(invalid("the name is taken".to_string()));
+            }
+            let parse_key = |key: &str| Pubkey::from_str(key).map_err(|_| invalid(format!("invalid key {}", key)));
+            let mut mints = pool
+                .mints
+                .iter()
+                .map(|mint| parse_key(mint))
+                .collect::<Result<Vec<_>, _>>()?;
+            mints.sort();
+            mints.dedup();
+            let mints: [Pubkey; TOKEN_COUNT] = mints
+                .try_into()
+                .map_err(|_| invalid(format!("the program's pools have {} different tokens", TOKEN_COUNT)))?;
+            let governance = parse_key(&pool.governance)?;
+
+            let amp_factor = parse_decimal(&pool.amp_factor).map_err(invalid)?;
+            let lp_fee = parse_fee(&pool.lp_fee).map_err(invalid)?;
+            let governance_fee = match &pool.governance_fee {
+                Some(governance_fee) => parse_fee(governance_fee).map_err(invalid)?,
+                None => DecimalU64::from(0),
+            };
+            AmpFactor::new(amp_factor).map_err(|err| invalid(err.to_string()))?;
+            PoolFee::new(lp_fee).map_err(|err| invalid(err.to_string()))?;
+            PoolFee::new(governance_fee).map_err(|err| invalid(err.to_string()))?;
+            if lp_fee + governance_fee >= DecimalU64::from(1) {
+                return Err(invalid("the fees add up to 100% or more".to_string()));
+            }
+
+            let pool_key = find_pool_address(program_id, &mints, pool.fee_tier).0;
+            if !pools.insert(pool_key) {
+                return Err(invalid("another pool has the same tokens and fee tier".to_string()));
+            }
+            steps.push(DeployStep {
+                name: pool.name.clone(),
+                pool: pool_key,
+                governance,
+                instruction: create_pool_ix(
+                    program_id,
+                    &mints,
+                    pool.fee_tier,
+                    &governance,
+                    payer,
+                    amp_factor,
+                    lp_fee,
+                    governance_fee,
+                )?,
+            });
+        }
+        Ok(DeployPlan {
+            program_id: *program_id,
+            steps,
+        })
+    }
+}
+
+impl DeployPlan {
+    /// Whether the pool of each step exists already. As anybody can create a pool, one that exists with another
+    /// governance than the manifest's is an error rather than deployed
+    pub fn status(&self, rpc_client: &RpcClient) -> ClientResult<Vec<StepStatus>> {
+        self.steps
+            .iter()
+            .map(|step| {
+                let account = rpc_client
+                    .get_account_with_commitment(&step.pool, rpc_client.commitment())?
+                    .value;
+                //the address of a pool that wasn't created yet may hold lamports nonetheless
+                let account = match account {
+                    Some(account) if account.owner == self.program_id => account,
+                    _ => return Ok(StepStatus::Pending),
+                };
+                let state = PoolState::<TOKEN_COUNT>::unpack(&account.data)
+                    .ok_or(ClientError::InvalidAccountData(step.pool))?;
+                if state.governance_key != step.governance {
+                    return Err(ClientError::ConflictingPool(step.pool));
+                }
+                Ok(StepStatus::Deployed)
+            })
+            .collect()
+    }
+
+    /// Sends the pending steps one transaction each, in order, and reports every step along with the signature of its
+    /// transaction (none for pools that were deployed before). A failed step ends the deployment, running the plan
+    /// again resumes with it
+    pub fn execute(
+        &self,
+        rpc_client: &RpcClient,
+        payer: &dyn Signer,
+        mut report: impl FnMut(&DeployStep, Option<&Signature>),
+    ) -> ClientResult<()> {
+        for (step, status) in self.steps.iter().zip(self.status(rpc_client)?) {
+            match status {
+                StepStatus::Deployed => report(step, None),
+                StepStatus::Pending => {
+                    let signature = send_instructions(rpc_client, slice::from_ref(&step.instruction), payer, &[])?;
+                    report(step, Some(&signature));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(all(test, not(feature = "test-bpf")))]
+mod tests {
+    use super::*;
+    use crate::common::create_array;
+
+    fn mints() -> [Pubkey; TOKEN_COUNT] {
+        create_array(|_| Pubkey::new_unique())
+    }
+
+    fn toml_pool(name: &str, mints: &[Pubkey], lp_fee: &str) -> String {
+        let mints: Vec<_> = mints.iter().map(|mint| format!("\"{}\"", mint)).collect();
+        format!(
+            "[[pools]]\nname = \"{}\"\nmints = [{}]\namp_factor = \"1000\"\nlp_fee = \"{}\"\ngovernance = \"{}\"\n",
+            name,
+            mints.join(", "),
+            lp_fee,
+            Pubkey::new_unique()
+        )
+    }
+
+    #[test]
+    fn plan_is_deterministic_and_ordered() {
+        let (program_id, payer) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let (first_mints, second_mints) = (mints(), mints());
+        let mut reversed_mints = first_mints;
+        reversed_mints.reverse();
+        let manifest = DeployManifest::from_toml(&format!(
+            "{}{}",
+            toml_pool("first", &reversed_mints, "4bps"),
+            toml_pool("second", &second_mints, "0.0003")
+        ))
+        .unwrap();
+
+        let plan = manifest.plan(&program_id, &payer).unwrap();
+        let names: Vec<_> = plan.steps.iter().map(|step| step.name.as_str()).collect();
+        assert_eq!(names, ["first", "second"]);
+        let mut sorted_mints = first_mints;
+        sorted_mints.sort();
+        assert_eq!(plan.steps[0].pool, find_pool_address(&program_id, &sorted_mints, 0).0);
+        let replanned = manifest.plan(&program_id, &payer).unwrap();
+        for (step, replanned_step) in plan.steps.iter().zip(replanned.steps.iter()) {
+            assert_eq!(step.instruction, replanned_step.instruction);
+        }
+    }
+
+    #[test]
+    fn json_manifest() {
+        let manifest = DeployManifest::from_json(&format!(
+            r#"{{"pools": [{{"name": "usd", "mints": [{}], "fee_tier": 1, "amp_factor": "500", "lp_fee": "0.04%",
+                "governance_fee": "1bp", "governance": "{}"}}]}}"#,
+            mints()
+                .iter()
+                .map(|mint| format!("\"{}\"", mint))
+                .collect::<Vec<_>>()
+                .join(", "),
+            Pubkey::new_unique()
+        ))
+        .unwrap();
+        assert_eq!(manifest.pools[0].fee_tier, 1);
+        assert_eq!(manifest.pools[0].governance_fee.as_deref(), Some("1bp"));
+        assert!(manifest.plan(&Pubkey::new_unique(), &Pubkey::new_unique()).is_ok());
+    }
+
+    #[test]
+    fn invalid_manifests() {
+        let (program_id, payer) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let plan = |manifest: String| DeployManifest::from_toml(&manifest)?.plan(&program_id, &payer);
+        let pool_mints = mints();
+        assert!(plan(toml_pool("usd", &pool_mints, "100%")).is_err());
+        assert!(plan(toml_pool("usd", &pool_mints[1..], "4bps")).is_err());
+        let mut duplicate_mints = pool_mints;
+        duplicate_mints[0] = duplicate_mints[1];
+        assert!(plan(toml_pool("usd", &duplicate_mints, "4bps")).is_err());
+        let same_pool = format!(
+            "{}{}",
+            toml_pool("usd", &pool_mints, "4bps"),
+            toml_pool("usdc", &pool_mints, "4bps")
+        );
+        assert!(plan(same_pool).is_err());
+        let same_name = format!(
+            "{}{}",
+            toml_pool("usd", &pool_mints, "4bps"),
+            toml_pool("usd", &mints(), "4bps")
+        );
+        assert!(plan(same_name).is_err());
+        assert!(plan(toml_pool("usd", &pool_mints, "4bps") + "amp = \"1000\"\n").is_err());
+    }
+}
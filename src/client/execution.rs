@@ -0,0 +1,203 @@
+//sends routed swaps in ways that leave little room for sandwiching
+//
+//a sandwich needs the swap to land with the pool moved against it but still within the swap's slippage limit. right
+// before sending, the route is re-quoted from the pools' current accounts and dropped if its output or price impact
+// got worse than allowed. then it's either sent through the RPC node with a priority fee and given up on as soon as
+// its blockhash expires, instead of lingering in forwarding queues, or sent as a Jito bundle, which block engines
+// execute atomically and in order, so nothing can be put in front of or behind the swap
+
+use super::{
+    router::{Route, Router},
+    ClientError, ClientResult,
+};
+use crate::error::PoolError;
+use serde_json::json;
+use solana_client::{rpc_client::RpcClient, rpc_request::RpcRequest};
+use solana_program::{instruction::Instruction, program_error::ProgramError, pubkey::Pubkey, system_instruction};
+use solana_sdk::{
+    signature::{Signature, Signer},
+    transaction::Transaction,
+};
+use std::{io, str::FromStr, thread, time::Duration};
+
+/// The compute budget program, whose instructions are encoded here since the pinned sdk predates compute unit prices
+pub const COMPUTE_BUDGET_PROGRAM_ID: &str = "ComputeBudget111111111111111111111111111111";
+const SET_COMPUTE_UNIT_LIMIT: u8 = 2;
+const SET_COMPUTE_UNIT_PRICE: u8 = 3;
+const CONFIRMATION_POLL_INTERVAL: Duration = Duration::from_millis(400);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ExecutionMode {
+    /// Sends the transaction through the RPC node
+    Rpc,
+    /// Sends the transaction as a bundle to a Jito block engine (e.g.
+    /// `https://mainnet.block-engine.jito.wtf/api/v1/bundles`), with a tip of `tip_lamports` to one of its tip
+    /// accounts
+    JitoBundle {
+        block_engine_url: String,
+        tip_lamports: u64,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExecutionConfig {
+    pub mode: ExecutionMode,
+    /// The priority fee in micro-lamports per compute unit, none if zero
+    pub compute_unit_price: u64,
+    /// The compute units to request, the runtime's default if `None`
+    pub compute_unit_limit: Option<u32>,
+    /// The highest price impact (see `Router::price_impact`) to still send the swap at, as a fraction
+    pub max_price_impact: f64,
+}
+
+/// The compute budget instructions that `config` asks for, to go first in the transaction
+pub fn compute_budget_ixs(config: &ExecutionConfig) -> Vec<Instruction> {
+    let program_id = Pubkey::from_str(COMPUTE_BUDGET_PROGRAM_ID).unwrap();
+    let mut instructions = vec![];
+    if let Some(units) = config.compute_unit_limit {
+        let mut data = vec![SET_COMPUTE_UNIT_LIMIT];
+        data.extend_from_slice(&units.to_le_bytes());
+        instructions.push(Instruction::new_with_bytes(program_id, &data, vec![]));
+    }
+    if config.compute_unit_price > 0 {
+        let mut data = vec![SET_COMPUTE_UNIT_PRICE];
+        data.extend_from_slice(&config.compute_unit_price.to_le_bytes());
+        instructions.push(Instruction::new_with_bytes(program_id, &data, vec![]));
+    }
+    instructions
+}
+
+/// Re-quotes `route` from the current accounts of its pools, fails if it no longer pays `minimum_output_amount` or
+/// its price impact exceeds `max_price_impact`
+pub fn recheck_route(
+    rpc_client: &RpcClient,
+    route: &Route,
+    minimum_output_amount: u64,
+    max_price_impact: f64,
+) -> ClientResult<Route> {
+    let pools: Vec<Pubkey> = route.hops.iter().map(|hop| hop.leg.pool).collect();
+    let router = Router::load_pools(rpc_client, &route.program_id, &pools)?;
+    let route = router.requote(route)?;
+    if route.out_amount() < minimum_output_amount {
+        return Err(PoolError::MinimumOutputNotMet.into());
+    }
+    let price_impact = router.price_impact(&route)?;
+    if price_impact > max_price_impact {
+        return Err(ClientError::PriceImpactExceeded(price_impact));
+    }
+    Ok(route)
+}
+
+/// Rechecks `route` (see `recheck_route`) and executes it as configured, waiting for its confirmation.
+/// `user_transfer_authority` owns the input and output accounts, `payer` pays the fees and the tip
+#[allow(clippy::too_many_arguments)]
+pub fn execute_route(
+    rpc_client: &RpcClient,
+    route: &Route,
+    config: &ExecutionConfig,
+    payer: &dyn Signer,
+    user_transfer_authority: &dyn Signer,
+    input_account: &Pubkey,
+    output_account: &Pubkey,
+    minimum_output_amount: u64,
+) -> ClientResult<Signature> {
+    let route = recheck_route(rpc_client, route, minimum_output_amount, config.max_price_impact)?;
+    let mut instructions = compute_budget_ixs(config);
+    instructions.extend(route.instructions(
+        &user_transfer_authority.pubkey(),
+        input_account,
+        output_account,
+        minimum_output_amount,
+    )?);
+    let bundle_client = match &config.mode {
+        ExecutionMode::Rpc => None,
+        ExecutionMode::JitoBundle {
+            block_engine_url,
+            tip_lamports,
+        } => {
+            let bundle_client = RpcClient::new(block_engine_url.clone());
+            let tip_account = fetch_tip_account(&bundle_client)?;
+            //the tip goes into the swap's transaction, so it's only paid if the swap lands
+            instructions.push(system_instruction::transfer(
+                &payer.pubkey(),
+                &tip_account,
+                *tip_lamports,
+            ));
+            Some(bundle_client)
+        }
+    };
+
+    let (blockhash, _, last_valid_slot) = rpc_client
+        .get_recent_blockhash_with_commitment(rpc_client.commitment())?
+        .value;
+    let mut signers = vec![payer];
+    if user_transfer_authority.pubkey() != payer.pubkey() {
+        signers.push(user_transfer_authority);
+    }
+    let transaction = Transaction::new_signed_with_payer(&instructions, Some(&payer.pubkey()), &signers, blockhash);
+    let signature = match bundle_client {
+        None => rpc_client.send_transaction(&transaction)?,
+        Some(bundle_client) => {
+            let serialized = bincode::serialize(&transaction)
+                .map_err(|error| ClientError::Io(io::Error::new(io::ErrorKind::InvalidData, error)))?;
+            let encoded = bs58::encode(serialized).into_string();
+            bundle_client.send::<String>(RpcRequest::Custom { method: "sendBundle" }, json!([[encoded]]))?;
+            transaction.signatures[0]
+        }
+    };
+    confirm(rpc_client, &signature, last_valid_slot)
+}
+
+//the first of the block engine's tip accounts
+fn fetch_tip_account(bundle_client: &RpcClient) -> ClientResult<Pubkey> {
+    let tip_accounts = bundle_client.send::<Vec<String>>(
+        RpcRequest::Custom {
+            method: "getTipAccounts",
+        },
+        json!([]),
+    )?;
+    tip_accounts
+        .first()
+        .and_then(|account| Pubkey::from_str(account).ok())
+        .ok_or(ClientError::Instruction(ProgramError::InvalidArgument))
+}
+
+//waits until the transaction is confirmed or can't land anymore because its blockhash expired
+fn confirm(rpc_client: &RpcClient, signature: &Signature, last_valid_slot: u64) -> ClientResult<Signature> {
+    loop {
+        match rpc_client.get_signature_status(signature)? {
+            Some(Ok(())) => return Ok(*signature),
+            Some(Err(error)) => return Err(ClientError::TransactionFailed(error)),
+            None if rpc_client.get_slot()? > last_valid_slot => {
+                return Err(ClientError::TransactionExpired(*signature))
+            }
+            None => thread::sleep(CONFIRMATION_POLL_INTERVAL),
+        }
+    }
+}
+
+#[cfg(all(test, not(feature = "test-bpf")))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compute_budget() {
+        let mut config = ExecutionConfig {
+            mode: ExecutionMode::Rpc,
+            compute_unit_price: 0,
+            compute_unit_limit: None,
+            max_price_impact: 0.01,
+        };
+        assert!(compute_budget_ixs(&config).is_empty());
+
+        config.compute_unit_price = 10_000;
+        config.compute_unit_limit = Some(300_000);
+        let ixs = compute_budget_ixs(&config);
+        assert_eq!(ixs.len(), 2);
+        assert_eq!(ixs[0].data, [&[2u8][..], &300_000u32.to_le_bytes()].concat());
+        assert_eq!(ixs[1].data, [&[3u8][..], &10_000u64.to_le_bytes()].concat());
+        assert!(ixs
+            .iter()
+            .all(|ix| ix.accounts.is_empty() && ix.program_id.to_string() == COMPUTE_BUDGET_PROGRAM_ID));
+    }
+}
@@ -0,0 +1,188 @@
+//turns failed transactions into explanations that frontends can show their users as they are
+//
+//custom error codes are only meaningful for the program that returned them. the pool's start at 100 and those below
+// are taken as spl token's, which is what transactions built by this client fail with (the pool checks the user's
+// token accounts before debiting them, other token program failures pass through), so explanations of transactions
+// that also call other programs can misname their errors
+
+use crate::error::{decode_custom_error, CustomError, PoolError};
+use solana_sdk::{instruction::InstructionError, transaction::TransactionError};
+use spl_token::error::TokenError;
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ExplainedError {
+    Pool(PoolError),
+    Token(TokenError),
+    /// A custom code that is neither the pool's nor spl token's
+    UnknownCustom(u32),
+    Instruction(InstructionError),
+    Transaction(TransactionError),
+}
+
+/// Broad classes of failures, e.g. for monitoring how often and why a pool rejects transactions
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ErrorClass {
+    /// A slippage limit that the user set was missed
+    Slippage,
+    /// The pool, or the tokens involved, don't trade right now
+    Paused,
+    /// The pool rejected the instruction's data or accounts
+    Validation,
+    /// Token program and transaction level failures
+    Other,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PoolErrorExplanation {
+    /// The index of the failed instruction within the transaction, if the failure was an instruction's
+    pub instruction_index: Option<u8>,
+    pub error: ExplainedError,
+    /// What went wrong, phrased for users
+    pub message: String,
+    /// What users can do about it, where there is something
+    pub hint: Option<&'static str>,
+}
+
+impl PoolErrorExplanation {
+    /// The custom error code the instruction failed with
+    pub fn code(&self) -> Option<u32> {
+        match self.error {
+            ExplainedError::Pool(error) => Some(error.code()),
+            ExplainedError::Token(ref error) => Some(error.clone() as u32),
+            ExplainedError::UnknownCustom(code) => Some(code),
+            _ => None,
+        }
+    }
+
+    pub fn class(&self) -> ErrorClass {
+        match self.error {
+            ExplainedError::Pool(error) => match error {
+                PoolError::OutsideSpecifiedLimits
+                | PoolError::MinimumMintNotMet
+                | PoolError::MinimumOutputNotMet
+                | PoolError::MaximumInputExceeded
+                | PoolError::MaximumBurnExceeded => ErrorClass::Slippage,
+                PoolError::PoolIsPaused
+                | PoolError::PoolIsSunset
+                | PoolError::OutsideTradingHours
+                | PoolError::PoolInMaintenance
+                | PoolError::TokenDepegged
+                | PoolError::TokenInputDisabled
+                | PoolError::TokenOutputDisabled
+                | PoolError::TokenDepositOnly
+                | PoolError::TokenWithdrawOnly => ErrorClass::Paused,
+                _ => ErrorClass::Validation,
+            },
+            ExplainedError::Instruction(_) => ErrorClass::Validation,
+            _ => ErrorClass::Other,
+        }
+    }
+}
+
+impl fmt::Display for PoolErrorExplanation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(index) = self.instruction_index {
+            write!(f, "Instruction {} failed: ", index)?;
+        }
+        write!(f, "{}", self.message)?;
+        if let Some(hint) = self.hint {
+            write!(f, ". {}", hint)?;
+        }
+        Ok(())
+    }
+}
+
+/// Explains why a transaction failed, see `PoolErrorExplanation`
+pub fn explain_error(error: TransactionError) -> PoolErrorExplanation {
+    let (instruction_index, error) = match error {
+        TransactionError::InstructionError(index, InstructionError::Custom(code)) => {
+            let error = match decode_custom_error(code) {
+                CustomError::Pool(error) => ExplainedError::Pool(error),
+                CustomError::Token(error) => ExplainedError::Token(error),
+                CustomError::Unknown(code) => ExplainedError::UnknownCustom(code),
+            };
+            (Some(index), error)
+        }
+        TransactionError::InstructionError(index, error) => (Some(index), ExplainedError::Instruction(error)),
+        error => (None, ExplainedError::Transaction(error)),
+    };
+    let message = match &error {
+        ExplainedError::Pool(error) => error.to_string(),
+        ExplainedError::Token(error) => error.to_string(),
+        ExplainedError::UnknownCustom(code) => format!("Failed with unknown error code {}", code),
+        ExplainedError::Instruction(error) => error.to_string(),
+        ExplainedError::Transaction(error) => error.to_string(),
+    };
+    PoolErrorExplanation {
+        instruction_index,
+        hint: hint(&error),
+        error,
+        message,
+    }
+}
+
+const INSUFFICIENT_FUNDS: &str = "Your token account holds less than the amount";
+const ACCOUNT_FROZEN: &str = "The token's issuer froze your token account";
+const OWNER_MISMATCH: &str = "The token account isn't owned by the signing wallet";
+
+fn hint(error: &ExplainedError) -> Option<&'static str> {
+    match error {
+        ExplainedError::Pool(error) => match error {
+            PoolError::OutsideSpecifiedLimits
+            | PoolError::MinimumMintNotMet
+            | PoolError::MinimumOutputNotMet
+            | PoolError::MaximumInputExceeded
+            | PoolError::MaximumBurnExceeded => {
+                Some("The price moved since the quote, retry with a fresh quote or a higher slippage tolerance")
+            }
+            PoolError::PoolIsPaused | PoolError::OutsideTradingHours | PoolError::PoolInMaintenance => {
+                Some("Trading is halted for now, retry later")
+            }
+            PoolError::PoolIsSunset => Some("The pool is winding down, only removing liquidity is possible"),
+            PoolError::TokenDepegged | PoolError::TokenInputDisabled | PoolError::TokenOutputDisabled => {
+                Some("Trade a different token of the pool or remove liquidity uniformly")
+            }
+            PoolError::TokenDepositOnly => Some("The token doesn't trade yet, only add it to the pool for now"),
+            PoolError::TokenWithdrawOnly => Some("The pool is phasing the token out, swap or remove it instead"),
+            PoolError::OraclePriceUnavailable | PoolError::RateUnavailable => {
+                Some("A price feed of the pool is stale, retry in a moment")
+            }
+            PoolError::InvariantOverflow | PoolError::InvariantUnderflow | PoolError::InvariantConvergenceFailure => {
+                Some("The pool can't compute a trade this size, retry with a smaller amount")
+            }
+            PoolError::PriceOutOfBand => {
+                Some("The pool only trades within its price band, trade less or the other way")
+            }
+            PoolError::DustPosition => Some("Increase the amount or remove all of your lp tokens"),
+            PoolError::LaunchCapExceeded => Some("Add less liquidity during the launch period"),
+            PoolError::LpStillLocked => Some("Wait until the lock expires"),
+            PoolError::SessionExpired | PoolError::SessionLimitExceeded => Some("Open a new trading session"),
+            PoolError::QuoteExpired | PoolError::QuoteAlreadyUsed => Some("Request a new quote"),
+            PoolError::WithdrawalNotAnnounced => {
+                Some("Announce the withdrawal first and remove after the cooldown, or remove less in a later slot")
+            }
+            PoolError::WithdrawalCooldownActive => Some("Retry once the announced withdrawal's cooldown passed"),
+            PoolError::UserNotAllowed | PoolError::DepositorNotAllowed => {
+                Some("The pool only admits approved users, contact its operator")
+            }
+            PoolError::InsufficientUserBalance => Some(INSUFFICIENT_FUNDS),
+            PoolError::UserTokenAccountFrozen => Some(ACCOUNT_FROZEN),
+            PoolError::InvalidUserAuthority => Some(OWNER_MISMATCH),
+            PoolError::InvalidUserTokenAccount => Some("Pass your token accounts in the order of the pool's tokens"),
+            _ => None,
+        },
+        ExplainedError::Token(error) => match error {
+            TokenError::InsufficientFunds => Some(INSUFFICIENT_FUNDS),
+            TokenError::AccountFrozen => Some(ACCOUNT_FROZEN),
+            TokenError::OwnerMismatch => Some(OWNER_MISMATCH),
+            _ => None,
+        },
+        ExplainedError::Transaction(error) => match error {
+            TransactionError::BlockhashNotFound => Some("The transaction expired, sign it again"),
+            TransactionError::InsufficientFundsForFee => Some("Your wallet needs SOL to pay the transaction fee"),
+            _ => None,
+        },
+        _ => None,
+    }
+}
@@ -0,0 +1,528 @@
+//a pool's history reconstructed from its confirmed transactions: every top-level instruction of the pool in a
+// successful transaction becomes an event, with the amounts taken from the token balance changes the cluster recorded
+//
+//besides the lp supply event of defi instructions (see LpSupplyEvent) the program doesn't log structured events, so this
+// decodes instruction data instead. balance changes are those of the whole transaction, i.e. if a transaction contains
+// several instructions of the same pool, each of their events reports the combined changes. instructions that other
+// programs invoke (CPI) aren't included
+
+use super::{
+    explain::{explain_error, ErrorClass},
+    pool::PoolClient,
+    ClientError, ClientResult,
+};
+use crate::{
+    instruction::{DeFiInstruction, GovernanceInstruction, PoolInstruction},
+    state::LpSupplyEvent,
+    TOKEN_COUNT,
+};
+use borsh::BorshDeserialize;
+use solana_client::rpc_client::GetConfirmedSignaturesForAddress2Config;
+use solana_program::{
+    clock::{Slot, UnixTimestamp},
+    message::Message,
+    pubkey::Pubkey,
+};
+use solana_sdk::{signature::Signature, transaction::TransactionError};
+use solana_transaction_status::{UiTransactionEncoding, UiTransactionTokenBalance};
+use std::{collections::HashMap, str::FromStr};
+
+//the most signatures a single getSignaturesForAddress request returns
+const SIGNATURE_PAGE_SIZE: usize = 1000;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EventTransaction {
+    pub signature: Signature,
+    pub slot: Slot,
+    pub block_time: Option<UnixTimestamp>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PoolEvent {
+    pub transaction: EventTransaction,
+    /// Name of the instruction, e.g. `add` or `prepare_fee_change`
+    pub kind: &'static str,
+    /// The user transfer authority of defi instructions, the governance of init and governance instructions (unset for
+    /// permissionless instructions)
+    pub authority: Pubkey,
+    /// Changes of the pool's token balances (in pool token order)
+    pub pool_balance_changes: [i128; TOKEN_COUNT],
+    /// Change of the user's lp token balance (only set for adds and removes)
+    pub user_lp_change: i128,
+    /// Change of the governance fee account's balance, i.e. the minted governance fee (only set for defi instructions
+    /// and the burned lp tokens of `burn_governance_fees`)
+    pub governance_fee_change: i128,
+    /// How the instruction changed the lp supply as the program logged it (only set for executed defi instructions),
+    /// unlike the balance changes this is the instruction's own
+    pub lp_supply_event: Option<LpSupplyEvent>,
+}
+
+/// How many of a pool's transactions failed, by class of their error (see `ErrorClass`)
+///
+/// The program can't count rejections itself since a failing transaction's writes are rolled back along with it, the
+/// counts are taken from the errors that the cluster recorded for the pool's transactions instead. They include
+/// transactions in which an instruction other than the pool's failed
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RejectionCounts {
+    pub succeeded: u64,
+    pub slippage: u64,
+    pub paused: u64,
+    pub validation: u64,
+    pub other: u64,
+}
+
+impl RejectionCounts {
+    pub fn rejected(&self) -> u64 {
+        self.slippage + self.paused + self.validation + self.other
+    }
+
+    fn count(&mut self, error: Option<TransactionError>) {
+        let counter = match error.map(|error| explain_error(error).class()) {
+            None => &mut self.succeeded,
+            Some(ErrorClass::Slippage) => &mut self.slippage,
+            Some(ErrorClass::Paused) => &mut self.paused,
+            Some(ErrorClass::Validation) => &mut self.validation,
+            Some(ErrorClass::Other) => &mut self.other,
+        };
+        *counter += 1;
+    }
+}
+
+impl PoolClient {
+    /// Calls `f` with the events of all successful transactions of the pool since `from_slot`, oldest first
+    pub fn events(&self, from_slot: Slot, mut f: impl FnMut(PoolEvent) -> ClientResult<()>) -> ClientResult<()> {
+        //signatures are returned newest first, so all of them are collected before fetching any transaction
+        let signatures: Vec<Signature> = self
+            .signatures(from_slot)?
+            .into_iter()
+            .filter(|(_, error)| error.is_none())
+            .map(|(signature, _)| signature)
+            .collect();
+
+        for signature in signatures.into_iter().rev() {
+            let invalid = || ClientError::InvalidTransaction(signature.to_string());
+            let confirmed = self
+                .rpc_client
+                .get_transaction(&signature, UiTransactionEncoding::Base64)?;
+            let transaction = confirmed.transaction.transaction.decode().ok_or_else(invalid)?;
+            let meta = confirmed.transaction.meta.ok_or_else(invalid)?;
+            let log_messages: Vec<String> = Option::from(meta.log_messages).unwrap_or_default();
+            let balance_changes = balance_changes(
+                &transaction.message,
+                Option::from(meta.pre_token_balances).unwrap_or_default(),
+                Option::from(meta.post_token_balances).unwrap_or_default(),
+            )
+            .ok_or_else(invalid)?;
+            let event_transaction = EventTransaction {
+                signature,
+                slot: confirmed.slot,
+                block_time: confirmed.block_time,
+            };
+            for event in parse_events(
+                &self.program_id,
+                &self.pool,
+                &self.state.token_keys,
+                event_transaction,
+                &transaction.message,
+                &balance_changes,
+                &log_messages,
+            ) {
+                f(event)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Counts the pool's transactions since `from_slot` by whether and why they failed
+    pub fn rejections(&self, from_slot: Slot) -> ClientResult<RejectionCounts> {
+        let mut counts = RejectionCounts::default();
+        for (_, error) in self.signatures(from_slot)? {
+            counts.count(error);
+        }
+        Ok(counts)
+    }
+
+    //the signatures of the pool's transactions since from_slot along with their errors, newest first
+    fn signatures(&self, from_slot: Slot) -> ClientResult<Vec<(Signature, Option<TransactionError>)>> {
+        let mut signatures = vec![];
+        let mut before = None;
+        loop {
+            let page = self.rpc_client.get_signatures_for_address_with_config(
+                &self.pool,
+                GetConfirmedSignaturesForAddress2Config {
+                    before,
+                    until: None,
+                    limit: Some(SIGNATURE_PAGE_SIZE),
+                    commitment: Some(self.rpc_client.commitment()),
+                },
+            )?;
+            let is_last_page = page.len() < SIGNATURE_PAGE_SIZE;
+            let mut reached_from_slot = false;
+            for status in page {
+                let signature = Signature::from_str(&status.signature)
+                    .map_err(|_| ClientError::InvalidTransaction(status.signature.clone()))?;
+                before = Some(signature);
+                if status.slot < from_slot {
+                    reached_from_slot = true;
+                    break;
+                }
+                signatures.push((signature, status.err));
+            }
+            if is_last_page || reached_from_slot {
+                return Ok(signatures);
+            }
+        }
+    }
+}
+
+/// The events of `pool` in a successful transaction, `balance_changes` are the changes of the token balances over the
+/// whole transaction (accounts that aren't included didn't change) and `log_messages` its logs
+pub fn parse_events(
+    program_id: &Pubkey,
+    pool: &Pubkey,
+    pool_token_keys: &[Pubkey; TOKEN_COUNT],
+    transaction: EventTransaction,
+    message: &Message,
+    balance_changes: &HashMap<Pubkey, i128>,
+    log_messages: &[String],
+) -> Vec<PoolEvent> {
+    let lp_supply_events = lp_supply_events(program_id, log_messages);
+    let change = |key: Option<&Pubkey>| key.and_then(|key| balance_changes.get(key)).copied().unwrap_or(0);
+    let mut pool_balance_changes = [0; TOKEN_COUNT];
+    for (balance_change, token_key) in pool_balance_changes.iter_mut().zip(pool_token_keys.iter()) {
+        *balance_change = change(Some(token_key));
+    }
+
+    let mut events = vec![];
+    for (instruction_index, instruction) in message.instructions.iter().enumerate() {
+        let account = |i: usize| {
+            instruction
+                .accounts
+                .get(i)
+                .and_then(|&index| message.account_keys.get(index as usize))
+        };
+        if message.account_keys.get(instruction.program_id_index as usize) != Some(program_id)
+            || account(0) != Some(pool)
+        {
+            continue;
+        }
+        let pool_instruction = match PoolInstruction::<TOKEN_COUNT>::try_from_slice(&instruction.data) {
+            Ok(pool_instruction) => pool_instruction,
+            Err(_) => continue,
+        };
+        //account indices are those of the instruction constructors in instruction.rs
+        let (kind, authority, user_lp_account, governance_fee_account) = match pool_instruction {
+            PoolInstruction::Init { .. } => ("init", account(2 + 2 * TOKEN_COUNT), None, None),
+            PoolInstruction::DeFiInstruction(defi_instruction) => {
+                let (kind, has_lp_account) = match defi_instruction {
+                    DeFiInstruction::Add { .. } => ("add", true),
+                    DeFiInstruction::SwapExactInput { .. } => ("swap_exact_input", false),
+                    DeFiInstruction::SwapExactOutput { .. } => ("swap_exact_output", false),
+                    DeFiInstruction::RemoveUniform { .. } => ("remove_uniform", true),
+                    DeFiInstruction::RemoveExactBurn { .. } => ("remove_exact_burn", true),
+                    DeFiInstruction::RemoveExactOutput { .. } => ("remove_exact_output", true),
+                    //simulations don't change anything
+                    DeFiInstruction::Simulate(_) => continue,
+                };
+                let user_lp_account = if has_lp_account {
+                    account(6 + 2 * TOKEN_COUNT)
+                } else {
+                    None
+                };
+                (
+                    kind,
+                    account(4 + TOKEN_COUNT),
+                    user_lp_account,
+                    account(3 + TOKEN_COUNT),
+                )
+            }
+            PoolInstruction::GovernanceInstruction(governance_instruction) => {
+                let kind = match governance_instruction {
+                    GovernanceInstruction::PrepareFeeChange { .. } => "prepare_fee_change",
+                    GovernanceInstruction::EnactFeeChange {} => "enact_fee_change",
+                    GovernanceInstruction::PrepareGovernanceTransition { .. } => "prepare_governance_transition",
+                    GovernanceInstruction::EnactGovernanceTransition {} => "enact_governance_transition",
+                    GovernanceInstruction::ChangeGovernanceFeeAccount { .. } => "change_governance_fee_account",
+                    GovernanceInstruction::AdjustAmpFactor { .. } => "adjust_amp_factor",
+                    GovernanceInstruction::SetPaused { .. } => "set_paused",
+                    GovernanceInstruction::SetOracleGuard { .. } => "set_oracle_guard",
+                    GovernanceInstruction::SetPegBand { .. } => "set_peg_band",
+                    GovernanceInstruction::SetRateSource { .. } => "set_rate_source",
+                    GovernanceInstruction::SetOracleFallback { .. } => "set_oracle_fallback",
+                    GovernanceInstruction::SetAmpDamping { .. } => "set_amp_damping",
+                    GovernanceInstruction::SetTokenOracle { .. } => "set_token_oracle",
+                    GovernanceInstruction::EnactTokenOracle {} => "enact_token_oracle",
+                    GovernanceInstruction::SetHookPrograms { .. } => "set_hook_programs",
+                    GovernanceInstruction::SetCallerAllowlist { .. } => "set_caller_allowlist",
+                    GovernanceInstruction::SetSunset { .. } => "set_sunset",
+                    GovernanceInstruction::RenounceGovernance { .. } => "renounce_governance",
+                    GovernanceInstruction::MigrateGovernanceFeeAccount { .. } => "migrate_governance_fee_account",
+                    GovernanceInstruction::SetLockFeeShare { .. } => "set_lock_fee_share",
+                    GovernanceInstruction::SetMinLpPosition { .. } => "set_min_lp_position",
+                    GovernanceInstruction::BurnGovernanceFees { .. } => "burn_governance_fees",
+                    GovernanceInstruction::SetLaunchCap { .. } => "set_launch_cap",
+                    GovernanceInstruction::SetUserGate { .. } => "set_user_gate",
+                    GovernanceInstruction::SetUserAllowed { .. } => "set_user_allowed",
+                    GovernanceInstruction::SetRole { .. } => "set_role",
+                    GovernanceInstruction::SetTokenFlags { .. } => "set_token_flags",
+                    GovernanceInstruction::SetDepositorGate { .. } => "set_depositor_gate",
+                    GovernanceInstruction::SetDepositorAllowed { .. } => "set_depositor_allowed",
+                    GovernanceInstruction::SetRfqQuoter { .. } => "set_rfq_quoter",
+                    GovernanceInstruction::SetComplianceProgram { .. } => "set_compliance_program",
+                    GovernanceInstruction::SetTradingSchedule { .. } => "set_trading_schedule",
+                    GovernanceInstruction::SetWithdrawalCooldown { .. } => "set_withdrawal_cooldown",
+                    GovernanceInstruction::SetEmergencyResponders { .. } => "set_emergency_responders",
+                    GovernanceInstruction::SetPriceBand { .. } => "set_price_band",
+                    GovernanceInstruction::SetTokenParticipation { .. } => "set_token_participation",
+                    GovernanceInstruction::SetPegKeeper { .. } => "set_peg_keeper",
+                    GovernanceInstruction::SetYieldAdapter { .. } => "set_yield_adapter",
+                    GovernanceInstruction::SetRepegParams { .. } => "set_repeg_params",
+                    GovernanceInstruction::SetGaugeReward { .. } => "set_gauge_reward",
+                    GovernanceInstruction::StartRebateCampaign { .. } => "start_rebate_campaign",
+                    GovernanceInstruction::ReclaimRebateBudget { .. } => "reclaim_rebate_budget",
+                    GovernanceInstruction::SetGaugeVeProgram { .. } => "set_gauge_ve_program",
+                    GovernanceInstruction::EnactGaugeVeProgram {} => "enact_gauge_ve_program",
+                    GovernanceInstruction::EnactRfqQuoter {} => "enact_rfq_quoter",
+                    GovernanceInstruction::EnactPegKeeper {} => "enact_peg_keeper",
+                    GovernanceInstruction::EnactYieldAdapter {} => "enact_yield_adapter",
+                };
+                let governance_fee_account =
+                    if matches!(governance_instruction, GovernanceInstruction::BurnGovernanceFees { .. }) {
+                        account(2)
+                    } else {
+                        None
+                    };
+                (kind, account(1), None, governance_fee_account)
+            }
+            PoolInstruction::CheckPeg {} => ("check_peg", None, None, None),
+            PoolInstruction::PublishVirtualPrice {} => ("publish_virtual_price", None, None, None),
+            //a routed swap's pools follow its other accounts, so it never passes the check above
+            PoolInstruction::RoutedSwap { .. } => continue,
+            PoolInstruction::CreatePoolTokenAccounts {} => ("create_pool_token_accounts", None, None, None),
+            PoolInstruction::CreatePool { .. } => ("create_pool", account(2 + 2 * TOKEN_COUNT), None, None),
+            PoolInstruction::ClonePoolConfig { .. } => ("clone_pool_config", account(2 + 2 * TOKEN_COUNT), None, None),
+            //starts with the accounts of an add
+            PoolInstruction::MigrateIn { .. } => (
+                "migrate_in",
+                account(4 + TOKEN_COUNT),
+                account(6 + 2 * TOKEN_COUNT),
+                account(3 + TOKEN_COUNT),
+            ),
+            PoolInstruction::RedeemSunset {} => (
+                "redeem_sunset",
+                account(4 + TOKEN_COUNT),
+                account(3 + TOKEN_COUNT),
+                None,
+            ),
+            PoolInstruction::ExportState { .. } => ("export_state", account(1), None, None),
+            PoolInstruction::ImportState { .. } => ("import_state", account(2 + 2 * TOKEN_COUNT), None, None),
+            //the accounts of an init followed by those of an add
+            PoolInstruction::InitAndAdd { .. } => (
+                "init_and_add",
+                account(8 + 3 * TOKEN_COUNT),
+                account(10 + 4 * TOKEN_COUNT),
+                account(7 + 3 * TOKEN_COUNT),
+            ),
+            PoolInstruction::RotateVaults {} => ("rotate_vaults", account(1), None, None),
+            PoolInstruction::LockLp { .. } => ("lock_lp", account(5), account(6), None),
+            PoolInstruction::ClaimLockRewards {} => ("claim_lock_rewards", account(4), account(5), None),
+            PoolInstruction::UnlockLp {} => ("unlock_lp", account(5), account(6), None),
+            PoolInstruction::CheckpointFees {} => ("checkpoint_fees", account(2), None, None),
+            PoolInstruction::SnapshotLpSupply {} => ("snapshot_lp_supply", None, None, None),
+            PoolInstruction::CreateLaunchAllocation {} => ("create_launch_allocation", None, None, None),
+            PoolInstruction::AuthorizeSession { .. } => ("authorize_session", account(2), None, None),
+            PoolInstruction::RevokeSession {} => ("revoke_session", account(2), None, None),
+            PoolInstruction::RfqSwap { .. } => ("rfq_swap", account(2 + TOKEN_COUNT), None, None),
+            PoolInstruction::AnnounceWithdrawal { .. } => ("announce_withdrawal", account(2), None, None),
+            PoolInstruction::ConfirmEmergency {} => ("confirm_emergency", account(1), None, None),
+            PoolInstruction::InitWeighted { .. } => ("init_weighted", account(2 + 2 * TOKEN_COUNT), None, None),
+            PoolInstruction::ProvidePegCredit { .. } => ("provide_peg_credit", account(2 + TOKEN_COUNT), None, None),
+            PoolInstruction::AbsorbPegCredit { .. } => ("absorb_peg_credit", account(2 + TOKEN_COUNT), None, None),
+            PoolInstruction::InitFixedRate { .. } => ("init_fixed_rate", account(2 + 2 * TOKEN_COUNT), None, None),
+            PoolInstruction::DeployYield { .. } => ("deploy_yield", None, None, None),
+            PoolInstruction::RecallYield { .. } => ("recall_yield", None, None, None),
+            PoolInstruction::InitHybrid { .. } => ("init_hybrid", account(2 + 2 * TOKEN_COUNT), None, None),
+            PoolInstruction::Repeg {} => ("repeg", None, None, None),
+            PoolInstruction::FundGauge { .. } => ("fund_gauge", account(1), None, None),
+            PoolInstruction::StakeLp { .. } => ("stake_lp", account(4), account(5), None),
+            PoolInstruction::UnstakeLp { .. } => ("unstake_lp", account(5), account(6), None),
+            PoolInstruction::ClaimGaugeRewards {} => ("claim_gauge_rewards", account(6), None, None),
+            PoolInstruction::JoinRebateCampaign {} => ("join_rebate_campaign", account(3), None, None),
+            PoolInstruction::ClaimRebate {} => ("claim_rebate", account(5), None, None),
+            PoolInstruction::UpdateGaugeBoost {} => ("update_gauge_boost", account(3), None, None),
+        };
+        events.push(PoolEvent {
+            transaction,
+            kind,
+            authority: authority.copied().unwrap_or_default(),
+            pool_balance_changes,
+            user_lp_change: change(user_lp_account),
+            governance_fee_change: change(governance_fee_account),
+            lp_supply_event: lp_supply_events.get(&instruction_index).copied(),
+        });
+    }
+    events
+}
+
+//the lp supply events that the program logged itself (rather than in a cpi call) by the index of the top-level
+// instruction. the runtime logs `Program <id> invoke [<depth>]` when a program is called and `Program <id> success`
+// resp. `Program <id> failed: <error>` when it returns
+fn lp_supply_events(program_id: &Pubkey, log_messages: &[String]) -> HashMap<usize, LpSupplyEvent> {
+    let program_id = program_id.to_string();
+    let mut events = HashMap::new();
+    let mut invoked_programs = vec![];
+    let mut instruction_index = None;
+    for log in log_messages {
+        //program logs (`Program log: ...`) can't be mistaken for these since program ids don't contain colons
+        let words: Vec<&str> = log.split(' ').collect();
+        match words.as_slice() {
+            ["Program", program, "invoke", depth] if !program.ends_with(':') => {
+                if *depth == "[1]" {
+                    instruction_index = Some(instruction_index.map_or(0, |index| index + 1));
+                }
+                invoked_programs.push(*program);
+            }
+            ["Program", program, "success"] | ["Program", program, "failed:", ..] if !program.ends_with(':') => {
+                invoked_programs.pop();
+            }
+            _ => {
+                if let (Some(index), [program]) = (instruction_index, invoked_programs.as_slice()) {
+                    if *program == program_id {
+                        if let Some(event) = LpSupplyEvent::from_log(log) {
+                            events.insert(index, event);
+                        }
+                    }
+                }
+            }
+        }
+    }
+    events
+}
+
+//the balance change of every token account whose balance is recorded before or after the transaction
+fn balance_changes(
+    message: &Message,
+    pre_balances: Vec<UiTransactionTokenBalance>,
+    post_balances: Vec<UiTransactionTokenBalance>,
+) -> Option<HashMap<Pubkey, i128>> {
+    let mut changes = HashMap::new();
+    for (balances, sign) in [(pre_balances, -1), (post_balances, 1)] {
+        for balance in balances {
+            let key = *message.account_keys.get(balance.account_index as usize)?;
+            let amount = balance.ui_token_amount.amount.parse::<u64>().ok()?;
+            *changes.entry(key).or_insert(0) += sign * amount as i128;
+        }
+    }
+    Some(changes)
+}
+
+#[cfg(all(test, not(feature = "test-bpf")))]
+mod tests {
+    use super::*;
+    use crate::{
+        decimal::DecimalU64,
+        instruction::{create_defi_ix, create_governance_ix},
+    };
+
+    #[test]
+    fn parse_add() {
+        let (program_id, pool, authority) = (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+        let pool_token_keys = [(); TOKEN_COUNT].map(|_| Pubkey::new_unique());
+        let user_token_keys = [(); TOKEN_COUNT].map(|_| Pubkey::new_unique());
+        let (lp_mint, governance_fee_account, user) =
+            (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+        let user_lp_account = Pubkey::new_unique();
+        let add = create_defi_ix(
+            DeFiInstruction::Add {
+                input_amounts: [10; TOKEN_COUNT],
+                minimum_mint_amount: 0,
+            },
+            &program_id,
+            &pool,
+            &authority,
+            &pool_token_keys,
+            &lp_mint,
+            &governance_fee_account,
+            &user,
+            &user_token_keys,
+            &spl_token::id(),
+            Some(&user_lp_account),
+        )
+        .unwrap();
+        let prepare_fee = create_governance_ix::<TOKEN_COUNT>(
+            GovernanceInstruction::PrepareFeeChange {
+                lp_fee: DecimalU64::new(4, 4).unwrap(),
+                governance_fee: DecimalU64::from(0),
+            },
+            &program_id,
+            &pool,
+            &user,
+            None,
+        )
+        .unwrap();
+        //instructions of other pools are skipped
+        let other_pool = create_governance_ix::<TOKEN_COUNT>(
+            GovernanceInstruction::EnactFeeChange {},
+            &program_id,
+            &Pubkey::new_unique(),
+            &user,
+            None,
+        )
+        .unwrap();
+        let message = Message::new(&[add, prepare_fee, other_pool], Some(&user));
+
+        let mut changes = HashMap::new();
+        changes.insert(pool_token_keys[0], 10);
+        changes.insert(user_lp_account, 60);
+        changes.insert(governance_fee_account, 1);
+        let transaction = EventTransaction {
+            signature: Signature::default(),
+            slot: 5,
+            block_time: None,
+        };
+        let logs = [
+            format!("Program {} invoke [1]", program_id),
+            //only the pool's own logs count
+            format!("Program {} invoke [2]", spl_token::id()),
+            "Program log: lp supply: 1 user 1 governance 0".to_string(),
+            format!("Program {} success", spl_token::id()),
+            "Program log: lp supply: 1060 user 59 governance 1".to_string(),
+            format!("Program {} consumed 5000 of 200000 compute units", program_id),
+            format!("Program {} success", program_id),
+            format!("Program {} invoke [1]", program_id),
+            format!("Program {} success", program_id),
+            format!("Program {} invoke [1]", program_id),
+            format!("Program {} failed: custom program error: 0x1", program_id),
+        ];
+        let events = parse_events(
+            &program_id,
+            &pool,
+            &pool_token_keys,
+            transaction,
+            &message,
+            &changes,
+            &logs,
+        );
+
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].kind, "add");
+        assert_eq!(events[0].authority, user);
+        assert_eq!(events[0].pool_balance_changes[0], 10);
+        assert_eq!(events[0].pool_balance_changes[1], 0);
+        assert_eq!(events[0].user_lp_change, 60);
+        assert_eq!(events[0].governance_fee_change, 1);
+        assert_eq!(
+            events[0].lp_supply_event,
+            Some(LpSupplyEvent {
+                user_lp_change: 59,
+                governance_mint_amount: 1,
+                lp_supply: 1060,
+            })
+        );
+        assert_eq!(events[1].kind, "prepare_fee_change");
+        assert_eq!(events[1].authority, user);
+        assert_eq!(events[1].user_lp_change, 0);
+        assert_eq!(events[1].governance_fee_change, 0);
+        assert_eq!(events[1].lp_supply_event, None);
+    }
+}
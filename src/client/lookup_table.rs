@@ -0,0 +1,432 @@
+//address lookup tables and the v0 transactions that load accounts from them
+//
+//a legacy transaction lists every account it uses with its full key, which routes through six token pools outgrow
+// quickly. v0 transactions can load accounts from lookup tables by a one byte index instead. the pinned sdk predates
+// both, so the lookup table program's instructions and the v0 message format are encoded here
+
+use super::{ClientError, ClientResult};
+use serde_json::json;
+use solana_client::{rpc_client::RpcClient, rpc_request::RpcRequest};
+use solana_program::{
+    clock::Slot,
+    hash::Hash,
+    instruction::{AccountMeta, Instruction},
+    program_error::ProgramError,
+    pubkey::Pubkey,
+    system_program,
+};
+use solana_sdk::{
+    signature::{Signature, Signer},
+    signer::SignerError,
+};
+use std::str::FromStr;
+
+/// The address lookup table program
+pub const ADDRESS_LOOKUP_TABLE_PROGRAM_ID: &str = "AddressLookupTab1e1111111111111111111111111";
+/// How many addresses `extend_lookup_table_ix`s add at most, so that each still fits a transaction with its table's
+/// creation
+pub const MAX_EXTEND_ADDRESSES: usize = 20;
+const CREATE_LOOKUP_TABLE: u32 = 0;
+const EXTEND_LOOKUP_TABLE: u32 = 2;
+//the table's metadata, which its addresses follow
+const LOOKUP_TABLE_META_SIZE: usize = 56;
+const LOOKUP_TABLE_DISCRIMINATOR: u32 = 1;
+//the first byte of versioned messages, legacy messages start with their signature count which is below it
+const MESSAGE_VERSION_PREFIX: u8 = 0x80;
+const MAX_ACCOUNTS: usize = 256;
+
+fn program_id() -> Pubkey {
+    Pubkey::from_str(ADDRESS_LOOKUP_TABLE_PROGRAM_ID).unwrap()
+}
+
+/// The address of the lookup table that `authority` creates at `recent_slot` and its bump seed
+pub fn find_lookup_table_address(authority: &Pubkey, recent_slot: Slot) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[authority.as_ref(), &recent_slot.to_le_bytes()], &program_id())
+}
+
+/// Creates a lookup table of `authority`, which `payer` pays the rent of, and returns it along with its address.
+/// `recent_slot` has to be one of the last 150 or so slots (e.g. `RpcClient::get_slot`) and picks the address
+pub fn create_lookup_table_ix(authority: &Pubkey, payer: &Pubkey, recent_slot: Slot) -> (Instruction, Pubkey) {
+    let (lookup_table, bump_seed) = find_lookup_table_address(authority, recent_slot);
+    let mut data = CREATE_LOOKUP_TABLE.to_le_bytes().to_vec();
+    data.extend_from_slice(&recent_slot.to_le_bytes());
+    data.push(bump_seed);
+    let accounts = vec![
+        AccountMeta::new(lookup_table, false),
+        //newer runtimes don't require the authority's signature to create a table, older ones do
+        AccountMeta::new_readonly(*authority, true),
+        AccountMeta::new(*payer, true),
+        AccountMeta::new_readonly(system_program::id(), false),
+    ];
+    (Instruction::new_with_bytes(program_id(), &data, accounts), lookup_table)
+}
+
+/// Appends `new_addresses` (at most `MAX_EXTEND_ADDRESSES` per transaction) to `lookup_table`, `payer` pays for the
+/// extra rent. Transactions can load them from the slot after the one the table was extended in
+pub fn extend_lookup_table_ix(
+    lookup_table: &Pubkey,
+    authority: &Pubkey,
+    payer: &Pubkey,
+    new_addresses: &[Pubkey],
+) -> Instruction {
+    let mut data = EXTEND_LOOKUP_TABLE.to_le_bytes().to_vec();
+    data.extend_from_slice(&(new_addresses.len() as u64).to_le_bytes());
+    for address in new_addresses {
+        data.extend_from_slice(address.as_ref());
+    }
+    let accounts = vec![
+        AccountMeta::new(*lookup_table, false),
+        AccountMeta::new_readonly(*authority, true),
+        AccountMeta::new(*payer, true),
+        AccountMeta::new_readonly(system_program::id(), false),
+    ];
+    Instruction::new_with_bytes(program_id(), &data, accounts)
+}
+
+/// The `extend_lookup_table_ix`s that add those of `keys` that `lookup_table` doesn't hold yet, one per transaction
+pub fn extend_lookup_table_ixs(
+    lookup_table: &LookupTable,
+    authority: &Pubkey,
+    payer: &Pubkey,
+    keys: &[Pubkey],
+) -> Vec<Instruction> {
+    let mut missing: Vec<Pubkey> = vec![];
+    for key in keys {
+        if !lookup_table.addresses.contains(key) && !missing.contains(key) {
+            missing.push(*key);
+        }
+    }
+    missing
+        .chunks(MAX_EXTEND_ADDRESSES)
+        .map(|chunk| extend_lookup_table_ix(&lookup_table.key, authority, payer, chunk))
+        .collect()
+}
+
+/// A lookup table's address and the addresses it holds
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LookupTable {
+    pub key: Pubkey,
+    pub addresses: Vec<Pubkey>,
+}
+
+impl LookupTable {
+    /// Decodes the data of the lookup table account at `key`
+    pub fn unpack(key: Pubkey, data: &[u8]) -> ClientResult<Self> {
+        if data.len() < LOOKUP_TABLE_META_SIZE
+            || data[..4] != LOOKUP_TABLE_DISCRIMINATOR.to_le_bytes()
+            || (data.len() - LOOKUP_TABLE_META_SIZE) % 32 != 0
+        {
+            return Err(ClientError::InvalidAccountData(key));
+        }
+        let addresses = data[LOOKUP_TABLE_META_SIZE..].chunks(32).map(Pubkey::new).collect();
+        Ok(Self { key, addresses })
+    }
+
+    pub fn fetch(rpc_client: &RpcClient, key: Pubkey) -> ClientResult<Self> {
+        let account = rpc_client
+            .get_account_with_commitment(&key, rpc_client.commitment())?
+            .value
+            .ok_or(ClientError::AccountNotFound(key))?;
+        if account.owner != program_id() {
+            return Err(ClientError::InvalidAccountData(key));
+        }
+        Self::unpack(key, &account.data)
+    }
+}
+
+/// A v0 message, which loads the accounts of its instructions that lookup tables hold from them
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct V0Message {
+    /// The accounts that aren't loaded from a lookup table, the signers first
+    pub static_keys: Vec<Pubkey>,
+    pub num_required_signatures: u8,
+    /// The serialized message, which is what the signers sign
+    pub data: Vec<u8>,
+}
+
+//an account of the message with what its instructions need of it
+struct KeyMeta {
+    key: Pubkey,
+    is_signer: bool,
+    is_writable: bool,
+    is_invoked: bool,
+}
+
+impl V0Message {
+    /// Compiles `instructions` into a message paid for by `payer`. Every account that one of `lookup_tables` holds is
+    /// loaded from the first one of them that does, except for signers and programs, which the runtime only takes
+    /// from the message itself
+    pub fn compile(
+        payer: &Pubkey,
+        instructions: &[Instruction],
+        lookup_tables: &[LookupTable],
+        recent_blockhash: &Hash,
+    ) -> ClientResult<Self> {
+        let mut metas = vec![KeyMeta {
+            key: *payer,
+            is_signer: true,
+            is_writable: true,
+            is_invoked: false,
+        }];
+        let mut add = |key: &Pubkey, is_signer: bool, is_writable: bool, is_invoked: bool| {
+            if let Some(meta) = metas.iter_mut().find(|meta| meta.key == *key) {
+                meta.is_signer |= is_signer;
+                meta.is_writable |= is_writable;
+                meta.is_invoked |= is_invoked;
+            } else {
+                metas.push(KeyMeta {
+                    key: *key,
+                    is_signer,
+                    is_writable,
+                    is_invoked,
+                });
+            }
+        };
+        for instruction in instructions {
+            add(&instruction.program_id, false, false, true);
+            for account in &instruction.accounts {
+                add(&account.pubkey, account.is_signer, account.is_writable, false);
+            }
+        }
+
+        //the writable and the readonly accounts loaded from each table, as indexes into the table
+        let mut lookups = vec![(vec![], vec![]); lookup_tables.len()];
+        let mut loaded = vec![(vec![], vec![]); lookup_tables.len()];
+        let mut static_metas = vec![];
+        for meta in metas {
+            let lookup = (!meta.is_signer && !meta.is_invoked)
+                .then(|| {
+                    lookup_tables.iter().enumerate().find_map(|(table, lookup_table)| {
+                        let index = lookup_table.addresses.iter().position(|address| *address == meta.key)?;
+                        Some((table, index as u8))
+                    })
+                })
+                .flatten();
+            match lookup {
+                Some((table, index)) if meta.is_writable => {
+                    lookups[table].0.push(index);
+                    loaded[table].0.push(meta.key);
+                }
+                Some((table, index)) => {
+                    lookups[table].1.push(index);
+                    loaded[table].1.push(meta.key);
+                }
+                None => static_metas.push(meta),
+            }
+        }
+        //signers go first, writable accounts before readonly ones. the sort is stable, so the payer stays first
+        static_metas.sort_by_key(|meta| (!meta.is_signer, !meta.is_writable));
+        let num_required_signatures = static_metas.iter().filter(|meta| meta.is_signer).count();
+        let num_readonly_signed = static_metas
+            .iter()
+            .filter(|meta| meta.is_signer && !meta.is_writable)
+            .count();
+        let num_readonly_unsigned = static_metas
+            .iter()
+            .filter(|meta| !meta.is_signer && !meta.is_writable)
+            .count();
+        let static_keys: Vec<Pubkey> = static_metas.iter().map(|meta| meta.key).collect();
+        //instructions index the static accounts, then the writable loaded ones and then the readonly loaded ones
+        let account_keys: Vec<Pubkey> = static_keys
+            .iter()
+            .chain(loaded.iter().flat_map(|(writable, _)| writable))
+            .chain(loaded.iter().flat_map(|(_, readonly)| readonly))
+            .copied()
+            .collect();
+        if account_keys.len() > MAX_ACCOUNTS {
+            return Err(ClientError::Instruction(ProgramError::InvalidArgument));
+        }
+        let index_of = |key: &Pubkey| account_keys.iter().position(|account_key| account_key == key).unwrap() as u8;
+
+        let mut data = vec![
+            MESSAGE_VERSION_PREFIX,
+            num_required_signatures as u8,
+            num_readonly_signed as u8,
+            num_readonly_unsigned as u8,
+        ];
+        encode_length(&mut data, static_keys.len());
+        for key in &static_keys {
+            data.extend_from_slice(key.as_ref());
+        }
+        data.extend_from_slice(recent_blockhash.as_ref());
+        encode_length(&mut data, instructions.len());
+        for instruction in instructions {
+            data.push(index_of(&instruction.program_id));
+            encode_length(&mut data, instruction.accounts.len());
+            data.extend(instruction.accounts.iter().map(|account| index_of(&account.pubkey)));
+            encode_length(&mut data, instruction.data.len());
+            data.extend_from_slice(&instruction.data);
+        }
+        let used_tables: Vec<_> = lookup_tables
+            .iter()
+            .zip(lookups)
+            .filter(|(_, (writable, readonly))| !writable.is_empty() || !readonly.is_empty())
+            .collect();
+        encode_length(&mut data, used_tables.len());
+        for (lookup_table, (writable, readonly)) in used_tables {
+            data.extend_from_slice(lookup_table.key.as_ref());
+            encode_length(&mut data, writable.len());
+            data.extend_from_slice(&writable);
+            encode_length(&mut data, readonly.len());
+            data.extend_from_slice(&readonly);
+        }
+        Ok(Self {
+            static_keys,
+            num_required_signatures: num_required_signatures as u8,
+            data,
+        })
+    }
+
+    /// The serialized transaction of the message signed by `signers`, which have to include all of its signers
+    pub fn sign(&self, signers: &[&dyn Signer]) -> ClientResult<Vec<u8>> {
+        let mut transaction = vec![];
+        encode_length(&mut transaction, self.num_required_signatures as usize);
+        for key in &self.static_keys[..self.num_required_signatures as usize] {
+            let signer = signers
+                .iter()
+                .find(|signer| signer.pubkey() == *key)
+                .ok_or(SignerError::NotEnoughSigners)?;
+            transaction.extend_from_slice(signer.try_sign_message(&self.data)?.as_ref());
+        }
+        transaction.extend_from_slice(&self.data);
+        Ok(transaction)
+    }
+}
+
+/// Sends `instructions` as a v0 transaction that loads what it can from `lookup_tables` (see `V0Message::compile`)
+/// through the RPC node, without waiting for its confirmation
+pub fn send_v0_transaction(
+    rpc_client: &RpcClient,
+    payer: &dyn Signer,
+    instructions: &[Instruction],
+    lookup_tables: &[LookupTable],
+    signers: &[&dyn Signer],
+) -> ClientResult<Signature> {
+    let (blockhash, _) = rpc_client.get_recent_blockhash()?;
+    let message = V0Message::compile(&payer.pubkey(), instructions, lookup_tables, &blockhash)?;
+    let mut all_signers = vec![payer];
+    all_signers.extend_from_slice(signers);
+    let transaction = message.sign(&all_signers)?;
+    let encoded = bs58::encode(&transaction).into_string();
+    rpc_client.send::<String>(RpcRequest::SendTransaction, json!([encoded, { "encoding": "base58" }]))?;
+    //the payer's signature is the transaction's id
+    Ok(Signature::new(&transaction[1..65]))
+}
+
+//the compact length prefix of the wire format: 7 bits per byte, low bits first, the top bit set on all but the last
+fn encode_length(data: &mut Vec<u8>, mut length: usize) {
+    loop {
+        let byte = (length & 0x7f) as u8;
+        length >>= 7;
+        if length == 0 {
+            data.push(byte);
+            return;
+        }
+        data.push(byte | 0x80);
+    }
+}
+
+#[cfg(all(test, not(feature = "test-bpf")))]
+mod tests {
+    use super::*;
+    use solana_sdk::signature::Keypair;
+
+    #[test]
+    fn length() {
+        let encode = |length| {
+            let mut data = vec![];
+            encode_length(&mut data, length);
+            data
+        };
+        assert_eq!(encode(0), [0]);
+        assert_eq!(encode(0x7f), [0x7f]);
+        assert_eq!(encode(0x80), [0x80, 0x01]);
+        assert_eq!(encode(0x3fff), [0xff, 0x7f]);
+        assert_eq!(encode(0x4000), [0x80, 0x80, 0x01]);
+    }
+
+    #[test]
+    fn lookup_table_instructions() {
+        let authority = Pubkey::new_unique();
+        let (create_ix, lookup_table) = create_lookup_table_ix(&authority, &authority, 42);
+        let bump_seed = find_lookup_table_address(&authority, 42).1;
+        assert_eq!(create_ix.accounts[0].pubkey, lookup_table);
+        assert_eq!(
+            create_ix.data,
+            [&[0, 0, 0, 0][..], &42u64.to_le_bytes(), &[bump_seed]].concat()
+        );
+
+        let mut data = vec![0; LOOKUP_TABLE_META_SIZE];
+        data[0] = 1;
+        let held = Pubkey::new_unique();
+        data.extend_from_slice(held.as_ref());
+        let table = LookupTable::unpack(lookup_table, &data).unwrap();
+        assert_eq!(table.addresses, vec![held]);
+        assert!(LookupTable::unpack(lookup_table, &data[..LOOKUP_TABLE_META_SIZE + 1]).is_err());
+
+        //only the keys the table lacks are added, in chunks that fit a transaction
+        let keys: Vec<Pubkey> = (0..MAX_EXTEND_ADDRESSES + 1).map(|_| Pubkey::new_unique()).collect();
+        let ixs = extend_lookup_table_ixs(&table, &authority, &authority, &[&[held][..], &keys, &keys].concat());
+        assert_eq!(ixs.len(), 2);
+        assert_eq!(
+            ixs[0].data[..12],
+            [&[2, 0, 0, 0][..], &(MAX_EXTEND_ADDRESSES as u64).to_le_bytes()].concat()[..]
+        );
+        assert_eq!(
+            ixs[1].data,
+            [
+                &[2, 0, 0, 0][..],
+                &1u64.to_le_bytes(),
+                keys[MAX_EXTEND_ADDRESSES].as_ref()
+            ]
+            .concat()
+        );
+    }
+
+    #[test]
+    fn v0_message() {
+        let payer = Keypair::new();
+        let program = Pubkey::new_unique();
+        let (pool, vault, mint, other) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        let table = LookupTable {
+            key: Pubkey::new_unique(),
+            addresses: vec![mint, program, vault, pool],
+        };
+        let instruction = Instruction::new_with_bytes(
+            program,
+            &[7],
+            vec![
+                AccountMeta::new(pool, false),
+                AccountMeta::new_readonly(mint, false),
+                AccountMeta::new(other, false),
+                AccountMeta::new_readonly(payer.pubkey(), true),
+            ],
+        );
+        let blockhash = Hash::new_unique();
+        let message = V0Message::compile(&payer.pubkey(), &[instruction], &[table.clone()], &blockhash).unwrap();
+        //the program can't be loaded from the table
+        assert_eq!(message.static_keys, vec![payer.pubkey(), other, program]);
+        assert_eq!(message.num_required_signatures, 1);
+        let mut expected = vec![0x80, 1, 0, 1, 3];
+        for key in &message.static_keys {
+            expected.extend_from_slice(key.as_ref());
+        }
+        expected.extend_from_slice(blockhash.as_ref());
+        //pool (writable) and mint (readonly) come after the static accounts
+        expected.extend_from_slice(&[1, 2, 4, 3, 4, 1, 0, 1, 7, 1]);
+        expected.extend_from_slice(table.key.as_ref());
+        expected.extend_from_slice(&[1, 3, 1, 0]);
+        assert_eq!(message.data, expected);
+
+        let transaction = message.sign(&[&payer]).unwrap();
+        assert_eq!(transaction[0], 1);
+        assert_eq!(Signature::new(&transaction[1..65]), payer.sign_message(&message.data));
+        assert_eq!(transaction[65..], message.data[..]);
+        assert!(message.sign(&[&Keypair::new()]).is_err());
+    }
+}
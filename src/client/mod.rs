@@ -0,0 +1,113 @@
+//off-chain helpers for interacting with deployed pools
+//only compiled with the `client` feature so none of this ends up in the on-chain program
+
+pub mod amm;
+pub mod arb;
+pub mod capture;
+pub mod deploy;
+pub mod execution;
+pub mod explain;
+pub mod history;
+pub mod lookup_table;
+pub mod otc;
+pub mod pool;
+pub mod route_cache;
+pub mod route_plan;
+pub mod router;
+pub mod snapshot;
+#[cfg(feature = "token-swap-venue")]
+pub mod token_swap_venue;
+pub mod venue;
+use crate::{decimal::DecimalU64, error::PoolError, invariant::InvariantError};
+use solana_program::{program_error::ProgramError, pubkey::Pubkey};
+use solana_sdk::{signature::Signature, signer::SignerError, transaction::TransactionError};
+use std::io;
+use thiserror::Error;
+
+pub use explain::{explain_error, PoolErrorExplanation};
+
+type RpcClientError = solana_client::client_error::ClientError;
+type PubsubClientError = solana_client::pubsub_client::PubsubClientError;
+
+#[derive(Error, Debug)]
+pub enum ClientError {
+    #[error("RPC request failed: {0}")]
+    Rpc(#[from] RpcClientError),
+    #[error("Websocket subscription failed: {0}")]
+    Pubsub(#[from] PubsubClientError),
+    #[error("Subscription to account {0} was closed")]
+    SubscriptionClosed(Pubkey),
+    #[error("Price impact of {:.4}% exceeds the maximum", .0 * 100.0)]
+    PriceImpactExceeded(f64),
+    #[error("Transaction failed: {0}")]
+    TransactionFailed(TransactionError),
+    #[error("Transaction {0} expired before it was confirmed")]
+    TransactionExpired(Signature),
+    #[error("Account {0} does not exist")]
+    AccountNotFound(Pubkey),
+    #[error("Account {0} could not be decoded")]
+    InvalidAccountData(Pubkey),
+    #[error("Invalid instruction: {0}")]
+    Instruction(#[from] ProgramError),
+    #[error("Signing failed: {0}")]
+    Signer(#[from] SignerError),
+    #[error("Transaction {0} could not be decoded")]
+    InvalidTransaction(String),
+    #[error("Simulation failed: {0}")]
+    SimulationFailed(TransactionError),
+    #[error("Accounts changed while capturing, retry")]
+    StateChanged,
+    #[error("{0}")]
+    Pool(#[from] PoolError),
+    #[error("Pool math failed: {0}")]
+    Invariant(#[from] InvariantError),
+    #[error("I/O error: {0}")]
+    Io(#[from] io::Error),
+    #[error("Invalid deploy manifest: {0}")]
+    InvalidManifest(String),
+    #[error("Invalid route plan: {0}")]
+    InvalidRoutePlan(String),
+    #[error("Pool {0} exists already with a different governance")]
+    ConflictingPool(Pubkey),
+    #[error("No pool or pair of pools swaps {0} for {1}")]
+    NoRoute(Pubkey, Pubkey),
+}
+
+pub type ClientResult<T> = Result<T, ClientError>;
+
+/// Parses decimal numbers such as `1000` or `0.0003`
+pub fn parse_decimal(s: &str) -> Result<DecimalU64, String> {
+    let invalid = || format!("invalid decimal number: {}", s);
+    let (integer, fraction) = match s.find('.') {
+        Some(index) => (&s[..index], &s[index + 1..]),
+        None => (s, ""),
+    };
+    if integer.is_empty() || !(integer.chars().chain(fraction.chars())).all(|c| c.is_ascii_digit()) {
+        return Err(invalid());
+    }
+    let decimals = fraction.len();
+    let value = format!("{}{}", integer, fraction)
+        .parse::<u64>()
+        .map_err(|_| invalid())?;
+    if decimals > u8::MAX as usize {
+        return Err(invalid());
+    }
+    DecimalU64::new(value, decimals as u8).map_err(|_| invalid())
+}
+
+/// Parses fees given in basis points (`4bps`), as percentage (`0.04%`) or as decimal number (`0.0004`)
+pub fn parse_fee(s: &str) -> Result<DecimalU64, String> {
+    let (number, shift) = if let Some(number) = s.strip_suffix("bps").or_else(|| s.strip_suffix("bp")) {
+        (number, 4)
+    } else if let Some(number) = s.strip_suffix('%') {
+        (number, 2)
+    } else {
+        (s, 0)
+    };
+    let decimal = parse_decimal(number)?;
+    decimal
+        .get_decimals()
+        .checked_add(shift)
+        .and_then(|decimals| DecimalU64::new(decimal.get_raw(), decimals).ok())
+        .ok_or_else(|| format!("invalid fee: {}", s))
+}
@@ -0,0 +1,113 @@
+//over-the-counter trades of lp tokens between two parties, with a price reference taken from the pool
+//
+//a trade is a single transaction of two transfers, the seller's lp tokens to the buyer and the buyer's payment to the
+// seller, which both parties sign, so either both transfers happen or none does and no escrow account is needed. one
+// party builds it (e.g. with `unsigned_transaction`, partially signed by themselves) and hands it to the other one to
+// sign and submit
+
+use super::{pool::PoolClient, snapshot::PoolSnapshot, ClientError, ClientResult};
+use crate::{
+    decimal::{U128, U256},
+    error::PoolError,
+    quote::{from_equalized, from_rated},
+    TOKEN_COUNT,
+};
+use solana_program::{instruction::Instruction, program_error::ProgramError, pubkey::Pubkey};
+
+/// One side of a trade: `amount` moves from `source` to `destination`, authorized by `owner`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OtcTransfer {
+    pub token_program: Pubkey,
+    pub source: Pubkey,
+    pub destination: Pubkey,
+    pub owner: Pubkey,
+    pub amount: u64,
+}
+
+impl OtcTransfer {
+    fn ix(&self) -> Result<Instruction, ProgramError> {
+        spl_token::instruction::transfer(
+            &self.token_program,
+            &self.source,
+            &self.destination,
+            &self.owner,
+            &[],
+            self.amount,
+        )
+    }
+}
+
+/// Suggested prices of lp tokens in one of the pool's tokens
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LpPrice {
+    /// The lp tokens' share of the pool's depth, i.e. their value at the virtual price, in the payment token
+    pub fair_amount: u64,
+    /// What burning the lp tokens for the payment token alone (`RemoveExactBurn`) would pay out, i.e. the least a
+    /// seller who could just as well remove them should accept
+    pub redeem_amount: u64,
+}
+
+impl PoolSnapshot {
+    /// Prices `lp_amount` lp tokens in the pool's token at `payment_token_index`
+    pub fn suggest_lp_price(&self, lp_amount: u64, payment_token_index: usize) -> ClientResult<LpPrice> {
+        if payment_token_index >= TOKEN_COUNT {
+            return Err(ProgramError::InvalidArgument.into());
+        }
+        if self.lp_supply == 0 {
+            return Err(PoolError::PoolIsEmpty.into());
+        }
+        if lp_amount > self.lp_supply {
+            return Err(ProgramError::InsufficientFunds.into());
+        }
+        //the depth is in equalized units valued at the tokens' rates, lp amounts cancel out so they need no equalizing
+        let equalized_value = U256::from(self.depth.as_u128()) * U256::from(lp_amount) / U256::from(self.lp_supply);
+        let fair_amount = from_equalized(
+            from_rated(
+                U128::from(equalized_value.as_u128()),
+                self.rates[payment_token_index],
+                false,
+            ),
+            self.state.token_decimal_equalizers[payment_token_index],
+            false,
+        );
+        let redeem_amount = self.quoter().remove_exact_burn(lp_amount, payment_token_index)?.amount;
+        Ok(LpPrice {
+            fair_amount,
+            redeem_amount,
+        })
+    }
+}
+
+/// The instructions of a trade of `lp_transfer` for `payment_transfer`, to be sent within a single transaction that
+/// the owners of both sides sign
+pub fn create_otc_ixs(lp_transfer: &OtcTransfer, payment_transfer: &OtcTransfer) -> ClientResult<Vec<Instruction>> {
+    if lp_transfer.owner == payment_transfer.owner {
+        return Err(ClientError::Instruction(ProgramError::InvalidArgument));
+    }
+    Ok(vec![lp_transfer.ix()?, payment_transfer.ix()?])
+}
+
+impl PoolClient {
+    /// The instructions of a trade of the seller's `lp_amount` lp tokens for the buyer's `payment_transfer`, fails for
+    /// pools with a non-transferable lp token
+    pub fn lp_otc_ixs(
+        &self,
+        seller: &Pubkey,
+        seller_lp_account: &Pubkey,
+        buyer_lp_account: &Pubkey,
+        lp_amount: u64,
+        payment_transfer: &OtcTransfer,
+    ) -> ClientResult<Vec<Instruction>> {
+        if self.state.lp_non_transferable {
+            return Err(PoolError::LpNonTransferable.into());
+        }
+        let lp_transfer = OtcTransfer {
+            token_program: self.state.lp_token_program_id(),
+            source: *seller_lp_account,
+            destination: *buyer_lp_account,
+            owner: *seller,
+            amount: lp_amount,
+        };
+        create_otc_ixs(&lp_transfer, payment_transfer)
+    }
+}
@@ -0,0 +1,1029 @@
+//typed access to a deployed pool: reading its state and building instructions with all of the pool's accounts filled in
+
+use super::{
+    lookup_table::{create_lookup_table_ix, extend_lookup_table_ixs, LookupTable},
+    ClientError, ClientResult,
+};
+use crate::{
+    error::PoolError,
+    instruction::{
+        create_absorb_peg_credit_ix, create_add_gauge_reward_ix, create_announce_withdrawal_ix,
+        create_authorize_session_ix, create_burn_governance_fees_ix, create_check_peg_ix, create_checkpoint_fees_ix,
+        create_claim_rebate_ix, create_clone_pool_config_ix, create_confirm_emergency_ix, create_defi_ix,
+        create_deploy_yield_ix, create_governance_ix, create_join_rebate_campaign_ix, create_launch_allocation_ix,
+        create_pool_token_accounts_ix, create_provide_peg_credit_ix, create_publish_virtual_price_ix,
+        create_recall_yield_ix, create_reclaim_rebate_budget_ix, create_redeem_sunset_ix, create_repeg_ix,
+        create_retire_gauge_reward_ix, create_revoke_session_ix, create_rfq_signature_ix, create_rfq_swap_ix,
+        create_set_base_pool_rate_ix, create_set_depositor_allowed_ix, create_set_user_allowed_ix,
+        create_snapshot_lp_supply_ix, create_start_rebate_campaign_ix, create_update_gauge_boost_ix,
+        find_compliance_address, find_depositor_gate_account, find_fee_checkpoint_address, find_gauge_address,
+        find_launch_allocation_address, find_lp_supply_address, find_rebate_address, find_rebate_campaign_address,
+        find_session_address, find_user_gate_account, find_virtual_price_address, find_withdrawal_announcement_address,
+        record_swap_rebate, sign_swap_with_session, DeFiInstruction, GovernanceInstruction, RfqQuote,
+    },
+    oracle::{load_rate, RATE_ONE},
+    state::{
+        pool_token_count, FeeCheckpoint, Gauge, LaunchAllocation, LpSupplySnapshots, PoolState, Rebate, RebateCampaign,
+        Session, VirtualPriceState, WithdrawalAnnouncement,
+    },
+    token_2022, TOKEN_COUNT,
+};
+use borsh::BorshDeserialize;
+use solana_client::{rpc_client::RpcClient, rpc_config::RpcSimulateTransactionConfig};
+use solana_program::{
+    clock::{Clock, Slot, UnixTimestamp},
+    instruction::{AccountMeta, Instruction},
+    program_pack::{IsInitialized, Pack},
+    pubkey::Pubkey,
+    sysvar,
+};
+use solana_sdk::{
+    account::{from_account, Account},
+    message::Message,
+    signature::{Signature, Signer},
+    transaction::Transaction,
+};
+use spl_token::state::{Account as TokenState, Mint as MintState};
+
+pub struct PoolClient {
+    pub rpc_client: RpcClient,
+    pub program_id: Pubkey,
+    pub pool: Pubkey,
+    pub state: PoolState<TOKEN_COUNT>,
+}
+
+impl PoolClient {
+    /// Fetches the state of `pool`, the program id is taken from the pool account's owner
+    pub fn load(rpc_client: RpcClient, pool: Pubkey) -> ClientResult<Self> {
+        let (program_id, state) = fetch_pool_state(&rpc_client, &pool)?;
+        Ok(Self {
+            rpc_client,
+            program_id,
+            pool,
+            state,
+        })
+    }
+
+    pub fn reload(&mut self) -> ClientResult<()> {
+        self.state = fetch_pool_state(&self.rpc_client, &self.pool)?.1;
+        Ok(())
+    }
+
+    pub fn authority(&self) -> Pubkey {
+        Pubkey::create_program_address(&[&self.pool.to_bytes()[..32], &[self.state.nonce]], &self.program_id).unwrap()
+    }
+
+    pub fn defi_ix(
+        &self,
+        defi_instruction: DeFiInstruction<TOKEN_COUNT>,
+        user_authority: &Pubkey,
+        user_token_accounts: &[Pubkey; TOKEN_COUNT],
+        user_lp_account: &Pubkey,
+    ) -> ClientResult<Instruction> {
+        let is_swap = defi_instruction.is_swap();
+        let user_lp_account = if is_swap { None } else { Some(user_lp_account) };
+        let is_uniform_remove = defi_instruction.is_uniform_remove();
+        let is_imbalanced_remove = defi_instruction.is_remove() && !is_uniform_remove;
+        let is_add = matches!(defi_instruction.executed(), DeFiInstruction::Add { .. });
+        let mut instruction = create_defi_ix(
+            defi_instruction,
+            &self.program_id,
+            &self.pool,
+            &self.authority(),
+            &self.state.token_keys,
+            &self.state.lp_mint_key,
+            &self.state.governance_fee_key,
+            user_authority,
+            user_token_accounts,
+            &spl_token::id(),
+            user_lp_account,
+        )?;
+        if self.state.lp_non_transferable {
+            instruction
+                .accounts
+                .push(AccountMeta::new_readonly(token_2022::id(), false));
+        }
+        if is_swap && self.state.is_oracle_guarded() {
+            for oracle_key in self.state.oracle_keys.iter() {
+                instruction.accounts.push(AccountMeta::new_readonly(*oracle_key, false));
+            }
+        }
+        if !is_uniform_remove {
+            for rate_key in self.state.rate_account_keys() {
+                instruction.accounts.push(AccountMeta::new_readonly(rate_key, false));
+            }
+            if self.state.restricts_callers() {
+                instruction
+                    .accounts
+                    .push(AccountMeta::new_readonly(sysvar::instructions::id(), false));
+            }
+            if let Some(gate_account) =
+                find_user_gate_account(&self.program_id, &self.pool, &self.state.user_gate, user_authority)
+            {
+                instruction
+                    .accounts
+                    .push(AccountMeta::new_readonly(gate_account, false));
+            }
+            if !is_swap {
+                if let Some(gate_account) = find_depositor_gate_account(
+                    &self.program_id,
+                    &self.pool,
+                    &self.state.depositor_gate,
+                    user_authority,
+                ) {
+                    instruction
+                        .accounts
+                        .push(AccountMeta::new_readonly(gate_account, false));
+                }
+            }
+        }
+        if self.state.has_compliance_program() && !is_uniform_remove {
+            let compliance_program = self.state.compliance_program;
+            let compliance_account = find_compliance_address(&compliance_program, &self.pool, user_authority).0;
+            instruction
+                .accounts
+                .push(AccountMeta::new_readonly(compliance_program, false));
+            instruction
+                .accounts
+                .push(AccountMeta::new_readonly(compliance_account, false));
+        }
+        if is_imbalanced_remove && self.state.has_withdrawal_cooldown() {
+            let announcement = find_withdrawal_announcement_address(&self.program_id, &self.pool, user_authority).0;
+            instruction.accounts.push(AccountMeta::new(announcement, false));
+        }
+        //the program ignores it once the launch window is over
+        if is_add && self.state.launch_lp_cap != 0 {
+            let launch_allocation = find_launch_allocation_address(&self.program_id, &self.pool, user_authority).0;
+            instruction.accounts.push(AccountMeta::new(launch_allocation, false));
+        }
+        Ok(instruction)
+    }
+
+    /// A swap of `user` that `session_key` signs in its place, see `authorize_session_ix`
+    pub fn session_swap_ix(
+        &self,
+        defi_instruction: DeFiInstruction<TOKEN_COUNT>,
+        user: &Pubkey,
+        user_token_accounts: &[Pubkey; TOKEN_COUNT],
+        session_key: &Pubkey,
+    ) -> ClientResult<Instruction> {
+        let mut instruction = self.defi_ix(defi_instruction, user, user_token_accounts, &Pubkey::default())?;
+        sign_swap_with_session::<TOKEN_COUNT>(&mut instruction, &self.pool, user, session_key);
+        Ok(instruction)
+    }
+
+    /// Makes a swap of `user` (see `defi_ix` and `session_swap_ix`) record its fees with the pool's current rebate
+    /// campaign, which `user` has to have joined (see `join_rebate_campaign_ix`)
+    pub fn record_swap_rebate(&self, swap_ix: &mut Instruction, user: &Pubkey) {
+        record_swap_rebate(swap_ix, &self.state.rebate_campaign, user);
+    }
+
+    /// The accounts that every defi instruction of the pool passes, whatever the user: the program, pool, authority,
+    /// token accounts, lp mint, governance fee account and token program, followed by the oracle and rate accounts
+    /// the pool currently uses (and the instructions sysvar if it restricts its callers). These are what an address
+    /// lookup table for the pool holds (see `create_lookup_table_ixs`)
+    pub fn static_account_keys(&self) -> Vec<Pubkey> {
+        let mut keys = vec![self.program_id, self.pool, self.authority()];
+        keys.extend_from_slice(&self.state.token_keys);
+        keys.push(self.state.lp_mint_key);
+        keys.push(self.state.governance_fee_key);
+        keys.push(spl_token::id());
+        if self.state.lp_non_transferable {
+            keys.push(token_2022::id());
+        }
+        if self.state.is_oracle_guarded() {
+            keys.extend_from_slice(&self.state.oracle_keys);
+        }
+        keys.extend(self.state.rate_account_keys());
+        if self.state.restricts_callers() {
+            keys.push(sysvar::instructions::id());
+        }
+        keys
+    }
+
+    /// The instructions that create a lookup table of `authority` holding the pool's static accounts (see
+    /// `static_account_keys`) and the table's address. The first extension fits in one transaction with the creation,
+    /// every other one needs its own. `recent_slot` picks the address, see `lookup_table::create_lookup_table_ix`
+    pub fn create_lookup_table_ixs(
+        &self,
+        authority: &Pubkey,
+        payer: &Pubkey,
+        recent_slot: Slot,
+    ) -> (Vec<Instruction>, Pubkey) {
+        let (create_ix, key) = create_lookup_table_ix(authority, payer, recent_slot);
+        let lookup_table = LookupTable { key, addresses: vec![] };
+        let mut instructions = vec![create_ix];
+        instructions.extend(extend_lookup_table_ixs(
+            &lookup_table,
+            authority,
+            payer,
+            &self.static_account_keys(),
+        ));
+        (instructions, key)
+    }
+
+    /// The instructions that add the pool's static accounts that `lookup_table` lacks, e.g. once the pool got oracles,
+    /// one per transaction
+    pub fn extend_lookup_table_ixs(
+        &self,
+        lookup_table: &LookupTable,
+        authority: &Pubkey,
+        payer: &Pubkey,
+    ) -> Vec<Instruction> {
+        extend_lookup_table_ixs(lookup_table, authority, payer, &self.static_account_keys())
+    }
+
+    pub fn governance_ix(
+        &self,
+        gov_instruction: GovernanceInstruction<TOKEN_COUNT>,
+        governance_fee_account: Option<&Pubkey>,
+    ) -> ClientResult<Instruction> {
+        Ok(create_governance_ix(
+            gov_instruction,
+            &self.program_id,
+            &self.pool,
+            &self.state.governance_key,
+            governance_fee_account,
+        )?)
+    }
+
+    /// Like `governance_ix` for an instruction of a role (see `GovernanceInstruction::role`), signed by `role_key`
+    /// instead of the governance
+    pub fn role_ix(
+        &self,
+        gov_instruction: GovernanceInstruction<TOKEN_COUNT>,
+        role_key: &Pubkey,
+    ) -> ClientResult<Instruction> {
+        Ok(create_governance_ix(
+            gov_instruction,
+            &self.program_id,
+            &self.pool,
+            role_key,
+            None,
+        )?)
+    }
+
+    /// Burns `burn_amount` of the lp tokens in the pool's governance fee account, signed by the pool's governance
+    pub fn burn_governance_fees_ix(&self, burn_amount: u64) -> ClientResult<Instruction> {
+        Ok(create_burn_governance_fees_ix::<TOKEN_COUNT>(
+            &self.program_id,
+            &self.pool,
+            &self.state.governance_key,
+            &self.state.governance_fee_key,
+            &self.state.lp_mint_key,
+            &self.state.lp_token_program_id(),
+            burn_amount,
+        )?)
+    }
+
+    pub fn check_peg_ix(&self) -> ClientResult<Instruction> {
+        Ok(create_check_peg_ix(
+            &self.program_id,
+            &self.pool,
+            &self.state.oracle_keys,
+        )?)
+    }
+
+    /// Creates whichever of the pool's token accounts and governance fee account are missing, does nothing if none are
+    pub fn create_pool_token_accounts_ix(&self, payer: &Pubkey) -> ClientResult<Instruction> {
+        Ok(create_pool_token_accounts_ix(
+            &self.program_id,
+            &self.pool,
+            &self.authority(),
+            &self.state.token_keys,
+            &self.state.token_mint_keys,
+            &self.state.governance_fee_key,
+            &self.state.governance_key,
+            &self.state.lp_mint_key,
+            payer,
+        )?)
+    }
+
+    /// Initializes a new pool with the accounts of `create_init_ix` and this pool's parameters (see
+    /// `PoolInstruction::ClonePoolConfig`)
+    #[allow(clippy::too_many_arguments)]
+    pub fn clone_config_ix(
+        &self,
+        pool: &Pubkey,
+        lp_mint: &Pubkey,
+        token_mints: &[Pubkey; TOKEN_COUNT],
+        token_accounts: &[Pubkey; TOKEN_COUNT],
+        governance: &Pubkey,
+        governance_fee_account: &Pubkey,
+        nonce: u8,
+    ) -> ClientResult<Instruction> {
+        Ok(create_clone_pool_config_ix(
+            &self.program_id,
+            pool,
+            lp_mint,
+            token_mints,
+            token_accounts,
+            governance,
+            governance_fee_account,
+            nonce,
+            &self.pool,
+        )?)
+    }
+
+    /// Burns all lp tokens of `user_lp_account` for their share of the pool's balances once its sunset deadline passed
+    /// (see `PoolInstruction::RedeemSunset`)
+    pub fn redeem_sunset_ix(
+        &self,
+        user_lp_account: &Pubkey,
+        user_authority: &Pubkey,
+        user_token_accounts: &[Pubkey; TOKEN_COUNT],
+    ) -> ClientResult<Instruction> {
+        Ok(create_redeem_sunset_ix(
+            &self.program_id,
+            &self.pool,
+            &self.authority(),
+            &self.state.token_keys,
+            &self.state.lp_mint_key,
+            &self.state.lp_token_program_id(),
+            user_lp_account,
+            user_authority,
+            user_token_accounts,
+        )?)
+    }
+
+    pub fn publish_virtual_price_ix(&self, payer: &Pubkey) -> ClientResult<Instruction> {
+        Ok(create_publish_virtual_price_ix(
+            &self.program_id,
+            &self.pool,
+            &self.state.lp_mint_key,
+            &self.state.token_keys,
+            payer,
+            &self.state.rate_account_keys(),
+        )?)
+    }
+
+    /// The virtual price last published by `PublishVirtualPrice`
+    pub fn virtual_price(&self) -> ClientResult<VirtualPriceState> {
+        let key = find_virtual_price_address(&self.program_id, &self.pool).0;
+        let account = self
+            .rpc_client
+            .get_account_with_commitment(&key, self.rpc_client.commitment())?
+            .value
+            .ok_or(ClientError::AccountNotFound(key))?;
+        VirtualPriceState::deserialize(&mut account.data.as_slice()).map_err(|_| ClientError::InvalidAccountData(key))
+    }
+
+    pub fn snapshot_lp_supply_ix(&self, payer: &Pubkey) -> ClientResult<Instruction> {
+        Ok(create_snapshot_lp_supply_ix::<TOKEN_COUNT>(
+            &self.program_id,
+            &self.pool,
+            &self.state.lp_mint_key,
+            payer,
+        )?)
+    }
+
+    /// The lp supply checkpoints recorded by `SnapshotLpSupply`
+    pub fn lp_supply_snapshots(&self) -> ClientResult<LpSupplySnapshots> {
+        let key = find_lp_supply_address(&self.program_id, &self.pool).0;
+        let account = self
+            .rpc_client
+            .get_account_with_commitment(&key, self.rpc_client.commitment())?
+            .value
+            .ok_or(ClientError::AccountNotFound(key))?;
+        LpSupplySnapshots::deserialize(&mut account.data.as_slice()).map_err(|_| ClientError::InvalidAccountData(key))
+    }
+
+    /// Records the balance of `lp_account` (owned by `owner`) along with the pool's lp fee growth, see
+    /// `PoolInstruction::CheckpointFees`
+    pub fn checkpoint_fees_ix(&self, lp_account: &Pubkey, owner: &Pubkey, payer: &Pubkey) -> ClientResult<Instruction> {
+        Ok(create_checkpoint_fees_ix::<TOKEN_COUNT>(
+            &self.program_id,
+            &self.pool,
+            lp_account,
+            owner,
+            payer,
+        )?)
+    }
+
+    /// The fee checkpoint that `CheckpointFees` last recorded for `lp_account`, `None` if there is none yet
+    pub fn fee_checkpoint(&self, lp_account: &Pubkey) -> ClientResult<Option<FeeCheckpoint>> {
+        let key = find_fee_checkpoint_address(&self.program_id, &self.pool, lp_account).0;
+        match self
+            .rpc_client
+            .get_account_with_commitment(&key, self.rpc_client.commitment())?
+            .value
+        {
+            Some(account) => Ok(Some(
+                FeeCheckpoint::deserialize(&mut account.data.as_slice())
+                    .map_err(|_| ClientError::InvalidAccountData(key))?,
+            )),
+            None => Ok(None),
+        }
+    }
+
+    /// Announces that `user` will burn up to `lp_amount` lp tokens in removes above the pool's withdrawal cooldown
+    /// threshold, 0 closes the announcement
+    pub fn announce_withdrawal_ix(&self, user: &Pubkey, lp_amount: u64) -> ClientResult<Instruction> {
+        Ok(create_announce_withdrawal_ix::<TOKEN_COUNT>(
+            &self.program_id,
+            &self.pool,
+            user,
+            lp_amount,
+        )?)
+    }
+
+    /// The withdrawal announcement of `user`, if there is one
+    pub fn withdrawal_announcement(&self, user: &Pubkey) -> ClientResult<Option<WithdrawalAnnouncement>> {
+        let key = find_withdrawal_announcement_address(&self.program_id, &self.pool, user).0;
+        match self
+            .rpc_client
+            .get_account_with_commitment(&key, self.rpc_client.commitment())?
+            .value
+        {
+            Some(account) => Ok(Some(
+                WithdrawalAnnouncement::deserialize(&mut account.data.as_slice())
+                    .map_err(|_| ClientError::InvalidAccountData(key))?,
+            )),
+            None => Ok(None),
+        }
+    }
+
+    /// Confirms an emergency as `responder`, one of the pool's emergency responders
+    pub fn confirm_emergency_ix(&self, responder: &Pubkey) -> ClientResult<Instruction> {
+        Ok(create_confirm_emergency_ix::<TOKEN_COUNT>(
+            &self.program_id,
+            &self.pool,
+            responder,
+        )?)
+    }
+
+    /// Lends the pool `amount` of its peg keeper's token as `peg_keeper`, see `PoolInstruction::ProvidePegCredit`
+    pub fn provide_peg_credit_ix(&self, peg_keeper: &Pubkey, amount: u64) -> ClientResult<Instruction> {
+        Ok(create_provide_peg_credit_ix(
+            &self.program_id,
+            &self.pool,
+            &self.state.lp_mint_key,
+            &self.state.token_keys,
+            peg_keeper,
+            &self.state.rate_account_keys(),
+            amount,
+        )?)
+    }
+
+    /// Takes `amount` of the peg keeper's virtual balance back out of the pool as `peg_keeper`, see
+    /// `PoolInstruction::AbsorbPegCredit`
+    pub fn absorb_peg_credit_ix(&self, peg_keeper: &Pubkey, amount: u64) -> ClientResult<Instruction> {
+        Ok(create_absorb_peg_credit_ix(
+            &self.program_id,
+            &self.pool,
+            &self.state.lp_mint_key,
+            &self.state.token_keys,
+            peg_keeper,
+            &self.state.rate_account_keys(),
+            amount,
+        )?)
+    }
+
+    /// Deploys what the pool holds of the token at `token_index` beyond its yield adapter's buffer, `adapter_accounts`
+    /// are the accounts that the adapter needs, see `PoolInstruction::DeployYield`
+    pub fn deploy_yield_ix(&self, token_index: u8, adapter_accounts: &[AccountMeta]) -> ClientResult<Instruction> {
+        Ok(create_deploy_yield_ix(
+            &self.program_id,
+            &self.pool,
+            &self.authority(),
+            &self.state.token_keys,
+            &self.state.rate_account_keys(),
+            token_index,
+            &self.yield_adapter(token_index)?,
+            adapter_accounts,
+        )?)
+    }
+
+    /// Recalls `amount` of the token at `token_index` from its yield adapter, see `PoolInstruction::RecallYield`
+    pub fn recall_yield_ix(
+        &self,
+        token_index: u8,
+        amount: u64,
+        adapter_accounts: &[AccountMeta],
+    ) -> ClientResult<Instruction> {
+        Ok(create_recall_yield_ix(
+            &self.program_id,
+            &self.pool,
+            &self.authority(),
+            &self.state.token_keys,
+            &self.state.rate_account_keys(),
+            token_index,
+            amount,
+            &self.yield_adapter(token_index)?,
+            adapter_accounts,
+        )?)
+    }
+
+    fn yield_adapter(&self, token_index: u8) -> ClientResult<Pubkey> {
+        match self.state.yield_adapters.get(token_index as usize) {
+            Some(yield_adapter) if yield_adapter.is_set() => Ok(yield_adapter.program),
+            _ => Err(PoolError::InvalidYieldAdapter.into()),
+        }
+    }
+
+    /// Moves a hybrid pool's price for its volatile token towards its oracle, see `PoolInstruction::Repeg`
+    pub fn repeg_ix(&self) -> ClientResult<Instruction> {
+        Ok(create_repeg_ix(
+            &self.program_id,
+            &self.pool,
+            &self.state.token_keys,
+            &self.state.rate_account_keys(),
+        )?)
+    }
+
+    /// Creates `owner`'s launch allocation account, which its adds need while the pool's launch window is open
+    pub fn create_launch_allocation_ix(&self, owner: &Pubkey, payer: &Pubkey) -> ClientResult<Instruction> {
+        Ok(create_launch_allocation_ix::<TOKEN_COUNT>(
+            &self.program_id,
+            &self.pool,
+            owner,
+            payer,
+        )?)
+    }
+
+    /// Allows resp. disallows `user` while the pool's user gate is an allowlist, `payer` pays for resp. gets back the
+    /// rent of its allowed user account. Signed by the pool's governance
+    pub fn set_user_allowed_ix(&self, user: &Pubkey, allowed: bool, payer: &Pubkey) -> ClientResult<Instruction> {
+        Ok(create_set_user_allowed_ix::<TOKEN_COUNT>(
+            &self.program_id,
+            &self.pool,
+            &self.state.governance_key,
+            user,
+            allowed,
+            payer,
+        )?)
+    }
+
+    /// Like `set_user_allowed_ix` for the pool's depositor allowlist
+    pub fn set_depositor_allowed_ix(
+        &self,
+        depositor: &Pubkey,
+        allowed: bool,
+        payer: &Pubkey,
+    ) -> ClientResult<Instruction> {
+        Ok(create_set_depositor_allowed_ix::<TOKEN_COUNT>(
+            &self.program_id,
+            &self.pool,
+            &self.state.governance_key,
+            depositor,
+            allowed,
+            payer,
+        )?)
+    }
+
+    /// Values the pool's token `token_index`, the lp token of `base_pool`, at the base pool's published virtual price
+    /// (see `RateSource::BasePool`). Signed by the pool's governance
+    pub fn set_base_pool_rate_ix(&self, token_index: u8, base_pool: &Pubkey) -> ClientResult<Instruction> {
+        let base_program_id = self
+            .rpc_client
+            .get_account_with_commitment(base_pool, self.rpc_client.commitment())?
+            .value
+            .ok_or(ClientError::AccountNotFound(*base_pool))?
+            .owner;
+        Ok(create_set_base_pool_rate_ix::<TOKEN_COUNT>(
+            &self.program_id,
+            &self.pool,
+            &self.state.governance_key,
+            token_index,
+            &base_program_id,
+            base_pool,
+        )?)
+    }
+
+    /// Adds `reward_mint` as a reward of the pool's gauge that `funder` funds (or sets its funder), signed by the
+    /// pool's governance
+    pub fn add_gauge_reward_ix(
+        &self,
+        reward_mint: &Pubkey,
+        funder: &Pubkey,
+        payer: &Pubkey,
+    ) -> ClientResult<Instruction> {
+        Ok(create_add_gauge_reward_ix::<TOKEN_COUNT>(
+            &self.program_id,
+            &self.pool,
+            &self.state.governance_key,
+            &self.authority(),
+            &self.state.lp_mint_key,
+            reward_mint,
+            funder,
+            payer,
+        )?)
+    }
+
+    /// Retires the gauge's reward of `reward_mint`, refunding what it was yet to emit to `funder_reward_account`,
+    /// signed by the pool's governance
+    pub fn retire_gauge_reward_ix(
+        &self,
+        reward_mint: &Pubkey,
+        funder_reward_account: &Pubkey,
+    ) -> ClientResult<Instruction> {
+        Ok(create_retire_gauge_reward_ix::<TOKEN_COUNT>(
+            &self.program_id,
+            &self.pool,
+            &self.state.governance_key,
+            &self.authority(),
+            reward_mint,
+            funder_reward_account,
+        )?)
+    }
+
+    /// The pool's liquidity mining gauge, `None` if governance didn't add a reward yet
+    pub fn gauge(&self) -> ClientResult<Option<Gauge>> {
+        let key = find_gauge_address(&self.program_id, &self.pool).0;
+        match self
+            .rpc_client
+            .get_account_with_commitment(&key, self.rpc_client.commitment())?
+            .value
+        {
+            Some(account) => Ok(Some(
+                Gauge::deserialize(&mut account.data.as_slice()).map_err(|_| ClientError::InvalidAccountData(key))?,
+            )),
+            None => Ok(None),
+        }
+    }
+
+    /// Updates the boost of `owner`'s gauge stake from the gauge's voting escrow program, see
+    /// `PoolInstruction::UpdateGaugeBoost`
+    pub fn update_gauge_boost_ix(&self, owner: &Pubkey) -> ClientResult<Instruction> {
+        let gauge = self.gauge()?.ok_or(PoolError::InvalidGauge)?;
+        Ok(create_update_gauge_boost_ix::<TOKEN_COUNT>(
+            &self.program_id,
+            &self.pool,
+            owner,
+            Some(&gauge.ve_program).filter(|ve_program| **ve_program != Pubkey::default()),
+        )?)
+    }
+
+    /// Starts a rebate campaign with a budget of `budget` tokens of `governance_rebate_account`, signed by the pool's
+    /// governance
+    #[allow(clippy::too_many_arguments)]
+    pub fn start_rebate_campaign_ix(
+        &self,
+        campaign_id: u64,
+        rebate_mint: &Pubkey,
+        governance_rebate_account: &Pubkey,
+        budget: u64,
+        start_ts: UnixTimestamp,
+        end_ts: UnixTimestamp,
+        payer: &Pubkey,
+    ) -> ClientResult<Instruction> {
+        Ok(create_start_rebate_campaign_ix::<TOKEN_COUNT>(
+            &self.program_id,
+            &self.pool,
+            &self.state.governance_key,
+            &self.authority(),
+            rebate_mint,
+            governance_rebate_account,
+            payer,
+            campaign_id,
+            budget,
+            start_ts,
+            end_ts,
+        )?)
+    }
+
+    /// Returns what's left of a rebate campaign's budget to `governance_rebate_account`, signed by the pool's
+    /// governance
+    pub fn reclaim_rebate_budget_ix(
+        &self,
+        campaign_id: u64,
+        governance_rebate_account: &Pubkey,
+    ) -> ClientResult<Instruction> {
+        Ok(create_reclaim_rebate_budget_ix::<TOKEN_COUNT>(
+            &self.program_id,
+            &self.pool,
+            &self.state.governance_key,
+            &self.authority(),
+            campaign_id,
+            governance_rebate_account,
+        )?)
+    }
+
+    /// Creates `owner`'s rebate account with the pool's current rebate campaign, paid by `payer`
+    pub fn join_rebate_campaign_ix(&self, owner: &Pubkey, payer: &Pubkey) -> ClientResult<Instruction> {
+        Ok(create_join_rebate_campaign_ix::<TOKEN_COUNT>(
+            &self.program_id,
+            &self.pool,
+            &self.state.rebate_campaign,
+            owner,
+            payer,
+        )?)
+    }
+
+    /// Pays `owner`'s rebate of the campaign with `campaign_id` to `owner_rebate_account`
+    pub fn claim_rebate_ix(
+        &self,
+        campaign_id: u64,
+        owner: &Pubkey,
+        owner_rebate_account: &Pubkey,
+    ) -> ClientResult<Instruction> {
+        Ok(create_claim_rebate_ix::<TOKEN_COUNT>(
+            &self.program_id,
+            &self.pool,
+            &self.authority(),
+            &find_rebate_campaign_address(&self.program_id, &self.pool, campaign_id).0,
+            owner,
+            owner_rebate_account,
+        )?)
+    }
+
+    /// The pool's rebate campaign with `campaign_id`, `None` if governance didn't start it
+    pub fn rebate_campaign(&self, campaign_id: u64) -> ClientResult<Option<RebateCampaign>> {
+        let key = find_rebate_campaign_address(&self.program_id, &self.pool, campaign_id).0;
+        match self
+            .rpc_client
+            .get_account_with_commitment(&key, self.rpc_client.commitment())?
+            .value
+        {
+            Some(account) => Ok(Some(
+                RebateCampaign::deserialize(&mut account.data.as_slice())
+                    .map_err(|_| ClientError::InvalidAccountData(key))?,
+            )),
+            None => Ok(None),
+        }
+    }
+
+    /// The fees that `owner`'s swaps recorded with `campaign`, `None` if `owner` didn't join it or claimed the rebate
+    pub fn rebate(&self, campaign: &Pubkey, owner: &Pubkey) -> ClientResult<Option<Rebate>> {
+        let key = find_rebate_address(&self.program_id, campaign, owner).0;
+        match self
+            .rpc_client
+            .get_account_with_commitment(&key, self.rpc_client.commitment())?
+            .value
+        {
+            Some(account) => Ok(Some(
+                Rebate::deserialize(&mut account.data.as_slice()).map_err(|_| ClientError::InvalidAccountData(key))?,
+            )),
+            None => Ok(None),
+        }
+    }
+
+    /// The lp tokens that adds minted to `owner` during the pool's launch window, `None` if it has no launch
+    /// allocation account yet
+    pub fn launch_allocation(&self, owner: &Pubkey) -> ClientResult<Option<LaunchAllocation>> {
+        let key = find_launch_allocation_address(&self.program_id, &self.pool, owner).0;
+        match self
+            .rpc_client
+            .get_account_with_commitment(&key, self.rpc_client.commitment())?
+            .value
+        {
+            Some(account) => Ok(Some(
+                LaunchAllocation::deserialize(&mut account.data.as_slice())
+                    .map_err(|_| ClientError::InvalidAccountData(key))?,
+            )),
+            None => Ok(None),
+        }
+    }
+
+    /// Authorizes `session_key` to sign `user`'s swaps until `expiry_ts`, spending at most `spend_limits`, signed by
+    /// the user
+    pub fn authorize_session_ix(
+        &self,
+        user: &Pubkey,
+        user_token_accounts: &[Pubkey; TOKEN_COUNT],
+        session_key: &Pubkey,
+        expiry_ts: UnixTimestamp,
+        spend_limits: [u64; TOKEN_COUNT],
+    ) -> ClientResult<Instruction> {
+        Ok(create_authorize_session_ix(
+            &self.program_id,
+            &self.pool,
+            user,
+            user_token_accounts,
+            session_key,
+            expiry_ts,
+            spend_limits,
+        )?)
+    }
+
+    /// Ends `user`'s session, signed by the user
+    pub fn revoke_session_ix(
+        &self,
+        user: &Pubkey,
+        user_token_accounts: &[Pubkey; TOKEN_COUNT],
+    ) -> ClientResult<Instruction> {
+        Ok(create_revoke_session_ix(
+            &self.program_id,
+            &self.pool,
+            user,
+            user_token_accounts,
+        )?)
+    }
+
+    /// The instructions that execute `quote` with the pool's quoter's `signature` of it, the verification of the
+    /// signature followed by the `RfqSwap`, which is signed by the quote's user
+    pub fn rfq_swap_ixs(
+        &self,
+        quote: RfqQuote,
+        signature: &[u8; 64],
+        user_input_token_account: &Pubkey,
+        user_output_token_account: &Pubkey,
+    ) -> ClientResult<[Instruction; 2]> {
+        let mut extra_accounts = self.state.rate_account_keys();
+        extra_accounts.extend(find_user_gate_account(
+            &self.program_id,
+            &self.pool,
+            &self.state.user_gate,
+            &quote.user,
+        ));
+        if self.state.has_compliance_program() {
+            let compliance_program = self.state.compliance_program;
+            extra_accounts.push(compliance_program);
+            extra_accounts.push(find_compliance_address(&compliance_program, &self.pool, &quote.user).0);
+        }
+        Ok([
+            create_rfq_signature_ix(&self.state.rfq_quoter_key, signature, &quote),
+            create_rfq_swap_ix(
+                &self.program_id,
+                &self.pool,
+                &self.authority(),
+                &self.state.token_keys,
+                user_input_token_account,
+                user_output_token_account,
+                quote,
+                &extra_accounts,
+            )?,
+        ])
+    }
+
+    /// `user`'s current session, `None` if it has none
+    pub fn session(&self, user: &Pubkey) -> ClientResult<Option<Session<TOKEN_COUNT>>> {
+        let key = find_session_address(&self.program_id, &self.pool, user).0;
+        match self
+            .rpc_client
+            .get_account_with_commitment(&key, self.rpc_client.commitment())?
+            .value
+        {
+            Some(account) => Ok(Some(
+                Session::deserialize(&mut account.data.as_slice()).map_err(|_| ClientError::InvalidAccountData(key))?,
+            )),
+            None => Ok(None),
+        }
+    }
+
+    /// The current exchange rates of the pool's tokens (see `oracle::load_rate`)
+    pub fn rates(&self) -> ClientResult<[u64; TOKEN_COUNT]> {
+        let rate_keys = self.state.rate_account_keys();
+        if rate_keys.is_empty() {
+            return compute_rates(&self.state, &[], &Clock::default());
+        }
+        let mut keys = vec![sysvar::clock::id()];
+        keys.extend_from_slice(&rate_keys);
+        let accounts = self
+            .rpc_client
+            .get_multiple_accounts_with_commitment(&keys, self.rpc_client.commitment())?
+            .value
+            .into_iter()
+            .zip(keys.iter())
+            .map(|(account, key)| account.ok_or(ClientError::AccountNotFound(*key)))
+            .collect::<ClientResult<Vec<_>>>()?;
+        let clock = from_account::<Clock, _>(&accounts[0]).ok_or(ClientError::InvalidAccountData(keys[0]))?;
+        compute_rates(&self.state, &accounts[1..], &clock)
+    }
+
+    pub fn token_balances(&self) -> ClientResult<[u64; TOKEN_COUNT]> {
+        let mut balances = [0; TOKEN_COUNT];
+        for (balance, token_key) in balances.iter_mut().zip(self.state.token_keys.iter()) {
+            *balance = fetch_packed::<TokenState>(&self.rpc_client, token_key)?.amount;
+        }
+        Ok(balances)
+    }
+
+    /// Includes the lock rewards that weren't claimed yet (see `PoolState::lp_supply`)
+    pub fn lp_supply(&self) -> ClientResult<u64> {
+        Ok(self
+            .state
+            .lp_supply(fetch_packed::<MintState>(&self.rpc_client, &self.state.lp_mint_key)?.supply))
+    }
+
+    /// The cluster's current time, i.e. what the program compares timelocks and amp factor adjustments against
+    pub fn now(&self) -> ClientResult<UnixTimestamp> {
+        let clock_account = self.rpc_client.get_account(&sysvar::clock::id())?;
+        let clock =
+            from_account::<Clock, _>(&clock_account).ok_or(ClientError::InvalidAccountData(sysvar::clock::id()))?;
+        Ok(clock.unix_timestamp)
+    }
+
+    pub fn send(
+        &self,
+        instructions: &[Instruction],
+        payer: &dyn Signer,
+        signers: &[&dyn Signer],
+    ) -> ClientResult<Signature> {
+        send_instructions(&self.rpc_client, instructions, payer, signers)
+    }
+
+    pub fn simulate(&self, instructions: &[Instruction], fee_payer: &Pubkey) -> ClientResult<Vec<String>> {
+        simulate_instructions(&self.rpc_client, instructions, fee_payer)
+    }
+}
+
+/// The exchange rates of the pool's tokens, `rate_accounts` are the accounts of `state.rate_account_keys()`. A hybrid
+/// pool's volatile token is at the pool's price for it rather than its rate source's (see `PoolState::scaled_rate`)
+pub fn compute_rates(
+    state: &PoolState<TOKEN_COUNT>,
+    rate_accounts: &[Account],
+    clock: &Clock,
+) -> ClientResult<[u64; TOKEN_COUNT]> {
+    let mut rates = [RATE_ONE; TOKEN_COUNT];
+    let mut rate_accounts = rate_accounts.iter();
+    for i in 0..TOKEN_COUNT {
+        let source = state.rate_sources[i];
+        let (token_mint, limits) = (&state.token_mint_keys[i], &state.oracle_limits[i]);
+        rates[i] = if source.has_account() {
+            let account = rate_accounts
+                .next()
+                .ok_or(ClientError::AccountNotFound(state.rate_keys[i]))?;
+            match state.scaled_rate(i) {
+                Some(rate) => rate,
+                None => load_rate(source, &account.owner, &account.data, token_mint, clock, limits)?,
+            }
+        } else if let Some(rate) = state.scaled_rate(i) {
+            rate
+        } else {
+            load_rate(source, &Pubkey::default(), &[], token_mint, clock, limits)?
+        };
+    }
+    Ok(rates)
+}
+
+//returns the owner of the pool account (i.e. the program id) along with the pool's state
+fn fetch_pool_state(rpc_client: &RpcClient, pool: &Pubkey) -> ClientResult<(Pubkey, PoolState<TOKEN_COUNT>)> {
+    let account = rpc_client
+        .get_account_with_commitment(pool, rpc_client.commitment())?
+        .value
+        .ok_or(ClientError::AccountNotFound(*pool))?;
+    let state = match PoolState::<TOKEN_COUNT>::unpack(&account.data) {
+        Some(state) if state.is_initialized() => state,
+        _ => {
+            return Err(match pool_token_count(&account.data) {
+                Some(token_count) if token_count != TOKEN_COUNT => PoolError::TokenCountMismatch.into(),
+                _ => ClientError::InvalidAccountData(*pool),
+            })
+        }
+    };
+    Ok((account.owner, state))
+}
+
+/// Reads a mint or token account
+pub fn fetch_packed<T: Pack + IsInitialized>(rpc_client: &RpcClient, pubkey: &Pubkey) -> ClientResult<T> {
+    let account = rpc_client
+        .get_account_with_commitment(pubkey, rpc_client.commitment())?
+        .value
+        .ok_or(ClientError::AccountNotFound(*pubkey))?;
+    if account.owner == token_2022::id() {
+        return token_2022::unpack(&account.data).map_err(|_| ClientError::InvalidAccountData(*pubkey));
+    }
+    T::unpack(&account.data).map_err(|_| ClientError::InvalidAccountData(*pubkey))
+}
+
+/// Sends all instructions within a single transaction (paid for by `payer`) and waits for its confirmation
+pub fn send_instructions(
+    rpc_client: &RpcClient,
+    instructions: &[Instruction],
+    payer: &dyn Signer,
+    signers: &[&dyn Signer],
+) -> ClientResult<Signature> {
+    let (blockhash, _) = rpc_client.get_recent_blockhash()?;
+    let mut all_signers = vec![payer];
+    all_signers.extend_from_slice(signers);
+    let transaction = Transaction::new_signed_with_payer(instructions, Some(&payer.pubkey()), &all_signers, blockhash);
+    Ok(rpc_client.send_and_confirm_transaction(&transaction)?)
+}
+
+/// Builds a transaction paid for by `payer` that only carries the signatures of `signers` (e.g. of accounts it creates),
+/// leaving those of the payer and any other required signer (such as a multisig) to whoever submits it
+pub fn unsigned_transaction(
+    rpc_client: &RpcClient,
+    instructions: &[Instruction],
+    payer: &Pubkey,
+    signers: &[&dyn Signer],
+) -> ClientResult<Transaction> {
+    let (blockhash, _) = rpc_client.get_recent_blockhash()?;
+    let mut transaction = Transaction::new_with_payer(instructions, Some(payer));
+    transaction.message.recent_blockhash = blockhash;
+    if !signers.is_empty() {
+        transaction.try_partial_sign(&signers.to_vec(), blockhash)?;
+    }
+    Ok(transaction)
+}
+
+/// Simulates all instructions within a single transaction without requiring any signatures and returns the logs
+pub fn simulate_instructions(
+    rpc_client: &RpcClient,
+    instructions: &[Instruction],
+    fee_payer: &Pubkey,
+) -> ClientResult<Vec<String>> {
+    let transaction = Transaction::new_unsigned(Message::new(instructions, Some(fee_payer)));
+    let config = RpcSimulateTransactionConfig {
+        sig_verify: false,
+        replace_recent_blockhash: true,
+        commitment: Some(rpc_client.commitment()),
+        ..RpcSimulateTransactionConfig::default()
+    };
+    let result = rpc_client.simulate_transaction_with_config(&transaction, config)?.value;
+    match result.err {
+        Some(err) => Err(ClientError::SimulationFailed(err)),
+        None => Ok(result.logs.unwrap_or_default()),
+    }
+}
@@ -0,0 +1,152 @@
+//keeps the router's pools up to date through websocket account subscriptions and caches the best routes, so that
+// re-quoting a pair takes no requests at all and only the routes an account change can affect are recomputed
+//
+//a changed account re-decodes just the pools that quote from it and drops the cached routes that start or end in one
+// of their tokens (every route through a pool does, see `Router::find_best_route`). the clock changes every slot and
+// all pools quote from it (amp factor ramps, rates, oracle staleness), so a new slot re-decodes all pools and drops all
+// routes, which still only takes local computation
+
+use super::{router::Route, router::Router, ClientError, ClientResult};
+use solana_account_decoder::UiAccountEncoding;
+use solana_client::{
+    pubsub_client::{AccountSubscription, PubsubClient},
+    rpc_client::RpcClient,
+    rpc_config::RpcAccountInfoConfig,
+};
+use solana_program::pubkey::Pubkey;
+use solana_sdk::account::Account;
+use std::collections::{BTreeSet, HashMap};
+
+pub struct RouteCache {
+    router: Router,
+    accounts: HashMap<Pubkey, Account>,
+    //the indices of the pools that quote from an account
+    dependents: HashMap<Pubkey, Vec<usize>>,
+    routes: HashMap<(Pubkey, Pubkey, u64), Route>,
+    //the subscriptions unsubscribe when dropped
+    subscriptions: Vec<(Pubkey, AccountSubscription)>,
+}
+
+impl RouteCache {
+    /// Caches routes through `router`'s pools, `accounts` are those they were updated with. Without subscriptions,
+    /// changes have to be passed to `apply`
+    pub fn new(router: Router, accounts: HashMap<Pubkey, Account>) -> Self {
+        let mut dependents: HashMap<Pubkey, Vec<usize>> = HashMap::new();
+        for (i, amm) in router.amms.iter().enumerate() {
+            for key in amm.get_accounts_to_update() {
+                dependents.entry(key).or_default().push(i);
+            }
+        }
+        Self {
+            router,
+            accounts,
+            dependents,
+            routes: HashMap::new(),
+            subscriptions: vec![],
+        }
+    }
+
+    /// Loads all pools of `program_id` (see `Router::load`) and subscribes to the accounts they quote from at
+    /// `ws_url`, one subscription per account. Accounts that pools start to quote from later on (e.g. a newly set rate
+    /// account) aren't subscribed to, which makes `refresh` fail for these pools until the cache is loaded again
+    pub fn subscribe(rpc_client: &RpcClient, ws_url: &str, program_id: &Pubkey) -> ClientResult<Self> {
+        let (router, _) = Router::load_with_accounts(rpc_client, program_id)?;
+        let account_config = RpcAccountInfoConfig {
+            encoding: Some(UiAccountEncoding::Base64),
+            commitment: Some(rpc_client.commitment()),
+            ..RpcAccountInfoConfig::default()
+        };
+        let mut keys: Vec<Pubkey> = router
+            .amms
+            .iter()
+            .flat_map(|amm| amm.get_accounts_to_update())
+            .collect();
+        keys.sort();
+        keys.dedup();
+        let mut subscriptions = vec![];
+        for key in keys {
+            subscriptions.push((
+                key,
+                PubsubClient::account_subscribe(ws_url, &key, Some(account_config.clone()))?,
+            ));
+        }
+        //loading again once subscribed, so that no change in between is missed
+        let (router, accounts) = Router::load_with_accounts(rpc_client, program_id)?;
+        let mut cache = Self::new(router, accounts);
+        cache.subscriptions = subscriptions;
+        Ok(cache)
+    }
+
+    pub fn router(&self) -> &Router {
+        &self.router
+    }
+
+    /// Takes the new state of an account, re-decodes the pools that quote from it and drops the cached routes they
+    /// can change. Accounts no pool quotes from are ignored
+    pub fn apply(&mut self, key: &Pubkey, account: Account) -> ClientResult<()> {
+        let mut changes = HashMap::new();
+        changes.insert(*key, account);
+        self.apply_all(changes)
+    }
+
+    //re-decodes each pool once, however many of its accounts changed
+    fn apply_all(&mut self, changes: HashMap<Pubkey, Account>) -> ClientResult<()> {
+        let mut changed_pools = BTreeSet::new();
+        for (key, account) in changes {
+            if let Some(dependents) = self.dependents.get(&key) {
+                changed_pools.extend(dependents.iter().copied());
+                self.accounts.insert(key, account);
+            }
+        }
+        for i in changed_pools {
+            let amm = &mut self.router.amms[i];
+            amm.update(&self.accounts)?;
+            let mints = amm.get_reserve_mints();
+            self.routes
+                .retain(|(mint_in, mint_out, _), _| !mints.contains(mint_in) && !mints.contains(mint_out));
+        }
+        Ok(())
+    }
+
+    /// Applies all account changes received since the last call, only the latest state of each account, and
+    /// returns the number of accounts that changed
+    pub fn refresh(&mut self) -> ClientResult<usize> {
+        let mut changes = HashMap::new();
+        for (key, (_, notifications)) in self.subscriptions.iter() {
+            loop {
+                match notifications.try_recv() {
+                    Ok(response) => {
+                        let account = response
+                            .value
+                            .decode::<Account>()
+                            .ok_or(ClientError::InvalidAccountData(*key))?;
+                        changes.insert(*key, account);
+                    }
+                    Err(error) if error.is_empty() => break,
+                    Err(_) => return Err(ClientError::SubscriptionClosed(*key)),
+                }
+            }
+        }
+        let count = changes.len();
+        self.apply_all(changes)?;
+        Ok(count)
+    }
+
+    /// The route that swaps `amount` of `mint_in` for the most `mint_out` (see `Router::find_best_route`) as of the
+    /// latest received account changes, only computed if no cached route is still valid
+    pub fn find_best_route(&mut self, mint_in: &Pubkey, mint_out: &Pubkey, amount: u64) -> ClientResult<Route> {
+        self.refresh()?;
+        let key = (*mint_in, *mint_out, amount);
+        if let Some(route) = self.routes.get(&key) {
+            return Ok(route.clone());
+        }
+        let route = self.router.find_best_route(mint_in, mint_out, amount)?;
+        self.routes.insert(key, route.clone());
+        Ok(route)
+    }
+
+    /// The number of cached routes
+    pub fn cached_routes(&self) -> usize {
+        self.routes.len()
+    }
+}
@@ -0,0 +1,164 @@
+//a compact description of a route to hand from a quoting service to an executing one, as Borsh or JSON
+//
+//the plan only names the pools, the executor loads them itself (see `Router::load_pools`) for the accounts that the
+// instructions need, so plans stay small and can't smuggle in accounts. the version comes first in both encodings,
+// a plan of another version is rejected rather than misread
+
+use super::{
+    router::{Route, RouteHop, Router},
+    ClientError, ClientResult,
+};
+use borsh::{BorshDeserialize, BorshSerialize};
+use serde::{Deserialize, Serialize};
+use solana_program::{clock::Slot, pubkey::Pubkey};
+
+pub const ROUTE_PLAN_VERSION: u8 = 1;
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct RoutePlanHop {
+    #[serde(with = "pubkey_string")]
+    pub pool: Pubkey,
+    #[serde(with = "pubkey_string")]
+    pub input_mint: Pubkey,
+    #[serde(with = "pubkey_string")]
+    pub output_mint: Pubkey,
+    pub input_index: u8,
+    pub output_index: u8,
+    pub in_amount: u64,
+    pub out_amount: u64,
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct RoutePlan {
+    pub version: u8,
+    #[serde(with = "pubkey_string")]
+    pub program_id: Pubkey,
+    /// The hops of the route as quoted, see `Route::hops`
+    pub hops: Vec<RoutePlanHop>,
+    pub minimum_output_amount: u64,
+    /// The last slot to execute the plan in
+    pub expiry_slot: Slot,
+}
+
+impl RoutePlan {
+    pub fn new(route: &Route, minimum_output_amount: u64, expiry_slot: Slot) -> Self {
+        let hops = route
+            .hops
+            .iter()
+            .map(|hop| RoutePlanHop {
+                pool: hop.leg.pool,
+                input_mint: hop.input_mint,
+                output_mint: hop.output_mint,
+                input_index: hop.input_index as u8,
+                output_index: hop.output_index as u8,
+                in_amount: hop.in_amount,
+                out_amount: hop.out_amount,
+            })
+            .collect();
+        Self {
+            version: ROUTE_PLAN_VERSION,
+            program_id: route.program_id,
+            hops,
+            minimum_output_amount,
+            expiry_slot,
+        }
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.try_to_vec().unwrap()
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> ClientResult<Self> {
+        check_version(bytes.first().copied())?;
+        Self::try_from_slice(bytes).map_err(|err| ClientError::InvalidRoutePlan(err.to_string()))
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap()
+    }
+
+    pub fn from_json(json: &str) -> ClientResult<Self> {
+        let invalid = |err: serde_json::Error| ClientError::InvalidRoutePlan(err.to_string());
+        //the version alone first, so that a plan of another version isn't rejected for its fields
+        let value: serde_json::Value = serde_json::from_str(json).map_err(invalid)?;
+        check_version(
+            value
+                .get("version")
+                .and_then(|version| version.as_u64())
+                .map(|version| version as u8),
+        )?;
+        serde_json::from_value(value).map_err(invalid)
+    }
+
+    pub fn is_expired(&self, slot: Slot) -> bool {
+        slot > self.expiry_slot
+    }
+
+    /// The pools the plan swaps in, to load the router for `to_route` with
+    pub fn pools(&self) -> Vec<Pubkey> {
+        self.hops.iter().map(|hop| hop.pool).collect()
+    }
+
+    /// The route to execute the plan with, the router has to hold the plan's pools. Fails if a hop doesn't match its
+    /// pool's tokens or the hops don't connect
+    pub fn to_route(&self, router: &Router) -> ClientResult<Route> {
+        let invalid = |reason: &str| ClientError::InvalidRoutePlan(reason.to_string());
+        if self.hops.is_empty() || self.hops.len() > 2 {
+            return Err(invalid("a route has one or two hops"));
+        }
+        let mut hops = vec![];
+        for hop in self.hops.iter() {
+            let amm = router.amm(&hop.pool)?;
+            let mints = amm.get_reserve_mints();
+            let (input_index, output_index) = (hop.input_index as usize, hop.output_index as usize);
+            if mints.get(input_index) != Some(&hop.input_mint) || mints.get(output_index) != Some(&hop.output_mint) {
+                return Err(invalid("a hop's tokens don't match its pool"));
+            }
+            hops.push(RouteHop {
+                leg: amm.route_leg()?,
+                input_mint: hop.input_mint,
+                output_mint: hop.output_mint,
+                input_index,
+                output_index,
+                in_amount: hop.in_amount,
+                out_amount: hop.out_amount,
+            });
+        }
+        if hops.windows(2).any(|pair| pair[0].output_mint != pair[1].input_mint) {
+            return Err(invalid("the hops don't connect"));
+        }
+        Ok(Route {
+            program_id: self.program_id,
+            hops,
+        })
+    }
+}
+
+fn check_version(version: Option<u8>) -> ClientResult<()> {
+    match version {
+        Some(ROUTE_PLAN_VERSION) => Ok(()),
+        Some(version) => Err(ClientError::InvalidRoutePlan(format!(
+            "unsupported version {}",
+            version
+        ))),
+        None => Err(ClientError::InvalidRoutePlan("missing version".to_string())),
+    }
+}
+
+//keys as base58 strings rather than arrays of bytes
+mod pubkey_string {
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+    use solana_program::pubkey::Pubkey;
+    use std::str::FromStr;
+
+    pub fn serialize<S: Serializer>(key: &Pubkey, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&key.to_string())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Pubkey, D::Error> {
+        let key = String::deserialize(deserializer)?;
+        Pubkey::from_str(&key).map_err(|_| D::Error::custom(format!("invalid key {}", key)))
+    }
+}
@@ -0,0 +1,567 @@
+//finds the best way to swap one token for another across all pools of a program: directly in a pool that holds both or
+// through an intermediate token in two pools (a `RoutedSwap`), quoting every candidate exactly like the program would
+// execute it (see `PoolAmm::quote`)
+//
+//the pools are those the program owns, i.e. its on-chain registry of pools, fetched with a single
+// `getProgramAccounts` request and the accounts they quote from with as few `getMultipleAccounts` requests as possible
+
+use super::{
+    amm::{AmmQuoteParams, PoolAmm, SwapMode},
+    venue::Venue,
+    ClientError, ClientResult,
+};
+use crate::{
+    instruction::{create_defi_ix, create_routed_swap_ix, DeFiInstruction, PoolInstruction, RouteLeg},
+    state::PoolState,
+    TOKEN_COUNT,
+};
+use solana_account_decoder::UiAccountEncoding;
+use solana_client::{
+    rpc_client::RpcClient,
+    rpc_config::{RpcAccountInfoConfig, RpcProgramAccountsConfig},
+    rpc_filter::RpcFilterType,
+    rpc_request::MAX_MULTIPLE_ACCOUNTS,
+};
+use solana_program::{
+    instruction::{AccountMeta, Instruction},
+    program_error::ProgramError,
+    pubkey::Pubkey,
+};
+use solana_sdk::account::Account;
+use std::collections::{HashMap, HashSet};
+
+/// A swap in a single pool along a route
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RouteHop {
+    pub leg: RouteLeg<TOKEN_COUNT>,
+    pub input_mint: Pubkey,
+    pub output_mint: Pubkey,
+    pub input_index: usize,
+    pub output_index: usize,
+    pub in_amount: u64,
+    pub out_amount: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Route {
+    pub program_id: Pubkey,
+    /// One hop, or two whose first one's output is the second one's input
+    pub hops: Vec<RouteHop>,
+}
+
+impl Route {
+    pub fn in_amount(&self) -> u64 {
+        self.hops[0].in_amount
+    }
+
+    pub fn out_amount(&self) -> u64 {
+        self.hops[self.hops.len() - 1].out_amount
+    }
+
+    /// The instructions that execute the route, `user_transfer_authority` has to sign them (and pays for the
+    /// intermediate token account of a two hop route, which it gets back within the instruction)
+    pub fn instructions(
+        &self,
+        user_transfer_authority: &Pubkey,
+        input_account: &Pubkey,
+        output_account: &Pubkey,
+        minimum_output_amount: u64,
+    ) -> ClientResult<Vec<Instruction>> {
+        match self.hops.as_slice() {
+            [hop] => Ok(vec![hop.swap_ix(
+                &self.program_id,
+                user_transfer_authority,
+                input_account,
+                output_account,
+                minimum_output_amount,
+            )?]),
+            [first, second] => Ok(vec![create_routed_swap_ix(
+                PoolInstruction::RoutedSwap {
+                    exact_input_amount: first.in_amount,
+                    input_token_index: first.input_index as u8,
+                    first_output_index: first.output_index as u8,
+                    second_input_index: second.input_index as u8,
+                    output_token_index: second.output_index as u8,
+                    minimum_output_amount,
+                },
+                &self.program_id,
+                user_transfer_authority,
+                input_account,
+                output_account,
+                &first.output_mint,
+                &first.leg,
+                &second.leg,
+            )?]),
+            _ => Err(ProgramError::InvalidArgument.into()),
+        }
+    }
+
+    /// The estimated compute units of executing the route, see `RouterConfig`
+    pub fn compute_units(&self, config: &RouterConfig) -> u32 {
+        match self.hops.len() {
+            1 => config.direct_swap_compute_units,
+            _ => config.routed_swap_compute_units,
+        }
+    }
+
+    /// The priority fee of executing the route in lamports, rounded up
+    pub fn priority_fee(&self, config: &RouterConfig) -> u64 {
+        let micro_lamports = self.compute_units(config) as u128 * config.compute_unit_price as u128;
+        ((micro_lamports + 999_999) / 1_000_000) as u64
+    }
+}
+
+impl RouteHop {
+    //the input account fills the user token account slots of the tokens that aren't swapped, like `PoolAmm` does
+    fn swap_ix(
+        &self,
+        program_id: &Pubkey,
+        user_transfer_authority: &Pubkey,
+        input_account: &Pubkey,
+        output_account: &Pubkey,
+        minimum_output_amount: u64,
+    ) -> ClientResult<Instruction> {
+        let mut exact_input_amounts = [0; TOKEN_COUNT];
+        exact_input_amounts[self.input_index] = self.in_amount;
+        let mut user_token_accounts = [*input_account; TOKEN_COUNT];
+        user_token_accounts[self.output_index] = *output_account;
+        let mut instruction = create_defi_ix(
+            DeFiInstruction::SwapExactInput {
+                exact_input_amounts,
+                output_token_index: self.output_index as u8,
+                minimum_output_amount,
+            },
+            program_id,
+            &self.leg.pool,
+            &self.leg.authority,
+            &self.leg.token_accounts,
+            &self.leg.lp_mint,
+            &self.leg.governance_fee_account,
+            user_transfer_authority,
+            &user_token_accounts,
+            &spl_token::id(),
+            None,
+        )?;
+        instruction.accounts.extend(
+            self.leg
+                .swap_accounts
+                .iter()
+                .map(|key| AccountMeta::new_readonly(*key, false)),
+        );
+        Ok(instruction)
+    }
+}
+
+/// Routes that together swap an amount, each executed by its own instruction within a single transaction
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SplitRoute {
+    pub routes: Vec<Route>,
+}
+
+impl SplitRoute {
+    pub fn in_amount(&self) -> u64 {
+        self.routes.iter().map(Route::in_amount).sum()
+    }
+
+    pub fn out_amount(&self) -> u64 {
+        self.routes.iter().map(Route::out_amount).sum()
+    }
+
+    /// The instructions of all routes, each route's minimum output is its share of `minimum_output_amount` (rounded up,
+    /// so they add up to at least the total)
+    pub fn instructions(
+        &self,
+        user_transfer_authority: &Pubkey,
+        input_account: &Pubkey,
+        output_account: &Pubkey,
+        minimum_output_amount: u64,
+    ) -> ClientResult<Vec<Instruction>> {
+        let out_amount = self.out_amount().max(1) as u128;
+        let mut instructions = vec![];
+        for route in self.routes.iter() {
+            let share = route.out_amount() as u128 * minimum_output_amount as u128;
+            let minimum_route_output = ((share + out_amount - 1) / out_amount) as u64;
+            instructions.extend(route.instructions(
+                user_transfer_authority,
+                input_account,
+                output_account,
+                minimum_route_output,
+            )?);
+        }
+        Ok(instructions)
+    }
+}
+
+/// Estimated compute units of a `Swap` in a single pool, the worst case of the program's swap paths (a rated and oracle
+/// guarded pool, which reads and checks the most accounts) with some headroom
+pub const DIRECT_SWAP_COMPUTE_UNITS: u32 = 80_000;
+/// Estimated compute units of a `RoutedSwap`, two swaps plus creating and closing the intermediate token account
+pub const ROUTED_SWAP_COMPUTE_UNITS: u32 = 170_000;
+
+/// How `Router::find_best_route` trades output for execution costs. Routes are scored by their output minus their
+/// weighted priority fee in the output token minus the hop penalty, the default scores by output alone
+#[derive(Debug, Clone, PartialEq)]
+pub struct RouterConfig {
+    /// The priority fee in micro-lamports per compute unit that routes are sent with (see `ExecutionConfig`)
+    pub compute_unit_price: u64,
+    /// The value of a lamport in the smallest unit of a token, by mint. Fees of routes to tokens without one aren't
+    /// scored
+    pub lamport_prices: HashMap<Pubkey, f64>,
+    /// The weight of the priority fee against the output, 1 values a lamport at its price and 0 ignores fees
+    pub fee_weight: f64,
+    /// The share of its output in basis points that a two hop route has to pay more than a direct swap, for the
+    /// risk of the second pool moving until the route lands
+    pub hop_penalty_bps: u64,
+    pub direct_swap_compute_units: u32,
+    pub routed_swap_compute_units: u32,
+}
+
+impl Default for RouterConfig {
+    fn default() -> Self {
+        Self {
+            compute_unit_price: 0,
+            lamport_prices: HashMap::new(),
+            fee_weight: 1.0,
+            hop_penalty_bps: 0,
+            direct_swap_compute_units: DIRECT_SWAP_COMPUTE_UNITS,
+            routed_swap_compute_units: ROUTED_SWAP_COMPUTE_UNITS,
+        }
+    }
+}
+
+impl RouterConfig {
+    /// The value of `route` in its output token after its costs
+    pub fn score(&self, route: &Route) -> f64 {
+        let output_mint = &route.hops[route.hops.len() - 1].output_mint;
+        let hop_penalty = (route.hops.len() - 1) as f64 * self.hop_penalty_bps as f64 / 10_000.0;
+        let fee = match self.lamport_prices.get(output_mint) {
+            Some(lamport_price) => self.fee_weight * route.priority_fee(self) as f64 * lamport_price,
+            None => 0.0,
+        };
+        route.out_amount() as f64 * (1.0 - hop_penalty) - fee
+    }
+
+    //a higher score is better, fewer hops break ties
+    fn is_better(&self, route: &Route, other: &Route) -> bool {
+        let (score, other_score) = (self.score(route), self.score(other));
+        score > other_score || (score == other_score && route.hops.len() < other.hops.len())
+    }
+}
+
+/// The number of parts that `Router::find_best_split` divides an amount into
+pub const SPLIT_PARTS: u64 = 20;
+/// The most pools that `Router::find_best_split` splits an amount across, which keeps the transaction within its size
+/// limit
+pub const MAX_SPLIT_ROUTES: usize = 3;
+
+pub struct Router {
+    pub(super) program_id: Pubkey,
+    pub(super) amms: Vec<PoolAmm>,
+    //other programs' pools, see `Router::add_venue`
+    pub(super) venues: Vec<Box<dyn Venue>>,
+    pub(super) config: RouterConfig,
+}
+
+impl Router {
+    /// Routes through `amms`, which have to be pools of `program_id` that were updated already
+    pub fn new(program_id: Pubkey, amms: Vec<PoolAmm>) -> Self {
+        Self {
+            program_id,
+            amms,
+            venues: vec![],
+            config: RouterConfig::default(),
+        }
+    }
+
+    /// Sets how routes are scored, see `RouterConfig`
+    pub fn set_config(&mut self, config: RouterConfig) {
+        self.config = config;
+    }
+
+    pub fn config(&self) -> &RouterConfig {
+        &self.config
+    }
+
+    /// Loads all pools of `program_id`, leaving out those whose accounts can't be loaded (e.g. a closed rate account)
+    pub fn load(rpc_client: &RpcClient, program_id: &Pubkey) -> ClientResult<Self> {
+        Ok(Self::load_with_accounts(rpc_client, program_id)?.0)
+    }
+
+    /// `load`, along with the accounts the pools were updated with
+    pub(super) fn load_with_accounts(
+        rpc_client: &RpcClient,
+        program_id: &Pubkey,
+    ) -> ClientResult<(Self, HashMap<Pubkey, Account>)> {
+        let config = RpcProgramAccountsConfig {
+            filters: Some(vec![RpcFilterType::DataSize(
+                solana_program::borsh::get_packed_len::<PoolState<TOKEN_COUNT>>() as u64,
+            )]),
+            account_config: RpcAccountInfoConfig {
+                encoding: Some(UiAccountEncoding::Base64),
+                commitment: Some(rpc_client.commitment()),
+                ..RpcAccountInfoConfig::default()
+            },
+            ..RpcProgramAccountsConfig::default()
+        };
+        let amms: Vec<PoolAmm> = rpc_client
+            .get_program_accounts_with_config(program_id, config)?
+            .iter()
+            .filter_map(|(pool, account)| PoolAmm::from_keyed_account(pool, account).ok())
+            .collect();
+
+        let accounts = fetch_accounts(rpc_client, &amms)?;
+        let amms = amms
+            .into_iter()
+            .filter_map(|mut amm| amm.update(&accounts).ok().map(|_| amm))
+            .collect();
+        Ok((Self::new(*program_id, amms), accounts))
+    }
+
+    /// Loads the given pools of `program_id` only, failing if any of them can't be loaded
+    pub fn load_pools(rpc_client: &RpcClient, program_id: &Pubkey, pools: &[Pubkey]) -> ClientResult<Self> {
+        let response = rpc_client.get_multiple_accounts_with_commitment(pools, rpc_client.commitment())?;
+        let amms = pools
+            .iter()
+            .zip(response.value)
+            .map(|(pool, account)| {
+                let account = account
+                    .filter(|account| account.owner == *program_id)
+                    .ok_or(ClientError::AccountNotFound(*pool))?;
+                PoolAmm::from_keyed_account(pool, &account)
+            })
+            .collect::<ClientResult<Vec<_>>>()?;
+        let accounts = fetch_accounts(rpc_client, &amms)?;
+        let amms = amms
+            .into_iter()
+            .map(|mut amm| amm.update(&accounts).map(|_| amm))
+            .collect::<ClientResult<Vec<_>>>()?;
+        Ok(Self::new(*program_id, amms))
+    }
+
+    pub fn pools(&self) -> &[PoolAmm] {
+        &self.amms
+    }
+
+    /// The route that swaps `amount` of `mint_in` for the most `mint_out` after costs (see `RouterConfig`), out of all
+    /// direct swaps and all two hop routes through the pools' other tokens. Pools that can't swap at the moment (e.g.
+    /// paused ones) are skipped
+    pub fn find_best_route(&self, mint_in: &Pubkey, mint_out: &Pubkey, amount: u64) -> ClientResult<Route> {
+        if amount == 0 || mint_in == mint_out {
+            return Err(ProgramError::InvalidArgument.into());
+        }
+        let mut best: Option<Route> = None;
+        let mut consider = |hops: Vec<RouteHop>| {
+            let route = Route {
+                program_id: self.program_id,
+                hops,
+            };
+            let is_better = match &best {
+                Some(best) => self.config.is_better(&route, best),
+                None => true,
+            };
+            if is_better {
+                best = Some(route);
+            }
+        };
+        for amm in self.amms.iter() {
+            let mints = amm.get_reserve_mints();
+            if !mints.contains(mint_in) {
+                continue;
+            }
+            if mints.contains(mint_out) {
+                if let Ok(hop) = quote_hop(amm, mint_in, mint_out, amount) {
+                    consider(vec![hop]);
+                }
+            }
+            for intermediate_mint in mints.iter().filter(|mint| *mint != mint_in && *mint != mint_out) {
+                let first = match quote_hop(amm, mint_in, intermediate_mint, amount) {
+                    Ok(first) => first,
+                    Err(_) => continue,
+                };
+                for second_amm in self.amms.iter().filter(|second_amm| second_amm.key() != amm.key()) {
+                    let second_mints = second_amm.get_reserve_mints();
+                    if !second_mints.contains(intermediate_mint) || !second_mints.contains(mint_out) {
+                        continue;
+                    }
+                    if let Ok(second) = quote_hop(second_amm, intermediate_mint, mint_out, first.out_amount) {
+                        consider(vec![first.clone(), second]);
+                    }
+                }
+            }
+        }
+        best.ok_or(ClientError::NoRoute(*mint_in, *mint_out))
+    }
+
+    /// Splits `amount` of `mint_in` across the (up to `MAX_SPLIT_ROUTES`) pools that pay the most `mint_out` for it,
+    /// in `SPLIT_PARTS` parts that each go to the pool where they add the most output. Price impact makes each pool's
+    /// output grow ever slower with its input, so this maximizes the total output up to the size of a part. Falls
+    /// back to the best single route (see `find_best_route`) if it pays more, e.g. a two hop route
+    pub fn find_best_split(&self, mint_in: &Pubkey, mint_out: &Pubkey, amount: u64) -> ClientResult<SplitRoute> {
+        let best_route = self.find_best_route(mint_in, mint_out, amount)?;
+        let mut candidates: Vec<(&PoolAmm, u64)> = self
+            .amms
+            .iter()
+            .filter_map(|amm| {
+                let hop = quote_hop(amm, mint_in, mint_out, amount).ok()?;
+                Some((amm, hop.out_amount))
+            })
+            .collect();
+        candidates.sort_by(|(_, a), (_, b)| b.cmp(a));
+        candidates.truncate(MAX_SPLIT_ROUTES);
+        let part = amount / SPLIT_PARTS;
+        if candidates.len() < 2 || part == 0 {
+            return Ok(SplitRoute {
+                routes: vec![best_route],
+            });
+        }
+
+        let mut hops: Vec<Option<RouteHop>> = vec![None; candidates.len()];
+        for n in 0..SPLIT_PARTS {
+            //the last part includes the remainder
+            let part_amount = if n == SPLIT_PARTS - 1 {
+                amount - (SPLIT_PARTS - 1) * part
+            } else {
+                part
+            };
+            let mut best_part: Option<(usize, RouteHop)> = None;
+            let mut best_gain = 0;
+            for (i, (amm, _)) in candidates.iter().enumerate() {
+                let (in_amount, out_amount) = match &hops[i] {
+                    Some(hop) => (hop.in_amount, hop.out_amount),
+                    None => (0, 0),
+                };
+                if let Ok(hop) = quote_hop(amm, mint_in, mint_out, in_amount + part_amount) {
+                    let gain = hop.out_amount.saturating_sub(out_amount);
+                    if best_part.is_none() || gain > best_gain {
+                        best_part = Some((i, hop));
+                        best_gain = gain;
+                    }
+                }
+            }
+            let (i, hop) = best_part.ok_or(ClientError::NoRoute(*mint_in, *mint_out))?;
+            hops[i] = Some(hop);
+        }
+
+        let split = SplitRoute {
+            routes: hops
+                .into_iter()
+                .flatten()
+                .map(|hop| Route {
+                    program_id: self.program_id,
+                    hops: vec![hop],
+                })
+                .collect(),
+        };
+        if split.out_amount() > best_route.out_amount() {
+            Ok(split)
+        } else {
+            Ok(SplitRoute {
+                routes: vec![best_route],
+            })
+        }
+    }
+
+    pub(super) fn amm(&self, pool: &Pubkey) -> ClientResult<&PoolAmm> {
+        self.amms
+            .iter()
+            .find(|amm| amm.key() == *pool)
+            .ok_or(ClientError::AccountNotFound(*pool))
+    }
+
+    /// Quotes the hops of `route` again, e.g. after the pools were updated
+    pub fn requote(&self, route: &Route) -> ClientResult<Route> {
+        let mut hops: Vec<RouteHop> = vec![];
+        for hop in route.hops.iter() {
+            let amount = hops.last().map_or(hop.in_amount, |previous| previous.out_amount);
+            hops.push(quote_hop(
+                self.amm(&hop.leg.pool)?,
+                &hop.input_mint,
+                &hop.output_mint,
+                amount,
+            )?);
+        }
+        Ok(Route {
+            program_id: route.program_id,
+            hops,
+        })
+    }
+
+    /// How much worse than the marginal price the route's fee-free price is, as a fraction, the marginal price being
+    /// approximated by the price of a swap of a millionth of the input token's balance (like `pool-cli quote` does)
+    pub fn price_impact(&self, route: &Route) -> ClientResult<f64> {
+        let mut retained = 1.0;
+        for hop in route.hops.iter() {
+            let amm = self.amm(&hop.leg.pool)?;
+            let snapshot = amm.snapshot().ok_or(ClientError::AccountNotFound(hop.leg.pool))?;
+            let fee_free_price = |amount: u64| -> ClientResult<f64> {
+                let quote = amm.quote(&AmmQuoteParams {
+                    input_mint: hop.input_mint,
+                    output_mint: hop.output_mint,
+                    amount,
+                    swap_mode: SwapMode::ExactIn,
+                })?;
+                Ok((quote.out_amount + quote.fee_amount) as f64 / amount as f64)
+            };
+            let marginal_amount = (snapshot.balances[hop.input_index] / 1_000_000).max(1);
+            let ratio = fee_free_price(hop.in_amount)? / fee_free_price(marginal_amount)?;
+            retained *= ratio.min(1.0);
+        }
+        Ok(1.0 - retained)
+    }
+}
+
+/// Loads all pools of `program_id` and finds the best route through them (see `Router::find_best_route`)
+pub fn find_best_route(
+    rpc_client: &RpcClient,
+    program_id: &Pubkey,
+    mint_in: &Pubkey,
+    mint_out: &Pubkey,
+    amount: u64,
+) -> ClientResult<Route> {
+    Router::load(rpc_client, program_id)?.find_best_route(mint_in, mint_out, amount)
+}
+
+//pools share the clock and often rate accounts, each account is only fetched once
+fn fetch_accounts(rpc_client: &RpcClient, amms: &[PoolAmm]) -> ClientResult<HashMap<Pubkey, Account>> {
+    let keys: Vec<Pubkey> = amms
+        .iter()
+        .flat_map(|amm| amm.get_accounts_to_update())
+        .collect::<HashSet<_>>()
+        .into_iter()
+        .collect();
+    let mut accounts = HashMap::new();
+    for chunk in keys.chunks(MAX_MULTIPLE_ACCOUNTS) {
+        let response = rpc_client.get_multiple_accounts_with_commitment(chunk, rpc_client.commitment())?;
+        for (key, account) in chunk.iter().zip(response.value) {
+            if let Some(account) = account {
+                accounts.insert(*key, account);
+            }
+        }
+    }
+    Ok(accounts)
+}
+
+pub(super) fn quote_hop(
+    amm: &PoolAmm,
+    input_mint: &Pubkey,
+    output_mint: &Pubkey,
+    amount: u64,
+) -> ClientResult<RouteHop> {
+    let quote = amm.quote(&AmmQuoteParams {
+        input_mint: *input_mint,
+        output_mint: *output_mint,
+        amount,
+        swap_mode: SwapMode::ExactIn,
+    })?;
+    let mints = amm.get_reserve_mints();
+    let index = |mint: &Pubkey| mints.iter().position(|pool_mint| pool_mint == mint).unwrap();
+    Ok(RouteHop {
+        leg: amm.route_leg()?,
+        input_mint: *input_mint,
+        output_mint: *output_mint,
+        input_index: index(input_mint),
+        output_index: index(output_mint),
+        in_amount: quote.in_amount,
+        out_amount: quote.out_amount,
+    })
+}
@@ -0,0 +1,314 @@
+//a consistent view of a pool at a single slot: its decoded state together with everything that lives in other accounts
+// (token balances, lp supply) or depends on the time (the interpolated amp factor)
+
+use super::{
+    pool::{compute_rates, PoolClient},
+    ClientError, ClientResult,
+};
+use crate::{
+    common::create_result_array,
+    decimal::{DecimalU64, U128},
+    error::PoolError,
+    instruction::{DeFiInstruction, SimulationResult},
+    oracle::{self, OraclePrice},
+    quote::{self, to_equalized, to_rated, Quote, Quoter},
+    state::PoolState,
+    token_2022, validation, TOKEN_COUNT,
+};
+use solana_program::{
+    clock::{Clock, Slot, UnixTimestamp},
+    program_pack::{IsInitialized, Pack},
+    pubkey::Pubkey,
+    sysvar,
+};
+use solana_sdk::account::{from_account, Account};
+use spl_token::state::{Account as TokenState, Mint as MintState};
+
+pub struct PoolSnapshot {
+    pub pool: Pubkey,
+    pub slot: Slot,
+    pub unix_timestamp: UnixTimestamp,
+    pub state: PoolState<TOKEN_COUNT>,
+    pub balances: [u64; TOKEN_COUNT],
+    pub lp_supply: u64,
+    /// The amp factor in effect at `unix_timestamp` (see `PoolState::effective_amp_factor`)
+    pub amp_factor: DecimalU64,
+    /// Exchange rates of the pool's tokens (see `oracle::load_rate`)
+    pub rates: [u64; TOKEN_COUNT],
+    /// The prices swaps are checked against (see `oracle::guard_prices`), `None` for unguarded swaps and the error
+    /// swaps fail with while a guard oracle is unavailable and the pool rejects them
+    pub oracle_guard: Result<Option<[OraclePrice; TOKEN_COUNT]>, PoolError>,
+    /// The depth of the current balances in equalized units (unlike `state.previous_depth` this accounts for tokens
+    /// that were sent to the pool directly and for amp factor adjustments since the last instruction)
+    pub depth: U128,
+}
+
+impl PoolSnapshot {
+    /// The value of a single lp token in units of the underlying tokens (1.0 at the first add), `None` for an empty pool
+    pub fn virtual_price(&self) -> Option<f64> {
+        if self.lp_supply == 0 {
+            return None;
+        }
+        let equalized_lp_supply = self.lp_supply as f64 * 10f64.powi(self.state.lp_decimal_equalizer as i32);
+        Some(self.depth.as_u128() as f64 / equalized_lp_supply)
+    }
+
+    /// The largest relative deviation of a token's equalized balance from the average equalized balance (0 for a
+    /// perfectly balanced pool, `TOKEN_COUNT - 1` if a single token makes up the entire pool)
+    pub fn imbalance(&self) -> f64 {
+        let balances: Vec<f64> = self
+            .equalized_balances()
+            .iter()
+            .map(|balance| balance.as_u128() as f64)
+            .collect();
+        let average = balances.iter().sum::<f64>() / TOKEN_COUNT as f64;
+        if average == 0.0 {
+            return 0.0;
+        }
+        balances
+            .iter()
+            .map(|balance| (balance - average).abs() / average)
+            .fold(0.0, f64::max)
+    }
+
+    /// Balances (including the tokens deployed to yield adapters and the peg keeper's virtual balance, see
+    /// `PoolState::invariant_balances`) scaled to a common
+    /// number of decimals and valued at their exchange rates (i.e. the equalized balances that the invariant operates
+    /// on)
+    pub fn equalized_balances(&self) -> [U128; TOKEN_COUNT] {
+        let balances = self.state.invariant_balances(&self.balances);
+        let mut equalized = [U128::zero(); TOKEN_COUNT];
+        for (i, balance) in equalized.iter_mut().enumerate() {
+            *balance = to_rated(
+                to_equalized(balances[i], self.state.token_decimal_equalizers[i]),
+                self.rates[i],
+                false,
+            );
+        }
+        equalized
+    }
+
+    /// Quotes adds and removes exactly like the program executes them at the snapshot's slot
+    pub fn quoter(&self) -> Quoter<'_, TOKEN_COUNT> {
+        Quoter::new(&self.state, &self.balances, self.lp_supply, self.amp_factor).with_rates(&self.rates)
+    }
+
+    /// Quotes swaps exactly like the program executes them at the snapshot's slot, including the oracle guard's
+    /// deviation check, fails while swaps fail because a guard oracle is unavailable
+    pub fn swap_quoter(&self) -> ClientResult<Quoter<'_, TOKEN_COUNT>> {
+        let oracle_prices = self.oracle_guard?;
+        Ok(self
+            .quoter()
+            .with_oracle_guard(oracle_prices.as_ref(), self.state.max_oracle_deviation.get()))
+    }
+
+    /// Runs the program's checks of `defi_instruction` that don't involve the user's accounts (see the `validation`
+    /// module) and quotes it, failing with the error that the program would fail with at the snapshot's slot, e.g. to
+    /// check an instruction before sending it. Expects the executed instruction, not a `Simulate`
+    pub fn preflight(
+        &self,
+        defi_instruction: &DeFiInstruction<TOKEN_COUNT>,
+    ) -> ClientResult<SimulationResult<TOKEN_COUNT>> {
+        validation::check_status(&self.state, defi_instruction, self.unix_timestamp)?;
+        validation::check_amounts(
+            defi_instruction,
+            &self.state.total_balances(&self.balances),
+            self.lp_supply,
+        )?;
+        validation::check_pegged(&self.state, defi_instruction)?;
+        let single = |index: u8, amount| {
+            let mut amounts = [0; TOKEN_COUNT];
+            amounts[index as usize] = amount;
+            amounts
+        };
+        let result = match *defi_instruction {
+            DeFiInstruction::Add { input_amounts, .. } => {
+                let Quote {
+                    amount,
+                    governance_mint_amount,
+                    ..
+                } = self.quoter().add(&input_amounts)?;
+                SimulationResult {
+                    lp_amount: amount,
+                    input_amounts,
+                    output_amounts: [0; TOKEN_COUNT],
+                    governance_mint_amount,
+                }
+            }
+            DeFiInstruction::RemoveUniform { exact_burn_amount, .. } => SimulationResult {
+                lp_amount: exact_burn_amount,
+                input_amounts: [0; TOKEN_COUNT],
+                output_amounts: quote::remove_uniform(&self.state, &self.balances, self.lp_supply, exact_burn_amount).0,
+                governance_mint_amount: 0,
+            },
+            DeFiInstruction::SwapExactInput {
+                exact_input_amounts,
+                output_token_index,
+                ..
+            } => {
+                let Quote {
+                    amount,
+                    governance_mint_amount,
+                    ..
+                } = self
+                    .swap_quoter()?
+                    .swap_exact_input(&exact_input_amounts, output_token_index as usize)?;
+                SimulationResult {
+                    lp_amount: 0,
+                    input_amounts: exact_input_amounts,
+                    output_amounts: single(output_token_index, amount),
+                    governance_mint_amount,
+                }
+            }
+            DeFiInstruction::SwapExactOutput {
+                input_token_index,
+                exact_output_amounts,
+                ..
+            } => {
+                let Quote {
+                    amount,
+                    governance_mint_amount,
+                    ..
+                } = self
+                    .swap_quoter()?
+                    .swap_exact_output(input_token_index as usize, &exact_output_amounts)?;
+                SimulationResult {
+                    lp_amount: 0,
+                    input_amounts: single(input_token_index, amount),
+                    output_amounts: exact_output_amounts,
+                    governance_mint_amount,
+                }
+            }
+            DeFiInstruction::RemoveExactBurn {
+                exact_burn_amount,
+                output_token_index,
+                ..
+            } => {
+                let Quote {
+                    amount,
+                    governance_mint_amount,
+                    ..
+                } = self
+                    .quoter()
+                    .remove_exact_burn(exact_burn_amount, output_token_index as usize)?;
+                SimulationResult {
+                    lp_amount: exact_burn_amount,
+                    input_amounts: [0; TOKEN_COUNT],
+                    output_amounts: single(output_token_index, amount),
+                    governance_mint_amount,
+                }
+            }
+            DeFiInstruction::RemoveExactOutput {
+                exact_output_amounts, ..
+            } => {
+                let Quote {
+                    amount,
+                    governance_mint_amount,
+                    ..
+                } = self.quoter().remove_exact_output(&exact_output_amounts)?;
+                SimulationResult {
+                    lp_amount: amount,
+                    input_amounts: [0; TOKEN_COUNT],
+                    output_amounts: exact_output_amounts,
+                    governance_mint_amount,
+                }
+            }
+            //rejected by check_amounts
+            DeFiInstruction::Simulate(_) => unreachable!(),
+        };
+        validation::check_limits(&self.state, defi_instruction, &result)?;
+        validation::check_liquidity(&self.balances, &result)?;
+        Ok(result)
+    }
+
+    /// The accounts that make up a pool whose state is `state`, in the order `from_accounts` expects them
+    pub fn account_keys(pool: &Pubkey, state: &PoolState<TOKEN_COUNT>) -> Vec<Pubkey> {
+        let mut keys = vec![*pool, sysvar::clock::id(), state.lp_mint_key];
+        keys.extend_from_slice(&state.token_keys);
+        keys.extend(state.rate_account_keys());
+        if state.is_oracle_guarded() {
+            keys.extend_from_slice(&state.oracle_keys);
+        }
+        keys
+    }
+
+    /// Decodes the accounts of `account_keys(pool, state)` (fetched at `slot`), `state` is only used for the order of
+    /// the accounts, the snapshot's state is decoded from the pool account
+    pub fn from_accounts(
+        pool: &Pubkey,
+        state: &PoolState<TOKEN_COUNT>,
+        slot: Slot,
+        accounts: &[Account],
+    ) -> ClientResult<Self> {
+        let keys = Self::account_keys(pool, state);
+        if let Some(missing_key) = keys.get(accounts.len()) {
+            return Err(ClientError::AccountNotFound(*missing_key));
+        }
+        let oracle_start = 3 + TOKEN_COUNT + state.rate_account_keys().len();
+
+        let state =
+            PoolState::<TOKEN_COUNT>::unpack(&accounts[0].data).ok_or(ClientError::InvalidAccountData(*pool))?;
+        let clock = from_account::<Clock, _>(&accounts[1]).ok_or(ClientError::InvalidAccountData(keys[1]))?;
+        let lp_supply = state.lp_supply(unpack::<MintState>(&keys[2], &accounts[2])?.supply);
+        let mut balances = [0; TOKEN_COUNT];
+        for i in 0..TOKEN_COUNT {
+            balances[i] = unpack::<TokenState>(&keys[3 + i], &accounts[3 + i])?.amount;
+        }
+
+        let rates = compute_rates(&state, &accounts[3 + TOKEN_COUNT..oracle_start], &clock)?;
+        let oracle_guard = if keys.len() > oracle_start {
+            let loaded_prices = create_result_array(|i| {
+                oracle::load_checked_price(
+                    state.oracle_kinds[i],
+                    &accounts[oracle_start + i].data,
+                    &state.oracle_limits[i],
+                    clock.slot,
+                )
+            });
+            oracle::guard_prices(loaded_prices, state.oracle_fallback, &state.ema_prices, &rates)
+        } else {
+            Ok(None)
+        };
+
+        let amp_factor = state.effective_amp_factor(clock.unix_timestamp);
+        let mut snapshot = PoolSnapshot {
+            pool: *pool,
+            slot,
+            unix_timestamp: clock.unix_timestamp,
+            state,
+            balances,
+            lp_supply,
+            amp_factor,
+            rates,
+            oracle_guard,
+            depth: U128::zero(),
+        };
+        if lp_supply != 0 {
+            snapshot.depth = quote::depth(&snapshot.state, &snapshot.equalized_balances(), amp_factor)?;
+        }
+        Ok(snapshot)
+    }
+}
+
+impl PoolClient {
+    /// Fetches all accounts that make up the pool with a single request, so they all reflect the same slot
+    pub fn snapshot(&self) -> ClientResult<PoolSnapshot> {
+        let keys = PoolSnapshot::account_keys(&self.pool, &self.state);
+        let response = self
+            .rpc_client
+            .get_multiple_accounts_with_commitment(&keys, self.rpc_client.commitment())?;
+        let accounts = keys
+            .iter()
+            .zip(response.value)
+            .map(|(key, account)| account.ok_or(ClientError::AccountNotFound(*key)))
+            .collect::<ClientResult<Vec<_>>>()?;
+        PoolSnapshot::from_accounts(&self.pool, &self.state, response.context.slot, &accounts)
+    }
+}
+
+fn unpack<T: Pack + IsInitialized>(key: &Pubkey, account: &Account) -> ClientResult<T> {
+    if account.owner == token_2022::id() {
+        return token_2022::unpack(&account.data).map_err(|_| ClientError::InvalidAccountData(*key));
+    }
+    T::unpack(&account.data).map_err(|_| ClientError::InvalidAccountData(*key))
+}
@@ -0,0 +1,317 @@
+//a `Venue` for pools of the spl token-swap program and its forks with the same account layout and swap instruction
+// (e.g. Orca's first pools), restricted to the constant product curve
+//
+//the layout is decoded by hand rather than through the program's crate, which would pull in its own solana version
+
+use super::{
+    amm::{AmmQuote, AmmQuoteParams, AmmSwapParams, SwapMode},
+    venue::Venue,
+    ClientError, ClientResult,
+};
+use solana_program::{
+    instruction::{AccountMeta, Instruction},
+    program_error::ProgramError,
+    program_pack::Pack,
+    pubkey::Pubkey,
+};
+use solana_sdk::account::Account;
+use spl_token::state::Account as TokenState;
+use std::{collections::HashMap, convert::TryInto};
+
+/// The length of a pool account: a version byte and the `SwapV1` state
+pub const TOKEN_SWAP_LEN: usize = 324;
+const SWAP_INSTRUCTION_TAG: u8 = 1;
+const CONSTANT_PRODUCT_CURVE: u8 = 0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TokenSwapFees {
+    pub trade_fee_numerator: u64,
+    pub trade_fee_denominator: u64,
+    pub owner_trade_fee_numerator: u64,
+    pub owner_trade_fee_denominator: u64,
+}
+
+impl TokenSwapFees {
+    //the program charges at least one token if the fee isn't zero
+    fn fee(amount: u64, numerator: u64, denominator: u64) -> Option<u64> {
+        if numerator == 0 || amount == 0 {
+            return Some(0);
+        }
+        let fee = (amount as u128 * numerator as u128).checked_div(denominator as u128)? as u64;
+        Some(fee.max(1))
+    }
+
+    fn amount_after_fees(&self, amount: u64) -> Option<u64> {
+        let trade_fee = Self::fee(amount, self.trade_fee_numerator, self.trade_fee_denominator)?;
+        let owner_fee = Self::fee(amount, self.owner_trade_fee_numerator, self.owner_trade_fee_denominator)?;
+        amount.checked_sub(trade_fee)?.checked_sub(owner_fee)
+    }
+}
+
+pub struct TokenSwapVenue {
+    program_id: Pubkey,
+    pool: Pubkey,
+    authority: Pubkey,
+    token_program_id: Pubkey,
+    token_accounts: [Pubkey; 2],
+    pool_mint: Pubkey,
+    mints: [Pubkey; 2],
+    pool_fee_account: Pubkey,
+    fees: TokenSwapFees,
+    //`None` until the first `update`
+    balances: Option<[u64; 2]>,
+}
+
+impl TokenSwapVenue {
+    /// Decodes the pool account, the program id is taken from its owner
+    pub fn from_keyed_account(pool: &Pubkey, account: &Account) -> ClientResult<Self> {
+        let invalid = || ClientError::InvalidAccountData(*pool);
+        let data = &account.data;
+        //version 1, initialized, constant product curve
+        if data.len() != TOKEN_SWAP_LEN || data[0] != 1 || data[1] != 1 || data[291] != CONSTANT_PRODUCT_CURVE {
+            return Err(invalid());
+        }
+        let key = |offset: usize| Pubkey::new_from_array(data[offset..offset + 32].try_into().unwrap());
+        let number = |offset: usize| u64::from_le_bytes(data[offset..offset + 8].try_into().unwrap());
+        let authority =
+            Pubkey::create_program_address(&[&pool.to_bytes(), &[data[2]]], &account.owner).map_err(|_| invalid())?;
+        Ok(Self {
+            program_id: account.owner,
+            pool: *pool,
+            authority,
+            token_program_id: key(3),
+            token_accounts: [key(35), key(67)],
+            pool_mint: key(99),
+            mints: [key(131), key(163)],
+            pool_fee_account: key(195),
+            fees: TokenSwapFees {
+                trade_fee_numerator: number(227),
+                trade_fee_denominator: number(235),
+                owner_trade_fee_numerator: number(243),
+                owner_trade_fee_denominator: number(251),
+            },
+            balances: None,
+        })
+    }
+
+    pub fn fees(&self) -> TokenSwapFees {
+        self.fees
+    }
+
+    //(input, output)
+    fn token_indices(&self, input_mint: &Pubkey, output_mint: &Pubkey) -> ClientResult<(usize, usize)> {
+        match (input_mint, output_mint) {
+            (input, output) if *input == self.mints[0] && *output == self.mints[1] => Ok((0, 1)),
+            (input, output) if *input == self.mints[1] && *output == self.mints[0] => Ok((1, 0)),
+            _ => Err(ProgramError::InvalidArgument.into()),
+        }
+    }
+
+    //the program's constant product curve, which rounds the remaining output balance up
+    fn swap_output(source_balance: u64, destination_balance: u64, source_amount: u64) -> Option<u64> {
+        let invariant = source_balance as u128 * destination_balance as u128;
+        let new_source_balance = source_balance as u128 + source_amount as u128;
+        let new_destination_balance = (invariant + new_source_balance)
+            .checked_sub(1)?
+            .checked_div(new_source_balance)?;
+        (destination_balance as u128)
+            .checked_sub(new_destination_balance)
+            .map(|amount| amount as u64)
+    }
+}
+
+impl Venue for TokenSwapVenue {
+    fn key(&self) -> Pubkey {
+        self.pool
+    }
+
+    fn label(&self) -> String {
+        "Token Swap".to_string()
+    }
+
+    fn get_reserve_mints(&self) -> Vec<Pubkey> {
+        self.mints.to_vec()
+    }
+
+    /// The pool account and its two token accounts
+    fn get_accounts_to_update(&self) -> Vec<Pubkey> {
+        vec![self.pool, self.token_accounts[0], self.token_accounts[1]]
+    }
+
+    fn update(&mut self, accounts: &HashMap<Pubkey, Account>) -> ClientResult<()> {
+        let account = |key: &Pubkey| accounts.get(key).ok_or(ClientError::AccountNotFound(*key));
+        let updated = Self::from_keyed_account(&self.pool, account(&self.pool)?)?;
+        let mut balances = [0; 2];
+        for (balance, key) in balances.iter_mut().zip(updated.token_accounts.iter()) {
+            *balance = TokenState::unpack(&account(key)?.data)
+                .map_err(|_| ClientError::InvalidAccountData(*key))?
+                .amount;
+        }
+        *self = Self {
+            balances: Some(balances),
+            ..updated
+        };
+        Ok(())
+    }
+
+    /// Only quotes exact input swaps, like the program only executes them
+    fn quote(&self, params: &AmmQuoteParams) -> ClientResult<AmmQuote> {
+        let balances = self.balances.ok_or(ClientError::AccountNotFound(self.pool))?;
+        let (input_index, output_index) = self.token_indices(&params.input_mint, &params.output_mint)?;
+        if params.swap_mode != SwapMode::ExactIn || params.amount == 0 {
+            return Err(ProgramError::InvalidArgument.into());
+        }
+        let (source_balance, destination_balance) = (balances[input_index], balances[output_index]);
+        let out_amount = self
+            .fees
+            .amount_after_fees(params.amount)
+            .and_then(|amount| Self::swap_output(source_balance, destination_balance, amount))
+            .filter(|amount| *amount > 0)
+            .ok_or(ProgramError::InsufficientFunds)?;
+        let fee_free_amount = Self::swap_output(source_balance, destination_balance, params.amount).unwrap_or(0);
+        Ok(AmmQuote {
+            in_amount: params.amount,
+            out_amount,
+            fee_amount: fee_free_amount.saturating_sub(out_amount),
+            fee_mint: params.output_mint,
+        })
+    }
+
+    fn build_swap_ix(&self, params: &AmmSwapParams) -> ClientResult<Instruction> {
+        let (input_index, output_index) = self.token_indices(&params.input_mint, &params.output_mint)?;
+        if params.swap_mode != SwapMode::ExactIn {
+            return Err(ProgramError::InvalidArgument.into());
+        }
+        let mut data = vec![SWAP_INSTRUCTION_TAG];
+        data.extend_from_slice(&params.in_amount.to_le_bytes());
+        data.extend_from_slice(&params.out_amount.to_le_bytes());
+        let accounts = vec![
+            AccountMeta::new_readonly(self.pool, false),
+            AccountMeta::new_readonly(self.authority, false),
+            AccountMeta::new_readonly(params.token_transfer_authority, true),
+            AccountMeta::new(params.source_token_account, false),
+            AccountMeta::new(self.token_accounts[input_index], false),
+            AccountMeta::new(self.token_accounts[output_index], false),
+            AccountMeta::new(params.destination_token_account, false),
+            AccountMeta::new(self.pool_mint, false),
+            AccountMeta::new(self.pool_fee_account, false),
+            AccountMeta::new_readonly(self.token_program_id, false),
+        ];
+        Ok(Instruction {
+            program_id: self.program_id,
+            accounts,
+            data,
+        })
+    }
+}
+
+#[cfg(all(test, not(feature = "test-bpf")))]
+mod tests {
+    use super::*;
+
+    fn pool_account(program_id: &Pubkey, bump: u8, keys: &[Pubkey; 7]) -> Account {
+        let mut data = vec![1, 1, bump];
+        for key in keys.iter() {
+            data.extend_from_slice(&key.to_bytes());
+        }
+        for number in [25u64, 10000, 5, 10000, 0, 0, 0, 0].iter() {
+            data.extend_from_slice(&number.to_le_bytes());
+        }
+        data.push(CONSTANT_PRODUCT_CURVE);
+        data.extend_from_slice(&[0; 32]);
+        Account {
+            lamports: 1,
+            data,
+            owner: *program_id,
+            executable: false,
+            rent_epoch: 0,
+        }
+    }
+
+    fn token_account(mint: &Pubkey, amount: u64) -> Account {
+        let mut data = vec![0; TokenState::LEN];
+        TokenState::pack(
+            TokenState {
+                mint: *mint,
+                owner: Pubkey::new_unique(),
+                amount,
+                state: spl_token::state::AccountState::Initialized,
+                ..TokenState::default()
+            },
+            &mut data,
+        )
+        .unwrap();
+        Account {
+            lamports: 1,
+            data,
+            owner: spl_token::id(),
+            executable: false,
+            rent_epoch: 0,
+        }
+    }
+
+    #[test]
+    fn quote_and_swap() {
+        let program_id = Pubkey::new_unique();
+        let (pool, bump) = std::iter::repeat_with(Pubkey::new_unique)
+            .find_map(|pool| {
+                (0..=u8::MAX)
+                    .rev()
+                    .find(|bump| Pubkey::create_program_address(&[&pool.to_bytes(), &[*bump]], &program_id).is_ok())
+                    .map(|bump| (pool, bump))
+            })
+            .unwrap();
+        let keys = [(); 7].map(|_| Pubkey::new_unique());
+        let (token_a, token_b, mint_a, mint_b) = (keys[1], keys[2], keys[4], keys[5]);
+        let mut venue = TokenSwapVenue::from_keyed_account(&pool, &pool_account(&program_id, bump, &keys)).unwrap();
+        assert_eq!(venue.get_reserve_mints(), vec![mint_a, mint_b]);
+
+        let params = AmmQuoteParams {
+            input_mint: mint_a,
+            output_mint: mint_b,
+            amount: 1_000_000,
+            swap_mode: SwapMode::ExactIn,
+        };
+        assert!(venue.quote(&params).is_err());
+        let mut accounts = HashMap::new();
+        accounts.insert(pool, pool_account(&program_id, bump, &keys));
+        accounts.insert(token_a, token_account(&mint_a, 1_000_000_000));
+        accounts.insert(token_b, token_account(&mint_b, 2_000_000_000));
+        venue.update(&accounts).unwrap();
+
+        //0.3% of fees, then 2 B per A with a little price impact
+        let quote = venue.quote(&params).unwrap();
+        let after_fees = 1_000_000 - 2_500 - 500;
+        let remaining =
+            (1_000_000_000u128 * 2_000_000_000 + 1_000_000_000 + after_fees - 1) / (1_000_000_000 + after_fees);
+        assert_eq!(quote.out_amount, (2_000_000_000 - remaining) as u64);
+        assert!(quote.out_amount < 2 * after_fees as u64 && quote.out_amount > 2 * after_fees as u64 * 999 / 1000);
+        assert!(quote.fee_amount > 0);
+        assert!(venue
+            .quote(&AmmQuoteParams {
+                swap_mode: SwapMode::ExactOut,
+                ..params
+            })
+            .is_err());
+
+        let user = Pubkey::new_unique();
+        let (source, destination) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let ix = venue
+            .build_swap_ix(&AmmSwapParams {
+                input_mint: mint_b,
+                output_mint: mint_a,
+                in_amount: 7,
+                out_amount: 3,
+                swap_mode: SwapMode::ExactIn,
+                source_token_account: source,
+                destination_token_account: destination,
+                token_transfer_authority: user,
+            })
+            .unwrap();
+        assert_eq!(ix.program_id, program_id);
+        assert_eq!(ix.data, [&[1u8][..], &7u64.to_le_bytes(), &3u64.to_le_bytes()].concat());
+        let ix_keys: Vec<Pubkey> = ix.accounts.iter().map(|meta| meta.pubkey).collect();
+        assert_eq!(ix_keys[2..7], [user, source, token_b, token_a, destination]);
+        assert!(ix.accounts[2].is_signer);
+    }
+}
@@ -0,0 +1,235 @@
+//routes across other programs' pools (venues) next to our own
+//
+//a venue quotes and builds the swap instruction of a single pool, like `PoolAmm` does for ours, which is the first
+// implementation and keeps the program's exact math for our legs. adapters for other programs implement `Venue`
+// outside of this crate or come with optional features (`token-swap-venue`)
+//
+//a route through two venues executes as two swap instructions within one transaction, the second one swaps exactly
+// the first one's quoted output, which is why that's also the first one's minimum output. routes within our own pools
+// are better served by `Router::find_best_route`, whose two hop routes execute as a single `RoutedSwap`
+
+use super::{
+    amm::{AmmQuote, AmmQuoteParams, AmmSwapParams, PoolAmm, SwapMode},
+    router::Router,
+    ClientError, ClientResult,
+};
+use solana_program::{instruction::Instruction, program_error::ProgramError, pubkey::Pubkey};
+use solana_sdk::account::Account;
+use std::collections::HashMap;
+
+/// A pool that the router can quote and swap in, with the methods of `PoolAmm`
+pub trait Venue {
+    fn key(&self) -> Pubkey;
+    fn label(&self) -> String;
+    fn get_reserve_mints(&self) -> Vec<Pubkey>;
+    fn get_accounts_to_update(&self) -> Vec<Pubkey>;
+    fn update(&mut self, accounts: &HashMap<Pubkey, Account>) -> ClientResult<()>;
+    fn quote(&self, params: &AmmQuoteParams) -> ClientResult<AmmQuote>;
+    fn build_swap_ix(&self, params: &AmmSwapParams) -> ClientResult<Instruction>;
+}
+
+impl Venue for PoolAmm {
+    fn key(&self) -> Pubkey {
+        PoolAmm::key(self)
+    }
+
+    fn label(&self) -> String {
+        PoolAmm::label(self)
+    }
+
+    fn get_reserve_mints(&self) -> Vec<Pubkey> {
+        PoolAmm::get_reserve_mints(self)
+    }
+
+    fn get_accounts_to_update(&self) -> Vec<Pubkey> {
+        PoolAmm::get_accounts_to_update(self)
+    }
+
+    fn update(&mut self, accounts: &HashMap<Pubkey, Account>) -> ClientResult<()> {
+        PoolAmm::update(self, accounts)
+    }
+
+    fn quote(&self, params: &AmmQuoteParams) -> ClientResult<AmmQuote> {
+        PoolAmm::quote(self, params)
+    }
+
+    fn build_swap_ix(&self, params: &AmmSwapParams) -> ClientResult<Instruction> {
+        self.get_swap_ix(params)
+    }
+}
+
+/// A swap in a single venue along a `VenueRoute`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VenueHop {
+    pub venue: Pubkey,
+    pub label: String,
+    pub input_mint: Pubkey,
+    pub output_mint: Pubkey,
+    pub in_amount: u64,
+    pub out_amount: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VenueRoute {
+    /// One hop, or two whose first one's output is the second one's input
+    pub hops: Vec<VenueHop>,
+}
+
+impl VenueRoute {
+    pub fn in_amount(&self) -> u64 {
+        self.hops[0].in_amount
+    }
+
+    pub fn out_amount(&self) -> u64 {
+        self.hops[self.hops.len() - 1].out_amount
+    }
+}
+
+impl Router {
+    /// Adds another program's pool to route through with `find_best_venue_route`, updated already
+    pub fn add_venue(&mut self, venue: Box<dyn Venue>) {
+        self.venues.push(venue);
+    }
+
+    pub fn venues(&self) -> &[Box<dyn Venue>] {
+        &self.venues
+    }
+
+    //our pools first, so that they win ties
+    fn all_venues(&self) -> impl Iterator<Item = &dyn Venue> {
+        self.amms
+            .iter()
+            .map(|amm| amm as &dyn Venue)
+            .chain(self.venues.iter().map(|venue| venue.as_ref()))
+    }
+
+    /// The route that swaps `amount` of `mint_in` for the most `mint_out` out of all direct swaps and two hop routes
+    /// in our pools and the added venues. Venues that can't quote the swap are skipped
+    pub fn find_best_venue_route(&self, mint_in: &Pubkey, mint_out: &Pubkey, amount: u64) -> ClientResult<VenueRoute> {
+        if amount == 0 || mint_in == mint_out {
+            return Err(ProgramError::InvalidArgument.into());
+        }
+        let mut best: Option<VenueRoute> = None;
+        let mut consider = |hops: Vec<VenueHop>| {
+            let route = VenueRoute { hops };
+            //more output is better, fewer hops break ties
+            let is_better = match &best {
+                Some(best) => (route.out_amount(), best.hops.len()) > (best.out_amount(), route.hops.len()),
+                None => true,
+            };
+            if is_better {
+                best = Some(route);
+            }
+        };
+        for venue in self.all_venues() {
+            let mints = venue.get_reserve_mints();
+            if !mints.contains(mint_in) {
+                continue;
+            }
+            if mints.contains(mint_out) {
+                if let Ok(hop) = quote_venue_hop(venue, mint_in, mint_out, amount) {
+                    consider(vec![hop]);
+                }
+            }
+            for intermediate_mint in mints.iter().filter(|mint| *mint != mint_in && *mint != mint_out) {
+                let first = match quote_venue_hop(venue, mint_in, intermediate_mint, amount) {
+                    Ok(first) => first,
+                    Err(_) => continue,
+                };
+                for second_venue in self
+                    .all_venues()
+                    .filter(|second_venue| second_venue.key() != venue.key())
+                {
+                    let second_mints = second_venue.get_reserve_mints();
+                    if !second_mints.contains(intermediate_mint) || !second_mints.contains(mint_out) {
+                        continue;
+                    }
+                    if let Ok(second) = quote_venue_hop(second_venue, intermediate_mint, mint_out, first.out_amount) {
+                        consider(vec![first.clone(), second]);
+                    }
+                }
+            }
+        }
+        best.ok_or(ClientError::NoRoute(*mint_in, *mint_out))
+    }
+
+    /// The instructions that execute `route`, one swap per hop. A two hop route needs the user's token account of the
+    /// intermediate token
+    pub fn venue_route_instructions(
+        &self,
+        route: &VenueRoute,
+        user_transfer_authority: &Pubkey,
+        input_account: &Pubkey,
+        intermediate_account: Option<&Pubkey>,
+        output_account: &Pubkey,
+        minimum_output_amount: u64,
+    ) -> ClientResult<Vec<Instruction>> {
+        let swap_ix = |hop: &VenueHop, in_amount, out_amount, source, destination| {
+            let venue = self
+                .all_venues()
+                .find(|venue| venue.key() == hop.venue)
+                .ok_or(ClientError::AccountNotFound(hop.venue))?;
+            venue.build_swap_ix(&AmmSwapParams {
+                input_mint: hop.input_mint,
+                output_mint: hop.output_mint,
+                in_amount,
+                out_amount,
+                swap_mode: SwapMode::ExactIn,
+                source_token_account: source,
+                destination_token_account: destination,
+                token_transfer_authority: *user_transfer_authority,
+            })
+        };
+        match route.hops.as_slice() {
+            [hop] => Ok(vec![swap_ix(
+                hop,
+                hop.in_amount,
+                minimum_output_amount,
+                *input_account,
+                *output_account,
+            )?]),
+            [first, second] => {
+                let intermediate_account = *intermediate_account.ok_or(ProgramError::InvalidArgument)?;
+                Ok(vec![
+                    swap_ix(
+                        first,
+                        first.in_amount,
+                        first.out_amount,
+                        *input_account,
+                        intermediate_account,
+                    )?,
+                    swap_ix(
+                        second,
+                        first.out_amount,
+                        minimum_output_amount,
+                        intermediate_account,
+                        *output_account,
+                    )?,
+                ])
+            }
+            _ => Err(ProgramError::InvalidArgument.into()),
+        }
+    }
+}
+
+fn quote_venue_hop(
+    venue: &dyn Venue,
+    input_mint: &Pubkey,
+    output_mint: &Pubkey,
+    amount: u64,
+) -> ClientResult<VenueHop> {
+    let quote = venue.quote(&AmmQuoteParams {
+        input_mint: *input_mint,
+        output_mint: *output_mint,
+        amount,
+        swap_mode: SwapMode::ExactIn,
+    })?;
+    Ok(VenueHop {
+        venue: venue.key(),
+        label: venue.label(),
+        input_mint: *input_mint,
+        output_mint: *output_mint,
+        in_amount: quote.in_amount,
+        out_amount: quote.out_amount,
+    })
+}
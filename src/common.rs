@@ -1,8 +1,10 @@
 use arrayvec::ArrayVec;
 use std::fmt::Debug;
 
-//final unwraps are safe because we know that there is enough capacity
+//final unwraps are safe because we know that there is enough capacity (and the arrays are full, which is what
+// into_inner checks), so they are exempt from the forbid-panics lints
 
+#[cfg_attr(feature = "forbid-panics", allow(clippy::unwrap_used))]
 pub fn create_array<T: Debug, const SIZE: usize>(closure: impl FnMut(usize) -> T) -> [T; SIZE] {
     (0..SIZE)
         .into_iter()
@@ -12,6 +14,7 @@ pub fn create_array<T: Debug, const SIZE: usize>(closure: impl FnMut(usize) -> T
         .unwrap()
 }
 
+#[cfg_attr(feature = "forbid-panics", allow(clippy::unwrap_used))]
 pub fn create_result_array<T: Debug, E: Debug, const SIZE: usize>(
     closure: impl FnMut(usize) -> Result<T, E>,
 ) -> Result<[T; SIZE], E> {
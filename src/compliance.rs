@@ -0,0 +1,45 @@
+//a reference implementation of a compliance program (see `GovernanceInstruction::SetComplianceProgram`) that allows
+// every instruction. like the vault it's meant to be read (and copied) rather than deployed as is: a program that
+// wants to use it calls `process_instruction` from its own entrypoint, depending on this crate with the `compliance`
+// and `no-entrypoint` features
+//
+//the pool calls the program with `ComplianceCheckData` and the accounts of the pool, the user transfer authority and
+// the user's compliance account, the program address of the compliance program that `find_compliance_address` gives.
+// a real compliance program would keep its attestation of the user there (e.g. the jurisdictions it verified) and fail
+// with an error of its own for users and instructions it doesn't allow, which fails the whole transaction
+
+use crate::instruction::{find_compliance_address, ComplianceCheckData};
+use borsh::BorshDeserialize;
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    entrypoint::ProgramResult,
+    msg,
+    program_error::ProgramError,
+    pubkey::Pubkey,
+};
+
+pub fn process_instruction<const TOKEN_COUNT: usize>(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    instruction_data: &[u8],
+) -> ProgramResult {
+    let check_data = ComplianceCheckData::<TOKEN_COUNT>::try_from_slice(instruction_data)
+        .map_err(|_| ProgramError::InvalidInstructionData)?;
+    let account_info_iter = &mut accounts.iter();
+    let pool_account = next_account_info(account_info_iter)?;
+    let user_authority_account = next_account_info(account_info_iter)?;
+    let compliance_account = next_account_info(account_info_iter)?;
+    //the pool already checked these, a compliance program that keeps state at the compliance account relies on them
+    if *pool_account.key != check_data.pool
+        || *user_authority_account.key != check_data.user
+        || *compliance_account.key
+            != find_compliance_address(program_id, pool_account.key, user_authority_account.key).0
+    {
+        return Err(ProgramError::InvalidArgument);
+    }
+
+    //this is where a compliance program decides, e.g. by checking the attestation in the compliance account (owned by
+    // the program and created by whoever attests users) and what the user is about to do in check_data.instruction
+    msg!("compliance check passed: {:?}", check_data.instruction);
+    Ok(())
+}
@@ -0,0 +1,290 @@
+//helpers for programs that call the pool via cross-program invocation
+//depend on this crate with the `no-entrypoint` feature so its entrypoint doesn't clash with that of the calling program
+//
+//callers can also pull payments from their users: users approve a program derived address of the caller as delegate of
+// their token accounts (spl_token::instruction::approve), the caller then passes that address as user transfer
+// authority and signs for it with `with_signer`, so the users don't have to sign the call itself
+
+use crate::{
+    common::create_array,
+    instruction::{create_defi_ix, DeFiInstruction, SimulationResult},
+};
+use borsh::BorshDeserialize;
+use solana_program::{
+    account_info::AccountInfo,
+    entrypoint::ProgramResult,
+    instruction::AccountMeta,
+    program::{get_return_data, invoke_signed},
+    program_error::ProgramError,
+    program_option::COption,
+    program_pack::Pack,
+    pubkey::Pubkey,
+};
+use spl_token::{error::TokenError, state::Account as TokenState};
+
+type AmountT = u64;
+
+/// The accounts of a DeFiInstruction, in the order the pool expects them (see `DeFiInstruction`)
+#[derive(Clone)]
+pub struct DeFiAccounts<'a, const TOKEN_COUNT: usize> {
+    pub pool: AccountInfo<'a>,
+    pub pool_authority: AccountInfo<'a>,
+    pub pool_token_accounts: [AccountInfo<'a>; TOKEN_COUNT],
+    pub lp_mint: AccountInfo<'a>,
+    pub governance_fee_account: AccountInfo<'a>,
+    /// Must be a signer of the calling instruction or a program derived address of the caller (see `with_signer`),
+    ///  and the owner or approved delegate of the user's token accounts (see `check_input_authority`)
+    pub user_transfer_authority: AccountInfo<'a>,
+    pub user_token_accounts: [AccountInfo<'a>; TOKEN_COUNT],
+    pub token_program: AccountInfo<'a>,
+    /// Only required by instructions that mint or burn lp tokens, ignored by swaps
+    pub user_lp_account: Option<AccountInfo<'a>>,
+    /// The Token-2022 program (pools with a non-transferable lp token) and oracle price accounts (swaps of oracle
+    ///  guarded pools) followed by the rate accounts of the pool's rated tokens,
+    ///  the instructions sysvar (pools that restrict their callers), the user transfer authority's gate account
+    ///  (pools that gate their users), its depositor gate account (adds and single token removes of pools that gate
+    ///  their depositors), the pool's compliance program and the user transfer authority's compliance account (pools
+    ///  with a compliance program), its withdrawal announcement account (imbalanced removes of pools with a
+    ///  withdrawal cooldown), its launch allocation account (adds during the pool's launch window) and optionally a
+    ///  swap's hook program along with the accounts to call it with
+    pub remaining_accounts: Vec<AccountInfo<'a>>,
+}
+
+impl<'a, const TOKEN_COUNT: usize> DeFiAccounts<'a, TOKEN_COUNT> {
+    /// Takes the accounts from a slice that is in the pool's order, e.g. the remaining accounts of the calling
+    ///  instruction. Everything after the token program (resp. the user lp account) becomes a remaining account.
+    pub fn from_slice(accounts: &[AccountInfo<'a>], with_user_lp_account: bool) -> Result<Self, ProgramError> {
+        let required = 6 + 2 * TOKEN_COUNT + with_user_lp_account as usize;
+        if accounts.len() < required {
+            return Err(ProgramError::NotEnoughAccountKeys);
+        }
+        let user_tokens_start = 5 + TOKEN_COUNT;
+        Ok(Self {
+            pool: accounts[0].clone(),
+            pool_authority: accounts[1].clone(),
+            pool_token_accounts: create_array(|i| accounts[2 + i].clone()),
+            lp_mint: accounts[2 + TOKEN_COUNT].clone(),
+            governance_fee_account: accounts[3 + TOKEN_COUNT].clone(),
+            user_transfer_authority: accounts[4 + TOKEN_COUNT].clone(),
+            user_token_accounts: create_array(|i| accounts[user_tokens_start + i].clone()),
+            token_program: accounts[user_tokens_start + TOKEN_COUNT].clone(),
+            user_lp_account: if with_user_lp_account {
+                Some(accounts[required - 1].clone())
+            } else {
+                None
+            },
+            remaining_accounts: accounts[required..].to_vec(),
+        })
+    }
+
+    /// The user's lp token account, which all but swaps take
+    pub fn user_lp_account(&self) -> Result<&AccountInfo<'a>, ProgramError> {
+        self.user_lp_account.as_ref().ok_or(ProgramError::NotEnoughAccountKeys)
+    }
+
+    /// Checks that the user transfer authority may move `input_amounts` out of the user token accounts, so callers
+    ///  can fail before invoking the pool rather than in the middle of its transfers
+    pub fn check_input_authority(&self, input_amounts: &[AmountT; TOKEN_COUNT]) -> ProgramResult {
+        for (user_token_account, input_amount) in self.user_token_accounts.iter().zip(input_amounts.iter()) {
+            if *input_amount > 0 {
+                check_authority(user_token_account, self.user_transfer_authority.key, *input_amount)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Checks that `authority` owns `token_account` or is its delegate for at least `amount`, failing with the error that
+///  the token program would fail the transfer with
+pub fn check_authority(token_account: &AccountInfo, authority: &Pubkey, amount: AmountT) -> ProgramResult {
+    let token_state = TokenState::unpack(&token_account.data.borrow())?;
+    if token_state.owner == *authority {
+        return Ok(());
+    }
+    match token_state.delegate {
+        COption::Some(delegate) if delegate == *authority => {
+            if token_state.delegated_amount < amount {
+                return Err(TokenError::InsufficientFunds.into());
+            }
+            Ok(())
+        }
+        _ => Err(TokenError::OwnerMismatch.into()),
+    }
+}
+
+/// The pool program, the accounts and the seeds to sign with of a cross-program invocation of the pool
+pub struct CpiContext<'a, 'b, const TOKEN_COUNT: usize> {
+    pub program: AccountInfo<'a>,
+    pub accounts: DeFiAccounts<'a, TOKEN_COUNT>,
+    pub signer_seeds: &'b [&'b [&'b [u8]]],
+}
+
+impl<'a, 'b, const TOKEN_COUNT: usize> CpiContext<'a, 'b, TOKEN_COUNT> {
+    pub fn new(program: AccountInfo<'a>, accounts: DeFiAccounts<'a, TOKEN_COUNT>) -> Self {
+        Self {
+            program,
+            accounts,
+            signer_seeds: &[],
+        }
+    }
+
+    /// Signs for program derived addresses of the caller, typically the user transfer authority
+    pub fn with_signer(mut self, signer_seeds: &'b [&'b [&'b [u8]]]) -> Self {
+        self.signer_seeds = signer_seeds;
+        self
+    }
+
+    fn account_infos(&self) -> Vec<AccountInfo<'a>> {
+        let accounts = &self.accounts;
+        let mut account_infos = vec![accounts.pool.clone(), accounts.pool_authority.clone()];
+        account_infos.extend_from_slice(&accounts.pool_token_accounts);
+        account_infos.push(accounts.lp_mint.clone());
+        account_infos.push(accounts.governance_fee_account.clone());
+        account_infos.push(accounts.user_transfer_authority.clone());
+        account_infos.extend_from_slice(&accounts.user_token_accounts);
+        account_infos.push(accounts.token_program.clone());
+        account_infos.extend(accounts.user_lp_account.clone());
+        account_infos.extend_from_slice(&accounts.remaining_accounts);
+        account_infos.push(self.program.clone());
+        account_infos
+    }
+}
+
+pub fn add_cpi<const TOKEN_COUNT: usize>(
+    ctx: &CpiContext<TOKEN_COUNT>,
+    input_amounts: [AmountT; TOKEN_COUNT],
+    minimum_mint_amount: AmountT,
+) -> ProgramResult {
+    invoke_defi(
+        ctx,
+        DeFiInstruction::Add {
+            input_amounts,
+            minimum_mint_amount,
+        },
+    )
+}
+
+pub fn swap_exact_input_cpi<const TOKEN_COUNT: usize>(
+    ctx: &CpiContext<TOKEN_COUNT>,
+    exact_input_amounts: [AmountT; TOKEN_COUNT],
+    output_token_index: u8,
+    minimum_output_amount: AmountT,
+) -> ProgramResult {
+    invoke_defi(
+        ctx,
+        DeFiInstruction::SwapExactInput {
+            exact_input_amounts,
+            output_token_index,
+            minimum_output_amount,
+        },
+    )
+}
+
+pub fn swap_exact_output_cpi<const TOKEN_COUNT: usize>(
+    ctx: &CpiContext<TOKEN_COUNT>,
+    maximum_input_amount: AmountT,
+    input_token_index: u8,
+    exact_output_amounts: [AmountT; TOKEN_COUNT],
+) -> ProgramResult {
+    invoke_defi(
+        ctx,
+        DeFiInstruction::SwapExactOutput {
+            maximum_input_amount,
+            input_token_index,
+            exact_output_amounts,
+        },
+    )
+}
+
+pub fn remove_uniform_cpi<const TOKEN_COUNT: usize>(
+    ctx: &CpiContext<TOKEN_COUNT>,
+    exact_burn_amount: AmountT,
+    minimum_output_amounts: [AmountT; TOKEN_COUNT],
+) -> ProgramResult {
+    invoke_defi(
+        ctx,
+        DeFiInstruction::RemoveUniform {
+            exact_burn_amount,
+            minimum_output_amounts,
+        },
+    )
+}
+
+pub fn remove_exact_burn_cpi<const TOKEN_COUNT: usize>(
+    ctx: &CpiContext<TOKEN_COUNT>,
+    exact_burn_amount: AmountT,
+    output_token_index: u8,
+    minimum_output_amount: AmountT,
+) -> ProgramResult {
+    invoke_defi(
+        ctx,
+        DeFiInstruction::RemoveExactBurn {
+            exact_burn_amount,
+            output_token_index,
+            minimum_output_amount,
+        },
+    )
+}
+
+pub fn remove_exact_output_cpi<const TOKEN_COUNT: usize>(
+    ctx: &CpiContext<TOKEN_COUNT>,
+    maximum_burn_amount: AmountT,
+    exact_output_amounts: [AmountT; TOKEN_COUNT],
+) -> ProgramResult {
+    invoke_defi(
+        ctx,
+        DeFiInstruction::RemoveExactOutput {
+            maximum_burn_amount,
+            exact_output_amounts,
+        },
+    )
+}
+
+/// Runs `defi_instruction` as `Simulate` and returns what it would do
+pub fn simulate_cpi<const TOKEN_COUNT: usize>(
+    ctx: &CpiContext<TOKEN_COUNT>,
+    defi_instruction: DeFiInstruction<TOKEN_COUNT>,
+) -> Result<SimulationResult<TOKEN_COUNT>, ProgramError> {
+    invoke_defi(ctx, DeFiInstruction::Simulate(Box::new(defi_instruction)))?;
+    match get_return_data() {
+        Some((program_id, data)) if program_id == *ctx.program.key => Ok(SimulationResult::try_from_slice(&data)?),
+        _ => Err(ProgramError::InvalidAccountData),
+    }
+}
+
+fn invoke_defi<const TOKEN_COUNT: usize>(
+    ctx: &CpiContext<TOKEN_COUNT>,
+    defi_instruction: DeFiInstruction<TOKEN_COUNT>,
+) -> ProgramResult {
+    let accounts = &ctx.accounts;
+    let is_swap = defi_instruction.is_swap();
+    //create_defi_ix panics on a missing lp account, which we rather report as an error
+    let user_lp_key = match (is_swap, &accounts.user_lp_account) {
+        (true, _) => None,
+        (false, Some(user_lp_account)) => Some(user_lp_account.key),
+        (false, None) => return Err(ProgramError::NotEnoughAccountKeys),
+    };
+    let mut ix = create_defi_ix(
+        defi_instruction,
+        ctx.program.key,
+        accounts.pool.key,
+        accounts.pool_authority.key,
+        &create_array(|i| *accounts.pool_token_accounts[i].key),
+        accounts.lp_mint.key,
+        accounts.governance_fee_account.key,
+        accounts.user_transfer_authority.key,
+        &create_array(|i| *accounts.user_token_accounts[i].key),
+        accounts.token_program.key,
+        user_lp_key,
+    )?;
+    ix.accounts.extend(accounts.remaining_accounts.iter().map(|account| {
+        //an add's launch allocation account and a remove's withdrawal announcement account are written to
+        if account.is_writable {
+            AccountMeta::new(*account.key, false)
+        } else {
+            AccountMeta::new_readonly(*account.key, false)
+        }
+    }));
+
+    invoke_signed(&ix, &ctx.account_infos(), ctx.signer_seeds)
+}
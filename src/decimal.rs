@@ -9,6 +9,10 @@
 // All math in this module is implemented in such a way that all operations that *aren't*
 // don't explicitly use checked_* (i.e. all the inline ops like +,-,*,/,%, etc.) should never
 // be able to fail and could hence be replaced by unsafe_* calls to reduce strain on compute budget.
+//
+// The operators panic where the checked_* methods return None, like those of the primitive types do with overflow
+// checks, so they are exempt from the forbid-panics lints.
+#![cfg_attr(feature = "forbid-panics", allow(clippy::panic))]
 
 use borsh::{BorshDeserialize, BorshSchema, BorshSerialize};
 use std::{
@@ -765,12 +769,11 @@ macro_rules! impl_interop {
     $larger_type:ty $(,)?
 ) => {
         impl $name {
-            pub fn upcast_mul(self, other: Self) -> $larger_name {
+            pub fn upcast_mul(self, other: Self) -> Result<$larger_name, DecimalError> {
                 $larger_name::new(
                     $upcast!(self.value, $larger_type) * $upcast!(other.value, $larger_type),
                     self.decimals + other.decimals,
                 )
-                .unwrap()
             }
         }
 
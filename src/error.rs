@@ -1,7 +1,9 @@
 use num_derive::FromPrimitive;
-use num_traits::FromPrimitive;
-use solana_program::program_error::ProgramError;
+//re-exported so tools can decode codes with `PoolError::from_u32` without depending on num-traits themselves
+pub use num_traits::FromPrimitive;
+use solana_program::{msg, program_error::ProgramError};
 use spl_token::error::TokenError;
+use std::{convert::TryFrom, fmt};
 use thiserror::Error;
 
 //OFFSET is used to deal with technical debt imposed on us by SPL::ProgramError
@@ -9,60 +11,220 @@ use thiserror::Error;
 // so to distinguish TokenErrors from PoolErrors, we're offsetting PoolErrors by 100 while TokenErrors start at 0
 const OFFSET: isize = 100;
 
-#[derive(Error, Debug, FromPrimitive)]
+//every variant spells out its code: clients match on InstructionError::Custom(code), so codes are never reused or
+// renumbered and new variants only ever get the next free one
+#[derive(Error, Debug, Clone, Copy, PartialEq, Eq, FromPrimitive)]
 pub enum PoolError {
     #[error("Specified amp factor is out of bounds")]
-    InvalidAmpFactorValue = OFFSET,
+    InvalidAmpFactorValue = 100,
     #[error("Amp factor adjustment window is too short")]
-    InvalidAmpFactorTimestamp,
+    InvalidAmpFactorTimestamp = 101,
     #[error("Given fee is invalid")]
-    InvalidFeeInput,
+    InvalidFeeInput = 102,
     #[error("Can't pass the same account twice here")]
-    DuplicateAccount,
+    DuplicateAccount = 103,
     #[error("Lp token mint has a positive balance")]
-    MintHasBalance,
-
-    // 105
+    MintHasBalance = 104,
     #[error("Pool does not have mint authority of lp token mint")]
-    InvalidMintAuthority,
+    InvalidMintAuthority = 105,
     #[error("Lp token mint's freeze authority is set")]
-    MintHasFreezeAuthority,
+    MintHasFreezeAuthority = 106,
     #[error("Token account has a positive balance")]
-    TokenAccountHasBalance,
+    TokenAccountHasBalance = 107,
     #[error("Token account's delegate is set")]
-    TokenAccountHasDelegate,
+    TokenAccountHasDelegate = 108,
     #[error("Token account's close authority is set")]
-    TokenAccountHasCloseAuthority,
-
-    // 110
+    TokenAccountHasCloseAuthority = 109,
     #[error("Invalid governance account")]
-    InvalidGovernanceAccount,
+    InvalidGovernanceAccount = 110,
     #[error("Invalid governance fee account")]
-    InvalidGovernanceFeeAccount,
+    InvalidGovernanceFeeAccount = 111,
     #[error("Invalid pool authority account")]
-    InvalidPoolAuthorityAccount,
+    InvalidPoolAuthorityAccount = 112,
     #[error("Invalid mint account")]
-    InvalidMintAccount,
+    InvalidMintAccount = 113,
     #[error("Not enough time has passed since prepare instruction")]
-    InsufficientDelay,
-
-    //115
+    InsufficientDelay = 114,
     #[error("Nothing to enact")]
-    InvalidEnact,
+    InvalidEnact = 115,
     #[error("Pool is paused")]
-    PoolIsPaused,
+    PoolIsPaused = 116,
     #[error("Expected a token account that belongs to the pool")]
-    PoolTokenAccountExpected,
+    PoolTokenAccountExpected = 117,
+    //no longer returned, the pool fails with the variant of the limit that was missed instead (MinimumMintNotMet etc.)
     #[error("The instruction could not be completed within the specified limits")]
-    OutsideSpecifiedLimits,
+    OutsideSpecifiedLimits = 118,
     #[error("Initial add to pool must include all tokens")]
-    AddRequiresAllTokens,
-
-    //120
+    AddRequiresAllTokens = 119,
     #[error("Remove can't completed due to the approximative nature of fee math implementation")]
-    ImpossibleRemove,
+    ImpossibleRemove = 120,
     #[error("The maximum difference in decimals between tokens in the pool has been exceeded")]
-    MaxDecimalDifferenceExceeded,
+    MaxDecimalDifferenceExceeded = 121,
+    #[error("Invalid oracle account")]
+    InvalidOracleAccount = 122,
+    #[error("Oracle price is currently unavailable")]
+    OraclePriceUnavailable = 123,
+    #[error("Execution price deviates too far from the oracle price")]
+    OracleDeviationExceeded = 124,
+    #[error("Swaps of the token are paused because its oracle price is off its peg")]
+    TokenDepegged = 125,
+    #[error("Invalid virtual price account")]
+    InvalidVirtualPriceAccount = 126,
+    #[error("Pool has no liquidity")]
+    PoolIsEmpty = 127,
+    #[error("Invalid exchange rate account")]
+    InvalidRateAccount = 128,
+    #[error("Exchange rate is currently unavailable")]
+    RateUnavailable = 129,
+    #[error("Swap hook program isn't allowed by the pool")]
+    HookProgramNotAllowed = 130,
+    #[error("Invalid route authority or intermediate token account")]
+    InvalidRouteAccount = 131,
+    #[error("Calling program isn't allowed by the pool")]
+    CallerNotAllowed = 132,
+    #[error("Token mints aren't in ascending order")]
+    UnsortedTokenMints = 133,
+    #[error("Account isn't at its canonical program address")]
+    NonCanonicalAddress = 134,
+    #[error("Source pool withdrawal doesn't pay out to the user's accounts of the add")]
+    InvalidMigrationAccount = 135,
+    #[error("Pool is being wound down")]
+    PoolIsSunset = 136,
+    #[error("Pool has to be paused")]
+    PoolNotPaused = 137,
+    #[error("State export doesn't match the pool it's imported into")]
+    InvalidStateExport = 138,
+    #[error("Pool's governance was renounced")]
+    GovernanceRenounced = 139,
+    #[error("LP tokens are still locked")]
+    LpStillLocked = 140,
+    #[error("Lock duration is out of bounds")]
+    InvalidLockDuration = 141,
+    #[error("The pool's lp token is non-transferable")]
+    LpNonTransferable = 142,
+    #[error("The lp position would be smaller than the pool's minimum")]
+    DustPosition = 143,
+    #[error("The add would mint more lp tokens to the wallet than the pool's launch cap")]
+    LaunchCapExceeded = 144,
+    #[error("The user is not allowed to use the pool")]
+    UserNotAllowed = 145,
+    #[error("The pool doesn't take this token at the moment")]
+    TokenInputDisabled = 146,
+    #[error("The pool doesn't give out this token at the moment")]
+    TokenOutputDisabled = 147,
+    #[error("The user is not allowed to add to or remove from the pool")]
+    DepositorNotAllowed = 148,
+    #[error("The session has expired")]
+    SessionExpired = 149,
+    #[error("The swap would spend more than the session's limit")]
+    SessionLimitExceeded = 150,
+    #[error("The quote isn't signed by the pool's quoter")]
+    InvalidQuoteSignature = 151,
+    #[error("The quote has expired")]
+    QuoteExpired = 152,
+    #[error("The quote or a later one has already been executed")]
+    QuoteAlreadyUsed = 153,
+    #[error("The quote's price deviates too far from the pool's price")]
+    QuoteDeviationExceeded = 154,
+    #[error("The compliance program or account isn't the pool's resp. the user's")]
+    InvalidComplianceAccount = 155,
+    #[error("The pool is outside of its trading hours")]
+    OutsideTradingHours = 156,
+    #[error("The pool is closed for a maintenance window")]
+    PoolInMaintenance = 157,
+    #[error("Invalid trading hours or maintenance window")]
+    InvalidTradingSchedule = 158,
+    #[error("Removes this large have to be announced ahead with AnnounceWithdrawal")]
+    WithdrawalNotAnnounced = 159,
+    #[error("The withdrawal's cooldown hasn't passed yet")]
+    WithdrawalCooldownActive = 160,
+    #[error("The signer isn't one of the pool's emergency responders")]
+    NotEmergencyResponder = 161,
+    #[error("The lp amount minted is below the specified minimum")]
+    MinimumMintNotMet = 162,
+    #[error("The output amount is below the specified minimum")]
+    MinimumOutputNotMet = 163,
+    #[error("The input amount exceeds the specified maximum")]
+    MaximumInputExceeded = 164,
+    #[error("The lp amount burned exceeds the specified maximum")]
+    MaximumBurnExceeded = 165,
+    #[error("The instruction or account is sized for a pool with a different number of tokens")]
+    TokenCountMismatch = 166,
+    #[error("The pool math overflowed")]
+    InvariantOverflow = 167,
+    #[error("The pool math underflowed")]
+    InvariantUnderflow = 168,
+    #[error("The pool math failed to converge")]
+    InvariantConvergenceFailure = 169,
+    #[error("The user's token account holds less than the amount")]
+    InsufficientUserBalance = 170,
+    #[error("The user's token account is not a token account of the expected mint")]
+    InvalidUserTokenAccount = 171,
+    #[error("The user's token account is neither owned by nor delegated enough to the signing authority")]
+    InvalidUserAuthority = 172,
+    #[error("The user's token account is frozen")]
+    UserTokenAccountFrozen = 173,
+    #[error("Token weights must each be at least the minimum weight and add up to WEIGHT_ONE")]
+    InvalidTokenWeights = 174,
+    #[error("The price band's minimum must be below its maximum and the pool must have two tokens")]
+    InvalidPriceBand = 175,
+    #[error("The trade would move the price outside the pool's price band")]
+    PriceOutOfBand = 176,
+    #[error("The token is being onboarded, only adds can take it")]
+    TokenDepositOnly = 177,
+    #[error("The token is being offboarded, nothing can take it in")]
+    TokenWithdrawOnly = 178,
+    #[error("Peg keepers require a stable swap pool and keep their token until the pool owes them nothing")]
+    InvalidPegKeeper = 179,
+    #[error("The peg keeper's credit doesn't cover the amount")]
+    PegCreditExceeded = 180,
+    #[error("The peg keeper's credit may only move the pool's balance of its token towards the peg")]
+    PegCreditPastPeg = 181,
+    #[error("The yield adapter is not the token's, didn't move the amount or didn't report the pool's position")]
+    InvalidYieldAdapter = 182,
+    #[error("The pool's token account doesn't hold the output, the deployed tokens have to be recalled first")]
+    YieldBufferShort = 183,
+    #[error("The pool isn't a hybrid pool or the volatile token, its price or the repeg parameters are invalid")]
+    InvalidPriceScale = 184,
+    #[error("The price scale is within the threshold of the oracle's rate or was repegged too recently")]
+    RepegNotDue = 185,
+    #[error("The gauge doesn't exist, emits another reward token or the emission period is invalid")]
+    InvalidGauge = 186,
+    #[error("The stake holds less than the amount")]
+    InsufficientStake = 187,
+    #[error("The rebate campaign or rebate is not the pool's, the window is invalid or the budget was reclaimed")]
+    InvalidRebateCampaign = 188,
+    #[error("The rebate campaign's window (resp. the claim period after it) hasn't ended yet")]
+    RebateCampaignNotEnded = 189,
+    #[error("The ve program or ve account isn't the gauge's resp. the owner's, or the program returned no boost")]
+    InvalidVeProgram = 190,
+    #[error("Oracle limits need a max age and a confidence bound of at most 10000 basis points")]
+    InvalidOracleLimits = 191,
+    #[error("The pool's state account has the first layout, which can't hold the change")]
+    PoolLayoutOutdated = 192,
+    #[error("The pool's state account doesn't have any of the program's layouts")]
+    InvalidPoolLayout = 193,
+}
+
+impl PoolError {
+    /// The code that the error surfaces with as `ProgramError::Custom`
+    pub fn code(self) -> u32 {
+        self as u32
+    }
+
+    /// Logs the error along with `context`, e.g. the index of the token that failed a check, which its code alone
+    /// doesn't tell, and converts it into the `ProgramError` to return
+    pub fn with_context(self, context: impl fmt::Display) -> ProgramError {
+        msg!("{} (code {}): {}", self, self.code(), context);
+        self.into()
+    }
+
+    /// Iterates all variants along with their codes, in ascending order
+    ///
+    /// Codes are part of the program's public interface (clients match on them), so they must never be renumbered.
+    pub fn codes() -> impl Iterator<Item = (u32, Self)> {
+        (OFFSET as u32..).map_while(|code| Self::from_u32(code).map(|error| (code, error)))
+    }
 }
 
 impl From<PoolError> for ProgramError {
@@ -71,10 +233,47 @@ impl From<PoolError> for ProgramError {
     }
 }
 
+impl TryFrom<u32> for PoolError {
+    /// The code, which isn't one of the pool's
+    type Error = u32;
+
+    fn try_from(code: u32) -> Result<Self, u32> {
+        Self::from_u32(code).ok_or(code)
+    }
+}
+
+/// A `ProgramError::Custom` code as the error of the pool or of spl token (whose errors the pool passes through) that
+///  it stands for
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CustomError {
+    Pool(PoolError),
+    Token(TokenError),
+    /// A code that is neither the pool's nor spl token's, e.g. of another program that failed within the transaction
+    Unknown(u32),
+}
+
+impl fmt::Display for CustomError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CustomError::Pool(error) => write!(f, "{}", error),
+            CustomError::Token(error) => write!(f, "{}", error),
+            CustomError::Unknown(code) => write!(f, "Unknown error code {}", code),
+        }
+    }
+}
+
+/// Maps a custom error code that an instruction of the pool failed with back to its error, codes below the pool's
+///  are taken as spl token's
+pub fn decode_custom_error(code: u32) -> CustomError {
+    if code >= OFFSET as u32 {
+        return PoolError::try_from(code).map_or(CustomError::Unknown(code), CustomError::Pool);
+    }
+    TokenError::from_u32(code).map_or(CustomError::Unknown(code), CustomError::Token)
+}
+
 pub fn to_error_msg(error: &ProgramError) -> String {
     match error {
-        ProgramError::Custom(ec) if *ec < OFFSET as u32 => TokenError::from_u32(*ec).unwrap().to_string(),
-        ProgramError::Custom(ec) => PoolError::from_u32(*ec).unwrap().to_string(),
+        ProgramError::Custom(code) => decode_custom_error(*code).to_string(),
         e => e.to_string(),
     }
 }
@@ -0,0 +1,406 @@
+//the fixed rate invariant of pools initialized with InitFixedRate (see state::InvariantKind): the pool trades its
+// tokens one for one in equalized and rated units, i.e. at the exchange rates of their rate sources (1:1 for tokens
+// without one, or a rate that governance sets with RateSource::Fixed) whatever its balances are. without any curvature
+// the price never slips, which suits pools that migrate holders of one mint to another or wrap one token into another.
+// its depth is the sum of the balances, which is what the other invariants' depths are at balanced pools
+//
+//fees work like the other invariants': swaps charge them on the input, adds and removes on the part of the amounts
+// that goes beyond the pool's proportions, and the governance fee's share of the fee is minted as lp tokens. the math
+// is exact integer math, amounts the pool pays out or mints are rounded down and amounts it receives or burns up
+
+use crate::{
+    common::create_array,
+    decimal::{DecimalU64, U256},
+    invariant::{AmountT, InvariantError, InvariantStage},
+    oracle::EMA_ONE,
+};
+
+type DecT = DecimalU64;
+type InvariantResult<T> = Result<T, InvariantError>;
+
+//the resolution of the fees in the math, finer than any fee the pool charges
+const FEE_DECIMALS: u8 = 12;
+
+/// The marginal prices of the pool's tokens in units of its first token (scaled by 10^EMA_DECIMALS) at the given
+/// (equalized and rated) balances, like `oracle::marginal_prices` for the stable swap invariant: one at any balances,
+/// `None` for an empty pool
+pub fn marginal_prices<const TOKEN_COUNT: usize>(balances: &[u128; TOKEN_COUNT]) -> Option<[u64; TOKEN_COUNT]> {
+    if balances.iter().all(|balance| *balance == 0) {
+        return None;
+    }
+    Some([EMA_ONE; TOKEN_COUNT])
+}
+
+pub struct FixedRateInvariant<const TOKEN_COUNT: usize>;
+impl<const TOKEN_COUNT: usize> FixedRateInvariant<TOKEN_COUNT> {
+    pub fn add(
+        input_amounts: &[AmountT; TOKEN_COUNT],
+        pool_balances: &[AmountT; TOKEN_COUNT],
+        lp_fee: DecT,
+        governance_fee: DecT,
+        lp_total_supply: AmountT,
+    ) -> InvariantResult<(AmountT, AmountT, AmountT)> {
+        let inputs = to_wide(input_amounts);
+        let balances = to_wide(pool_balances);
+        let input = sum(&inputs);
+        let initial_depth = sum(&balances);
+        if lp_total_supply.is_zero() {
+            let depth = to_amount(initial_depth + input, InvariantStage::Depth)?;
+            return Ok((depth, AmountT::zero(), depth));
+        }
+        nonzero(initial_depth)?;
+        let fees = Fees::new(lp_fee, governance_fee);
+        //the inputs beyond the pool's balances grown in proportion to the depth pay the fee
+        let fee = fees.charge(excess(&inputs, &balances, input, initial_depth));
+
+        let lp_total_supply = U256::from(lp_total_supply.as_u128());
+        let mint_amount = lp_total_supply * (input - fee) / initial_depth;
+        let latest_depth = initial_depth + input;
+        let governance_mint_amount =
+            governance_mint(lp_total_supply + mint_amount, fees.governance_share(fee), latest_depth);
+        Ok((
+            to_amount(mint_amount, InvariantStage::LpSupply)?,
+            to_amount(governance_mint_amount, InvariantStage::Fees)?,
+            to_amount(latest_depth, InvariantStage::Depth)?,
+        ))
+    }
+
+    pub fn swap_exact_input(
+        input_amounts: &[AmountT; TOKEN_COUNT],
+        output_index: usize,
+        pool_balances: &[AmountT; TOKEN_COUNT],
+        lp_fee: DecT,
+        governance_fee: DecT,
+        lp_total_supply: AmountT,
+    ) -> InvariantResult<(AmountT, AmountT, AmountT)> {
+        let balances = to_wide(pool_balances);
+        let fees = Fees::new(lp_fee, governance_fee);
+        let input = sum(&to_wide(input_amounts));
+        let fee = fees.charge(input);
+        let output = input - fee;
+        if output >= balances[output_index] {
+            return Err(InvariantError::Underflow {
+                stage: InvariantStage::UnknownBalance,
+            });
+        }
+        let (governance_mint_amount, latest_depth) = Self::swap_fees(&balances, &fees, fee, lp_total_supply)?;
+        Ok((
+            to_amount(output, InvariantStage::UnknownBalance)?,
+            governance_mint_amount,
+            latest_depth,
+        ))
+    }
+
+    pub fn swap_exact_output(
+        input_index: usize,
+        output_amounts: &[AmountT; TOKEN_COUNT],
+        pool_balances: &[AmountT; TOKEN_COUNT],
+        lp_fee: DecT,
+        governance_fee: DecT,
+        lp_total_supply: AmountT,
+    ) -> InvariantResult<(AmountT, AmountT, AmountT)> {
+        let outputs = to_wide(output_amounts);
+        let balances = to_wide(pool_balances);
+        if (0..TOKEN_COUNT).any(|i| i != input_index && outputs[i] >= balances[i]) {
+            return Err(InvariantError::Underflow {
+                stage: InvariantStage::Balances,
+            });
+        }
+        let fees = Fees::new(lp_fee, governance_fee);
+        let output = sum(&outputs);
+        //the input after its fee pays for the output
+        let input = ceil_div(output * fees.one, fees.one - fees.total);
+        let fee = input - output;
+        let (governance_mint_amount, latest_depth) = Self::swap_fees(&balances, &fees, fee, lp_total_supply)?;
+        Ok((
+            to_amount(input, InvariantStage::UnknownBalance)?,
+            governance_mint_amount,
+            latest_depth,
+        ))
+    }
+
+    pub fn remove_exact_burn(
+        burn_amount: AmountT,
+        output_index: usize,
+        pool_balances: &[AmountT; TOKEN_COUNT],
+        lp_fee: DecT,
+        governance_fee: DecT,
+        lp_total_supply: AmountT,
+    ) -> InvariantResult<(AmountT, AmountT, AmountT)> {
+        if burn_amount >= lp_total_supply {
+            return Err(InvariantError::Underflow {
+                stage: InvariantStage::LpSupply,
+            });
+        }
+        let balances = to_wide(pool_balances);
+        let initial_depth = nonzero(sum(&balances))?;
+        let fees = Fees::new(lp_fee, governance_fee);
+        let lp_total_supply = U256::from(lp_total_supply.as_u128());
+        let burn_amount = U256::from(burn_amount.as_u128());
+        //the burnt share of the depth, of which the output token's share of a uniform remove is exempt from the fee
+        let value = initial_depth * burn_amount / lp_total_supply;
+        let uniform_output = balances[output_index] * burn_amount / lp_total_supply;
+        let fee = fees.charge(value.saturating_sub(uniform_output));
+        let output = value - fee;
+        if output >= balances[output_index] {
+            return Err(InvariantError::ImpossibleRemove);
+        }
+
+        let latest_depth = initial_depth - output;
+        let governance_mint_amount =
+            governance_mint(lp_total_supply - burn_amount, fees.governance_share(fee), latest_depth);
+        Ok((
+            to_amount(output, InvariantStage::UnknownBalance)?,
+            to_amount(governance_mint_amount, InvariantStage::Fees)?,
+            to_amount(latest_depth, InvariantStage::Depth)?,
+        ))
+    }
+
+    pub fn remove_exact_output(
+        output_amounts: &[AmountT; TOKEN_COUNT],
+        pool_balances: &[AmountT; TOKEN_COUNT],
+        lp_fee: DecT,
+        governance_fee: DecT,
+        lp_total_supply: AmountT,
+    ) -> InvariantResult<(AmountT, AmountT, AmountT)> {
+        let outputs = to_wide(output_amounts);
+        let balances = to_wide(pool_balances);
+        if (0..TOKEN_COUNT).any(|i| outputs[i] >= balances[i]) {
+            return Err(InvariantError::Underflow {
+                stage: InvariantStage::Balances,
+            });
+        }
+        let initial_depth = nonzero(sum(&balances))?;
+        let fees = Fees::new(lp_fee, governance_fee);
+        let output = sum(&outputs);
+        //the outputs beyond the pool's balances shrunk in proportion to the depth pay the fee
+        let fee = fees.charge(excess(&outputs, &balances, output, initial_depth));
+
+        let lp_total_supply = U256::from(lp_total_supply.as_u128());
+        let burn_amount = ceil_div(lp_total_supply * (output + fee), initial_depth);
+        if burn_amount >= lp_total_supply {
+            return Err(InvariantError::ImpossibleRemove);
+        }
+        let latest_depth = initial_depth - output;
+        let governance_mint_amount =
+            governance_mint(lp_total_supply - burn_amount, fees.governance_share(fee), latest_depth);
+        Ok((
+            to_amount(burn_amount, InvariantStage::LpSupply)?,
+            to_amount(governance_mint_amount, InvariantStage::Fees)?,
+            to_amount(latest_depth, InvariantStage::Depth)?,
+        ))
+    }
+
+    /// The depth (i.e. the total value in equalized units) of a pool with the given (equalized) balances
+    pub fn depth(pool_balances: &[AmountT; TOKEN_COUNT]) -> InvariantResult<AmountT> {
+        to_amount(sum(&to_wide(pool_balances)), InvariantStage::Depth)
+    }
+
+    //the fee of a swap stays in the pool and raises its depth by as much
+    fn swap_fees(
+        balances: &[U256; TOKEN_COUNT],
+        fees: &Fees,
+        fee: U256,
+        lp_total_supply: AmountT,
+    ) -> InvariantResult<(AmountT, AmountT)> {
+        let latest_depth = sum(balances) + fee;
+        let governance_mint_amount = governance_mint(
+            U256::from(lp_total_supply.as_u128()),
+            fees.governance_share(fee),
+            latest_depth,
+        );
+        Ok((
+            to_amount(governance_mint_amount, InvariantStage::Fees)?,
+            to_amount(latest_depth, InvariantStage::Depth)?,
+        ))
+    }
+}
+
+//the total fee and the governance fee in parts of one
+struct Fees {
+    total: U256,
+    governance: U256,
+    one: U256,
+}
+
+impl Fees {
+    fn new(lp_fee: DecT, governance_fee: DecT) -> Self {
+        let one = U256::exp10(FEE_DECIMALS as usize);
+        let to_parts = |fee: DecT| U256::from(fee.get_raw()) * one / U256::exp10(fee.get_decimals() as usize);
+        let governance = to_parts(governance_fee);
+        Self {
+            total: to_parts(lp_fee) + governance,
+            governance,
+            one,
+        }
+    }
+
+    //the fee on amount, rounded up
+    fn charge(&self, amount: U256) -> U256 {
+        ceil_div(amount * self.total, self.one)
+    }
+
+    fn governance_share(&self, fee: U256) -> U256 {
+        if self.total.is_zero() {
+            U256::zero()
+        } else {
+            fee * self.governance / self.total
+        }
+    }
+}
+
+fn to_wide<const TOKEN_COUNT: usize>(amounts: &[AmountT; TOKEN_COUNT]) -> [U256; TOKEN_COUNT] {
+    create_array(|i| U256::from(amounts[i].as_u128()))
+}
+
+fn sum<const TOKEN_COUNT: usize>(amounts: &[U256; TOKEN_COUNT]) -> U256 {
+    amounts.iter().fold(U256::zero(), |sum, amount| sum + *amount)
+}
+
+fn nonzero(depth: U256) -> InvariantResult<U256> {
+    if depth.is_zero() {
+        Err(InvariantError::Underflow {
+            stage: InvariantStage::Depth,
+        })
+    } else {
+        Ok(depth)
+    }
+}
+
+//the part of amounts (which add up to total) beyond the balances' shares of total, i.e. what a swap would have traded
+fn excess<const TOKEN_COUNT: usize>(
+    amounts: &[U256; TOKEN_COUNT],
+    balances: &[U256; TOKEN_COUNT],
+    total: U256,
+    depth: U256,
+) -> U256 {
+    (0..TOKEN_COUNT).fold(U256::zero(), |excess, i| {
+        excess + amounts[i].saturating_sub(balances[i] * total / depth)
+    })
+}
+
+//the lp tokens that dilute a supply of lp_total_supply by the governance's share of the depth
+fn governance_mint(lp_total_supply: U256, governance_depth: U256, latest_depth: U256) -> U256 {
+    if governance_depth.is_zero() || governance_depth >= latest_depth {
+        U256::zero()
+    } else {
+        lp_total_supply * governance_depth / (latest_depth - governance_depth)
+    }
+}
+
+fn ceil_div(numerator: U256, denominator: U256) -> U256 {
+    (numerator + denominator - 1) / denominator
+}
+
+fn to_amount(value: U256, stage: InvariantStage) -> InvariantResult<AmountT> {
+    if value > U256::from(u128::MAX) {
+        return Err(InvariantError::Overflow { stage });
+    }
+    Ok(AmountT::from(value.as_u128()))
+}
+
+#[cfg(all(test, not(feature = "test-bpf")))]
+mod tests {
+    use super::*;
+
+    fn amounts(values: [u128; 2]) -> [AmountT; 2] {
+        create_array(|i| AmountT::from(values[i]))
+    }
+
+    fn fees() -> (DecT, DecT) {
+        (DecT::new(3, 3).unwrap(), DecT::new(1, 3).unwrap())
+    }
+
+    #[test]
+    fn swaps_trade_one_for_one_after_fees() {
+        let balances = amounts([5_000_000_000, 1_000_000_000]);
+        let supply = AmountT::from(6_000_000_000u128);
+        assert_eq!(marginal_prices(&[5_000_000_000, 1_000_000_000]), Some([EMA_ONE; 2]));
+        assert_eq!(marginal_prices(&[0, 0]), None);
+
+        let zero = DecT::from(0);
+        let (output, governance_mint, latest_depth) =
+            FixedRateInvariant::<2>::swap_exact_input(&amounts([100_000_000, 0]), 1, &balances, zero, zero, supply)
+                .unwrap();
+        assert_eq!(output.as_u128(), 100_000_000);
+        assert!(governance_mint.is_zero());
+        assert_eq!(latest_depth.as_u128(), 6_000_000_000);
+
+        let (lp_fee, governance_fee) = fees();
+        let (output, governance_mint, latest_depth) = FixedRateInvariant::<2>::swap_exact_input(
+            &amounts([100_000_000, 0]),
+            1,
+            &balances,
+            lp_fee,
+            governance_fee,
+            supply,
+        )
+        .unwrap();
+        assert_eq!(output.as_u128(), 99_600_000);
+        assert!(!governance_mint.is_zero());
+        assert_eq!(latest_depth.as_u128(), 6_000_400_000);
+        let (input, _, _) = FixedRateInvariant::<2>::swap_exact_output(
+            0,
+            &amounts([0, 99_600_000]),
+            &balances,
+            lp_fee,
+            governance_fee,
+            supply,
+        )
+        .unwrap();
+        assert_eq!(input.as_u128(), 100_000_000);
+
+        //the pool can't pay out more than it holds, however little the curve would have slipped
+        assert!(FixedRateInvariant::<2>::swap_exact_input(
+            &amounts([1_000_000_000, 0]),
+            1,
+            &balances,
+            zero,
+            zero,
+            supply
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn adds_and_removes_never_profit() {
+        let balances = amounts([5_000_000_000, 1_000_000_000]);
+        let supply = AmountT::from(6_000_000_000u128);
+        let (lp_fee, governance_fee) = fees();
+        for input in vec![[1_000_000, 0], [5_000_000, 1_000_000], [1, 1]] {
+            let (mint, _, _) =
+                FixedRateInvariant::<2>::add(&amounts(input), &balances, lp_fee, governance_fee, supply).unwrap();
+            let updated_balances = create_array(|i| balances[i] + AmountT::from(input[i]));
+            let (output, _, _) = FixedRateInvariant::<2>::remove_exact_burn(
+                mint,
+                1,
+                &updated_balances,
+                lp_fee,
+                governance_fee,
+                supply + mint,
+            )
+            .unwrap();
+            assert!(output.as_u128() <= input[0] + input[1], "{:?} {}", input, output);
+        }
+
+        //a uniform remove costs no fee, anything else does
+        let (burn, _, _) = FixedRateInvariant::<2>::remove_exact_output(
+            &amounts([5_000_000, 1_000_000]),
+            &balances,
+            lp_fee,
+            governance_fee,
+            supply,
+        )
+        .unwrap();
+        assert_eq!(burn.as_u128(), 6_000_000);
+        let (burn, _, _) = FixedRateInvariant::<2>::remove_exact_output(
+            &amounts([0, 6_000_000]),
+            &balances,
+            lp_fee,
+            governance_fee,
+            supply,
+        )
+        .unwrap();
+        assert!(burn.as_u128() > 6_000_000, "{}", burn);
+    }
+}
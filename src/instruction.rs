@@ -1,11 +1,27 @@
-use crate::decimal::DecimalU64;
+use crate::{
+    decimal::DecimalU64,
+    error::PoolError,
+    migration::{MigrationSource, SourcePool},
+    oracle::{OracleFallback, OracleKind, RateSource},
+    state::{
+        CallerMode, MaintenanceWindow, PriceBand, Role, TokenFlags, TokenParticipation, TradingHours, UserGate,
+        ALLOWED_DEPOSITOR_SEED, ALLOWED_USER_SEED, COMPLIANCE_SEED, EXPORT_SEED, FEE_CHECKPOINT_SEED, GAUGE_SEED,
+        GAUGE_STAKE_SEED, GOVERNANCE_FEE_SEED, LAUNCH_ALLOCATION_SEED, LOCK_SEED, LP_MINT_SEED, LP_SUPPLY_SEED,
+        MAX_ALLOWED_CALLERS, MAX_EMERGENCY_RESPONDERS, MAX_HOOK_PROGRAMS, MAX_MAINTENANCE_WINDOWS, POOL_SEED,
+        REBATE_CAMPAIGN_SEED, REBATE_SEED, ROUTE_SEED, SESSION_SEED, VAULT_SEED, VE_BOOST_SEED, VIRTUAL_PRICE_SEED,
+        WITHDRAWAL_ANNOUNCEMENT_SEED, YIELD_DELEGATE_SEED,
+    },
+    MAX_TOKEN_COUNT,
+};
 use borsh::{BorshDeserialize, BorshSerialize};
 use solana_program::{
-    clock::UnixTimestamp,
+    clock::{Slot, UnixTimestamp},
     instruction::{AccountMeta, Instruction},
     program_error::ProgramError,
     pubkey::Pubkey,
+    system_program, sysvar,
 };
+use std::io;
 
 #[cfg(feature = "fuzz")]
 use arbitrary::Arbitrary;
@@ -17,6 +33,15 @@ type DecT = DecimalU64;
 pub enum PoolInstruction<const TOKEN_COUNT: usize> {
     /// Initializes a new pool
     ///
+    /// The tokens' decimals can be at most MAX_DECIMAL_DIFFERENCE apart, the LP Token Mint's decimals at most
+    /// MAX_DECIMAL_DIFFERENCE apart from the largest of them (in either direction)
+    ///
+    /// The LP Token Mint can also be a Token-2022 mint with the NonTransferable extension (and no other extensions
+    /// but its metadata), e.g. for pools whose shares may only be held by vetted accounts. Lp tokens can then only be
+    /// minted and burned, lp token accounts and the governance fee account are Token-2022 accounts and the pool
+    /// can't lock lp tokens, migrate its governance fee account or export its state (see
+    /// `PoolState::lp_non_transferable`)
+    ///
     /// Accounts expected by this instruction:
     ///     0. `[w]` The pool state account to initalize
     ///     1. `[]` LP Token Mint. Must be empty, owned by authority
@@ -33,189 +58,2725 @@ pub enum PoolInstruction<const TOKEN_COUNT: usize> {
     },
     DeFiInstruction(DeFiInstruction<TOKEN_COUNT>),
     GovernanceInstruction(GovernanceInstruction<TOKEN_COUNT>),
-}
-
-/// Creates an `Init` instruction
-pub fn create_init_ix<const TOKEN_COUNT: usize>(
-    program_id: &Pubkey,
-    pool: &Pubkey,
-    lp_mint: &Pubkey,
-    token_mints: &[Pubkey; TOKEN_COUNT],
-    token_accounts: &[Pubkey; TOKEN_COUNT],
-    governance_account: &Pubkey,
-    governance_fee_account: &Pubkey,
-    nonce: u8,
-    amp_factor: DecT,
-    lp_fee: DecT,
-    governance_fee: DecT,
-) -> Result<Instruction, ProgramError> {
-    let mut accounts = vec![
-        AccountMeta::new(*pool, false),
-        AccountMeta::new_readonly(*lp_mint, false),
-    ];
-    for i in 0..TOKEN_COUNT {
-        accounts.push(AccountMeta::new_readonly(token_mints[i], false));
-    }
-    for i in 0..TOKEN_COUNT {
-        accounts.push(AccountMeta::new_readonly(token_accounts[i], false));
-    }
-    accounts.push(AccountMeta::new_readonly(*governance_account, false));
-    accounts.push(AccountMeta::new_readonly(*governance_fee_account, false));
-    let data = PoolInstruction::<TOKEN_COUNT>::Init {
-        nonce,
-        amp_factor,
-        lp_fee,
-        governance_fee,
-    }
-    .try_to_vec()?;
-
-    Ok(Instruction {
-        program_id: *program_id,
-        accounts,
-        data,
-    })
-}
-
-#[cfg_attr(feature = "fuzz", derive(Arbitrary))]
-#[derive(BorshSerialize, BorshDeserialize, Debug)]
-pub enum DeFiInstruction<const TOKEN_COUNT: usize> {
-    /// Adds/Deposits the specified input_amounts and mints
-    /// at least `minimum_mint_amount` LP tokens
+    /// Pauses swaps of every token whose oracle price is outside the pool's peg band (see
+    /// `GovernanceInstruction::SetPegBand`) and advances the amp damping (see `GovernanceInstruction::SetAmpDamping`),
+    /// can be called by anyone. Does nothing while both are off and skips tokens whose oracle price is unavailable
     ///
     /// Accounts expected by this instruction:
     ///     0. `[w]` The pool state account
+    ///     1. ..1 + TOKEN_COUNT `[]` Oracle price accounts
+    CheckPeg {},
+    /// Writes the current virtual price of the lp token (see `state::VirtualPriceState`) to the pool's virtual price
+    /// account, creating the account if necessary, can be called by anyone
+    ///
+    /// Accounts expected by this instruction:
+    ///     0. `[]` The pool state account
+    ///     1. `[w]` Virtual price account (see `find_virtual_price_address`)
+    ///     2. `[]` LP Token Mint
+    ///     3. ..3 + TOKEN_COUNT `[]` Pool token accounts
+    ///     4. ..3 + TOKEN_COUNT `[ws]` Payer (only pays if the virtual price account doesn't exist yet)
+    ///     5. ..4 + TOKEN_COUNT `[]` System program
+    ///     6. ..5 + TOKEN_COUNT `[]` Rate accounts of the pool's rated tokens (in pool token order)
+    PublishVirtualPrice {},
+    /// Swaps `exact_input_amount` of the first pool's token `input_token_index` for its token
+    /// `first_output_index`, then all of that in the second pool (where it's token `second_input_index`) for its
+    /// token `output_token_index`, receiving at least `minimum_output_amount`. Both legs are swaps of this program,
+    /// the intermediate tokens are held by a token account that only exists during the instruction
+    ///
+    /// Accounts expected by this instruction:
+    ///     0. `[]` This program
+    ///     1. `[ws]` user transfer authority account, pays for the intermediate token account (and gets it back)
+    ///     2. `[w]` user input token account
+    ///     3. `[w]` user output token account
+    ///     4. `[w]` intermediate token account (see `find_intermediate_address`)
+    ///     5. `[]` route authority (see `find_route_authority_address`)
+    ///     6. `[]` intermediate token mint
+    ///     7. `[]` SPL token program account
+    ///     8. `[]` System program
+    ///     9. `[]` Rent sysvar
+    ///     10. `[w]` first pool's state account, followed by its authority, token accounts, lp mint and governance
+    ///          fee account and the oracle and rate accounts that its swaps pass (see `DeFiInstruction`)
+    ///     11. `[w]` second pool's state account, followed by the same accounts of the second pool
+    RoutedSwap {
+        exact_input_amount: AmountT,
+        input_token_index: u8,
+        first_output_index: u8,
+        second_input_index: u8,
+        output_token_index: u8,
+        minimum_output_amount: AmountT,
+    },
+    /// Creates those of the pool's token accounts and its governance fee account that don't exist (e.g. after they
+    /// were closed or the pool's keys were changed), can be called by anyone. Missing accounts have to be the
+    /// associated token accounts of the pool authority (resp. of the governance for the lp mint), existing accounts
+    /// are left alone. A non-transferable lp token's governance fee account has to exist already
+    ///
+    /// Accounts expected by this instruction:
+    ///     0. `[]` The pool state account
     ///     1. `[]` pool authority
     ///     2. ..2 + TOKEN_COUNT `[w]` pool's token accounts
-    ///     3. ..3 + TOKEN_COUNT `[w]` LP Token Mint
-    ///     4. ..4 + TOKEN_COUNT `[w]` governance_fee_account
-    ///     5. ..5 + TOKEN_COUNT `[s]` user transfer authority account
-    ///     6. ..6 + TOKEN_COUNT `[w]` user token accounts
-    ///     7. ..6 + (2 * TOKEN_COUNT) `[]` SPL token program account
-    ///     8. ..7 + (2 * TOKEN_COUNT) `[w]` user LP token account
-    Add {
-        input_amounts: [AmountT; TOKEN_COUNT],
+    ///     3. ..2 + (2 * TOKEN_COUNT) `[]` Token mints
+    ///     4. ..2 + (2 * TOKEN_COUNT) `[w]` governance_fee_account (Pubkey::default() if the pool has none)
+    ///     5. ..3 + (2 * TOKEN_COUNT) `[]` Pool Governance Account
+    ///     6. ..4 + (2 * TOKEN_COUNT) `[]` LP Token Mint
+    ///     7. ..5 + (2 * TOKEN_COUNT) `[ws]` Payer of the created accounts
+    ///     8. ..6 + (2 * TOKEN_COUNT) `[]` System program
+    ///     9. ..7 + (2 * TOKEN_COUNT) `[]` SPL token program account
+    ///     10. ..8 + (2 * TOKEN_COUNT) `[]` SPL associated token account program
+    ///     11. ..9 + (2 * TOKEN_COUNT) `[]` Rent sysvar
+    CreatePoolTokenAccounts {},
+    /// Creates and initializes the pool of the given token mints and `fee_tier` at its canonical address: the pool
+    /// state account, lp mint, token accounts and governance fee account are program addresses derived from the
+    /// mints (in ascending order) and the fee tier (see `find_pool_address`), so there's only one such pool and
+    /// anybody can find it from its mints. The lp token gets the largest number of decimals of the pool's tokens.
+    /// The fee tier only tells pools of the same tokens apart, the pool's fees can be changed by governance later
+    ///
+    /// Accounts expected by this instruction:
+    ///     0. `[w]` The pool state account (see `find_pool_address`)
+    ///     1. `[w]` LP Token Mint (see `find_lp_mint_address`)
+    ///     2. ..2 + TOKEN_COUNT `[]` Token mints, in ascending order of their keys
+    ///     3. ..2 + (2 * TOKEN_COUNT) `[w]` pool's token accounts (see `find_vault_address`)
+    ///     4. ..2 + (2 * TOKEN_COUNT) `[]` Pool Governance Account
+    ///     5. ..3 + (2 * TOKEN_COUNT) `[w]` governance_fee_account (see `find_governance_fee_address`)
+    ///     6. ..4 + (2 * TOKEN_COUNT) `[ws]` Payer of the created accounts
+    ///     7. ..5 + (2 * TOKEN_COUNT) `[]` System program
+    ///     8. ..6 + (2 * TOKEN_COUNT) `[]` SPL token program account
+    ///     9. ..7 + (2 * TOKEN_COUNT) `[]` Rent sysvar
+    CreatePool {
+        fee_tier: u16,
+        amp_factor: DecT,
+        lp_fee: DecT,
+        governance_fee: DecT,
+    },
+    /// Withdraws `source_lp_amount` lp tokens from a two token pool of spl token-swap or Saber's stable-swap (see
+    /// `migration`) to the user's token accounts of the pool tokens `token_indices` and adds everything withdrawn to
+    /// the pool, minting at least `minimum_mint_amount` lp tokens. Pools without liquidity can only be migrated to if
+    /// they have two tokens, since the first add needs all of them
+    ///
+    /// Accounts expected by this instruction:
+    ///     0. ..0 + (7 + 2 * TOKEN_COUNT) the accounts of `DeFiInstruction::Add`, followed by its rate accounts and
+    ///          instructions sysvar where the pool needs them
+    ///     1. `[]` source pool program (see `MigrationSource::program_id`)
+    ///     2. ..2 + `MigrationSource::account_count` the accounts of the source's withdraw instruction (see
+    ///          `SourcePool::withdraw_accounts`), its user transfer authority and user token accounts have to be those
+    ///          of the add
+    MigrateIn {
+        source: MigrationSource,
+        source_lp_amount: AmountT,
+        token_indices: [u8; 2],
         minimum_mint_amount: AmountT,
     },
-    /// Swaps in the exact specified amounts for
-    /// at least `minimum_out_amount` of the output_token specified
-    /// by output_token_index
+    /// Initializes a new pool like `Init` with the parameters of an existing pool: its amp factor (the current value
+    /// if it's being adjusted), fees, oracle guard deviation, oracle limits and fallback, peg band, amp damping, hook
+    /// programs, caller allowlist, user and depositor gates, compliance program, trading hours and withdrawal cooldown.
+    /// Oracle and rate accounts belong to the template's tokens and aren't copied, the oracle guard and peg band take
+    /// effect once governance sets the new pool's oracles. Neither are the template's allowed users and depositors, so
+    /// an allowlist gated pool starts out allowing nobody, nor its maintenance windows
+    ///
+    /// Accounts expected by this instruction:
+    ///     0. ..0 + (4 + 2 * TOKEN_COUNT) the accounts of `Init`
+    ///     1. ..4 + (2 * TOKEN_COUNT) `[]` The template pool's state account
+    ClonePoolConfig {
+        nonce: u8,
+    },
+    /// Burns all lp tokens of the user's lp token account for their share of the pool's remaining balances once the
+    /// deadline of `GovernanceInstruction::SetSunset` has passed. Nothing else changes the lp supply or the balances
+    /// after the deadline, so every lp token is paid the same no matter when it is redeemed (see
+    /// `create_redeem_sunset_ix`)
     ///
     /// Accounts expected by this instruction:
     ///     0. `[w]` The pool state account
     ///     1. `[]` pool authority
     ///     2. ..2 + TOKEN_COUNT `[w]` pool's token accounts
-    ///     3. ..3 + TOKEN_COUNT `[w]` LP Token Mint
-    ///     4. ..4 + TOKEN_COUNT `[w]` governance_fee_account
-    ///     5. ..5 + TOKEN_COUNT `[s]` user transfer authority account
-    ///     6. ..6 + TOKEN_COUNT `[w]` user token accounts
-    ///     7. ..6 + (2 * TOKEN_COUNT) `[]` SPL token program account
-    SwapExactInput {
-        exact_input_amounts: [AmountT; TOKEN_COUNT],
-        output_token_index: u8,
-        minimum_output_amount: AmountT,
+    ///     3. ..2 + TOKEN_COUNT `[w]` LP Token Mint
+    ///     4. ..2 + TOKEN_COUNT `[w]` User LP token account
+    ///     5. ..2 + TOKEN_COUNT `[s]` User Transfer Authority Account
+    ///     6. ..2 + TOKEN_COUNT `[w]` User token accounts
+    ///     7. ..2 + (2 * TOKEN_COUNT) `[]` SPL token program account
+    ///     8. ..2 + (2 * TOKEN_COUNT) `[]` the lp token's program (see `PoolState::lp_token_program_id`)
+    RedeemSunset {},
+    /// Moves the pool to the uninitialized pool `target_pool` of `target_program`, e.g. a new version of this
+    /// program with a different TOKEN_COUNT or state layout, can only be called by governance while the pool is
+    /// paused. Transfers all of the pool's balances to the target pool's token accounts, hands the lp mint's authority
+    /// to the target pool's authority (so lp holders keep their tokens) and writes the pool's configuration to the
+    /// export account (see `state::PoolExport`), which the target program's `ImportState` initializes the target pool
+    /// from. Both should be sent in the same transaction, the target pool can be initialized by anyone until then.
+    /// Not for pools with a non-transferable lp token
+    ///
+    /// Accounts expected by this instruction:
+    ///     0. `[w]` The pool state account
+    ///     1. `[s]` Pool Governance Account
+    ///     2. `[]` pool authority
+    ///     3. ..3 + TOKEN_COUNT `[w]` pool's token accounts
+    ///     4. ..3 + TOKEN_COUNT `[w]` LP Token Mint
+    ///     5. ..4 + TOKEN_COUNT `[w]` target pool's token accounts, owned by its authority (the program address of
+    ///          the target pool's key with the canonical bump seed)
+    ///     6. ..4 + (2 * TOKEN_COUNT) `[w]` export account (see `find_export_address`)
+    ///     7. ..5 + (2 * TOKEN_COUNT) `[ws]` Payer of the export account
+    ///     8. ..6 + (2 * TOKEN_COUNT) `[]` SPL token program account
+    ///     9. ..7 + (2 * TOKEN_COUNT) `[]` System program
+    ExportState {
+        target_program: Pubkey,
+        target_pool: Pubkey,
     },
-    /// Swaps in at most `maximum_input_amount` of the input token specified by
-    /// `input_token_index` for the exact_output_amounts
+    /// Initializes a pool from the export of a pool that moved to it with `ExportState`: with the exported pool's lp
+    /// mint, tokens (in the same order), governance, governance fee account, amp factor and fees and the balances
+    /// that `ExportState` transferred. The pool starts out paused, governance unpauses it once it checked the pool
+    ///
+    /// Accounts expected by this instruction:
+    ///     0. ..0 + (4 + 2 * TOKEN_COUNT) the accounts of `Init`, the governance account has to sign
+    ///     1. ..4 + (2 * TOKEN_COUNT) `[]` export account of the source pool (see `find_export_address`)
+    ImportState {
+        nonce: u8,
+    },
+    /// Initializes a new pool like `Init` and adds `input_amounts` to it like `DeFiInstruction::Add` in the same
+    /// instruction, so the creator holds the pool's first lp tokens before anybody else can add to it (e.g. dust
+    /// amounts that leave the pool with a lopsided first deposit)
+    ///
+    /// Accounts expected by this instruction:
+    ///     0. ..0 + (4 + 2 * TOKEN_COUNT) the accounts of `Init`
+    ///     1. ..4 + (2 * TOKEN_COUNT) the accounts of `DeFiInstruction::Add`
+    InitAndAdd {
+        nonce: u8,
+        amp_factor: DecT,
+        lp_fee: DecT,
+        governance_fee: DecT,
+        input_amounts: [AmountT; TOKEN_COUNT],
+        minimum_mint_amount: AmountT,
+    },
+    /// Moves the pool's balances to new token accounts at program addresses (see `find_rotated_vault_address`) that
+    /// only the pool authority controls and replaces the pool's token accounts with them, for when the current ones
+    /// can't be trusted anymore (e.g. a delegate or close authority that was set up off-band). The old accounts are
+    /// left empty. Only while the pool is paused
+    ///
+    /// Accounts expected by this instruction:
+    ///     0. `[w]` The pool state account
+    ///     1. `[s]` Pool Governance Account
+    ///     2. `[]` pool authority
+    ///     3. ..3 + TOKEN_COUNT `[w]` pool's token accounts
+    ///     4. ..3 + (2 * TOKEN_COUNT) `[]` Token mints
+    ///     5. ..3 + (3 * TOKEN_COUNT) `[w]` new token accounts (see `find_rotated_vault_address`)
+    ///     6. ..3 + (3 * TOKEN_COUNT) `[ws]` Payer of the new token accounts
+    ///     7. ..4 + (3 * TOKEN_COUNT) `[]` SPL token program account
+    ///     8. ..5 + (3 * TOKEN_COUNT) `[]` System program
+    ///     9. ..6 + (3 * TOKEN_COUNT) `[]` Rent sysvar
+    RotateVaults {},
+    /// Locks `amount` of the owner's lp tokens in the pool's lock vault (see `find_lock_vault_address`) for at least
+    /// `duration` seconds (see `state::MIN_LOCK_DURATION` and `state::MAX_LOCK_DURATION`). Locks earn the pool's lock
+    /// fee share of the governance fee (see `GovernanceInstruction::SetLockFeeShare`) in proportion to their weight,
+    /// which grows with the duration (see `state::lock_weight`). Every owner has one lock per pool (see
+    /// `find_lock_address`): locking more adds to it, settles its rewards so far and keeps the later of both
+    /// deadlines. The vault and the lock are created if they don't exist yet. Not while the pool is paused or being
+    /// wound down, nor for pools with a non-transferable lp token
     ///
     /// Accounts expected by this instruction:
     ///     0. `[w]` The pool state account
     ///     1. `[]` pool authority
-    ///     2. ..2 + TOKEN_COUNT `[w]` pool's token accounts
-    ///     3. ..3 + TOKEN_COUNT `[w]` LP Token Mint
-    ///     4. ..4 + TOKEN_COUNT `[w]` governance_fee_account
-    ///     5. ..5 + TOKEN_COUNT `[s]` user transfer authority account
-    ///     6. ..6 + TOKEN_COUNT `[w]` user token accounts
-    ///     7. ..6 + (2 * TOKEN_COUNT) `[]` SPL token program account
-    SwapExactOutput {
-        maximum_input_amount: AmountT,
-        input_token_index: u8,
-        exact_output_amounts: [AmountT; TOKEN_COUNT],
+    ///     2. `[w]` LP Token Mint
+    ///     3. `[w]` lock vault (see `find_lock_vault_address`)
+    ///     4. `[w]` lock account (see `find_lock_address`)
+    ///     5. `[s]` lock owner
+    ///     6. `[w]` owner's lp token account, pays the locked lp tokens and receives the settled rewards
+    ///     7. `[ws]` Payer (only pays if the vault or the lock don't exist yet)
+    ///     8. `[]` SPL token program account
+    ///     9. `[]` System program
+    ///     10. `[]` Rent sysvar
+    LockLp {
+        amount: AmountT,
+        duration: UnixTimestamp,
     },
-
-    /// Withdraw at least the number of tokens specified by `minimum_output_amounts` by
-    /// burning `exact_burn_amount` of LP tokens
-    /// Final withdrawal amounts are based on current deposit ratios
+    /// Mints the lp tokens that the owner's lock earned since its rewards were last settled
     ///
+    /// Accounts expected by this instruction:
+    ///     0. `[w]` The pool state account
+    ///     1. `[]` pool authority
+    ///     2. `[w]` LP Token Mint
+    ///     3. `[w]` lock account (see `find_lock_address`)
+    ///     4. `[s]` lock owner
+    ///     5. `[w]` owner's lp token account
+    ///     6. `[]` SPL token program account
+    ClaimLockRewards {},
+    /// Settles the owner's lock rewards, returns the locked lp tokens and closes the lock, which gives its lamports to
+    /// the owner. Only once the lock's deadline passed, or any time once the pool is being wound down
     ///
     /// Accounts expected by this instruction:
     ///     0. `[w]` The pool state account
     ///     1. `[]` pool authority
-    ///     2. ..2 + TOKEN_COUNT `[w]` pool's token accounts
-    ///     3. ..3 + TOKEN_COUNT `[w]` LP Token Mint
-    ///     4. ..4 + TOKEN_COUNT `[w]` governance_fee_account
-    ///     5. ..5 + TOKEN_COUNT `[s]` user transfer authority account
-    ///     6. ..6 + TOKEN_COUNT `[w]` user token accounts
-    ///     7. ..6 + (2 * TOKEN_COUNT) `[]` SPL token program account
-    ///     8. ..7 + (2 * TOKEN_COUNT) `[w]` user LP token account to withdraw/burn from
-    RemoveUniform {
-        exact_burn_amount: AmountT,
-        minimum_output_amounts: [AmountT; TOKEN_COUNT],
+    ///     2. `[w]` LP Token Mint
+    ///     3. `[w]` lock vault (see `find_lock_vault_address`)
+    ///     4. `[w]` lock account (see `find_lock_address`)
+    ///     5. `[ws]` lock owner
+    ///     6. `[w]` owner's lp token account
+    ///     7. `[]` SPL token program account
+    UnlockLp {},
+    /// Records the balance of an lp token account along with the pool's lp fee growth (see `state::FeeCheckpoint`)
+    /// and returns (as return data) the lp tokens' worth of lp fees that the previously recorded balance earned since
+    /// its checkpoint, 0 for the account's first checkpoint
+    ///
+    /// Accounts expected by this instruction:
+    ///     0. `[]` The pool state account
+    ///     1. `[]` lp token account
+    ///     2. `[s]` owner of the lp token account
+    ///     3. `[w]` fee checkpoint account (see `find_fee_checkpoint_address`)
+    ///     4. `[ws]` Payer (only pays if the checkpoint account doesn't exist yet)
+    ///     5. `[]` System program
+    CheckpointFees {},
+    /// Records the pool's lp supply along with its integral over time (see `state::LpSupplySnapshots`) in the pool's
+    /// lp supply account, creating the account if necessary, can be called by anyone
+    ///
+    /// Accounts expected by this instruction:
+    ///     0. `[]` The pool state account
+    ///     1. `[w]` lp supply account (see `find_lp_supply_address`)
+    ///     2. `[]` LP Token Mint
+    ///     3. `[ws]` Payer (only pays if the lp supply account doesn't exist yet)
+    ///     4. `[]` System program
+    SnapshotLpSupply {},
+    /// Creates the launch allocation account (see `state::LaunchAllocation`) that a wallet's adds pass while the pool's
+    /// launch window is open, does nothing if it already exists. Can be called by anyone
+    ///
+    /// Accounts expected by this instruction:
+    ///     0. `[]` The pool state account
+    ///     1. `[w]` launch allocation account (see `find_launch_allocation_address`)
+    ///     2. `[]` wallet, i.e. the user transfer authority of its adds
+    ///     3. `[ws]` Payer
+    ///     4. `[]` System program
+    CreateLaunchAllocation {},
+    /// Authorizes `session_key` to sign the user's swaps in its place until `expiry_ts` (see `state::Session`),
+    /// spending at most `spend_limits` of each token. The session account becomes the delegate (for the token's spend
+    /// limit) of the user's token accounts of all tokens with a limit, replacing their current delegate. A new
+    /// session replaces the user's current one
+    ///
+    /// Accounts expected by this instruction:
+    ///     0. `[]` The pool state account
+    ///     1. `[w]` session account (see `find_session_address`)
+    ///     2. `[ws]` user, pays for the session account
+    ///     3. ..3 + TOKEN_COUNT `[w]` user token accounts
+    ///     4. ..3 + TOKEN_COUNT `[]` SPL token program account
+    ///     5. ..4 + TOKEN_COUNT `[]` System program
+    AuthorizeSession {
+        session_key: Pubkey,
+        expiry_ts: UnixTimestamp,
+        spend_limits: [AmountT; TOKEN_COUNT],
     },
-    /// Withdraw at least `minimum_output_amount` of output token specified by `output_token_index` by
-    /// burning `exact_burn_amount` of LP tokens
-    /// "WithdrawOne"
+    /// Ends the user's session: revokes the session account's delegation of the user's token accounts and closes it,
+    /// which gives its lamports to the user
     ///
+    /// Accounts expected by this instruction:
+    ///     0. `[]` The pool state account
+    ///     1. `[w]` session account (see `find_session_address`)
+    ///     2. `[ws]` user
+    ///     3. ..3 + TOKEN_COUNT `[w]` user token accounts
+    ///     4. ..3 + TOKEN_COUNT `[]` SPL token program account
+    RevokeSession {},
+    /// Swaps at the price of a quote that the pool's quoter (see `GovernanceInstruction::SetRfqQuoter`) signed for the
+    /// user, rather than the invariant's, for request-for-quote flow of large trades. The instruction has to directly
+    /// follow the ed25519 program's verification of the quoter's signature (see `create_rfq_signature_ix`). Fails with
+    /// `QuoteExpired` once the quote expired, with `QuoteAlreadyUsed` unless its id exceeds that of the last quote the
+    /// pool executed and with `QuoteDeviationExceeded` if its price is further than the pool's max deviation from the
+    /// internal oracle's. Charges no fees
     ///
     /// Accounts expected by this instruction:
     ///     0. `[w]` The pool state account
     ///     1. `[]` pool authority
     ///     2. ..2 + TOKEN_COUNT `[w]` pool's token accounts
-    ///     3. ..3 + TOKEN_COUNT `[w]` LP Token Mint
-    ///     4. ..4 + TOKEN_COUNT `[w]` governance_fee_account
-    ///     5. ..5 + TOKEN_COUNT `[s]` user transfer authority account
-    ///     6. ..6 + TOKEN_COUNT `[w]` user token accounts
-    ///     7. ..6 + (2 * TOKEN_COUNT) `[]` SPL token program account
-    ///     8. ..7 + (2 * TOKEN_COUNT) `[w]` user LP token account to withdraw/burn from
-    RemoveExactBurn {
-        exact_burn_amount: AmountT,
-        output_token_index: u8,
-        minimum_output_amount: AmountT,
+    ///     3. ..2 + TOKEN_COUNT `[s]` user transfer authority, the quote's user
+    ///     4. ..3 + TOKEN_COUNT `[w]` user's token account of the input token
+    ///     5. ..4 + TOKEN_COUNT `[w]` user's token account of the output token
+    ///     6. ..5 + TOKEN_COUNT `[]` SPL token program account
+    ///     7. ..6 + TOKEN_COUNT `[]` instructions sysvar
+    ///     8. ..7 + TOKEN_COUNT `[]` rate accounts of the pool's rated tokens (in pool token order)
+    ///     9. ..7 + TOKEN_COUNT + rate accounts `[]` gate account of the user transfer authority (only if the pool
+    ///          gates its users, see `GovernanceInstruction::SetUserGate`)
+    ///     10. ..8 + TOKEN_COUNT + rate accounts `[]` the pool's compliance program, followed by the user transfer
+    ///          authority's compliance account (only if the pool has a compliance program, see `ComplianceCheckData`)
+    RfqSwap {
+        quote: RfqQuote,
     },
-    /// Withdraw exactly the number of output tokens specified by `exact_output_amount`
-    /// by burning at most `maximum_burn_amounts` of LP tokens
+    /// Announces that the user will burn up to `lp_amount` lp tokens in removes above the pool's withdrawal cooldown
+    /// threshold (see `GovernanceInstruction::SetWithdrawalCooldown` and `state::WithdrawalAnnouncement`), which they
+    /// can once the pool's cooldown has passed. A new announcement replaces the user's current one and restarts the
+    /// cooldown, announcing 0 closes the announcement account, which gives its lamports to the user
+    ///
+    /// Accounts expected by this instruction:
+    ///     0. `[]` The pool state account
+    ///     1. `[w]` withdrawal announcement account (see `find_withdrawal_announcement_address`)
+    ///     2. `[ws]` user, pays for the withdrawal announcement account
+    ///     3. `[]` System program
+    AnnounceWithdrawal {
+        lp_amount: AmountT,
+    },
+    /// Confirms an emergency as one of the pool's emergency responders (see
+    /// `GovernanceInstruction::SetEmergencyResponders`). The confirmation that reaches the pool's threshold of
+    /// confirmations within `EMERGENCY_CONFIRMATION_WINDOW` pauses the pool, so only uniform removes remain, and resets
+    /// all confirmations. Only governance (or the pause admin) can unpause it
+    ///
+    /// Accounts expected by this instruction:
+    ///     0. `[w]` The pool state account
+    ///     1. `[s]` emergency responder
+    ConfirmEmergency {},
+    /// Initializes a new pool like `Init`, with the weighted constant product invariant (see `state::InvariantKind`)
+    /// instead of the stable swap one, for tokens of unrelated values. The pool holds `token_weights` (in parts of
+    /// `weighted::WEIGHT_ONE`, each at least `weighted::MIN_WEIGHT`) of its value in each token
+    ///
+    /// Accounts expected by this instruction are the same as for `Init`
+    InitWeighted {
+        nonce: u8,
+        token_weights: [u32; TOKEN_COUNT],
+        lp_fee: DecT,
+        governance_fee: DecT,
+    },
+    /// Lends the pool `amount` of the peg keeper's token (see `GovernanceInstruction::SetPegKeeper`) while the pool is
+    /// short of it, as a virtual balance rather than a transfer, at the invariant's price without fees. Fails with
+    /// `PegCreditExceeded` beyond the keeper's credit limit or once the credit would be worth more than
+    /// `state::MAX_PEG_CREDIT_SHARE_BPS` of the lp supply, and with `PegCreditPastPeg` unless the pool's (rated)
+    /// balance of the token stays at most the average of the others', i.e. the token's price doesn't drop below its
+    /// peg
+    ///
+    /// Accounts expected by this instruction:
+    ///     0. `[w]` The pool state account
+    ///     1. `[]` LP Token Mint
+    ///     2. ..2 + TOKEN_COUNT `[]` pool's token accounts
+    ///     3. ..2 + TOKEN_COUNT `[s]` peg keeper
+    ///     4. ..3 + TOKEN_COUNT `[]` rate accounts of the pool's rated tokens (in pool token order)
+    ProvidePegCredit {
+        amount: AmountT,
+    },
+    /// Takes `amount` of the peg keeper's virtual balance back out of the pool while the pool holds too much of its
+    /// token, the reverse of `ProvidePegCredit`. Fails with `PegCreditExceeded` beyond the pool's debt to the keeper
+    /// or the lp tokens of its credit and with `PegCreditPastPeg` unless the pool's balance of the token stays at least
+    /// the average of the others'. Repaying the whole debt leaves the keeper's remaining lp tokens to the liquidity
+    /// providers
+    ///
+    /// Accounts expected by this instruction are the same as for `ProvidePegCredit`
+    AbsorbPegCredit {
+        amount: AmountT,
+    },
+    /// Initializes a new pool like `Init`, with the fixed rate invariant (see `state::InvariantKind`) instead of the
+    /// stable swap one, for pools that migrate or wrap one token into another. The pool trades its tokens one for one
+    /// at their exchange rates (see `GovernanceInstruction::SetRateSource`) without curvature, the fees may be zero
+    ///
+    /// Accounts expected by this instruction are the same as for `Init`
+    InitFixedRate {
+        nonce: u8,
+        lp_fee: DecT,
+        governance_fee: DecT,
+    },
+    /// Deploys what the pool's account of the token at `token_index` holds beyond its yield adapter's buffer (see
+    /// `state::YieldAdapter`) to the adapter with `YieldAdapterInstruction::Deposit`, for which the pool approves
+    /// the adapter's yield delegate to transfer exactly that amount, and records the value of the pool's position that
+    /// the adapter reports, at most what it held before plus the amount. Anyone can call it, not while the pool is
+    /// paused or sunset. Fails with `InvalidYieldAdapter` if the adapter isn't the token's, didn't take exactly the
+    /// amount, moved any other of the pool's tokens or didn't report the position
     ///
     /// Accounts expected by this instruction:
     ///     0. `[w]` The pool state account
     ///     1. `[]` pool authority
-    ///     2. ..2 + TOKEN_COUNT `[w]` pool's token accounts
-    ///     3. ..3 + TOKEN_COUNT `[w]` LP Token Mint
-    ///     4. ..4 + TOKEN_COUNT `[w]` governance_fee_account
-    ///     5. ..5 + TOKEN_COUNT `[s]` user transfer authority account
-    ///     6. ..6 + TOKEN_COUNT `[w]` user token accounts
-    ///     7. ..6 + (2 * TOKEN_COUNT) `[]` SPL token program account
-    ///     8. ..7 + (2 * TOKEN_COUNT) `[w]` user LP token account to withdraw/burn from
-    RemoveExactOutput {
-        maximum_burn_amount: AmountT,
-        exact_output_amounts: [AmountT; TOKEN_COUNT],
+    ///     2. ..2 + TOKEN_COUNT `[]` pool's token accounts, that of the token writable
+    ///     3. ..2 + TOKEN_COUNT `[]` SPL token program account
+    ///     4. ..3 + TOKEN_COUNT `[]` rate accounts of the pool's rated tokens (in pool token order)
+    ///     5. ..3 + TOKEN_COUNT + rate accounts `[]` yield adapter program
+    ///     6. ..4 + TOKEN_COUNT + rate accounts `[]` the adapter's yield delegate (see `find_yield_delegate_address`)
+    ///     7. ..5 + TOKEN_COUNT + rate accounts the accounts that the yield adapter needs, passed on without their
+    ///          signatures
+    DeployYield {
+        token_index: u8,
+    },
+    /// Recalls `amount` of the token at `token_index` from its yield adapter into the pool's token account with
+    /// `YieldAdapterInstruction::Withdraw`, e.g. ahead of a withdrawal that the buffer doesn't cover (see
+    /// `YieldBufferShort`), and records the value of the pool's position like `DeployYield`, at most what it held
+    /// before less the amount, so whatever the position earned counts once it is recalled. Anyone can call it, also
+    /// while the pool is paused, so users can always exit
+    ///
+    /// Accounts expected by this instruction are the same as for `DeployYield`
+    RecallYield {
+        token_index: u8,
+        amount: AmountT,
+    },
+    /// Initializes a new pool like `Init`, with the hybrid invariant (see `state::InvariantKind`) for stablecoins and
+    /// the volatile token at `volatile_index`, which the invariant values at the pool's own price `initial_rate`
+    /// (scaled by 10^RATE_DECIMALS, see `state::PriceScale`) until `Repeg` moves it. The volatile token's rate source
+    /// is set with `GovernanceInstruction::SetRateSource` as usual, its repeg parameters with
+    /// `GovernanceInstruction::SetRepegParams`
+    ///
+    /// Accounts expected by this instruction are the same as for `Init`
+    InitHybrid {
+        nonce: u8,
+        amp_factor: DecT,
+        lp_fee: DecT,
+        governance_fee: DecT,
+        volatile_index: u8,
+        initial_rate: u64,
     },
+    /// Moves a hybrid pool's price for its volatile token towards the current rate of the token's rate source, by at
+    /// most the repeg parameters' step (see `state::PriceScale`). Anyone can call it, not while the pool is paused.
+    /// Fails with `RepegNotDue` while the price is within the threshold of the oracle's rate or the pool repegged less
+    /// than the minimum interval ago
+    ///
+    /// Accounts expected by this instruction:
+    ///     0. `[w]` The pool state account
+    ///     1. ..1 + TOKEN_COUNT `[]` pool's token accounts
+    ///     2. ..1 + TOKEN_COUNT `[]` rate accounts of the pool's rated tokens (in pool token order)
+    Repeg {},
+    /// Funds one of the rewards of the pool's liquidity mining gauge (see `state::Gauge`, added with
+    /// `GovernanceInstruction::SetGaugeReward`) with `amount` of its token, which it emits along with what it is yet to
+    /// emit evenly over the next `duration` seconds (at most `state::MAX_GAUGE_DURATION`) to the stakes of the gauge
+    /// (see `StakeLp`), in proportion to their working amount. Every reward has its own emission schedule and can only
+    /// be funded by its funder. Fails with `InvalidGauge` for a token that isn't an active reward of the gauge,
+    /// another funder or if the emission rate would round down to zero. Not while the pool is being wound down
+    ///
+    /// Accounts expected by this instruction:
+    ///     0. `[]` The pool state account
+    ///     1. `[s]` the reward's funder
+    ///     2. `[w]` gauge account (see `find_gauge_address`)
+    ///     3. `[]` reward token mint
+    ///     4. `[w]` gauge's reward vault (see `find_gauge_vault_address`)
+    ///     5. `[w]` funder's token account of the reward token, pays `amount`
+    ///     6. `[]` SPL token program account
+    FundGauge {
+        amount: AmountT,
+        duration: UnixTimestamp,
+    },
+    /// Stakes `amount` of the owner's lp tokens with the pool's gauge, which holds them in its lp vault and fails with
+    /// `InvalidGauge` until governance added a reward to it. Every owner has one stake per pool (see
+    /// `find_gauge_stake_address`), staking more adds to it after settling its rewards so far, the stake is created if
+    /// it doesn't exist yet. Staking updates the stake's boost (see `UpdateGaugeBoost`). Not while the pool is paused
+    /// or being wound down
+    ///
+    /// Accounts expected by this instruction:
+    ///     0. `[]` The pool state account
+    ///     1. `[w]` gauge account (see `find_gauge_address`)
+    ///     2. `[w]` gauge's lp vault (see `find_gauge_vault_address`)
+    ///     3. `[w]` stake account (see `find_gauge_stake_address`)
+    ///     4. `[s]` stake owner
+    ///     5. `[w]` owner's lp token account
+    ///     6. `[ws]` Payer (only pays if the stake doesn't exist yet)
+    ///     7. `[]` SPL token program account
+    ///     8. `[]` System program
+    ///     9. `[]` gauge's ve program, only if the gauge has one (see `add_ve_boost_accounts`)
+    ///     10. `[]` owner's ve account, only if the gauge has a ve program (see `find_ve_boost_address`)
+    StakeLp {
+        amount: AmountT,
+    },
+    /// Returns `amount` of the owner's staked lp tokens after settling the stake's rewards so far. Unstaking drops the
+    /// stake's boost without calling the gauge's ve program, so exits never depend on it. Unstaking what's left of a
+    /// stake without unclaimed rewards closes it, which gives its lamports to the owner. Fails with
+    /// `InsufficientStake` beyond the staked amount
+    ///
+    /// Accounts expected by this instruction:
+    ///     0. `[]` The pool state account
+    ///     1. `[]` pool authority
+    ///     2. `[w]` gauge account (see `find_gauge_address`)
+    ///     3. `[w]` gauge's lp vault (see `find_gauge_vault_address`)
+    ///     4. `[w]` stake account (see `find_gauge_stake_address`)
+    ///     5. `[ws]` stake owner
+    ///     6. `[w]` owner's lp token account
+    ///     7. `[]` SPL token program account
+    UnstakeLp {
+        amount: AmountT,
+    },
+    /// Pays out the tokens of one of the gauge's rewards, retired ones included, that the owner's stake earned and
+    /// didn't claim yet. Leaves the stake's boost as it is
+    ///
+    /// Accounts expected by this instruction:
+    ///     0. `[]` The pool state account
+    ///     1. `[]` pool authority
+    ///     2. `[w]` gauge account (see `find_gauge_address`)
+    ///     3. `[]` reward token mint
+    ///     4. `[w]` gauge's reward vault (see `find_gauge_vault_address`)
+    ///     5. `[w]` stake account (see `find_gauge_stake_address`)
+    ///     6. `[s]` stake owner
+    ///     7. `[w]` owner's token account of the reward token
+    ///     8. `[]` SPL token program account
+    ClaimGaugeRewards {},
+    /// Creates the owner's rebate account with one of the pool's trading fee rebate campaigns (see `state::Rebate`),
+    /// which the owner's swaps then pass to record their fees with the campaign while its window is open (see
+    /// `DeFiInstruction::SwapExactInput`). Does nothing if the account exists already. Fails with
+    /// `InvalidRebateCampaign` for another pool's campaign or once the campaign's window ended
+    ///
+    /// Accounts expected by this instruction:
+    ///     0. `[]` The pool state account
+    ///     1. `[]` rebate campaign account (see `find_rebate_campaign_address`)
+    ///     2. `[w]` rebate account (see `find_rebate_address`)
+    ///     3. `[]` rebate owner, the user transfer authority of the swaps
+    ///     4. `[ws]` Payer
+    ///     5. `[]` System program
+    JoinRebateCampaign {},
+    /// Pays out the owner's rebate once the campaign's window ended: the share of the campaign's budget that the fees
+    /// the owner's swaps recorded make up of all recorded fees. Closes the rebate account, which gives its lamports to
+    /// the owner. Fails with `RebateCampaignNotEnded` while the window is open and with `InvalidRebateCampaign` once
+    /// the governance reclaimed what was left of the budget (see `GovernanceInstruction::ReclaimRebateBudget`)
+    ///
+    /// Accounts expected by this instruction:
+    ///     0. `[]` The pool state account
+    ///     1. `[]` pool authority
+    ///     2. `[]` rebate campaign account (see `find_rebate_campaign_address`)
+    ///     3. `[w]` campaign's budget vault (see `find_rebate_vault_address`)
+    ///     4. `[w]` rebate account (see `find_rebate_address`)
+    ///     5. `[ws]` rebate owner
+    ///     6. `[w]` owner's token account of the rebate token
+    ///     7. `[]` SPL token program account
+    ClaimRebate {},
+    /// Settles a stake's rewards so far and sets its working amount (see `state::GaugeStake`) to its amount times the
+    /// boost that the gauge's voting escrow program returns for it (see `VeBoostQuery`), or to its amount if the gauge
+    /// has no ve program (anymore). Anyone can call it, e.g. to bring a boost that ran out down to what the owner's
+    /// vote-escrowed balance is still worth. Fails with `InvalidVeProgram` if the ve program or account isn't the
+    /// gauge's resp. the owner's or the program returns no boost
+    ///
+    /// Accounts expected by this instruction:
+    ///     0. `[]` The pool state account
+    ///     1. `[w]` gauge account (see `find_gauge_address`)
+    ///     2. `[w]` stake account (see `find_gauge_stake_address`)
+    ///     3. `[]` stake owner
+    ///     4. `[]` gauge's ve program, only if the gauge has one
+    ///     5. `[]` owner's ve account, only if the gauge has a ve program (see `find_ve_boost_address`)
+    UpdateGaugeBoost {},
 }
 
-pub fn create_defi_ix<const TOKEN_COUNT: usize>(
-    defi_instruction: DeFiInstruction<TOKEN_COUNT>,
-    program_id: &Pubkey,
-    pool: &Pubkey,
-    authority: &Pubkey,
-    pool_token_accounts: &[Pubkey; TOKEN_COUNT],
-    lp_mint: &Pubkey,
-    governance_fee_account: &Pubkey,
-    user_transfer_authority: &Pubkey,
-    user_token_accounts: &[Pubkey; TOKEN_COUNT],
-    token_program_account: &Pubkey,
-    user_lp_token_account: Option<&Pubkey>,
-) -> Result<Instruction, ProgramError> {
-    let mut accounts = vec![
-        AccountMeta::new(*pool, false),
-        AccountMeta::new_readonly(*authority, false),
-    ];
-    for i in 0..TOKEN_COUNT {
-        accounts.push(AccountMeta::new(pool_token_accounts[i], false));
+impl<const TOKEN_COUNT: usize> PoolInstruction<TOKEN_COUNT> {
+    /// Decodes instruction data like `try_from_slice`, but fails with `TokenCountMismatch` for defi instructions
+    /// encoded for pools of a different number of tokens, which would otherwise fail with an unrelated Borsh error
+    pub fn unpack(data: &[u8]) -> Result<Self, ProgramError> {
+        Self::try_from_slice(data).map_err(|error| match defi_token_count(data) {
+            Some(token_count) if token_count != TOKEN_COUNT => {
+                PoolError::TokenCountMismatch.with_context(format_args!("defi instruction for {} tokens", token_count))
+            }
+            _ => error.into(),
+        })
     }
-    accounts.push(AccountMeta::new(*lp_mint, false));
-    accounts.push(AccountMeta::new(*governance_fee_account, false));
+}
 
-    // used from SPL binary-oracle-pair. not actually necessary since the implementation only supports
-    //  that using a separate keypair
-    accounts.push(AccountMeta::new_readonly(
+//the token count that a PoolInstruction::DeFiInstruction was encoded for. an encoding only decodes under one token
+// count since its amount arrays make up a different length for every other
+fn defi_token_count(data: &[u8]) -> Option<usize> {
+    fn decodes<const TOKEN_COUNT: usize>(data: &[u8]) -> bool {
+        DeFiInstruction::<TOKEN_COUNT>::try_from_slice(data).is_ok()
+    }
+    let defi_data = match data.split_first() {
+        //1 is the tag of PoolInstruction::DeFiInstruction
+        Some((1, defi_data)) => defi_data,
+        _ => return None,
+    };
+    //every token count up to MAX_TOKEN_COUNT
+    let candidates: [(usize, fn(&[u8]) -> bool); MAX_TOKEN_COUNT - 1] = [
+        (2, decodes::<2>),
+        (3, decodes::<3>),
+        (4, decodes::<4>),
+        (5, decodes::<5>),
+        (6, decodes::<6>),
+        (7, decodes::<7>),
+        (8, decodes::<8>),
+    ];
+    candidates
+        .iter()
+        .find(|(_, decodes)| decodes(defi_data))
+        .map(|(token_count, _)| *token_count)
+}
+
+/// A price that a pool's quoter offers a user: `input_amount` of the pool's token `input_token_index` for
+/// `output_amount` of its token `output_token_index`, until `expiry_ts`. The quoter signs its Borsh encoding (see
+/// `message`), which `RfqSwap` executes
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RfqQuote {
+    pub pool: Pubkey,
+    pub user: Pubkey,
+    pub input_token_index: u8,
+    pub input_amount: AmountT,
+    pub output_token_index: u8,
+    pub output_amount: AmountT,
+    //has to exceed the id of the last quote that the pool executed
+    pub quote_id: u64,
+    pub expiry_ts: UnixTimestamp,
+}
+
+impl RfqQuote {
+    /// The message that the quoter signs
+    pub fn message(&self) -> Vec<u8> {
+        //serializing into a vec doesn't fail
+        self.try_to_vec().unwrap_or_default()
+    }
+}
+
+/// The native program that verifies ed25519 signatures, whose id the pinned solana-program doesn't export
+pub mod ed25519_program {
+    solana_program::declare_id!("Ed25519SigVerify111111111111111111111111111");
+}
+
+//the ed25519 program's instruction data: the signature count and a padding byte, then per signature the offsets
+// (u16 each) of the signature, public key and message along with the indices of the instructions that hold them
+// (u16::MAX for the instruction itself) and the message's size
+const ED25519_OFFSETS_START: usize = 2;
+const ED25519_OFFSETS_LEN: usize = 14;
+const ED25519_CURRENT_INSTRUCTION: u16 = u16::MAX;
+
+/// The accounts of a pool that a `RoutedSwap` swaps in
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RouteLeg<const TOKEN_COUNT: usize> {
+    pub pool: Pubkey,
+    pub authority: Pubkey,
+    pub token_accounts: [Pubkey; TOKEN_COUNT],
+    pub lp_mint: Pubkey,
+    pub governance_fee_account: Pubkey,
+    /// The Token-2022 program (if the pool's lp token is non-transferable) and oracle accounts (if the pool is oracle
+    /// guarded) followed by the rate accounts of its rated tokens, the instructions sysvar (if the pool restricts
+    /// its callers) and the user's gate account (if the pool gates its users, see `find_user_gate_account`)
+    pub swap_accounts: Vec<Pubkey>,
+}
+
+impl<const TOKEN_COUNT: usize> RouteLeg<TOKEN_COUNT> {
+    fn append_to(&self, accounts: &mut Vec<AccountMeta>) {
+        accounts.push(AccountMeta::new(self.pool, false));
+        accounts.push(AccountMeta::new_readonly(self.authority, false));
+        for token_account in self.token_accounts.iter() {
+            accounts.push(AccountMeta::new(*token_account, false));
+        }
+        accounts.push(AccountMeta::new(self.lp_mint, false));
+        accounts.push(AccountMeta::new(self.governance_fee_account, false));
+        for swap_account in self.swap_accounts.iter() {
+            accounts.push(AccountMeta::new_readonly(*swap_account, false));
+        }
+    }
+}
+
+/// Creates an `Init` instruction
+pub fn create_init_ix<const TOKEN_COUNT: usize>(
+    program_id: &Pubkey,
+    pool: &Pubkey,
+    lp_mint: &Pubkey,
+    token_mints: &[Pubkey; TOKEN_COUNT],
+    token_accounts: &[Pubkey; TOKEN_COUNT],
+    governance_account: &Pubkey,
+    governance_fee_account: &Pubkey,
+    nonce: u8,
+    amp_factor: DecT,
+    lp_fee: DecT,
+    governance_fee: DecT,
+) -> Result<Instruction, ProgramError> {
+    let accounts = init_accounts(
+        pool,
+        lp_mint,
+        token_mints,
+        token_accounts,
+        governance_account,
+        governance_fee_account,
+    );
+    let data = PoolInstruction::<TOKEN_COUNT>::Init {
+        nonce,
+        amp_factor,
+        lp_fee,
+        governance_fee,
+    }
+    .try_to_vec()?;
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates an `InitWeighted` instruction
+pub fn create_init_weighted_ix<const TOKEN_COUNT: usize>(
+    program_id: &Pubkey,
+    pool: &Pubkey,
+    lp_mint: &Pubkey,
+    token_mints: &[Pubkey; TOKEN_COUNT],
+    token_accounts: &[Pubkey; TOKEN_COUNT],
+    governance_account: &Pubkey,
+    governance_fee_account: &Pubkey,
+    nonce: u8,
+    token_weights: [u32; TOKEN_COUNT],
+    lp_fee: DecT,
+    governance_fee: DecT,
+) -> Result<Instruction, ProgramError> {
+    let accounts = init_accounts(
+        pool,
+        lp_mint,
+        token_mints,
+        token_accounts,
+        governance_account,
+        governance_fee_account,
+    );
+    let data = PoolInstruction::<TOKEN_COUNT>::InitWeighted {
+        nonce,
+        token_weights,
+        lp_fee,
+        governance_fee,
+    }
+    .try_to_vec()?;
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates an `InitFixedRate` instruction
+pub fn create_init_fixed_rate_ix<const TOKEN_COUNT: usize>(
+    program_id: &Pubkey,
+    pool: &Pubkey,
+    lp_mint: &Pubkey,
+    token_mints: &[Pubkey; TOKEN_COUNT],
+    token_accounts: &[Pubkey; TOKEN_COUNT],
+    governance_account: &Pubkey,
+    governance_fee_account: &Pubkey,
+    nonce: u8,
+    lp_fee: DecT,
+    governance_fee: DecT,
+) -> Result<Instruction, ProgramError> {
+    let accounts = init_accounts(
+        pool,
+        lp_mint,
+        token_mints,
+        token_accounts,
+        governance_account,
+        governance_fee_account,
+    );
+    let data = PoolInstruction::<TOKEN_COUNT>::InitFixedRate {
+        nonce,
+        lp_fee,
+        governance_fee,
+    }
+    .try_to_vec()?;
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates an `InitHybrid` instruction
+#[allow(clippy::too_many_arguments)]
+pub fn create_init_hybrid_ix<const TOKEN_COUNT: usize>(
+    program_id: &Pubkey,
+    pool: &Pubkey,
+    lp_mint: &Pubkey,
+    token_mints: &[Pubkey; TOKEN_COUNT],
+    token_accounts: &[Pubkey; TOKEN_COUNT],
+    governance_account: &Pubkey,
+    governance_fee_account: &Pubkey,
+    nonce: u8,
+    amp_factor: DecT,
+    lp_fee: DecT,
+    governance_fee: DecT,
+    volatile_index: u8,
+    initial_rate: u64,
+) -> Result<Instruction, ProgramError> {
+    let accounts = init_accounts(
+        pool,
+        lp_mint,
+        token_mints,
+        token_accounts,
+        governance_account,
+        governance_fee_account,
+    );
+    let data = PoolInstruction::<TOKEN_COUNT>::InitHybrid {
+        nonce,
+        amp_factor,
+        lp_fee,
+        governance_fee,
+        volatile_index,
+        initial_rate,
+    }
+    .try_to_vec()?;
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+fn init_accounts<const TOKEN_COUNT: usize>(
+    pool: &Pubkey,
+    lp_mint: &Pubkey,
+    token_mints: &[Pubkey; TOKEN_COUNT],
+    token_accounts: &[Pubkey; TOKEN_COUNT],
+    governance_account: &Pubkey,
+    governance_fee_account: &Pubkey,
+) -> Vec<AccountMeta> {
+    let mut accounts = vec![
+        AccountMeta::new(*pool, false),
+        AccountMeta::new_readonly(*lp_mint, false),
+    ];
+    for i in 0..TOKEN_COUNT {
+        accounts.push(AccountMeta::new_readonly(token_mints[i], false));
+    }
+    for i in 0..TOKEN_COUNT {
+        accounts.push(AccountMeta::new_readonly(token_accounts[i], false));
+    }
+    accounts.push(AccountMeta::new_readonly(*governance_account, false));
+    accounts.push(AccountMeta::new_readonly(*governance_fee_account, false));
+    accounts
+}
+
+/// Creates an `InitAndAdd` instruction from the pool's init instruction (see `create_init_ix`) and the creator's add
+/// instruction (see `create_defi_ix`)
+pub fn create_init_and_add_ix<const TOKEN_COUNT: usize>(
+    init_ix: Instruction,
+    add_ix: Instruction,
+) -> Result<Instruction, ProgramError> {
+    let init = PoolInstruction::<TOKEN_COUNT>::try_from_slice(&init_ix.data)?;
+    let add = PoolInstruction::<TOKEN_COUNT>::try_from_slice(&add_ix.data)?;
+    let init_and_add = match (init, add) {
+        (
+            PoolInstruction::Init {
+                nonce,
+                amp_factor,
+                lp_fee,
+                governance_fee,
+            },
+            PoolInstruction::DeFiInstruction(DeFiInstruction::Add {
+                input_amounts,
+                minimum_mint_amount,
+            }),
+        ) => PoolInstruction::InitAndAdd {
+            nonce,
+            amp_factor,
+            lp_fee,
+            governance_fee,
+            input_amounts,
+            minimum_mint_amount,
+        },
+        _ => return Err(ProgramError::InvalidInstructionData),
+    };
+    let mut accounts = init_ix.accounts;
+    accounts.extend(add_ix.accounts);
+
+    Ok(Instruction {
+        program_id: init_ix.program_id,
+        accounts,
+        data: init_and_add.try_to_vec()?,
+    })
+}
+
+/// Creates a `ClonePoolConfig` instruction, the accounts are those of `create_init_ix`
+#[allow(clippy::too_many_arguments)]
+pub fn create_clone_pool_config_ix<const TOKEN_COUNT: usize>(
+    program_id: &Pubkey,
+    pool: &Pubkey,
+    lp_mint: &Pubkey,
+    token_mints: &[Pubkey; TOKEN_COUNT],
+    token_accounts: &[Pubkey; TOKEN_COUNT],
+    governance_account: &Pubkey,
+    governance_fee_account: &Pubkey,
+    nonce: u8,
+    template_pool: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let mut accounts = vec![
+        AccountMeta::new(*pool, false),
+        AccountMeta::new_readonly(*lp_mint, false),
+    ];
+    for token_mint in token_mints.iter() {
+        accounts.push(AccountMeta::new_readonly(*token_mint, false));
+    }
+    for token_account in token_accounts.iter() {
+        accounts.push(AccountMeta::new_readonly(*token_account, false));
+    }
+    accounts.push(AccountMeta::new_readonly(*governance_account, false));
+    accounts.push(AccountMeta::new_readonly(*governance_fee_account, false));
+    accounts.push(AccountMeta::new_readonly(*template_pool, false));
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data: PoolInstruction::<TOKEN_COUNT>::ClonePoolConfig { nonce }.try_to_vec()?,
+    })
+}
+
+/// The address of the account that `ExportState` writes the export of `pool` to and its bump seed
+pub fn find_export_address(program_id: &Pubkey, pool: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[EXPORT_SEED, &pool.to_bytes()], program_id)
+}
+
+/// Creates an `ExportState` instruction of `program_id`'s `pool` to `target_pool` of `target_program`
+#[allow(clippy::too_many_arguments)]
+pub fn create_export_state_ix<const TOKEN_COUNT: usize>(
+    program_id: &Pubkey,
+    pool: &Pubkey,
+    governance: &Pubkey,
+    authority: &Pubkey,
+    pool_token_accounts: &[Pubkey; TOKEN_COUNT],
+    lp_mint: &Pubkey,
+    target_program: &Pubkey,
+    target_pool: &Pubkey,
+    target_token_accounts: &[Pubkey; TOKEN_COUNT],
+    payer: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let mut accounts = vec![
+        AccountMeta::new(*pool, false),
+        AccountMeta::new_readonly(*governance, true),
+        AccountMeta::new_readonly(*authority, false),
+    ];
+    for pool_token_account in pool_token_accounts.iter() {
+        accounts.push(AccountMeta::new(*pool_token_account, false));
+    }
+    accounts.push(AccountMeta::new(*lp_mint, false));
+    for target_token_account in target_token_accounts.iter() {
+        accounts.push(AccountMeta::new(*target_token_account, false));
+    }
+    accounts.push(AccountMeta::new(find_export_address(program_id, pool).0, false));
+    accounts.push(AccountMeta::new(*payer, true));
+    accounts.push(AccountMeta::new_readonly(spl_token::id(), false));
+    accounts.push(AccountMeta::new_readonly(system_program::id(), false));
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data: PoolInstruction::<TOKEN_COUNT>::ExportState {
+            target_program: *target_program,
+            target_pool: *target_pool,
+        }
+        .try_to_vec()?,
+    })
+}
+
+/// Creates an `ImportState` instruction of the pool that `source_pool` of `source_program` moved to, the accounts are
+/// those of `create_init_ix`
+#[allow(clippy::too_many_arguments)]
+pub fn create_import_state_ix<const TOKEN_COUNT: usize>(
+    program_id: &Pubkey,
+    pool: &Pubkey,
+    lp_mint: &Pubkey,
+    token_mints: &[Pubkey; TOKEN_COUNT],
+    token_accounts: &[Pubkey; TOKEN_COUNT],
+    governance_account: &Pubkey,
+    governance_fee_account: &Pubkey,
+    nonce: u8,
+    source_program: &Pubkey,
+    source_pool: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let mut accounts = vec![
+        AccountMeta::new(*pool, false),
+        AccountMeta::new_readonly(*lp_mint, false),
+    ];
+    for token_mint in token_mints.iter() {
+        accounts.push(AccountMeta::new_readonly(*token_mint, false));
+    }
+    for token_account in token_accounts.iter() {
+        accounts.push(AccountMeta::new_readonly(*token_account, false));
+    }
+    accounts.push(AccountMeta::new_readonly(*governance_account, true));
+    accounts.push(AccountMeta::new_readonly(*governance_fee_account, false));
+    accounts.push(AccountMeta::new_readonly(
+        find_export_address(source_program, source_pool).0,
+        false,
+    ));
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data: PoolInstruction::<TOKEN_COUNT>::ImportState { nonce }.try_to_vec()?,
+    })
+}
+
+/// Creates a `CheckPeg` instruction
+pub fn create_check_peg_ix<const TOKEN_COUNT: usize>(
+    program_id: &Pubkey,
+    pool: &Pubkey,
+    oracle_keys: &[Pubkey; TOKEN_COUNT],
+) -> Result<Instruction, ProgramError> {
+    let mut accounts = vec![AccountMeta::new(*pool, false)];
+    for oracle_key in oracle_keys.iter() {
+        accounts.push(AccountMeta::new_readonly(*oracle_key, false));
+    }
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data: PoolInstruction::<TOKEN_COUNT>::CheckPeg {}.try_to_vec()?,
+    })
+}
+
+/// Creates a `Repeg` instruction
+pub fn create_repeg_ix<const TOKEN_COUNT: usize>(
+    program_id: &Pubkey,
+    pool: &Pubkey,
+    pool_token_accounts: &[Pubkey; TOKEN_COUNT],
+    rate_accounts: &[Pubkey],
+) -> Result<Instruction, ProgramError> {
+    let mut accounts = vec![AccountMeta::new(*pool, false)];
+    for pool_token_account in pool_token_accounts.iter() {
+        accounts.push(AccountMeta::new_readonly(*pool_token_account, false));
+    }
+    for rate_account in rate_accounts.iter() {
+        accounts.push(AccountMeta::new_readonly(*rate_account, false));
+    }
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data: PoolInstruction::<TOKEN_COUNT>::Repeg {}.try_to_vec()?,
+    })
+}
+
+/// The address of a pool's liquidity mining gauge (see `state::Gauge`) and its bump seed
+pub fn find_gauge_address(program_id: &Pubkey, pool: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[GAUGE_SEED, &pool.to_bytes()], program_id)
+}
+
+/// The address of the token account of `mint` that a pool's gauge holds its staked lp tokens (`mint` being the lp
+/// mint) resp. its rewards in and its bump seed
+pub fn find_gauge_vault_address(program_id: &Pubkey, pool: &Pubkey, mint: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[GAUGE_SEED, &pool.to_bytes(), &mint.to_bytes()], program_id)
+}
+
+/// The address of `owner`'s stake with a pool's gauge (see `state::GaugeStake`) and its bump seed
+pub fn find_gauge_stake_address(program_id: &Pubkey, pool: &Pubkey, owner: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[GAUGE_STAKE_SEED, &pool.to_bytes(), &owner.to_bytes()], program_id)
+}
+
+/// The address of `owner`'s ve account that gauge instructions of gauges with `ve_program` pass (see
+/// `state::VE_BOOST_SEED`). It's up to the ve program what's at the address, if anything
+pub fn find_ve_boost_address(ve_program: &Pubkey, pool: &Pubkey, owner: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[VE_BOOST_SEED, &pool.to_bytes(), &owner.to_bytes()], ve_program)
+}
+
+/// The address of `adapter_program` that `DeployYield` approves to transfer the deployed amount out of the pool's token
+/// account (see `state::YIELD_DELEGATE_SEED`), which the adapter signs for in the transfer
+pub fn find_yield_delegate_address(adapter_program: &Pubkey, pool: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[YIELD_DELEGATE_SEED, &pool.to_bytes()], adapter_program)
+}
+
+/// Adds the accounts that a `StakeLp` instruction of `owner` takes if the pool's gauge has the voting escrow program
+/// `ve_program`
+pub fn add_ve_boost_accounts(gauge_ix: &mut Instruction, pool: &Pubkey, owner: &Pubkey, ve_program: &Pubkey) {
+    gauge_ix.accounts.push(AccountMeta::new_readonly(*ve_program, false));
+    gauge_ix.accounts.push(AccountMeta::new_readonly(
+        find_ve_boost_address(ve_program, pool, owner).0,
+        false,
+    ));
+}
+
+/// Creates a `FundGauge` instruction that funds the gauge's reward of `reward_mint` with `amount` of the tokens of
+/// `funder_reward_account` to emit over `duration` seconds
+pub fn create_fund_gauge_ix<const TOKEN_COUNT: usize>(
+    program_id: &Pubkey,
+    pool: &Pubkey,
+    funder: &Pubkey,
+    reward_mint: &Pubkey,
+    funder_reward_account: &Pubkey,
+    amount: AmountT,
+    duration: UnixTimestamp,
+) -> Result<Instruction, ProgramError> {
+    let accounts = vec![
+        AccountMeta::new_readonly(*pool, false),
+        AccountMeta::new_readonly(*funder, true),
+        AccountMeta::new(find_gauge_address(program_id, pool).0, false),
+        AccountMeta::new_readonly(*reward_mint, false),
+        AccountMeta::new(find_gauge_vault_address(program_id, pool, reward_mint).0, false),
+        AccountMeta::new(*funder_reward_account, false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+    ];
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data: PoolInstruction::<TOKEN_COUNT>::FundGauge { amount, duration }.try_to_vec()?,
+    })
+}
+
+/// Creates a `StakeLp` instruction that stakes `amount` of the lp tokens of `owner_lp_account` with the pool's gauge
+pub fn create_stake_lp_ix<const TOKEN_COUNT: usize>(
+    program_id: &Pubkey,
+    pool: &Pubkey,
+    lp_mint: &Pubkey,
+    owner: &Pubkey,
+    owner_lp_account: &Pubkey,
+    payer: &Pubkey,
+    amount: AmountT,
+) -> Result<Instruction, ProgramError> {
+    let accounts = vec![
+        AccountMeta::new_readonly(*pool, false),
+        AccountMeta::new(find_gauge_address(program_id, pool).0, false),
+        AccountMeta::new(find_gauge_vault_address(program_id, pool, lp_mint).0, false),
+        AccountMeta::new(find_gauge_stake_address(program_id, pool, owner).0, false),
+        AccountMeta::new_readonly(*owner, true),
+        AccountMeta::new(*owner_lp_account, false),
+        AccountMeta::new(*payer, true),
+        AccountMeta::new_readonly(spl_token::id(), false),
+        AccountMeta::new_readonly(system_program::id(), false),
+    ];
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data: PoolInstruction::<TOKEN_COUNT>::StakeLp { amount }.try_to_vec()?,
+    })
+}
+
+/// Creates an `UnstakeLp` instruction that returns `amount` of `owner`'s staked lp tokens to `owner_lp_account`
+pub fn create_unstake_lp_ix<const TOKEN_COUNT: usize>(
+    program_id: &Pubkey,
+    pool: &Pubkey,
+    authority: &Pubkey,
+    lp_mint: &Pubkey,
+    owner: &Pubkey,
+    owner_lp_account: &Pubkey,
+    amount: AmountT,
+) -> Result<Instruction, ProgramError> {
+    let accounts = vec![
+        AccountMeta::new_readonly(*pool, false),
+        AccountMeta::new_readonly(*authority, false),
+        AccountMeta::new(find_gauge_address(program_id, pool).0, false),
+        AccountMeta::new(find_gauge_vault_address(program_id, pool, lp_mint).0, false),
+        AccountMeta::new(find_gauge_stake_address(program_id, pool, owner).0, false),
+        AccountMeta::new(*owner, true),
+        AccountMeta::new(*owner_lp_account, false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+    ];
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data: PoolInstruction::<TOKEN_COUNT>::UnstakeLp { amount }.try_to_vec()?,
+    })
+}
+
+/// Creates a `ClaimGaugeRewards` instruction that pays `owner`'s rewards of `reward_mint` to `owner_reward_account`
+pub fn create_claim_gauge_rewards_ix<const TOKEN_COUNT: usize>(
+    program_id: &Pubkey,
+    pool: &Pubkey,
+    authority: &Pubkey,
+    reward_mint: &Pubkey,
+    owner: &Pubkey,
+    owner_reward_account: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let accounts = vec![
+        AccountMeta::new_readonly(*pool, false),
+        AccountMeta::new_readonly(*authority, false),
+        AccountMeta::new(find_gauge_address(program_id, pool).0, false),
+        AccountMeta::new_readonly(*reward_mint, false),
+        AccountMeta::new(find_gauge_vault_address(program_id, pool, reward_mint).0, false),
+        AccountMeta::new(find_gauge_stake_address(program_id, pool, owner).0, false),
+        AccountMeta::new_readonly(*owner, true),
+        AccountMeta::new(*owner_reward_account, false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+    ];
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data: PoolInstruction::<TOKEN_COUNT>::ClaimGaugeRewards {}.try_to_vec()?,
+    })
+}
+
+/// Creates an `UpdateGaugeBoost` instruction for `owner`'s stake, `ve_program` is the gauge's voting escrow program,
+/// if it has one
+pub fn create_update_gauge_boost_ix<const TOKEN_COUNT: usize>(
+    program_id: &Pubkey,
+    pool: &Pubkey,
+    owner: &Pubkey,
+    ve_program: Option<&Pubkey>,
+) -> Result<Instruction, ProgramError> {
+    let mut instruction = Instruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new_readonly(*pool, false),
+            AccountMeta::new(find_gauge_address(program_id, pool).0, false),
+            AccountMeta::new(find_gauge_stake_address(program_id, pool, owner).0, false),
+            AccountMeta::new_readonly(*owner, false),
+        ],
+        data: PoolInstruction::<TOKEN_COUNT>::UpdateGaugeBoost {}.try_to_vec()?,
+    };
+    if let Some(ve_program) = ve_program {
+        add_ve_boost_accounts(&mut instruction, pool, owner, ve_program);
+    }
+    Ok(instruction)
+}
+
+/// The address of a pool's trading fee rebate campaign with `campaign_id` (see `state::RebateCampaign`) and its bump
+/// seed
+pub fn find_rebate_campaign_address(program_id: &Pubkey, pool: &Pubkey, campaign_id: u64) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[REBATE_CAMPAIGN_SEED, &pool.to_bytes(), &campaign_id.to_le_bytes()],
+        program_id,
+    )
+}
+
+/// The address of the token account that escrows a rebate campaign's budget and its bump seed
+pub fn find_rebate_vault_address(program_id: &Pubkey, campaign: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[REBATE_CAMPAIGN_SEED, &campaign.to_bytes()], program_id)
+}
+
+/// The address of `owner`'s rebate account with a rebate campaign (see `state::Rebate`) and its bump seed
+pub fn find_rebate_address(program_id: &Pubkey, campaign: &Pubkey, owner: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[REBATE_SEED, &campaign.to_bytes(), &owner.to_bytes()], program_id)
+}
+
+/// Creates a `JoinRebateCampaign` instruction that creates `owner`'s rebate account with `campaign`
+pub fn create_join_rebate_campaign_ix<const TOKEN_COUNT: usize>(
+    program_id: &Pubkey,
+    pool: &Pubkey,
+    campaign: &Pubkey,
+    owner: &Pubkey,
+    payer: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let accounts = vec![
+        AccountMeta::new_readonly(*pool, false),
+        AccountMeta::new_readonly(*campaign, false),
+        AccountMeta::new(find_rebate_address(program_id, campaign, owner).0, false),
+        AccountMeta::new_readonly(*owner, false),
+        AccountMeta::new(*payer, true),
+        AccountMeta::new_readonly(system_program::id(), false),
+    ];
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data: PoolInstruction::<TOKEN_COUNT>::JoinRebateCampaign {}.try_to_vec()?,
+    })
+}
+
+/// Creates a `ClaimRebate` instruction that pays `owner`'s rebate from `campaign` to `owner_rebate_account`
+pub fn create_claim_rebate_ix<const TOKEN_COUNT: usize>(
+    program_id: &Pubkey,
+    pool: &Pubkey,
+    authority: &Pubkey,
+    campaign: &Pubkey,
+    owner: &Pubkey,
+    owner_rebate_account: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let accounts = vec![
+        AccountMeta::new_readonly(*pool, false),
+        AccountMeta::new_readonly(*authority, false),
+        AccountMeta::new_readonly(*campaign, false),
+        AccountMeta::new(find_rebate_vault_address(program_id, campaign).0, false),
+        AccountMeta::new(find_rebate_address(program_id, campaign, owner).0, false),
+        AccountMeta::new(*owner, true),
+        AccountMeta::new(*owner_rebate_account, false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+    ];
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data: PoolInstruction::<TOKEN_COUNT>::ClaimRebate {}.try_to_vec()?,
+    })
+}
+
+/// The address of a pool's lp supply account (see `state::LpSupplySnapshots`) and its bump seed
+pub fn find_lp_supply_address(program_id: &Pubkey, pool: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[LP_SUPPLY_SEED, &pool.to_bytes()], program_id)
+}
+
+/// Creates a `SnapshotLpSupply` instruction
+pub fn create_snapshot_lp_supply_ix<const TOKEN_COUNT: usize>(
+    program_id: &Pubkey,
+    pool: &Pubkey,
+    lp_mint: &Pubkey,
+    payer: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let accounts = vec![
+        AccountMeta::new_readonly(*pool, false),
+        AccountMeta::new(find_lp_supply_address(program_id, pool).0, false),
+        AccountMeta::new_readonly(*lp_mint, false),
+        AccountMeta::new(*payer, true),
+        AccountMeta::new_readonly(system_program::id(), false),
+    ];
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data: PoolInstruction::<TOKEN_COUNT>::SnapshotLpSupply {}.try_to_vec()?,
+    })
+}
+
+/// The address of `user`'s allowed user account (see `state::AllowedUser`) and its bump seed
+pub fn find_allowed_user_address(program_id: &Pubkey, pool: &Pubkey, user: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[ALLOWED_USER_SEED, &pool.to_bytes(), &user.to_bytes()], program_id)
+}
+
+/// The address of `depositor`'s account of the pool's depositor allowlist (see `state::AllowedUser`) and its bump
+/// seed
+pub fn find_allowed_depositor_address(program_id: &Pubkey, pool: &Pubkey, depositor: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[ALLOWED_DEPOSITOR_SEED, &pool.to_bytes(), &depositor.to_bytes()],
+        program_id,
+    )
+}
+
+/// Like `find_user_gate_account` for the pool's `depositor_gate`, which adds and single token removes pass through
+/// as well
+pub fn find_depositor_gate_account(
+    program_id: &Pubkey,
+    pool: &Pubkey,
+    depositor_gate: &UserGate,
+    depositor: &Pubkey,
+) -> Option<Pubkey> {
+    match depositor_gate {
+        UserGate::Open => None,
+        UserGate::Allowlist => Some(find_allowed_depositor_address(program_id, pool, depositor).0),
+        UserGate::GatingToken { mint } => Some(spl_associated_token_account::get_associated_token_address(
+            depositor, mint,
+        )),
+    }
+}
+
+/// The account that `user`'s defi instructions pass to get through the pool's `user_gate`: its allowed user account
+/// or its associated token account of the gating mint (any token account of the mint that `user` owns will do), none
+/// for open pools
+pub fn find_user_gate_account(
+    program_id: &Pubkey,
+    pool: &Pubkey,
+    user_gate: &UserGate,
+    user: &Pubkey,
+) -> Option<Pubkey> {
+    match user_gate {
+        UserGate::Open => None,
+        UserGate::Allowlist => Some(find_allowed_user_address(program_id, pool, user).0),
+        UserGate::GatingToken { mint } => Some(spl_associated_token_account::get_associated_token_address(user, mint)),
+    }
+}
+
+/// The address of `owner`'s launch allocation account (see `state::LaunchAllocation`) and its bump seed
+pub fn find_launch_allocation_address(program_id: &Pubkey, pool: &Pubkey, owner: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[LAUNCH_ALLOCATION_SEED, &pool.to_bytes(), &owner.to_bytes()],
+        program_id,
+    )
+}
+
+/// Creates a `CreateLaunchAllocation` instruction for `owner`
+pub fn create_launch_allocation_ix<const TOKEN_COUNT: usize>(
+    program_id: &Pubkey,
+    pool: &Pubkey,
+    owner: &Pubkey,
+    payer: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let accounts = vec![
+        AccountMeta::new_readonly(*pool, false),
+        AccountMeta::new(find_launch_allocation_address(program_id, pool, owner).0, false),
+        AccountMeta::new_readonly(*owner, false),
+        AccountMeta::new(*payer, true),
+        AccountMeta::new_readonly(system_program::id(), false),
+    ];
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data: PoolInstruction::<TOKEN_COUNT>::CreateLaunchAllocation {}.try_to_vec()?,
+    })
+}
+
+/// The address of `user`'s compliance account that defi instructions of pools with `compliance_program` pass (see
+/// `state::COMPLIANCE_SEED`). It's up to the compliance program what's at the address, if anything
+pub fn find_compliance_address(compliance_program: &Pubkey, pool: &Pubkey, user: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[COMPLIANCE_SEED, &pool.to_bytes(), &user.to_bytes()],
+        compliance_program,
+    )
+}
+
+/// The address of `user`'s session account (see `state::Session`) and its bump seed
+pub fn find_session_address(program_id: &Pubkey, pool: &Pubkey, user: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[SESSION_SEED, &pool.to_bytes(), &user.to_bytes()], program_id)
+}
+
+/// Creates an `AuthorizeSession` instruction for `user`
+pub fn create_authorize_session_ix<const TOKEN_COUNT: usize>(
+    program_id: &Pubkey,
+    pool: &Pubkey,
+    user: &Pubkey,
+    user_token_accounts: &[Pubkey; TOKEN_COUNT],
+    session_key: &Pubkey,
+    expiry_ts: UnixTimestamp,
+    spend_limits: [AmountT; TOKEN_COUNT],
+) -> Result<Instruction, ProgramError> {
+    let mut accounts = session_accounts(program_id, pool, user, user_token_accounts);
+    accounts.push(AccountMeta::new_readonly(system_program::id(), false));
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data: PoolInstruction::AuthorizeSession {
+            session_key: *session_key,
+            expiry_ts,
+            spend_limits,
+        }
+        .try_to_vec()?,
+    })
+}
+
+/// Creates a `RevokeSession` instruction for `user`
+pub fn create_revoke_session_ix<const TOKEN_COUNT: usize>(
+    program_id: &Pubkey,
+    pool: &Pubkey,
+    user: &Pubkey,
+    user_token_accounts: &[Pubkey; TOKEN_COUNT],
+) -> Result<Instruction, ProgramError> {
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts: session_accounts(program_id, pool, user, user_token_accounts),
+        data: PoolInstruction::<TOKEN_COUNT>::RevokeSession {}.try_to_vec()?,
+    })
+}
+
+fn session_accounts<const TOKEN_COUNT: usize>(
+    program_id: &Pubkey,
+    pool: &Pubkey,
+    user: &Pubkey,
+    user_token_accounts: &[Pubkey; TOKEN_COUNT],
+) -> Vec<AccountMeta> {
+    let mut accounts = vec![
+        AccountMeta::new_readonly(*pool, false),
+        AccountMeta::new(find_session_address(program_id, pool, user).0, false),
+        AccountMeta::new(*user, true),
+    ];
+    for user_token_account in user_token_accounts.iter() {
+        accounts.push(AccountMeta::new(*user_token_account, false));
+    }
+    accounts.push(AccountMeta::new_readonly(spl_token::id(), false));
+    accounts
+}
+
+/// Makes a swap instruction of `user` (with all its accounts but the optional hook program) one that `session_key`
+/// signs in the user's place, see `PoolInstruction::AuthorizeSession`
+pub fn sign_swap_with_session<const TOKEN_COUNT: usize>(
+    swap_ix: &mut Instruction,
+    pool: &Pubkey,
+    user: &Pubkey,
+    session_key: &Pubkey,
+) {
+    swap_ix.accounts[4 + TOKEN_COUNT].is_signer = false;
+    swap_ix.accounts.push(AccountMeta::new(
+        find_session_address(&swap_ix.program_id, pool, user).0,
+        false,
+    ));
+    swap_ix.accounts.push(AccountMeta::new_readonly(*session_key, true));
+}
+
+/// Makes a swap instruction of `user` (with all its accounts but the optional hook program) record its fees with the
+/// rebate campaign `campaign`, which `user` has to have joined, see `PoolInstruction::JoinRebateCampaign`
+pub fn record_swap_rebate(swap_ix: &mut Instruction, campaign: &Pubkey, user: &Pubkey) {
+    let rebate = find_rebate_address(&swap_ix.program_id, campaign, user).0;
+    swap_ix.accounts.push(AccountMeta::new(*campaign, false));
+    swap_ix.accounts.push(AccountMeta::new(rebate, false));
+}
+
+/// The address of `user`'s withdrawal announcement account (see `state::WithdrawalAnnouncement`) and its bump seed
+pub fn find_withdrawal_announcement_address(program_id: &Pubkey, pool: &Pubkey, user: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[WITHDRAWAL_ANNOUNCEMENT_SEED, &pool.to_bytes(), &user.to_bytes()],
+        program_id,
+    )
+}
+
+/// Creates an `AnnounceWithdrawal` instruction for `user`
+pub fn create_announce_withdrawal_ix<const TOKEN_COUNT: usize>(
+    program_id: &Pubkey,
+    pool: &Pubkey,
+    user: &Pubkey,
+    lp_amount: AmountT,
+) -> Result<Instruction, ProgramError> {
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new_readonly(*pool, false),
+            AccountMeta::new(find_withdrawal_announcement_address(program_id, pool, user).0, false),
+            AccountMeta::new(*user, true),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+        data: PoolInstruction::<TOKEN_COUNT>::AnnounceWithdrawal { lp_amount }.try_to_vec()?,
+    })
+}
+
+/// Creates a `ConfirmEmergency` instruction signed by `responder`
+pub fn create_confirm_emergency_ix<const TOKEN_COUNT: usize>(
+    program_id: &Pubkey,
+    pool: &Pubkey,
+    responder: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new(*pool, false),
+            AccountMeta::new_readonly(*responder, true),
+        ],
+        data: PoolInstruction::<TOKEN_COUNT>::ConfirmEmergency {}.try_to_vec()?,
+    })
+}
+
+/// Creates a `ProvidePegCredit` instruction signed by the pool's peg keeper
+pub fn create_provide_peg_credit_ix<const TOKEN_COUNT: usize>(
+    program_id: &Pubkey,
+    pool: &Pubkey,
+    lp_mint: &Pubkey,
+    pool_token_accounts: &[Pubkey; TOKEN_COUNT],
+    peg_keeper: &Pubkey,
+    rate_accounts: &[Pubkey],
+    amount: AmountT,
+) -> Result<Instruction, ProgramError> {
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts: peg_credit_accounts(pool, lp_mint, pool_token_accounts, peg_keeper, rate_accounts),
+        data: PoolInstruction::<TOKEN_COUNT>::ProvidePegCredit { amount }.try_to_vec()?,
+    })
+}
+
+/// Creates an `AbsorbPegCredit` instruction signed by the pool's peg keeper
+pub fn create_absorb_peg_credit_ix<const TOKEN_COUNT: usize>(
+    program_id: &Pubkey,
+    pool: &Pubkey,
+    lp_mint: &Pubkey,
+    pool_token_accounts: &[Pubkey; TOKEN_COUNT],
+    peg_keeper: &Pubkey,
+    rate_accounts: &[Pubkey],
+    amount: AmountT,
+) -> Result<Instruction, ProgramError> {
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts: peg_credit_accounts(pool, lp_mint, pool_token_accounts, peg_keeper, rate_accounts),
+        data: PoolInstruction::<TOKEN_COUNT>::AbsorbPegCredit { amount }.try_to_vec()?,
+    })
+}
+
+fn peg_credit_accounts<const TOKEN_COUNT: usize>(
+    pool: &Pubkey,
+    lp_mint: &Pubkey,
+    pool_token_accounts: &[Pubkey; TOKEN_COUNT],
+    peg_keeper: &Pubkey,
+    rate_accounts: &[Pubkey],
+) -> Vec<AccountMeta> {
+    let mut accounts = vec![
+        AccountMeta::new(*pool, false),
+        AccountMeta::new_readonly(*lp_mint, false),
+    ];
+    for pool_token_account in pool_token_accounts.iter() {
+        accounts.push(AccountMeta::new_readonly(*pool_token_account, false));
+    }
+    accounts.push(AccountMeta::new_readonly(*peg_keeper, true));
+    for rate_account in rate_accounts.iter() {
+        accounts.push(AccountMeta::new_readonly(*rate_account, false));
+    }
+    accounts
+}
+
+/// Creates a `DeployYield` instruction, `adapter_accounts` are the accounts that the token's yield adapter needs
+#[allow(clippy::too_many_arguments)]
+pub fn create_deploy_yield_ix<const TOKEN_COUNT: usize>(
+    program_id: &Pubkey,
+    pool: &Pubkey,
+    pool_authority: &Pubkey,
+    pool_token_accounts: &[Pubkey; TOKEN_COUNT],
+    rate_accounts: &[Pubkey],
+    token_index: u8,
+    adapter_program: &Pubkey,
+    adapter_accounts: &[AccountMeta],
+) -> Result<Instruction, ProgramError> {
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts: yield_accounts(
+            pool,
+            pool_authority,
+            pool_token_accounts,
+            rate_accounts,
+            token_index,
+            adapter_program,
+            adapter_accounts,
+        ),
+        data: PoolInstruction::<TOKEN_COUNT>::DeployYield { token_index }.try_to_vec()?,
+    })
+}
+
+/// Creates a `RecallYield` instruction, `adapter_accounts` are the accounts that the token's yield adapter needs
+#[allow(clippy::too_many_arguments)]
+pub fn create_recall_yield_ix<const TOKEN_COUNT: usize>(
+    program_id: &Pubkey,
+    pool: &Pubkey,
+    pool_authority: &Pubkey,
+    pool_token_accounts: &[Pubkey; TOKEN_COUNT],
+    rate_accounts: &[Pubkey],
+    token_index: u8,
+    amount: AmountT,
+    adapter_program: &Pubkey,
+    adapter_accounts: &[AccountMeta],
+) -> Result<Instruction, ProgramError> {
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts: yield_accounts(
+            pool,
+            pool_authority,
+            pool_token_accounts,
+            rate_accounts,
+            token_index,
+            adapter_program,
+            adapter_accounts,
+        ),
+        data: PoolInstruction::<TOKEN_COUNT>::RecallYield { token_index, amount }.try_to_vec()?,
+    })
+}
+
+fn yield_accounts<const TOKEN_COUNT: usize>(
+    pool: &Pubkey,
+    pool_authority: &Pubkey,
+    pool_token_accounts: &[Pubkey; TOKEN_COUNT],
+    rate_accounts: &[Pubkey],
+    token_index: u8,
+    adapter_program: &Pubkey,
+    adapter_accounts: &[AccountMeta],
+) -> Vec<AccountMeta> {
+    let mut accounts = vec![
+        AccountMeta::new(*pool, false),
+        AccountMeta::new_readonly(*pool_authority, false),
+    ];
+    for (i, pool_token_account) in pool_token_accounts.iter().enumerate() {
+        if i == token_index as usize {
+            accounts.push(AccountMeta::new(*pool_token_account, false));
+        } else {
+            accounts.push(AccountMeta::new_readonly(*pool_token_account, false));
+        }
+    }
+    accounts.push(AccountMeta::new_readonly(spl_token::id(), false));
+    for rate_account in rate_accounts.iter() {
+        accounts.push(AccountMeta::new_readonly(*rate_account, false));
+    }
+    accounts.push(AccountMeta::new_readonly(*adapter_program, false));
+    accounts.push(AccountMeta::new_readonly(
+        find_yield_delegate_address(adapter_program, pool).0,
+        false,
+    ));
+    accounts.extend_from_slice(adapter_accounts);
+    accounts
+}
+
+/// The address of a pool's virtual price account and its bump seed
+pub fn find_virtual_price_address(program_id: &Pubkey, pool: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[VIRTUAL_PRICE_SEED, &pool.to_bytes()], program_id)
+}
+
+/// Creates a `CreatePoolTokenAccounts` instruction
+#[allow(clippy::too_many_arguments)]
+pub fn create_pool_token_accounts_ix<const TOKEN_COUNT: usize>(
+    program_id: &Pubkey,
+    pool: &Pubkey,
+    authority: &Pubkey,
+    pool_token_accounts: &[Pubkey; TOKEN_COUNT],
+    token_mints: &[Pubkey; TOKEN_COUNT],
+    governance_fee_account: &Pubkey,
+    governance: &Pubkey,
+    lp_mint: &Pubkey,
+    payer: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let mut accounts = vec![
+        AccountMeta::new_readonly(*pool, false),
+        AccountMeta::new_readonly(*authority, false),
+    ];
+    for pool_token_account in pool_token_accounts.iter() {
+        accounts.push(AccountMeta::new(*pool_token_account, false));
+    }
+    for token_mint in token_mints.iter() {
+        accounts.push(AccountMeta::new_readonly(*token_mint, false));
+    }
+    accounts.push(AccountMeta::new(*governance_fee_account, false));
+    accounts.push(AccountMeta::new_readonly(*governance, false));
+    accounts.push(AccountMeta::new_readonly(*lp_mint, false));
+    accounts.push(AccountMeta::new(*payer, true));
+    accounts.push(AccountMeta::new_readonly(system_program::id(), false));
+    accounts.push(AccountMeta::new_readonly(spl_token::id(), false));
+    accounts.push(AccountMeta::new_readonly(spl_associated_token_account::id(), false));
+    accounts.push(AccountMeta::new_readonly(sysvar::rent::id(), false));
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data: PoolInstruction::<TOKEN_COUNT>::CreatePoolTokenAccounts {}.try_to_vec()?,
+    })
+}
+
+/// The canonical address of the pool of `token_mints` (in ascending order) and `fee_tier` and its bump seed
+pub fn find_pool_address<const TOKEN_COUNT: usize>(
+    program_id: &Pubkey,
+    token_mints: &[Pubkey; TOKEN_COUNT],
+    fee_tier: u16,
+) -> (Pubkey, u8) {
+    let mint_bytes: Vec<[u8; 32]> = token_mints.iter().map(|mint| mint.to_bytes()).collect();
+    let fee_tier_bytes = fee_tier.to_le_bytes();
+    let mut seeds: Vec<&[u8]> = vec![POOL_SEED];
+    seeds.extend(mint_bytes.iter().map(|bytes| &bytes[..]));
+    seeds.push(&fee_tier_bytes);
+    Pubkey::find_program_address(&seeds, program_id)
+}
+
+/// The address of the lp mint of a `CreatePool` pool and its bump seed
+pub fn find_lp_mint_address(program_id: &Pubkey, pool: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[LP_MINT_SEED, &pool.to_bytes()], program_id)
+}
+
+/// The address of a `CreatePool` pool's token account for `mint` and its bump seed
+pub fn find_vault_address(program_id: &Pubkey, pool: &Pubkey, mint: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[VAULT_SEED, &pool.to_bytes(), &mint.to_bytes()], program_id)
+}
+
+/// The address of the token account that `RotateVaults` replaces the pool's token account `vault` with and its bump
+/// seed
+pub fn find_rotated_vault_address(program_id: &Pubkey, pool: &Pubkey, vault: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[VAULT_SEED, &pool.to_bytes(), &vault.to_bytes()], program_id)
+}
+
+/// Creates a `RotateVaults` instruction that replaces `pool_token_accounts`
+pub fn create_rotate_vaults_ix<const TOKEN_COUNT: usize>(
+    program_id: &Pubkey,
+    pool: &Pubkey,
+    governance: &Pubkey,
+    authority: &Pubkey,
+    pool_token_accounts: &[Pubkey; TOKEN_COUNT],
+    token_mints: &[Pubkey; TOKEN_COUNT],
+    payer: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let mut accounts = vec![
+        AccountMeta::new(*pool, false),
+        AccountMeta::new_readonly(*governance, true),
+        AccountMeta::new_readonly(*authority, false),
+    ];
+    for pool_token_account in pool_token_accounts.iter() {
+        accounts.push(AccountMeta::new(*pool_token_account, false));
+    }
+    for token_mint in token_mints.iter() {
+        accounts.push(AccountMeta::new_readonly(*token_mint, false));
+    }
+    for pool_token_account in pool_token_accounts.iter() {
+        accounts.push(AccountMeta::new(
+            find_rotated_vault_address(program_id, pool, pool_token_account).0,
+            false,
+        ));
+    }
+    accounts.push(AccountMeta::new(*payer, true));
+    accounts.push(AccountMeta::new_readonly(spl_token::id(), false));
+    accounts.push(AccountMeta::new_readonly(system_program::id(), false));
+    accounts.push(AccountMeta::new_readonly(sysvar::rent::id(), false));
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data: PoolInstruction::<TOKEN_COUNT>::RotateVaults {}.try_to_vec()?,
+    })
+}
+
+/// The address of the token account that holds a pool's locked lp tokens and its bump seed
+pub fn find_lock_vault_address(program_id: &Pubkey, pool: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[LOCK_SEED, &pool.to_bytes()], program_id)
+}
+
+/// The address of `owner`'s lp token lock of a pool and its bump seed
+pub fn find_lock_address(program_id: &Pubkey, pool: &Pubkey, owner: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[LOCK_SEED, &pool.to_bytes(), &owner.to_bytes()], program_id)
+}
+
+/// Creates a `LockLp` instruction that locks `amount` of the lp tokens of `owner_lp_account` for `duration`
+#[allow(clippy::too_many_arguments)]
+pub fn create_lock_lp_ix<const TOKEN_COUNT: usize>(
+    program_id: &Pubkey,
+    pool: &Pubkey,
+    authority: &Pubkey,
+    lp_mint: &Pubkey,
+    owner: &Pubkey,
+    owner_lp_account: &Pubkey,
+    payer: &Pubkey,
+    amount: AmountT,
+    duration: UnixTimestamp,
+) -> Result<Instruction, ProgramError> {
+    let accounts = vec![
+        AccountMeta::new(*pool, false),
+        AccountMeta::new_readonly(*authority, false),
+        AccountMeta::new(*lp_mint, false),
+        AccountMeta::new(find_lock_vault_address(program_id, pool).0, false),
+        AccountMeta::new(find_lock_address(program_id, pool, owner).0, false),
+        AccountMeta::new_readonly(*owner, true),
+        AccountMeta::new(*owner_lp_account, false),
+        AccountMeta::new(*payer, true),
+        AccountMeta::new_readonly(spl_token::id(), false),
+        AccountMeta::new_readonly(system_program::id(), false),
+        AccountMeta::new_readonly(sysvar::rent::id(), false),
+    ];
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data: PoolInstruction::<TOKEN_COUNT>::LockLp { amount, duration }.try_to_vec()?,
+    })
+}
+
+/// Creates a `ClaimLockRewards` instruction that mints `owner`'s lock rewards to `owner_lp_account`
+pub fn create_claim_lock_rewards_ix<const TOKEN_COUNT: usize>(
+    program_id: &Pubkey,
+    pool: &Pubkey,
+    authority: &Pubkey,
+    lp_mint: &Pubkey,
+    owner: &Pubkey,
+    owner_lp_account: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let accounts = vec![
+        AccountMeta::new(*pool, false),
+        AccountMeta::new_readonly(*authority, false),
+        AccountMeta::new(*lp_mint, false),
+        AccountMeta::new(find_lock_address(program_id, pool, owner).0, false),
+        AccountMeta::new_readonly(*owner, true),
+        AccountMeta::new(*owner_lp_account, false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+    ];
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data: PoolInstruction::<TOKEN_COUNT>::ClaimLockRewards {}.try_to_vec()?,
+    })
+}
+
+/// Creates an `UnlockLp` instruction that returns `owner`'s locked lp tokens to `owner_lp_account`
+pub fn create_unlock_lp_ix<const TOKEN_COUNT: usize>(
+    program_id: &Pubkey,
+    pool: &Pubkey,
+    authority: &Pubkey,
+    lp_mint: &Pubkey,
+    owner: &Pubkey,
+    owner_lp_account: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let accounts = vec![
+        AccountMeta::new(*pool, false),
+        AccountMeta::new_readonly(*authority, false),
+        AccountMeta::new(*lp_mint, false),
+        AccountMeta::new(find_lock_vault_address(program_id, pool).0, false),
+        AccountMeta::new(find_lock_address(program_id, pool, owner).0, false),
+        AccountMeta::new(*owner, true),
+        AccountMeta::new(*owner_lp_account, false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+    ];
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data: PoolInstruction::<TOKEN_COUNT>::UnlockLp {}.try_to_vec()?,
+    })
+}
+
+/// The address of the `FeeCheckpoint` of `lp_account` and its bump seed
+pub fn find_fee_checkpoint_address(program_id: &Pubkey, pool: &Pubkey, lp_account: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[FEE_CHECKPOINT_SEED, &pool.to_bytes(), &lp_account.to_bytes()],
+        program_id,
+    )
+}
+
+/// Creates a `CheckpointFees` instruction for `lp_account`, which `owner` owns
+pub fn create_checkpoint_fees_ix<const TOKEN_COUNT: usize>(
+    program_id: &Pubkey,
+    pool: &Pubkey,
+    lp_account: &Pubkey,
+    owner: &Pubkey,
+    payer: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let accounts = vec![
+        AccountMeta::new_readonly(*pool, false),
+        AccountMeta::new_readonly(*lp_account, false),
+        AccountMeta::new_readonly(*owner, true),
+        AccountMeta::new(find_fee_checkpoint_address(program_id, pool, lp_account).0, false),
+        AccountMeta::new(*payer, true),
+        AccountMeta::new_readonly(system_program::id(), false),
+    ];
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data: PoolInstruction::<TOKEN_COUNT>::CheckpointFees {}.try_to_vec()?,
+    })
+}
+
+/// The address of the governance fee account of a `CreatePool` pool and its bump seed
+pub fn find_governance_fee_address(program_id: &Pubkey, pool: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[GOVERNANCE_FEE_SEED, &pool.to_bytes()], program_id)
+}
+
+/// Creates a `CreatePool` instruction, `token_mints` have to be in ascending order
+#[allow(clippy::too_many_arguments)]
+pub fn create_pool_ix<const TOKEN_COUNT: usize>(
+    program_id: &Pubkey,
+    token_mints: &[Pubkey; TOKEN_COUNT],
+    fee_tier: u16,
+    governance: &Pubkey,
+    payer: &Pubkey,
+    amp_factor: DecT,
+    lp_fee: DecT,
+    governance_fee: DecT,
+) -> Result<Instruction, ProgramError> {
+    let pool = find_pool_address(program_id, token_mints, fee_tier).0;
+    let mut accounts = vec![
+        AccountMeta::new(pool, false),
+        AccountMeta::new(find_lp_mint_address(program_id, &pool).0, false),
+    ];
+    for token_mint in token_mints.iter() {
+        accounts.push(AccountMeta::new_readonly(*token_mint, false));
+    }
+    for token_mint in token_mints.iter() {
+        accounts.push(AccountMeta::new(
+            find_vault_address(program_id, &pool, token_mint).0,
+            false,
+        ));
+    }
+    accounts.push(AccountMeta::new_readonly(*governance, false));
+    accounts.push(AccountMeta::new(
+        find_governance_fee_address(program_id, &pool).0,
+        false,
+    ));
+    accounts.push(AccountMeta::new(*payer, true));
+    accounts.push(AccountMeta::new_readonly(system_program::id(), false));
+    accounts.push(AccountMeta::new_readonly(spl_token::id(), false));
+    accounts.push(AccountMeta::new_readonly(sysvar::rent::id(), false));
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data: PoolInstruction::<TOKEN_COUNT>::CreatePool {
+            fee_tier,
+            amp_factor,
+            lp_fee,
+            governance_fee,
+        }
+        .try_to_vec()?,
+    })
+}
+
+/// Creates a `MigrateIn` instruction from the pool's add instruction (see `create_defi_ix`, which also takes its rate
+/// accounts and instructions sysvar) and the user's lp token account of `source_pool`
+pub fn create_migrate_in_ix<const TOKEN_COUNT: usize>(
+    migrate_in: PoolInstruction<TOKEN_COUNT>,
+    source_pool: &SourcePool,
+    user_source_lp_account: &Pubkey,
+    add_ix: Instruction,
+) -> Result<Instruction, ProgramError> {
+    let token_indices = match migrate_in {
+        PoolInstruction::MigrateIn {
+            source, token_indices, ..
+        } => {
+            if source != source_pool.source {
+                return Err(ProgramError::InvalidArgument);
+            }
+            token_indices
+        }
+        _ => return Err(ProgramError::InvalidArgument),
+    };
+    let user_token_account = |i: u8| &add_ix.accounts[5 + TOKEN_COUNT + i as usize].pubkey;
+    let withdraw_accounts = source_pool.withdraw_accounts(
+        &add_ix.accounts[4 + TOKEN_COUNT].pubkey,
+        user_source_lp_account,
+        [
+            user_token_account(token_indices[0]),
+            user_token_account(token_indices[1]),
+        ],
+    );
+    let mut accounts = add_ix.accounts.clone();
+    accounts.push(AccountMeta::new_readonly(source_pool.source.program_id(), false));
+    accounts.extend(withdraw_accounts);
+
+    Ok(Instruction {
+        program_id: add_ix.program_id,
+        accounts,
+        data: migrate_in.try_to_vec()?,
+    })
+}
+
+/// Creates a `RedeemSunset` instruction that burns all lp tokens of `user_lp_account` for their share of the pool's
+/// balances, paid to `user_token_accounts`. `lp_token_program` is Token-2022 for pools with a non-transferable lp token
+#[allow(clippy::too_many_arguments)]
+pub fn create_redeem_sunset_ix<const TOKEN_COUNT: usize>(
+    program_id: &Pubkey,
+    pool: &Pubkey,
+    authority: &Pubkey,
+    pool_token_accounts: &[Pubkey; TOKEN_COUNT],
+    lp_mint: &Pubkey,
+    lp_token_program: &Pubkey,
+    user_lp_account: &Pubkey,
+    user_authority: &Pubkey,
+    user_token_accounts: &[Pubkey; TOKEN_COUNT],
+) -> Result<Instruction, ProgramError> {
+    let mut accounts = vec![
+        AccountMeta::new(*pool, false),
+        AccountMeta::new_readonly(*authority, false),
+    ];
+    for pool_token_account in pool_token_accounts.iter() {
+        accounts.push(AccountMeta::new(*pool_token_account, false));
+    }
+    accounts.push(AccountMeta::new(*lp_mint, false));
+    accounts.push(AccountMeta::new(*user_lp_account, false));
+    accounts.push(AccountMeta::new_readonly(*user_authority, true));
+    for user_token_account in user_token_accounts.iter() {
+        accounts.push(AccountMeta::new(*user_token_account, false));
+    }
+    accounts.push(AccountMeta::new_readonly(spl_token::id(), false));
+    accounts.push(AccountMeta::new_readonly(*lp_token_program, false));
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data: PoolInstruction::<TOKEN_COUNT>::RedeemSunset {}.try_to_vec()?,
+    })
+}
+
+/// The address of the program's authority over intermediate token accounts and its bump seed
+pub fn find_route_authority_address(program_id: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[ROUTE_SEED], program_id)
+}
+
+/// The address of the token account that holds a user's intermediate tokens during a `RoutedSwap` and its bump seed
+pub fn find_intermediate_address(program_id: &Pubkey, user_transfer_authority: &Pubkey, mint: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[ROUTE_SEED, &user_transfer_authority.to_bytes(), &mint.to_bytes()],
+        program_id,
+    )
+}
+
+/// Creates a `RoutedSwap` instruction
+pub fn create_routed_swap_ix<const TOKEN_COUNT: usize>(
+    routed_swap: PoolInstruction<TOKEN_COUNT>,
+    program_id: &Pubkey,
+    user_transfer_authority: &Pubkey,
+    user_input_token_account: &Pubkey,
+    user_output_token_account: &Pubkey,
+    intermediate_mint: &Pubkey,
+    first_leg: &RouteLeg<TOKEN_COUNT>,
+    second_leg: &RouteLeg<TOKEN_COUNT>,
+) -> Result<Instruction, ProgramError> {
+    if !matches!(routed_swap, PoolInstruction::RoutedSwap { .. }) {
+        return Err(ProgramError::InvalidArgument);
+    }
+    let mut accounts = vec![
+        AccountMeta::new_readonly(*program_id, false),
+        AccountMeta::new(*user_transfer_authority, true),
+        AccountMeta::new(*user_input_token_account, false),
+        AccountMeta::new(*user_output_token_account, false),
+        AccountMeta::new(
+            find_intermediate_address(program_id, user_transfer_authority, intermediate_mint).0,
+            false,
+        ),
+        AccountMeta::new_readonly(find_route_authority_address(program_id).0, false),
+        AccountMeta::new_readonly(*intermediate_mint, false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+        AccountMeta::new_readonly(system_program::id(), false),
+        AccountMeta::new_readonly(sysvar::rent::id(), false),
+    ];
+    first_leg.append_to(&mut accounts);
+    second_leg.append_to(&mut accounts);
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data: routed_swap.try_to_vec()?,
+    })
+}
+
+/// Creates the ed25519 program instruction that verifies `quoter`'s `signature` of `quote`, which its `RfqSwap` has to
+/// directly follow
+pub fn create_rfq_signature_ix(quoter: &Pubkey, signature: &[u8; 64], quote: &RfqQuote) -> Instruction {
+    let message = quote.message();
+    let public_key_offset = ED25519_OFFSETS_START + ED25519_OFFSETS_LEN;
+    let signature_offset = public_key_offset + 32;
+    let message_offset = signature_offset + 64;
+    let mut data = vec![1, 0];
+    for value in [
+        signature_offset as u16,
+        ED25519_CURRENT_INSTRUCTION,
+        public_key_offset as u16,
+        ED25519_CURRENT_INSTRUCTION,
+        message_offset as u16,
+        message.len() as u16,
+        ED25519_CURRENT_INSTRUCTION,
+    ] {
+        data.extend_from_slice(&value.to_le_bytes());
+    }
+    data.extend_from_slice(&quoter.to_bytes());
+    data.extend_from_slice(signature);
+    data.extend_from_slice(&message);
+
+    Instruction {
+        program_id: ed25519_program::id(),
+        accounts: vec![],
+        data,
+    }
+}
+
+/// The public key and message of an ed25519 program instruction that verifies a single signature whose parts are all
+/// within the instruction itself, `None` for any other instruction data
+pub fn parse_ed25519_ix(data: &[u8]) -> Option<(Pubkey, &[u8])> {
+    if data.len() < ED25519_OFFSETS_START + ED25519_OFFSETS_LEN || data[0] != 1 {
+        return None;
+    }
+    let read_u16 = |i: usize| {
+        let offset = ED25519_OFFSETS_START + 2 * i;
+        u16::from_le_bytes([data[offset], data[offset + 1]])
+    };
+    if [read_u16(1), read_u16(3), read_u16(6)]
+        .iter()
+        .any(|index| *index != ED25519_CURRENT_INSTRUCTION)
+    {
+        return None;
+    }
+    let public_key_offset = read_u16(2) as usize;
+    let message_offset = read_u16(4) as usize;
+    let public_key = data.get(public_key_offset..public_key_offset + 32)?;
+    let message = data.get(message_offset..message_offset + read_u16(5) as usize)?;
+    Some((Pubkey::new(public_key), message))
+}
+
+/// Creates an `RfqSwap` instruction of `quote`, `extra_accounts` are the rate accounts of the pool's rated tokens
+/// followed by the user's gate account if the pool gates its users (see `find_user_gate_account`) and the pool's
+/// compliance program and the user's compliance account if it has one (see `find_compliance_address`)
+#[allow(clippy::too_many_arguments)]
+pub fn create_rfq_swap_ix<const TOKEN_COUNT: usize>(
+    program_id: &Pubkey,
+    pool: &Pubkey,
+    authority: &Pubkey,
+    pool_token_accounts: &[Pubkey; TOKEN_COUNT],
+    user_input_token_account: &Pubkey,
+    user_output_token_account: &Pubkey,
+    quote: RfqQuote,
+    extra_accounts: &[Pubkey],
+) -> Result<Instruction, ProgramError> {
+    let mut accounts = vec![
+        AccountMeta::new(*pool, false),
+        AccountMeta::new_readonly(*authority, false),
+    ];
+    for pool_token_account in pool_token_accounts.iter() {
+        accounts.push(AccountMeta::new(*pool_token_account, false));
+    }
+    accounts.push(AccountMeta::new_readonly(quote.user, true));
+    accounts.push(AccountMeta::new(*user_input_token_account, false));
+    accounts.push(AccountMeta::new(*user_output_token_account, false));
+    accounts.push(AccountMeta::new_readonly(spl_token::id(), false));
+    accounts.push(AccountMeta::new_readonly(sysvar::instructions::id(), false));
+    for extra_account in extra_accounts.iter() {
+        accounts.push(AccountMeta::new_readonly(*extra_account, false));
+    }
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data: PoolInstruction::<TOKEN_COUNT>::RfqSwap { quote }.try_to_vec()?,
+    })
+}
+
+/// Creates a `PublishVirtualPrice` instruction
+pub fn create_publish_virtual_price_ix<const TOKEN_COUNT: usize>(
+    program_id: &Pubkey,
+    pool: &Pubkey,
+    lp_mint: &Pubkey,
+    pool_token_accounts: &[Pubkey; TOKEN_COUNT],
+    payer: &Pubkey,
+    rate_accounts: &[Pubkey],
+) -> Result<Instruction, ProgramError> {
+    let mut accounts = vec![
+        AccountMeta::new_readonly(*pool, false),
+        AccountMeta::new(find_virtual_price_address(program_id, pool).0, false),
+        AccountMeta::new_readonly(*lp_mint, false),
+    ];
+    for pool_token_account in pool_token_accounts.iter() {
+        accounts.push(AccountMeta::new_readonly(*pool_token_account, false));
+    }
+    accounts.push(AccountMeta::new(*payer, true));
+    accounts.push(AccountMeta::new_readonly(system_program::id(), false));
+    for rate_account in rate_accounts.iter() {
+        accounts.push(AccountMeta::new_readonly(*rate_account, false));
+    }
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data: PoolInstruction::<TOKEN_COUNT>::PublishVirtualPrice {}.try_to_vec()?,
+    })
+}
+
+/// Pools with a non-transferable lp token (see `PoolState::lp_non_transferable`) mint and burn through the Token-2022
+/// program, which all of their defi instructions expect right after the user LP token account (resp. after the SPL
+/// token program account for swaps)
+///
+/// Executed defi instructions log how they changed the lp supply (see `state::LpSupplyEvent`)
+///
+/// Pools with a compliance program (see `GovernanceInstruction::SetComplianceProgram`) expect it and the user
+/// transfer authority's compliance account (see `find_compliance_address`) right after the gate accounts of their
+/// defi instructions but uniform removes, before any session, launch allocation or hook program accounts. They call
+/// the program with `ComplianceCheckData` before executing the instruction, so an error of the program vetoes it
+#[cfg_attr(feature = "fuzz", derive(Arbitrary))]
+#[derive(Debug, Clone)]
+pub enum DeFiInstruction<const TOKEN_COUNT: usize> {
+    /// Adds/Deposits the specified input_amounts and mints
+    /// at least `minimum_mint_amount` LP tokens
+    ///
+    /// Accounts expected by this instruction:
+    ///     0. `[w]` The pool state account
+    ///     1. `[]` pool authority
+    ///     2. ..2 + TOKEN_COUNT `[w]` pool's token accounts
+    ///     3. ..3 + TOKEN_COUNT `[w]` LP Token Mint
+    ///     4. ..4 + TOKEN_COUNT `[w]` governance_fee_account
+    ///     5. ..5 + TOKEN_COUNT `[s]` user transfer authority account
+    ///     6. ..6 + TOKEN_COUNT `[w]` user token accounts
+    ///     7. ..6 + (2 * TOKEN_COUNT) `[]` SPL token program account
+    ///     8. ..7 + (2 * TOKEN_COUNT) `[w]` user LP token account
+    ///     9. ..8 + (2 * TOKEN_COUNT) `[]` rate accounts of the pool's rated tokens (in pool token order)
+    ///     10. `[]` instructions sysvar (only if the pool restricts its callers, see `SetCallerAllowlist`)
+    ///     11. `[]` gate account of the user transfer authority (only if the pool gates its users, see
+    ///          `GovernanceInstruction::SetUserGate`)
+    ///     12. `[]` depositor gate account of the user transfer authority (only if the pool gates its depositors, see
+    ///          `GovernanceInstruction::SetDepositorGate`)
+    ///     13. `[w]` launch allocation account of the user transfer authority (only while the pool's launch window is
+    ///          open, see `GovernanceInstruction::SetLaunchCap`)
+    Add {
+        input_amounts: [AmountT; TOKEN_COUNT],
+        minimum_mint_amount: AmountT,
+    },
+    /// Swaps in the exact specified amounts for
+    /// at least `minimum_out_amount` of the output_token specified
+    /// by output_token_index
+    ///
+    /// Accounts expected by this instruction:
+    ///     0. `[w]` The pool state account
+    ///     1. `[]` pool authority
+    ///     2. ..2 + TOKEN_COUNT `[w]` pool's token accounts
+    ///     3. ..3 + TOKEN_COUNT `[w]` LP Token Mint
+    ///     4. ..4 + TOKEN_COUNT `[w]` governance_fee_account
+    ///     5. ..5 + TOKEN_COUNT `[s]` user transfer authority account
+    ///     6. ..6 + TOKEN_COUNT `[w]` user token accounts
+    ///     7. ..6 + (2 * TOKEN_COUNT) `[]` SPL token program account
+    ///     8. ..7 + (2 * TOKEN_COUNT) `[]` oracle price accounts (only if the pool is oracle guarded)
+    ///     9. ..7 + (3 * TOKEN_COUNT) `[]` rate accounts of the pool's rated tokens (in pool token order)
+    ///     10. `[]` instructions sysvar (only if the pool restricts its callers, see `SetCallerAllowlist`)
+    ///     11. `[]` gate account of the user transfer authority (only if the pool gates its users, see
+    ///          `GovernanceInstruction::SetUserGate`)
+    ///     12. `[w]` session account of the user transfer authority and 13. `[s]` its session key (only if the user
+    ///          transfer authority doesn't sign, see `PoolInstruction::AuthorizeSession`)
+    ///     14. `[w]` the pool's current rebate campaign and 15. `[w]` the user transfer authority's rebate account with
+    ///          it (optional, records the swap's fees with the campaign, see `PoolInstruction::JoinRebateCampaign`)
+    ///     16. `[]` optional hook program that is called back with the swap's `SwapHookData`, must be allowed by
+    ///          `SetHookPrograms`. The remaining accounts are passed on to it
+    SwapExactInput {
+        exact_input_amounts: [AmountT; TOKEN_COUNT],
+        output_token_index: u8,
+        minimum_output_amount: AmountT,
+    },
+    /// Swaps in at most `maximum_input_amount` of the input token specified by
+    /// `input_token_index` for the exact_output_amounts
+    ///
+    /// Accounts expected by this instruction:
+    ///     0. `[w]` The pool state account
+    ///     1. `[]` pool authority
+    ///     2. ..2 + TOKEN_COUNT `[w]` pool's token accounts
+    ///     3. ..3 + TOKEN_COUNT `[w]` LP Token Mint
+    ///     4. ..4 + TOKEN_COUNT `[w]` governance_fee_account
+    ///     5. ..5 + TOKEN_COUNT `[s]` user transfer authority account
+    ///     6. ..6 + TOKEN_COUNT `[w]` user token accounts
+    ///     7. ..6 + (2 * TOKEN_COUNT) `[]` SPL token program account
+    ///     8. ..7 + (2 * TOKEN_COUNT) `[]` oracle price accounts (only if the pool is oracle guarded)
+    ///     9. ..7 + (3 * TOKEN_COUNT) `[]` rate accounts of the pool's rated tokens (in pool token order)
+    ///     10. `[]` instructions sysvar (only if the pool restricts its callers, see `SetCallerAllowlist`)
+    ///     11. `[]` gate account of the user transfer authority (only if the pool gates its users, see
+    ///          `GovernanceInstruction::SetUserGate`)
+    ///     12. `[w]` session account of the user transfer authority and 13. `[s]` its session key (only if the user
+    ///          transfer authority doesn't sign, see `PoolInstruction::AuthorizeSession`)
+    ///     14. `[w]` the pool's current rebate campaign and 15. `[w]` the user transfer authority's rebate account with
+    ///          it (optional, records the swap's fees with the campaign, see `PoolInstruction::JoinRebateCampaign`)
+    ///     16. `[]` optional hook program that is called back with the swap's `SwapHookData`, must be allowed by
+    ///          `SetHookPrograms`. The remaining accounts are passed on to it
+    SwapExactOutput {
+        maximum_input_amount: AmountT,
+        input_token_index: u8,
+        exact_output_amounts: [AmountT; TOKEN_COUNT],
+    },
+
+    /// Withdraw at least the number of tokens specified by `minimum_output_amounts` by
+    /// burning `exact_burn_amount` of LP tokens
+    /// Final withdrawal amounts are based on current deposit ratios
+    ///
+    ///
+    /// Accounts expected by this instruction:
+    ///     0. `[w]` The pool state account
+    ///     1. `[]` pool authority
+    ///     2. ..2 + TOKEN_COUNT `[w]` pool's token accounts
+    ///     3. ..3 + TOKEN_COUNT `[w]` LP Token Mint
+    ///     4. ..4 + TOKEN_COUNT `[w]` governance_fee_account
+    ///     5. ..5 + TOKEN_COUNT `[s]` user transfer authority account
+    ///     6. ..6 + TOKEN_COUNT `[w]` user token accounts
+    ///     7. ..6 + (2 * TOKEN_COUNT) `[]` SPL token program account
+    ///     8. ..7 + (2 * TOKEN_COUNT) `[w]` user LP token account to withdraw/burn from
+    RemoveUniform {
+        exact_burn_amount: AmountT,
+        minimum_output_amounts: [AmountT; TOKEN_COUNT],
+    },
+    /// Withdraw at least `minimum_output_amount` of output token specified by `output_token_index` by
+    /// burning `exact_burn_amount` of LP tokens
+    /// "WithdrawOne"
+    ///
+    ///
+    /// Accounts expected by this instruction:
+    ///     0. `[w]` The pool state account
+    ///     1. `[]` pool authority
+    ///     2. ..2 + TOKEN_COUNT `[w]` pool's token accounts
+    ///     3. ..3 + TOKEN_COUNT `[w]` LP Token Mint
+    ///     4. ..4 + TOKEN_COUNT `[w]` governance_fee_account
+    ///     5. ..5 + TOKEN_COUNT `[s]` user transfer authority account
+    ///     6. ..6 + TOKEN_COUNT `[w]` user token accounts
+    ///     7. ..6 + (2 * TOKEN_COUNT) `[]` SPL token program account
+    ///     8. ..7 + (2 * TOKEN_COUNT) `[w]` user LP token account to withdraw/burn from
+    ///     9. ..8 + (2 * TOKEN_COUNT) `[]` rate accounts of the pool's rated tokens (in pool token order)
+    ///     10. `[]` instructions sysvar (only if the pool restricts its callers, see `SetCallerAllowlist`)
+    ///     11. `[]` gate account of the user transfer authority (only if the pool gates its users, see
+    ///          `GovernanceInstruction::SetUserGate`)
+    ///     12. `[]` depositor gate account of the user transfer authority (only if the pool gates its depositors, see
+    ///          `GovernanceInstruction::SetDepositorGate`)
+    ///     13. `[w]` withdrawal announcement account of the user transfer authority (only if the pool has a withdrawal
+    ///          cooldown, see `GovernanceInstruction::SetWithdrawalCooldown`)
+    RemoveExactBurn {
+        exact_burn_amount: AmountT,
+        output_token_index: u8,
+        minimum_output_amount: AmountT,
+    },
+    /// Withdraw exactly the number of output tokens specified by `exact_output_amount`
+    /// by burning at most `maximum_burn_amounts` of LP tokens
+    ///
+    /// Accounts expected by this instruction:
+    ///     0. `[w]` The pool state account
+    ///     1. `[]` pool authority
+    ///     2. ..2 + TOKEN_COUNT `[w]` pool's token accounts
+    ///     3. ..3 + TOKEN_COUNT `[w]` LP Token Mint
+    ///     4. ..4 + TOKEN_COUNT `[w]` governance_fee_account
+    ///     5. ..5 + TOKEN_COUNT `[s]` user transfer authority account
+    ///     6. ..6 + TOKEN_COUNT `[w]` user token accounts
+    ///     7. ..6 + (2 * TOKEN_COUNT) `[]` SPL token program account
+    ///     8. ..7 + (2 * TOKEN_COUNT) `[w]` user LP token account to withdraw/burn from
+    ///     9. ..8 + (2 * TOKEN_COUNT) `[]` rate accounts of the pool's rated tokens (in pool token order)
+    ///     10. `[]` instructions sysvar (only if the pool restricts its callers, see `SetCallerAllowlist`)
+    ///     11. `[]` gate account of the user transfer authority (only if the pool gates its users, see
+    ///          `GovernanceInstruction::SetUserGate`)
+    ///     12. `[]` depositor gate account of the user transfer authority (only if the pool gates its depositors, see
+    ///          `GovernanceInstruction::SetDepositorGate`)
+    ///     13. `[w]` withdrawal announcement account of the user transfer authority (only if the pool has a withdrawal
+    ///          cooldown, see `GovernanceInstruction::SetWithdrawalCooldown`)
+    RemoveExactOutput {
+        maximum_burn_amount: AmountT,
+        exact_output_amounts: [AmountT; TOKEN_COUNT],
+    },
+    /// Runs the validation and math of the wrapped instruction without moving any tokens or updating the pool
+    /// state, the outcome is returned Borsh encoded as `SimulationResult` via return data
+    /// Lets on-chain callers check their leg of a route before committing to it.
+    ///
+    /// Accounts expected by this instruction: those of the wrapped instruction, the user transfer authority
+    ///  doesn't have to sign
+    Simulate(Box<DeFiInstruction<TOKEN_COUNT>>),
+}
+
+impl<const TOKEN_COUNT: usize> DeFiInstruction<TOKEN_COUNT> {
+    /// The instruction whose accounts are expected, i.e. the wrapped one of a `Simulate`
+    pub fn executed(&self) -> &Self {
+        match self {
+            DeFiInstruction::Simulate(defi_instruction) => defi_instruction.executed(),
+            _ => self,
+        }
+    }
+
+    pub fn is_swap(&self) -> bool {
+        matches!(
+            self.executed(),
+            DeFiInstruction::SwapExactInput { .. } | DeFiInstruction::SwapExactOutput { .. }
+        )
+    }
+
+    pub fn is_uniform_remove(&self) -> bool {
+        matches!(self.executed(), DeFiInstruction::RemoveUniform { .. })
+    }
+
+    pub fn is_remove(&self) -> bool {
+        matches!(
+            self.executed(),
+            DeFiInstruction::RemoveUniform { .. }
+                | DeFiInstruction::RemoveExactBurn { .. }
+                | DeFiInstruction::RemoveExactOutput { .. }
+        )
+    }
+
+    /// Which tokens the instruction takes from resp. gives to the user: those with a nonzero amount and the chosen
+    /// token of swaps and single token removes, uniform removes give all of them
+    pub fn token_directions(&self) -> ([bool; TOKEN_COUNT], [bool; TOKEN_COUNT]) {
+        let nonzero = |amounts: &[AmountT; TOKEN_COUNT]| {
+            let mut tokens = [false; TOKEN_COUNT];
+            for (token, amount) in tokens.iter_mut().zip(amounts.iter()) {
+                *token = *amount > 0;
+            }
+            tokens
+        };
+        let single = |index: u8| {
+            let mut tokens = [false; TOKEN_COUNT];
+            if let Some(token) = tokens.get_mut(index as usize) {
+                *token = true;
+            }
+            tokens
+        };
+        match self.executed() {
+            DeFiInstruction::Add { input_amounts, .. } => (nonzero(input_amounts), [false; TOKEN_COUNT]),
+            DeFiInstruction::SwapExactInput {
+                exact_input_amounts,
+                output_token_index,
+                ..
+            } => (nonzero(exact_input_amounts), single(*output_token_index)),
+            DeFiInstruction::SwapExactOutput {
+                input_token_index,
+                exact_output_amounts,
+                ..
+            } => (single(*input_token_index), nonzero(exact_output_amounts)),
+            DeFiInstruction::RemoveUniform { .. } => ([false; TOKEN_COUNT], [true; TOKEN_COUNT]),
+            DeFiInstruction::RemoveExactBurn { output_token_index, .. } => {
+                ([false; TOKEN_COUNT], single(*output_token_index))
+            }
+            DeFiInstruction::RemoveExactOutput {
+                exact_output_amounts, ..
+            } => ([false; TOKEN_COUNT], nonzero(exact_output_amounts)),
+            //executed() unwraps simulations
+            DeFiInstruction::Simulate(_) => ([false; TOKEN_COUNT], [false; TOKEN_COUNT]),
+        }
+    }
+}
+
+//the Borsh impls are written out since the derived ones can't handle the recursion of Simulate
+impl<const TOKEN_COUNT: usize> BorshSerialize for DeFiInstruction<TOKEN_COUNT> {
+    fn serialize<W: io::Write>(&self, writer: &mut W) -> io::Result<()> {
+        match self {
+            DeFiInstruction::Add {
+                input_amounts,
+                minimum_mint_amount,
+            } => {
+                0u8.serialize(writer)?;
+                input_amounts.serialize(writer)?;
+                minimum_mint_amount.serialize(writer)
+            }
+            DeFiInstruction::SwapExactInput {
+                exact_input_amounts,
+                output_token_index,
+                minimum_output_amount,
+            } => {
+                1u8.serialize(writer)?;
+                exact_input_amounts.serialize(writer)?;
+                output_token_index.serialize(writer)?;
+                minimum_output_amount.serialize(writer)
+            }
+            DeFiInstruction::SwapExactOutput {
+                maximum_input_amount,
+                input_token_index,
+                exact_output_amounts,
+            } => {
+                2u8.serialize(writer)?;
+                maximum_input_amount.serialize(writer)?;
+                input_token_index.serialize(writer)?;
+                exact_output_amounts.serialize(writer)
+            }
+            DeFiInstruction::RemoveUniform {
+                exact_burn_amount,
+                minimum_output_amounts,
+            } => {
+                3u8.serialize(writer)?;
+                exact_burn_amount.serialize(writer)?;
+                minimum_output_amounts.serialize(writer)
+            }
+            DeFiInstruction::RemoveExactBurn {
+                exact_burn_amount,
+                output_token_index,
+                minimum_output_amount,
+            } => {
+                4u8.serialize(writer)?;
+                exact_burn_amount.serialize(writer)?;
+                output_token_index.serialize(writer)?;
+                minimum_output_amount.serialize(writer)
+            }
+            DeFiInstruction::RemoveExactOutput {
+                maximum_burn_amount,
+                exact_output_amounts,
+            } => {
+                5u8.serialize(writer)?;
+                maximum_burn_amount.serialize(writer)?;
+                exact_output_amounts.serialize(writer)
+            }
+            DeFiInstruction::Simulate(defi_instruction) => {
+                6u8.serialize(writer)?;
+                defi_instruction.serialize(writer)
+            }
+        }
+    }
+}
+
+impl<const TOKEN_COUNT: usize> BorshDeserialize for DeFiInstruction<TOKEN_COUNT> {
+    fn deserialize(buf: &mut &[u8]) -> io::Result<Self> {
+        Ok(match u8::deserialize(buf)? {
+            0 => DeFiInstruction::Add {
+                input_amounts: BorshDeserialize::deserialize(buf)?,
+                minimum_mint_amount: BorshDeserialize::deserialize(buf)?,
+            },
+            1 => DeFiInstruction::SwapExactInput {
+                exact_input_amounts: BorshDeserialize::deserialize(buf)?,
+                output_token_index: BorshDeserialize::deserialize(buf)?,
+                minimum_output_amount: BorshDeserialize::deserialize(buf)?,
+            },
+            2 => DeFiInstruction::SwapExactOutput {
+                maximum_input_amount: BorshDeserialize::deserialize(buf)?,
+                input_token_index: BorshDeserialize::deserialize(buf)?,
+                exact_output_amounts: BorshDeserialize::deserialize(buf)?,
+            },
+            3 => DeFiInstruction::RemoveUniform {
+                exact_burn_amount: BorshDeserialize::deserialize(buf)?,
+                minimum_output_amounts: BorshDeserialize::deserialize(buf)?,
+            },
+            4 => DeFiInstruction::RemoveExactBurn {
+                exact_burn_amount: BorshDeserialize::deserialize(buf)?,
+                output_token_index: BorshDeserialize::deserialize(buf)?,
+                minimum_output_amount: BorshDeserialize::deserialize(buf)?,
+            },
+            5 => DeFiInstruction::RemoveExactOutput {
+                maximum_burn_amount: BorshDeserialize::deserialize(buf)?,
+                exact_output_amounts: BorshDeserialize::deserialize(buf)?,
+            },
+            6 => DeFiInstruction::Simulate(Box::new(Self::deserialize(buf)?)),
+            variant => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("unexpected DeFiInstruction variant: {}", variant),
+                ))
+            }
+        })
+    }
+}
+
+/// The instruction data (Borsh encoded) that a swap calls its hook program with, after the pool's state and all
+/// transfers have been committed. The hook is called without any of the pool's privileges and gets no signatures: the
+/// accounts passed on to it are never signers, even those that signed the swap
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SwapHookData<const TOKEN_COUNT: usize> {
+    pub pool: Pubkey,
+    /// Tokens the user paid
+    pub input_amounts: [AmountT; TOKEN_COUNT],
+    /// Tokens the user received
+    pub output_amounts: [AmountT; TOKEN_COUNT],
+}
+
+/// The instruction data (Borsh encoded) that a defi instruction calls the pool's compliance program with, followed by
+/// the accounts of the pool, the user transfer authority and its compliance account. The program vetoes the
+/// instruction by failing, it is called without any of the pool's privileges. An `RfqSwap` is checked as the
+/// `SwapExactInput` of the quote's input amount with the quoted output amount as its minimum output
+#[derive(BorshSerialize, BorshDeserialize, Debug)]
+pub struct ComplianceCheckData<const TOKEN_COUNT: usize> {
+    pub pool: Pubkey,
+    pub user: Pubkey,
+    /// The instruction the user is about to execute (or to simulate)
+    pub instruction: DeFiInstruction<TOKEN_COUNT>,
+}
+
+/// The instruction data (Borsh encoded) that `StakeLp` and `UpdateGaugeBoost` call the gauge's voting escrow program
+/// (see `GovernanceInstruction::SetGaugeVeProgram`) with, followed by the accounts of the pool, the stake's owner and
+/// its ve account (see `find_ve_boost_address`). The program sets the boost of the owner's stake of `amount` lp tokens,
+/// in basis points of the amount and Borsh encoded as a u64, as return data, which the pool clamps between no boost
+/// and `state::MAX_GAUGE_BOOST_BPS`. It is called without any of the pool's privileges
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VeBoostQuery {
+    pub pool: Pubkey,
+    pub owner: Pubkey,
+    /// The stake's amount
+    pub amount: AmountT,
+}
+
+/// The instruction data (Borsh encoded) that `DeployYield` and `RecallYield` call a token's yield adapter (see
+/// `GovernanceInstruction::SetYieldAdapter`) with, followed by the accounts of the pool, the adapter's yield delegate
+/// (see `find_yield_delegate_address`), the pool's token account of the token (writable), the token program and
+/// whatever accounts the adapter needs. The adapter is called without any of the pool's privileges, or the signatures
+/// of the accounts it needs. Either sets the value of the pool's position with the adapter, in the token and Borsh
+/// encoded as a u64, as return data
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum YieldAdapterInstruction {
+    /// Transfers `amount` from the pool's token account into the market, signing for the yield delegate, which the
+    /// pool approved for exactly the amount
+    Deposit { amount: AmountT },
+    /// Transfers `amount` out of the market back into the pool's token account
+    Withdraw { amount: AmountT },
+}
+
+/// The return data of a `Simulate`d DeFiInstruction
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SimulationResult<const TOKEN_COUNT: usize> {
+    /// Lp tokens that would be minted (add) or burned (removes)
+    pub lp_amount: AmountT,
+    /// Tokens the user would pay (add, swaps)
+    pub input_amounts: [AmountT; TOKEN_COUNT],
+    /// Tokens the user would receive (swaps, removes)
+    pub output_amounts: [AmountT; TOKEN_COUNT],
+    /// Lp tokens that would be minted to the governance fee account
+    pub governance_mint_amount: AmountT,
+}
+
+/// Anchor's instruction discriminators (the first 8 bytes of sha256("global:<instruction name>")) of the
+///  DeFiInstructions, in the order of their variants
+///
+/// None of them starts with a PoolInstruction variant index, so both encodings can be told apart by the first byte
+#[cfg(feature = "anchor-discriminators")]
+pub const ANCHOR_DEFI_DISCRIMINATORS: [(&str, [u8; 8]); 6] = [
+    ("add", [41, 249, 249, 146, 197, 111, 56, 181]),
+    ("swap_exact_input", [194, 203, 142, 150, 137, 110, 81, 94]),
+    ("swap_exact_output", [45, 99, 76, 242, 223, 112, 168, 162]),
+    ("remove_uniform", [43, 142, 34, 92, 207, 166, 7, 103]),
+    ("remove_exact_burn", [86, 126, 165, 66, 213, 166, 195, 92]),
+    ("remove_exact_output", [191, 122, 82, 167, 138, 96, 230, 254]),
+];
+
+#[cfg(feature = "anchor-discriminators")]
+impl<const TOKEN_COUNT: usize> DeFiInstruction<TOKEN_COUNT> {
+    /// Encodes the instruction like Anchor does: the discriminator followed by the Borsh encoded fields
+    pub fn try_to_anchor_vec(&self) -> Result<Vec<u8>, ProgramError> {
+        let borsh_data = self.try_to_vec()?;
+        //simulations can't be encoded this way
+        let mut data = ANCHOR_DEFI_DISCRIMINATORS
+            .get(borsh_data[0] as usize)
+            .ok_or(ProgramError::InvalidInstructionData)?
+            .1
+            .to_vec();
+        data.extend_from_slice(&borsh_data[1..]);
+        Ok(data)
+    }
+
+    /// Decodes Anchor encoded instruction data, returns None if it doesn't start with one of our discriminators
+    pub fn try_from_anchor_slice(data: &[u8]) -> Result<Option<Self>, ProgramError> {
+        if data.len() < 8 {
+            return Ok(None);
+        }
+        let variant = match ANCHOR_DEFI_DISCRIMINATORS
+            .iter()
+            .position(|(_, discriminator)| *discriminator == data[..8])
+        {
+            Some(variant) => variant,
+            None => return Ok(None),
+        };
+        let mut borsh_data = vec![variant as u8];
+        borsh_data.extend_from_slice(&data[8..]);
+        Ok(Some(Self::try_from_slice(&borsh_data)?))
+    }
+}
+
+pub fn create_defi_ix<const TOKEN_COUNT: usize>(
+    defi_instruction: DeFiInstruction<TOKEN_COUNT>,
+    program_id: &Pubkey,
+    pool: &Pubkey,
+    authority: &Pubkey,
+    pool_token_accounts: &[Pubkey; TOKEN_COUNT],
+    lp_mint: &Pubkey,
+    governance_fee_account: &Pubkey,
+    user_transfer_authority: &Pubkey,
+    user_token_accounts: &[Pubkey; TOKEN_COUNT],
+    token_program_account: &Pubkey,
+    user_lp_token_account: Option<&Pubkey>,
+) -> Result<Instruction, ProgramError> {
+    let mut accounts = vec![
+        AccountMeta::new(*pool, false),
+        AccountMeta::new_readonly(*authority, false),
+    ];
+    for i in 0..TOKEN_COUNT {
+        accounts.push(AccountMeta::new(pool_token_accounts[i], false));
+    }
+    accounts.push(AccountMeta::new(*lp_mint, false));
+    accounts.push(AccountMeta::new(*governance_fee_account, false));
+
+    // used from SPL binary-oracle-pair. not actually necessary since the implementation only supports
+    //  that using a separate keypair
+    accounts.push(AccountMeta::new_readonly(
         *user_transfer_authority,
         authority != user_transfer_authority,
     ));
@@ -223,157 +2784,638 @@ pub fn create_defi_ix<const TOKEN_COUNT: usize>(
         accounts.push(AccountMeta::new(user_token_accounts[i], false));
     }
     accounts.push(AccountMeta::new_readonly(*token_program_account, false));
-    match defi_instruction {
-        DeFiInstruction::Add { .. } => {
-            accounts.push(AccountMeta::new(*user_lp_token_account.unwrap(), false));
-        }
-        DeFiInstruction::RemoveUniform { .. } => {
-            accounts.push(AccountMeta::new(*user_lp_token_account.unwrap(), false));
-        }
-        DeFiInstruction::RemoveExactBurn { .. } => {
-            accounts.push(AccountMeta::new(*user_lp_token_account.unwrap(), false));
-        }
-        DeFiInstruction::RemoveExactOutput { .. } => {
-            accounts.push(AccountMeta::new(*user_lp_token_account.unwrap(), false));
-        }
-        _ => {
-            assert!(user_lp_token_account.is_none());
-        }
+    //swaps take no lp token account, all other instructions need one
+    match (defi_instruction.is_swap(), user_lp_token_account) {
+        (false, Some(user_lp_token_account)) => accounts.push(AccountMeta::new(*user_lp_token_account, false)),
+        (true, None) => (),
+        _ => return Err(ProgramError::InvalidArgument),
+    }
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data: PoolInstruction::DeFiInstruction(defi_instruction).try_to_vec()?,
+    })
+}
+
+/// Creates a `SwapExactOutput` DefiInstruction
+/// Swaps in at most `maximum_input_amount` of the input token specified by
+/// `input_token_index` for the exact_output_amounts
+///
+/// Accounts expected by this instruction:
+///     0. `[w]` The pool state account
+///     1. `[]` pool authority
+///     2. ..2 + TOKEN_COUNT `[w]` pool's token accounts
+///     3. ..3 + TOKEN_COUNT `[w]` LP Token Mint
+///     4. ..4 + TOKEN_COUNT `[w]` governance_fee_account
+///     5. ..5 + TOKEN_COUNT `[s]` user transfer authority account
+///     6. ..6 + TOKEN_COUNT `[w]` user token accounts
+///     7. ..6 + (2 * TOKEN_COUNT) `[]` SPL token program account
+pub fn create_swap_exact_output_ix<const TOKEN_COUNT: usize>(
+    program_id: &Pubkey,
+    pool: &Pubkey,
+    authority: &Pubkey,
+    pool_token_accounts: [Pubkey; TOKEN_COUNT],
+    lp_mint: &Pubkey,
+    governance_fee_account: &Pubkey,
+    user_transfer_authority: &Pubkey,
+    user_token_accounts: [Pubkey; TOKEN_COUNT],
+    token_program_account: &Pubkey,
+    maximum_input_amount: AmountT,
+    input_token_index: u8,
+    exact_output_amounts: [AmountT; TOKEN_COUNT],
+) -> Result<Instruction, ProgramError> {
+    let mut accounts = vec![
+        AccountMeta::new_readonly(*pool, false),
+        AccountMeta::new_readonly(*authority, false),
+    ];
+    for i in 0..TOKEN_COUNT {
+        accounts.push(AccountMeta::new(pool_token_accounts[i], false));
+    }
+    accounts.push(AccountMeta::new(*lp_mint, false));
+    accounts.push(AccountMeta::new(*governance_fee_account, false));
+
+    // used from SPL binary-oracle-pair. not actually necessary since the implementation only supports
+    //  that using a separate keypair
+    accounts.push(AccountMeta::new_readonly(
+        *user_transfer_authority,
+        authority != user_transfer_authority,
+    ));
+    for i in 0..TOKEN_COUNT {
+        accounts.push(AccountMeta::new(user_token_accounts[i], false));
     }
+    accounts.push(AccountMeta::new_readonly(*token_program_account, false));
+
+    let d = DeFiInstruction::<TOKEN_COUNT>::SwapExactOutput {
+        maximum_input_amount,
+        input_token_index,
+        exact_output_amounts,
+    };
+
+    let data = PoolInstruction::<TOKEN_COUNT>::DeFiInstruction(d).try_to_vec()?;
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Debug)]
+pub enum GovernanceInstruction<const TOKEN_COUNT: usize> {
+    /// Sets the lp_fee and governance_fee values that the pool
+    /// will transition to
+    ///
+    /// Accounts expected by this instruction:
+    ///     0. `[w]` The pool state account
+    ///     1. `[s]` Pool Governance Account or fee admin (see `SetRole`)
+    PrepareFeeChange { lp_fee: DecT, governance_fee: DecT },
+
+    /// Sets the `pool.lp_fee` and `pool.governance_fee` using the
+    /// values from `pool.prepared_lp_fee` and `pool.prepared_governance_fee`
+    ///
+    ///
+    /// Accounts expected by this instruction:
+    ///     0. `[w]` The pool state account
+    ///     1. `[s]` Pool Governance Account or fee admin (see `SetRole`)
+    EnactFeeChange {},
+
+    /// Sets the governance account that the pool
+    /// will transition to
+    ///
+    ///
+    /// Accounts expected by this instruction:
+    ///     0. `[w]` The pool state account
+    ///     1. `[s]` Pool Governance Account
+    PrepareGovernanceTransition { upcoming_governance_key: Pubkey },
+
+    /// Applies the prepared governance account as the
+    /// current governance account
+    ///
+    ///
+    /// Accounts expected by this instruction:
+    ///     0. `[w]` The pool state account
+    ///     1. `[s]` Pool Governance Account
+    EnactGovernanceTransition {},
+
+    /// Switches the governance fee account
+    ///
+    ///
+    /// Accounts expected by this instruction:
+    ///     0. `[w]` The pool state account
+    ///     1. `[s]` Pool Governance Account
+    ///     2. `[]`  New Governance Fee account
+    ChangeGovernanceFeeAccount { governance_fee_key: Pubkey },
+
+    /// Adjusts the amp factor for the pool
+    ///
+    ///
+    /// Accounts expected by this instruction:
+    ///     0. `[w]` The pool state account
+    ///     1. `[s]` Pool Governance Account or amp admin (see `SetRole`)
+    AdjustAmpFactor {
+        target_ts: UnixTimestamp,
+        target_value: DecT,
+    },
+
+    /// Pause/Unpauses the pool
+    ///
+    ///
+    /// Accounts expected by this instruction:
+    ///     0. `[w]` The pool state account
+    ///     1. `[s]` Pool Governance Account or pause admin (see `SetRole`)
+    SetPaused { paused: bool },
+
+    /// Checks swaps against the tokens' oracles (see `SetTokenOracle`, which binds them after the enact delay) and
+    /// rejects those whose execution price deviates from the oracle cross-rate by more than `max_deviation`, 0 turns
+    /// the check off. Fails with `InvalidOracleAccount` unless every token has an oracle
+    ///
+    /// Accounts expected by this instruction:
+    ///     0. `[w]` The pool state account
+    ///     1. `[s]` Pool Governance Account
+    SetOracleGuard { max_deviation: DecT },
+
+    /// Sets how far (relative to 1) the oracle price of a token may be off before `CheckPeg` pauses its swaps, 0 turns
+    /// the automatic pause off. Also resumes swaps of all tokens that were paused by `CheckPeg`
+    ///
+    /// Accounts expected by this instruction:
+    ///     0. `[w]` The pool state account
+    ///     1. `[s]` Pool Governance Account
+    SetPegBand { band: DecT },
+
+    /// Sets where the exchange rate of the token at `token_index` comes from (`RateSource::None` for a token that tracks
+    /// the pool's asset 1:1). The pool values its balances at the new rate immediately, so this fails with
+    /// `PoolNotPaused` unless the pool is paused or empty
+    ///
+    /// Accounts expected by this instruction:
+    ///     0. `[w]` The pool state account
+    ///     1. `[s]` Pool Governance Account
+    ///     2. `[]` Rate account (only if the source has one, see `RateSource::has_account`)
+    ///     3. `[]` The base pool's state account (only for `RateSource::BasePool`, whose rate account is the base pool's
+    ///        virtual price account, see `create_set_base_pool_rate_ix`)
+    SetRateSource {
+        token_index: u8,
+        source: RateSource,
+        rate_key: Pubkey,
+    },
+
+    /// Sets what guarded swaps do while a guard oracle is unavailable, i.e. outside of its token's limits (which
+    /// `SetTokenOracle` sets along with the oracle). Rated instructions always fail while a rate is unavailable
+    ///
+    /// Accounts expected by this instruction:
+    ///     0. `[w]` The pool state account
+    ///     1. `[s]` Pool Governance Account
+    SetOracleFallback { fallback: OracleFallback },
+
+    /// Sets the guard oracle (`Pubkey::default()` to remove it) and the oracle limits (see `OracleLimits`) that the
+    /// token at `token_index` will transition to, the limits apply to the token's rate feed as well. The oracle account
+    /// has to be a price account of `oracle_kind`'s program and the limits need a max age and a confidence bound of at
+    /// most 10000 basis points (`InvalidOracleLimits` otherwise). Swaps are checked against the oracles once every
+    /// token has one and `SetOracleGuard` set a max deviation
+    ///
+    /// Accounts expected by this instruction:
+    ///     0. `[w]` The pool state account
+    ///     1. `[s]` Pool Governance Account
+    ///     2. `[]` Oracle price account (unless removing the oracle)
+    SetTokenOracle {
+        token_index: u8,
+        oracle_kind: OracleKind,
+        oracle_key: Pubkey,
+        max_age: Slot,
+        max_conf_bps: u16,
+    },
+
+    /// Has the pool scale its amp factor by `factor` while an oracle price has been further than `trigger_band`
+    /// (relative to 1) off for `delay` seconds, until all prices are back within `release_band` (see
+    /// `amp_factor::AmpDamping`), a factor of 1 turns this off. Requires every token to have an oracle, the damping
+    /// state is advanced by `CheckPeg`
+    ///
+    /// Accounts expected by this instruction:
+    ///     0. `[w]` The pool state account
+    ///     1. `[s]` Pool Governance Account
+    SetAmpDamping {
+        factor: DecT,
+        trigger_band: DecT,
+        release_band: DecT,
+        delay: UnixTimestamp,
+    },
+
+    /// Applies the oracle prepared by `SetTokenOracle` to its token
+    ///
+    ///
+    /// Accounts expected by this instruction:
+    ///     0. `[w]` The pool state account
+    ///     1. `[s]` Pool Governance Account
+    EnactTokenOracle {},
+
+    /// Sets the programs that swaps may call back once they executed (see `SwapHookData`), unused slots are
+    /// Pubkey::default()
+    ///
+    /// Accounts expected by this instruction:
+    ///     0. `[w]` The pool state account
+    ///     1. `[s]` Pool Governance Account
+    SetHookPrograms { hook_programs: [Pubkey; MAX_HOOK_PROGRAMS] },
+
+    /// Sets who may call the pool's defi instructions (see `CallerMode`) and the programs allowed to call them via
+    /// cpi, unused slots are Pubkey::default()
+    ///
+    /// Accounts expected by this instruction:
+    ///     0. `[w]` The pool state account
+    ///     1. `[s]` Pool Governance Account
+    SetCallerAllowlist {
+        caller_mode: CallerMode,
+        allowed_callers: [Pubkey; MAX_ALLOWED_CALLERS],
+    },
+
+    /// Winds the pool down: adds and swaps stop right away, removes until `deadline`, after which lp token holders
+    /// redeem their share of the remaining balances with `PoolInstruction::RedeemSunset`. The deadline has to be at
+    /// least the enact delay away, 0 calls the sunset off (only before its deadline)
+    ///
+    /// Accounts expected by this instruction:
+    ///     0. `[w]` The pool state account
+    ///     1. `[s]` Pool Governance Account
+    SetSunset { deadline: UnixTimestamp },
+
+    /// Gives up governance for good: the governance key becomes `Pubkey::default()`, which no governance instruction
+    /// accepts, so fees, oracles and all other parameters stay as they are. Prepared changes are dropped, an amp
+    /// factor adjustment in progress runs to its target unless `lock_amp_factor` stops it at its current value and
+    /// delegated roles are revoked. Fails while the pool is paused or being wound down, which only governance could end
+    ///
+    /// Accounts expected by this instruction:
+    ///     0. `[w]` The pool state account
+    ///     1. `[s]` Pool Governance Account
+    RenounceGovernance { lock_amp_factor: bool },
+
+    /// Switches the governance fee account like `ChangeGovernanceFeeAccount` and moves the balance of the current one
+    /// over to it in the same instruction, which requires the current account to be owned by governance. The new
+    /// account can't have a delegate or close authority or be frozen. A current account that doesn't hold lp tokens
+    /// of the pool's lp mint (anymore), e.g. one of the mint the lp token was reissued from, is left alone. Not for
+    /// pools with a non-transferable lp token, use `ChangeGovernanceFeeAccount` instead
+    ///
+    /// Accounts expected by this instruction:
+    ///     0. `[w]` The pool state account
+    ///     1. `[s]` Pool Governance Account
+    ///     2. `[w]` Current Governance Fee account
+    ///     3. `[w]` New Governance Fee account
+    ///     4. `[]` SPL token program account
+    MigrateGovernanceFeeAccount { governance_fee_key: Pubkey },
+
+    /// Sets the share of the governance fee that goes to the pool's lp token locks (see `PoolInstruction::LockLp`),
+    /// effective for the fees of later instructions. The share is taken from the governance fee, not from the lp fee
+    ///
+    /// Accounts expected by this instruction:
+    ///     0. `[w]` The pool state account
+    ///     1. `[s]` Pool Governance Account
+    SetLockFeeShare { share: DecT },
+
+    /// Sets the least lp tokens that an add has to mint and that a remove has to leave in the user's lp token account,
+    /// unless it removes everything, so the pool doesn't collect dust positions. Both fail with `DustPosition`
+    /// otherwise, 0 turns a minimum off. Lp tokens minted as governance fee aren't subject to it
+    ///
+    /// Accounts expected by this instruction:
+    ///     0. `[w]` The pool state account
+    ///     1. `[s]` Pool Governance Account
+    SetMinLpPosition {
+        min_mint_amount: AmountT,
+        min_residual_amount: AmountT,
+    },
+
+    /// Burns `burn_amount` of the lp tokens in the governance fee account, which the governance has to own. Rather
+    /// than being paid out, the burned governance fees stay in the pool, raising the depth per remaining lp token.
+    /// Logs an `LpBurnEvent` with the resulting virtual price
+    ///
+    /// Accounts expected by this instruction:
+    ///     0. `[w]` The pool state account
+    ///     1. `[s]` Pool Governance Account
+    ///     2. `[w]` Governance Fee account
+    ///     3. `[w]` LP Token Mint account
+    ///     4. `[]` Lp token program account (Token-2022 if the pool's lp token is non-transferable)
+    BurnGovernanceFees { burn_amount: AmountT },
+
+    /// Opens a launch window until `end_ts` during which adds may mint at most `max_lp_per_wallet` lp tokens to each
+    /// wallet in total (counted in the wallet's `state::LaunchAllocation`, see `PoolInstruction::CreateLaunchAllocation`)
+    /// and fail with `LaunchCapExceeded` otherwise. The cap lifts by itself once `end_ts` passed, a cap of 0 lifts it
+    /// right away
+    ///
+    /// Accounts expected by this instruction:
+    ///     0. `[w]` The pool state account
+    ///     1. `[s]` Pool Governance Account
+    SetLaunchCap {
+        max_lp_per_wallet: AmountT,
+        end_ts: UnixTimestamp,
+    },
+
+    /// Sets which users may call the pool's defi instructions (see `state::UserGate`), who then fail with
+    /// `UserNotAllowed` unless they pass their gate account (see `find_user_gate_account`). Uniform removes stay open
+    /// to everyone. A gated pool can only be the first leg of a `PoolInstruction::RoutedSwap`, since the second one
+    /// swaps on behalf of the route authority
+    ///
+    /// Accounts expected by this instruction:
+    ///     0. `[w]` The pool state account
+    ///     1. `[s]` Pool Governance Account
+    SetUserGate { user_gate: UserGate },
 
-    Ok(Instruction {
-        program_id: *program_id,
-        accounts,
-        data: PoolInstruction::DeFiInstruction(defi_instruction).try_to_vec()?,
-    })
-}
+    /// Allows `user` while the pool's user gate is `UserGate::Allowlist` by creating its allowed user account (see
+    /// `state::AllowedUser`), or disallows it by closing the account and refunding its rent to the payer. Does nothing
+    /// if the user already is allowed resp. disallowed
+    ///
+    /// Accounts expected by this instruction:
+    ///     0. `[w]` The pool state account
+    ///     1. `[s]` Pool Governance Account
+    ///     2. `[w]` Allowed user account (see `find_allowed_user_address`)
+    ///     3. `[ws]` Payer
+    ///     4. `[]` System program
+    SetUserAllowed { user: Pubkey, allowed: bool },
 
-/// Creates a `SwapExactOutput` DefiInstruction
-/// Swaps in at most `maximum_input_amount` of the input token specified by
-/// `input_token_index` for the exact_output_amounts
-///
-/// Accounts expected by this instruction:
-///     0. `[w]` The pool state account
-///     1. `[]` pool authority
-///     2. ..2 + TOKEN_COUNT `[w]` pool's token accounts
-///     3. ..3 + TOKEN_COUNT `[w]` LP Token Mint
-///     4. ..4 + TOKEN_COUNT `[w]` governance_fee_account
-///     5. ..5 + TOKEN_COUNT `[s]` user transfer authority account
-///     6. ..6 + TOKEN_COUNT `[w]` user token accounts
-///     7. ..6 + (2 * TOKEN_COUNT) `[]` SPL token program account
-pub fn create_swap_exact_output_ix<const TOKEN_COUNT: usize>(
-    program_id: &Pubkey,
-    pool: &Pubkey,
-    authority: &Pubkey,
-    pool_token_accounts: [Pubkey; TOKEN_COUNT],
-    lp_mint: &Pubkey,
-    governance_fee_account: &Pubkey,
-    user_transfer_authority: &Pubkey,
-    user_token_accounts: [Pubkey; TOKEN_COUNT],
-    token_program_account: &Pubkey,
-    maximum_input_amount: AmountT,
-    input_token_index: u8,
-    exact_output_amounts: [AmountT; TOKEN_COUNT],
-) -> Result<Instruction, ProgramError> {
-    let mut accounts = vec![
-        AccountMeta::new_readonly(*pool, false),
-        AccountMeta::new_readonly(*authority, false),
-    ];
-    for i in 0..TOKEN_COUNT {
-        accounts.push(AccountMeta::new(pool_token_accounts[i], false));
-    }
-    accounts.push(AccountMeta::new(*lp_mint, false));
-    accounts.push(AccountMeta::new(*governance_fee_account, false));
+    /// Delegates `role` to `key`, which may then sign the role's instructions in place of the governance (see
+    /// `state::Role`), `Pubkey::default()` revokes it. Replaces the role's previous key, renouncing governance
+    /// revokes all roles
+    ///
+    /// Accounts expected by this instruction:
+    ///     0. `[w]` The pool state account
+    ///     1. `[s]` Pool Governance Account
+    SetRole { role: Role, key: Pubkey },
 
-    // used from SPL binary-oracle-pair. not actually necessary since the implementation only supports
-    //  that using a separate keypair 
-    accounts.push(AccountMeta::new_readonly(
-        *user_transfer_authority,
-        authority != user_transfer_authority,
-    ));
-    for i in 0..TOKEN_COUNT {
-        accounts.push(AccountMeta::new(user_token_accounts[i], false));
-    }
-    accounts.push(AccountMeta::new_readonly(*token_program_account, false));
+    /// Disables (or enables again) the token at `token_index` as an input and/or output of defi instructions (see
+    /// `state::TokenFlags`), which then fail with `TokenInputDisabled` resp. `TokenOutputDisabled`. Uniform removes
+    /// always give all tokens
+    ///
+    /// Accounts expected by this instruction:
+    ///     0. `[w]` The pool state account
+    ///     1. `[s]` Pool Governance Account or pause admin (see `SetRole`)
+    SetTokenFlags { token_index: u8, flags: TokenFlags },
 
-    let d = DeFiInstruction::<TOKEN_COUNT>::SwapExactOutput {
-        maximum_input_amount,
-        input_token_index,
-        exact_output_amounts,
-    };
+    /// Sets which users may add and remove other than uniformly (see `state::UserGate`), on top of the user gate, who
+    /// then fail with `DepositorNotAllowed` unless they pass their depositor gate account (see
+    /// `find_depositor_gate_account`). E.g. for pools with protocol owned liquidity that still trade publicly
+    ///
+    /// Accounts expected by this instruction:
+    ///     0. `[w]` The pool state account
+    ///     1. `[s]` Pool Governance Account
+    SetDepositorGate { depositor_gate: UserGate },
 
-    let data = PoolInstruction::<TOKEN_COUNT>::DeFiInstruction(d).try_to_vec()?;
-    Ok(Instruction {
-        program_id: *program_id,
-        accounts,
-        data,
-    })
-}
+    /// Like `SetUserAllowed` for the pool's depositor allowlist, which `UserGate::Allowlist` as the depositor gate
+    /// checks
+    ///
+    /// Accounts expected by this instruction:
+    ///     0. `[w]` The pool state account
+    ///     1. `[s]` Pool Governance Account
+    ///     2. `[w]` Allowed depositor account (see `find_allowed_depositor_address`)
+    ///     3. `[ws]` Payer
+    ///     4. `[]` System program
+    SetDepositorAllowed { depositor: Pubkey, allowed: bool },
 
-#[derive(BorshSerialize, BorshDeserialize, Debug)]
-pub enum GovernanceInstruction<const TOKEN_COUNT: usize> {
-    /// Sets the lp_fee and governance_fee values that the pool
-    /// will transition to
+    /// Prepares the key whose signed quotes `PoolInstruction::RfqSwap` executes, `Pubkey::default()` turns RFQ swaps
+    /// off, enactable after the enact delay (see `EnactRfqQuoter`). A quote's output may deviate from its input's value
+    /// at the internal oracle's prices by at most `max_deviation`, which fails with `InvalidFeeInput` beyond
+    /// `state::MAX_RFQ_DEVIATION_BPS`
     ///
     /// Accounts expected by this instruction:
     ///     0. `[w]` The pool state account
     ///     1. `[s]` Pool Governance Account
-    PrepareFeeChange { lp_fee: DecT, governance_fee: DecT },
+    SetRfqQuoter { quoter_key: Pubkey, max_deviation: DecT },
 
-    /// Sets the `pool.lp_fee` and `pool.governance_fee` using the
-    /// values from `pool.prepared_lp_fee` and `pool.prepared_governance_fee`
+    /// Sets the program that defi instructions (other than uniform removes, so users can always exit) call before they
+    /// execute, which can veto them (see `ComplianceCheckData`), `Pubkey::default()` for none
     ///
+    /// Accounts expected by this instruction:
+    ///     0. `[w]` The pool state account
+    ///     1. `[s]` Pool Governance Account
+    SetComplianceProgram { compliance_program: Pubkey },
+
+    /// Sets the daily trading hours and the maintenance windows (unused slots all zeros) outside resp. during which
+    /// defi instructions other than uniform removes fail, like while the pool is paused. Logs a `MaintenanceEvent` for
+    /// each window that hasn't ended yet
     ///
     /// Accounts expected by this instruction:
     ///     0. `[w]` The pool state account
     ///     1. `[s]` Pool Governance Account
-    EnactFeeChange {},
+    SetTradingSchedule {
+        trading_hours: TradingHours,
+        maintenance_windows: [MaintenanceWindow; MAX_MAINTENANCE_WINDOWS],
+    },
 
-    /// Sets the governance account that the pool
-    /// will transition to
+    /// Makes removes other than uniform ones that burn more than `threshold` lp tokens require a withdrawal that the
+    /// user announced at least `cooldown` seconds (at most `MAX_WITHDRAWAL_COOLDOWN`) ahead (see
+    /// `PoolInstruction::AnnounceWithdrawal`), which keeps large imbalanced removes out of flash loan assisted
+    /// transactions. The threshold applies to the running total of the slot's unannounced removes of all users, so
+    /// splitting a remove doesn't get around it. A `cooldown` of 0 turns this off
     ///
+    /// Accounts expected by this instruction:
+    ///     0. `[w]` The pool state account
+    ///     1. `[s]` Pool Governance Account
+    SetWithdrawalCooldown {
+        threshold: AmountT,
+        cooldown: UnixTimestamp,
+    },
+
+    /// Replaces the pool's emergency responders (unused slots `Pubkey::default()`), `threshold` of whom have to
+    /// confirm an emergency to pause the pool (see `PoolInstruction::ConfirmEmergency`), and resets their
+    /// confirmations. `threshold` has to be between 1 and the number of responders, 0 without responders. Renouncing
+    /// governance removes all responders
     ///
     /// Accounts expected by this instruction:
     ///     0. `[w]` The pool state account
     ///     1. `[s]` Pool Governance Account
-    PrepareGovernanceTransition { upcoming_governance_key: Pubkey },
+    SetEmergencyResponders {
+        responders: [Pubkey; MAX_EMERGENCY_RESPONDERS],
+        threshold: u8,
+    },
 
-    /// Applies the prepared governance account as the
-    /// current governance account
+    /// Sets the price band of a two token pool (see `state::PriceBand`), whose trades then fail with `PriceOutOfBand`
+    /// rather than move the price outside of it, so liquidity providers of loosely pegged pairs aren't exposed beyond
+    /// it. Uniform removes always work. All zeros turn the band off
     ///
+    /// Accounts expected by this instruction:
+    ///     0. `[w]` The pool state account
+    ///     1. `[s]` Pool Governance Account
+    SetPriceBand { price_band: PriceBand },
+
+    /// Sets a token's participation (see `state::TokenParticipation`) to onboard or offboard it within the pool: a
+    /// deposit-only token can only be added, a withdraw-only one only swapped out and removed, instructions that move
+    /// them otherwise fail with `TokenDepositOnly` resp. `TokenWithdrawOnly`. Uniform removes always give all tokens.
+    /// Logs a `TokenParticipationEvent`
     ///
     /// Accounts expected by this instruction:
     ///     0. `[w]` The pool state account
     ///     1. `[s]` Pool Governance Account
-    EnactGovernanceTransition {},
+    SetTokenParticipation {
+        token_index: u8,
+        participation: TokenParticipation,
+    },
 
-    /// Switches the governance fee account
+    /// Prepares giving `keeper_key` (usually a program's address, which signs via cpi) a credit line of up to
+    /// `credit_limit` of the token at `token_index` to keep it at its peg (see `state::PegKeeper` and
+    /// `PoolInstruction::ProvidePegCredit`), `Pubkey::default()` removes the keeper, enactable after the enact delay
+    /// (see `EnactPegKeeper`). Only for stable swap pools, else `InvalidPegKeeper`. Lowering the limit below the debt
+    /// leaves the keeper to absorb its credit
     ///
+    /// Accounts expected by this instruction:
+    ///     0. `[w]` The pool state account
+    ///     1. `[s]` Pool Governance Account
+    SetPegKeeper {
+        keeper_key: Pubkey,
+        token_index: u8,
+        credit_limit: AmountT,
+    },
+
+    /// Prepares the yield adapter program of the token at `token_index` (see `state::YieldAdapter` and
+    /// `PoolInstruction::DeployYield`), which may hold what the pool's token account holds beyond `buffer`, enactable
+    /// after the enact delay (see `EnactYieldAdapter`). `Pubkey::default()` removes the adapter
     ///
     /// Accounts expected by this instruction:
     ///     0. `[w]` The pool state account
     ///     1. `[s]` Pool Governance Account
-    ///     2. `[]`  New Governance Fee account
-    ChangeGovernanceFeeAccount { governance_fee_key: Pubkey },
+    SetYieldAdapter {
+        token_index: u8,
+        adapter_program: Pubkey,
+        buffer: AmountT,
+    },
 
-    /// Adjusts the amp factor for the pool
+    /// Sets how `PoolInstruction::Repeg` moves a hybrid pool's price for its volatile token (see `state::PriceScale`):
+    /// once the oracle's rate is more than `threshold_bps` away, by at most `max_step_bps` of the price and at most
+    /// every `min_interval` seconds. A `max_step_bps` of 0 turns repegging off. Fails with `InvalidPriceScale` for
+    /// other pools or basis points above 10000
+    ///
+    /// Accounts expected by this instruction:
+    ///     0. `[w]` The pool state account
+    ///     1. `[s]` Pool Governance Account
+    SetRepegParams {
+        threshold_bps: u16,
+        max_step_bps: u16,
+        min_interval: UnixTimestamp,
+    },
+
+    /// Adds `reward_mint` as a reward of the pool's liquidity mining gauge (see `state::Gauge`), which `funder` then
+    /// funds with `PoolInstruction::FundGauge`, or sets the funder of a reward the gauge already has. Adding the first
+    /// reward creates the gauge along with its lp vault, every reward gets its own reward vault (see
+    /// `find_gauge_vault_address`). `Pubkey::default()` as funder retires the reward instead: its emissions stop and
+    /// what it was yet to emit goes back to the funder, while stakes can still claim what they earned. Retired rewards
+    /// keep their slot until a new token is added once all slots are taken: it takes the slot of a reward whose
+    /// emissions ended at least `state::GAUGE_CLAIM_PERIOD` ago, and stakes forfeit what they didn't claim of it. Fails
+    /// with `InvalidGauge` for the lp mint, when no slot is free that way or to retire a token that isn't an active
+    /// reward. Adding isn't possible while the pool is being wound down, nor for pools with a non-transferable lp token
+    ///
+    /// Accounts expected by this instruction:
+    ///     0. `[w]` The pool state account
+    ///     1. `[s]` Pool Governance Account
+    ///     2. `[]` pool authority
+    ///     3. `[w]` gauge account (see `find_gauge_address`)
+    ///     4. `[]` reward token mint
+    ///     5. `[w]` gauge's reward vault (see `find_gauge_vault_address`)
+    ///     6. `[]` SPL token program account
     ///
+    ///   To add a reward:
+    ///     7. `[]` LP Token Mint
+    ///     8. `[w]` gauge's lp vault (see `find_gauge_vault_address`)
+    ///     9. `[ws]` Payer (only pays for the accounts that don't exist yet)
+    ///     10. `[]` System program
+    ///     11. `[]` Rent sysvar
+    ///
+    ///   To retire a reward:
+    ///     7. `[w]` funder's token account of the reward token, gets what the reward was yet to emit
+    SetGaugeReward { reward_mint: Pubkey, funder: Pubkey },
+
+    /// Starts a trading fee rebate campaign (see `state::RebateCampaign`) at the address that `campaign_id` picks (see
+    /// `find_rebate_campaign_address`), which escrows a budget of `budget` tokens of `rebate_mint` from the governance
+    /// in its budget vault (see `find_rebate_vault_address`). The fees that swaps record with the campaign between
+    /// `start_ts` and `end_ts` (see `PoolInstruction::JoinRebateCampaign`) earn their swappers a pro rata share of the
+    /// budget, which they claim once the window ended. Fails with `InvalidRebateCampaign` for an empty budget or a window that
+    /// starts in the past, is empty or longer than `state::MAX_REBATE_WINDOW`, and with `RebateCampaignNotEnded`
+    /// while the window of the pool's previous campaign is open. Not while the pool is being wound down
     ///
     /// Accounts expected by this instruction:
     ///     0. `[w]` The pool state account
     ///     1. `[s]` Pool Governance Account
-    AdjustAmpFactor {
-        target_ts: UnixTimestamp,
-        target_value: DecT,
+    ///     2. `[]` pool authority
+    ///     3. `[w]` rebate campaign account (see `find_rebate_campaign_address`)
+    ///     4. `[]` rebate token mint
+    ///     5. `[w]` campaign's budget vault (see `find_rebate_vault_address`)
+    ///     6. `[w]` governance's token account of the rebate token, pays the budget
+    ///     7. `[ws]` Payer
+    ///     8. `[]` SPL token program account
+    ///     9. `[]` System program
+    ///     10. `[]` Rent sysvar
+    StartRebateCampaign {
+        campaign_id: u64,
+        rebate_mint: Pubkey,
+        budget: AmountT,
+        start_ts: UnixTimestamp,
+        end_ts: UnixTimestamp,
     },
 
-    /// Pause/Unpauses the pool
+    /// Returns what's left of a rebate campaign's budget to the governance once `state::REBATE_CLAIM_PERIOD` passed
+    /// after the campaign's window ended (right after it if no swap recorded its fees), after which the campaign's
+    /// rebates can't be claimed anymore. Fails with `RebateCampaignNotEnded` before and with `InvalidRebateCampaign`
+    /// if the budget was reclaimed already
+    ///
+    /// Accounts expected by this instruction:
+    ///     0. `[w]` The pool state account
+    ///     1. `[s]` Pool Governance Account
+    ///     2. `[]` pool authority
+    ///     3. `[w]` rebate campaign account (see `find_rebate_campaign_address`)
+    ///     4. `[w]` campaign's budget vault (see `find_rebate_vault_address`)
+    ///     5. `[w]` governance's token account of the rebate token
+    ///     6. `[]` SPL token program account
+    ReclaimRebateBudget { campaign_id: u64 },
+
+    /// Sets the voting escrow program that boosts the stakes of the pool's gauge (see `state::Gauge`) that the gauge
+    /// will transition to, `Pubkey::default()` for none. Once enacted (see `EnactGaugeVeProgram`), `StakeLp` and
+    /// `PoolInstruction::UpdateGaugeBoost` pass the program and the stake owner's ve account and set the stake's
+    /// working amount from the boost the program returns. Stakes keep their boost until it is updated, also when the
+    /// program changes. Fails with `InvalidGauge` if the pool has no gauge
     ///
+    /// Accounts expected by this instruction:
+    ///     0. `[w]` The pool state account
+    ///     1. `[s]` Pool Governance Account
+    ///     2. `[w]` gauge account (see `find_gauge_address`)
+    SetGaugeVeProgram { ve_program: Pubkey },
+
+    /// Applies the voting escrow program prepared by `SetGaugeVeProgram` to the pool's gauge
     ///
     /// Accounts expected by this instruction:
     ///     0. `[w]` The pool state account
     ///     1. `[s]` Pool Governance Account
-    SetPaused { paused: bool },
+    ///     2. `[w]` gauge account (see `find_gauge_address`)
+    EnactGaugeVeProgram {},
+
+    /// Applies the quoter and maximum deviation prepared by `SetRfqQuoter`
+    ///
+    /// Accounts expected by this instruction:
+    ///     0. `[w]` The pool state account
+    ///     1. `[s]` Pool Governance Account
+    EnactRfqQuoter {},
+
+    /// Applies the peg keeper prepared by `SetPegKeeper`. While the pool owes its keeper something, the keeper can
+    /// neither be removed nor change its token, else `InvalidPegKeeper`
+    ///
+    /// Accounts expected by this instruction:
+    ///     0. `[w]` The pool state account
+    ///     1. `[s]` Pool Governance Account
+    EnactPegKeeper {},
+
+    /// Applies the yield adapter prepared by `SetYieldAdapter` to its token. The program can't change while the pool
+    /// has tokens deployed to it, else `InvalidYieldAdapter`, the buffer can
+    ///
+    /// Accounts expected by this instruction:
+    ///     0. `[w]` The pool state account
+    ///     1. `[s]` Pool Governance Account
+    EnactYieldAdapter {},
+}
+
+impl<const TOKEN_COUNT: usize> GovernanceInstruction<TOKEN_COUNT> {
+    /// The role whose key may sign the instruction besides the governance, if any
+    pub fn role(&self) -> Option<Role> {
+        match self {
+            GovernanceInstruction::PrepareFeeChange { .. } | GovernanceInstruction::EnactFeeChange {} => {
+                Some(Role::FeeAdmin)
+            }
+            GovernanceInstruction::AdjustAmpFactor { .. } => Some(Role::AmpAdmin),
+            GovernanceInstruction::SetPaused { .. }
+            | GovernanceInstruction::SetTokenFlags { .. }
+            | GovernanceInstruction::SetTradingSchedule { .. } => Some(Role::PauseAdmin),
+            _ => None,
+        }
+    }
 }
 
 pub fn create_governance_ix<const TOKEN_COUNT: usize>(
@@ -388,13 +3430,59 @@ pub fn create_governance_ix<const TOKEN_COUNT: usize>(
         AccountMeta::new_readonly(*governance_account, true),
     ];
 
+    //only the instructions that involve the governance fee account take one
+    let takes_governance_fee_account = matches!(
+        gov_instruction,
+        GovernanceInstruction::ChangeGovernanceFeeAccount { .. }
+            | GovernanceInstruction::MigrateGovernanceFeeAccount { .. }
+            | GovernanceInstruction::BurnGovernanceFees { .. }
+    );
+    if !takes_governance_fee_account && governance_fee_account.is_some() {
+        return Err(ProgramError::InvalidArgument);
+    }
+    let fee_account = || governance_fee_account.copied().ok_or(ProgramError::InvalidArgument);
     match gov_instruction {
         GovernanceInstruction::ChangeGovernanceFeeAccount { .. } => {
-            accounts.push(AccountMeta::new_readonly(*governance_fee_account.unwrap(), false))
+            accounts.push(AccountMeta::new_readonly(fee_account()?, false))
+        }
+        //governance_fee_account is the current one here
+        GovernanceInstruction::MigrateGovernanceFeeAccount { governance_fee_key } => {
+            accounts.push(AccountMeta::new(fee_account()?, false));
+            accounts.push(AccountMeta::new(governance_fee_key, false));
+            accounts.push(AccountMeta::new_readonly(spl_token::id(), false));
+        }
+        //followed by the lp mint and lp token program, see create_burn_governance_fees_ix
+        GovernanceInstruction::BurnGovernanceFees { .. } => {
+            accounts.push(AccountMeta::new(fee_account()?, false));
+        }
+        //followed by the payer and system program, see create_set_user_allowed_ix resp. create_set_depositor_allowed_ix
+        GovernanceInstruction::SetUserAllowed { user, .. } => {
+            accounts.push(AccountMeta::new(
+                find_allowed_user_address(program_id, pool, &user).0,
+                false,
+            ));
+        }
+        GovernanceInstruction::SetDepositorAllowed { depositor, .. } => {
+            accounts.push(AccountMeta::new(
+                find_allowed_depositor_address(program_id, pool, &depositor).0,
+                false,
+            ));
+        }
+        //followed by the base pool for base pool sources, see create_set_base_pool_rate_ix
+        GovernanceInstruction::SetRateSource { source, rate_key, .. } => {
+            if source.has_account() {
+                accounts.push(AccountMeta::new_readonly(rate_key, false));
+            }
+        }
+        GovernanceInstruction::SetGaugeVeProgram { .. } | GovernanceInstruction::EnactGaugeVeProgram {} => {
+            accounts.push(AccountMeta::new(find_gauge_address(program_id, pool).0, false));
         }
-        _ => {
-            assert!(governance_fee_account.is_none());
+        GovernanceInstruction::SetTokenOracle { oracle_key, .. } => {
+            if oracle_key != Pubkey::default() {
+                accounts.push(AccountMeta::new_readonly(oracle_key, false));
+            }
         }
+        _ => (),
     }
 
     Ok(Instruction {
@@ -403,3 +3491,412 @@ pub fn create_governance_ix<const TOKEN_COUNT: usize>(
         data: PoolInstruction::GovernanceInstruction(gov_instruction).try_to_vec()?,
     })
 }
+
+/// Creates a `BurnGovernanceFees` governance instruction that burns `burn_amount` of the lp tokens in
+/// `governance_fee_account`, `lp_token_program` is Token-2022 for pools with a non-transferable lp token
+pub fn create_burn_governance_fees_ix<const TOKEN_COUNT: usize>(
+    program_id: &Pubkey,
+    pool: &Pubkey,
+    governance_account: &Pubkey,
+    governance_fee_account: &Pubkey,
+    lp_mint: &Pubkey,
+    lp_token_program: &Pubkey,
+    burn_amount: AmountT,
+) -> Result<Instruction, ProgramError> {
+    let mut instruction = create_governance_ix(
+        GovernanceInstruction::<TOKEN_COUNT>::BurnGovernanceFees { burn_amount },
+        program_id,
+        pool,
+        governance_account,
+        Some(governance_fee_account),
+    )?;
+    instruction.accounts.push(AccountMeta::new(*lp_mint, false));
+    instruction
+        .accounts
+        .push(AccountMeta::new_readonly(*lp_token_program, false));
+    Ok(instruction)
+}
+
+/// Creates a `SetUserAllowed` governance instruction for `user`, `payer` pays for resp. gets back the rent of its
+/// allowed user account
+pub fn create_set_user_allowed_ix<const TOKEN_COUNT: usize>(
+    program_id: &Pubkey,
+    pool: &Pubkey,
+    governance_account: &Pubkey,
+    user: &Pubkey,
+    allowed: bool,
+    payer: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let mut instruction = create_governance_ix(
+        GovernanceInstruction::<TOKEN_COUNT>::SetUserAllowed { user: *user, allowed },
+        program_id,
+        pool,
+        governance_account,
+        None,
+    )?;
+    instruction.accounts.push(AccountMeta::new(*payer, true));
+    instruction
+        .accounts
+        .push(AccountMeta::new_readonly(system_program::id(), false));
+    Ok(instruction)
+}
+
+/// Creates a `SetRateSource` governance instruction that values the pool's token `token_index`, the lp token of
+/// `base_pool` (a pool of the program `base_program_id`), at the base pool's virtual price, which has to have been
+/// published (see `create_publish_virtual_price_ix`)
+pub fn create_set_base_pool_rate_ix<const TOKEN_COUNT: usize>(
+    program_id: &Pubkey,
+    pool: &Pubkey,
+    governance_account: &Pubkey,
+    token_index: u8,
+    base_program_id: &Pubkey,
+    base_pool: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let mut instruction = create_governance_ix(
+        GovernanceInstruction::<TOKEN_COUNT>::SetRateSource {
+            token_index,
+            source: RateSource::BasePool,
+            rate_key: find_virtual_price_address(base_program_id, base_pool).0,
+        },
+        program_id,
+        pool,
+        governance_account,
+        None,
+    )?;
+    instruction.accounts.push(AccountMeta::new_readonly(*base_pool, false));
+    Ok(instruction)
+}
+
+/// Like `create_set_user_allowed_ix` for the pool's depositor allowlist
+pub fn create_set_depositor_allowed_ix<const TOKEN_COUNT: usize>(
+    program_id: &Pubkey,
+    pool: &Pubkey,
+    governance_account: &Pubkey,
+    depositor: &Pubkey,
+    allowed: bool,
+    payer: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let mut instruction = create_governance_ix(
+        GovernanceInstruction::<TOKEN_COUNT>::SetDepositorAllowed {
+            depositor: *depositor,
+            allowed,
+        },
+        program_id,
+        pool,
+        governance_account,
+        None,
+    )?;
+    instruction.accounts.push(AccountMeta::new(*payer, true));
+    instruction
+        .accounts
+        .push(AccountMeta::new_readonly(system_program::id(), false));
+    Ok(instruction)
+}
+
+/// Creates a `SetGaugeReward` governance instruction that adds `reward_mint` as a reward of the pool's gauge funded by
+/// `funder` (or sets its funder), `payer` pays for the gauge's accounts that don't exist yet
+#[allow(clippy::too_many_arguments)]
+pub fn create_add_gauge_reward_ix<const TOKEN_COUNT: usize>(
+    program_id: &Pubkey,
+    pool: &Pubkey,
+    governance_account: &Pubkey,
+    authority: &Pubkey,
+    lp_mint: &Pubkey,
+    reward_mint: &Pubkey,
+    funder: &Pubkey,
+    payer: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let mut instruction = create_governance_ix(
+        GovernanceInstruction::<TOKEN_COUNT>::SetGaugeReward {
+            reward_mint: *reward_mint,
+            funder: *funder,
+        },
+        program_id,
+        pool,
+        governance_account,
+        None,
+    )?;
+    instruction.accounts.extend([
+        AccountMeta::new_readonly(*authority, false),
+        AccountMeta::new(find_gauge_address(program_id, pool).0, false),
+        AccountMeta::new_readonly(*reward_mint, false),
+        AccountMeta::new(find_gauge_vault_address(program_id, pool, reward_mint).0, false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+        AccountMeta::new_readonly(*lp_mint, false),
+        AccountMeta::new(find_gauge_vault_address(program_id, pool, lp_mint).0, false),
+        AccountMeta::new(*payer, true),
+        AccountMeta::new_readonly(system_program::id(), false),
+        AccountMeta::new_readonly(sysvar::rent::id(), false),
+    ]);
+    Ok(instruction)
+}
+
+/// Creates a `SetGaugeReward` governance instruction that retires the gauge's reward of `reward_mint`, whose
+/// `funder_reward_account` gets back what it was yet to emit
+pub fn create_retire_gauge_reward_ix<const TOKEN_COUNT: usize>(
+    program_id: &Pubkey,
+    pool: &Pubkey,
+    governance_account: &Pubkey,
+    authority: &Pubkey,
+    reward_mint: &Pubkey,
+    funder_reward_account: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let mut instruction = create_governance_ix(
+        GovernanceInstruction::<TOKEN_COUNT>::SetGaugeReward {
+            reward_mint: *reward_mint,
+            funder: Pubkey::default(),
+        },
+        program_id,
+        pool,
+        governance_account,
+        None,
+    )?;
+    instruction.accounts.extend([
+        AccountMeta::new_readonly(*authority, false),
+        AccountMeta::new(find_gauge_address(program_id, pool).0, false),
+        AccountMeta::new_readonly(*reward_mint, false),
+        AccountMeta::new(find_gauge_vault_address(program_id, pool, reward_mint).0, false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+        AccountMeta::new(*funder_reward_account, false),
+    ]);
+    Ok(instruction)
+}
+
+/// Creates a `StartRebateCampaign` governance instruction that escrows `budget` of the tokens of
+/// `governance_rebate_account` for the campaign, `payer` pays for its accounts
+#[allow(clippy::too_many_arguments)]
+pub fn create_start_rebate_campaign_ix<const TOKEN_COUNT: usize>(
+    program_id: &Pubkey,
+    pool: &Pubkey,
+    governance_account: &Pubkey,
+    authority: &Pubkey,
+    rebate_mint: &Pubkey,
+    governance_rebate_account: &Pubkey,
+    payer: &Pubkey,
+    campaign_id: u64,
+    budget: AmountT,
+    start_ts: UnixTimestamp,
+    end_ts: UnixTimestamp,
+) -> Result<Instruction, ProgramError> {
+    let mut instruction = create_governance_ix(
+        GovernanceInstruction::<TOKEN_COUNT>::StartRebateCampaign {
+            campaign_id,
+            rebate_mint: *rebate_mint,
+            budget,
+            start_ts,
+            end_ts,
+        },
+        program_id,
+        pool,
+        governance_account,
+        None,
+    )?;
+    let campaign = find_rebate_campaign_address(program_id, pool, campaign_id).0;
+    instruction.accounts.extend([
+        AccountMeta::new_readonly(*authority, false),
+        AccountMeta::new(campaign, false),
+        AccountMeta::new_readonly(*rebate_mint, false),
+        AccountMeta::new(find_rebate_vault_address(program_id, &campaign).0, false),
+        AccountMeta::new(*governance_rebate_account, false),
+        AccountMeta::new(*payer, true),
+        AccountMeta::new_readonly(spl_token::id(), false),
+        AccountMeta::new_readonly(system_program::id(), false),
+        AccountMeta::new_readonly(sysvar::rent::id(), false),
+    ]);
+    Ok(instruction)
+}
+
+/// Creates a `ReclaimRebateBudget` governance instruction that returns what's left of the campaign's budget to
+/// `governance_rebate_account`
+pub fn create_reclaim_rebate_budget_ix<const TOKEN_COUNT: usize>(
+    program_id: &Pubkey,
+    pool: &Pubkey,
+    governance_account: &Pubkey,
+    authority: &Pubkey,
+    campaign_id: u64,
+    governance_rebate_account: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let mut instruction = create_governance_ix(
+        GovernanceInstruction::<TOKEN_COUNT>::ReclaimRebateBudget { campaign_id },
+        program_id,
+        pool,
+        governance_account,
+        None,
+    )?;
+    let campaign = find_rebate_campaign_address(program_id, pool, campaign_id).0;
+    instruction.accounts.extend([
+        AccountMeta::new_readonly(*authority, false),
+        AccountMeta::new(campaign, false),
+        AccountMeta::new(find_rebate_vault_address(program_id, &campaign).0, false),
+        AccountMeta::new(*governance_rebate_account, false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+    ]);
+    Ok(instruction)
+}
+
+#[cfg(all(test, not(feature = "test-bpf")))]
+mod tests {
+    use super::*;
+    use crate::common::create_array;
+    use solana_program::message::Message;
+
+    #[test]
+    fn simulate_encoding() {
+        let remove = DeFiInstruction::<2>::RemoveExactBurn {
+            exact_burn_amount: 1000,
+            output_token_index: 1,
+            minimum_output_amount: 900,
+        };
+        let data = DeFiInstruction::Simulate(Box::new(remove)).try_to_vec().unwrap();
+        assert_eq!(data[0], 6);
+        assert_eq!(data[1..], [4, 232, 3, 0, 0, 0, 0, 0, 0, 1, 132, 3, 0, 0, 0, 0, 0, 0]);
+        match DeFiInstruction::<2>::try_from_slice(&data).unwrap() {
+            DeFiInstruction::Simulate(simulated) => match *simulated {
+                DeFiInstruction::RemoveExactBurn {
+                    exact_burn_amount: 1000,
+                    output_token_index: 1,
+                    minimum_output_amount: 900,
+                } => {}
+                decoded => panic!("unexpected {:?}", decoded),
+            },
+            decoded => panic!("unexpected {:?}", decoded),
+        }
+        assert!(DeFiInstruction::<2>::try_from_slice(&[7]).is_err());
+    }
+
+    #[test]
+    fn token_count_mismatch() {
+        let swap = PoolInstruction::<3>::DeFiInstruction(DeFiInstruction::SwapExactInput {
+            exact_input_amounts: [100, 0, 0],
+            output_token_index: 1,
+            minimum_output_amount: 90,
+        });
+        let data = swap.try_to_vec().unwrap();
+        assert!(matches!(
+            PoolInstruction::<3>::unpack(&data),
+            Ok(PoolInstruction::DeFiInstruction(DeFiInstruction::SwapExactInput { .. }))
+        ));
+        assert_eq!(
+            PoolInstruction::<2>::unpack(&data).unwrap_err(),
+            ProgramError::Custom(PoolError::TokenCountMismatch.code())
+        );
+        assert_eq!(
+            PoolInstruction::<4>::unpack(&data).unwrap_err(),
+            ProgramError::Custom(PoolError::TokenCountMismatch.code())
+        );
+        //data that doesn't decode under any token count keeps its Borsh error
+        assert!(matches!(
+            PoolInstruction::<2>::unpack(&data[..data.len() - 1]),
+            Err(ProgramError::BorshIoError(_))
+        ));
+
+        let add = PoolInstruction::<MAX_TOKEN_COUNT>::DeFiInstruction(DeFiInstruction::Add {
+            input_amounts: [100; MAX_TOKEN_COUNT],
+            minimum_mint_amount: 0,
+        });
+        let data = add.try_to_vec().unwrap();
+        assert_eq!(defi_token_count(&data), Some(MAX_TOKEN_COUNT));
+        assert_eq!(
+            PoolInstruction::<6>::unpack(&data).unwrap_err(),
+            ProgramError::Custom(PoolError::TokenCountMismatch.code())
+        );
+    }
+
+    #[test]
+    fn max_token_count_fits_a_transaction() {
+        //the size of a transaction packet (solana_sdk::packet::PACKET_DATA_SIZE)
+        const PACKET_DATA_SIZE: usize = 1280 - 40 - 8;
+        let keys = || create_array(|_| Pubkey::new_unique());
+        let user = Pubkey::new_unique();
+        let mut add_ix = create_defi_ix::<MAX_TOKEN_COUNT>(
+            DeFiInstruction::Add {
+                input_amounts: [u64::MAX; MAX_TOKEN_COUNT],
+                minimum_mint_amount: u64::MAX,
+            },
+            &Pubkey::new_unique(),
+            &Pubkey::new_unique(),
+            &Pubkey::new_unique(),
+            &keys(),
+            &Pubkey::new_unique(),
+            &Pubkey::new_unique(),
+            &user,
+            &keys(),
+            &spl_token::id(),
+            Some(&Pubkey::new_unique()),
+        )
+        .unwrap();
+        //the accounts of the largest defi instructions leave room for the rate accounts of half the tokens, pools of
+        // that many tokens whose every token is rated need a pool of fewer tokens to fit
+        for rate_account in keys().iter().take(MAX_TOKEN_COUNT / 2) {
+            add_ix.accounts.push(AccountMeta::new_readonly(*rate_account, false));
+        }
+        let message = Message::new(&[add_ix], Some(&user));
+        assert!(
+            1 + 64 * message.header.num_required_signatures as usize + message.serialize().len() <= PACKET_DATA_SIZE
+        );
+    }
+
+    #[test]
+    fn rfq_signature_encoding() {
+        let quote = RfqQuote {
+            pool: Pubkey::new_unique(),
+            user: Pubkey::new_unique(),
+            input_token_index: 0,
+            input_amount: 1_000_000,
+            output_token_index: 1,
+            output_amount: 999_000,
+            quote_id: 7,
+            expiry_ts: 1_700_000_000,
+        };
+        let quoter = Pubkey::new_unique();
+        let ix = create_rfq_signature_ix(&quoter, &[3; 64], &quote);
+        assert_eq!(ix.program_id, ed25519_program::id());
+        assert_eq!(parse_ed25519_ix(&ix.data), Some((quoter, &quote.message()[..])));
+
+        //signatures whose parts are in other instructions aren't accepted
+        let mut data = ix.data.clone();
+        data[ED25519_OFFSETS_START + 10..ED25519_OFFSETS_START + 12].copy_from_slice(&0u16.to_le_bytes());
+        assert_eq!(parse_ed25519_ix(&data), None);
+        let mut data = ix.data;
+        data[0] = 2;
+        assert_eq!(parse_ed25519_ix(&data), None);
+    }
+
+    #[cfg(feature = "anchor-discriminators")]
+    #[test]
+    fn anchor_discriminators() {
+        use solana_program::hash::hashv;
+
+        for (name, discriminator) in ANCHOR_DEFI_DISCRIMINATORS.iter() {
+            assert_eq!(
+                hashv(&[b"global:", name.as_bytes()]).to_bytes()[..8],
+                discriminator[..],
+                "{}",
+                name
+            );
+            //UpdateGaugeBoost is the last PoolInstruction
+            let last = PoolInstruction::<2>::UpdateGaugeBoost {};
+            assert!(discriminator[0] > last.try_to_vec().unwrap()[0]);
+        }
+
+        let swap = DeFiInstruction::<2>::SwapExactOutput {
+            maximum_input_amount: 100,
+            input_token_index: 1,
+            exact_output_amounts: [50, 0],
+        };
+        let data = swap.try_to_anchor_vec().unwrap();
+        assert_eq!(data[..8], ANCHOR_DEFI_DISCRIMINATORS[2].1);
+        assert_eq!(data[8..], swap.try_to_vec().unwrap()[1..]);
+        match DeFiInstruction::<2>::try_from_anchor_slice(&data).unwrap() {
+            Some(DeFiInstruction::SwapExactOutput {
+                maximum_input_amount: 100,
+                input_token_index: 1,
+                exact_output_amounts: [50, 0],
+            }) => {}
+            decoded => panic!("unexpected {:?}", decoded),
+        }
+        assert!(DeFiInstruction::<2>::try_from_anchor_slice(&swap.try_to_vec().unwrap())
+            .unwrap()
+            .is_none());
+    }
+}
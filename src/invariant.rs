@@ -1,13 +1,16 @@
 use crate::{
-    common::create_array,
+    common::{create_array, create_result_array},
     decimal::{self, DecimalU64, U128},
     error::PoolError,
 };
 
+use solana_program::{msg, program_error::ProgramError};
 use std::{
+    fmt,
     ops::{Add, Sub},
     vec::Vec,
 };
+use thiserror::Error;
 
 use uint::construct_uint;
 construct_uint! {
@@ -15,7 +18,78 @@ construct_uint! {
 }
 
 use rust_decimal::{prelude::*, Decimal};
-type InvariantResult<T> = Result<T, PoolError>;
+type InvariantResult<T> = Result<T, InvariantError>;
+
+//Newton's method converges within a handful of iterations for any pool the program admits, a search that takes this
+// many has run into numeric trouble
+const MAX_ITERATIONS: u32 = 256;
+
+/// The part of the invariant math that failed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InvariantStage {
+    /// Applying the user's amounts to the pool's balances
+    Balances,
+    /// Charging the fees on the amounts
+    Fees,
+    /// Scaling the depth with the lp supply
+    LpSupply,
+    /// Solving for the depth of given balances
+    Depth,
+    /// Solving for the balance of one token given the others and the depth
+    UnknownBalance,
+}
+
+impl fmt::Display for InvariantStage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            InvariantStage::Balances => "balances",
+            InvariantStage::Fees => "fees",
+            InvariantStage::LpSupply => "lp supply",
+            InvariantStage::Depth => "depth",
+            InvariantStage::UnknownBalance => "unknown balance",
+        })
+    }
+}
+
+/// Why the invariant math failed, surfaces as the `PoolError` of the same name (see `From<InvariantError>`)
+#[derive(Error, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InvariantError {
+    #[error("overflow computing the {stage}")]
+    Overflow { stage: InvariantStage },
+    #[error("underflow computing the {stage}")]
+    Underflow { stage: InvariantStage },
+    #[error("no convergence after {iterations} iterations")]
+    ConvergenceFailure { iterations: u32 },
+    #[error("the fees on the remove exceed the pool's balance")]
+    ImpossibleRemove,
+}
+
+impl From<InvariantError> for PoolError {
+    fn from(e: InvariantError) -> Self {
+        match e {
+            InvariantError::Overflow { .. } => PoolError::InvariantOverflow,
+            InvariantError::Underflow { .. } => PoolError::InvariantUnderflow,
+            InvariantError::ConvergenceFailure { .. } => PoolError::InvariantConvergenceFailure,
+            InvariantError::ImpossibleRemove => PoolError::ImpossibleRemove,
+        }
+    }
+}
+
+//logs the failing stage, which the error code alone doesn't tell
+impl From<InvariantError> for ProgramError {
+    fn from(e: InvariantError) -> Self {
+        PoolError::from(e).with_context(e)
+    }
+}
+
+impl InvariantError {
+    /// Logs the failing stage like the conversion into `ProgramError` does, for callers that return `PoolError`s
+    pub fn logged(self) -> PoolError {
+        let error = PoolError::from(self);
+        msg!("{} (code {}): {}", error, error.code(), self);
+        error
+    }
+}
 
 pub type AmountT = U128;
 type AmpT = Decimal;
@@ -63,6 +137,15 @@ fn binary_op_balances<const TOKEN_COUNT: usize>(
 ) -> [AmountT; TOKEN_COUNT] {
 }
 
+fn checked_op_balances<const TOKEN_COUNT: usize>(
+    op: impl Fn(AmountT, AmountT) -> Option<AmountT>,
+    balances1: &[AmountT; TOKEN_COUNT],
+    balances2: &[AmountT; TOKEN_COUNT],
+    error: InvariantError,
+) -> InvariantResult<[AmountT; TOKEN_COUNT]> {
+    create_result_array(|i| op(balances1[i], balances2[i]).ok_or(error))
+}
+
 fn unary_op_balances<const TOKEN_COUNT: usize>(
     op: impl Fn(AmountT) -> AmountT,
     balances: &[AmountT; TOKEN_COUNT],
@@ -197,6 +280,15 @@ impl<const TOKEN_COUNT: usize> Invariant<TOKEN_COUNT> {
             lp_total_supply,
     }
 
+    /// The depth (i.e. the total value in equalized units) of a pool with the given (equalized) balances
+    pub fn depth(pool_balances: &[AmountT; TOKEN_COUNT], amp_factor: DecT) -> InvariantResult<AmountT> {
+        Ok(fast_round(Self::calculate_depth(
+            pool_balances,
+            amp_factor.into(),
+            Decimal::zero(),
+        )?))
+    }
+
     fn swap(
         is_exact_input: bool, //false => exact output
         amounts: &[AmountT; TOKEN_COUNT],
@@ -206,11 +298,25 @@ impl<const TOKEN_COUNT: usize> Invariant<TOKEN_COUNT> {
         total_fee: FeeT,
         governance_fee: FeeT,
         lp_total_supply: AmountT,
-        let mut updated_balances = binary_op_balances(
-            if is_exact_input { AmountT::add } else { AmountT::sub },
-            &pool_balances,
-            &amounts,
-        );
+        let mut updated_balances = if is_exact_input {
+            checked_op_balances(
+                AmountT::checked_add,
+                &pool_balances,
+                &amounts,
+                InvariantError::Overflow {
+                    stage: InvariantStage::Balances,
+                },
+            )?
+        } else {
+            checked_op_balances(
+                AmountT::checked_sub,
+                &pool_balances,
+                &amounts,
+                InvariantError::Underflow {
+                    stage: InvariantStage::Balances,
+                },
+            )?
+        };
             binary_op_balances(AmountT::sub, &updated_balances, &input_fee_amounts)
         } else {
     }
@@ -223,11 +329,25 @@ impl<const TOKEN_COUNT: usize> Invariant<TOKEN_COUNT> {
         total_fee: FeeT,
         governance_fee: FeeT,
         lp_total_supply: AmountT,
-        let updated_balances = binary_op_balances(
-            if is_add { AmountT::add } else { AmountT::sub },
-            &pool_balances,
-            &amounts,
-        );
+        let updated_balances = if is_add {
+            checked_op_balances(
+                AmountT::checked_add,
+                &pool_balances,
+                &amounts,
+                InvariantError::Overflow {
+                    stage: InvariantStage::Balances,
+                },
+            )?
+        } else {
+            checked_op_balances(
+                AmountT::checked_sub,
+                &pool_balances,
+                &amounts,
+                InvariantError::Underflow {
+                    stage: InvariantStage::Balances,
+                },
+            )?
+        };
                 pool_balances,
             );
             let taxbase = binary_op_balances(
@@ -263,7 +383,7 @@ impl<const TOKEN_COUNT: usize> Invariant<TOKEN_COUNT> {
                 //Overall, this issue should be of little practical concern however since any remove that would run
                 //into it is economically trumped by a proportional remove that avoids fees altogether and would
                 //essentially withdraw all token balances, including the requested one.
-                return Err(PoolError::ImpossibleRemove);
+                return Err(InvariantError::ImpossibleRemove);
             }
             let fee_adjusted_balances = binary_op_balances(AmountT::sub, &updated_balances, &fee_amounts);
             //solana_program::msg!("ADD/REMOVE 5");
@@ -280,18 +400,32 @@ impl<const TOKEN_COUNT: usize> Invariant<TOKEN_COUNT> {
         total_fee: FeeT,
         governance_fee: FeeT,
         lp_total_supply: AmountT,
-        let updated_depth =
-            initial_depth * (Decimal::from(lp_total_supply - burn_amount) / Decimal::from(lp_total_supply));
+        let remaining_lp_supply = lp_total_supply
+            .checked_sub(burn_amount)
+            .ok_or(InvariantError::Underflow {
+                stage: InvariantStage::LpSupply,
+            })?;
+        let updated_depth = initial_depth * (Decimal::from(remaining_lp_supply) / Decimal::from(lp_total_supply));
         debug_assert!(initial_depth > updated_depth);
         let known_balances = exclude_index(output_index, &pool_balances);
         let unknown_balance =
             Self::calculate_unknown_balance(&known_balances, updated_depth, amp_factor, pool_balances[output_index])?;
-        let base_amount = pool_balances[output_index] - unknown_balance;
+        let output_balance = pool_balances[output_index];
+        let base_amount = output_balance
+            .checked_sub(unknown_balance)
+            .ok_or(InvariantError::Underflow {
+                stage: InvariantStage::UnknownBalance,
+            })?;
         let (output_amount, governance_mint_amount) = if !total_fee.is_zero() {
             let sum_pool_balances = sum_balances(&pool_balances);
-            let output_amount = base_amount - fee_amount;
+            let fees_underflow = InvariantError::Underflow {
+                stage: InvariantStage::Fees,
+            };
+            let output_amount = base_amount.checked_sub(fee_amount).ok_or(fees_underflow)?;
             let mut updated_balances = *pool_balances;
-            updated_balances[output_index] -= output_amount;
+            updated_balances[output_index] = updated_balances[output_index]
+                .checked_sub(output_amount)
+                .ok_or(fees_underflow)?;
         } else {
             (base_amount, 0.into())
         };
@@ -318,9 +452,9 @@ impl<const TOKEN_COUNT: usize> Invariant<TOKEN_COUNT> {
             //scaled pool balances are at most 19 decimals (u64) + the decimal shift to
             // make them uniform in regards to token decimals (see MAX_DECIMAL_DIFFERENCE
             // in processor.rs - currently 8)
-            //overall, even if the entire U128 range were to be used, this would still only
-            // give 38 decimals per token and hence a product less than 10^240 for a pool
-            // with 6 tokens, which is well within the range of f64 (i.e. < 10^308)
+            //overall, this gives at most 28 decimals per token (after multiplying by n) and
+            // hence a product less than 10^226 for a pool with MAX_TOKEN_COUNT (8) tokens,
+            // which is well within the range of f64 (i.e. < 10^308)
             let reciprocal_decay_precomp = pool_balances_times_n
                 .iter()
                 .fold(1f64, |acc, &pool_balance| acc * pool_balance.as_u128() as f64)
@@ -366,7 +500,8 @@ impl<const TOKEN_COUNT: usize> Invariant<TOKEN_COUNT> {
                 previous_depth = depth;
 
                 //similar consideration as above:
-                //depth.powi(TOKEN_COUNT+1) will always be less than 10^308 for 6 tokens
+                //depth is at most the sum of the pool balances (< 10^28), so depth.powi(TOKEN_COUNT+1)
+                // will always be less than 10^254 for MAX_TOKEN_COUNT (8) tokens
                 let reciprocal_decay = depth.powi(TOKEN_COUNT as i32) * reciprocal_decay_precomp;
                 let n_times_depth_times_decay = depth * reciprocal_decay * TOKEN_COUNT as f64;
                 let numerator = amp_times_sum + n_times_depth_times_decay;
@@ -379,22 +514,41 @@ impl<const TOKEN_COUNT: usize> Invariant<TOKEN_COUNT> {
         };
 
         let pool_balances_times_n: [_; TOKEN_COUNT] = create_array(|i| Decimal::from(pool_balances_times_n[i]));
-        let amp_times_sum = Decimal::from(pool_balances_sum) * amp_factor;
+        let amp_times_sum = Decimal::from(pool_balances_sum)
+            .checked_mul(amp_factor)
+            .ok_or(InvariantError::Overflow {
+                stage: InvariantStage::Depth,
+            })?;
         let denominator_fixed = amp_factor - Decimal::one();
 
         let mut previous_depth = Decimal::zero();
+        let mut iterations = 0;
             previous_depth = depth;
+            iterations += 1;
+            if iterations > MAX_ITERATIONS {
+                return Err(InvariantError::ConvergenceFailure { iterations });
+            }
 
+            let overflow = InvariantError::Overflow {
+                stage: InvariantStage::Depth,
+            };
             let reciprocal_decay = pool_balances_times_n
                 .iter()
-                .fold(Decimal::one(), |acc, &pool_balance_times_n| {
-                    acc * (depth / pool_balance_times_n)
-                });
-            let n_times_depth_times_decay = depth * reciprocal_decay * Decimal::from(TOKEN_COUNT);
-            let numerator = amp_times_sum + n_times_depth_times_decay;
-            let denominator = denominator_fixed + reciprocal_decay * Decimal::from(TOKEN_COUNT + 1);
-
-            depth = numerator / denominator;
+                .try_fold(Decimal::one(), |acc, &pool_balance_times_n| {
+                    acc.checked_mul(depth.checked_div(pool_balance_times_n)?)
+                })
+                .ok_or(overflow)?;
+            let n_times_depth_times_decay = depth
+                .checked_mul(reciprocal_decay)
+                .and_then(|product| product.checked_mul(Decimal::from(TOKEN_COUNT)))
+                .ok_or(overflow)?;
+            let numerator = amp_times_sum.checked_add(n_times_depth_times_decay).ok_or(overflow)?;
+            let denominator = reciprocal_decay
+                .checked_mul(Decimal::from(TOKEN_COUNT + 1))
+                .and_then(|product| product.checked_add(denominator_fixed))
+                .ok_or(overflow)?;
+
+            depth = numerator.checked_div(denominator).ok_or(overflow)?;
         }
 
         Ok(depth)
@@ -409,11 +563,30 @@ impl<const TOKEN_COUNT: usize> Invariant<TOKEN_COUNT> {
         initial_guess: AmountT,
     ) -> InvariantResult<AmountT> {
         let n = AmountT::from(TOKEN_COUNT);
+        let mut iterations = 0;
         let known_balance_sum = known_balances
             .iter()
             previous_unknown_balance = unknown_balance;
-            let numerator = numerator_fixed + unknown_balance * unknown_balance;
-            let denominator = (denominator_fixed + unknown_balance * 2) - depth;
+            iterations += 1;
+            if iterations > MAX_ITERATIONS {
+                return Err(InvariantError::ConvergenceFailure { iterations });
+            }
+            let overflow = InvariantError::Overflow {
+                stage: InvariantStage::UnknownBalance,
+            };
+            let numerator = unknown_balance
+                .checked_mul(unknown_balance)
+                .and_then(|square| square.checked_add(numerator_fixed))
+                .ok_or(overflow)?;
+            let denominator = unknown_balance
+                .checked_add(unknown_balance)
+                .and_then(|double| double.checked_add(denominator_fixed))
+                .ok_or(overflow)?
+                .checked_sub(depth)
+                .filter(|denominator| !denominator.is_zero())
+                .ok_or(InvariantError::Underflow {
+                    stage: InvariantStage::UnknownBalance,
+                })?;
 
             unknown_balance = numerator.rounded_div(denominator);
             // println!(".         numerator: {}", numerator);
@@ -472,6 +645,28 @@ mod tests {
         }
     }
 
+    #[test]
+    fn max_token_count_convergence() {
+        const TOKEN_COUNT: usize = crate::MAX_TOKEN_COUNT;
+        let balances = create_array(|i| AmountT::from(1_000_000_000_000u64 * (i as u64 + 1)));
+        let sum = sum_balances(&balances);
+        for amp_factor in [1, 1_000, 1_000_000] {
+            let amp_factor = DecT::from(amp_factor);
+            let depth = Invariant::<TOKEN_COUNT>::depth(&balances, amp_factor).unwrap();
+            assert!(depth > balances[0] * AmountT::from(TOKEN_COUNT) && depth <= sum);
+            for i in 0..TOKEN_COUNT {
+                let unknown_balance = Invariant::<TOKEN_COUNT>::calculate_unknown_balance(
+                    &exclude_index(i, &balances),
+                    Decimal::from(depth.as_u128()),
+                    amp_factor.into(),
+                    depth,
+                )
+                .unwrap();
+                assert_close_enough(unknown_balance, balances[i], 1.into());
+            }
+        }
+    }
+
     #[test]
     fn swap_in_vs_out() {
         let amp_factor = DecT::new(1313, 3).unwrap();
@@ -537,6 +732,50 @@ mod tests {
             lp_total_supply,
     }
 
+    #[test]
+    fn invariant_errors() {
+        let balances = [AmountT::from(1_000_000u64); 3];
+        let lp_total_supply = AmountT::from(3_000_000u64);
+        let error = Invariant::<3>::remove_exact_burn(
+            lp_total_supply + 1,
+            0,
+            &balances,
+            DecT::from(1),
+            DecT::from(0),
+            DecT::from(0),
+            lp_total_supply,
+            lp_total_supply,
+        )
+        .unwrap_err();
+        assert_eq!(
+            error,
+            InvariantError::Underflow {
+                stage: InvariantStage::LpSupply
+            }
+        );
+        assert_eq!(PoolError::from(error), PoolError::InvariantUnderflow);
+        assert_eq!(error.to_string(), "underflow computing the lp supply");
+
+        let mut output = [AmountT::zero(); 3];
+        output[1] = balances[1] + 1;
+        let error = Invariant::<3>::remove_exact_output(
+            &output,
+            &balances,
+            DecT::from(1),
+            DecT::from(0),
+            DecT::from(0),
+            lp_total_supply,
+            lp_total_supply,
+        )
+        .unwrap_err();
+        assert_eq!(
+            error,
+            InvariantError::Underflow {
+                stage: InvariantStage::Balances
+            }
+        );
+    }
+
     #[test]
     fn uniform_and_imbalanced_vs_together_add() {
         uniform_and_imbalanced_vs_together(true);
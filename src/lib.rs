@@ -1,12 +1,58 @@
+//built with the forbid-panics feature, clippy rejects the explicit panics of the program's code (unwraps, expects,
+// panic!s and unreachable!s), which abort transactions without an error that tells why. the client is off-chain and
+// exempt
+#![cfg_attr(
+    feature = "forbid-panics",
+    deny(
+        clippy::unwrap_used,
+        clippy::expect_used,
+        clippy::panic,
+        clippy::unreachable,
+        clippy::todo,
+        clippy::unimplemented
+    )
+)]
+
 pub mod amp_factor;
+#[cfg(feature = "client")]
+#[cfg_attr(
+    feature = "forbid-panics",
+    allow(clippy::unwrap_used, clippy::expect_used, clippy::panic, clippy::unreachable)
+)]
+pub mod client;
 pub mod common;
+#[cfg(feature = "compliance")]
+pub mod compliance;
+pub mod cpi;
 pub mod decimal;
 #[cfg(not(feature = "no-entrypoint"))]
 pub mod entrypoint;
 pub mod error;
+pub mod fixed_rate;
 pub mod instruction;
 pub mod invariant;
+pub mod log_exp;
+pub mod migration;
+pub mod oracle;
 pub mod pool_fee;
 pub mod processor;
+pub mod quote;
 pub mod state;
+pub mod token_2022;
+pub mod validation;
+#[cfg(feature = "vault")]
+pub mod vault;
+pub mod weighted;
+
+pub use error::decode_custom_error;
+
+/// The largest TOKEN_COUNT that the program supports, which the invariants' numeric range (see `invariant`), the pool
+/// state's size and the accounts of defi instructions are checked against
+pub const MAX_TOKEN_COUNT: usize = 8;
 
+//a build for more tokens than that (or fewer than two) fails to compile rather than misbehaving at runtime
+#[allow(clippy::assertions_on_constants)]
+const _: () = assert!(
+    TOKEN_COUNT >= 2 && TOKEN_COUNT <= MAX_TOKEN_COUNT,
+    "TOKEN_COUNT has to be between 2 and MAX_TOKEN_COUNT"
+);
@@ -0,0 +1,165 @@
+//fixed-point natural logarithm and exponential for the weighted invariant (see weighted.rs), in the spirit of
+// balancer's LogExpMath. values are scaled by ONE (18 decimals). both functions are off by at most a bounded number of
+// units of the last decimal (MAX_LN_ERROR absolute for ln_ratio, MAX_EXP_ERROR relative for exp), which exp_up and
+// exp_down (and the callers' own bounds of ln_ratio) add in the direction the caller needs, so results can be rounded
+// in the pool's favor without relying on an arbitrary margin
+//
+//ln_ratio reduces its arguments to a ratio in (1/2, 2) by powers of two and sums the series of 2*atanh((x-y)/(x+y)),
+// exp reduces its exponent to [-ln(2)/2, ln(2)/2] by multiples of ln(2) and sums the taylor series
+
+use crate::decimal::U256;
+
+pub const ONE: i128 = 1_000_000_000_000_000_000;
+/// The largest error of `ln_ratio` in units of the last decimal
+pub const MAX_LN_ERROR: i128 = 64;
+/// The largest relative error of `exp` in parts of ONE (plus one unit of the last decimal)
+pub const MAX_EXP_ERROR: i128 = 64;
+/// Exponents beyond it are rejected, their exponentials don't fit a U256 (scaled by ONE) for long
+pub const MAX_EXPONENT: i128 = 130 * ONE;
+
+//ln(2) scaled by 10^36, so multiples of it stay exact to the 18th decimal
+const LN_2_36: i128 = 693_147_180_559_945_309_417_232_121_458_176_568;
+const SCALE_18: i128 = 1_000_000_000_000_000_000;
+//the ratio of ln_ratio's arguments is reduced to arguments of at most this many bits, which keeps it exact to far less
+// than a unit of the last decimal
+const RATIO_BITS: u32 = 64;
+
+/// ln(numerator / denominator) scaled by ONE, `None` if either is zero
+pub fn ln_ratio(numerator: u128, denominator: u128) -> Option<i128> {
+    if numerator == 0 || denominator == 0 {
+        return None;
+    }
+    let numerator_bits = u128::BITS - numerator.leading_zeros();
+    let denominator_bits = u128::BITS - denominator.leading_zeros();
+    //shifting the shorter argument to the other's length keeps both exact and their ratio in (1/2, 2)
+    let (mut x, mut y) = if numerator_bits >= denominator_bits {
+        (numerator, denominator << (numerator_bits - denominator_bits))
+    } else {
+        (numerator << (denominator_bits - numerator_bits), denominator)
+    };
+    let length = numerator_bits.max(denominator_bits);
+    if length > RATIO_BITS {
+        x >>= length - RATIO_BITS;
+        y >>= length - RATIO_BITS;
+    }
+    //|z| < 1/3, so each term of the series is less than a ninth of the one before
+    let z = if x >= y {
+        ((x - y) * ONE as u128 / (x + y)) as i128
+    } else {
+        -(((y - x) * ONE as u128 / (x + y)) as i128)
+    };
+    let z_squared = z * z / ONE;
+    let mut power = z;
+    let mut series = z;
+    let mut divisor = 1;
+    while power != 0 {
+        power = power * z_squared / ONE;
+        divisor += 2;
+        series += power / divisor;
+    }
+    let doublings = numerator_bits as i128 - denominator_bits as i128;
+    Some(2 * series + doublings * LN_2_36 / SCALE_18)
+}
+
+/// e^exponent scaled by ONE (the exponent being scaled by ONE too), `None` if the exponent exceeds MAX_EXPONENT
+pub fn exp(exponent: i128) -> Option<U256> {
+    if exponent > MAX_EXPONENT {
+        return None;
+    }
+    if exponent < -MAX_EXPONENT {
+        return Some(U256::zero());
+    }
+    //exponent = doublings * ln(2) + remainder with |remainder| <= ln(2)/2
+    let exponent_36 = exponent * SCALE_18;
+    let half_ln_2 = if exponent_36 >= 0 { LN_2_36 / 2 } else { -LN_2_36 / 2 };
+    let doublings = (exponent_36 + half_ln_2) / LN_2_36;
+    let remainder = (exponent_36 - doublings * LN_2_36) / SCALE_18;
+    let mut term = ONE;
+    let mut series = ONE;
+    let mut n = 0;
+    while term != 0 {
+        n += 1;
+        term = term * remainder / ONE / n;
+        series += term;
+    }
+    let series = U256::from(series as u128);
+    Some(if doublings >= 0 {
+        series << doublings as usize
+    } else {
+        series >> (-doublings) as usize
+    })
+}
+
+/// An upper bound of e^exponent (see `exp`)
+pub fn exp_up(exponent: i128) -> Option<U256> {
+    let value = exp(exponent)?;
+    Some(value + value * U256::from(MAX_EXP_ERROR as u128) / U256::from(ONE as u128) + U256::one())
+}
+
+/// A lower bound of e^exponent (see `exp`)
+pub fn exp_down(exponent: i128) -> Option<U256> {
+    let value = exp(exponent)?;
+    Some(value.saturating_sub(value * U256::from(MAX_EXP_ERROR as u128) / U256::from(ONE as u128) + U256::one()))
+}
+
+#[cfg(all(test, not(feature = "test-bpf")))]
+mod tests {
+    use super::*;
+
+    //e, ln(2), ln(10) and e^-1 to 18 decimals
+    const E: i128 = 2_718_281_828_459_045_235;
+    const LN_2: i128 = 693_147_180_559_945_309;
+    const LN_10: i128 = 2_302_585_092_994_045_684;
+    const INVERSE_E: i128 = 367_879_441_171_442_321;
+
+    fn assert_close(value: i128, expected: i128, error: i128) {
+        assert!((value - expected).abs() <= error, "{} {}", value, expected);
+    }
+
+    #[test]
+    fn ln_ratio_of_known_values() {
+        assert_eq!(ln_ratio(1, 1), Some(0));
+        assert_eq!(ln_ratio(u128::MAX, u128::MAX), Some(0));
+        assert_eq!(ln_ratio(0, 1), None);
+        assert_eq!(ln_ratio(1, 0), None);
+        assert_close(ln_ratio(2, 1).unwrap(), LN_2, MAX_LN_ERROR);
+        assert_close(ln_ratio(1, 2).unwrap(), -LN_2, MAX_LN_ERROR);
+        assert_close(ln_ratio(10, 1).unwrap(), LN_10, MAX_LN_ERROR);
+        assert_close(
+            ln_ratio(10u128.pow(30), 10u128.pow(12)).unwrap(),
+            18 * LN_10,
+            MAX_LN_ERROR,
+        );
+        assert_close(ln_ratio(3, 10u128.pow(36) * 3).unwrap(), -36 * LN_10, MAX_LN_ERROR);
+        //ln(1 + 10^-12) = 10^-12 - 5 * 10^-25
+        assert_close(
+            ln_ratio(10u128.pow(12) + 1, 10u128.pow(12)).unwrap(),
+            1_000_000,
+            MAX_LN_ERROR,
+        );
+    }
+
+    #[test]
+    fn exp_of_known_values() {
+        let exp_i128 = |exponent: i128| exp(exponent).unwrap().as_u128() as i128;
+        assert_eq!(exp_i128(0), ONE);
+        assert_close(exp_i128(ONE), E, MAX_EXP_ERROR * 3);
+        assert_close(exp_i128(-ONE), INVERSE_E, MAX_EXP_ERROR);
+        assert_close(exp_i128(LN_2), 2 * ONE, MAX_EXP_ERROR * 2);
+        assert_close(exp_i128(10 * LN_10), 10i128.pow(28), 10i128.pow(12));
+        assert_eq!(exp(MAX_EXPONENT + 1), None);
+        assert_eq!(exp(-MAX_EXPONENT - 1), Some(U256::zero()));
+        assert!(exp(MAX_EXPONENT).is_some());
+    }
+
+    #[test]
+    fn bounds_enclose_exp_of_ln() {
+        for (numerator, denominator) in vec![(1, 1), (3, 7), (10u128.pow(20), 7), (123_456_789, 123_456_788)] {
+            let ln = ln_ratio(numerator, denominator).unwrap();
+            let up = exp_up(ln + MAX_LN_ERROR).unwrap() * U256::from(denominator);
+            let down = exp_down(ln - MAX_LN_ERROR).unwrap() * U256::from(denominator);
+            let expected = U256::from(numerator) * U256::from(ONE as u128);
+            assert!(down <= expected && expected <= up, "{} {} {}", down, expected, up);
+        }
+    }
+}
@@ -0,0 +1,106 @@
+//the pools that MigrateIn moves liquidity out of: two token pools of spl token-swap and of Saber's stable-swap
+//
+//their withdraw instructions (token-swap's WithdrawAllTokenTypes, stable-swap's Withdraw) are encoded directly since
+// the programs' crates require a newer solana-program than the pool is built against. both take the same data and
+// pay out to the same account positions, they only differ in their fee accounts
+
+use borsh::{BorshDeserialize, BorshSerialize};
+use solana_program::{
+    instruction::{AccountMeta, Instruction},
+    pubkey::Pubkey,
+};
+
+type AmountT = u64;
+
+pub mod spl_token_swap {
+    solana_program::declare_id!("SwapsVeCiPHMUAtzQWZw7RjsKjgCjhwU55QGu4U1Szw");
+}
+
+pub mod saber_stable_swap {
+    solana_program::declare_id!("SSwpkEEcbUqx4vtoEByFjSkhKdCT862DNVb52nZg1UZ");
+}
+
+//the tag of WithdrawAllTokenTypes resp. Withdraw
+const WITHDRAW_TAG: u8 = 3;
+
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MigrationSource {
+    TokenSwap,
+    Saber,
+}
+
+impl MigrationSource {
+    /// Indices of the user's token accounts of token a and b among the withdraw instruction's accounts
+    pub const DESTINATION_INDICES: [usize; 2] = [7, 8];
+    /// Index of the user transfer authority among the withdraw instruction's accounts
+    pub const AUTHORITY_INDEX: usize = 2;
+
+    pub fn program_id(&self) -> Pubkey {
+        match self {
+            MigrationSource::TokenSwap => spl_token_swap::id(),
+            MigrationSource::Saber => saber_stable_swap::id(),
+        }
+    }
+
+    /// Number of accounts that the source's withdraw instruction takes
+    pub fn account_count(&self) -> usize {
+        match self {
+            MigrationSource::TokenSwap => 11,
+            MigrationSource::Saber => 12,
+        }
+    }
+
+    /// The withdraw instruction for `pool_token_amount` with the given accounts, accepting any payout since the
+    /// deposit that follows checks the outcome
+    pub fn withdraw_ix(&self, accounts: Vec<AccountMeta>, pool_token_amount: AmountT) -> Instruction {
+        let mut data = vec![WITHDRAW_TAG];
+        data.extend_from_slice(&pool_token_amount.to_le_bytes());
+        //minimum token a and b amounts
+        data.extend_from_slice(&[0; 16]);
+        Instruction {
+            program_id: self.program_id(),
+            accounts,
+            data,
+        }
+    }
+}
+
+/// The accounts of a token-swap or Saber pool that its withdraw instruction takes
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SourcePool {
+    pub source: MigrationSource,
+    pub swap: Pubkey,
+    pub authority: Pubkey,
+    pub pool_mint: Pubkey,
+    pub token_a: Pubkey,
+    pub token_b: Pubkey,
+    /// token-swap's fee account, resp. Saber's admin fee accounts of token a and b
+    pub fee_accounts: Vec<Pubkey>,
+}
+
+impl SourcePool {
+    /// The accounts of the withdraw instruction in the source's order
+    pub fn withdraw_accounts(
+        &self,
+        user_transfer_authority: &Pubkey,
+        user_pool_token_account: &Pubkey,
+        user_token_accounts: [&Pubkey; 2],
+    ) -> Vec<AccountMeta> {
+        let mut accounts = vec![
+            AccountMeta::new_readonly(self.swap, false),
+            AccountMeta::new_readonly(self.authority, false),
+            AccountMeta::new_readonly(*user_transfer_authority, true),
+            AccountMeta::new(self.pool_mint, false),
+            AccountMeta::new(*user_pool_token_account, false),
+            AccountMeta::new(self.token_a, false),
+            AccountMeta::new(self.token_b, false),
+            AccountMeta::new(*user_token_accounts[0], false),
+            AccountMeta::new(*user_token_accounts[1], false),
+        ];
+        for fee_account in self.fee_accounts.iter() {
+            accounts.push(AccountMeta::new(*fee_account, false));
+        }
+        accounts.push(AccountMeta::new_readonly(spl_token::id(), false));
+        accounts
+    }
+}
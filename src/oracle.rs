@@ -0,0 +1,1209 @@
+//price oracles that guard the pool against trading at prices far off the market, e.g. when one of its tokens depegs,
+// and exchange rate sources of rated pools (tokens that aren't worth 1 unit of the asset the pool's tokens track, e.g. a
+// liquid staking token in a pool with SOL)
+//
+//pyth price, switchboard aggregator and spl stake pool accounts are read directly (pyth layout v2, see pyth-sdk-solana's
+// PriceAccount, and switchboard-v2's AggregatorAccountData) since the vendors' crates require a newer solana-program
+// than the pool is built against. only the fields the pool needs are decoded
+
+use crate::{
+    decimal::{DecimalU64, U256},
+    error::PoolError,
+    quote::to_equalized,
+    state::{VirtualPriceState, VIRTUAL_PRICE_EXPO},
+};
+use borsh::{BorshDeserialize, BorshSchema, BorshSerialize};
+use solana_program::{
+    borsh::get_packed_len,
+    clock::{Clock, Epoch, Slot, UnixTimestamp},
+    pubkey::Pubkey,
+};
+use std::convert::TryInto;
+
+//pyth's mainnet-beta program
+pub mod pyth {
+    solana_program::declare_id!("FsJ3A3u2vn5cTVofAjvy6y5kwABJAqYWpe4975bi2epH");
+}
+
+pub mod spl_stake_pool {
+    solana_program::declare_id!("SPoo1Ku8WFXoNDMHPsrGSTSG1Y47rzgn41SLUNakuHy");
+}
+
+pub mod switchboard_v2 {
+    solana_program::declare_id!("SW1TCH7qEPTdLsDHRgPuMQjbQxKdH2aBStViMFnt64f");
+}
+
+type AtomicT = u64;
+type DecT = DecimalU64;
+
+const PYTH_MAGIC: u32 = 0xa1b2c3d4;
+const PYTH_VERSION: u32 = 2;
+const PYTH_PRICE_ACCOUNT: u32 = 3;
+const PYTH_STATUS_TRADING: u32 = 1;
+
+const PYTH_EXPO_OFFSET: usize = 20;
+const PYTH_TIMESTAMP_OFFSET: usize = 96;
+const PYTH_AGG_PRICE_OFFSET: usize = 208;
+const PYTH_AGG_CONF_OFFSET: usize = 216;
+const PYTH_AGG_STATUS_OFFSET: usize = 224;
+const PYTH_AGG_PUB_SLOT_OFFSET: usize = 232;
+const PYTH_MIN_LEN: usize = 240;
+
+const STAKE_POOL_ACCOUNT: u8 = 1;
+const STAKE_POOL_MINT_OFFSET: usize = 162;
+const STAKE_POOL_TOTAL_LAMPORTS_OFFSET: usize = 258;
+const STAKE_POOL_TOKEN_SUPPLY_OFFSET: usize = 266;
+const STAKE_POOL_LAST_UPDATE_EPOCH_OFFSET: usize = 274;
+const STAKE_POOL_MIN_LEN: usize = 282;
+
+//anchor account discriminator, i.e. the first 8 bytes of sha256("account:AggregatorAccountData")
+const SWITCHBOARD_AGGREGATOR_DISCRIMINATOR: [u8; 8] = [217, 230, 65, 101, 201, 162, 27, 125];
+//fields of latest_confirmed_round, the result is a SwitchboardDecimal (i128 mantissa, u32 scale)
+const SWITCHBOARD_NUM_SUCCESS_OFFSET: usize = 341;
+const SWITCHBOARD_ROUND_OPEN_SLOT_OFFSET: usize = 350;
+const SWITCHBOARD_RESULT_MANTISSA_OFFSET: usize = 366;
+const SWITCHBOARD_RESULT_SCALE_OFFSET: usize = 382;
+const SWITCHBOARD_STD_DEVIATION_MANTISSA_OFFSET: usize = 386;
+const SWITCHBOARD_STD_DEVIATION_SCALE_OFFSET: usize = 402;
+const SWITCHBOARD_MIN_LEN: usize = 406;
+
+/// Exchange rates are scaled by 10^RATE_DECIMALS, i.e. RATE_ONE is a rate of 1
+pub const RATE_DECIMALS: u32 = 12;
+pub const RATE_ONE: u64 = 1_000_000_000_000;
+
+/// Basis points of one, e.g. of `OracleLimits::max_conf_bps` or `state::PriceScale::max_step_bps`
+pub const BPS_ONE: u64 = 10_000;
+
+/// Prices of the pool's internal oracle are scaled by 10^EMA_DECIMALS, i.e. EMA_ONE is a price of 1
+pub const EMA_DECIMALS: u32 = 12;
+pub const EMA_ONE: u64 = 1_000_000_000_000;
+/// Seconds it takes the internal oracle's moving average to fully reflect a new price
+pub const EMA_WINDOW: UnixTimestamp = 600;
+
+//prices are scaled to at most 10^18, which keeps values of equalized amounts well within U256
+const MIN_EXPO: i32 = -18;
+
+/// An oracle's aggregate price of a whole token (i.e. not of its atomic unit) as `price * 10^expo`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OraclePrice {
+    pub price: u64,
+    pub conf: u64,
+    pub expo: i32,
+    pub publish_time: UnixTimestamp,
+    pub publish_slot: Slot,
+}
+
+//the loaders check the length of the account data before reading, reads past its end read 0
+fn read_u32(data: &[u8], offset: usize) -> u32 {
+    data.get(offset..offset + 4)
+        .and_then(|bytes| bytes.try_into().ok())
+        .map_or(0, u32::from_le_bytes)
+}
+
+fn read_u64(data: &[u8], offset: usize) -> u64 {
+    data.get(offset..offset + 8)
+        .and_then(|bytes| bytes.try_into().ok())
+        .map_or(0, u64::from_le_bytes)
+}
+
+fn read_i128(data: &[u8], offset: usize) -> i128 {
+    data.get(offset..offset + 16)
+        .and_then(|bytes| bytes.try_into().ok())
+        .map_or(0, i128::from_le_bytes)
+}
+
+/// Decodes the aggregate price of a pyth price account, which has to be currently trading at a positive price
+pub fn load_pyth_price(data: &[u8]) -> Result<OraclePrice, PoolError> {
+    if data.len() < PYTH_MIN_LEN
+        || read_u32(data, 0) != PYTH_MAGIC
+        || read_u32(data, 4) != PYTH_VERSION
+        || read_u32(data, 8) != PYTH_PRICE_ACCOUNT
+    {
+        return Err(PoolError::InvalidOracleAccount);
+    }
+    let expo = read_u32(data, PYTH_EXPO_OFFSET) as i32;
+    if !(MIN_EXPO..=0).contains(&expo) {
+        return Err(PoolError::InvalidOracleAccount);
+    }
+    let price = read_u64(data, PYTH_AGG_PRICE_OFFSET) as i64;
+    if read_u32(data, PYTH_AGG_STATUS_OFFSET) != PYTH_STATUS_TRADING || price <= 0 {
+        return Err(PoolError::OraclePriceUnavailable);
+    }
+    Ok(OraclePrice {
+        price: price as u64,
+        conf: read_u64(data, PYTH_AGG_CONF_OFFSET),
+        expo,
+        publish_time: read_u64(data, PYTH_TIMESTAMP_OFFSET) as i64,
+        publish_slot: read_u64(data, PYTH_AGG_PUB_SLOT_OFFSET),
+    })
+}
+
+/// Governance limits on the readings of a token's oracles (its guard oracle and its rate feed), 0 turns a check off.
+/// Readings outside of them count as unavailable
+#[derive(BorshSerialize, BorshDeserialize, BorshSchema, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct OracleLimits {
+    /// How many slots ago a reading may have been published at most
+    pub max_age: Slot,
+    /// How wide a reading's confidence interval may be at most, in basis points of the reading
+    pub max_conf_bps: u16,
+}
+
+impl OracleLimits {
+    /// Whether governance may bind an oracle with these limits: readings can't be of any age, and a confidence
+    /// interval wider than the reading itself bounds nothing
+    pub fn is_valid(&self) -> bool {
+        self.max_age != 0 && self.max_conf_bps as u64 <= BPS_ONE
+    }
+
+    /// Whether a reading of `value` ± `conf` that was published at `publish_slot` is within the limits at `current_slot`
+    pub fn allow(&self, value: U256, conf: U256, publish_slot: Slot, current_slot: Slot) -> bool {
+        (self.max_age == 0 || current_slot.saturating_sub(publish_slot) <= self.max_age)
+            && (self.max_conf_bps == 0 || conf * U256::from(BPS_ONE) <= value * U256::from(self.max_conf_bps))
+    }
+}
+
+/// What guarded swaps do while one of the pool's guard oracles is unavailable (see `OracleLimits`)
+#[derive(BorshSerialize, BorshDeserialize, BorshSchema, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OracleFallback {
+    /// Swaps fail
+    Reject,
+    /// Swaps go through without the deviation check
+    IgnoreGuard,
+    /// Swaps are checked against the pool's internal oracle (see `marginal_prices`) instead
+    InternalEma,
+}
+
+impl Default for OracleFallback {
+    fn default() -> Self {
+        OracleFallback::Reject
+    }
+}
+
+/// Decodes the latest confirmed result of a switchboard v2 aggregator as a price, which has to be positive
+pub fn load_switchboard_price(data: &[u8]) -> Result<OraclePrice, PoolError> {
+    if data.len() < SWITCHBOARD_MIN_LEN || data[..8] != SWITCHBOARD_AGGREGATOR_DISCRIMINATOR {
+        return Err(PoolError::InvalidOracleAccount);
+    }
+    let mut mantissa = read_i128(data, SWITCHBOARD_RESULT_MANTISSA_OFFSET);
+    let mut scale = read_u32(data, SWITCHBOARD_RESULT_SCALE_OFFSET);
+    //an aggregator that never completed a round has no result yet
+    if read_u32(data, SWITCHBOARD_NUM_SUCCESS_OFFSET) == 0 || mantissa <= 0 {
+        return Err(PoolError::OraclePriceUnavailable);
+    }
+    //drops the digits that don't fit a pyth-like price (u64 at an exponent of at least MIN_EXPO)
+    while mantissa > u64::MAX as i128 || scale > (-MIN_EXPO) as u32 {
+        if scale == 0 {
+            return Err(PoolError::OraclePriceUnavailable);
+        }
+        mantissa /= 10;
+        scale -= 1;
+    }
+    if mantissa == 0 {
+        return Err(PoolError::OraclePriceUnavailable);
+    }
+    //the standard deviation of the oracles' responses plays the part of pyth's confidence interval
+    let std_deviation = U256::from(read_i128(data, SWITCHBOARD_STD_DEVIATION_MANTISSA_OFFSET).max(0) as u128);
+    let std_deviation_scale = read_u32(data, SWITCHBOARD_STD_DEVIATION_SCALE_OFFSET);
+    let conf = if std_deviation_scale > scale {
+        std_deviation / U256::exp10((std_deviation_scale - scale).min(77) as usize)
+    } else {
+        std_deviation * U256::exp10((scale - std_deviation_scale) as usize)
+    };
+    Ok(OraclePrice {
+        price: mantissa as u64,
+        conf: conf.min(U256::from(u64::MAX)).as_u64(),
+        expo: -(scale as i32),
+        publish_time: 0,
+        publish_slot: read_u64(data, SWITCHBOARD_ROUND_OPEN_SLOT_OFFSET),
+    })
+}
+
+/// The kind of price account a token's guard oracle is
+#[derive(BorshSerialize, BorshDeserialize, BorshSchema, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OracleKind {
+    Pyth,
+    Switchboard,
+}
+
+//borsh only deserializes arrays of Default types
+impl Default for OracleKind {
+    fn default() -> Self {
+        OracleKind::Pyth
+    }
+}
+
+impl OracleKind {
+    /// The program that owns the oracle's price accounts
+    pub fn owner(&self) -> Pubkey {
+        match self {
+            OracleKind::Pyth => pyth::id(),
+            OracleKind::Switchboard => switchboard_v2::id(),
+        }
+    }
+
+    /// Decodes the price of a price account of this kind
+    pub fn load_price(&self, data: &[u8]) -> Result<OraclePrice, PoolError> {
+        match self {
+            OracleKind::Pyth => load_pyth_price(data),
+            OracleKind::Switchboard => load_switchboard_price(data),
+        }
+    }
+}
+
+/// A token's guard oracle along with the limits on its readings, see `GovernanceInstruction::SetTokenOracle`
+#[derive(BorshSerialize, BorshDeserialize, BorshSchema, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct TokenOracle {
+    pub kind: OracleKind,
+    /// `Pubkey::default()` removes the token's oracle
+    pub key: Pubkey,
+    pub limits: OracleLimits,
+}
+
+/// The price of a price account of the given kind whose reading is within `limits` at `current_slot`
+pub fn load_checked_price(
+    kind: OracleKind,
+    data: &[u8],
+    limits: &OracleLimits,
+    current_slot: Slot,
+) -> Result<OraclePrice, PoolError> {
+    let price = kind.load_price(data)?;
+    if !limits.allow(
+        U256::from(price.price),
+        U256::from(price.conf),
+        price.publish_slot,
+        current_slot,
+    ) {
+        return Err(PoolError::OraclePriceUnavailable);
+    }
+    Ok(price)
+}
+
+/// Where the exchange rate of a token comes from, i.e. how many units of the asset that the pool's tokens track a whole
+/// token is worth
+#[derive(BorshSerialize, BorshDeserialize, BorshSchema, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RateSource {
+    /// The token tracks the asset 1:1
+    None,
+    /// An spl stake pool whose pool mint is the token, its rate is the pool's lamports per pool token
+    SplStakePool,
+    /// A pyth price account quoting the token in the asset
+    Pyth,
+    /// A switchboard v2 aggregator whose latest result is the token's rate
+    Switchboard,
+    /// A rate set by governance (scaled by 10^RATE_DECIMALS), for tokens without a feed that the pool can read
+    Fixed { rate: u64 },
+    /// The virtual price of another pool (the base pool) whose lp token is the token, as published by its
+    /// `PublishVirtualPrice`, i.e. the token is worth its share of the base pool in the base pool's asset
+    BasePool,
+    /// The exchange rate account (see `WrapperRate`) of a wrapper program whose non-rebasing shares are the token, i.e.
+    /// the pool holds shares of a rebasing or interest bearing token and values them at the underlying they're worth
+    Wrapper,
+}
+
+//borsh only deserializes arrays of Default types
+impl Default for RateSource {
+    fn default() -> Self {
+        RateSource::None
+    }
+}
+
+impl RateSource {
+    /// Whether the rate is read from an account, which instructions that value the pool's balances then have to pass
+    pub fn has_account(&self) -> bool {
+        !matches!(self, RateSource::None | RateSource::Fixed { .. })
+    }
+}
+
+/// A rate (scaled by 10^RATE_DECIMALS) as reported by a rate provider
+pub struct RateReading {
+    pub rate: U256,
+    pub conf: U256,
+    /// `None` for readings that don't age by slot (e.g. stake pools, which are current for the whole epoch)
+    pub publish_slot: Option<Slot>,
+}
+
+impl RateReading {
+    fn exact(rate: U256) -> Self {
+        RateReading {
+            rate,
+            conf: U256::zero(),
+            publish_slot: None,
+        }
+    }
+}
+
+/// Reads a token's exchange rate from the account of a rate provider, adding a provider means implementing this and
+/// mapping a new `RateSource` to it in `load_rate`
+pub trait RateAdapter {
+    /// The rate of the token with mint `token_mint` as reported by the account with the given `owner` and `data`
+    fn read_rate(
+        &self,
+        owner: &Pubkey,
+        data: &[u8],
+        token_mint: &Pubkey,
+        epoch: Epoch,
+    ) -> Result<RateReading, PoolError>;
+}
+
+pub struct SplStakePoolAdapter;
+
+impl RateAdapter for SplStakePoolAdapter {
+    fn read_rate(
+        &self,
+        owner: &Pubkey,
+        data: &[u8],
+        token_mint: &Pubkey,
+        epoch: Epoch,
+    ) -> Result<RateReading, PoolError> {
+        if *owner != spl_stake_pool::id()
+            || data.len() < STAKE_POOL_MIN_LEN
+            || data[0] != STAKE_POOL_ACCOUNT
+            || data[STAKE_POOL_MINT_OFFSET..STAKE_POOL_MINT_OFFSET + 32] != token_mint.as_ref()[..]
+        {
+            return Err(PoolError::InvalidRateAccount);
+        }
+        //stake pools only account for staking rewards once somebody updates them in the new epoch
+        let token_supply = read_u64(data, STAKE_POOL_TOKEN_SUPPLY_OFFSET);
+        if read_u64(data, STAKE_POOL_LAST_UPDATE_EPOCH_OFFSET) != epoch || token_supply == 0 {
+            return Err(PoolError::RateUnavailable);
+        }
+        Ok(RateReading::exact(
+            U256::from(read_u64(data, STAKE_POOL_TOTAL_LAMPORTS_OFFSET)) * U256::from(RATE_ONE)
+                / U256::from(token_supply),
+        ))
+    }
+}
+
+pub struct PythAdapter;
+
+impl RateAdapter for PythAdapter {
+    fn read_rate(
+        &self,
+        _owner: &Pubkey,
+        data: &[u8],
+        _token_mint: &Pubkey,
+        _epoch: Epoch,
+    ) -> Result<RateReading, PoolError> {
+        let price = load_pyth_price(data)?;
+        Ok(RateReading {
+            rate: scale_to_rate(U256::from(price.price), price.expo),
+            conf: scale_to_rate(U256::from(price.conf), price.expo),
+            publish_slot: Some(price.publish_slot),
+        })
+    }
+}
+
+pub struct SwitchboardAdapter;
+
+impl RateAdapter for SwitchboardAdapter {
+    fn read_rate(
+        &self,
+        owner: &Pubkey,
+        data: &[u8],
+        _token_mint: &Pubkey,
+        _epoch: Epoch,
+    ) -> Result<RateReading, PoolError> {
+        if *owner != switchboard_v2::id() {
+            return Err(PoolError::InvalidRateAccount);
+        }
+        let price = load_switchboard_price(data).map_err(|error| match error {
+            PoolError::InvalidOracleAccount => PoolError::InvalidRateAccount,
+            _ => PoolError::RateUnavailable,
+        })?;
+        Ok(RateReading {
+            rate: scale_to_rate(U256::from(price.price), price.expo),
+            conf: scale_to_rate(U256::from(price.conf), price.expo),
+            publish_slot: Some(price.publish_slot),
+        })
+    }
+}
+
+//SetRateSource checks that the rate account is the virtual price account of a pool with the token as its lp mint, a
+// program address that only the base pool's program can write to
+pub struct BasePoolAdapter;
+
+impl RateAdapter for BasePoolAdapter {
+    fn read_rate(
+        &self,
+        _owner: &Pubkey,
+        data: &[u8],
+        _token_mint: &Pubkey,
+        _epoch: Epoch,
+    ) -> Result<RateReading, PoolError> {
+        if data.len() != get_packed_len::<VirtualPriceState>() {
+            return Err(PoolError::InvalidRateAccount);
+        }
+        let virtual_price = VirtualPriceState::try_from_slice(data).or(Err(PoolError::InvalidRateAccount))?;
+        if virtual_price.is_paused || virtual_price.expo != VIRTUAL_PRICE_EXPO {
+            return Err(PoolError::RateUnavailable);
+        }
+        Ok(RateReading {
+            rate: scale_to_rate(U256::from(virtual_price.price), virtual_price.expo),
+            conf: scale_to_rate(U256::from(virtual_price.conf), virtual_price.expo),
+            publish_slot: Some(virtual_price.publish_slot),
+        })
+    }
+}
+
+/// The exchange rate account that a wrapper program publishes for its shares (see `RateSource::Wrapper`):
+/// `underlying_amount` atomic units of the underlying token back `share_supply` atomic shares. The shares and the
+/// underlying token can have different decimals, the rate is that of whole tokens
+#[derive(BorshSerialize, BorshDeserialize, BorshSchema, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WrapperRate {
+    pub share_mint: Pubkey,
+    pub share_decimals: u8,
+    pub underlying_decimals: u8,
+    pub share_supply: u64,
+    pub underlying_amount: u64,
+    pub publish_slot: Slot,
+}
+
+//more decimals than a u64 amount has digits
+const MAX_WRAPPER_DECIMALS: u8 = 19;
+
+impl WrapperRate {
+    /// Whole underlying tokens per whole share (scaled by 10^RATE_DECIMALS), `None` without shares
+    pub fn rate(&self) -> Option<U256> {
+        if self.share_supply == 0
+            || self.share_decimals > MAX_WRAPPER_DECIMALS
+            || self.underlying_decimals > MAX_WRAPPER_DECIMALS
+        {
+            return None;
+        }
+        Some(
+            U256::from(self.underlying_amount) * U256::exp10(self.share_decimals as usize) * U256::from(RATE_ONE)
+                / (U256::from(self.share_supply) * U256::exp10(self.underlying_decimals as usize)),
+        )
+    }
+}
+
+//SetRateSource binds the account, a wrapper's shares are only worth what its own account reports
+pub struct WrapperAdapter;
+
+impl RateAdapter for WrapperAdapter {
+    fn read_rate(
+        &self,
+        _owner: &Pubkey,
+        data: &[u8],
+        token_mint: &Pubkey,
+        _epoch: Epoch,
+    ) -> Result<RateReading, PoolError> {
+        if data.len() != get_packed_len::<WrapperRate>() {
+            return Err(PoolError::InvalidRateAccount);
+        }
+        let wrapper_rate = WrapperRate::try_from_slice(data).or(Err(PoolError::InvalidRateAccount))?;
+        if wrapper_rate.share_mint != *token_mint {
+            return Err(PoolError::InvalidRateAccount);
+        }
+        Ok(RateReading {
+            rate: wrapper_rate.rate().ok_or(PoolError::RateUnavailable)?,
+            conf: U256::zero(),
+            publish_slot: Some(wrapper_rate.publish_slot),
+        })
+    }
+}
+
+pub struct FixedRateAdapter(pub u64);
+
+impl RateAdapter for FixedRateAdapter {
+    fn read_rate(
+        &self,
+        _owner: &Pubkey,
+        _data: &[u8],
+        _token_mint: &Pubkey,
+        _epoch: Epoch,
+    ) -> Result<RateReading, PoolError> {
+        Ok(RateReading::exact(U256::from(self.0)))
+    }
+}
+
+//value * 10^expo scaled by 10^RATE_DECIMALS
+fn scale_to_rate(value: U256, expo: i32) -> U256 {
+    let shift = RATE_DECIMALS as i32 + expo;
+    if shift >= 0 {
+        value * U256::exp10(shift as usize)
+    } else {
+        value / U256::exp10((-shift) as usize)
+    }
+}
+
+/// The exchange rate of the token with mint `token_mint` (scaled by 10^RATE_DECIMALS) as reported by the account with
+/// the given `owner` and `data`, sources without an account (see `RateSource::has_account`) ignore both. Readings
+/// outside of the token's `limits` are unavailable
+pub fn load_rate(
+    source: RateSource,
+    owner: &Pubkey,
+    data: &[u8],
+    token_mint: &Pubkey,
+    clock: &Clock,
+    limits: &OracleLimits,
+) -> Result<u64, PoolError> {
+    let fixed;
+    let adapter: &dyn RateAdapter = match source {
+        RateSource::None => return Ok(RATE_ONE),
+        RateSource::SplStakePool => &SplStakePoolAdapter,
+        RateSource::Pyth => &PythAdapter,
+        RateSource::Switchboard => &SwitchboardAdapter,
+        RateSource::Fixed { rate } => {
+            fixed = FixedRateAdapter(rate);
+            &fixed
+        }
+        RateSource::BasePool => &BasePoolAdapter,
+        RateSource::Wrapper => &WrapperAdapter,
+    };
+    let reading = adapter.read_rate(owner, data, token_mint, clock.epoch)?;
+    let publish_slot = reading.publish_slot.unwrap_or(clock.slot);
+    if reading.rate.is_zero()
+        || reading.rate > U256::from(u64::MAX)
+        || !limits.allow(reading.rate, reading.conf, publish_slot, clock.slot)
+    {
+        return Err(PoolError::RateUnavailable);
+    }
+    Ok(reading.rate.as_u64())
+}
+
+/// The marginal prices of the pool's tokens in units of its first token (scaled by 10^EMA_DECIMALS) at the given
+/// (equalized and rated) balances and depth, i.e. the exchange rates of infinitesimal swaps on the invariant's curve.
+/// `None` for an empty pool or if the balances are so far apart that the prices don't fit
+pub fn marginal_prices<const TOKEN_COUNT: usize>(
+    balances: &[u128; TOKEN_COUNT],
+    amp_factor: DecT,
+    depth: u128,
+) -> Option<[u64; TOKEN_COUNT]> {
+    if depth == 0 || balances.iter().any(|balance| *balance == 0) {
+        return None;
+    }
+    //the invariant is amp * (sum - depth) + depth - depth^(n+1) / (n^n * prod), whose partial derivative by balance i
+    // is amp + depth * decay / balance_i with decay = depth^n / (n^n * prod). a swap trades tokens at the ratio of the
+    // derivatives
+    let one = U256::exp10(18);
+    let depth = U256::from(depth);
+    let mut decay = one;
+    for balance in balances.iter() {
+        decay = decay.checked_mul(depth)? / (U256::from(*balance) * U256::from(TOKEN_COUNT));
+    }
+    let amp = U256::from(amp_factor.get_raw()) * one / U256::exp10(amp_factor.get_decimals() as usize);
+    let slopes = balances
+        .iter()
+        .map(|balance| Some(amp + decay.checked_mul(depth)? / U256::from(*balance)))
+        .collect::<Option<Vec<_>>>()?;
+    let mut prices = [0; TOKEN_COUNT];
+    for i in 0..TOKEN_COUNT {
+        let price = slopes[i].checked_mul(U256::from(EMA_ONE))? / slopes[0];
+        if price > U256::from(u64::MAX) {
+            return None;
+        }
+        prices[i] = price.as_u64();
+    }
+    Some(prices)
+}
+
+/// Moves the moving average `ema_prices` towards `last_prices` for the `elapsed` seconds that the pool traded at them
+pub fn update_ema<const TOKEN_COUNT: usize>(
+    ema_prices: &mut [u64; TOKEN_COUNT],
+    last_prices: &[u64; TOKEN_COUNT],
+    elapsed: UnixTimestamp,
+) {
+    let weight = elapsed.max(0).min(EMA_WINDOW) as u128;
+    let window = EMA_WINDOW as u128;
+    for (ema_price, last_price) in ema_prices.iter_mut().zip(last_prices.iter()) {
+        *ema_price = ((*ema_price as u128 * (window - weight) + *last_price as u128 * weight) / window) as u64;
+    }
+}
+
+/// The internal oracle's prices as oracle prices of whole tokens in a unit shared by all of them, `rates` are the
+/// exchange rates the marginal prices were computed at
+pub fn ema_oracle_prices<const TOKEN_COUNT: usize>(
+    ema_prices: &[u64; TOKEN_COUNT],
+    rates: &[u64; TOKEN_COUNT],
+) -> [OraclePrice; TOKEN_COUNT] {
+    let mut prices = [OraclePrice {
+        price: 0,
+        conf: 0,
+        expo: -(EMA_DECIMALS as i32),
+        publish_time: 0,
+        publish_slot: 0,
+    }; TOKEN_COUNT];
+    for i in 0..TOKEN_COUNT {
+        prices[i].price = (ema_prices[i] as u128 * rates[i] as u128 / RATE_ONE as u128) as u64;
+    }
+    prices
+}
+
+/// The prices that guarded swaps are checked against given the result of loading the guard oracles' prices (with
+/// `load_checked_price`), `None` if swaps go through unchecked because of the pool's fallback
+pub fn guard_prices<const TOKEN_COUNT: usize>(
+    loaded_prices: Result<[OraclePrice; TOKEN_COUNT], PoolError>,
+    fallback: OracleFallback,
+    ema_prices: &[u64; TOKEN_COUNT],
+    rates: &[u64; TOKEN_COUNT],
+) -> Result<Option<[OraclePrice; TOKEN_COUNT]>, PoolError> {
+    match loaded_prices {
+        Ok(prices) => Ok(Some(prices)),
+        Err(PoolError::OraclePriceUnavailable) => match fallback {
+            OracleFallback::Reject => Err(PoolError::OraclePriceUnavailable),
+            OracleFallback::IgnoreGuard => Ok(None),
+            OracleFallback::InternalEma => Ok(Some(ema_oracle_prices(ema_prices, rates))),
+        },
+        Err(error) => Err(error),
+    }
+}
+
+//the value of the given amounts at oracle prices, in a unit shared by all values computed with the same min_expo
+fn value<const TOKEN_COUNT: usize>(
+    amounts: &[AtomicT; TOKEN_COUNT],
+    equalizers: &[u8; TOKEN_COUNT],
+    prices: &[OraclePrice; TOKEN_COUNT],
+    min_expo: i32,
+) -> U256 {
+    let mut value = U256::zero();
+    for i in 0..TOKEN_COUNT {
+        if amounts[i] > 0 {
+            let price = U256::from(prices[i].price) * U256::exp10((prices[i].expo - min_expo) as usize);
+            value += U256::from(to_equalized(amounts[i], equalizers[i]).as_u128()) * price;
+        }
+    }
+    value
+}
+
+/// Fails if the value of the output amounts deviates from the value of the input amounts (both at oracle prices) by
+/// more than `max_deviation`, i.e. if the swap's execution price is too far off the oracle cross-rate
+pub fn check_swap_deviation<const TOKEN_COUNT: usize>(
+    input_amounts: &[AtomicT; TOKEN_COUNT],
+    output_amounts: &[AtomicT; TOKEN_COUNT],
+    equalizers: &[u8; TOKEN_COUNT],
+    prices: &[OraclePrice; TOKEN_COUNT],
+    max_deviation: DecT,
+) -> Result<(), PoolError> {
+    let min_expo = prices.iter().map(|price| price.expo).min().unwrap_or(0);
+    let input_value = value(input_amounts, equalizers, prices, min_expo);
+    let output_value = value(output_amounts, equalizers, prices, min_expo);
+
+    //|output_value / input_value - 1| <= max_deviation, multiplied by input_value * 10^decimals
+    let one = U256::exp10(max_deviation.get_decimals() as usize);
+    let deviation = U256::from(max_deviation.get_raw());
+    let scaled_output = output_value * one;
+    if scaled_output > input_value * (one + deviation) || scaled_output + input_value * deviation < input_value * one {
+        return Err(PoolError::OracleDeviationExceeded);
+    }
+    Ok(())
+}
+
+/// Whether the price is further than `band` from 1, i.e. off the peg of a token that tracks the oracle's quote currency
+pub fn is_off_peg(price: &OraclePrice, band: DecT) -> bool {
+    //|price * 10^expo - 1| > band, multiplied by 10^-expo * 10^decimals
+    let one = U256::exp10((-price.expo) as usize);
+    let distance = if U256::from(price.price) > one {
+        U256::from(price.price) - one
+    } else {
+        one - U256::from(price.price)
+    };
+    distance * U256::exp10(band.get_decimals() as usize) > U256::from(band.get_raw()) * one
+}
+
+#[cfg(all(test, not(feature = "test-bpf")))]
+mod tests {
+    use super::*;
+    use crate::invariant::{AmountT, Invariant};
+
+    fn pyth_account(price: i64, expo: i32, status: u32) -> Vec<u8> {
+        let mut data = vec![0u8; 3312];
+        data[0..4].copy_from_slice(&PYTH_MAGIC.to_le_bytes());
+        data[4..8].copy_from_slice(&PYTH_VERSION.to_le_bytes());
+        data[8..12].copy_from_slice(&PYTH_PRICE_ACCOUNT.to_le_bytes());
+        data[PYTH_EXPO_OFFSET..PYTH_EXPO_OFFSET + 4].copy_from_slice(&expo.to_le_bytes());
+        data[PYTH_TIMESTAMP_OFFSET..PYTH_TIMESTAMP_OFFSET + 8].copy_from_slice(&1234i64.to_le_bytes());
+        data[PYTH_AGG_PRICE_OFFSET..PYTH_AGG_PRICE_OFFSET + 8].copy_from_slice(&price.to_le_bytes());
+        data[PYTH_AGG_CONF_OFFSET..PYTH_AGG_CONF_OFFSET + 8].copy_from_slice(&5u64.to_le_bytes());
+        data[PYTH_AGG_STATUS_OFFSET..PYTH_AGG_STATUS_OFFSET + 4].copy_from_slice(&status.to_le_bytes());
+        data[PYTH_AGG_PUB_SLOT_OFFSET..PYTH_AGG_PUB_SLOT_OFFSET + 8].copy_from_slice(&77u64.to_le_bytes());
+        data
+    }
+
+    fn price(price: u64, expo: i32) -> OraclePrice {
+        OraclePrice {
+            price,
+            conf: 0,
+            expo,
+            publish_time: 0,
+            publish_slot: 0,
+        }
+    }
+
+    #[test]
+    fn load_pyth() {
+        let loaded = load_pyth_price(&pyth_account(99_980_000, -8, PYTH_STATUS_TRADING)).unwrap();
+        assert_eq!(
+            loaded,
+            OraclePrice {
+                price: 99_980_000,
+                conf: 5,
+                expo: -8,
+                publish_time: 1234,
+                publish_slot: 77,
+            }
+        );
+
+        assert_eq!(
+            load_pyth_price(&pyth_account(99_980_000, -8, 2)),
+            Err(PoolError::OraclePriceUnavailable)
+        );
+        assert_eq!(
+            load_pyth_price(&pyth_account(-1, -8, PYTH_STATUS_TRADING)),
+            Err(PoolError::OraclePriceUnavailable)
+        );
+        assert_eq!(
+            load_pyth_price(&pyth_account(1, 2, PYTH_STATUS_TRADING)),
+            Err(PoolError::InvalidOracleAccount)
+        );
+        let mut wrong_type = pyth_account(1, -8, PYTH_STATUS_TRADING);
+        wrong_type[8] = 2;
+        assert_eq!(load_pyth_price(&wrong_type), Err(PoolError::InvalidOracleAccount));
+        assert_eq!(load_pyth_price(&[0; 100]), Err(PoolError::InvalidOracleAccount));
+    }
+
+    #[test]
+    fn swap_deviation() {
+        let one_percent = DecT::new(1, 2).unwrap();
+        //6 and 8 decimals, i.e. the second token's equalizer is 0
+        let equalizers = [2, 0];
+        let at_peg = [price(100_000_000, -8), price(1_000_000, -6)];
+        let check = |input, output, prices: &[OraclePrice; 2]| {
+            check_swap_deviation(&input, &output, &equalizers, prices, one_percent)
+        };
+
+        assert!(check([1_000_000, 0], [0, 100_000_000], &at_peg).is_ok());
+        assert!(check([1_000_000, 0], [0, 99_000_000], &at_peg).is_ok());
+        assert!(check([1_000_000, 0], [0, 101_000_000], &at_peg).is_ok());
+        assert_eq!(
+            check([1_000_000, 0], [0, 98_999_999], &at_peg),
+            Err(PoolError::OracleDeviationExceeded)
+        );
+        assert_eq!(
+            check([1_000_000, 0], [0, 101_000_001], &at_peg),
+            Err(PoolError::OracleDeviationExceeded)
+        );
+
+        //the first token depegged to 0.5, swapping it 1:1 drains the pool
+        let depegged = [price(50_000_000, -8), price(1_000_000, -6)];
+        assert_eq!(
+            check([1_000_000, 0], [0, 100_000_000], &depegged),
+            Err(PoolError::OracleDeviationExceeded)
+        );
+        assert!(check([1_000_000, 0], [0, 50_000_000], &depegged).is_ok());
+    }
+
+    fn stake_pool_account(token_mint: &Pubkey, total_lamports: u64, token_supply: u64, epoch: Epoch) -> Vec<u8> {
+        let mut data = vec![0u8; 611];
+        data[0] = STAKE_POOL_ACCOUNT;
+        data[STAKE_POOL_MINT_OFFSET..STAKE_POOL_MINT_OFFSET + 32].copy_from_slice(token_mint.as_ref());
+        data[STAKE_POOL_TOTAL_LAMPORTS_OFFSET..STAKE_POOL_TOTAL_LAMPORTS_OFFSET + 8]
+            .copy_from_slice(&total_lamports.to_le_bytes());
+        data[STAKE_POOL_TOKEN_SUPPLY_OFFSET..STAKE_POOL_TOKEN_SUPPLY_OFFSET + 8]
+            .copy_from_slice(&token_supply.to_le_bytes());
+        data[STAKE_POOL_LAST_UPDATE_EPOCH_OFFSET..STAKE_POOL_LAST_UPDATE_EPOCH_OFFSET + 8]
+            .copy_from_slice(&epoch.to_le_bytes());
+        data
+    }
+
+    fn clock_at(epoch: Epoch, slot: Slot) -> Clock {
+        Clock {
+            epoch,
+            slot,
+            ..Clock::default()
+        }
+    }
+
+    fn switchboard_account(mantissa: i128, scale: u32, num_success: u32) -> Vec<u8> {
+        let mut data = vec![0u8; 3851];
+        data[..8].copy_from_slice(&SWITCHBOARD_AGGREGATOR_DISCRIMINATOR);
+        data[SWITCHBOARD_NUM_SUCCESS_OFFSET..SWITCHBOARD_NUM_SUCCESS_OFFSET + 4]
+            .copy_from_slice(&num_success.to_le_bytes());
+        data[SWITCHBOARD_RESULT_MANTISSA_OFFSET..SWITCHBOARD_RESULT_MANTISSA_OFFSET + 16]
+            .copy_from_slice(&mantissa.to_le_bytes());
+        data[SWITCHBOARD_RESULT_SCALE_OFFSET..SWITCHBOARD_RESULT_SCALE_OFFSET + 4]
+            .copy_from_slice(&scale.to_le_bytes());
+        data
+    }
+
+    #[test]
+    fn rates() {
+        let mint = Pubkey::new_unique();
+        let no_limits = OracleLimits::default();
+        let stake_pool = |data: &[u8], epoch| {
+            load_rate(
+                RateSource::SplStakePool,
+                &spl_stake_pool::id(),
+                data,
+                &mint,
+                &clock_at(epoch, 0),
+                &no_limits,
+            )
+        };
+        assert_eq!(
+            stake_pool(&stake_pool_account(&mint, 1_100, 1_000, 7), 7),
+            Ok(RATE_ONE / 10 * 11)
+        );
+        assert_eq!(
+            stake_pool(&stake_pool_account(&mint, 1_100, 1_000, 6), 7),
+            Err(PoolError::RateUnavailable)
+        );
+        assert_eq!(
+            stake_pool(&stake_pool_account(&mint, 0, 0, 7), 7),
+            Err(PoolError::RateUnavailable)
+        );
+        assert_eq!(
+            stake_pool(&stake_pool_account(&Pubkey::new_unique(), 1_100, 1_000, 7), 7),
+            Err(PoolError::InvalidRateAccount)
+        );
+        assert_eq!(
+            load_rate(
+                RateSource::SplStakePool,
+                &Pubkey::new_unique(),
+                &stake_pool_account(&mint, 1_100, 1_000, 7),
+                &mint,
+                &clock_at(7, 0),
+                &no_limits
+            ),
+            Err(PoolError::InvalidRateAccount)
+        );
+
+        let pyth = |price, expo| {
+            load_rate(
+                RateSource::Pyth,
+                &Pubkey::new_unique(),
+                &pyth_account(price, expo, PYTH_STATUS_TRADING),
+                &mint,
+                &clock_at(0, 0),
+                &no_limits,
+            )
+        };
+        assert_eq!(pyth(105_000_000, -8), Ok(RATE_ONE / 100 * 105));
+        assert_eq!(pyth(1_050_000_000_000_000_000, -18), Ok(RATE_ONE / 100 * 105));
+        assert_eq!(pyth(1, -18), Err(PoolError::RateUnavailable));
+
+        let switchboard = |data: &[u8]| {
+            load_rate(
+                RateSource::Switchboard,
+                &switchboard_v2::id(),
+                data,
+                &mint,
+                &clock_at(0, 0),
+                &no_limits,
+            )
+        };
+        assert_eq!(switchboard(&switchboard_account(105, 2, 3)), Ok(RATE_ONE / 100 * 105));
+        assert_eq!(
+            switchboard(&switchboard_account(1_050_000_000_000_000_000_000_000_000, 27, 3)),
+            Ok(RATE_ONE / 100 * 105)
+        );
+        assert_eq!(
+            switchboard(&switchboard_account(105, 2, 0)),
+            Err(PoolError::RateUnavailable)
+        );
+        assert_eq!(
+            switchboard(&switchboard_account(-105, 2, 3)),
+            Err(PoolError::RateUnavailable)
+        );
+        let mut wrong_discriminator = switchboard_account(105, 2, 3);
+        wrong_discriminator[0] = 0;
+        assert_eq!(switchboard(&wrong_discriminator), Err(PoolError::InvalidRateAccount));
+        assert_eq!(
+            load_rate(
+                RateSource::Switchboard,
+                &Pubkey::new_unique(),
+                &switchboard_account(105, 2, 3),
+                &mint,
+                &clock_at(0, 0),
+                &no_limits
+            ),
+            Err(PoolError::InvalidRateAccount)
+        );
+
+        assert_eq!(
+            load_rate(
+                RateSource::None,
+                &Pubkey::default(),
+                &[],
+                &mint,
+                &clock_at(0, 0),
+                &no_limits
+            ),
+            Ok(RATE_ONE)
+        );
+        let fixed = |rate| {
+            load_rate(
+                RateSource::Fixed { rate },
+                &Pubkey::default(),
+                &[],
+                &mint,
+                &clock_at(0, 0),
+                &no_limits,
+            )
+        };
+        assert_eq!(fixed(RATE_ONE / 10 * 11), Ok(RATE_ONE / 10 * 11));
+        assert_eq!(fixed(0), Err(PoolError::RateUnavailable));
+        assert!(!RateSource::Fixed { rate: RATE_ONE }.has_account());
+        assert!(RateSource::Switchboard.has_account());
+    }
+
+    #[test]
+    fn base_pool_rate() {
+        let mint = Pubkey::new_unique();
+        let virtual_price = |price, is_paused| {
+            VirtualPriceState {
+                pool: Pubkey::new_unique(),
+                price,
+                conf: 0,
+                expo: VIRTUAL_PRICE_EXPO,
+                publish_time: 0,
+                publish_slot: 90,
+                is_paused,
+                lp_supply: 1_000,
+                depth: 1_000,
+            }
+            .try_to_vec()
+            .unwrap()
+        };
+        let rate = |data: &[u8], limits: &OracleLimits| {
+            load_rate(
+                RateSource::BasePool,
+                &Pubkey::new_unique(),
+                data,
+                &mint,
+                &clock_at(0, 100),
+                limits,
+            )
+        };
+        let no_limits = OracleLimits::default();
+        assert_eq!(
+            rate(&virtual_price(RATE_ONE / 100 * 102, false), &no_limits),
+            Ok(RATE_ONE / 100 * 102)
+        );
+        assert_eq!(
+            rate(&virtual_price(RATE_ONE, true), &no_limits),
+            Err(PoolError::RateUnavailable)
+        );
+        let max_age = OracleLimits {
+            max_age: 5,
+            max_conf_bps: 0,
+        };
+        assert_eq!(
+            rate(&virtual_price(RATE_ONE, false), &max_age),
+            Err(PoolError::RateUnavailable)
+        );
+        assert_eq!(rate(&[0; 20], &no_limits), Err(PoolError::InvalidRateAccount));
+        assert!(RateSource::BasePool.has_account());
+    }
+
+    #[test]
+    fn wrapper_rate() {
+        let mint = Pubkey::new_unique();
+        let wrapper_rate = WrapperRate {
+            share_mint: mint,
+            share_decimals: 6,
+            underlying_decimals: 9,
+            share_supply: 1_000_000,
+            underlying_amount: 1_050_000_000,
+            publish_slot: 100,
+        };
+        let rate = |wrapper_rate: WrapperRate| {
+            load_rate(
+                RateSource::Wrapper,
+                &Pubkey::new_unique(),
+                &wrapper_rate.try_to_vec().unwrap(),
+                &mint,
+                &clock_at(0, 100),
+                &OracleLimits::default(),
+            )
+        };
+        //a whole share backed by 1.05 whole underlying tokens, whatever their decimals
+        assert_eq!(rate(wrapper_rate), Ok(RATE_ONE / 100 * 105));
+        assert_eq!(
+            rate(WrapperRate {
+                share_decimals: 9,
+                share_supply: 1_000_000_000,
+                ..wrapper_rate
+            }),
+            Ok(RATE_ONE / 100 * 105)
+        );
+        assert_eq!(
+            rate(WrapperRate {
+                share_supply: 0,
+                ..wrapper_rate
+            }),
+            Err(PoolError::RateUnavailable)
+        );
+        assert_eq!(
+            rate(WrapperRate {
+                share_mint: Pubkey::new_unique(),
+                ..wrapper_rate
+            }),
+            Err(PoolError::InvalidRateAccount)
+        );
+        assert!(RateSource::Wrapper.has_account());
+    }
+
+    #[test]
+    fn switchboard_prices() {
+        let price = load_switchboard_price(&switchboard_account(105, 2, 3)).unwrap();
+        assert_eq!((price.price, price.expo, price.conf), (105, -2, 0));
+        //digits beyond what a u64 at 10^-18 holds are dropped
+        let price = load_switchboard_price(&switchboard_account(1_050_000_000_000_000_000_000_000_001, 27, 3)).unwrap();
+        assert_eq!((price.price, price.expo), (1_050_000_000_000_000_000, -18));
+        assert_eq!(
+            load_switchboard_price(&switchboard_account(i128::MAX, 0, 3)),
+            Err(PoolError::OraclePriceUnavailable)
+        );
+        assert_eq!(
+            load_switchboard_price(&switchboard_account(105, 2, 0)),
+            Err(PoolError::OraclePriceUnavailable)
+        );
+        assert_eq!(load_switchboard_price(&[0; 100]), Err(PoolError::InvalidOracleAccount));
+
+        let mut unsure = switchboard_account(105, 2, 3);
+        unsure[SWITCHBOARD_STD_DEVIATION_MANTISSA_OFFSET..SWITCHBOARD_STD_DEVIATION_MANTISSA_OFFSET + 16]
+            .copy_from_slice(&5i128.to_le_bytes());
+        unsure[SWITCHBOARD_STD_DEVIATION_SCALE_OFFSET..SWITCHBOARD_STD_DEVIATION_SCALE_OFFSET + 4]
+            .copy_from_slice(&3u32.to_le_bytes());
+        assert_eq!(OracleKind::Switchboard.load_price(&unsure).unwrap().conf, 0);
+        unsure[SWITCHBOARD_STD_DEVIATION_SCALE_OFFSET..SWITCHBOARD_STD_DEVIATION_SCALE_OFFSET + 4]
+            .copy_from_slice(&1u32.to_le_bytes());
+        assert_eq!(OracleKind::Switchboard.load_price(&unsure).unwrap().conf, 50);
+        assert_eq!(OracleKind::Switchboard.owner(), switchboard_v2::id());
+    }
+
+    #[test]
+    fn limits() {
+        let limits = OracleLimits {
+            max_age: 10,
+            max_conf_bps: 1,
+        };
+        //published at slot 77 with a confidence of 5
+        let account = pyth_account(99_980_000, -8, PYTH_STATUS_TRADING);
+        assert!(load_checked_price(OracleKind::Pyth, &account, &limits, 87).is_ok());
+        assert_eq!(
+            load_checked_price(OracleKind::Pyth, &account, &limits, 88),
+            Err(PoolError::OraclePriceUnavailable)
+        );
+        assert!(load_checked_price(OracleKind::Pyth, &account, &OracleLimits::default(), 1_000_000).is_ok());
+
+        let mut unsure = pyth_account(100_000_000, -8, PYTH_STATUS_TRADING);
+        unsure[PYTH_AGG_CONF_OFFSET..PYTH_AGG_CONF_OFFSET + 8].copy_from_slice(&10_000u64.to_le_bytes());
+        assert!(load_checked_price(OracleKind::Pyth, &unsure, &limits, 77).is_ok());
+        unsure[PYTH_AGG_CONF_OFFSET..PYTH_AGG_CONF_OFFSET + 8].copy_from_slice(&10_001u64.to_le_bytes());
+        assert_eq!(
+            load_checked_price(OracleKind::Pyth, &unsure, &limits, 77),
+            Err(PoolError::OraclePriceUnavailable)
+        );
+
+        //rates that age by slot are subject to the same limits, the others aren't
+        let mint = Pubkey::new_unique();
+        let rate = |source, owner: &Pubkey, data: &[u8], slot| {
+            load_rate(source, owner, data, &mint, &clock_at(7, slot), &limits)
+        };
+        assert!(rate(RateSource::Pyth, &Pubkey::new_unique(), &account, 87).is_ok());
+        assert_eq!(
+            rate(RateSource::Pyth, &Pubkey::new_unique(), &account, 88),
+            Err(PoolError::RateUnavailable)
+        );
+        assert_eq!(
+            rate(
+                RateSource::Switchboard,
+                &switchboard_v2::id(),
+                &switchboard_account(105, 2, 3),
+                11
+            ),
+            Err(PoolError::RateUnavailable)
+        );
+        let stake_pool = stake_pool_account(&mint, 1_100, 1_000, 7);
+        assert!(rate(RateSource::SplStakePool, &spl_stake_pool::id(), &stake_pool, 1_000).is_ok());
+    }
+
+    #[test]
+    fn internal_oracle() {
+        let amp = DecT::from(1000);
+        //a balanced pool trades 1:1, its depth is the sum of the balances
+        assert_eq!(marginal_prices(&[1_000_000; 3], amp, 3_000_000), Some([EMA_ONE; 3]));
+        assert_eq!(marginal_prices(&[1_000_000, 0, 1_000_000], amp, 2_000_000), None);
+
+        //the abundant token is cheaper, the scarce one pricier, and the flatter the curve the closer to 1
+        let balances = [1_800_000, 200_000, 1_000_000];
+        let depth = Invariant::<3>::depth(&balances.map(AmountT::from), amp)
+            .unwrap()
+            .as_u128();
+        let prices = marginal_prices(&balances, amp, depth).unwrap();
+        assert_eq!(prices[0], EMA_ONE);
+        assert!(prices[1] > prices[2] && prices[2] > EMA_ONE, "{:?}", prices);
+        let flat_depth = Invariant::<3>::depth(&balances.map(AmountT::from), DecT::from(1_000_000))
+            .unwrap()
+            .as_u128();
+        let flat_prices = marginal_prices(&balances, DecT::from(1_000_000), flat_depth).unwrap();
+        assert!(
+            flat_prices[1] < prices[1] && flat_prices[1] > EMA_ONE,
+            "{:?}",
+            flat_prices
+        );
+
+        let mut ema_prices = [EMA_ONE, EMA_ONE];
+        let last_prices = [EMA_ONE, EMA_ONE / 10 * 9];
+        update_ema(&mut ema_prices, &last_prices, 0);
+        assert_eq!(ema_prices, [EMA_ONE, EMA_ONE]);
+        update_ema(&mut ema_prices, &last_prices, EMA_WINDOW / 2);
+        assert_eq!(ema_prices, [EMA_ONE, EMA_ONE / 100 * 95]);
+        update_ema(&mut ema_prices, &last_prices, 2 * EMA_WINDOW);
+        assert_eq!(ema_prices, last_prices);
+
+        let prices = ema_oracle_prices(&[EMA_ONE, EMA_ONE / 2], &[RATE_ONE / 10 * 11, RATE_ONE]);
+        assert_eq!(
+            (prices[0].price, prices[1].price, prices[1].expo),
+            (EMA_ONE / 10 * 11, EMA_ONE / 2, -12)
+        );
+    }
+
+    #[test]
+    fn guard_fallbacks() {
+        let prices = [price(100_000_000, -8), price(99_000_000, -8)];
+        let (ema_prices, rates) = ([EMA_ONE, EMA_ONE / 2], [RATE_ONE; 2]);
+        let fallbacks = [
+            OracleFallback::Reject,
+            OracleFallback::IgnoreGuard,
+            OracleFallback::InternalEma,
+        ];
+        for fallback in fallbacks.iter() {
+            assert_eq!(
+                guard_prices(Ok(prices), *fallback, &ema_prices, &rates),
+                Ok(Some(prices))
+            );
+            assert_eq!(
+                guard_prices(Err(PoolError::InvalidOracleAccount), *fallback, &ema_prices, &rates),
+                Err(PoolError::InvalidOracleAccount)
+            );
+        }
+
+        let unavailable = Err(PoolError::OraclePriceUnavailable);
+        assert_eq!(
+            guard_prices(unavailable, OracleFallback::Reject, &ema_prices, &rates),
+            Err(PoolError::OraclePriceUnavailable)
+        );
+        assert_eq!(
+            guard_prices(unavailable, OracleFallback::IgnoreGuard, &ema_prices, &rates),
+            Ok(None)
+        );
+        assert_eq!(
+            guard_prices(unavailable, OracleFallback::InternalEma, &ema_prices, &rates),
+            Ok(Some(ema_oracle_prices(&ema_prices, &rates)))
+        );
+    }
+
+    #[test]
+    fn peg() {
+        let half_percent = DecT::new(5, 3).unwrap();
+        assert!(!is_off_peg(&price(100_000_000, -8), half_percent));
+        assert!(!is_off_peg(&price(99_500_000, -8), half_percent));
+        assert!(!is_off_peg(&price(1_005, -3), half_percent));
+        assert!(is_off_peg(&price(99_499_999, -8), half_percent));
+        assert!(is_off_peg(&price(1_006, -3), half_percent));
+        assert!(is_off_peg(&price(2, 0), half_percent));
+    }
+}
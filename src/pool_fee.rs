@@ -32,7 +32,8 @@ impl PoolFee {
     }
 
     pub fn get(&self) -> DecT {
-        DecT::new(self.0 as u64, DECIMALS).unwrap()
+        //DECIMALS are within DecT's, so this never falls back to 0
+        DecT::new(self.0 as u64, DECIMALS).unwrap_or_default()
     }
 }
 
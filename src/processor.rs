@@ -4,37 +4,70 @@ use solana_program::{
     account_info::{next_account_info, AccountInfo},
     clock::UnixTimestamp,
     entrypoint::ProgramResult,
-    program::{invoke, invoke_signed},
+    instruction::{AccountMeta, Instruction},
+    program::{get_return_data, invoke, invoke_signed, set_return_data},
     program_error::ProgramError,
     program_option::COption,
     program_pack::{IsInitialized, Pack},
     pubkey::Pubkey,
-    sysvar::{clock::Clock, rent::Rent, Sysvar},
+    system_instruction,
+    sysvar::{
+        clock::Clock,
+        instructions::{load_current_index_checked, load_instruction_at_checked},
+        rent::Rent,
+        Sysvar,
+    },
 };
 
+use spl_associated_token_account::{create_associated_token_account, get_associated_token_address};
 use spl_token::{
     error::TokenError,
-    instruction::{burn, mint_to, transfer},
+    instruction::{approve, burn, mint_to, revoke, set_authority, transfer, AuthorityType},
     state::Account as TokenState,
     state::Mint as MintState,
 };
 
 use crate::{
-    amp_factor::AmpFactor,
+    amp_factor::{AmpDamping, AmpFactor},
     common::{create_array, create_result_array},
-    decimal::DecimalU64,
+    decimal::{DecimalU64, U256},
     error::PoolError,
-    instruction::{DeFiInstruction, GovernanceInstruction, PoolInstruction},
-    invariant::{AmountT, Invariant},
+    instruction::{
+        ed25519_program, find_allowed_depositor_address, find_allowed_user_address, find_compliance_address,
+        find_export_address, find_fee_checkpoint_address, find_gauge_address, find_gauge_stake_address,
+        find_gauge_vault_address, find_governance_fee_address, find_intermediate_address,
+        find_launch_allocation_address, find_lock_address, find_lock_vault_address, find_lp_mint_address,
+        find_lp_supply_address, find_pool_address, find_rebate_address, find_rebate_campaign_address,
+        find_rebate_vault_address, find_rotated_vault_address, find_route_authority_address, find_session_address,
+        find_vault_address, find_ve_boost_address, find_virtual_price_address, find_withdrawal_announcement_address,
+        find_yield_delegate_address, parse_ed25519_ix, ComplianceCheckData, DeFiInstruction, GovernanceInstruction,
+        PoolInstruction, RfqQuote, SimulationResult, SwapHookData, VeBoostQuery, YieldAdapterInstruction,
+    },
+    migration::MigrationSource,
+    oracle::{self, OracleFallback, OracleKind, OracleLimits, OraclePrice, RateSource, TokenOracle, BPS_ONE, RATE_ONE},
     pool_fee::PoolFee,
-    state::PoolState,
-    TOKEN_COUNT,
+    quote::{self, Quote, Quoter},
+    state::{
+        lock_weight, pool_lp_mint_key, pool_token_count, AllowedUser, CallerMode, FeeCheckpoint, Gauge, GaugeStake,
+        InvariantKind, LaunchAllocation, LpBurnEvent, LpLock, LpSupplyCheckpoint, LpSupplyEvent, LpSupplySnapshots,
+        MaintenanceEvent, MaintenanceWindow, PegKeeper, PoolExport, PoolState, PriceBand, PriceScale, Rebate,
+        RebateCampaign, Session, TokenFlags, TokenParticipation, TokenParticipationEvent, TradingHours, UserGate,
+        VirtualPriceState, WithdrawalAnnouncement, YieldAdapter, ALLOWED_DEPOSITOR_SEED, ALLOWED_USER_SEED,
+        EXPORT_SEED, FEE_CHECKPOINT_SEED, GAUGE_SEED, GAUGE_STAKE_SEED, GOVERNANCE_FEE_SEED, LAUNCH_ALLOCATION_SEED,
+        LOCK_SEED, LP_MINT_SEED, LP_SUPPLY_SEED, MAINTENANCE_NOTICE, MAX_ALLOWED_CALLERS, MAX_EMERGENCY_RESPONDERS,
+        MAX_GAUGE_DURATION, MAX_HOOK_PROGRAMS, MAX_LOCK_DURATION, MAX_MAINTENANCE_WINDOWS, MAX_PEG_CREDIT_SHARE_BPS,
+        MAX_REBATE_WINDOW, MAX_RFQ_DEVIATION_BPS, MAX_WITHDRAWAL_COOLDOWN, MIN_LOCK_DURATION, POOL_EXPORT_VERSION,
+        POOL_SEED, POOL_STATE_LAYOUT_VERSION, REBATE_CAMPAIGN_SEED, REBATE_CLAIM_PERIOD, REBATE_SEED, ROLE_COUNT,
+        ROUTE_SEED, SESSION_SEED, VAULT_SEED, VIRTUAL_PRICE_EXPO, VIRTUAL_PRICE_SEED, WITHDRAWAL_ANNOUNCEMENT_SEED,
+        WITHDRAWAL_ANNOUNCEMENT_VALIDITY,
+    },
+    token_2022, validation, weighted, TOKEN_COUNT,
 };
 use borsh::{BorshDeserialize, BorshSerialize};
 //Note - using this b/c of not all bytes read error. found from using this - https://brson.github.io/2021/06/08/rust-on-solana
 // use solana_program::borsh::try_from_slice_unchecked;
-const ENACT_DELAY: UnixTimestamp = 3 * 86400;
-const MAX_DECIMAL_DIFFERENCE: u8 = 8;
+pub const ENACT_DELAY: UnixTimestamp = 3 * 86400;
+pub const MAX_DECIMAL_DIFFERENCE: u8 = 8;
 
 type AtomicT = u64;
 type DecT = DecimalU64;
@@ -43,7 +76,11 @@ pub struct Processor<const TOKEN_COUNT: usize>;
 impl<const TOKEN_COUNT: usize> Processor<TOKEN_COUNT> {
     pub fn process(program_id: &Pubkey, accounts: &[AccountInfo], instruction_data: &[u8]) -> ProgramResult {
         //msg!("[DEV] process - TOKEN_COUNT: {}", TOKEN_COUNT);
-        match PoolInstruction::<TOKEN_COUNT>::try_from_slice(instruction_data)? {
+        #[cfg(feature = "anchor-discriminators")]
+        if let Some(defi_instruction) = DeFiInstruction::<TOKEN_COUNT>::try_from_anchor_slice(instruction_data)? {
+            return Self::process_defi_instruction(defi_instruction, program_id, accounts);
+        }
+        match PoolInstruction::<TOKEN_COUNT>::unpack(instruction_data)? {
             PoolInstruction::Init {
                 nonce,
                 amp_factor,
@@ -51,7 +88,7 @@ impl<const TOKEN_COUNT: usize> Processor<TOKEN_COUNT> {
                 governance_fee,
             } => {
                 ////msg!("[DEV] process_init");
-                Self::process_init(nonce, amp_factor, lp_fee, governance_fee, program_id, accounts)
+                Self::process_init(nonce, amp_factor, lp_fee, governance_fee, false, program_id, accounts)
             }
             PoolInstruction::DeFiInstruction(defi_instruction) => {
                 ////msg!("[DEV] Processing Defi ix");
@@ -60,14 +97,151 @@ impl<const TOKEN_COUNT: usize> Processor<TOKEN_COUNT> {
             PoolInstruction::GovernanceInstruction(governance_instruction) => {
                 Self::process_governance_instruction(governance_instruction, program_id, accounts)
             }
+            PoolInstruction::CheckPeg {} => Self::process_check_peg(program_id, accounts),
+            PoolInstruction::PublishVirtualPrice {} => Self::process_publish_virtual_price(program_id, accounts),
+            PoolInstruction::RoutedSwap {
+                exact_input_amount,
+                input_token_index,
+                first_output_index,
+                second_input_index,
+                output_token_index,
+                minimum_output_amount,
+            } => Self::process_routed_swap(
+                exact_input_amount,
+                input_token_index as usize,
+                first_output_index as usize,
+                second_input_index as usize,
+                output_token_index as usize,
+                minimum_output_amount,
+                program_id,
+                accounts,
+            ),
+            PoolInstruction::CreatePoolTokenAccounts {} => {
+                Self::process_create_pool_token_accounts(program_id, accounts)
+            }
+            PoolInstruction::CreatePool {
+                fee_tier,
+                amp_factor,
+                lp_fee,
+                governance_fee,
+            } => Self::process_create_pool(fee_tier, amp_factor, lp_fee, governance_fee, program_id, accounts),
+            PoolInstruction::MigrateIn {
+                source,
+                source_lp_amount,
+                token_indices,
+                minimum_mint_amount,
+            } => Self::process_migrate_in(
+                source,
+                source_lp_amount,
+                [token_indices[0] as usize, token_indices[1] as usize],
+                minimum_mint_amount,
+                program_id,
+                accounts,
+            ),
+            PoolInstruction::ClonePoolConfig { nonce } => Self::process_clone_pool_config(nonce, program_id, accounts),
+            PoolInstruction::RedeemSunset {} => Self::process_redeem_sunset(program_id, accounts),
+            PoolInstruction::ExportState {
+                target_program,
+                target_pool,
+            } => Self::process_export_state(&target_program, &target_pool, program_id, accounts),
+            PoolInstruction::ImportState { nonce } => Self::process_import_state(nonce, program_id, accounts),
+            PoolInstruction::InitAndAdd {
+                nonce,
+                amp_factor,
+                lp_fee,
+                governance_fee,
+                input_amounts,
+                minimum_mint_amount,
+            } => Self::process_init_and_add(
+                nonce,
+                amp_factor,
+                lp_fee,
+                governance_fee,
+                input_amounts,
+                minimum_mint_amount,
+                program_id,
+                accounts,
+            ),
+            PoolInstruction::RotateVaults {} => Self::process_rotate_vaults(program_id, accounts),
+            PoolInstruction::LockLp { amount, duration } => {
+                Self::process_lock_lp(amount, duration, program_id, accounts)
+            }
+            PoolInstruction::ClaimLockRewards {} => Self::process_claim_lock_rewards(program_id, accounts),
+            PoolInstruction::UnlockLp {} => Self::process_unlock_lp(program_id, accounts),
+            PoolInstruction::CheckpointFees {} => Self::process_checkpoint_fees(program_id, accounts),
+            PoolInstruction::SnapshotLpSupply {} => Self::process_snapshot_lp_supply(program_id, accounts),
+            PoolInstruction::CreateLaunchAllocation {} => Self::process_create_launch_allocation(program_id, accounts),
+            PoolInstruction::AuthorizeSession {
+                session_key,
+                expiry_ts,
+                spend_limits,
+            } => Self::process_authorize_session(&session_key, expiry_ts, &spend_limits, program_id, accounts),
+            PoolInstruction::RevokeSession {} => Self::process_revoke_session(program_id, accounts),
+            PoolInstruction::RfqSwap { quote } => Self::process_rfq_swap(quote, program_id, accounts),
+            PoolInstruction::AnnounceWithdrawal { lp_amount } => {
+                Self::process_announce_withdrawal(lp_amount, program_id, accounts)
+            }
+            PoolInstruction::ConfirmEmergency {} => Self::process_confirm_emergency(program_id, accounts),
+            PoolInstruction::InitWeighted {
+                nonce,
+                token_weights,
+                lp_fee,
+                governance_fee,
+            } => Self::process_init_weighted(nonce, token_weights, lp_fee, governance_fee, program_id, accounts),
+            PoolInstruction::ProvidePegCredit { amount } => {
+                Self::process_peg_credit(amount, true, program_id, accounts)
+            }
+            PoolInstruction::AbsorbPegCredit { amount } => {
+                Self::process_peg_credit(amount, false, program_id, accounts)
+            }
+            PoolInstruction::InitFixedRate {
+                nonce,
+                lp_fee,
+                governance_fee,
+            } => Self::process_init_fixed_rate(nonce, lp_fee, governance_fee, program_id, accounts),
+            PoolInstruction::DeployYield { token_index } => {
+                Self::process_yield(token_index, None, program_id, accounts)
+            }
+            PoolInstruction::RecallYield { token_index, amount } => {
+                Self::process_yield(token_index, Some(amount), program_id, accounts)
+            }
+            PoolInstruction::InitHybrid {
+                nonce,
+                amp_factor,
+                lp_fee,
+                governance_fee,
+                volatile_index,
+                initial_rate,
+            } => Self::process_init_hybrid(
+                nonce,
+                amp_factor,
+                lp_fee,
+                governance_fee,
+                volatile_index,
+                initial_rate,
+                program_id,
+                accounts,
+            ),
+            PoolInstruction::Repeg {} => Self::process_repeg(program_id, accounts),
+            PoolInstruction::FundGauge { amount, duration } => {
+                Self::process_fund_gauge(amount, duration, program_id, accounts)
+            }
+            PoolInstruction::StakeLp { amount } => Self::process_stake_lp(amount, program_id, accounts),
+            PoolInstruction::UnstakeLp { amount } => Self::process_unstake_lp(amount, program_id, accounts),
+            PoolInstruction::ClaimGaugeRewards {} => Self::process_claim_gauge_rewards(program_id, accounts),
+            PoolInstruction::JoinRebateCampaign {} => Self::process_join_rebate_campaign(program_id, accounts),
+            PoolInstruction::ClaimRebate {} => Self::process_claim_rebate(program_id, accounts),
+            PoolInstruction::UpdateGaugeBoost {} => Self::process_update_gauge_boost(program_id, accounts),
         }
     }
 
+    //an imported pool (see ImportState) starts out with the balances and lp tokens of the pool it was exported from
     fn process_init(
         nonce: u8,
         amp_factor: DecT,
         lp_fee: DecT,
         governance_fee: DecT,
+        is_import: bool,
         program_id: &Pubkey,
         accounts: &[AccountInfo],
     ) -> ProgramResult {
@@ -112,8 +286,13 @@ impl<const TOKEN_COUNT: usize> Processor<TOKEN_COUNT> {
 
         //msg!("[DEV] checking lp_mint_account");
         let lp_mint_account = check_duplicate_and_get_next()?;
-        let lp_mint_state = Self::check_program_owner_and_unpack::<MintState>(lp_mint_account)?;
-        if lp_mint_state.supply != 0 {
+        //a Token-2022 lp mint makes the lp token non-transferable, which it must enforce itself
+        let lp_non_transferable = *lp_mint_account.owner == token_2022::id();
+        if lp_non_transferable && !token_2022::is_non_transferable_mint(&lp_mint_account.data.borrow()) {
+            return Err(PoolError::InvalidMintAccount.into());
+        }
+        let lp_mint_state = Self::unpack_lp_mint(lp_mint_account)?;
+        if lp_mint_state.supply != 0 && !is_import {
             return Err(PoolError::MintHasBalance.into());
         }
         if COption::Some(pool_authority_account) != lp_mint_state.mint_authority {
@@ -128,8 +307,8 @@ impl<const TOKEN_COUNT: usize> Processor<TOKEN_COUNT> {
         let token_accounts: [_; TOKEN_COUNT] = create_result_array(|_| check_duplicate_and_get_next())?;
         //msg!("[DEV] token_accounts.len: {}", token_accounts.len());
 
-        let mut decimal_range_min = lp_mint_state.decimals;
-        let mut decimal_range_max = decimal_range_min;
+        let mut decimal_range_min = u8::MAX;
+        let mut decimal_range_max = 0;
         //msg!("[DEV] passed lp_mint_account checks");
         let token_decimals: [_; TOKEN_COUNT] = create_result_array(|i| -> Result<_, ProgramError> {
             let mint_decimals = Self::check_program_owner_and_unpack::<MintState>(token_mint_accounts[i])?.decimals;
@@ -141,6 +320,12 @@ impl<const TOKEN_COUNT: usize> Processor<TOKEN_COUNT> {
         if decimal_range_max - decimal_range_min > MAX_DECIMAL_DIFFERENCE {
             return Err(PoolError::MaxDecimalDifferenceExceeded.into());
         }
+        //the lp token's decimals are chosen independently of the tokens' as long as they're within the same range of
+        // the most precise token's, its amounts are then scaled up or down into equalized units
+        let lp_decimal_equalizer = decimal_range_max as i16 - lp_mint_state.decimals as i16;
+        if lp_decimal_equalizer.abs() > MAX_DECIMAL_DIFFERENCE as i16 {
+            return Err(PoolError::MaxDecimalDifferenceExceeded.into());
+        }
 
         for i in 0..TOKEN_COUNT {
             let token_account = token_accounts[i];
@@ -153,7 +338,7 @@ impl<const TOKEN_COUNT: usize> Processor<TOKEN_COUNT> {
             if token_state.owner != pool_authority_account {
                 return Err(TokenError::OwnerMismatch.into());
             }
-            if token_state.amount != 0 {
+            if token_state.amount != 0 && !is_import {
                 return Err(PoolError::TokenAccountHasBalance.into());
             }
             if token_state.delegate.is_some() {
@@ -169,7 +354,7 @@ impl<const TOKEN_COUNT: usize> Processor<TOKEN_COUNT> {
         let governance_account = check_duplicate_and_get_next()?;
         let governance_fee_account = check_duplicate_and_get_next()?;
         if (governance_fee != DecT::from(0) || *governance_fee_account.key != Pubkey::default())
-            && Self::check_program_owner_and_unpack::<TokenState>(governance_fee_account)?.mint != *lp_mint_account.key
+            && Self::unpack_lp_account(governance_fee_account)?.mint != *lp_mint_account.key
         {
             return Err(TokenError::MintMismatch.into());
         }
@@ -183,7 +368,7 @@ impl<const TOKEN_COUNT: usize> Processor<TOKEN_COUNT> {
                 lp_fee: PoolFee::new(lp_fee)?,
                 governance_fee: PoolFee::new(governance_fee)?,
                 lp_mint_key: lp_mint_account.key.clone(),
-                lp_decimal_equalizer: decimal_range_max - lp_mint_state.decimals,
+                lp_decimal_equalizer: lp_decimal_equalizer as i8,
                 token_mint_keys: create_array(|i| token_mint_accounts[i].key.clone()),
                 token_decimal_equalizers: create_array(|i| decimal_range_max - token_decimals[i]),
                 token_keys: create_array(|i| token_accounts[i].key.clone()),
@@ -195,24 +380,181 @@ impl<const TOKEN_COUNT: usize> Processor<TOKEN_COUNT> {
                 prepared_governance_fee: PoolFee::default(),
                 fee_transition_ts: 0,
                 previous_depth: 0,
+                oracle_keys: [Pubkey::default(); TOKEN_COUNT],
+                oracle_kinds: [OracleKind::Pyth; TOKEN_COUNT],
+                max_oracle_deviation: PoolFee::default(),
+                oracle_limits: [OracleLimits::default(); TOKEN_COUNT],
+                oracle_fallback: OracleFallback::Reject,
+                prepared_token_oracle: TokenOracle::default(),
+                prepared_oracle_index: 0,
+                oracle_transition_ts: 0,
+                ema_prices: [0; TOKEN_COUNT],
+                last_prices: [0; TOKEN_COUNT],
+                ema_ts: 0,
+                peg_band: PoolFee::default(),
+                depegged_tokens: [false; TOKEN_COUNT],
+                amp_damping: AmpDamping::default(),
+                rate_sources: [RateSource::None; TOKEN_COUNT],
+                rate_keys: [Pubkey::default(); TOKEN_COUNT],
+                hook_programs: [Pubkey::default(); MAX_HOOK_PROGRAMS],
+                caller_mode: CallerMode::Unrestricted,
+                allowed_callers: [Pubkey::default(); MAX_ALLOWED_CALLERS],
+                sunset_ts: 0,
+                lock_fee_share: PoolFee::default(),
+                lock_total_weight: 0,
+                lock_reward_growth: 0,
+                lock_rewards_unminted: 0,
+                lp_fee_growth: 0,
+                lp_non_transferable,
+                lp_supply_seconds: 0,
+                lp_supply_ts: 0,
+                min_lp_mint_amount: 0,
+                min_lp_residual_amount: 0,
+                launch_lp_cap: 0,
+                launch_end_ts: 0,
+                user_gate: UserGate::Open,
+                depositor_gate: UserGate::Open,
+                roles: [Pubkey::default(); ROLE_COUNT],
+                token_flags: [TokenFlags::default(); TOKEN_COUNT],
+                rfq_quoter_key: Pubkey::default(),
+                rfq_max_deviation: PoolFee::default(),
+                rfq_last_quote_id: 0,
+                prepared_rfq_quoter_key: Pubkey::default(),
+                prepared_rfq_max_deviation: PoolFee::default(),
+                rfq_quoter_transition_ts: 0,
+                compliance_program: Pubkey::default(),
+                trading_hours: TradingHours::default(),
+                maintenance_windows: [MaintenanceWindow::default(); MAX_MAINTENANCE_WINDOWS],
+                withdrawal_cooldown_threshold: 0,
+                withdrawal_cooldown: 0,
+                unannounced_burn_slot: 0,
+                unannounced_burn_amount: 0,
+                emergency_responders: [Pubkey::default(); MAX_EMERGENCY_RESPONDERS],
+                emergency_threshold: 0,
+                emergency_confirmation_ts: [0; MAX_EMERGENCY_RESPONDERS],
+                invariant_kind: InvariantKind::StableSwap,
+                token_weights: [0; TOKEN_COUNT],
+                price_band: PriceBand::default(),
+                depth_rates: [RATE_ONE; TOKEN_COUNT],
+                token_participation: [TokenParticipation::Active; TOKEN_COUNT],
+                peg_keeper: PegKeeper::default(),
+                prepared_peg_keeper: PegKeeper::default(),
+                peg_keeper_transition_ts: 0,
+                yield_adapters: [YieldAdapter::default(); TOKEN_COUNT],
+                prepared_yield_adapter: YieldAdapter::default(),
+                prepared_yield_adapter_index: 0,
+                yield_adapter_transition_ts: 0,
+                price_scale: PriceScale::default(),
+                rebate_campaign: Pubkey::default(),
+                rebate_end_ts: 0,
+                token_count: TOKEN_COUNT as u8,
+                layout_version: POOL_STATE_LAYOUT_VERSION,
             },
             &pool_account,
         )
     }
 
+    //the amp factor of a weighted pool is unused, it keeps the minimum
+    fn process_init_weighted(
+        nonce: u8,
+        token_weights: [u32; TOKEN_COUNT],
+        lp_fee: DecT,
+        governance_fee: DecT,
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        if !weighted::is_valid_weights(&token_weights) {
+            return Err(PoolError::InvalidTokenWeights.with_context(format_args!("weights {:?}", token_weights)));
+        }
+        Self::process_init(
+            nonce,
+            DecT::from(1),
+            lp_fee,
+            governance_fee,
+            false,
+            program_id,
+            accounts,
+        )?;
+        let pool_account = &accounts[0];
+        let mut pool_state = Self::check_and_deserialize_pool_state(pool_account, program_id)?;
+        pool_state.invariant_kind = InvariantKind::WeightedProduct;
+        pool_state.token_weights = token_weights;
+        Self::serialize_pool(&pool_state, pool_account)
+    }
+
+    //like a weighted pool's, the amp factor of a fixed rate pool is unused
+    fn process_init_fixed_rate(
+        nonce: u8,
+        lp_fee: DecT,
+        governance_fee: DecT,
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        Self::process_init(
+            nonce,
+            DecT::from(1),
+            lp_fee,
+            governance_fee,
+            false,
+            program_id,
+            accounts,
+        )?;
+        let pool_account = &accounts[0];
+        let mut pool_state = Self::check_and_deserialize_pool_state(pool_account, program_id)?;
+        pool_state.invariant_kind = InvariantKind::FixedRate;
+        Self::serialize_pool(&pool_state, pool_account)
+    }
+
+    //a hybrid pool starts out without repegging, governance sets its parameters once the volatile token's rate source
+    // is in place
+    #[allow(clippy::too_many_arguments)]
+    fn process_init_hybrid(
+        nonce: u8,
+        amp_factor: DecT,
+        lp_fee: DecT,
+        governance_fee: DecT,
+        volatile_index: u8,
+        initial_rate: u64,
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        if volatile_index as usize >= TOKEN_COUNT || initial_rate == 0 {
+            return Err(PoolError::InvalidPriceScale.with_context(format_args!(
+                "volatile token {} at rate {}",
+                volatile_index, initial_rate
+            )));
+        }
+        Self::process_init(nonce, amp_factor, lp_fee, governance_fee, false, program_id, accounts)?;
+        let pool_account = &accounts[0];
+        let mut pool_state = Self::check_and_deserialize_pool_state(pool_account, program_id)?;
+        pool_state.invariant_kind = InvariantKind::Hybrid;
+        pool_state.price_scale = PriceScale {
+            token_index: volatile_index,
+            rate: initial_rate,
+            ..PriceScale::default()
+        };
+        Self::serialize_pool(&pool_state, pool_account)
+    }
+
     fn process_defi_instruction(
         defi_instruction: DeFiInstruction<TOKEN_COUNT>,
         program_id: &Pubkey,
         accounts: &[AccountInfo],
     ) -> ProgramResult {
         //msg!("[DEV] processing defi ix\n");
+        let (defi_instruction, is_simulation) = match defi_instruction {
+            DeFiInstruction::Simulate(defi_instruction) => (*defi_instruction, true),
+            defi_instruction => (defi_instruction, false),
+        };
         let mut account_info_iter = accounts.iter();
         let pool_account = next_account_info(&mut account_info_iter)?;
         let mut pool_state = Self::check_and_deserialize_pool_state(pool_account, &program_id)?;
         //msg!("[DEV] checked & deserialized pool_state");
 
-        if pool_state.is_paused && !matches!(defi_instruction, DeFiInstruction::RemoveUniform { .. }) {
-            return Err(PoolError::PoolIsPaused.into());
+        let current_ts = Self::get_current_ts()?;
+        validation::check_status(&pool_state, &defi_instruction, current_ts)?;
+        if !defi_instruction.is_uniform_remove() {
+            Self::log_maintenance_notice(&pool_state, current_ts);
         }
 
         let pool_authority_account = next_account_info(&mut account_info_iter)?;
@@ -224,7 +566,7 @@ impl<const TOKEN_COUNT: usize> Processor<TOKEN_COUNT> {
             let check_pool_token_account = |i| -> Result<_, ProgramError> {
                 let pool_token_account = next_account_info(&mut account_info_iter)?;
                 if *pool_token_account.key != pool_state.token_keys[i] {
-                    return Err(PoolError::PoolTokenAccountExpected.into());
+                    return Err(PoolError::PoolTokenAccountExpected.with_context(format_args!("token index {}", i)));
                 }
                 Ok(pool_token_account)
             };
@@ -242,7 +584,7 @@ impl<const TOKEN_COUNT: usize> Processor<TOKEN_COUNT> {
             return Err(PoolError::InvalidMintAccount.into());
         }
         //msg!("[DEV] checked lp_mint_account");
-        let lp_total_supply = Self::check_program_owner_and_unpack::<MintState>(lp_mint_account)?.supply;
+        let lp_total_supply = pool_state.lp_supply(Self::unpack_lp_mint(lp_mint_account)?.supply);
         let governance_fee_account = next_account_info(&mut account_info_iter)?;
         if *governance_fee_account.key != pool_state.governance_fee_key {
             return Err(PoolError::InvalidGovernanceFeeAccount.into());
@@ -256,62 +598,270 @@ impl<const TOKEN_COUNT: usize> Processor<TOKEN_COUNT> {
         //msg!("[DEV] checked user_token_accounts");
         let token_program_account = next_account_info(&mut account_info_iter)?;
 
-        let to_equalized = |value, equalizer| {
-            if equalizer > 0 {
-                AmountT::from(value) * AmountT::ten_to_the(equalizer)
-            } else {
-                AmountT::from(value)
+        let is_swap = defi_instruction.is_swap();
+        let user_lp_token_account = if is_swap {
+            None
+        } else {
+            Some(next_account_info(&mut account_info_iter)?)
+        };
+        let lp_token_program_account = if pool_state.lp_non_transferable {
+            let lp_token_program_account = next_account_info(&mut account_info_iter)?;
+            if *lp_token_program_account.key != token_2022::id() {
+                return Err(ProgramError::IncorrectProgramId);
+            }
+            lp_token_program_account
+        } else {
+            token_program_account
+        };
+        let oracle_accounts: Option<[&AccountInfo; TOKEN_COUNT]> = if is_swap && pool_state.is_oracle_guarded() {
+            Some(create_result_array(|i| -> Result<_, ProgramError> {
+                let oracle_account = next_account_info(&mut account_info_iter)?;
+                if *oracle_account.key != pool_state.oracle_keys[i] {
+                    return Err(PoolError::InvalidOracleAccount.with_context(format_args!("token index {}", i)));
+                }
+                Ok(oracle_account)
+            })?)
+        } else {
+            None
+        };
+        //uniform removes don't depend on how the pool values its balances, so they keep working while a rate is unavailable
+        let is_uniform_remove = defi_instruction.is_uniform_remove();
+        let rates = if is_uniform_remove {
+            [RATE_ONE; TOKEN_COUNT]
+        } else {
+            Self::load_rates(&pool_state, &mut account_info_iter)?
+        };
+        if pool_state.restricts_callers() && !is_uniform_remove {
+            Self::check_caller(&pool_state, program_id, next_account_info(&mut account_info_iter)?)?;
+        }
+        if pool_state.gates_users()
+            && !is_uniform_remove
+            && !Self::passes_gate(
+                pool_state.user_gate,
+                find_allowed_user_address,
+                pool_account,
+                user_authority_account,
+                next_account_info(&mut account_info_iter)?,
+                program_id,
+            )
+        {
+            return Err(PoolError::UserNotAllowed.into());
+        }
+        if pool_state.gates_depositors()
+            && !is_swap
+            && !is_uniform_remove
+            && !Self::passes_gate(
+                pool_state.depositor_gate,
+                find_allowed_depositor_address,
+                pool_account,
+                user_authority_account,
+                next_account_info(&mut account_info_iter)?,
+                program_id,
+            )
+        {
+            return Err(PoolError::DepositorNotAllowed.into());
+        }
+        if pool_state.has_compliance_program() && !is_uniform_remove {
+            Self::check_compliance(
+                &pool_state,
+                pool_account,
+                user_authority_account,
+                &mut account_info_iter,
+                if is_simulation {
+                    DeFiInstruction::Simulate(Box::new(defi_instruction.clone()))
+                } else {
+                    defi_instruction.clone()
+                },
+            )?;
+        }
+        //swaps that the user transfer authority doesn't sign have to be signed by the key of its session, which can
+        // only swap the user's tokens for the user
+        let mut session = if is_swap && !is_simulation && !user_authority_account.is_signer {
+            let session_account = next_account_info(&mut account_info_iter)?;
+            let (session, session_bump) = Self::check_and_deserialize_session(
+                session_account,
+                next_account_info(&mut account_info_iter)?,
+                pool_account,
+                user_authority_account,
+                program_id,
+            )?;
+            let (_, outputs) = defi_instruction.token_directions();
+            for i in 0..TOKEN_COUNT {
+                if outputs[i]
+                    && Self::check_program_owner_and_unpack::<TokenState>(user_token_accounts[i])?.owner
+                        != *user_authority_account.key
+                {
+                    return Err(TokenError::OwnerMismatch.into());
+                }
             }
+            Some((session_account, session, session_bump))
+        } else {
+            None
         };
-        let from_equalized = |value: AmountT, equalizer| {
-            if equalizer > 0 {
-                ((value + AmountT::ten_to_the(equalizer - 1) * 5u64) / AmountT::ten_to_the(equalizer)).as_u64()
+        let withdrawal_announcement_account =
+            if pool_state.has_withdrawal_cooldown() && defi_instruction.is_remove() && !is_uniform_remove {
+                let withdrawal_announcement_account = next_account_info(&mut account_info_iter)?;
+                let withdrawal_announcement_key =
+                    find_withdrawal_announcement_address(program_id, pool_account.key, user_authority_account.key).0;
+                if *withdrawal_announcement_account.key != withdrawal_announcement_key {
+                    return Err(PoolError::NonCanonicalAddress.into());
+                }
+                Some(withdrawal_announcement_account)
             } else {
-                value.as_u64()
+                None
+            };
+        let launch_allocation_account = if matches!(defi_instruction.executed(), DeFiInstruction::Add { .. })
+            && pool_state.is_launch_capped(Self::get_current_ts()?)
+        {
+            Some(next_account_info(&mut account_info_iter)?)
+        } else {
+            None
+        };
+        //a swap records its fees with the pool's rebate campaign if it passes the campaign and the user's rebate
+        let rebate_accounts = match account_info_iter.as_slice() {
+            [campaign_account, rebate_account, ..]
+                if is_swap
+                    && !is_simulation
+                    && pool_state.rebate_campaign != Pubkey::default()
+                    && *campaign_account.key == pool_state.rebate_campaign =>
+            {
+                account_info_iter.nth(1);
+                Some((campaign_account, rebate_account))
+            }
+            _ => None,
+        };
+        //whatever follows a swap's accounts is a hook program and the accounts to call it with
+        let hook_accounts = match account_info_iter.next() {
+            Some(hook_program_account) if is_swap && !is_simulation => {
+                if !pool_state.is_hook_program_allowed(hook_program_account.key) {
+                    return Err(PoolError::HookProgramNotAllowed.into());
+                }
+                Some((hook_program_account, account_info_iter.as_slice()))
+            }
+            _ => None,
+        };
+        //the amounts the user paid and received in a swap, for its hook
+        let mut swap_amounts = None;
+        let oracle_prices: Option<[OraclePrice; TOKEN_COUNT]> = match oracle_accounts {
+            Some(oracle_accounts) => {
+                let current_slot = Clock::get()?.slot;
+                let loaded_prices = create_result_array(|i| {
+                    oracle::load_checked_price(
+                        pool_state.oracle_kinds[i],
+                        &oracle_accounts[i].data.borrow(),
+                        &pool_state.oracle_limits[i],
+                        current_slot,
+                    )
+                });
+                if matches!(loaded_prices, Err(PoolError::OraclePriceUnavailable)) {
+                    match pool_state.oracle_fallback {
+                        OracleFallback::Reject => (),
+                        OracleFallback::IgnoreGuard => msg!("oracle unavailable, swap isn't guarded"),
+                        OracleFallback::InternalEma => {
+                            msg!("oracle unavailable, swap is checked against the internal oracle")
+                        }
+                    }
+                }
+                oracle::guard_prices(
+                    loaded_prices,
+                    pool_state.oracle_fallback,
+                    &pool_state.ema_prices,
+                    &rates,
+                )?
             }
+            None => None,
         };
-        let array_equalize = |amounts: &[AtomicT; TOKEN_COUNT]| -> [_; TOKEN_COUNT] {
-            create_array(|i| to_equalized(amounts[i], pool_state.token_decimal_equalizers[i]))
+
+        validation::check_amounts(
+            &defi_instruction,
+            &pool_state.total_balances(&pool_balances),
+            lp_total_supply,
+        )?;
+        validation::check_pegged(&pool_state, &defi_instruction)?;
+
+        let check_residual = |user_lp_token_account: &AccountInfo, burn_amount: AtomicT| -> ProgramResult {
+            if pool_state.min_lp_residual_amount > 0 {
+                let lp_balance = Self::unpack_lp_account(user_lp_token_account)?.amount;
+                validation::check_residual(&pool_state, lp_balance, burn_amount)?;
+            }
+            Ok(())
         };
-        let result_from_equalized = |(user_amount, governance_mint_amount, latest_depth): (_, _, AmountT),
-                                     user_equalizer| {
-            (
-                from_equalized(user_amount, user_equalizer),
-                from_equalized(governance_mint_amount, pool_state.lp_decimal_equalizer),
-                latest_depth.as_u128(),
+
+        let quoter = || -> Result<_, ProgramError> {
+            Ok(Quoter::new(
+                &pool_state,
+                &pool_balances,
+                lp_total_supply,
+                pool_state.effective_amp_factor(Self::get_current_ts()?),
             )
+            .with_rates(&rates)
+            .with_oracle_guard(oracle_prices.as_ref(), pool_state.max_oracle_deviation.get()))
+        };
+
+        //pays `amount` of the token at index `i` into the pool. a session's swaps pay with the user's tokens as the
+        // delegate of its session account, within what's left of the session's limit
+        let token_mint_keys = pool_state.token_mint_keys;
+        let mut transfer_user_token = |i: usize, amount| -> ProgramResult {
+            match &mut session {
+                Some((session_account, session, session_bump)) => {
+                    Self::check_user_debit(
+                        user_token_accounts[i],
+                        &token_mint_keys[i],
+                        amount,
+                        session_account.key,
+                        format_args!("input token index {}", i),
+                    )?;
+                    session.remaining_limits[i] = session.remaining_limits[i]
+                        .checked_sub(amount)
+                        .ok_or(PoolError::SessionLimitExceeded)?;
+                    Self::transfer_token_signed(
+                        user_token_accounts[i],
+                        pool_token_accounts[i],
+                        amount,
+                        session_account,
+                        token_program_account,
+                        &[
+                            SESSION_SEED,
+                            &pool_account.key.to_bytes(),
+                            &user_authority_account.key.to_bytes(),
+                            &[*session_bump],
+                        ],
+                    )
+                }
+                None => {
+                    Self::check_user_debit(
+                        user_token_accounts[i],
+                        &token_mint_keys[i],
+                        amount,
+                        user_authority_account.key,
+                        format_args!("input token index {}", i),
+                    )?;
+                    Self::transfer_token(
+                        user_token_accounts[i],
+                        pool_token_accounts[i],
+                        amount,
+                        user_authority_account,
+                        token_program_account,
+                    )
+                }
+            }
         };
 
         //msg!("[DEV] checked token_program_account");
-        let (governance_mint_amount, latest_depth) = match defi_instruction {
+        let (user_lp_change, governance_mint_amount, lp_fee_amount, latest_depth) = match defi_instruction {
             DeFiInstruction::Add {
                 input_amounts,
                 minimum_mint_amount,
             } => {
                 //msg!("[DEV] Processing Add ix");
-                if input_amounts.iter().all(|amount| *amount == 0) {
-                    return Err(ProgramError::InvalidInstructionData);
-                }
-
-                //check if the pool is currently empty
-                if lp_total_supply == 0 && input_amounts.iter().any(|amount| *amount == 0) {
-                    return Err(PoolError::AddRequiresAllTokens.into());
-                }
-
-                let user_lp_token_account = next_account_info(&mut account_info_iter)?;
+                let user_lp_token_account = user_lp_token_account.ok_or(ProgramError::NotEnoughAccountKeys)?;
 
-                let (mint_amount, governance_mint_amount, latest_depth) = result_from_equalized(
-                    Invariant::<TOKEN_COUNT>::add(
-                        &array_equalize(&input_amounts),
-                        &array_equalize(&pool_balances),
-                        pool_state.amp_factor.get(Self::get_current_ts()?),
-                        pool_state.lp_fee.get(),
-                        pool_state.governance_fee.get(),
-                        to_equalized(lp_total_supply, pool_state.lp_decimal_equalizer),
-                        pool_state.previous_depth.into(),
-                    )?,
-                    pool_state.lp_decimal_equalizer,
-                );
+                let Quote {
+                    amount: mint_amount,
+                    governance_mint_amount,
+                    lp_fee_amount,
+                    latest_depth,
+                } = quoter()?.add(&input_amounts)?;
 
                 // msg!(
                 //     "[DEV] Add: {:?}, mint_amount: {:?}, governance_mint_amount: {:?}",
@@ -320,17 +870,45 @@ impl<const TOKEN_COUNT: usize> Processor<TOKEN_COUNT> {
                 //     governance_mint_amount
                 // );
 
-                if mint_amount < minimum_mint_amount {
-                    // msg!(
-                    //     "[DEV] Returning OutsideSpecifiedLimits for Add ix: {:?}",
-                    //     defi_instruction
-                    // );
-                    return Err(PoolError::OutsideSpecifiedLimits.into());
+                let result = SimulationResult {
+                    lp_amount: mint_amount,
+                    input_amounts,
+                    output_amounts: [0; TOKEN_COUNT],
+                    governance_mint_amount,
+                };
+                validation::check_limits(&pool_state, &defi_instruction, &result)?;
+                let launch_allocation = match launch_allocation_account {
+                    Some(launch_allocation_account) => {
+                        let mut launch_allocation = Self::check_and_deserialize_launch_allocation(
+                            launch_allocation_account,
+                            pool_account,
+                            user_authority_account,
+                            program_id,
+                        )?;
+                        let lp_minted = launch_allocation.lp_minted_in(pool_state.launch_end_ts) + mint_amount;
+                        if lp_minted > pool_state.launch_lp_cap {
+                            return Err(PoolError::LaunchCapExceeded.into());
+                        }
+                        launch_allocation.launch_end_ts = pool_state.launch_end_ts;
+                        launch_allocation.lp_minted = lp_minted;
+                        Some((launch_allocation_account, launch_allocation))
+                    }
+                    None => None,
+                };
+                if is_simulation {
+                    return Self::return_simulation(result);
                 }
 
                 for i in 0..TOKEN_COUNT {
                     if input_amounts[i] > 0 {
                         // msg!("[DEV] transferring {} for i = {}", input_amounts[i], i);
+                        Self::check_user_debit(
+                            user_token_accounts[i],
+                            &pool_state.token_mint_keys[i],
+                            input_amounts[i],
+                            user_authority_account.key,
+                            format_args!("input token index {}", i),
+                        )?;
                         Self::transfer_token(
                             user_token_accounts[i],
                             pool_token_accounts[i],
@@ -345,43 +923,45 @@ impl<const TOKEN_COUNT: usize> Processor<TOKEN_COUNT> {
                     user_lp_token_account,
                     mint_amount,
                     pool_authority_account,
-                    token_program_account,
+                    lp_token_program_account,
                     pool_account,
                     pool_state.nonce,
                 )?;
+                if let Some((launch_allocation_account, launch_allocation)) = launch_allocation {
+                    launch_allocation
+                        .serialize(&mut &mut launch_allocation_account.try_borrow_mut_data()?[..])
+                        .or(Err(ProgramError::AccountDataTooSmall))?;
+                }
 
-                (governance_mint_amount, latest_depth)
+                (mint_amount as i128, governance_mint_amount, lp_fee_amount, latest_depth)
             }
 
             DeFiInstruction::RemoveUniform {
                 exact_burn_amount,
                 minimum_output_amounts,
             } => {
-                if exact_burn_amount == 0 || exact_burn_amount > lp_total_supply {
-                    return Err(ProgramError::InvalidInstructionData);
-                }
+                let user_lp_token_account = user_lp_token_account.ok_or(ProgramError::NotEnoughAccountKeys)?;
+                let (output_amounts, latest_depth) =
+                    quote::remove_uniform(&pool_state, &pool_balances, lp_total_supply, exact_burn_amount);
 
-                let user_lp_token_account = next_account_info(&mut account_info_iter)?;
-                let user_share = DecT::from(exact_burn_amount) / lp_total_supply;
-                //u64 can store 19 decimals, previous_depth can theoretically go up to TOKEN_COUNT * u64::MAX
-                //hence, just to be safe, we allow for previous depth to have up to 20 decimals
-                //therefore we can only multiply with a number with at most 18 decimals to stay within
-                //the 38 max decimals range of u128
-                const DECIMAL_UPSHIFT: u32 = 18;
-                let user_depth = (pool_state.previous_depth
-                    * ((user_share * 10u64.pow(DECIMAL_UPSHIFT)).trunc() as u128))
-                    / 10u128.pow(DECIMAL_UPSHIFT);
-                let latest_depth = pool_state.previous_depth - user_depth;
+                let result = SimulationResult {
+                    lp_amount: exact_burn_amount,
+                    input_amounts: [0; TOKEN_COUNT],
+                    output_amounts,
+                    governance_mint_amount: 0,
+                };
+                validation::check_limits(&pool_state, &defi_instruction, &result)?;
+                validation::check_liquidity(&pool_balances, &result)?;
+                check_residual(user_lp_token_account, exact_burn_amount)?;
+                if is_simulation {
+                    return Self::return_simulation(result);
+                }
 
                 for i in 0..TOKEN_COUNT {
-                    let output_amount = (pool_balances[i] * user_share).trunc();
-                    if output_amount < minimum_output_amounts[i] {
-                        return Err(PoolError::OutsideSpecifiedLimits.into());
-                    }
                     Self::transfer_pool_token(
                         pool_token_accounts[i],
                         user_token_accounts[i],
-                        output_amount,
+                        output_amounts[i],
                         pool_authority_account,
                         token_program_account,
                         pool_account,
@@ -389,15 +969,22 @@ impl<const TOKEN_COUNT: usize> Processor<TOKEN_COUNT> {
                     )?;
                 }
 
+                Self::check_user_debit(
+                    user_lp_token_account,
+                    &pool_state.lp_mint_key,
+                    exact_burn_amount,
+                    user_authority_account.key,
+                    "lp token account",
+                )?;
                 Self::burn_token(
                     user_lp_token_account,
                     lp_mint_account,
                     exact_burn_amount,
                     user_authority_account,
-                    token_program_account,
+                    lp_token_program_account,
                 )?;
 
-                (0, latest_depth)
+                (-(exact_burn_amount as i128), 0, 0, latest_depth)
             }
 
             DeFiInstruction::SwapExactInput {
@@ -406,40 +993,28 @@ impl<const TOKEN_COUNT: usize> Processor<TOKEN_COUNT> {
                 minimum_output_amount,
             } => {
                 let output_token_index = output_token_index as usize;
-                if exact_input_amounts.iter().all(|amount| *amount == 0)
-                    || output_token_index >= TOKEN_COUNT
-                    || exact_input_amounts[output_token_index] != 0
-                {
-                    return Err(ProgramError::InvalidInstructionData);
-                }
+                let Quote {
+                    amount: output_amount,
+                    governance_mint_amount,
+                    lp_fee_amount,
+                    latest_depth,
+                } = quoter()?.swap_exact_input(&exact_input_amounts, output_token_index)?;
 
-                let (output_amount, governance_mint_amount, latest_depth) = result_from_equalized(
-                    Invariant::<TOKEN_COUNT>::swap_exact_input(
-                        &array_equalize(&exact_input_amounts),
-                        output_token_index,
-                        &array_equalize(&pool_balances),
-                        pool_state.amp_factor.get(Self::get_current_ts()?),
-                        pool_state.lp_fee.get(),
-                        pool_state.governance_fee.get(),
-                        to_equalized(lp_total_supply, pool_state.lp_decimal_equalizer),
-                        pool_state.previous_depth.into(),
-                    )?,
-                    pool_state.token_decimal_equalizers[output_token_index],
-                );
-
-                if output_amount < minimum_output_amount {
-                    return Err(PoolError::OutsideSpecifiedLimits.into());
+                let result = SimulationResult {
+                    lp_amount: 0,
+                    input_amounts: exact_input_amounts,
+                    output_amounts: create_array(|i| if i == output_token_index { output_amount } else { 0 }),
+                    governance_mint_amount,
+                };
+                validation::check_limits(&pool_state, &defi_instruction, &result)?;
+                validation::check_liquidity(&pool_balances, &result)?;
+                if is_simulation {
+                    return Self::return_simulation(result);
                 }
 
                 for i in 0..TOKEN_COUNT {
                     if exact_input_amounts[i] > 0 {
-                        Self::transfer_token(
-                            user_token_accounts[i],
-                            pool_token_accounts[i],
-                            exact_input_amounts[i],
-                            user_authority_account,
-                            token_program_account,
-                        )?;
+                        transfer_user_token(i, exact_input_amounts[i])?;
                     }
                 }
 
@@ -452,8 +1027,9 @@ impl<const TOKEN_COUNT: usize> Processor<TOKEN_COUNT> {
                     pool_account,
                     pool_state.nonce,
                 )?;
+                swap_amounts = Some((result.input_amounts, result.output_amounts));
 
-                (governance_mint_amount, latest_depth)
+                (0, governance_mint_amount, lp_fee_amount, latest_depth)
             }
 
             DeFiInstruction::SwapExactOutput {
@@ -462,45 +1038,33 @@ impl<const TOKEN_COUNT: usize> Processor<TOKEN_COUNT> {
                 exact_output_amounts,
             } => {
                 let input_token_index = input_token_index as usize;
-
-                if exact_output_amounts.iter().all(|amount| *amount == 0)
-                    || input_token_index >= TOKEN_COUNT
-                    || exact_output_amounts[input_token_index] != 0
-                    || exact_output_amounts
-                        .iter()
-                        .zip(pool_balances.iter())
-                        .any(|(output_amount, pool_balance)| *output_amount >= *pool_balance)
-                {
-                    return Err(ProgramError::InvalidInstructionData);
-                }
                 msg!("[DEV] calculating input_amount & governance_mint_amount");
 
-                let (input_amount, governance_mint_amount, latest_depth) = result_from_equalized(
-                    Invariant::<TOKEN_COUNT>::swap_exact_output(
-                        input_token_index,
-                        &array_equalize(&exact_output_amounts),
-                        &array_equalize(&pool_balances),
-                        pool_state.amp_factor.get(Self::get_current_ts()?),
-                        pool_state.lp_fee.get(),
-                        pool_state.governance_fee.get(),
-                        to_equalized(lp_total_supply, pool_state.lp_decimal_equalizer),
-                        pool_state.previous_depth.into(),
-                    )?,
-                    pool_state.token_decimal_equalizers[input_token_index],
-                );
+                let Quote {
+                    amount: input_amount,
+                    governance_mint_amount,
+                    lp_fee_amount,
+                    latest_depth,
+                } = quoter()?.swap_exact_output(input_token_index, &exact_output_amounts)?;
 
-                msg!("[DEV] input_amount: {}, governanace_mint_amount: {}", input_amount, governance_mint_amount);
-                if input_amount > maximum_input_amount {
-                    return Err(PoolError::OutsideSpecifiedLimits.into());
+                msg!(
+                    "[DEV] input_amount: {}, governanace_mint_amount: {}",
+                    input_amount,
+                    governance_mint_amount
+                );
+                let result = SimulationResult {
+                    lp_amount: 0,
+                    input_amounts: create_array(|i| if i == input_token_index { input_amount } else { 0 }),
+                    output_amounts: exact_output_amounts,
+                    governance_mint_amount,
+                };
+                validation::check_limits(&pool_state, &defi_instruction, &result)?;
+                validation::check_liquidity(&pool_balances, &result)?;
+                if is_simulation {
+                    return Self::return_simulation(result);
                 }
 
-                Self::transfer_token(
-                    user_token_accounts[input_token_index],
-                    pool_token_accounts[input_token_index],
-                    input_amount,
-                    user_authority_account,
-                    token_program_account,
-                )?;
+                transfer_user_token(input_token_index, input_amount)?;
 
                 for i in 0..TOKEN_COUNT {
                     msg!("[DEV] swapping exact_output_amount[{}]: {}", i, exact_output_amounts[i]);
@@ -516,8 +1080,9 @@ impl<const TOKEN_COUNT: usize> Processor<TOKEN_COUNT> {
                         )?;
                     }
                 }
+                swap_amounts = Some((result.input_amounts, result.output_amounts));
 
-                (governance_mint_amount, latest_depth)
+                (0, governance_mint_amount, lp_fee_amount, latest_depth)
             }
 
             DeFiInstruction::RemoveExactBurn {
@@ -526,36 +1091,48 @@ impl<const TOKEN_COUNT: usize> Processor<TOKEN_COUNT> {
                 minimum_output_amount,
             } => {
                 let output_token_index = output_token_index as usize;
-                if output_token_index >= TOKEN_COUNT || exact_burn_amount == 0 || exact_burn_amount >= lp_total_supply {
-                    return Err(ProgramError::InvalidInstructionData);
-                }
+                let user_lp_token_account = user_lp_token_account.ok_or(ProgramError::NotEnoughAccountKeys)?;
 
-                let user_lp_token_account = next_account_info(&mut account_info_iter)?;
+                let Quote {
+                    amount: output_amount,
+                    governance_mint_amount,
+                    lp_fee_amount,
+                    latest_depth,
+                } = quoter()?.remove_exact_burn(exact_burn_amount, output_token_index)?;
 
-                let (output_amount, governance_mint_amount, latest_depth) = result_from_equalized(
-                    Invariant::<TOKEN_COUNT>::remove_exact_burn(
-                        to_equalized(exact_burn_amount, pool_state.lp_decimal_equalizer),
-                        output_token_index,
-                        &array_equalize(&pool_balances),
-                        pool_state.amp_factor.get(Self::get_current_ts()?),
-                        pool_state.lp_fee.get(),
-                        pool_state.governance_fee.get(),
-                        to_equalized(lp_total_supply, pool_state.lp_decimal_equalizer),
-                        pool_state.previous_depth.into(),
-                    )?,
-                    pool_state.token_decimal_equalizers[output_token_index],
-                );
-
-                if output_amount < minimum_output_amount {
-                    return Err(PoolError::OutsideSpecifiedLimits.into());
+                let result = SimulationResult {
+                    lp_amount: exact_burn_amount,
+                    input_amounts: [0; TOKEN_COUNT],
+                    output_amounts: create_array(|i| if i == output_token_index { output_amount } else { 0 }),
+                    governance_mint_amount,
+                };
+                validation::check_limits(&pool_state, &defi_instruction, &result)?;
+                validation::check_liquidity(&pool_balances, &result)?;
+                check_residual(user_lp_token_account, exact_burn_amount)?;
+                Self::spend_withdrawal_announcement(
+                    &mut pool_state,
+                    withdrawal_announcement_account,
+                    exact_burn_amount,
+                    program_id,
+                    is_simulation,
+                )?;
+                if is_simulation {
+                    return Self::return_simulation(result);
                 }
 
+                Self::check_user_debit(
+                    user_lp_token_account,
+                    &pool_state.lp_mint_key,
+                    exact_burn_amount,
+                    user_authority_account.key,
+                    "lp token account",
+                )?;
                 Self::burn_token(
                     user_lp_token_account,
                     lp_mint_account,
                     exact_burn_amount,
                     user_authority_account,
-                    token_program_account,
+                    lp_token_program_account,
                 )?;
 
                 Self::transfer_pool_token(
@@ -568,48 +1145,60 @@ impl<const TOKEN_COUNT: usize> Processor<TOKEN_COUNT> {
                     pool_state.nonce,
                 )?;
 
-                (governance_mint_amount, latest_depth)
+                (
+                    -(exact_burn_amount as i128),
+                    governance_mint_amount,
+                    lp_fee_amount,
+                    latest_depth,
+                )
             }
 
             DeFiInstruction::RemoveExactOutput {
                 maximum_burn_amount,
                 exact_output_amounts,
             } => {
-                if exact_output_amounts.iter().all(|amount| *amount == 0)
-                    || maximum_burn_amount == 0
-                    || exact_output_amounts
-                        .iter()
-                        .zip(pool_balances.iter())
-                        .any(|(output_amount, pool_balance)| *output_amount >= *pool_balance)
-                {
-                    return Err(ProgramError::InvalidInstructionData);
-                }
-
-                let user_lp_token_account = next_account_info(&mut account_info_iter)?;
+                let user_lp_token_account = user_lp_token_account.ok_or(ProgramError::NotEnoughAccountKeys)?;
 
-                let (burn_amount, governance_mint_amount, latest_depth) = result_from_equalized(
-                    Invariant::<TOKEN_COUNT>::remove_exact_output(
-                        &array_equalize(&exact_output_amounts),
-                        &array_equalize(&pool_balances),
-                        pool_state.amp_factor.get(Self::get_current_ts()?),
-                        pool_state.lp_fee.get(),
-                        pool_state.governance_fee.get(),
-                        to_equalized(lp_total_supply, pool_state.lp_decimal_equalizer),
-                        pool_state.previous_depth.into(),
-                    )?,
-                    pool_state.lp_decimal_equalizer,
-                );
+                let Quote {
+                    amount: burn_amount,
+                    governance_mint_amount,
+                    lp_fee_amount,
+                    latest_depth,
+                } = quoter()?.remove_exact_output(&exact_output_amounts)?;
 
-                if burn_amount > maximum_burn_amount {
-                    return Err(PoolError::OutsideSpecifiedLimits.into());
+                let result = SimulationResult {
+                    lp_amount: burn_amount,
+                    input_amounts: [0; TOKEN_COUNT],
+                    output_amounts: exact_output_amounts,
+                    governance_mint_amount,
+                };
+                validation::check_limits(&pool_state, &defi_instruction, &result)?;
+                validation::check_liquidity(&pool_balances, &result)?;
+                check_residual(user_lp_token_account, burn_amount)?;
+                Self::spend_withdrawal_announcement(
+                    &mut pool_state,
+                    withdrawal_announcement_account,
+                    burn_amount,
+                    program_id,
+                    is_simulation,
+                )?;
+                if is_simulation {
+                    return Self::return_simulation(result);
                 }
 
+                Self::check_user_debit(
+                    user_lp_token_account,
+                    &pool_state.lp_mint_key,
+                    burn_amount,
+                    user_authority_account.key,
+                    "lp token account",
+                )?;
                 Self::burn_token(
                     user_lp_token_account,
                     lp_mint_account,
                     burn_amount,
                     user_authority_account,
-                    token_program_account,
+                    lp_token_program_account,
                 )?;
 
                 for i in 0..TOKEN_COUNT {
@@ -626,10 +1215,42 @@ impl<const TOKEN_COUNT: usize> Processor<TOKEN_COUNT> {
                     }
                 }
 
-                (governance_mint_amount, latest_depth)
+                (
+                    -(burn_amount as i128),
+                    governance_mint_amount,
+                    lp_fee_amount,
+                    latest_depth,
+                )
             }
+
+            //rejected by check_amounts
+            DeFiInstruction::Simulate(_) => return Err(ProgramError::InvalidInstructionData),
         };
 
+        if let Some((session_account, session, _)) = session {
+            session
+                .serialize(&mut &mut session_account.try_borrow_mut_data()?[..])
+                .or(Err(ProgramError::AccountDataTooSmall))?;
+        }
+        if let Some((campaign_account, rebate_account)) = rebate_accounts {
+            Self::record_rebate_fees(
+                lp_fee_amount as u128 + governance_mint_amount as u128,
+                campaign_account,
+                rebate_account,
+                user_authority_account,
+                program_id,
+            )?;
+        }
+
+        pool_state.accrue_lp_fee(lp_fee_amount, lp_total_supply);
+        pool_state.accrue_lp_supply_seconds(lp_total_supply, Self::get_current_ts()?);
+        let lp_supply_event = LpSupplyEvent {
+            user_lp_change,
+            governance_mint_amount,
+            lp_supply: (lp_total_supply as i128 + user_lp_change + governance_mint_amount as i128) as u64,
+        };
+        //the locks' share is only minted once claimed (see ClaimLockRewards)
+        let governance_mint_amount = governance_mint_amount - pool_state.accrue_lock_rewards(governance_mint_amount);
         if governance_mint_amount > 0 {
             // msg!("[DEV] transferring {} as governance_fee", governance_mint_amount);
             Self::mint_token(
@@ -637,118 +1258,4365 @@ impl<const TOKEN_COUNT: usize> Processor<TOKEN_COUNT> {
                 governance_fee_account,
                 governance_mint_amount,
                 pool_authority_account,
-                token_program_account,
+                lp_token_program_account,
                 pool_account,
                 pool_state.nonce,
             )?;
         }
 
+        //uniform removes leave the prices as they are (and aren't valued at the pool's rates)
+        if !is_uniform_remove {
+            let latest_balances: [_; TOKEN_COUNT] = create_result_array(|i| -> Result<_, ProgramError> {
+                Ok(Self::check_program_owner_and_unpack::<TokenState>(pool_token_accounts[i])?.amount)
+            })?;
+            let amp_factor = pool_state.effective_amp_factor(Self::get_current_ts()?);
+            Self::update_internal_oracle(&mut pool_state, &latest_balances, &rates, amp_factor, latest_depth)?;
+            pool_state.depth_rates = rates;
+        }
         pool_state.previous_depth = latest_depth;
-        Self::serialize_pool(&pool_state, pool_account)
+        Self::serialize_pool(&pool_state, pool_account)?;
+        msg!("{}", lp_supply_event.to_log());
+
+        if let (Some((hook_program_account, hook_accounts)), Some((input_amounts, output_amounts))) =
+            (hook_accounts, swap_amounts)
+        {
+            let hook_data = SwapHookData {
+                pool: *pool_account.key,
+                input_amounts,
+                output_amounts,
+            };
+            Self::call_swap_hook(hook_program_account, hook_accounts, &hook_data)?;
+        }
+        Ok(())
     }
 
-    fn process_governance_instruction(
-        governance_instruction: GovernanceInstruction<TOKEN_COUNT>,
+    //the instructions sysvar only holds the transaction's top-level instructions, so the caller that is checked is the
+    // program the transaction called, i.e. the calling program unless it was called via cpi itself (the stack height,
+    // which would reveal the direct caller, isn't available to programs yet). a top-level instruction of this program
+    // is a direct call (or a RoutedSwap, whose legs are checked like direct calls)
+    fn check_caller(
+        pool_state: &PoolState<TOKEN_COUNT>,
         program_id: &Pubkey,
-        accounts: &[AccountInfo],
+        instructions_account: &AccountInfo,
     ) -> ProgramResult {
-        let account_info_iter = &mut accounts.iter();
-        let pool_account = next_account_info(account_info_iter)?;
-        let mut pool_state = Self::check_and_deserialize_pool_state(&pool_account, &program_id)?;
-
-        Self::verify_governance_signature(next_account_info(account_info_iter)?, &pool_state)?;
-
-        match governance_instruction {
-            GovernanceInstruction::PrepareFeeChange { lp_fee, governance_fee } => {
-                if lp_fee + governance_fee >= DecT::from(1) {
-                    return Err(PoolError::InvalidFeeInput.into());
-                }
+        let current_index = load_current_index_checked(instructions_account)?;
+        let caller = load_instruction_at_checked(current_index as usize, instructions_account)?.program_id;
+        let is_allowed = if caller == *program_id {
+            pool_state.caller_mode == CallerMode::AllowedPrograms
+        } else {
+            pool_state.is_caller_allowed(&caller)
+        };
+        if !is_allowed {
+            return Err(PoolError::CallerNotAllowed.into());
+        }
+        Ok(())
+    }
 
-                pool_state.prepared_lp_fee = PoolFee::new(lp_fee)?;
-                pool_state.prepared_governance_fee = PoolFee::new(governance_fee)?;
-                pool_state.fee_transition_ts = Self::get_current_ts()? + ENACT_DELAY;
+    //whether the user passed its account of the gate's allowlist (at the address `find_allowed_address` gives) resp.
+    // a token account of the gating mint that holds some
+    fn passes_gate(
+        gate: UserGate,
+        find_allowed_address: fn(&Pubkey, &Pubkey, &Pubkey) -> (Pubkey, u8),
+        pool_account: &AccountInfo,
+        user_authority_account: &AccountInfo,
+        gate_account: &AccountInfo,
+        program_id: &Pubkey,
+    ) -> bool {
+        match gate {
+            UserGate::Open => true,
+            //only governance creates accounts at these addresses, a closed one has no lamports left
+            UserGate::Allowlist => {
+                *gate_account.key == find_allowed_address(program_id, pool_account.key, user_authority_account.key).0
+                    && gate_account.owner == program_id
+                    && gate_account.lamports() > 0
+            }
+            //frozen accounts count too, passes are often frozen to make them non-transferable
+            UserGate::GatingToken { mint } => {
+                *gate_account.owner == spl_token::id()
+                    && matches!(
+                        TokenState::unpack(&gate_account.data.borrow()),
+                        Ok(token_state) if token_state.mint == mint
+                            && token_state.owner == *user_authority_account.key
+                            && token_state.amount > 0
+                    )
             }
+        }
+    }
 
-            GovernanceInstruction::EnactFeeChange {} => {
-                if pool_state.fee_transition_ts == 0 {
-                    return Err(PoolError::InvalidEnact.into());
-                }
+    //creates resp. closes `user`'s account of an allowlist, whose addresses `find_allowed_address` gives, from the
+    // accounts that follow a SetUserAllowed resp. SetDepositorAllowed
+    fn set_allowed<'a>(
+        allowlist_seed: &[u8],
+        find_allowed_address: fn(&Pubkey, &Pubkey, &Pubkey) -> (Pubkey, u8),
+        user: &Pubkey,
+        allowed: bool,
+        pool_account: &AccountInfo<'a>,
+        account_info_iter: &mut std::slice::Iter<AccountInfo<'a>>,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let allowed_user_account = next_account_info(account_info_iter)?;
+        let payer_account = next_account_info(account_info_iter)?;
+        let system_program_account = next_account_info(account_info_iter)?;
+        let (allowed_user_key, allowed_user_bump) = find_allowed_address(program_id, pool_account.key, user);
+        if *allowed_user_account.key != allowed_user_key {
+            return Err(PoolError::NonCanonicalAddress.into());
+        }
+        let is_allowed = allowed_user_account.owner == program_id;
+        if allowed && !is_allowed {
+            Self::create_program_address_account(
+                payer_account,
+                allowed_user_account,
+                system_program_account,
+                solana_program::borsh::get_packed_len::<AllowedUser>(),
+                program_id,
+                &[
+                    allowlist_seed,
+                    &pool_account.key.to_bytes(),
+                    &user.to_bytes(),
+                    &[allowed_user_bump],
+                ],
+            )?;
+            AllowedUser {
+                pool: *pool_account.key,
+                user: *user,
+            }
+            .serialize(&mut &mut allowed_user_account.try_borrow_mut_data()?[..])
+            .or(Err(ProgramError::AccountDataTooSmall))?;
+        } else if !allowed && is_allowed {
+            **payer_account.lamports.borrow_mut() += allowed_user_account.lamports();
+            **allowed_user_account.lamports.borrow_mut() = 0;
+            allowed_user_account.data.borrow_mut().fill(0);
+        }
+        Ok(())
+    }
+
+    //adds `reward_mint` as a reward of the pool's gauge, creating the gauge and the vaults that don't exist yet, or
+    // retires the reward for the default funder, see SetGaugeReward
+    fn set_gauge_reward<'a>(
+        reward_mint: &Pubkey,
+        funder: &Pubkey,
+        pool_state: &PoolState<TOKEN_COUNT>,
+        pool_account: &AccountInfo<'a>,
+        account_info_iter: &mut std::slice::Iter<AccountInfo<'a>>,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let pool_authority_account = next_account_info(account_info_iter)?;
+        if *pool_authority_account.key != Self::get_pool_authority(pool_account.key, pool_state.nonce, program_id)? {
+            return Err(PoolError::InvalidPoolAuthorityAccount.into());
+        }
+        let gauge_account = next_account_info(account_info_iter)?;
+        let reward_mint_account = next_account_info(account_info_iter)?;
+        if *reward_mint_account.key != *reward_mint {
+            return Err(PoolError::InvalidMintAccount.into());
+        }
+        let reward_vault_account = next_account_info(account_info_iter)?;
+        let token_program_account = next_account_info(account_info_iter)?;
+        if *token_program_account.key != spl_token::id() {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let (gauge_key, gauge_bump) = find_gauge_address(program_id, pool_account.key);
+        let (reward_vault_key, reward_vault_bump) = find_gauge_vault_address(program_id, pool_account.key, reward_mint);
+        if *gauge_account.key != gauge_key || *reward_vault_account.key != reward_vault_key {
+            return Err(PoolError::NonCanonicalAddress.into());
+        }
+        let current_ts = Self::get_current_ts()?;
+
+        if *funder == Pubkey::default() {
+            let funder_reward_account = next_account_info(account_info_iter)?;
+            let mut gauge = Self::check_and_deserialize_gauge(gauge_account, pool_account, program_id)?;
+            let index = gauge
+                .reward_index(reward_mint)
+                .filter(|&index| !gauge.rewards[index].is_retired())
+                .ok_or_else(|| PoolError::InvalidGauge.with_context("not an active reward of the gauge"))?;
+            //only the funder gets back what it funded
+            let funder_reward_state = Self::check_program_owner_and_unpack::<TokenState>(funder_reward_account)?;
+            if funder_reward_state.owner != gauge.rewards[index].funder {
+                return Err(
+                    PoolError::InvalidGauge.with_context(format_args!("funded by {}", gauge.rewards[index].funder))
+                );
+            }
+
+            let unemitted = gauge.retire(index, current_ts);
+            if unemitted > 0 {
+                Self::transfer_pool_token(
+                    reward_vault_account,
+                    funder_reward_account,
+                    unemitted,
+                    pool_authority_account,
+                    token_program_account,
+                    pool_account,
+                    pool_state.nonce,
+                )?;
+            }
+            gauge
+                .serialize(&mut &mut gauge_account.try_borrow_mut_data()?[..])
+                .or(Err(ProgramError::AccountDataTooSmall))?;
+            msg!("gauge reward {} retired, refunded {}", reward_mint, unemitted);
+            return Ok(());
+        }
+
+        if pool_state.is_sunset() {
+            return Err(PoolError::PoolIsSunset.into());
+        }
+        //the gauge's lp vault would have to receive them
+        if pool_state.lp_non_transferable {
+            return Err(PoolError::LpNonTransferable.into());
+        }
+        //the staked lp tokens and the rewards would share a vault
+        if *reward_mint == pool_state.lp_mint_key {
+            return Err(PoolError::InvalidGauge.with_context("the lp token can't be a reward token"));
+        }
+        let lp_mint_account = next_account_info(account_info_iter)?;
+        if *lp_mint_account.key != pool_state.lp_mint_key {
+            return Err(PoolError::InvalidMintAccount.into());
+        }
+        let lp_vault_account = next_account_info(account_info_iter)?;
+        let (lp_vault_key, lp_vault_bump) = find_gauge_vault_address(program_id, pool_account.key, lp_mint_account.key);
+        if *lp_vault_account.key != lp_vault_key {
+            return Err(PoolError::NonCanonicalAddress.into());
+        }
+        let payer_account = next_account_info(account_info_iter)?;
+        let system_program_account = next_account_info(account_info_iter)?;
+        let rent_account = next_account_info(account_info_iter)?;
+
+        let create_vault = |vault_account: &AccountInfo<'a>, mint_account: &AccountInfo<'a>, bump: u8| {
+            Self::create_program_address_account(
+                payer_account,
+                vault_account,
+                system_program_account,
+                TokenState::LEN,
+                &spl_token::id(),
+                &[
+                    GAUGE_SEED,
+                    &pool_account.key.to_bytes(),
+                    &mint_account.key.to_bytes(),
+                    &[bump],
+                ],
+            )?;
+            invoke(
+                &spl_token::instruction::initialize_account2(
+                    &spl_token::id(),
+                    vault_account.key,
+                    mint_account.key,
+                    pool_authority_account.key,
+                )?,
+                &[
+                    vault_account.clone(),
+                    mint_account.clone(),
+                    rent_account.clone(),
+                    token_program_account.clone(),
+                ],
+            )
+        };
+        let mut gauge = if gauge_account.owner != program_id {
+            create_vault(lp_vault_account, lp_mint_account, lp_vault_bump)?;
+            Self::create_program_address_account(
+                payer_account,
+                gauge_account,
+                system_program_account,
+                solana_program::borsh::get_packed_len::<Gauge>(),
+                program_id,
+                &[GAUGE_SEED, &pool_account.key.to_bytes(), &[gauge_bump]],
+            )?;
+            Gauge {
+                pool: *pool_account.key,
+                last_update_ts: current_ts,
+                ..Gauge::default()
+            }
+        } else {
+            Gauge::deserialize(&mut &**gauge_account.data.borrow())?
+        };
+        let index = match gauge.reward_index(reward_mint) {
+            Some(index) => index,
+            None => {
+                let index = gauge.free_index(current_ts).ok_or_else(|| {
+                    PoolError::InvalidGauge.with_context("all reward slots are taken or still claimable")
+                })?;
+                //the vault of a mint whose slot went to another one is still around
+                if reward_vault_account.owner != &spl_token::id() {
+                    create_vault(reward_vault_account, reward_mint_account, reward_vault_bump)?;
+                }
+                gauge.add_reward(index, reward_mint);
+                index
+            }
+        };
+        gauge.rewards[index].funder = *funder;
+        gauge
+            .serialize(&mut &mut gauge_account.try_borrow_mut_data()?[..])
+            .or(Err(ProgramError::AccountDataTooSmall))?;
+        msg!("gauge reward {} funded by {}", reward_mint, funder);
+        Ok(())
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn start_rebate_campaign<'a>(
+        campaign_id: u64,
+        rebate_mint: &Pubkey,
+        budget: AtomicT,
+        start_ts: UnixTimestamp,
+        end_ts: UnixTimestamp,
+        pool_state: &mut PoolState<TOKEN_COUNT>,
+        pool_account: &AccountInfo<'a>,
+        governance_account: &AccountInfo<'a>,
+        account_info_iter: &mut std::slice::Iter<AccountInfo<'a>>,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        if pool_state.is_sunset() {
+            return Err(PoolError::PoolIsSunset.into());
+        }
+        let current_ts = Self::get_current_ts()?;
+        if budget == 0 || start_ts < current_ts || end_ts <= start_ts || end_ts - start_ts > MAX_REBATE_WINDOW {
+            return Err(PoolError::InvalidRebateCampaign.into());
+        }
+        //swaps only pass the latest campaign
+        if pool_state.rebate_end_ts > current_ts {
+            return Err(PoolError::RebateCampaignNotEnded.with_context(format_args!(
+                "{} ends at {}",
+                pool_state.rebate_campaign, pool_state.rebate_end_ts
+            )));
+        }
+        let pool_authority_account = next_account_info(account_info_iter)?;
+        if *pool_authority_account.key != Self::get_pool_authority(pool_account.key, pool_state.nonce, program_id)? {
+            return Err(PoolError::InvalidPoolAuthorityAccount.into());
+        }
+        let campaign_account = next_account_info(account_info_iter)?;
+        let rebate_mint_account = next_account_info(account_info_iter)?;
+        if *rebate_mint_account.key != *rebate_mint {
+            return Err(PoolError::InvalidMintAccount.into());
+        }
+        let vault_account = next_account_info(account_info_iter)?;
+        let governance_rebate_account = next_account_info(account_info_iter)?;
+        let payer_account = next_account_info(account_info_iter)?;
+        let token_program_account = next_account_info(account_info_iter)?;
+        let system_program_account = next_account_info(account_info_iter)?;
+        let rent_account = next_account_info(account_info_iter)?;
+        if *token_program_account.key != spl_token::id() {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let (campaign_key, campaign_bump) = find_rebate_campaign_address(program_id, pool_account.key, campaign_id);
+        let (vault_key, vault_bump) = find_rebate_vault_address(program_id, &campaign_key);
+        if *campaign_account.key != campaign_key || *vault_account.key != vault_key {
+            return Err(PoolError::NonCanonicalAddress.into());
+        }
+        if campaign_account.owner == program_id {
+            return Err(PoolError::InvalidRebateCampaign.with_context(format_args!("id {} is taken", campaign_id)));
+        }
+
+        Self::create_program_address_account(
+            payer_account,
+            campaign_account,
+            system_program_account,
+            solana_program::borsh::get_packed_len::<RebateCampaign>(),
+            program_id,
+            &[
+                REBATE_CAMPAIGN_SEED,
+                &pool_account.key.to_bytes(),
+                &campaign_id.to_le_bytes(),
+                &[campaign_bump],
+            ],
+        )?;
+        Self::create_program_address_account(
+            payer_account,
+            vault_account,
+            system_program_account,
+            TokenState::LEN,
+            &spl_token::id(),
+            &[REBATE_CAMPAIGN_SEED, &campaign_key.to_bytes(), &[vault_bump]],
+        )?;
+        invoke(
+            &spl_token::instruction::initialize_account2(
+                &spl_token::id(),
+                vault_account.key,
+                rebate_mint_account.key,
+                pool_authority_account.key,
+            )?,
+            &[
+                vault_account.clone(),
+                rebate_mint_account.clone(),
+                rent_account.clone(),
+                token_program_account.clone(),
+            ],
+        )?;
+        Self::transfer_token(
+            governance_rebate_account,
+            vault_account,
+            budget,
+            governance_account,
+            token_program_account,
+        )?;
+        RebateCampaign {
+            pool: *pool_account.key,
+            id: campaign_id,
+            rebate_mint: *rebate_mint,
+            budget,
+            start_ts,
+            end_ts,
+            ..RebateCampaign::default()
+        }
+        .serialize(&mut &mut campaign_account.try_borrow_mut_data()?[..])
+        .or(Err(ProgramError::AccountDataTooSmall))?;
+        pool_state.rebate_campaign = campaign_key;
+        pool_state.rebate_end_ts = end_ts;
+        msg!("rebate campaign {}: {} until {}", campaign_id, budget, end_ts);
+        Ok(())
+    }
+
+    fn reclaim_rebate_budget<'a>(
+        campaign_id: u64,
+        pool_state: &PoolState<TOKEN_COUNT>,
+        pool_account: &AccountInfo<'a>,
+        account_info_iter: &mut std::slice::Iter<AccountInfo<'a>>,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let pool_authority_account = next_account_info(account_info_iter)?;
+        if *pool_authority_account.key != Self::get_pool_authority(pool_account.key, pool_state.nonce, program_id)? {
+            return Err(PoolError::InvalidPoolAuthorityAccount.into());
+        }
+        let campaign_account = next_account_info(account_info_iter)?;
+        let vault_account = next_account_info(account_info_iter)?;
+        let governance_rebate_account = next_account_info(account_info_iter)?;
+        let token_program_account = next_account_info(account_info_iter)?;
+        if *token_program_account.key != spl_token::id() {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let mut campaign = Self::check_and_deserialize_rebate_campaign(campaign_account, pool_account, program_id)?;
+        if campaign.id != campaign_id
+            || *vault_account.key != find_rebate_vault_address(program_id, campaign_account.key).0
+        {
+            return Err(PoolError::NonCanonicalAddress.into());
+        }
+        if campaign.reclaimed {
+            return Err(PoolError::InvalidRebateCampaign.with_context("the budget was reclaimed already"));
+        }
+        //nobody can claim a rebate of a campaign that no swap recorded its fees with
+        let reclaimable_ts = if campaign.total_fees == 0 {
+            campaign.end_ts
+        } else {
+            campaign.end_ts + REBATE_CLAIM_PERIOD
+        };
+        if Self::get_current_ts()? < reclaimable_ts {
+            return Err(
+                PoolError::RebateCampaignNotEnded.with_context(format_args!("reclaimable at {}", reclaimable_ts))
+            );
+        }
+
+        let unclaimed = Self::check_program_owner_and_unpack::<TokenState>(vault_account)?.amount;
+        if unclaimed > 0 {
+            Self::transfer_pool_token(
+                vault_account,
+                governance_rebate_account,
+                unclaimed,
+                pool_authority_account,
+                token_program_account,
+                pool_account,
+                pool_state.nonce,
+            )?;
+        }
+        campaign.reclaimed = true;
+        campaign
+            .serialize(&mut &mut campaign_account.try_borrow_mut_data()?[..])
+            .or(Err(ProgramError::AccountDataTooSmall))?;
+        msg!("rebate campaign {}: reclaimed {}", campaign_id, unclaimed);
+        Ok(())
+    }
+
+    //adds a swap's fees to those of the user's rebate and of the campaign, unless the campaign's window isn't open
+    fn record_rebate_fees(
+        fees: u128,
+        campaign_account: &AccountInfo,
+        rebate_account: &AccountInfo,
+        user_account: &AccountInfo,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        if *rebate_account.key != find_rebate_address(program_id, campaign_account.key, user_account.key).0 {
+            return Err(PoolError::NonCanonicalAddress.into());
+        }
+        if rebate_account.owner != program_id {
+            return Err(PoolError::InvalidRebateCampaign.with_context("the user hasn't joined the campaign"));
+        }
+        //the pool's campaign, which the program created
+        let mut campaign = RebateCampaign::deserialize(&mut &**campaign_account.data.borrow())?;
+        if !campaign.is_open(Self::get_current_ts()?) {
+            return Ok(());
+        }
+        let mut rebate = Rebate::deserialize(&mut &**rebate_account.data.borrow())?;
+        rebate.fees += fees;
+        campaign.total_fees += fees;
+
+        rebate
+            .serialize(&mut &mut rebate_account.try_borrow_mut_data()?[..])
+            .or(Err(ProgramError::AccountDataTooSmall))?;
+        campaign
+            .serialize(&mut &mut campaign_account.try_borrow_mut_data()?[..])
+            .or(Err(ProgramError::AccountDataTooSmall))
+    }
+
+    //sets the settled stake's working amount from the boost that the gauge's ve program returns for it, which follows
+    // along with the owner's ve account, or to its amount if the gauge has no ve program, see UpdateGaugeBoost
+    fn update_gauge_boost<'a>(
+        gauge: &mut Gauge,
+        stake: &mut GaugeStake,
+        pool_account: &AccountInfo<'a>,
+        owner_account: &AccountInfo<'a>,
+        account_info_iter: &mut std::slice::Iter<AccountInfo<'a>>,
+    ) -> ProgramResult {
+        let boost_bps = if gauge.ve_program == Pubkey::default() {
+            BPS_ONE
+        } else {
+            let ve_program_account = next_account_info(account_info_iter)?;
+            let ve_account = next_account_info(account_info_iter)?;
+            if *ve_program_account.key != gauge.ve_program
+                || *ve_account.key != find_ve_boost_address(&gauge.ve_program, pool_account.key, owner_account.key).0
+            {
+                return Err(PoolError::InvalidVeProgram.into());
+            }
+            let query = VeBoostQuery {
+                pool: *pool_account.key,
+                owner: *owner_account.key,
+                amount: stake.amount,
+            };
+            //invoked (rather than invoke_signed) so the ve program gets none of the pool's privileges
+            let query_ix = Instruction {
+                program_id: gauge.ve_program,
+                accounts: vec![
+                    AccountMeta::new_readonly(*pool_account.key, false),
+                    AccountMeta::new_readonly(*owner_account.key, false),
+                    AccountMeta::new_readonly(*ve_account.key, false),
+                ],
+                data: query.try_to_vec()?,
+            };
+            invoke(
+                &query_ix,
+                &[
+                    pool_account.clone(),
+                    owner_account.clone(),
+                    ve_account.clone(),
+                    ve_program_account.clone(),
+                ],
+            )?;
+            match get_return_data() {
+                Some((program, data)) if program == gauge.ve_program => {
+                    u64::try_from_slice(&data).or(Err(PoolError::InvalidVeProgram))?
+                }
+                _ => return Err(PoolError::InvalidVeProgram.with_context("no boost returned")),
+            }
+        };
+        stake.apply_boost(gauge, boost_bps);
+        Ok(())
+    }
+
+    //invoked (rather than invoke_signed) so the hook gets none of the pool's privileges, and without the signatures of
+    // the accounts passed on to it, which would let the hook spend what the user transfer authority may
+    fn call_swap_hook<'a>(
+        hook_program_account: &AccountInfo<'a>,
+        hook_accounts: &[AccountInfo<'a>],
+        hook_data: &SwapHookData<TOKEN_COUNT>,
+    ) -> ProgramResult {
+        let hook_ix = Instruction {
+            program_id: *hook_program_account.key,
+            accounts: hook_accounts
+                .iter()
+                .map(|account| AccountMeta {
+                    pubkey: *account.key,
+                    is_signer: false,
+                    is_writable: account.is_writable,
+                })
+                .collect(),
+            data: hook_data.try_to_vec()?,
+        };
+        let mut account_infos = hook_accounts.to_vec();
+        account_infos.push(hook_program_account.clone());
+        invoke(&hook_ix, &account_infos)
+    }
+
+    //fails outside of the pool's trading hours and during its maintenance windows, announces the next window from
+    // MAINTENANCE_NOTICE before its start
+    fn check_trading_schedule(pool_state: &PoolState<TOKEN_COUNT>, current_ts: UnixTimestamp) -> ProgramResult {
+        validation::check_trading_schedule(pool_state, current_ts)?;
+        Self::log_maintenance_notice(pool_state, current_ts);
+        Ok(())
+    }
+
+    fn log_maintenance_notice(pool_state: &PoolState<TOKEN_COUNT>, current_ts: UnixTimestamp) {
+        if let Some(window) = pool_state.next_maintenance(current_ts) {
+            if window.start_ts - current_ts <= MAINTENANCE_NOTICE {
+                let event = MaintenanceEvent {
+                    start_ts: window.start_ts,
+                    end_ts: window.end_ts,
+                };
+                msg!("{}", event.to_log());
+            }
+        }
+    }
+
+    //removes that, along with the unannounced removes of the current slot, burn more than the pool's threshold spend
+    // `burn_amount` of the user's announcement, whose cooldown has to have passed. the others add to the slot's
+    // unannounced burns. simulations are checked alike but don't spend or add anything
+    fn spend_withdrawal_announcement(
+        pool_state: &mut PoolState<TOKEN_COUNT>,
+        withdrawal_announcement_account: Option<&AccountInfo>,
+        burn_amount: AtomicT,
+        program_id: &Pubkey,
+        is_simulation: bool,
+    ) -> ProgramResult {
+        let withdrawal_announcement_account = match withdrawal_announcement_account {
+            Some(account) => account,
+            None => return Ok(()),
+        };
+        let current_slot = Clock::get()?.slot;
+        let unannounced_burn_amount = if pool_state.unannounced_burn_slot == current_slot {
+            pool_state.unannounced_burn_amount
+        } else {
+            0
+        }
+        .saturating_add(burn_amount);
+        if unannounced_burn_amount <= pool_state.withdrawal_cooldown_threshold {
+            if !is_simulation {
+                pool_state.unannounced_burn_slot = current_slot;
+                pool_state.unannounced_burn_amount = unannounced_burn_amount;
+            }
+            return Ok(());
+        }
+        if withdrawal_announcement_account.owner != program_id || withdrawal_announcement_account.lamports() == 0 {
+            return Err(PoolError::WithdrawalNotAnnounced.into());
+        }
+        let mut announcement =
+            WithdrawalAnnouncement::deserialize(&mut &**withdrawal_announcement_account.data.borrow())?;
+        let current_ts = Self::get_current_ts()?;
+        let ready_ts = announcement.ready_ts(pool_state.withdrawal_cooldown);
+        if current_ts < ready_ts {
+            return Err(PoolError::WithdrawalCooldownActive.into());
+        }
+        if current_ts >= ready_ts + WITHDRAWAL_ANNOUNCEMENT_VALIDITY || announcement.lp_amount < burn_amount {
+            return Err(PoolError::WithdrawalNotAnnounced.into());
+        }
+        if !is_simulation {
+            announcement.lp_amount -= burn_amount;
+            announcement
+                .serialize(&mut &mut withdrawal_announcement_account.try_borrow_mut_data()?[..])
+                .or(Err(ProgramError::AccountDataTooSmall))?;
+        }
+        Ok(())
+    }
+
+    //the program vetoes the instruction by failing, which fails the whole transaction. the accounts are passed on
+    // read-only and without the pool's signature
+    fn check_compliance<'a>(
+        pool_state: &PoolState<TOKEN_COUNT>,
+        pool_account: &AccountInfo<'a>,
+        user_authority_account: &AccountInfo<'a>,
+        account_info_iter: &mut std::slice::Iter<AccountInfo<'a>>,
+        instruction: DeFiInstruction<TOKEN_COUNT>,
+    ) -> ProgramResult {
+        let compliance_program_account = next_account_info(account_info_iter)?;
+        let compliance_account = next_account_info(account_info_iter)?;
+        let compliance_key = find_compliance_address(
+            &pool_state.compliance_program,
+            pool_account.key,
+            user_authority_account.key,
+        )
+        .0;
+        if *compliance_program_account.key != pool_state.compliance_program || *compliance_account.key != compliance_key
+        {
+            return Err(PoolError::InvalidComplianceAccount.into());
+        }
+        let check_data = ComplianceCheckData {
+            pool: *pool_account.key,
+            user: *user_authority_account.key,
+            instruction,
+        };
+        Self::call_compliance_program(
+            compliance_program_account,
+            &[pool_account, user_authority_account, compliance_account],
+            &check_data,
+        )
+    }
+
+    fn call_compliance_program<'a>(
+        compliance_program_account: &AccountInfo<'a>,
+        accounts: &[&AccountInfo<'a>],
+        check_data: &ComplianceCheckData<TOKEN_COUNT>,
+    ) -> ProgramResult {
+        let check_ix = Instruction {
+            program_id: *compliance_program_account.key,
+            accounts: accounts
+                .iter()
+                .map(|account| AccountMeta::new_readonly(*account.key, account.is_signer))
+                .collect(),
+            data: check_data.try_to_vec()?,
+        };
+        let mut account_infos: Vec<_> = accounts.iter().map(|account| (*account).clone()).collect();
+        account_infos.push(compliance_program_account.clone());
+        invoke(&check_ix, &account_infos)
+    }
+
+    fn return_simulation(simulation_result: SimulationResult<TOKEN_COUNT>) -> ProgramResult {
+        set_return_data(&simulation_result.try_to_vec()?);
+        Ok(())
+    }
+
+    fn process_governance_instruction(
+        governance_instruction: GovernanceInstruction<TOKEN_COUNT>,
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let pool_account = next_account_info(account_info_iter)?;
+        let mut pool_state = Self::check_and_deserialize_pool_state(&pool_account, &program_id)?;
+
+        let governance_account = next_account_info(account_info_iter)?;
+        //renouncing governance clears the roles too
+        match governance_instruction.role() {
+            Some(role) if pool_state.role_key(role) == Some(*governance_account.key) => {
+                if !governance_account.is_signer {
+                    return Err(ProgramError::MissingRequiredSignature);
+                }
+            }
+            _ => Self::verify_governance_signature(governance_account, &pool_state)?,
+        }
+
+        match governance_instruction {
+            GovernanceInstruction::PrepareFeeChange { lp_fee, governance_fee } => {
+                if lp_fee + governance_fee >= DecT::from(1) {
+                    return Err(PoolError::InvalidFeeInput.into());
+                }
+
+                pool_state.prepared_lp_fee = PoolFee::new(lp_fee)?;
+                pool_state.prepared_governance_fee = PoolFee::new(governance_fee)?;
+                pool_state.fee_transition_ts = Self::get_current_ts()? + ENACT_DELAY;
+            }
+
+            GovernanceInstruction::EnactFeeChange {} => {
+                if pool_state.fee_transition_ts == 0 {
+                    return Err(PoolError::InvalidEnact.into());
+                }
+
+                if pool_state.fee_transition_ts > Self::get_current_ts()? {
+                    return Err(PoolError::InsufficientDelay.into());
+                }
+
+                if pool_state.prepared_governance_fee.get() > DecT::from(0)
+                    && pool_state.governance_fee_key == Pubkey::default()
+                {
+                    return Err(PoolError::InvalidGovernanceFeeAccount.into());
+                }
+
+                pool_state.lp_fee = pool_state.prepared_lp_fee;
+                pool_state.governance_fee = pool_state.prepared_governance_fee;
+                pool_state.prepared_lp_fee = PoolFee::default();
+                pool_state.prepared_governance_fee = PoolFee::default();
+                pool_state.fee_transition_ts = 0;
+            }
+
+            GovernanceInstruction::PrepareGovernanceTransition {
+                upcoming_governance_key,
+            } => {
+                pool_state.prepared_governance_key = upcoming_governance_key;
+                pool_state.governance_transition_ts = Self::get_current_ts()? + ENACT_DELAY;
+            }
+
+            GovernanceInstruction::EnactGovernanceTransition {} => {
+                if pool_state.governance_transition_ts == 0 {
+                    return Err(PoolError::InvalidEnact.into());
+                }
+
+                if pool_state.governance_transition_ts > Self::get_current_ts()? {
+                    return Err(PoolError::InsufficientDelay.into());
+                }
+
+                pool_state.governance_key = pool_state.prepared_governance_key;
+                pool_state.prepared_governance_key = Pubkey::default();
+                pool_state.governance_transition_ts = 0;
+            }
+
+            GovernanceInstruction::ChangeGovernanceFeeAccount { governance_fee_key } => {
+                if governance_fee_key != Pubkey::default() {
+                    let governance_fee_account = next_account_info(account_info_iter)?;
+                    if *governance_fee_account.key != governance_fee_key {
+                        return Err(PoolError::InvalidGovernanceFeeAccount.into());
+                    }
+
+                    let governance_fee_state = Self::unpack_lp_account(governance_fee_account)?;
+                    if governance_fee_state.mint != pool_state.lp_mint_key {
+                        return Err(TokenError::MintMismatch.into());
+                    }
+                } else if pool_state.governance_fee.get() != DecT::from(0) {
+                    return Err(PoolError::InvalidGovernanceFeeAccount.into());
+                }
+
+                pool_state.governance_fee_key = governance_fee_key;
+            }
+
+            GovernanceInstruction::MigrateGovernanceFeeAccount { governance_fee_key } => {
+                if pool_state.lp_non_transferable {
+                    return Err(PoolError::LpNonTransferable.into());
+                }
+                let old_fee_account = next_account_info(account_info_iter)?;
+                let new_fee_account = next_account_info(account_info_iter)?;
+                let token_program_account = next_account_info(account_info_iter)?;
+                if *old_fee_account.key != pool_state.governance_fee_key
+                    || *new_fee_account.key != governance_fee_key
+                    || governance_fee_key == pool_state.governance_fee_key
+                {
+                    return Err(PoolError::InvalidGovernanceFeeAccount.into());
+                }
+                if *token_program_account.key != spl_token::id() {
+                    return Err(ProgramError::IncorrectProgramId);
+                }
+
+                let new_fee_state = Self::check_program_owner_and_unpack::<TokenState>(new_fee_account)?;
+                if new_fee_state.mint != pool_state.lp_mint_key {
+                    return Err(TokenError::MintMismatch.into());
+                }
+                //only its owner can ever move the fees
+                if new_fee_state.is_frozen()
+                    || new_fee_state.delegate.is_some()
+                    || new_fee_state.close_authority.is_some()
+                {
+                    return Err(PoolError::InvalidGovernanceFeeAccount.into());
+                }
+
+                if *old_fee_account.owner == spl_token::id() {
+                    let old_fee_state = Self::check_program_owner_and_unpack::<TokenState>(old_fee_account)?;
+                    if old_fee_state.mint == pool_state.lp_mint_key && old_fee_state.amount > 0 {
+                        msg!(
+                            "governance fee account: moving {} from {} to {}",
+                            old_fee_state.amount,
+                            old_fee_account.key,
+                            new_fee_account.key
+                        );
+                        Self::transfer_token(
+                            old_fee_account,
+                            new_fee_account,
+                            old_fee_state.amount,
+                            governance_account,
+                            token_program_account,
+                        )?;
+                    }
+                }
+
+                pool_state.governance_fee_key = governance_fee_key;
+            }
+
+            GovernanceInstruction::AdjustAmpFactor {
+                target_ts,
+                target_value,
+            } => {
+                pool_state
+                    .amp_factor
+                    .set_target(Self::get_current_ts()?, target_value, target_ts)?;
+            }
+
+            GovernanceInstruction::SetPaused { paused } => {
+                pool_state.is_paused = paused;
+            }
+
+            GovernanceInstruction::SetTokenFlags { token_index, flags } => {
+                let token_index = token_index as usize;
+                if token_index >= TOKEN_COUNT {
+                    return Err(ProgramError::InvalidInstructionData);
+                }
+                pool_state.token_flags[token_index] = flags;
+            }
+
+            GovernanceInstruction::SetOracleGuard { max_deviation } => {
+                //the oracles themselves are only bound through the enact delay of SetTokenOracle
+                if max_deviation != DecT::from(0) && !pool_state.has_token_oracles() {
+                    return Err(PoolError::InvalidOracleAccount.into());
+                }
+                pool_state.max_oracle_deviation = PoolFee::new(max_deviation)?;
+            }
+
+            GovernanceInstruction::SetRateSource {
+                token_index,
+                source,
+                rate_key,
+            } => {
+                let token_index = token_index as usize;
+                if token_index >= TOKEN_COUNT {
+                    return Err(ProgramError::InvalidInstructionData);
+                }
+                //the new rate revalues the balances at once, which would hand their difference in value to whoever
+                // trades first
+                if !pool_state.is_paused && pool_state.previous_depth != 0 {
+                    return Err(PoolError::PoolNotPaused.with_context("the pool holds balances"));
+                }
+                let token_mint = &pool_state.token_mint_keys[token_index];
+                let limits = &pool_state.oracle_limits[token_index];
+                let clock = Clock::get()?;
+                pool_state.rate_keys[token_index] = if source.has_account() {
+                    let rate_account = next_account_info(account_info_iter)?;
+                    if *rate_account.key != rate_key {
+                        return Err(PoolError::InvalidRateAccount.into());
+                    }
+                    oracle::load_rate(
+                        source,
+                        rate_account.owner,
+                        &rate_account.data.borrow(),
+                        token_mint,
+                        &clock,
+                        limits,
+                    )?;
+                    //the base pool's virtual price account is a program address of the base pool's program, so nothing
+                    // else can write to it once it's bound
+                    if source == RateSource::BasePool {
+                        let base_pool_account = next_account_info(account_info_iter)?;
+                        let (virtual_price_key, _) =
+                            find_virtual_price_address(base_pool_account.owner, base_pool_account.key);
+                        if rate_key != virtual_price_key
+                            || rate_account.owner != base_pool_account.owner
+                            || pool_lp_mint_key(&base_pool_account.try_borrow_data()?) != Some(*token_mint)
+                        {
+                            return Err(PoolError::InvalidRateAccount.with_context(format_args!(
+                                "not the virtual price account of a pool with lp mint {}",
+                                token_mint
+                            )));
+                        }
+                    }
+                    rate_key
+                } else {
+                    oracle::load_rate(source, &Pubkey::default(), &[], token_mint, &clock, limits)?;
+                    Pubkey::default()
+                };
+                pool_state.rate_sources[token_index] = source;
+            }
+
+            GovernanceInstruction::SetPegBand { band } => {
+                if band != DecT::from(0) && !pool_state.has_token_oracles() {
+                    return Err(PoolError::InvalidOracleAccount.into());
+                }
+                pool_state.peg_band = PoolFee::new(band)?;
+                pool_state.depegged_tokens = [false; TOKEN_COUNT];
+            }
+
+            GovernanceInstruction::SetOracleFallback { fallback } => {
+                pool_state.oracle_fallback = fallback;
+            }
+
+            GovernanceInstruction::SetHookPrograms { hook_programs } => {
+                pool_state.hook_programs = hook_programs;
+            }
+
+            GovernanceInstruction::SetCallerAllowlist {
+                caller_mode,
+                allowed_callers,
+            } => {
+                pool_state.caller_mode = caller_mode;
+                pool_state.allowed_callers = allowed_callers;
+            }
+
+            GovernanceInstruction::SetSunset { deadline } => {
+                let current_ts = Self::get_current_ts()?;
+                //once the deadline passed, the pool's balances belong to the distribution
+                if pool_state.is_sunset() && pool_state.sunset_ts <= current_ts {
+                    return Err(PoolError::PoolIsSunset.into());
+                }
+                if deadline != 0 && deadline < current_ts + ENACT_DELAY {
+                    return Err(PoolError::InsufficientDelay.into());
+                }
+                pool_state.sunset_ts = deadline;
+            }
+
+            GovernanceInstruction::RenounceGovernance { lock_amp_factor } => {
+                if pool_state.is_paused {
+                    return Err(PoolError::PoolIsPaused.into());
+                }
+                if pool_state.is_sunset() {
+                    return Err(PoolError::PoolIsSunset.into());
+                }
+                if lock_amp_factor {
+                    let current_ts = Self::get_current_ts()?;
+                    pool_state.amp_factor = AmpFactor::new(pool_state.amp_factor.get(current_ts))?;
+                }
+                pool_state.governance_key = Pubkey::default();
+                pool_state.prepared_governance_key = Pubkey::default();
+                pool_state.governance_transition_ts = 0;
+                pool_state.prepared_lp_fee = PoolFee::default();
+                pool_state.prepared_governance_fee = PoolFee::default();
+                pool_state.fee_transition_ts = 0;
+                pool_state.prepared_token_oracle = TokenOracle::default();
+                pool_state.prepared_oracle_index = 0;
+                pool_state.oracle_transition_ts = 0;
+                pool_state.roles = [Pubkey::default(); ROLE_COUNT];
+                pool_state.emergency_responders = [Pubkey::default(); MAX_EMERGENCY_RESPONDERS];
+                pool_state.emergency_threshold = 0;
+                pool_state.emergency_confirmation_ts = [0; MAX_EMERGENCY_RESPONDERS];
+            }
+
+            GovernanceInstruction::SetRole { role, key } => {
+                pool_state.roles[role as usize] = key;
+            }
+
+            GovernanceInstruction::SetLockFeeShare { share } => {
+                pool_state.lock_fee_share = PoolFee::new(share)?;
+            }
+
+            GovernanceInstruction::SetMinLpPosition {
+                min_mint_amount,
+                min_residual_amount,
+            } => {
+                pool_state.min_lp_mint_amount = min_mint_amount;
+                pool_state.min_lp_residual_amount = min_residual_amount;
+            }
+
+            GovernanceInstruction::SetLaunchCap {
+                max_lp_per_wallet,
+                end_ts,
+            } => {
+                pool_state.launch_lp_cap = max_lp_per_wallet;
+                pool_state.launch_end_ts = end_ts;
+            }
+
+            GovernanceInstruction::SetUserGate { user_gate } => {
+                pool_state.user_gate = user_gate;
+            }
+
+            GovernanceInstruction::SetUserAllowed { user, allowed } => {
+                Self::set_allowed(
+                    ALLOWED_USER_SEED,
+                    find_allowed_user_address,
+                    &user,
+                    allowed,
+                    pool_account,
+                    account_info_iter,
+                    program_id,
+                )?;
+            }
+
+            GovernanceInstruction::SetDepositorGate { depositor_gate } => {
+                pool_state.depositor_gate = depositor_gate;
+            }
+
+            GovernanceInstruction::SetDepositorAllowed { depositor, allowed } => {
+                Self::set_allowed(
+                    ALLOWED_DEPOSITOR_SEED,
+                    find_allowed_depositor_address,
+                    &depositor,
+                    allowed,
+                    pool_account,
+                    account_info_iter,
+                    program_id,
+                )?;
+            }
+
+            GovernanceInstruction::SetRfqQuoter {
+                quoter_key,
+                max_deviation,
+            } => {
+                let max_deviation = PoolFee::new(max_deviation)?;
+                if max_deviation.get() * BPS_ONE > MAX_RFQ_DEVIATION_BPS {
+                    return Err(PoolError::InvalidFeeInput.into());
+                }
+                pool_state.prepared_rfq_quoter_key = quoter_key;
+                pool_state.prepared_rfq_max_deviation = max_deviation;
+                pool_state.rfq_quoter_transition_ts = Self::get_current_ts()? + ENACT_DELAY;
+            }
+
+            GovernanceInstruction::EnactRfqQuoter {} => {
+                if pool_state.rfq_quoter_transition_ts == 0 {
+                    return Err(PoolError::InvalidEnact.into());
+                }
+
+                if pool_state.rfq_quoter_transition_ts > Self::get_current_ts()? {
+                    return Err(PoolError::InsufficientDelay.into());
+                }
+
+                pool_state.rfq_quoter_key = pool_state.prepared_rfq_quoter_key;
+                pool_state.rfq_max_deviation = pool_state.prepared_rfq_max_deviation;
+                pool_state.prepared_rfq_quoter_key = Pubkey::default();
+                pool_state.prepared_rfq_max_deviation = PoolFee::default();
+                pool_state.rfq_quoter_transition_ts = 0;
+            }
+
+            GovernanceInstruction::SetComplianceProgram { compliance_program } => {
+                pool_state.compliance_program = compliance_program;
+            }
+
+            GovernanceInstruction::SetTradingSchedule {
+                trading_hours,
+                maintenance_windows,
+            } => {
+                if !trading_hours.is_valid()
+                    || maintenance_windows
+                        .iter()
+                        .any(|window| window.is_set() && window.start_ts >= window.end_ts)
+                {
+                    return Err(PoolError::InvalidTradingSchedule.into());
+                }
+                let current_ts = Self::get_current_ts()?;
+                for window in maintenance_windows.iter().filter(|window| window.end_ts > current_ts) {
+                    let event = MaintenanceEvent {
+                        start_ts: window.start_ts,
+                        end_ts: window.end_ts,
+                    };
+                    msg!("{}", event.to_log());
+                }
+                pool_state.trading_hours = trading_hours;
+                pool_state.maintenance_windows = maintenance_windows;
+            }
+
+            GovernanceInstruction::SetWithdrawalCooldown { threshold, cooldown } => {
+                if !(0..=MAX_WITHDRAWAL_COOLDOWN).contains(&cooldown) {
+                    return Err(ProgramError::InvalidInstructionData);
+                }
+                pool_state.withdrawal_cooldown_threshold = threshold;
+                pool_state.withdrawal_cooldown = cooldown;
+            }
+
+            GovernanceInstruction::SetPriceBand { price_band } => {
+                if TOKEN_COUNT != 2 || !price_band.is_valid() {
+                    return Err(PoolError::InvalidPriceBand.into());
+                }
+                pool_state.price_band = price_band;
+            }
+
+            GovernanceInstruction::SetTokenParticipation {
+                token_index,
+                participation,
+            } => {
+                if token_index as usize >= TOKEN_COUNT {
+                    return Err(ProgramError::InvalidInstructionData);
+                }
+                pool_state.token_participation[token_index as usize] = participation;
+                let event = TokenParticipationEvent {
+                    token_index,
+                    participation,
+                };
+                msg!("{}", event.to_log());
+            }
+
+            GovernanceInstruction::SetPegKeeper {
+                keeper_key,
+                token_index,
+                credit_limit,
+            } => {
+                if token_index as usize >= TOKEN_COUNT {
+                    return Err(ProgramError::InvalidInstructionData);
+                }
+                if pool_state.invariant_kind != InvariantKind::StableSwap {
+                    return Err(PoolError::InvalidPegKeeper.into());
+                }
+                pool_state.prepared_peg_keeper = PegKeeper {
+                    keeper_key,
+                    token_index,
+                    credit_limit: if keeper_key == Pubkey::default() {
+                        0
+                    } else {
+                        credit_limit
+                    },
+                    ..PegKeeper::default()
+                };
+                pool_state.peg_keeper_transition_ts = Self::get_current_ts()? + ENACT_DELAY;
+            }
+
+            GovernanceInstruction::EnactPegKeeper {} => {
+                if pool_state.peg_keeper_transition_ts == 0 {
+                    return Err(PoolError::InvalidEnact.into());
+                }
+
+                if pool_state.peg_keeper_transition_ts > Self::get_current_ts()? {
+                    return Err(PoolError::InsufficientDelay.into());
+                }
+
+                //the keeper may have provided credit since the change was prepared
+                let prepared = pool_state.prepared_peg_keeper;
+                let peg_keeper = &mut pool_state.peg_keeper;
+                if peg_keeper.debt > 0 && (!prepared.is_set() || prepared.token_index != peg_keeper.token_index) {
+                    return Err(PoolError::InvalidPegKeeper.into());
+                }
+                peg_keeper.keeper_key = prepared.keeper_key;
+                peg_keeper.token_index = prepared.token_index;
+                peg_keeper.credit_limit = prepared.credit_limit;
+                pool_state.prepared_peg_keeper = PegKeeper::default();
+                pool_state.peg_keeper_transition_ts = 0;
+            }
+
+            GovernanceInstruction::SetYieldAdapter {
+                token_index,
+                adapter_program,
+                buffer,
+            } => {
+                if token_index as usize >= TOKEN_COUNT {
+                    return Err(ProgramError::InvalidInstructionData);
+                }
+                pool_state.prepared_yield_adapter = YieldAdapter {
+                    program: adapter_program,
+                    buffer: if adapter_program == Pubkey::default() {
+                        0
+                    } else {
+                        buffer
+                    },
+                    deployed: 0,
+                };
+                pool_state.prepared_yield_adapter_index = token_index;
+                pool_state.yield_adapter_transition_ts = Self::get_current_ts()? + ENACT_DELAY;
+            }
+
+            GovernanceInstruction::EnactYieldAdapter {} => {
+                if pool_state.yield_adapter_transition_ts == 0 {
+                    return Err(PoolError::InvalidEnact.into());
+                }
+
+                if pool_state.yield_adapter_transition_ts > Self::get_current_ts()? {
+                    return Err(PoolError::InsufficientDelay.into());
+                }
+
+                let prepared = pool_state.prepared_yield_adapter;
+                let yield_adapter = &mut pool_state.yield_adapters[pool_state.prepared_yield_adapter_index as usize];
+                if yield_adapter.deployed > 0 && prepared.program != yield_adapter.program {
+                    return Err(PoolError::InvalidYieldAdapter.with_context("recall the deployed tokens first"));
+                }
+                yield_adapter.program = prepared.program;
+                yield_adapter.buffer = prepared.buffer;
+                pool_state.prepared_yield_adapter = YieldAdapter::default();
+                pool_state.prepared_yield_adapter_index = 0;
+                pool_state.yield_adapter_transition_ts = 0;
+            }
+
+            GovernanceInstruction::SetRepegParams {
+                threshold_bps,
+                max_step_bps,
+                min_interval,
+            } => {
+                if pool_state.invariant_kind != InvariantKind::Hybrid
+                    || threshold_bps as u64 > BPS_ONE
+                    || max_step_bps as u64 > BPS_ONE
+                    || min_interval < 0
+                {
+                    return Err(PoolError::InvalidPriceScale.into());
+                }
+                pool_state.price_scale.threshold_bps = threshold_bps;
+                pool_state.price_scale.max_step_bps = max_step_bps;
+                pool_state.price_scale.min_interval = min_interval;
+            }
+
+            GovernanceInstruction::SetGaugeReward { reward_mint, funder } => {
+                Self::set_gauge_reward(
+                    &reward_mint,
+                    &funder,
+                    &pool_state,
+                    pool_account,
+                    account_info_iter,
+                    program_id,
+                )?;
+            }
+
+            GovernanceInstruction::StartRebateCampaign {
+                campaign_id,
+                rebate_mint,
+                budget,
+                start_ts,
+                end_ts,
+            } => {
+                Self::start_rebate_campaign(
+                    campaign_id,
+                    &rebate_mint,
+                    budget,
+                    start_ts,
+                    end_ts,
+                    &mut pool_state,
+                    pool_account,
+                    governance_account,
+                    account_info_iter,
+                    program_id,
+                )?;
+            }
+
+            GovernanceInstruction::ReclaimRebateBudget { campaign_id } => {
+                Self::reclaim_rebate_budget(campaign_id, &pool_state, pool_account, account_info_iter, program_id)?;
+            }
+
+            GovernanceInstruction::SetGaugeVeProgram { ve_program } => {
+                let gauge_account = next_account_info(account_info_iter)?;
+                let mut gauge = Self::check_and_deserialize_gauge(gauge_account, pool_account, program_id)?;
+                gauge.prepared_ve_program = ve_program;
+                gauge.ve_program_transition_ts = Self::get_current_ts()? + ENACT_DELAY;
+                gauge
+                    .serialize(&mut &mut gauge_account.try_borrow_mut_data()?[..])
+                    .or(Err(ProgramError::AccountDataTooSmall))?;
+            }
+
+            GovernanceInstruction::EnactGaugeVeProgram {} => {
+                let gauge_account = next_account_info(account_info_iter)?;
+                let mut gauge = Self::check_and_deserialize_gauge(gauge_account, pool_account, program_id)?;
+                if gauge.ve_program_transition_ts == 0 {
+                    return Err(PoolError::InvalidEnact.into());
+                }
+
+                if gauge.ve_program_transition_ts > Self::get_current_ts()? {
+                    return Err(PoolError::InsufficientDelay.into());
+                }
+
+                gauge.ve_program = gauge.prepared_ve_program;
+                gauge.prepared_ve_program = Pubkey::default();
+                gauge.ve_program_transition_ts = 0;
+                gauge
+                    .serialize(&mut &mut gauge_account.try_borrow_mut_data()?[..])
+                    .or(Err(ProgramError::AccountDataTooSmall))?;
+                msg!("gauge ve program: {}", gauge.ve_program);
+            }
+
+            GovernanceInstruction::SetEmergencyResponders { responders, threshold } => {
+                let responder_keys: Vec<_> = responders.iter().filter(|key| **key != Pubkey::default()).collect();
+                if (responder_keys.is_empty() && threshold != 0)
+                    || (!responder_keys.is_empty() && !(1..=responder_keys.len()).contains(&(threshold as usize)))
+                {
+                    return Err(ProgramError::InvalidInstructionData);
+                }
+                if (1..responder_keys.len()).any(|i| responder_keys[..i].contains(&responder_keys[i])) {
+                    return Err(PoolError::DuplicateAccount.into());
+                }
+                pool_state.emergency_responders = responders;
+                pool_state.emergency_threshold = threshold;
+                pool_state.emergency_confirmation_ts = [0; MAX_EMERGENCY_RESPONDERS];
+            }
+
+            GovernanceInstruction::BurnGovernanceFees { burn_amount } => {
+                let governance_fee_account = next_account_info(account_info_iter)?;
+                if *governance_fee_account.key != pool_state.governance_fee_key
+                    || pool_state.governance_fee_key == Pubkey::default()
+                {
+                    return Err(PoolError::InvalidGovernanceFeeAccount.into());
+                }
+                let lp_mint_account = next_account_info(account_info_iter)?;
+                if *lp_mint_account.key != pool_state.lp_mint_key {
+                    return Err(PoolError::InvalidMintAccount.into());
+                }
+                let lp_token_program_account = next_account_info(account_info_iter)?;
+                if *lp_token_program_account.key != pool_state.lp_token_program_id() {
+                    return Err(ProgramError::IncorrectProgramId);
+                }
+
+                let lp_supply = pool_state.lp_supply(Self::unpack_lp_mint(lp_mint_account)?.supply);
+                //the pool's balances would be left without lp tokens to claim them
+                if burn_amount >= lp_supply {
+                    return Err(PoolError::PoolIsEmpty.into());
+                }
+                pool_state.accrue_lp_supply_seconds(lp_supply, Self::get_current_ts()?);
+                //the governance has to own the governance fee account (or be its delegate)
+                Self::burn_token(
+                    governance_fee_account,
+                    lp_mint_account,
+                    burn_amount,
+                    governance_account,
+                    lp_token_program_account,
+                )?;
+
+                //the burn leaves the depth as it is, each remaining lp token's share of it grows
+                let lp_supply = lp_supply - burn_amount;
+                let equalized_lp_supply =
+                    quote::to_equalized_lp(lp_supply, pool_state.lp_decimal_equalizer, true).as_u128();
+                let lp_burn_event = LpBurnEvent {
+                    burn_amount,
+                    lp_supply,
+                    virtual_price: Self::virtual_price(pool_state.previous_depth, equalized_lp_supply)?,
+                };
+                msg!("{}", lp_burn_event.to_log());
+            }
+
+            GovernanceInstruction::SetAmpDamping {
+                factor,
+                trigger_band,
+                release_band,
+                delay,
+            } => {
+                if factor != DecT::from(1) && !pool_state.has_token_oracles() {
+                    return Err(PoolError::InvalidOracleAccount.into());
+                }
+                pool_state.amp_damping.set_params(
+                    Self::get_current_ts()?,
+                    factor,
+                    trigger_band,
+                    release_band,
+                    delay,
+                )?;
+            }
+
+            GovernanceInstruction::SetTokenOracle {
+                token_index,
+                oracle_kind,
+                oracle_key,
+                max_age,
+                max_conf_bps,
+            } => {
+                if token_index as usize >= TOKEN_COUNT {
+                    return Err(ProgramError::InvalidInstructionData);
+                }
+                let limits = OracleLimits { max_age, max_conf_bps };
+                if !limits.is_valid() {
+                    return Err(PoolError::InvalidOracleLimits.into());
+                }
+                if oracle_key != Pubkey::default() {
+                    let oracle_account = next_account_info(account_info_iter)?;
+                    if *oracle_account.key != oracle_key || *oracle_account.owner != oracle_kind.owner() {
+                        return Err(PoolError::InvalidOracleAccount.into());
+                    }
+                    oracle_kind.load_price(&oracle_account.data.borrow())?;
+                }
+
+                pool_state.prepared_token_oracle = TokenOracle {
+                    kind: oracle_kind,
+                    key: oracle_key,
+                    limits,
+                };
+                pool_state.prepared_oracle_index = token_index;
+                pool_state.oracle_transition_ts = Self::get_current_ts()? + ENACT_DELAY;
+            }
+
+            GovernanceInstruction::EnactTokenOracle {} => {
+                if pool_state.oracle_transition_ts == 0 {
+                    return Err(PoolError::InvalidEnact.into());
+                }
+
+                if pool_state.oracle_transition_ts > Self::get_current_ts()? {
+                    return Err(PoolError::InsufficientDelay.into());
+                }
+
+                let token_index = pool_state.prepared_oracle_index as usize;
+                let token_oracle = pool_state.prepared_token_oracle;
+                pool_state.oracle_keys[token_index] = token_oracle.key;
+                pool_state.oracle_kinds[token_index] = token_oracle.kind;
+                pool_state.oracle_limits[token_index] = token_oracle.limits;
+                pool_state.prepared_token_oracle = TokenOracle::default();
+                pool_state.prepared_oracle_index = 0;
+                pool_state.oracle_transition_ts = 0;
+            }
+        }
+
+        Self::serialize_pool(&pool_state, pool_account)
+    }
+
+    fn process_check_peg(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let pool_account = next_account_info(account_info_iter)?;
+        let mut pool_state = Self::check_and_deserialize_pool_state(&pool_account, &program_id)?;
+        let peg_band = pool_state.peg_band.get();
+        //tokens can lose their oracle after the band was set (see SetTokenOracle)
+        if (peg_band == DecT::from(0) && !pool_state.amp_damping.is_enabled()) || !pool_state.has_token_oracles() {
+            return Ok(());
+        }
+
+        let clock = Clock::get()?;
+        let current_slot = clock.slot;
+        let (mut beyond_trigger, mut beyond_release) = (false, false);
+        for i in 0..TOKEN_COUNT {
+            let oracle_account = next_account_info(account_info_iter)?;
+            if *oracle_account.key != pool_state.oracle_keys[i] {
+                return Err(PoolError::InvalidOracleAccount.into());
+            }
+            //an unavailable price says nothing about the peg, the token is checked again once its oracle recovers
+            let price = match oracle::load_checked_price(
+                pool_state.oracle_kinds[i],
+                &oracle_account.data.borrow(),
+                &pool_state.oracle_limits[i],
+                current_slot,
+            ) {
+                Ok(price) => price,
+                Err(PoolError::OraclePriceUnavailable) => {
+                    msg!("oracle of token {} unavailable", i);
+                    continue;
+                }
+                Err(error) => return Err(error.into()),
+            };
+            if peg_band != DecT::from(0) && !pool_state.depegged_tokens[i] && oracle::is_off_peg(&price, peg_band) {
+                //the event that monitoring picks up, swaps of the token stay paused until governance resumes them
+                msg!("depeg: token {} at {}e{}", i, price.price, price.expo);
+                pool_state.depegged_tokens[i] = true;
+            }
+            beyond_trigger |= oracle::is_off_peg(&price, pool_state.amp_damping.trigger_band());
+            beyond_release |= oracle::is_off_peg(&price, pool_state.amp_damping.release_band());
+        }
+
+        if pool_state.amp_damping.is_enabled() {
+            let was_damping = pool_state.amp_damping.is_damping();
+            pool_state
+                .amp_damping
+                .update(beyond_trigger, beyond_release, clock.unix_timestamp);
+            if pool_state.amp_damping.is_damping() != was_damping {
+                msg!("amp damping: {}", pool_state.amp_damping.is_damping());
+            }
+        }
+
+        Self::serialize_pool(&pool_state, pool_account)
+    }
+
+    fn process_publish_virtual_price(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let pool_account = next_account_info(account_info_iter)?;
+        let pool_state = Self::check_and_deserialize_pool_state(&pool_account, &program_id)?;
+        let virtual_price_account = next_account_info(account_info_iter)?;
+        let (virtual_price_key, bump) = find_virtual_price_address(program_id, pool_account.key);
+        if *virtual_price_account.key != virtual_price_key {
+            return Err(PoolError::InvalidVirtualPriceAccount.into());
+        }
+        let lp_mint_account = next_account_info(account_info_iter)?;
+        if *lp_mint_account.key != pool_state.lp_mint_key {
+            return Err(PoolError::InvalidMintAccount.into());
+        }
+        let lp_supply = pool_state.lp_supply(Self::unpack_lp_mint(lp_mint_account)?.supply);
+        let pool_balances: [_; TOKEN_COUNT] = create_result_array(|i| -> Result<_, ProgramError> {
+            let pool_token_account = next_account_info(account_info_iter)?;
+            if *pool_token_account.key != pool_state.token_keys[i] {
+                return Err(PoolError::PoolTokenAccountExpected.into());
+            }
+            Ok(Self::check_program_owner_and_unpack::<TokenState>(pool_token_account)?.amount)
+        })?;
+        let payer_account = next_account_info(account_info_iter)?;
+        let system_program_account = next_account_info(account_info_iter)?;
+        let rates = Self::load_rates(&pool_state, account_info_iter)?;
+        if lp_supply == 0 {
+            return Err(PoolError::PoolIsEmpty.into());
+        }
+
+        let clock = Clock::get()?;
+        let amp_factor = pool_state.effective_amp_factor(clock.unix_timestamp);
+        let pool_balances = pool_state.invariant_balances(&pool_balances);
+        let equalized_balances =
+            create_array(|i| quote::to_equalized(pool_balances[i], pool_state.token_decimal_equalizers[i]));
+        let depth = quote::depth(
+            &pool_state,
+            &create_array(|i| quote::to_rated(equalized_balances[i], rates[i], false)),
+            amp_factor,
+        )?
+        .as_u128();
+        let equalized_lp_supply = quote::to_equalized_lp(lp_supply, pool_state.lp_decimal_equalizer, true).as_u128();
+        let price = Self::virtual_price(depth, equalized_lp_supply)?;
+        //a change of rates since the last instruction isn't uncertainty about the price
+        let previous_depth = quote::rebased_previous_depth(&pool_state, &equalized_balances, &rates, amp_factor);
+        let previous_price = Self::virtual_price(previous_depth.as_u128(), equalized_lp_supply)?;
+        let virtual_price_state = VirtualPriceState {
+            pool: *pool_account.key,
+            price,
+            conf: max(price, previous_price) - min(price, previous_price),
+            expo: VIRTUAL_PRICE_EXPO,
+            publish_time: clock.unix_timestamp,
+            publish_slot: clock.slot,
+            is_paused: pool_state.is_paused || pool_state.depegged_tokens.iter().any(|depegged| *depegged),
+            lp_supply,
+            depth,
+        };
+
+        if virtual_price_account.owner != program_id {
+            Self::create_program_address_account(
+                payer_account,
+                virtual_price_account,
+                system_program_account,
+                solana_program::borsh::get_packed_len::<VirtualPriceState>(),
+                program_id,
+                &[VIRTUAL_PRICE_SEED, &pool_account.key.to_bytes(), &[bump]],
+            )?;
+        }
+        virtual_price_state
+            .serialize(&mut &mut virtual_price_account.try_borrow_mut_data()?[..])
+            .or(Err(ProgramError::AccountDataTooSmall))
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn process_routed_swap(
+        exact_input_amount: AtomicT,
+        input_token_index: usize,
+        first_output_index: usize,
+        second_input_index: usize,
+        output_token_index: usize,
+        minimum_output_amount: AtomicT,
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        if [
+            input_token_index,
+            first_output_index,
+            second_input_index,
+            output_token_index,
+        ]
+        .iter()
+        .any(|&index| index >= TOKEN_COUNT)
+        {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+        let account_info_iter = &mut accounts.iter();
+        let program_account = next_account_info(account_info_iter)?;
+        if program_account.key != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let user_authority_account = next_account_info(account_info_iter)?;
+        let user_input_account = next_account_info(account_info_iter)?;
+        let user_output_account = next_account_info(account_info_iter)?;
+        let intermediate_account = next_account_info(account_info_iter)?;
+        let route_authority_account = next_account_info(account_info_iter)?;
+        let intermediate_mint_account = next_account_info(account_info_iter)?;
+        let token_program_account = next_account_info(account_info_iter)?;
+        let system_program_account = next_account_info(account_info_iter)?;
+        let rent_account = next_account_info(account_info_iter)?;
+        let (route_authority_key, route_bump) = find_route_authority_address(program_id);
+        let (intermediate_key, intermediate_bump) =
+            find_intermediate_address(program_id, user_authority_account.key, intermediate_mint_account.key);
+        if *route_authority_account.key != route_authority_key || *intermediate_account.key != intermediate_key {
+            return Err(PoolError::InvalidRouteAccount.into());
+        }
+        if *token_program_account.key != spl_token::id() {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+
+        let (first_leg, accounts) = Self::split_route_leg(
+            account_info_iter.as_slice(),
+            first_output_index,
+            intermediate_mint_account.key,
+            program_id,
+        )?;
+        let (second_leg, _) =
+            Self::split_route_leg(accounts, second_input_index, intermediate_mint_account.key, program_id)?;
+
+        let intermediate_seeds: &[&[u8]] = &[
+            ROUTE_SEED,
+            &user_authority_account.key.to_bytes(),
+            &intermediate_mint_account.key.to_bytes(),
+            &[intermediate_bump],
+        ];
+        let route_authority_seeds: &[&[u8]] = &[ROUTE_SEED, &[route_bump]];
+        Self::create_program_address_account(
+            user_authority_account,
+            intermediate_account,
+            system_program_account,
+            TokenState::LEN,
+            &spl_token::id(),
+            intermediate_seeds,
+        )?;
+        invoke(
+            &spl_token::instruction::initialize_account(
+                &spl_token::id(),
+                &intermediate_key,
+                intermediate_mint_account.key,
+                &route_authority_key,
+            )?,
+            &[
+                intermediate_account.clone(),
+                intermediate_mint_account.clone(),
+                route_authority_account.clone(),
+                rent_account.clone(),
+                token_program_account.clone(),
+            ],
+        )?;
+
+        //both legs are regular swaps of this program, so they do all of their own checks
+        Self::invoke_route_leg(
+            program_account,
+            first_leg,
+            user_authority_account,
+            token_program_account,
+            create_array(|i| {
+                if i == first_output_index {
+                    intermediate_account
+                } else {
+                    user_input_account
+                }
+            }),
+            DeFiInstruction::SwapExactInput {
+                exact_input_amounts: create_array(|i| if i == input_token_index { exact_input_amount } else { 0 }),
+                output_token_index: first_output_index as u8,
+                minimum_output_amount: 0,
+            },
+            &[],
+        )?;
+        let intermediate_amount = TokenState::unpack(&intermediate_account.data.borrow())?.amount;
+        Self::invoke_route_leg(
+            program_account,
+            second_leg,
+            route_authority_account,
+            token_program_account,
+            create_array(|i| {
+                if i == output_token_index {
+                    user_output_account
+                } else {
+                    intermediate_account
+                }
+            }),
+            DeFiInstruction::SwapExactInput {
+                exact_input_amounts: create_array(|i| {
+                    if i == second_input_index {
+                        intermediate_amount
+                    } else {
+                        0
+                    }
+                }),
+                output_token_index: output_token_index as u8,
+                minimum_output_amount,
+            },
+            &[route_authority_seeds],
+        )?;
+
+        //the second leg took all of the intermediate tokens, so the account can be closed and its rent refunded
+        invoke_signed(
+            &spl_token::instruction::close_account(
+                &spl_token::id(),
+                &intermediate_key,
+                user_authority_account.key,
+                &route_authority_key,
+                &[],
+            )?,
+            &[
+                intermediate_account.clone(),
+                user_authority_account.clone(),
+                route_authority_account.clone(),
+                token_program_account.clone(),
+            ],
+            &[route_authority_seeds],
+        )
+    }
+
+    fn process_create_pool_token_accounts<'a>(program_id: &Pubkey, accounts: &[AccountInfo<'a>]) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let pool_account = next_account_info(account_info_iter)?;
+        let pool_state = Self::check_and_deserialize_pool_state(pool_account, program_id)?;
+        let pool_authority_account = next_account_info(account_info_iter)?;
+        if *pool_authority_account.key != Self::get_pool_authority(pool_account.key, pool_state.nonce, program_id)? {
+            return Err(PoolError::InvalidPoolAuthorityAccount.into());
+        }
+        let pool_token_accounts: [_; TOKEN_COUNT] = create_result_array(|i| -> Result<_, ProgramError> {
+            let pool_token_account = next_account_info(account_info_iter)?;
+            if *pool_token_account.key != pool_state.token_keys[i] {
+                return Err(PoolError::PoolTokenAccountExpected.into());
+            }
+            Ok(pool_token_account)
+        })?;
+        let token_mint_accounts: [_; TOKEN_COUNT] = create_result_array(|i| -> Result<_, ProgramError> {
+            let token_mint_account = next_account_info(account_info_iter)?;
+            if *token_mint_account.key != pool_state.token_mint_keys[i] {
+                return Err(PoolError::InvalidMintAccount.into());
+            }
+            Ok(token_mint_account)
+        })?;
+        let governance_fee_account = next_account_info(account_info_iter)?;
+        if *governance_fee_account.key != pool_state.governance_fee_key {
+            return Err(PoolError::InvalidGovernanceFeeAccount.into());
+        }
+        let governance_account = next_account_info(account_info_iter)?;
+        if *governance_account.key != pool_state.governance_key {
+            return Err(PoolError::InvalidGovernanceAccount.into());
+        }
+        let lp_mint_account = next_account_info(account_info_iter)?;
+        if *lp_mint_account.key != pool_state.lp_mint_key {
+            return Err(PoolError::InvalidMintAccount.into());
+        }
+        let payer_account = next_account_info(account_info_iter)?;
+        let system_program_account = next_account_info(account_info_iter)?;
+        let token_program_account = next_account_info(account_info_iter)?;
+        let associated_token_program_account = next_account_info(account_info_iter)?;
+        let rent_account = next_account_info(account_info_iter)?;
+        if *token_program_account.key != spl_token::id()
+            || *associated_token_program_account.key != spl_associated_token_account::id()
+        {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+
+        //an account that the token program owns exists, whatever its address (e.g. one set up before the pool was
+        // initialized), anything else can only be created at the associated token address
+        let create_if_missing =
+            |token_account: &AccountInfo<'a>, owner: &AccountInfo<'a>, mint: &AccountInfo<'a>, error: PoolError| {
+                if *token_account.owner == spl_token::id() {
+                    return Ok(());
+                }
+                if *token_account.key != get_associated_token_address(owner.key, mint.key) {
+                    return Err(ProgramError::from(error));
+                }
+                invoke(
+                    &create_associated_token_account(payer_account.key, owner.key, mint.key),
+                    &[
+                        payer_account.clone(),
+                        token_account.clone(),
+                        owner.clone(),
+                        mint.clone(),
+                        system_program_account.clone(),
+                        token_program_account.clone(),
+                        rent_account.clone(),
+                        associated_token_program_account.clone(),
+                    ],
+                )
+            };
+        for i in 0..TOKEN_COUNT {
+            create_if_missing(
+                pool_token_accounts[i],
+                pool_authority_account,
+                token_mint_accounts[i],
+                PoolError::PoolTokenAccountExpected,
+            )?;
+        }
+        //associated token accounts are created through the spl token program, a non-transferable lp token's governance
+        // fee account has to be a Token-2022 account that already exists
+        if pool_state.lp_non_transferable {
+            if *governance_fee_account.owner != token_2022::id() {
+                return Err(PoolError::LpNonTransferable.into());
+            }
+        } else if pool_state.governance_fee_key != Pubkey::default() {
+            create_if_missing(
+                governance_fee_account,
+                governance_account,
+                lp_mint_account,
+                PoolError::InvalidGovernanceFeeAccount,
+            )?;
+        }
+        Ok(())
+    }
+
+    fn process_create_pool<'a>(
+        fee_tier: u16,
+        amp_factor: DecT,
+        lp_fee: DecT,
+        governance_fee: DecT,
+        program_id: &Pubkey,
+        accounts: &[AccountInfo<'a>],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let pool_account = next_account_info(account_info_iter)?;
+        let lp_mint_account = next_account_info(account_info_iter)?;
+        let token_mint_accounts: [_; TOKEN_COUNT] = create_result_array(|_| next_account_info(account_info_iter))?;
+        let token_accounts: [_; TOKEN_COUNT] = create_result_array(|_| next_account_info(account_info_iter))?;
+        let governance_account = next_account_info(account_info_iter)?;
+        let governance_fee_account = next_account_info(account_info_iter)?;
+        let payer_account = next_account_info(account_info_iter)?;
+        let system_program_account = next_account_info(account_info_iter)?;
+        let token_program_account = next_account_info(account_info_iter)?;
+        let rent_account = next_account_info(account_info_iter)?;
+        if *token_program_account.key != spl_token::id() {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+
+        //ascending order makes the pool address independent of the order the mints are given in and rules out
+        // duplicate mints
+        let token_mints: [Pubkey; TOKEN_COUNT] = create_array(|i| *token_mint_accounts[i].key);
+        if (1..TOKEN_COUNT).any(|i| token_mints[i - 1] >= token_mints[i]) {
+            return Err(PoolError::UnsortedTokenMints.into());
+        }
+        let (pool_key, pool_bump) = find_pool_address(program_id, &token_mints, fee_tier);
+        let (lp_mint_key, lp_mint_bump) = find_lp_mint_address(program_id, &pool_key);
+        let (governance_fee_key, governance_fee_bump) = find_governance_fee_address(program_id, &pool_key);
+        if *pool_account.key != pool_key
+            || *lp_mint_account.key != lp_mint_key
+            || *governance_fee_account.key != governance_fee_key
+        {
+            return Err(PoolError::NonCanonicalAddress.into());
+        }
+        let vault_bumps: [_; TOKEN_COUNT] = create_result_array(|i| -> Result<_, ProgramError> {
+            let (vault_key, vault_bump) = find_vault_address(program_id, &pool_key, &token_mints[i]);
+            if *token_accounts[i].key != vault_key {
+                return Err(PoolError::NonCanonicalAddress.into());
+            }
+            Ok(vault_bump)
+        })?;
+        //the authority of regular pools is derived the same way, Init finds it from the nonce
+        let (pool_authority, nonce) = Pubkey::find_program_address(&[&pool_key.to_bytes()], program_id);
+        let mut lp_decimals = 0;
+        for token_mint_account in token_mint_accounts.iter() {
+            let mint_decimals = Self::check_program_owner_and_unpack::<MintState>(token_mint_account)?.decimals;
+            lp_decimals = max(lp_decimals, mint_decimals);
+        }
+
+        let mint_bytes: [[u8; 32]; TOKEN_COUNT] = create_array(|i| token_mints[i].to_bytes());
+        let fee_tier_bytes = fee_tier.to_le_bytes();
+        let mut pool_seeds: Vec<&[u8]> = vec![POOL_SEED];
+        pool_seeds.extend(mint_bytes.iter().map(|bytes| &bytes[..]));
+        pool_seeds.push(&fee_tier_bytes);
+        let pool_bump = [pool_bump];
+        pool_seeds.push(&pool_bump);
+        //fails for pools that exist already, the system program only allocates accounts without data
+        Self::create_program_address_account(
+            payer_account,
+            pool_account,
+            system_program_account,
+            solana_program::borsh::get_packed_len::<PoolState<TOKEN_COUNT>>(),
+            program_id,
+            &pool_seeds,
+        )?;
+
+        Self::create_program_address_account(
+            payer_account,
+            lp_mint_account,
+            system_program_account,
+            MintState::LEN,
+            &spl_token::id(),
+            &[LP_MINT_SEED, &pool_key.to_bytes(), &[lp_mint_bump]],
+        )?;
+        invoke(
+            &spl_token::instruction::initialize_mint(
+                &spl_token::id(),
+                &lp_mint_key,
+                &pool_authority,
+                None,
+                lp_decimals,
+            )?,
+            &[
+                lp_mint_account.clone(),
+                rent_account.clone(),
+                token_program_account.clone(),
+            ],
+        )?;
+
+        let create_token_account =
+            |token_account: &AccountInfo<'a>, mint_account: &AccountInfo<'a>, owner: &Pubkey, seeds: &[&[u8]]| {
+                Self::create_program_address_account(
+                    payer_account,
+                    token_account,
+                    system_program_account,
+                    TokenState::LEN,
+                    &spl_token::id(),
+                    seeds,
+                )?;
+                invoke(
+                    &spl_token::instruction::initialize_account2(
+                        &spl_token::id(),
+                        token_account.key,
+                        mint_account.key,
+                        owner,
+                    )?,
+                    &[
+                        token_account.clone(),
+                        mint_account.clone(),
+                        rent_account.clone(),
+                        token_program_account.clone(),
+                    ],
+                )
+            };
+        for i in 0..TOKEN_COUNT {
+            create_token_account(
+                token_accounts[i],
+                token_mint_accounts[i],
+                &pool_authority,
+                &[VAULT_SEED, &pool_key.to_bytes(), &mint_bytes[i], &[vault_bumps[i]]],
+            )?;
+        }
+        create_token_account(
+            governance_fee_account,
+            lp_mint_account,
+            governance_account.key,
+            &[GOVERNANCE_FEE_SEED, &pool_key.to_bytes(), &[governance_fee_bump]],
+        )?;
+
+        //the created accounts pass all of Init's checks
+        let mut init_accounts = vec![pool_account.clone(), lp_mint_account.clone()];
+        init_accounts.extend(token_mint_accounts.iter().map(|&account| account.clone()));
+        init_accounts.extend(token_accounts.iter().map(|&account| account.clone()));
+        init_accounts.push(governance_account.clone());
+        init_accounts.push(governance_fee_account.clone());
+        Self::process_init(
+            nonce,
+            amp_factor,
+            lp_fee,
+            governance_fee,
+            false,
+            program_id,
+            &init_accounts,
+        )
+    }
+
+    fn process_migrate_in(
+        source: MigrationSource,
+        source_lp_amount: AtomicT,
+        token_indices: [usize; 2],
+        minimum_mint_amount: AtomicT,
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        if token_indices[0] == token_indices[1] || token_indices.iter().any(|&index| index >= TOKEN_COUNT) {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+        //the add's account count depends on the pool, the source's doesn't
+        let source_len = 1 + source.account_count();
+        if accounts.len() < 7 + 2 * TOKEN_COUNT + source_len {
+            return Err(ProgramError::NotEnoughAccountKeys);
+        }
+        let (add_accounts, source_accounts) = accounts.split_at(accounts.len() - source_len);
+        let (source_program_account, withdraw_accounts) = source_accounts
+            .split_first()
+            .ok_or(ProgramError::NotEnoughAccountKeys)?;
+        if *source_program_account.key != source.program_id() {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let user_token_accounts = &add_accounts[5 + TOKEN_COUNT..5 + 2 * TOKEN_COUNT];
+        if withdraw_accounts[MigrationSource::AUTHORITY_INDEX].key != add_accounts[4 + TOKEN_COUNT].key
+            || (0..2).any(|i| {
+                withdraw_accounts[MigrationSource::DESTINATION_INDICES[i]].key
+                    != user_token_accounts[token_indices[i]].key
+            })
+        {
+            return Err(PoolError::InvalidMigrationAccount.into());
+        }
+
+        let balances = || -> Result<[AtomicT; 2], ProgramError> {
+            create_result_array(|i| {
+                Ok(Self::check_program_owner_and_unpack::<TokenState>(&user_token_accounts[token_indices[i]])?.amount)
+            })
+        };
+        let balances_before = balances()?;
+        let withdraw_metas = withdraw_accounts
+            .iter()
+            .map(|account| AccountMeta {
+                pubkey: *account.key,
+                is_signer: account.is_signer,
+                is_writable: account.is_writable,
+            })
+            .collect();
+        invoke(&source.withdraw_ix(withdraw_metas, source_lp_amount), source_accounts)?;
+        let balances_after = balances()?;
+
+        //adds what the withdrawal paid out, not the user's whole balance
+        let mut input_amounts = [0; TOKEN_COUNT];
+        for i in 0..2 {
+            input_amounts[token_indices[i]] = balances_after[i] - balances_before[i];
+        }
+        Self::process_defi_instruction(
+            DeFiInstruction::Add {
+                input_amounts,
+                minimum_mint_amount,
+            },
+            program_id,
+            add_accounts,
+        )
+    }
+
+    fn process_clone_pool_config(nonce: u8, program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+        let (template_account, init_accounts) = accounts.split_last().ok_or(ProgramError::NotEnoughAccountKeys)?;
+        let template_state = Self::check_and_deserialize_pool_state(template_account, program_id)?;
+        let current_ts = Clock::get()?.unix_timestamp;
+        Self::process_init(
+            nonce,
+            template_state.amp_factor.get(current_ts),
+            template_state.lp_fee.get(),
+            template_state.governance_fee.get(),
+            false,
+            program_id,
+            init_accounts,
+        )?;
+
+        let pool_account = &init_accounts[0];
+        let mut pool_state = Self::check_and_deserialize_pool_state(pool_account, program_id)?;
+        //oracle limits are per token index, like the oracles that governance sets for the new pool's tokens
+        pool_state.max_oracle_deviation = template_state.max_oracle_deviation;
+        pool_state.oracle_limits = template_state.oracle_limits;
+        pool_state.oracle_fallback = template_state.oracle_fallback;
+        pool_state.peg_band = template_state.peg_band;
+        //only the parameters, the new pool starts out undamped
+        let template_damping = &template_state.amp_damping;
+        pool_state.amp_damping.set_params(
+            current_ts,
+            template_damping.factor(),
+            template_damping.trigger_band(),
+            template_damping.release_band(),
+            template_damping.delay(),
+        )?;
+        pool_state.hook_programs = template_state.hook_programs;
+        pool_state.caller_mode = template_state.caller_mode;
+        pool_state.allowed_callers = template_state.allowed_callers;
+        pool_state.user_gate = template_state.user_gate;
+        pool_state.depositor_gate = template_state.depositor_gate;
+        pool_state.compliance_program = template_state.compliance_program;
+        pool_state.trading_hours = template_state.trading_hours;
+        pool_state.withdrawal_cooldown_threshold = template_state.withdrawal_cooldown_threshold;
+        pool_state.withdrawal_cooldown = template_state.withdrawal_cooldown;
+        pool_state.invariant_kind = template_state.invariant_kind;
+        pool_state.token_weights = template_state.token_weights;
+        pool_state.price_band = template_state.price_band;
+        //per token index, like the oracle limits
+        pool_state.token_participation = template_state.token_participation;
+        //the keeper's credit line, but not what the template owes it
+        pool_state.peg_keeper = PegKeeper {
+            debt: 0,
+            lp_amount: 0,
+            ..template_state.peg_keeper
+        };
+        //the adapters and buffers, but not the template's positions with them
+        pool_state.yield_adapters = create_array(|i| YieldAdapter {
+            deployed: 0,
+            ..template_state.yield_adapters[i]
+        });
+        //the template's price and repeg parameters, the new pool can repeg right away
+        pool_state.price_scale = PriceScale {
+            last_repeg_ts: 0,
+            ..template_state.price_scale
+        };
+        Self::serialize_pool(&pool_state, pool_account)
+    }
+
+    fn process_redeem_sunset<'a>(program_id: &Pubkey, accounts: &[AccountInfo<'a>]) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let pool_account = next_account_info(account_info_iter)?;
+        let mut pool_state = Self::check_and_deserialize_pool_state(pool_account, program_id)?;
+        if !pool_state.is_sunset() {
+            return Err(PoolError::InvalidEnact.into());
+        }
+        let current_ts = Self::get_current_ts()?;
+        if pool_state.sunset_ts > current_ts {
+            return Err(PoolError::InsufficientDelay.into());
+        }
+
+        let pool_authority_account = next_account_info(account_info_iter)?;
+        if *pool_authority_account.key != Self::get_pool_authority(pool_account.key, pool_state.nonce, program_id)? {
+            return Err(PoolError::InvalidPoolAuthorityAccount.into());
+        }
+        let pool_token_accounts: [_; TOKEN_COUNT] = create_result_array(|i| -> Result<_, ProgramError> {
+            let pool_token_account = next_account_info(account_info_iter)?;
+            if *pool_token_account.key != pool_state.token_keys[i] {
+                return Err(PoolError::PoolTokenAccountExpected.into());
+            }
+            Ok(pool_token_account)
+        })?;
+        let lp_mint_account = next_account_info(account_info_iter)?;
+        if *lp_mint_account.key != pool_state.lp_mint_key {
+            return Err(PoolError::InvalidMintAccount.into());
+        }
+        let user_lp_token_account = next_account_info(account_info_iter)?;
+        let user_authority_account = next_account_info(account_info_iter)?;
+        if !user_authority_account.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        let user_token_accounts: [_; TOKEN_COUNT] =
+            create_result_array(|_| -> Result<_, ProgramError> { Ok(next_account_info(account_info_iter)?) })?;
+        let token_program_account = next_account_info(account_info_iter)?;
+        if *token_program_account.key != spl_token::id() {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let lp_token_program_account = next_account_info(account_info_iter)?;
+        if *lp_token_program_account.key != pool_state.lp_token_program_id() {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+
+        //neither lp tokens nor pool balances change after the deadline but through redemptions, which burn the
+        // redeemed lp tokens, so every lp token is worth the same share of what's left
+        let burn_amount = Self::unpack_lp_account(user_lp_token_account)?.amount;
+        if burn_amount == 0 {
+            return Err(PoolError::InsufficientUserBalance.with_context("lp token account"));
+        }
+        Self::check_user_debit(
+            user_lp_token_account,
+            &pool_state.lp_mint_key,
+            burn_amount,
+            user_authority_account.key,
+            "lp token account",
+        )?;
+        let lp_supply = pool_state.lp_supply(Self::unpack_lp_mint(lp_mint_account)?.supply);
+        pool_state.accrue_lp_supply_seconds(lp_supply, current_ts);
+        Self::burn_token(
+            user_lp_token_account,
+            lp_mint_account,
+            burn_amount,
+            user_authority_account,
+            lp_token_program_account,
+        )?;
+        for i in 0..TOKEN_COUNT {
+            let pool_balance = Self::check_program_owner_and_unpack::<TokenState>(pool_token_accounts[i])?.amount;
+            let payout = (pool_balance as u128 * burn_amount as u128 / lp_supply as u128) as AtomicT;
+            if payout > 0 {
+                Self::transfer_pool_token(
+                    pool_token_accounts[i],
+                    user_token_accounts[i],
+                    payout,
+                    pool_authority_account,
+                    token_program_account,
+                    pool_account,
+                    pool_state.nonce,
+                )?;
+            }
+        }
+
+        Self::serialize_pool(&pool_state, pool_account)
+    }
+
+    fn process_export_state<'a>(
+        target_program: &Pubkey,
+        target_pool: &Pubkey,
+        program_id: &Pubkey,
+        accounts: &[AccountInfo<'a>],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let pool_account = next_account_info(account_info_iter)?;
+        let pool_state = Self::check_and_deserialize_pool_state(pool_account, program_id)?;
+        Self::verify_governance_signature(next_account_info(account_info_iter)?, &pool_state)?;
+        if !pool_state.is_paused {
+            return Err(PoolError::PoolNotPaused.into());
+        }
+        //the lock vault is this program's, locked lp tokens couldn't be unlocked anymore
+        if pool_state.lock_total_weight > 0 {
+            return Err(PoolError::LpStillLocked.into());
+        }
+        //the lp mint's authority is handed over through the spl token program
+        if pool_state.lp_non_transferable {
+            return Err(PoolError::LpNonTransferable.into());
+        }
+        //the export only carries the parameters of the stable swap invariant
+        if pool_state.invariant_kind != InvariantKind::StableSwap {
+            return Err(PoolError::InvalidStateExport.with_context("only stable swap pools can be exported"));
+        }
+        //the export only carries what the pool's token accounts hold
+        if pool_state.yield_adapters.iter().any(|adapter| adapter.deployed > 0) {
+            return Err(PoolError::InvalidStateExport.with_context("recall the deployed tokens first"));
+        }
+
+        let pool_authority_account = next_account_info(account_info_iter)?;
+        if *pool_authority_account.key != Self::get_pool_authority(pool_account.key, pool_state.nonce, program_id)? {
+            return Err(PoolError::InvalidPoolAuthorityAccount.into());
+        }
+        let pool_token_accounts: [_; TOKEN_COUNT] = create_result_array(|i| -> Result<_, ProgramError> {
+            let pool_token_account = next_account_info(account_info_iter)?;
+            if *pool_token_account.key != pool_state.token_keys[i] {
+                return Err(PoolError::PoolTokenAccountExpected.into());
+            }
+            Ok(pool_token_account)
+        })?;
+        let lp_mint_account = next_account_info(account_info_iter)?;
+        if *lp_mint_account.key != pool_state.lp_mint_key {
+            return Err(PoolError::InvalidMintAccount.into());
+        }
+        //ImportState requires the authority of the canonical bump seed, like pools that CreatePool creates
+        let target_authority = Pubkey::find_program_address(&[&target_pool.to_bytes()], target_program).0;
+        let target_token_accounts: [_; TOKEN_COUNT] = create_result_array(|i| -> Result<_, ProgramError> {
+            let target_token_account = next_account_info(account_info_iter)?;
+            let token_state = Self::check_program_owner_and_unpack::<TokenState>(target_token_account)?;
+            if token_state.mint != pool_state.token_mint_keys[i] {
+                return Err(TokenError::MintMismatch.into());
+            }
+            if token_state.owner != target_authority {
+                return Err(TokenError::OwnerMismatch.into());
+            }
+            Ok(target_token_account)
+        })?;
+        let export_account = next_account_info(account_info_iter)?;
+        let payer_account = next_account_info(account_info_iter)?;
+        let token_program_account = next_account_info(account_info_iter)?;
+        let system_program_account = next_account_info(account_info_iter)?;
+        if *token_program_account.key != spl_token::id() {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let (export_key, export_bump) = find_export_address(program_id, pool_account.key);
+        if *export_account.key != export_key {
+            return Err(PoolError::NonCanonicalAddress.into());
+        }
+
+        for i in 0..TOKEN_COUNT {
+            let balance = Self::check_program_owner_and_unpack::<TokenState>(pool_token_accounts[i])?.amount;
+            Self::transfer_pool_token(
+                pool_token_accounts[i],
+                target_token_accounts[i],
+                balance,
+                pool_authority_account,
+                token_program_account,
+                pool_account,
+                pool_state.nonce,
+            )?;
+        }
+        invoke_signed(
+            &set_authority(
+                token_program_account.key,
+                lp_mint_account.key,
+                Some(&target_authority),
+                AuthorityType::MintTokens,
+                pool_authority_account.key,
+                &[],
+            )?,
+            &[
+                lp_mint_account.clone(),
+                pool_authority_account.clone(),
+                token_program_account.clone(),
+            ],
+            &[&[&pool_account.key.to_bytes()[..32], &[pool_state.nonce]][..]],
+        )?;
+
+        let current_ts = Self::get_current_ts()?;
+        let export = PoolExport {
+            version: POOL_EXPORT_VERSION,
+            source_pool: *pool_account.key,
+            target_program: *target_program,
+            target_pool: *target_pool,
+            lp_mint_key: pool_state.lp_mint_key,
+            token_mint_keys: pool_state.token_mint_keys.to_vec(),
+            amp_factor: pool_state.amp_factor.get(current_ts),
+            lp_fee: pool_state.lp_fee.get(),
+            governance_fee: pool_state.governance_fee.get(),
+            governance_key: pool_state.governance_key,
+            governance_fee_key: pool_state.governance_fee_key,
+        };
+        let export_data = export.try_to_vec()?;
+        //fails if the pool was exported before
+        Self::create_program_address_account(
+            payer_account,
+            export_account,
+            system_program_account,
+            export_data.len(),
+            program_id,
+            &[EXPORT_SEED, &pool_account.key.to_bytes(), &[export_bump]],
+        )?;
+        export_account.data.borrow_mut().copy_from_slice(&export_data);
+        Ok(())
+    }
+
+    fn process_import_state(nonce: u8, program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+        let (export_account, init_accounts) = accounts.split_last().ok_or(ProgramError::NotEnoughAccountKeys)?;
+        if init_accounts.len() < 4 + 2 * TOKEN_COUNT {
+            return Err(ProgramError::NotEnoughAccountKeys);
+        }
+        let export =
+            PoolExport::deserialize(&mut &**export_account.data.borrow()).or(Err(PoolError::InvalidStateExport))?;
+        //the export account is a program address of the program that exported the pool
+        let is_valid_export = export.version == POOL_EXPORT_VERSION
+            && *export_account.key == find_export_address(export_account.owner, &export.source_pool).0
+            && export.target_program == *program_id
+            && export.target_pool == *init_accounts[0].key
+            && export.lp_mint_key == *init_accounts[1].key
+            && export.token_mint_keys.len() == TOKEN_COUNT
+            && (0..TOKEN_COUNT).all(|i| export.token_mint_keys[i] == *init_accounts[2 + i].key)
+            && export.governance_key == *init_accounts[2 + 2 * TOKEN_COUNT].key
+            && export.governance_fee_key == *init_accounts[3 + 2 * TOKEN_COUNT].key;
+        if !is_valid_export {
+            return Err(PoolError::InvalidStateExport.into());
+        }
+        if !init_accounts[2 + 2 * TOKEN_COUNT].is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        Self::process_init(
+            nonce,
+            export.amp_factor,
+            export.lp_fee,
+            export.governance_fee,
+            true,
+            program_id,
+            init_accounts,
+        )?;
+        let pool_account = &init_accounts[0];
+        let mut pool_state = Self::check_and_deserialize_pool_state(pool_account, program_id)?;
+        pool_state.is_paused = true;
+        Self::serialize_pool(&pool_state, pool_account)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn process_init_and_add(
+        nonce: u8,
+        amp_factor: DecT,
+        lp_fee: DecT,
+        governance_fee: DecT,
+        input_amounts: [AtomicT; TOKEN_COUNT],
+        minimum_mint_amount: AtomicT,
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        if accounts.len() < 4 + 2 * TOKEN_COUNT {
+            return Err(ProgramError::NotEnoughAccountKeys);
+        }
+        let (init_accounts, add_accounts) = accounts.split_at(4 + 2 * TOKEN_COUNT);
+        Self::process_init(
+            nonce,
+            amp_factor,
+            lp_fee,
+            governance_fee,
+            false,
+            program_id,
+            init_accounts,
+        )?;
+        Self::process_defi_instruction(
+            DeFiInstruction::Add {
+                input_amounts,
+                minimum_mint_amount,
+            },
+            program_id,
+            add_accounts,
+        )
+    }
+
+    fn process_rotate_vaults<'a>(program_id: &Pubkey, accounts: &[AccountInfo<'a>]) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let pool_account = next_account_info(account_info_iter)?;
+        let mut pool_state = Self::check_and_deserialize_pool_state(pool_account, program_id)?;
+        Self::verify_governance_signature(next_account_info(account_info_iter)?, &pool_state)?;
+        if !pool_state.is_paused {
+            return Err(PoolError::PoolNotPaused.into());
+        }
+
+        let pool_authority_account = next_account_info(account_info_iter)?;
+        if *pool_authority_account.key != Self::get_pool_authority(pool_account.key, pool_state.nonce, program_id)? {
+            return Err(PoolError::InvalidPoolAuthorityAccount.into());
+        }
+        let pool_token_accounts: [_; TOKEN_COUNT] = create_result_array(|i| -> Result<_, ProgramError> {
+            let pool_token_account = next_account_info(account_info_iter)?;
+            if *pool_token_account.key != pool_state.token_keys[i] {
+                return Err(PoolError::PoolTokenAccountExpected.into());
+            }
+            Ok(pool_token_account)
+        })?;
+        let token_mint_accounts: [_; TOKEN_COUNT] = create_result_array(|i| -> Result<_, ProgramError> {
+            let token_mint_account = next_account_info(account_info_iter)?;
+            if *token_mint_account.key != pool_state.token_mint_keys[i] {
+                return Err(PoolError::InvalidMintAccount.into());
+            }
+            Ok(token_mint_account)
+        })?;
+        let vault_accounts: [_; TOKEN_COUNT] = create_result_array(|_| next_account_info(account_info_iter))?;
+        let payer_account = next_account_info(account_info_iter)?;
+        let token_program_account = next_account_info(account_info_iter)?;
+        let system_program_account = next_account_info(account_info_iter)?;
+        let rent_account = next_account_info(account_info_iter)?;
+        if *token_program_account.key != spl_token::id() {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+
+        for i in 0..TOKEN_COUNT {
+            let old_key = pool_state.token_keys[i];
+            let (vault_key, vault_bump) = find_rotated_vault_address(program_id, pool_account.key, &old_key);
+            if *vault_accounts[i].key != vault_key {
+                return Err(PoolError::NonCanonicalAddress.into());
+            }
+            Self::create_program_address_account(
+                payer_account,
+                vault_accounts[i],
+                system_program_account,
+                TokenState::LEN,
+                &spl_token::id(),
+                &[
+                    VAULT_SEED,
+                    &pool_account.key.to_bytes(),
+                    &old_key.to_bytes(),
+                    &[vault_bump],
+                ],
+            )?;
+            invoke(
+                &spl_token::instruction::initialize_account2(
+                    &spl_token::id(),
+                    &vault_key,
+                    token_mint_accounts[i].key,
+                    pool_authority_account.key,
+                )?,
+                &[
+                    vault_accounts[i].clone(),
+                    token_mint_accounts[i].clone(),
+                    rent_account.clone(),
+                    token_program_account.clone(),
+                ],
+            )?;
+            let old_state = Self::check_program_owner_and_unpack::<TokenState>(pool_token_accounts[i])?;
+            if old_state.amount > 0 {
+                Self::transfer_pool_token(
+                    pool_token_accounts[i],
+                    vault_accounts[i],
+                    old_state.amount,
+                    pool_authority_account,
+                    token_program_account,
+                    pool_account,
+                    pool_state.nonce,
+                )?;
+            }
+            //what monitoring needs to tell what was wrong with the old account and where the balance went
+            msg!(
+                "vault rotation: token {} from {} (delegate: {:?}, close authority: {:?}) to {}, moved {}",
+                i,
+                old_key,
+                Option::<Pubkey>::from(old_state.delegate),
+                Option::<Pubkey>::from(old_state.close_authority),
+                vault_key,
+                old_state.amount
+            );
+            pool_state.token_keys[i] = vault_key;
+        }
+
+        Self::serialize_pool(&pool_state, pool_account)
+    }
+
+    fn process_lock_lp<'a>(
+        amount: AtomicT,
+        duration: UnixTimestamp,
+        program_id: &Pubkey,
+        accounts: &[AccountInfo<'a>],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let pool_account = next_account_info(account_info_iter)?;
+        let mut pool_state = Self::check_and_deserialize_pool_state(pool_account, program_id)?;
+        if pool_state.is_paused {
+            return Err(PoolError::PoolIsPaused.into());
+        }
+        if pool_state.is_sunset() {
+            return Err(PoolError::PoolIsSunset.into());
+        }
+        //the lock vault would have to receive them
+        if pool_state.lp_non_transferable {
+            return Err(PoolError::LpNonTransferable.into());
+        }
+        if !(MIN_LOCK_DURATION..=MAX_LOCK_DURATION).contains(&duration) {
+            return Err(PoolError::InvalidLockDuration.into());
+        }
+
+        let pool_authority_account = next_account_info(account_info_iter)?;
+        if *pool_authority_account.key != Self::get_pool_authority(pool_account.key, pool_state.nonce, program_id)? {
+            return Err(PoolError::InvalidPoolAuthorityAccount.into());
+        }
+        let lp_mint_account = next_account_info(account_info_iter)?;
+        if *lp_mint_account.key != pool_state.lp_mint_key {
+            return Err(PoolError::InvalidMintAccount.into());
+        }
+        let vault_account = next_account_info(account_info_iter)?;
+        let lock_account = next_account_info(account_info_iter)?;
+        let owner_account = next_account_info(account_info_iter)?;
+        let owner_lp_account = next_account_info(account_info_iter)?;
+        let payer_account = next_account_info(account_info_iter)?;
+        let token_program_account = next_account_info(account_info_iter)?;
+        let system_program_account = next_account_info(account_info_iter)?;
+        let rent_account = next_account_info(account_info_iter)?;
+        if *token_program_account.key != spl_token::id() {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        if !owner_account.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        let (vault_key, vault_bump) = find_lock_vault_address(program_id, pool_account.key);
+        let (lock_key, lock_bump) = find_lock_address(program_id, pool_account.key, owner_account.key);
+        if *vault_account.key != vault_key || *lock_account.key != lock_key {
+            return Err(PoolError::NonCanonicalAddress.into());
+        }
+
+        if vault_account.owner != &spl_token::id() {
+            Self::create_program_address_account(
+                payer_account,
+                vault_account,
+                system_program_account,
+                TokenState::LEN,
+                &spl_token::id(),
+                &[LOCK_SEED, &pool_account.key.to_bytes(), &[vault_bump]],
+            )?;
+            invoke(
+                &spl_token::instruction::initialize_account2(
+                    &spl_token::id(),
+                    &vault_key,
+                    lp_mint_account.key,
+                    pool_authority_account.key,
+                )?,
+                &[
+                    vault_account.clone(),
+                    lp_mint_account.clone(),
+                    rent_account.clone(),
+                    token_program_account.clone(),
+                ],
+            )?;
+        }
+        let mut lock = if lock_account.owner != program_id {
+            Self::create_program_address_account(
+                payer_account,
+                lock_account,
+                system_program_account,
+                solana_program::borsh::get_packed_len::<LpLock>(),
+                program_id,
+                &[
+                    LOCK_SEED,
+                    &pool_account.key.to_bytes(),
+                    &owner_account.key.to_bytes(),
+                    &[lock_bump],
+                ],
+            )?;
+            LpLock {
+                pool: *pool_account.key,
+                owner: *owner_account.key,
+                reward_growth_checkpoint: pool_state.lock_reward_growth,
+                ..LpLock::default()
+            }
+        } else {
+            LpLock::deserialize(&mut &**lock_account.data.borrow())?
+        };
+
+        Self::settle_lock_rewards(
+            &mut pool_state,
+            &mut lock,
+            lp_mint_account,
+            owner_lp_account,
+            pool_authority_account,
+            token_program_account,
+            pool_account,
+        )?;
+        if amount > 0 {
+            Self::transfer_token(
+                owner_lp_account,
+                vault_account,
+                amount,
+                owner_account,
+                token_program_account,
+            )?;
+        }
+
+        //the weight only depends on the time left, so locking more for a shorter time doesn't shorten the lock
+        let current_ts = Self::get_current_ts()?;
+        if amount > 0 {
+            lock.lp_fee_growth_checkpoint = pool_state.lp_fee_growth;
+        }
+        lock.amount += amount;
+        lock.unlock_ts = max(lock.unlock_ts, current_ts + duration);
+        pool_state.lock_total_weight -= lock.weight;
+        lock.weight = lock_weight(lock.amount, lock.unlock_ts - current_ts);
+        pool_state.lock_total_weight += lock.weight;
+
+        lock.serialize(&mut &mut lock_account.try_borrow_mut_data()?[..])
+            .or(Err(ProgramError::AccountDataTooSmall))?;
+        Self::serialize_pool(&pool_state, pool_account)
+    }
+
+    fn process_claim_lock_rewards<'a>(program_id: &Pubkey, accounts: &[AccountInfo<'a>]) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let pool_account = next_account_info(account_info_iter)?;
+        let mut pool_state = Self::check_and_deserialize_pool_state(pool_account, program_id)?;
+        let pool_authority_account = next_account_info(account_info_iter)?;
+        if *pool_authority_account.key != Self::get_pool_authority(pool_account.key, pool_state.nonce, program_id)? {
+            return Err(PoolError::InvalidPoolAuthorityAccount.into());
+        }
+        let lp_mint_account = next_account_info(account_info_iter)?;
+        if *lp_mint_account.key != pool_state.lp_mint_key {
+            return Err(PoolError::InvalidMintAccount.into());
+        }
+        let lock_account = next_account_info(account_info_iter)?;
+        let owner_account = next_account_info(account_info_iter)?;
+        let owner_lp_account = next_account_info(account_info_iter)?;
+        let token_program_account = next_account_info(account_info_iter)?;
+        if *token_program_account.key != spl_token::id() {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let mut lock = Self::check_and_deserialize_lock(lock_account, pool_account, owner_account, program_id)?;
+
+        Self::settle_lock_rewards(
+            &mut pool_state,
+            &mut lock,
+            lp_mint_account,
+            owner_lp_account,
+            pool_authority_account,
+            token_program_account,
+            pool_account,
+        )?;
+
+        lock.serialize(&mut &mut lock_account.try_borrow_mut_data()?[..])
+            .or(Err(ProgramError::AccountDataTooSmall))?;
+        Self::serialize_pool(&pool_state, pool_account)
+    }
+
+    fn process_unlock_lp<'a>(program_id: &Pubkey, accounts: &[AccountInfo<'a>]) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let pool_account = next_account_info(account_info_iter)?;
+        let mut pool_state = Self::check_and_deserialize_pool_state(pool_account, program_id)?;
+        let pool_authority_account = next_account_info(account_info_iter)?;
+        if *pool_authority_account.key != Self::get_pool_authority(pool_account.key, pool_state.nonce, program_id)? {
+            return Err(PoolError::InvalidPoolAuthorityAccount.into());
+        }
+        let lp_mint_account = next_account_info(account_info_iter)?;
+        if *lp_mint_account.key != pool_state.lp_mint_key {
+            return Err(PoolError::InvalidMintAccount.into());
+        }
+        let vault_account = next_account_info(account_info_iter)?;
+        if *vault_account.key != find_lock_vault_address(program_id, pool_account.key).0 {
+            return Err(PoolError::NonCanonicalAddress.into());
+        }
+        let lock_account = next_account_info(account_info_iter)?;
+        let owner_account = next_account_info(account_info_iter)?;
+        let owner_lp_account = next_account_info(account_info_iter)?;
+        let token_program_account = next_account_info(account_info_iter)?;
+        if *token_program_account.key != spl_token::id() {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let mut lock = Self::check_and_deserialize_lock(lock_account, pool_account, owner_account, program_id)?;
+        //winding the pool down releases every lock, the lp tokens are needed for the distribution
+        if lock.unlock_ts > Self::get_current_ts()? && !pool_state.is_sunset() {
+            return Err(PoolError::LpStillLocked.into());
+        }
+
+        Self::settle_lock_rewards(
+            &mut pool_state,
+            &mut lock,
+            lp_mint_account,
+            owner_lp_account,
+            pool_authority_account,
+            token_program_account,
+            pool_account,
+        )?;
+        if lock.amount > 0 {
+            Self::transfer_pool_token(
+                vault_account,
+                owner_lp_account,
+                lock.amount,
+                pool_authority_account,
+                token_program_account,
+                pool_account,
+                pool_state.nonce,
+            )?;
+        }
+        pool_state.lock_total_weight -= lock.weight;
+
+        **owner_account.lamports.borrow_mut() += lock_account.lamports();
+        **lock_account.lamports.borrow_mut() = 0;
+        lock_account.data.borrow_mut().fill(0);
+        Self::serialize_pool(&pool_state, pool_account)
+    }
+
+    fn process_checkpoint_fees<'a>(program_id: &Pubkey, accounts: &[AccountInfo<'a>]) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let pool_account = next_account_info(account_info_iter)?;
+        let pool_state = Self::check_and_deserialize_pool_state(pool_account, program_id)?;
+        //pools of the first layout don't keep their lp fee growth (see PoolState::fits_layout_v0)
+        if pool_state.layout_version == 0 {
+            return Err(PoolError::PoolLayoutOutdated.into());
+        }
+        let lp_account = next_account_info(account_info_iter)?;
+        let owner_account = next_account_info(account_info_iter)?;
+        let checkpoint_account = next_account_info(account_info_iter)?;
+        let payer_account = next_account_info(account_info_iter)?;
+        let system_program_account = next_account_info(account_info_iter)?;
+        let lp_state = Self::unpack_lp_account(lp_account)?;
+        if lp_state.mint != pool_state.lp_mint_key {
+            return Err(PoolError::InvalidMintAccount.into());
+        }
+        if lp_state.owner != *owner_account.key {
+            return Err(TokenError::OwnerMismatch.into());
+        }
+        if !owner_account.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        let (checkpoint_key, checkpoint_bump) =
+            find_fee_checkpoint_address(program_id, pool_account.key, lp_account.key);
+        if *checkpoint_account.key != checkpoint_key {
+            return Err(PoolError::NonCanonicalAddress.into());
+        }
+
+        let lp_fees_earned = if checkpoint_account.owner == program_id {
+            FeeCheckpoint::deserialize(&mut &**checkpoint_account.data.borrow())?
+                .lp_fees_earned(pool_state.lp_fee_growth)
+        } else {
+            Self::create_program_address_account(
+                payer_account,
+                checkpoint_account,
+                system_program_account,
+                solana_program::borsh::get_packed_len::<FeeCheckpoint>(),
+                program_id,
+                &[
+                    FEE_CHECKPOINT_SEED,
+                    &pool_account.key.to_bytes(),
+                    &lp_account.key.to_bytes(),
+                    &[checkpoint_bump],
+                ],
+            )?;
+            0
+        };
+        let checkpoint = FeeCheckpoint {
+            pool: *pool_account.key,
+            lp_account: *lp_account.key,
+            lp_amount: lp_state.amount,
+            lp_fee_growth: pool_state.lp_fee_growth,
+            ts: Self::get_current_ts()?,
+        };
+        checkpoint
+            .serialize(&mut &mut checkpoint_account.try_borrow_mut_data()?[..])
+            .or(Err(ProgramError::AccountDataTooSmall))?;
+        set_return_data(&lp_fees_earned.try_to_vec()?);
+        Ok(())
+    }
+
+    fn process_snapshot_lp_supply(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let pool_account = next_account_info(account_info_iter)?;
+        let pool_state = Self::check_and_deserialize_pool_state(pool_account, program_id)?;
+        //pools of the first layout don't keep their lp supply seconds (see PoolState::fits_layout_v0)
+        if pool_state.layout_version == 0 {
+            return Err(PoolError::PoolLayoutOutdated.into());
+        }
+        let snapshots_account = next_account_info(account_info_iter)?;
+        let lp_mint_account = next_account_info(account_info_iter)?;
+        let payer_account = next_account_info(account_info_iter)?;
+        let system_program_account = next_account_info(account_info_iter)?;
+        let (snapshots_key, snapshots_bump) = find_lp_supply_address(program_id, pool_account.key);
+        if *snapshots_account.key != snapshots_key {
+            return Err(PoolError::NonCanonicalAddress.into());
+        }
+        if *lp_mint_account.key != pool_state.lp_mint_key {
+            return Err(PoolError::InvalidMintAccount.into());
+        }
+        let lp_supply = pool_state.lp_supply(Self::unpack_lp_mint(lp_mint_account)?.supply);
+
+        let mut snapshots = if snapshots_account.owner == program_id {
+            LpSupplySnapshots::deserialize(&mut &**snapshots_account.data.borrow())?
+        } else {
+            Self::create_program_address_account(
+                payer_account,
+                snapshots_account,
+                system_program_account,
+                solana_program::borsh::get_packed_len::<LpSupplySnapshots>(),
+                program_id,
+                &[LP_SUPPLY_SEED, &pool_account.key.to_bytes(), &[snapshots_bump]],
+            )?;
+            LpSupplySnapshots {
+                pool: *pool_account.key,
+                ..LpSupplySnapshots::default()
+            }
+        };
+        //the supply didn't change since lp_supply_ts, or the pool would have advanced it
+        let clock = Clock::get()?;
+        snapshots.record(LpSupplyCheckpoint {
+            lp_supply,
+            lp_supply_seconds: pool_state.lp_supply_seconds_at(lp_supply, clock.unix_timestamp),
+            ts: clock.unix_timestamp,
+            slot: clock.slot,
+        });
+        snapshots
+            .serialize(&mut &mut snapshots_account.try_borrow_mut_data()?[..])
+            .or(Err(ProgramError::AccountDataTooSmall))
+    }
+
+    fn process_create_launch_allocation(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let pool_account = next_account_info(account_info_iter)?;
+        Self::check_and_deserialize_pool_state(pool_account, program_id)?;
+        let launch_allocation_account = next_account_info(account_info_iter)?;
+        let owner_account = next_account_info(account_info_iter)?;
+        let payer_account = next_account_info(account_info_iter)?;
+        let system_program_account = next_account_info(account_info_iter)?;
+        let (launch_allocation_key, launch_allocation_bump) =
+            find_launch_allocation_address(program_id, pool_account.key, owner_account.key);
+        if *launch_allocation_account.key != launch_allocation_key {
+            return Err(PoolError::NonCanonicalAddress.into());
+        }
+        if launch_allocation_account.owner == program_id {
+            return Ok(());
+        }
+
+        Self::create_program_address_account(
+            payer_account,
+            launch_allocation_account,
+            system_program_account,
+            solana_program::borsh::get_packed_len::<LaunchAllocation>(),
+            program_id,
+            &[
+                LAUNCH_ALLOCATION_SEED,
+                &pool_account.key.to_bytes(),
+                &owner_account.key.to_bytes(),
+                &[launch_allocation_bump],
+            ],
+        )?;
+        LaunchAllocation {
+            pool: *pool_account.key,
+            owner: *owner_account.key,
+            ..LaunchAllocation::default()
+        }
+        .serialize(&mut &mut launch_allocation_account.try_borrow_mut_data()?[..])
+        .or(Err(ProgramError::AccountDataTooSmall))
+    }
+
+    fn process_authorize_session(
+        session_key: &Pubkey,
+        expiry_ts: UnixTimestamp,
+        spend_limits: &[AtomicT; TOKEN_COUNT],
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let pool_account = next_account_info(account_info_iter)?;
+        Self::check_and_deserialize_pool_state(pool_account, program_id)?;
+        let session_account = next_account_info(account_info_iter)?;
+        let user_account = next_account_info(account_info_iter)?;
+        let user_token_accounts: [_; TOKEN_COUNT] = create_result_array(|_| next_account_info(account_info_iter))?;
+        let token_program_account = next_account_info(account_info_iter)?;
+        let system_program_account = next_account_info(account_info_iter)?;
+        if !user_account.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        if *token_program_account.key != spl_token::id() {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        if expiry_ts <= Self::get_current_ts()? {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+        let (session_address, session_bump) = find_session_address(program_id, pool_account.key, user_account.key);
+        if *session_account.key != session_address {
+            return Err(PoolError::NonCanonicalAddress.into());
+        }
+
+        if session_account.owner != program_id {
+            Self::create_program_address_account(
+                user_account,
+                session_account,
+                system_program_account,
+                solana_program::borsh::get_packed_len::<Session<TOKEN_COUNT>>(),
+                program_id,
+                &[
+                    SESSION_SEED,
+                    &pool_account.key.to_bytes(),
+                    &user_account.key.to_bytes(),
+                    &[session_bump],
+                ],
+            )?;
+        }
+        Session {
+            pool: *pool_account.key,
+            user: *user_account.key,
+            session_key: *session_key,
+            expiry_ts,
+            remaining_limits: *spend_limits,
+        }
+        .serialize(&mut &mut session_account.try_borrow_mut_data()?[..])
+        .or(Err(ProgramError::AccountDataTooSmall))?;
+
+        for i in 0..TOKEN_COUNT {
+            if spend_limits[i] > 0 {
+                invoke(
+                    &approve(
+                        token_program_account.key,
+                        user_token_accounts[i].key,
+                        session_account.key,
+                        user_account.key,
+                        &[],
+                        spend_limits[i],
+                    )?,
+                    &[
+                        user_token_accounts[i].clone(),
+                        session_account.clone(),
+                        user_account.clone(),
+                        token_program_account.clone(),
+                    ],
+                )?;
+            }
+        }
+        Ok(())
+    }
+
+    fn process_revoke_session(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let pool_account = next_account_info(account_info_iter)?;
+        Self::check_and_deserialize_pool_state(pool_account, program_id)?;
+        let session_account = next_account_info(account_info_iter)?;
+        let user_account = next_account_info(account_info_iter)?;
+        let user_token_accounts: [_; TOKEN_COUNT] = create_result_array(|_| next_account_info(account_info_iter))?;
+        let token_program_account = next_account_info(account_info_iter)?;
+        if !user_account.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        if *token_program_account.key != spl_token::id() {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        if *session_account.key != find_session_address(program_id, pool_account.key, user_account.key).0 {
+            return Err(PoolError::NonCanonicalAddress.into());
+        }
+        if session_account.owner != program_id {
+            return Err(ProgramError::UninitializedAccount);
+        }
+
+        //accounts whose delegate the user changed since keep their new one
+        for user_token_account in user_token_accounts.iter() {
+            let token_state = Self::check_program_owner_and_unpack::<TokenState>(user_token_account)?;
+            if token_state.delegate == COption::Some(*session_account.key) {
+                invoke(
+                    &revoke(token_program_account.key, user_token_account.key, user_account.key, &[])?,
+                    &[
+                        (*user_token_account).clone(),
+                        user_account.clone(),
+                        token_program_account.clone(),
+                    ],
+                )?;
+            }
+        }
+        **user_account.lamports.borrow_mut() += session_account.lamports();
+        **session_account.lamports.borrow_mut() = 0;
+        session_account.data.borrow_mut().fill(0);
+        Ok(())
+    }
+
+    fn process_announce_withdrawal(lp_amount: AtomicT, program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let pool_account = next_account_info(account_info_iter)?;
+        Self::check_and_deserialize_pool_state(pool_account, program_id)?;
+        let withdrawal_announcement_account = next_account_info(account_info_iter)?;
+        let user_account = next_account_info(account_info_iter)?;
+        let system_program_account = next_account_info(account_info_iter)?;
+        if !user_account.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        let (withdrawal_announcement_address, withdrawal_announcement_bump) =
+            find_withdrawal_announcement_address(program_id, pool_account.key, user_account.key);
+        if *withdrawal_announcement_account.key != withdrawal_announcement_address {
+            return Err(PoolError::NonCanonicalAddress.into());
+        }
+
+        if lp_amount == 0 {
+            if withdrawal_announcement_account.owner == program_id {
+                **user_account.lamports.borrow_mut() += withdrawal_announcement_account.lamports();
+                **withdrawal_announcement_account.lamports.borrow_mut() = 0;
+                withdrawal_announcement_account.data.borrow_mut().fill(0);
+            }
+            return Ok(());
+        }
+        if withdrawal_announcement_account.owner != program_id {
+            Self::create_program_address_account(
+                user_account,
+                withdrawal_announcement_account,
+                system_program_account,
+                solana_program::borsh::get_packed_len::<WithdrawalAnnouncement>(),
+                program_id,
+                &[
+                    WITHDRAWAL_ANNOUNCEMENT_SEED,
+                    &pool_account.key.to_bytes(),
+                    &user_account.key.to_bytes(),
+                    &[withdrawal_announcement_bump],
+                ],
+            )?;
+        }
+        WithdrawalAnnouncement {
+            pool: *pool_account.key,
+            user: *user_account.key,
+            lp_amount,
+            announced_ts: Self::get_current_ts()?,
+        }
+        .serialize(&mut &mut withdrawal_announcement_account.try_borrow_mut_data()?[..])
+        .or(Err(ProgramError::AccountDataTooSmall))
+    }
+
+    fn process_confirm_emergency(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let pool_account = next_account_info(account_info_iter)?;
+        let mut pool_state = Self::check_and_deserialize_pool_state(pool_account, program_id)?;
+        let responder_account = next_account_info(account_info_iter)?;
+        if !responder_account.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        let responder_index = pool_state
+            .emergency_responder_index(responder_account.key)
+            .ok_or(PoolError::NotEmergencyResponder)?;
+        if pool_state.is_paused {
+            return Err(PoolError::PoolIsPaused.into());
+        }
+
+        let current_ts = Self::get_current_ts()?;
+        pool_state.emergency_confirmation_ts[responder_index] = current_ts;
+        let confirmations = pool_state.emergency_confirmations(current_ts);
+        msg!(
+            "emergency confirmed by {} of {} responders",
+            confirmations,
+            pool_state.emergency_threshold
+        );
+        if confirmations >= pool_state.emergency_threshold as usize {
+            msg!("pool paused by its emergency responders, only uniform removes remain");
+            pool_state.is_paused = true;
+            pool_state.emergency_confirmation_ts = [0; MAX_EMERGENCY_RESPONDERS];
+        }
+        Self::serialize_pool(&pool_state, pool_account)
+    }
+
+    fn process_peg_credit(
+        amount: AtomicT,
+        provide: bool,
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        if amount == 0 {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+        let mut account_info_iter = accounts.iter();
+        let pool_account = next_account_info(&mut account_info_iter)?;
+        let mut pool_state = Self::check_and_deserialize_pool_state(pool_account, program_id)?;
+        let lp_mint_account = next_account_info(&mut account_info_iter)?;
+        if *lp_mint_account.key != pool_state.lp_mint_key {
+            return Err(PoolError::InvalidMintAccount.into());
+        }
+        let pool_balances: [_; TOKEN_COUNT] = create_result_array(|i| -> Result<_, ProgramError> {
+            let pool_token_account = next_account_info(&mut account_info_iter)?;
+            if *pool_token_account.key != pool_state.token_keys[i] {
+                return Err(PoolError::PoolTokenAccountExpected.with_context(format_args!("token index {}", i)));
+            }
+            Ok(Self::check_program_owner_and_unpack::<TokenState>(pool_token_account)?.amount)
+        })?;
+        let keeper_account = next_account_info(&mut account_info_iter)?;
+        if !keeper_account.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        if !pool_state.peg_keeper.is_set() || *keeper_account.key != pool_state.peg_keeper.keeper_key {
+            return Err(PoolError::InvalidPegKeeper.into());
+        }
+        if pool_state.is_paused {
+            return Err(PoolError::PoolIsPaused.into());
+        }
+        //repaying the keeper only takes liquidity out, like a remove
+        if provide && pool_state.is_sunset() {
+            return Err(PoolError::PoolIsSunset.into());
+        }
+        let rates = Self::load_rates(&pool_state, &mut account_info_iter)?;
+        let lp_supply = pool_state.lp_supply(Self::unpack_lp_mint(lp_mint_account)?.supply);
+        if lp_supply == 0 {
+            return Err(PoolError::PoolIsEmpty.into());
+        }
+
+        let peg_keeper = pool_state.peg_keeper;
+        let exceeds_credit = if provide {
+            amount > peg_keeper.available_credit()
+        } else {
+            amount > peg_keeper.debt
+        };
+        if exceeds_credit {
+            return Err(PoolError::PegCreditExceeded.into());
+        }
+        let current_ts = Self::get_current_ts()?;
+        let amp_factor = pool_state.effective_amp_factor(current_ts);
+        let Quote {
+            amount: lp_amount,
+            latest_depth,
+            ..
+        } = Quoter::new(&pool_state, &pool_balances, lp_supply, amp_factor)
+            .with_rates(&rates)
+            .peg_credit(amount, provide)?;
+
+        let mut peg_keeper = peg_keeper;
+        let lp_change = if provide {
+            peg_keeper.debt += amount;
+            peg_keeper.lp_amount += lp_amount;
+            //however high governance set the credit limit, the credit can only make up a bounded share of the pool
+            if peg_keeper.lp_amount as u128 * BPS_ONE as u128
+                > (lp_supply + lp_amount) as u128 * MAX_PEG_CREDIT_SHARE_BPS as u128
+            {
+                return Err(PoolError::PegCreditExceeded.into());
+            }
+            lp_amount as i128
+        } else {
+            peg_keeper.debt -= amount;
+            peg_keeper.lp_amount = peg_keeper
+                .lp_amount
+                .checked_sub(lp_amount)
+                .ok_or(PoolError::PegCreditExceeded)?;
+            //whatever the keeper's credit earned beyond its debt stays with the liquidity providers
+            let dropped_amount = if peg_keeper.debt == 0 { peg_keeper.lp_amount } else { 0 };
+            peg_keeper.lp_amount -= dropped_amount;
+            -((lp_amount + dropped_amount) as i128)
+        };
+        pool_state.accrue_lp_supply_seconds(lp_supply, current_ts);
+        pool_state.peg_keeper = peg_keeper;
+        Self::update_internal_oracle(&mut pool_state, &pool_balances, &rates, amp_factor, latest_depth)?;
+        pool_state.previous_depth = latest_depth;
+        pool_state.depth_rates = rates;
+        Self::serialize_pool(&pool_state, pool_account)?;
+        msg!(
+            "peg keeper {} {} of token {}, debt {}",
+            if provide { "provided" } else { "absorbed" },
+            amount,
+            peg_keeper.token_index,
+            peg_keeper.debt
+        );
+        let lp_supply_event = LpSupplyEvent {
+            user_lp_change: lp_change,
+            governance_mint_amount: 0,
+            lp_supply: (lp_supply as i128 + lp_change) as u64,
+        };
+        msg!("{}", lp_supply_event.to_log());
+        Ok(())
+    }
+
+    //a deploy (`recall` None) moves what the token account holds beyond the adapter's buffer to the adapter, a recall
+    // the given amount back. either records the value of the pool's position that the adapter reports
+    fn process_yield<'a>(
+        token_index: u8,
+        recall: Option<AtomicT>,
+        program_id: &Pubkey,
+        accounts: &[AccountInfo<'a>],
+    ) -> ProgramResult {
+        let index = token_index as usize;
+        if index >= TOKEN_COUNT {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+        let mut account_info_iter = accounts.iter();
+        let pool_account = next_account_info(&mut account_info_iter)?;
+        let mut pool_state = Self::check_and_deserialize_pool_state(pool_account, program_id)?;
+        let pool_authority_account = next_account_info(&mut account_info_iter)?;
+        if *pool_authority_account.key != Self::get_pool_authority(pool_account.key, pool_state.nonce, program_id)? {
+            return Err(PoolError::InvalidPoolAuthorityAccount.into());
+        }
+        let pool_token_accounts: [_; TOKEN_COUNT] = create_result_array(|i| -> Result<_, ProgramError> {
+            let pool_token_account = next_account_info(&mut account_info_iter)?;
+            if *pool_token_account.key != pool_state.token_keys[i] {
+                return Err(PoolError::PoolTokenAccountExpected.with_context(format_args!("token index {}", i)));
+            }
+            Ok(pool_token_account)
+        })?;
+        let token_program_account = next_account_info(&mut account_info_iter)?;
+        if *token_program_account.key != spl_token::id() {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let rates = Self::load_rates(&pool_state, &mut account_info_iter)?;
+        let adapter_program_account = next_account_info(&mut account_info_iter)?;
+        let yield_adapter = pool_state.yield_adapters[index];
+        if !yield_adapter.is_set() || *adapter_program_account.key != yield_adapter.program {
+            return Err(PoolError::InvalidYieldAdapter.with_context(format_args!("token index {}", index)));
+        }
+        let yield_delegate_account = next_account_info(&mut account_info_iter)?;
+        if *yield_delegate_account.key != find_yield_delegate_address(&yield_adapter.program, pool_account.key).0 {
+            return Err(PoolError::InvalidYieldAdapter.with_context("yield delegate expected"));
+        }
+        //recalls take liquidity out, like a remove, so they stay possible
+        if recall.is_none() {
+            if pool_state.is_paused {
+                return Err(PoolError::PoolIsPaused.into());
+            }
+            if pool_state.is_sunset() {
+                return Err(PoolError::PoolIsSunset.into());
+            }
+        }
+
+        let previous_balances: [_; TOKEN_COUNT] = create_result_array(|i| -> Result<_, ProgramError> {
+            Ok(Self::check_program_owner_and_unpack::<TokenState>(pool_token_accounts[i])?.amount)
+        })?;
+        let pool_token_account = pool_token_accounts[index];
+        let balance = previous_balances[index];
+        //the pool can't check what the adapter reports, so the position is never worth more than what went into it
+        // less what came out of it
+        let (adapter_instruction, expected_balance, max_deployed) = match recall {
+            Some(amount) => (
+                YieldAdapterInstruction::Withdraw { amount },
+                balance.checked_add(amount).ok_or(ProgramError::ArithmeticOverflow)?,
+                yield_adapter.deployed.saturating_sub(amount),
+            ),
+            None => {
+                let amount = balance.saturating_sub(yield_adapter.buffer);
+                (
+                    YieldAdapterInstruction::Deposit { amount },
+                    balance - amount,
+                    yield_adapter
+                        .deployed
+                        .checked_add(amount)
+                        .ok_or(ProgramError::ArithmeticOverflow)?,
+                )
+            }
+        };
+        let approve_amount = match adapter_instruction {
+            YieldAdapterInstruction::Deposit { amount } if amount > 0 => amount,
+            _ => 0,
+        };
+        //the adapter can only take what the pool approves its delegate for, rather than acting as the pool authority
+        if approve_amount > 0 {
+            invoke_signed(
+                &approve(
+                    token_program_account.key,
+                    pool_token_account.key,
+                    yield_delegate_account.key,
+                    pool_authority_account.key,
+                    &[],
+                    approve_amount,
+                )?,
+                &[
+                    pool_token_account.clone(),
+                    yield_delegate_account.clone(),
+                    pool_authority_account.clone(),
+                    token_program_account.clone(),
+                ],
+                &[&[&pool_account.key.to_bytes()[..32], &[pool_state.nonce]][..]],
+            )?;
+        }
+        let adapter_accounts = account_info_iter.as_slice();
+        let mut account_metas = vec![
+            AccountMeta::new_readonly(*pool_account.key, false),
+            AccountMeta::new_readonly(*yield_delegate_account.key, false),
+            AccountMeta::new(*pool_token_account.key, false),
+            AccountMeta::new_readonly(*token_program_account.key, false),
+        ];
+        //the caller's signatures don't carry over to the adapter
+        account_metas.extend(adapter_accounts.iter().map(|account| AccountMeta {
+            pubkey: *account.key,
+            is_signer: false,
+            is_writable: account.is_writable,
+        }));
+        let mut account_infos = vec![
+            pool_account.clone(),
+            yield_delegate_account.clone(),
+            pool_token_account.clone(),
+            token_program_account.clone(),
+        ];
+        account_infos.extend_from_slice(adapter_accounts);
+        account_infos.push(adapter_program_account.clone());
+        invoke(
+            &Instruction {
+                program_id: yield_adapter.program,
+                accounts: account_metas,
+                data: adapter_instruction.try_to_vec()?,
+            },
+            &account_infos,
+        )?;
+        if approve_amount > 0 {
+            invoke_signed(
+                &revoke(
+                    token_program_account.key,
+                    pool_token_account.key,
+                    pool_authority_account.key,
+                    &[],
+                )?,
+                &[
+                    pool_token_account.clone(),
+                    pool_authority_account.clone(),
+                    token_program_account.clone(),
+                ],
+                &[&[&pool_account.key.to_bytes()[..32], &[pool_state.nonce]][..]],
+            )?;
+        }
+
+        let pool_balances: [_; TOKEN_COUNT] = create_result_array(|i| -> Result<_, ProgramError> {
+            Ok(Self::check_program_owner_and_unpack::<TokenState>(pool_token_accounts[i])?.amount)
+        })?;
+        if pool_balances[index] != expected_balance {
+            return Err(PoolError::InvalidYieldAdapter.with_context(format_args!(
+                "balance {} instead of {}",
+                pool_balances[index], expected_balance
+            )));
+        }
+        if let Some(i) = (0..TOKEN_COUNT).find(|&i| i != index && pool_balances[i] != previous_balances[i]) {
+            return Err(PoolError::InvalidYieldAdapter.with_context(format_args!("token index {} moved", i)));
+        }
+        let deployed = match get_return_data() {
+            Some((program, data)) if program == yield_adapter.program => {
+                AtomicT::try_from_slice(&data).or(Err(PoolError::InvalidYieldAdapter))?
+            }
+            _ => return Err(PoolError::InvalidYieldAdapter.with_context("no position reported")),
+        };
+        pool_state.yield_adapters[index].deployed = deployed.min(max_deployed);
+
+        //what recalls brought back beyond the recorded position accrues to the depth like fees
+        let amp_factor = pool_state.effective_amp_factor(Self::get_current_ts()?);
+        let invariant_balances = pool_state.invariant_balances(&pool_balances);
+        let latest_depth = quote::depth(
+            &pool_state,
+            &create_array(|i| {
+                quote::to_rated(
+                    quote::to_equalized(invariant_balances[i], pool_state.token_decimal_equalizers[i]),
+                    rates[i],
+                    false,
+                )
+            }),
+            amp_factor,
+        )?
+        .as_u128();
+        Self::update_internal_oracle(&mut pool_state, &pool_balances, &rates, amp_factor, latest_depth)?;
+        pool_state.previous_depth = latest_depth;
+        pool_state.depth_rates = rates;
+        Self::serialize_pool(&pool_state, pool_account)?;
+        msg!(
+            "yield adapter {:?} of token {}, deployed {}",
+            adapter_instruction,
+            index,
+            pool_state.yield_adapters[index].deployed
+        );
+        Ok(())
+    }
+
+    fn process_repeg(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+        let mut account_info_iter = accounts.iter();
+        let pool_account = next_account_info(&mut account_info_iter)?;
+        let mut pool_state = Self::check_and_deserialize_pool_state(pool_account, program_id)?;
+        if pool_state.invariant_kind != InvariantKind::Hybrid {
+            return Err(PoolError::InvalidPriceScale.into());
+        }
+        if pool_state.is_paused {
+            return Err(PoolError::PoolIsPaused.into());
+        }
+        let pool_balances: [_; TOKEN_COUNT] = create_result_array(|i| -> Result<_, ProgramError> {
+            let pool_token_account = next_account_info(&mut account_info_iter)?;
+            if *pool_token_account.key != pool_state.token_keys[i] {
+                return Err(PoolError::PoolTokenAccountExpected.with_context(format_args!("token index {}", i)));
+            }
+            Ok(Self::check_program_owner_and_unpack::<TokenState>(pool_token_account)?.amount)
+        })?;
+        let rate_accounts = account_info_iter.as_slice();
+        let mut rates = Self::load_rates(&pool_state, &mut account_info_iter)?;
+
+        let price_scale = pool_state.price_scale;
+        let index = price_scale.token_index as usize;
+        let source = pool_state.rate_sources[index];
+        let clock = Clock::get()?;
+        let rate_account = rate_accounts
+            .iter()
+            .find(|account| source.has_account() && *account.key == pool_state.rate_keys[index]);
+        let oracle_rate = match rate_account {
+            Some(rate_account) => oracle::load_rate(
+                source,
+                rate_account.owner,
+                &rate_account.data.borrow(),
+                &pool_state.token_mint_keys[index],
+                &clock,
+                &pool_state.oracle_limits[index],
+            )?,
+            None => oracle::load_rate(
+                source,
+                &Pubkey::default(),
+                &[],
+                &pool_state.token_mint_keys[index],
+                &clock,
+                &pool_state.oracle_limits[index],
+            )?,
+        };
+        let (rate, oracle) = (price_scale.rate as u128, oracle_rate as u128);
+        let difference = max(rate, oracle) - min(rate, oracle);
+        if price_scale.max_step_bps == 0
+            || clock.unix_timestamp < price_scale.last_repeg_ts.saturating_add(price_scale.min_interval)
+            || difference * BPS_ONE as u128 <= rate * price_scale.threshold_bps as u128
+        {
+            return Err(PoolError::RepegNotDue.with_context(format_args!(
+                "oracle rate {}, price scale {}",
+                oracle_rate, price_scale.rate
+            )));
+        }
+        //at most a step at a time, so a manipulated oracle can only move the price so far
+        let step = min(difference, rate * price_scale.max_step_bps as u128 / BPS_ONE as u128);
+        //between the old price and the oracle's rate, so it fits
+        let new_rate = (if oracle > rate { rate + step } else { rate - step }) as u64;
+        pool_state.price_scale.rate = new_rate;
+        pool_state.price_scale.last_repeg_ts = clock.unix_timestamp;
+
+        //the pool values the volatile token at its new price from here on, like after a rate change
+        rates[index] = new_rate;
+        let amp_factor = pool_state.effective_amp_factor(clock.unix_timestamp);
+        let invariant_balances = pool_state.invariant_balances(&pool_balances);
+        let latest_depth = quote::depth(
+            &pool_state,
+            &create_array(|i| {
+                quote::to_rated(
+                    quote::to_equalized(invariant_balances[i], pool_state.token_decimal_equalizers[i]),
+                    rates[i],
+                    false,
+                )
+            }),
+            amp_factor,
+        )?
+        .as_u128();
+        Self::update_internal_oracle(&mut pool_state, &pool_balances, &rates, amp_factor, latest_depth)?;
+        pool_state.previous_depth = latest_depth;
+        pool_state.depth_rates = rates;
+        Self::serialize_pool(&pool_state, pool_account)?;
+        msg!(
+            "repegged token {}: {} -> {} (oracle rate {})",
+            index,
+            price_scale.rate,
+            new_rate,
+            oracle_rate
+        );
+        Ok(())
+    }
+
+    fn process_fund_gauge<'a>(
+        amount: AtomicT,
+        duration: UnixTimestamp,
+        program_id: &Pubkey,
+        accounts: &[AccountInfo<'a>],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let pool_account = next_account_info(account_info_iter)?;
+        let pool_state = Self::check_and_deserialize_pool_state(pool_account, program_id)?;
+        if pool_state.is_sunset() {
+            return Err(PoolError::PoolIsSunset.into());
+        }
+        if !(1..=MAX_GAUGE_DURATION).contains(&duration) {
+            return Err(PoolError::InvalidGauge.with_context(format_args!("duration {}", duration)));
+        }
+        let funder_account = next_account_info(account_info_iter)?;
+        if !funder_account.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        let gauge_account = next_account_info(account_info_iter)?;
+        let mut gauge = Self::check_and_deserialize_gauge(gauge_account, pool_account, program_id)?;
+        let reward_mint_account = next_account_info(account_info_iter)?;
+        let reward_vault_account = next_account_info(account_info_iter)?;
+        let funder_reward_account = next_account_info(account_info_iter)?;
+        let token_program_account = next_account_info(account_info_iter)?;
+        if *token_program_account.key != spl_token::id() {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let index = gauge
+            .reward_index(reward_mint_account.key)
+            .filter(|&index| !gauge.rewards[index].is_retired())
+            .ok_or_else(|| PoolError::InvalidGauge.with_context("not an active reward of the gauge"))?;
+        if gauge.rewards[index].funder != *funder_account.key {
+            return Err(PoolError::InvalidGauge.with_context(format_args!("funded by {}", gauge.rewards[index].funder)));
+        }
+        if *reward_vault_account.key
+            != find_gauge_vault_address(program_id, pool_account.key, reward_mint_account.key).0
+        {
+            return Err(PoolError::NonCanonicalAddress.into());
+        }
+
+        if amount > 0 {
+            Self::transfer_token(
+                funder_reward_account,
+                reward_vault_account,
+                amount,
+                funder_account,
+                token_program_account,
+            )?;
+        }
+        gauge
+            .fund(index, amount, duration, Self::get_current_ts()?)
+            .ok_or_else(|| PoolError::InvalidGauge.with_context("the rewards exceed a u64"))?;
+        let reward = gauge.rewards[index];
+        //the rewards would never be emitted
+        if reward.emission_rate == 0 {
+            return Err(PoolError::InvalidGauge.with_context("the emission rate rounds down to 0"));
+        }
+        gauge
+            .serialize(&mut &mut gauge_account.try_borrow_mut_data()?[..])
+            .or(Err(ProgramError::AccountDataTooSmall))?;
+        msg!(
+            "gauge emits {} {} per second until {}",
+            reward.emission_rate,
+            reward.mint,
+            reward.emission_end_ts
+        );
+        Ok(())
+    }
+
+    fn process_stake_lp<'a>(amount: AtomicT, program_id: &Pubkey, accounts: &[AccountInfo<'a>]) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let pool_account = next_account_info(account_info_iter)?;
+        let pool_state = Self::check_and_deserialize_pool_state(pool_account, program_id)?;
+        if pool_state.is_paused {
+            return Err(PoolError::PoolIsPaused.into());
+        }
+        if pool_state.is_sunset() {
+            return Err(PoolError::PoolIsSunset.into());
+        }
+        let gauge_account = next_account_info(account_info_iter)?;
+        let mut gauge = Self::check_and_deserialize_gauge(gauge_account, pool_account, program_id)?;
+        let lp_vault_account = next_account_info(account_info_iter)?;
+        if *lp_vault_account.key != find_gauge_vault_address(program_id, pool_account.key, &pool_state.lp_mint_key).0 {
+            return Err(PoolError::NonCanonicalAddress.into());
+        }
+        let stake_account = next_account_info(account_info_iter)?;
+        let owner_account = next_account_info(account_info_iter)?;
+        let owner_lp_account = next_account_info(account_info_iter)?;
+        let payer_account = next_account_info(account_info_iter)?;
+        let token_program_account = next_account_info(account_info_iter)?;
+        let system_program_account = next_account_info(account_info_iter)?;
+        if *token_program_account.key != spl_token::id() {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        if !owner_account.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        let (stake_key, stake_bump) = find_gauge_stake_address(program_id, pool_account.key, owner_account.key);
+        if *stake_account.key != stake_key {
+            return Err(PoolError::NonCanonicalAddress.into());
+        }
+
+        gauge.update(Self::get_current_ts()?);
+        let mut stake = if stake_account.owner != program_id {
+            Self::create_program_address_account(
+                payer_account,
+                stake_account,
+                system_program_account,
+                solana_program::borsh::get_packed_len::<GaugeStake>(),
+                program_id,
+                &[
+                    GAUGE_STAKE_SEED,
+                    &pool_account.key.to_bytes(),
+                    &owner_account.key.to_bytes(),
+                    &[stake_bump],
+                ],
+            )?;
+            //settling the empty stake checkpoints the gauge's rewards
+            GaugeStake {
+                pool: *pool_account.key,
+                owner: *owner_account.key,
+                ..GaugeStake::default()
+            }
+        } else {
+            GaugeStake::deserialize(&mut &**stake_account.data.borrow())?
+        };
+        stake.settle(&gauge);
+        if amount > 0 {
+            Self::transfer_token(
+                owner_lp_account,
+                lp_vault_account,
+                amount,
+                owner_account,
+                token_program_account,
+            )?;
+        }
+        stake.amount += amount;
+        gauge.total_staked += amount;
+        Self::update_gauge_boost(&mut gauge, &mut stake, pool_account, owner_account, account_info_iter)?;
+
+        stake
+            .serialize(&mut &mut stake_account.try_borrow_mut_data()?[..])
+            .or(Err(ProgramError::AccountDataTooSmall))?;
+        gauge
+            .serialize(&mut &mut gauge_account.try_borrow_mut_data()?[..])
+            .or(Err(ProgramError::AccountDataTooSmall))
+    }
+
+    fn process_unstake_lp<'a>(amount: AtomicT, program_id: &Pubkey, accounts: &[AccountInfo<'a>]) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let pool_account = next_account_info(account_info_iter)?;
+        let pool_state = Self::check_and_deserialize_pool_state(pool_account, program_id)?;
+        let pool_authority_account = next_account_info(account_info_iter)?;
+        if *pool_authority_account.key != Self::get_pool_authority(pool_account.key, pool_state.nonce, program_id)? {
+            return Err(PoolError::InvalidPoolAuthorityAccount.into());
+        }
+        let gauge_account = next_account_info(account_info_iter)?;
+        let mut gauge = Self::check_and_deserialize_gauge(gauge_account, pool_account, program_id)?;
+        let lp_vault_account = next_account_info(account_info_iter)?;
+        if *lp_vault_account.key != find_gauge_vault_address(program_id, pool_account.key, &pool_state.lp_mint_key).0 {
+            return Err(PoolError::NonCanonicalAddress.into());
+        }
+        let stake_account = next_account_info(account_info_iter)?;
+        let owner_account = next_account_info(account_info_iter)?;
+        let owner_lp_account = next_account_info(account_info_iter)?;
+        let token_program_account = next_account_info(account_info_iter)?;
+        if *token_program_account.key != spl_token::id() {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let mut stake = Self::check_and_deserialize_stake(stake_account, pool_account, owner_account, program_id)?;
+        if amount > stake.amount {
+            return Err(PoolError::InsufficientStake.with_context(format_args!("staked {}", stake.amount)));
+        }
+
+        gauge.update(Self::get_current_ts()?);
+        stake.settle(&gauge);
+        if amount > 0 {
+            Self::transfer_pool_token(
+                lp_vault_account,
+                owner_lp_account,
+                amount,
+                pool_authority_account,
+                token_program_account,
+                pool_account,
+                pool_state.nonce,
+            )?;
+        }
+        stake.amount -= amount;
+        gauge.total_staked -= amount;
+        //exits don't call the ve program, so it can't hold the stake's lp tokens
+        stake.apply_boost(&mut gauge, BPS_ONE);
+
+        if stake.is_empty() {
+            **owner_account.lamports.borrow_mut() += stake_account.lamports();
+            **stake_account.lamports.borrow_mut() = 0;
+            stake_account.data.borrow_mut().fill(0);
+        } else {
+            stake
+                .serialize(&mut &mut stake_account.try_borrow_mut_data()?[..])
+                .or(Err(ProgramError::AccountDataTooSmall))?;
+        }
+        gauge
+            .serialize(&mut &mut gauge_account.try_borrow_mut_data()?[..])
+            .or(Err(ProgramError::AccountDataTooSmall))
+    }
+
+    fn process_claim_gauge_rewards<'a>(program_id: &Pubkey, accounts: &[AccountInfo<'a>]) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let pool_account = next_account_info(account_info_iter)?;
+        let pool_state = Self::check_and_deserialize_pool_state(pool_account, program_id)?;
+        let pool_authority_account = next_account_info(account_info_iter)?;
+        if *pool_authority_account.key != Self::get_pool_authority(pool_account.key, pool_state.nonce, program_id)? {
+            return Err(PoolError::InvalidPoolAuthorityAccount.into());
+        }
+        let gauge_account = next_account_info(account_info_iter)?;
+        let mut gauge = Self::check_and_deserialize_gauge(gauge_account, pool_account, program_id)?;
+        let reward_mint_account = next_account_info(account_info_iter)?;
+        let index = gauge
+            .reward_index(reward_mint_account.key)
+            .ok_or_else(|| PoolError::InvalidGauge.with_context("not a reward of the gauge"))?;
+        let reward_vault_account = next_account_info(account_info_iter)?;
+        if *reward_vault_account.key
+            != find_gauge_vault_address(program_id, pool_account.key, reward_mint_account.key).0
+        {
+            return Err(PoolError::NonCanonicalAddress.into());
+        }
+        let stake_account = next_account_info(account_info_iter)?;
+        let owner_account = next_account_info(account_info_iter)?;
+        let owner_reward_account = next_account_info(account_info_iter)?;
+        let token_program_account = next_account_info(account_info_iter)?;
+        if *token_program_account.key != spl_token::id() {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let mut stake = Self::check_and_deserialize_stake(stake_account, pool_account, owner_account, program_id)?;
+
+        gauge.update(Self::get_current_ts()?);
+        stake.settle(&gauge);
+        let rewards_owed = stake.rewards[index].rewards_owed;
+        if rewards_owed > 0 {
+            Self::transfer_pool_token(
+                reward_vault_account,
+                owner_reward_account,
+                rewards_owed,
+                pool_authority_account,
+                token_program_account,
+                pool_account,
+                pool_state.nonce,
+            )?;
+            stake.rewards[index].rewards_owed = 0;
+        }
+
+        stake
+            .serialize(&mut &mut stake_account.try_borrow_mut_data()?[..])
+            .or(Err(ProgramError::AccountDataTooSmall))?;
+        gauge
+            .serialize(&mut &mut gauge_account.try_borrow_mut_data()?[..])
+            .or(Err(ProgramError::AccountDataTooSmall))
+    }
+
+    fn process_update_gauge_boost<'a>(program_id: &Pubkey, accounts: &[AccountInfo<'a>]) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let pool_account = next_account_info(account_info_iter)?;
+        Self::check_and_deserialize_pool_state(pool_account, program_id)?;
+        let gauge_account = next_account_info(account_info_iter)?;
+        let mut gauge = Self::check_and_deserialize_gauge(gauge_account, pool_account, program_id)?;
+        let stake_account = next_account_info(account_info_iter)?;
+        let owner_account = next_account_info(account_info_iter)?;
+        //unlike the stake's other instructions, this one doesn't need the owner's signature
+        if *stake_account.key != find_gauge_stake_address(program_id, pool_account.key, owner_account.key).0 {
+            return Err(PoolError::NonCanonicalAddress.into());
+        }
+        if stake_account.owner != program_id {
+            return Err(ProgramError::UninitializedAccount);
+        }
+        let mut stake = GaugeStake::deserialize(&mut &**stake_account.data.borrow())?;
+
+        gauge.update(Self::get_current_ts()?);
+        stake.settle(&gauge);
+        Self::update_gauge_boost(&mut gauge, &mut stake, pool_account, owner_account, account_info_iter)?;
+        msg!(
+            "gauge stake {}: working amount {}",
+            owner_account.key,
+            stake.working_amount
+        );
+
+        stake
+            .serialize(&mut &mut stake_account.try_borrow_mut_data()?[..])
+            .or(Err(ProgramError::AccountDataTooSmall))?;
+        gauge
+            .serialize(&mut &mut gauge_account.try_borrow_mut_data()?[..])
+            .or(Err(ProgramError::AccountDataTooSmall))
+    }
+
+    fn process_join_rebate_campaign(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let pool_account = next_account_info(account_info_iter)?;
+        Self::check_and_deserialize_pool_state(pool_account, program_id)?;
+        let campaign_account = next_account_info(account_info_iter)?;
+        let campaign = Self::check_and_deserialize_rebate_campaign(campaign_account, pool_account, program_id)?;
+        let rebate_account = next_account_info(account_info_iter)?;
+        let owner_account = next_account_info(account_info_iter)?;
+        let payer_account = next_account_info(account_info_iter)?;
+        let system_program_account = next_account_info(account_info_iter)?;
+        if Self::get_current_ts()? >= campaign.end_ts {
+            return Err(PoolError::InvalidRebateCampaign.with_context("the campaign ended"));
+        }
+        let (rebate_key, rebate_bump) = find_rebate_address(program_id, campaign_account.key, owner_account.key);
+        if *rebate_account.key != rebate_key {
+            return Err(PoolError::NonCanonicalAddress.into());
+        }
+        if rebate_account.owner == program_id {
+            return Ok(());
+        }
+
+        Self::create_program_address_account(
+            payer_account,
+            rebate_account,
+            system_program_account,
+            solana_program::borsh::get_packed_len::<Rebate>(),
+            program_id,
+            &[
+                REBATE_SEED,
+                &campaign_account.key.to_bytes(),
+                &owner_account.key.to_bytes(),
+                &[rebate_bump],
+            ],
+        )?;
+        Rebate {
+            campaign: *campaign_account.key,
+            owner: *owner_account.key,
+            fees: 0,
+        }
+        .serialize(&mut &mut rebate_account.try_borrow_mut_data()?[..])
+        .or(Err(ProgramError::AccountDataTooSmall))
+    }
+
+    fn process_claim_rebate<'a>(program_id: &Pubkey, accounts: &[AccountInfo<'a>]) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let pool_account = next_account_info(account_info_iter)?;
+        let pool_state = Self::check_and_deserialize_pool_state(pool_account, program_id)?;
+        let pool_authority_account = next_account_info(account_info_iter)?;
+        if *pool_authority_account.key != Self::get_pool_authority(pool_account.key, pool_state.nonce, program_id)? {
+            return Err(PoolError::InvalidPoolAuthorityAccount.into());
+        }
+        let campaign_account = next_account_info(account_info_iter)?;
+        let campaign = Self::check_and_deserialize_rebate_campaign(campaign_account, pool_account, program_id)?;
+        let vault_account = next_account_info(account_info_iter)?;
+        if *vault_account.key != find_rebate_vault_address(program_id, campaign_account.key).0 {
+            return Err(PoolError::NonCanonicalAddress.into());
+        }
+        let rebate_account = next_account_info(account_info_iter)?;
+        let owner_account = next_account_info(account_info_iter)?;
+        let owner_rebate_account = next_account_info(account_info_iter)?;
+        let token_program_account = next_account_info(account_info_iter)?;
+        if *token_program_account.key != spl_token::id() {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        if !owner_account.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        if *rebate_account.key != find_rebate_address(program_id, campaign_account.key, owner_account.key).0 {
+            return Err(PoolError::NonCanonicalAddress.into());
+        }
+        if rebate_account.owner != program_id {
+            return Err(ProgramError::UninitializedAccount);
+        }
+        if Self::get_current_ts()? < campaign.end_ts {
+            return Err(PoolError::RebateCampaignNotEnded.with_context(format_args!("ends at {}", campaign.end_ts)));
+        }
+        if campaign.reclaimed {
+            return Err(PoolError::InvalidRebateCampaign.with_context("the budget was reclaimed"));
+        }
+
+        let rebate = Rebate::deserialize(&mut &**rebate_account.data.borrow())?;
+        let amount = campaign.rebate(rebate.fees);
+        if amount > 0 {
+            Self::transfer_pool_token(
+                vault_account,
+                owner_rebate_account,
+                amount,
+                pool_authority_account,
+                token_program_account,
+                pool_account,
+                pool_state.nonce,
+            )?;
+        }
+        **owner_account.lamports.borrow_mut() += rebate_account.lamports();
+        **rebate_account.lamports.borrow_mut() = 0;
+        rebate_account.data.borrow_mut().fill(0);
+        Ok(())
+    }
+
+    // -------------------------------- Helper Functions --------------------------------
+
+    //the session of `user_account`, whose session key has to sign and whose expiry can't have passed
+    fn process_rfq_swap(quote: RfqQuote, program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+        let input_index = quote.input_token_index as usize;
+        let output_index = quote.output_token_index as usize;
+        if input_index >= TOKEN_COUNT
+            || output_index >= TOKEN_COUNT
+            || input_index == output_index
+            || quote.input_amount == 0
+            || quote.output_amount == 0
+        {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+        let mut account_info_iter = accounts.iter();
+        let pool_account = next_account_info(&mut account_info_iter)?;
+        let mut pool_state = Self::check_and_deserialize_pool_state(pool_account, program_id)?;
+        if pool_state.is_paused {
+            return Err(PoolError::PoolIsPaused.into());
+        }
+        if pool_state.is_sunset() {
+            return Err(PoolError::PoolIsSunset.into());
+        }
+        if pool_state.token_flags[input_index].input_disabled {
+            return Err(PoolError::TokenInputDisabled.with_context(format_args!("token index {}", input_index)));
+        }
+        if pool_state.token_flags[output_index].output_disabled {
+            return Err(PoolError::TokenOutputDisabled.with_context(format_args!("token index {}", output_index)));
+        }
+        let (input_participation, output_participation) = (
+            pool_state.token_participation[input_index],
+            pool_state.token_participation[output_index],
+        );
+        if !input_participation.allows_input(false) {
+            return Err(validation::participation_error(input_participation)
+                .with_context(format_args!("token index {}", input_index)));
+        }
+        if !output_participation.allows_output() {
+            return Err(validation::participation_error(output_participation)
+                .with_context(format_args!("token index {}", output_index)));
+        }
+        if pool_state.depegged_tokens[input_index] || pool_state.depegged_tokens[output_index] {
+            return Err(PoolError::TokenDepegged.into());
+        }
+        if !pool_state.has_rfq_quoter() || quote.pool != *pool_account.key {
+            return Err(PoolError::InvalidQuoteSignature.into());
+        }
+        let current_ts = Self::get_current_ts()?;
+        Self::check_trading_schedule(&pool_state, current_ts)?;
+        if current_ts >= quote.expiry_ts {
+            return Err(PoolError::QuoteExpired.into());
+        }
+        if quote.quote_id <= pool_state.rfq_last_quote_id {
+            return Err(PoolError::QuoteAlreadyUsed.into());
+        }
+
+        let pool_authority_account = next_account_info(&mut account_info_iter)?;
+        if *pool_authority_account.key != Self::get_pool_authority(pool_account.key, pool_state.nonce, program_id)? {
+            return Err(PoolError::InvalidPoolAuthorityAccount.into());
+        }
+        let pool_token_accounts: [_; TOKEN_COUNT] = create_result_array(|i| -> Result<_, ProgramError> {
+            let pool_token_account = next_account_info(&mut account_info_iter)?;
+            if *pool_token_account.key != pool_state.token_keys[i] {
+                return Err(PoolError::PoolTokenAccountExpected.with_context(format_args!("token index {}", i)));
+            }
+            Ok(pool_token_account)
+        })?;
+        let user_authority_account = next_account_info(&mut account_info_iter)?;
+        if !user_authority_account.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        if *user_authority_account.key != quote.user {
+            return Err(PoolError::InvalidQuoteSignature.into());
+        }
+        let user_input_account = next_account_info(&mut account_info_iter)?;
+        let user_output_account = next_account_info(&mut account_info_iter)?;
+        let token_program_account = next_account_info(&mut account_info_iter)?;
+        if *token_program_account.key != spl_token::id() {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let instructions_account = next_account_info(&mut account_info_iter)?;
+        let rates = Self::load_rates(&pool_state, &mut account_info_iter)?;
+        if pool_state.restricts_callers() {
+            Self::check_caller(&pool_state, program_id, instructions_account)?;
+        }
+        if pool_state.gates_users()
+            && !Self::passes_gate(
+                pool_state.user_gate,
+                find_allowed_user_address,
+                pool_account,
+                user_authority_account,
+                next_account_info(&mut account_info_iter)?,
+                program_id,
+            )
+        {
+            return Err(PoolError::UserNotAllowed.into());
+        }
+        //the compliance program sees the quote as the swap it executes, an exact input swap whose minimum output is
+        // the quoted output
+        if pool_state.has_compliance_program() {
+            let mut exact_input_amounts = [0; TOKEN_COUNT];
+            exact_input_amounts[input_index] = quote.input_amount;
+            Self::check_compliance(
+                &pool_state,
+                pool_account,
+                user_authority_account,
+                &mut account_info_iter,
+                DeFiInstruction::SwapExactInput {
+                    exact_input_amounts,
+                    output_token_index: quote.output_token_index,
+                    minimum_output_amount: quote.output_amount,
+                },
+            )?;
+        }
 
-                if pool_state.fee_transition_ts > Self::get_current_ts()? {
-                    return Err(PoolError::InsufficientDelay.into());
-                }
+        //the quoter's signature is verified by the ed25519 program in the instruction right before this one, which
+        // fails the whole transaction if the signature doesn't match its key and message
+        let current_index = load_current_index_checked(instructions_account)? as usize;
+        let signature_ix = match current_index.checked_sub(1) {
+            Some(index) => load_instruction_at_checked(index, instructions_account)?,
+            None => return Err(PoolError::InvalidQuoteSignature.into()),
+        };
+        if signature_ix.program_id != ed25519_program::id()
+            || parse_ed25519_ix(&signature_ix.data) != Some((pool_state.rfq_quoter_key, &quote.message()[..]))
+        {
+            return Err(PoolError::InvalidQuoteSignature.into());
+        }
 
-                if pool_state.prepared_governance_fee.get() > DecT::from(0)
-                    && pool_state.governance_fee_key == Pubkey::default()
-                {
-                    return Err(PoolError::InvalidGovernanceFeeAccount.into());
-                }
+        let pool_balances: [_; TOKEN_COUNT] = create_result_array(|i| -> Result<_, ProgramError> {
+            Ok(Self::check_program_owner_and_unpack::<TokenState>(pool_token_accounts[i])?.amount)
+        })?;
+        if quote.output_amount >= pool_balances[output_index] {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+        //the quote bypasses the invariant, so its price is bounded by the internal oracle's instead
+        if pool_state.ema_ts == 0 {
+            return Err(PoolError::OraclePriceUnavailable.into());
+        }
+        let input_amounts = create_array(|i| if i == input_index { quote.input_amount } else { 0 });
+        let output_amounts = create_array(|i| if i == output_index { quote.output_amount } else { 0 });
+        oracle::check_swap_deviation(
+            &input_amounts,
+            &output_amounts,
+            &pool_state.token_decimal_equalizers,
+            &oracle::ema_oracle_prices(&pool_state.ema_prices, &rates),
+            pool_state.rfq_max_deviation.get(),
+        )
+        .or(Err(PoolError::QuoteDeviationExceeded))?;
 
-                pool_state.lp_fee = pool_state.prepared_lp_fee;
-                pool_state.governance_fee = pool_state.prepared_governance_fee;
-                pool_state.prepared_lp_fee = PoolFee::default();
-                pool_state.prepared_governance_fee = PoolFee::default();
-                pool_state.fee_transition_ts = 0;
-            }
+        Self::check_user_debit(
+            user_input_account,
+            &pool_state.token_mint_keys[input_index],
+            quote.input_amount,
+            user_authority_account.key,
+            format_args!("input token index {}", input_index),
+        )?;
+        Self::transfer_token(
+            user_input_account,
+            pool_token_accounts[input_index],
+            quote.input_amount,
+            user_authority_account,
+            token_program_account,
+        )?;
+        Self::transfer_pool_token(
+            pool_token_accounts[output_index],
+            user_output_account,
+            quote.output_amount,
+            pool_authority_account,
+            token_program_account,
+            pool_account,
+            pool_state.nonce,
+        )?;
 
-            GovernanceInstruction::PrepareGovernanceTransition {
-                upcoming_governance_key,
-            } => {
-                pool_state.prepared_governance_key = upcoming_governance_key;
-                pool_state.governance_transition_ts = Self::get_current_ts()? + ENACT_DELAY;
-            }
+        let latest_balances: [_; TOKEN_COUNT] = create_result_array(|i| -> Result<_, ProgramError> {
+            Ok(Self::check_program_owner_and_unpack::<TokenState>(pool_token_accounts[i])?.amount)
+        })?;
+        let amp_factor = pool_state.effective_amp_factor(current_ts);
+        let invariant_balances = pool_state.invariant_balances(&latest_balances);
+        let latest_depth = quote::depth(
+            &pool_state,
+            &create_array(|i| {
+                quote::to_rated(
+                    quote::to_equalized(invariant_balances[i], pool_state.token_decimal_equalizers[i]),
+                    rates[i],
+                    false,
+                )
+            }),
+            amp_factor,
+        )?
+        .as_u128();
+        Self::update_internal_oracle(&mut pool_state, &latest_balances, &rates, amp_factor, latest_depth)?;
+        pool_state.previous_depth = latest_depth;
+        pool_state.depth_rates = rates;
+        pool_state.rfq_last_quote_id = quote.quote_id;
+        Self::serialize_pool(&pool_state, pool_account)?;
+        msg!(
+            "rfq quote {} executed: {} of token {} for {} of token {}",
+            quote.quote_id,
+            quote.input_amount,
+            input_index,
+            quote.output_amount,
+            output_index
+        );
+        Ok(())
+    }
 
-            GovernanceInstruction::EnactGovernanceTransition {} => {
-                if pool_state.governance_transition_ts == 0 {
-                    return Err(PoolError::InvalidEnact.into());
-                }
+    fn check_and_deserialize_session(
+        session_account: &AccountInfo,
+        session_key_account: &AccountInfo,
+        pool_account: &AccountInfo,
+        user_account: &AccountInfo,
+        program_id: &Pubkey,
+    ) -> Result<(Session<TOKEN_COUNT>, u8), ProgramError> {
+        let (session_address, session_bump) = find_session_address(program_id, pool_account.key, user_account.key);
+        if *session_account.key != session_address {
+            return Err(PoolError::NonCanonicalAddress.into());
+        }
+        if session_account.owner != program_id || session_account.lamports() == 0 {
+            return Err(ProgramError::UninitializedAccount);
+        }
+        let session = Session::<TOKEN_COUNT>::deserialize(&mut &**session_account.data.borrow())?;
+        if !session_key_account.is_signer || *session_key_account.key != session.session_key {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        if Self::get_current_ts()? >= session.expiry_ts {
+            return Err(PoolError::SessionExpired.into());
+        }
+        Ok((session, session_bump))
+    }
 
-                if pool_state.governance_transition_ts > Self::get_current_ts()? {
-                    return Err(PoolError::InsufficientDelay.into());
-                }
+    //the launch allocation of `owner_account`, which CreateLaunchAllocation has to have created
+    fn check_and_deserialize_launch_allocation(
+        launch_allocation_account: &AccountInfo,
+        pool_account: &AccountInfo,
+        owner_account: &AccountInfo,
+        program_id: &Pubkey,
+    ) -> Result<LaunchAllocation, ProgramError> {
+        if *launch_allocation_account.key
+            != find_launch_allocation_address(program_id, pool_account.key, owner_account.key).0
+        {
+            return Err(PoolError::NonCanonicalAddress.into());
+        }
+        if launch_allocation_account.owner != program_id {
+            return Err(ProgramError::UninitializedAccount);
+        }
+        Ok(LaunchAllocation::deserialize(
+            &mut &**launch_allocation_account.data.borrow(),
+        )?)
+    }
 
-                pool_state.governance_key = pool_state.prepared_governance_key;
-                pool_state.prepared_governance_key = Pubkey::default();
-                pool_state.governance_transition_ts = 0;
-            }
+    //the lock of `owner_account`, who has to sign
+    fn check_and_deserialize_lock(
+        lock_account: &AccountInfo,
+        pool_account: &AccountInfo,
+        owner_account: &AccountInfo,
+        program_id: &Pubkey,
+    ) -> Result<LpLock, ProgramError> {
+        if !owner_account.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        if *lock_account.key != find_lock_address(program_id, pool_account.key, owner_account.key).0 {
+            return Err(PoolError::NonCanonicalAddress.into());
+        }
+        if lock_account.owner != program_id {
+            return Err(ProgramError::UninitializedAccount);
+        }
+        Ok(LpLock::deserialize(&mut &**lock_account.data.borrow())?)
+    }
 
-            GovernanceInstruction::ChangeGovernanceFeeAccount { governance_fee_key } => {
-                if governance_fee_key != Pubkey::default() {
-                    let governance_fee_account = next_account_info(account_info_iter)?;
-                    if *governance_fee_account.key != governance_fee_key {
-                        return Err(PoolError::InvalidGovernanceFeeAccount.into());
-                    }
+    //the gauge of `pool_account`, which has to exist (see SetGaugeReward)
+    fn check_and_deserialize_gauge(
+        gauge_account: &AccountInfo,
+        pool_account: &AccountInfo,
+        program_id: &Pubkey,
+    ) -> Result<Gauge, ProgramError> {
+        if *gauge_account.key != find_gauge_address(program_id, pool_account.key).0 {
+            return Err(PoolError::NonCanonicalAddress.into());
+        }
+        if gauge_account.owner != program_id {
+            return Err(PoolError::InvalidGauge.with_context("the pool has no gauge"));
+        }
+        Ok(Gauge::deserialize(&mut &**gauge_account.data.borrow())?)
+    }
 
-                    let governance_fee_state =
-                        Self::check_program_owner_and_unpack::<TokenState>(governance_fee_account)?;
-                    if governance_fee_state.mint != pool_state.lp_mint_key {
-                        return Err(TokenError::MintMismatch.into());
-                    }
-                } else if pool_state.governance_fee.get() != DecT::from(0) {
-                    return Err(PoolError::InvalidGovernanceFeeAccount.into());
-                }
+    //the gauge stake of `owner_account`, who has to sign
+    fn check_and_deserialize_stake(
+        stake_account: &AccountInfo,
+        pool_account: &AccountInfo,
+        owner_account: &AccountInfo,
+        program_id: &Pubkey,
+    ) -> Result<GaugeStake, ProgramError> {
+        if !owner_account.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        if *stake_account.key != find_gauge_stake_address(program_id, pool_account.key, owner_account.key).0 {
+            return Err(PoolError::NonCanonicalAddress.into());
+        }
+        if stake_account.owner != program_id {
+            return Err(ProgramError::UninitializedAccount);
+        }
+        Ok(GaugeStake::deserialize(&mut &**stake_account.data.borrow())?)
+    }
 
-                pool_state.governance_fee_key = governance_fee_key;
-            }
+    //a rebate campaign of the pool. its address has to be checked along with its owner, since the program's other
+    // accounts that start with the pool's key would deserialize as well
+    fn check_and_deserialize_rebate_campaign(
+        campaign_account: &AccountInfo,
+        pool_account: &AccountInfo,
+        program_id: &Pubkey,
+    ) -> Result<RebateCampaign, ProgramError> {
+        if campaign_account.owner != program_id {
+            return Err(PoolError::InvalidRebateCampaign.with_context("no such campaign"));
+        }
+        let campaign = RebateCampaign::deserialize(&mut &**campaign_account.data.borrow())
+            .or(Err(PoolError::InvalidRebateCampaign))?;
+        if campaign.pool != *pool_account.key
+            || *campaign_account.key != find_rebate_campaign_address(program_id, pool_account.key, campaign.id).0
+        {
+            return Err(PoolError::InvalidRebateCampaign.with_context("not a campaign of the pool"));
+        }
+        Ok(campaign)
+    }
 
-            GovernanceInstruction::AdjustAmpFactor {
-                target_ts,
-                target_value,
-            } => {
-                pool_state
-                    .amp_factor
-                    .set_target(Self::get_current_ts()?, target_value, target_ts)?;
-            }
+    //mints the lock's rewards since they were last settled to `recipient_account`
+    fn settle_lock_rewards<'a>(
+        pool_state: &mut PoolState<TOKEN_COUNT>,
+        lock: &mut LpLock,
+        lp_mint_account: &AccountInfo<'a>,
+        recipient_account: &AccountInfo<'a>,
+        pool_authority_account: &AccountInfo<'a>,
+        token_program_account: &AccountInfo<'a>,
+        pool_account: &AccountInfo,
+    ) -> ProgramResult {
+        //rounding leaves the sum of all locks' rewards at most at what was set aside for them
+        let rewards = min(
+            lock.pending_rewards(pool_state.lock_reward_growth),
+            pool_state.lock_rewards_unminted,
+        );
+        lock.reward_growth_checkpoint = pool_state.lock_reward_growth;
+        if rewards > 0 {
+            pool_state.lock_rewards_unminted -= rewards;
+            Self::mint_token(
+                lp_mint_account,
+                recipient_account,
+                rewards,
+                pool_authority_account,
+                token_program_account,
+                pool_account,
+                pool_state.nonce,
+            )?;
+        }
+        Ok(())
+    }
 
-            GovernanceInstruction::SetPaused { paused } => {
-                pool_state.is_paused = paused;
-            }
+    //transfer + allocate + assign rather than create_account, which fails if somebody already sent lamports to the
+    // address
+    fn create_program_address_account<'a>(
+        payer_account: &AccountInfo<'a>,
+        account: &AccountInfo<'a>,
+        system_program_account: &AccountInfo<'a>,
+        len: usize,
+        owner: &Pubkey,
+        seeds: &[&[u8]],
+    ) -> ProgramResult {
+        let required_lamports = Rent::get()?.minimum_balance(len).saturating_sub(account.lamports());
+        let account_infos = [payer_account.clone(), account.clone(), system_program_account.clone()];
+        if required_lamports > 0 {
+            invoke(
+                &system_instruction::transfer(payer_account.key, account.key, required_lamports),
+                &account_infos,
+            )?;
         }
+        invoke_signed(
+            &system_instruction::allocate(account.key, len as u64),
+            &account_infos,
+            &[seeds],
+        )?;
+        invoke_signed(
+            &system_instruction::assign(account.key, owner),
+            &account_infos,
+            &[seeds],
+        )
+    }
 
-        Self::serialize_pool(&pool_state, pool_account)
+    //invokes a swap of this program with the pool accounts of `leg` and the given user accounts
+    fn invoke_route_leg<'a>(
+        program_account: &AccountInfo<'a>,
+        leg: &[AccountInfo<'a>],
+        authority_account: &AccountInfo<'a>,
+        token_program_account: &AccountInfo<'a>,
+        user_token_accounts: [&AccountInfo<'a>; TOKEN_COUNT],
+        defi_instruction: DeFiInstruction<TOKEN_COUNT>,
+        signer_seeds: &[&[&[u8]]],
+    ) -> ProgramResult {
+        let (pool_accounts, swap_accounts) = leg.split_at(4 + TOKEN_COUNT);
+        let mut account_metas = vec![
+            AccountMeta::new(*pool_accounts[0].key, false),
+            AccountMeta::new_readonly(*pool_accounts[1].key, false),
+        ];
+        for pool_account in pool_accounts[2..].iter() {
+            account_metas.push(AccountMeta::new(*pool_account.key, false));
+        }
+        account_metas.push(AccountMeta::new_readonly(*authority_account.key, true));
+        for user_token_account in user_token_accounts.iter() {
+            account_metas.push(AccountMeta::new(*user_token_account.key, false));
+        }
+        account_metas.push(AccountMeta::new_readonly(*token_program_account.key, false));
+        for swap_account in swap_accounts.iter() {
+            account_metas.push(AccountMeta::new_readonly(*swap_account.key, false));
+        }
+
+        let mut account_infos = pool_accounts.to_vec();
+        account_infos.push(authority_account.clone());
+        account_infos.extend(user_token_accounts.iter().map(|&account| account.clone()));
+        account_infos.push(token_program_account.clone());
+        account_infos.extend_from_slice(swap_accounts);
+        account_infos.push(program_account.clone());
+        let ix = Instruction {
+            program_id: *program_account.key,
+            accounts: account_metas,
+            data: PoolInstruction::DeFiInstruction(defi_instruction).try_to_vec()?,
+        };
+        invoke_signed(&ix, &account_infos, signer_seeds)
     }
 
-    // -------------------------------- Helper Functions --------------------------------
+    //splits a RoutedSwap leg's accounts off `accounts`. a pool's accounts are followed by the oracle and rate accounts
+    // its swaps take, so how many accounts a leg has depends on the pool's state
+    fn split_route_leg<'a, 'b>(
+        accounts: &'b [AccountInfo<'a>],
+        intermediate_index: usize,
+        intermediate_mint: &Pubkey,
+        program_id: &Pubkey,
+    ) -> Result<(&'b [AccountInfo<'a>], &'b [AccountInfo<'a>]), ProgramError> {
+        let pool_account = accounts.first().ok_or(ProgramError::NotEnoughAccountKeys)?;
+        let pool_state = Self::check_and_deserialize_pool_state(pool_account, program_id)?;
+        if pool_state.token_mint_keys[intermediate_index] != *intermediate_mint {
+            return Err(PoolError::InvalidMintAccount.into());
+        }
+        let oracle_count = if pool_state.is_oracle_guarded() { TOKEN_COUNT } else { 0 };
+        let leg_len = 4
+            + TOKEN_COUNT
+            + pool_state.lp_non_transferable as usize
+            + oracle_count
+            + pool_state.rate_account_keys().len()
+            + pool_state.restricts_callers() as usize
+            + pool_state.gates_users() as usize;
+        if accounts.len() < leg_len {
+            return Err(ProgramError::NotEnoughAccountKeys);
+        }
+        Ok(accounts.split_at(leg_len))
+    }
+
+    //the exchange rates of the pool's tokens, read from the accounts that follow for sources that have one
+    fn load_rates(
+        pool_state: &PoolState<TOKEN_COUNT>,
+        account_info_iter: &mut std::slice::Iter<AccountInfo>,
+    ) -> Result<[u64; TOKEN_COUNT], ProgramError> {
+        if !pool_state.rate_sources.iter().any(|source| *source != RateSource::None)
+            && pool_state.invariant_kind != InvariantKind::Hybrid
+        {
+            return Ok([RATE_ONE; TOKEN_COUNT]);
+        }
+        let clock = Clock::get()?;
+        create_result_array(|i| -> Result<_, ProgramError> {
+            let source = pool_state.rate_sources[i];
+            let token_mint = &pool_state.token_mint_keys[i];
+            let limits = &pool_state.oracle_limits[i];
+            //a hybrid pool's volatile token trades at the pool's price, Repeg is what follows its oracle
+            if let Some(rate) = pool_state.scaled_rate(i) {
+                if source.has_account() && *next_account_info(account_info_iter)?.key != pool_state.rate_keys[i] {
+                    return Err(PoolError::InvalidRateAccount.with_context(format_args!("token index {}", i)));
+                }
+                return Ok(rate);
+            }
+            if !source.has_account() {
+                return Ok(oracle::load_rate(
+                    source,
+                    &Pubkey::default(),
+                    &[],
+                    token_mint,
+                    &clock,
+                    limits,
+                )?);
+            }
+            let rate_account = next_account_info(account_info_iter)?;
+            if *rate_account.key != pool_state.rate_keys[i] {
+                return Err(PoolError::InvalidRateAccount.with_context(format_args!("token index {}", i)));
+            }
+            Ok(oracle::load_rate(
+                source,
+                rate_account.owner,
+                &rate_account.data.borrow(),
+                token_mint,
+                &clock,
+                limits,
+            )?)
+        })
+    }
+
+    //moves the internal oracle's average towards the prices since the previous update and records the prices at the
+    // balances after this instruction
+    fn update_internal_oracle(
+        pool_state: &mut PoolState<TOKEN_COUNT>,
+        latest_balances: &[AtomicT; TOKEN_COUNT],
+        rates: &[u64; TOKEN_COUNT],
+        amp_factor: DecT,
+        latest_depth: u128,
+    ) -> ProgramResult {
+        let latest_balances = pool_state.invariant_balances(latest_balances);
+        let balances = create_array(|i| {
+            let equalized = quote::to_equalized(latest_balances[i], pool_state.token_decimal_equalizers[i]);
+            quote::to_rated(equalized, rates[i], false).as_u128()
+        });
+        let prices = match quote::marginal_prices(pool_state, &balances, amp_factor, latest_depth) {
+            Some(prices) => prices,
+            None => return Ok(()),
+        };
+        let current_ts = Self::get_current_ts()?;
+        if pool_state.ema_ts == 0 {
+            pool_state.ema_prices = prices;
+        } else {
+            oracle::update_ema(
+                &mut pool_state.ema_prices,
+                &pool_state.last_prices,
+                current_ts - pool_state.ema_ts,
+            );
+        }
+        pool_state.last_prices = prices;
+        pool_state.ema_ts = current_ts;
+        Ok(())
+    }
+
+    //depth per lp token, scaled by 10^-VIRTUAL_PRICE_EXPO
+    fn virtual_price(depth: u128, equalized_lp_supply: u128) -> Result<u64, ProgramError> {
+        let price = U256::from(depth) * U256::exp10((-VIRTUAL_PRICE_EXPO) as usize) / U256::from(equalized_lp_supply);
+        if price > U256::from(u64::MAX) {
+            return Err(ProgramError::ArithmeticOverflow);
+        }
+        Ok(price.as_u64())
+    }
 
     fn get_pool_authority(pool_key: &Pubkey, nonce: u8, program_id: &Pubkey) -> Result<Pubkey, ProgramError> {
         Pubkey::create_program_address(&[&pool_key.to_bytes(), &[nonce]], program_id)
@@ -760,6 +5628,58 @@ impl<const TOKEN_COUNT: usize> Processor<TOKEN_COUNT> {
         T::unpack(&account.data.borrow()).or(Err(ProgramError::InvalidAccountData))
     }
 
+    //lp mints and lp token accounts belong to Token-2022 if the pool's lp token is non-transferable
+    fn unpack_lp_mint(account: &AccountInfo) -> Result<MintState, ProgramError> {
+        if *account.owner == token_2022::id() {
+            return token_2022::unpack_mint(&account.data.borrow());
+        }
+        Self::check_program_owner_and_unpack(account)
+    }
+
+    fn unpack_lp_account(account: &AccountInfo) -> Result<TokenState, ProgramError> {
+        if *account.owner == token_2022::id() {
+            return token_2022::unpack_account(&account.data.borrow());
+        }
+        Self::check_program_owner_and_unpack(account)
+    }
+
+    //a failed transfer or burn aborts the whole transaction with the token program's error, which doesn't tell which
+    // of the instruction's accounts it was about, so debits of user accounts are checked up front instead
+    fn check_user_debit(
+        user_token_account: &AccountInfo,
+        mint: &Pubkey,
+        amount: AtomicT,
+        authority: &Pubkey,
+        account: impl std::fmt::Display,
+    ) -> ProgramResult {
+        let token_state = token_2022::unpack_account(&user_token_account.data.borrow())
+            .map_err(|_| PoolError::InvalidUserTokenAccount.with_context(&account))?;
+        if token_state.mint != *mint {
+            return Err(PoolError::InvalidUserTokenAccount.with_context(account));
+        }
+        if token_state.is_frozen() {
+            return Err(PoolError::UserTokenAccountFrozen.with_context(account));
+        }
+        if token_state.owner != *authority {
+            if token_state.delegate != COption::Some(*authority) {
+                return Err(PoolError::InvalidUserAuthority.with_context(account));
+            }
+            if token_state.delegated_amount < amount {
+                return Err(PoolError::InsufficientUserBalance.with_context(format_args!(
+                    "{}: delegated {}, needs {}",
+                    account, token_state.delegated_amount, amount
+                )));
+            }
+        }
+        if token_state.amount < amount {
+            return Err(PoolError::InsufficientUserBalance.with_context(format_args!(
+                "{}: balance {}, needs {}",
+                account, token_state.amount, amount
+            )));
+        }
+        Ok(())
+    }
+
     fn check_and_deserialize_pool_state(
         pool_account: &AccountInfo,
         program_id: &Pubkey,
@@ -768,7 +5688,26 @@ impl<const TOKEN_COUNT: usize> Processor<TOKEN_COUNT> {
             return Err(ProgramError::IllegalOwner);
         }
 
-        let pool_state = PoolState::<TOKEN_COUNT>::deserialize(&mut &**pool_account.data.try_borrow_mut().unwrap())?;
+        //the program owns only pool accounts of its own layouts, one that doesn't decode belongs to a pool of a
+        // different TOKEN_COUNT (of a program deployed from the same source) or has a layout the program doesn't know
+        let data = pool_account.try_borrow_data()?;
+        //an account that was allocated for a pool but not initialized yet has no layout version either
+        if data.iter().all(|byte| *byte == 0) {
+            return Err(ProgramError::UninitializedAccount);
+        }
+        let pool_state = match PoolState::<TOKEN_COUNT>::unpack(&data) {
+            Some(pool_state) => pool_state,
+            None => {
+                return Err(match pool_token_count(&data) {
+                    Some(token_count) if token_count != TOKEN_COUNT => PoolError::TokenCountMismatch.with_context(
+                        format_args!("pool of {} tokens, program of {}", token_count, TOKEN_COUNT),
+                    ),
+                    _ => {
+                        PoolError::InvalidPoolLayout.with_context(format_args!("pool account of {} bytes", data.len()))
+                    }
+                })
+            }
+        };
 
         if !pool_state.is_initialized() {
             return Err(ProgramError::UninitializedAccount);
@@ -778,8 +5717,20 @@ impl<const TOKEN_COUNT: usize> Processor<TOKEN_COUNT> {
     }
 
     fn serialize_pool(pool_state: &PoolState<TOKEN_COUNT>, pool_account: &AccountInfo) -> ProgramResult {
+        //accounts of the first layout can't grow, they take back its fields unless that would lose a later setting
+        if pool_state.layout_version == 0 {
+            if !pool_state.fits_layout_v0() {
+                return Err(PoolError::PoolLayoutOutdated.with_context("the pool's state account has the first layout"));
+            }
+            let mut data = pool_account.try_borrow_mut_data()?;
+            let len = data.len();
+            data.copy_from_slice(&pool_state.try_to_vec()?[..len]);
+            return Ok(());
+        }
+        //serializing into a fresh slice reference, writing through the account's own &mut [u8] would advance it
+        // and leave the account info with empty data (which the native test runtime then writes back)
         pool_state
-            .serialize(&mut *pool_account.data.try_borrow_mut().unwrap())
+            .serialize(&mut &mut pool_account.try_borrow_mut_data()?[..])
             .or(Err(ProgramError::AccountDataTooSmall))
     }
 
@@ -787,6 +5738,10 @@ impl<const TOKEN_COUNT: usize> Processor<TOKEN_COUNT> {
         governance_account: &AccountInfo,
         pool_state: &PoolState<TOKEN_COUNT>,
     ) -> ProgramResult {
+        if pool_state.is_governance_renounced() {
+            return Err(PoolError::GovernanceRenounced.into());
+        }
+
         if *governance_account.key != pool_state.governance_key {
             return Err(PoolError::InvalidGovernanceAccount.into());
         }
@@ -825,6 +5780,35 @@ impl<const TOKEN_COUNT: usize> Processor<TOKEN_COUNT> {
         )
     }
 
+    fn transfer_token_signed<'a>(
+        sender_account: &AccountInfo<'a>,
+        recipient_account: &AccountInfo<'a>,
+        amount: AtomicT,
+        authority_account: &AccountInfo<'a>,
+        token_program_account: &AccountInfo<'a>,
+        signer_seeds: &[&[u8]],
+    ) -> ProgramResult {
+        let transfer_ix = transfer(
+            token_program_account.key,
+            sender_account.key,
+            recipient_account.key,
+            authority_account.key,
+            &[],
+            amount,
+        )?;
+
+        invoke_signed(
+            &transfer_ix,
+            &[
+                sender_account.clone(),
+                recipient_account.clone(),
+                authority_account.clone(),
+                token_program_account.clone(),
+            ],
+            &[signer_seeds],
+        )
+    }
+
     fn transfer_pool_token<'a>(
         pool_token_account: &AccountInfo<'a>,
         recipient_account: &AccountInfo<'a>,
@@ -864,14 +5848,23 @@ impl<const TOKEN_COUNT: usize> Processor<TOKEN_COUNT> {
         pool_account: &AccountInfo,
         nonce: u8,
     ) -> ProgramResult {
-        let mint_ix = mint_to(
-            token_program_account.key,
-            lp_mint_account.key,
-            recipient_account.key,
-            pool_authority_account.key,
-            &[],
-            mint_amount,
-        )?;
+        let mint_ix = if *token_program_account.key == token_2022::id() {
+            token_2022::mint_to(
+                lp_mint_account.key,
+                recipient_account.key,
+                pool_authority_account.key,
+                mint_amount,
+            )
+        } else {
+            mint_to(
+                token_program_account.key,
+                lp_mint_account.key,
+                recipient_account.key,
+                pool_authority_account.key,
+                &[],
+                mint_amount,
+            )?
+        };
 
         invoke_signed(
             &mint_ix,
@@ -892,14 +5885,18 @@ impl<const TOKEN_COUNT: usize> Processor<TOKEN_COUNT> {
         lp_authority: &AccountInfo<'a>,
         token_program_account: &AccountInfo<'a>,
     ) -> Result<(), ProgramError> {
-        let burn_ix = burn(
-            token_program_account.key,
-            lp_account.key,
-            lp_mint_account.key,
-            lp_authority.key,
-            &[],
-            burn_amount,
-        )?;
+        let burn_ix = if *token_program_account.key == token_2022::id() {
+            token_2022::burn(lp_account.key, lp_mint_account.key, lp_authority.key, burn_amount)
+        } else {
+            burn(
+                token_program_account.key,
+                lp_account.key,
+                lp_mint_account.key,
+                lp_authority.key,
+                &[],
+                burn_amount,
+            )?
+        };
 
         invoke(
             &burn_ix,
@@ -914,7 +5911,9 @@ impl<const TOKEN_COUNT: usize> Processor<TOKEN_COUNT> {
 
     fn get_current_ts() -> Result<UnixTimestamp, ProgramError> {
         let current_ts = Clock::get()?.unix_timestamp;
-        assert!(current_ts > 0);
+        if current_ts <= 0 {
+            return Err(ProgramError::InvalidArgument);
+        }
         Ok(current_ts)
     }
 }
@@ -0,0 +1,655 @@
+//the amounts that defi instructions result in, computed from the pool's state exactly like the program does (including
+// the conversion to and from equalized amounts and its rounding), the processor executes instructions based on these
+// quotes, so off-chain users of this module always get the same result as the program
+
+use crate::{
+    common::create_array,
+    decimal::{DecimalU64, U256},
+    error::PoolError,
+    fixed_rate::{self, FixedRateInvariant},
+    invariant::{AmountT, Invariant, InvariantError},
+    oracle::{self, OraclePrice, RATE_ONE},
+    state::{InvariantKind, PoolState},
+    weighted::{self, WeightedInvariant},
+};
+use solana_program::msg;
+
+type AtomicT = u64;
+type DecT = DecimalU64;
+
+pub fn to_equalized(value: AtomicT, equalizer: u8) -> AmountT {
+    if equalizer > 0 {
+        AmountT::from(value) * AmountT::ten_to_the(equalizer)
+    } else {
+        AmountT::from(value)
+    }
+}
+
+//always round in the pool's favor: down for amounts the user receives, up for amounts the user pays. rounding half up
+// either way (as the program once did) let outputs exceed their exact value by up to half an atom, which dust-sized
+// instructions could repeat to draw value from the pool
+pub fn from_equalized(value: AmountT, equalizer: u8, round_up: bool) -> AtomicT {
+    if equalizer > 0 {
+        let divisor = AmountT::ten_to_the(equalizer);
+        let quotient = value / divisor;
+        if round_up && !(value % divisor).is_zero() {
+            (quotient + 1u64).as_u64()
+        } else {
+            quotient.as_u64()
+        }
+    } else {
+        value.as_u64()
+    }
+}
+
+//the lp token's equalizer is negative if its mint has more decimals than the pool's tokens, its amounts then are scaled
+// down into equalized units (see PoolState::lp_decimal_equalizer)
+pub fn to_equalized_lp(value: AtomicT, equalizer: i8, round_up: bool) -> AmountT {
+    if equalizer >= 0 {
+        to_equalized(value, equalizer as u8)
+    } else {
+        AmountT::from(from_equalized(AmountT::from(value), equalizer.unsigned_abs(), round_up))
+    }
+}
+
+pub fn from_equalized_lp(value: AmountT, equalizer: i8, round_up: bool) -> AtomicT {
+    if equalizer >= 0 {
+        from_equalized(value, equalizer as u8, round_up)
+    } else {
+        (value * AmountT::ten_to_the(equalizer.unsigned_abs())).as_u64()
+    }
+}
+
+//equalized amounts of a token with the given exchange rate in units of the asset that the pool's tokens track, which is
+// what the invariant of a rated pool operates on
+pub fn to_rated(value: AmountT, rate: u64, round_up: bool) -> AmountT {
+    if rate == RATE_ONE {
+        return value;
+    }
+    scale(value, rate, RATE_ONE, round_up)
+}
+
+pub fn from_rated(value: AmountT, rate: u64, round_up: bool) -> AmountT {
+    if rate == RATE_ONE {
+        return value;
+    }
+    scale(value, RATE_ONE, rate, round_up)
+}
+
+fn scale(value: AmountT, numerator: u64, denominator: u64, round_up: bool) -> AmountT {
+    let product = U256::from(value.as_u128()) * U256::from(numerator);
+    let mut quotient = product / U256::from(denominator);
+    if round_up && !(product % U256::from(denominator)).is_zero() {
+        quotient += U256::one();
+    }
+    AmountT::from(quotient.as_u128())
+}
+
+/// The depth (i.e. the total value in equalized units) of a pool with the given equalized (and rated) balances under
+/// the pool's invariant, `amp_factor` only applies to stable swap and hybrid pools, whose volatile token's rate is
+/// the pool's price for it (see `state::PriceScale`)
+pub fn depth<const TOKEN_COUNT: usize>(
+    pool_state: &PoolState<TOKEN_COUNT>,
+    balances: &[AmountT; TOKEN_COUNT],
+    amp_factor: DecT,
+) -> Result<AmountT, InvariantError> {
+    match pool_state.invariant_kind {
+        InvariantKind::StableSwap | InvariantKind::Hybrid => Invariant::<TOKEN_COUNT>::depth(balances, amp_factor),
+        InvariantKind::WeightedProduct => WeightedInvariant::<TOKEN_COUNT>::depth(balances, &pool_state.token_weights),
+        InvariantKind::FixedRate => FixedRateInvariant::<TOKEN_COUNT>::depth(balances),
+    }
+}
+
+/// The marginal prices of the pool's tokens at the given equalized (and rated) balances and depth under the pool's
+/// invariant (see `oracle::marginal_prices`, `weighted::marginal_prices` and `fixed_rate::marginal_prices`)
+pub fn marginal_prices<const TOKEN_COUNT: usize>(
+    pool_state: &PoolState<TOKEN_COUNT>,
+    balances: &[u128; TOKEN_COUNT],
+    amp_factor: DecT,
+    depth: u128,
+) -> Option<[u64; TOKEN_COUNT]> {
+    match pool_state.invariant_kind {
+        InvariantKind::StableSwap | InvariantKind::Hybrid => oracle::marginal_prices(balances, amp_factor, depth),
+        InvariantKind::WeightedProduct => weighted::marginal_prices(balances, &pool_state.token_weights),
+        InvariantKind::FixedRate => fixed_rate::marginal_prices(balances),
+    }
+}
+
+/// `pool_state.previous_depth` revalued from the rates it was recorded at (`pool_state.depth_rates`) to `rates`, i.e.
+/// scaled by how much the change of rates alone changes the depth of the (equalized, unrated) `balances`. The yield of
+/// a rated token (e.g. the shares of a rebasing token) thus doesn't show up as a change of the pool's depth
+pub fn rebased_previous_depth<const TOKEN_COUNT: usize>(
+    pool_state: &PoolState<TOKEN_COUNT>,
+    balances: &[AmountT; TOKEN_COUNT],
+    rates: &[u64; TOKEN_COUNT],
+    amp_factor: DecT,
+) -> AmountT {
+    let previous_depth = AmountT::from(pool_state.previous_depth);
+    if *rates == pool_state.depth_rates || previous_depth.is_zero() {
+        return previous_depth;
+    }
+    let depth_at = |rates: &[u64; TOKEN_COUNT]| {
+        depth(
+            pool_state,
+            &create_array(|i| to_rated(balances[i], rates[i], false)),
+            amp_factor,
+        )
+    };
+    match (depth_at(&pool_state.depth_rates), depth_at(rates)) {
+        (Ok(recorded_rates_depth), Ok(rates_depth)) if !recorded_rates_depth.is_zero() => {
+            let rebased = U256::from(pool_state.previous_depth) * U256::from(rates_depth.as_u128())
+                / U256::from(recorded_rates_depth.as_u128());
+            AmountT::from(rebased.as_u128())
+        }
+        //the quote fails on the same balances anyway
+        _ => previous_depth,
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Quote {
+    /// Lp tokens minted (add), tokens received (exact input swap, exact burn remove), tokens paid (exact output swap)
+    /// or lp tokens burned (exact output remove)
+    pub amount: AtomicT,
+    /// Lp tokens minted to the governance fee account
+    pub governance_mint_amount: AtomicT,
+    /// Lp tokens' worth of the lp fee, i.e. by how much the fee that stays in the pool raised the value of the lp
+    /// tokens, valued at their price after the instruction
+    pub lp_fee_amount: AtomicT,
+    /// The pool's depth after the instruction
+    pub latest_depth: u128,
+}
+
+/// Computes the quotes for a pool, inputs aren't validated, i.e. callers have to apply the processor's checks first
+/// (e.g. that output amounts are below the pool's balances)
+#[derive(Clone)]
+pub struct Quoter<'a, const TOKEN_COUNT: usize> {
+    pool_state: &'a PoolState<TOKEN_COUNT>,
+    pool_balances: [AmountT; TOKEN_COUNT],
+    lp_total_supply: AmountT,
+    previous_depth: AmountT,
+    amp_factor: DecT,
+    lp_fee: DecT,
+    governance_fee: DecT,
+    rates: [u64; TOKEN_COUNT],
+    oracle_guard: Option<([OraclePrice; TOKEN_COUNT], DecT)>,
+}
+
+impl<'a, const TOKEN_COUNT: usize> Quoter<'a, TOKEN_COUNT> {
+    /// `amp_factor` is the pool's amp factor at the time of execution (i.e.
+    /// `pool_state.effective_amp_factor(current_ts)`)
+    pub fn new(
+        pool_state: &'a PoolState<TOKEN_COUNT>,
+        pool_balances: &[AtomicT; TOKEN_COUNT],
+        lp_total_supply: AtomicT,
+        amp_factor: DecT,
+    ) -> Self {
+        Self {
+            pool_state,
+            pool_balances: {
+                let balances = pool_state.invariant_balances(pool_balances);
+                create_array(|i| to_equalized(balances[i], pool_state.token_decimal_equalizers[i]))
+            },
+            //rounding up the supply of an lp token with more decimals than the pool's tokens values each lp token a
+            // little lower, i.e. in the pool's favor, and never turns a pool with lp tokens into an empty one
+            lp_total_supply: to_equalized_lp(lp_total_supply, pool_state.lp_decimal_equalizer, true),
+            previous_depth: pool_state.previous_depth.into(),
+            amp_factor,
+            lp_fee: pool_state.lp_fee.get(),
+            governance_fee: pool_state.governance_fee.get(),
+            rates: [RATE_ONE; TOKEN_COUNT],
+            oracle_guard: None,
+        }
+    }
+
+    /// Applies the exchange rates of a rated pool (scaled by 10^RATE_DECIMALS, see `oracle::load_rate`)
+    pub fn with_rates(mut self, rates: &[u64; TOKEN_COUNT]) -> Self {
+        self.previous_depth = rebased_previous_depth(self.pool_state, &self.pool_balances, rates, self.amp_factor);
+        self.pool_balances = create_array(|i| to_rated(self.pool_balances[i], rates[i], false));
+        self.rates = *rates;
+        self
+    }
+
+    /// Fails swaps whose execution price deviates from the cross-rate of `oracle_prices` by more than `max_deviation`
+    /// (see `oracle::check_swap_deviation`), `None` for pools without a guard or whose fallback ignores it (see
+    /// `oracle::guard_prices`)
+    pub fn with_oracle_guard(
+        mut self,
+        oracle_prices: Option<&[OraclePrice; TOKEN_COUNT]>,
+        max_deviation: DecT,
+    ) -> Self {
+        self.oracle_guard = oracle_prices.map(|prices| (*prices, max_deviation));
+        self
+    }
+
+    /// Quotes as if the pool charged no fees (the difference to the actual quote is the fee the user pays), such
+    /// hypothetical swaps aren't checked against the oracle guard
+    pub fn without_fees(mut self) -> Self {
+        self.lp_fee = DecT::from(0);
+        self.governance_fee = DecT::from(0);
+        self.oracle_guard = None;
+        self
+    }
+
+    pub fn add(&self, input_amounts: &[AtomicT; TOKEN_COUNT]) -> Result<Quote, PoolError> {
+        let (lp_fee, governance_fee) = self.invariant_fees();
+        let input_amounts = self.equalize(input_amounts, false);
+        let result = match self.pool_state.invariant_kind {
+            InvariantKind::StableSwap | InvariantKind::Hybrid => Invariant::<TOKEN_COUNT>::add(
+                &input_amounts,
+                &self.pool_balances,
+                self.amp_factor,
+                lp_fee,
+                governance_fee,
+                self.lp_total_supply,
+                self.previous_depth,
+            ),
+            InvariantKind::WeightedProduct => WeightedInvariant::<TOKEN_COUNT>::add(
+                &input_amounts,
+                &self.pool_balances,
+                &self.pool_state.token_weights,
+                lp_fee,
+                governance_fee,
+                self.lp_total_supply,
+            ),
+            InvariantKind::FixedRate => FixedRateInvariant::<TOKEN_COUNT>::add(
+                &input_amounts,
+                &self.pool_balances,
+                lp_fee,
+                governance_fee,
+                self.lp_total_supply,
+            ),
+        }
+        .map_err(InvariantError::logged)?;
+        self.check_price_band(create_array(|i| self.pool_balances[i] + input_amounts[i]), result.2)?;
+        Ok(self.quote(result, self.pool_state.lp_decimal_equalizer, RATE_ONE, false))
+    }
+
+    pub fn swap_exact_input(
+        &self,
+        exact_input_amounts: &[AtomicT; TOKEN_COUNT],
+        output_token_index: usize,
+    ) -> Result<Quote, PoolError> {
+        let (lp_fee, governance_fee) = self.invariant_fees();
+        let input_amounts = self.equalize(exact_input_amounts, false);
+        let result = match self.pool_state.invariant_kind {
+            InvariantKind::StableSwap | InvariantKind::Hybrid => Invariant::<TOKEN_COUNT>::swap_exact_input(
+                &input_amounts,
+                output_token_index,
+                &self.pool_balances,
+                self.amp_factor,
+                lp_fee,
+                governance_fee,
+                self.lp_total_supply,
+                self.previous_depth,
+            ),
+            InvariantKind::WeightedProduct => WeightedInvariant::<TOKEN_COUNT>::swap_exact_input(
+                &input_amounts,
+                output_token_index,
+                &self.pool_balances,
+                &self.pool_state.token_weights,
+                lp_fee,
+                governance_fee,
+                self.lp_total_supply,
+            ),
+            InvariantKind::FixedRate => FixedRateInvariant::<TOKEN_COUNT>::swap_exact_input(
+                &input_amounts,
+                output_token_index,
+                &self.pool_balances,
+                lp_fee,
+                governance_fee,
+                self.lp_total_supply,
+            ),
+        }
+        .map_err(InvariantError::logged)?;
+        let mut latest_balances = create_array(|i| self.pool_balances[i] + input_amounts[i]);
+        latest_balances[output_token_index] = latest_balances[output_token_index].saturating_sub(result.0);
+        self.check_price_band(latest_balances, result.2)?;
+        let quote = self.quote(
+            result,
+            self.pool_state.token_decimal_equalizers[output_token_index] as i8,
+            self.rates[output_token_index],
+            false,
+        );
+        let mut output_amounts = [0; TOKEN_COUNT];
+        output_amounts[output_token_index] = quote.amount;
+        self.check_oracle_deviation(exact_input_amounts, &output_amounts)?;
+        Ok(quote)
+    }
+
+    pub fn swap_exact_output(
+        &self,
+        input_token_index: usize,
+        exact_output_amounts: &[AtomicT; TOKEN_COUNT],
+    ) -> Result<Quote, PoolError> {
+        let (lp_fee, governance_fee) = self.invariant_fees();
+        let output_amounts = self.equalize(exact_output_amounts, true);
+        let result = match self.pool_state.invariant_kind {
+            InvariantKind::StableSwap | InvariantKind::Hybrid => Invariant::<TOKEN_COUNT>::swap_exact_output(
+                input_token_index,
+                &output_amounts,
+                &self.pool_balances,
+                self.amp_factor,
+                lp_fee,
+                governance_fee,
+                self.lp_total_supply,
+                self.previous_depth,
+            ),
+            InvariantKind::WeightedProduct => WeightedInvariant::<TOKEN_COUNT>::swap_exact_output(
+                input_token_index,
+                &output_amounts,
+                &self.pool_balances,
+                &self.pool_state.token_weights,
+                lp_fee,
+                governance_fee,
+                self.lp_total_supply,
+            ),
+            InvariantKind::FixedRate => FixedRateInvariant::<TOKEN_COUNT>::swap_exact_output(
+                input_token_index,
+                &output_amounts,
+                &self.pool_balances,
+                lp_fee,
+                governance_fee,
+                self.lp_total_supply,
+            ),
+        }
+        .map_err(InvariantError::logged)?;
+        let mut latest_balances = create_array(|i| self.pool_balances[i].saturating_sub(output_amounts[i]));
+        latest_balances[input_token_index] = latest_balances[input_token_index] + result.0;
+        self.check_price_band(latest_balances, result.2)?;
+        let quote = self.quote(
+            result,
+            self.pool_state.token_decimal_equalizers[input_token_index] as i8,
+            self.rates[input_token_index],
+            true,
+        );
+        let mut input_amounts = [0; TOKEN_COUNT];
+        input_amounts[input_token_index] = quote.amount;
+        self.check_oracle_deviation(&input_amounts, exact_output_amounts)?;
+        Ok(quote)
+    }
+
+    pub fn remove_exact_burn(&self, exact_burn_amount: AtomicT, output_token_index: usize) -> Result<Quote, PoolError> {
+        let (lp_fee, governance_fee) = self.invariant_fees();
+        let burn_amount = to_equalized_lp(exact_burn_amount, self.pool_state.lp_decimal_equalizer, false);
+        let result = match self.pool_state.invariant_kind {
+            InvariantKind::StableSwap | InvariantKind::Hybrid => Invariant::<TOKEN_COUNT>::remove_exact_burn(
+                burn_amount,
+                output_token_index,
+                &self.pool_balances,
+                self.amp_factor,
+                lp_fee,
+                governance_fee,
+                self.lp_total_supply,
+                self.previous_depth,
+            ),
+            InvariantKind::WeightedProduct => WeightedInvariant::<TOKEN_COUNT>::remove_exact_burn(
+                burn_amount,
+                output_token_index,
+                &self.pool_balances,
+                &self.pool_state.token_weights,
+                lp_fee,
+                governance_fee,
+                self.lp_total_supply,
+            ),
+            InvariantKind::FixedRate => FixedRateInvariant::<TOKEN_COUNT>::remove_exact_burn(
+                burn_amount,
+                output_token_index,
+                &self.pool_balances,
+                lp_fee,
+                governance_fee,
+                self.lp_total_supply,
+            ),
+        }
+        .map_err(InvariantError::logged)?;
+        let mut latest_balances = self.pool_balances;
+        latest_balances[output_token_index] = latest_balances[output_token_index].saturating_sub(result.0);
+        self.check_price_band(latest_balances, result.2)?;
+        Ok(self.quote(
+            result,
+            self.pool_state.token_decimal_equalizers[output_token_index] as i8,
+            self.rates[output_token_index],
+            false,
+        ))
+    }
+
+    pub fn remove_exact_output(&self, exact_output_amounts: &[AtomicT; TOKEN_COUNT]) -> Result<Quote, PoolError> {
+        let (lp_fee, governance_fee) = self.invariant_fees();
+        let output_amounts = self.equalize(exact_output_amounts, true);
+        let result = match self.pool_state.invariant_kind {
+            InvariantKind::StableSwap | InvariantKind::Hybrid => Invariant::<TOKEN_COUNT>::remove_exact_output(
+                &output_amounts,
+                &self.pool_balances,
+                self.amp_factor,
+                lp_fee,
+                governance_fee,
+                self.lp_total_supply,
+                self.previous_depth,
+            ),
+            InvariantKind::WeightedProduct => WeightedInvariant::<TOKEN_COUNT>::remove_exact_output(
+                &output_amounts,
+                &self.pool_balances,
+                &self.pool_state.token_weights,
+                lp_fee,
+                governance_fee,
+                self.lp_total_supply,
+            ),
+            InvariantKind::FixedRate => FixedRateInvariant::<TOKEN_COUNT>::remove_exact_output(
+                &output_amounts,
+                &self.pool_balances,
+                lp_fee,
+                governance_fee,
+                self.lp_total_supply,
+            ),
+        }
+        .map_err(InvariantError::logged)?;
+        self.check_price_band(
+            create_array(|i| self.pool_balances[i].saturating_sub(output_amounts[i])),
+            result.2,
+        )?;
+        Ok(self.quote(result, self.pool_state.lp_decimal_equalizer, RATE_ONE, true))
+    }
+
+    /// Quotes the pool's peg keeper providing (`provide`) resp. absorbing `amount` of its token (see
+    /// `state::PegKeeper`) at the invariant's price without fees, the quote's amount is the lp tokens that its credit
+    /// gains resp. loses. Fails with `PegCreditPastPeg` if the pool's (rated) balance of the token would end up above
+    /// resp. below the average of the other tokens' balances, i.e. if the credit would push the token past its peg
+    pub fn peg_credit(&self, amount: AtomicT, provide: bool) -> Result<Quote, PoolError> {
+        let token_index = self.pool_state.peg_keeper.token_index as usize;
+        let amounts = create_array(|i| if i == token_index { amount } else { 0 });
+        let quoter = self.clone().without_fees();
+        let quote = if provide {
+            quoter.add(&amounts)?
+        } else {
+            quoter.remove_exact_output(&amounts)?
+        };
+        let amount = self.equalize(&amounts, !provide)[token_index];
+        let balance = if provide {
+            self.pool_balances[token_index] + amount
+        } else {
+            self.pool_balances[token_index].saturating_sub(amount)
+        };
+        let others = (0..TOKEN_COUNT)
+            .filter(|&i| i != token_index)
+            .fold(AmountT::from(0u64), |sum, i| sum + self.pool_balances[i]);
+        let scaled_balance = balance * AmountT::from((TOKEN_COUNT - 1) as u64);
+        if (provide && scaled_balance > others) || (!provide && scaled_balance < others) {
+            return Err(PoolError::PegCreditPastPeg);
+        }
+        Ok(quote)
+    }
+
+    fn check_oracle_deviation(
+        &self,
+        input_amounts: &[AtomicT; TOKEN_COUNT],
+        output_amounts: &[AtomicT; TOKEN_COUNT],
+    ) -> Result<(), PoolError> {
+        match &self.oracle_guard {
+            Some((oracle_prices, max_deviation)) => oracle::check_swap_deviation(
+                input_amounts,
+                output_amounts,
+                &self.pool_state.token_decimal_equalizers,
+                oracle_prices,
+                *max_deviation,
+            ),
+            None => Ok(()),
+        }
+    }
+
+    //fails instructions that leave the pool's price outside its price band (see PriceBand) unless they move it towards
+    // the band, `latest_balances` are the pool's (equalized and rated) balances after the instruction
+    fn check_price_band(
+        &self,
+        latest_balances: [AmountT; TOKEN_COUNT],
+        latest_depth: AmountT,
+    ) -> Result<(), PoolError> {
+        let price_band = &self.pool_state.price_band;
+        if !price_band.is_set() {
+            return Ok(());
+        }
+        let price = |balances: &[AmountT; TOKEN_COUNT], depth: AmountT| {
+            let balances = create_array(|i| balances[i].as_u128());
+            marginal_prices(self.pool_state, &balances, self.amp_factor, depth.as_u128()).map(|prices| prices[1])
+        };
+        let latest_price = match price(&latest_balances, latest_depth) {
+            Some(latest_price) => latest_price,
+            None => return Ok(()),
+        };
+        let distance = price_band.distance(latest_price);
+        if distance == 0 {
+            return Ok(());
+        }
+        let previous_distance = depth(self.pool_state, &self.pool_balances, self.amp_factor)
+            .ok()
+            .and_then(|previous_depth| price(&self.pool_balances, previous_depth))
+            .map(|previous_price| price_band.distance(previous_price));
+        if matches!(previous_distance, Some(previous_distance) if distance <= previous_distance) {
+            return Ok(());
+        }
+        msg!(
+            "price {} outside of band {}..={}",
+            latest_price,
+            price_band.min_price,
+            price_band.max_price
+        );
+        Err(PoolError::PriceOutOfBand)
+    }
+
+    //amounts the pool receives are rounded down and amounts it pays out are rounded up
+    fn equalize(&self, amounts: &[AtomicT; TOKEN_COUNT], round_up: bool) -> [AmountT; TOKEN_COUNT] {
+        create_array(|i| {
+            to_rated(
+                to_equalized(amounts[i], self.pool_state.token_decimal_equalizers[i]),
+                self.rates[i],
+                round_up,
+            )
+        })
+    }
+
+    //the invariant splits the fee between the lp tokens and the governance fee in proportion to the two fees, so a pool
+    // without governance fee is quoted as if its lp fee were the governance fee: the user's amount and the depth only
+    // depend on the total fee and the governance mint amount then tells what the lp fee is worth (see quote)
+    fn invariant_fees(&self) -> (DecT, DecT) {
+        if self.governance_fee == DecT::from(0) {
+            (DecT::from(0), self.lp_fee)
+        } else {
+            (self.lp_fee, self.governance_fee)
+        }
+    }
+
+    fn quote(
+        &self,
+        (user_amount, governance_mint_amount, latest_depth): (AmountT, AmountT, AmountT),
+        user_equalizer: i8,
+        user_rate: u64,
+        user_pays: bool,
+    ) -> Quote {
+        //the lp fee is worth lp_fee / governance_fee times what the governance fee mints, what the lp fee of a pool
+        // without governance fee would have minted instead is the lp fee's worth before the dilution by the mint
+        let mint_amount = U256::from(governance_mint_amount.as_u128());
+        let (governance_mint_amount, lp_fee_amount) = if self.governance_fee == DecT::from(0) {
+            let lp_total_supply = U256::from(self.lp_total_supply.as_u128());
+            let lp_fee_amount = if mint_amount.is_zero() {
+                U256::zero()
+            } else {
+                mint_amount * lp_total_supply / (lp_total_supply + mint_amount)
+            };
+            (AmountT::from(0u64), lp_fee_amount)
+        } else {
+            let lp_fee_amount = mint_amount
+                * U256::from(self.lp_fee.get_raw())
+                * U256::exp10(self.governance_fee.get_decimals() as usize)
+                / (U256::from(self.governance_fee.get_raw()) * U256::exp10(self.lp_fee.get_decimals() as usize));
+            (governance_mint_amount, lp_fee_amount)
+        };
+        let lp_decimal_equalizer = self.pool_state.lp_decimal_equalizer;
+        Quote {
+            amount: from_equalized_lp(from_rated(user_amount, user_rate, user_pays), user_equalizer, user_pays),
+            governance_mint_amount: from_equalized_lp(governance_mint_amount, lp_decimal_equalizer, false),
+            lp_fee_amount: from_equalized_lp(AmountT::from(lp_fee_amount.as_u128()), lp_decimal_equalizer, false),
+            latest_depth: latest_depth.as_u128(),
+        }
+    }
+}
+
+/// Output amounts of burning `exact_burn_amount` lp tokens in a uniform remove and the pool's depth afterwards. The
+///  user's share includes the tokens deployed to yield adapters, which may have to be recalled first
+pub fn remove_uniform<const TOKEN_COUNT: usize>(
+    pool_state: &PoolState<TOKEN_COUNT>,
+    pool_balances: &[AtomicT; TOKEN_COUNT],
+    lp_total_supply: AtomicT,
+    exact_burn_amount: AtomicT,
+) -> ([AtomicT; TOKEN_COUNT], u128) {
+    let user_share = DecT::from(exact_burn_amount) / lp_total_supply;
+    //u64 can store 19 decimals, previous_depth can theoretically go up to TOKEN_COUNT * u64::MAX
+    //hence, just to be safe, we allow for previous depth to have up to 20 decimals
+    //therefore we can only multiply with a number with at most 18 decimals to stay within
+    //the 38 max decimals range of u128
+    const DECIMAL_UPSHIFT: u32 = 18;
+    let user_depth = (pool_state.previous_depth * ((user_share * 10u64.pow(DECIMAL_UPSHIFT)).trunc() as u128))
+        / 10u128.pow(DECIMAL_UPSHIFT);
+    let latest_depth = pool_state.previous_depth - user_depth;
+    let total_balances = pool_state.total_balances(pool_balances);
+    let output_amounts = create_array(|i| (total_balances[i] * user_share).trunc());
+    (output_amounts, latest_depth)
+}
+
+#[cfg(all(test, not(feature = "test-bpf")))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn equalized_round_trip() {
+        assert_eq!(to_equalized(123, 0), AmountT::from(123));
+        assert_eq!(to_equalized(123, 3), AmountT::from(123000));
+        assert_eq!(from_equalized(AmountT::from(123000), 3, false), 123);
+        assert_eq!(from_equalized(AmountT::from(123000), 3, true), 123);
+        assert_eq!(from_equalized(AmountT::from(123001), 3, false), 123);
+        assert_eq!(from_equalized(AmountT::from(123001), 3, true), 124);
+        assert_eq!(from_equalized(AmountT::from(123999), 3, false), 123);
+        assert_eq!(from_equalized(AmountT::from(7), 0, true), 7);
+    }
+
+    #[test]
+    fn equalized_lp_round_trip() {
+        assert_eq!(to_equalized_lp(123, 3, false), AmountT::from(123000));
+        assert_eq!(from_equalized_lp(AmountT::from(123001), 3, true), 124);
+        assert_eq!(to_equalized_lp(123456, -3, false), AmountT::from(123));
+        assert_eq!(to_equalized_lp(123456, -3, true), AmountT::from(124));
+        assert_eq!(to_equalized_lp(123000, -3, true), AmountT::from(123));
+        assert_eq!(from_equalized_lp(AmountT::from(123), -3, false), 123000);
+        assert_eq!(from_equalized_lp(AmountT::from(123), -3, true), 123000);
+    }
+
+    #[test]
+    fn rated_round_trip() {
+        let rate = RATE_ONE / 10 * 11;
+        assert_eq!(to_rated(AmountT::from(1000), rate, false), AmountT::from(1100));
+        assert_eq!(to_rated(AmountT::from(7), rate, false), AmountT::from(7));
+        assert_eq!(to_rated(AmountT::from(7), rate, true), AmountT::from(8));
+        assert_eq!(from_rated(AmountT::from(1100), rate, true), AmountT::from(1000));
+        assert_eq!(from_rated(AmountT::from(7), rate, false), AmountT::from(6));
+        assert_eq!(from_rated(AmountT::from(7), rate, true), AmountT::from(7));
+        assert_eq!(to_rated(AmountT::from(7), RATE_ONE, true), AmountT::from(7));
+    }
+}
@@ -1,7 +1,18 @@
 use borsh::{BorshDeserialize, BorshSchema, BorshSerialize};
-use solana_program::{clock::UnixTimestamp, pubkey::Pubkey};
+use solana_program::{
+    borsh::get_packed_len,
+    clock::{Slot, UnixTimestamp},
+    pubkey::Pubkey,
+};
 
-use crate::{amp_factor::AmpFactor, pool_fee::PoolFee};
+use crate::{
+    amp_factor::{AmpDamping, AmpFactor},
+    decimal::{DecimalU64, U256},
+    oracle::{OracleFallback, OracleKind, OracleLimits, RateSource, TokenOracle, BPS_ONE, RATE_ONE},
+    pool_fee::PoolFee,
+    token_2022,
+};
+use std::cmp::Ordering;
 
 //arguably, various fields should be Options (e.g. all the prepared_* fields)
 //the advantage of taking a special value approach is that serialized data
@@ -17,7 +28,8 @@ pub struct PoolState<const TOKEN_COUNT: usize> {
     pub governance_fee: PoolFee,
 
     pub lp_mint_key: Pubkey,
-    pub lp_decimal_equalizer: u8,
+    //negative if the lp mint has more decimals than the pool's most precise token
+    pub lp_decimal_equalizer: i8,
 
     pub token_mint_keys: [Pubkey; TOKEN_COUNT],
     pub token_decimal_equalizers: [u8; TOKEN_COUNT],
@@ -31,10 +43,1855 @@ pub struct PoolState<const TOKEN_COUNT: usize> {
     pub prepared_governance_fee: PoolFee,
     pub fee_transition_ts: UnixTimestamp,
     pub previous_depth: u128,
+
+    //swaps are checked against oracle prices once all tokens have an oracle, tokens without one have Pubkey::default()
+    pub oracle_keys: [Pubkey; TOKEN_COUNT],
+    pub oracle_kinds: [OracleKind; TOKEN_COUNT],
+    pub max_oracle_deviation: PoolFee,
+    //applies to the guard oracle and the rate feed of each token
+    pub oracle_limits: [OracleLimits; TOKEN_COUNT],
+    pub oracle_fallback: OracleFallback,
+    //the oracle that EnactTokenOracle binds to token prepared_oracle_index
+    pub prepared_token_oracle: TokenOracle,
+    pub prepared_oracle_index: u8,
+    pub oracle_transition_ts: UnixTimestamp,
+    //the pool's internal oracle: a moving average of its marginal prices (see oracle::marginal_prices) that every defi
+    // instruction moves towards the prices the pool traded at since the previous one, so a price only counts for as
+    // long as it lasted and can't be moved within a single slot
+    pub ema_prices: [u64; TOKEN_COUNT],
+    pub last_prices: [u64; TOKEN_COUNT],
+    pub ema_ts: UnixTimestamp,
+    //a zero band means CheckPeg is off
+    pub peg_band: PoolFee,
+    pub depegged_tokens: [bool; TOKEN_COUNT],
+    //advanced by CheckPeg
+    pub amp_damping: AmpDamping,
+
+    //the invariant values balances at these exchange rates, keys of sources without an account are Pubkey::default()
+    pub rate_sources: [RateSource; TOKEN_COUNT],
+    pub rate_keys: [Pubkey; TOKEN_COUNT],
+
+    //programs that swaps may call back once they executed (see SetHookPrograms), unused slots are Pubkey::default()
+    pub hook_programs: [Pubkey; MAX_HOOK_PROGRAMS],
+
+    //which programs may call defi instructions via cpi (see SetCallerAllowlist), unused slots are Pubkey::default()
+    pub caller_mode: CallerMode,
+    pub allowed_callers: [Pubkey; MAX_ALLOWED_CALLERS],
+
+    //0 unless governance is winding the pool down (see SetSunset): only removes are allowed until then, afterwards
+    // nothing but RedeemSunset
+    pub sunset_ts: UnixTimestamp,
+
+    //the share of the governance fee that goes to the lp tokens locked with LockLp (see SetLockFeeShare) in
+    // proportion to the locks' weights, none while nothing is locked
+    pub lock_fee_share: PoolFee,
+    pub lock_total_weight: u128,
+    //lp tokens per lock weight that locks earned since the pool's first lock, times GROWTH_ONE
+    pub lock_reward_growth: u128,
+    //lp tokens that locks earned but didn't claim yet, they are only minted once claimed but count towards the lp
+    // supply (see lp_supply) in the meantime
+    pub lock_rewards_unminted: u64,
+
+    //lp tokens' worth of lp fees that every lp token earned since the pool's start, times GROWTH_ONE. the lp fees of
+    // an instruction are valued at the lp token's price after it and shared by the lp tokens before it (see
+    // FeeCheckpoint)
+    pub lp_fee_growth: u128,
+
+    //whether the lp mint is a Token-2022 mint with the NonTransferable extension (see Init), lp tokens can then only be
+    // minted and burned, so everything that moves them (locks, migrating the governance fee account or the pool's
+    // state) is unavailable
+    pub lp_non_transferable: bool,
+
+    //the lp supply (see lp_supply) integrated over time in lp token seconds since the pool's start, advanced whenever
+    // the supply changes (see SnapshotLpSupply)
+    pub lp_supply_seconds: u128,
+    pub lp_supply_ts: UnixTimestamp,
+
+    //the least lp tokens that an add has to mint and that a remove has to leave in the user's lp token account unless
+    // it empties it (see SetMinLpPosition), 0 for none
+    pub min_lp_mint_amount: u64,
+    pub min_lp_residual_amount: u64,
+
+    //until launch_end_ts, adds may mint at most launch_lp_cap lp tokens to each wallet (see SetLaunchCap and
+    // LaunchAllocation), 0 for no cap
+    pub launch_lp_cap: u64,
+    pub launch_end_ts: UnixTimestamp,
+
+    //which users may call defi instructions (see SetUserGate), and which of them may also add and remove other than
+    // uniformly (see SetDepositorGate)
+    pub user_gate: UserGate,
+    pub depositor_gate: UserGate,
+
+    //keys that may sign a role's governance instructions besides the governance itself (see SetRole), indexed by
+    // Role, Pubkey::default() for none
+    pub roles: [Pubkey; ROLE_COUNT],
+
+    //tokens that governance disabled as an input or output of defi instructions (see SetTokenFlags)
+    pub token_flags: [TokenFlags; TOKEN_COUNT],
+
+    //the key whose signed quotes RfqSwap executes (see SetRfqQuoter), Pubkey::default() for none. a quote's output may
+    // deviate from its input's value at the internal oracle's prices by at most rfq_max_deviation, and each quote's id
+    // has to exceed that of the last one executed, so quotes can't be replayed
+    pub rfq_quoter_key: Pubkey,
+    pub rfq_max_deviation: PoolFee,
+    pub rfq_last_quote_id: u64,
+    //the quoter and deviation that EnactRfqQuoter switches to once rfq_quoter_transition_ts passed, 0 if none is
+    // prepared
+    pub prepared_rfq_quoter_key: Pubkey,
+    pub prepared_rfq_max_deviation: PoolFee,
+    pub rfq_quoter_transition_ts: UnixTimestamp,
+
+    //the program that defi instructions (other than uniform removes) call before they execute, which can veto them
+    // (see SetComplianceProgram), Pubkey::default() for none
+    pub compliance_program: Pubkey,
+
+    //when the pool is open for trading (see SetTradingSchedule): defi instructions other than uniform removes fail
+    // outside of the daily trading hours and during maintenance windows, which they announce ahead (see
+    // MaintenanceEvent)
+    pub trading_hours: TradingHours,
+    pub maintenance_windows: [MaintenanceWindow; MAX_MAINTENANCE_WINDOWS],
+
+    //removes other than uniform ones that burn more than withdrawal_cooldown_threshold lp tokens have to be announced
+    // withdrawal_cooldown seconds ahead (see SetWithdrawalCooldown and WithdrawalAnnouncement), 0 for no cooldown
+    pub withdrawal_cooldown_threshold: u64,
+    pub withdrawal_cooldown: UnixTimestamp,
+    //lp tokens that unannounced removes of all users together burned in unannounced_burn_slot. the threshold applies
+    // to their running total, so splitting a remove into small ones (of one wallet or several) doesn't get around it
+    pub unannounced_burn_slot: Slot,
+    pub unannounced_burn_amount: u64,
+
+    //keys that can put the pool into withdrawal-only mode (i.e. pause it) but do nothing else (see
+    // SetEmergencyResponders and ConfirmEmergency), once emergency_threshold of them confirmed within
+    // EMERGENCY_CONFIRMATION_WINDOW. Pubkey::default() for unused slots, emergency_confirmation_ts is when each of them
+    // last confirmed, 0 for never
+    pub emergency_responders: [Pubkey; MAX_EMERGENCY_RESPONDERS],
+    pub emergency_threshold: u8,
+    pub emergency_confirmation_ts: [UnixTimestamp; MAX_EMERGENCY_RESPONDERS],
+
+    //the invariant that prices the pool's tokens, chosen at Init (stable swap), InitWeighted (weighted product),
+    // InitFixedRate (fixed rate) or InitHybrid (hybrid). token_weights are the weighted product invariant's weights in
+    // parts of weighted::WEIGHT_ONE, all zero for other pools
+    pub invariant_kind: InvariantKind,
+    pub token_weights: [u32; TOKEN_COUNT],
+
+    //the band that the price of a two token pool has to stay within (see SetPriceBand), all zeros for none
+    pub price_band: PriceBand,
+
+    //the exchange rates that previous_depth values the balances at, instructions that value them at other rates (e.g.
+    // after a wrapped rebasing token's rate grew, see quote::rebased_previous_depth) rebase previous_depth first
+    pub depth_rates: [u64; TOKEN_COUNT],
+
+    //which defi instructions may move each token while it's being onboarded or offboarded (see
+    // SetTokenParticipation), all Active for a pool whose tokens all trade
+    pub token_participation: [TokenParticipation; TOKEN_COUNT],
+
+    //the program that may lend the pool one of its tokens up to a credit limit to keep it at its peg (see SetPegKeeper
+    // and ProvidePegCredit), along with the pool's debt to it
+    pub peg_keeper: PegKeeper,
+    //the keeper, token and credit limit that EnactPegKeeper switches to once peg_keeper_transition_ts passed (without
+    // a debt of its own), 0 if none is prepared
+    pub prepared_peg_keeper: PegKeeper,
+    pub peg_keeper_transition_ts: UnixTimestamp,
+
+    //the program that each token's balance beyond a buffer may be deployed to for yield (see SetYieldAdapter and
+    // DeployYield), along with what the pool's position with it is worth
+    pub yield_adapters: [YieldAdapter; TOKEN_COUNT],
+    //the adapter and buffer that EnactYieldAdapter switches token prepared_yield_adapter_index to once
+    // yield_adapter_transition_ts passed (without a position of its own), 0 if none is prepared
+    pub prepared_yield_adapter: YieldAdapter,
+    pub prepared_yield_adapter_index: u8,
+    pub yield_adapter_transition_ts: UnixTimestamp,
+
+    //the internal price of a hybrid pool's volatile token that its invariant values the token at, and how Repeg moves
+    // it towards the token's oracle (see SetRepegParams), all zeros for other pools
+    pub price_scale: PriceScale,
+
+    //the pool's latest trading fee rebate campaign (see StartRebateCampaign), which swaps record their fees with until
+    // its window ends at rebate_end_ts, the default key if there never was one
+    pub rebate_campaign: Pubkey,
+    pub rebate_end_ts: UnixTimestamp,
+
+    //the TOKEN_COUNT of the program that initialized the pool, the layout of everything above depends on it
+    pub token_count: u8,
+    //POOL_STATE_LAYOUT_VERSION when the pool's account was created, 0 for a state decoded from an account of the
+    // first layout (see PoolStateV0). accounts can't grow, so fields are only ever added along with a new version
+    // whose accounts PoolState::unpack tells apart from those of the previous ones
+    pub layout_version: u8,
+}
+
+/// The layout version of the pool state accounts that the program creates (see `PoolState::layout_version`)
+pub const POOL_STATE_LAYOUT_VERSION: u8 = 1;
+
+/// How many swap hook programs a pool can allow
+pub const MAX_HOOK_PROGRAMS: usize = 4;
+/// How many programs a pool can allow to call it via cpi
+pub const MAX_ALLOWED_CALLERS: usize = 8;
+/// How many maintenance windows a pool can schedule
+pub const MAX_MAINTENANCE_WINDOWS: usize = 4;
+/// How long before a maintenance window starts defi instructions announce it (see `MaintenanceEvent`)
+pub const MAINTENANCE_NOTICE: UnixTimestamp = 86400;
+const SECONDS_PER_DAY: UnixTimestamp = 86400;
+/// The longest cooldown that governance can set between announcing a large withdrawal and making it
+pub const MAX_WITHDRAWAL_COOLDOWN: UnixTimestamp = 86400;
+/// How long a withdrawal announcement can be used once its cooldown has passed
+pub const WITHDRAWAL_ANNOUNCEMENT_VALIDITY: UnixTimestamp = 86400;
+/// The largest deviation of an RFQ quote's output from its input's value that governance can allow, in basis points
+pub const MAX_RFQ_DEVIATION_BPS: u64 = 100;
+/// How many emergency responders a pool can have
+pub const MAX_EMERGENCY_RESPONDERS: usize = 8;
+/// How long an emergency responder's confirmation counts towards the pool's emergency threshold
+pub const EMERGENCY_CONFIRMATION_WINDOW: UnixTimestamp = 3600;
+
+/// Who may call a pool's defi instructions. Uniform removes are never restricted, so liquidity providers can always
+/// exit
+#[derive(BorshSerialize, BorshDeserialize, BorshSchema, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CallerMode {
+    /// Anybody, directly or via cpi
+    Unrestricted,
+    /// Users directly and allowed programs via cpi
+    AllowedPrograms,
+    /// Only allowed programs via cpi
+    AllowedProgramsOnly,
+}
+
+/// The invariant of a pool: stable swap for like-valued tokens (whose amp factor sets how flat its curve is), a
+/// weighted constant product, whose pools hold a fixed share of their value in each token at any price and can thus
+/// pair tokens of unrelated values, a fixed rate without any curvature, whose pools trade their tokens at their
+/// exchange rates whatever their balances, e.g. to migrate one mint to another, or a hybrid of stablecoins and one
+/// volatile token, whose stable swap curve values the volatile token at the pool's own price for it (see `PriceScale`)
+/// rather than at its oracle's, so its liquidity concentrates around that price while the oracle moves
+#[derive(BorshSerialize, BorshDeserialize, BorshSchema, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InvariantKind {
+    StableSwap,
+    WeightedProduct,
+    FixedRate,
+    Hybrid,
+}
+
+/// The price range of a two token pool (see `SetPriceBand`): trades may not move the price of token 1 in units of
+/// token 0 (of whole tokens valued at their exchange rates, scaled by 10^EMA_DECIMALS like the internal oracle's
+/// prices) outside of `min_price..=max_price`, or further away from it while it's outside (e.g. after a rate
+/// changed). All zeros (the default) for none
+#[derive(BorshSerialize, BorshDeserialize, BorshSchema, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct PriceBand {
+    pub min_price: u64,
+    pub max_price: u64,
+}
+
+impl PriceBand {
+    pub fn is_set(&self) -> bool {
+        *self != Self::default()
+    }
+
+    pub fn is_valid(&self) -> bool {
+        !self.is_set() || self.min_price < self.max_price
+    }
+
+    /// How far `price` is outside the band, 0 within it
+    pub fn distance(&self, price: u64) -> u64 {
+        if price < self.min_price {
+            self.min_price - price
+        } else {
+            price.saturating_sub(self.max_price)
+        }
+    }
+}
+
+/// Which users (i.e. user transfer authorities) may call a pool's defi instructions, for permissioned pools. Like
+/// `CallerMode`, uniform removes are never restricted
+#[derive(BorshSerialize, BorshDeserialize, BorshSchema, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UserGate {
+    /// Anybody
+    Open,
+    /// Users that governance allowed (see `SetUserAllowed`), who pass their `AllowedUser` account
+    Allowlist,
+    /// Users that hold a token of `mint` (e.g. a KYC pass), who pass a token account of theirs with a balance of it
+    GatingToken { mint: Pubkey },
 }
 
+/// The daily hours in which a pool is open for trading, in seconds since midnight UTC: from `open_secs` until
+/// `close_secs`, past midnight if that's the smaller one. Equal values (the default) keep the pool open around the
+/// clock
+#[derive(BorshSerialize, BorshDeserialize, BorshSchema, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct TradingHours {
+    pub open_secs: u32,
+    pub close_secs: u32,
+}
+
+impl TradingHours {
+    pub fn is_valid(&self) -> bool {
+        (self.open_secs as UnixTimestamp) < SECONDS_PER_DAY && (self.close_secs as UnixTimestamp) < SECONDS_PER_DAY
+    }
+
+    pub fn is_open(&self, ts: UnixTimestamp) -> bool {
+        let secs = ts.rem_euclid(SECONDS_PER_DAY) as u32;
+        match self.open_secs.cmp(&self.close_secs) {
+            Ordering::Equal => true,
+            Ordering::Less => self.open_secs <= secs && secs < self.close_secs,
+            Ordering::Greater => secs >= self.open_secs || secs < self.close_secs,
+        }
+    }
+}
+
+/// A period from `start_ts` until `end_ts` in which a pool is closed for trading, e.g. during a scheduled mint
+/// migration. Unused slots are all zeros
+#[derive(BorshSerialize, BorshDeserialize, BorshSchema, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct MaintenanceWindow {
+    pub start_ts: UnixTimestamp,
+    pub end_ts: UnixTimestamp,
+}
+
+impl MaintenanceWindow {
+    pub fn is_set(&self) -> bool {
+        *self != Self::default()
+    }
+
+    pub fn contains(&self, ts: UnixTimestamp) -> bool {
+        self.start_ts <= ts && ts < self.end_ts
+    }
+}
+
+/// What governance disabled a token for (see `SetTokenFlags`), e.g. to stop taking in a depegging token while users
+/// can still swap out of it. Uniform removes ignore the flags
+#[derive(BorshSerialize, BorshDeserialize, BorshSchema, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct TokenFlags {
+    /// Adds and swaps can't take the token
+    pub input_disabled: bool,
+    /// Swaps and single token removes can't give the token
+    pub output_disabled: bool,
+}
+
+/// The phase of a token's onboarding or offboarding (see `SetTokenParticipation`), so a pool can take on or phase out
+/// a token without migrating to a new pool. Uniform removes ignore it
+#[derive(BorshSerialize, BorshDeserialize, BorshSchema, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenParticipation {
+    /// Adds, swaps and removes all move the token
+    Active,
+    /// A token being onboarded: adds can take it to build up its balance, but it's neither swapped nor removed
+    /// other than uniformly until it's active
+    DepositOnly,
+    /// A token being offboarded: swaps and removes can give it, but nothing takes it in anymore
+    WithdrawOnly,
+}
+
+//borsh only deserializes arrays of Default types
+impl Default for TokenParticipation {
+    fn default() -> Self {
+        TokenParticipation::Active
+    }
+}
+
+impl TokenParticipation {
+    /// Whether adds (`is_add`) resp. swaps can take the token
+    pub fn allows_input(&self, is_add: bool) -> bool {
+        match self {
+            TokenParticipation::Active => true,
+            TokenParticipation::DepositOnly => is_add,
+            TokenParticipation::WithdrawOnly => false,
+        }
+    }
+
+    /// Whether swaps and single token removes can give the token
+    pub fn allows_output(&self) -> bool {
+        *self != TokenParticipation::DepositOnly
+    }
+}
+
+/// The largest share of the lp supply (in basis points) that a peg keeper's credit can be worth, whatever its credit
+/// limit
+pub const MAX_PEG_CREDIT_SHARE_BPS: u64 = 2_000;
+
+/// A peg keeper's credit line with a pool (see `SetPegKeeper`): `keeper_key` (usually a program's address) may provide
+/// the pool with up to `credit_limit` of the token at `token_index` while the pool is short of it and absorb it again
+/// while the pool holds too much of it, both at the invariant's price without fees. The tokens aren't transferred, the
+/// pool instead holds `debt` as a virtual balance that the invariant values like the tokens in its token account, and
+/// the lp tokens that the credit is worth count towards the lp supply as `lp_amount` without being minted. What's left
+/// of them once the debt is repaid is the keeper's profit, which goes to the liquidity providers
+#[derive(BorshSerialize, BorshDeserialize, BorshSchema, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct PegKeeper {
+    pub keeper_key: Pubkey,
+    pub token_index: u8,
+    pub credit_limit: u64,
+    pub debt: u64,
+    pub lp_amount: u64,
+}
+
+impl PegKeeper {
+    pub fn is_set(&self) -> bool {
+        self.keeper_key != Pubkey::default()
+    }
+
+    /// What the keeper can still provide
+    pub fn available_credit(&self) -> u64 {
+        self.credit_limit.saturating_sub(self.debt)
+    }
+}
+
+/// A token's yield adapter (see `SetYieldAdapter`): a program that deposits the token into a lending market on the
+/// pool's behalf (see `instruction::YieldAdapterInstruction`). `DeployYield` moves what the pool's token account holds
+/// beyond `buffer` to it and `RecallYield` brings tokens back, both record what the pool's position is worth as
+/// `deployed`, which the invariant values like the tokens in the token account. Since the pool can't verify the
+/// adapter's report, `deployed` never exceeds what was deposited less what was recalled, so yield only counts once it
+/// is recalled. Outputs are still limited to the token account's balance, so large withdrawals recall the tokens they
+/// need first
+#[derive(BorshSerialize, BorshDeserialize, BorshSchema, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct YieldAdapter {
+    pub program: Pubkey,
+    pub buffer: u64,
+    pub deployed: u64,
+}
+
+impl YieldAdapter {
+    pub fn is_set(&self) -> bool {
+        self.program != Pubkey::default()
+    }
+}
+
+/// The internal price of a hybrid pool's volatile token (see `InvariantKind::Hybrid`): the invariant values the token
+/// at `rate` (scaled by 10^RATE_DECIMALS) instead of the current rate of its rate source, whose account the pool's
+/// instructions still take but don't read, so trades keep working while the oracle is unavailable. `Repeg` moves
+/// `rate` towards the oracle's rate once they're more than `threshold_bps` apart, by at most `max_step_bps` of `rate`
+/// and at most every `min_interval` seconds (see `SetRepegParams`), a `max_step_bps` of 0 never repegs
+#[derive(BorshSerialize, BorshDeserialize, BorshSchema, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct PriceScale {
+    pub token_index: u8,
+    pub rate: u64,
+    pub threshold_bps: u16,
+    pub max_step_bps: u16,
+    pub min_interval: UnixTimestamp,
+    pub last_repeg_ts: UnixTimestamp,
+}
+
+/// A share of governance's powers that it can delegate to a key of its own (see `SetRole`), so routine changes don't
+/// need the governance key. The governance keeps all of them
+#[derive(BorshSerialize, BorshDeserialize, BorshSchema, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Role {
+    /// `PrepareFeeChange` and `EnactFeeChange`
+    FeeAdmin,
+    /// `AdjustAmpFactor`
+    AmpAdmin,
+    /// `SetPaused`, `SetTokenFlags` and `SetTradingSchedule`
+    PauseAdmin,
+}
+
+pub const ROLE_COUNT: usize = 3;
+
 impl<const TOKEN_COUNT: usize> PoolState<TOKEN_COUNT> {
     pub fn is_initialized(&self) -> bool {
         self.lp_mint_key != Pubkey::default()
     }
+
+    /// Decodes a pool state account of the current layout or of the first one (see `PoolStateV0`), `None` for any
+    /// other account, which `pool_token_count` tells apart from those of pools of another token count
+    pub fn unpack(data: &[u8]) -> Option<Self> {
+        let mut remaining = data;
+        let pool_state = if data.len() == get_packed_len::<PoolStateV0<TOKEN_COUNT>>() {
+            Self::from(PoolStateV0::<TOKEN_COUNT>::deserialize(&mut remaining).ok()?)
+        } else {
+            Self::deserialize(&mut remaining)
+                .ok()
+                .filter(|pool_state| pool_state.layout_version == POOL_STATE_LAYOUT_VERSION)?
+        };
+        Some(pool_state).filter(|pool_state| remaining.is_empty() && pool_state.token_count as usize == TOKEN_COUNT)
+    }
+
+    /// Whether writing back only the fields of `PoolStateV0` loses nothing but the bookkeeping of features that a pool
+    /// of the first layout can't turn on anyway (its internal oracle, lp supply and fee tracking), i.e. whether all
+    /// later settings are still at their defaults
+    pub fn fits_layout_v0(&self) -> bool {
+        let data = match self.try_to_vec() {
+            Ok(data) => data,
+            Err(_) => return false,
+        };
+        let v0 = match PoolStateV0::<TOKEN_COUNT>::deserialize(&mut &data[..]) {
+            Ok(v0) => v0,
+            Err(_) => return false,
+        };
+        let written_back = PoolState {
+            ema_prices: self.ema_prices,
+            last_prices: self.last_prices,
+            ema_ts: self.ema_ts,
+            lp_fee_growth: self.lp_fee_growth,
+            lp_supply_seconds: self.lp_supply_seconds,
+            lp_supply_ts: self.lp_supply_ts,
+            depth_rates: self.depth_rates,
+            unannounced_burn_slot: self.unannounced_burn_slot,
+            unannounced_burn_amount: self.unannounced_burn_amount,
+            layout_version: self.layout_version,
+            ..Self::from(v0)
+        };
+        written_back.try_to_vec().ok() == Some(data)
+    }
+
+    /// Whether every token has a guard oracle, which `CheckPeg` requires
+    pub fn has_token_oracles(&self) -> bool {
+        self.oracle_keys.iter().all(|key| *key != Pubkey::default())
+    }
+
+    /// Whether swaps are checked against the guard oracles, which also takes a max deviation (see `SetOracleGuard`)
+    pub fn is_oracle_guarded(&self) -> bool {
+        self.has_token_oracles() && self.max_oracle_deviation.get() > DecimalU64::from(0)
+    }
+
+    /// The amp factor that instructions use at `current_ts`, i.e. `amp_factor` scaled down by `amp_damping`
+    pub fn effective_amp_factor(&self, current_ts: UnixTimestamp) -> DecimalU64 {
+        self.amp_damping.apply(self.amp_factor.get(current_ts), current_ts)
+    }
+
+    /// Whether governance is winding the pool down (see `GovernanceInstruction::SetSunset`)
+    pub fn is_sunset(&self) -> bool {
+        self.sunset_ts != 0
+    }
+
+    /// The token program of the pool's lp mint and lp token accounts
+    pub fn lp_token_program_id(&self) -> Pubkey {
+        if self.lp_non_transferable {
+            token_2022::id()
+        } else {
+            spl_token::id()
+        }
+    }
+
+    /// The pool's lp supply given the supply of its lp mint, including the lock rewards that weren't claimed yet and
+    /// the lp tokens of the peg keeper's credit
+    pub fn lp_supply(&self, mint_supply: u64) -> u64 {
+        mint_supply + self.lock_rewards_unminted + self.peg_keeper.lp_amount
+    }
+
+    /// The rate that a hybrid pool's invariant values its volatile token at in place of the rate source's (see
+    /// `PriceScale`), `None` for the other tokens and for other pools
+    pub fn scaled_rate(&self, token_index: usize) -> Option<u64> {
+        if self.invariant_kind == InvariantKind::Hybrid && token_index == self.price_scale.token_index as usize {
+            Some(self.price_scale.rate)
+        } else {
+            None
+        }
+    }
+
+    /// The balances the pool owns, i.e. those of its token accounts plus what it deployed to its tokens' yield
+    /// adapters (see `YieldAdapter`)
+    pub fn total_balances(&self, pool_balances: &[u64; TOKEN_COUNT]) -> [u64; TOKEN_COUNT] {
+        let mut balances = *pool_balances;
+        for (balance, adapter) in balances.iter_mut().zip(self.yield_adapters.iter()) {
+            *balance = balance.saturating_add(adapter.deployed);
+        }
+        balances
+    }
+
+    /// The balances that the invariant values, i.e. the total balances (see `total_balances`) plus the virtual balance
+    /// of the peg keeper's token (see `PegKeeper`). Outputs are still limited to the token accounts' balances
+    pub fn invariant_balances(&self, pool_balances: &[u64; TOKEN_COUNT]) -> [u64; TOKEN_COUNT] {
+        let mut balances = self.total_balances(pool_balances);
+        if self.peg_keeper.debt > 0 {
+            let token_index = self.peg_keeper.token_index as usize;
+            balances[token_index] = balances[token_index].saturating_add(self.peg_keeper.debt);
+        }
+        balances
+    }
+
+    /// Sets aside the locks' share of a governance fee of `governance_mint_amount` lp tokens and returns it
+    pub fn accrue_lock_rewards(&mut self, governance_mint_amount: u64) -> u64 {
+        if self.lock_total_weight == 0 {
+            return 0;
+        }
+        let share = self.lock_fee_share.get();
+        let lock_amount =
+            (governance_mint_amount as u128 * share.get_raw() as u128 / 10u128.pow(share.get_decimals() as u32)) as u64;
+        self.lock_reward_growth += lock_amount as u128 * GROWTH_ONE / self.lock_total_weight;
+        self.lock_rewards_unminted += lock_amount;
+        lock_amount
+    }
+
+    /// Adds the lp fee of an instruction, worth `lp_fee_amount` lp tokens, to `lp_fee_growth`, `lp_supply` is the lp
+    /// supply before the instruction
+    pub fn accrue_lp_fee(&mut self, lp_fee_amount: u64, lp_supply: u64) {
+        if lp_supply > 0 {
+            self.lp_fee_growth += lp_fee_amount as u128 * GROWTH_ONE / lp_supply as u128;
+        }
+    }
+
+    /// `lp_supply_seconds` at `current_ts` if the lp supply has been `lp_supply` since it last changed
+    pub fn lp_supply_seconds_at(&self, lp_supply: u64, current_ts: UnixTimestamp) -> u128 {
+        let elapsed = (current_ts - self.lp_supply_ts).max(0) as u128;
+        self.lp_supply_seconds + lp_supply as u128 * elapsed
+    }
+
+    /// Advances `lp_supply_seconds` to `current_ts` before the lp supply changes from `lp_supply`
+    pub fn accrue_lp_supply_seconds(&mut self, lp_supply: u64, current_ts: UnixTimestamp) {
+        self.lp_supply_seconds = self.lp_supply_seconds_at(lp_supply, current_ts);
+        self.lp_supply_ts = self.lp_supply_ts.max(current_ts);
+    }
+
+    /// Whether a remove that leaves `residual_amount` lp tokens in the user's lp token account would leave dust
+    pub fn is_dust_residual(&self, residual_amount: u64) -> bool {
+        residual_amount != 0 && residual_amount < self.min_lp_residual_amount
+    }
+
+    /// Whether large removes have to be announced ahead (see `SetWithdrawalCooldown`)
+    pub fn has_withdrawal_cooldown(&self) -> bool {
+        self.withdrawal_cooldown != 0
+    }
+
+    /// Whether adds at `current_ts` count towards their wallet's launch cap
+    pub fn is_launch_capped(&self, current_ts: UnixTimestamp) -> bool {
+        self.launch_lp_cap != 0 && current_ts < self.launch_end_ts
+    }
+
+    /// Whether governance gave up the pool (see `GovernanceInstruction::RenounceGovernance`)
+    pub fn is_governance_renounced(&self) -> bool {
+        self.governance_key == Pubkey::default()
+    }
+
+    pub fn is_hook_program_allowed(&self, program: &Pubkey) -> bool {
+        *program != Pubkey::default() && self.hook_programs.contains(program)
+    }
+
+    pub fn is_caller_allowed(&self, program: &Pubkey) -> bool {
+        *program != Pubkey::default() && self.allowed_callers.contains(program)
+    }
+
+    /// The key that holds `role` besides the governance, `None` if governance didn't delegate it
+    pub fn role_key(&self, role: Role) -> Option<Pubkey> {
+        Some(self.roles[role as usize]).filter(|key| *key != Pubkey::default())
+    }
+
+    /// Whether defi instructions (other than uniform removes) have to pass the instructions sysvar, which the pool
+    /// checks the calling program with (see `CallerMode`)
+    pub fn restricts_callers(&self) -> bool {
+        self.caller_mode != CallerMode::Unrestricted
+    }
+
+    /// Whether defi instructions (other than uniform removes) have to pass the user's gate account (see `UserGate`)
+    pub fn gates_users(&self) -> bool {
+        self.user_gate != UserGate::Open
+    }
+
+    /// Whether adds and single token removes also have to pass the user's depositor gate account
+    pub fn gates_depositors(&self) -> bool {
+        self.depositor_gate != UserGate::Open
+    }
+
+    /// Whether governance designated a quoter whose quotes `RfqSwap` executes
+    pub fn has_rfq_quoter(&self) -> bool {
+        self.rfq_quoter_key != Pubkey::default()
+    }
+
+    /// Whether defi instructions (other than uniform removes) have to pass the pool's compliance program and the user's
+    /// compliance account
+    pub fn has_compliance_program(&self) -> bool {
+        self.compliance_program != Pubkey::default()
+    }
+
+    /// The index of `key` among the pool's emergency responders, if it is one
+    pub fn emergency_responder_index(&self, key: &Pubkey) -> Option<usize> {
+        if *key == Pubkey::default() {
+            return None;
+        }
+        self.emergency_responders.iter().position(|responder| responder == key)
+    }
+
+    /// How many emergency responders confirmed within `EMERGENCY_CONFIRMATION_WINDOW` before `current_ts`
+    pub fn emergency_confirmations(&self, current_ts: UnixTimestamp) -> usize {
+        self.emergency_confirmation_ts
+            .iter()
+            .filter(|&&ts| ts != 0 && current_ts - ts < EMERGENCY_CONFIRMATION_WINDOW)
+            .count()
+    }
+
+    /// The maintenance window the pool is in at `ts`, if any
+    pub fn current_maintenance(&self, ts: UnixTimestamp) -> Option<MaintenanceWindow> {
+        self.maintenance_windows
+            .iter()
+            .copied()
+            .find(|window| window.contains(ts))
+    }
+
+    /// The maintenance window that starts next after `ts`, if any
+    pub fn next_maintenance(&self, ts: UnixTimestamp) -> Option<MaintenanceWindow> {
+        self.maintenance_windows
+            .iter()
+            .copied()
+            .filter(|window| window.is_set() && window.start_ts > ts)
+            .min_by_key(|window| window.start_ts)
+    }
+
+    /// The keys of the accounts that defi instructions have to pass to provide the exchange rates of rated tokens
+    pub fn rate_account_keys(&self) -> Vec<Pubkey> {
+        (0..TOKEN_COUNT)
+            .filter(|&i| self.rate_sources[i].has_account())
+            .map(|i| self.rate_keys[i])
+            .collect()
+    }
+}
+
+//the fields of a PoolState that precede its first array of TOKEN_COUNT elements, so they decode the same for pools of
+// any token count
+#[derive(BorshDeserialize)]
+struct PoolStateHead {
+    _nonce: u8,
+    _is_paused: bool,
+    _amp_factor: AmpFactor,
+    _lp_fee: PoolFee,
+    _governance_fee: PoolFee,
+    lp_mint_key: Pubkey,
+}
+
+/// The lp mint of the pool with the state account `data`, whatever its token count, e.g. of the base pool of a
+/// metapool (see `RateSource::BasePool`)
+pub fn pool_lp_mint_key(data: &[u8]) -> Option<Pubkey> {
+    PoolStateHead::deserialize(&mut &data[..])
+        .ok()
+        .map(|head| head.lp_mint_key)
+}
+
+/// The token count of the pool whose state account holds `data` if it has one of the program's layouts (for any
+/// token count): accounts of the current layout record it at the end, right before their layout version, and have to
+/// be sized for it, those of the first layout are only told by their size
+pub fn pool_token_count(data: &[u8]) -> Option<usize> {
+    //every array of the layouts has TOKEN_COUNT elements, so their sizes grow linearly with it
+    let token_count_of_size = |size_1: usize, size_2: usize| {
+        let token_size = size_2 - size_1;
+        let base_size = size_1 - token_size;
+        let token_count = data.len().checked_sub(base_size)? / token_size;
+        Some(token_count).filter(|&token_count| token_count > 0 && base_size + token_count * token_size == data.len())
+    };
+    match data {
+        [.., recorded_token_count, POOL_STATE_LAYOUT_VERSION] => {
+            token_count_of_size(get_packed_len::<PoolState<1>>(), get_packed_len::<PoolState<2>>())
+                .filter(|&token_count| token_count == *recorded_token_count as usize)
+        }
+        _ => token_count_of_size(get_packed_len::<PoolStateV0<1>>(), get_packed_len::<PoolStateV0<2>>()),
+    }
+}
+
+/// The layout of the pool's first release, i.e. the fields of `PoolState` up to `previous_depth`, which its pools'
+/// accounts keep since accounts can't grow. `PoolState::unpack` decodes them with a new pool's defaults for all later
+/// fields, and the program writes back only these fields as long as that loses no setting (see
+/// `PoolState::fits_layout_v0`), so their liquidity providers can always remove
+#[derive(BorshSerialize, BorshDeserialize, BorshSchema, Debug)]
+pub struct PoolStateV0<const TOKEN_COUNT: usize> {
+    pub nonce: u8,
+    pub is_paused: bool,
+    pub amp_factor: AmpFactor,
+    pub lp_fee: PoolFee,
+    pub governance_fee: PoolFee,
+
+    pub lp_mint_key: Pubkey,
+    pub lp_decimal_equalizer: u8,
+
+    pub token_mint_keys: [Pubkey; TOKEN_COUNT],
+    pub token_decimal_equalizers: [u8; TOKEN_COUNT],
+    pub token_keys: [Pubkey; TOKEN_COUNT],
+
+    pub governance_key: Pubkey,
+    pub governance_fee_key: Pubkey,
+    pub prepared_governance_key: Pubkey,
+    pub governance_transition_ts: UnixTimestamp,
+    pub prepared_lp_fee: PoolFee,
+    pub prepared_governance_fee: PoolFee,
+    pub fee_transition_ts: UnixTimestamp,
+    pub previous_depth: u128,
+}
+
+impl<const TOKEN_COUNT: usize> From<PoolStateV0<TOKEN_COUNT>> for PoolState<TOKEN_COUNT> {
+    fn from(v0: PoolStateV0<TOKEN_COUNT>) -> Self {
+        PoolState {
+            nonce: v0.nonce,
+            is_paused: v0.is_paused,
+            amp_factor: v0.amp_factor,
+            lp_fee: v0.lp_fee,
+            governance_fee: v0.governance_fee,
+            lp_mint_key: v0.lp_mint_key,
+            //the first release only supported lp mints of at most the tokens' decimals
+            lp_decimal_equalizer: v0.lp_decimal_equalizer as i8,
+            token_mint_keys: v0.token_mint_keys,
+            token_decimal_equalizers: v0.token_decimal_equalizers,
+            token_keys: v0.token_keys,
+            governance_key: v0.governance_key,
+            governance_fee_key: v0.governance_fee_key,
+            prepared_governance_key: v0.prepared_governance_key,
+            governance_transition_ts: v0.governance_transition_ts,
+            prepared_lp_fee: v0.prepared_lp_fee,
+            prepared_governance_fee: v0.prepared_governance_fee,
+            fee_transition_ts: v0.fee_transition_ts,
+            previous_depth: v0.previous_depth,
+            oracle_keys: [Pubkey::default(); TOKEN_COUNT],
+            oracle_kinds: [OracleKind::Pyth; TOKEN_COUNT],
+            max_oracle_deviation: PoolFee::default(),
+            oracle_limits: [OracleLimits::default(); TOKEN_COUNT],
+            oracle_fallback: OracleFallback::Reject,
+            prepared_token_oracle: TokenOracle::default(),
+            prepared_oracle_index: 0,
+            oracle_transition_ts: 0,
+            ema_prices: [0; TOKEN_COUNT],
+            last_prices: [0; TOKEN_COUNT],
+            ema_ts: 0,
+            peg_band: PoolFee::default(),
+            depegged_tokens: [false; TOKEN_COUNT],
+            amp_damping: AmpDamping::default(),
+            rate_sources: [RateSource::None; TOKEN_COUNT],
+            rate_keys: [Pubkey::default(); TOKEN_COUNT],
+            hook_programs: [Pubkey::default(); MAX_HOOK_PROGRAMS],
+            caller_mode: CallerMode::Unrestricted,
+            allowed_callers: [Pubkey::default(); MAX_ALLOWED_CALLERS],
+            sunset_ts: 0,
+            lock_fee_share: PoolFee::default(),
+            lock_total_weight: 0,
+            lock_reward_growth: 0,
+            lock_rewards_unminted: 0,
+            lp_fee_growth: 0,
+            lp_non_transferable: false,
+            lp_supply_seconds: 0,
+            lp_supply_ts: 0,
+            min_lp_mint_amount: 0,
+            min_lp_residual_amount: 0,
+            launch_lp_cap: 0,
+            launch_end_ts: 0,
+            user_gate: UserGate::Open,
+            depositor_gate: UserGate::Open,
+            roles: [Pubkey::default(); ROLE_COUNT],
+            token_flags: [TokenFlags::default(); TOKEN_COUNT],
+            rfq_quoter_key: Pubkey::default(),
+            rfq_max_deviation: PoolFee::default(),
+            rfq_last_quote_id: 0,
+            prepared_rfq_quoter_key: Pubkey::default(),
+            prepared_rfq_max_deviation: PoolFee::default(),
+            rfq_quoter_transition_ts: 0,
+            compliance_program: Pubkey::default(),
+            trading_hours: TradingHours::default(),
+            maintenance_windows: [MaintenanceWindow::default(); MAX_MAINTENANCE_WINDOWS],
+            withdrawal_cooldown_threshold: 0,
+            withdrawal_cooldown: 0,
+            unannounced_burn_slot: 0,
+            unannounced_burn_amount: 0,
+            emergency_responders: [Pubkey::default(); MAX_EMERGENCY_RESPONDERS],
+            emergency_threshold: 0,
+            emergency_confirmation_ts: [0; MAX_EMERGENCY_RESPONDERS],
+            invariant_kind: InvariantKind::StableSwap,
+            token_weights: [0; TOKEN_COUNT],
+            price_band: PriceBand::default(),
+            depth_rates: [RATE_ONE; TOKEN_COUNT],
+            token_participation: [TokenParticipation::Active; TOKEN_COUNT],
+            peg_keeper: PegKeeper::default(),
+            prepared_peg_keeper: PegKeeper::default(),
+            peg_keeper_transition_ts: 0,
+            yield_adapters: [YieldAdapter::default(); TOKEN_COUNT],
+            prepared_yield_adapter: YieldAdapter::default(),
+            prepared_yield_adapter_index: 0,
+            yield_adapter_transition_ts: 0,
+            price_scale: PriceScale::default(),
+            rebate_campaign: Pubkey::default(),
+            rebate_end_ts: 0,
+            token_count: TOKEN_COUNT as u8,
+            layout_version: 0,
+        }
+    }
+}
+
+/// Seed (along with the pool's key) of the program address that holds a pool's `VirtualPriceState`
+pub const VIRTUAL_PRICE_SEED: &[u8] = b"virtual_price";
+/// Seed of the program addresses of `RoutedSwap`'s route authority and intermediate token accounts
+pub const ROUTE_SEED: &[u8] = b"route";
+/// Seed (along with the sorted token mints and the fee tier) of the program address of a `CreatePool` pool
+pub const POOL_SEED: &[u8] = b"pool";
+/// Seed (along with the pool's key) of the program address of a `CreatePool` pool's lp mint
+pub const LP_MINT_SEED: &[u8] = b"lp_mint";
+/// Seed (along with the pool's key and the token mint) of the program addresses of a `CreatePool` pool's token accounts
+/// and (along with the pool's key and the replaced token account) of those that `RotateVaults` creates
+pub const VAULT_SEED: &[u8] = b"vault";
+/// Seed (along with the pool's key) of the program address of a `CreatePool` pool's governance fee account
+pub const GOVERNANCE_FEE_SEED: &[u8] = b"governance_fee";
+/// Seed (along with the pool's key) of the program address of the account that `ExportState` writes to
+pub const EXPORT_SEED: &[u8] = b"export";
+/// Seed (along with the pool's key) of the program address of the token account that holds a pool's locked lp tokens
+/// and (along with the pool's key and the lock's owner) of the program addresses of `LpLock`s
+pub const LOCK_SEED: &[u8] = b"lock";
+
+/// The shortest lock that `LockLp` accepts
+pub const MIN_LOCK_DURATION: UnixTimestamp = 7 * 24 * 60 * 60;
+/// The longest lock that `LockLp` accepts, which earns the largest boost (see `lock_weight`)
+pub const MAX_LOCK_DURATION: UnixTimestamp = 4 * 365 * 24 * 60 * 60;
+/// One lp token per lp token (`PoolState::lp_fee_growth`) resp. per lock weight (`PoolState::lock_reward_growth`)
+pub const GROWTH_ONE: u128 = 1_000_000_000_000_000_000;
+
+/// The weight of `amount` lp tokens locked for `duration`: from the amount itself for the shortest lock up to 2.5 times
+/// the amount for MAX_LOCK_DURATION
+pub fn lock_weight(amount: u64, duration: UnixTimestamp) -> u128 {
+    let duration = duration.max(0).min(MAX_LOCK_DURATION) as u128;
+    amount as u128 * (2 * MAX_LOCK_DURATION as u128 + 3 * duration) / (2 * MAX_LOCK_DURATION as u128)
+}
+
+//lp tokens that their owner locked with LockLp, held by the pool's lock vault until unlock_ts
+#[derive(BorshSerialize, BorshDeserialize, BorshSchema, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct LpLock {
+    pub pool: Pubkey,
+    pub owner: Pubkey,
+    pub amount: u64,
+    pub weight: u128,
+    pub unlock_ts: UnixTimestamp,
+    //the pool's lock_reward_growth when the lock's rewards were last claimed
+    pub reward_growth_checkpoint: u128,
+    //the pool's lp_fee_growth when the lock's amount last changed
+    pub lp_fee_growth_checkpoint: u128,
+}
+
+impl LpLock {
+    /// The lp tokens the lock earned since its rewards were last claimed
+    pub fn pending_rewards(&self, lock_reward_growth: u128) -> u64 {
+        (self.weight * (lock_reward_growth - self.reward_growth_checkpoint) / GROWTH_ONE) as u64
+    }
+
+    /// Lp tokens' worth of the lp fees that the locked lp tokens earned since the lock's amount last changed
+    pub fn lp_fees_earned(&self, lp_fee_growth: u128) -> u64 {
+        lp_fees_earned(self.amount, lp_fee_growth - self.lp_fee_growth_checkpoint)
+    }
+}
+
+/// Lp tokens' worth of the lp fees that `lp_amount` lp tokens earned while the pool's `lp_fee_growth` grew by
+/// `lp_fee_growth_delta`
+pub fn lp_fees_earned(lp_amount: u64, lp_fee_growth_delta: u128) -> u64 {
+    (U256::from(lp_amount) * U256::from(lp_fee_growth_delta) / U256::from(GROWTH_ONE)).as_u64()
+}
+
+/// Seed (along with the pool's key and the lp token account's key) of the program address of an lp token account's
+/// `FeeCheckpoint`
+pub const FEE_CHECKPOINT_SEED: &[u8] = b"fee_checkpoint";
+
+//the balance of an lp token account and the pool's lp_fee_growth when CheckpointFees last recorded them, so that the
+// lp fees the account earned since then can be computed exactly as long as its balance didn't change in between
+// (programs that hold lp tokens record a checkpoint on every change)
+#[derive(BorshSerialize, BorshDeserialize, BorshSchema, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct FeeCheckpoint {
+    pub pool: Pubkey,
+    pub lp_account: Pubkey,
+    pub lp_amount: u64,
+    pub lp_fee_growth: u128,
+    pub ts: UnixTimestamp,
+}
+
+impl FeeCheckpoint {
+    /// Lp tokens' worth of the lp fees that the checkpoint's balance earned since it was recorded
+    pub fn lp_fees_earned(&self, lp_fee_growth: u128) -> u64 {
+        lp_fees_earned(self.lp_amount, lp_fee_growth - self.lp_fee_growth)
+    }
+}
+
+/// Seed (along with the pool's key) of the program address of a pool's `Gauge` and (along with the pool's key and the
+/// mint) of the token accounts that hold its staked lp tokens resp. its rewards
+pub const GAUGE_SEED: &[u8] = b"gauge";
+/// Seed (along with the pool's key and the staker) of the program addresses of `GaugeStake`s
+pub const GAUGE_STAKE_SEED: &[u8] = b"gauge_stake";
+/// The longest emission period that `FundGauge` accepts
+pub const MAX_GAUGE_DURATION: UnixTimestamp = 365 * 24 * 60 * 60;
+/// How many reward tokens a gauge can emit, retired ones included until their slot is reused (see
+/// `GovernanceInstruction::SetGaugeReward`)
+pub const MAX_GAUGE_REWARDS: usize = 8;
+/// How long after a retired reward's emissions ended stakes can claim it before its slot may go to a new reward token,
+/// which forfeits what the stakes didn't claim
+pub const GAUGE_CLAIM_PERIOD: UnixTimestamp = 90 * 24 * 60 * 60;
+/// Seed (along with the pool's key and the stake's owner) of the voting escrow program's program address that gauge
+/// instructions pass as the owner's ve account, e.g. the owner's escrow (see `instruction::VeBoostQuery`)
+pub const VE_BOOST_SEED: &[u8] = b"ve_boost";
+/// Seed (along with the pool's key) of a yield adapter's program address that `DeployYield` approves to transfer the
+/// deployed amount out of the pool's token account (see `instruction::find_yield_delegate_address`)
+pub const YIELD_DELEGATE_SEED: &[u8] = b"yield_delegate";
+/// The largest boost that a gauge's voting escrow program can give a stake, in basis points of its amount: 2.5 times
+/// the amount like the longest lock (see `lock_weight`)
+pub const MAX_GAUGE_BOOST_BPS: u64 = 25_000;
+
+//a pool's liquidity mining gauge (see SetGaugeReward): each of its rewards emits its token at a constant rate until
+// its emission_end_ts (see FundGauge), split among the stakes by their working amount, which is their amount unless
+// the gauge's voting escrow program boosts it (see SetGaugeVeProgram, which takes effect after ENACT_DELAY)
+#[derive(BorshSerialize, BorshDeserialize, BorshSchema, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Gauge {
+    pub pool: Pubkey,
+    pub total_staked: u64,
+    //the sum of the stakes' working amounts
+    pub total_working: u128,
+    //the default key if stakes aren't boosted
+    pub ve_program: Pubkey,
+    //the ve program that EnactGaugeVeProgram switches to once ve_program_transition_ts passed, 0 if none is prepared
+    pub prepared_ve_program: Pubkey,
+    pub ve_program_transition_ts: UnixTimestamp,
+    //when the rewards' reward_per_share was last brought up to date
+    pub last_update_ts: UnixTimestamp,
+    //unused slots have the default mint
+    pub rewards: [GaugeReward; MAX_GAUGE_REWARDS],
+}
+
+//one of a gauge's reward tokens, which only its funder can fund. Retiring the reward clears the funder, but the reward
+// keeps its slot so that stakes can still claim what they earned, and governance can add it again. Once the claim
+// period passed, governance can add a new reward token in its place instead (see Gauge::free_index)
+#[derive(BorshSerialize, BorshDeserialize, BorshSchema, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct GaugeReward {
+    pub mint: Pubkey,
+    pub funder: Pubkey,
+    //reward tokens per second
+    pub emission_rate: u64,
+    pub emission_end_ts: UnixTimestamp,
+    //reward tokens per working amount that the reward emitted since it was added, times GROWTH_ONE
+    pub reward_per_share: u128,
+    //how many reward tokens the slot held so far, which tells stakes' rewards of a previous token apart
+    pub generation: u32,
+}
+
+impl GaugeReward {
+    pub fn is_retired(&self) -> bool {
+        self.funder == Pubkey::default()
+    }
+
+    /// The reward tokens that the reward is yet to emit after `current_ts`
+    pub fn unemitted(&self, current_ts: UnixTimestamp) -> u64 {
+        let remaining = (self.emission_end_ts - current_ts).max(0) as u64;
+        remaining.saturating_mul(self.emission_rate)
+    }
+}
+
+impl Gauge {
+    /// Brings the `reward_per_share` of every reward up to `current_ts`. What the gauge emits while nothing is staked
+    /// isn't paid out
+    pub fn update(&mut self, current_ts: UnixTimestamp) {
+        for reward in self.rewards.iter_mut() {
+            let emitted_until = current_ts.min(reward.emission_end_ts);
+            if self.total_working > 0 && emitted_until > self.last_update_ts {
+                let emitted = U256::from(reward.emission_rate) * U256::from(emitted_until - self.last_update_ts);
+                reward.reward_per_share +=
+                    (emitted * U256::from(GROWTH_ONE) / U256::from(self.total_working)).as_u128();
+            }
+        }
+        self.last_update_ts = self.last_update_ts.max(current_ts);
+    }
+
+    /// The index of the reward that emits `mint`, if any
+    pub fn reward_index(&self, mint: &Pubkey) -> Option<usize> {
+        self.rewards
+            .iter()
+            .position(|reward| reward.mint == *mint && *mint != Pubkey::default())
+    }
+
+    /// The slot that a new reward token goes to at `current_ts`: an unused one or else one whose reward was retired
+    /// and ended at least `GAUGE_CLAIM_PERIOD` ago
+    pub fn free_index(&self, current_ts: UnixTimestamp) -> Option<usize> {
+        self.rewards
+            .iter()
+            .position(|reward| reward.mint == Pubkey::default())
+            .or_else(|| {
+                self.rewards.iter().position(|reward| {
+                    reward.is_retired() && reward.emission_end_ts.saturating_add(GAUGE_CLAIM_PERIOD) <= current_ts
+                })
+            })
+    }
+
+    /// Puts a new reward emitting `mint` into the slot at `index` (see `free_index`), stakes forfeit what they didn't
+    /// claim of the slot's previous reward
+    pub fn add_reward(&mut self, index: usize, mint: &Pubkey) {
+        self.rewards[index] = GaugeReward {
+            mint: *mint,
+            generation: self.rewards[index].generation + 1,
+            ..GaugeReward::default()
+        };
+    }
+
+    /// Emits `amount` more of the reward at `index`, along with those it is yet to emit, evenly over the `duration`
+    /// seconds after `current_ts`. None if they add up to more than a u64
+    pub fn fund(
+        &mut self,
+        index: usize,
+        amount: u64,
+        duration: UnixTimestamp,
+        current_ts: UnixTimestamp,
+    ) -> Option<()> {
+        self.update(current_ts);
+        let reward = &mut self.rewards[index];
+        let total = amount.checked_add(reward.unemitted(current_ts))?;
+        reward.emission_rate = total / duration as u64;
+        reward.emission_end_ts = current_ts + duration;
+        Some(())
+    }
+
+    /// Stops the emissions of the reward at `index` and returns the reward tokens it was yet to emit
+    pub fn retire(&mut self, index: usize, current_ts: UnixTimestamp) -> u64 {
+        self.update(current_ts);
+        let reward = &mut self.rewards[index];
+        let unemitted = reward.unemitted(current_ts);
+        reward.funder = Pubkey::default();
+        reward.emission_rate = 0;
+        reward.emission_end_ts = reward.emission_end_ts.min(current_ts);
+        unemitted
+    }
+}
+
+//lp tokens that their owner staked with a pool's gauge, held by the gauge's lp vault
+#[derive(BorshSerialize, BorshDeserialize, BorshSchema, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct GaugeStake {
+    pub pool: Pubkey,
+    pub owner: Pubkey,
+    pub amount: u64,
+    //the amount times the boost that the gauge's ve program last gave the stake (see apply_boost)
+    pub working_amount: u128,
+    //what the stake earned of the gauge's reward at the same index
+    pub rewards: [GaugeStakeReward; MAX_GAUGE_REWARDS],
+}
+
+#[derive(BorshSerialize, BorshDeserialize, BorshSchema, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct GaugeStakeReward {
+    //the reward's reward_per_share when the stake's rewards were last settled
+    pub reward_per_share_checkpoint: u128,
+    //reward tokens that were settled but not claimed yet
+    pub rewards_owed: u64,
+    //the generation of the gauge's reward that the checkpoint and rewards_owed are of
+    pub generation: u32,
+}
+
+impl GaugeStake {
+    /// Adds the reward tokens that the stake earned of each of the gauge's rewards since they were last settled to
+    /// their `rewards_owed`, dropping what it didn't claim of rewards whose slot went to a new reward token
+    pub fn settle(&mut self, gauge: &Gauge) {
+        for (stake_reward, reward) in self.rewards.iter_mut().zip(gauge.rewards.iter()) {
+            if stake_reward.generation != reward.generation {
+                *stake_reward = GaugeStakeReward {
+                    generation: reward.generation,
+                    ..GaugeStakeReward::default()
+                };
+            }
+            let earned = U256::from(self.working_amount)
+                * U256::from(reward.reward_per_share - stake_reward.reward_per_share_checkpoint)
+                / U256::from(GROWTH_ONE);
+            stake_reward.rewards_owed += earned.as_u64();
+            stake_reward.reward_per_share_checkpoint = reward.reward_per_share;
+        }
+    }
+
+    /// Sets the stake's working amount to its amount times `boost_bps` (in basis points), which is clamped between no
+    /// boost and MAX_GAUGE_BOOST_BPS, and the gauge's total working amount accordingly. The stake has to be settled
+    /// first, since what it earned so far accrued by its previous working amount
+    pub fn apply_boost(&mut self, gauge: &mut Gauge, boost_bps: u64) {
+        let boost_bps = boost_bps.max(BPS_ONE).min(MAX_GAUGE_BOOST_BPS);
+        let working_amount = self.amount as u128 * boost_bps as u128 / BPS_ONE as u128;
+        gauge.total_working = gauge.total_working - self.working_amount + working_amount;
+        self.working_amount = working_amount;
+    }
+
+    /// Whether the stake holds neither lp tokens nor unclaimed rewards, so it can be closed
+    pub fn is_empty(&self) -> bool {
+        self.amount == 0 && self.rewards.iter().all(|reward| reward.rewards_owed == 0)
+    }
+}
+
+/// Seed (along with the pool's key and the campaign's id) of the program address of a `RebateCampaign` and (along with
+/// the campaign's key) of the token account that escrows its budget
+pub const REBATE_CAMPAIGN_SEED: &[u8] = b"rebate_campaign";
+/// Seed (along with the campaign's key and the swapper's key) of the program addresses of `Rebate`s
+pub const REBATE_SEED: &[u8] = b"rebate";
+/// The longest window that `GovernanceInstruction::StartRebateCampaign` accepts
+pub const MAX_REBATE_WINDOW: UnixTimestamp = 365 * 24 * 60 * 60;
+/// How long after a campaign's window ended its rebates can be claimed before governance may reclaim what's left of
+/// its budget (see `GovernanceInstruction::ReclaimRebateBudget`)
+pub const REBATE_CLAIM_PERIOD: UnixTimestamp = 90 * 24 * 60 * 60;
+
+//a trading fee rebate campaign (see StartRebateCampaign): the fees that swaps of the campaign's joined swappers (see
+// JoinRebateCampaign) pay between start_ts and end_ts earn them a pro rata share of its budget, which they can claim
+// once the window ended (see ClaimRebate)
+#[derive(BorshSerialize, BorshDeserialize, BorshSchema, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct RebateCampaign {
+    pub pool: Pubkey,
+    pub id: u64,
+    pub rebate_mint: Pubkey,
+    pub budget: u64,
+    pub start_ts: UnixTimestamp,
+    pub end_ts: UnixTimestamp,
+    //the fees (in lp tokens, the liquidity providers' along with the governance's) that swaps recorded
+    pub total_fees: u128,
+    //whether governance reclaimed what was left of the budget, after which rebates can't be claimed anymore
+    pub reclaimed: bool,
+}
+
+impl RebateCampaign {
+    /// Whether swaps at `current_ts` record their fees
+    pub fn is_open(&self, current_ts: UnixTimestamp) -> bool {
+        self.start_ts <= current_ts && current_ts < self.end_ts
+    }
+
+    /// The share of the budget that a swapper's recorded `fees` earned
+    pub fn rebate(&self, fees: u128) -> u64 {
+        if self.total_fees == 0 {
+            return 0;
+        }
+        (U256::from(self.budget) * U256::from(fees) / U256::from(self.total_fees)).as_u64()
+    }
+}
+
+//the fees that a swapper's swaps recorded with a rebate campaign, closed once the swapper claimed the rebate
+#[derive(BorshSerialize, BorshDeserialize, BorshSchema, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Rebate {
+    pub campaign: Pubkey,
+    pub owner: Pubkey,
+    pub fees: u128,
+}
+
+/// Seed (along with the pool's key and the user's key) of the program address of an `AllowedUser`
+pub const ALLOWED_USER_SEED: &[u8] = b"allowed_user";
+/// Seed (along with the pool's key and the depositor's key) of the program address of an `AllowedUser` of the pool's
+/// depositor allowlist
+pub const ALLOWED_DEPOSITOR_SEED: &[u8] = b"allowed_depositor";
+
+//marks a user as allowed by governance while the pool's user (resp. depositor) gate is UserGate::Allowlist, it only
+// exists at its program address and is closed once governance disallows the user again
+#[derive(BorshSerialize, BorshDeserialize, BorshSchema, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct AllowedUser {
+    pub pool: Pubkey,
+    pub user: Pubkey,
+}
+
+/// Seed (along with the pool's key and the wallet's key) of the program address of a wallet's `LaunchAllocation`
+pub const LAUNCH_ALLOCATION_SEED: &[u8] = b"launch_allocation";
+
+//the lp tokens that adds minted to a wallet (their user transfer authority) during the pool's launch window ending at
+// launch_end_ts. a new window (see SetLaunchCap) starts the count over
+#[derive(BorshSerialize, BorshDeserialize, BorshSchema, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct LaunchAllocation {
+    pub pool: Pubkey,
+    pub owner: Pubkey,
+    pub launch_end_ts: UnixTimestamp,
+    pub lp_minted: u64,
+}
+
+impl LaunchAllocation {
+    /// The lp tokens minted to the wallet during the window ending at `launch_end_ts`
+    pub fn lp_minted_in(&self, launch_end_ts: UnixTimestamp) -> u64 {
+        if self.launch_end_ts == launch_end_ts {
+            self.lp_minted
+        } else {
+            0
+        }
+    }
+}
+
+/// Seed (along with the pool's key and the user's key) of the program address of a user's `Session`
+pub const SESSION_SEED: &[u8] = b"session";
+
+//a key that the user authorized with AuthorizeSession to sign its swaps in its place until expiry_ts. the session
+// account is the delegate of the user's token accounts, remaining_limits is what the key may still spend of each token
+#[derive(BorshSerialize, BorshDeserialize, BorshSchema, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Session<const TOKEN_COUNT: usize> {
+    pub pool: Pubkey,
+    pub user: Pubkey,
+    pub session_key: Pubkey,
+    pub expiry_ts: UnixTimestamp,
+    pub remaining_limits: [u64; TOKEN_COUNT],
+}
+
+/// Seed (along with the pool's key and the user's key) of the program address of a user's `WithdrawalAnnouncement`
+pub const WITHDRAWAL_ANNOUNCEMENT_SEED: &[u8] = b"withdrawal";
+
+//a user's announcement (see AnnounceWithdrawal) that it will burn up to lp_amount lp tokens in removes above the
+// pool's withdrawal cooldown threshold, which it can do once the pool's cooldown has passed since announced_ts and
+// for WITHDRAWAL_ANNOUNCEMENT_VALIDITY after. removes reduce lp_amount by what they burn
+#[derive(BorshSerialize, BorshDeserialize, BorshSchema, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WithdrawalAnnouncement {
+    pub pool: Pubkey,
+    pub user: Pubkey,
+    pub lp_amount: u64,
+    pub announced_ts: UnixTimestamp,
+}
+
+impl WithdrawalAnnouncement {
+    /// When removes can use the announcement given the pool's `withdrawal_cooldown`
+    pub fn ready_ts(&self, withdrawal_cooldown: UnixTimestamp) -> UnixTimestamp {
+        self.announced_ts + withdrawal_cooldown
+    }
+}
+
+/// `VirtualPriceState::price` is the value of a whole lp token in whole underlying tokens times 10^-VIRTUAL_PRICE_EXPO
+pub const VIRTUAL_PRICE_EXPO: i32 = -12;
+
+//the virtual price of a pool's lp token as published by PublishVirtualPrice, for protocols that price the lp token
+// (e.g. as collateral) without running an indexer. all fields have a fixed size, so they are at fixed offsets in the
+// account's data (little endian): pool 0, price 32, conf 40, expo 48, publish_time 52, publish_slot 60, is_paused 68,
+// lp_supply 69, depth 77
+#[derive(BorshSerialize, BorshDeserialize, BorshSchema, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VirtualPriceState {
+    pub pool: Pubkey,
+    pub price: u64,
+    //how far the price is off the price at the depth the pool recorded after its last instruction, i.e. how much it
+    // moved due to tokens sent to the pool directly or amp factor adjustments since then
+    pub conf: u64,
+    pub expo: i32,
+    pub publish_time: UnixTimestamp,
+    pub publish_slot: Slot,
+    //set if the pool is paused or swaps of any of its tokens are paused, consumers shouldn't rely on the price then
+    pub is_paused: bool,
+    pub lp_supply: u64,
+    pub depth: u128,
+}
+
+/// Seed (along with the pool's key and the user's key) of the compliance program's program address that defi
+/// instructions pass as the user's compliance account, e.g. the compliance program's attestation of the user
+pub const COMPLIANCE_SEED: &[u8] = b"compliance";
+
+/// Seed (along with the pool's key) of the program address that holds a pool's `LpSupplySnapshots`
+pub const LP_SUPPLY_SEED: &[u8] = b"lp_supply";
+/// How many checkpoints `LpSupplySnapshots` keeps
+pub const LP_SUPPLY_CHECKPOINTS: usize = 24;
+/// The least time between two checkpoints of `LpSupplySnapshots`, snapshots within it replace the latest checkpoint
+pub const LP_SUPPLY_CHECKPOINT_INTERVAL: UnixTimestamp = 60 * 60;
+
+//a pool's lp supply and lp_supply_seconds (see PoolState) at a SnapshotLpSupply, the average lp supply between two
+// checkpoints is the difference of their lp_supply_seconds divided by the time between them
+#[derive(BorshSerialize, BorshDeserialize, BorshSchema, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct LpSupplyCheckpoint {
+    pub lp_supply: u64,
+    pub lp_supply_seconds: u128,
+    pub ts: UnixTimestamp,
+    pub slot: Slot,
+}
+
+//the latest checkpoints that SnapshotLpSupply recorded, a ring buffer in which checkpoint_count % LP_SUPPLY_CHECKPOINTS
+// is the next checkpoint's slot, for programs (e.g. of points or airdrops) that weigh lp holdings by time
+#[derive(BorshSerialize, BorshDeserialize, BorshSchema, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct LpSupplySnapshots {
+    pub pool: Pubkey,
+    pub checkpoint_count: u64,
+    pub checkpoints: [LpSupplyCheckpoint; LP_SUPPLY_CHECKPOINTS],
+}
+
+impl LpSupplySnapshots {
+    pub fn latest(&self) -> Option<&LpSupplyCheckpoint> {
+        if self.checkpoint_count == 0 {
+            return None;
+        }
+        Some(&self.checkpoints[((self.checkpoint_count - 1) % LP_SUPPLY_CHECKPOINTS as u64) as usize])
+    }
+
+    /// Appends `checkpoint`, overwriting the oldest one once all slots are taken, or replaces the latest checkpoint if
+    /// it's less than LP_SUPPLY_CHECKPOINT_INTERVAL older
+    pub fn record(&mut self, checkpoint: LpSupplyCheckpoint) {
+        if let Some(latest) = self.latest() {
+            if checkpoint.ts - latest.ts < LP_SUPPLY_CHECKPOINT_INTERVAL {
+                let index = (self.checkpoint_count - 1) % LP_SUPPLY_CHECKPOINTS as u64;
+                self.checkpoints[index as usize] = checkpoint;
+                return;
+            }
+        }
+        self.checkpoints[(self.checkpoint_count % LP_SUPPLY_CHECKPOINTS as u64) as usize] = checkpoint;
+        self.checkpoint_count += 1;
+    }
+
+    /// The recorded checkpoints, oldest first
+    pub fn checkpoints(&self) -> Vec<LpSupplyCheckpoint> {
+        let count = self.checkpoint_count.min(LP_SUPPLY_CHECKPOINTS as u64) as usize;
+        let next = (self.checkpoint_count % LP_SUPPLY_CHECKPOINTS as u64) as usize;
+        (0..count)
+            .map(|i| self.checkpoints[(next + LP_SUPPLY_CHECKPOINTS - count + i) % LP_SUPPLY_CHECKPOINTS])
+            .collect()
+    }
+}
+
+/// Prefix of the message that defi instructions log with their `LpSupplyEvent`
+pub const LP_SUPPLY_EVENT_PREFIX: &str = "lp supply: ";
+
+//how a defi instruction changed the pool's lp supply: the lp tokens it minted to (positive) or burned from (negative)
+// the user's lp token account, the governance fee it minted (including the locks' share) and the lp supply afterwards
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct LpSupplyEvent {
+    pub user_lp_change: i128,
+    pub governance_mint_amount: u64,
+    pub lp_supply: u64,
+}
+
+impl LpSupplyEvent {
+    /// The message that the program logs, e.g. `lp supply: 1000500 user -500 governance 1`
+    pub fn to_log(&self) -> String {
+        format!(
+            "{}{} user {} governance {}",
+            LP_SUPPLY_EVENT_PREFIX, self.lp_supply, self.user_lp_change, self.governance_mint_amount
+        )
+    }
+
+    /// Parses a message of `to_log`, with or without the runtime's `Program log: ` prefix
+    pub fn from_log(log: &str) -> Option<Self> {
+        let log = log.strip_prefix("Program log: ").unwrap_or(log);
+        let mut words = log.strip_prefix(LP_SUPPLY_EVENT_PREFIX)?.split(' ');
+        let lp_supply = words.next()?.parse().ok()?;
+        if words.next()? != "user" {
+            return None;
+        }
+        let user_lp_change = words.next()?.parse().ok()?;
+        if words.next()? != "governance" {
+            return None;
+        }
+        let governance_mint_amount = words.next()?.parse().ok()?;
+        if words.next().is_some() {
+            return None;
+        }
+        Some(Self {
+            user_lp_change,
+            governance_mint_amount,
+            lp_supply,
+        })
+    }
+}
+
+/// Prefix of the message that `GovernanceInstruction::BurnGovernanceFees` logs with its `LpBurnEvent`
+pub const LP_BURN_EVENT_PREFIX: &str = "lp burn: ";
+
+//the governance fee lp tokens that governance burned, the lp supply afterwards and the resulting virtual price (see
+// VirtualPriceState::price) at the depth the pool recorded after its last instruction
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct LpBurnEvent {
+    pub burn_amount: u64,
+    pub lp_supply: u64,
+    pub virtual_price: u64,
+}
+
+impl LpBurnEvent {
+    /// The message that the program logs, e.g. `lp burn: 500 supply 1000000 price 1000500000000`
+    pub fn to_log(&self) -> String {
+        format!(
+            "{}{} supply {} price {}",
+            LP_BURN_EVENT_PREFIX, self.burn_amount, self.lp_supply, self.virtual_price
+        )
+    }
+
+    /// Parses a message of `to_log`, with or without the runtime's `Program log: ` prefix
+    pub fn from_log(log: &str) -> Option<Self> {
+        let log = log.strip_prefix("Program log: ").unwrap_or(log);
+        let mut words = log.strip_prefix(LP_BURN_EVENT_PREFIX)?.split(' ');
+        let burn_amount = words.next()?.parse().ok()?;
+        if words.next()? != "supply" {
+            return None;
+        }
+        let lp_supply = words.next()?.parse().ok()?;
+        if words.next()? != "price" {
+            return None;
+        }
+        let virtual_price = words.next()?.parse().ok()?;
+        if words.next().is_some() {
+            return None;
+        }
+        Some(Self {
+            burn_amount,
+            lp_supply,
+            virtual_price,
+        })
+    }
+}
+
+/// Prefix of the message that announces an upcoming `MaintenanceEvent`
+pub const MAINTENANCE_EVENT_PREFIX: &str = "maintenance: ";
+
+//a maintenance window that is about to start, announced by SetTradingSchedule and, from MAINTENANCE_NOTICE before its
+// start, by every defi instruction
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct MaintenanceEvent {
+    pub start_ts: UnixTimestamp,
+    pub end_ts: UnixTimestamp,
+}
+
+impl MaintenanceEvent {
+    /// The message that the program logs, e.g. `maintenance: 1700000000 until 1700003600`
+    pub fn to_log(&self) -> String {
+        format!("{}{} until {}", MAINTENANCE_EVENT_PREFIX, self.start_ts, self.end_ts)
+    }
+
+    /// Parses a message of `to_log`, with or without the runtime's `Program log: ` prefix
+    pub fn from_log(log: &str) -> Option<Self> {
+        let log = log.strip_prefix("Program log: ").unwrap_or(log);
+        let mut words = log.strip_prefix(MAINTENANCE_EVENT_PREFIX)?.split(' ');
+        let start_ts = words.next()?.parse().ok()?;
+        if words.next()? != "until" {
+            return None;
+        }
+        let end_ts = words.next()?.parse().ok()?;
+        if words.next().is_some() {
+            return None;
+        }
+        Some(Self { start_ts, end_ts })
+    }
+}
+
+/// Prefix of the message that logs a `TokenParticipationEvent`
+pub const TOKEN_PARTICIPATION_EVENT_PREFIX: &str = "token participation: ";
+
+//a token's participation changed (see SetTokenParticipation), so indexers can follow its onboarding or offboarding
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TokenParticipationEvent {
+    pub token_index: u8,
+    pub participation: TokenParticipation,
+}
+
+impl TokenParticipationEvent {
+    /// The message that the program logs, e.g. `token participation: 2 deposit-only`
+    pub fn to_log(&self) -> String {
+        let participation = match self.participation {
+            TokenParticipation::Active => "active",
+            TokenParticipation::DepositOnly => "deposit-only",
+            TokenParticipation::WithdrawOnly => "withdraw-only",
+        };
+        format!(
+            "{}{} {}",
+            TOKEN_PARTICIPATION_EVENT_PREFIX, self.token_index, participation
+        )
+    }
+
+    /// Parses a message of `to_log`, with or without the runtime's `Program log: ` prefix
+    pub fn from_log(log: &str) -> Option<Self> {
+        let log = log.strip_prefix("Program log: ").unwrap_or(log);
+        let mut words = log.strip_prefix(TOKEN_PARTICIPATION_EVENT_PREFIX)?.split(' ');
+        let token_index = words.next()?.parse().ok()?;
+        let participation = match words.next()? {
+            "active" => TokenParticipation::Active,
+            "deposit-only" => TokenParticipation::DepositOnly,
+            "withdraw-only" => TokenParticipation::WithdrawOnly,
+            _ => return None,
+        };
+        if words.next().is_some() {
+            return None;
+        }
+        Some(Self {
+            token_index,
+            participation,
+        })
+    }
+}
+
+/// Version of the `PoolExport` layout that `ExportState` writes
+pub const POOL_EXPORT_VERSION: u8 = 1;
+
+//the configuration of a pool that moved to another program (see ExportState), as read by ImportState of that program.
+// unlike PoolState it doesn't depend on TOKEN_COUNT and keeps its layout across program versions (new fields go at
+// the end with a new version), so programs built with a different TOKEN_COUNT or state layout can read it
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq, Eq)]
+pub struct PoolExport {
+    pub version: u8,
+    pub source_pool: Pubkey,
+    pub target_program: Pubkey,
+    pub target_pool: Pubkey,
+    pub lp_mint_key: Pubkey,
+    pub token_mint_keys: Vec<Pubkey>,
+    //the amp factor's value at the export, an adjustment in progress stops there
+    pub amp_factor: DecimalU64,
+    pub lp_fee: DecimalU64,
+    pub governance_fee: DecimalU64,
+    pub governance_key: Pubkey,
+    pub governance_fee_key: Pubkey,
+}
+
+#[cfg(all(test, not(feature = "test-bpf")))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lp_supply_snapshots_ring() {
+        let checkpoint = |ts| LpSupplyCheckpoint {
+            lp_supply: ts as u64,
+            ts,
+            ..LpSupplyCheckpoint::default()
+        };
+        let mut snapshots = LpSupplySnapshots::default();
+        assert_eq!(snapshots.latest(), None);
+        assert!(snapshots.checkpoints().is_empty());
+
+        snapshots.record(checkpoint(0));
+        snapshots.record(checkpoint(1));
+        assert_eq!(snapshots.checkpoint_count, 1);
+        assert_eq!(snapshots.latest().unwrap().ts, 1);
+
+        for i in 2..=LP_SUPPLY_CHECKPOINTS as i64 + 5 {
+            snapshots.record(checkpoint(i * LP_SUPPLY_CHECKPOINT_INTERVAL));
+        }
+        let checkpoints = snapshots.checkpoints();
+        assert_eq!(checkpoints.len(), LP_SUPPLY_CHECKPOINTS);
+        assert_eq!(checkpoints[0].ts, 6 * LP_SUPPLY_CHECKPOINT_INTERVAL);
+        assert_eq!(checkpoints.last(), snapshots.latest());
+        assert!(checkpoints.windows(2).all(|pair| pair[0].ts < pair[1].ts));
+    }
+
+    #[test]
+    fn lp_supply_event_log() {
+        let event = LpSupplyEvent {
+            user_lp_change: -500,
+            governance_mint_amount: 1,
+            lp_supply: 1000500,
+        };
+        assert_eq!(event.to_log(), "lp supply: 1000500 user -500 governance 1");
+        assert_eq!(LpSupplyEvent::from_log(&event.to_log()), Some(event));
+        assert_eq!(
+            LpSupplyEvent::from_log(&format!("Program log: {}", event.to_log())),
+            Some(event)
+        );
+        assert_eq!(LpSupplyEvent::from_log("lp supply: 1000500 user -500"), None);
+        assert_eq!(
+            LpSupplyEvent::from_log("lp supply: 1000500 user -500 governance 1 more"),
+            None
+        );
+    }
+
+    #[test]
+    fn maintenance_event_log() {
+        let event = MaintenanceEvent {
+            start_ts: 1_700_000_000,
+            end_ts: 1_700_003_600,
+        };
+        assert_eq!(event.to_log(), "maintenance: 1700000000 until 1700003600");
+        assert_eq!(MaintenanceEvent::from_log(&event.to_log()), Some(event));
+        assert_eq!(
+            MaintenanceEvent::from_log(&format!("Program log: {}", event.to_log())),
+            Some(event)
+        );
+        assert_eq!(MaintenanceEvent::from_log("maintenance: 1700000000"), None);
+    }
+
+    #[test]
+    fn token_participation_event_log() {
+        let event = TokenParticipationEvent {
+            token_index: 2,
+            participation: TokenParticipation::DepositOnly,
+        };
+        assert_eq!(event.to_log(), "token participation: 2 deposit-only");
+        assert_eq!(
+            TokenParticipationEvent::from_log(&format!("Program log: {}", event.to_log())),
+            Some(event)
+        );
+        assert_eq!(
+            TokenParticipationEvent::from_log("token participation: 2 bootstrap"),
+            None
+        );
+        assert_eq!(TokenParticipationEvent::from_log("token participation: 2"), None);
+    }
+
+    #[test]
+    fn token_participation() {
+        use TokenParticipation::*;
+        assert_eq!(TokenParticipation::default(), Active);
+        assert!(Active.allows_input(false) && Active.allows_output());
+        assert!(DepositOnly.allows_input(true));
+        assert!(!DepositOnly.allows_input(false) && !DepositOnly.allows_output());
+        assert!(!WithdrawOnly.allows_input(true) && WithdrawOnly.allows_output());
+    }
+
+    #[test]
+    fn peg_keeper_credit() {
+        assert!(!PegKeeper::default().is_set());
+        let mut peg_keeper = PegKeeper {
+            keeper_key: Pubkey::new_unique(),
+            token_index: 1,
+            credit_limit: 1_000,
+            debt: 400,
+            lp_amount: 390,
+        };
+        assert!(peg_keeper.is_set());
+        assert_eq!(peg_keeper.available_credit(), 600);
+        //a lowered limit leaves the keeper to absorb what it provided
+        peg_keeper.credit_limit = 300;
+        assert_eq!(peg_keeper.available_credit(), 0);
+    }
+
+    #[test]
+    fn trading_hours() {
+        let day = 19_675 * 86400;
+        let hours = |open_secs, close_secs| TradingHours { open_secs, close_secs };
+        assert!(TradingHours::default().is_open(day + 123));
+
+        let office = hours(9 * 3600, 17 * 3600);
+        assert!(office.is_valid());
+        assert!(!office.is_open(day + 9 * 3600 - 1));
+        assert!(office.is_open(day + 9 * 3600));
+        assert!(!office.is_open(day + 17 * 3600));
+
+        //overnight hours wrap past midnight
+        let overnight = hours(22 * 3600, 2 * 3600);
+        assert!(overnight.is_open(day + 23 * 3600));
+        assert!(overnight.is_open(day + 86400 + 3600));
+        assert!(!overnight.is_open(day + 12 * 3600));
+
+        assert!(!hours(86400, 0).is_valid());
+    }
+
+    #[test]
+    fn lp_burn_event_log() {
+        let event = LpBurnEvent {
+            burn_amount: 500,
+            lp_supply: 1000000,
+            virtual_price: 1000500000000,
+        };
+        assert_eq!(event.to_log(), "lp burn: 500 supply 1000000 price 1000500000000");
+        assert_eq!(
+            LpBurnEvent::from_log(&format!("Program log: {}", event.to_log())),
+            Some(event)
+        );
+        assert_eq!(LpSupplyEvent::from_log(&event.to_log()), None);
+        assert_eq!(LpBurnEvent::from_log("lp burn: 500 supply 1000000"), None);
+    }
+
+    #[test]
+    fn gauge_emissions() {
+        let funder = Pubkey::new_unique();
+        let mut gauge = Gauge::default();
+        gauge.rewards[0] = GaugeReward {
+            mint: Pubkey::new_unique(),
+            funder,
+            ..GaugeReward::default()
+        };
+        gauge.fund(0, 1_000, 100, 1_000).unwrap();
+        assert_eq!(
+            (gauge.rewards[0].emission_rate, gauge.rewards[0].emission_end_ts),
+            (10, 1_100)
+        );
+
+        //nothing is staked for the first 10 seconds
+        gauge.update(1_010);
+        assert_eq!(gauge.rewards[0].reward_per_share, 0);
+        let mut first = GaugeStake {
+            amount: 3,
+            ..GaugeStake::default()
+        };
+        first.apply_boost(&mut gauge, BPS_ONE);
+        gauge.update(1_040);
+        first.settle(&gauge);
+        assert_eq!(first.rewards[0].rewards_owed, 300);
+
+        //a second reward only emits from when it's funded
+        gauge.rewards[1] = GaugeReward {
+            mint: Pubkey::new_unique(),
+            funder,
+            ..GaugeReward::default()
+        };
+        gauge.fund(1, 3_000, 1_000, 1_040).unwrap();
+        let mut second = GaugeStake {
+            amount: 1,
+            ..GaugeStake::default()
+        };
+        second.settle(&gauge);
+        second.apply_boost(&mut gauge, BPS_ONE);
+        //the first reward's emissions end at 1_100
+        gauge.update(2_000);
+        first.settle(&gauge);
+        second.settle(&gauge);
+        assert_eq!(
+            (first.rewards[0].rewards_owed, second.rewards[0].rewards_owed),
+            (300 + 450, 150)
+        );
+        assert_eq!(
+            (first.rewards[1].rewards_owed, second.rewards[1].rewards_owed),
+            (3 * 960 * 3 / 4, 3 * 960 / 4)
+        );
+        assert_eq!(gauge.rewards[0].unemitted(2_000), 0);
+
+        //retiring refunds what's left and stops the emissions
+        assert_eq!(gauge.retire(1, 2_000), 3 * 40);
+        assert!(gauge.rewards[1].is_retired());
+        gauge.update(3_000);
+        first.settle(&gauge);
+        assert_eq!(first.rewards[1].rewards_owed, 3 * 960 * 3 / 4);
+        assert_eq!(gauge.reward_index(&gauge.rewards[1].mint), Some(1));
+        assert_eq!(gauge.reward_index(&Pubkey::default()), None);
+
+        //once all slots are taken, the retired reward's slot goes to a new token after the claim period, which resets
+        // what the stakes earned of it
+        for index in 2..MAX_GAUGE_REWARDS {
+            gauge.add_reward(index, &Pubkey::new_unique());
+            gauge.rewards[index].funder = funder;
+        }
+        assert_eq!(gauge.free_index(2_000 + GAUGE_CLAIM_PERIOD - 1), None);
+        assert_eq!(gauge.free_index(2_000 + GAUGE_CLAIM_PERIOD), Some(1));
+        let mint = Pubkey::new_unique();
+        gauge.add_reward(1, &mint);
+        gauge.rewards[1].funder = funder;
+        assert_eq!(gauge.reward_index(&mint), Some(1));
+        assert_eq!(gauge.rewards[1].generation, 1);
+        gauge.fund(1, 1_000, 100, 3_000).unwrap();
+        gauge.update(3_100);
+        first.settle(&gauge);
+        assert_eq!(
+            (first.rewards[1].rewards_owed, first.rewards[1].generation),
+            (1_000 * 3 / 4, 1)
+        );
+
+        //refunding midway carries over what's left
+        let mut gauge = Gauge::default();
+        gauge.fund(0, 1_000, 100, 1_000).unwrap();
+        gauge.fund(0, 500, 50, 1_050).unwrap();
+        assert_eq!(
+            (gauge.rewards[0].emission_rate, gauge.rewards[0].emission_end_ts),
+            (20, 1_100)
+        );
+        assert_eq!(gauge.fund(0, u64::MAX, 50, 1_050), None);
+    }
+
+    #[test]
+    fn gauge_boosts() {
+        let mut gauge = Gauge::default();
+        gauge.rewards[0].emission_rate = 35;
+        gauge.rewards[0].emission_end_ts = i64::MAX;
+        let mut boosted = GaugeStake {
+            amount: 10,
+            ..GaugeStake::default()
+        };
+        let mut unboosted = boosted;
+        boosted.apply_boost(&mut gauge, 2 * BPS_ONE);
+        //boosts are clamped to the range that the program accepts
+        unboosted.apply_boost(&mut gauge, BPS_ONE / 2);
+        assert_eq!((boosted.working_amount, unboosted.working_amount), (20, 10));
+        gauge.update(10);
+        boosted.settle(&gauge);
+        unboosted.settle(&gauge);
+        assert_eq!(
+            (boosted.rewards[0].rewards_owed, unboosted.rewards[0].rewards_owed),
+            (233, 116)
+        );
+
+        //the boost ran out
+        boosted.apply_boost(&mut gauge, 0);
+        assert_eq!(gauge.total_working, 20);
+        boosted.apply_boost(&mut gauge, u64::MAX);
+        assert_eq!(boosted.working_amount, 25);
+        assert_eq!(gauge.total_working, 35);
+    }
+
+    #[test]
+    fn rebate_shares() {
+        let mut campaign = RebateCampaign {
+            budget: 1_000,
+            start_ts: 100,
+            end_ts: 200,
+            ..RebateCampaign::default()
+        };
+        assert_eq!(campaign.rebate(0), 0);
+        assert!(!campaign.is_open(99) && campaign.is_open(100) && !campaign.is_open(200));
+
+        campaign.total_fees = 3;
+        assert_eq!((campaign.rebate(1), campaign.rebate(2)), (333, 666));
+        campaign.budget = u64::MAX;
+        campaign.total_fees = u128::MAX;
+        assert_eq!(campaign.rebate(u128::MAX), u64::MAX);
+    }
+
+    #[test]
+    fn pool_token_count_of_each_layout() {
+        let mut data = vec![0; get_packed_len::<PoolState<3>>()];
+        let len = data.len();
+        data[len - 2] = 3;
+        data[len - 1] = POOL_STATE_LAYOUT_VERSION;
+        assert_eq!(pool_token_count(&data), Some(3));
+        assert!(PoolState::<2>::unpack(&data).is_none());
+        //the recorded token count has to agree with the size
+        data[len - 2] = 2;
+        assert_eq!(pool_token_count(&data), None);
+
+        //the first layout doesn't record it
+        let data = vec![0; get_packed_len::<PoolStateV0<3>>()];
+        assert_eq!(pool_token_count(&data), Some(3));
+        assert_eq!(PoolState::<3>::unpack(&data).unwrap().layout_version, 0);
+        assert_eq!(pool_token_count(&data[1..]), None);
+    }
+
+    #[test]
+    fn max_token_count_pool_state_can_be_created() {
+        //CreatePool allocates the pool's state account through a cross-program invocation, which can't exceed this
+        assert!(
+            solana_program::borsh::get_packed_len::<PoolState<{ crate::MAX_TOKEN_COUNT }>>()
+                <= solana_program::entrypoint::MAX_PERMITTED_DATA_INCREASE
+        );
+    }
 }
@@ -0,0 +1,236 @@
+//the parts of the Token-2022 program that pools with a non-transferable lp token (see
+// PoolState::lp_non_transferable) rely on. the pinned spl-token release predates Token-2022 and its instruction
+// builders reject any other program id, so the few instructions and layouts needed are reproduced here
+//
+//Token-2022 keeps the spl token layouts for mints and accounts without extensions. mints with extensions are padded to
+// the length of an account, followed by a byte for the account type and the extensions as (u16 type, u16 length,
+// value) entries
+
+use solana_program::{
+    declare_id,
+    instruction::{AccountMeta, Instruction},
+    program_error::ProgramError,
+    program_option::COption,
+    program_pack::{IsInitialized, Pack},
+    pubkey::Pubkey,
+    system_program, sysvar,
+};
+use spl_token::{
+    instruction::TokenInstruction,
+    state::{Account as TokenState, Mint as MintState},
+};
+
+declare_id!("TokenzQdBNbLqP5VEhdkAS6EPFLC1PHnBqCXEpPxuEb");
+
+const ACCOUNT_TYPE_MINT: u8 = 1;
+const ACCOUNT_TYPE_ACCOUNT: u8 = 2;
+const EXTENSION_TYPE_UNINITIALIZED: u16 = 0;
+const EXTENSION_TYPE_NON_TRANSFERABLE: u16 = 9;
+const EXTENSION_TYPE_METADATA_POINTER: u16 = 18;
+const EXTENSION_TYPE_TOKEN_METADATA: u16 = 19;
+const INITIALIZE_NON_TRANSFERABLE_MINT: u8 = 32;
+
+/// Size of a mint whose only extension is NonTransferable
+pub const NON_TRANSFERABLE_MINT_LEN: usize = TokenState::LEN + 1 + 4;
+
+/// Unpacks the base state of a Token-2022 mint or token account, with or without extensions
+pub fn unpack<T: Pack + IsInitialized>(data: &[u8]) -> Result<T, ProgramError> {
+    let account_type = if T::LEN == MintState::LEN {
+        ACCOUNT_TYPE_MINT
+    } else {
+        ACCOUNT_TYPE_ACCOUNT
+    };
+    if data.len() == T::LEN {
+        return T::unpack(data);
+    }
+    if data.len() <= TokenState::LEN || data[TokenState::LEN] != account_type {
+        return Err(ProgramError::InvalidAccountData);
+    }
+    T::unpack(&data[..T::LEN])
+}
+
+pub fn unpack_mint(data: &[u8]) -> Result<MintState, ProgramError> {
+    unpack(data)
+}
+
+pub fn unpack_account(data: &[u8]) -> Result<TokenState, ProgramError> {
+    unpack(data)
+}
+
+/// The extension types of a Token-2022 mint
+pub fn mint_extension_types(data: &[u8]) -> Result<Vec<u16>, ProgramError> {
+    unpack_mint(data)?;
+    let mut extension_types = vec![];
+    let mut offset = TokenState::LEN + 1;
+    while offset + 4 <= data.len() {
+        let extension_type = u16::from_le_bytes([data[offset], data[offset + 1]]);
+        let length = u16::from_le_bytes([data[offset + 2], data[offset + 3]]) as usize;
+        if extension_type == EXTENSION_TYPE_UNINITIALIZED {
+            break;
+        }
+        extension_types.push(extension_type);
+        offset += 4 + length;
+    }
+    if offset > data.len() {
+        return Err(ProgramError::InvalidAccountData);
+    }
+    Ok(extension_types)
+}
+
+/// Whether a Token-2022 mint has the NonTransferable extension and no other extensions than its metadata, whose
+///  tokens then can only be minted and burned
+pub fn is_non_transferable_mint(data: &[u8]) -> bool {
+    match mint_extension_types(data) {
+        Ok(extension_types) => {
+            extension_types.contains(&EXTENSION_TYPE_NON_TRANSFERABLE)
+                && extension_types.iter().all(|extension_type| {
+                    matches!(
+                        *extension_type,
+                        EXTENSION_TYPE_NON_TRANSFERABLE
+                            | EXTENSION_TYPE_METADATA_POINTER
+                            | EXTENSION_TYPE_TOKEN_METADATA
+                    )
+                })
+        }
+        Err(_) => false,
+    }
+}
+
+pub fn mint_to(mint: &Pubkey, account: &Pubkey, owner: &Pubkey, amount: u64) -> Instruction {
+    Instruction {
+        program_id: id(),
+        accounts: vec![
+            AccountMeta::new(*mint, false),
+            AccountMeta::new(*account, false),
+            AccountMeta::new_readonly(*owner, true),
+        ],
+        data: TokenInstruction::MintTo { amount }.pack(),
+    }
+}
+
+pub fn burn(account: &Pubkey, mint: &Pubkey, owner: &Pubkey, amount: u64) -> Instruction {
+    Instruction {
+        program_id: id(),
+        accounts: vec![
+            AccountMeta::new(*account, false),
+            AccountMeta::new(*mint, false),
+            AccountMeta::new_readonly(*owner, true),
+        ],
+        data: TokenInstruction::Burn { amount }.pack(),
+    }
+}
+
+/// Must come before `initialize_mint`, on a mint account of `NON_TRANSFERABLE_MINT_LEN` bytes
+pub fn initialize_non_transferable_mint(mint: &Pubkey) -> Instruction {
+    Instruction {
+        program_id: id(),
+        accounts: vec![AccountMeta::new(*mint, false)],
+        data: vec![INITIALIZE_NON_TRANSFERABLE_MINT],
+    }
+}
+
+pub fn initialize_mint(mint: &Pubkey, mint_authority: &Pubkey, decimals: u8) -> Instruction {
+    Instruction {
+        program_id: id(),
+        accounts: vec![
+            AccountMeta::new(*mint, false),
+            AccountMeta::new_readonly(sysvar::rent::id(), false),
+        ],
+        data: TokenInstruction::InitializeMint {
+            decimals,
+            mint_authority: *mint_authority,
+            freeze_authority: COption::None,
+        }
+        .pack(),
+    }
+}
+
+/// The associated token account of `wallet` for a Token-2022 `mint`
+pub fn get_associated_token_address(wallet: &Pubkey, mint: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(
+        &[&wallet.to_bytes(), &id().to_bytes(), &mint.to_bytes()],
+        &spl_associated_token_account::id(),
+    )
+    .0
+}
+
+pub fn create_associated_token_account(payer: &Pubkey, wallet: &Pubkey, mint: &Pubkey) -> Instruction {
+    Instruction {
+        program_id: spl_associated_token_account::id(),
+        accounts: vec![
+            AccountMeta::new(*payer, true),
+            AccountMeta::new(get_associated_token_address(wallet, mint), false),
+            AccountMeta::new_readonly(*wallet, false),
+            AccountMeta::new_readonly(*mint, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new_readonly(id(), false),
+            AccountMeta::new_readonly(sysvar::rent::id(), false),
+        ],
+        data: vec![],
+    }
+}
+
+#[cfg(all(test, not(feature = "test-bpf")))]
+mod tests {
+    use super::*;
+
+    fn mint_data(extensions: &[(u16, usize)]) -> Vec<u8> {
+        let mut data = vec![0u8; MintState::LEN];
+        MintState::pack(
+            MintState {
+                mint_authority: COption::Some(Pubkey::new_unique()),
+                decimals: 6,
+                is_initialized: true,
+                ..MintState::default()
+            },
+            &mut data,
+        )
+        .unwrap();
+        if extensions.is_empty() {
+            return data;
+        }
+        data.resize(TokenState::LEN, 0);
+        data.push(ACCOUNT_TYPE_MINT);
+        for (extension_type, length) in extensions {
+            data.extend_from_slice(&extension_type.to_le_bytes());
+            data.extend_from_slice(&(*length as u16).to_le_bytes());
+            data.resize(data.len() + length, 0);
+        }
+        data
+    }
+
+    #[test]
+    fn test_unpack_mint() {
+        let data = mint_data(&[(EXTENSION_TYPE_NON_TRANSFERABLE, 0)]);
+        assert_eq!(data.len(), NON_TRANSFERABLE_MINT_LEN);
+        assert_eq!(unpack_mint(&data).unwrap().decimals, 6);
+        assert_eq!(unpack_mint(&mint_data(&[])).unwrap().decimals, 6);
+        //a mint isn't a token account
+        assert_eq!(unpack_account(&data), Err(ProgramError::InvalidAccountData));
+    }
+
+    #[test]
+    fn test_is_non_transferable_mint() {
+        assert!(is_non_transferable_mint(&mint_data(&[(
+            EXTENSION_TYPE_NON_TRANSFERABLE,
+            0
+        )])));
+        assert!(is_non_transferable_mint(&mint_data(&[
+            (EXTENSION_TYPE_METADATA_POINTER, 64),
+            (EXTENSION_TYPE_NON_TRANSFERABLE, 0),
+        ])));
+        assert!(!is_non_transferable_mint(&mint_data(&[])));
+        assert!(!is_non_transferable_mint(&mint_data(&[(
+            EXTENSION_TYPE_METADATA_POINTER,
+            64
+        )])));
+        //e.g. a permanent delegate could burn anybody's lp tokens
+        assert!(!is_non_transferable_mint(&mint_data(&[
+            (EXTENSION_TYPE_NON_TRANSFERABLE, 0),
+            (12, 32),
+        ])));
+        let mut truncated = mint_data(&[(EXTENSION_TYPE_METADATA_POINTER, 64)]);
+        truncated.truncate(truncated.len() - 1);
+        assert!(!is_non_transferable_mint(&truncated));
+    }
+}
@@ -0,0 +1,281 @@
+//the checks of defi instructions that only depend on the pool's decoded state, its balances and the instruction itself,
+// which the processor runs on execution and clients can run before sending an instruction, failing with the same
+// errors (codes and logged context) in both places
+//
+//checks of the instruction's accounts stay with the processor. the functions expect the executed instruction, i.e. the
+// one a `Simulate` wraps, and reject a `Simulate` like the processor rejects nested simulations. the processor runs
+// them in the order they are defined in, later ones expect an instruction that passed `check_amounts`
+
+use crate::{
+    error::PoolError,
+    instruction::{DeFiInstruction, SimulationResult},
+    state::{PoolState, TokenParticipation},
+};
+use solana_program::{clock::UnixTimestamp, entrypoint::ProgramResult, program_error::ProgramError};
+
+type AtomicT = u64;
+
+/// Whether the pool accepts the instruction at `current_ts`: paused and sunset pools only let users exit, maintenance
+///  windows, trading hours, token flags and token participation restrict everything but uniform removes
+pub fn check_status<const TOKEN_COUNT: usize>(
+    pool_state: &PoolState<TOKEN_COUNT>,
+    defi_instruction: &DeFiInstruction<TOKEN_COUNT>,
+    current_ts: UnixTimestamp,
+) -> ProgramResult {
+    if pool_state.is_paused && !defi_instruction.is_uniform_remove() {
+        return Err(PoolError::PoolIsPaused.into());
+    }
+    if pool_state.is_sunset() && (!defi_instruction.is_remove() || current_ts >= pool_state.sunset_ts) {
+        return Err(PoolError::PoolIsSunset.into());
+    }
+    //uniform removes ignore the token flags and the trading schedule, so users can always exit
+    if !defi_instruction.is_uniform_remove() {
+        check_trading_schedule(pool_state, current_ts)?;
+        let (inputs, outputs) = defi_instruction.token_directions();
+        let flags = &pool_state.token_flags;
+        if let Some(i) = (0..TOKEN_COUNT).position(|i| inputs[i] && flags[i].input_disabled) {
+            return Err(PoolError::TokenInputDisabled.with_context(format_args!("token index {}", i)));
+        }
+        if let Some(i) = (0..TOKEN_COUNT).position(|i| outputs[i] && flags[i].output_disabled) {
+            return Err(PoolError::TokenOutputDisabled.with_context(format_args!("token index {}", i)));
+        }
+        //only swaps and adds take tokens
+        let is_add = !defi_instruction.is_swap();
+        let participation = &pool_state.token_participation;
+        if let Some(i) = (0..TOKEN_COUNT).position(|i| {
+            (inputs[i] && !participation[i].allows_input(is_add)) || (outputs[i] && !participation[i].allows_output())
+        }) {
+            return Err(participation_error(participation[i]).with_context(format_args!("token index {}", i)));
+        }
+    }
+    Ok(())
+}
+
+/// The error of instructions that move a token in a way that its participation doesn't allow
+pub fn participation_error(participation: TokenParticipation) -> PoolError {
+    match participation {
+        TokenParticipation::WithdrawOnly => PoolError::TokenWithdrawOnly,
+        _ => PoolError::TokenDepositOnly,
+    }
+}
+
+/// Whether the pool trades at `current_ts`, i.e. isn't in maintenance and within its trading hours
+pub fn check_trading_schedule<const TOKEN_COUNT: usize>(
+    pool_state: &PoolState<TOKEN_COUNT>,
+    current_ts: UnixTimestamp,
+) -> ProgramResult {
+    if pool_state.current_maintenance(current_ts).is_some() {
+        return Err(PoolError::PoolInMaintenance.into());
+    }
+    if !pool_state.trading_hours.is_open(current_ts) {
+        return Err(PoolError::OutsideTradingHours.into());
+    }
+    Ok(())
+}
+
+/// Whether the instruction's amounts and token indices are well-formed for a pool with `pool_balances` and
+///  `lp_total_supply`
+pub fn check_amounts<const TOKEN_COUNT: usize>(
+    defi_instruction: &DeFiInstruction<TOKEN_COUNT>,
+    pool_balances: &[AtomicT; TOKEN_COUNT],
+    lp_total_supply: AtomicT,
+) -> ProgramResult {
+    let exceeds_balances = |amounts: &[AtomicT; TOKEN_COUNT]| {
+        amounts
+            .iter()
+            .zip(pool_balances.iter())
+            .any(|(amount, pool_balance)| *amount >= *pool_balance)
+    };
+    let is_valid = match defi_instruction {
+        DeFiInstruction::Add { input_amounts, .. } => {
+            if input_amounts.iter().all(|amount| *amount == 0) {
+                return Err(ProgramError::InvalidInstructionData);
+            }
+            //an empty pool needs all of its tokens
+            if lp_total_supply == 0 && input_amounts.iter().any(|amount| *amount == 0) {
+                return Err(PoolError::AddRequiresAllTokens.into());
+            }
+            true
+        }
+        DeFiInstruction::RemoveUniform { exact_burn_amount, .. } => {
+            *exact_burn_amount != 0 && *exact_burn_amount <= lp_total_supply
+        }
+        DeFiInstruction::SwapExactInput {
+            exact_input_amounts,
+            output_token_index,
+            ..
+        } => {
+            let output_token_index = *output_token_index as usize;
+            exact_input_amounts.iter().any(|amount| *amount != 0)
+                && output_token_index < TOKEN_COUNT
+                && exact_input_amounts[output_token_index] == 0
+        }
+        DeFiInstruction::SwapExactOutput {
+            input_token_index,
+            exact_output_amounts,
+            ..
+        } => {
+            let input_token_index = *input_token_index as usize;
+            exact_output_amounts.iter().any(|amount| *amount != 0)
+                && input_token_index < TOKEN_COUNT
+                && exact_output_amounts[input_token_index] == 0
+                && !exceeds_balances(exact_output_amounts)
+        }
+        DeFiInstruction::RemoveExactBurn {
+            exact_burn_amount,
+            output_token_index,
+            ..
+        } => {
+            (*output_token_index as usize) < TOKEN_COUNT
+                && *exact_burn_amount != 0
+                && *exact_burn_amount < lp_total_supply
+        }
+        DeFiInstruction::RemoveExactOutput {
+            maximum_burn_amount,
+            exact_output_amounts,
+        } => {
+            exact_output_amounts.iter().any(|amount| *amount != 0)
+                && *maximum_burn_amount != 0
+                && !exceeds_balances(exact_output_amounts)
+        }
+        //simulations can't be nested
+        DeFiInstruction::Simulate(_) => false,
+    };
+    if !is_valid {
+        return Err(ProgramError::InvalidInstructionData);
+    }
+    Ok(())
+}
+
+/// Swaps fail if any of their tokens has been paused by CheckPeg
+pub fn check_pegged<const TOKEN_COUNT: usize>(
+    pool_state: &PoolState<TOKEN_COUNT>,
+    defi_instruction: &DeFiInstruction<TOKEN_COUNT>,
+) -> ProgramResult {
+    let (amounts, token_index) = match defi_instruction {
+        DeFiInstruction::SwapExactInput {
+            exact_input_amounts,
+            output_token_index,
+            ..
+        } => (exact_input_amounts, *output_token_index as usize),
+        DeFiInstruction::SwapExactOutput {
+            input_token_index,
+            exact_output_amounts,
+            ..
+        } => (exact_output_amounts, *input_token_index as usize),
+        _ => return Ok(()),
+    };
+    if let Some(i) =
+        (0..TOKEN_COUNT).position(|i| pool_state.depegged_tokens[i] && (amounts[i] > 0 || i == token_index))
+    {
+        return Err(PoolError::TokenDepegged.with_context(format_args!("token index {}", i)));
+    }
+    Ok(())
+}
+
+/// Whether the amounts that the instruction results in (as quoted, see the `quote` module) are within the limits the
+///  user set, and adds mint at least the pool's minimum position
+pub fn check_limits<const TOKEN_COUNT: usize>(
+    pool_state: &PoolState<TOKEN_COUNT>,
+    defi_instruction: &DeFiInstruction<TOKEN_COUNT>,
+    result: &SimulationResult<TOKEN_COUNT>,
+) -> ProgramResult {
+    match defi_instruction {
+        DeFiInstruction::Add {
+            minimum_mint_amount, ..
+        } => {
+            if result.lp_amount < *minimum_mint_amount {
+                return Err(PoolError::MinimumMintNotMet.with_context(format_args!(
+                    "mint amount {} below minimum {}",
+                    result.lp_amount, minimum_mint_amount
+                )));
+            }
+            if result.lp_amount < pool_state.min_lp_mint_amount {
+                return Err(PoolError::DustPosition.into());
+            }
+        }
+        DeFiInstruction::RemoveUniform {
+            minimum_output_amounts, ..
+        } => {
+            let output_amounts = &result.output_amounts;
+            if let Some(i) = (0..TOKEN_COUNT).position(|i| output_amounts[i] < minimum_output_amounts[i]) {
+                return Err(PoolError::MinimumOutputNotMet.with_context(format_args!(
+                    "output amount {} of token index {} below minimum {}",
+                    output_amounts[i], i, minimum_output_amounts[i]
+                )));
+            }
+        }
+        DeFiInstruction::SwapExactInput {
+            output_token_index,
+            minimum_output_amount,
+            ..
+        }
+        | DeFiInstruction::RemoveExactBurn {
+            output_token_index,
+            minimum_output_amount,
+            ..
+        } => {
+            let output_amount = result.output_amounts[*output_token_index as usize];
+            if output_amount < *minimum_output_amount {
+                return Err(PoolError::MinimumOutputNotMet.with_context(format_args!(
+                    "output amount {} below minimum {}",
+                    output_amount, minimum_output_amount
+                )));
+            }
+        }
+        DeFiInstruction::SwapExactOutput {
+            maximum_input_amount,
+            input_token_index,
+            ..
+        } => {
+            let input_amount = result.input_amounts[*input_token_index as usize];
+            if input_amount > *maximum_input_amount {
+                return Err(PoolError::MaximumInputExceeded.with_context(format_args!(
+                    "input amount {} above maximum {}",
+                    input_amount, maximum_input_amount
+                )));
+            }
+        }
+        DeFiInstruction::RemoveExactOutput {
+            maximum_burn_amount, ..
+        } => {
+            if result.lp_amount > *maximum_burn_amount {
+                return Err(PoolError::MaximumBurnExceeded.with_context(format_args!(
+                    "burn amount {} above maximum {}",
+                    result.lp_amount, maximum_burn_amount
+                )));
+            }
+        }
+        DeFiInstruction::Simulate(_) => return Err(ProgramError::InvalidInstructionData),
+    }
+    Ok(())
+}
+
+/// Whether the pool's token accounts hold the amounts that the instruction pays out (as quoted). Tokens deployed to
+///  yield adapters count towards the pool's balances but have to be recalled first (see `RecallYield`)
+pub fn check_liquidity<const TOKEN_COUNT: usize>(
+    pool_balances: &[AtomicT; TOKEN_COUNT],
+    result: &SimulationResult<TOKEN_COUNT>,
+) -> ProgramResult {
+    let output_amounts = &result.output_amounts;
+    if let Some(i) = (0..TOKEN_COUNT).position(|i| output_amounts[i] > pool_balances[i]) {
+        return Err(PoolError::YieldBufferShort.with_context(format_args!(
+            "output amount {} of token index {} above balance {}",
+            output_amounts[i], i, pool_balances[i]
+        )));
+    }
+    Ok(())
+}
+
+/// Removes either empty the user's lp token account (holding `lp_balance`) or leave at least the pool's minimum
+///  position in it
+pub fn check_residual<const TOKEN_COUNT: usize>(
+    pool_state: &PoolState<TOKEN_COUNT>,
+    lp_balance: AtomicT,
+    burn_amount: AtomicT,
+) -> ProgramResult {
+    if pool_state.is_dust_residual(lp_balance.saturating_sub(burn_amount)) {
+        return Err(PoolError::DustPosition.into());
+    }
+    Ok(())
+}
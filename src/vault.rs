@@ -0,0 +1,547 @@
+//a reference implementation of an auto-compounding vault that integrates with the pool via cross-program invocation
+//it's meant to be read (and copied) as a worked example of the cpi module rather than deployed as is: a program that
+// wants to use it calls `process_instruction` from its own entrypoint, depending on this crate with the `vault` and
+// `no-entrypoint` features
+//
+//the vault holds lp tokens of one pool in an lp token account it owns and issues shares of them:
+// - Deposit adds the depositor's tokens to the pool with the vault's lp token account as the receiving lp account and
+//   mints shares in proportion to the lp tokens it got
+// - Withdraw burns the owner's shares and removes their share of the vault's lp tokens, uniformly, to their accounts
+// - Compound records the lp fees that the vault's lp tokens earned (see `PoolInstruction::CheckpointFees`) and adds
+//   whatever tokens ended up in the vault's token accounts (e.g. wrapped SOL sent to it) back to the pool
+//lp fees stay in the pool and increase what each lp token is worth, so the shares compound without the vault having
+// to claim anything
+
+use crate::{
+    common::create_array,
+    cpi::{add_cpi, remove_uniform_cpi, CpiContext, DeFiAccounts},
+    error::PoolError,
+    instruction::{create_checkpoint_fees_ix, find_fee_checkpoint_address},
+    state::PoolState,
+};
+use borsh::{BorshDeserialize, BorshSerialize};
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    entrypoint::ProgramResult,
+    instruction::{AccountMeta, Instruction},
+    msg,
+    program::{get_return_data, invoke, invoke_signed},
+    program_error::ProgramError,
+    program_option::COption,
+    program_pack::Pack,
+    pubkey::Pubkey,
+    rent::Rent,
+    system_instruction, system_program,
+    sysvar::Sysvar,
+};
+use spl_token::state::{Account as TokenState, Mint};
+
+type AmountT = u64;
+
+/// Seed (along with the pool's key) of the vault's program address, which is also the authority of its accounts
+pub const VAULT_SEED: &[u8] = b"vault";
+
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq, Eq)]
+pub struct VaultState<const TOKEN_COUNT: usize> {
+    pub bump: u8,
+    pub pool: Pubkey,
+    pub share_mint: Pubkey,
+    pub lp_account: Pubkey,
+    pub token_accounts: [Pubkey; TOKEN_COUNT],
+    //sum of the lp fees earned as reported by the pool's fee checkpoints, for display only
+    pub lp_fees_earned: AmountT,
+}
+
+impl<const TOKEN_COUNT: usize> VaultState<TOKEN_COUNT> {
+    pub const LEN: usize = 1 + 32 * (3 + TOKEN_COUNT) + 8;
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq, Eq)]
+pub enum VaultInstruction<const TOKEN_COUNT: usize> {
+    /// Creates the vault of a pool. Its share mint, lp token account and token accounts have to exist already, with
+    /// the vault (see `find_vault_address`) as their mint authority resp. owner
+    ///
+    /// Accounts expected by this instruction:
+    ///     0. `[]` The pool program
+    ///     1. `[]` The pool state account
+    ///     2. `[w]` vault account
+    ///     3. `[]` share mint, without supply or freeze authority
+    ///     4. `[]` vault's lp token account
+    ///     5. ..5 + TOKEN_COUNT `[]` vault's token accounts
+    ///     6 + TOKEN_COUNT. `[ws]` Payer
+    ///     7 + TOKEN_COUNT. `[]` System program
+    Init {},
+    /// Adds `input_amounts` to the pool and mints the depositor shares for the lp tokens the vault received
+    ///
+    /// Accounts expected by this instruction:
+    ///     0. `[]` The pool program
+    ///     1. `[]` vault account
+    ///     2. `[w]` share mint
+    ///     3. `[w]` depositor's share account
+    ///     4. .. the accounts of the pool's `Add`, with the depositor as user transfer authority and the vault's lp
+    ///        token account as user lp token account
+    Deposit {
+        input_amounts: [AmountT; TOKEN_COUNT],
+        minimum_share_amount: AmountT,
+    },
+    /// Burns `share_amount` of the owner's shares and removes the lp tokens they're worth uniformly
+    ///
+    /// Accounts expected by this instruction:
+    ///     0. `[]` The pool program
+    ///     1. `[]` vault account
+    ///     2. `[w]` share mint
+    ///     3. `[w]` owner's share account
+    ///     4. `[s]` owner of the share account
+    ///     5. .. the accounts of the pool's `RemoveUniform`, with the vault as user transfer authority, the owner's
+    ///        token accounts as user token accounts and the vault's lp token account as user lp token account
+    Withdraw {
+        share_amount: AmountT,
+        minimum_output_amounts: [AmountT; TOKEN_COUNT],
+    },
+    /// Checkpoints the vault's lp fees and adds the balances of the vault's token accounts to the pool, can be called
+    /// by anyone
+    ///
+    /// Accounts expected by this instruction:
+    ///     0. `[]` The pool program
+    ///     1. `[w]` vault account
+    ///     2. `[w]` fee checkpoint account of the vault's lp token account (see `find_fee_checkpoint_address`)
+    ///     3. `[ws]` Payer (only pays if the checkpoint account doesn't exist yet)
+    ///     4. `[]` System program
+    ///     5. .. the accounts of the pool's `Add`, with the vault as user transfer authority and its token accounts and
+    ///        lp token account as user token accounts resp. user lp token account
+    Compound {},
+}
+
+/// The address of the vault of `pool` and its bump seed
+pub fn find_vault_address(vault_program_id: &Pubkey, pool: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[VAULT_SEED, &pool.to_bytes()], vault_program_id)
+}
+
+/// Creates an `Init` instruction for the vault of `pool`
+#[allow(clippy::too_many_arguments)]
+pub fn create_init_ix<const TOKEN_COUNT: usize>(
+    vault_program_id: &Pubkey,
+    pool_program_id: &Pubkey,
+    pool: &Pubkey,
+    share_mint: &Pubkey,
+    lp_account: &Pubkey,
+    token_accounts: &[Pubkey; TOKEN_COUNT],
+    payer: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let mut accounts = vec![
+        AccountMeta::new_readonly(*pool_program_id, false),
+        AccountMeta::new_readonly(*pool, false),
+        AccountMeta::new(find_vault_address(vault_program_id, pool).0, false),
+        AccountMeta::new_readonly(*share_mint, false),
+        AccountMeta::new_readonly(*lp_account, false),
+    ];
+    accounts.extend(token_accounts.iter().map(|key| AccountMeta::new_readonly(*key, false)));
+    accounts.push(AccountMeta::new(*payer, true));
+    accounts.push(AccountMeta::new_readonly(system_program::id(), false));
+
+    Ok(Instruction {
+        program_id: *vault_program_id,
+        accounts,
+        data: VaultInstruction::<TOKEN_COUNT>::Init {}.try_to_vec()?,
+    })
+}
+
+/// Creates a `Deposit` instruction, `add_accounts` are those of the pool's `Add` instruction (e.g. as built by
+/// `create_defi_ix`) followed by the accounts it requires for the pool's configuration
+#[allow(clippy::too_many_arguments)]
+pub fn create_deposit_ix<const TOKEN_COUNT: usize>(
+    vault_program_id: &Pubkey,
+    pool_program_id: &Pubkey,
+    pool: &Pubkey,
+    share_mint: &Pubkey,
+    share_account: &Pubkey,
+    add_accounts: &[AccountMeta],
+    input_amounts: [AmountT; TOKEN_COUNT],
+    minimum_share_amount: AmountT,
+) -> Result<Instruction, ProgramError> {
+    let mut accounts = vec![
+        AccountMeta::new_readonly(*pool_program_id, false),
+        AccountMeta::new_readonly(find_vault_address(vault_program_id, pool).0, false),
+        AccountMeta::new(*share_mint, false),
+        AccountMeta::new(*share_account, false),
+    ];
+    accounts.extend_from_slice(add_accounts);
+
+    Ok(Instruction {
+        program_id: *vault_program_id,
+        accounts,
+        data: VaultInstruction::Deposit {
+            input_amounts,
+            minimum_share_amount,
+        }
+        .try_to_vec()?,
+    })
+}
+
+/// Creates a `Withdraw` instruction, `remove_accounts` are those of the pool's `RemoveUniform` instruction, with the
+/// vault as a non-signing user transfer authority
+#[allow(clippy::too_many_arguments)]
+pub fn create_withdraw_ix<const TOKEN_COUNT: usize>(
+    vault_program_id: &Pubkey,
+    pool_program_id: &Pubkey,
+    pool: &Pubkey,
+    share_mint: &Pubkey,
+    share_account: &Pubkey,
+    owner: &Pubkey,
+    remove_accounts: &[AccountMeta],
+    share_amount: AmountT,
+    minimum_output_amounts: [AmountT; TOKEN_COUNT],
+) -> Result<Instruction, ProgramError> {
+    let mut accounts = vec![
+        AccountMeta::new_readonly(*pool_program_id, false),
+        AccountMeta::new_readonly(find_vault_address(vault_program_id, pool).0, false),
+        AccountMeta::new(*share_mint, false),
+        AccountMeta::new(*share_account, false),
+        AccountMeta::new_readonly(*owner, true),
+    ];
+    accounts.extend_from_slice(remove_accounts);
+
+    Ok(Instruction {
+        program_id: *vault_program_id,
+        accounts,
+        data: VaultInstruction::Withdraw {
+            share_amount,
+            minimum_output_amounts,
+        }
+        .try_to_vec()?,
+    })
+}
+
+/// Creates a `Compound` instruction, `add_accounts` are those of the pool's `Add` instruction, with the vault as a
+/// non-signing user transfer authority
+pub fn create_compound_ix<const TOKEN_COUNT: usize>(
+    vault_program_id: &Pubkey,
+    pool_program_id: &Pubkey,
+    pool: &Pubkey,
+    lp_account: &Pubkey,
+    payer: &Pubkey,
+    add_accounts: &[AccountMeta],
+) -> Result<Instruction, ProgramError> {
+    let mut accounts = vec![
+        AccountMeta::new_readonly(*pool_program_id, false),
+        AccountMeta::new(find_vault_address(vault_program_id, pool).0, false),
+        AccountMeta::new(find_fee_checkpoint_address(pool_program_id, pool, lp_account).0, false),
+        AccountMeta::new(*payer, true),
+        AccountMeta::new_readonly(system_program::id(), false),
+    ];
+    accounts.extend_from_slice(add_accounts);
+
+    Ok(Instruction {
+        program_id: *vault_program_id,
+        accounts,
+        data: VaultInstruction::<TOKEN_COUNT>::Compound {}.try_to_vec()?,
+    })
+}
+
+/// The processor of the vault program, to be called from the entrypoint of the program that deploys it
+pub fn process_instruction<const TOKEN_COUNT: usize>(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    instruction_data: &[u8],
+) -> ProgramResult {
+    let instruction = VaultInstruction::<TOKEN_COUNT>::try_from_slice(instruction_data)
+        .map_err(|_| ProgramError::InvalidInstructionData)?;
+    match instruction {
+        VaultInstruction::Init {} => process_init::<TOKEN_COUNT>(program_id, accounts),
+        VaultInstruction::Deposit {
+            input_amounts,
+            minimum_share_amount,
+        } => process_deposit(program_id, accounts, input_amounts, minimum_share_amount),
+        VaultInstruction::Withdraw {
+            share_amount,
+            minimum_output_amounts,
+        } => process_withdraw(program_id, accounts, share_amount, minimum_output_amounts),
+        VaultInstruction::Compound {} => process_compound::<TOKEN_COUNT>(program_id, accounts),
+    }
+}
+
+fn process_init<const TOKEN_COUNT: usize>(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let pool_program_account = next_account_info(account_info_iter)?;
+    let pool_account = next_account_info(account_info_iter)?;
+    let vault_account = next_account_info(account_info_iter)?;
+    let share_mint_account = next_account_info(account_info_iter)?;
+    let lp_account = next_account_info(account_info_iter)?;
+    let token_accounts: Vec<&AccountInfo> = (0..TOKEN_COUNT)
+        .map(|_| next_account_info(account_info_iter))
+        .collect::<Result<_, _>>()?;
+    let payer_account = next_account_info(account_info_iter)?;
+    let system_program_account = next_account_info(account_info_iter)?;
+
+    if pool_account.owner != pool_program_account.key {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    let pool_state =
+        PoolState::<TOKEN_COUNT>::unpack(&pool_account.data.borrow()).ok_or(ProgramError::InvalidAccountData)?;
+    let (vault_key, bump) = find_vault_address(program_id, pool_account.key);
+    if *vault_account.key != vault_key {
+        return Err(PoolError::NonCanonicalAddress.into());
+    }
+    if vault_account.owner == program_id {
+        return Err(ProgramError::AccountAlreadyInitialized);
+    }
+    let share_mint = Mint::unpack(&share_mint_account.data.borrow())?;
+    if share_mint.mint_authority != COption::Some(vault_key)
+        || share_mint.supply != 0
+        || share_mint.freeze_authority.is_some()
+    {
+        return Err(PoolError::InvalidMintAccount.into());
+    }
+    check_vault_token_account(lp_account, &pool_state.lp_mint_key, &vault_key)?;
+    for (token_account, mint_key) in token_accounts.iter().zip(pool_state.token_mint_keys.iter()) {
+        check_vault_token_account(token_account, mint_key, &vault_key)?;
+    }
+
+    let vault_state = VaultState::<TOKEN_COUNT> {
+        bump,
+        pool: *pool_account.key,
+        share_mint: *share_mint_account.key,
+        lp_account: *lp_account.key,
+        token_accounts: create_array(|i| *token_accounts[i].key),
+        lp_fees_earned: 0,
+    };
+    let len = VaultState::<TOKEN_COUNT>::LEN;
+    invoke_signed(
+        &system_instruction::create_account(
+            payer_account.key,
+            vault_account.key,
+            Rent::get()?.minimum_balance(len),
+            len as u64,
+            program_id,
+        ),
+        &[
+            payer_account.clone(),
+            vault_account.clone(),
+            system_program_account.clone(),
+        ],
+        &[&[VAULT_SEED, &pool_account.key.to_bytes(), &[bump]]],
+    )?;
+    vault_state
+        .serialize(&mut &mut vault_account.try_borrow_mut_data()?[..])
+        .or(Err(ProgramError::AccountDataTooSmall))
+}
+
+fn process_deposit<const TOKEN_COUNT: usize>(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    input_amounts: [AmountT; TOKEN_COUNT],
+    minimum_share_amount: AmountT,
+) -> ProgramResult {
+    if accounts.len() < 4 {
+        return Err(ProgramError::NotEnoughAccountKeys);
+    }
+    let vault_account = &accounts[1];
+    let share_mint_account = &accounts[2];
+    let share_account = &accounts[3];
+    let vault_state = check_and_deserialize_vault::<TOKEN_COUNT>(program_id, vault_account)?;
+    let ctx = vault_cpi_context(&accounts[0], &accounts[4..], &vault_state)?;
+    if *share_mint_account.key != vault_state.share_mint {
+        return Err(PoolError::InvalidMintAccount.into());
+    }
+    ctx.accounts.check_input_authority(&input_amounts)?;
+
+    let share_supply = Mint::unpack(&share_mint_account.data.borrow())?.supply;
+    let lp_account = ctx.accounts.user_lp_account()?;
+    let lp_before = token_balance(lp_account)?;
+    add_cpi(&ctx, input_amounts, 0)?;
+    let lp_minted = token_balance(lp_account)? - lp_before;
+    //shares are rounded down, in favor of the vault's other share holders
+    let share_amount = if share_supply == 0 || lp_before == 0 {
+        lp_minted
+    } else {
+        (lp_minted as u128 * share_supply as u128 / lp_before as u128) as AmountT
+    };
+    if share_amount == 0 || share_amount < minimum_share_amount {
+        return Err(PoolError::MinimumMintNotMet.with_context(format_args!(
+            "share amount {} below minimum {}",
+            share_amount, minimum_share_amount
+        )));
+    }
+
+    let pool_seed = vault_state.pool.to_bytes();
+    let seeds: &[&[u8]] = &[VAULT_SEED, &pool_seed, &[vault_state.bump]];
+    invoke_signed(
+        &spl_token::instruction::mint_to(
+            share_mint_account.owner,
+            share_mint_account.key,
+            share_account.key,
+            vault_account.key,
+            &[],
+            share_amount,
+        )?,
+        &[share_mint_account.clone(), share_account.clone(), vault_account.clone()],
+        &[seeds],
+    )?;
+    msg!("vault deposit: {} lp for {} shares", lp_minted, share_amount);
+    Ok(())
+}
+
+fn process_withdraw<const TOKEN_COUNT: usize>(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    share_amount: AmountT,
+    minimum_output_amounts: [AmountT; TOKEN_COUNT],
+) -> ProgramResult {
+    if accounts.len() < 5 {
+        return Err(ProgramError::NotEnoughAccountKeys);
+    }
+    let vault_account = &accounts[1];
+    let share_mint_account = &accounts[2];
+    let share_account = &accounts[3];
+    let owner_account = &accounts[4];
+    let vault_state = check_and_deserialize_vault::<TOKEN_COUNT>(program_id, vault_account)?;
+    let ctx = vault_cpi_context(&accounts[0], &accounts[5..], &vault_state)?;
+    if *share_mint_account.key != vault_state.share_mint {
+        return Err(PoolError::InvalidMintAccount.into());
+    }
+    if *ctx.accounts.user_transfer_authority.key != *vault_account.key {
+        return Err(ProgramError::InvalidArgument);
+    }
+
+    let share_supply = Mint::unpack(&share_mint_account.data.borrow())?.supply;
+    if share_amount == 0 || share_amount > share_supply {
+        return Err(ProgramError::InsufficientFunds);
+    }
+    let lp_balance = token_balance(ctx.accounts.user_lp_account()?)?;
+    //rounded down, in favor of the remaining share holders
+    let burn_amount = (share_amount as u128 * lp_balance as u128 / share_supply as u128) as AmountT;
+
+    invoke(
+        &spl_token::instruction::burn(
+            share_mint_account.owner,
+            share_account.key,
+            share_mint_account.key,
+            owner_account.key,
+            &[],
+            share_amount,
+        )?,
+        &[share_account.clone(), share_mint_account.clone(), owner_account.clone()],
+    )?;
+    let pool_seed = vault_state.pool.to_bytes();
+    let seeds: &[&[u8]] = &[VAULT_SEED, &pool_seed, &[vault_state.bump]];
+    remove_uniform_cpi(&ctx.with_signer(&[seeds]), burn_amount, minimum_output_amounts)?;
+    msg!("vault withdraw: {} shares for {} lp", share_amount, burn_amount);
+    Ok(())
+}
+
+fn process_compound<const TOKEN_COUNT: usize>(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    if accounts.len() < 5 {
+        return Err(ProgramError::NotEnoughAccountKeys);
+    }
+    let pool_program_account = &accounts[0];
+    let vault_account = &accounts[1];
+    let checkpoint_account = &accounts[2];
+    let payer_account = &accounts[3];
+    let system_program_account = &accounts[4];
+    let mut vault_state = check_and_deserialize_vault::<TOKEN_COUNT>(program_id, vault_account)?;
+    let ctx = vault_cpi_context(pool_program_account, &accounts[5..], &vault_state)?;
+    if *ctx.accounts.user_transfer_authority.key != *vault_account.key {
+        return Err(ProgramError::InvalidArgument);
+    }
+    if ctx
+        .accounts
+        .user_token_accounts
+        .iter()
+        .map(|account| account.key)
+        .ne(vault_state.token_accounts.iter())
+    {
+        return Err(ProgramError::InvalidArgument);
+    }
+    let pool_seed = vault_state.pool.to_bytes();
+    let seeds: &[&[u8]] = &[VAULT_SEED, &pool_seed, &[vault_state.bump]];
+    let lp_account = ctx.accounts.user_lp_account()?;
+
+    invoke_signed(
+        &create_checkpoint_fees_ix::<TOKEN_COUNT>(
+            pool_program_account.key,
+            &vault_state.pool,
+            lp_account.key,
+            vault_account.key,
+            payer_account.key,
+        )?,
+        &[
+            ctx.accounts.pool.clone(),
+            lp_account.clone(),
+            vault_account.clone(),
+            checkpoint_account.clone(),
+            payer_account.clone(),
+            system_program_account.clone(),
+            pool_program_account.clone(),
+        ],
+        &[seeds],
+    )?;
+    let lp_fees_earned = match get_return_data() {
+        Some((program_id, data)) if program_id == *pool_program_account.key => AmountT::try_from_slice(&data)?,
+        _ => return Err(ProgramError::InvalidAccountData),
+    };
+    vault_state.lp_fees_earned += lp_fees_earned;
+
+    let mut input_amounts = [0; TOKEN_COUNT];
+    for (input_amount, token_account) in input_amounts.iter_mut().zip(ctx.accounts.user_token_accounts.iter()) {
+        *input_amount = token_balance(token_account)?;
+    }
+    if input_amounts.iter().any(|amount| *amount > 0) {
+        add_cpi(&ctx.with_signer(&[seeds]), input_amounts, 0)?;
+    }
+
+    vault_state
+        .serialize(&mut &mut vault_account.try_borrow_mut_data()?[..])
+        .or(Err(ProgramError::AccountDataTooSmall))?;
+    msg!(
+        "vault compound: {} lp fees earned, added {:?}",
+        lp_fees_earned,
+        input_amounts
+    );
+    Ok(())
+}
+
+fn check_and_deserialize_vault<const TOKEN_COUNT: usize>(
+    program_id: &Pubkey,
+    vault_account: &AccountInfo,
+) -> Result<VaultState<TOKEN_COUNT>, ProgramError> {
+    if vault_account.owner != program_id {
+        return Err(ProgramError::UninitializedAccount);
+    }
+    Ok(VaultState::deserialize(&mut &**vault_account.data.borrow())?)
+}
+
+//the pool accounts of a deposit, withdrawal or compounding, which have to be those of the vault's pool and lp account
+fn vault_cpi_context<'a, 'b, const TOKEN_COUNT: usize>(
+    pool_program_account: &AccountInfo<'a>,
+    defi_accounts: &[AccountInfo<'a>],
+    vault_state: &VaultState<TOKEN_COUNT>,
+) -> Result<CpiContext<'a, 'b, TOKEN_COUNT>, ProgramError> {
+    let defi_accounts = DeFiAccounts::from_slice(defi_accounts, true)?;
+    if *defi_accounts.pool.key != vault_state.pool || defi_accounts.pool.owner != pool_program_account.key {
+        return Err(ProgramError::InvalidArgument);
+    }
+    if *defi_accounts.user_lp_account()?.key != vault_state.lp_account {
+        return Err(ProgramError::InvalidArgument);
+    }
+    Ok(CpiContext::new(pool_program_account.clone(), defi_accounts))
+}
+
+fn check_vault_token_account(token_account: &AccountInfo, mint: &Pubkey, vault: &Pubkey) -> ProgramResult {
+    //the lp token account may belong to Token-2022, whose accounts start out like those of the token program
+    let token_state = TokenState::unpack_from_slice(&token_account.data.borrow()[..TokenState::LEN])?;
+    if token_state.mint != *mint {
+        return Err(PoolError::InvalidMintAccount.into());
+    }
+    if token_state.owner != *vault || token_state.delegate.is_some() || token_state.close_authority.is_some() {
+        return Err(ProgramError::InvalidAccountData);
+    }
+    Ok(())
+}
+
+fn token_balance(token_account: &AccountInfo) -> Result<AmountT, ProgramError> {
+    let data = token_account.data.borrow();
+    if data.len() < TokenState::LEN {
+        return Err(ProgramError::InvalidAccountData);
+    }
+    Ok(TokenState::unpack_from_slice(&data[..TokenState::LEN])?.amount)
+}
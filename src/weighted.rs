@@ -0,0 +1,679 @@
+//the weighted constant product invariant of pools initialized with InitWeighted (see state::InvariantKind): swaps keep
+// the product of the balances, each raised to its token's weight, constant, so the pool holds a fixed share of its
+// value in each token at any price, which lets it pair tokens of unrelated values. its depth is
+// prod((balance_i / weight_i)^weight_i), which like the stable swap invariant's depth is the sum of the balances when
+// they are in the proportion of the weights
+//
+//the math runs in fixed point (see log_exp), so its results are the same on every machine. changes of the depth are
+// computed from the ratios of the balances before and after rather than as differences of depths, so small amounts
+// keep their precision. the logarithms and exponentials come with bounds of their errors, and each step takes the
+// bound in the pool's favor: amounts it pays out or mints are rounded down, amounts it receives or burns are rounded
+// up, so rounding errors never pay out to the user
+//
+//fees work like the stable swap invariant's: swaps charge them on the input, adds and removes on the part of the
+// amounts that goes beyond the pool's proportions (i.e. what a swap would have traded), and the governance fee's share
+// of the fee is minted as lp tokens
+
+use crate::{
+    common::create_array,
+    decimal::{DecimalU64, U256},
+    invariant::{AmountT, InvariantError, InvariantStage},
+    log_exp::{exp_down, exp_up, ln_ratio, MAX_LN_ERROR, ONE},
+    oracle::EMA_ONE,
+};
+
+type DecT = DecimalU64;
+type InvariantResult<T> = Result<T, InvariantError>;
+
+/// Token weights are parts of WEIGHT_ONE, the weights of a pool's tokens add up to it
+pub const WEIGHT_ONE: u32 = 1_000_000;
+/// The smallest weight of a token (1%), the balance of a token with a smaller weight would have to move too much for
+/// its price to follow the market
+pub const MIN_WEIGHT: u32 = WEIGHT_ONE / 100;
+
+const ONE_U128: u128 = ONE as u128;
+
+/// Whether `weights` can be the weights of a pool's tokens
+pub fn is_valid_weights<const TOKEN_COUNT: usize>(weights: &[u32; TOKEN_COUNT]) -> bool {
+    weights.iter().all(|weight| *weight >= MIN_WEIGHT)
+        && weights.iter().map(|weight| *weight as u64).sum::<u64>() == WEIGHT_ONE as u64
+}
+
+/// The marginal prices of the pool's tokens in units of its first token (scaled by 10^EMA_DECIMALS) at the given
+/// (equalized and rated) balances, like `oracle::marginal_prices` for the stable swap invariant. `None` for an empty
+/// pool or if the balances are so far apart that the prices don't fit
+pub fn marginal_prices<const TOKEN_COUNT: usize>(
+    balances: &[u128; TOKEN_COUNT],
+    weights: &[u32; TOKEN_COUNT],
+) -> Option<[u64; TOKEN_COUNT]> {
+    if balances.iter().any(|balance| *balance == 0) {
+        return None;
+    }
+    //the partial derivative of the invariant by balance i is proportional to weight_i / balance_i, a swap trades
+    // tokens at the ratio of the derivatives
+    let mut prices = [0; TOKEN_COUNT];
+    for i in 0..TOKEN_COUNT {
+        let price = U256::from(weights[i]) * U256::from(balances[0]) * U256::from(EMA_ONE)
+            / (U256::from(weights[0]) * U256::from(balances[i]));
+        if price > U256::from(u64::MAX) {
+            return None;
+        }
+        prices[i] = price.as_u64();
+    }
+    Some(prices)
+}
+
+pub struct WeightedInvariant<const TOKEN_COUNT: usize>;
+impl<const TOKEN_COUNT: usize> WeightedInvariant<TOKEN_COUNT> {
+    pub fn add(
+        input_amounts: &[AmountT; TOKEN_COUNT],
+        pool_balances: &[AmountT; TOKEN_COUNT],
+        weights: &[u32; TOKEN_COUNT],
+        lp_fee: DecT,
+        governance_fee: DecT,
+        lp_total_supply: AmountT,
+    ) -> InvariantResult<(AmountT, AmountT, AmountT)> {
+        let inputs = to_u128s(input_amounts);
+        let balances = to_u128s(pool_balances);
+        let updated_balances = add_amounts(&balances, &inputs)?;
+        if lp_total_supply.is_zero() {
+            let depth = depth_of(&updated_balances, weights)?;
+            return Ok((depth, AmountT::zero(), depth));
+        }
+        nonzero_balances(&balances)?;
+        let scaled_weights = to_scaled_weights(weights);
+        let (total_fee, governance_share) = fee_parts(lp_fee, governance_fee);
+        let growth = ln_growth(&updated_balances, &balances, &scaled_weights)?;
+        //the pool's balances grown in proportion to the depth, the inputs beyond them pay the fee (the lower bound of
+        // the growth leaves the larger excess)
+        let proportional_growth = to_factor(exp_down(growth.down), InvariantStage::Fees)?.saturating_sub(ONE_U128);
+        let mut fee_adjusted_balances = updated_balances;
+        for i in 0..TOKEN_COUNT {
+            let proportional_input = mul_down(balances[i], proportional_growth, InvariantStage::Fees)?;
+            let excess = inputs[i].saturating_sub(proportional_input);
+            fee_adjusted_balances[i] -= mul_up(excess, total_fee, InvariantStage::Fees)?.min(inputs[i]);
+        }
+        let fee_adjusted_growth = ln_growth(&fee_adjusted_balances, &balances, &scaled_weights)?;
+
+        let lp_total_supply = lp_total_supply.as_u128();
+        let mint_growth = to_factor(exp_down(fee_adjusted_growth.down), InvariantStage::LpSupply)?;
+        let mint_amount = mul_down(
+            lp_total_supply,
+            mint_growth.saturating_sub(ONE_U128),
+            InvariantStage::LpSupply,
+        )?;
+        //the fees make up 1 - e^(fee_adjusted_growth - growth) of the latest depth
+        let governance_mint_amount = governance_mint(
+            lp_total_supply
+                .checked_add(mint_amount)
+                .ok_or(InvariantError::Overflow {
+                    stage: InvariantStage::LpSupply,
+                })?,
+            fee_adjusted_growth.minus(growth),
+            governance_share,
+        )?;
+        Ok((
+            AmountT::from(mint_amount),
+            governance_mint_amount,
+            depth_of(&updated_balances, weights)?,
+        ))
+    }
+
+    pub fn swap_exact_input(
+        input_amounts: &[AmountT; TOKEN_COUNT],
+        output_index: usize,
+        pool_balances: &[AmountT; TOKEN_COUNT],
+        weights: &[u32; TOKEN_COUNT],
+        lp_fee: DecT,
+        governance_fee: DecT,
+        lp_total_supply: AmountT,
+    ) -> InvariantResult<(AmountT, AmountT, AmountT)> {
+        let inputs = to_u128s(input_amounts);
+        let balances = to_u128s(pool_balances);
+        nonzero_balances(&balances)?;
+        let scaled_weights = to_scaled_weights(weights);
+        let (total_fee, governance_share) = fee_parts(lp_fee, governance_fee);
+        let mut fee_adjusted_inputs = inputs;
+        for input in fee_adjusted_inputs.iter_mut() {
+            *input -= mul_up(*input, total_fee, InvariantStage::Fees)?.min(*input);
+        }
+        //the output balance shrinks by as much as the inputs after fees grow the rest of the product
+        let fee_adjusted_balances = add_amounts(&balances, &fee_adjusted_inputs)?;
+        let input_growth =
+            ln_growth(&fee_adjusted_balances, &balances, &scaled_weights)?.per_weight(scaled_weights[output_index]);
+        let remaining_share = to_factor(exp_up(-input_growth.down), InvariantStage::UnknownBalance)?;
+        let output_balance = balances[output_index];
+        let output_amount = mul_down(
+            output_balance,
+            ONE_U128.saturating_sub(remaining_share),
+            InvariantStage::UnknownBalance,
+        )?;
+        if output_amount >= output_balance {
+            return Err(InvariantError::Underflow {
+                stage: InvariantStage::UnknownBalance,
+            });
+        }
+        let mut updated_balances = add_amounts(&balances, &inputs)?;
+        updated_balances[output_index] = output_balance - output_amount;
+        let (governance_mint_amount, latest_depth) = Self::swap_fees(
+            &updated_balances,
+            &balances,
+            weights,
+            governance_share,
+            lp_total_supply.as_u128(),
+        )?;
+        Ok((AmountT::from(output_amount), governance_mint_amount, latest_depth))
+    }
+
+    pub fn swap_exact_output(
+        input_index: usize,
+        output_amounts: &[AmountT; TOKEN_COUNT],
+        pool_balances: &[AmountT; TOKEN_COUNT],
+        weights: &[u32; TOKEN_COUNT],
+        lp_fee: DecT,
+        governance_fee: DecT,
+        lp_total_supply: AmountT,
+    ) -> InvariantResult<(AmountT, AmountT, AmountT)> {
+        let outputs = to_u128s(output_amounts);
+        let balances = to_u128s(pool_balances);
+        nonzero_balances(&balances)?;
+        if (0..TOKEN_COUNT).any(|i| outputs[i] >= balances[i]) {
+            return Err(InvariantError::Underflow {
+                stage: InvariantStage::Balances,
+            });
+        }
+        let scaled_weights = to_scaled_weights(weights);
+        let (total_fee, governance_share) = fee_parts(lp_fee, governance_fee);
+        if total_fee >= ONE_U128 {
+            return Err(InvariantError::Overflow {
+                stage: InvariantStage::Fees,
+            });
+        }
+        let withdrawn_balances = create_array(|i| balances[i] - outputs[i]);
+        let output_shrinkage = ln_growth(&withdrawn_balances, &balances, &scaled_weights)?
+            .negated()
+            .per_weight(scaled_weights[input_index]);
+        let input_growth = to_factor(exp_up(output_shrinkage.up), InvariantStage::UnknownBalance)?;
+        let input_after_fees = mul_up(
+            balances[input_index],
+            input_growth.saturating_sub(ONE_U128),
+            InvariantStage::UnknownBalance,
+        )?;
+        //input_after_fees / (1 - total_fee), rounded up
+        let input_amount = to_u128(
+            (U256::from(input_after_fees) * U256::from(ONE_U128) + U256::from(ONE_U128 - total_fee - 1))
+                / U256::from(ONE_U128 - total_fee),
+            InvariantStage::UnknownBalance,
+        )?;
+        let mut updated_balances = withdrawn_balances;
+        updated_balances[input_index] =
+            balances[input_index]
+                .checked_add(input_amount)
+                .ok_or(InvariantError::Overflow {
+                    stage: InvariantStage::UnknownBalance,
+                })?;
+        let (governance_mint_amount, latest_depth) = Self::swap_fees(
+            &updated_balances,
+            &balances,
+            weights,
+            governance_share,
+            lp_total_supply.as_u128(),
+        )?;
+        Ok((AmountT::from(input_amount), governance_mint_amount, latest_depth))
+    }
+
+    pub fn remove_exact_burn(
+        burn_amount: AmountT,
+        output_index: usize,
+        pool_balances: &[AmountT; TOKEN_COUNT],
+        weights: &[u32; TOKEN_COUNT],
+        lp_fee: DecT,
+        governance_fee: DecT,
+        lp_total_supply: AmountT,
+    ) -> InvariantResult<(AmountT, AmountT, AmountT)> {
+        if burn_amount >= lp_total_supply {
+            return Err(InvariantError::Underflow {
+                stage: InvariantStage::LpSupply,
+            });
+        }
+        let balances = to_u128s(pool_balances);
+        nonzero_balances(&balances)?;
+        let scaled_weights = to_scaled_weights(weights);
+        let (total_fee, governance_share) = fee_parts(lp_fee, governance_fee);
+        let lp_total_supply = lp_total_supply.as_u128();
+        let burn_amount = burn_amount.as_u128();
+        let remaining_supply = lp_total_supply - burn_amount;
+        let ln_remaining_share = ln_ratio(remaining_supply, lp_total_supply)
+            .map(|ln| Bounds::around(ln, MAX_LN_ERROR))
+            .ok_or(InvariantError::Underflow {
+                stage: InvariantStage::LpSupply,
+            })?;
+        //the depth shrinks in proportion to the lp supply, which the output balance alone makes up for. its share of a
+        // uniform remove is exempt from the fee
+        let output_balance = balances[output_index];
+        let output_remaining_share = to_factor(
+            exp_up(ln_remaining_share.per_weight(scaled_weights[output_index]).up),
+            InvariantStage::UnknownBalance,
+        )?;
+        let feeless_output = mul_down(
+            output_balance,
+            ONE_U128.saturating_sub(output_remaining_share),
+            InvariantStage::UnknownBalance,
+        )?;
+        let uniform_output = to_u128(
+            U256::from(output_balance) * U256::from(burn_amount) / U256::from(lp_total_supply),
+            InvariantStage::UnknownBalance,
+        )?;
+        let fee = mul_up(
+            feeless_output.saturating_sub(uniform_output),
+            total_fee,
+            InvariantStage::Fees,
+        )?;
+        let output_amount = feeless_output.saturating_sub(fee);
+        if output_amount >= output_balance {
+            return Err(InvariantError::ImpossibleRemove);
+        }
+
+        let mut updated_balances = balances;
+        updated_balances[output_index] = output_balance - output_amount;
+        let growth = ln_growth(&updated_balances, &balances, &scaled_weights)?;
+        //the fees make up 1 - e^(ln_remaining_share - growth) of the latest depth
+        let governance_mint_amount =
+            governance_mint(remaining_supply, ln_remaining_share.minus(growth), governance_share)?;
+        Ok((
+            AmountT::from(output_amount),
+            governance_mint_amount,
+            depth_of(&updated_balances, weights)?,
+        ))
+    }
+
+    pub fn remove_exact_output(
+        output_amounts: &[AmountT; TOKEN_COUNT],
+        pool_balances: &[AmountT; TOKEN_COUNT],
+        weights: &[u32; TOKEN_COUNT],
+        lp_fee: DecT,
+        governance_fee: DecT,
+        lp_total_supply: AmountT,
+    ) -> InvariantResult<(AmountT, AmountT, AmountT)> {
+        let outputs = to_u128s(output_amounts);
+        let balances = to_u128s(pool_balances);
+        if (0..TOKEN_COUNT).any(|i| outputs[i] >= balances[i]) {
+            return Err(InvariantError::Underflow {
+                stage: InvariantStage::Balances,
+            });
+        }
+        let scaled_weights = to_scaled_weights(weights);
+        let (total_fee, governance_share) = fee_parts(lp_fee, governance_fee);
+        let withdrawn_balances = create_array(|i| balances[i] - outputs[i]);
+        let growth = ln_growth(&withdrawn_balances, &balances, &scaled_weights)?;
+        //the pool's balances shrunk in proportion to the depth, the outputs beyond them pay the fee (the upper bound of
+        // the growth leaves the larger excess)
+        let proportional_share = to_factor(exp_up(growth.up), InvariantStage::Fees)?;
+        let mut fee_adjusted_balances = withdrawn_balances;
+        for i in 0..TOKEN_COUNT {
+            let proportional_output = mul_down(
+                balances[i],
+                ONE_U128.saturating_sub(proportional_share),
+                InvariantStage::Fees,
+            )?;
+            let fee = mul_up(
+                outputs[i].saturating_sub(proportional_output),
+                total_fee,
+                InvariantStage::Fees,
+            )?;
+            if fee >= withdrawn_balances[i] {
+                return Err(InvariantError::ImpossibleRemove);
+            }
+            fee_adjusted_balances[i] -= fee;
+        }
+        let fee_adjusted_growth = ln_growth(&fee_adjusted_balances, &balances, &scaled_weights)?;
+
+        let lp_total_supply = lp_total_supply.as_u128();
+        let remaining_share = to_factor(exp_down(fee_adjusted_growth.down), InvariantStage::LpSupply)?;
+        let burn_amount = mul_up(
+            lp_total_supply,
+            ONE_U128.saturating_sub(remaining_share),
+            InvariantStage::LpSupply,
+        )?;
+        //the fees make up 1 - e^(fee_adjusted_growth - growth) of the latest depth
+        let governance_mint_amount = governance_mint(
+            lp_total_supply.saturating_sub(burn_amount),
+            fee_adjusted_growth.minus(growth),
+            governance_share,
+        )?;
+        Ok((
+            AmountT::from(burn_amount),
+            governance_mint_amount,
+            depth_of(&withdrawn_balances, weights)?,
+        ))
+    }
+
+    /// The depth (i.e. the total value in equalized units) of a pool with the given (equalized) balances
+    pub fn depth(pool_balances: &[AmountT; TOKEN_COUNT], weights: &[u32; TOKEN_COUNT]) -> InvariantResult<AmountT> {
+        depth_of(&to_u128s(pool_balances), weights)
+    }
+
+    //the fees of a swap stay in the pool and raise its depth, they make up 1 - e^-growth of the latest depth
+    fn swap_fees(
+        updated_balances: &[u128; TOKEN_COUNT],
+        balances: &[u128; TOKEN_COUNT],
+        weights: &[u32; TOKEN_COUNT],
+        governance_share: u128,
+        lp_total_supply: u128,
+    ) -> InvariantResult<(AmountT, AmountT)> {
+        let growth = ln_growth(updated_balances, balances, &to_scaled_weights(weights))?;
+        Ok((
+            governance_mint(lp_total_supply, growth.negated(), governance_share)?,
+            depth_of(updated_balances, weights)?,
+        ))
+    }
+}
+
+//a value scaled by ONE that lies between down and up
+#[derive(Clone, Copy, Debug)]
+struct Bounds {
+    down: i128,
+    up: i128,
+}
+
+impl Bounds {
+    fn around(value: i128, error: i128) -> Self {
+        Self {
+            down: value - error,
+            up: value + error,
+        }
+    }
+
+    fn negated(self) -> Self {
+        Self {
+            down: -self.up,
+            up: -self.down,
+        }
+    }
+
+    fn minus(self, other: Self) -> Self {
+        Self {
+            down: self.down - other.up,
+            up: self.up - other.down,
+        }
+    }
+
+    //divided by a weight scaled by ONE, rounding outwards. the values are logarithms of ratios of u128s (at most 89
+    // in absolute), so they can be scaled by ONE once more
+    fn per_weight(self, weight: i128) -> Self {
+        Self {
+            down: (self.down * ONE).div_euclid(weight),
+            up: -(-self.up * ONE).div_euclid(weight),
+        }
+    }
+}
+
+fn to_u128s<const TOKEN_COUNT: usize>(amounts: &[AmountT; TOKEN_COUNT]) -> [u128; TOKEN_COUNT] {
+    create_array(|i| amounts[i].as_u128())
+}
+
+fn to_scaled_weights<const TOKEN_COUNT: usize>(weights: &[u32; TOKEN_COUNT]) -> [i128; TOKEN_COUNT] {
+    create_array(|i| weights[i] as i128 * (ONE / WEIGHT_ONE as i128))
+}
+
+fn add_amounts<const TOKEN_COUNT: usize>(
+    balances: &[u128; TOKEN_COUNT],
+    amounts: &[u128; TOKEN_COUNT],
+) -> InvariantResult<[u128; TOKEN_COUNT]> {
+    let mut sums = *balances;
+    for (sum, amount) in sums.iter_mut().zip(amounts.iter()) {
+        *sum = sum.checked_add(*amount).ok_or(InvariantError::Overflow {
+            stage: InvariantStage::Balances,
+        })?;
+    }
+    Ok(sums)
+}
+
+fn nonzero_balances<const TOKEN_COUNT: usize>(balances: &[u128; TOKEN_COUNT]) -> InvariantResult<()> {
+    if balances.iter().any(|balance| *balance == 0) {
+        Err(InvariantError::Underflow {
+            stage: InvariantStage::Depth,
+        })
+    } else {
+        Ok(())
+    }
+}
+
+//the total fee (rounded up) and the governance fee's share of it (rounded down), scaled by ONE
+fn fee_parts(lp_fee: DecT, governance_fee: DecT) -> (u128, u128) {
+    //raw values of a DecimalU64 have at most 20 digits, so scaled by ONE they still fit a u128
+    let scaled = |fee: DecT| (fee.get_raw() as u128 * ONE_U128, 10u128.pow(fee.get_decimals() as u32));
+    let (lp_fee, lp_unit) = scaled(lp_fee);
+    let (governance_fee, governance_unit) = scaled(governance_fee);
+    let total_fee = (lp_fee + lp_unit - 1) / lp_unit + (governance_fee + governance_unit - 1) / governance_unit;
+    if total_fee == 0 {
+        (0, 0)
+    } else {
+        let governance_share =
+            U256::from(governance_fee / governance_unit) * U256::from(ONE_U128) / U256::from(total_fee);
+        (total_fee, governance_share.as_u128())
+    }
+}
+
+//a lower bound of the depth prod((balance_i / weight_i)^weight_i), zero if any balance is
+fn depth_of<const TOKEN_COUNT: usize>(
+    balances: &[u128; TOKEN_COUNT],
+    weights: &[u32; TOKEN_COUNT],
+) -> InvariantResult<AmountT> {
+    if balances.iter().any(|balance| *balance == 0) {
+        return Ok(AmountT::zero());
+    }
+    let scaled_weights = to_scaled_weights(weights);
+    let mut ln_depth = 0;
+    for i in 0..TOKEN_COUNT {
+        let ln_balance = ln_ratio(balances[i], 1).ok_or(InvariantError::Underflow {
+            stage: InvariantStage::Depth,
+        })?;
+        let ln_weight = ln_ratio(weights[i] as u128, WEIGHT_ONE as u128).ok_or(InvariantError::Underflow {
+            stage: InvariantStage::Depth,
+        })?;
+        ln_depth += scaled_weights[i] * (ln_balance - ln_weight) / ONE;
+    }
+    //the weights add up to ONE, so the errors of the logarithms add up to at most twice MAX_LN_ERROR
+    let error = 2 * MAX_LN_ERROR + TOKEN_COUNT as i128;
+    let depth = exp_down(ln_depth - error).ok_or(InvariantError::Overflow {
+        stage: InvariantStage::Depth,
+    })?;
+    to_u128(depth / U256::from(ONE_U128), InvariantStage::Depth).map(AmountT::from)
+}
+
+//bounds of the logarithm of the factor by which the depth grows when the balances change to updated_balances
+fn ln_growth<const TOKEN_COUNT: usize>(
+    updated_balances: &[u128; TOKEN_COUNT],
+    balances: &[u128; TOKEN_COUNT],
+    scaled_weights: &[i128; TOKEN_COUNT],
+) -> InvariantResult<Bounds> {
+    let mut growth = 0;
+    for i in 0..TOKEN_COUNT {
+        let ln = ln_ratio(updated_balances[i], balances[i]).ok_or(InvariantError::Underflow {
+            stage: InvariantStage::Balances,
+        })?;
+        growth += scaled_weights[i] * ln / ONE;
+    }
+    //the weights add up to ONE, so the errors of the logarithms add up to at most MAX_LN_ERROR, plus a unit for each
+    // rounded term
+    Ok(Bounds::around(growth, MAX_LN_ERROR + TOKEN_COUNT as i128))
+}
+
+//the lp tokens that dilute lp_total_supply by the governance's share of the fees, which make up
+// 1 - e^ln_feeless_share of the latest depth
+fn governance_mint(
+    lp_total_supply: u128,
+    ln_feeless_share: Bounds,
+    governance_share: u128,
+) -> InvariantResult<AmountT> {
+    let feeless_share = to_factor(exp_up(ln_feeless_share.up), InvariantStage::Fees)?;
+    if feeless_share >= ONE_U128 {
+        return Ok(AmountT::zero());
+    }
+    //below ONE since the feeless share is at least a unit
+    let governance_depth_share = mul_down(ONE_U128 - feeless_share, governance_share, InvariantStage::Fees)?;
+    to_u128(
+        U256::from(lp_total_supply) * U256::from(governance_depth_share)
+            / U256::from(ONE_U128 - governance_depth_share),
+        InvariantStage::Fees,
+    )
+    .map(AmountT::from)
+}
+
+fn to_u128(value: U256, stage: InvariantStage) -> InvariantResult<u128> {
+    if value > U256::from(u128::MAX) {
+        return Err(InvariantError::Overflow { stage });
+    }
+    Ok(value.as_u128())
+}
+
+//a factor scaled by ONE from the result of exp_up or exp_down
+fn to_factor(value: Option<U256>, stage: InvariantStage) -> InvariantResult<u128> {
+    to_u128(value.ok_or(InvariantError::Overflow { stage })?, stage)
+}
+
+//value * factor / ONE, amounts the pool pays out or mints are rounded down
+fn mul_down(value: u128, factor: u128, stage: InvariantStage) -> InvariantResult<u128> {
+    to_u128(U256::from(value) * U256::from(factor) / U256::from(ONE_U128), stage)
+}
+
+//value * factor / ONE, amounts the pool receives or burns are rounded up
+fn mul_up(value: u128, factor: u128, stage: InvariantStage) -> InvariantResult<u128> {
+    to_u128(
+        (U256::from(value) * U256::from(factor) + U256::from(ONE_U128 - 1)) / U256::from(ONE_U128),
+        stage,
+    )
+}
+
+#[cfg(all(test, not(feature = "test-bpf")))]
+mod tests {
+    use super::*;
+
+    const WEIGHTS: [u32; 2] = [800_000, 200_000];
+
+    fn amounts(values: [u128; 2]) -> [AmountT; 2] {
+        create_array(|i| AmountT::from(values[i]))
+    }
+
+    fn fees() -> (DecT, DecT) {
+        (DecT::new(3, 3).unwrap(), DecT::new(1, 3).unwrap())
+    }
+
+    #[test]
+    fn weights() {
+        assert!(is_valid_weights(&WEIGHTS));
+        assert!(is_valid_weights(&[500_000; 2]));
+        assert!(!is_valid_weights(&[500_000, 499_999]));
+        assert!(!is_valid_weights(&[MIN_WEIGHT - 1, WEIGHT_ONE - MIN_WEIGHT + 1]));
+        assert!(!is_valid_weights(&[u32::MAX, WEIGHT_ONE + 1]));
+    }
+
+    #[test]
+    fn depth_of_balances_in_proportion_is_their_sum() {
+        let depth = WeightedInvariant::<2>::depth(&amounts([8_000_000_000, 2_000_000_000]), &WEIGHTS).unwrap();
+        let diff = depth.as_u128() as i128 - 10_000_000_000;
+        assert!(diff.abs() <= 1, "{}", depth);
+        assert_eq!(
+            marginal_prices(&[8_000_000_000, 2_000_000_000], &WEIGHTS),
+            Some([EMA_ONE; 2])
+        );
+        assert_eq!(
+            marginal_prices(&[8_000_000_000, 1_000_000_000], &WEIGHTS),
+            Some([EMA_ONE, 2 * EMA_ONE])
+        );
+        assert_eq!(marginal_prices(&[8_000_000_000, 0], &WEIGHTS), None);
+    }
+
+    #[test]
+    fn swaps_follow_the_weighted_product() {
+        let balances = amounts([8_000_000_000, 2_000_000_000]);
+        let supply = AmountT::from(10_000_000_000u128);
+        let (lp_fee, governance_fee) = fees();
+        let input = 100_000_000u128;
+        let (output, governance_mint, _) = WeightedInvariant::<2>::swap_exact_input(
+            &amounts([input, 0]),
+            1,
+            &balances,
+            &WEIGHTS,
+            lp_fee,
+            governance_fee,
+            supply,
+        )
+        .unwrap();
+        //output = balance_out * (1 - (balance_in / (balance_in + input_after_fee))^(weight_in / weight_out))
+        let after_fee = input as f64 * 0.996;
+        let expected = 2e9 * (1.0 - (8e9 / (8e9 + after_fee)).powf(4.0));
+        assert!(output.as_u128() as f64 <= expected);
+        assert!(output.as_u128() as f64 > expected - 10.0, "{} {}", output, expected);
+        assert!(!governance_mint.is_zero());
+
+        //buying back the output costs at most the input
+        let (input_back, _, _) = WeightedInvariant::<2>::swap_exact_output(
+            0,
+            &[AmountT::zero(), output],
+            &balances,
+            &WEIGHTS,
+            lp_fee,
+            governance_fee,
+            supply,
+        )
+        .unwrap();
+        assert!(input_back.as_u128() <= input + 2);
+        assert!(input_back.as_u128() > input - 10);
+    }
+
+    #[test]
+    fn adds_and_removes_never_profit() {
+        let balances = amounts([8_000_000_000, 2_000_000_000]);
+        let supply = AmountT::from(10_000_000_000u128);
+        let (lp_fee, governance_fee) = fees();
+        //amounts valued at the marginal prices before the add, round trips trade at worse prices than those
+        let prices = marginal_prices(&[8_000_000_000, 2_000_000_000], &WEIGHTS).unwrap();
+        let value =
+            |amounts: [u128; 2]| -> u128 { (0..2).map(|i| amounts[i] * prices[i] as u128 / EMA_ONE as u128).sum() };
+        for input in vec![[1_000_000, 0], [0, 1_000_000], [8_000_000, 2_000_000], [1, 1]] {
+            let (mint, _, _) =
+                WeightedInvariant::<2>::add(&amounts(input), &balances, &WEIGHTS, lp_fee, governance_fee, supply)
+                    .unwrap();
+            let updated_balances = create_array(|i| balances[i] + AmountT::from(input[i]));
+            for output_index in 0..2 {
+                let (output, _, _) = WeightedInvariant::<2>::remove_exact_burn(
+                    mint,
+                    output_index,
+                    &updated_balances,
+                    &WEIGHTS,
+                    lp_fee,
+                    governance_fee,
+                    supply + mint,
+                )
+                .unwrap();
+                let mut outputs = [0; 2];
+                outputs[output_index] = output.as_u128();
+                assert!(value(outputs) <= value(input), "{:?} {:?}", input, outputs);
+            }
+        }
+
+        //a uniform remove costs no fee (but for a unit of the rounding of its excess), anything else does
+        let (burn, _, _) = WeightedInvariant::<2>::remove_exact_output(
+            &amounts([8_000_000, 2_000_000]),
+            &balances,
+            &WEIGHTS,
+            lp_fee,
+            governance_fee,
+            supply,
+        )
+        .unwrap();
+        assert!(burn.as_u128() >= 10_000_000 && burn.as_u128() <= 10_000_003, "{}", burn);
+        let (burn, _, _) = WeightedInvariant::<2>::remove_exact_output(
+            &amounts([10_000_000, 0]),
+            &balances,
+            &WEIGHTS,
+            lp_fee,
+            governance_fee,
+            supply,
+        )
+        .unwrap();
+        assert!(burn.as_u128() > 10_000_000, "{}", burn);
+    }
+}
@@ -0,0 +1,91 @@
+#![cfg(feature = "test-bpf")]
+
+mod helpers;
+
+use helpers::{bank::*, AmountT};
+
+use pool::{
+    client::amm::{AmmQuoteParams, AmmSwapParams, PoolAmm, SwapMode},
+    instruction::DeFiInstruction,
+    TOKEN_COUNT,
+};
+use solana_program_test::*;
+use solana_sdk::signature::Signer;
+use std::collections::HashMap;
+
+const POOL_BALANCE: AmountT = 1_000_000_000;
+const SWAP_AMOUNT: AmountT = 1_000_000;
+
+async fn funded_pool() -> (BankPool, BankUser) {
+    let mut pool = BankPool::new(&BankPoolParams::default()).await.unwrap();
+    //the user keeps twice the swapped amount after adding, exact output swaps pay more than they receive
+    let user = pool.create_user(&[POOL_BALANCE + 2 * SWAP_AMOUNT; TOKEN_COUNT]);
+    pool.execute_defi_instruction(
+        DeFiInstruction::Add {
+            input_amounts: [POOL_BALANCE; TOKEN_COUNT],
+            minimum_mint_amount: 0,
+        },
+        &user,
+    )
+    .await
+    .unwrap();
+    (pool, user)
+}
+
+//what an aggregator does: fetch the accounts the amm asks for and hand them to it
+async fn updated_amm(pool: &mut BankPool) -> PoolAmm {
+    let banks_client = &mut pool.context.banks_client;
+    let pool_account = banks_client.get_account(pool.pool).await.unwrap().unwrap();
+    let mut amm = PoolAmm::from_keyed_account(&pool.pool, &pool_account).unwrap();
+    let mut accounts = HashMap::new();
+    for key in amm.get_accounts_to_update() {
+        accounts.insert(key, banks_client.get_account(key).await.unwrap().unwrap());
+    }
+    amm.update(&accounts).unwrap();
+    amm
+}
+
+async fn check_quote_is_exact(swap_mode: SwapMode) {
+    let (mut pool, user) = funded_pool().await;
+    let amm = updated_amm(&mut pool).await;
+    assert_eq!(amm.get_reserve_mints(), pool.token_mints.to_vec());
+    assert_eq!(amm.program_id(), pool::id());
+
+    let (input_mint, output_mint) = (pool.token_mints[0], pool.token_mints[1]);
+    let quote = amm
+        .quote(&AmmQuoteParams {
+            input_mint,
+            output_mint,
+            amount: SWAP_AMOUNT,
+            swap_mode,
+        })
+        .unwrap();
+    assert!(quote.fee_amount > 0);
+    let ix = amm
+        .get_swap_ix(&AmmSwapParams {
+            input_mint,
+            output_mint,
+            in_amount: quote.in_amount,
+            out_amount: quote.out_amount,
+            swap_mode,
+            source_token_account: user.tokens[0],
+            destination_token_account: user.tokens[1],
+            token_transfer_authority: user.keypair.pubkey(),
+        })
+        .unwrap();
+    pool.execute_transaction(ix, &[&user.keypair]).await.unwrap();
+
+    let balances = pool.balances(&user.tokens).await;
+    assert_eq!(balances[0], 2 * SWAP_AMOUNT - quote.in_amount);
+    assert_eq!(balances[1], 2 * SWAP_AMOUNT + quote.out_amount);
+}
+
+#[tokio::test]
+async fn test_exact_input_quote_matches_swap() {
+    check_quote_is_exact(SwapMode::ExactIn).await;
+}
+
+#[tokio::test]
+async fn test_exact_output_quote_matches_swap() {
+    check_quote_is_exact(SwapMode::ExactOut).await;
+}
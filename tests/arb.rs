@@ -0,0 +1,82 @@
+#![cfg(feature = "test-bpf")]
+
+mod helpers;
+
+use helpers::{bank::*, AmountT};
+
+use pool::{client::arb::find_opportunities, instruction::DeFiInstruction, TOKEN_COUNT};
+use solana_program_test::*;
+use solana_sdk::signature::Signer;
+use std::collections::HashMap;
+
+const POOL_BALANCE: AmountT = 1_000_000_000;
+const MIN_PROFIT_BPS: u64 = 5;
+
+#[tokio::test]
+async fn test_find_opportunities() {
+    let mut pool = BankPool::new(&BankPoolParams::default()).await.unwrap();
+    let lp = pool.create_user(&[POOL_BALANCE; TOKEN_COUNT]);
+    pool.execute_defi_instruction(
+        DeFiInstruction::Add {
+            input_amounts: [POOL_BALANCE; TOKEN_COUNT],
+            minimum_mint_amount: 0,
+        },
+        &lp,
+    )
+    .await
+    .unwrap();
+    let mints = pool.token_mints;
+    let oracle_prices: HashMap<_, _> = mints.iter().map(|mint| (*mint, 1.0)).collect();
+
+    //a balanced pool is at parity
+    assert!(find_opportunities(&[pool.amm().await], &oracle_prices, 0).is_empty());
+
+    //selling a lot of token 0 makes token 1 expensive in the pool
+    let mut balances = [0; TOKEN_COUNT];
+    balances[0] = POOL_BALANCE / 2;
+    let whale = pool.create_user(&balances);
+    let mut exact_input_amounts = [0; TOKEN_COUNT];
+    exact_input_amounts[0] = POOL_BALANCE / 2;
+    pool.execute_defi_instruction(
+        DeFiInstruction::SwapExactInput {
+            exact_input_amounts,
+            output_token_index: 1,
+            minimum_output_amount: 0,
+        },
+        &whale,
+    )
+    .await
+    .unwrap();
+
+    let opportunities = find_opportunities(&[pool.amm().await], &oracle_prices, MIN_PROFIT_BPS);
+    assert_eq!(opportunities.len(), 1);
+    let opportunity = &opportunities[0];
+    let hop = &opportunity.route.hops[0];
+    assert_eq!((hop.input_mint, hop.output_mint), (mints[1], mints[0]));
+    assert!(opportunity.profit_bps >= MIN_PROFIT_BPS);
+    assert_eq!(
+        opportunity.profit_amount,
+        opportunity.route.out_amount() - opportunity.route.in_amount()
+    );
+
+    let mut balances = [0; TOKEN_COUNT];
+    balances[1] = opportunity.route.in_amount();
+    let keeper = pool.create_user(&balances);
+    let ixs = opportunity
+        .route
+        .instructions(
+            &keeper.keypair.pubkey(),
+            &keeper.tokens[1],
+            &keeper.tokens[0],
+            opportunity.route.out_amount(),
+        )
+        .unwrap();
+    pool.execute_instructions(&ixs, &[&keeper.keypair]).await.unwrap();
+    assert_eq!(pool.balance(&keeper.tokens[0]).await, opportunity.route.out_amount());
+
+    //the pool is back at parity, up to fees
+    let remaining = find_opportunities(&[pool.amm().await], &oracle_prices, 0);
+    assert!(remaining
+        .iter()
+        .all(|remaining| remaining.profit_amount < opportunity.profit_amount / 100));
+}
@@ -0,0 +1,18 @@
+//the program relies on release builds checking integer arithmetic for overflows (failing the transaction rather than
+// wrapping), which is a setting of the build profile that nothing in the code itself would catch being dropped
+
+const MANIFEST: &str = include_str!("../Cargo.toml");
+
+#[test]
+fn test_release_profile_checks_overflows() {
+    let release_profile = MANIFEST
+        .split("\n[")
+        .find(|section| section.starts_with("profile.release]"))
+        .expect("Cargo.toml has no release profile");
+    assert!(
+        release_profile
+            .lines()
+            .any(|line| line.replace(' ', "") == "overflow-checks=true"),
+        "the release profile doesn't check overflows"
+    );
+}
@@ -0,0 +1,100 @@
+#![cfg(feature = "test-bpf")]
+
+mod helpers;
+
+use helpers::{bank::*, AmountT, DecT};
+
+use pool::{error::PoolError, instruction::DeFiInstruction, TOKEN_COUNT};
+use solana_program_test::*;
+use solana_sdk::instruction::InstructionError;
+
+const POOL_BALANCE: AmountT = 1_000_000_000;
+
+//a pool whose governance fee account collected the governance fee of a few swaps
+async fn pool_with_governance_fees() -> BankPool {
+    let params = BankPoolParams {
+        governance_fee: DecT::new(1, 2).unwrap(),
+        ..BankPoolParams::default()
+    };
+    let mut pool = BankPool::new(&params).await.unwrap();
+    let user = pool.create_user(&[POOL_BALANCE; TOKEN_COUNT]);
+    pool.execute_defi_instruction(
+        DeFiInstruction::Add {
+            input_amounts: [POOL_BALANCE / 2; TOKEN_COUNT],
+            minimum_mint_amount: 0,
+        },
+        &user,
+    )
+    .await
+    .unwrap();
+    for i in 0..TOKEN_COUNT {
+        let mut exact_input_amounts = [0; TOKEN_COUNT];
+        exact_input_amounts[i] = POOL_BALANCE / 10;
+        pool.execute_defi_instruction(
+            DeFiInstruction::SwapExactInput {
+                exact_input_amounts,
+                output_token_index: ((i + 1) % TOKEN_COUNT) as u8,
+                minimum_output_amount: 0,
+            },
+            &user,
+        )
+        .await
+        .unwrap();
+    }
+    pool
+}
+
+#[tokio::test]
+async fn test_burn_governance_fees() {
+    let mut pool = pool_with_governance_fees().await;
+    let governance_fee_account = pool.governance_fee_account;
+    let fees = pool.balance(&governance_fee_account).await;
+    assert!(fees > 0);
+    pool.publish_virtual_price().await.unwrap();
+    let before = pool.virtual_price().await;
+    let swapped = pool.state().await;
+
+    pool.advance_clock(60).await;
+    pool.burn_governance_fees(fees / 2).await.unwrap();
+    assert_eq!(pool.balance(&governance_fee_account).await, fees - fees / 2);
+    assert_eq!(pool.lp_total_supply().await, before.lp_supply - fees / 2);
+    //the supply was integrated up to the burn
+    let state = pool.state().await;
+    assert!(state.lp_supply_ts >= swapped.lp_supply_ts + 60);
+    assert_eq!(
+        state.lp_supply_seconds,
+        swapped.lp_supply_seconds_at(before.lp_supply, state.lp_supply_ts)
+    );
+
+    //the depth stays in the pool, so each remaining lp token is worth more
+    pool.publish_virtual_price().await.unwrap();
+    let after = pool.virtual_price().await;
+    assert_eq!(after.depth, before.depth);
+    assert_eq!(after.lp_supply, before.lp_supply - fees / 2);
+    assert!(after.price > before.price);
+
+    pool.burn_governance_fees(fees - fees / 2).await.unwrap();
+    assert_eq!(pool.balance(&governance_fee_account).await, 0);
+}
+
+#[tokio::test]
+async fn test_burn_governance_fees_limits() {
+    let mut pool = pool_with_governance_fees().await;
+    let governance_fee_account = pool.governance_fee_account;
+    let fees = pool.balance(&governance_fee_account).await;
+    let lp_supply = pool.lp_total_supply().await;
+
+    //the token program refuses to burn more than the account holds
+    assert!(pool.burn_governance_fees(fees + 1).await.is_err());
+    assert_eq!(
+        pool.burn_governance_fees(lp_supply).await,
+        Err(InstructionError::Custom(PoolError::PoolIsEmpty as u32))
+    );
+    assert_eq!(pool.balance(&governance_fee_account).await, fees);
+
+    pool.governance_fee_account = pool.create_user(&[0; TOKEN_COUNT]).lp;
+    assert_eq!(
+        pool.burn_governance_fees(1).await,
+        Err(InstructionError::Custom(PoolError::InvalidGovernanceFeeAccount as u32))
+    );
+}
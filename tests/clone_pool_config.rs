@@ -0,0 +1,155 @@
+#![cfg(feature = "test-bpf")]
+
+mod helpers;
+
+use helpers::{bank::*, DecT};
+
+use pool::{
+    oracle::{OracleFallback, OracleLimits},
+    pool_fee::PoolFee,
+    state::{CallerMode, PegKeeper, PriceScale, TokenParticipation, YieldAdapter},
+    TOKEN_COUNT,
+};
+use solana_program_test::*;
+use solana_sdk::{instruction::InstructionError, pubkey::Pubkey};
+
+//a pool whose parameters all differ from the defaults
+async fn template_pool() -> BankPool {
+    let mut template = BankPool::new(&BankPoolParams {
+        amp_factor: DecT::from(500),
+        lp_fee: DecT::new(7, 4).unwrap(),
+        governance_fee: DecT::new(2, 4).unwrap(),
+        ..BankPoolParams::default()
+    })
+    .await
+    .unwrap();
+    let now = template.now().await;
+    let mut state = template.state().await;
+    state.oracle_keys = [Pubkey::new_unique(); TOKEN_COUNT];
+    state.max_oracle_deviation = PoolFee::new(DecT::new(1, 2).unwrap()).unwrap();
+    state.oracle_limits = [OracleLimits {
+        max_age: 25,
+        max_conf_bps: 50,
+    }; TOKEN_COUNT];
+    state.oracle_fallback = OracleFallback::InternalEma;
+    state.peg_band = PoolFee::new(DecT::new(3, 2).unwrap()).unwrap();
+    state
+        .amp_damping
+        .set_params(
+            now,
+            DecT::new(5, 1).unwrap(),
+            DecT::new(2, 2).unwrap(),
+            DecT::new(1, 2).unwrap(),
+            600,
+        )
+        .unwrap();
+    state.hook_programs[0] = Pubkey::new_unique();
+    state.caller_mode = CallerMode::AllowedPrograms;
+    state.allowed_callers[0] = Pubkey::new_unique();
+    //a template for pools that onboard their last token
+    state.token_participation[TOKEN_COUNT - 1] = TokenParticipation::DepositOnly;
+    state.peg_keeper = PegKeeper {
+        keeper_key: Pubkey::new_unique(),
+        token_index: 0,
+        credit_limit: 1_000_000,
+        debt: 1_000,
+        lp_amount: 990,
+    };
+    state.yield_adapters[0] = YieldAdapter {
+        program: Pubkey::new_unique(),
+        buffer: 1_000_000,
+        deployed: 1_000,
+    };
+    state.price_scale = PriceScale {
+        token_index: 1,
+        rate: 2_000,
+        threshold_bps: 50,
+        max_step_bps: 100,
+        min_interval: 3_600,
+        last_repeg_ts: now,
+    };
+    template.set_state(&state).await;
+    template
+}
+
+#[tokio::test]
+async fn test_clone_pool_config() {
+    let mut template = template_pool().await;
+    let template_state = template.state().await;
+    let template_account = template
+        .context
+        .banks_client
+        .get_account(template.pool)
+        .await
+        .unwrap()
+        .unwrap();
+
+    let mut pool = BankPool::new_uninitialized(&BankPoolParams::default()).await;
+    pool.context.set_account(&template.pool, &template_account.into());
+    let ix = pool.clone_pool_config_ix(&template.pool);
+    pool.execute_transaction(ix, &[]).await.unwrap();
+
+    let now = pool.now().await;
+    let state = pool.state().await;
+    assert_eq!(state.token_mint_keys, pool.token_mints);
+    assert_eq!(state.amp_factor.get(now), DecT::from(500));
+    assert_eq!(state.lp_fee.get(), template_state.lp_fee.get());
+    assert_eq!(state.governance_fee.get(), template_state.governance_fee.get());
+    assert_eq!(
+        state.max_oracle_deviation.get(),
+        template_state.max_oracle_deviation.get()
+    );
+    assert_eq!(state.oracle_limits, template_state.oracle_limits);
+    assert_eq!(state.oracle_fallback, OracleFallback::InternalEma);
+    assert_eq!(state.peg_band.get(), template_state.peg_band.get());
+    assert_eq!(state.amp_damping.factor(), DecT::new(5, 1).unwrap());
+    assert_eq!(state.amp_damping.trigger_band(), DecT::new(2, 2).unwrap());
+    assert_eq!(state.amp_damping.release_band(), DecT::new(1, 2).unwrap());
+    assert_eq!(state.amp_damping.delay(), 600);
+    assert_eq!(state.hook_programs, template_state.hook_programs);
+    assert_eq!(state.caller_mode, CallerMode::AllowedPrograms);
+    assert_eq!(state.allowed_callers, template_state.allowed_callers);
+    assert_eq!(
+        state.token_participation[TOKEN_COUNT - 1],
+        TokenParticipation::DepositOnly
+    );
+    //the new pool doesn't owe the keeper what the template does
+    assert_eq!(
+        state.peg_keeper,
+        PegKeeper {
+            debt: 0,
+            lp_amount: 0,
+            ..template_state.peg_keeper
+        }
+    );
+    //nor its position with a yield adapter
+    assert_eq!(
+        state.yield_adapters[0],
+        YieldAdapter {
+            deployed: 0,
+            ..template_state.yield_adapters[0]
+        }
+    );
+    //the new pool can repeg right away
+    assert_eq!(
+        state.price_scale,
+        PriceScale {
+            last_repeg_ts: 0,
+            ..template_state.price_scale
+        }
+    );
+    //the template's oracles price its own tokens
+    assert_eq!(state.oracle_keys, [Pubkey::default(); TOKEN_COUNT]);
+    assert!(!state.is_oracle_guarded());
+}
+
+#[tokio::test]
+async fn test_clone_pool_config_requires_initialized_template() {
+    let mut pool = BankPool::new_uninitialized(&BankPoolParams::default()).await;
+    //the pool itself isn't initialized yet
+    let ix = pool.clone_pool_config_ix(&pool.pool.clone());
+    assert_eq!(
+        pool.execute_transaction(ix, &[]).await,
+        Err(InstructionError::UninitializedAccount)
+    );
+}
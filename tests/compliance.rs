@@ -0,0 +1,152 @@
+#![cfg(feature = "test-bpf")]
+
+mod helpers;
+
+use helpers::{bank::*, AmountT, DecT};
+
+use borsh::BorshDeserialize;
+use pool::{
+    error::PoolError,
+    instruction::{ComplianceCheckData, DeFiInstruction, RfqQuote},
+    TOKEN_COUNT,
+};
+use solana_program::{account_info::AccountInfo, entrypoint::ProgramResult, program_error::ProgramError};
+use solana_program_test::*;
+use solana_sdk::{
+    instruction::InstructionError,
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+};
+
+const POOL_BALANCE: AmountT = 1_000_000_000;
+const VETO: u32 = 7;
+
+fn process_reference(program_id: &Pubkey, accounts: &[AccountInfo], data: &[u8]) -> ProgramResult {
+    pool::compliance::process_instruction::<TOKEN_COUNT>(program_id, accounts, data)
+}
+
+//a compliance program that doesn't let anyone swap
+fn process_no_swaps(_program_id: &Pubkey, _accounts: &[AccountInfo], data: &[u8]) -> ProgramResult {
+    let check_data = ComplianceCheckData::<TOKEN_COUNT>::try_from_slice(data).unwrap();
+    if check_data.instruction.executed().is_swap() {
+        return Err(ProgramError::Custom(VETO));
+    }
+    Ok(())
+}
+
+fn swap() -> DeFiInstruction<TOKEN_COUNT> {
+    let mut exact_input_amounts = [0; TOKEN_COUNT];
+    exact_input_amounts[0] = 1_000_000;
+    DeFiInstruction::SwapExactInput {
+        exact_input_amounts,
+        output_token_index: 1,
+        minimum_output_amount: 0,
+    }
+}
+
+fn add() -> DeFiInstruction<TOKEN_COUNT> {
+    DeFiInstruction::Add {
+        input_amounts: [POOL_BALANCE / 2; TOKEN_COUNT],
+        minimum_mint_amount: 0,
+    }
+}
+
+async fn pool_with_programs() -> (BankPool, BankUser, Pubkey, Pubkey) {
+    let reference = Pubkey::new_unique();
+    let no_swaps = Pubkey::new_unique();
+    let mut pool = BankPool::new_with(&BankPoolParams::default(), |program_test| {
+        program_test.add_program("reference_compliance", reference, processor!(process_reference));
+        program_test.add_program("no_swaps_compliance", no_swaps, processor!(process_no_swaps));
+    })
+    .await
+    .unwrap();
+    let user = pool.create_user(&[POOL_BALANCE; TOKEN_COUNT]);
+    (pool, user, reference, no_swaps)
+}
+
+#[tokio::test]
+async fn test_reference_compliance_program_allows_everything() {
+    let (mut pool, user, reference, _) = pool_with_programs().await;
+    pool.set_compliance_program(Some(reference)).await.unwrap();
+    assert_eq!(pool.state().await.compliance_program, reference);
+
+    pool.execute_defi_instruction(add(), &user).await.unwrap();
+    pool.execute_defi_instruction(swap(), &user).await.unwrap();
+    pool.execute_defi_instruction(DeFiInstruction::Simulate(Box::new(swap())), &user)
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn test_compliance_program_vetoes() {
+    let (mut pool, user, _, no_swaps) = pool_with_programs().await;
+    pool.set_compliance_program(Some(no_swaps)).await.unwrap();
+    pool.execute_defi_instruction(add(), &user).await.unwrap();
+    assert_eq!(
+        pool.execute_defi_instruction(swap(), &user).await,
+        Err(InstructionError::Custom(VETO))
+    );
+    //simulations are checked like the instructions they simulate
+    assert_eq!(
+        pool.execute_defi_instruction(DeFiInstruction::Simulate(Box::new(swap())), &user)
+            .await,
+        Err(InstructionError::Custom(VETO))
+    );
+    //uniform removes don't call the program, users can always exit
+    let lp_balance = pool.balance(&user.lp).await;
+    pool.execute_defi_instruction(
+        DeFiInstruction::RemoveUniform {
+            exact_burn_amount: lp_balance / 2,
+            minimum_output_amounts: [0; TOKEN_COUNT],
+        },
+        &user,
+    )
+    .await
+    .unwrap();
+
+    pool.set_compliance_program(None).await.unwrap();
+    pool.execute_defi_instruction(swap(), &user).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_compliance_program_vetoes_rfq_swaps() {
+    let (mut pool, user, reference, no_swaps) = pool_with_programs().await;
+    pool.execute_defi_instruction(add(), &user).await.unwrap();
+    let quoter = Keypair::new();
+    pool.set_rfq_quoter(&quoter.pubkey(), DecT::new(5, 3).unwrap())
+        .await
+        .unwrap();
+    pool.set_compliance_program(Some(no_swaps)).await.unwrap();
+    let mut quote = RfqQuote {
+        pool: pool.pool,
+        user: user.keypair.pubkey(),
+        input_token_index: 0,
+        input_amount: 1_000_000,
+        output_token_index: 1,
+        output_amount: 999_000,
+        quote_id: 1,
+        expiry_ts: pool.now().await + 60,
+    };
+    //a quote doesn't get around the program's veto of swaps
+    assert_eq!(
+        pool.execute_rfq_swap(quote, &quoter, &user).await,
+        Err(InstructionError::Custom(VETO))
+    );
+
+    pool.set_compliance_program(Some(reference)).await.unwrap();
+    quote.quote_id = 2;
+    pool.execute_rfq_swap(quote, &quoter, &user).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_compliance_accounts_are_checked() {
+    let (mut pool, user, reference, no_swaps) = pool_with_programs().await;
+    pool.set_compliance_program(Some(no_swaps)).await.unwrap();
+    pool.execute_defi_instruction(add(), &user).await.unwrap();
+    //passing another program doesn't get around the pool's
+    pool.compliance_program = Some(reference);
+    assert_eq!(
+        pool.execute_defi_instruction(swap(), &user).await,
+        Err(InstructionError::Custom(PoolError::InvalidComplianceAccount as u32))
+    );
+}
@@ -0,0 +1,408 @@
+#![cfg(feature = "test-bpf")]
+
+mod helpers;
+
+use helpers::{bank::*, AmountT};
+
+use borsh::{BorshDeserialize, BorshSerialize};
+use pool::{
+    cpi::{simulate_cpi, swap_exact_input_cpi, CpiContext, DeFiAccounts},
+    error::PoolError,
+    instruction::{DeFiInstruction, GovernanceInstruction, SwapHookData},
+    state::{CallerMode, MAX_HOOK_PROGRAMS},
+    TOKEN_COUNT,
+};
+use solana_program::{
+    account_info::AccountInfo, entrypoint::ProgramResult, program_error::ProgramError, program_pack::Pack,
+};
+use solana_program_test::*;
+use solana_sdk::{
+    account::Account,
+    instruction::{AccountMeta, InstructionError},
+    pubkey::Pubkey,
+    rent::Rent,
+    signature::Signer,
+};
+use spl_token::{error::TokenError, state::Account as TokenState};
+
+const POOL_BALANCE: AmountT = 1_000_000_000;
+
+#[derive(BorshSerialize, BorshDeserialize)]
+struct CallerSwap {
+    exact_input_amounts: [AmountT; TOKEN_COUNT],
+    output_token_index: u8,
+    minimum_output_amount: AmountT,
+}
+
+//an integrating program: expects the pool program followed by the accounts of the swap in the pool's order
+//simulates the swap first and insists on receiving exactly what the simulation returned
+fn process_caller(_program_id: &Pubkey, accounts: &[AccountInfo], data: &[u8]) -> ProgramResult {
+    let swap = CallerSwap::try_from_slice(data).map_err(|_| ProgramError::InvalidInstructionData)?;
+    let ctx = CpiContext::new(accounts[0].clone(), DeFiAccounts::from_slice(&accounts[1..], false)?);
+    let simulation_result = simulate_cpi(
+        &ctx,
+        DeFiInstruction::SwapExactInput {
+            exact_input_amounts: swap.exact_input_amounts,
+            output_token_index: swap.output_token_index,
+            minimum_output_amount: swap.minimum_output_amount,
+        },
+    )?;
+    let output_account = &ctx.accounts.user_token_accounts[swap.output_token_index as usize];
+    let balance = || TokenState::unpack(&output_account.data.borrow()).map(|token_state| token_state.amount);
+    let balance_before = balance()?;
+    swap_exact_input_cpi(
+        &ctx,
+        swap.exact_input_amounts,
+        swap.output_token_index,
+        simulation_result.output_amounts[swap.output_token_index as usize],
+    )?;
+    if balance()? - balance_before != simulation_result.output_amounts[swap.output_token_index as usize] {
+        return Err(ProgramError::Custom(u32::MAX));
+    }
+    Ok(())
+}
+
+//a swap hook that records what it was called back with in its first account and fails if any account signed
+fn process_hook(_program_id: &Pubkey, accounts: &[AccountInfo], data: &[u8]) -> ProgramResult {
+    if accounts.iter().any(|account| account.is_signer) {
+        return Err(ProgramError::Custom(u32::MAX));
+    }
+    accounts[0].data.borrow_mut()[..data.len()].copy_from_slice(data);
+    Ok(())
+}
+
+const DELEGATE_SEED: &[u8] = b"delegate";
+
+//an integrating program that pays with its program derived address, which users approve as delegate
+fn process_delegate_caller(program_id: &Pubkey, accounts: &[AccountInfo], data: &[u8]) -> ProgramResult {
+    let swap = CallerSwap::try_from_slice(data).map_err(|_| ProgramError::InvalidInstructionData)?;
+    let (delegate, bump) = Pubkey::find_program_address(&[DELEGATE_SEED], program_id);
+    let defi_accounts = DeFiAccounts::from_slice(&accounts[1..], false)?;
+    if *defi_accounts.user_transfer_authority.key != delegate {
+        return Err(ProgramError::InvalidArgument);
+    }
+    defi_accounts.check_input_authority(&swap.exact_input_amounts)?;
+    let signer_seeds: &[&[&[u8]]] = &[&[DELEGATE_SEED, &[bump]]];
+    let ctx = CpiContext::new(accounts[0].clone(), defi_accounts).with_signer(signer_seeds);
+    swap_exact_input_cpi(
+        &ctx,
+        swap.exact_input_amounts,
+        swap.output_token_index,
+        swap.minimum_output_amount,
+    )
+}
+
+async fn caller_swap(
+    pool: &mut BankPool,
+    caller: &Pubkey,
+    user: &BankUser,
+    swap: CallerSwap,
+) -> Result<(), InstructionError> {
+    let mut ix = pool.defi_ix(
+        DeFiInstruction::SwapExactInput {
+            exact_input_amounts: swap.exact_input_amounts,
+            output_token_index: swap.output_token_index,
+            minimum_output_amount: swap.minimum_output_amount,
+        },
+        user,
+    );
+    ix.accounts.insert(0, AccountMeta::new_readonly(pool::id(), false));
+    ix.program_id = *caller;
+    ix.data = swap.try_to_vec().unwrap();
+    pool.execute_transaction(ix, &[&user.keypair]).await
+}
+
+#[tokio::test]
+async fn test_swap_via_cpi() {
+    let caller = Pubkey::new_unique();
+    let mut pool = BankPool::new_with(&BankPoolParams::default(), |program_test| {
+        program_test.add_program("caller", caller, processor!(process_caller));
+    })
+    .await
+    .unwrap();
+    let user = pool.create_user(&[POOL_BALANCE; TOKEN_COUNT]);
+    pool.execute_defi_instruction(
+        DeFiInstruction::Add {
+            input_amounts: [POOL_BALANCE / 2; TOKEN_COUNT],
+            minimum_mint_amount: 0,
+        },
+        &user,
+    )
+    .await
+    .unwrap();
+
+    let mut exact_input_amounts = [0; TOKEN_COUNT];
+    exact_input_amounts[0] = 1_000_000;
+    let swap = |minimum_output_amount| CallerSwap {
+        exact_input_amounts,
+        output_token_index: 1,
+        minimum_output_amount,
+    };
+
+    assert_eq!(
+        caller_swap(&mut pool, &caller, &user, swap(1_000_000)).await,
+        Err(InstructionError::Custom(PoolError::MinimumOutputNotMet as u32))
+    );
+
+    let balances_before = pool.balances(&user.tokens).await;
+    caller_swap(&mut pool, &caller, &user, swap(990_000)).await.unwrap();
+    let balances_after = pool.balances(&user.tokens).await;
+    assert_eq!(balances_after[0], balances_before[0] - 1_000_000);
+    assert!(balances_after[1] > balances_before[1] + 990_000);
+}
+
+#[tokio::test]
+async fn test_simulate_leaves_pool_untouched() {
+    let mut pool = BankPool::new(&BankPoolParams::default()).await.unwrap();
+    let user = pool.create_user(&[POOL_BALANCE; TOKEN_COUNT]);
+    let add = || DeFiInstruction::Add {
+        input_amounts: [POOL_BALANCE / 2; TOKEN_COUNT],
+        minimum_mint_amount: 0,
+    };
+    pool.execute_defi_instruction(add(), &user).await.unwrap();
+
+    let state_before = pool.state().await.try_to_vec().unwrap();
+    let balances_before = pool.balances(&user.tokens).await;
+    let lp_before = pool.balance(&user.lp).await;
+    let mut exact_input_amounts = [0; TOKEN_COUNT];
+    exact_input_amounts[0] = 1_000_000;
+    for defi_instruction in vec![
+        add(),
+        DeFiInstruction::SwapExactInput {
+            exact_input_amounts,
+            output_token_index: 1,
+            minimum_output_amount: 0,
+        },
+        DeFiInstruction::RemoveUniform {
+            exact_burn_amount: lp_before / 2,
+            minimum_output_amounts: [0; TOKEN_COUNT],
+        },
+    ] {
+        pool.execute_defi_instruction(DeFiInstruction::Simulate(Box::new(defi_instruction)), &user)
+            .await
+            .unwrap();
+    }
+    assert_eq!(pool.balances(&user.tokens).await, balances_before);
+    assert_eq!(pool.balance(&user.lp).await, lp_before);
+    assert_eq!(pool.state().await.try_to_vec().unwrap(), state_before);
+
+    let nested = DeFiInstruction::Simulate(Box::new(DeFiInstruction::Simulate(Box::new(add()))));
+    assert_eq!(
+        pool.execute_defi_instruction(nested, &user).await,
+        Err(InstructionError::InvalidInstructionData)
+    );
+}
+
+#[tokio::test]
+async fn test_swap_hook() {
+    let hook = Pubkey::new_unique();
+    let mut pool = BankPool::new_with(&BankPoolParams::default(), |program_test| {
+        program_test.add_program("hook", hook, processor!(process_hook));
+    })
+    .await
+    .unwrap();
+    let user = pool.create_user(&[POOL_BALANCE; TOKEN_COUNT]);
+    pool.execute_defi_instruction(
+        DeFiInstruction::Add {
+            input_amounts: [POOL_BALANCE / 2; TOKEN_COUNT],
+            minimum_mint_amount: 0,
+        },
+        &user,
+    )
+    .await
+    .unwrap();
+    let record = Pubkey::new_unique();
+    let record_len = SwapHookData {
+        pool: Pubkey::default(),
+        input_amounts: [0; TOKEN_COUNT],
+        output_amounts: [0; TOKEN_COUNT],
+    }
+    .try_to_vec()
+    .unwrap()
+    .len();
+    pool.context.set_account(
+        &record,
+        &Account {
+            lamports: Rent::default().minimum_balance(record_len),
+            data: vec![0; record_len],
+            owner: hook,
+            ..Account::default()
+        }
+        .into(),
+    );
+
+    let mut exact_output_amounts = [0; TOKEN_COUNT];
+    exact_output_amounts[1] = 1_000_000;
+    let mut ix = pool.defi_ix(
+        DeFiInstruction::SwapExactOutput {
+            maximum_input_amount: 2_000_000,
+            input_token_index: 0,
+            exact_output_amounts,
+        },
+        &user,
+    );
+    ix.accounts.push(AccountMeta::new_readonly(hook, false));
+    ix.accounts.push(AccountMeta::new(record, false));
+    //the user signed the swap, but the hook doesn't get the signature
+    ix.accounts.push(AccountMeta::new_readonly(user.keypair.pubkey(), true));
+    assert_eq!(
+        pool.execute_transaction(ix.clone(), &[&user.keypair]).await,
+        Err(InstructionError::Custom(PoolError::HookProgramNotAllowed as u32))
+    );
+
+    let mut hook_programs = [Pubkey::default(); MAX_HOOK_PROGRAMS];
+    hook_programs[0] = hook;
+    pool.execute_governance_instruction(GovernanceInstruction::SetHookPrograms { hook_programs }, None)
+        .await
+        .unwrap();
+    assert_eq!(pool.state().await.hook_programs, hook_programs);
+
+    let balance_before = pool.balance(&user.tokens[0]).await;
+    pool.execute_transaction(ix, &[&user.keypair]).await.unwrap();
+    let input_amount = balance_before - pool.balance(&user.tokens[0]).await;
+    let account = pool.context.banks_client.get_account(record).await.unwrap().unwrap();
+    let mut expected_input_amounts = [0; TOKEN_COUNT];
+    expected_input_amounts[0] = input_amount;
+    assert_eq!(
+        SwapHookData::<TOKEN_COUNT>::try_from_slice(&account.data).unwrap(),
+        SwapHookData {
+            pool: pool.pool,
+            input_amounts: expected_input_amounts,
+            output_amounts: exact_output_amounts,
+        }
+    );
+}
+
+#[tokio::test]
+async fn test_swap_via_cpi_with_delegate() {
+    let caller = Pubkey::new_unique();
+    let mut pool = BankPool::new_with(&BankPoolParams::default(), |program_test| {
+        program_test.add_program("delegate_caller", caller, processor!(process_delegate_caller));
+    })
+    .await
+    .unwrap();
+    let user = pool.create_user(&[POOL_BALANCE; TOKEN_COUNT]);
+    pool.execute_defi_instruction(
+        DeFiInstruction::Add {
+            input_amounts: [POOL_BALANCE / 2; TOKEN_COUNT],
+            minimum_mint_amount: 0,
+        },
+        &user,
+    )
+    .await
+    .unwrap();
+
+    let delegate = Pubkey::find_program_address(&[DELEGATE_SEED], &caller).0;
+    let mut exact_input_amounts = [0; TOKEN_COUNT];
+    exact_input_amounts[0] = 1_000_000;
+    let mut ix = pool.defi_ix(
+        DeFiInstruction::SwapExactInput {
+            exact_input_amounts,
+            output_token_index: 1,
+            minimum_output_amount: 0,
+        },
+        &user,
+    );
+    //the user doesn't sign the call, the caller's delegate does
+    ix.accounts[4 + TOKEN_COUNT] = AccountMeta::new_readonly(delegate, false);
+    ix.accounts.insert(0, AccountMeta::new_readonly(pool::id(), false));
+    ix.program_id = caller;
+    ix.data = CallerSwap {
+        exact_input_amounts,
+        output_token_index: 1,
+        minimum_output_amount: 0,
+    }
+    .try_to_vec()
+    .unwrap();
+    let approve = |amount| {
+        spl_token::instruction::approve(
+            &spl_token::id(),
+            &user.tokens[0],
+            &delegate,
+            &user.keypair.pubkey(),
+            &[],
+            amount,
+        )
+        .unwrap()
+    };
+
+    assert_eq!(
+        pool.execute_transaction(ix.clone(), &[]).await,
+        Err(InstructionError::Custom(TokenError::OwnerMismatch as u32))
+    );
+    assert_eq!(
+        pool.execute_instructions(&[approve(999_999), ix.clone()], &[&user.keypair])
+            .await,
+        Err(InstructionError::Custom(TokenError::InsufficientFunds as u32))
+    );
+
+    let balances_before = pool.balances(&user.tokens).await;
+    pool.execute_instructions(&[approve(1_000_000), ix], &[&user.keypair])
+        .await
+        .unwrap();
+    let balances_after = pool.balances(&user.tokens).await;
+    assert_eq!(balances_after[0], balances_before[0] - 1_000_000);
+    assert!(balances_after[1] > balances_before[1]);
+}
+
+#[tokio::test]
+async fn test_caller_allowlist() {
+    let caller = Pubkey::new_unique();
+    let mut pool = BankPool::new_with(&BankPoolParams::default(), |program_test| {
+        program_test.add_program("caller", caller, processor!(process_caller));
+    })
+    .await
+    .unwrap();
+    let user = pool.create_user(&[POOL_BALANCE; TOKEN_COUNT]);
+    pool.execute_defi_instruction(
+        DeFiInstruction::Add {
+            input_amounts: [POOL_BALANCE / 2; TOKEN_COUNT],
+            minimum_mint_amount: 0,
+        },
+        &user,
+    )
+    .await
+    .unwrap();
+
+    let mut exact_input_amounts = [0; TOKEN_COUNT];
+    exact_input_amounts[0] = 1_000;
+    let swap = || CallerSwap {
+        exact_input_amounts,
+        output_token_index: 1,
+        minimum_output_amount: 0,
+    };
+    let direct_swap = || DeFiInstruction::SwapExactInput {
+        exact_input_amounts,
+        output_token_index: 1,
+        minimum_output_amount: 0,
+    };
+    let not_allowed = Err(InstructionError::Custom(PoolError::CallerNotAllowed as u32));
+
+    pool.set_caller_allowlist(CallerMode::AllowedPrograms, &[Pubkey::new_unique()])
+        .await
+        .unwrap();
+    assert_eq!(caller_swap(&mut pool, &caller, &user, swap()).await, not_allowed);
+    pool.execute_defi_instruction(direct_swap(), &user).await.unwrap();
+
+    pool.set_caller_allowlist(CallerMode::AllowedPrograms, &[caller])
+        .await
+        .unwrap();
+    caller_swap(&mut pool, &caller, &user, swap()).await.unwrap();
+
+    pool.set_caller_allowlist(CallerMode::AllowedProgramsOnly, &[caller])
+        .await
+        .unwrap();
+    caller_swap(&mut pool, &caller, &user, swap()).await.unwrap();
+    assert_eq!(pool.execute_defi_instruction(direct_swap(), &user).await, not_allowed);
+    //liquidity providers can always exit
+    let lp_amount = pool.balance(&user.lp).await;
+    pool.execute_defi_instruction(
+        DeFiInstruction::RemoveUniform {
+            exact_burn_amount: lp_amount,
+            minimum_output_amounts: [0; TOKEN_COUNT],
+        },
+        &user,
+    )
+    .await
+    .unwrap();
+}
@@ -0,0 +1,63 @@
+#![cfg(feature = "test-bpf")]
+
+mod helpers;
+
+use helpers::{bank::*, AmountT};
+
+use pool::{
+    instruction::{find_pool_address, DeFiInstruction},
+    TOKEN_COUNT,
+};
+use solana_program_test::*;
+use solana_sdk::{instruction::InstructionError, signature::Signer};
+use spl_token::state::{Account as TokenState, Mint as MintState};
+
+const POOL_BALANCE: AmountT = 1_000_000_000;
+
+#[tokio::test]
+async fn test_create_pool_at_canonical_address() {
+    let mut pool = BankPool::new_canonical(&BankPoolParams::default(), 4).await.unwrap();
+    let state = pool.state().await;
+    assert_eq!(state.token_mint_keys, pool.token_mints);
+    assert_eq!(state.token_keys, pool.token_accounts);
+    assert_eq!(state.lp_mint_key, pool.lp_mint);
+    assert_eq!(state.governance_key, pool.governance_keypair.pubkey());
+    assert_eq!(state.governance_fee_key, pool.governance_fee_account);
+
+    let lp_mint_key = pool.lp_mint;
+    let lp_mint = pool.get_packed::<MintState>(&lp_mint_key).await;
+    assert_eq!(lp_mint.mint_authority, Some(pool.authority).into());
+    assert_eq!(lp_mint.decimals, 6);
+    let governance_fee_key = pool.governance_fee_account;
+    let governance_fee_account = pool.get_packed::<TokenState>(&governance_fee_key).await;
+    assert_eq!(governance_fee_account.owner, pool.governance_keypair.pubkey());
+
+    //the created pool works like any other
+    let user = pool.create_user(&[POOL_BALANCE; TOKEN_COUNT]);
+    pool.execute_defi_instruction(
+        DeFiInstruction::Add {
+            input_amounts: [POOL_BALANCE; TOKEN_COUNT],
+            minimum_mint_amount: 0,
+        },
+        &user,
+    )
+    .await
+    .unwrap();
+    assert_eq!(pool.pool_balances().await, [POOL_BALANCE; TOKEN_COUNT]);
+}
+
+#[tokio::test]
+async fn test_create_pool_only_once_per_fee_tier() {
+    let params = BankPoolParams::default();
+    let mut pool = BankPool::new_canonical(&params, 4).await.unwrap();
+    let ix = pool.create_pool_ix(&params, 4);
+    //the system program refuses to allocate the existing pool account
+    assert_eq!(
+        pool.execute_transaction(ix, &[]).await,
+        Err(InstructionError::Custom(0))
+    );
+
+    let ix = pool.create_pool_ix(&params, 30);
+    pool.execute_transaction(ix, &[]).await.unwrap();
+    assert_ne!(find_pool_address(&pool::id(), &pool.token_mints, 30).0, pool.pool);
+}
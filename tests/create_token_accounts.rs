@@ -0,0 +1,61 @@
+#![cfg(feature = "test-bpf")]
+
+mod helpers;
+
+use helpers::bank::*;
+
+use pool::{error::PoolError, instruction::create_pool_token_accounts_ix, TOKEN_COUNT};
+use solana_program_test::*;
+use solana_sdk::{instruction::InstructionError, pubkey::Pubkey, signature::Signer};
+use spl_associated_token_account::get_associated_token_address;
+use spl_token::state::Account as TokenState;
+
+async fn create_pool_token_accounts(pool: &mut BankPool) -> Result<(), InstructionError> {
+    let state = pool.state().await;
+    let ix = create_pool_token_accounts_ix(
+        &pool::id(),
+        &pool.pool,
+        &pool.authority,
+        &state.token_keys,
+        &state.token_mint_keys,
+        &state.governance_fee_key,
+        &state.governance_key,
+        &state.lp_mint_key,
+        &pool.context.payer.pubkey(),
+    )
+    .unwrap();
+    pool.execute_transaction(ix, &[]).await
+}
+
+#[tokio::test]
+async fn test_creates_missing_associated_token_accounts() {
+    let mut pool = BankPool::new(&BankPoolParams::default()).await.unwrap();
+    //nothing is missing
+    create_pool_token_accounts(&mut pool).await.unwrap();
+
+    //as if the pool's first token had been remapped to a mint that the pool has no account for yet
+    let associated_token_account = get_associated_token_address(&pool.authority, &pool.token_mints[0]);
+    let mut state = pool.state().await;
+    state.token_keys[0] = associated_token_account;
+    pool.set_state(&state).await;
+
+    create_pool_token_accounts(&mut pool).await.unwrap();
+    let token_state = pool.get_packed::<TokenState>(&associated_token_account).await;
+    assert_eq!(token_state.owner, pool.authority);
+    assert_eq!(token_state.mint, pool.token_mints[0]);
+    //and again, which leaves the now existing account alone
+    create_pool_token_accounts(&mut pool).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_only_creates_associated_token_accounts() {
+    let mut pool = BankPool::new(&BankPoolParams::default()).await.unwrap();
+    let mut state = pool.state().await;
+    state.token_keys[TOKEN_COUNT - 1] = Pubkey::new_unique();
+    pool.set_state(&state).await;
+
+    assert_eq!(
+        create_pool_token_accounts(&mut pool).await,
+        Err(InstructionError::Custom(PoolError::PoolTokenAccountExpected as u32))
+    );
+}
@@ -0,0 +1,43 @@
+#![cfg(feature = "test-bpf")]
+
+mod helpers;
+
+use helpers::{bank::*, DecT};
+
+use borsh::BorshDeserialize;
+use pool::{client::deploy::DeployManifest, state::PoolState, TOKEN_COUNT};
+use solana_program_test::*;
+use solana_sdk::{pubkey::Pubkey, signature::Signer};
+
+fn manifest_pool(name: &str, mints: &[Pubkey; TOKEN_COUNT], fee_tier: u16, governance: &Pubkey) -> String {
+    let mints: Vec<_> = mints.iter().map(|mint| format!("\"{}\"", mint)).collect();
+    format!(
+        "[[pools]]\nname = \"{}\"\nmints = [{}]\nfee_tier = {}\namp_factor = \"1000\"\nlp_fee = \"3bps\"\ngovernance = \"{}\"\n",
+        name,
+        mints.join(", "),
+        fee_tier,
+        governance
+    )
+}
+
+#[tokio::test]
+async fn test_deploy_plan_creates_every_pool_of_the_manifest() {
+    let mut pool = BankPool::new_uninitialized(&BankPoolParams::default()).await;
+    let governance = pool.governance_keypair.pubkey();
+    let manifest = DeployManifest::from_toml(&format!(
+        "{}{}",
+        manifest_pool("tier 0", &pool.token_mints, 0, &governance),
+        manifest_pool("tier 1", &pool.token_mints, 1, &governance)
+    ))
+    .unwrap();
+    let plan = manifest.plan(&pool::id(), &pool.context.payer.pubkey()).unwrap();
+
+    for step in plan.steps.iter() {
+        pool.execute_transaction(step.instruction.clone(), &[]).await.unwrap();
+        let account = pool.context.banks_client.get_account(step.pool).await.unwrap().unwrap();
+        let state = PoolState::<TOKEN_COUNT>::deserialize(&mut &account.data[..]).unwrap();
+        assert_eq!(state.governance_key, governance);
+        assert_eq!(state.lp_fee.get(), DecT::new(3, 4).unwrap());
+    }
+    assert_ne!(plan.steps[0].pool, plan.steps[1].pool);
+}
@@ -0,0 +1,167 @@
+#![cfg(feature = "test-bpf")]
+
+mod helpers;
+
+use helpers::{bank::*, AmountT};
+
+use pool::{
+    error::PoolError,
+    instruction::{DeFiInstruction, GovernanceInstruction},
+    state::{EMERGENCY_CONFIRMATION_WINDOW, MAX_EMERGENCY_RESPONDERS},
+    TOKEN_COUNT,
+};
+use solana_program_test::*;
+use solana_sdk::{
+    instruction::InstructionError,
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+};
+
+const POOL_BALANCE: AmountT = 1_000_000_000;
+
+fn swap() -> DeFiInstruction<TOKEN_COUNT> {
+    let mut exact_input_amounts = [0; TOKEN_COUNT];
+    exact_input_amounts[0] = 1_000_000;
+    DeFiInstruction::SwapExactInput {
+        exact_input_amounts,
+        output_token_index: 1,
+        minimum_output_amount: 0,
+    }
+}
+
+//a funded pool with three responders, two of whom have to confirm
+async fn pool_with_responders() -> (BankPool, BankUser, [Keypair; 3]) {
+    let mut pool = BankPool::new(&BankPoolParams::default()).await.unwrap();
+    let user = pool.create_user(&[POOL_BALANCE; TOKEN_COUNT]);
+    pool.execute_defi_instruction(
+        DeFiInstruction::Add {
+            input_amounts: [POOL_BALANCE / 2; TOKEN_COUNT],
+            minimum_mint_amount: 0,
+        },
+        &user,
+    )
+    .await
+    .unwrap();
+    let responders = [Keypair::new(), Keypair::new(), Keypair::new()];
+    let keys: Vec<Pubkey> = responders.iter().map(|responder| responder.pubkey()).collect();
+    pool.set_emergency_responders(&keys, 2).await.unwrap();
+    (pool, user, responders)
+}
+
+#[tokio::test]
+async fn test_responders_pause_the_pool() {
+    let (mut pool, user, responders) = pool_with_responders().await;
+    pool.confirm_emergency(&responders[0]).await.unwrap();
+    //a responder confirming again doesn't count twice
+    pool.advance_clock(1).await;
+    pool.confirm_emergency(&responders[0]).await.unwrap();
+    assert!(!pool.state().await.is_paused);
+    pool.execute_defi_instruction(swap(), &user).await.unwrap();
+
+    pool.confirm_emergency(&responders[2]).await.unwrap();
+    let state = pool.state().await;
+    assert!(state.is_paused);
+    assert_eq!(state.emergency_confirmation_ts, [0; MAX_EMERGENCY_RESPONDERS]);
+    assert_eq!(
+        pool.execute_defi_instruction(swap(), &user).await,
+        Err(InstructionError::Custom(PoolError::PoolIsPaused as u32))
+    );
+    //withdrawal-only: uniform removes still work
+    let lp_balance = pool.balance(&user.lp).await;
+    pool.execute_defi_instruction(
+        DeFiInstruction::RemoveUniform {
+            exact_burn_amount: lp_balance / 2,
+            minimum_output_amounts: [0; TOKEN_COUNT],
+        },
+        &user,
+    )
+    .await
+    .unwrap();
+    assert_eq!(
+        pool.confirm_emergency(&responders[1]).await,
+        Err(InstructionError::Custom(PoolError::PoolIsPaused as u32))
+    );
+
+    //only governance lifts it
+    pool.execute_governance_instruction(GovernanceInstruction::SetPaused { paused: false }, None)
+        .await
+        .unwrap();
+    pool.execute_defi_instruction(swap(), &user).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_confirmations_expire() {
+    let (mut pool, _, responders) = pool_with_responders().await;
+    pool.confirm_emergency(&responders[0]).await.unwrap();
+    pool.advance_clock(EMERGENCY_CONFIRMATION_WINDOW).await;
+    pool.confirm_emergency(&responders[1]).await.unwrap();
+    assert!(!pool.state().await.is_paused);
+    pool.confirm_emergency(&responders[2]).await.unwrap();
+    assert!(pool.state().await.is_paused);
+}
+
+#[tokio::test]
+async fn test_responders_can_do_nothing_else() {
+    let (mut pool, _, responders) = pool_with_responders().await;
+    assert_eq!(
+        pool.confirm_emergency(&Keypair::new()).await,
+        Err(InstructionError::Custom(PoolError::NotEmergencyResponder as u32))
+    );
+    assert_eq!(
+        pool.execute_governance_instruction_as(GovernanceInstruction::SetPaused { paused: true }, None, &responders[0])
+            .await,
+        Err(InstructionError::Custom(PoolError::InvalidGovernanceAccount as u32))
+    );
+}
+
+#[tokio::test]
+async fn test_set_emergency_responders() {
+    let (mut pool, _, responders) = pool_with_responders().await;
+    pool.confirm_emergency(&responders[0]).await.unwrap();
+    let keys = [responders[0].pubkey(), responders[1].pubkey()];
+    for threshold in [0, 3] {
+        assert_eq!(
+            pool.set_emergency_responders(&keys, threshold).await,
+            Err(InstructionError::InvalidInstructionData)
+        );
+    }
+    assert_eq!(
+        pool.set_emergency_responders(&[keys[0], keys[0]], 1).await,
+        Err(InstructionError::Custom(PoolError::DuplicateAccount as u32))
+    );
+    //replacing the responders resets their confirmations
+    pool.set_emergency_responders(&keys, 2).await.unwrap();
+    assert_eq!(
+        pool.state().await.emergency_confirmation_ts,
+        [0; MAX_EMERGENCY_RESPONDERS]
+    );
+    pool.confirm_emergency(&responders[1]).await.unwrap();
+    assert!(!pool.state().await.is_paused);
+    assert_eq!(
+        pool.confirm_emergency(&responders[2]).await,
+        Err(InstructionError::Custom(PoolError::NotEmergencyResponder as u32))
+    );
+
+    pool.set_emergency_responders(&[], 0).await.unwrap();
+    assert_eq!(
+        pool.confirm_emergency(&responders[1]).await,
+        Err(InstructionError::Custom(PoolError::NotEmergencyResponder as u32))
+    );
+}
+
+#[tokio::test]
+async fn test_renouncing_governance_removes_responders() {
+    let (mut pool, _, responders) = pool_with_responders().await;
+    pool.execute_governance_instruction(
+        GovernanceInstruction::RenounceGovernance { lock_amp_factor: false },
+        None,
+    )
+    .await
+    .unwrap();
+    let state = pool.state().await;
+    assert_eq!(state.emergency_threshold, 0);
+    assert_eq!(
+        pool.confirm_emergency(&responders[0]).await,
+        Err(InstructionError::Custom(PoolError::NotEmergencyResponder as u32))
+    );
+}
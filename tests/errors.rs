@@ -0,0 +1,1108 @@
+#![cfg(feature = "test-bpf")]
+
+//one minimal malformed input per PoolError variant, asserting the exact error code that the program returns
+//
+//the match in trigger is exhaustive so a new variant doesn't compile without a corresponding input, and the code table
+// catches accidental renumbering (clients match on these codes, so they are part of the program's interface)
+
+mod helpers;
+
+use borsh::BorshSerialize;
+use helpers::{bank::*, AmountT, DecT};
+
+use pool::{
+    amp_factor::MIN_ADJUSTMENT_WINDOW,
+    client::{
+        explain::{ErrorClass, ExplainedError},
+        explain_error, ClientError,
+    },
+    decode_custom_error,
+    error::{CustomError, PoolError},
+    instruction::{
+        create_migrate_in_ix, create_publish_virtual_price_ix, create_routed_swap_ix, find_virtual_price_address,
+        DeFiInstruction, GovernanceInstruction, PoolInstruction, RfqQuote,
+    },
+    migration::{MigrationSource, SourcePool},
+    oracle::{OracleKind, RateSource, EMA_ONE, RATE_ONE},
+    processor::{ENACT_DELAY, MAX_DECIMAL_DIFFERENCE},
+    state::{
+        CallerMode, MaintenanceWindow, PriceBand, TokenFlags, TokenParticipation, TradingHours, UserGate, YieldAdapter,
+        MAX_LOCK_DURATION, MIN_LOCK_DURATION, POOL_STATE_LAYOUT_VERSION,
+    },
+    TOKEN_COUNT,
+};
+use solana_program::program_option::COption;
+use solana_program_test::*;
+use solana_sdk::{
+    instruction::{AccountMeta, Instruction, InstructionError},
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+    transaction::TransactionError,
+};
+use spl_token::{
+    error::TokenError,
+    state::{Account as TokenState, AccountState, Mint as MintState},
+};
+use std::convert::TryFrom;
+
+const EXPECTED_CODES: [(u32, PoolError); 92] = [
+    (100, PoolError::InvalidAmpFactorValue),
+    (101, PoolError::InvalidAmpFactorTimestamp),
+    (102, PoolError::InvalidFeeInput),
+    (103, PoolError::DuplicateAccount),
+    (104, PoolError::MintHasBalance),
+    (105, PoolError::InvalidMintAuthority),
+    (106, PoolError::MintHasFreezeAuthority),
+    (107, PoolError::TokenAccountHasBalance),
+    (108, PoolError::TokenAccountHasDelegate),
+    (109, PoolError::TokenAccountHasCloseAuthority),
+    (110, PoolError::InvalidGovernanceAccount),
+    (111, PoolError::InvalidGovernanceFeeAccount),
+    (112, PoolError::InvalidPoolAuthorityAccount),
+    (113, PoolError::InvalidMintAccount),
+    (114, PoolError::InsufficientDelay),
+    (115, PoolError::InvalidEnact),
+    (116, PoolError::PoolIsPaused),
+    (117, PoolError::PoolTokenAccountExpected),
+    (118, PoolError::OutsideSpecifiedLimits),
+    (119, PoolError::AddRequiresAllTokens),
+    (120, PoolError::ImpossibleRemove),
+    (121, PoolError::MaxDecimalDifferenceExceeded),
+    (122, PoolError::InvalidOracleAccount),
+    (123, PoolError::OraclePriceUnavailable),
+    (124, PoolError::OracleDeviationExceeded),
+    (125, PoolError::TokenDepegged),
+    (126, PoolError::InvalidVirtualPriceAccount),
+    (127, PoolError::PoolIsEmpty),
+    (128, PoolError::InvalidRateAccount),
+    (129, PoolError::RateUnavailable),
+    (130, PoolError::HookProgramNotAllowed),
+    (131, PoolError::InvalidRouteAccount),
+    (132, PoolError::CallerNotAllowed),
+    (133, PoolError::UnsortedTokenMints),
+    (134, PoolError::NonCanonicalAddress),
+    (135, PoolError::InvalidMigrationAccount),
+    (136, PoolError::PoolIsSunset),
+    (137, PoolError::PoolNotPaused),
+    (138, PoolError::InvalidStateExport),
+    (139, PoolError::GovernanceRenounced),
+    (140, PoolError::LpStillLocked),
+    (141, PoolError::InvalidLockDuration),
+    (142, PoolError::LpNonTransferable),
+    (143, PoolError::DustPosition),
+    (144, PoolError::LaunchCapExceeded),
+    (145, PoolError::UserNotAllowed),
+    (146, PoolError::TokenInputDisabled),
+    (147, PoolError::TokenOutputDisabled),
+    (148, PoolError::DepositorNotAllowed),
+    (149, PoolError::SessionExpired),
+    (150, PoolError::SessionLimitExceeded),
+    (151, PoolError::InvalidQuoteSignature),
+    (152, PoolError::QuoteExpired),
+    (153, PoolError::QuoteAlreadyUsed),
+    (154, PoolError::QuoteDeviationExceeded),
+    (155, PoolError::InvalidComplianceAccount),
+    (156, PoolError::OutsideTradingHours),
+    (157, PoolError::PoolInMaintenance),
+    (158, PoolError::InvalidTradingSchedule),
+    (159, PoolError::WithdrawalNotAnnounced),
+    (160, PoolError::WithdrawalCooldownActive),
+    (161, PoolError::NotEmergencyResponder),
+    (162, PoolError::MinimumMintNotMet),
+    (163, PoolError::MinimumOutputNotMet),
+    (164, PoolError::MaximumInputExceeded),
+    (165, PoolError::MaximumBurnExceeded),
+    (166, PoolError::TokenCountMismatch),
+    (167, PoolError::InvariantOverflow),
+    (168, PoolError::InvariantUnderflow),
+    (169, PoolError::InvariantConvergenceFailure),
+    (170, PoolError::InsufficientUserBalance),
+    (171, PoolError::InvalidUserTokenAccount),
+    (172, PoolError::InvalidUserAuthority),
+    (173, PoolError::UserTokenAccountFrozen),
+    (174, PoolError::InvalidTokenWeights),
+    (175, PoolError::InvalidPriceBand),
+    (176, PoolError::PriceOutOfBand),
+    (177, PoolError::TokenDepositOnly),
+    (178, PoolError::TokenWithdrawOnly),
+    (179, PoolError::InvalidPegKeeper),
+    (180, PoolError::PegCreditExceeded),
+    (181, PoolError::PegCreditPastPeg),
+    (182, PoolError::InvalidYieldAdapter),
+    (183, PoolError::YieldBufferShort),
+    (184, PoolError::InvalidPriceScale),
+    (185, PoolError::RepegNotDue),
+    (186, PoolError::InvalidGauge),
+    (187, PoolError::InsufficientStake),
+    (188, PoolError::InvalidRebateCampaign),
+    (189, PoolError::RebateCampaignNotEnded),
+    (190, PoolError::InvalidVeProgram),
+    (191, PoolError::InvalidOracleLimits),
+    (192, PoolError::PoolLayoutOutdated),
+    (193, PoolError::InvalidPoolLayout),
+];
+
+const AT_PEG: (i64, i32) = (100_000_000, -8);
+
+const POOL_BALANCE: AmountT = 1_000_000_000;
+
+fn single(index: usize, amount: AmountT) -> [AmountT; TOKEN_COUNT] {
+    let mut amounts = [0; TOKEN_COUNT];
+    amounts[index] = amount;
+    amounts
+}
+
+fn swap(minimum_output_amount: AmountT) -> DeFiInstruction<TOKEN_COUNT> {
+    DeFiInstruction::SwapExactInput {
+        exact_input_amounts: single(0, POOL_BALANCE / 100),
+        output_token_index: 1,
+        minimum_output_amount,
+    }
+}
+
+fn remove_exact_burn(exact_burn_amount: AmountT) -> DeFiInstruction<TOKEN_COUNT> {
+    DeFiInstruction::RemoveExactBurn {
+        exact_burn_amount,
+        output_token_index: 0,
+        minimum_output_amount: 0,
+    }
+}
+
+fn replace_account(mut ix: Instruction, old: &Pubkey, new: &Pubkey) -> Instruction {
+    for meta in ix.accounts.iter_mut().filter(|meta| meta.pubkey == *old) {
+        meta.pubkey = *new;
+    }
+    ix
+}
+
+async fn init(params: BankPoolParams) -> Result<(), InstructionError> {
+    BankPool::new(&params).await.map(|_| ())
+}
+
+async fn tampered_lp_mint(tamper: impl FnOnce(&mut MintState)) -> Result<(), InstructionError> {
+    let params = BankPoolParams::default();
+    let mut pool = BankPool::new_uninitialized(&params).await;
+    let lp_mint = pool.lp_mint;
+    let mut state: MintState = pool.get_packed(&lp_mint).await;
+    tamper(&mut state);
+    pool.set_packed(&lp_mint, state);
+    let init_ix = pool.init_ix(&params);
+    pool.execute_transaction(init_ix, &[]).await
+}
+
+async fn tampered_token_account(tamper: impl FnOnce(&mut TokenState)) -> Result<(), InstructionError> {
+    let params = BankPoolParams::default();
+    let mut pool = BankPool::new_uninitialized(&params).await;
+    let token_account = pool.token_accounts[0];
+    let mut state: TokenState = pool.get_packed(&token_account).await;
+    tamper(&mut state);
+    pool.set_packed(&token_account, state);
+    let init_ix = pool.init_ix(&params);
+    pool.execute_transaction(init_ix, &[]).await
+}
+
+//a pool that holds POOL_BALANCE of every token and a user that can trade against it
+async fn funded_pool(params: &BankPoolParams) -> (BankPool, BankUser) {
+    let mut pool = BankPool::new(params).await.unwrap();
+    let user = pool.create_user(&[POOL_BALANCE; TOKEN_COUNT]);
+    pool.execute_defi_instruction(
+        DeFiInstruction::Add {
+            input_amounts: [POOL_BALANCE; TOKEN_COUNT],
+            minimum_mint_amount: 0,
+        },
+        &user,
+    )
+    .await
+    .unwrap();
+    (pool, user)
+}
+
+//a pool with an rfq quoter (allowing 1% deviation) and a quote of 1000 of token 0 for `output_amount` of token 1
+async fn rfq_setup(output_amount: AmountT, expiry_offset: i64) -> (BankPool, BankUser, Keypair, RfqQuote) {
+    let (mut pool, user) = funded_pool(&BankPoolParams::default()).await;
+    let quoter = Keypair::new();
+    pool.set_rfq_quoter(&quoter.pubkey(), DecT::new(1, 2).unwrap())
+        .await
+        .unwrap();
+    let quote = RfqQuote {
+        pool: pool.pool,
+        user: user.keypair.pubkey(),
+        input_token_index: 0,
+        input_amount: 1000,
+        output_token_index: 1,
+        output_amount,
+        quote_id: 1,
+        expiry_ts: pool.now().await + expiry_offset,
+    };
+    (pool, user, quoter, quote)
+}
+
+//a swap with one of the accounts that the pool verifies swapped for an unrelated one
+async fn swap_with_replaced_account(select: impl FnOnce(&BankPool) -> Pubkey) -> Result<(), InstructionError> {
+    let (mut pool, user) = funded_pool(&BankPoolParams::default()).await;
+    let ix = replace_account(pool.defi_ix(swap(0), &user), &select(&pool), &Pubkey::new_unique());
+    pool.execute_transaction(ix, &[&user.keypair]).await
+}
+
+//a funded pool whose swaps are checked against oracles quoting every token at 1
+async fn oracle_guarded_pool() -> (BankPool, BankUser, [Pubkey; TOKEN_COUNT]) {
+    let (mut pool, user) = funded_pool(&BankPoolParams::default()).await;
+    let oracle_keys = pool
+        .set_oracle_guard(&[AT_PEG; TOKEN_COUNT], DecT::new(1, 2).unwrap())
+        .await
+        .unwrap();
+    (pool, user, oracle_keys)
+}
+
+//a funded pool whose first token is a stake pool token worth 1.1 of the others
+async fn rated_pool() -> (BankPool, BankUser, Pubkey) {
+    let (mut pool, user) = funded_pool(&BankPoolParams::default()).await;
+    let stake_pool = Pubkey::new_unique();
+    pool.set_stake_pool(&stake_pool, 0, 1_100, 1_000).await;
+    pool.set_rate_source(0, RateSource::SplStakePool, stake_pool)
+        .await
+        .unwrap();
+    (pool, user, stake_pool)
+}
+
+async fn trigger(error: PoolError) -> Result<(), InstructionError> {
+    match error {
+        PoolError::InvalidAmpFactorValue => {
+            init(BankPoolParams {
+                amp_factor: DecT::from(0),
+                ..BankPoolParams::default()
+            })
+            .await
+        }
+        PoolError::InvalidAmpFactorTimestamp => {
+            let mut pool = BankPool::new(&BankPoolParams::default()).await.unwrap();
+            let target_ts = pool.now().await + MIN_ADJUSTMENT_WINDOW - 1;
+            pool.execute_governance_instruction(
+                GovernanceInstruction::AdjustAmpFactor {
+                    target_ts,
+                    target_value: DecT::new(1000, 0).unwrap(),
+                },
+                None,
+            )
+            .await
+        }
+        PoolError::InvalidFeeInput => {
+            init(BankPoolParams {
+                lp_fee: DecT::new(5, 1).unwrap(),
+                governance_fee: DecT::new(5, 1).unwrap(),
+                ..BankPoolParams::default()
+            })
+            .await
+        }
+        PoolError::DuplicateAccount => {
+            let params = BankPoolParams::default();
+            let mut pool = BankPool::new_uninitialized(&params).await;
+            let init_ix = replace_account(pool.init_ix(&params), &pool.token_mints[1], &pool.token_mints[0]);
+            pool.execute_transaction(init_ix, &[]).await
+        }
+        PoolError::MintHasBalance => tampered_lp_mint(|mint| mint.supply = 1).await,
+        PoolError::InvalidMintAuthority => {
+            tampered_lp_mint(|mint| mint.mint_authority = COption::Some(Pubkey::new_unique())).await
+        }
+        PoolError::MintHasFreezeAuthority => {
+            tampered_lp_mint(|mint| mint.freeze_authority = COption::Some(Pubkey::new_unique())).await
+        }
+        PoolError::TokenAccountHasBalance => tampered_token_account(|account| account.amount = 1).await,
+        PoolError::TokenAccountHasDelegate => {
+            tampered_token_account(|account| account.delegate = COption::Some(Pubkey::new_unique())).await
+        }
+        PoolError::TokenAccountHasCloseAuthority => {
+            tampered_token_account(|account| account.close_authority = COption::Some(Pubkey::new_unique())).await
+        }
+        PoolError::InvalidGovernanceAccount => {
+            let mut pool = BankPool::new(&BankPoolParams::default()).await.unwrap();
+            pool.execute_governance_instruction_as(
+                GovernanceInstruction::SetPaused { paused: true },
+                None,
+                &Keypair::new(),
+            )
+            .await
+        }
+        PoolError::InvalidGovernanceFeeAccount => swap_with_replaced_account(|pool| pool.governance_fee_account).await,
+        PoolError::InvalidPoolAuthorityAccount => swap_with_replaced_account(|pool| pool.authority).await,
+        PoolError::InvalidMintAccount => swap_with_replaced_account(|pool| pool.lp_mint).await,
+        PoolError::InsufficientDelay => {
+            let mut pool = BankPool::new(&BankPoolParams::default()).await.unwrap();
+            pool.execute_governance_instruction(
+                GovernanceInstruction::PrepareFeeChange {
+                    lp_fee: DecT::from(0),
+                    governance_fee: DecT::from(0),
+                },
+                None,
+            )
+            .await
+            .unwrap();
+            pool.execute_governance_instruction(GovernanceInstruction::EnactFeeChange {}, None)
+                .await
+        }
+        PoolError::InvalidEnact => {
+            let mut pool = BankPool::new(&BankPoolParams::default()).await.unwrap();
+            pool.execute_governance_instruction(GovernanceInstruction::EnactFeeChange {}, None)
+                .await
+        }
+        PoolError::PoolIsPaused => {
+            let (mut pool, user) = funded_pool(&BankPoolParams::default()).await;
+            pool.execute_governance_instruction(GovernanceInstruction::SetPaused { paused: true }, None)
+                .await
+                .unwrap();
+            pool.execute_defi_instruction(swap(0), &user).await
+        }
+        PoolError::PoolTokenAccountExpected => swap_with_replaced_account(|pool| pool.token_accounts[0]).await,
+        PoolError::OutsideSpecifiedLimits => unreachable!("replaced by the variants of the individual limits"),
+        PoolError::AddRequiresAllTokens => {
+            let mut pool = BankPool::new(&BankPoolParams::default()).await.unwrap();
+            let user = pool.create_user(&[POOL_BALANCE; TOKEN_COUNT]);
+            pool.execute_defi_instruction(
+                DeFiInstruction::Add {
+                    input_amounts: single(0, POOL_BALANCE),
+                    minimum_mint_amount: 0,
+                },
+                &user,
+            )
+            .await
+        }
+        PoolError::ImpossibleRemove => {
+            //with a fee this high, the fee on withdrawing almost the entire balance of a token exceeds what's left
+            let (mut pool, user) = funded_pool(&BankPoolParams {
+                lp_fee: DecT::new(9, 1).unwrap(),
+                governance_fee: DecT::from(0),
+                ..BankPoolParams::default()
+            })
+            .await;
+            pool.execute_defi_instruction(
+                DeFiInstruction::RemoveExactOutput {
+                    maximum_burn_amount: AmountT::MAX,
+                    exact_output_amounts: single(0, POOL_BALANCE - 1),
+                },
+                &user,
+            )
+            .await
+        }
+        PoolError::MaxDecimalDifferenceExceeded => {
+            let mut token_decimals = [0; TOKEN_COUNT];
+            token_decimals[0] = MAX_DECIMAL_DIFFERENCE + 1;
+            init(BankPoolParams {
+                lp_decimals: 0,
+                token_decimals,
+                ..BankPoolParams::default()
+            })
+            .await
+        }
+        PoolError::InvalidOracleAccount => {
+            let (mut pool, user, oracle_keys) = oracle_guarded_pool().await;
+            let ix = replace_account(pool.defi_ix(swap(0), &user), &oracle_keys[0], &Pubkey::new_unique());
+            pool.execute_transaction(ix, &[&user.keypair]).await
+        }
+        PoolError::OraclePriceUnavailable => {
+            let (mut pool, user, oracle_keys) = oracle_guarded_pool().await;
+            let halted = pyth_price_account(AT_PEG.0, AT_PEG.1, 0, 0, 0);
+            pool.context.set_account(&oracle_keys[0], &halted.into());
+            pool.execute_defi_instruction(swap(0), &user).await
+        }
+        PoolError::OracleDeviationExceeded => {
+            let (mut pool, user, oracle_keys) = oracle_guarded_pool().await;
+            pool.set_pyth_price(&oracle_keys[0], AT_PEG.0 / 2, AT_PEG.1).await;
+            pool.execute_defi_instruction(swap(0), &user).await
+        }
+        PoolError::TokenDepegged => {
+            let (mut pool, user, oracle_keys) = oracle_guarded_pool().await;
+            pool.execute_governance_instruction(
+                GovernanceInstruction::SetPegBand {
+                    band: DecT::new(1, 2).unwrap(),
+                },
+                None,
+            )
+            .await
+            .unwrap();
+            pool.set_pyth_price(&oracle_keys[0], AT_PEG.0 / 2, AT_PEG.1).await;
+            pool.check_peg().await.unwrap();
+            pool.execute_defi_instruction(swap(0), &user).await
+        }
+        PoolError::InvalidVirtualPriceAccount => {
+            let (mut pool, _) = funded_pool(&BankPoolParams::default()).await;
+            let ix = create_publish_virtual_price_ix(
+                &pool::id(),
+                &pool.pool,
+                &pool.lp_mint,
+                &pool.token_accounts,
+                &pool.context.payer.pubkey(),
+                &[],
+            )
+            .unwrap();
+            let virtual_price_key = find_virtual_price_address(&pool::id(), &pool.pool).0;
+            let ix = replace_account(ix, &virtual_price_key, &Pubkey::new_unique());
+            pool.execute_transaction(ix, &[]).await
+        }
+        PoolError::PoolIsEmpty => {
+            let mut pool = BankPool::new(&BankPoolParams::default()).await.unwrap();
+            pool.publish_virtual_price().await
+        }
+        PoolError::InvalidRateAccount => {
+            let (mut pool, user, stake_pool) = rated_pool().await;
+            let ix = replace_account(pool.defi_ix(swap(0), &user), &stake_pool, &Pubkey::new_unique());
+            pool.execute_transaction(ix, &[&user.keypair]).await
+        }
+        PoolError::RateUnavailable => {
+            let (mut pool, user, stake_pool) = rated_pool().await;
+            let epoch = pool.epoch().await;
+            let outdated = stake_pool_account(&pool.token_mints[0], 1_100, 1_000, epoch + 1);
+            pool.context.set_account(&stake_pool, &outdated.into());
+            pool.execute_defi_instruction(swap(0), &user).await
+        }
+        PoolError::HookProgramNotAllowed => {
+            let (mut pool, user) = funded_pool(&BankPoolParams::default()).await;
+            let mut ix = pool.defi_ix(swap(0), &user);
+            ix.accounts.push(AccountMeta::new_readonly(Pubkey::new_unique(), false));
+            pool.execute_transaction(ix, &[&user.keypair]).await
+        }
+        PoolError::InvalidRouteAccount => {
+            let (mut pool, user) = funded_pool(&BankPoolParams::default()).await;
+            let mut ix = create_routed_swap_ix(
+                PoolInstruction::RoutedSwap {
+                    exact_input_amount: 1,
+                    input_token_index: 0,
+                    first_output_index: 1,
+                    second_input_index: 1,
+                    output_token_index: 2,
+                    minimum_output_amount: 0,
+                },
+                &pool::id(),
+                &user.keypair.pubkey(),
+                &user.tokens[0],
+                &user.tokens[2],
+                &pool.token_mints[1],
+                &pool.route_leg(),
+                &pool.route_leg(),
+            )
+            .unwrap();
+            ix.accounts[4].pubkey = Pubkey::new_unique();
+            pool.execute_transaction(ix, &[&user.keypair]).await
+        }
+        PoolError::CallerNotAllowed => {
+            let (mut pool, user) = funded_pool(&BankPoolParams::default()).await;
+            pool.set_caller_allowlist(CallerMode::AllowedProgramsOnly, &[])
+                .await
+                .unwrap();
+            pool.execute_defi_instruction(swap(0), &user).await
+        }
+        PoolError::UnsortedTokenMints => {
+            let params = BankPoolParams::default();
+            let mut pool = BankPool::new_uninitialized(&params).await;
+            pool.token_mints.sort();
+            pool.token_mints.reverse();
+            let ix = pool.create_pool_ix(&params, 0);
+            pool.execute_transaction(ix, &[]).await
+        }
+        PoolError::NonCanonicalAddress => {
+            let params = BankPoolParams::default();
+            let mut pool = BankPool::new_uninitialized(&params).await;
+            pool.token_mints.sort();
+            let mut ix = pool.create_pool_ix(&params, 0);
+            //the lp mint of the uninitialized pool
+            ix.accounts[1].pubkey = pool.lp_mint;
+            pool.execute_transaction(ix, &[]).await
+        }
+        PoolError::InvalidMigrationAccount => {
+            let (mut pool, user) = funded_pool(&BankPoolParams::default()).await;
+            let add_ix = pool.defi_ix(
+                DeFiInstruction::Add {
+                    input_amounts: [0; TOKEN_COUNT],
+                    minimum_mint_amount: 0,
+                },
+                &user,
+            );
+            let source_pool = SourcePool {
+                source: MigrationSource::TokenSwap,
+                swap: Pubkey::new_unique(),
+                authority: Pubkey::new_unique(),
+                pool_mint: Pubkey::new_unique(),
+                token_a: Pubkey::new_unique(),
+                token_b: Pubkey::new_unique(),
+                fee_accounts: vec![Pubkey::new_unique()],
+            };
+            let mut ix = create_migrate_in_ix::<TOKEN_COUNT>(
+                PoolInstruction::MigrateIn {
+                    source: MigrationSource::TokenSwap,
+                    source_lp_amount: 1,
+                    token_indices: [0, 1],
+                    minimum_mint_amount: 0,
+                },
+                &source_pool,
+                &Pubkey::new_unique(),
+                add_ix,
+            )
+            .unwrap();
+            //the withdrawal's user token account of token a
+            let len = ix.accounts.len();
+            ix.accounts[len - 4].pubkey = Pubkey::new_unique();
+            pool.execute_transaction(ix, &[&user.keypair]).await
+        }
+        PoolError::PoolIsSunset => {
+            let (mut pool, user) = funded_pool(&BankPoolParams::default()).await;
+            let deadline = pool.now().await + ENACT_DELAY;
+            pool.execute_governance_instruction(GovernanceInstruction::SetSunset { deadline }, None)
+                .await
+                .unwrap();
+            pool.execute_defi_instruction(swap(0), &user).await
+        }
+        PoolError::PoolNotPaused => {
+            let (mut pool, _) = funded_pool(&BankPoolParams::default()).await;
+            let target = pool.create_export_target(&pool::id());
+            let ix = pool.export_state_ix(&target);
+            let governance_keypair = Keypair::from_bytes(&pool.governance_keypair.to_bytes()).unwrap();
+            pool.execute_transaction(ix, &[&governance_keypair]).await
+        }
+        PoolError::InvalidStateExport => {
+            let (mut pool, _) = funded_pool(&BankPoolParams::default()).await;
+            pool.execute_governance_instruction(GovernanceInstruction::SetPaused { paused: true }, None)
+                .await
+                .unwrap();
+            let target = pool.create_export_target(&pool::id());
+            let other_target = pool.create_export_target(&pool::id());
+            let governance_keypair = Keypair::from_bytes(&pool.governance_keypair.to_bytes()).unwrap();
+            let ixs = [pool.export_state_ix(&target), pool.import_state_ix(&other_target)];
+            pool.execute_instructions(&ixs, &[&governance_keypair]).await
+        }
+        PoolError::GovernanceRenounced => {
+            let mut pool = BankPool::new(&BankPoolParams::default()).await.unwrap();
+            pool.execute_governance_instruction(
+                GovernanceInstruction::RenounceGovernance { lock_amp_factor: false },
+                None,
+            )
+            .await
+            .unwrap();
+            pool.execute_governance_instruction(GovernanceInstruction::SetPaused { paused: true }, None)
+                .await
+        }
+        PoolError::LpStillLocked => {
+            let (mut pool, user) = funded_pool(&BankPoolParams::default()).await;
+            pool.lock_lp(&user, 1, MIN_LOCK_DURATION).await.unwrap();
+            pool.unlock_lp(&user).await
+        }
+        PoolError::InvalidLockDuration => {
+            let (mut pool, user) = funded_pool(&BankPoolParams::default()).await;
+            pool.lock_lp(&user, 1, MAX_LOCK_DURATION + 1).await
+        }
+        PoolError::LpNonTransferable => {
+            let params = BankPoolParams::default();
+            let mut pool = BankPool::new_uninitialized(&params).await;
+            pool.use_token_2022_lp_mint(true).await;
+            let init_ix = pool.init_ix(&params);
+            pool.execute_transaction(init_ix, &[]).await.unwrap();
+            let user = pool.create_user(&[0; TOKEN_COUNT]);
+            pool.lock_lp(&user, 1, MIN_LOCK_DURATION).await
+        }
+        PoolError::DustPosition => {
+            let (mut pool, user) = funded_pool(&BankPoolParams::default()).await;
+            pool.execute_governance_instruction(
+                GovernanceInstruction::SetMinLpPosition {
+                    min_mint_amount: 2,
+                    min_residual_amount: 0,
+                },
+                None,
+            )
+            .await
+            .unwrap();
+            pool.execute_defi_instruction(
+                DeFiInstruction::Add {
+                    input_amounts: single(0, 1),
+                    minimum_mint_amount: 0,
+                },
+                &user,
+            )
+            .await
+        }
+        PoolError::LaunchCapExceeded => {
+            let (mut pool, user) = funded_pool(&BankPoolParams::default()).await;
+            let now = pool.now().await;
+            pool.set_launch_cap(1, now + 3600).await.unwrap();
+            pool.create_launch_allocation(&user).await.unwrap();
+            pool.execute_defi_instruction(
+                DeFiInstruction::Add {
+                    input_amounts: single(0, 1000),
+                    minimum_mint_amount: 0,
+                },
+                &user,
+            )
+            .await
+        }
+        PoolError::UserNotAllowed => {
+            let (mut pool, user) = funded_pool(&BankPoolParams::default()).await;
+            pool.set_user_gate(UserGate::Allowlist).await.unwrap();
+            pool.execute_defi_instruction(swap(0), &user).await
+        }
+        PoolError::TokenInputDisabled | PoolError::TokenOutputDisabled => {
+            let (mut pool, user) = funded_pool(&BankPoolParams::default()).await;
+            let input = error == PoolError::TokenInputDisabled;
+            pool.execute_governance_instruction(
+                GovernanceInstruction::SetTokenFlags {
+                    token_index: if input { 0 } else { 1 },
+                    flags: TokenFlags {
+                        input_disabled: input,
+                        output_disabled: !input,
+                    },
+                },
+                None,
+            )
+            .await
+            .unwrap();
+            pool.execute_defi_instruction(swap(0), &user).await
+        }
+        PoolError::DepositorNotAllowed => {
+            let (mut pool, user) = funded_pool(&BankPoolParams::default()).await;
+            pool.set_depositor_gate(UserGate::Allowlist).await.unwrap();
+            pool.execute_defi_instruction(
+                DeFiInstruction::Add {
+                    input_amounts: single(0, 1000),
+                    minimum_mint_amount: 0,
+                },
+                &user,
+            )
+            .await
+        }
+        PoolError::SessionExpired => {
+            let (mut pool, user) = funded_pool(&BankPoolParams::default()).await;
+            let session_key = Keypair::new();
+            let expiry_ts = pool.now().await + 1;
+            pool.authorize_session(&user, &session_key.pubkey(), expiry_ts, single(0, POOL_BALANCE))
+                .await
+                .unwrap();
+            pool.advance_clock(1).await;
+            pool.execute_session_swap(swap(0), &user, &session_key).await
+        }
+        PoolError::SessionLimitExceeded => {
+            let (mut pool, user) = funded_pool(&BankPoolParams::default()).await;
+            let session_key = Keypair::new();
+            let expiry_ts = pool.now().await + 1;
+            pool.authorize_session(&user, &session_key.pubkey(), expiry_ts, single(0, 1))
+                .await
+                .unwrap();
+            pool.execute_session_swap(swap(0), &user, &session_key).await
+        }
+        PoolError::InvalidQuoteSignature => {
+            let (mut pool, user, _, quote) = rfq_setup(1000, 60).await;
+            pool.execute_rfq_swap(quote, &Keypair::new(), &user).await
+        }
+        PoolError::QuoteExpired => {
+            let (mut pool, user, quoter, quote) = rfq_setup(1000, 0).await;
+            pool.execute_rfq_swap(quote, &quoter, &user).await
+        }
+        PoolError::QuoteAlreadyUsed => {
+            let (mut pool, user, quoter, quote) = rfq_setup(1000, 60).await;
+            let mut state = pool.state().await;
+            state.rfq_last_quote_id = quote.quote_id;
+            pool.set_state(&state).await;
+            pool.execute_rfq_swap(quote, &quoter, &user).await
+        }
+        PoolError::QuoteDeviationExceeded => {
+            let (mut pool, user, quoter, quote) = rfq_setup(1100, 60).await;
+            pool.execute_rfq_swap(quote, &quoter, &user).await
+        }
+        PoolError::InvalidComplianceAccount => {
+            let (mut pool, user) = funded_pool(&BankPoolParams::default()).await;
+            pool.set_compliance_program(Some(Pubkey::new_unique())).await.unwrap();
+            pool.compliance_program = Some(Pubkey::new_unique());
+            pool.execute_defi_instruction(swap(0), &user).await
+        }
+        PoolError::OutsideTradingHours => {
+            let (mut pool, user) = funded_pool(&BankPoolParams::default()).await;
+            //open for one second of the day, long past
+            let closed_secs = (pool.now().await + 43200).rem_euclid(86400) as u32;
+            let trading_hours = TradingHours {
+                open_secs: closed_secs,
+                close_secs: (closed_secs + 1) % 86400,
+            };
+            pool.set_trading_schedule(trading_hours, &[]).await.unwrap();
+            pool.execute_defi_instruction(swap(0), &user).await
+        }
+        PoolError::PoolInMaintenance => {
+            let (mut pool, user) = funded_pool(&BankPoolParams::default()).await;
+            let now = pool.now().await;
+            let window = MaintenanceWindow {
+                start_ts: now,
+                end_ts: now + 60,
+            };
+            pool.set_trading_schedule(TradingHours::default(), &[window])
+                .await
+                .unwrap();
+            pool.execute_defi_instruction(swap(0), &user).await
+        }
+        PoolError::InvalidTradingSchedule => {
+            let mut pool = BankPool::new(&BankPoolParams::default()).await.unwrap();
+            let trading_hours = TradingHours {
+                open_secs: 86400,
+                close_secs: 0,
+            };
+            pool.set_trading_schedule(trading_hours, &[]).await
+        }
+        PoolError::WithdrawalNotAnnounced => {
+            let (mut pool, user) = funded_pool(&BankPoolParams::default()).await;
+            pool.set_withdrawal_cooldown(0, 60).await.unwrap();
+            pool.execute_defi_instruction(remove_exact_burn(1000), &user).await
+        }
+        PoolError::WithdrawalCooldownActive => {
+            let (mut pool, user) = funded_pool(&BankPoolParams::default()).await;
+            pool.set_withdrawal_cooldown(0, 60).await.unwrap();
+            pool.announce_withdrawal(&user, 1000).await.unwrap();
+            pool.execute_defi_instruction(remove_exact_burn(1000), &user).await
+        }
+        PoolError::NotEmergencyResponder => {
+            let mut pool = BankPool::new(&BankPoolParams::default()).await.unwrap();
+            pool.confirm_emergency(&Keypair::new()).await
+        }
+        PoolError::MinimumMintNotMet => {
+            let (mut pool, user) = funded_pool(&BankPoolParams::default()).await;
+            pool.execute_defi_instruction(
+                DeFiInstruction::Add {
+                    input_amounts: single(0, POOL_BALANCE / 100),
+                    minimum_mint_amount: AmountT::MAX,
+                },
+                &user,
+            )
+            .await
+        }
+        PoolError::MinimumOutputNotMet => {
+            let (mut pool, user) = funded_pool(&BankPoolParams::default()).await;
+            pool.execute_defi_instruction(swap(AmountT::MAX), &user).await
+        }
+        PoolError::MaximumInputExceeded => {
+            let (mut pool, user) = funded_pool(&BankPoolParams::default()).await;
+            pool.execute_defi_instruction(
+                DeFiInstruction::SwapExactOutput {
+                    maximum_input_amount: 0,
+                    input_token_index: 0,
+                    exact_output_amounts: single(1, POOL_BALANCE / 100),
+                },
+                &user,
+            )
+            .await
+        }
+        PoolError::MaximumBurnExceeded => {
+            let (mut pool, user) = funded_pool(&BankPoolParams::default()).await;
+            pool.execute_defi_instruction(
+                DeFiInstruction::RemoveExactOutput {
+                    maximum_burn_amount: 1,
+                    exact_output_amounts: single(0, POOL_BALANCE / 100),
+                },
+                &user,
+            )
+            .await
+        }
+        PoolError::TokenCountMismatch => {
+            //a swap encoded for pools of another number of tokens, sent with this pool's accounts
+            const OTHER_TOKEN_COUNT: usize = if TOKEN_COUNT == 2 { 3 } else { 2 };
+            let (mut pool, user) = funded_pool(&BankPoolParams::default()).await;
+            let mut ix = pool.defi_ix(swap(0), &user);
+            ix.data = PoolInstruction::<OTHER_TOKEN_COUNT>::DeFiInstruction(DeFiInstruction::SwapExactInput {
+                exact_input_amounts: [POOL_BALANCE / 100; OTHER_TOKEN_COUNT],
+                output_token_index: 1,
+                minimum_output_amount: 0,
+            })
+            .try_to_vec()
+            .unwrap();
+            pool.execute_transaction(ix, &[&user.keypair]).await
+        }
+        PoolError::InvariantOverflow | PoolError::InvariantUnderflow | PoolError::InvariantConvergenceFailure => {
+            unreachable!("the processor rejects inputs that the invariant can't compute, see the invariant's tests")
+        }
+        PoolError::InsufficientUserBalance => {
+            //the user added all of their tokens
+            let (mut pool, user) = funded_pool(&BankPoolParams::default()).await;
+            pool.execute_defi_instruction(swap(0), &user).await
+        }
+        PoolError::InvalidUserTokenAccount => {
+            let (mut pool, user) = funded_pool(&BankPoolParams::default()).await;
+            let ix = replace_account(pool.defi_ix(swap(0), &user), &user.tokens[0], &user.lp);
+            pool.execute_transaction(ix, &[&user.keypair]).await
+        }
+        PoolError::InvalidUserAuthority => {
+            let (mut pool, user) = funded_pool(&BankPoolParams::default()).await;
+            let mint = pool.token_mints[0];
+            pool.set_token_account(&user.tokens[0], &mint, &Pubkey::new_unique(), POOL_BALANCE);
+            pool.execute_defi_instruction(swap(0), &user).await
+        }
+        PoolError::UserTokenAccountFrozen => {
+            let (mut pool, user) = funded_pool(&BankPoolParams::default()).await;
+            let state = TokenState {
+                mint: pool.token_mints[0],
+                owner: user.keypair.pubkey(),
+                amount: POOL_BALANCE,
+                state: AccountState::Frozen,
+                ..TokenState::default()
+            };
+            pool.set_packed(&user.tokens[0], state);
+            pool.execute_defi_instruction(swap(0), &user).await
+        }
+        PoolError::InvalidTokenWeights => {
+            let params = BankPoolParams::default();
+            let mut pool = BankPool::new_uninitialized(&params).await;
+            let init_ix = pool.init_weighted_ix(&params, [0; TOKEN_COUNT]);
+            pool.execute_transaction(init_ix, &[]).await
+        }
+        PoolError::InvalidPriceBand => {
+            let mut pool = BankPool::new(&BankPoolParams::default()).await.unwrap();
+            pool.execute_governance_instruction(
+                GovernanceInstruction::SetPriceBand {
+                    price_band: PriceBand {
+                        min_price: 2,
+                        max_price: 1,
+                    },
+                },
+                None,
+            )
+            .await
+        }
+        PoolError::PriceOutOfBand => {
+            //set directly since only two token pools can set a band
+            let (mut pool, user) = funded_pool(&BankPoolParams::default()).await;
+            let mut state = pool.state().await;
+            state.price_band = PriceBand {
+                min_price: EMA_ONE - 1,
+                max_price: EMA_ONE + 1,
+            };
+            pool.set_state(&state).await;
+            pool.execute_defi_instruction(swap(0), &user).await
+        }
+        PoolError::TokenDepositOnly => {
+            let (mut pool, user) = funded_pool(&BankPoolParams::default()).await;
+            pool.set_token_participation(0, TokenParticipation::DepositOnly)
+                .await
+                .unwrap();
+            pool.execute_defi_instruction(swap(0), &user).await
+        }
+        PoolError::TokenWithdrawOnly => {
+            let (mut pool, user) = funded_pool(&BankPoolParams::default()).await;
+            pool.set_token_participation(0, TokenParticipation::WithdrawOnly)
+                .await
+                .unwrap();
+            pool.execute_defi_instruction(swap(0), &user).await
+        }
+        PoolError::InvalidPegKeeper => {
+            let (mut pool, _) = funded_pool(&BankPoolParams::default()).await;
+            pool.use_peg_credit(&Keypair::new(), 1, true).await
+        }
+        PoolError::PegCreditExceeded => {
+            let (mut pool, _) = funded_pool(&BankPoolParams::default()).await;
+            let keeper = Keypair::new();
+            pool.set_peg_keeper(&keeper.pubkey(), 0, 1).await.unwrap();
+            pool.use_peg_credit(&keeper, 2, true).await
+        }
+        PoolError::PegCreditPastPeg => {
+            //the balanced pool's token 0 is at its peg already
+            let (mut pool, _) = funded_pool(&BankPoolParams::default()).await;
+            let keeper = Keypair::new();
+            pool.set_peg_keeper(&keeper.pubkey(), 0, POOL_BALANCE).await.unwrap();
+            pool.use_peg_credit(&keeper, POOL_BALANCE / 10, true).await
+        }
+        PoolError::InvalidYieldAdapter => {
+            let (mut pool, _) = funded_pool(&BankPoolParams::default()).await;
+            pool.set_yield_adapter(0, &Pubkey::new_unique(), 0).await.unwrap();
+            pool.deploy_yield(0, &Pubkey::new_unique(), &[]).await
+        }
+        PoolError::YieldBufferShort => {
+            //set directly, as if an adapter held as much of each token as the pool's token accounts
+            let (mut pool, user) = funded_pool(&BankPoolParams::default()).await;
+            let mut state = pool.state().await;
+            state.yield_adapters = [YieldAdapter {
+                program: Pubkey::new_unique(),
+                buffer: 0,
+                deployed: POOL_BALANCE,
+            }; TOKEN_COUNT];
+            pool.set_state(&state).await;
+            let lp_balance = pool.balance(&user.lp).await;
+            pool.execute_defi_instruction(
+                DeFiInstruction::RemoveUniform {
+                    exact_burn_amount: lp_balance / 4 * 3,
+                    minimum_output_amounts: [0; TOKEN_COUNT],
+                },
+                &user,
+            )
+            .await
+        }
+        PoolError::InvalidPriceScale => {
+            let mut pool = BankPool::new(&BankPoolParams::default()).await.unwrap();
+            pool.set_repeg_params(100, 100, 0).await
+        }
+        PoolError::RepegNotDue => {
+            //hybrid pools start out without repegging
+            let mut pool = BankPool::new_hybrid(&BankPoolParams::default(), 0, RATE_ONE)
+                .await
+                .unwrap();
+            pool.repeg().await
+        }
+        PoolError::InvalidGauge => {
+            let (mut pool, user) = funded_pool(&BankPoolParams::default()).await;
+            pool.stake_lp(&user, 1).await
+        }
+        PoolError::InsufficientStake => {
+            let (mut pool, user) = funded_pool(&BankPoolParams::default()).await;
+            let (reward_mint, _) = pool.create_reward_token(&Pubkey::new_unique(), 0);
+            pool.add_gauge_reward(&reward_mint, &Pubkey::new_unique())
+                .await
+                .unwrap();
+            pool.stake_lp(&user, 1).await.unwrap();
+            pool.unstake_lp(&user, 2).await
+        }
+        PoolError::InvalidRebateCampaign => {
+            let (mut pool, user) = funded_pool(&BankPoolParams::default()).await;
+            pool.join_rebate_campaign(1, &user).await
+        }
+        PoolError::RebateCampaignNotEnded => {
+            let mut pool = BankPool::new(&BankPoolParams::default()).await.unwrap();
+            let governance = pool.governance_keypair.pubkey();
+            let (rebate_mint, governance_rebate_account) = pool.create_reward_token(&governance, 1);
+            let now = pool.now().await;
+            pool.start_rebate_campaign(1, &rebate_mint, &governance_rebate_account, 1, now, now + 1)
+                .await
+                .unwrap();
+            pool.reclaim_rebate_budget(1, &governance_rebate_account).await
+        }
+        PoolError::InvalidVeProgram => {
+            let (mut pool, user) = funded_pool(&BankPoolParams::default()).await;
+            let (reward_mint, _) = pool.create_reward_token(&Pubkey::new_unique(), 0);
+            pool.add_gauge_reward(&reward_mint, &Pubkey::new_unique())
+                .await
+                .unwrap();
+            pool.set_gauge_ve_program(Some(Pubkey::new_unique())).await.unwrap();
+            //the stake passes another program than the gauge's
+            pool.gauge_ve_program = Some(Pubkey::new_unique());
+            pool.stake_lp(&user, 1).await
+        }
+        PoolError::InvalidOracleLimits => {
+            let (mut pool, _) = funded_pool(&BankPoolParams::default()).await;
+            //readings of any age
+            pool.execute_governance_instruction(
+                GovernanceInstruction::SetTokenOracle {
+                    token_index: 0,
+                    oracle_kind: OracleKind::Pyth,
+                    oracle_key: Pubkey::default(),
+                    max_age: 0,
+                    max_conf_bps: 0,
+                },
+                None,
+            )
+            .await
+        }
+        PoolError::PoolLayoutOutdated => {
+            let (mut pool, _) = funded_pool(&BankPoolParams::default()).await;
+            pool.truncate_to_layout_v0().await;
+            pool.set_withdrawal_cooldown(0, 60).await
+        }
+        PoolError::InvalidPoolLayout => {
+            //a layout version that the program doesn't know
+            let (mut pool, user) = funded_pool(&BankPoolParams::default()).await;
+            let mut account = pool.context.banks_client.get_account(pool.pool).await.unwrap().unwrap();
+            *account.data.last_mut().unwrap() = POOL_STATE_LAYOUT_VERSION + 1;
+            pool.context.set_account(&pool.pool, &account.into());
+            pool.execute_defi_instruction(swap(0), &user).await
+        }
+    }
+}
+
+#[test]
+fn test_error_codes() {
+    assert_eq!(PoolError::codes().collect::<Vec<_>>(), EXPECTED_CODES.to_vec());
+    for &(code, error) in EXPECTED_CODES.iter() {
+        assert_eq!(error.code(), code);
+        assert_eq!(PoolError::try_from(code), Ok(error));
+        assert_eq!(decode_custom_error(code), CustomError::Pool(error));
+    }
+    assert_eq!(
+        decode_custom_error(1),
+        CustomError::Token(TokenError::InsufficientFunds)
+    );
+    assert_eq!(decode_custom_error(99), CustomError::Unknown(99));
+    let unused = EXPECTED_CODES[EXPECTED_CODES.len() - 1].0 + 1;
+    assert_eq!(PoolError::try_from(unused), Err(unused));
+    assert_eq!(decode_custom_error(unused), CustomError::Unknown(unused));
+}
+
+#[test]
+fn test_explain_error() {
+    let explanation = explain_error(TransactionError::InstructionError(
+        2,
+        InstructionError::Custom(PoolError::MinimumOutputNotMet.code()),
+    ));
+    assert_eq!(explanation.instruction_index, Some(2));
+    assert_eq!(explanation.error, ExplainedError::Pool(PoolError::MinimumOutputNotMet));
+    assert_eq!(explanation.code(), Some(163));
+    assert!(explanation.hint.is_some());
+    assert_eq!(explanation.class(), ErrorClass::Slippage);
+
+    let explanation = explain_error(TransactionError::InstructionError(
+        0,
+        InstructionError::Custom(TokenError::InsufficientFunds as u32),
+    ));
+    assert_eq!(explanation.error, ExplainedError::Token(TokenError::InsufficientFunds));
+    assert_eq!(explanation.code(), Some(1));
+    assert_eq!(explanation.class(), ErrorClass::Other);
+
+    let explanation = explain_error(TransactionError::InstructionError(1, InstructionError::Custom(99)));
+    assert_eq!(explanation.error, ExplainedError::UnknownCustom(99));
+
+    let explanation = explain_error(TransactionError::InstructionError(
+        0,
+        InstructionError::Custom(PoolError::PoolIsPaused.code()),
+    ));
+    assert_eq!(explanation.class(), ErrorClass::Paused);
+    let explanation = explain_error(TransactionError::InstructionError(
+        0,
+        InstructionError::InvalidInstructionData,
+    ));
+    assert_eq!(explanation.class(), ErrorClass::Validation);
+
+    let explanation = explain_error(TransactionError::BlockhashNotFound);
+    assert_eq!(explanation.instruction_index, None);
+    assert_eq!(explanation.code(), None);
+    assert!(explanation.hint.is_some());
+}
+
+#[tokio::test]
+async fn test_every_error_is_triggered_by_its_minimal_input() {
+    //errors that are no longer returned or that no input passing the processor's checks can cause
+    let untriggerable = [
+        PoolError::OutsideSpecifiedLimits,
+        PoolError::InvariantOverflow,
+        PoolError::InvariantUnderflow,
+        PoolError::InvariantConvergenceFailure,
+    ];
+    for (code, error) in PoolError::codes().filter(|(_, error)| !untriggerable.contains(error)) {
+        assert_eq!(
+            trigger(error).await,
+            Err(InstructionError::Custom(code)),
+            "wrong result for {:?}",
+            error
+        );
+    }
+}
+
+#[tokio::test]
+async fn test_preflight_fails_like_the_program() {
+    let (mut pool, user) = funded_pool(&BankPoolParams::default()).await;
+    let defi_instructions = vec![
+        swap(AmountT::MAX),
+        remove_exact_burn(0),
+        DeFiInstruction::Add {
+            input_amounts: [POOL_BALANCE / 100; TOKEN_COUNT],
+            minimum_mint_amount: AmountT::MAX,
+        },
+    ];
+    for defi_instruction in defi_instructions {
+        let expected = match pool.snapshot().await.preflight(&defi_instruction) {
+            Err(ClientError::Instruction(error)) => InstructionError::from(u64::from(error)),
+            result => panic!("preflight of {:?} returned {:?}", defi_instruction, result.map(|_| ())),
+        };
+        assert_eq!(
+            pool.execute_defi_instruction(defi_instruction, &user).await,
+            Err(expected)
+        );
+    }
+
+    //the user added all of their tokens, which only the program sees
+    assert!(pool.snapshot().await.preflight(&swap(0)).is_ok());
+}
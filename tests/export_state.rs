@@ -0,0 +1,143 @@
+#![cfg(feature = "test-bpf")]
+
+mod helpers;
+
+use helpers::{bank::*, AmountT};
+
+use borsh::BorshDeserialize;
+use pool::{
+    error::PoolError,
+    instruction::{create_defi_ix, create_governance_ix, DeFiInstruction, GovernanceInstruction},
+    processor::Processor,
+    state::PoolState,
+    TOKEN_COUNT,
+};
+use solana_program_test::*;
+use solana_sdk::{
+    instruction::InstructionError,
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+};
+use spl_token::state::Mint as MintState;
+
+const POOL_BALANCE: AmountT = 1_000_000_000;
+
+//a pool with liquidity and a second deployment of the program (standing in for its next version) to move it to
+async fn exported_pool() -> (BankPool, BankUser, Pubkey) {
+    let target_program = Pubkey::new_unique();
+    let mut pool = BankPool::new_with(&BankPoolParams::default(), |program_test| {
+        program_test.add_program(
+            "pool_v2",
+            target_program,
+            processor!(Processor::<{ TOKEN_COUNT }>::process),
+        );
+    })
+    .await
+    .unwrap();
+    let user = pool.create_user(&[POOL_BALANCE; TOKEN_COUNT]);
+    pool.execute_defi_instruction(
+        DeFiInstruction::Add {
+            input_amounts: [POOL_BALANCE; TOKEN_COUNT],
+            minimum_mint_amount: 0,
+        },
+        &user,
+    )
+    .await
+    .unwrap();
+    (pool, user, target_program)
+}
+
+fn governance_keypair(pool: &BankPool) -> Keypair {
+    Keypair::from_bytes(&pool.governance_keypair.to_bytes()).unwrap()
+}
+
+#[tokio::test]
+async fn test_export_state_moves_pool_to_another_program() {
+    let (mut pool, user, target_program) = exported_pool().await;
+    let target = pool.create_export_target(&target_program);
+    pool.execute_governance_instruction(GovernanceInstruction::SetPaused { paused: true }, None)
+        .await
+        .unwrap();
+    let (lp_supply, pool_balances) = (pool.lp_total_supply().await, pool.pool_balances().await);
+    let params = BankPoolParams::default();
+
+    let governance = governance_keypair(&pool);
+    let ixs = [pool.export_state_ix(&target), pool.import_state_ix(&target)];
+    pool.execute_instructions(&ixs, &[&governance]).await.unwrap();
+
+    assert_eq!(pool.pool_balances().await, [0; TOKEN_COUNT]);
+    assert_eq!(pool.balances(&target.token_accounts).await, pool_balances);
+    let lp_mint_key = pool.lp_mint;
+    let lp_mint = pool.get_packed::<MintState>(&lp_mint_key).await;
+    assert_eq!(lp_mint.mint_authority, Some(target.authority).into());
+    assert_eq!(lp_mint.supply, lp_supply);
+    let target_account = pool
+        .context
+        .banks_client
+        .get_account(target.pool)
+        .await
+        .unwrap()
+        .unwrap();
+    let target_state = PoolState::<TOKEN_COUNT>::deserialize(&mut &target_account.data[..]).unwrap();
+    assert!(target_state.is_paused);
+    assert_eq!(target_state.lp_mint_key, pool.lp_mint);
+    assert_eq!(target_state.token_mint_keys, pool.token_mints);
+    assert_eq!(target_state.token_keys, target.token_accounts);
+    assert_eq!(target_state.governance_key, governance.pubkey());
+    assert_eq!(target_state.governance_fee_key, pool.governance_fee_account);
+    assert_eq!(target_state.lp_fee.get(), params.lp_fee);
+    assert_eq!(target_state.governance_fee.get(), params.governance_fee);
+
+    //lp holders withdraw from the new pool once governance unpauses it
+    let unpause_ix = create_governance_ix(
+        GovernanceInstruction::<TOKEN_COUNT>::SetPaused { paused: false },
+        &target_program,
+        &target.pool,
+        &governance.pubkey(),
+        None,
+    )
+    .unwrap();
+    pool.execute_transaction(unpause_ix, &[&governance]).await.unwrap();
+    let lp_amount = pool.balance(&user.lp).await;
+    let remove_ix = create_defi_ix(
+        DeFiInstruction::RemoveUniform {
+            exact_burn_amount: lp_amount,
+            minimum_output_amounts: [0; TOKEN_COUNT],
+        },
+        &target_program,
+        &target.pool,
+        &target.authority,
+        &target.token_accounts,
+        &pool.lp_mint,
+        &pool.governance_fee_account,
+        &user.keypair.pubkey(),
+        &user.tokens,
+        &spl_token::id(),
+        Some(&user.lp),
+    )
+    .unwrap();
+    pool.execute_transaction(remove_ix, &[&user.keypair]).await.unwrap();
+    assert_eq!(pool.balance(&user.lp).await, 0);
+    assert!(pool.balances(&user.tokens).await.iter().all(|&balance| balance > 0));
+}
+
+#[tokio::test]
+async fn test_import_state_only_into_export_target() {
+    let (mut pool, _, target_program) = exported_pool().await;
+    let target = pool.create_export_target(&target_program);
+    let other_target = pool.create_export_target(&target_program);
+    pool.execute_governance_instruction(GovernanceInstruction::SetPaused { paused: true }, None)
+        .await
+        .unwrap();
+    let governance = governance_keypair(&pool);
+    let ix = pool.export_state_ix(&target);
+    pool.execute_transaction(ix, &[&governance]).await.unwrap();
+
+    let ix = pool.import_state_ix(&other_target);
+    assert_eq!(
+        pool.execute_transaction(ix, &[&governance]).await,
+        Err(InstructionError::Custom(PoolError::InvalidStateExport as u32))
+    );
+    let ix = pool.import_state_ix(&target);
+    pool.execute_transaction(ix, &[&governance]).await.unwrap();
+}
@@ -0,0 +1,106 @@
+#![cfg(feature = "test-bpf")]
+
+mod helpers;
+
+use helpers::{bank::*, AmountT, DecT};
+
+use pool::{
+    instruction::DeFiInstruction,
+    state::{lp_fees_earned, GROWTH_ONE},
+    TOKEN_COUNT,
+};
+use solana_program_test::*;
+
+const POOL_BALANCE: AmountT = 1_000_000_000;
+
+async fn funded_pool(params: &BankPoolParams) -> (BankPool, BankUser) {
+    let mut pool = BankPool::new(params).await.unwrap();
+    let user = pool.create_user(&[POOL_BALANCE; TOKEN_COUNT]);
+    pool.execute_defi_instruction(
+        DeFiInstruction::Add {
+            input_amounts: [POOL_BALANCE; TOKEN_COUNT],
+            minimum_mint_amount: 0,
+        },
+        &user,
+    )
+    .await
+    .unwrap();
+    (pool, user)
+}
+
+async fn swap(pool: &mut BankPool) {
+    let mut input_amounts = [0; TOKEN_COUNT];
+    input_amounts[0] = POOL_BALANCE / 10;
+    let trader = pool.create_user(&input_amounts);
+    pool.execute_defi_instruction(
+        DeFiInstruction::SwapExactInput {
+            exact_input_amounts: input_amounts,
+            output_token_index: 1,
+            minimum_output_amount: 0,
+        },
+        &trader,
+    )
+    .await
+    .unwrap();
+}
+
+fn difference(a: AmountT, b: AmountT) -> AmountT {
+    a.max(b) - a.min(b)
+}
+
+//lp tokens' worth of the lp fee of a swap, from the pool's lp fee growth
+async fn swap_lp_fee(pool: &mut BankPool) -> (AmountT, AmountT) {
+    let lp_supply = pool.lp_total_supply().await;
+    let governance_fee_account = pool.governance_fee_account;
+    let governance_balance = pool.balance(&governance_fee_account).await;
+    let lp_fee_growth = pool.state().await.lp_fee_growth;
+    swap(pool).await;
+    let lp_fee_growth_delta = pool.state().await.lp_fee_growth - lp_fee_growth;
+    let governance_amount = pool.balance(&governance_fee_account).await - governance_balance;
+    (lp_fees_earned(lp_supply, lp_fee_growth_delta), governance_amount)
+}
+
+#[tokio::test]
+async fn test_lp_fee_growth_follows_the_fee_split() {
+    let (mut pool, _) = funded_pool(&BankPoolParams::default()).await;
+    let (lp_fee_amount, governance_amount) = swap_lp_fee(&mut pool).await;
+    //the lp fee is three times the governance fee
+    assert!(governance_amount > 0);
+    assert!(difference(3 * governance_amount, lp_fee_amount) <= 3);
+
+    //a pool without governance fee attributes the whole fee to the lp tokens
+    let (mut pool, _) = funded_pool(&BankPoolParams {
+        lp_fee: DecT::new(4, 6).unwrap(),
+        governance_fee: DecT::from(0),
+        ..BankPoolParams::default()
+    })
+    .await;
+    let (total_fee_amount, governance_amount) = swap_lp_fee(&mut pool).await;
+    assert_eq!(governance_amount, 0);
+    assert!(difference(lp_fee_amount * 4 / 3, total_fee_amount) <= 2);
+}
+
+#[tokio::test]
+async fn test_checkpoint_fees() {
+    let (mut pool, user) = funded_pool(&BankPoolParams::default()).await;
+    pool.checkpoint_fees(&user).await.unwrap();
+    let lp_amount = pool.balance(&user.lp).await;
+    let checkpoint = pool.fee_checkpoint(&user.lp).await;
+    assert_eq!(checkpoint.lp_amount, lp_amount);
+    assert_eq!(checkpoint.lp_fee_growth, pool.state().await.lp_fee_growth);
+
+    let lp_supply = pool.lp_total_supply().await;
+    let (lp_fee_amount, _) = swap_lp_fee(&mut pool).await;
+    let lp_fee_growth = pool.state().await.lp_fee_growth;
+    let earned = checkpoint.lp_fees_earned(lp_fee_growth);
+    assert!(earned > 0);
+    let expected_earned = (lp_fee_amount as u128 * lp_amount as u128 / lp_supply as u128) as AmountT;
+    assert!(difference(expected_earned, earned) <= 1);
+    assert_eq!(
+        earned as u128,
+        lp_amount as u128 * (lp_fee_growth - checkpoint.lp_fee_growth) / GROWTH_ONE
+    );
+
+    pool.checkpoint_fees(&user).await.unwrap();
+    assert_eq!(pool.fee_checkpoint(&user.lp).await.lp_fee_growth, lp_fee_growth);
+}
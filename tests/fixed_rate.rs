@@ -0,0 +1,116 @@
+#![cfg(feature = "test-bpf")]
+
+mod helpers;
+
+use helpers::{bank::*, AmountT, DecT};
+
+use pool::{
+    instruction::DeFiInstruction,
+    oracle::{RateSource, RATE_ONE},
+    state::InvariantKind,
+    TOKEN_COUNT,
+};
+use solana_program_test::*;
+use solana_sdk::pubkey::Pubkey;
+
+const POOL_BALANCE: AmountT = 1_000_000_000;
+const SWAP_AMOUNT: AmountT = 100_000_000;
+
+fn swap(input_index: usize, output_index: usize, amount: AmountT) -> DeFiInstruction<TOKEN_COUNT> {
+    let mut exact_input_amounts = [0; TOKEN_COUNT];
+    exact_input_amounts[input_index] = amount;
+    DeFiInstruction::SwapExactInput {
+        exact_input_amounts,
+        output_token_index: output_index as u8,
+        minimum_output_amount: 0,
+    }
+}
+
+async fn swap_output(pool: &mut BankPool, user: &BankUser, input_index: usize, output_index: usize) -> AmountT {
+    let before = pool.balance(&user.tokens[output_index]).await;
+    pool.execute_defi_instruction(swap(input_index, output_index, SWAP_AMOUNT), user)
+        .await
+        .unwrap();
+    pool.balance(&user.tokens[output_index]).await - before
+}
+
+//within rounding of the conversions from and to the rated amounts
+fn assert_close(actual: AmountT, expected: AmountT) {
+    assert!(
+        actual + 2 >= expected && actual <= expected + 2,
+        "{} isn't {}",
+        actual,
+        expected
+    );
+}
+
+async fn fund(pool: &mut BankPool, input_amounts: [AmountT; TOKEN_COUNT]) -> BankUser {
+    let user = pool.create_user(&[2 * POOL_BALANCE; TOKEN_COUNT]);
+    pool.execute_defi_instruction(
+        DeFiInstruction::Add {
+            input_amounts,
+            minimum_mint_amount: 0,
+        },
+        &user,
+    )
+    .await
+    .unwrap();
+    user
+}
+
+#[tokio::test]
+async fn test_fixed_rate_pool_trades_one_for_one() {
+    let params = BankPoolParams {
+        lp_fee: DecT::from(0),
+        governance_fee: DecT::from(0),
+        ..BankPoolParams::default()
+    };
+    let mut pool = BankPool::new_fixed_rate(&params).await.unwrap();
+    assert_eq!(pool.state().await.invariant_kind, InvariantKind::FixedRate);
+    let user = fund(&mut pool, [POOL_BALANCE; TOKEN_COUNT]).await;
+
+    //without curvature the price doesn't move however far the balances drift apart
+    for _ in 0..5 {
+        assert_eq!(swap_output(&mut pool, &user, 0, 1).await, SWAP_AMOUNT);
+    }
+    let mut pool_balances = [POOL_BALANCE; TOKEN_COUNT];
+    pool_balances[0] += 5 * SWAP_AMOUNT;
+    pool_balances[1] -= 5 * SWAP_AMOUNT;
+    assert_eq!(pool.pool_balances().await, pool_balances);
+
+    //but it can't pay out more than it holds
+    assert!(pool
+        .execute_defi_instruction(swap(0, 1, POOL_BALANCE), &user)
+        .await
+        .is_err());
+}
+
+#[tokio::test]
+async fn test_fixed_rate_pool_trades_at_the_governance_rate() {
+    let params = BankPoolParams::default();
+    let mut pool = BankPool::new_fixed_rate(&params).await.unwrap();
+    //the first token migrates to the others at 1.1 of them per token
+    pool.set_rate_source(
+        0,
+        RateSource::Fixed {
+            rate: RATE_ONE / 10 * 11,
+        },
+        Pubkey::default(),
+    )
+    .await
+    .unwrap();
+    let mut input_amounts = [POOL_BALANCE; TOKEN_COUNT];
+    input_amounts[0] = POOL_BALANCE * 10 / 11;
+    let user = fund(&mut pool, input_amounts).await;
+
+    //the fee is all that the swaps lose to the pool
+    let after_fee = |amount: AmountT| amount * (1_000_000 - 4) / 1_000_000;
+    assert_close(
+        swap_output(&mut pool, &user, 0, 1).await,
+        after_fee(SWAP_AMOUNT * 11 / 10),
+    );
+    assert_close(
+        swap_output(&mut pool, &user, 1, 0).await,
+        after_fee(SWAP_AMOUNT * 10 / 11),
+    );
+}
@@ -0,0 +1,114 @@
+#![cfg(feature = "test-bpf")]
+
+//dry-runs routes the router plans through pools forked from recorded (mainnet) accounts and requires them to realize
+// exactly what the router predicted
+//see tests/fixtures/README.md for how to record new fork fixtures
+
+mod helpers;
+
+use helpers::{bank::*, fork::ForkedBank, AmountT};
+
+use pool::{
+    client::capture::{fork_account_keys, FixtureAccount, ForkFixture, FIXTURE_VERSION},
+    instruction::DeFiInstruction,
+    TOKEN_COUNT,
+};
+use solana_program_test::*;
+use std::path::Path;
+
+const FIXTURE_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures");
+const POOL_BALANCE: AmountT = 1_000_000_000;
+
+//swaps a ten-thousandth and a hundredth of the input balance between all pairs of tokens of all pools
+async fn dry_run_all_pairs(fixture: ForkFixture) {
+    let mut bank = ForkedBank::start(fixture).await;
+    let router = bank.router().await;
+    let mut routes = vec![];
+    for amm in router.pools() {
+        let mints = amm.get_reserve_mints();
+        let balances = amm.snapshot().unwrap().balances;
+        for input_index in 0..mints.len() {
+            for output_index in (0..mints.len()).filter(|output_index| *output_index != input_index) {
+                for fraction in [10_000, 100] {
+                    let amount = (balances[input_index] / fraction).max(1);
+                    //paused pools and amounts beyond a pool's limits just have no route
+                    if let Ok(route) = router.find_best_route(&mints[input_index], &mints[output_index], amount) {
+                        routes.push(route);
+                    }
+                }
+            }
+        }
+    }
+    //every route executes against the forked state, not against the one the previous route left behind
+    for route in routes {
+        let mut bank = ForkedBank::start(bank.fixture.clone()).await;
+        bank.dry_run(&route).await;
+    }
+}
+
+#[tokio::test]
+async fn dry_run_recorded_forks() {
+    let fixtures = ForkFixture::load_dir(Path::new(FIXTURE_DIR)).unwrap();
+    //an empty corpus would pass without checking anything
+    assert!(!fixtures.is_empty(), "no fork fixtures found in {}", FIXTURE_DIR);
+    for (path, fixture) in fixtures {
+        println!("dry-running {}", path.display());
+        dry_run_all_pairs(fixture).await;
+    }
+}
+
+//a funded local pool, recorded the way `capture_fork` does from the bank instead of an rpc node
+async fn local_fork() -> ForkFixture {
+    let mut pool = BankPool::new(&BankPoolParams::default()).await.unwrap();
+    let lp = pool.create_user(&[POOL_BALANCE; TOKEN_COUNT]);
+    pool.execute_defi_instruction(
+        DeFiInstruction::Add {
+            input_amounts: [POOL_BALANCE; TOKEN_COUNT],
+            minimum_mint_amount: 0,
+        },
+        &lp,
+    )
+    .await
+    .unwrap();
+
+    let amm = pool.amm().await;
+    let keys = fork_account_keys(&amm);
+    let accounts = pool.accounts(&keys).await;
+    ForkFixture {
+        version: FIXTURE_VERSION,
+        description: "local pool".to_string(),
+        slot: 1,
+        unix_timestamp: pool.now().await,
+        program_id: pool::id(),
+        pools: vec![pool.pool],
+        accounts: keys
+            .iter()
+            .map(|key| {
+                let account = accounts[key].clone();
+                FixtureAccount {
+                    pubkey: *key,
+                    owner: account.owner,
+                    lamports: account.lamports,
+                    data: account.data,
+                    executable: account.executable,
+                    rent_epoch: account.rent_epoch,
+                }
+            })
+            .collect(),
+    }
+}
+
+#[tokio::test]
+async fn dry_run_fork_of_local_pool() {
+    dry_run_all_pairs(local_fork().await).await;
+}
+
+//records the local pool as a fork fixture, for layouts that aren't deployed anywhere to record from. runs on request:
+// cargo test-bpf --test fork -- --ignored record_local_fork
+#[tokio::test]
+#[ignore]
+async fn record_local_fork() {
+    let path = Path::new(FIXTURE_DIR).join(format!("0001_local_pool_{}_tokens.fork", TOKEN_COUNT));
+    local_fork().await.save(&path).unwrap();
+    println!("recorded {}", path.display());
+}
@@ -0,0 +1,302 @@
+#![cfg(feature = "test-bpf")]
+
+mod helpers;
+
+use helpers::{bank::*, AmountT};
+
+use pool::{
+    error::PoolError,
+    instruction::{DeFiInstruction, GovernanceInstruction},
+    state::{GAUGE_CLAIM_PERIOD, MAX_GAUGE_DURATION, MAX_GAUGE_REWARDS},
+    TOKEN_COUNT,
+};
+use solana_program_test::*;
+use solana_sdk::{
+    instruction::InstructionError,
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+};
+
+const POOL_BALANCE: AmountT = 1_000_000_000;
+const REWARDS: AmountT = 100_000_000;
+const DURATION: i64 = 100_000;
+
+fn pool_error(error: PoolError) -> InstructionError {
+    InstructionError::Custom(error as u32)
+}
+
+async fn add(pool: &mut BankPool, amount: AmountT) -> BankUser {
+    let user = pool.create_user(&[amount; TOKEN_COUNT]);
+    pool.execute_defi_instruction(
+        DeFiInstruction::Add {
+            input_amounts: [amount; TOKEN_COUNT],
+            minimum_mint_amount: 0,
+        },
+        &user,
+    )
+    .await
+    .unwrap();
+    user
+}
+
+//claims the user's rewards to a new token account and returns them
+async fn claim(pool: &mut BankPool, user: &BankUser, reward_mint: &Pubkey) -> AmountT {
+    let reward_account = Pubkey::new_unique();
+    pool.set_token_account(&reward_account, reward_mint, &user.keypair.pubkey(), 0);
+    pool.claim_gauge_rewards(user, reward_mint, &reward_account)
+        .await
+        .unwrap();
+    pool.balance(&reward_account).await
+}
+
+#[tokio::test]
+async fn test_gauge_splits_emissions_by_stake() {
+    let mut pool = BankPool::new(&BankPoolParams::default()).await.unwrap();
+    let first = add(&mut pool, POOL_BALANCE).await;
+    let second = add(&mut pool, POOL_BALANCE).await;
+    let lp_amount = pool.balance(&first.lp).await;
+    let stake = lp_amount / 2;
+    let funder = Keypair::new();
+    let (reward_mint, funder_reward_account) = pool.create_reward_token(&funder.pubkey(), REWARDS);
+    assert_eq!(
+        pool.stake_lp(&first, stake).await,
+        Err(pool_error(PoolError::InvalidGauge))
+    );
+
+    pool.add_gauge_reward(&reward_mint, &funder.pubkey()).await.unwrap();
+    pool.fund_gauge(&funder, &reward_mint, &funder_reward_account, REWARDS, DURATION)
+        .await
+        .unwrap();
+    pool.stake_lp(&first, stake).await.unwrap();
+    pool.stake_lp(&second, stake).await.unwrap();
+    assert_eq!(pool.balance(&first.lp).await, lp_amount - stake);
+    assert_eq!(pool.gauge().await.unwrap().total_staked, 2 * stake);
+
+    pool.advance_clock(DURATION / 2).await;
+    let second_lp = pool.balance(&second.lp).await;
+    pool.unstake_lp(&second, stake).await.unwrap();
+    assert_eq!(pool.balance(&second.lp).await, second_lp + stake);
+    assert_eq!(
+        pool.unstake_lp(&first, stake + 1).await,
+        Err(pool_error(PoolError::InsufficientStake))
+    );
+
+    //the first stake earns everything that's emitted after the second left, until the emissions end
+    pool.advance_clock(DURATION).await;
+    let first_rewards = claim(&mut pool, &first, &reward_mint).await;
+    let second_rewards = claim(&mut pool, &second, &reward_mint).await;
+    assert!(first_rewards + second_rewards <= REWARDS);
+    assert!(first_rewards + second_rewards >= REWARDS / 100 * 99);
+    assert!(first_rewards > 2 * second_rewards && first_rewards < 4 * second_rewards);
+    //claiming again yields nothing
+    assert_eq!(claim(&mut pool, &first, &reward_mint).await, 0);
+
+    //unstaking what's left of a stake without rewards closes it
+    pool.unstake_lp(&second, 0).await.unwrap();
+    assert_eq!(pool.gauge_stake(&second.keypair.pubkey()).await, None);
+    pool.unstake_lp(&first, stake).await.unwrap();
+    assert_eq!(pool.balance(&first.lp).await, lp_amount);
+    assert_eq!(pool.gauge().await.unwrap().total_staked, 0);
+}
+
+#[tokio::test]
+async fn test_gauge_rewards() {
+    let mut pool = BankPool::new(&BankPoolParams::default()).await.unwrap();
+    let user = add(&mut pool, POOL_BALANCE).await;
+    let lp_amount = pool.balance(&user.lp).await;
+    let governance_keypair = Keypair::from_bytes(&pool.governance_keypair.to_bytes()).unwrap();
+    let partner = Keypair::new();
+    let (governance_mint, governance_reward_account) = pool.create_reward_token(&governance_keypair.pubkey(), REWARDS);
+    let (partner_mint, partner_reward_account) = pool.create_reward_token(&partner.pubkey(), REWARDS);
+    let lp_mint = pool.lp_mint;
+    assert_eq!(
+        pool.add_gauge_reward(&lp_mint, &partner.pubkey()).await,
+        Err(pool_error(PoolError::InvalidGauge))
+    );
+    pool.add_gauge_reward(&governance_mint, &governance_keypair.pubkey())
+        .await
+        .unwrap();
+    pool.add_gauge_reward(&partner_mint, &partner.pubkey()).await.unwrap();
+
+    //only a reward's funder funds it
+    assert_eq!(
+        pool.fund_gauge(
+            &governance_keypair,
+            &partner_mint,
+            &partner_reward_account,
+            REWARDS,
+            DURATION
+        )
+        .await,
+        Err(pool_error(PoolError::InvalidGauge))
+    );
+    pool.fund_gauge(
+        &governance_keypair,
+        &governance_mint,
+        &governance_reward_account,
+        REWARDS,
+        DURATION,
+    )
+    .await
+    .unwrap();
+    pool.fund_gauge(
+        &partner,
+        &partner_mint,
+        &partner_reward_account,
+        REWARDS / 2,
+        DURATION / 2,
+    )
+    .await
+    .unwrap();
+    pool.stake_lp(&user, lp_amount).await.unwrap();
+
+    //retiring the partner's reward halfway through its emissions refunds the other half
+    pool.advance_clock(DURATION / 4).await;
+    pool.retire_gauge_reward(&partner_mint, &partner_reward_account)
+        .await
+        .unwrap();
+    let refunded = pool.balance(&partner_reward_account).await - REWARDS / 2;
+    assert!(refunded <= REWARDS / 4 && refunded >= REWARDS / 4 / 100 * 99);
+    assert_eq!(
+        pool.fund_gauge(&partner, &partner_mint, &partner_reward_account, REWARDS / 2, DURATION)
+            .await,
+        Err(pool_error(PoolError::InvalidGauge))
+    );
+
+    //the stake earns each reward on its own schedule and can still claim the retired one
+    pool.advance_clock(DURATION).await;
+    let governance_rewards = claim(&mut pool, &user, &governance_mint).await;
+    let partner_rewards = claim(&mut pool, &user, &partner_mint).await;
+    assert!(governance_rewards <= REWARDS && governance_rewards >= REWARDS / 100 * 99);
+    assert!(partner_rewards + refunded <= REWARDS / 2 && partner_rewards + refunded >= REWARDS / 2 / 100 * 99);
+
+    //a retired reward keeps its slot and can be added again
+    pool.add_gauge_reward(&partner_mint, &partner.pubkey()).await.unwrap();
+    pool.fund_gauge(&partner, &partner_mint, &partner_reward_account, REWARDS / 2, DURATION)
+        .await
+        .unwrap();
+    let gauge = pool.gauge().await.unwrap();
+    assert_eq!(gauge.rewards[1].mint, partner_mint);
+    assert_eq!(gauge.rewards[1].funder, partner.pubkey());
+    for _ in 2..MAX_GAUGE_REWARDS {
+        let (mint, _) = pool.create_reward_token(&partner.pubkey(), 0);
+        pool.add_gauge_reward(&mint, &partner.pubkey()).await.unwrap();
+    }
+    let (mint, _) = pool.create_reward_token(&partner.pubkey(), 0);
+    assert_eq!(
+        pool.add_gauge_reward(&mint, &partner.pubkey()).await,
+        Err(pool_error(PoolError::InvalidGauge))
+    );
+}
+
+#[tokio::test]
+async fn test_retired_gauge_reward_slot() {
+    let mut pool = BankPool::new(&BankPoolParams::default()).await.unwrap();
+    let user = add(&mut pool, POOL_BALANCE).await;
+    let lp_amount = pool.balance(&user.lp).await;
+    let funder = Keypair::new();
+    let (old_mint, old_funder_account) = pool.create_reward_token(&funder.pubkey(), REWARDS);
+    let (new_mint, new_funder_account) = pool.create_reward_token(&funder.pubkey(), REWARDS);
+    pool.add_gauge_reward(&old_mint, &funder.pubkey()).await.unwrap();
+    for _ in 1..MAX_GAUGE_REWARDS {
+        let (mint, _) = pool.create_reward_token(&funder.pubkey(), 0);
+        pool.add_gauge_reward(&mint, &funder.pubkey()).await.unwrap();
+    }
+    pool.fund_gauge(&funder, &old_mint, &old_funder_account, REWARDS, DURATION)
+        .await
+        .unwrap();
+    pool.stake_lp(&user, lp_amount).await.unwrap();
+    pool.advance_clock(DURATION).await;
+    pool.retire_gauge_reward(&old_mint, &old_funder_account).await.unwrap();
+
+    //the retired reward's slot is only free once its rewards could be claimed for the whole claim period
+    assert_eq!(
+        pool.add_gauge_reward(&new_mint, &funder.pubkey()).await,
+        Err(pool_error(PoolError::InvalidGauge))
+    );
+    pool.advance_clock(GAUGE_CLAIM_PERIOD).await;
+    pool.add_gauge_reward(&new_mint, &funder.pubkey()).await.unwrap();
+    let gauge = pool.gauge().await.unwrap();
+    assert_eq!(gauge.reward_index(&new_mint), Some(0));
+    assert_eq!(gauge.reward_index(&old_mint), None);
+    assert_eq!(gauge.rewards[0].generation, 2);
+
+    //the stake forfeits what it didn't claim of the old reward and earns the new one from scratch
+    assert_eq!(
+        pool.claim_gauge_rewards(&user, &old_mint, &Pubkey::new_unique()).await,
+        Err(pool_error(PoolError::InvalidGauge))
+    );
+    pool.fund_gauge(&funder, &new_mint, &new_funder_account, REWARDS, DURATION)
+        .await
+        .unwrap();
+    pool.advance_clock(DURATION).await;
+    let new_rewards = claim(&mut pool, &user, &new_mint).await;
+    assert!(new_rewards <= REWARDS && new_rewards >= REWARDS / 100 * 99);
+    assert_eq!(
+        pool.gauge_stake(&user.keypair.pubkey()).await.unwrap().rewards[0].rewards_owed,
+        0
+    );
+
+    //a replaced reward token can take a slot again, its vault still holds what wasn't claimed
+    pool.retire_gauge_reward(&new_mint, &new_funder_account).await.unwrap();
+    pool.advance_clock(GAUGE_CLAIM_PERIOD).await;
+    pool.add_gauge_reward(&old_mint, &funder.pubkey()).await.unwrap();
+    assert_eq!(pool.gauge().await.unwrap().reward_index(&old_mint), Some(0));
+    assert_eq!(claim(&mut pool, &user, &old_mint).await, 0);
+}
+
+#[tokio::test]
+async fn test_fund_gauge() {
+    let mut pool = BankPool::new(&BankPoolParams::default()).await.unwrap();
+    let user = add(&mut pool, POOL_BALANCE).await;
+    let funder = Keypair::new();
+    let (reward_mint, funder_reward_account) = pool.create_reward_token(&funder.pubkey(), 2 * REWARDS);
+    //the gauge doesn't exist before its first reward
+    assert_eq!(
+        pool.fund_gauge(&funder, &reward_mint, &funder_reward_account, REWARDS, DURATION)
+            .await,
+        Err(pool_error(PoolError::InvalidGauge))
+    );
+    pool.add_gauge_reward(&reward_mint, &funder.pubkey()).await.unwrap();
+    for duration in [0, MAX_GAUGE_DURATION + 1] {
+        assert_eq!(
+            pool.fund_gauge(&funder, &reward_mint, &funder_reward_account, REWARDS, duration)
+                .await,
+            Err(pool_error(PoolError::InvalidGauge))
+        );
+    }
+    //rewards that would be emitted at less than one token per second
+    assert_eq!(
+        pool.fund_gauge(&funder, &reward_mint, &funder_reward_account, 1, DURATION)
+            .await,
+        Err(pool_error(PoolError::InvalidGauge))
+    );
+
+    pool.fund_gauge(&funder, &reward_mint, &funder_reward_account, REWARDS, DURATION)
+        .await
+        .unwrap();
+    let reward = pool.gauge().await.unwrap().rewards[0];
+    assert_eq!(reward.mint, reward_mint);
+    assert_eq!(reward.emission_rate, REWARDS / DURATION as AmountT);
+    let (other_mint, other_account) = pool.create_reward_token(&funder.pubkey(), REWARDS);
+    assert_eq!(
+        pool.fund_gauge(&funder, &other_mint, &other_account, REWARDS, DURATION)
+            .await,
+        Err(pool_error(PoolError::InvalidGauge))
+    );
+
+    //funding again emits what's left along with the new rewards
+    pool.advance_clock(DURATION / 2).await;
+    pool.fund_gauge(&funder, &reward_mint, &funder_reward_account, REWARDS, DURATION)
+        .await
+        .unwrap();
+    let reward = pool.gauge().await.unwrap().rewards[0];
+    let emission_rate = REWARDS * 3 / 2 / DURATION as AmountT;
+    assert!(reward.emission_rate <= emission_rate && reward.emission_rate >= emission_rate / 100 * 99);
+    assert_eq!(reward.emission_end_ts, pool.now().await + DURATION);
+
+    pool.execute_governance_instruction(GovernanceInstruction::SetPaused { paused: true }, None)
+        .await
+        .unwrap();
+    assert_eq!(pool.stake_lp(&user, 1).await, Err(pool_error(PoolError::PoolIsPaused)));
+}
@@ -0,0 +1,265 @@
+#![cfg(feature = "test-bpf")]
+
+mod helpers;
+
+use helpers::{bank::*, AmountT};
+
+use borsh::{BorshDeserialize, BorshSerialize};
+use pool::{
+    error::PoolError,
+    instruction::{find_ve_boost_address, DeFiInstruction, GovernanceInstruction, VeBoostQuery},
+    oracle::BPS_ONE,
+    processor::ENACT_DELAY,
+    state::MAX_GAUGE_BOOST_BPS,
+    TOKEN_COUNT,
+};
+use solana_program::{
+    account_info::AccountInfo, entrypoint::ProgramResult, program::set_return_data, program_error::ProgramError,
+};
+use solana_program_test::*;
+use solana_sdk::{
+    account::Account,
+    instruction::InstructionError,
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+};
+
+const POOL_BALANCE: AmountT = 1_000_000_000;
+const REWARDS: AmountT = 100_000_000;
+const DURATION: i64 = 100_000;
+
+fn pool_error(error: PoolError) -> InstructionError {
+    InstructionError::Custom(error as u32)
+}
+
+//a voting escrow program that boosts a stake by what its owner's ve account holds (a u64 in basis points), not at all
+// if the account doesn't exist
+fn process_ve(program_id: &Pubkey, accounts: &[AccountInfo], data: &[u8]) -> ProgramResult {
+    let query = VeBoostQuery::try_from_slice(data)?;
+    let ve_account = match accounts {
+        [_pool, _owner, ve_account] => ve_account,
+        _ => return Err(ProgramError::NotEnoughAccountKeys),
+    };
+    if *ve_account.key != find_ve_boost_address(program_id, &query.pool, &query.owner).0 {
+        return Err(ProgramError::InvalidArgument);
+    }
+    let boost_bps = if ve_account.owner == program_id {
+        u64::try_from_slice(&ve_account.data.borrow())?
+    } else {
+        0
+    };
+    set_return_data(&boost_bps.try_to_vec()?);
+    Ok(())
+}
+
+//a voting escrow program that is broken (or hostile) and fails every query
+fn process_failing_ve(_program_id: &Pubkey, _accounts: &[AccountInfo], _data: &[u8]) -> ProgramResult {
+    Err(ProgramError::Custom(0))
+}
+
+fn set_boost(pool: &mut BankPool, ve_program: &Pubkey, owner: &BankUser, boost_bps: u64) {
+    let ve_account = find_ve_boost_address(ve_program, &pool.pool, &owner.keypair.pubkey()).0;
+    pool.context.set_account(
+        &ve_account,
+        &Account {
+            lamports: 1_000_000,
+            data: boost_bps.try_to_vec().unwrap(),
+            owner: *ve_program,
+            ..Account::default()
+        }
+        .into(),
+    );
+}
+
+async fn add(pool: &mut BankPool, amount: AmountT) -> BankUser {
+    let user = pool.create_user(&[amount; TOKEN_COUNT]);
+    pool.execute_defi_instruction(
+        DeFiInstruction::Add {
+            input_amounts: [amount; TOKEN_COUNT],
+            minimum_mint_amount: 0,
+        },
+        &user,
+    )
+    .await
+    .unwrap();
+    user
+}
+
+//claims the user's rewards to a new token account and returns them
+async fn claim(pool: &mut BankPool, user: &BankUser, reward_mint: &Pubkey) -> AmountT {
+    let reward_account = Pubkey::new_unique();
+    pool.set_token_account(&reward_account, reward_mint, &user.keypair.pubkey(), 0);
+    pool.claim_gauge_rewards(user, reward_mint, &reward_account)
+        .await
+        .unwrap();
+    pool.balance(&reward_account).await
+}
+
+#[tokio::test]
+async fn test_ve_boost_splits_emissions_by_working_amount() {
+    let ve_program = Pubkey::new_unique();
+    let mut pool = BankPool::new_with(&BankPoolParams::default(), |program_test| {
+        program_test.add_program("ve", ve_program, processor!(process_ve));
+    })
+    .await
+    .unwrap();
+    let boosted = add(&mut pool, POOL_BALANCE).await;
+    let unboosted = add(&mut pool, POOL_BALANCE).await;
+    let stake = pool.balance(&boosted.lp).await / 2;
+    let funder = Keypair::new();
+    let (reward_mint, funder_reward_account) = pool.create_reward_token(&funder.pubkey(), REWARDS);
+    assert_eq!(
+        pool.set_gauge_ve_program(Some(ve_program)).await,
+        Err(pool_error(PoolError::InvalidGauge))
+    );
+
+    pool.add_gauge_reward(&reward_mint, &funder.pubkey()).await.unwrap();
+    pool.set_gauge_ve_program(Some(ve_program)).await.unwrap();
+    assert_eq!(pool.gauge().await.unwrap().ve_program, ve_program);
+    set_boost(&mut pool, &ve_program, &boosted, 2 * BPS_ONE);
+    pool.stake_lp(&boosted, stake).await.unwrap();
+    pool.stake_lp(&unboosted, stake).await.unwrap();
+    pool.fund_gauge(&funder, &reward_mint, &funder_reward_account, REWARDS, DURATION)
+        .await
+        .unwrap();
+    let gauge = pool.gauge().await.unwrap();
+    assert_eq!(
+        (gauge.total_staked, gauge.total_working),
+        (2 * stake, 3 * stake as u128)
+    );
+    assert_eq!(
+        pool.gauge_stake(&boosted.keypair.pubkey())
+            .await
+            .unwrap()
+            .working_amount,
+        2 * stake as u128
+    );
+
+    //stakes have to pass the gauge's ve program and their owner's ve account
+    pool.gauge_ve_program = Some(Pubkey::new_unique());
+    assert_eq!(
+        pool.update_gauge_boost(&boosted.keypair.pubkey()).await,
+        Err(pool_error(PoolError::InvalidVeProgram))
+    );
+    pool.gauge_ve_program = Some(ve_program);
+
+    //the boost ran out halfway, which anyone can apply
+    pool.advance_clock(DURATION / 2).await;
+    set_boost(&mut pool, &ve_program, &boosted, 0);
+    pool.update_gauge_boost(&boosted.keypair.pubkey()).await.unwrap();
+    assert_eq!(pool.gauge().await.unwrap().total_working, 2 * stake as u128);
+
+    //the boosted stake earned 2/3 of the first half and 1/2 of the second
+    pool.advance_clock(DURATION).await;
+    let boosted_rewards = claim(&mut pool, &boosted, &reward_mint).await;
+    let unboosted_rewards = claim(&mut pool, &unboosted, &reward_mint).await;
+    assert!(boosted_rewards + unboosted_rewards <= REWARDS);
+    assert!((boosted_rewards as i128 * 5 - unboosted_rewards as i128 * 7).abs() < REWARDS as i128 / 100);
+}
+
+#[tokio::test]
+async fn test_set_gauge_ve_program() {
+    let ve_program = Pubkey::new_unique();
+    let mut pool = BankPool::new_with(&BankPoolParams::default(), |program_test| {
+        program_test.add_program("ve", ve_program, processor!(process_ve));
+    })
+    .await
+    .unwrap();
+    let user = add(&mut pool, POOL_BALANCE).await;
+    let stake = pool.balance(&user.lp).await / 2;
+    let (reward_mint, _) = pool.create_reward_token(&Pubkey::new_unique(), 0);
+    pool.add_gauge_reward(&reward_mint, &Pubkey::new_unique())
+        .await
+        .unwrap();
+    pool.stake_lp(&user, stake).await.unwrap();
+    assert_eq!(
+        pool.gauge_stake(&user.keypair.pubkey()).await.unwrap().working_amount,
+        stake as u128
+    );
+
+    //boosts are capped, and staking more updates them as well
+    pool.set_gauge_ve_program(Some(ve_program)).await.unwrap();
+    set_boost(&mut pool, &ve_program, &user, u64::MAX);
+    pool.stake_lp(&user, 0).await.unwrap();
+    assert_eq!(
+        pool.gauge_stake(&user.keypair.pubkey()).await.unwrap().working_amount,
+        stake as u128 * MAX_GAUGE_BOOST_BPS as u128 / BPS_ONE as u128
+    );
+
+    //stakes keep their boost until they are updated once the program is removed
+    pool.set_gauge_ve_program(None).await.unwrap();
+    assert_ne!(
+        pool.gauge_stake(&user.keypair.pubkey()).await.unwrap().working_amount,
+        stake as u128
+    );
+    pool.update_gauge_boost(&user.keypair.pubkey()).await.unwrap();
+    let gauge = pool.gauge().await.unwrap();
+    assert_eq!(
+        (gauge.ve_program, gauge.total_working),
+        (Pubkey::default(), stake as u128)
+    );
+}
+
+#[tokio::test]
+async fn test_failing_ve_program_does_not_lock_stakes() {
+    let ve_program = Pubkey::new_unique();
+    let failing_ve_program = Pubkey::new_unique();
+    let mut pool = BankPool::new_with(&BankPoolParams::default(), |program_test| {
+        program_test.add_program("ve", ve_program, processor!(process_ve));
+        program_test.add_program("failing_ve", failing_ve_program, processor!(process_failing_ve));
+    })
+    .await
+    .unwrap();
+    let user = add(&mut pool, POOL_BALANCE).await;
+    let lp_balance = pool.balance(&user.lp).await;
+    let stake = lp_balance / 2;
+    let funder = Keypair::new();
+    let (reward_mint, funder_reward_account) = pool.create_reward_token(&funder.pubkey(), REWARDS);
+    pool.add_gauge_reward(&reward_mint, &funder.pubkey()).await.unwrap();
+    pool.set_gauge_ve_program(Some(ve_program)).await.unwrap();
+    set_boost(&mut pool, &ve_program, &user, 2 * BPS_ONE);
+    pool.stake_lp(&user, stake).await.unwrap();
+    pool.fund_gauge(&funder, &reward_mint, &funder_reward_account, REWARDS, DURATION)
+        .await
+        .unwrap();
+
+    //switching the program only takes effect after the timelock
+    pool.execute_governance_instruction(
+        GovernanceInstruction::SetGaugeVeProgram {
+            ve_program: failing_ve_program,
+        },
+        None,
+    )
+    .await
+    .unwrap();
+    assert_eq!(
+        pool.execute_governance_instruction(GovernanceInstruction::EnactGaugeVeProgram {}, None)
+            .await,
+        Err(pool_error(PoolError::InsufficientDelay))
+    );
+    let gauge = pool.gauge().await.unwrap();
+    assert_eq!(
+        (gauge.ve_program, gauge.prepared_ve_program),
+        (ve_program, failing_ve_program)
+    );
+    pool.advance_clock(ENACT_DELAY).await;
+    pool.execute_governance_instruction(GovernanceInstruction::EnactGaugeVeProgram {}, None)
+        .await
+        .unwrap();
+    pool.gauge_ve_program = Some(failing_ve_program);
+    assert_eq!(pool.gauge().await.unwrap().ve_program, failing_ve_program);
+
+    //the failing program blocks stakes and boost updates, but neither unstakes nor claims
+    assert!(pool.stake_lp(&user, 1).await.is_err());
+    assert!(pool.update_gauge_boost(&user.keypair.pubkey()).await.is_err());
+    pool.unstake_lp(&user, stake / 2).await.unwrap();
+    let remaining = (stake - stake / 2) as u128;
+    assert_eq!(
+        pool.gauge_stake(&user.keypair.pubkey()).await.unwrap().working_amount,
+        remaining
+    );
+    assert_eq!(pool.gauge().await.unwrap().total_working, remaining);
+    assert!(claim(&mut pool, &user, &reward_mint).await > 0);
+    pool.unstake_lp(&user, stake - stake / 2).await.unwrap();
+    assert_eq!(pool.balance(&user.lp).await, lp_balance);
+}
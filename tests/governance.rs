@@ -0,0 +1,373 @@
+#![cfg(feature = "test-bpf")]
+
+mod helpers;
+
+use helpers::{bank::*, DecT};
+
+use pool::{
+    amp_factor::MIN_ADJUSTMENT_WINDOW, error::PoolError, instruction::GovernanceInstruction, processor::ENACT_DELAY,
+    TOKEN_COUNT,
+};
+use solana_program::program_option::COption;
+use solana_program_test::*;
+use solana_sdk::{
+    instruction::InstructionError,
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+};
+use spl_token::{error::TokenError, state::Account as TokenState};
+
+fn pool_error(error: PoolError) -> InstructionError {
+    InstructionError::Custom(error as u32)
+}
+
+fn prepare_fee_change(lp_fee: DecT, governance_fee: DecT) -> GovernanceInstruction<TOKEN_COUNT> {
+    GovernanceInstruction::PrepareFeeChange { lp_fee, governance_fee }
+}
+
+fn prepare_governance_transition(upcoming_governance_key: Pubkey) -> GovernanceInstruction<TOKEN_COUNT> {
+    GovernanceInstruction::PrepareGovernanceTransition {
+        upcoming_governance_key,
+    }
+}
+
+#[tokio::test]
+async fn test_enact_fee_change_at_delay_boundary() {
+    let mut pool = BankPool::new(&BankPoolParams::default()).await.unwrap();
+    let (lp_fee, governance_fee) = (DecT::new(4, 4).unwrap(), DecT::new(2, 4).unwrap());
+
+    let prepare_ts = pool.now().await;
+    pool.execute_governance_instruction(prepare_fee_change(lp_fee, governance_fee), None)
+        .await
+        .unwrap();
+    assert_eq!(pool.state().await.fee_transition_ts, prepare_ts + ENACT_DELAY);
+
+    pool.set_now(prepare_ts + ENACT_DELAY - 1).await;
+    assert_eq!(
+        pool.execute_governance_instruction(GovernanceInstruction::EnactFeeChange {}, None)
+            .await,
+        Err(pool_error(PoolError::InsufficientDelay))
+    );
+    assert_eq!(pool.state().await.lp_fee.get(), DecT::new(3, 6).unwrap());
+
+    pool.set_now(prepare_ts + ENACT_DELAY).await;
+    pool.execute_governance_instruction(GovernanceInstruction::EnactFeeChange {}, None)
+        .await
+        .unwrap();
+
+    let state = pool.state().await;
+    assert_eq!(state.lp_fee.get(), lp_fee);
+    assert_eq!(state.governance_fee.get(), governance_fee);
+    assert_eq!(state.prepared_lp_fee.get(), DecT::from(0));
+    assert_eq!(state.prepared_governance_fee.get(), DecT::from(0));
+    assert_eq!(state.fee_transition_ts, 0);
+}
+
+#[tokio::test]
+async fn test_enact_fee_change_without_prepare() {
+    let mut pool = BankPool::new(&BankPoolParams::default()).await.unwrap();
+
+    assert_eq!(
+        pool.execute_governance_instruction(GovernanceInstruction::EnactFeeChange {}, None)
+            .await,
+        Err(pool_error(PoolError::InvalidEnact))
+    );
+
+    //a prepared change can only be enacted once
+    pool.execute_governance_instruction(prepare_fee_change(DecT::new(4, 4).unwrap(), DecT::from(0)), None)
+        .await
+        .unwrap();
+    pool.advance_clock(ENACT_DELAY).await;
+    pool.execute_governance_instruction(GovernanceInstruction::EnactFeeChange {}, None)
+        .await
+        .unwrap();
+    assert_eq!(
+        pool.execute_governance_instruction(GovernanceInstruction::EnactFeeChange {}, None)
+            .await,
+        Err(pool_error(PoolError::InvalidEnact))
+    );
+}
+
+#[tokio::test]
+async fn test_re_prepare_fee_change_restarts_delay() {
+    let mut pool = BankPool::new(&BankPoolParams::default()).await.unwrap();
+    let (first_lp_fee, second_lp_fee) = (DecT::new(4, 4).unwrap(), DecT::new(5, 4).unwrap());
+
+    let first_prepare_ts = pool.now().await;
+    pool.execute_governance_instruction(prepare_fee_change(first_lp_fee, DecT::from(0)), None)
+        .await
+        .unwrap();
+
+    pool.set_now(first_prepare_ts + ENACT_DELAY - 1).await;
+    pool.execute_governance_instruction(prepare_fee_change(second_lp_fee, DecT::from(0)), None)
+        .await
+        .unwrap();
+
+    //the first change would be enactable by now but it was replaced
+    pool.set_now(first_prepare_ts + ENACT_DELAY).await;
+    assert_eq!(
+        pool.execute_governance_instruction(GovernanceInstruction::EnactFeeChange {}, None)
+            .await,
+        Err(pool_error(PoolError::InsufficientDelay))
+    );
+
+    pool.set_now(first_prepare_ts + 2 * ENACT_DELAY - 1).await;
+    pool.execute_governance_instruction(GovernanceInstruction::EnactFeeChange {}, None)
+        .await
+        .unwrap();
+    assert_eq!(pool.state().await.lp_fee.get(), second_lp_fee);
+}
+
+#[tokio::test]
+async fn test_cancel_fee_change() {
+    //there is no dedicated cancel instruction, governance cancels by preparing the current fees instead
+    let mut pool = BankPool::new(&BankPoolParams::default()).await.unwrap();
+    let original = pool.state().await;
+
+    pool.execute_governance_instruction(prepare_fee_change(DecT::new(4, 4).unwrap(), DecT::from(0)), None)
+        .await
+        .unwrap();
+    pool.advance_clock(ENACT_DELAY / 2).await;
+    pool.execute_governance_instruction(
+        prepare_fee_change(original.lp_fee.get(), original.governance_fee.get()),
+        None,
+    )
+    .await
+    .unwrap();
+    pool.advance_clock(ENACT_DELAY).await;
+    pool.execute_governance_instruction(GovernanceInstruction::EnactFeeChange {}, None)
+        .await
+        .unwrap();
+
+    let state = pool.state().await;
+    assert_eq!(state.lp_fee.get(), original.lp_fee.get());
+    assert_eq!(state.governance_fee.get(), original.governance_fee.get());
+}
+
+#[tokio::test]
+async fn test_prepare_fee_change_requires_governance() {
+    let mut pool = BankPool::new(&BankPoolParams::default()).await.unwrap();
+    let impostor = Keypair::new();
+
+    assert_eq!(
+        pool.execute_governance_instruction_as(
+            prepare_fee_change(DecT::new(4, 4).unwrap(), DecT::from(0)),
+            None,
+            &impostor
+        )
+        .await,
+        Err(pool_error(PoolError::InvalidGovernanceAccount))
+    );
+    assert_eq!(pool.state().await.fee_transition_ts, 0);
+}
+
+#[tokio::test]
+async fn test_enact_governance_transition_at_delay_boundary() {
+    let mut pool = BankPool::new(&BankPoolParams::default()).await.unwrap();
+    let upcoming_governance = Keypair::new();
+
+    let prepare_ts = pool.now().await;
+    pool.execute_governance_instruction(prepare_governance_transition(upcoming_governance.pubkey()), None)
+        .await
+        .unwrap();
+    let state = pool.state().await;
+    assert_eq!(state.prepared_governance_key, upcoming_governance.pubkey());
+    assert_eq!(state.governance_transition_ts, prepare_ts + ENACT_DELAY);
+
+    pool.set_now(prepare_ts + ENACT_DELAY - 1).await;
+    assert_eq!(
+        pool.execute_governance_instruction(GovernanceInstruction::EnactGovernanceTransition {}, None)
+            .await,
+        Err(pool_error(PoolError::InsufficientDelay))
+    );
+
+    //the prepared governance has no privileges until the transition is enacted
+    assert_eq!(
+        pool.execute_governance_instruction_as(
+            GovernanceInstruction::SetPaused { paused: true },
+            None,
+            &upcoming_governance
+        )
+        .await,
+        Err(pool_error(PoolError::InvalidGovernanceAccount))
+    );
+
+    pool.set_now(prepare_ts + ENACT_DELAY).await;
+    pool.execute_governance_instruction(GovernanceInstruction::EnactGovernanceTransition {}, None)
+        .await
+        .unwrap();
+
+    let state = pool.state().await;
+    assert_eq!(state.governance_key, upcoming_governance.pubkey());
+    assert_eq!(state.prepared_governance_key, Pubkey::default());
+    assert_eq!(state.governance_transition_ts, 0);
+
+    assert_eq!(
+        pool.execute_governance_instruction(GovernanceInstruction::SetPaused { paused: true }, None)
+            .await,
+        Err(pool_error(PoolError::InvalidGovernanceAccount))
+    );
+    pool.execute_governance_instruction_as(
+        GovernanceInstruction::SetPaused { paused: true },
+        None,
+        &upcoming_governance,
+    )
+    .await
+    .unwrap();
+    assert!(pool.state().await.is_paused);
+}
+
+#[tokio::test]
+async fn test_enact_governance_transition_without_prepare() {
+    let mut pool = BankPool::new(&BankPoolParams::default()).await.unwrap();
+
+    assert_eq!(
+        pool.execute_governance_instruction(GovernanceInstruction::EnactGovernanceTransition {}, None)
+            .await,
+        Err(pool_error(PoolError::InvalidEnact))
+    );
+}
+
+#[tokio::test]
+async fn test_re_prepare_governance_transition() {
+    let mut pool = BankPool::new(&BankPoolParams::default()).await.unwrap();
+    let (first_governance, second_governance) = (Pubkey::new_unique(), Pubkey::new_unique());
+
+    let first_prepare_ts = pool.now().await;
+    pool.execute_governance_instruction(prepare_governance_transition(first_governance), None)
+        .await
+        .unwrap();
+    pool.set_now(first_prepare_ts + ENACT_DELAY / 2).await;
+    pool.execute_governance_instruction(prepare_governance_transition(second_governance), None)
+        .await
+        .unwrap();
+
+    pool.set_now(first_prepare_ts + ENACT_DELAY).await;
+    assert_eq!(
+        pool.execute_governance_instruction(GovernanceInstruction::EnactGovernanceTransition {}, None)
+            .await,
+        Err(pool_error(PoolError::InsufficientDelay))
+    );
+
+    pool.set_now(first_prepare_ts + ENACT_DELAY / 2 + ENACT_DELAY).await;
+    pool.execute_governance_instruction(GovernanceInstruction::EnactGovernanceTransition {}, None)
+        .await
+        .unwrap();
+    assert_eq!(pool.state().await.governance_key, second_governance);
+}
+
+#[tokio::test]
+async fn test_cancel_governance_transition() {
+    //as with fees, a pending transition is cancelled by preparing a transition to the current governance
+    let mut pool = BankPool::new(&BankPoolParams::default()).await.unwrap();
+    let governance = pool.governance_keypair.pubkey();
+
+    pool.execute_governance_instruction(prepare_governance_transition(Pubkey::new_unique()), None)
+        .await
+        .unwrap();
+    pool.execute_governance_instruction(prepare_governance_transition(governance), None)
+        .await
+        .unwrap();
+    pool.advance_clock(ENACT_DELAY).await;
+    pool.execute_governance_instruction(GovernanceInstruction::EnactGovernanceTransition {}, None)
+        .await
+        .unwrap();
+
+    assert_eq!(pool.state().await.governance_key, governance);
+    pool.execute_governance_instruction(GovernanceInstruction::SetPaused { paused: true }, None)
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn test_renounce_governance() {
+    let mut pool = BankPool::new(&BankPoolParams::default()).await.unwrap();
+    let now = pool.now().await;
+    pool.execute_governance_instruction(
+        GovernanceInstruction::AdjustAmpFactor {
+            target_ts: now + MIN_ADJUSTMENT_WINDOW,
+            target_value: DecT::from(2000),
+        },
+        None,
+    )
+    .await
+    .unwrap();
+    pool.execute_governance_instruction(prepare_fee_change(DecT::new(4, 4).unwrap(), DecT::from(0)), None)
+        .await
+        .unwrap();
+
+    pool.set_now(now + MIN_ADJUSTMENT_WINDOW / 2).await;
+    pool.execute_governance_instruction(
+        GovernanceInstruction::RenounceGovernance { lock_amp_factor: true },
+        None,
+    )
+    .await
+    .unwrap();
+    let state = pool.state().await;
+    assert_eq!(state.governance_key, Pubkey::default());
+    assert_eq!(state.fee_transition_ts, 0);
+    //the adjustment stopped halfway
+    let amp_factor = state.amp_factor.get(now + MIN_ADJUSTMENT_WINDOW / 2);
+    assert!(amp_factor > DecT::from(1000) && amp_factor < DecT::from(2000));
+    assert_eq!(state.amp_factor.get(now + MIN_ADJUSTMENT_WINDOW), amp_factor);
+
+    pool.advance_clock(ENACT_DELAY).await;
+    assert_eq!(
+        pool.execute_governance_instruction(GovernanceInstruction::EnactFeeChange {}, None)
+            .await,
+        Err(pool_error(PoolError::GovernanceRenounced))
+    );
+}
+
+#[tokio::test]
+async fn test_renounce_governance_of_paused_pool() {
+    //nobody could unpause it afterwards
+    let mut pool = BankPool::new(&BankPoolParams::default()).await.unwrap();
+    pool.execute_governance_instruction(GovernanceInstruction::SetPaused { paused: true }, None)
+        .await
+        .unwrap();
+    assert_eq!(
+        pool.execute_governance_instruction(
+            GovernanceInstruction::RenounceGovernance { lock_amp_factor: false },
+            None
+        )
+        .await,
+        Err(pool_error(PoolError::PoolIsPaused))
+    );
+}
+
+#[tokio::test]
+async fn test_migrate_governance_fee_account() {
+    let mut pool = BankPool::new(&BankPoolParams::default()).await.unwrap();
+    let (old_fee_account, lp_mint) = (pool.governance_fee_account, pool.lp_mint);
+    let governance = pool.governance_keypair.pubkey();
+    pool.set_token_account(&old_fee_account, &lp_mint, &governance, 5_000);
+    let migrate = |governance_fee_key| GovernanceInstruction::MigrateGovernanceFeeAccount { governance_fee_key };
+
+    let (other_mint_account, token_mint) = (Pubkey::new_unique(), pool.token_mints[0]);
+    pool.set_token_account(&other_mint_account, &token_mint, &governance, 0);
+    assert_eq!(
+        pool.execute_governance_instruction(migrate(other_mint_account), Some(&old_fee_account))
+            .await,
+        Err(InstructionError::Custom(TokenError::MintMismatch as u32))
+    );
+    let closable_account = Pubkey::new_unique();
+    pool.set_token_account(&closable_account, &lp_mint, &governance, 0);
+    let mut token_state = pool.get_packed::<TokenState>(&closable_account).await;
+    token_state.close_authority = COption::Some(Pubkey::new_unique());
+    pool.set_packed(&closable_account, token_state);
+    assert_eq!(
+        pool.execute_governance_instruction(migrate(closable_account), Some(&old_fee_account))
+            .await,
+        Err(pool_error(PoolError::InvalidGovernanceFeeAccount))
+    );
+
+    let new_fee_account = Pubkey::new_unique();
+    pool.set_token_account(&new_fee_account, &lp_mint, &Pubkey::new_unique(), 0);
+    pool.execute_governance_instruction(migrate(new_fee_account), Some(&old_fee_account))
+        .await
+        .unwrap();
+    assert_eq!(pool.balance(&old_fee_account).await, 0);
+    assert_eq!(pool.balance(&new_fee_account).await, 5_000);
+    assert_eq!(pool.state().await.governance_fee_key, new_fee_account);
+}
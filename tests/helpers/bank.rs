@@ -0,0 +1,1938 @@
+//ProgramTest (BanksClient) based counterpart of SolanaNode/DeployedPool
+//unlike the test validator, the bank lets us set up accounts directly and override the clock, which is what
+// anything time dependent (governance timelocks, amp factor adjustments) needs to be tested
+
+use super::{AmountT, DecT};
+use borsh::{BorshDeserialize, BorshSerialize};
+use pool::{
+    client::{amm::PoolAmm, snapshot::PoolSnapshot},
+    common::*,
+    instruction::*,
+    oracle::{self, OracleKind, OracleLimits, RateSource, WrapperRate},
+    processor::{Processor, ENACT_DELAY},
+    state::{
+        CallerMode, FeeCheckpoint, Gauge, GaugeStake, LaunchAllocation, LpLock, LpSupplySnapshots, MaintenanceWindow,
+        PoolState, PoolStateV0, PriceBand, Rebate, RebateCampaign, Session, TokenParticipation, TradingHours, UserGate,
+        VirtualPriceState, WithdrawalAnnouncement, MAX_ALLOWED_CALLERS, MAX_EMERGENCY_RESPONDERS,
+        MAX_MAINTENANCE_WINDOWS, VIRTUAL_PRICE_EXPO,
+    },
+    token_2022, TOKEN_COUNT,
+};
+use solana_program::{
+    clock::{Clock, Epoch, Slot, UnixTimestamp},
+    hash::Hash,
+    program_option::COption,
+    program_pack::{IsInitialized, Pack},
+    pubkey::Pubkey,
+    rent::Rent,
+    sysvar,
+};
+use solana_program_test::*;
+use solana_sdk::{
+    account::{create_account_shared_data_for_test, Account},
+    instruction::{AccountMeta, Instruction, InstructionError},
+    native_token::LAMPORTS_PER_SOL,
+    signature::{Keypair, Signer},
+    system_program,
+    transaction::{Transaction, TransactionError},
+    transport::TransportError,
+};
+use spl_token::state::{Account as TokenState, AccountState as TokenAccountState, Mint as MintState};
+use std::{collections::HashMap, convert::TryInto};
+
+pub struct BankPoolParams {
+    pub amp_factor: DecT,
+    pub lp_fee: DecT,
+    pub governance_fee: DecT,
+    pub lp_decimals: u8,
+    pub token_decimals: [u8; TOKEN_COUNT],
+}
+
+impl Default for BankPoolParams {
+    fn default() -> Self {
+        Self {
+            amp_factor: DecT::new(1000, 0).unwrap(),
+            lp_fee: DecT::new(3, 6).unwrap(),
+            governance_fee: DecT::new(1, 6).unwrap(),
+            lp_decimals: 6,
+            token_decimals: [6; TOKEN_COUNT],
+        }
+    }
+}
+
+pub struct BankUser {
+    pub keypair: Keypair,
+    pub lp: Pubkey,
+    pub tokens: [Pubkey; TOKEN_COUNT],
+}
+
+/// An uninitialized pool with the tokens of a `BankPool` that it can be moved to with `ExportState`
+pub struct BankExportTarget {
+    pub program_id: Pubkey,
+    pub pool: Pubkey,
+    pub authority: Pubkey,
+    pub nonce: u8,
+    pub token_accounts: [Pubkey; TOKEN_COUNT],
+}
+
+pub struct BankPool {
+    pub context: ProgramTestContext,
+    pub pool: Pubkey,
+    pub authority: Pubkey,
+    pub nonce: u8,
+    pub lp_mint: Pubkey,
+    pub token_mints: [Pubkey; TOKEN_COUNT],
+    pub token_accounts: [Pubkey; TOKEN_COUNT],
+    pub governance_keypair: Keypair,
+    pub governance_fee_account: Pubkey,
+    /// Oracle price accounts that swaps pass along (see `set_oracle_guard`)
+    pub oracle_keys: Option<[Pubkey; TOKEN_COUNT]>,
+    /// Rate accounts of the rated tokens that defi instructions pass along (see `set_rate_source`)
+    pub rate_keys: Vec<Pubkey>,
+    /// Whether defi instructions pass the instructions sysvar (see `set_caller_allowlist`)
+    pub restricts_callers: bool,
+    /// Whether defi instructions pass the Token-2022 program and users get Token-2022 lp token accounts (see
+    /// `use_token_2022_lp_mint`)
+    pub lp_non_transferable: bool,
+    /// Whether adds pass their user's launch allocation account (see `set_launch_cap`)
+    pub launch_capped: bool,
+    /// The gate that defi instructions pass their user's gate account for (see `set_user_gate`)
+    pub user_gate: UserGate,
+    /// The gate that adds and single token removes also pass their user's gate account for (see `set_depositor_gate`)
+    pub depositor_gate: UserGate,
+    /// The compliance program that defi instructions (but uniform removes) pass along with their user's compliance
+    /// account (see `set_compliance_program`)
+    pub compliance_program: Option<Pubkey>,
+    /// Whether imbalanced removes pass their user's withdrawal announcement account (see `set_withdrawal_cooldown`)
+    pub withdrawal_cooldown: bool,
+    /// The voting escrow program that stakes and boost updates pass along with their owner's ve account (see
+    /// `set_gauge_ve_program`)
+    pub gauge_ve_program: Option<Pubkey>,
+    last_blockhash: Hash,
+}
+
+impl BankPool {
+    pub async fn new(params: &BankPoolParams) -> Result<Self, InstructionError> {
+        Self::new_with(params, |_| {}).await
+    }
+
+    /// Like `new` but lets `setup` add further programs (e.g. ones that call the pool via CPI)
+    pub async fn new_with(
+        params: &BankPoolParams,
+        setup: impl FnOnce(&mut ProgramTest),
+    ) -> Result<Self, InstructionError> {
+        let mut bank_pool = Self::new_uninitialized_with(params, setup).await;
+        let init_ix = bank_pool.init_ix(params);
+        bank_pool.execute_transaction(init_ix, &[]).await?;
+        Ok(bank_pool)
+    }
+
+    /// Like `new` but initializes a weighted pool (see `init_weighted_ix`)
+    pub async fn new_weighted(
+        params: &BankPoolParams,
+        token_weights: [u32; TOKEN_COUNT],
+    ) -> Result<Self, InstructionError> {
+        let mut bank_pool = Self::new_uninitialized(params).await;
+        let init_ix = bank_pool.init_weighted_ix(params, token_weights);
+        bank_pool.execute_transaction(init_ix, &[]).await?;
+        Ok(bank_pool)
+    }
+
+    /// Like `new` but initializes a fixed rate pool (see `init_fixed_rate_ix`)
+    pub async fn new_fixed_rate(params: &BankPoolParams) -> Result<Self, InstructionError> {
+        let mut bank_pool = Self::new_uninitialized(params).await;
+        let init_ix = bank_pool.init_fixed_rate_ix(params);
+        bank_pool.execute_transaction(init_ix, &[]).await?;
+        Ok(bank_pool)
+    }
+
+    /// Like `new` but initializes a hybrid pool (see `init_hybrid_ix`)
+    pub async fn new_hybrid(
+        params: &BankPoolParams,
+        volatile_index: u8,
+        initial_rate: u64,
+    ) -> Result<Self, InstructionError> {
+        let mut bank_pool = Self::new_uninitialized(params).await;
+        let init_ix = bank_pool.init_hybrid_ix(params, volatile_index, initial_rate);
+        bank_pool.execute_transaction(init_ix, &[]).await?;
+        Ok(bank_pool)
+    }
+
+    /// Sets up all accounts of the pool without initializing it, so they can be tampered with before running `init_ix`
+    pub async fn new_uninitialized(params: &BankPoolParams) -> Self {
+        Self::new_uninitialized_with(params, |_| {}).await
+    }
+
+    pub async fn new_uninitialized_with(params: &BankPoolParams, setup: impl FnOnce(&mut ProgramTest)) -> Self {
+        let mut program_test = ProgramTest::new("pool", pool::id(), processor!(Processor::<{ TOKEN_COUNT }>::process));
+        setup(&mut program_test);
+
+        let pool = Pubkey::new_unique();
+        let pool_len = solana_program::borsh::get_packed_len::<PoolState<TOKEN_COUNT>>();
+        program_test.add_account(
+            pool,
+            Account {
+                lamports: Rent::default().minimum_balance(pool_len),
+                data: vec![0; pool_len],
+                owner: pool::id(),
+                ..Account::default()
+            },
+        );
+        let (authority, nonce) = Pubkey::find_program_address(&[&pool.to_bytes()[..32]], &pool::id());
+
+        let lp_mint = Pubkey::new_unique();
+        program_test.add_account(lp_mint, mint_account(params.lp_decimals, &authority));
+        let token_mints: [_; TOKEN_COUNT] = create_array(|_| Pubkey::new_unique());
+        for i in 0..TOKEN_COUNT {
+            //nobody needs to mint, user balances are set up directly (see create_user)
+            program_test.add_account(
+                token_mints[i],
+                mint_account(params.token_decimals[i], &Pubkey::new_unique()),
+            );
+        }
+        let token_accounts: [_; TOKEN_COUNT] = create_array(|_| Pubkey::new_unique());
+        for i in 0..TOKEN_COUNT {
+            program_test.add_account(token_accounts[i], token_account(&token_mints[i], &authority, 0));
+        }
+
+        let governance_keypair = Keypair::new();
+        let governance_fee_account = Pubkey::new_unique();
+        program_test.add_account(
+            governance_fee_account,
+            token_account(&lp_mint, &governance_keypair.pubkey(), 0),
+        );
+
+        let context = program_test.start_with_context().await;
+        let last_blockhash = context.last_blockhash;
+        Self {
+            context,
+            pool,
+            authority,
+            nonce,
+            lp_mint,
+            token_mints,
+            token_accounts,
+            governance_keypair,
+            governance_fee_account,
+            oracle_keys: None,
+            rate_keys: vec![],
+            restricts_callers: false,
+            lp_non_transferable: false,
+            launch_capped: false,
+            user_gate: UserGate::Open,
+            depositor_gate: UserGate::Open,
+            compliance_program: None,
+            withdrawal_cooldown: false,
+            gauge_ve_program: None,
+            last_blockhash,
+        }
+    }
+
+    /// Creates the pool with `CreatePool` at the canonical address of `fee_tier` and the mints of `new_uninitialized`
+    /// (which get sorted, so `params.token_decimals` shouldn't differ), the pool then uses the created accounts
+    pub async fn new_canonical(params: &BankPoolParams, fee_tier: u16) -> Result<Self, InstructionError> {
+        let mut bank_pool = Self::new_uninitialized(params).await;
+        bank_pool.token_mints.sort();
+        let create_ix = bank_pool.create_pool_ix(params, fee_tier);
+        bank_pool.execute_transaction(create_ix, &[]).await?;
+
+        let pool = find_pool_address(&pool::id(), &bank_pool.token_mints, fee_tier).0;
+        let (authority, nonce) = Pubkey::find_program_address(&[&pool.to_bytes()[..32]], &pool::id());
+        bank_pool.pool = pool;
+        bank_pool.authority = authority;
+        bank_pool.nonce = nonce;
+        bank_pool.lp_mint = find_lp_mint_address(&pool::id(), &pool).0;
+        bank_pool.token_accounts =
+            create_array(|i| find_vault_address(&pool::id(), &pool, &bank_pool.token_mints[i]).0);
+        bank_pool.governance_fee_account = find_governance_fee_address(&pool::id(), &pool).0;
+        Ok(bank_pool)
+    }
+
+    pub fn create_pool_ix(&self, params: &BankPoolParams, fee_tier: u16) -> Instruction {
+        create_pool_ix::<TOKEN_COUNT>(
+            &pool::id(),
+            &self.token_mints,
+            fee_tier,
+            &self.governance_keypair.pubkey(),
+            &self.context.payer.pubkey(),
+            params.amp_factor,
+            params.lp_fee,
+            params.governance_fee,
+        )
+        .unwrap()
+    }
+
+    pub fn init_ix(&self, params: &BankPoolParams) -> Instruction {
+        create_init_ix::<TOKEN_COUNT>(
+            &pool::id(),
+            &self.pool,
+            &self.lp_mint,
+            &self.token_mints,
+            &self.token_accounts,
+            &self.governance_keypair.pubkey(),
+            &self.governance_fee_account,
+            self.nonce,
+            params.amp_factor,
+            params.lp_fee,
+            params.governance_fee,
+        )
+        .unwrap()
+    }
+
+    /// Initializes the pool of `new_uninitialized` with the weighted product invariant (`params.amp_factor` is unused)
+    pub fn init_weighted_ix(&self, params: &BankPoolParams, token_weights: [u32; TOKEN_COUNT]) -> Instruction {
+        create_init_weighted_ix::<TOKEN_COUNT>(
+            &pool::id(),
+            &self.pool,
+            &self.lp_mint,
+            &self.token_mints,
+            &self.token_accounts,
+            &self.governance_keypair.pubkey(),
+            &self.governance_fee_account,
+            self.nonce,
+            token_weights,
+            params.lp_fee,
+            params.governance_fee,
+        )
+        .unwrap()
+    }
+
+    /// Initializes the pool of `new_uninitialized` with the fixed rate invariant (`params.amp_factor` is unused)
+    pub fn init_fixed_rate_ix(&self, params: &BankPoolParams) -> Instruction {
+        create_init_fixed_rate_ix::<TOKEN_COUNT>(
+            &pool::id(),
+            &self.pool,
+            &self.lp_mint,
+            &self.token_mints,
+            &self.token_accounts,
+            &self.governance_keypair.pubkey(),
+            &self.governance_fee_account,
+            self.nonce,
+            params.lp_fee,
+            params.governance_fee,
+        )
+        .unwrap()
+    }
+
+    /// Initializes the pool of `new_uninitialized` with the hybrid invariant, valuing the token at `volatile_index` at
+    /// `initial_rate`
+    pub fn init_hybrid_ix(&self, params: &BankPoolParams, volatile_index: u8, initial_rate: u64) -> Instruction {
+        create_init_hybrid_ix::<TOKEN_COUNT>(
+            &pool::id(),
+            &self.pool,
+            &self.lp_mint,
+            &self.token_mints,
+            &self.token_accounts,
+            &self.governance_keypair.pubkey(),
+            &self.governance_fee_account,
+            self.nonce,
+            params.amp_factor,
+            params.lp_fee,
+            params.governance_fee,
+            volatile_index,
+            initial_rate,
+        )
+        .unwrap()
+    }
+
+    /// Initializes the pool of `new_uninitialized` with the parameters of `template_pool`
+    pub fn clone_pool_config_ix(&self, template_pool: &Pubkey) -> Instruction {
+        create_clone_pool_config_ix::<TOKEN_COUNT>(
+            &pool::id(),
+            &self.pool,
+            &self.lp_mint,
+            &self.token_mints,
+            &self.token_accounts,
+            &self.governance_keypair.pubkey(),
+            &self.governance_fee_account,
+            self.nonce,
+            template_pool,
+        )
+        .unwrap()
+    }
+
+    pub async fn state(&mut self) -> PoolState<TOKEN_COUNT> {
+        let account = self.context.banks_client.get_account(self.pool).await.unwrap().unwrap();
+        PoolState::<TOKEN_COUNT>::unpack(&account.data).unwrap()
+    }
+
+    /// Cuts the pool's state account down to the first layout (see `PoolStateV0`), like those of the pools that the
+    /// program's first release created
+    pub async fn truncate_to_layout_v0(&mut self) {
+        let mut account = self.context.banks_client.get_account(self.pool).await.unwrap().unwrap();
+        account
+            .data
+            .truncate(solana_program::borsh::get_packed_len::<PoolStateV0<TOKEN_COUNT>>());
+        self.context.set_account(&self.pool, &account.into());
+    }
+
+    /// Overwrites the pool's state, e.g. to set up states that governance can't reach
+    pub async fn set_state(&mut self, state: &PoolState<TOKEN_COUNT>) {
+        let mut account = self.context.banks_client.get_account(self.pool).await.unwrap().unwrap();
+        let data = state.try_to_vec().unwrap();
+        account.data[..data.len()].copy_from_slice(&data);
+        self.context.set_account(&self.pool, &account.into());
+    }
+
+    pub async fn now(&mut self) -> UnixTimestamp {
+        self.context.banks_client.get_clock().await.unwrap().unix_timestamp
+    }
+
+    pub async fn epoch(&mut self) -> Epoch {
+        self.context.banks_client.get_clock().await.unwrap().epoch
+    }
+
+    /// Moves the timestamp that the program sees via `Clock::get()` forward to `unix_timestamp`
+    pub async fn set_now(&mut self, unix_timestamp: UnixTimestamp) {
+        let clock = self.context.banks_client.get_clock().await.unwrap();
+        assert!(unix_timestamp >= clock.unix_timestamp, "the clock can't be turned back");
+        let slot = clock.slot;
+        let clock = Clock {
+            unix_timestamp,
+            ..clock
+        };
+        self.context
+            .set_account(&sysvar::clock::id(), &create_account_shared_data_for_test(&clock));
+        //programs read sysvars from a cache that is only filled when a bank is created, so storing the account
+        // alone isn't enough. the next bank takes the later of its own estimate and the stored timestamp
+        self.context.warp_to_slot(slot + 1).unwrap();
+    }
+
+    /// Moves the bank `slots` slots forward, e.g. to age oracle readings
+    pub async fn advance_slots(&mut self, slots: Slot) {
+        let slot = self.context.banks_client.get_clock().await.unwrap().slot;
+        //one slot at a time, longer warps verify the accounts hash which accounts stored via set_account don't pass
+        for warp_slot in slot + 1..=slot + slots {
+            self.context.warp_to_slot(warp_slot).unwrap();
+        }
+    }
+
+    pub async fn advance_clock(&mut self, seconds: UnixTimestamp) {
+        let now = self.now().await;
+        self.set_now(now + seconds).await;
+    }
+
+    /// Creates a user whose token accounts hold `balances` (and an empty lp token account)
+    pub fn create_user(&mut self, balances: &[AmountT; TOKEN_COUNT]) -> BankUser {
+        let keypair = Keypair::new();
+        let lp = Pubkey::new_unique();
+        let mut lp_account = token_account(&self.lp_mint, &keypair.pubkey(), 0);
+        if self.lp_non_transferable {
+            to_non_transferable_account(&mut lp_account);
+        }
+        self.context.set_account(&lp, &lp_account.into());
+        let tokens: [_; TOKEN_COUNT] = create_array(|_| Pubkey::new_unique());
+        for i in 0..TOKEN_COUNT {
+            self.set_token_account(&tokens[i], &self.token_mints[i].clone(), &keypair.pubkey(), balances[i]);
+        }
+        BankUser { keypair, lp, tokens }
+    }
+
+    pub async fn balance(&mut self, token_account: &Pubkey) -> AmountT {
+        let account = self
+            .context
+            .banks_client
+            .get_account(*token_account)
+            .await
+            .unwrap()
+            .unwrap();
+        token_2022::unpack_account(&account.data).unwrap().amount
+    }
+
+    pub async fn balances(&mut self, token_accounts: &[Pubkey; TOKEN_COUNT]) -> [AmountT; TOKEN_COUNT] {
+        let mut balances = [0; TOKEN_COUNT];
+        for i in 0..TOKEN_COUNT {
+            balances[i] = self.balance(&token_accounts[i]).await;
+        }
+        balances
+    }
+
+    pub async fn pool_balances(&mut self) -> [AmountT; TOKEN_COUNT] {
+        let token_accounts = self.token_accounts;
+        self.balances(&token_accounts).await
+    }
+
+    /// Reads a mint or token account
+    pub async fn get_packed<T: Pack + IsInitialized>(&mut self, pubkey: &Pubkey) -> T {
+        let account = self.context.banks_client.get_account(*pubkey).await.unwrap().unwrap();
+        T::unpack(&account.data).unwrap()
+    }
+
+    /// Overwrites a mint or token account (e.g. to hand the pool malformed accounts)
+    pub fn set_packed<T: Pack>(&mut self, pubkey: &Pubkey, state: T) {
+        self.context.set_account(pubkey, &packed_account(state).into());
+    }
+
+    pub async fn lp_total_supply(&mut self) -> AmountT {
+        let account = self
+            .context
+            .banks_client
+            .get_account(self.lp_mint)
+            .await
+            .unwrap()
+            .unwrap();
+        token_2022::unpack_mint(&account.data).unwrap().supply
+    }
+
+    pub fn defi_ix(&self, defi_instruction: DeFiInstruction<TOKEN_COUNT>, user: &BankUser) -> Instruction {
+        let user_lp = if defi_instruction.is_swap() {
+            None
+        } else {
+            Some(&user.lp)
+        };
+        let is_uniform_remove = defi_instruction.is_uniform_remove();
+        let is_imbalanced_remove = defi_instruction.is_remove() && !is_uniform_remove;
+        let is_add = matches!(defi_instruction.executed(), DeFiInstruction::Add { .. });
+        let mut ix = create_defi_ix(
+            defi_instruction,
+            &pool::id(),
+            &self.pool,
+            &self.authority,
+            &self.token_accounts,
+            &self.lp_mint,
+            &self.governance_fee_account,
+            &user.keypair.pubkey(),
+            &user.tokens,
+            &spl_token::id(),
+            user_lp,
+        )
+        .unwrap();
+        if self.lp_non_transferable {
+            ix.accounts.push(AccountMeta::new_readonly(token_2022::id(), false));
+        }
+        if let (None, Some(oracle_keys)) = (user_lp, self.oracle_keys) {
+            for oracle_key in oracle_keys.iter() {
+                ix.accounts.push(AccountMeta::new_readonly(*oracle_key, false));
+            }
+        }
+        if !is_uniform_remove {
+            for rate_key in self.rate_keys.iter() {
+                ix.accounts.push(AccountMeta::new_readonly(*rate_key, false));
+            }
+            if self.restricts_callers {
+                ix.accounts
+                    .push(AccountMeta::new_readonly(sysvar::instructions::id(), false));
+            }
+            if let Some(gate_account) =
+                find_user_gate_account(&pool::id(), &self.pool, &self.user_gate, &user.keypair.pubkey())
+            {
+                ix.accounts.push(AccountMeta::new_readonly(gate_account, false));
+            }
+            if user_lp.is_some() {
+                if let Some(gate_account) =
+                    find_depositor_gate_account(&pool::id(), &self.pool, &self.depositor_gate, &user.keypair.pubkey())
+                {
+                    ix.accounts.push(AccountMeta::new_readonly(gate_account, false));
+                }
+            }
+        }
+        if let (Some(compliance_program), false) = (self.compliance_program, is_uniform_remove) {
+            let compliance_account = find_compliance_address(&compliance_program, &self.pool, &user.keypair.pubkey()).0;
+            ix.accounts.push(AccountMeta::new_readonly(compliance_program, false));
+            ix.accounts.push(AccountMeta::new_readonly(compliance_account, false));
+        }
+        if is_imbalanced_remove && self.withdrawal_cooldown {
+            let announcement = find_withdrawal_announcement_address(&pool::id(), &self.pool, &user.keypair.pubkey()).0;
+            ix.accounts.push(AccountMeta::new(announcement, false));
+        }
+        if is_add && self.launch_capped {
+            let launch_allocation = find_launch_allocation_address(&pool::id(), &self.pool, &user.keypair.pubkey()).0;
+            ix.accounts.push(AccountMeta::new(launch_allocation, false));
+        }
+        ix
+    }
+
+    /// The pool's accounts as a leg of a `RoutedSwap`
+    pub fn route_leg(&self) -> RouteLeg<TOKEN_COUNT> {
+        let mut swap_accounts: Vec<Pubkey> = self.lp_non_transferable.then(token_2022::id).into_iter().collect();
+        swap_accounts.extend(self.oracle_keys.iter().flatten());
+        swap_accounts.extend_from_slice(&self.rate_keys);
+        if self.restricts_callers {
+            swap_accounts.push(sysvar::instructions::id());
+        }
+        RouteLeg {
+            pool: self.pool,
+            authority: self.authority,
+            token_accounts: self.token_accounts,
+            lp_mint: self.lp_mint,
+            governance_fee_account: self.governance_fee_account,
+            swap_accounts,
+        }
+    }
+
+    pub async fn execute_defi_instruction(
+        &mut self,
+        defi_instruction: DeFiInstruction<TOKEN_COUNT>,
+        user: &BankUser,
+    ) -> Result<(), InstructionError> {
+        let ix = self.defi_ix(defi_instruction, user);
+        self.execute_transaction(ix, &[&user.keypair]).await
+    }
+
+    pub async fn execute_governance_instruction(
+        &mut self,
+        gov_instruction: GovernanceInstruction<TOKEN_COUNT>,
+        gov_fee_account: Option<&Pubkey>,
+    ) -> Result<(), InstructionError> {
+        let governance_keypair = Keypair::from_bytes(&self.governance_keypair.to_bytes()).unwrap();
+        self.execute_governance_instruction_as(gov_instruction, gov_fee_account, &governance_keypair)
+            .await
+    }
+
+    /// Like `execute_governance_instruction` but signed by `signer` instead of the pool's governance
+    pub async fn execute_governance_instruction_as(
+        &mut self,
+        gov_instruction: GovernanceInstruction<TOKEN_COUNT>,
+        gov_fee_account: Option<&Pubkey>,
+        signer: &Keypair,
+    ) -> Result<(), InstructionError> {
+        let ix = create_governance_ix(
+            gov_instruction,
+            &pool::id(),
+            &self.pool,
+            &signer.pubkey(),
+            gov_fee_account,
+        )
+        .unwrap();
+        self.execute_transaction(ix, &[signer]).await
+    }
+
+    /// Binds `oracle_key` with `limits` to the token at `token_index` through `SetTokenOracle`, moving the clock past
+    /// the enact delay
+    pub async fn set_token_oracle(
+        &mut self,
+        token_index: u8,
+        oracle_kind: OracleKind,
+        oracle_key: &Pubkey,
+        limits: OracleLimits,
+    ) -> Result<(), InstructionError> {
+        self.execute_governance_instruction(
+            GovernanceInstruction::SetTokenOracle {
+                token_index,
+                oracle_kind,
+                oracle_key: *oracle_key,
+                max_age: limits.max_age,
+                max_conf_bps: limits.max_conf_bps,
+            },
+            None,
+        )
+        .await?;
+        self.advance_clock(ENACT_DELAY).await;
+        self.execute_governance_instruction(GovernanceInstruction::EnactTokenOracle {}, None)
+            .await
+    }
+
+    /// Sets up pyth price accounts quoting `prices` (as `price * 10^expo`, published now), binds them without limits on
+    /// their age (which moves the clock past one enact delay per token) and has the pool check swaps against them
+    pub async fn set_oracle_guard(
+        &mut self,
+        prices: &[(i64, i32); TOKEN_COUNT],
+        max_deviation: DecT,
+    ) -> Result<[Pubkey; TOKEN_COUNT], InstructionError> {
+        let oracle_keys: [_; TOKEN_COUNT] = create_array(|_| Pubkey::new_unique());
+        let limits = OracleLimits {
+            max_age: Slot::MAX,
+            max_conf_bps: 0,
+        };
+        for i in 0..TOKEN_COUNT {
+            self.set_pyth_price(&oracle_keys[i], prices[i].0, prices[i].1).await;
+            self.set_token_oracle(i as u8, OracleKind::Pyth, &oracle_keys[i], limits)
+                .await?;
+        }
+        for i in 0..TOKEN_COUNT {
+            self.set_pyth_price(&oracle_keys[i], prices[i].0, prices[i].1).await;
+        }
+        self.execute_governance_instruction(GovernanceInstruction::SetOracleGuard { max_deviation }, None)
+            .await?;
+        self.oracle_keys = Some(oracle_keys);
+        Ok(oracle_keys)
+    }
+
+    /// Runs the permissionless `CheckPeg` against the pool's oracles
+    pub async fn check_peg(&mut self) -> Result<(), InstructionError> {
+        let oracle_keys = self.oracle_keys.unwrap_or([Pubkey::default(); TOKEN_COUNT]);
+        let ix = create_check_peg_ix(&pool::id(), &self.pool, &oracle_keys).unwrap();
+        self.execute_transaction(ix, &[]).await
+    }
+
+    /// Runs the permissionless `PublishVirtualPrice`, paid for by the test's payer
+    pub async fn publish_virtual_price(&mut self) -> Result<(), InstructionError> {
+        let ix = create_publish_virtual_price_ix(
+            &pool::id(),
+            &self.pool,
+            &self.lp_mint,
+            &self.token_accounts,
+            &self.context.payer.pubkey(),
+            &self.rate_keys,
+        )
+        .unwrap();
+        self.execute_transaction(ix, &[]).await
+    }
+
+    pub async fn virtual_price(&mut self) -> VirtualPriceState {
+        let key = find_virtual_price_address(&pool::id(), &self.pool).0;
+        let account = self.context.banks_client.get_account(key).await.unwrap().unwrap();
+        assert_eq!(account.owner, pool::id());
+        VirtualPriceState::deserialize(&mut account.data.as_slice()).unwrap()
+    }
+
+    /// Has the pool value the token at `token_index` at the rate of `rate_key` and passes it along from then on,
+    /// pausing the pool around the change if it holds balances (as governance has to)
+    pub async fn set_rate_source(
+        &mut self,
+        token_index: usize,
+        source: RateSource,
+        rate_key: Pubkey,
+    ) -> Result<(), InstructionError> {
+        let state = self.state().await;
+        let pause = !state.is_paused && state.previous_depth != 0;
+        if pause {
+            self.execute_governance_instruction(GovernanceInstruction::SetPaused { paused: true }, None)
+                .await?;
+        }
+        let result = self
+            .execute_governance_instruction(
+                GovernanceInstruction::SetRateSource {
+                    token_index: token_index as u8,
+                    source,
+                    rate_key,
+                },
+                None,
+            )
+            .await;
+        if pause {
+            self.execute_governance_instruction(GovernanceInstruction::SetPaused { paused: false }, None)
+                .await?;
+        }
+        result?;
+        self.rate_keys = self.state().await.rate_account_keys();
+        Ok(())
+    }
+
+    /// Values the token `token_index` at the virtual price of `base_pool` (see `set_base_pool`)
+    pub async fn set_base_pool_rate(
+        &mut self,
+        token_index: usize,
+        base_program_id: &Pubkey,
+        base_pool: &Pubkey,
+    ) -> Result<(), InstructionError> {
+        let ix = create_set_base_pool_rate_ix::<TOKEN_COUNT>(
+            &pool::id(),
+            &self.pool,
+            &self.governance_keypair.pubkey(),
+            token_index as u8,
+            base_program_id,
+            base_pool,
+        )
+        .unwrap();
+        let governance_keypair = Keypair::from_bytes(&self.governance_keypair.to_bytes()).unwrap();
+        self.execute_transaction(ix, &[&governance_keypair]).await?;
+        self.rate_keys = self.state().await.rate_account_keys();
+        Ok(())
+    }
+
+    /// Has the pool check who calls its defi instructions and passes the instructions sysvar along from then on
+    pub async fn set_caller_allowlist(
+        &mut self,
+        caller_mode: CallerMode,
+        callers: &[Pubkey],
+    ) -> Result<(), InstructionError> {
+        let mut allowed_callers = [Pubkey::default(); MAX_ALLOWED_CALLERS];
+        allowed_callers[..callers.len()].copy_from_slice(callers);
+        self.execute_governance_instruction(
+            GovernanceInstruction::SetCallerAllowlist {
+                caller_mode,
+                allowed_callers,
+            },
+            None,
+        )
+        .await?;
+        self.restricts_callers = caller_mode != CallerMode::Unrestricted;
+        Ok(())
+    }
+
+    /// Caps what adds may mint to each wallet until `end_ts`, adds then pass their user's launch allocation account
+    pub async fn set_launch_cap(
+        &mut self,
+        max_lp_per_wallet: AmountT,
+        end_ts: UnixTimestamp,
+    ) -> Result<(), InstructionError> {
+        self.execute_governance_instruction(
+            GovernanceInstruction::SetLaunchCap {
+                max_lp_per_wallet,
+                end_ts,
+            },
+            None,
+        )
+        .await?;
+        self.launch_capped = max_lp_per_wallet != 0;
+        Ok(())
+    }
+
+    /// Requires removes above `threshold` to be announced `cooldown` ahead, imbalanced removes then pass their user's
+    /// withdrawal announcement account
+    pub async fn set_withdrawal_cooldown(
+        &mut self,
+        threshold: AmountT,
+        cooldown: UnixTimestamp,
+    ) -> Result<(), InstructionError> {
+        self.execute_governance_instruction(
+            GovernanceInstruction::SetWithdrawalCooldown { threshold, cooldown },
+            None,
+        )
+        .await?;
+        self.withdrawal_cooldown = cooldown != 0;
+        Ok(())
+    }
+
+    pub async fn set_emergency_responders(
+        &mut self,
+        responders: &[Pubkey],
+        threshold: u8,
+    ) -> Result<(), InstructionError> {
+        let mut responder_keys = [Pubkey::default(); MAX_EMERGENCY_RESPONDERS];
+        responder_keys[..responders.len()].copy_from_slice(responders);
+        self.execute_governance_instruction(
+            GovernanceInstruction::SetEmergencyResponders {
+                responders: responder_keys,
+                threshold,
+            },
+            None,
+        )
+        .await
+    }
+
+    pub async fn set_price_band(&mut self, min_price: u64, max_price: u64) -> Result<(), InstructionError> {
+        self.execute_governance_instruction(
+            GovernanceInstruction::SetPriceBand {
+                price_band: PriceBand { min_price, max_price },
+            },
+            None,
+        )
+        .await
+    }
+
+    pub async fn set_token_participation(
+        &mut self,
+        token_index: u8,
+        participation: TokenParticipation,
+    ) -> Result<(), InstructionError> {
+        self.execute_governance_instruction(
+            GovernanceInstruction::SetTokenParticipation {
+                token_index,
+                participation,
+            },
+            None,
+        )
+        .await
+    }
+
+    /// Prepares the peg keeper and enacts it once `ENACT_DELAY` passed
+    pub async fn set_peg_keeper(
+        &mut self,
+        keeper_key: &Pubkey,
+        token_index: u8,
+        credit_limit: AmountT,
+    ) -> Result<(), InstructionError> {
+        self.execute_governance_instruction(
+            GovernanceInstruction::SetPegKeeper {
+                keeper_key: *keeper_key,
+                token_index,
+                credit_limit,
+            },
+            None,
+        )
+        .await?;
+        self.advance_clock(ENACT_DELAY).await;
+        self.execute_governance_instruction(GovernanceInstruction::EnactPegKeeper {}, None)
+            .await
+    }
+
+    /// Provides (`provide`) resp. absorbs `amount` of the peg keeper's credit as `keeper`
+    pub async fn use_peg_credit(
+        &mut self,
+        keeper: &Keypair,
+        amount: AmountT,
+        provide: bool,
+    ) -> Result<(), InstructionError> {
+        let create_ix = if provide {
+            create_provide_peg_credit_ix::<TOKEN_COUNT>
+        } else {
+            create_absorb_peg_credit_ix::<TOKEN_COUNT>
+        };
+        let ix = create_ix(
+            &pool::id(),
+            &self.pool,
+            &self.lp_mint,
+            &self.token_accounts,
+            &keeper.pubkey(),
+            &self.rate_keys,
+            amount,
+        )
+        .unwrap();
+        self.execute_transaction(ix, &[keeper]).await
+    }
+
+    /// Prepares the token's yield adapter and enacts it once `ENACT_DELAY` passed
+    pub async fn set_yield_adapter(
+        &mut self,
+        token_index: u8,
+        adapter_program: &Pubkey,
+        buffer: AmountT,
+    ) -> Result<(), InstructionError> {
+        self.execute_governance_instruction(
+            GovernanceInstruction::SetYieldAdapter {
+                token_index,
+                adapter_program: *adapter_program,
+                buffer,
+            },
+            None,
+        )
+        .await?;
+        self.advance_clock(ENACT_DELAY).await;
+        self.execute_governance_instruction(GovernanceInstruction::EnactYieldAdapter {}, None)
+            .await
+    }
+
+    /// Deploys the token's balance beyond its yield adapter's buffer to `adapter_program`, which gets
+    /// `adapter_accounts`
+    pub async fn deploy_yield(
+        &mut self,
+        token_index: u8,
+        adapter_program: &Pubkey,
+        adapter_accounts: &[AccountMeta],
+    ) -> Result<(), InstructionError> {
+        let ix = create_deploy_yield_ix(
+            &pool::id(),
+            &self.pool,
+            &self.authority,
+            &self.token_accounts,
+            &self.rate_keys,
+            token_index,
+            adapter_program,
+            adapter_accounts,
+        )
+        .unwrap();
+        self.execute_transaction(ix, &[]).await
+    }
+
+    /// Like `deploy_yield` but recalls `amount` of the token
+    pub async fn recall_yield(
+        &mut self,
+        token_index: u8,
+        amount: AmountT,
+        adapter_program: &Pubkey,
+        adapter_accounts: &[AccountMeta],
+    ) -> Result<(), InstructionError> {
+        let ix = create_recall_yield_ix(
+            &pool::id(),
+            &self.pool,
+            &self.authority,
+            &self.token_accounts,
+            &self.rate_keys,
+            token_index,
+            amount,
+            adapter_program,
+            adapter_accounts,
+        )
+        .unwrap();
+        self.execute_transaction(ix, &[]).await
+    }
+
+    pub async fn set_repeg_params(
+        &mut self,
+        threshold_bps: u16,
+        max_step_bps: u16,
+        min_interval: UnixTimestamp,
+    ) -> Result<(), InstructionError> {
+        self.execute_governance_instruction(
+            GovernanceInstruction::SetRepegParams {
+                threshold_bps,
+                max_step_bps,
+                min_interval,
+            },
+            None,
+        )
+        .await
+    }
+
+    /// Moves the hybrid pool's price for its volatile token towards the token's rate source
+    pub async fn repeg(&mut self) -> Result<(), InstructionError> {
+        let ix = create_repeg_ix(&pool::id(), &self.pool, &self.token_accounts, &self.rate_keys).unwrap();
+        self.execute_transaction(ix, &[]).await
+    }
+
+    pub async fn confirm_emergency(&mut self, responder: &Keypair) -> Result<(), InstructionError> {
+        let ix = create_confirm_emergency_ix::<TOKEN_COUNT>(&pool::id(), &self.pool, &responder.pubkey()).unwrap();
+        self.execute_transaction(ix, &[responder]).await
+    }
+
+    /// Gates who may use the pool, defi instructions then pass their user's gate account
+    pub async fn set_user_gate(&mut self, user_gate: UserGate) -> Result<(), InstructionError> {
+        self.execute_governance_instruction(GovernanceInstruction::SetUserGate { user_gate }, None)
+            .await?;
+        self.user_gate = user_gate;
+        Ok(())
+    }
+
+    /// Sets the pool's compliance program (`None` removes it), defi instructions then pass it and their user's
+    /// compliance account
+    pub async fn set_compliance_program(&mut self, compliance_program: Option<Pubkey>) -> Result<(), InstructionError> {
+        self.execute_governance_instruction(
+            GovernanceInstruction::SetComplianceProgram {
+                compliance_program: compliance_program.unwrap_or_default(),
+            },
+            None,
+        )
+        .await?;
+        self.compliance_program = compliance_program;
+        Ok(())
+    }
+
+    pub async fn set_trading_schedule(
+        &mut self,
+        trading_hours: TradingHours,
+        maintenance_windows: &[MaintenanceWindow],
+    ) -> Result<(), InstructionError> {
+        let mut windows = [MaintenanceWindow::default(); MAX_MAINTENANCE_WINDOWS];
+        windows[..maintenance_windows.len()].copy_from_slice(maintenance_windows);
+        self.execute_governance_instruction(
+            GovernanceInstruction::SetTradingSchedule {
+                trading_hours,
+                maintenance_windows: windows,
+            },
+            None,
+        )
+        .await
+    }
+
+    /// Allows resp. disallows `user` while the pool's user gate is an allowlist, the governance pays the rent
+    pub async fn set_user_allowed(&mut self, user: &Pubkey, allowed: bool) -> Result<(), InstructionError> {
+        let ix = create_set_user_allowed_ix::<TOKEN_COUNT>(
+            &pool::id(),
+            &self.pool,
+            &self.governance_keypair.pubkey(),
+            user,
+            allowed,
+            &self.context.payer.pubkey(),
+        )
+        .unwrap();
+        let governance_keypair = Keypair::from_bytes(&self.governance_keypair.to_bytes()).unwrap();
+        self.execute_transaction(ix, &[&governance_keypair]).await
+    }
+
+    /// Gates who may add and remove other than uniformly, these then pass their user's depositor gate account
+    pub async fn set_depositor_gate(&mut self, depositor_gate: UserGate) -> Result<(), InstructionError> {
+        self.execute_governance_instruction(GovernanceInstruction::SetDepositorGate { depositor_gate }, None)
+            .await?;
+        self.depositor_gate = depositor_gate;
+        Ok(())
+    }
+
+    /// Like `set_user_allowed` for the pool's depositor allowlist
+    pub async fn set_depositor_allowed(&mut self, depositor: &Pubkey, allowed: bool) -> Result<(), InstructionError> {
+        let ix = create_set_depositor_allowed_ix::<TOKEN_COUNT>(
+            &pool::id(),
+            &self.pool,
+            &self.governance_keypair.pubkey(),
+            depositor,
+            allowed,
+            &self.context.payer.pubkey(),
+        )
+        .unwrap();
+        let governance_keypair = Keypair::from_bytes(&self.governance_keypair.to_bytes()).unwrap();
+        self.execute_transaction(ix, &[&governance_keypair]).await
+    }
+
+    /// Creates or overwrites an spl stake pool of the token at `token_index` that is up to date in the current epoch
+    pub async fn set_stake_pool(&mut self, stake_pool: &Pubkey, token_index: usize, total_lamports: u64, supply: u64) {
+        let epoch = self.epoch().await;
+        let account = stake_pool_account(&self.token_mints[token_index], total_lamports, supply, epoch);
+        self.context.set_account(stake_pool, &account.into());
+    }
+
+    /// Creates or overwrites a switchboard aggregator whose latest result is `mantissa * 10^-scale`
+    pub fn set_switchboard_result(&mut self, aggregator: &Pubkey, mantissa: i128, scale: u32) {
+        self.context
+            .set_account(aggregator, &switchboard_aggregator_account(mantissa, scale).into());
+    }
+
+    /// Creates or overwrites a trading pyth price account quoting `price * 10^expo`, published now
+    pub async fn set_pyth_price(&mut self, oracle: &Pubkey, price: i64, expo: i32) {
+        let clock = self.context.banks_client.get_clock().await.unwrap();
+        let account = pyth_price_account(price, expo, PYTH_STATUS_TRADING, clock.unix_timestamp, clock.slot);
+        self.context.set_account(oracle, &account.into());
+    }
+
+    /// Creates or overwrites a pool of `base_program_id` whose lp token is the pool's token `token_index` and its
+    /// virtual price account, publishing `price` (see `VirtualPriceState::price`) now
+    pub async fn set_base_pool(
+        &mut self,
+        base_program_id: &Pubkey,
+        base_pool: &Pubkey,
+        token_index: usize,
+        price: u64,
+    ) {
+        let mut base_state = self.state().await;
+        base_state.lp_mint_key = self.token_mints[token_index];
+        let clock = self.context.banks_client.get_clock().await.unwrap();
+        let virtual_price = VirtualPriceState {
+            pool: *base_pool,
+            price,
+            conf: 0,
+            expo: VIRTUAL_PRICE_EXPO,
+            publish_time: clock.unix_timestamp,
+            publish_slot: clock.slot,
+            is_paused: false,
+            lp_supply: 1,
+            depth: 1,
+        };
+        let virtual_price_key = find_virtual_price_address(base_program_id, base_pool).0;
+        for (key, data) in vec![
+            (*base_pool, base_state.try_to_vec().unwrap()),
+            (virtual_price_key, virtual_price.try_to_vec().unwrap()),
+        ] {
+            let account = Account {
+                lamports: Rent::default().minimum_balance(data.len()),
+                data,
+                owner: *base_program_id,
+                ..Account::default()
+            };
+            self.context.set_account(&key, &account.into());
+        }
+    }
+
+    /// Creates or overwrites the exchange rate account of a wrapper whose shares are the pool's token `token_index`,
+    /// with `underlying_amount` atomic units of an underlying token of `underlying_decimals` backing `share_supply`
+    /// shares, published now
+    pub async fn set_wrapper_rate(
+        &mut self,
+        wrapper_rate: &Pubkey,
+        token_index: usize,
+        share_supply: u64,
+        underlying_amount: u64,
+        underlying_decimals: u8,
+    ) {
+        let share_mint = self.token_mints[token_index];
+        let share_decimals = self.get_packed::<MintState>(&share_mint).await.decimals;
+        let clock = self.context.banks_client.get_clock().await.unwrap();
+        let data = WrapperRate {
+            share_mint,
+            share_decimals,
+            underlying_decimals,
+            share_supply,
+            underlying_amount,
+            publish_slot: clock.slot,
+        }
+        .try_to_vec()
+        .unwrap();
+        let account = Account {
+            lamports: Rent::default().minimum_balance(data.len()),
+            data,
+            owner: Pubkey::new_unique(),
+            ..Account::default()
+        };
+        self.context.set_account(wrapper_rate, &account.into());
+    }
+
+    pub async fn execute_transaction(&mut self, ix: Instruction, signers: &[&Keypair]) -> Result<(), InstructionError> {
+        self.execute_instructions(&[ix], signers).await
+    }
+
+    /// Executes all instructions within a single (atomic) transaction
+    pub async fn execute_instructions(
+        &mut self,
+        ixs: &[Instruction],
+        signers: &[&Keypair],
+    ) -> Result<(), InstructionError> {
+        //identical transactions (e.g. retrying an enact) need a new blockhash or they are rejected as duplicates
+        let blockhash = self.new_blockhash().await;
+        let mut all_signers = vec![&self.context.payer];
+        all_signers.extend_from_slice(signers);
+        let transaction =
+            Transaction::new_signed_with_payer(ixs, Some(&self.context.payer.pubkey()), &all_signers, blockhash);
+
+        match self.context.banks_client.process_transaction(transaction).await {
+            Ok(()) => Ok(()),
+            Err(TransportError::TransactionError(TransactionError::InstructionError(_, err))) => Err(err),
+            Err(err) => panic!("transaction failed unexpectedly: {:?}", err),
+        }
+    }
+
+    async fn new_blockhash(&mut self) -> Hash {
+        loop {
+            let blockhash = self.context.banks_client.get_recent_blockhash().await.unwrap();
+            if blockhash != self.last_blockhash {
+                self.last_blockhash = blockhash;
+                return blockhash;
+            }
+        }
+    }
+
+    pub fn set_token_account(&mut self, pubkey: &Pubkey, mint: &Pubkey, owner: &Pubkey, amount: AmountT) {
+        self.context
+            .set_account(pubkey, &token_account(mint, owner, amount).into());
+    }
+
+    pub fn set_mint_account(&mut self, pubkey: &Pubkey, decimals: u8, mint_authority: &Pubkey) {
+        self.context
+            .set_account(pubkey, &mint_account(decimals, mint_authority).into());
+    }
+
+    /// Moves the lp mint and the governance fee account of `new_uninitialized` to Token-2022, the lp mint gets the
+    /// NonTransferable extension if `non_transferable`
+    pub async fn use_token_2022_lp_mint(&mut self, non_transferable: bool) {
+        let lp_mint = self.lp_mint;
+        let mut lp_mint_account = self.context.banks_client.get_account(lp_mint).await.unwrap().unwrap();
+        if non_transferable {
+            //padded to the length of a token account, followed by the account type and the extension
+            lp_mint_account.data.resize(TokenState::LEN, 0);
+            lp_mint_account.data.push(1);
+            lp_mint_account.data.extend_from_slice(&9u16.to_le_bytes());
+            lp_mint_account.data.extend_from_slice(&0u16.to_le_bytes());
+            lp_mint_account.lamports = Rent::default().minimum_balance(lp_mint_account.data.len());
+        }
+        lp_mint_account.owner = token_2022::id();
+        self.context.set_account(&lp_mint, &lp_mint_account.into());
+
+        let governance_fee_account = self.governance_fee_account;
+        let mut fee_account = self
+            .context
+            .banks_client
+            .get_account(governance_fee_account)
+            .await
+            .unwrap()
+            .unwrap();
+        if non_transferable {
+            to_non_transferable_account(&mut fee_account);
+        } else {
+            fee_account.owner = token_2022::id();
+        }
+        self.context.set_account(&governance_fee_account, &fee_account.into());
+        self.lp_non_transferable = non_transferable;
+    }
+
+    /// Sets up an uninitialized pool of `program_id` (which has to run the pool's processor) with empty token
+    /// accounts of the pool's tokens
+    pub fn create_export_target(&mut self, program_id: &Pubkey) -> BankExportTarget {
+        let pool = Pubkey::new_unique();
+        let pool_len = solana_program::borsh::get_packed_len::<PoolState<TOKEN_COUNT>>();
+        self.context.set_account(
+            &pool,
+            &Account {
+                lamports: Rent::default().minimum_balance(pool_len),
+                data: vec![0; pool_len],
+                owner: *program_id,
+                ..Account::default()
+            }
+            .into(),
+        );
+        let (authority, nonce) = Pubkey::find_program_address(&[&pool.to_bytes()[..32]], program_id);
+        let token_accounts: [_; TOKEN_COUNT] = create_array(|_| Pubkey::new_unique());
+        for i in 0..TOKEN_COUNT {
+            self.set_token_account(&token_accounts[i], &self.token_mints[i].clone(), &authority, 0);
+        }
+        BankExportTarget {
+            program_id: *program_id,
+            pool,
+            authority,
+            nonce,
+            token_accounts,
+        }
+    }
+
+    pub fn export_state_ix(&self, target: &BankExportTarget) -> Instruction {
+        create_export_state_ix(
+            &pool::id(),
+            &self.pool,
+            &self.governance_keypair.pubkey(),
+            &self.authority,
+            &self.token_accounts,
+            &self.lp_mint,
+            &target.program_id,
+            &target.pool,
+            &target.token_accounts,
+            &self.context.payer.pubkey(),
+        )
+        .unwrap()
+    }
+
+    pub fn import_state_ix(&self, target: &BankExportTarget) -> Instruction {
+        create_import_state_ix(
+            &target.program_id,
+            &target.pool,
+            &self.lp_mint,
+            &self.token_mints,
+            &target.token_accounts,
+            &self.governance_keypair.pubkey(),
+            &self.governance_fee_account,
+            target.nonce,
+            &pool::id(),
+            &self.pool,
+        )
+        .unwrap()
+    }
+
+    /// Executes `RotateVaults` and switches over to the pool's new token accounts
+    pub async fn rotate_vaults(&mut self) -> Result<(), InstructionError> {
+        let ix = create_rotate_vaults_ix(
+            &pool::id(),
+            &self.pool,
+            &self.governance_keypair.pubkey(),
+            &self.authority,
+            &self.token_accounts,
+            &self.token_mints,
+            &self.context.payer.pubkey(),
+        )
+        .unwrap();
+        let governance_keypair = Keypair::from_bytes(&self.governance_keypair.to_bytes()).unwrap();
+        self.execute_transaction(ix, &[&governance_keypair]).await?;
+        self.token_accounts = self.state().await.token_keys;
+        Ok(())
+    }
+
+    pub async fn lock_lp(
+        &mut self,
+        user: &BankUser,
+        amount: AmountT,
+        duration: UnixTimestamp,
+    ) -> Result<(), InstructionError> {
+        let ix = create_lock_lp_ix::<TOKEN_COUNT>(
+            &pool::id(),
+            &self.pool,
+            &self.authority,
+            &self.lp_mint,
+            &user.keypair.pubkey(),
+            &user.lp,
+            &self.context.payer.pubkey(),
+            amount,
+            duration,
+        )
+        .unwrap();
+        self.execute_transaction(ix, &[&user.keypair]).await
+    }
+
+    pub async fn claim_lock_rewards(&mut self, user: &BankUser) -> Result<(), InstructionError> {
+        let ix = create_claim_lock_rewards_ix::<TOKEN_COUNT>(
+            &pool::id(),
+            &self.pool,
+            &self.authority,
+            &self.lp_mint,
+            &user.keypair.pubkey(),
+            &user.lp,
+        )
+        .unwrap();
+        self.execute_transaction(ix, &[&user.keypair]).await
+    }
+
+    pub async fn unlock_lp(&mut self, user: &BankUser) -> Result<(), InstructionError> {
+        let ix = create_unlock_lp_ix::<TOKEN_COUNT>(
+            &pool::id(),
+            &self.pool,
+            &self.authority,
+            &self.lp_mint,
+            &user.keypair.pubkey(),
+            &user.lp,
+        )
+        .unwrap();
+        self.execute_transaction(ix, &[&user.keypair]).await
+    }
+
+    /// Creates a token mint and a token account of it that holds `amount` for `owner`, e.g. to fund a gauge reward with
+    pub fn create_reward_token(&mut self, owner: &Pubkey, amount: AmountT) -> (Pubkey, Pubkey) {
+        let mint = Pubkey::new_unique();
+        self.set_mint_account(&mint, 6, &Pubkey::new_unique());
+        let owner_account = Pubkey::new_unique();
+        self.set_token_account(&owner_account, &mint, owner, amount);
+        (mint, owner_account)
+    }
+
+    /// Adds `reward_mint` as a reward of the gauge that `funder` funds, the test's payer pays for the new accounts
+    pub async fn add_gauge_reward(&mut self, reward_mint: &Pubkey, funder: &Pubkey) -> Result<(), InstructionError> {
+        let governance_keypair = Keypair::from_bytes(&self.governance_keypair.to_bytes()).unwrap();
+        let ix = create_add_gauge_reward_ix::<TOKEN_COUNT>(
+            &pool::id(),
+            &self.pool,
+            &governance_keypair.pubkey(),
+            &self.authority,
+            &self.lp_mint,
+            reward_mint,
+            funder,
+            &self.context.payer.pubkey(),
+        )
+        .unwrap();
+        self.execute_transaction(ix, &[&governance_keypair]).await
+    }
+
+    pub async fn retire_gauge_reward(
+        &mut self,
+        reward_mint: &Pubkey,
+        funder_reward_account: &Pubkey,
+    ) -> Result<(), InstructionError> {
+        let governance_keypair = Keypair::from_bytes(&self.governance_keypair.to_bytes()).unwrap();
+        let ix = create_retire_gauge_reward_ix::<TOKEN_COUNT>(
+            &pool::id(),
+            &self.pool,
+            &governance_keypair.pubkey(),
+            &self.authority,
+            reward_mint,
+            funder_reward_account,
+        )
+        .unwrap();
+        self.execute_transaction(ix, &[&governance_keypair]).await
+    }
+
+    pub async fn fund_gauge(
+        &mut self,
+        funder: &Keypair,
+        reward_mint: &Pubkey,
+        funder_reward_account: &Pubkey,
+        amount: AmountT,
+        duration: UnixTimestamp,
+    ) -> Result<(), InstructionError> {
+        let ix = create_fund_gauge_ix::<TOKEN_COUNT>(
+            &pool::id(),
+            &self.pool,
+            &funder.pubkey(),
+            reward_mint,
+            funder_reward_account,
+            amount,
+            duration,
+        )
+        .unwrap();
+        self.execute_transaction(ix, &[funder]).await
+    }
+
+    /// Prepares the gauge's voting escrow program (`None` removes it) and enacts it once `ENACT_DELAY` passed, stakes
+    /// and boost updates then pass it and their owner's ve account
+    pub async fn set_gauge_ve_program(&mut self, ve_program: Option<Pubkey>) -> Result<(), InstructionError> {
+        self.execute_governance_instruction(
+            GovernanceInstruction::SetGaugeVeProgram {
+                ve_program: ve_program.unwrap_or_default(),
+            },
+            None,
+        )
+        .await?;
+        self.advance_clock(ENACT_DELAY).await;
+        self.execute_governance_instruction(GovernanceInstruction::EnactGaugeVeProgram {}, None)
+            .await?;
+        self.gauge_ve_program = ve_program;
+        Ok(())
+    }
+
+    fn push_ve_boost_accounts(&self, ix: &mut Instruction, owner: &Pubkey) {
+        if let Some(ve_program) = self.gauge_ve_program {
+            add_ve_boost_accounts(ix, &self.pool, owner, &ve_program);
+        }
+    }
+
+    pub async fn stake_lp(&mut self, user: &BankUser, amount: AmountT) -> Result<(), InstructionError> {
+        let mut ix = create_stake_lp_ix::<TOKEN_COUNT>(
+            &pool::id(),
+            &self.pool,
+            &self.lp_mint,
+            &user.keypair.pubkey(),
+            &user.lp,
+            &self.context.payer.pubkey(),
+            amount,
+        )
+        .unwrap();
+        self.push_ve_boost_accounts(&mut ix, &user.keypair.pubkey());
+        self.execute_transaction(ix, &[&user.keypair]).await
+    }
+
+    pub async fn unstake_lp(&mut self, user: &BankUser, amount: AmountT) -> Result<(), InstructionError> {
+        let ix = create_unstake_lp_ix::<TOKEN_COUNT>(
+            &pool::id(),
+            &self.pool,
+            &self.authority,
+            &self.lp_mint,
+            &user.keypair.pubkey(),
+            &user.lp,
+            amount,
+        )
+        .unwrap();
+        self.execute_transaction(ix, &[&user.keypair]).await
+    }
+
+    pub async fn claim_gauge_rewards(
+        &mut self,
+        user: &BankUser,
+        reward_mint: &Pubkey,
+        reward_account: &Pubkey,
+    ) -> Result<(), InstructionError> {
+        let ix = create_claim_gauge_rewards_ix::<TOKEN_COUNT>(
+            &pool::id(),
+            &self.pool,
+            &self.authority,
+            reward_mint,
+            &user.keypair.pubkey(),
+            reward_account,
+        )
+        .unwrap();
+        self.execute_transaction(ix, &[&user.keypair]).await
+    }
+
+    /// Updates the boost of `owner`'s stake, signed by the test's payer only
+    pub async fn update_gauge_boost(&mut self, owner: &Pubkey) -> Result<(), InstructionError> {
+        let ix =
+            create_update_gauge_boost_ix::<TOKEN_COUNT>(&pool::id(), &self.pool, owner, self.gauge_ve_program.as_ref())
+                .unwrap();
+        self.execute_transaction(ix, &[]).await
+    }
+
+    /// Starts rebate campaign `campaign_id` with `budget` of the governance's tokens of `governance_rebate_account`
+    pub async fn start_rebate_campaign(
+        &mut self,
+        campaign_id: u64,
+        rebate_mint: &Pubkey,
+        governance_rebate_account: &Pubkey,
+        budget: AmountT,
+        start_ts: UnixTimestamp,
+        end_ts: UnixTimestamp,
+    ) -> Result<(), InstructionError> {
+        let governance_keypair = Keypair::from_bytes(&self.governance_keypair.to_bytes()).unwrap();
+        let ix = create_start_rebate_campaign_ix::<TOKEN_COUNT>(
+            &pool::id(),
+            &self.pool,
+            &governance_keypair.pubkey(),
+            &self.authority,
+            rebate_mint,
+            governance_rebate_account,
+            &self.context.payer.pubkey(),
+            campaign_id,
+            budget,
+            start_ts,
+            end_ts,
+        )
+        .unwrap();
+        self.execute_transaction(ix, &[&governance_keypair]).await
+    }
+
+    pub async fn reclaim_rebate_budget(
+        &mut self,
+        campaign_id: u64,
+        governance_rebate_account: &Pubkey,
+    ) -> Result<(), InstructionError> {
+        let governance_keypair = Keypair::from_bytes(&self.governance_keypair.to_bytes()).unwrap();
+        let ix = create_reclaim_rebate_budget_ix::<TOKEN_COUNT>(
+            &pool::id(),
+            &self.pool,
+            &governance_keypair.pubkey(),
+            &self.authority,
+            campaign_id,
+            governance_rebate_account,
+        )
+        .unwrap();
+        self.execute_transaction(ix, &[&governance_keypair]).await
+    }
+
+    pub async fn join_rebate_campaign(&mut self, campaign_id: u64, user: &BankUser) -> Result<(), InstructionError> {
+        let ix = create_join_rebate_campaign_ix::<TOKEN_COUNT>(
+            &pool::id(),
+            &self.pool,
+            &find_rebate_campaign_address(&pool::id(), &self.pool, campaign_id).0,
+            &user.keypair.pubkey(),
+            &self.context.payer.pubkey(),
+        )
+        .unwrap();
+        self.execute_transaction(ix, &[]).await
+    }
+
+    /// Executes a swap of `user` that records its fees with rebate campaign `campaign_id`
+    pub async fn execute_rebate_swap(
+        &mut self,
+        defi_instruction: DeFiInstruction<TOKEN_COUNT>,
+        user: &BankUser,
+        campaign_id: u64,
+    ) -> Result<(), InstructionError> {
+        let mut ix = self.defi_ix(defi_instruction, user);
+        let campaign = find_rebate_campaign_address(&pool::id(), &self.pool, campaign_id).0;
+        record_swap_rebate(&mut ix, &campaign, &user.keypair.pubkey());
+        self.execute_transaction(ix, &[&user.keypair]).await
+    }
+
+    pub async fn claim_rebate(
+        &mut self,
+        campaign_id: u64,
+        user: &BankUser,
+        rebate_account: &Pubkey,
+    ) -> Result<(), InstructionError> {
+        let ix = create_claim_rebate_ix::<TOKEN_COUNT>(
+            &pool::id(),
+            &self.pool,
+            &self.authority,
+            &find_rebate_campaign_address(&pool::id(), &self.pool, campaign_id).0,
+            &user.keypair.pubkey(),
+            rebate_account,
+        )
+        .unwrap();
+        self.execute_transaction(ix, &[&user.keypair]).await
+    }
+
+    pub async fn checkpoint_fees(&mut self, user: &BankUser) -> Result<(), InstructionError> {
+        let ix = create_checkpoint_fees_ix::<TOKEN_COUNT>(
+            &pool::id(),
+            &self.pool,
+            &user.lp,
+            &user.keypair.pubkey(),
+            &self.context.payer.pubkey(),
+        )
+        .unwrap();
+        self.execute_transaction(ix, &[&user.keypair]).await
+    }
+
+    pub async fn create_launch_allocation(&mut self, user: &BankUser) -> Result<(), InstructionError> {
+        let ix = create_launch_allocation_ix::<TOKEN_COUNT>(
+            &pool::id(),
+            &self.pool,
+            &user.keypair.pubkey(),
+            &self.context.payer.pubkey(),
+        )
+        .unwrap();
+        self.execute_transaction(ix, &[]).await
+    }
+
+    /// The launch allocation of `owner`, if there is one
+    pub async fn launch_allocation(&mut self, owner: &Pubkey) -> Option<LaunchAllocation> {
+        let key = find_launch_allocation_address(&pool::id(), &self.pool, owner).0;
+        let account = self.context.banks_client.get_account(key).await.unwrap()?;
+        Some(LaunchAllocation::deserialize(&mut account.data.as_slice()).unwrap())
+    }
+
+    /// Authorizes `session_key` to sign `user`'s swaps, the user (which gets some lamports for it) pays for the session
+    /// account
+    pub async fn authorize_session(
+        &mut self,
+        user: &BankUser,
+        session_key: &Pubkey,
+        expiry_ts: UnixTimestamp,
+        spend_limits: [AmountT; TOKEN_COUNT],
+    ) -> Result<(), InstructionError> {
+        let user_key = user.keypair.pubkey();
+        let user_account = Account::new(LAMPORTS_PER_SOL, 0, &system_program::id());
+        self.context.set_account(&user_key, &user_account.into());
+        let ix = create_authorize_session_ix(
+            &pool::id(),
+            &self.pool,
+            &user_key,
+            &user.tokens,
+            session_key,
+            expiry_ts,
+            spend_limits,
+        )
+        .unwrap();
+        self.execute_transaction(ix, &[&user.keypair]).await
+    }
+
+    pub async fn revoke_session(&mut self, user: &BankUser) -> Result<(), InstructionError> {
+        let ix = create_revoke_session_ix(&pool::id(), &self.pool, &user.keypair.pubkey(), &user.tokens).unwrap();
+        self.execute_transaction(ix, &[&user.keypair]).await
+    }
+
+    /// Announces that `user` (which gets some lamports for it) will remove up to `lp_amount`, 0 closes the announcement
+    pub async fn announce_withdrawal(&mut self, user: &BankUser, lp_amount: AmountT) -> Result<(), InstructionError> {
+        let user_key = user.keypair.pubkey();
+        if self.context.banks_client.get_balance(user_key).await.unwrap() == 0 {
+            let user_account = Account::new(LAMPORTS_PER_SOL, 0, &system_program::id());
+            self.context.set_account(&user_key, &user_account.into());
+        }
+        let ix = create_announce_withdrawal_ix::<TOKEN_COUNT>(&pool::id(), &self.pool, &user_key, lp_amount).unwrap();
+        self.execute_transaction(ix, &[&user.keypair]).await
+    }
+
+    /// The withdrawal announcement of `user`, if there is one
+    pub async fn withdrawal_announcement(&mut self, user: &Pubkey) -> Option<WithdrawalAnnouncement> {
+        let key = find_withdrawal_announcement_address(&pool::id(), &self.pool, user).0;
+        let account = self.context.banks_client.get_account(key).await.unwrap()?;
+        Some(WithdrawalAnnouncement::deserialize(&mut account.data.as_slice()).unwrap())
+    }
+
+    /// The session of `user`, if there is one
+    pub async fn session(&mut self, user: &Pubkey) -> Option<Session<TOKEN_COUNT>> {
+        let key = find_session_address(&pool::id(), &self.pool, user).0;
+        let account = self.context.banks_client.get_account(key).await.unwrap()?;
+        Some(Session::deserialize(&mut account.data.as_slice()).unwrap())
+    }
+
+    /// Executes a swap of `user` signed by `session_key` in its place
+    pub async fn execute_session_swap(
+        &mut self,
+        defi_instruction: DeFiInstruction<TOKEN_COUNT>,
+        user: &BankUser,
+        session_key: &Keypair,
+    ) -> Result<(), InstructionError> {
+        let mut ix = self.defi_ix(defi_instruction, user);
+        sign_swap_with_session::<TOKEN_COUNT>(&mut ix, &self.pool, &user.keypair.pubkey(), &session_key.pubkey());
+        self.execute_transaction(ix, &[session_key]).await
+    }
+
+    /// Prepares `quoter_key` with `max_deviation` as the pool's rfq quoter and enacts it once `ENACT_DELAY` passed
+    pub async fn set_rfq_quoter(&mut self, quoter_key: &Pubkey, max_deviation: DecT) -> Result<(), InstructionError> {
+        self.execute_governance_instruction(
+            GovernanceInstruction::SetRfqQuoter {
+                quoter_key: *quoter_key,
+                max_deviation,
+            },
+            None,
+        )
+        .await?;
+        self.advance_clock(ENACT_DELAY).await;
+        self.execute_governance_instruction(GovernanceInstruction::EnactRfqQuoter {}, None)
+            .await
+    }
+
+    /// Executes `quote` for `user`, preceded by the verification of `quoter`'s signature of it
+    pub async fn execute_rfq_swap(
+        &mut self,
+        quote: RfqQuote,
+        quoter: &Keypair,
+        user: &BankUser,
+    ) -> Result<(), InstructionError> {
+        let signature: [u8; 64] = quoter.sign_message(&quote.message()).as_ref().try_into().unwrap();
+        let mut extra_accounts = self.rate_keys.clone();
+        extra_accounts.extend(find_user_gate_account(
+            &pool::id(),
+            &self.pool,
+            &self.user_gate,
+            &user.keypair.pubkey(),
+        ));
+        if let Some(compliance_program) = self.compliance_program {
+            extra_accounts.push(compliance_program);
+            extra_accounts.push(find_compliance_address(&compliance_program, &self.pool, &user.keypair.pubkey()).0);
+        }
+        let ixs = [
+            create_rfq_signature_ix(&quoter.pubkey(), &signature, &quote),
+            create_rfq_swap_ix(
+                &pool::id(),
+                &self.pool,
+                &self.authority,
+                &self.token_accounts,
+                &user.tokens[quote.input_token_index as usize],
+                &user.tokens[quote.output_token_index as usize],
+                quote,
+                &extra_accounts,
+            )
+            .unwrap(),
+        ];
+        self.execute_instructions(&ixs, &[&user.keypair]).await
+    }
+
+    /// Has the pool's governance burn `burn_amount` of the governance fee account's lp tokens
+    pub async fn burn_governance_fees(&mut self, burn_amount: AmountT) -> Result<(), InstructionError> {
+        let lp_token_program = if self.lp_non_transferable {
+            token_2022::id()
+        } else {
+            spl_token::id()
+        };
+        let governance_keypair = Keypair::from_bytes(&self.governance_keypair.to_bytes()).unwrap();
+        let ix = create_burn_governance_fees_ix::<TOKEN_COUNT>(
+            &pool::id(),
+            &self.pool,
+            &governance_keypair.pubkey(),
+            &self.governance_fee_account,
+            &self.lp_mint,
+            &lp_token_program,
+            burn_amount,
+        )
+        .unwrap();
+        self.execute_transaction(ix, &[&governance_keypair]).await
+    }
+
+    /// Runs the permissionless `SnapshotLpSupply`, paid for by the test's payer
+    pub async fn snapshot_lp_supply(&mut self) -> Result<(), InstructionError> {
+        let ix = create_snapshot_lp_supply_ix::<TOKEN_COUNT>(
+            &pool::id(),
+            &self.pool,
+            &self.lp_mint,
+            &self.context.payer.pubkey(),
+        )
+        .unwrap();
+        self.execute_transaction(ix, &[]).await
+    }
+
+    pub async fn lp_supply_snapshots(&mut self) -> LpSupplySnapshots {
+        let key = find_lp_supply_address(&pool::id(), &self.pool).0;
+        let account = self.context.banks_client.get_account(key).await.unwrap().unwrap();
+        assert_eq!(account.owner, pool::id());
+        LpSupplySnapshots::deserialize(&mut account.data.as_slice()).unwrap()
+    }
+
+    /// The pool as the client's `PoolSnapshot` decodes it, for testing what the client quotes from it
+    pub async fn snapshot(&mut self) -> PoolSnapshot {
+        let state = self.state().await;
+        let mut accounts = vec![];
+        for key in PoolSnapshot::account_keys(&self.pool, &state) {
+            accounts.push(self.context.banks_client.get_account(key).await.unwrap().unwrap());
+        }
+        let slot = self.context.banks_client.get_root_slot().await.unwrap();
+        PoolSnapshot::from_accounts(&self.pool, &state, slot, &accounts).unwrap()
+    }
+
+    /// The pool as the client's `PoolAmm`, updated with the bank's accounts
+    pub async fn amm(&mut self) -> PoolAmm {
+        let pool_account = self.context.banks_client.get_account(self.pool).await.unwrap().unwrap();
+        let mut amm = PoolAmm::from_keyed_account(&self.pool, &pool_account).unwrap();
+        let accounts = self.accounts(&amm.get_accounts_to_update()).await;
+        amm.update(&accounts).unwrap();
+        amm
+    }
+
+    pub async fn accounts(&mut self, keys: &[Pubkey]) -> HashMap<Pubkey, Account> {
+        let mut accounts = HashMap::new();
+        for key in keys {
+            let account = self.context.banks_client.get_account(*key).await.unwrap().unwrap();
+            accounts.insert(*key, account);
+        }
+        accounts
+    }
+
+    pub async fn fee_checkpoint(&mut self, lp_account: &Pubkey) -> FeeCheckpoint {
+        let key = find_fee_checkpoint_address(&pool::id(), &self.pool, lp_account).0;
+        let account = self.context.banks_client.get_account(key).await.unwrap().unwrap();
+        FeeCheckpoint::deserialize(&mut account.data.as_slice()).unwrap()
+    }
+
+    /// The pool's gauge, if it has one
+    pub async fn gauge(&mut self) -> Option<Gauge> {
+        let key = find_gauge_address(&pool::id(), &self.pool).0;
+        let account = self.context.banks_client.get_account(key).await.unwrap()?;
+        Some(Gauge::deserialize(&mut account.data.as_slice()).unwrap())
+    }
+
+    /// The gauge stake of `owner`, if there is one
+    pub async fn gauge_stake(&mut self, owner: &Pubkey) -> Option<GaugeStake> {
+        let key = find_gauge_stake_address(&pool::id(), &self.pool, owner).0;
+        let account = self.context.banks_client.get_account(key).await.unwrap()?;
+        Some(GaugeStake::deserialize(&mut account.data.as_slice()).unwrap())
+    }
+
+    /// The pool's rebate campaign `campaign_id`, if governance started it
+    pub async fn rebate_campaign(&mut self, campaign_id: u64) -> Option<RebateCampaign> {
+        let key = find_rebate_campaign_address(&pool::id(), &self.pool, campaign_id).0;
+        let account = self.context.banks_client.get_account(key).await.unwrap()?;
+        Some(RebateCampaign::deserialize(&mut account.data.as_slice()).unwrap())
+    }
+
+    /// The rebate of `owner` with rebate campaign `campaign_id`, if `owner` joined it and didn't claim it yet
+    pub async fn rebate(&mut self, campaign_id: u64, owner: &Pubkey) -> Option<Rebate> {
+        let campaign = find_rebate_campaign_address(&pool::id(), &self.pool, campaign_id).0;
+        let key = find_rebate_address(&pool::id(), &campaign, owner).0;
+        let account = self.context.banks_client.get_account(key).await.unwrap()?;
+        Some(Rebate::deserialize(&mut account.data.as_slice()).unwrap())
+    }
+
+    /// The lp token lock of `owner`, if there is one
+    pub async fn lp_lock(&mut self, owner: &Pubkey) -> Option<LpLock> {
+        let key = find_lock_address(&pool::id(), &self.pool, owner).0;
+        let account = self.context.banks_client.get_account(key).await.unwrap()?;
+        Some(LpLock::deserialize(&mut account.data.as_slice()).unwrap())
+    }
+}
+
+fn mint_account(decimals: u8, mint_authority: &Pubkey) -> Account {
+    packed_account(MintState {
+        mint_authority: COption::Some(*mint_authority),
+        supply: 0,
+        decimals,
+        is_initialized: true,
+        freeze_authority: COption::None,
+    })
+}
+
+//token accounts of a non-transferable mint need the ImmutableOwner and NonTransferableAccount extensions, which the
+// associated token account program adds when it creates them
+fn to_non_transferable_account(account: &mut Account) {
+    account.data.push(2);
+    for extension_type in [7u16, 13u16].iter() {
+        account.data.extend_from_slice(&extension_type.to_le_bytes());
+        account.data.extend_from_slice(&0u16.to_le_bytes());
+    }
+    account.lamports = Rent::default().minimum_balance(account.data.len());
+    account.owner = token_2022::id();
+}
+
+pub fn token_account(mint: &Pubkey, owner: &Pubkey, amount: AmountT) -> Account {
+    packed_account(TokenState {
+        mint: *mint,
+        owner: *owner,
+        amount,
+        state: TokenAccountState::Initialized,
+        ..TokenState::default()
+    })
+}
+
+pub const PYTH_STATUS_TRADING: u32 = 1;
+
+//the fields of a pyth price account (layout v2) that the pool reads, see src/oracle.rs
+pub fn pyth_price_account(
+    price: i64,
+    expo: i32,
+    status: u32,
+    publish_time: UnixTimestamp,
+    publish_slot: u64,
+) -> Account {
+    let mut data = vec![0u8; 3312];
+    data[0..4].copy_from_slice(&0xa1b2c3d4u32.to_le_bytes());
+    data[4..8].copy_from_slice(&2u32.to_le_bytes());
+    data[8..12].copy_from_slice(&3u32.to_le_bytes());
+    data[20..24].copy_from_slice(&expo.to_le_bytes());
+    data[96..104].copy_from_slice(&publish_time.to_le_bytes());
+    data[208..216].copy_from_slice(&price.to_le_bytes());
+    data[224..228].copy_from_slice(&status.to_le_bytes());
+    data[232..240].copy_from_slice(&publish_slot.to_le_bytes());
+    Account {
+        lamports: Rent::default().minimum_balance(data.len()),
+        data,
+        owner: oracle::pyth::id(),
+        ..Account::default()
+    }
+}
+
+//the fields of an spl stake pool account that the pool reads, see src/oracle.rs
+pub fn stake_pool_account(
+    pool_mint: &Pubkey,
+    total_lamports: u64,
+    pool_token_supply: u64,
+    last_update_epoch: u64,
+) -> Account {
+    let mut data = vec![0u8; 611];
+    data[0] = 1;
+    data[162..194].copy_from_slice(pool_mint.as_ref());
+    data[258..266].copy_from_slice(&total_lamports.to_le_bytes());
+    data[266..274].copy_from_slice(&pool_token_supply.to_le_bytes());
+    data[274..282].copy_from_slice(&last_update_epoch.to_le_bytes());
+    Account {
+        lamports: Rent::default().minimum_balance(data.len()),
+        data,
+        owner: oracle::spl_stake_pool::id(),
+        ..Account::default()
+    }
+}
+
+//the fields of a switchboard v2 aggregator account that the pool reads, see src/oracle.rs
+pub fn switchboard_aggregator_account(mantissa: i128, scale: u32) -> Account {
+    let mut data = vec![0u8; 3851];
+    data[0..8].copy_from_slice(&[217, 230, 65, 101, 201, 162, 27, 125]);
+    data[341..345].copy_from_slice(&1u32.to_le_bytes());
+    data[366..382].copy_from_slice(&mantissa.to_le_bytes());
+    data[382..386].copy_from_slice(&scale.to_le_bytes());
+    Account {
+        lamports: Rent::default().minimum_balance(data.len()),
+        data,
+        owner: oracle::switchboard_v2::id(),
+        ..Account::default()
+    }
+}
+
+//a rent exempt account of the token program holding `state`
+fn packed_account<T: Pack>(state: T) -> Account {
+    let mut data = vec![0; T::LEN];
+    T::pack(state, &mut data).unwrap();
+    Account {
+        lamports: Rent::default().minimum_balance(T::LEN),
+        data,
+        owner: spl_token::id(),
+        ..Account::default()
+    }
+}
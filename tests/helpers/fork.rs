@@ -0,0 +1,125 @@
+//a local bank forked from the recorded accounts of a set of pools (see `ForkFixture`), to dry-run routes the router
+// plans against the local processor and check that they realize exactly what the router predicted
+
+use super::bank::token_account;
+use pool::{
+    client::{amm::PoolAmm, capture::ForkFixture, router::Route, router::Router},
+    processor::Processor,
+    TOKEN_COUNT,
+};
+use solana_program::{clock::Clock, program_pack::Pack, pubkey::Pubkey, sysvar};
+use solana_program_test::*;
+use solana_sdk::{
+    account::{create_account_shared_data_for_test, Account},
+    signature::{Keypair, Signer},
+    transaction::Transaction,
+};
+use spl_token::state::Account as TokenState;
+use std::collections::HashMap;
+
+pub struct ForkedBank {
+    pub context: ProgramTestContext,
+    pub fixture: ForkFixture,
+}
+
+impl ForkedBank {
+    pub async fn start(fixture: ForkFixture) -> Self {
+        let mut program_test = ProgramTest::new(
+            "pool",
+            fixture.program_id,
+            processor!(Processor::<{ TOKEN_COUNT }>::process),
+        );
+        for account in &fixture.accounts {
+            program_test.add_account(account.pubkey, account.to_account());
+        }
+        let mut context = program_test.start_with_context().await;
+        //fees and amp factor ramps depend on the timestamp the pools were recorded at
+        let clock = Clock {
+            slot: fixture.slot,
+            unix_timestamp: fixture.unix_timestamp,
+            ..Clock::default()
+        };
+        context.set_account(&sysvar::clock::id(), &create_account_shared_data_for_test(&clock));
+        Self { context, fixture }
+    }
+
+    /// A router over the forked pools, quoting from the bank's current accounts
+    pub async fn router(&mut self) -> Router {
+        let mut amms = vec![];
+        for pool in self.fixture.pools.clone() {
+            let pool_account = self.account(&pool).await;
+            let mut amm = PoolAmm::from_keyed_account(&pool, &pool_account).unwrap();
+            let mut accounts = HashMap::new();
+            for key in amm.get_accounts_to_update() {
+                accounts.insert(key, self.account(&key).await);
+            }
+            amm.update(&accounts).unwrap();
+            amms.push(amm);
+        }
+        Router::new(self.fixture.program_id, amms)
+    }
+
+    async fn account(&mut self, key: &Pubkey) -> Account {
+        self.context
+            .banks_client
+            .get_account(*key)
+            .await
+            .unwrap()
+            .unwrap_or_else(|| panic!("\"{}\": account {} missing", self.fixture.description, key))
+    }
+
+    async fn balance(&mut self, token_account: &Pubkey) -> u64 {
+        TokenState::unpack(&self.account(token_account).await.data)
+            .unwrap()
+            .amount
+    }
+
+    /// Executes `route` from a fresh user holding exactly its input, panics unless it realizes exactly its quote
+    pub async fn dry_run(&mut self, route: &Route) {
+        let user = Keypair::new();
+        let (input_account, output_account) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let input_mint = route.hops[0].input_mint;
+        let output_mint = route.hops[route.hops.len() - 1].output_mint;
+        self.context.set_account(
+            &input_account,
+            &token_account(&input_mint, &user.pubkey(), route.in_amount()).into(),
+        );
+        self.context
+            .set_account(&output_account, &token_account(&output_mint, &user.pubkey(), 0).into());
+        //a two hop route's intermediate token account is paid for by the user
+        self.context.set_account(
+            &user.pubkey(),
+            &Account::new(1_000_000_000, 0, &solana_program::system_program::id()).into(),
+        );
+
+        let ixs = route
+            .instructions(&user.pubkey(), &input_account, &output_account, route.out_amount())
+            .unwrap();
+        let transaction = Transaction::new_signed_with_payer(
+            &ixs,
+            Some(&self.context.payer.pubkey()),
+            &[&self.context.payer, &user],
+            self.context.last_blockhash,
+        );
+        self.context
+            .banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_or_else(|err| panic!("\"{}\": route {:?} failed: {:?}", self.fixture.description, route, err));
+
+        assert_eq!(
+            self.balance(&input_account).await,
+            0,
+            "\"{}\": route {:?} didn't spend its input",
+            self.fixture.description,
+            route
+        );
+        assert_eq!(
+            self.balance(&output_account).await,
+            route.out_amount(),
+            "\"{}\": route {:?} realized a different output",
+            self.fixture.description,
+            route
+        );
+    }
+}
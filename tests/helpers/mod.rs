@@ -1,4 +1,7 @@
 #![allow(dead_code)]
+pub mod bank;
+pub mod fork;
+
 use borsh::BorshDeserialize;
 use pool::{common::*, decimal::*, instruction::*, state::PoolState, TOKEN_COUNT};
 use solana_program::{program_pack::Pack, pubkey::Pubkey, rent::Rent};
@@ -0,0 +1,138 @@
+#![cfg(feature = "test-bpf")]
+
+mod helpers;
+
+use helpers::{bank::*, AmountT};
+
+use pool::{
+    error::PoolError,
+    instruction::{DeFiInstruction, GovernanceInstruction},
+    oracle::{RateSource, RATE_ONE},
+    state::{InvariantKind, PriceScale},
+    TOKEN_COUNT,
+};
+use solana_program_test::*;
+use solana_sdk::{instruction::InstructionError, pubkey::Pubkey};
+
+const POOL_BALANCE: AmountT = 1_000_000_000;
+const SWAP_AMOUNT: AmountT = 1_000_000;
+//the volatile token, which starts out at two of the stablecoins
+const VOLATILE: usize = 0;
+const STABLE: usize = 1;
+const INITIAL_RATE: u64 = 2 * RATE_ONE;
+
+fn pool_error(error: PoolError) -> InstructionError {
+    InstructionError::Custom(error as u32)
+}
+
+fn swap(input_index: usize, output_index: usize, amount: AmountT) -> DeFiInstruction<TOKEN_COUNT> {
+    let mut exact_input_amounts = [0; TOKEN_COUNT];
+    exact_input_amounts[input_index] = amount;
+    DeFiInstruction::SwapExactInput {
+        exact_input_amounts,
+        output_token_index: output_index as u8,
+        minimum_output_amount: 0,
+    }
+}
+
+//how many stablecoins a small swap gets for the volatile token
+async fn sell_price(pool: &mut BankPool, user: &BankUser) -> f64 {
+    let before = pool.balance(&user.tokens[STABLE]).await;
+    pool.execute_defi_instruction(swap(VOLATILE, STABLE, SWAP_AMOUNT), user)
+        .await
+        .unwrap();
+    (pool.balance(&user.tokens[STABLE]).await - before) as f64 / SWAP_AMOUNT as f64
+}
+
+//a pool that holds the same value of each token at its initial price
+async fn funded_pool() -> (BankPool, BankUser) {
+    let mut pool = BankPool::new_hybrid(&BankPoolParams::default(), VOLATILE as u8, INITIAL_RATE)
+        .await
+        .unwrap();
+    let user = pool.create_user(&[2 * POOL_BALANCE; TOKEN_COUNT]);
+    let mut input_amounts = [POOL_BALANCE; TOKEN_COUNT];
+    input_amounts[VOLATILE] = POOL_BALANCE / 2;
+    pool.execute_defi_instruction(
+        DeFiInstruction::Add {
+            input_amounts,
+            minimum_mint_amount: 0,
+        },
+        &user,
+    )
+    .await
+    .unwrap();
+    (pool, user)
+}
+
+async fn set_oracle_rate(pool: &mut BankPool, rate: u64) {
+    pool.set_rate_source(VOLATILE, RateSource::Fixed { rate }, Pubkey::default())
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn test_hybrid_pool_trades_at_its_price_scale() {
+    let mut uninitialized = BankPool::new_uninitialized(&BankPoolParams::default()).await;
+    let ix = uninitialized.init_hybrid_ix(&BankPoolParams::default(), TOKEN_COUNT as u8, INITIAL_RATE);
+    assert_eq!(
+        uninitialized.execute_transaction(ix, &[]).await,
+        Err(pool_error(PoolError::InvalidPriceScale))
+    );
+
+    let (mut pool, user) = funded_pool().await;
+    let state = pool.state().await;
+    assert_eq!(state.invariant_kind, InvariantKind::Hybrid);
+    assert_eq!(
+        state.price_scale,
+        PriceScale {
+            token_index: VOLATILE as u8,
+            rate: INITIAL_RATE,
+            ..PriceScale::default()
+        }
+    );
+    let price = sell_price(&mut pool, &user).await;
+    assert!(price > 1.99 && price < 2.0, "{}", price);
+
+    //the oracle only moves the price through repegs
+    set_oracle_rate(&mut pool, 3 * RATE_ONE).await;
+    let price = sell_price(&mut pool, &user).await;
+    assert!(price > 1.99 && price < 2.0, "{}", price);
+}
+
+#[tokio::test]
+async fn test_repeg_moves_the_price_scale_towards_the_oracle() {
+    let (mut pool, user) = funded_pool().await;
+    set_oracle_rate(&mut pool, 3 * RATE_ONE).await;
+    assert_eq!(
+        pool.set_repeg_params(10_001, 500, 3_600).await,
+        Err(pool_error(PoolError::InvalidPriceScale))
+    );
+    //at most 5% per hour once the oracle is 1% away
+    pool.set_repeg_params(100, 500, 3_600).await.unwrap();
+
+    pool.repeg().await.unwrap();
+    assert_eq!(pool.state().await.price_scale.rate, INITIAL_RATE / 100 * 105);
+    let price = sell_price(&mut pool, &user).await;
+    assert!(price > 2.09 && price < 2.1, "{}", price);
+    assert_eq!(pool.repeg().await, Err(pool_error(PoolError::RepegNotDue)));
+
+    pool.advance_clock(3_600).await;
+    pool.execute_governance_instruction(GovernanceInstruction::SetPaused { paused: true }, None)
+        .await
+        .unwrap();
+    assert_eq!(pool.repeg().await, Err(pool_error(PoolError::PoolIsPaused)));
+    pool.execute_governance_instruction(GovernanceInstruction::SetPaused { paused: false }, None)
+        .await
+        .unwrap();
+    pool.repeg().await.unwrap();
+    let rate = INITIAL_RATE / 100 * 105 / 100 * 105;
+    assert_eq!(pool.state().await.price_scale.rate, rate);
+
+    //within the threshold the price stays, up to it repegs go all the way to the oracle
+    pool.advance_clock(3_600).await;
+    set_oracle_rate(&mut pool, rate / 1_000 * 1_005).await;
+    assert_eq!(pool.repeg().await, Err(pool_error(PoolError::RepegNotDue)));
+    set_oracle_rate(&mut pool, rate / 100 * 98).await;
+    pool.repeg().await.unwrap();
+    assert_eq!(pool.state().await.price_scale.rate, rate / 100 * 98);
+}
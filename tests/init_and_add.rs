@@ -0,0 +1,56 @@
+#![cfg(feature = "test-bpf")]
+
+mod helpers;
+
+use helpers::{bank::*, AmountT};
+
+use pool::{
+    error::PoolError,
+    instruction::{create_init_and_add_ix, DeFiInstruction},
+    TOKEN_COUNT,
+};
+use solana_program_test::*;
+use solana_sdk::instruction::InstructionError;
+
+const POOL_BALANCE: AmountT = 1_000_000_000;
+
+fn add(input_amount: AmountT) -> DeFiInstruction<TOKEN_COUNT> {
+    DeFiInstruction::Add {
+        input_amounts: [input_amount; TOKEN_COUNT],
+        minimum_mint_amount: 0,
+    }
+}
+
+#[tokio::test]
+async fn test_init_and_add_mints_creators_lp_tokens() {
+    let params = BankPoolParams::default();
+    let mut pool = BankPool::new_uninitialized(&params).await;
+    let creator = pool.create_user(&[POOL_BALANCE; TOKEN_COUNT]);
+    let ix = create_init_and_add_ix::<TOKEN_COUNT>(pool.init_ix(&params), pool.defi_ix(add(POOL_BALANCE), &creator))
+        .unwrap();
+    pool.execute_transaction(ix, &[&creator.keypair]).await.unwrap();
+
+    assert_eq!(pool.pool_balances().await, [POOL_BALANCE; TOKEN_COUNT]);
+    let creator_lp = pool.balance(&creator.lp).await;
+    assert!(creator_lp > 0);
+    assert_eq!(pool.lp_total_supply().await, creator_lp);
+    assert_eq!(pool.state().await.lp_fee.get(), params.lp_fee);
+}
+
+#[tokio::test]
+async fn test_init_and_add_initializes_nothing_if_add_fails() {
+    let params = BankPoolParams::default();
+    let mut pool = BankPool::new_uninitialized(&params).await;
+    let creator = pool.create_user(&[POOL_BALANCE; TOKEN_COUNT]);
+    let ix =
+        create_init_and_add_ix::<TOKEN_COUNT>(pool.init_ix(&params), pool.defi_ix(add(POOL_BALANCE + 1), &creator))
+            .unwrap();
+    assert_eq!(
+        pool.execute_transaction(ix, &[&creator.keypair]).await,
+        Err(InstructionError::Custom(PoolError::InsufficientUserBalance as u32))
+    );
+
+    //so the pool can still be initialized
+    let init_ix = pool.init_ix(&params);
+    pool.execute_transaction(init_ix, &[]).await.unwrap();
+}
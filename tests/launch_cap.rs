@@ -0,0 +1,113 @@
+#![cfg(feature = "test-bpf")]
+
+mod helpers;
+
+use helpers::{bank::*, AmountT};
+
+use pool::{error::PoolError, instruction::DeFiInstruction, TOKEN_COUNT};
+use solana_program_test::*;
+use solana_sdk::{
+    instruction::InstructionError,
+    signature::{Keypair, Signer},
+};
+
+const LAUNCH_WINDOW: i64 = 3600;
+//a balanced add of 1_000_000 of every token mints about TOKEN_COUNT * 1_000_000 lp tokens
+const CAP: AmountT = (TOKEN_COUNT as AmountT * 3 / 2) * 1_000_000;
+
+fn add(amount: AmountT) -> DeFiInstruction<TOKEN_COUNT> {
+    DeFiInstruction::Add {
+        input_amounts: [amount; TOKEN_COUNT],
+        minimum_mint_amount: 0,
+    }
+}
+
+async fn launch_pool() -> BankPool {
+    let mut pool = BankPool::new(&BankPoolParams::default()).await.unwrap();
+    let seed = pool.create_user(&[100_000_000; TOKEN_COUNT]);
+    pool.execute_defi_instruction(add(100_000_000), &seed).await.unwrap();
+    let now = pool.now().await;
+    pool.set_launch_cap(CAP, now + LAUNCH_WINDOW).await.unwrap();
+    pool
+}
+
+#[tokio::test]
+async fn test_launch_cap() {
+    let mut pool = launch_pool().await;
+    let state = pool.state().await;
+    assert_eq!(state.launch_lp_cap, CAP);
+    let user = pool.create_user(&[10_000_000; TOKEN_COUNT]);
+
+    //adds need the wallet's launch allocation during the window
+    assert_eq!(
+        pool.execute_defi_instruction(add(1_000_000), &user).await,
+        Err(InstructionError::UninitializedAccount)
+    );
+    pool.create_launch_allocation(&user).await.unwrap();
+    //creating it again changes nothing
+    pool.create_launch_allocation(&user).await.unwrap();
+
+    pool.execute_defi_instruction(add(1_000_000), &user).await.unwrap();
+    let minted = pool.balance(&user.lp).await;
+    let allocation = pool.launch_allocation(&user.keypair.pubkey()).await.unwrap();
+    assert_eq!(allocation.pool, pool.pool);
+    assert_eq!(allocation.lp_minted, minted);
+    assert_eq!(allocation.launch_end_ts, state.launch_end_ts);
+
+    //the cap applies to everything the wallet got, not to a single add
+    assert_eq!(
+        pool.execute_defi_instruction(add(1_000_000), &user).await,
+        Err(InstructionError::Custom(PoolError::LaunchCapExceeded as u32))
+    );
+    //not even to another lp token account of the same wallet
+    let other_lp = pool.create_user(&[0; TOKEN_COUNT]).lp;
+    let same_wallet = BankUser {
+        keypair: Keypair::from_bytes(&user.keypair.to_bytes()).unwrap(),
+        lp: other_lp,
+        tokens: user.tokens,
+    };
+    assert_eq!(
+        pool.execute_defi_instruction(add(1_000_000), &same_wallet).await,
+        Err(InstructionError::Custom(PoolError::LaunchCapExceeded as u32))
+    );
+    pool.execute_defi_instruction(add(100_000), &user).await.unwrap();
+
+    //other wallets have caps of their own
+    let other = pool.create_user(&[10_000_000; TOKEN_COUNT]);
+    pool.create_launch_allocation(&other).await.unwrap();
+    pool.execute_defi_instruction(add(1_000_000), &other).await.unwrap();
+
+    //the cap lifts by itself after the window, adds then don't need the account
+    pool.advance_clock(LAUNCH_WINDOW).await;
+    pool.execute_defi_instruction(add(5_000_000), &user).await.unwrap();
+    let late = pool.create_user(&[10_000_000; TOKEN_COUNT]);
+    pool.execute_defi_instruction(add(5_000_000), &late).await.unwrap();
+
+    //a new window starts the count over
+    let now = pool.now().await;
+    pool.set_launch_cap(CAP, now + LAUNCH_WINDOW).await.unwrap();
+    pool.execute_defi_instruction(add(1_000_000), &user).await.unwrap();
+    let allocation = pool.launch_allocation(&user.keypair.pubkey()).await.unwrap();
+    assert_eq!(allocation.launch_end_ts, now + LAUNCH_WINDOW);
+    assert!(allocation.lp_minted < CAP);
+}
+
+#[tokio::test]
+async fn test_launch_allocation_of_another_wallet() {
+    let mut pool = launch_pool().await;
+    let user = pool.create_user(&[10_000_000; TOKEN_COUNT]);
+    let other = pool.create_user(&[0; TOKEN_COUNT]);
+    pool.create_launch_allocation(&other).await.unwrap();
+
+    let mut ix = pool.defi_ix(add(1_000_000), &user);
+    let other_allocation = pool.defi_ix(add(1_000_000), &other).accounts.pop().unwrap();
+    *ix.accounts.last_mut().unwrap() = other_allocation;
+    assert_eq!(
+        pool.execute_transaction(ix, &[&user.keypair]).await,
+        Err(InstructionError::Custom(PoolError::NonCanonicalAddress as u32))
+    );
+
+    //lifting the cap also lifts the requirement
+    pool.set_launch_cap(0, 0).await.unwrap();
+    pool.execute_defi_instruction(add(1_000_000), &user).await.unwrap();
+}
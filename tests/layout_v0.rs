@@ -0,0 +1,97 @@
+#![cfg(feature = "test-bpf")]
+
+mod helpers;
+
+use helpers::{bank::*, AmountT};
+
+use pool::{
+    error::PoolError,
+    instruction::{DeFiInstruction, GovernanceInstruction},
+    state::{PoolStateV0, POOL_STATE_LAYOUT_VERSION},
+    TOKEN_COUNT,
+};
+use solana_program::borsh::get_packed_len;
+use solana_program_test::*;
+use solana_sdk::instruction::InstructionError;
+
+const POOL_BALANCE: AmountT = 1_000_000_000;
+const SWAP_AMOUNT: AmountT = 1_000;
+
+//a funded pool whose state account has the layout of the program's first release
+async fn layout_v0_pool() -> (BankPool, BankUser) {
+    let mut pool = BankPool::new(&BankPoolParams::default()).await.unwrap();
+    let user = pool.create_user(&[POOL_BALANCE; TOKEN_COUNT]);
+    pool.execute_defi_instruction(
+        DeFiInstruction::Add {
+            input_amounts: [POOL_BALANCE / 2; TOKEN_COUNT],
+            minimum_mint_amount: 0,
+        },
+        &user,
+    )
+    .await
+    .unwrap();
+    assert_eq!(pool.state().await.layout_version, POOL_STATE_LAYOUT_VERSION);
+    pool.truncate_to_layout_v0().await;
+    (pool, user)
+}
+
+async fn pool_account_len(pool: &mut BankPool) -> usize {
+    let account = pool.context.banks_client.get_account(pool.pool).await.unwrap().unwrap();
+    account.data.len()
+}
+
+#[tokio::test]
+async fn test_pools_of_the_first_layout_keep_working() {
+    let (mut pool, user) = layout_v0_pool().await;
+    let state = pool.state().await;
+    assert_eq!(state.layout_version, 0);
+    assert!(state.fits_layout_v0());
+
+    let mut exact_input_amounts = [0; TOKEN_COUNT];
+    exact_input_amounts[0] = SWAP_AMOUNT;
+    pool.execute_defi_instruction(
+        DeFiInstruction::SwapExactInput {
+            exact_input_amounts,
+            output_token_index: 1,
+            minimum_output_amount: 0,
+        },
+        &user,
+    )
+    .await
+    .unwrap();
+    pool.execute_governance_instruction(GovernanceInstruction::SetPaused { paused: true }, None)
+        .await
+        .unwrap();
+    assert!(pool.state().await.is_paused);
+
+    //liquidity providers can always exit
+    let exact_burn_amount = pool.balance(&user.lp).await;
+    pool.execute_defi_instruction(
+        DeFiInstruction::RemoveUniform {
+            exact_burn_amount,
+            minimum_output_amounts: [0; TOKEN_COUNT],
+        },
+        &user,
+    )
+    .await
+    .unwrap();
+    assert_eq!(pool.balance(&user.lp).await, 0);
+    assert_eq!(
+        pool_account_len(&mut pool).await,
+        get_packed_len::<PoolStateV0<TOKEN_COUNT>>()
+    );
+}
+
+#[tokio::test]
+async fn test_later_settings_dont_fit_the_first_layout() {
+    let (mut pool, _) = layout_v0_pool().await;
+    assert_eq!(
+        pool.set_withdrawal_cooldown(0, 60).await,
+        Err(InstructionError::Custom(PoolError::PoolLayoutOutdated as u32))
+    );
+    //settings of the first layout still change
+    pool.execute_governance_instruction(GovernanceInstruction::SetPaused { paused: true }, None)
+        .await
+        .unwrap();
+    assert_eq!(pool.state().await.withdrawal_cooldown, 0);
+}
@@ -0,0 +1,143 @@
+#![cfg(feature = "test-bpf")]
+
+mod helpers;
+
+use helpers::{bank::*, AmountT, DecT};
+
+use pool::{
+    error::PoolError,
+    instruction::{DeFiInstruction, GovernanceInstruction},
+    state::{lock_weight, MAX_LOCK_DURATION, MIN_LOCK_DURATION},
+    TOKEN_COUNT,
+};
+use solana_program_test::*;
+use solana_sdk::{instruction::InstructionError, signature::Signer};
+
+const POOL_BALANCE: AmountT = 1_000_000_000;
+
+fn params() -> BankPoolParams {
+    BankPoolParams {
+        governance_fee: DecT::new(1, 3).unwrap(),
+        ..BankPoolParams::default()
+    }
+}
+
+async fn add(pool: &mut BankPool, amount: AmountT) -> BankUser {
+    let user = pool.create_user(&[amount; TOKEN_COUNT]);
+    pool.execute_defi_instruction(
+        DeFiInstruction::Add {
+            input_amounts: [amount; TOKEN_COUNT],
+            minimum_mint_amount: 0,
+        },
+        &user,
+    )
+    .await
+    .unwrap();
+    user
+}
+
+async fn swap(pool: &mut BankPool, amount: AmountT) {
+    let mut input_amounts = [0; TOKEN_COUNT];
+    input_amounts[0] = amount;
+    let trader = pool.create_user(&input_amounts);
+    pool.execute_defi_instruction(
+        DeFiInstruction::SwapExactInput {
+            exact_input_amounts: input_amounts,
+            output_token_index: 1,
+            minimum_output_amount: 0,
+        },
+        &trader,
+    )
+    .await
+    .unwrap();
+}
+
+#[tokio::test]
+async fn test_locks_share_the_governance_fee_by_weight() {
+    let mut pool = BankPool::new(&params()).await.unwrap();
+    let short = add(&mut pool, POOL_BALANCE).await;
+    let long = add(&mut pool, POOL_BALANCE).await;
+    pool.execute_governance_instruction(
+        GovernanceInstruction::SetLockFeeShare {
+            share: DecT::new(5, 1).unwrap(),
+        },
+        None,
+    )
+    .await
+    .unwrap();
+    let locked = pool.balance(&short.lp).await / 2;
+    assert_eq!(
+        pool.lock_lp(&short, locked, MIN_LOCK_DURATION - 1).await,
+        Err(InstructionError::Custom(PoolError::InvalidLockDuration as u32))
+    );
+    pool.lock_lp(&short, locked, MIN_LOCK_DURATION).await.unwrap();
+    pool.lock_lp(&long, locked, MAX_LOCK_DURATION).await.unwrap();
+    let (short_weight, long_weight) = (
+        lock_weight(locked, MIN_LOCK_DURATION),
+        lock_weight(locked, MAX_LOCK_DURATION),
+    );
+    assert_eq!(long_weight, locked as u128 * 5 / 2);
+    assert_eq!(
+        pool.lp_lock(&short.keypair.pubkey()).await.unwrap().weight,
+        short_weight
+    );
+    assert_eq!(pool.state().await.lock_total_weight, short_weight + long_weight);
+
+    let lp_supply = pool.lp_total_supply().await;
+    let governance_fee_account = pool.governance_fee_account;
+    let governance_balance = pool.balance(&governance_fee_account).await;
+    swap(&mut pool, POOL_BALANCE / 10).await;
+    let governance_amount = pool.balance(&governance_fee_account).await - governance_balance;
+    let lock_amount = pool.state().await.lock_rewards_unminted;
+    assert!(governance_amount > 0);
+    assert_eq!(lock_amount, (governance_amount + lock_amount) / 2);
+    //the locks' share isn't minted until claimed
+    assert_eq!(pool.lp_total_supply().await, lp_supply + governance_amount);
+
+    let short_lp = pool.balance(&short.lp).await;
+    let long_lp = pool.balance(&long.lp).await;
+    pool.claim_lock_rewards(&short).await.unwrap();
+    pool.claim_lock_rewards(&long).await.unwrap();
+    let short_reward = pool.balance(&short.lp).await - short_lp;
+    let long_reward = pool.balance(&long.lp).await - long_lp;
+    let expected_long_reward = (lock_amount as u128 * long_weight / (short_weight + long_weight)) as AmountT;
+    assert!(expected_long_reward - long_reward <= 1);
+    assert!(short_reward + long_reward <= lock_amount);
+    assert!(lock_amount - short_reward - long_reward <= 2);
+    assert_eq!(
+        pool.state().await.lock_rewards_unminted,
+        lock_amount - short_reward - long_reward
+    );
+    //claiming again yields nothing
+    pool.claim_lock_rewards(&short).await.unwrap();
+    assert_eq!(pool.balance(&short.lp).await, short_lp + short_reward);
+}
+
+#[tokio::test]
+async fn test_unlock_lp() {
+    let mut pool = BankPool::new(&params()).await.unwrap();
+    let user = add(&mut pool, POOL_BALANCE).await;
+    let lp_amount = pool.balance(&user.lp).await;
+    pool.lock_lp(&user, lp_amount, MIN_LOCK_DURATION).await.unwrap();
+    assert_eq!(pool.balance(&user.lp).await, 0);
+    pool.advance_clock(MIN_LOCK_DURATION / 2).await;
+    assert_eq!(
+        pool.unlock_lp(&user).await,
+        Err(InstructionError::Custom(PoolError::LpStillLocked as u32))
+    );
+
+    pool.advance_clock(MIN_LOCK_DURATION / 2).await;
+    pool.unlock_lp(&user).await.unwrap();
+    assert_eq!(pool.balance(&user.lp).await, lp_amount);
+    assert_eq!(pool.lp_lock(&user.keypair.pubkey()).await, None);
+    assert_eq!(pool.state().await.lock_total_weight, 0);
+
+    //winding the pool down releases locks right away
+    pool.lock_lp(&user, lp_amount, MAX_LOCK_DURATION).await.unwrap();
+    let deadline = pool.now().await + 7 * 24 * 60 * 60;
+    pool.execute_governance_instruction(GovernanceInstruction::SetSunset { deadline }, None)
+        .await
+        .unwrap();
+    pool.unlock_lp(&user).await.unwrap();
+    assert_eq!(pool.balance(&user.lp).await, lp_amount);
+}
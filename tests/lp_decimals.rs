@@ -0,0 +1,126 @@
+#![cfg(feature = "test-bpf")]
+
+//the lp mint's decimals don't have to match the pool's tokens', its amounts are scaled up (fewer decimals) or down
+// (more decimals) into the equalized units the invariant works with
+
+mod helpers;
+
+use helpers::{bank::*, AmountT};
+
+use pool::{error::PoolError, instruction::DeFiInstruction, processor::MAX_DECIMAL_DIFFERENCE, TOKEN_COUNT};
+use solana_program_test::*;
+use solana_sdk::instruction::InstructionError;
+
+const WHOLE_TOKENS: AmountT = 1000;
+
+async fn funded_pool(lp_decimals: u8, token_decimals: u8) -> (BankPool, BankUser) {
+    let params = BankPoolParams {
+        lp_decimals,
+        token_decimals: [token_decimals; TOKEN_COUNT],
+        ..BankPoolParams::default()
+    };
+    let mut pool = BankPool::new(&params).await.unwrap();
+    let balances = [WHOLE_TOKENS * 10u64.pow(token_decimals as u32); TOKEN_COUNT];
+    let lp = pool.create_user(&balances);
+    pool.execute_defi_instruction(
+        DeFiInstruction::Add {
+            input_amounts: balances,
+            minimum_mint_amount: 0,
+        },
+        &lp,
+    )
+    .await
+    .unwrap();
+    (pool, lp)
+}
+
+fn single(index: usize, amount: AmountT) -> [AmountT; TOKEN_COUNT] {
+    let mut amounts = [0; TOKEN_COUNT];
+    amounts[index] = amount;
+    amounts
+}
+
+#[tokio::test]
+async fn test_fewer_lp_decimals_than_tokens() {
+    let (mut pool, lp) = funded_pool(6, 9).await;
+    let state = pool.state().await;
+    assert_eq!(state.lp_decimal_equalizer, 3);
+    assert_eq!(state.token_decimal_equalizers, [0; TOKEN_COUNT]);
+    //a balanced pool's first lp tokens are worth one token each
+    let minted = pool.balance(&lp.lp).await;
+    assert_eq!(minted, TOKEN_COUNT as AmountT * WHOLE_TOKENS * 10u64.pow(6));
+
+    let before = pool.balance(&lp.tokens[0]).await;
+    pool.execute_defi_instruction(
+        DeFiInstruction::RemoveExactBurn {
+            exact_burn_amount: 10u64.pow(6),
+            output_token_index: 0,
+            minimum_output_amount: 0,
+        },
+        &lp,
+    )
+    .await
+    .unwrap();
+    let received = pool.balance(&lp.tokens[0]).await - before;
+    assert!(
+        received > 99 * 10u64.pow(7) && received < 10u64.pow(9),
+        "received {}",
+        received
+    );
+}
+
+#[tokio::test]
+async fn test_more_lp_decimals_than_tokens() {
+    let (mut pool, lp) = funded_pool(9, 6).await;
+    let state = pool.state().await;
+    assert_eq!(state.lp_decimal_equalizer, -3);
+    assert_eq!(state.token_decimal_equalizers, [0; TOKEN_COUNT]);
+    let minted = pool.balance(&lp.lp).await;
+    assert_eq!(minted, TOKEN_COUNT as AmountT * WHOLE_TOKENS * 10u64.pow(9));
+
+    //lp tokens are only minted and burned in multiples of a token atom's worth, burns are rounded up to them
+    let lp_before = pool.balance(&lp.lp).await;
+    pool.execute_defi_instruction(
+        DeFiInstruction::RemoveExactOutput {
+            maximum_burn_amount: AmountT::MAX,
+            exact_output_amounts: single(0, 1),
+        },
+        &lp,
+    )
+    .await
+    .unwrap();
+    let burned = lp_before - pool.balance(&lp.lp).await;
+    assert!(burned >= 1000 && burned % 1000 == 0, "burned {}", burned);
+
+    let trader = pool.create_user(&[10u64.pow(6); TOKEN_COUNT]);
+    let lp_before = pool.balance(&trader.lp).await;
+    pool.execute_defi_instruction(
+        DeFiInstruction::Add {
+            input_amounts: single(1, 10u64.pow(6)),
+            minimum_mint_amount: 0,
+        },
+        &trader,
+    )
+    .await
+    .unwrap();
+    let minted = pool.balance(&trader.lp).await - lp_before;
+    assert!(minted < 10u64.pow(9) && minted % 1000 == 0, "minted {}", minted);
+}
+
+#[tokio::test]
+async fn test_lp_decimals_out_of_range() {
+    for &(lp_decimals, token_decimals) in [(0, MAX_DECIMAL_DIFFERENCE + 1), (MAX_DECIMAL_DIFFERENCE + 7, 6)].iter() {
+        let params = BankPoolParams {
+            lp_decimals,
+            token_decimals: [token_decimals; TOKEN_COUNT],
+            ..BankPoolParams::default()
+        };
+        assert_eq!(
+            BankPool::new(&params).await.err(),
+            Some(InstructionError::Custom(PoolError::MaxDecimalDifferenceExceeded as u32)),
+            "lp decimals {} for token decimals {}",
+            lp_decimals,
+            token_decimals
+        );
+    }
+}
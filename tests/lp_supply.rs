@@ -0,0 +1,80 @@
+#![cfg(feature = "test-bpf")]
+
+mod helpers;
+
+use helpers::bank::*;
+
+use pool::{instruction::DeFiInstruction, state::LP_SUPPLY_CHECKPOINT_INTERVAL, TOKEN_COUNT};
+use solana_program_test::*;
+
+#[tokio::test]
+async fn test_snapshots_integrate_lp_supply() {
+    let params = BankPoolParams::default();
+    let mut pool = BankPool::new(&params).await.unwrap();
+    let user = pool.create_user(&[1_000_000; TOKEN_COUNT]);
+    pool.execute_defi_instruction(
+        DeFiInstruction::Add {
+            input_amounts: [1_000_000; TOKEN_COUNT],
+            minimum_mint_amount: 0,
+        },
+        &user,
+    )
+    .await
+    .unwrap();
+    let added_supply = pool.lp_total_supply().await;
+    let added = pool.state().await;
+    //there was nothing to integrate before the first add
+    assert_eq!(added.lp_supply_seconds, 0);
+
+    pool.advance_clock(2 * LP_SUPPLY_CHECKPOINT_INTERVAL).await;
+    pool.snapshot_lp_supply().await.unwrap();
+    let first = *pool.lp_supply_snapshots().await.latest().unwrap();
+    assert_eq!(first.lp_supply, added_supply);
+    assert!(first.ts >= added.lp_supply_ts + 2 * LP_SUPPLY_CHECKPOINT_INTERVAL);
+    assert_eq!(
+        first.lp_supply_seconds,
+        added_supply as u128 * (first.ts - added.lp_supply_ts) as u128
+    );
+
+    pool.advance_clock(LP_SUPPLY_CHECKPOINT_INTERVAL).await;
+    pool.execute_defi_instruction(
+        DeFiInstruction::RemoveUniform {
+            exact_burn_amount: added_supply / 2,
+            minimum_output_amounts: [0; TOKEN_COUNT],
+        },
+        &user,
+    )
+    .await
+    .unwrap();
+    let removed_supply = pool.lp_total_supply().await;
+    let removed = pool.state().await;
+    assert_eq!(
+        removed.lp_supply_seconds,
+        added_supply as u128 * (removed.lp_supply_ts - added.lp_supply_ts) as u128
+    );
+
+    pool.advance_clock(2 * LP_SUPPLY_CHECKPOINT_INTERVAL).await;
+    pool.snapshot_lp_supply().await.unwrap();
+    let snapshots = pool.lp_supply_snapshots().await;
+    assert_eq!(snapshots.pool, pool.pool);
+    let checkpoints = snapshots.checkpoints();
+    assert_eq!(checkpoints.len(), 2);
+    assert_eq!(checkpoints[0], first);
+    let second = checkpoints[1];
+    assert_eq!(second.lp_supply, removed_supply);
+    assert_eq!(
+        second.lp_supply_seconds,
+        removed.lp_supply_seconds + removed_supply as u128 * (second.ts - removed.lp_supply_ts) as u128
+    );
+    //the average in between lies between the supply before and after the remove
+    let average = (second.lp_supply_seconds - first.lp_supply_seconds) / (second.ts - first.ts) as u128;
+    assert!(average > removed_supply as u128 && average < added_supply as u128);
+
+    //snapshots within the interval only update the latest checkpoint
+    pool.advance_clock(60).await;
+    pool.snapshot_lp_supply().await.unwrap();
+    let snapshots = pool.lp_supply_snapshots().await;
+    assert_eq!(snapshots.checkpoint_count, 2);
+    assert_eq!(snapshots.checkpoints()[0], first);
+    assert!(snapshots.latest().unwrap().ts > second.ts);
+}
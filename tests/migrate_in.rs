@@ -0,0 +1,186 @@
+#![cfg(feature = "test-bpf")]
+
+mod helpers;
+
+use helpers::{bank::*, AmountT};
+
+use pool::{
+    error::PoolError,
+    instruction::{create_migrate_in_ix, DeFiInstruction, PoolInstruction},
+    migration::{saber_stable_swap, spl_token_swap, MigrationSource, SourcePool},
+    TOKEN_COUNT,
+};
+use solana_program::{
+    account_info::AccountInfo,
+    entrypoint::ProgramResult,
+    program::{invoke, invoke_signed},
+};
+use solana_program_test::*;
+use solana_sdk::{
+    instruction::{Instruction, InstructionError},
+    pubkey::Pubkey,
+    signature::Signer,
+};
+use spl_token::instruction::transfer;
+use std::convert::TryInto;
+
+const POOL_BALANCE: AmountT = 1_000_000_000;
+const MIGRATE_AMOUNT: AmountT = 1_000_000;
+
+//stands in for token-swap and Saber: pays out as many of both tokens as it receives lp tokens, which go to its (first)
+// fee account
+fn process_source_pool(program_id: &Pubkey, accounts: &[AccountInfo], data: &[u8]) -> ProgramResult {
+    assert_eq!(data[0], 3);
+    let amount = u64::from_le_bytes(data[1..9].try_into().unwrap());
+    let (swap, authority, user_authority) = (&accounts[0], &accounts[1], &accounts[2]);
+    let (user_lp, fee, token_program) = (&accounts[4], &accounts[9], &accounts[accounts.len() - 1]);
+    invoke(
+        &transfer(&spl_token::id(), user_lp.key, fee.key, user_authority.key, &[], amount)?,
+        &[
+            user_lp.clone(),
+            fee.clone(),
+            user_authority.clone(),
+            token_program.clone(),
+        ],
+    )?;
+    let bump = Pubkey::find_program_address(&[&swap.key.to_bytes()], program_id).1;
+    for (vault, destination) in [(&accounts[5], &accounts[7]), (&accounts[6], &accounts[8])].iter() {
+        invoke_signed(
+            &transfer(&spl_token::id(), vault.key, destination.key, authority.key, &[], amount)?,
+            &[
+                (*vault).clone(),
+                (*destination).clone(),
+                authority.clone(),
+                token_program.clone(),
+            ],
+            &[&[&swap.key.to_bytes(), &[bump]]],
+        )?;
+    }
+    Ok(())
+}
+
+struct Migration {
+    pool: BankPool,
+    user: BankUser,
+    source_pool: SourcePool,
+    user_source_lp: Pubkey,
+}
+
+//a funded pool and a source pool of its tokens 1 and 2, in which the user holds MIGRATE_AMOUNT lp tokens
+async fn migration(source: MigrationSource) -> Migration {
+    let mut pool = BankPool::new_with(&BankPoolParams::default(), |program_test| {
+        for program_id in [spl_token_swap::id(), saber_stable_swap::id()].iter() {
+            program_test.add_program("source_pool", *program_id, processor!(process_source_pool));
+        }
+    })
+    .await
+    .unwrap();
+    let user = pool.create_user(&[POOL_BALANCE; TOKEN_COUNT]);
+    pool.execute_defi_instruction(
+        DeFiInstruction::Add {
+            input_amounts: [POOL_BALANCE; TOKEN_COUNT],
+            minimum_mint_amount: 0,
+        },
+        &user,
+    )
+    .await
+    .unwrap();
+
+    let swap = Pubkey::new_unique();
+    let authority = Pubkey::find_program_address(&[&swap.to_bytes()], &source.program_id()).0;
+    let pool_mint = Pubkey::new_unique();
+    pool.set_mint_account(&pool_mint, 6, &authority);
+    let (token_a, token_b) = (Pubkey::new_unique(), Pubkey::new_unique());
+    let token_mints = pool.token_mints;
+    pool.set_token_account(&token_a, &token_mints[1], &authority, POOL_BALANCE);
+    pool.set_token_account(&token_b, &token_mints[2], &authority, POOL_BALANCE);
+    let fee_count = source.account_count() - 10;
+    let fee_accounts: Vec<_> = (0..fee_count).map(|_| Pubkey::new_unique()).collect();
+    for fee_account in fee_accounts.iter() {
+        pool.set_token_account(fee_account, &pool_mint, &authority, 0);
+    }
+    let user_source_lp = Pubkey::new_unique();
+    pool.set_token_account(&user_source_lp, &pool_mint, &user.keypair.pubkey(), MIGRATE_AMOUNT);
+
+    Migration {
+        pool,
+        user,
+        source_pool: SourcePool {
+            source,
+            swap,
+            authority,
+            pool_mint,
+            token_a,
+            token_b,
+            fee_accounts,
+        },
+        user_source_lp,
+    }
+}
+
+fn migrate_in_ix(migration: &Migration, token_indices: [u8; 2]) -> Instruction {
+    let add_ix = migration.pool.defi_ix(
+        DeFiInstruction::Add {
+            input_amounts: [0; TOKEN_COUNT],
+            minimum_mint_amount: 0,
+        },
+        &migration.user,
+    );
+    create_migrate_in_ix::<TOKEN_COUNT>(
+        PoolInstruction::MigrateIn {
+            source: migration.source_pool.source,
+            source_lp_amount: MIGRATE_AMOUNT,
+            token_indices,
+            minimum_mint_amount: 0,
+        },
+        &migration.source_pool,
+        &migration.user_source_lp,
+        add_ix,
+    )
+    .unwrap()
+}
+
+async fn check_migrate_in(source: MigrationSource) {
+    let migration = migration(source).await;
+    let ix = migrate_in_ix(&migration, [1, 2]);
+    let Migration {
+        mut pool,
+        user,
+        user_source_lp,
+        ..
+    } = migration;
+    let lp_before = pool.balance(&user.lp).await;
+    pool.execute_transaction(ix, &[&user.keypair]).await.unwrap();
+
+    assert_eq!(pool.balance(&user_source_lp).await, 0);
+    assert!(pool.balance(&user.lp).await > lp_before);
+    //everything withdrawn went into the pool
+    assert_eq!(pool.balances(&user.tokens).await, [0; TOKEN_COUNT]);
+    let mut expected_pool_balances = [POOL_BALANCE; TOKEN_COUNT];
+    expected_pool_balances[1] += MIGRATE_AMOUNT;
+    expected_pool_balances[2] += MIGRATE_AMOUNT;
+    assert_eq!(pool.pool_balances().await, expected_pool_balances);
+}
+
+#[tokio::test]
+async fn test_migrate_in_from_token_swap() {
+    check_migrate_in(MigrationSource::TokenSwap).await;
+}
+
+#[tokio::test]
+async fn test_migrate_in_from_saber() {
+    check_migrate_in(MigrationSource::Saber).await;
+}
+
+#[tokio::test]
+async fn test_migrate_in_requires_matching_token_accounts() {
+    let mut migration = migration(MigrationSource::TokenSwap).await;
+    let mut ix = migrate_in_ix(&migration, [1, 2]);
+    //the withdrawal would pay token a out to the user's account of token 3
+    let len = ix.accounts.len();
+    ix.accounts[len - 4].pubkey = migration.user.tokens[3];
+    assert_eq!(
+        migration.pool.execute_transaction(ix, &[&migration.user.keypair]).await,
+        Err(InstructionError::Custom(PoolError::InvalidMigrationAccount as u32))
+    );
+}
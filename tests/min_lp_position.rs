@@ -0,0 +1,103 @@
+#![cfg(feature = "test-bpf")]
+
+mod helpers;
+
+use helpers::{bank::*, AmountT};
+
+use pool::{
+    error::PoolError,
+    instruction::{DeFiInstruction, GovernanceInstruction},
+    TOKEN_COUNT,
+};
+use solana_program_test::*;
+use solana_sdk::instruction::InstructionError;
+
+const MIN_MINT: AmountT = 1_000_000;
+const MIN_RESIDUAL: AmountT = 500_000;
+
+async fn set_min_lp_position(pool: &mut BankPool, min_mint_amount: AmountT, min_residual_amount: AmountT) {
+    pool.execute_governance_instruction(
+        GovernanceInstruction::SetMinLpPosition {
+            min_mint_amount,
+            min_residual_amount,
+        },
+        None,
+    )
+    .await
+    .unwrap();
+}
+
+fn add(amount: AmountT) -> DeFiInstruction<TOKEN_COUNT> {
+    DeFiInstruction::Add {
+        input_amounts: [amount; TOKEN_COUNT],
+        minimum_mint_amount: 0,
+    }
+}
+
+fn remove_uniform(exact_burn_amount: AmountT) -> DeFiInstruction<TOKEN_COUNT> {
+    DeFiInstruction::RemoveUniform {
+        exact_burn_amount,
+        minimum_output_amounts: [0; TOKEN_COUNT],
+    }
+}
+
+#[tokio::test]
+async fn test_min_lp_position() {
+    let mut pool = BankPool::new(&BankPoolParams::default()).await.unwrap();
+    let whale = pool.create_user(&[1_000_000_000; TOKEN_COUNT]);
+    pool.execute_defi_instruction(add(1_000_000_000), &whale).await.unwrap();
+    set_min_lp_position(&mut pool, MIN_MINT, MIN_RESIDUAL).await;
+    let state = pool.state().await;
+    assert_eq!(state.min_lp_mint_amount, MIN_MINT);
+    assert_eq!(state.min_lp_residual_amount, MIN_RESIDUAL);
+
+    let dust = Err(InstructionError::Custom(PoolError::DustPosition as u32));
+    let user = pool.create_user(&[10_000_000; TOKEN_COUNT]);
+    assert_eq!(pool.execute_defi_instruction(add(1_000), &user).await, dust);
+    pool.execute_defi_instruction(add(1_000_000), &user).await.unwrap();
+    let lp_balance = pool.balance(&user.lp).await;
+    assert!(lp_balance >= MIN_MINT);
+
+    //partial removes have to leave at least the minimum behind
+    let residual = MIN_RESIDUAL - 1;
+    assert_eq!(
+        pool.execute_defi_instruction(remove_uniform(lp_balance - residual), &user)
+            .await,
+        dust
+    );
+    assert_eq!(
+        pool.execute_defi_instruction(
+            DeFiInstruction::RemoveExactBurn {
+                exact_burn_amount: lp_balance - residual,
+                output_token_index: 0,
+                minimum_output_amount: 0,
+            },
+            &user,
+        )
+        .await,
+        dust
+    );
+    assert_eq!(
+        pool.execute_defi_instruction(
+            DeFiInstruction::RemoveExactOutput {
+                maximum_burn_amount: AmountT::MAX,
+                exact_output_amounts: [950_000; TOKEN_COUNT],
+            },
+            &user,
+        )
+        .await,
+        dust
+    );
+    pool.execute_defi_instruction(remove_uniform(lp_balance - MIN_RESIDUAL), &user)
+        .await
+        .unwrap();
+    //but emptying the account is always possible
+    pool.execute_defi_instruction(remove_uniform(MIN_RESIDUAL), &user)
+        .await
+        .unwrap();
+    assert_eq!(pool.balance(&user.lp).await, 0);
+
+    set_min_lp_position(&mut pool, 0, 0).await;
+    pool.execute_defi_instruction(add(1_000), &user).await.unwrap();
+    pool.execute_defi_instruction(remove_uniform(1), &user).await.unwrap();
+}
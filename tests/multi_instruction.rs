@@ -0,0 +1,199 @@
+#![cfg(feature = "test-bpf")]
+
+//routers and aggregators pack several pool instructions into a single transaction, so every instruction has to leave
+// the pool in a state (in particular previous_depth) that the next instruction of the same transaction can build on
+//
+//each test executes a sequence of instructions once within a single transaction and once as separate transactions
+// on an identically set up pool, and requires both to end up in exactly the same state
+
+mod helpers;
+
+use helpers::{bank::*, AmountT};
+
+use pool::{error::PoolError, instruction::DeFiInstruction, TOKEN_COUNT};
+use solana_program_test::*;
+use solana_sdk::instruction::InstructionError;
+
+const POOL_BALANCE: AmountT = 1_000_000_000;
+const USER_BALANCE: AmountT = 100_000_000;
+
+#[derive(Debug, PartialEq)]
+struct Snapshot {
+    pool_balances: [AmountT; TOKEN_COUNT],
+    lp_supply: AmountT,
+    governance_fee_balance: AmountT,
+    previous_depth: u128,
+    user_balances: [AmountT; TOKEN_COUNT],
+    user_lp: AmountT,
+}
+
+async fn snapshot(pool: &mut BankPool, user: &BankUser) -> Snapshot {
+    let governance_fee_account = pool.governance_fee_account;
+    Snapshot {
+        pool_balances: pool.pool_balances().await,
+        lp_supply: pool.lp_total_supply().await,
+        governance_fee_balance: pool.balance(&governance_fee_account).await,
+        previous_depth: pool.state().await.previous_depth,
+        user_balances: pool.balances(&user.tokens).await,
+        user_lp: pool.balance(&user.lp).await,
+    }
+}
+
+//a pool with some liquidity and a user that holds tokens but no lp tokens yet
+async fn setup() -> (BankPool, BankUser) {
+    let mut pool = BankPool::new(&BankPoolParams::default()).await.unwrap();
+    let seeder = pool.create_user(&[POOL_BALANCE; TOKEN_COUNT]);
+    pool.execute_defi_instruction(
+        DeFiInstruction::Add {
+            input_amounts: [POOL_BALANCE; TOKEN_COUNT],
+            minimum_mint_amount: 0,
+        },
+        &seeder,
+    )
+    .await
+    .unwrap();
+    let user = pool.create_user(&[USER_BALANCE; TOKEN_COUNT]);
+    (pool, user)
+}
+
+async fn execute_in_one_transaction(
+    sequence: Vec<DeFiInstruction<TOKEN_COUNT>>,
+) -> (Result<(), InstructionError>, Snapshot, Snapshot) {
+    let (mut pool, user) = setup().await;
+    let before = snapshot(&mut pool, &user).await;
+    let ixs: Vec<_> = sequence
+        .into_iter()
+        .map(|defi_instruction| pool.defi_ix(defi_instruction, &user))
+        .collect();
+    let result = pool.execute_instructions(&ixs, &[&user.keypair]).await;
+    let after = snapshot(&mut pool, &user).await;
+    (result, before, after)
+}
+
+async fn execute_one_by_one(sequence: Vec<DeFiInstruction<TOKEN_COUNT>>) -> Snapshot {
+    let (mut pool, user) = setup().await;
+    for defi_instruction in sequence {
+        pool.execute_defi_instruction(defi_instruction, &user).await.unwrap();
+    }
+    snapshot(&mut pool, &user).await
+}
+
+fn single(index: usize, amount: AmountT) -> [AmountT; TOKEN_COUNT] {
+    let mut amounts = [0; TOKEN_COUNT];
+    amounts[index] = amount;
+    amounts
+}
+
+//lp tokens that the user ends up with after the first add of the sequences below
+// (remove amounts have to be known when the transaction is built)
+async fn lp_minted_by_first_add() -> AmountT {
+    let (mut pool, user) = setup().await;
+    pool.execute_defi_instruction(add(), &user).await.unwrap();
+    pool.balance(&user.lp).await
+}
+
+fn add() -> DeFiInstruction<TOKEN_COUNT> {
+    DeFiInstruction::Add {
+        input_amounts: [USER_BALANCE / 2; TOKEN_COUNT],
+        minimum_mint_amount: 0,
+    }
+}
+
+fn swap(input_index: usize, output_index: usize, amount: AmountT) -> DeFiInstruction<TOKEN_COUNT> {
+    DeFiInstruction::SwapExactInput {
+        exact_input_amounts: single(input_index, amount),
+        output_token_index: output_index as u8,
+        minimum_output_amount: 0,
+    }
+}
+
+async fn assert_equivalent(sequence: impl Fn() -> Vec<DeFiInstruction<TOKEN_COUNT>>) {
+    let (result, before, in_one_transaction) = execute_in_one_transaction(sequence()).await;
+    result.unwrap();
+    let one_by_one = execute_one_by_one(sequence()).await;
+
+    assert_eq!(in_one_transaction, one_by_one);
+    //make sure the sequence actually did something depth related
+    assert_ne!(in_one_transaction.previous_depth, before.previous_depth);
+}
+
+#[tokio::test]
+async fn test_add_swap_remove() {
+    let lp = lp_minted_by_first_add().await;
+    assert_equivalent(|| {
+        vec![
+            add(),
+            swap(0, 1, USER_BALANCE / 4),
+            DeFiInstruction::RemoveUniform {
+                exact_burn_amount: lp / 2,
+                minimum_output_amounts: [0; TOKEN_COUNT],
+            },
+        ]
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn test_every_instruction_in_one_transaction() {
+    let lp = lp_minted_by_first_add().await;
+    assert_equivalent(|| {
+        vec![
+            add(),
+            swap(0, 1, USER_BALANCE / 4),
+            DeFiInstruction::SwapExactOutput {
+                maximum_input_amount: USER_BALANCE / 4,
+                input_token_index: 2,
+                exact_output_amounts: single(3, USER_BALANCE / 8),
+            },
+            DeFiInstruction::RemoveExactBurn {
+                exact_burn_amount: lp / 4,
+                output_token_index: 4,
+                minimum_output_amount: 0,
+            },
+            DeFiInstruction::RemoveExactOutput {
+                maximum_burn_amount: lp / 4,
+                exact_output_amounts: single(5, USER_BALANCE / 8),
+            },
+            DeFiInstruction::RemoveUniform {
+                exact_burn_amount: lp / 4,
+                minimum_output_amounts: [0; TOKEN_COUNT],
+            },
+        ]
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn test_swaps_back_and_forth() {
+    //governance fees are minted based on the depth increase since previous_depth, so if it weren't updated between
+    // instructions the later swaps would be charged for the earlier ones again
+    assert_equivalent(|| {
+        vec![
+            swap(0, 1, USER_BALANCE / 4),
+            swap(1, 0, USER_BALANCE / 4),
+            swap(0, 1, USER_BALANCE / 4),
+            swap(1, 0, USER_BALANCE / 4),
+        ]
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn test_failing_instruction_reverts_whole_transaction() {
+    let (result, before, after) = execute_in_one_transaction(vec![
+        add(),
+        swap(0, 1, USER_BALANCE / 4),
+        DeFiInstruction::SwapExactInput {
+            exact_input_amounts: single(1, USER_BALANCE / 4),
+            output_token_index: 0,
+            minimum_output_amount: USER_BALANCE,
+        },
+    ])
+    .await;
+
+    assert_eq!(
+        result,
+        Err(InstructionError::Custom(PoolError::MinimumOutputNotMet as u32))
+    );
+    assert_eq!(after, before);
+}
@@ -0,0 +1,62 @@
+#![cfg(feature = "test-bpf")]
+
+mod helpers;
+
+use helpers::bank::*;
+
+use pool::{error::PoolError, instruction::GovernanceInstruction, state::MIN_LOCK_DURATION, token_2022, TOKEN_COUNT};
+use solana_program::pubkey::Pubkey;
+use solana_program_test::*;
+use solana_sdk::{instruction::InstructionError, signature::Signer};
+
+async fn non_transferable_pool() -> BankPool {
+    let params = BankPoolParams::default();
+    let mut pool = BankPool::new_uninitialized(&params).await;
+    pool.use_token_2022_lp_mint(true).await;
+    let init_ix = pool.init_ix(&params);
+    pool.execute_transaction(init_ix, &[]).await.unwrap();
+    pool
+}
+
+#[tokio::test]
+async fn test_init_with_non_transferable_lp_mint() {
+    let mut pool = non_transferable_pool().await;
+    let state = pool.state().await;
+    assert!(state.lp_non_transferable);
+    assert_eq!(state.lp_token_program_id(), token_2022::id());
+
+    //a Token-2022 lp mint that doesn't enforce non-transferability itself is rejected
+    let params = BankPoolParams::default();
+    let mut pool = BankPool::new_uninitialized(&params).await;
+    pool.use_token_2022_lp_mint(false).await;
+    let init_ix = pool.init_ix(&params);
+    assert_eq!(
+        pool.execute_transaction(init_ix, &[]).await,
+        Err(InstructionError::Custom(PoolError::InvalidMintAccount as u32))
+    );
+}
+
+#[tokio::test]
+async fn test_non_transferable_lp_tokens_stay_put() {
+    let mut pool = non_transferable_pool().await;
+    let user = pool.create_user(&[0; TOKEN_COUNT]);
+    assert_eq!(
+        pool.lock_lp(&user, 1, MIN_LOCK_DURATION).await,
+        Err(InstructionError::Custom(PoolError::LpNonTransferable as u32))
+    );
+
+    let (old_fee_account, lp_mint) = (pool.governance_fee_account, pool.lp_mint);
+    let governance = pool.governance_keypair.pubkey();
+    let new_fee_account = Pubkey::new_unique();
+    pool.set_token_account(&new_fee_account, &lp_mint, &governance, 0);
+    assert_eq!(
+        pool.execute_governance_instruction(
+            GovernanceInstruction::MigrateGovernanceFeeAccount {
+                governance_fee_key: new_fee_account,
+            },
+            Some(&old_fee_account),
+        )
+        .await,
+        Err(InstructionError::Custom(PoolError::LpNonTransferable as u32))
+    );
+}
@@ -0,0 +1,644 @@
+#![cfg(feature = "test-bpf")]
+
+mod helpers;
+
+use helpers::{bank::*, AmountT, DecT};
+
+use pool::{
+    amp_factor::DAMPING_WINDOW,
+    common::create_array,
+    error::PoolError,
+    instruction::{DeFiInstruction, GovernanceInstruction},
+    oracle::{OracleFallback, OracleKind, OracleLimits, TokenOracle, EMA_ONE, EMA_WINDOW},
+    processor::ENACT_DELAY,
+    TOKEN_COUNT,
+};
+use solana_program_test::*;
+use solana_sdk::{instruction::InstructionError, pubkey::Pubkey, signature::Keypair};
+
+const POOL_BALANCE: AmountT = 1_000_000_000;
+const AT_PEG: (i64, i32) = (100_000_000, -8);
+
+fn pool_error(error: PoolError) -> InstructionError {
+    InstructionError::Custom(error as u32)
+}
+
+fn swap(input_index: usize, output_index: usize, amount: AmountT) -> DeFiInstruction<TOKEN_COUNT> {
+    let mut exact_input_amounts = [0; TOKEN_COUNT];
+    exact_input_amounts[input_index] = amount;
+    DeFiInstruction::SwapExactInput {
+        exact_input_amounts,
+        output_token_index: output_index as u8,
+        minimum_output_amount: 0,
+    }
+}
+
+async fn funded_pool() -> (BankPool, BankUser) {
+    let mut pool = BankPool::new(&BankPoolParams::default()).await.unwrap();
+    let user = pool.create_user(&[POOL_BALANCE; TOKEN_COUNT]);
+    pool.execute_defi_instruction(
+        DeFiInstruction::Add {
+            input_amounts: [POOL_BALANCE / 2; TOKEN_COUNT],
+            minimum_mint_amount: 0,
+        },
+        &user,
+    )
+    .await
+    .unwrap();
+    (pool, user)
+}
+
+#[tokio::test]
+async fn test_oracle_guard_rejects_swaps_of_depegged_token() {
+    let (mut pool, user) = funded_pool().await;
+    let one_percent = DecT::new(1, 2).unwrap();
+    let oracle_keys = pool
+        .set_oracle_guard(&[AT_PEG; TOKEN_COUNT], one_percent)
+        .await
+        .unwrap();
+    let state = pool.state().await;
+    assert_eq!(state.oracle_keys, oracle_keys);
+    assert_eq!(state.max_oracle_deviation.get(), one_percent);
+
+    pool.execute_defi_instruction(swap(0, 1, POOL_BALANCE / 100), &user)
+        .await
+        .unwrap();
+
+    //token 0 collapses to half its value: selling it at the pool's price would drain the other tokens and buying it
+    // would cost twice what it's worth
+    pool.set_pyth_price(&oracle_keys[0], AT_PEG.0 / 2, AT_PEG.1).await;
+    assert_eq!(
+        pool.execute_defi_instruction(swap(0, 1, POOL_BALANCE / 100), &user)
+            .await,
+        Err(pool_error(PoolError::OracleDeviationExceeded))
+    );
+    let mut exact_output_amounts = [0; TOKEN_COUNT];
+    exact_output_amounts[0] = POOL_BALANCE / 100;
+    assert_eq!(
+        pool.execute_defi_instruction(
+            DeFiInstruction::SwapExactOutput {
+                maximum_input_amount: AmountT::MAX,
+                input_token_index: 1,
+                exact_output_amounts,
+            },
+            &user,
+        )
+        .await,
+        Err(pool_error(PoolError::OracleDeviationExceeded))
+    );
+    //swaps between the remaining tokens are unaffected
+    pool.execute_defi_instruction(swap(1, 2, POOL_BALANCE / 100), &user)
+        .await
+        .unwrap();
+    //so are adds and removes
+    pool.execute_defi_instruction(
+        DeFiInstruction::RemoveUniform {
+            exact_burn_amount: 1000,
+            minimum_output_amounts: [0; TOKEN_COUNT],
+        },
+        &user,
+    )
+    .await
+    .unwrap();
+
+    //turning the guard off allows the swap again, the tokens keep their oracles
+    pool.execute_governance_instruction(
+        GovernanceInstruction::SetOracleGuard {
+            max_deviation: DecT::from(0),
+        },
+        None,
+    )
+    .await
+    .unwrap();
+    pool.oracle_keys = None;
+    let state = pool.state().await;
+    assert!(!state.is_oracle_guarded());
+    assert_eq!(state.oracle_keys, oracle_keys);
+    pool.execute_defi_instruction(swap(0, 1, POOL_BALANCE / 100), &user)
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn test_oracle_guard_requires_every_swap_to_pass_the_oracles() {
+    let (mut pool, user) = funded_pool().await;
+    pool.set_oracle_guard(&[AT_PEG; TOKEN_COUNT], DecT::new(1, 2).unwrap())
+        .await
+        .unwrap();
+
+    let mut ix = pool.defi_ix(swap(0, 1, POOL_BALANCE / 100), &user);
+    ix.accounts.truncate(ix.accounts.len() - TOKEN_COUNT);
+    assert_eq!(
+        pool.execute_transaction(ix, &[&user.keypair]).await,
+        Err(InstructionError::NotEnoughAccountKeys)
+    );
+}
+
+#[tokio::test]
+async fn test_set_oracle_guard_validation() {
+    let (mut pool, _) = funded_pool().await;
+    let one_percent = DecT::new(1, 2).unwrap();
+    //the guard needs every token to have an oracle, turning it off doesn't
+    assert_eq!(
+        pool.execute_governance_instruction(
+            GovernanceInstruction::SetOracleGuard {
+                max_deviation: one_percent
+            },
+            None
+        )
+        .await,
+        Err(pool_error(PoolError::InvalidOracleAccount))
+    );
+    pool.execute_governance_instruction(
+        GovernanceInstruction::SetOracleGuard {
+            max_deviation: DecT::from(0),
+        },
+        None,
+    )
+    .await
+    .unwrap();
+
+    let oracle_keys = pool
+        .set_oracle_guard(&[AT_PEG; TOKEN_COUNT], one_percent)
+        .await
+        .unwrap();
+    assert_eq!(pool.state().await.oracle_keys, oracle_keys);
+    assert_eq!(
+        pool.execute_governance_instruction_as(
+            GovernanceInstruction::SetOracleGuard {
+                max_deviation: DecT::new(5, 2).unwrap(),
+            },
+            None,
+            &Keypair::new(),
+        )
+        .await,
+        Err(pool_error(PoolError::InvalidGovernanceAccount))
+    );
+}
+
+#[tokio::test]
+async fn test_check_peg_pauses_swaps_of_depegged_token() {
+    let (mut pool, user) = funded_pool().await;
+    let oracle_keys = pool
+        .set_oracle_guard(&[AT_PEG; TOKEN_COUNT], DecT::new(5, 2).unwrap())
+        .await
+        .unwrap();
+    //checking the peg does nothing until governance sets a band
+    pool.set_pyth_price(&oracle_keys[0], AT_PEG.0 / 2, AT_PEG.1).await;
+    pool.check_peg().await.unwrap();
+    assert_eq!(pool.state().await.depegged_tokens, [false; TOKEN_COUNT]);
+    pool.execute_governance_instruction(
+        GovernanceInstruction::SetPegBand {
+            band: DecT::new(1, 2).unwrap(),
+        },
+        None,
+    )
+    .await
+    .unwrap();
+
+    //within the band nothing changes
+    pool.set_pyth_price(&oracle_keys[0], AT_PEG.0 * 995 / 1000, AT_PEG.1)
+        .await;
+    pool.check_peg().await.unwrap();
+    assert_eq!(pool.state().await.depegged_tokens, [false; TOKEN_COUNT]);
+
+    //anyone can pause swaps of a token that is off its peg, even though its swaps are still within the max deviation
+    pool.set_pyth_price(&oracle_keys[0], AT_PEG.0 * 97 / 100, AT_PEG.1)
+        .await;
+    pool.check_peg().await.unwrap();
+    let mut depegged_tokens = [false; TOKEN_COUNT];
+    depegged_tokens[0] = true;
+    assert_eq!(pool.state().await.depegged_tokens, depegged_tokens);
+    for (input_index, output_index) in [(0, 1), (1, 0)] {
+        assert_eq!(
+            pool.execute_defi_instruction(swap(input_index, output_index, POOL_BALANCE / 100), &user)
+                .await,
+            Err(pool_error(PoolError::TokenDepegged))
+        );
+    }
+    pool.execute_defi_instruction(swap(1, 2, POOL_BALANCE / 100), &user)
+        .await
+        .unwrap();
+
+    //swaps stay paused after the price recovers until governance resumes them
+    pool.set_pyth_price(&oracle_keys[0], AT_PEG.0, AT_PEG.1).await;
+    pool.check_peg().await.unwrap();
+    assert_eq!(pool.state().await.depegged_tokens, depegged_tokens);
+    pool.execute_governance_instruction(
+        GovernanceInstruction::SetPegBand {
+            band: DecT::new(1, 2).unwrap(),
+        },
+        None,
+    )
+    .await
+    .unwrap();
+    pool.execute_defi_instruction(swap(0, 1, POOL_BALANCE / 100), &user)
+        .await
+        .unwrap();
+}
+
+//(re)binds pyth oracles quoting the peg with new limits, which moves the clock past an enact delay per token, and
+// publishes fresh readings afterwards
+async fn set_oracle_limits(pool: &mut BankPool, oracle_keys: &[Pubkey; TOKEN_COUNT], max_age: u64, max_conf_bps: u16) {
+    let limits = OracleLimits { max_age, max_conf_bps };
+    for (i, oracle_key) in oracle_keys.iter().enumerate() {
+        pool.set_pyth_price(oracle_key, AT_PEG.0, AT_PEG.1).await;
+        pool.set_token_oracle(i as u8, OracleKind::Pyth, oracle_key, limits)
+            .await
+            .unwrap();
+    }
+    for oracle_key in oracle_keys.iter() {
+        pool.set_pyth_price(oracle_key, AT_PEG.0, AT_PEG.1).await;
+    }
+}
+
+async fn set_oracle_fallback(pool: &mut BankPool, fallback: OracleFallback) {
+    pool.execute_governance_instruction(GovernanceInstruction::SetOracleFallback { fallback }, None)
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn test_oracle_limits() {
+    let (mut pool, user) = funded_pool().await;
+    let oracle_keys = pool
+        .set_oracle_guard(&[AT_PEG; TOKEN_COUNT], DecT::new(1, 2).unwrap())
+        .await
+        .unwrap();
+    set_oracle_limits(&mut pool, &oracle_keys, 10, 0).await;
+    assert_eq!(pool.state().await.oracle_limits[0].max_age, 10);
+    pool.execute_defi_instruction(swap(0, 1, POOL_BALANCE / 100), &user)
+        .await
+        .unwrap();
+
+    //token 0 collapses but its oracle stops publishing
+    pool.set_pyth_price(&oracle_keys[0], AT_PEG.0 / 2, AT_PEG.1).await;
+    pool.advance_slots(20).await;
+    for i in 1..TOKEN_COUNT {
+        pool.set_pyth_price(&oracle_keys[i], AT_PEG.0, AT_PEG.1).await;
+    }
+    assert_eq!(
+        pool.execute_defi_instruction(swap(1, 2, POOL_BALANCE / 100), &user)
+            .await,
+        Err(pool_error(PoolError::OraclePriceUnavailable))
+    );
+
+    //a stale price can't pause swaps either
+    pool.execute_governance_instruction(
+        GovernanceInstruction::SetPegBand {
+            band: DecT::new(1, 2).unwrap(),
+        },
+        None,
+    )
+    .await
+    .unwrap();
+    pool.check_peg().await.unwrap();
+    assert_eq!(pool.state().await.depegged_tokens, [false; TOKEN_COUNT]);
+
+    //unless swaps skip the guard while an oracle is unavailable, which lets them trade the collapsed token at par
+    set_oracle_fallback(&mut pool, OracleFallback::IgnoreGuard).await;
+    pool.execute_defi_instruction(swap(0, 1, POOL_BALANCE / 100), &user)
+        .await
+        .unwrap();
+
+    //a price that is too uncertain is just as unavailable as a stale one
+    set_oracle_fallback(&mut pool, OracleFallback::Reject).await;
+    set_oracle_limits(&mut pool, &oracle_keys, 1_000_000, 100).await;
+    pool.execute_defi_instruction(swap(1, 2, POOL_BALANCE / 100), &user)
+        .await
+        .unwrap();
+    let mut unsure = pool
+        .context
+        .banks_client
+        .get_account(oracle_keys[1])
+        .await
+        .unwrap()
+        .unwrap();
+    unsure.data[216..224].copy_from_slice(&(AT_PEG.0 as u64 / 50).to_le_bytes());
+    pool.context.set_account(&oracle_keys[1], &unsure.into());
+    assert_eq!(
+        pool.execute_defi_instruction(swap(1, 2, POOL_BALANCE / 100), &user)
+            .await,
+        Err(pool_error(PoolError::OraclePriceUnavailable))
+    );
+}
+
+#[tokio::test]
+async fn test_internal_oracle_fallback() {
+    let (mut pool, user) = funded_pool().await;
+    let state = pool.state().await;
+    assert_eq!(state.ema_prices, [EMA_ONE; TOKEN_COUNT]);
+    assert_eq!(state.ema_ts, pool.now().await);
+    //the oracles are bound before the guard is turned on, the balanced pool keeps its average at par meanwhile
+    let oracle_keys: [Pubkey; TOKEN_COUNT] = create_array(|_| Pubkey::new_unique());
+    set_oracle_limits(&mut pool, &oracle_keys, 10, 0).await;
+
+    //a large swap moves the pool's price but not the average, which only follows prices that last
+    pool.execute_defi_instruction(swap(0, 1, POOL_BALANCE * 2 / 5), &user)
+        .await
+        .unwrap();
+    let state = pool.state().await;
+    //prices are in units of token 0, which is now cheap
+    assert!(state.last_prices[1] > EMA_ONE / 100 * 105, "{:?}", state.last_prices);
+    assert!(state.last_prices[2] > EMA_ONE);
+    assert_eq!(state.ema_prices, [EMA_ONE; TOKEN_COUNT]);
+    let traded_prices = state.last_prices;
+
+    //with stale external oracles the guard checks swaps against the average instead, so selling token 0 at the
+    // pool's current price deviates too far
+    set_oracle_fallback(&mut pool, OracleFallback::InternalEma).await;
+    pool.execute_governance_instruction(
+        GovernanceInstruction::SetOracleGuard {
+            max_deviation: DecT::new(1, 2).unwrap(),
+        },
+        None,
+    )
+    .await
+    .unwrap();
+    pool.oracle_keys = Some(oracle_keys);
+    pool.advance_slots(20).await;
+    assert_eq!(
+        pool.execute_defi_instruction(swap(0, 1, POOL_BALANCE / 100), &user)
+            .await,
+        Err(pool_error(PoolError::OracleDeviationExceeded))
+    );
+
+    //once the price held for a whole window the average caught up with it (any defi instruction updates it)
+    pool.advance_clock(EMA_WINDOW).await;
+    pool.execute_defi_instruction(
+        DeFiInstruction::Add {
+            input_amounts: [1; TOKEN_COUNT],
+            minimum_mint_amount: 0,
+        },
+        &user,
+    )
+    .await
+    .unwrap();
+    assert_eq!(pool.state().await.ema_prices, traded_prices);
+    pool.execute_defi_instruction(swap(0, 1, POOL_BALANCE / 100), &user)
+        .await
+        .unwrap();
+
+    //fresh external prices take precedence again
+    for oracle_key in oracle_keys.iter() {
+        pool.set_pyth_price(oracle_key, AT_PEG.0, AT_PEG.1).await;
+    }
+    assert_eq!(
+        pool.execute_defi_instruction(swap(0, 1, POOL_BALANCE / 100), &user)
+            .await,
+        Err(pool_error(PoolError::OracleDeviationExceeded))
+    );
+}
+
+#[tokio::test]
+async fn test_set_peg_band_requires_oracle_guard() {
+    let (mut pool, _) = funded_pool().await;
+    assert_eq!(
+        pool.execute_governance_instruction(
+            GovernanceInstruction::SetPegBand {
+                band: DecT::new(1, 2).unwrap(),
+            },
+            None,
+        )
+        .await,
+        Err(pool_error(PoolError::InvalidOracleAccount))
+    );
+    pool.set_oracle_guard(&[AT_PEG; TOKEN_COUNT], DecT::new(1, 2).unwrap())
+        .await
+        .unwrap();
+    pool.execute_governance_instruction(
+        GovernanceInstruction::SetPegBand {
+            band: DecT::new(1, 2).unwrap(),
+        },
+        None,
+    )
+    .await
+    .unwrap();
+
+    //checking the peg requires the pool's oracles
+    let oracle_keys = pool.oracle_keys.take();
+    assert_eq!(pool.check_peg().await, Err(pool_error(PoolError::InvalidOracleAccount)));
+    pool.oracle_keys = oracle_keys;
+    pool.check_peg().await.unwrap();
+}
+
+fn prepare_token_oracle(
+    token_index: usize,
+    oracle_kind: OracleKind,
+    oracle_key: Pubkey,
+    max_age: u64,
+    max_conf_bps: u16,
+) -> GovernanceInstruction<TOKEN_COUNT> {
+    GovernanceInstruction::SetTokenOracle {
+        token_index: token_index as u8,
+        oracle_kind,
+        oracle_key,
+        max_age,
+        max_conf_bps,
+    }
+}
+
+#[tokio::test]
+async fn test_set_token_oracle() {
+    let (mut pool, user) = funded_pool().await;
+    //the last token is guarded by a switchboard aggregator, all others by pyth
+    let switchboard_index = TOKEN_COUNT - 1;
+    let oracle_keys: [Pubkey; TOKEN_COUNT] = create_array(|_| Pubkey::new_unique());
+    for i in 0..switchboard_index {
+        pool.set_pyth_price(&oracle_keys[i], AT_PEG.0, AT_PEG.1).await;
+    }
+    pool.set_switchboard_result(&oracle_keys[switchboard_index], 100, 2);
+
+    assert_eq!(
+        pool.execute_governance_instruction(GovernanceInstruction::EnactTokenOracle {}, None)
+            .await,
+        Err(pool_error(PoolError::InvalidEnact))
+    );
+    //the account has to belong to the program of the given kind
+    assert_eq!(
+        pool.execute_governance_instruction(
+            prepare_token_oracle(0, OracleKind::Switchboard, oracle_keys[0], 1000, 100),
+            None
+        )
+        .await,
+        Err(pool_error(PoolError::InvalidOracleAccount))
+    );
+    assert_eq!(
+        pool.execute_governance_instruction(
+            prepare_token_oracle(0, OracleKind::Pyth, pool.token_accounts[0], 1000, 100),
+            None
+        )
+        .await,
+        Err(pool_error(PoolError::InvalidOracleAccount))
+    );
+    //readings need a max age and a confidence interval no wider than the reading
+    for (max_age, max_conf_bps) in [(0, 100), (1000, 10_001)] {
+        assert_eq!(
+            pool.execute_governance_instruction(
+                prepare_token_oracle(0, OracleKind::Pyth, oracle_keys[0], max_age, max_conf_bps),
+                None
+            )
+            .await,
+            Err(pool_error(PoolError::InvalidOracleLimits))
+        );
+    }
+
+    for i in 0..TOKEN_COUNT {
+        let kind = if i == switchboard_index {
+            OracleKind::Switchboard
+        } else {
+            OracleKind::Pyth
+        };
+        let prepare_ts = pool.now().await;
+        pool.execute_governance_instruction(prepare_token_oracle(i, kind, oracle_keys[i], 1000, 100), None)
+            .await
+            .unwrap();
+        let state = pool.state().await;
+        assert_eq!(state.oracle_keys[i], Pubkey::default());
+        assert_eq!(state.oracle_transition_ts, prepare_ts + ENACT_DELAY);
+
+        pool.set_now(prepare_ts + ENACT_DELAY - 1).await;
+        assert_eq!(
+            pool.execute_governance_instruction(GovernanceInstruction::EnactTokenOracle {}, None)
+                .await,
+            Err(pool_error(PoolError::InsufficientDelay))
+        );
+        pool.set_now(prepare_ts + ENACT_DELAY).await;
+        pool.execute_governance_instruction(GovernanceInstruction::EnactTokenOracle {}, None)
+            .await
+            .unwrap();
+        let state = pool.state().await;
+        assert_eq!(
+            (state.oracle_keys[i], state.oracle_kinds[i], state.oracle_limits[i]),
+            (
+                oracle_keys[i],
+                kind,
+                OracleLimits {
+                    max_age: 1000,
+                    max_conf_bps: 100
+                }
+            )
+        );
+        assert_eq!(state.prepared_token_oracle, TokenOracle::default());
+        assert_eq!(state.oracle_transition_ts, 0);
+    }
+    //readings age while the enact delay passes
+    for i in 0..switchboard_index {
+        pool.set_pyth_price(&oracle_keys[i], AT_PEG.0, AT_PEG.1).await;
+    }
+    let state = pool.state().await;
+    assert!(state.has_token_oracles() && !state.is_oracle_guarded());
+
+    //setting the max deviation checks swaps against the bound oracles of either kind
+    pool.execute_governance_instruction(
+        GovernanceInstruction::SetOracleGuard {
+            max_deviation: DecT::new(1, 2).unwrap(),
+        },
+        None,
+    )
+    .await
+    .unwrap();
+    pool.oracle_keys = Some(oracle_keys);
+    assert_eq!(
+        pool.state().await.oracle_kinds[switchboard_index],
+        OracleKind::Switchboard
+    );
+    pool.execute_defi_instruction(swap(0, switchboard_index, POOL_BALANCE / 100), &user)
+        .await
+        .unwrap();
+
+    //the switchboard oracle reports a depeg of its token
+    pool.set_switchboard_result(&oracle_keys[switchboard_index], 50, 2);
+    assert_eq!(
+        pool.execute_defi_instruction(swap(0, switchboard_index, POOL_BALANCE / 100), &user)
+            .await,
+        Err(pool_error(PoolError::OracleDeviationExceeded))
+    );
+
+    //removing an oracle turns the guard off
+    let prepare_ts = pool.now().await;
+    pool.execute_governance_instruction(
+        prepare_token_oracle(0, OracleKind::Pyth, Pubkey::default(), 1000, 100),
+        None,
+    )
+    .await
+    .unwrap();
+    pool.set_now(prepare_ts + ENACT_DELAY).await;
+    pool.execute_governance_instruction(GovernanceInstruction::EnactTokenOracle {}, None)
+        .await
+        .unwrap();
+    assert!(!pool.state().await.is_oracle_guarded());
+    pool.oracle_keys = None;
+    pool.execute_defi_instruction(swap(0, switchboard_index, POOL_BALANCE / 100), &user)
+        .await
+        .unwrap();
+}
+
+fn set_amp_damping(
+    factor: DecT,
+    trigger_band: DecT,
+    release_band: DecT,
+    delay: i64,
+) -> GovernanceInstruction<TOKEN_COUNT> {
+    GovernanceInstruction::SetAmpDamping {
+        factor,
+        trigger_band,
+        release_band,
+        delay,
+    }
+}
+
+#[tokio::test]
+async fn test_amp_damping() {
+    let (mut pool, _) = funded_pool().await;
+    let (half, two_percent, one_percent) = (
+        DecT::new(5, 1).unwrap(),
+        DecT::new(2, 2).unwrap(),
+        DecT::new(1, 2).unwrap(),
+    );
+    assert_eq!(
+        pool.execute_governance_instruction(set_amp_damping(half, two_percent, one_percent, 60), None)
+            .await,
+        Err(pool_error(PoolError::InvalidOracleAccount))
+    );
+    let oracle_keys = pool
+        .set_oracle_guard(&[AT_PEG; TOKEN_COUNT], DecT::new(5, 2).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(
+        pool.execute_governance_instruction(set_amp_damping(half, one_percent, two_percent, 60), None)
+            .await,
+        Err(pool_error(PoolError::InvalidAmpFactorValue))
+    );
+    pool.execute_governance_instruction(set_amp_damping(half, two_percent, one_percent, 60), None)
+        .await
+        .unwrap();
+    let amp_factor = BankPoolParams::default().amp_factor;
+
+    //the depeg has to last for the delay
+    pool.set_pyth_price(&oracle_keys[1], 97_000_000, -8).await;
+    pool.check_peg().await.unwrap();
+    assert!(!pool.state().await.amp_damping.is_damping());
+    pool.advance_clock(60).await;
+    pool.check_peg().await.unwrap();
+    let state = pool.state().await;
+    assert!(state.amp_damping.is_damping());
+    //the peg band is off, so swaps of the token aren't paused
+    assert!(!state.depegged_tokens[1]);
+
+    pool.advance_clock(DAMPING_WINDOW).await;
+    let now = pool.now().await;
+    assert_eq!(pool.state().await.effective_amp_factor(now), amp_factor * half);
+
+    //within the trigger but not the release band
+    pool.set_pyth_price(&oracle_keys[1], 98_500_000, -8).await;
+    pool.check_peg().await.unwrap();
+    assert!(pool.state().await.amp_damping.is_damping());
+
+    pool.set_pyth_price(&oracle_keys[1], AT_PEG.0, AT_PEG.1).await;
+    pool.check_peg().await.unwrap();
+    assert!(!pool.state().await.amp_damping.is_damping());
+    pool.advance_clock(DAMPING_WINDOW).await;
+    let now = pool.now().await;
+    assert_eq!(pool.state().await.effective_amp_factor(now), amp_factor);
+}
@@ -0,0 +1,82 @@
+#![cfg(feature = "test-bpf")]
+
+mod helpers;
+
+use helpers::{bank::*, AmountT};
+
+use pool::{
+    client::otc::{create_otc_ixs, OtcTransfer},
+    instruction::DeFiInstruction,
+    TOKEN_COUNT,
+};
+use solana_program_test::*;
+use solana_sdk::{instruction::InstructionError, signature::Signer};
+use spl_token::error::TokenError;
+
+const POOL_BALANCE: AmountT = 1_000_000_000;
+const LP_AMOUNT: AmountT = 60_000_000;
+
+#[tokio::test]
+async fn test_otc_trade_at_suggested_price() {
+    let mut pool = BankPool::new(&BankPoolParams::default()).await.unwrap();
+    let seller = pool.create_user(&[POOL_BALANCE; TOKEN_COUNT]);
+    pool.execute_defi_instruction(
+        DeFiInstruction::Add {
+            input_amounts: [POOL_BALANCE; TOKEN_COUNT],
+            minimum_mint_amount: 0,
+        },
+        &seller,
+    )
+    .await
+    .unwrap();
+
+    //a balanced pool's lp tokens are worth a token each, removing them into a single token costs fees
+    let price = pool.snapshot().await.suggest_lp_price(LP_AMOUNT, 0).unwrap();
+    assert!(
+        price.fair_amount <= LP_AMOUNT && price.fair_amount > LP_AMOUNT / 1000 * 999,
+        "fair amount {}",
+        price.fair_amount
+    );
+    assert!(price.redeem_amount < price.fair_amount);
+
+    let mut buyer_balances = [0; TOKEN_COUNT];
+    buyer_balances[0] = price.fair_amount;
+    let buyer = pool.create_user(&buyer_balances);
+    let trade = |payment_amount| {
+        create_otc_ixs(
+            &OtcTransfer {
+                token_program: spl_token::id(),
+                source: seller.lp,
+                destination: buyer.lp,
+                owner: seller.keypair.pubkey(),
+                amount: LP_AMOUNT,
+            },
+            &OtcTransfer {
+                token_program: spl_token::id(),
+                source: buyer.tokens[0],
+                destination: seller.tokens[0],
+                owner: buyer.keypair.pubkey(),
+                amount: payment_amount,
+            },
+        )
+        .unwrap()
+    };
+    let seller_lp = pool.balance(&seller.lp).await;
+
+    //either both sides of the trade happen or neither does
+    assert_eq!(
+        pool.execute_instructions(&trade(price.fair_amount + 1), &[&seller.keypair, &buyer.keypair])
+            .await,
+        Err(InstructionError::Custom(TokenError::InsufficientFunds as u32))
+    );
+    assert_eq!(pool.balance(&seller.lp).await, seller_lp);
+    assert_eq!(pool.balance(&buyer.lp).await, 0);
+
+    pool.execute_instructions(&trade(price.fair_amount), &[&seller.keypair, &buyer.keypair])
+        .await
+        .unwrap();
+    assert_eq!(pool.balance(&seller.lp).await, seller_lp - LP_AMOUNT);
+    assert_eq!(pool.balance(&buyer.lp).await, LP_AMOUNT);
+    assert_eq!(pool.balance(&seller.tokens[0]).await, price.fair_amount);
+    assert_eq!(pool.balance(&buyer.tokens[0]).await, 0);
+}
@@ -0,0 +1,252 @@
+#![cfg(feature = "test-bpf")]
+
+//pushes pools to the edge of the u64 range in combination with the maximum decimal difference between tokens
+// (i.e. equalizers that upshift by 10^8) and runs every DeFi instruction against them
+//
+//an instruction may legitimately refuse to handle such amounts, but it has to do so with a proper error rather than
+// by panicking (which under BPF surfaces as ProgramFailedToComplete), and if it succeeds its results must be sane
+//
+//the matrix is ignored for as long as the math panics, run it with `cargo test-bpf -- --ignored`
+
+mod helpers;
+
+use helpers::{bank::*, AmountT};
+
+use pool::{instruction::DeFiInstruction, processor::MAX_DECIMAL_DIFFERENCE, TOKEN_COUNT};
+use solana_program_test::*;
+use solana_sdk::instruction::InstructionError;
+
+const BALANCE_LEVELS: [AmountT; 4] = [
+    u64::MAX,
+    u64::MAX / 2,
+    u64::MAX / TOKEN_COUNT as u64,
+    1_000_000_000_000_000_000,
+];
+
+#[derive(Debug, Clone, Copy)]
+enum Variant {
+    Add,
+    SwapExactInput,
+    SwapExactOutput,
+    RemoveUniform,
+    RemoveExactBurn,
+    RemoveExactOutput,
+}
+
+const VARIANTS: [Variant; 6] = [
+    Variant::Add,
+    Variant::SwapExactInput,
+    Variant::SwapExactOutput,
+    Variant::RemoveUniform,
+    Variant::RemoveExactBurn,
+    Variant::RemoveExactOutput,
+];
+
+fn uniform_decimals() -> BankPoolParams {
+    BankPoolParams::default()
+}
+
+//every token but the first gets upshifted by 10^8, as does the lp token
+fn upshifted_token_decimals() -> BankPoolParams {
+    let mut token_decimals = [0; TOKEN_COUNT];
+    token_decimals[0] = MAX_DECIMAL_DIFFERENCE;
+    BankPoolParams {
+        lp_decimals: 0,
+        token_decimals,
+        ..BankPoolParams::default()
+    }
+}
+
+//only the lp token gets upshifted by 10^8
+fn upshifted_lp_decimals() -> BankPoolParams {
+    BankPoolParams {
+        lp_decimals: 0,
+        token_decimals: [MAX_DECIMAL_DIFFERENCE; TOKEN_COUNT],
+        ..BankPoolParams::default()
+    }
+}
+
+fn assert_clean(result: &Result<(), InstructionError>, context: &str) {
+    match result {
+        Ok(()) => (),
+        Err(InstructionError::ProgramFailedToComplete) => panic!("{}: program panicked", context),
+        Err(InstructionError::ComputationalBudgetExceeded) => panic!("{}: exceeded compute budget", context),
+        Err(_) => (),
+    }
+}
+
+//seeds a pool where every balance equals `level` (or reports why that's impossible)
+async fn seeded_pool(params: &BankPoolParams, level: AmountT, context: &str) -> Option<(BankPool, BankUser)> {
+    let mut pool = BankPool::new(params).await.unwrap();
+    let seeder = pool.create_user(&[level; TOKEN_COUNT]);
+    let result = pool
+        .execute_defi_instruction(
+            DeFiInstruction::Add {
+                input_amounts: [level; TOKEN_COUNT],
+                minimum_mint_amount: 0,
+            },
+            &seeder,
+        )
+        .await;
+    assert_clean(&result, &format!("{} seeding", context));
+    match result {
+        Ok(()) => {
+            assert_eq!(pool.pool_balances().await, [level; TOKEN_COUNT]);
+            assert!(
+                pool.balance(&seeder.lp).await > 0,
+                "{}: seeding minted no lp tokens",
+                context
+            );
+            Some((pool, seeder))
+        }
+        Err(err) => {
+            println!("{}: seeding rejected with {:?}", context, err);
+            None
+        }
+    }
+}
+
+async fn run_variant(pool: &mut BankPool, seeder: &BankUser, variant: Variant, level: AmountT, context: &str) {
+    let trader = pool.create_user(&[level; TOKEN_COUNT]);
+    let seeder_lp = pool.balance(&seeder.lp).await;
+    let last = TOKEN_COUNT - 1;
+
+    let (user, instruction) = match variant {
+        Variant::Add => (
+            &trader,
+            DeFiInstruction::Add {
+                input_amounts: [level; TOKEN_COUNT],
+                minimum_mint_amount: 0,
+            },
+        ),
+        Variant::SwapExactInput => {
+            let mut exact_input_amounts = [0; TOKEN_COUNT];
+            exact_input_amounts[0] = level;
+            (
+                &trader,
+                DeFiInstruction::SwapExactInput {
+                    exact_input_amounts,
+                    output_token_index: last as u8,
+                    minimum_output_amount: 0,
+                },
+            )
+        }
+        Variant::SwapExactOutput => {
+            let mut exact_output_amounts = [0; TOKEN_COUNT];
+            exact_output_amounts[last] = level / 2;
+            (
+                &trader,
+                DeFiInstruction::SwapExactOutput {
+                    maximum_input_amount: AmountT::MAX,
+                    input_token_index: 0,
+                    exact_output_amounts,
+                },
+            )
+        }
+        Variant::RemoveUniform => (
+            seeder,
+            DeFiInstruction::RemoveUniform {
+                exact_burn_amount: seeder_lp / 2,
+                minimum_output_amounts: [0; TOKEN_COUNT],
+            },
+        ),
+        Variant::RemoveExactBurn => (
+            seeder,
+            DeFiInstruction::RemoveExactBurn {
+                exact_burn_amount: seeder_lp / 2,
+                output_token_index: 0,
+                minimum_output_amount: 0,
+            },
+        ),
+        Variant::RemoveExactOutput => {
+            let mut exact_output_amounts = [0; TOKEN_COUNT];
+            exact_output_amounts[0] = level / 2;
+            (
+                seeder,
+                DeFiInstruction::RemoveExactOutput {
+                    maximum_burn_amount: seeder_lp,
+                    exact_output_amounts,
+                },
+            )
+        }
+    };
+
+    let user_balances_before = pool.balances(&user.tokens).await;
+    let user_lp_before = pool.balance(&user.lp).await;
+    let lp_supply_before = pool.lp_total_supply().await;
+
+    let result = pool.execute_defi_instruction(instruction, user).await;
+    assert_clean(&result, context);
+    if let Err(err) = result {
+        println!("{}: rejected with {:?}", context, err);
+        return;
+    }
+
+    let user_balances_after = pool.balances(&user.tokens).await;
+    let user_lp_after = pool.balance(&user.lp).await;
+    let lp_supply_after = pool.lp_total_supply().await;
+    let paid = |i: usize| user_balances_before[i].saturating_sub(user_balances_after[i]);
+    let received = |i: usize| user_balances_after[i].saturating_sub(user_balances_before[i]);
+
+    match variant {
+        Variant::Add => {
+            assert!(user_lp_after > user_lp_before, "{}: add minted nothing", context);
+            assert!(lp_supply_after > lp_supply_before, "{}: lp supply didn't grow", context);
+        }
+        Variant::SwapExactInput => {
+            assert_eq!(paid(0), level, "{}: wrong input amount", context);
+            assert!(received(last) < level, "{}: output exceeds input", context);
+        }
+        Variant::SwapExactOutput => {
+            assert_eq!(received(last), level / 2, "{}: wrong output amount", context);
+            assert!(paid(0) > level / 2, "{}: input doesn't exceed output", context);
+        }
+        Variant::RemoveUniform | Variant::RemoveExactBurn => {
+            assert_eq!(
+                user_lp_before - user_lp_after,
+                seeder_lp / 2,
+                "{}: wrong burn amount",
+                context
+            );
+            assert!(
+                (0..TOKEN_COUNT).any(|i| received(i) > 0),
+                "{}: burned lp for nothing",
+                context
+            );
+            assert!(received(0) <= level, "{}: received more than the pool held", context);
+        }
+        Variant::RemoveExactOutput => {
+            assert_eq!(received(0), level / 2, "{}: wrong output amount", context);
+            assert!(user_lp_after < user_lp_before, "{}: burned nothing", context);
+        }
+    }
+}
+
+async fn run_matrix(params: BankPoolParams, name: &str) {
+    for &level in BALANCE_LEVELS.iter() {
+        for &variant in VARIANTS.iter() {
+            let context = format!("{} decimals, balances {}, {:?}", name, level, variant);
+            if let Some((mut pool, seeder)) = seeded_pool(&params, level, &context).await {
+                run_variant(&mut pool, &seeder, variant, level, &context).await;
+            }
+        }
+    }
+}
+
+#[tokio::test]
+#[ignore = "the invariant math still panics (rust_decimal/U128 overflow) at these magnitudes"]
+async fn test_extreme_balances_uniform_decimals() {
+    run_matrix(uniform_decimals(), "uniform").await;
+}
+
+#[tokio::test]
+#[ignore = "the invariant math still panics (rust_decimal/U128 overflow) at these magnitudes"]
+async fn test_extreme_balances_upshifted_tokens() {
+    run_matrix(upshifted_token_decimals(), "upshifted token").await;
+}
+
+#[tokio::test]
+#[ignore = "the invariant math still panics (rust_decimal/U128 overflow) at these magnitudes"]
+async fn test_extreme_balances_upshifted_lp() {
+    run_matrix(upshifted_lp_decimals(), "upshifted lp").await;
+}
@@ -0,0 +1,193 @@
+#![cfg(feature = "test-bpf")]
+
+mod helpers;
+
+use helpers::{bank::*, AmountT};
+
+use pool::{
+    error::PoolError,
+    instruction::{DeFiInstruction, GovernanceInstruction},
+    processor::ENACT_DELAY,
+    state::PegKeeper,
+    weighted::WEIGHT_ONE,
+    TOKEN_COUNT,
+};
+use solana_program_test::*;
+use solana_sdk::{
+    instruction::InstructionError,
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+};
+
+const POOL_BALANCE: AmountT = 1_000_000_000;
+//the token that the keeper keeps at its peg
+const PEGGED: usize = 0;
+
+fn pool_error(error: PoolError) -> InstructionError {
+    InstructionError::Custom(error as u32)
+}
+
+fn swap(input_token_index: usize, output_token_index: usize, amount: AmountT) -> DeFiInstruction<TOKEN_COUNT> {
+    let mut exact_input_amounts = [0; TOKEN_COUNT];
+    exact_input_amounts[input_token_index] = amount;
+    DeFiInstruction::SwapExactInput {
+        exact_input_amounts,
+        output_token_index: output_token_index as u8,
+        minimum_output_amount: 0,
+    }
+}
+
+async fn funded_pool() -> (BankPool, BankUser) {
+    let mut pool = BankPool::new(&BankPoolParams::default()).await.unwrap();
+    let user = pool.create_user(&[3 * POOL_BALANCE; TOKEN_COUNT]);
+    pool.execute_defi_instruction(
+        DeFiInstruction::Add {
+            input_amounts: [POOL_BALANCE; TOKEN_COUNT],
+            minimum_mint_amount: 0,
+        },
+        &user,
+    )
+    .await
+    .unwrap();
+    (pool, user)
+}
+
+#[tokio::test]
+async fn test_set_peg_keeper() {
+    let mut pool = BankPool::new(&BankPoolParams::default()).await.unwrap();
+    let keeper = Pubkey::new_unique();
+    assert_eq!(
+        pool.set_peg_keeper(&keeper, TOKEN_COUNT as u8, POOL_BALANCE).await,
+        Err(InstructionError::InvalidInstructionData)
+    );
+    pool.set_peg_keeper(&keeper, PEGGED as u8, POOL_BALANCE).await.unwrap();
+    assert_eq!(
+        pool.state().await.peg_keeper,
+        PegKeeper {
+            keeper_key: keeper,
+            token_index: PEGGED as u8,
+            credit_limit: POOL_BALANCE,
+            debt: 0,
+            lp_amount: 0,
+        }
+    );
+    pool.set_peg_keeper(&Pubkey::default(), 0, 0).await.unwrap();
+    assert!(!pool.state().await.peg_keeper.is_set());
+
+    //a keeper only gets its credit line after the timelock
+    pool.execute_governance_instruction(
+        GovernanceInstruction::SetPegKeeper {
+            keeper_key: keeper,
+            token_index: PEGGED as u8,
+            credit_limit: POOL_BALANCE,
+        },
+        None,
+    )
+    .await
+    .unwrap();
+    assert_eq!(
+        pool.execute_governance_instruction(GovernanceInstruction::EnactPegKeeper {}, None)
+            .await,
+        Err(pool_error(PoolError::InsufficientDelay))
+    );
+    assert!(!pool.state().await.peg_keeper.is_set());
+    pool.advance_clock(ENACT_DELAY).await;
+    pool.execute_governance_instruction(GovernanceInstruction::EnactPegKeeper {}, None)
+        .await
+        .unwrap();
+    assert_eq!(pool.state().await.peg_keeper.keeper_key, keeper);
+
+    //virtual balances only make sense for tokens of the same value
+    let mut weights = [WEIGHT_ONE / TOKEN_COUNT as u32; TOKEN_COUNT];
+    weights[0] += WEIGHT_ONE % TOKEN_COUNT as u32;
+    let mut weighted_pool = BankPool::new_weighted(&BankPoolParams::default(), weights)
+        .await
+        .unwrap();
+    assert_eq!(
+        weighted_pool.set_peg_keeper(&keeper, PEGGED as u8, POOL_BALANCE).await,
+        Err(pool_error(PoolError::InvalidPegKeeper))
+    );
+}
+
+#[tokio::test]
+async fn test_peg_credit_moves_the_token_towards_its_peg() {
+    let (mut pool, user) = funded_pool().await;
+    let keeper = Keypair::new();
+    pool.set_peg_keeper(&keeper.pubkey(), PEGGED as u8, POOL_BALANCE)
+        .await
+        .unwrap();
+    assert_eq!(
+        pool.use_peg_credit(&Keypair::new(), POOL_BALANCE / 4, true).await,
+        Err(pool_error(PoolError::InvalidPegKeeper))
+    );
+    //the keeper can't lend while the pool holds enough of the token
+    assert_eq!(
+        pool.use_peg_credit(&keeper, POOL_BALANCE / 4, true).await,
+        Err(pool_error(PoolError::PegCreditPastPeg))
+    );
+
+    //users take the token out of the pool, so its price is above the peg
+    pool.execute_defi_instruction(swap(1, PEGGED, POOL_BALANCE / 2), &user)
+        .await
+        .unwrap();
+    let pool_balances = pool.pool_balances().await;
+    pool.use_peg_credit(&keeper, POOL_BALANCE / 4, true).await.unwrap();
+    let peg_keeper = pool.state().await.peg_keeper;
+    assert_eq!(peg_keeper.debt, POOL_BALANCE / 4);
+    assert!(peg_keeper.lp_amount > 0);
+    //nothing is transferred, the pool owes the keeper instead
+    assert_eq!(pool.pool_balances().await, pool_balances);
+    for &(amount, error) in [
+        (POOL_BALANCE, PoolError::PegCreditExceeded),
+        (POOL_BALANCE / 4 * 3, PoolError::PegCreditPastPeg),
+    ]
+    .iter()
+    {
+        assert_eq!(pool.use_peg_credit(&keeper, amount, true).await, Err(pool_error(error)));
+    }
+    assert_eq!(
+        pool.use_peg_credit(&keeper, POOL_BALANCE / 8, false).await,
+        Err(pool_error(PoolError::PegCreditPastPeg))
+    );
+    assert_eq!(
+        pool.set_peg_keeper(&Pubkey::default(), 0, 0).await,
+        Err(pool_error(PoolError::InvalidPegKeeper))
+    );
+
+    //once users put too much of the token into the pool, the keeper absorbs its credit at a lower price than it
+    // provided it at
+    pool.execute_defi_instruction(swap(PEGGED, 1, POOL_BALANCE), &user)
+        .await
+        .unwrap();
+    assert_eq!(
+        pool.use_peg_credit(&keeper, POOL_BALANCE / 4 + 1, false).await,
+        Err(pool_error(PoolError::PegCreditExceeded))
+    );
+    pool.use_peg_credit(&keeper, POOL_BALANCE / 8, false).await.unwrap();
+    let lp_amount = pool.state().await.peg_keeper.lp_amount;
+    assert!(lp_amount > 0 && lp_amount < peg_keeper.lp_amount);
+    pool.use_peg_credit(&keeper, POOL_BALANCE / 8, false).await.unwrap();
+    let peg_keeper = pool.state().await.peg_keeper;
+    assert_eq!((peg_keeper.debt, peg_keeper.lp_amount), (0, 0));
+}
+
+#[tokio::test]
+async fn test_peg_credit_share_is_capped() {
+    let (mut pool, user) = funded_pool().await;
+    let keeper = Keypair::new();
+    //a limit far beyond the pool's depth
+    pool.set_peg_keeper(&keeper.pubkey(), PEGGED as u8, 100 * POOL_BALANCE)
+        .await
+        .unwrap();
+    pool.execute_defi_instruction(swap(1, PEGGED, POOL_BALANCE / 2), &user)
+        .await
+        .unwrap();
+
+    //credit that would be worth more than MAX_PEG_CREDIT_SHARE_BPS of the lp supply fails even though it stays
+    // below the peg
+    assert_eq!(
+        pool.use_peg_credit(&keeper, POOL_BALANCE / 10 * 7, true).await,
+        Err(pool_error(PoolError::PegCreditExceeded))
+    );
+    pool.use_peg_credit(&keeper, POOL_BALANCE / 4, true).await.unwrap();
+}
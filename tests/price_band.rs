@@ -0,0 +1,126 @@
+#![cfg(feature = "test-bpf")]
+
+mod helpers;
+
+use helpers::{bank::*, AmountT};
+
+use pool::{error::PoolError, instruction::DeFiInstruction, oracle::EMA_ONE, state::PriceBand, TOKEN_COUNT};
+use solana_program_test::*;
+use solana_sdk::instruction::InstructionError;
+
+const POOL_BALANCE: AmountT = 1_000_000_000;
+//a price of 1 give or take 0.1%
+const MIN_PRICE: u64 = EMA_ONE / 1000 * 999;
+const MAX_PRICE: u64 = EMA_ONE / 1000 * 1001;
+
+fn pool_error(error: PoolError) -> InstructionError {
+    InstructionError::Custom(error as u32)
+}
+
+fn swap(input_token_index: usize, amount: AmountT) -> DeFiInstruction<TOKEN_COUNT> {
+    let mut exact_input_amounts = [0; TOKEN_COUNT];
+    exact_input_amounts[input_token_index] = amount;
+    DeFiInstruction::SwapExactInput {
+        exact_input_amounts,
+        output_token_index: 1 - input_token_index as u8,
+        minimum_output_amount: 0,
+    }
+}
+
+async fn funded_pool() -> (BankPool, BankUser) {
+    let mut pool = BankPool::new(&BankPoolParams::default()).await.unwrap();
+    let user = pool.create_user(&[3 * POOL_BALANCE; TOKEN_COUNT]);
+    pool.execute_defi_instruction(
+        DeFiInstruction::Add {
+            input_amounts: [POOL_BALANCE; TOKEN_COUNT],
+            minimum_mint_amount: 0,
+        },
+        &user,
+    )
+    .await
+    .unwrap();
+    (pool, user)
+}
+
+#[tokio::test]
+async fn test_set_price_band() {
+    let mut pool = BankPool::new(&BankPoolParams::default()).await.unwrap();
+    if TOKEN_COUNT != 2 {
+        assert_eq!(
+            pool.set_price_band(MIN_PRICE, MAX_PRICE).await,
+            Err(pool_error(PoolError::InvalidPriceBand))
+        );
+        return;
+    }
+
+    for &(min_price, max_price) in [(MAX_PRICE, MIN_PRICE), (MIN_PRICE, MIN_PRICE)].iter() {
+        assert_eq!(
+            pool.set_price_band(min_price, max_price).await,
+            Err(pool_error(PoolError::InvalidPriceBand))
+        );
+    }
+    pool.set_price_band(MIN_PRICE, MAX_PRICE).await.unwrap();
+    assert_eq!(
+        pool.state().await.price_band,
+        PriceBand {
+            min_price: MIN_PRICE,
+            max_price: MAX_PRICE
+        }
+    );
+
+    pool.set_price_band(0, 0).await.unwrap();
+    assert!(!pool.state().await.price_band.is_set());
+}
+
+#[tokio::test]
+async fn test_trades_cant_move_the_price_out_of_the_band() {
+    if TOKEN_COUNT != 2 {
+        return;
+    }
+    let (mut pool, user) = funded_pool().await;
+    pool.set_price_band(MIN_PRICE, MAX_PRICE).await.unwrap();
+
+    pool.execute_defi_instruction(swap(0, POOL_BALANCE / 100), &user)
+        .await
+        .unwrap();
+    for input_token_index in 0..2 {
+        assert_eq!(
+            pool.execute_defi_instruction(swap(input_token_index, POOL_BALANCE), &user)
+                .await,
+            Err(pool_error(PoolError::PriceOutOfBand))
+        );
+    }
+
+    //uniform removes don't move the price and are always possible
+    let lp_balance = pool.balance(&user.lp).await;
+    pool.execute_defi_instruction(
+        DeFiInstruction::RemoveUniform {
+            exact_burn_amount: lp_balance / 2,
+            minimum_output_amounts: [0; TOKEN_COUNT],
+        },
+        &user,
+    )
+    .await
+    .unwrap();
+}
+
+#[tokio::test]
+async fn test_trades_toward_the_band_are_allowed() {
+    if TOKEN_COUNT != 2 {
+        return;
+    }
+    //the second token gets scarce, so its price ends up far above the band
+    let (mut pool, user) = funded_pool().await;
+    pool.execute_defi_instruction(swap(0, POOL_BALANCE), &user)
+        .await
+        .unwrap();
+    pool.set_price_band(MIN_PRICE, MAX_PRICE).await.unwrap();
+
+    assert_eq!(
+        pool.execute_defi_instruction(swap(0, POOL_BALANCE / 100), &user).await,
+        Err(pool_error(PoolError::PriceOutOfBand))
+    );
+    pool.execute_defi_instruction(swap(1, POOL_BALANCE / 100), &user)
+        .await
+        .unwrap();
+}
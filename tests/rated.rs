@@ -0,0 +1,339 @@
+#![cfg(feature = "test-bpf")]
+
+mod helpers;
+
+use helpers::{bank::*, AmountT};
+
+use borsh::{BorshDeserialize, BorshSerialize};
+use pool::{
+    error::PoolError,
+    instruction::{
+        create_set_base_pool_rate_ix, find_virtual_price_address, DeFiInstruction, GovernanceInstruction,
+        PoolInstruction,
+    },
+    oracle::{RateSource, RATE_ONE},
+    state::VirtualPriceState,
+    TOKEN_COUNT,
+};
+use solana_program_test::*;
+use solana_sdk::{
+    instruction::InstructionError,
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+};
+
+const POOL_BALANCE: AmountT = 1_000_000_000;
+const SWAP_AMOUNT: AmountT = 1_000_000;
+
+fn pool_error(error: PoolError) -> InstructionError {
+    InstructionError::Custom(error as u32)
+}
+
+fn swap(input_index: usize, output_index: usize) -> DeFiInstruction<TOKEN_COUNT> {
+    let mut exact_input_amounts = [0; TOKEN_COUNT];
+    exact_input_amounts[input_index] = SWAP_AMOUNT;
+    DeFiInstruction::SwapExactInput {
+        exact_input_amounts,
+        output_token_index: output_index as u8,
+        minimum_output_amount: 0,
+    }
+}
+
+//a pool whose first token is a stake pool token worth 1.1 of the others, funded with balances of equal value
+async fn rated_pool() -> (BankPool, BankUser, Pubkey) {
+    let mut pool = BankPool::new(&BankPoolParams::default()).await.unwrap();
+    let stake_pool = Pubkey::new_unique();
+    pool.set_stake_pool(&stake_pool, 0, 1_100, 1_000).await;
+    pool.set_rate_source(0, RateSource::SplStakePool, stake_pool)
+        .await
+        .unwrap();
+
+    let user = pool.create_user(&[2 * POOL_BALANCE; TOKEN_COUNT]);
+    let mut input_amounts = [POOL_BALANCE; TOKEN_COUNT];
+    input_amounts[0] = POOL_BALANCE * 10 / 11;
+    pool.execute_defi_instruction(
+        DeFiInstruction::Add {
+            input_amounts,
+            minimum_mint_amount: 0,
+        },
+        &user,
+    )
+    .await
+    .unwrap();
+    (pool, user, stake_pool)
+}
+
+fn assert_close(actual: AmountT, expected: AmountT) {
+    let tolerance = expected / 1_000;
+    assert!(
+        actual + tolerance > expected && actual < expected + tolerance,
+        "{} isn't within 0.1% of {}",
+        actual,
+        expected
+    );
+}
+
+async fn swap_output(pool: &mut BankPool, user: &BankUser, input_index: usize, output_index: usize) -> AmountT {
+    let before = pool.balance(&user.tokens[output_index]).await;
+    pool.execute_defi_instruction(swap(input_index, output_index), user)
+        .await
+        .unwrap();
+    pool.balance(&user.tokens[output_index]).await - before
+}
+
+#[tokio::test]
+async fn test_rated_pool_swaps_at_exchange_rate() {
+    let (mut pool, user, stake_pool) = rated_pool().await;
+    let state = pool.state().await;
+    assert_eq!(state.rate_sources[0], RateSource::SplStakePool);
+    assert_eq!(state.rate_keys[0], stake_pool);
+
+    //balances of equal value trade close to the exchange rate (give or take fees and slippage)
+    assert_close(swap_output(&mut pool, &user, 0, 1).await, SWAP_AMOUNT * 11 / 10);
+    assert_close(swap_output(&mut pool, &user, 1, 0).await, SWAP_AMOUNT * 10 / 11);
+
+    //staking rewards raise the rate and with it what the token is worth in the pool
+    pool.set_stake_pool(&stake_pool, 0, 1_200, 1_000).await;
+    let output = swap_output(&mut pool, &user, 0, 1).await;
+    assert!(output > SWAP_AMOUNT * 11 / 10, "{}", output);
+}
+
+#[tokio::test]
+async fn test_rate_sources_of_other_providers() {
+    let (mut pool, user, _) = rated_pool().await;
+
+    let aggregator = Pubkey::new_unique();
+    pool.set_switchboard_result(&aggregator, 1_100_000_000, 9);
+    pool.set_rate_source(0, RateSource::Switchboard, aggregator)
+        .await
+        .unwrap();
+    assert_eq!(pool.rate_keys, vec![aggregator]);
+    assert_close(swap_output(&mut pool, &user, 0, 1).await, SWAP_AMOUNT * 11 / 10);
+
+    //a fixed rate doesn't need an account
+    assert_eq!(
+        pool.set_rate_source(0, RateSource::Fixed { rate: 0 }, Pubkey::default())
+            .await,
+        Err(pool_error(PoolError::RateUnavailable))
+    );
+    pool.set_rate_source(
+        0,
+        RateSource::Fixed {
+            rate: RATE_ONE / 10 * 11,
+        },
+        Pubkey::default(),
+    )
+    .await
+    .unwrap();
+    assert!(pool.rate_keys.is_empty());
+    assert_eq!(pool.state().await.rate_keys[0], Pubkey::default());
+    assert_close(swap_output(&mut pool, &user, 1, 0).await, SWAP_AMOUNT * 10 / 11);
+}
+
+#[tokio::test]
+async fn test_wrapped_rebasing_token_is_valued_at_its_underlying() {
+    let (mut pool, user, _) = rated_pool().await;
+
+    //a whole share (6 decimals like the pool's tokens) backed by 1.1 whole underlying tokens of 9 decimals
+    let wrapper_rate = Pubkey::new_unique();
+    pool.set_wrapper_rate(&wrapper_rate, 0, 1_000_000, 1_100_000_000, 9)
+        .await;
+    pool.set_rate_source(0, RateSource::Wrapper, wrapper_rate)
+        .await
+        .unwrap();
+    assert_eq!(pool.rate_keys, vec![wrapper_rate]);
+    assert_close(swap_output(&mut pool, &user, 0, 1).await, SWAP_AMOUNT * 11 / 10);
+
+    //the underlying rebases, the pool's shares are worth more of it
+    pool.set_wrapper_rate(&wrapper_rate, 0, 1_000_000, 1_200_000_000, 9)
+        .await;
+    let output = swap_output(&mut pool, &user, 0, 1).await;
+    assert!(output > SWAP_AMOUNT * 11 / 10, "{}", output);
+    assert_eq!(pool.state().await.depth_rates[0], RATE_ONE / 10 * 12);
+
+    //the account has to be the wrapper of the token's shares
+    pool.set_wrapper_rate(&wrapper_rate, 1, 1_000_000, 1_100_000_000, 9)
+        .await;
+    assert_eq!(
+        pool.execute_defi_instruction(swap(1, 2), &user).await,
+        Err(pool_error(PoolError::InvalidRateAccount))
+    );
+}
+
+#[tokio::test]
+async fn test_rated_pool_requires_current_rates() {
+    let (mut pool, user, stake_pool) = rated_pool().await;
+
+    let mut ix = pool.defi_ix(swap(1, 2), &user);
+    ix.accounts.pop();
+    assert_eq!(
+        pool.execute_transaction(ix, &[&user.keypair]).await,
+        Err(InstructionError::NotEnoughAccountKeys)
+    );
+
+    //a stake pool that hasn't been updated in the current epoch blocks every instruction that values the balances,
+    // uniform removes don't and remain possible
+    let epoch = pool.epoch().await;
+    let outdated = stake_pool_account(&pool.token_mints[0], 1_100, 1_000, epoch + 1);
+    pool.context.set_account(&stake_pool, &outdated.into());
+    assert_eq!(
+        pool.execute_defi_instruction(swap(1, 2), &user).await,
+        Err(pool_error(PoolError::RateUnavailable))
+    );
+    pool.execute_defi_instruction(
+        DeFiInstruction::RemoveUniform {
+            exact_burn_amount: 1_000,
+            minimum_output_amounts: [0; TOKEN_COUNT],
+        },
+        &user,
+    )
+    .await
+    .unwrap();
+
+    //back to a 1:1 valuation
+    pool.set_rate_source(0, RateSource::None, Pubkey::default())
+        .await
+        .unwrap();
+    assert!(pool.rate_keys.is_empty());
+    pool.execute_defi_instruction(swap(1, 2), &user).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_set_rate_source_validation() {
+    let mut pool = BankPool::new(&BankPoolParams::default()).await.unwrap();
+    let stake_pool = Pubkey::new_unique();
+    pool.set_stake_pool(&stake_pool, 1, 1_100, 1_000).await;
+
+    //the stake pool's mint has to be the token's mint
+    assert_eq!(
+        pool.set_rate_source(0, RateSource::SplStakePool, stake_pool).await,
+        Err(pool_error(PoolError::InvalidRateAccount))
+    );
+    //an account of any other program isn't a stake pool
+    let not_a_stake_pool = pool.token_accounts[0];
+    assert_eq!(
+        pool.set_rate_source(0, RateSource::SplStakePool, not_a_stake_pool)
+            .await,
+        Err(pool_error(PoolError::InvalidRateAccount))
+    );
+    assert_eq!(
+        pool.execute_governance_instruction(
+            GovernanceInstruction::SetRateSource {
+                token_index: TOKEN_COUNT as u8,
+                source: RateSource::None,
+                rate_key: Pubkey::default(),
+            },
+            None,
+        )
+        .await,
+        Err(InstructionError::InvalidInstructionData)
+    );
+
+    pool.set_rate_source(1, RateSource::SplStakePool, stake_pool)
+        .await
+        .unwrap();
+    assert_eq!(pool.rate_keys, vec![stake_pool]);
+}
+
+#[tokio::test]
+async fn test_rate_source_of_a_funded_pool_only_changes_while_paused() {
+    let (mut pool, user, _) = rated_pool().await;
+    let set_none = || GovernanceInstruction::SetRateSource {
+        token_index: 0,
+        source: RateSource::None,
+        rate_key: Pubkey::default(),
+    };
+    assert_eq!(
+        pool.execute_governance_instruction(set_none(), None).await,
+        Err(pool_error(PoolError::PoolNotPaused))
+    );
+
+    pool.execute_governance_instruction(GovernanceInstruction::SetPaused { paused: true }, None)
+        .await
+        .unwrap();
+    pool.execute_governance_instruction(set_none(), None).await.unwrap();
+    pool.execute_governance_instruction(GovernanceInstruction::SetPaused { paused: false }, None)
+        .await
+        .unwrap();
+    assert_eq!(pool.state().await.rate_sources[0], RateSource::None);
+    pool.rate_keys = pool.state().await.rate_account_keys();
+    pool.execute_defi_instruction(swap(1, 2), &user).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_metapool_values_base_pool_lp_at_its_virtual_price() {
+    let mut pool = BankPool::new(&BankPoolParams::default()).await.unwrap();
+    let (base_program_id, base_pool) = (Pubkey::new_unique(), Pubkey::new_unique());
+    pool.set_base_pool(&base_program_id, &base_pool, 0, RATE_ONE / 10 * 11)
+        .await;
+
+    //the base pool's lp mint has to be the token's mint
+    assert_eq!(
+        pool.set_base_pool_rate(1, &base_program_id, &base_pool).await,
+        Err(pool_error(PoolError::InvalidRateAccount))
+    );
+    //and the rate account its virtual price account, not just an account of the same data
+    let virtual_price_key = find_virtual_price_address(&base_program_id, &base_pool).0;
+    let mut virtual_price_account = pool
+        .context
+        .banks_client
+        .get_account(virtual_price_key)
+        .await
+        .unwrap()
+        .unwrap();
+    let copy_key = Pubkey::new_unique();
+    pool.context
+        .set_account(&copy_key, &virtual_price_account.clone().into());
+    let mut ix = create_set_base_pool_rate_ix::<TOKEN_COUNT>(
+        &pool::id(),
+        &pool.pool,
+        &pool.governance_keypair.pubkey(),
+        0,
+        &base_program_id,
+        &base_pool,
+    )
+    .unwrap();
+    ix.accounts[2].pubkey = copy_key;
+    ix.data = PoolInstruction::<TOKEN_COUNT>::GovernanceInstruction(GovernanceInstruction::SetRateSource {
+        token_index: 0,
+        source: RateSource::BasePool,
+        rate_key: copy_key,
+    })
+    .try_to_vec()
+    .unwrap();
+    let governance_keypair = Keypair::from_bytes(&pool.governance_keypair.to_bytes()).unwrap();
+    assert_eq!(
+        pool.execute_transaction(ix, &[&governance_keypair]).await,
+        Err(pool_error(PoolError::InvalidRateAccount))
+    );
+
+    pool.set_base_pool_rate(0, &base_program_id, &base_pool).await.unwrap();
+    assert_eq!(pool.rate_keys, vec![virtual_price_key]);
+    assert_eq!(pool.state().await.rate_sources[0], RateSource::BasePool);
+
+    //funded with balances of equal value, the base pool's lp token trades at its virtual price
+    let user = pool.create_user(&[2 * POOL_BALANCE; TOKEN_COUNT]);
+    let mut input_amounts = [POOL_BALANCE; TOKEN_COUNT];
+    input_amounts[0] = POOL_BALANCE * 10 / 11;
+    pool.execute_defi_instruction(
+        DeFiInstruction::Add {
+            input_amounts,
+            minimum_mint_amount: 0,
+        },
+        &user,
+    )
+    .await
+    .unwrap();
+    assert_close(swap_output(&mut pool, &user, 0, 1).await, SWAP_AMOUNT * 11 / 10);
+
+    //a paused base pool's virtual price can't be relied on
+    let mut virtual_price = VirtualPriceState::try_from_slice(&virtual_price_account.data).unwrap();
+    virtual_price.is_paused = true;
+    virtual_price_account.data = virtual_price.try_to_vec().unwrap();
+    pool.context
+        .set_account(&virtual_price_key, &virtual_price_account.into());
+    assert_eq!(
+        pool.execute_defi_instruction(swap(1, 2), &user).await,
+        Err(pool_error(PoolError::RateUnavailable))
+    );
+}
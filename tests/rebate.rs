@@ -0,0 +1,205 @@
+#![cfg(feature = "test-bpf")]
+
+mod helpers;
+
+use helpers::{bank::*, AmountT};
+
+use pool::{
+    error::PoolError,
+    instruction::{find_rebate_campaign_address, DeFiInstruction},
+    state::{MAX_REBATE_WINDOW, REBATE_CLAIM_PERIOD},
+    TOKEN_COUNT,
+};
+use solana_program_test::*;
+use solana_sdk::{
+    instruction::InstructionError,
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+};
+
+const POOL_BALANCE: AmountT = 1_000_000_000;
+const BUDGET: AmountT = 10_000_000;
+const WINDOW: i64 = 7 * 86400;
+const SWAP: AmountT = 1_000_000;
+
+fn pool_error(error: PoolError) -> InstructionError {
+    InstructionError::Custom(error as u32)
+}
+
+fn swap(input_index: usize, amount: AmountT) -> DeFiInstruction<TOKEN_COUNT> {
+    let mut exact_input_amounts = [0; TOKEN_COUNT];
+    exact_input_amounts[input_index] = amount;
+    DeFiInstruction::SwapExactInput {
+        exact_input_amounts,
+        output_token_index: 1 - input_index as u8,
+        minimum_output_amount: 0,
+    }
+}
+
+//a pool with liquidity along with the governance's token account of a new rebate token that holds the budget
+async fn funded_pool() -> (BankPool, Pubkey, Pubkey) {
+    let mut pool = BankPool::new(&BankPoolParams::default()).await.unwrap();
+    let seed = pool.create_user(&[POOL_BALANCE; TOKEN_COUNT]);
+    pool.execute_defi_instruction(
+        DeFiInstruction::Add {
+            input_amounts: [POOL_BALANCE; TOKEN_COUNT],
+            minimum_mint_amount: 0,
+        },
+        &seed,
+    )
+    .await
+    .unwrap();
+    let governance_keypair = Keypair::from_bytes(&pool.governance_keypair.to_bytes()).unwrap();
+    let (rebate_mint, governance_rebate_account) = pool.create_reward_token(&governance_keypair.pubkey(), BUDGET);
+    (pool, rebate_mint, governance_rebate_account)
+}
+
+//claims the user's rebate to a new token account and returns it
+async fn claim(pool: &mut BankPool, campaign_id: u64, user: &BankUser, rebate_mint: &Pubkey) -> AmountT {
+    let rebate_account = Pubkey::new_unique();
+    pool.set_token_account(&rebate_account, rebate_mint, &user.keypair.pubkey(), 0);
+    pool.claim_rebate(campaign_id, user, &rebate_account).await.unwrap();
+    pool.balance(&rebate_account).await
+}
+
+#[tokio::test]
+async fn test_rebates_split_budget_by_fees() {
+    let (mut pool, rebate_mint, governance_rebate_account) = funded_pool().await;
+    let now = pool.now().await;
+    pool.start_rebate_campaign(1, &rebate_mint, &governance_rebate_account, BUDGET, now, now + WINDOW)
+        .await
+        .unwrap();
+    assert_eq!(pool.balance(&governance_rebate_account).await, 0);
+    assert_eq!(
+        pool.state().await.rebate_campaign,
+        find_rebate_campaign_address(&pool::id(), &pool.pool, 1).0
+    );
+
+    let first = pool.create_user(&[10 * SWAP; TOKEN_COUNT]);
+    let second = pool.create_user(&[10 * SWAP; TOKEN_COUNT]);
+    let outsider = pool.create_user(&[10 * SWAP; TOKEN_COUNT]);
+    pool.join_rebate_campaign(1, &first).await.unwrap();
+    pool.join_rebate_campaign(1, &second).await.unwrap();
+    //joining again does nothing
+    pool.join_rebate_campaign(1, &first).await.unwrap();
+    assert_eq!(
+        pool.execute_rebate_swap(swap(0, SWAP), &outsider, 1).await,
+        Err(pool_error(PoolError::InvalidRebateCampaign))
+    );
+
+    pool.execute_rebate_swap(swap(0, SWAP), &first, 1).await.unwrap();
+    pool.execute_rebate_swap(swap(1, SWAP), &first, 1).await.unwrap();
+    pool.execute_rebate_swap(swap(0, SWAP), &second, 1).await.unwrap();
+    //swaps that don't pass the campaign don't record their fees
+    pool.execute_defi_instruction(swap(1, SWAP), &second).await.unwrap();
+    let first_fees = pool.rebate(1, &first.keypair.pubkey()).await.unwrap().fees;
+    let second_fees = pool.rebate(1, &second.keypair.pubkey()).await.unwrap().fees;
+    assert!(second_fees > 0 && first_fees > 19 * second_fees / 10 && first_fees < 21 * second_fees / 10);
+    assert_eq!(
+        pool.rebate_campaign(1).await.unwrap().total_fees,
+        first_fees + second_fees
+    );
+    assert_eq!(
+        pool.claim_rebate(1, &first, &Pubkey::new_unique()).await,
+        Err(pool_error(PoolError::RebateCampaignNotEnded))
+    );
+
+    //once the window ended swaps stop recording and nobody can join anymore
+    pool.advance_clock(WINDOW).await;
+    pool.execute_rebate_swap(swap(1, SWAP), &second, 1).await.unwrap();
+    assert_eq!(
+        pool.rebate(1, &second.keypair.pubkey()).await.unwrap().fees,
+        second_fees
+    );
+    assert_eq!(
+        pool.join_rebate_campaign(1, &outsider).await,
+        Err(pool_error(PoolError::InvalidRebateCampaign))
+    );
+
+    let campaign = pool.rebate_campaign(1).await.unwrap();
+    let first_rebate = claim(&mut pool, 1, &first, &rebate_mint).await;
+    let second_rebate = claim(&mut pool, 1, &second, &rebate_mint).await;
+    assert_eq!(first_rebate, campaign.rebate(first_fees));
+    assert_eq!(second_rebate, campaign.rebate(second_fees));
+    assert!(first_rebate + second_rebate <= BUDGET && first_rebate + second_rebate >= BUDGET - 1);
+    //claiming closes the rebate
+    assert_eq!(pool.rebate(1, &first.keypair.pubkey()).await, None);
+
+    //the rounding dust goes back to the governance once the claim period passed
+    assert_eq!(
+        pool.reclaim_rebate_budget(1, &governance_rebate_account).await,
+        Err(pool_error(PoolError::RebateCampaignNotEnded))
+    );
+    pool.advance_clock(REBATE_CLAIM_PERIOD).await;
+    pool.reclaim_rebate_budget(1, &governance_rebate_account).await.unwrap();
+    assert_eq!(
+        pool.balance(&governance_rebate_account).await,
+        BUDGET - first_rebate - second_rebate
+    );
+    assert_eq!(
+        pool.reclaim_rebate_budget(1, &governance_rebate_account).await,
+        Err(pool_error(PoolError::InvalidRebateCampaign))
+    );
+}
+
+#[tokio::test]
+async fn test_start_rebate_campaign() {
+    let (mut pool, rebate_mint, governance_rebate_account) = funded_pool().await;
+    let now = pool.now().await;
+    for (budget, start_ts, end_ts) in [
+        (0, now, now + WINDOW),
+        (BUDGET, now - 1, now + WINDOW),
+        (BUDGET, now + WINDOW, now + WINDOW),
+        (BUDGET, now, now + MAX_REBATE_WINDOW + 1),
+    ] {
+        assert_eq!(
+            pool.start_rebate_campaign(1, &rebate_mint, &governance_rebate_account, budget, start_ts, end_ts)
+                .await,
+            Err(pool_error(PoolError::InvalidRebateCampaign))
+        );
+    }
+
+    pool.start_rebate_campaign(
+        1,
+        &rebate_mint,
+        &governance_rebate_account,
+        BUDGET / 2,
+        now,
+        now + WINDOW,
+    )
+    .await
+    .unwrap();
+    //a pool runs one campaign at a time
+    let (next_start, next_end) = (now + WINDOW, now + 2 * WINDOW);
+    assert_eq!(
+        pool.start_rebate_campaign(
+            2,
+            &rebate_mint,
+            &governance_rebate_account,
+            BUDGET / 2,
+            next_start,
+            next_end
+        )
+        .await,
+        Err(pool_error(PoolError::RebateCampaignNotEnded))
+    );
+
+    //nobody can claim a rebate of a campaign that recorded no fees, so its budget can be reclaimed right away
+    pool.advance_clock(WINDOW).await;
+    pool.reclaim_rebate_budget(1, &governance_rebate_account).await.unwrap();
+    assert_eq!(pool.balance(&governance_rebate_account).await, BUDGET);
+    let now = pool.now().await;
+    assert_eq!(
+        pool.start_rebate_campaign(1, &rebate_mint, &governance_rebate_account, BUDGET, now, now + WINDOW)
+            .await,
+        Err(pool_error(PoolError::InvalidRebateCampaign))
+    );
+    pool.start_rebate_campaign(2, &rebate_mint, &governance_rebate_account, BUDGET, now, now + WINDOW)
+        .await
+        .unwrap();
+    let state = pool.state().await;
+    assert_eq!(
+        (state.rebate_campaign, state.rebate_end_ts),
+        (find_rebate_campaign_address(&pool::id(), &pool.pool, 2).0, now + WINDOW)
+    );
+}
@@ -0,0 +1,187 @@
+#![cfg(feature = "test-bpf")]
+
+//replays every recorded operation in tests/fixtures against the local processor and requires the resulting
+// account states to match the recorded ones byte-for-byte
+//see tests/fixtures/README.md for how to record new fixtures
+
+mod helpers;
+
+use helpers::{bank::*, AmountT};
+
+use pool::{
+    client::capture::{FixtureAccount, FixtureAccountMeta, ReplayFixture, FIXTURE_VERSION},
+    instruction::DeFiInstruction,
+    processor::Processor,
+    TOKEN_COUNT,
+};
+use solana_program::{clock::Clock, pubkey::Pubkey, sysvar};
+use solana_program_test::*;
+use solana_sdk::{
+    account::create_account_shared_data_for_test,
+    signature::{Keypair, Signer},
+    transaction::Transaction,
+};
+use std::path::Path;
+
+const FIXTURE_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures");
+
+async fn replay(mut fixture: ReplayFixture) {
+    //we don't have the original signers' keys so they are swapped for fresh ones everywhere
+    let signers: Vec<Keypair> = fixture
+        .signers()
+        .into_iter()
+        .map(|original| {
+            let signer = Keypair::new();
+            fixture.substitute_key(&original, &signer.pubkey());
+            signer
+        })
+        .collect();
+
+    let mut program_test = ProgramTest::new(
+        "pool",
+        fixture.program_id,
+        processor!(Processor::<{ TOKEN_COUNT }>::process),
+    );
+    for account in &fixture.pre_accounts {
+        program_test.add_account(account.pubkey, account.to_account());
+    }
+    let mut context = program_test.start_with_context().await;
+
+    //governance and fee transitions depend on the timestamp of the original execution
+    let clock = Clock {
+        slot: fixture.slot,
+        unix_timestamp: fixture.unix_timestamp,
+        ..Clock::default()
+    };
+    context.set_account(&sysvar::clock::id(), &create_account_shared_data_for_test(&clock));
+
+    let mut all_signers: Vec<&Keypair> = vec![&context.payer];
+    all_signers.extend(signers.iter());
+    let transaction = Transaction::new_signed_with_payer(
+        &[fixture.instruction()],
+        Some(&context.payer.pubkey()),
+        &all_signers,
+        context.last_blockhash,
+    );
+    context
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap_or_else(|err| panic!("replay of \"{}\" failed: {:?}", fixture.description, err));
+
+    for expected in &fixture.post_accounts {
+        let actual = context
+            .banks_client
+            .get_account(expected.pubkey)
+            .await
+            .unwrap()
+            .unwrap_or_else(|| panic!("\"{}\": account {} missing", fixture.description, expected.pubkey));
+        assert_eq!(
+            actual.data, expected.data,
+            "\"{}\": data of {} differs",
+            fixture.description, expected.pubkey
+        );
+        assert_eq!(
+            actual.lamports, expected.lamports,
+            "\"{}\": lamports of {} differ",
+            fixture.description, expected.pubkey
+        );
+        assert_eq!(
+            actual.owner, expected.owner,
+            "\"{}\": owner of {} differs",
+            fixture.description, expected.pubkey
+        );
+    }
+}
+
+#[tokio::test]
+async fn replay_recorded_operations() {
+    let fixtures = ReplayFixture::load_dir(Path::new(FIXTURE_DIR)).unwrap();
+    //an empty corpus would pass without checking anything
+    assert!(!fixtures.is_empty(), "no fixtures found in {}", FIXTURE_DIR);
+    for (path, fixture) in fixtures {
+        println!("replaying {}", path.display());
+        replay(fixture).await;
+    }
+}
+
+async fn fixture_accounts(pool: &mut BankPool, keys: &[Pubkey]) -> Vec<FixtureAccount> {
+    let accounts = pool.accounts(keys).await;
+    keys.iter()
+        .map(|key| (key, &accounts[key]))
+        .filter(|(_, account)| !account.executable)
+        .map(|(key, account)| FixtureAccount {
+            pubkey: *key,
+            owner: account.owner,
+            lamports: account.lamports,
+            data: account.data.clone(),
+            executable: account.executable,
+            rent_epoch: account.rent_epoch,
+        })
+        .collect()
+}
+
+//records a swap in a local pool the way `capture_fixture` does, for layouts that aren't deployed anywhere to record
+// from. fixtures are only ever added, so this runs on request:
+// cargo test-bpf --test replay -- --ignored record_local_swap
+#[tokio::test]
+#[ignore]
+async fn record_local_swap() {
+    const POOL_BALANCE: AmountT = 1_000_000_000;
+    let mut pool = BankPool::new(&BankPoolParams::default()).await.unwrap();
+    let user = pool.create_user(&[POOL_BALANCE; TOKEN_COUNT]);
+    pool.execute_defi_instruction(
+        DeFiInstruction::Add {
+            input_amounts: [POOL_BALANCE / 2; TOKEN_COUNT],
+            minimum_mint_amount: 0,
+        },
+        &user,
+    )
+    .await
+    .unwrap();
+    let mut exact_input_amounts = [0; TOKEN_COUNT];
+    exact_input_amounts[0] = POOL_BALANCE / 100;
+    let instruction = pool.defi_ix(
+        DeFiInstruction::SwapExactInput {
+            exact_input_amounts,
+            output_token_index: 1,
+            minimum_output_amount: 0,
+        },
+        &user,
+    );
+    let mut keys: Vec<Pubkey> = vec![];
+    for meta in &instruction.accounts {
+        if !keys.contains(&meta.pubkey) {
+            keys.push(meta.pubkey);
+        }
+    }
+
+    let clock = pool.context.banks_client.get_clock().await.unwrap();
+    let pre_accounts = fixture_accounts(&mut pool, &keys).await;
+    pool.execute_transaction(instruction.clone(), &[&user.keypair])
+        .await
+        .unwrap();
+    let post_accounts = fixture_accounts(&mut pool, &keys).await;
+    let fixture = ReplayFixture {
+        version: FIXTURE_VERSION,
+        description: "local swap of token 0 for token 1".to_string(),
+        slot: clock.slot,
+        unix_timestamp: clock.unix_timestamp,
+        program_id: instruction.program_id,
+        account_metas: instruction
+            .accounts
+            .iter()
+            .map(|meta| FixtureAccountMeta {
+                pubkey: meta.pubkey,
+                is_signer: meta.is_signer,
+                is_writable: meta.is_writable,
+            })
+            .collect(),
+        instruction_data: instruction.data.clone(),
+        pre_accounts,
+        post_accounts,
+    };
+    let path = Path::new(FIXTURE_DIR).join(format!("0001_local_swap_{}_tokens.fixture", TOKEN_COUNT));
+    fixture.save(&path).unwrap();
+    println!("recorded {}", path.display());
+}
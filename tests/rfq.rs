@@ -0,0 +1,177 @@
+#![cfg(feature = "test-bpf")]
+
+mod helpers;
+
+use helpers::{bank::*, AmountT, DecT};
+
+use pool::{
+    error::PoolError,
+    instruction::{DeFiInstruction, GovernanceInstruction, RfqQuote},
+    processor::ENACT_DELAY,
+    TOKEN_COUNT,
+};
+use solana_program::pubkey::Pubkey;
+use solana_program_test::*;
+use solana_sdk::{
+    instruction::InstructionError,
+    signature::{Keypair, Signer},
+};
+
+const POOL_BALANCE: AmountT = 100_000_000;
+
+async fn rfq_pool() -> (BankPool, BankUser, Keypair) {
+    let mut pool = BankPool::new(&BankPoolParams::default()).await.unwrap();
+    let seed = pool.create_user(&[POOL_BALANCE; TOKEN_COUNT]);
+    pool.execute_defi_instruction(
+        DeFiInstruction::Add {
+            input_amounts: [POOL_BALANCE; TOKEN_COUNT],
+            minimum_mint_amount: 0,
+        },
+        &seed,
+    )
+    .await
+    .unwrap();
+    let quoter = Keypair::new();
+    pool.set_rfq_quoter(&quoter.pubkey(), DecT::new(5, 3).unwrap())
+        .await
+        .unwrap();
+    let user = pool.create_user(&[10_000_000; TOKEN_COUNT]);
+    (pool, user, quoter)
+}
+
+async fn quote(pool: &mut BankPool, user: &BankUser, output_amount: AmountT, quote_id: u64) -> RfqQuote {
+    RfqQuote {
+        pool: pool.pool,
+        user: user.keypair.pubkey(),
+        input_token_index: 0,
+        input_amount: 5_000_000,
+        output_token_index: 1,
+        output_amount,
+        quote_id,
+        expiry_ts: pool.now().await + 60,
+    }
+}
+
+#[tokio::test]
+async fn test_rfq_swap_at_quoted_price() {
+    let (mut pool, user, quoter) = rfq_pool().await;
+    //a price that the curve wouldn't give for a trade of this size
+    let quote = quote(&mut pool, &user, 4_990_000, 1).await;
+    pool.execute_rfq_swap(quote, &quoter, &user).await.unwrap();
+
+    assert_eq!(pool.balances(&user.tokens).await, [5_000_000, 14_990_000]);
+    let pool_token_accounts = pool.token_accounts;
+    assert_eq!(
+        pool.balances(&pool_token_accounts).await,
+        [POOL_BALANCE + 5_000_000, POOL_BALANCE - 4_990_000]
+    );
+    assert_eq!(pool.state().await.rfq_last_quote_id, 1);
+}
+
+#[tokio::test]
+async fn test_rfq_quotes_cant_be_replayed() {
+    let (mut pool, user, quoter) = rfq_pool().await;
+    let first = quote(&mut pool, &user, 4_990_000, 5).await;
+    pool.execute_rfq_swap(first, &quoter, &user).await.unwrap();
+    assert_eq!(
+        pool.execute_rfq_swap(first, &quoter, &user).await,
+        Err(InstructionError::Custom(PoolError::QuoteAlreadyUsed as u32))
+    );
+    //quotes with lower ids than the last executed one are void as well
+    let earlier = quote(&mut pool, &user, 4_980_000, 4).await;
+    assert_eq!(
+        pool.execute_rfq_swap(earlier, &quoter, &user).await,
+        Err(InstructionError::Custom(PoolError::QuoteAlreadyUsed as u32))
+    );
+    let later = quote(&mut pool, &user, 4_980_000, 6).await;
+    pool.execute_rfq_swap(later, &quoter, &user).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_rfq_quote_bounds() {
+    let (mut pool, user, quoter) = rfq_pool().await;
+    //1% better than the pool's price, beyond the 0.5% that governance allows
+    let generous = quote(&mut pool, &user, 5_050_000, 1).await;
+    assert_eq!(
+        pool.execute_rfq_swap(generous, &quoter, &user).await,
+        Err(InstructionError::Custom(PoolError::QuoteDeviationExceeded as u32))
+    );
+
+    let mut expiring = quote(&mut pool, &user, 4_990_000, 1).await;
+    expiring.expiry_ts = pool.now().await + 10;
+    pool.advance_clock(10).await;
+    assert_eq!(
+        pool.execute_rfq_swap(expiring, &quoter, &user).await,
+        Err(InstructionError::Custom(PoolError::QuoteExpired as u32))
+    );
+
+    //quotes are only good for the user they were made for
+    let other_user = pool.create_user(&[10_000_000; TOKEN_COUNT]);
+    let mut foreign = quote(&mut pool, &user, 4_990_000, 1).await;
+    foreign.user = other_user.keypair.pubkey();
+    foreign.pool = Pubkey::new_unique();
+    assert_eq!(
+        pool.execute_rfq_swap(foreign, &quoter, &other_user).await,
+        Err(InstructionError::Custom(PoolError::InvalidQuoteSignature as u32))
+    );
+}
+
+#[tokio::test]
+async fn test_rfq_swaps_off() {
+    let (mut pool, user, quoter) = rfq_pool().await;
+    pool.set_rfq_quoter(&Pubkey::default(), DecT::from(0)).await.unwrap();
+    let quote = quote(&mut pool, &user, 4_990_000, 1).await;
+    assert_eq!(
+        pool.execute_rfq_swap(quote, &quoter, &user).await,
+        Err(InstructionError::Custom(PoolError::InvalidQuoteSignature as u32))
+    );
+}
+
+#[tokio::test]
+async fn test_rfq_quoter_changes_are_timelocked() {
+    let (mut pool, user, quoter) = rfq_pool().await;
+    let new_quoter = Keypair::new();
+    //deviations beyond MAX_RFQ_DEVIATION_BPS can't be prepared
+    assert_eq!(
+        pool.execute_governance_instruction(
+            GovernanceInstruction::SetRfqQuoter {
+                quoter_key: new_quoter.pubkey(),
+                max_deviation: DecT::new(2, 2).unwrap(),
+            },
+            None,
+        )
+        .await,
+        Err(InstructionError::Custom(PoolError::InvalidFeeInput as u32))
+    );
+    pool.execute_governance_instruction(
+        GovernanceInstruction::SetRfqQuoter {
+            quoter_key: new_quoter.pubkey(),
+            max_deviation: DecT::new(5, 3).unwrap(),
+        },
+        None,
+    )
+    .await
+    .unwrap();
+    assert_eq!(
+        pool.execute_governance_instruction(GovernanceInstruction::EnactRfqQuoter {}, None)
+            .await,
+        Err(InstructionError::Custom(PoolError::InsufficientDelay as u32))
+    );
+
+    //until it is enacted, the current quoter's quotes execute and the prepared one's don't
+    let current = quote(&mut pool, &user, 4_990_000, 1).await;
+    pool.execute_rfq_swap(current, &quoter, &user).await.unwrap();
+    let early = quote(&mut pool, &user, 4_990_000, 2).await;
+    assert_eq!(
+        pool.execute_rfq_swap(early, &new_quoter, &user).await,
+        Err(InstructionError::Custom(PoolError::InvalidQuoteSignature as u32))
+    );
+
+    pool.advance_clock(ENACT_DELAY).await;
+    pool.execute_governance_instruction(GovernanceInstruction::EnactRfqQuoter {}, None)
+        .await
+        .unwrap();
+    assert_eq!(pool.state().await.rfq_quoter_key, new_quoter.pubkey());
+    let enacted = quote(&mut pool, &user, 4_990_000, 2).await;
+    pool.execute_rfq_swap(enacted, &new_quoter, &user).await.unwrap();
+}
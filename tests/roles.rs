@@ -0,0 +1,129 @@
+#![cfg(feature = "test-bpf")]
+
+mod helpers;
+
+use helpers::{bank::*, DecT};
+
+use pool::{
+    amp_factor::MIN_ADJUSTMENT_WINDOW, error::PoolError, instruction::GovernanceInstruction, processor::ENACT_DELAY,
+    state::Role, TOKEN_COUNT,
+};
+use solana_program_test::*;
+use solana_sdk::{
+    instruction::InstructionError,
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+};
+
+const NOT_GOVERNANCE: InstructionError = InstructionError::Custom(PoolError::InvalidGovernanceAccount as u32);
+
+fn set_role(role: Role, key: Pubkey) -> GovernanceInstruction<TOKEN_COUNT> {
+    GovernanceInstruction::SetRole { role, key }
+}
+
+fn adjust_amp(now: i64, target_value: u64) -> GovernanceInstruction<TOKEN_COUNT> {
+    GovernanceInstruction::AdjustAmpFactor {
+        target_ts: now + MIN_ADJUSTMENT_WINDOW,
+        target_value: DecT::from(target_value),
+    }
+}
+
+fn prepare_fee_change() -> GovernanceInstruction<TOKEN_COUNT> {
+    GovernanceInstruction::PrepareFeeChange {
+        lp_fee: DecT::new(4, 4).unwrap(),
+        governance_fee: DecT::from(0),
+    }
+}
+
+#[tokio::test]
+async fn test_roles() {
+    let mut pool = BankPool::new(&BankPoolParams::default()).await.unwrap();
+    let (fee_admin, amp_admin, pause_admin) = (Keypair::new(), Keypair::new(), Keypair::new());
+    for (role, admin) in [
+        (Role::FeeAdmin, &fee_admin),
+        (Role::AmpAdmin, &amp_admin),
+        (Role::PauseAdmin, &pause_admin),
+    ] {
+        pool.execute_governance_instruction(set_role(role, admin.pubkey()), None)
+            .await
+            .unwrap();
+    }
+    let state = pool.state().await;
+    assert_eq!(state.role_key(Role::AmpAdmin), Some(amp_admin.pubkey()));
+    let now = pool.now().await;
+
+    //each admin can do what its role covers
+    pool.execute_governance_instruction_as(adjust_amp(now, 2000), None, &amp_admin)
+        .await
+        .unwrap();
+    pool.execute_governance_instruction_as(prepare_fee_change(), None, &fee_admin)
+        .await
+        .unwrap();
+    pool.advance_clock(ENACT_DELAY).await;
+    pool.execute_governance_instruction_as(GovernanceInstruction::EnactFeeChange {}, None, &fee_admin)
+        .await
+        .unwrap();
+    assert_eq!(pool.state().await.lp_fee.get(), DecT::new(4, 4).unwrap());
+    pool.execute_governance_instruction_as(GovernanceInstruction::SetPaused { paused: true }, None, &pause_admin)
+        .await
+        .unwrap();
+    assert!(pool.state().await.is_paused);
+
+    //but nothing else
+    assert_eq!(
+        pool.execute_governance_instruction_as(GovernanceInstruction::SetPaused { paused: false }, None, &amp_admin)
+            .await,
+        Err(NOT_GOVERNANCE)
+    );
+    assert_eq!(
+        pool.execute_governance_instruction_as(adjust_amp(now, 3000), None, &pause_admin)
+            .await,
+        Err(NOT_GOVERNANCE)
+    );
+    assert_eq!(
+        pool.execute_governance_instruction_as(set_role(Role::FeeAdmin, amp_admin.pubkey()), None, &fee_admin)
+            .await,
+        Err(NOT_GOVERNANCE)
+    );
+
+    //the governance keeps every role's powers
+    pool.execute_governance_instruction(GovernanceInstruction::SetPaused { paused: false }, None)
+        .await
+        .unwrap();
+
+    //revoking a role leaves the others
+    pool.execute_governance_instruction(set_role(Role::PauseAdmin, Pubkey::default()), None)
+        .await
+        .unwrap();
+    assert_eq!(
+        pool.execute_governance_instruction_as(GovernanceInstruction::SetPaused { paused: true }, None, &pause_admin)
+            .await,
+        Err(NOT_GOVERNANCE)
+    );
+    pool.execute_governance_instruction_as(prepare_fee_change(), None, &fee_admin)
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn test_renounce_revokes_roles() {
+    let mut pool = BankPool::new(&BankPoolParams::default()).await.unwrap();
+    let amp_admin = Keypair::new();
+    pool.execute_governance_instruction(set_role(Role::AmpAdmin, amp_admin.pubkey()), None)
+        .await
+        .unwrap();
+    pool.execute_governance_instruction(
+        GovernanceInstruction::RenounceGovernance { lock_amp_factor: false },
+        None,
+    )
+    .await
+    .unwrap();
+    assert_eq!(pool.state().await.role_key(Role::AmpAdmin), None);
+
+    let now = pool.now().await;
+    assert_eq!(
+        pool.execute_governance_instruction_as(adjust_amp(now, 2000), None, &amp_admin)
+            .await,
+        Err(InstructionError::Custom(PoolError::GovernanceRenounced as u32))
+    );
+}
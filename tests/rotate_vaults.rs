@@ -0,0 +1,95 @@
+#![cfg(feature = "test-bpf")]
+
+mod helpers;
+
+use helpers::{bank::*, AmountT};
+
+use pool::{
+    error::PoolError,
+    instruction::{find_rotated_vault_address, DeFiInstruction, GovernanceInstruction},
+    TOKEN_COUNT,
+};
+use solana_program::program_option::COption;
+use solana_program_test::*;
+use solana_sdk::{instruction::InstructionError, pubkey::Pubkey};
+use spl_token::state::Account as TokenState;
+
+const POOL_BALANCE: AmountT = 1_000_000_000;
+
+async fn pool_with_liquidity() -> (BankPool, BankUser) {
+    let mut pool = BankPool::new(&BankPoolParams::default()).await.unwrap();
+    let user = pool.create_user(&[POOL_BALANCE; TOKEN_COUNT]);
+    pool.execute_defi_instruction(
+        DeFiInstruction::Add {
+            input_amounts: [POOL_BALANCE; TOKEN_COUNT],
+            minimum_mint_amount: 0,
+        },
+        &user,
+    )
+    .await
+    .unwrap();
+    (pool, user)
+}
+
+async fn set_paused(pool: &mut BankPool, paused: bool) {
+    pool.execute_governance_instruction(GovernanceInstruction::SetPaused { paused }, None)
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn test_rotate_vaults_moves_balances_to_new_token_accounts() {
+    let (mut pool, user) = pool_with_liquidity().await;
+    let old_token_accounts = pool.token_accounts;
+    //a delegate that somebody set up off-band
+    let mut token_state = pool.get_packed::<TokenState>(&old_token_accounts[0]).await;
+    token_state.delegate = COption::Some(Pubkey::new_unique());
+    token_state.delegated_amount = POOL_BALANCE;
+    pool.set_packed(&old_token_accounts[0], token_state);
+    assert_eq!(
+        pool.rotate_vaults().await,
+        Err(InstructionError::Custom(PoolError::PoolNotPaused as u32))
+    );
+
+    set_paused(&mut pool, true).await;
+    pool.rotate_vaults().await.unwrap();
+    assert_eq!(pool.balances(&old_token_accounts).await, [0; TOKEN_COUNT]);
+    assert_eq!(pool.pool_balances().await, [POOL_BALANCE; TOKEN_COUNT]);
+    for i in 0..TOKEN_COUNT {
+        assert_eq!(
+            pool.token_accounts[i],
+            find_rotated_vault_address(&pool::id(), &pool.pool, &old_token_accounts[i]).0
+        );
+        let token_account = pool.token_accounts[i];
+        let token_state = pool.get_packed::<TokenState>(&token_account).await;
+        assert_eq!(token_state.owner, pool.authority);
+        assert_eq!(token_state.mint, pool.token_mints[i]);
+        assert_eq!(token_state.delegate, COption::None);
+    }
+
+    //the pool works as before with its new token accounts
+    set_paused(&mut pool, false).await;
+    let lp_amount = pool.balance(&user.lp).await;
+    pool.execute_defi_instruction(
+        DeFiInstruction::RemoveUniform {
+            exact_burn_amount: lp_amount,
+            minimum_output_amounts: [0; TOKEN_COUNT],
+        },
+        &user,
+    )
+    .await
+    .unwrap();
+    assert_eq!(pool.balance(&user.lp).await, 0);
+}
+
+#[tokio::test]
+async fn test_rotate_vaults_again() {
+    let (mut pool, _) = pool_with_liquidity().await;
+    set_paused(&mut pool, true).await;
+    pool.rotate_vaults().await.unwrap();
+    let first_rotation = pool.token_accounts;
+    pool.rotate_vaults().await.unwrap();
+    assert_ne!(pool.token_accounts, first_rotation);
+    assert_eq!(pool.balances(&first_rotation).await, [0; TOKEN_COUNT]);
+    assert_eq!(pool.pool_balances().await, [POOL_BALANCE; TOKEN_COUNT]);
+}
@@ -0,0 +1,425 @@
+#![cfg(feature = "test-bpf")]
+
+//every amount that the pool computes (rather than the user specifying it) has to be rounded in the pool's favor:
+// amounts the user receives (swap outputs, minted lp tokens, removed tokens) are rounded down, amounts the user pays
+// (swap inputs, burned lp tokens) are rounded up
+//
+//the pool mixes tokens with 0 and 8 decimals (and an lp token with 0 decimals) so all conversions from the equalized
+// representation back into token amounts drop 8 digits, and the tested amounts are chosen to sit right at the rounding
+// boundaries. values are compared in equalized units, in which all tokens are worth (roughly) the same in a balanced pool,
+// which is why most cases start out with a fresh pool.
+
+mod helpers;
+
+use helpers::{bank::*, AmountT};
+
+use pool::{instruction::DeFiInstruction, processor::MAX_DECIMAL_DIFFERENCE, quote::Quoter, TOKEN_COUNT};
+use solana_program_test::*;
+
+const WHOLE_TOKENS: AmountT = 1000;
+
+fn token_decimals() -> [u8; TOKEN_COUNT] {
+    let mut token_decimals = [0; TOKEN_COUNT];
+    for i in (0..TOKEN_COUNT).step_by(2) {
+        token_decimals[i] = MAX_DECIMAL_DIFFERENCE;
+    }
+    token_decimals
+}
+
+//token indices: EIGHT has 8 decimals, ZERO and OTHER_ZERO have 0
+const EIGHT: usize = 0;
+const ZERO: usize = 1;
+const OTHER_EIGHT: usize = 2;
+
+fn equalizer(decimals: u8) -> u32 {
+    (MAX_DECIMAL_DIFFERENCE - decimals) as u32
+}
+
+fn equalized(amount: AmountT, decimals: u8) -> u128 {
+    amount as u128 * 10u128.pow(equalizer(decimals))
+}
+
+struct RoundingPool {
+    pool: BankPool,
+    token_decimals: [u8; TOKEN_COUNT],
+    lp_decimals: u8,
+}
+
+impl RoundingPool {
+    //returns the pool along with the user that holds all of its lp tokens
+    async fn new() -> (Self, BankUser) {
+        let params = BankPoolParams {
+            lp_decimals: 0,
+            token_decimals: token_decimals(),
+            ..BankPoolParams::default()
+        };
+        let mut pool = BankPool::new(&params).await.unwrap();
+        let balances = {
+            let mut balances = [0; TOKEN_COUNT];
+            for i in 0..TOKEN_COUNT {
+                balances[i] = WHOLE_TOKENS * 10u64.pow(params.token_decimals[i] as u32);
+            }
+            balances
+        };
+        let lp = pool.create_user(&balances);
+        pool.execute_defi_instruction(
+            DeFiInstruction::Add {
+                input_amounts: balances,
+                minimum_mint_amount: 0,
+            },
+            &lp,
+        )
+        .await
+        .unwrap();
+
+        (
+            Self {
+                pool,
+                token_decimals: params.token_decimals,
+                lp_decimals: params.lp_decimals,
+            },
+            lp,
+        )
+    }
+
+    async fn trader(&mut self) -> BankUser {
+        let mut balances = [0; TOKEN_COUNT];
+        for i in 0..TOKEN_COUNT {
+            balances[i] = 10 * 10u64.pow(self.token_decimals[i] as u32);
+        }
+        self.pool.create_user(&balances)
+    }
+
+    //the value of `lp_amount` lp tokens in equalized units, i.e. lp_amount * pool_value / lp_supply
+    // returned as numerator and denominator to avoid rounding
+    async fn lp_value(&mut self, lp_amount: AmountT) -> (u128, u128) {
+        let pool_balances = self.pool.pool_balances().await;
+        let pool_value: u128 = (0..TOKEN_COUNT)
+            .map(|i| equalized(pool_balances[i], self.token_decimals[i]))
+            .sum();
+        let lp_supply = self.pool.lp_total_supply().await;
+        (
+            equalized(lp_amount, self.lp_decimals) * pool_value,
+            equalized(lp_supply, self.lp_decimals),
+        )
+    }
+
+    //executes the instruction and returns the equalized value that the user paid and received
+    async fn execute(&mut self, instruction: DeFiInstruction<TOKEN_COUNT>, user: &BankUser) -> (u128, u128) {
+        let before = self.pool.balances(&user.tokens).await;
+        self.pool.execute_defi_instruction(instruction, user).await.unwrap();
+        let after = self.pool.balances(&user.tokens).await;
+
+        let (mut paid, mut received) = (0, 0);
+        for i in 0..TOKEN_COUNT {
+            if after[i] < before[i] {
+                paid += equalized(before[i] - after[i], self.token_decimals[i]);
+            } else {
+                received += equalized(after[i] - before[i], self.token_decimals[i]);
+            }
+        }
+        (paid, received)
+    }
+}
+
+fn single(index: usize, amount: AmountT) -> [AmountT; TOKEN_COUNT] {
+    let mut amounts = [0; TOKEN_COUNT];
+    amounts[index] = amount;
+    amounts
+}
+
+#[tokio::test]
+async fn test_swap_exact_input_rounds_output_down() {
+    //fractions of a single atom of the 0 decimal token
+    let cases = [
+        (EIGHT, ZERO, 1),
+        (EIGHT, ZERO, 49_999_999),
+        (EIGHT, ZERO, 50_000_000),
+        (EIGHT, ZERO, 99_999_999),
+        (EIGHT, ZERO, 100_000_000),
+        (EIGHT, ZERO, 150_000_000),
+        (ZERO, EIGHT, 1),
+        (ZERO, OTHER_EIGHT, 2),
+    ];
+    for &(input_index, output_index, amount) in cases.iter() {
+        let (mut pool, _) = RoundingPool::new().await;
+        let trader = pool.trader().await;
+        let (paid, received) = pool
+            .execute(
+                DeFiInstruction::SwapExactInput {
+                    exact_input_amounts: single(input_index, amount),
+                    output_token_index: output_index as u8,
+                    minimum_output_amount: 0,
+                },
+                &trader,
+            )
+            .await;
+        assert_eq!(paid, equalized(amount, pool.token_decimals[input_index]));
+        assert!(
+            received < paid,
+            "swapping {} of token {} for token {} returned {} for {}",
+            amount,
+            input_index,
+            output_index,
+            received,
+            paid
+        );
+    }
+}
+
+#[tokio::test]
+async fn test_swap_exact_output_rounds_input_up() {
+    let cases = [
+        (EIGHT, ZERO, 1),
+        (EIGHT, ZERO, 2),
+        (ZERO, EIGHT, 1),
+        (ZERO, EIGHT, 50_000_000),
+        (ZERO, OTHER_EIGHT, 100_000_001),
+    ];
+    for &(input_index, output_index, amount) in cases.iter() {
+        let (mut pool, _) = RoundingPool::new().await;
+        let trader = pool.trader().await;
+        let (paid, received) = pool
+            .execute(
+                DeFiInstruction::SwapExactOutput {
+                    maximum_input_amount: AmountT::MAX,
+                    input_token_index: input_index as u8,
+                    exact_output_amounts: single(output_index, amount),
+                },
+                &trader,
+            )
+            .await;
+        assert_eq!(received, equalized(amount, pool.token_decimals[output_index]));
+        assert!(
+            paid > received,
+            "buying {} of token {} with token {} cost only {} for {}",
+            amount,
+            output_index,
+            input_index,
+            paid,
+            received
+        );
+    }
+}
+
+#[tokio::test]
+async fn test_add_rounds_mint_down() {
+    let cases = [
+        (EIGHT, 1),
+        (EIGHT, 50_000_000),
+        (EIGHT, 99_999_999),
+        (EIGHT, 150_000_000),
+        (ZERO, 1),
+    ];
+    for &(input_index, amount) in cases.iter() {
+        let (mut pool, _) = RoundingPool::new().await;
+        let trader = pool.trader().await;
+        let lp_before = pool.pool.balance(&trader.lp).await;
+        let (value_per_lp, lp_denominator) = pool.lp_value(1).await;
+        let (paid, _) = pool
+            .execute(
+                DeFiInstruction::Add {
+                    input_amounts: single(input_index, amount),
+                    minimum_mint_amount: 0,
+                },
+                &trader,
+            )
+            .await;
+        let minted = pool.pool.balance(&trader.lp).await - lp_before;
+        assert!(
+            minted as u128 * value_per_lp < paid * lp_denominator,
+            "adding {} of token {} minted {} lp tokens",
+            amount,
+            input_index,
+            minted
+        );
+    }
+}
+
+#[tokio::test]
+async fn test_remove_uniform_rounds_outputs_down() {
+    let (mut pool, lp) = RoundingPool::new().await;
+
+    for &burn_amount in [1, 2, 7].iter() {
+        let (burned_value, lp_denominator) = pool.lp_value(burn_amount).await;
+        let (_, received) = pool
+            .execute(
+                DeFiInstruction::RemoveUniform {
+                    exact_burn_amount: burn_amount,
+                    minimum_output_amounts: [0; TOKEN_COUNT],
+                },
+                &lp,
+            )
+            .await;
+        assert!(
+            received * lp_denominator <= burned_value,
+            "burning {} lp tokens uniformly returned {}",
+            burn_amount,
+            received
+        );
+    }
+}
+
+#[tokio::test]
+async fn test_remove_exact_burn_rounds_output_down() {
+    let cases = [(ZERO, 1), (ZERO, 2), (EIGHT, 1), (OTHER_EIGHT, 3)];
+    for &(output_index, burn_amount) in cases.iter() {
+        let (mut pool, lp) = RoundingPool::new().await;
+        let (burned_value, lp_denominator) = pool.lp_value(burn_amount).await;
+        let (_, received) = pool
+            .execute(
+                DeFiInstruction::RemoveExactBurn {
+                    exact_burn_amount: burn_amount,
+                    output_token_index: output_index as u8,
+                    minimum_output_amount: 0,
+                },
+                &lp,
+            )
+            .await;
+        assert!(
+            received * lp_denominator < burned_value,
+            "burning {} lp tokens for token {} returned {}",
+            burn_amount,
+            output_index,
+            received
+        );
+    }
+}
+
+#[tokio::test]
+async fn test_remove_exact_output_rounds_burn_up() {
+    let cases = [(EIGHT, 1), (EIGHT, 50_000_000), (EIGHT, 100_000_001), (ZERO, 1)];
+    for &(output_index, amount) in cases.iter() {
+        let (mut pool, lp) = RoundingPool::new().await;
+        let lp_before = pool.pool.balance(&lp.lp).await;
+        let (value_per_lp, lp_denominator) = pool.lp_value(1).await;
+        let (_, received) = pool
+            .execute(
+                DeFiInstruction::RemoveExactOutput {
+                    maximum_burn_amount: AmountT::MAX,
+                    exact_output_amounts: single(output_index, amount),
+                },
+                &lp,
+            )
+            .await;
+        let burned = lp_before - pool.pool.balance(&lp.lp).await;
+        assert_eq!(received, equalized(amount, pool.token_decimals[output_index]));
+        assert!(
+            burned as u128 * value_per_lp > received * lp_denominator,
+            "removing {} of token {} burned only {} lp tokens",
+            amount,
+            output_index,
+            burned
+        );
+    }
+}
+
+#[tokio::test]
+async fn test_round_trips_never_profit() {
+    let (mut pool, _) = RoundingPool::new().await;
+    let trader = pool.trader().await;
+    let initial = pool.pool.balances(&trader.tokens).await;
+
+    //1.5 tokens in, whatever comes out goes straight back
+    for _ in 0..10 {
+        let (_, received) = pool
+            .execute(
+                DeFiInstruction::SwapExactInput {
+                    exact_input_amounts: single(EIGHT, 150_000_000),
+                    output_token_index: ZERO as u8,
+                    minimum_output_amount: 0,
+                },
+                &trader,
+            )
+            .await;
+        let received = (received / 10u128.pow(equalizer(pool.token_decimals[ZERO]))) as AmountT;
+        if received > 0 {
+            pool.execute(
+                DeFiInstruction::SwapExactInput {
+                    exact_input_amounts: single(ZERO, received),
+                    output_token_index: EIGHT as u8,
+                    minimum_output_amount: 0,
+                },
+                &trader,
+            )
+            .await;
+        }
+    }
+
+    let balances = pool.pool.balances(&trader.tokens).await;
+    assert!(balances[ZERO] <= initial[ZERO]);
+    assert!(balances[EIGHT] < initial[EIGHT]);
+}
+
+#[tokio::test]
+async fn test_quotes_match_execution() {
+    let (mut pool, _) = RoundingPool::new().await;
+    let trader = pool.trader().await;
+    let cases = [
+        (EIGHT, ZERO, 1, 1),
+        (EIGHT, ZERO, 150_000_000, 2),
+        (ZERO, EIGHT, 1, 50_000_000),
+        (ZERO, OTHER_EIGHT, 3, 100_000_001),
+    ];
+
+    //swapping repeatedly without resetting the pool also checks quotes for imbalanced pools
+    for &(input_index, output_index, input_amount, output_amount) in cases.iter() {
+        let state = pool.pool.state().await;
+        let pool_balances = pool.pool.pool_balances().await;
+        let lp_total_supply = pool.pool.lp_total_supply().await;
+        let amp_factor = state.amp_factor.get(pool.pool.now().await);
+        let quoter = Quoter::new(&state, &pool_balances, lp_total_supply, amp_factor);
+
+        let exact_input_amounts = single(input_index, input_amount);
+        let quote = quoter.swap_exact_input(&exact_input_amounts, output_index).unwrap();
+        let exact_input = |minimum_output_amount| DeFiInstruction::SwapExactInput {
+            exact_input_amounts,
+            output_token_index: output_index as u8,
+            minimum_output_amount,
+        };
+        assert!(pool
+            .pool
+            .execute_defi_instruction(exact_input(quote.amount + 1), &trader)
+            .await
+            .is_err());
+        let before = pool.pool.balance(&trader.tokens[output_index]).await;
+        pool.pool
+            .execute_defi_instruction(exact_input(quote.amount), &trader)
+            .await
+            .unwrap();
+        assert_eq!(
+            pool.pool.balance(&trader.tokens[output_index]).await - before,
+            quote.amount
+        );
+        assert_eq!(pool.pool.state().await.previous_depth, quote.latest_depth);
+
+        let state = pool.pool.state().await;
+        let pool_balances = pool.pool.pool_balances().await;
+        let lp_total_supply = pool.pool.lp_total_supply().await;
+        let amp_factor = state.amp_factor.get(pool.pool.now().await);
+        let quoter = Quoter::new(&state, &pool_balances, lp_total_supply, amp_factor);
+
+        let exact_output_amounts = single(output_index, output_amount);
+        let quote = quoter.swap_exact_output(input_index, &exact_output_amounts).unwrap();
+        let exact_output = |maximum_input_amount| DeFiInstruction::SwapExactOutput {
+            maximum_input_amount,
+            input_token_index: input_index as u8,
+            exact_output_amounts,
+        };
+        assert!(pool
+            .pool
+            .execute_defi_instruction(exact_output(quote.amount - 1), &trader)
+            .await
+            .is_err());
+        let before = pool.pool.balance(&trader.tokens[input_index]).await;
+        pool.pool
+            .execute_defi_instruction(exact_output(quote.amount), &trader)
+            .await
+            .unwrap();
+        assert_eq!(
+            before - pool.pool.balance(&trader.tokens[input_index]).await,
+            quote.amount
+        );
+        assert_eq!(pool.pool.state().await.previous_depth, quote.latest_depth);
+    }
+}
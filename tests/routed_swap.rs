@@ -0,0 +1,154 @@
+#![cfg(feature = "test-bpf")]
+
+mod helpers;
+
+use helpers::{bank::*, AmountT};
+
+use pool::{
+    error::PoolError,
+    instruction::{create_routed_swap_ix, find_intermediate_address, DeFiInstruction, PoolInstruction},
+    TOKEN_COUNT,
+};
+use solana_program_test::*;
+use solana_sdk::{
+    account::Account, instruction::InstructionError, native_token::LAMPORTS_PER_SOL, signature::Signer, system_program,
+};
+
+const POOL_BALANCE: AmountT = 1_000_000_000;
+const INPUT_AMOUNT: AmountT = 1_000_000;
+
+async fn funded_pool() -> (BankPool, BankUser) {
+    let mut pool = BankPool::new(&BankPoolParams::default()).await.unwrap();
+    //the user keeps INPUT_AMOUNT of each token after adding liquidity
+    let user = pool.create_user(&[POOL_BALANCE + INPUT_AMOUNT; TOKEN_COUNT]);
+    pool.execute_defi_instruction(
+        DeFiInstruction::Add {
+            input_amounts: [POOL_BALANCE; TOKEN_COUNT],
+            minimum_mint_amount: 0,
+        },
+        &user,
+    )
+    .await
+    .unwrap();
+    //pays for the intermediate token account
+    let user_account = Account::new(LAMPORTS_PER_SOL, 0, &system_program::id());
+    pool.context.set_account(&user.keypair.pubkey(), &user_account.into());
+    (pool, user)
+}
+
+fn swap(input_index: usize, output_index: usize, amount: AmountT) -> DeFiInstruction<TOKEN_COUNT> {
+    let mut exact_input_amounts = [0; TOKEN_COUNT];
+    exact_input_amounts[input_index] = amount;
+    DeFiInstruction::SwapExactInput {
+        exact_input_amounts,
+        output_token_index: output_index as u8,
+        minimum_output_amount: 0,
+    }
+}
+
+//the bank only sets up one pool, so both legs swap in the same pool: token 0 for token 1, then token 1 for token 2
+async fn routed_swap(
+    pool: &mut BankPool,
+    user: &BankUser,
+    minimum_output_amount: AmountT,
+) -> Result<(), InstructionError> {
+    let ix = create_routed_swap_ix(
+        PoolInstruction::RoutedSwap {
+            exact_input_amount: INPUT_AMOUNT,
+            input_token_index: 0,
+            first_output_index: 1,
+            second_input_index: 1,
+            output_token_index: 2,
+            minimum_output_amount,
+        },
+        &pool::id(),
+        &user.keypair.pubkey(),
+        &user.tokens[0],
+        &user.tokens[2],
+        &pool.token_mints[1],
+        &pool.route_leg(),
+        &pool.route_leg(),
+    )
+    .unwrap();
+    pool.execute_transaction(ix, &[&user.keypair]).await
+}
+
+#[tokio::test]
+async fn test_routed_swap_matches_two_swaps() {
+    let (mut routed_pool, routed_user) = funded_pool().await;
+    routed_swap(&mut routed_pool, &routed_user, 0).await.unwrap();
+    let routed_balances = routed_pool.balances(&routed_user.tokens).await;
+
+    let (mut pool, user) = funded_pool().await;
+    pool.execute_defi_instruction(swap(0, 1, INPUT_AMOUNT), &user)
+        .await
+        .unwrap();
+    let intermediate_amount = pool.balance(&user.tokens[1]).await - INPUT_AMOUNT;
+    pool.execute_defi_instruction(swap(1, 2, intermediate_amount), &user)
+        .await
+        .unwrap();
+    let mut expected_balances = pool.balances(&user.tokens).await;
+    //the routed swap took the intermediate tokens from a temporary account rather than the user's own
+    expected_balances[1] = INPUT_AMOUNT;
+
+    assert_eq!(routed_balances, expected_balances);
+    assert!(routed_balances[2] > INPUT_AMOUNT);
+    assert_eq!(routed_pool.pool_balances().await, pool.pool_balances().await);
+}
+
+#[tokio::test]
+async fn test_routed_swap_refunds_intermediate_account() {
+    let (mut pool, user) = funded_pool().await;
+    routed_swap(&mut pool, &user, 0).await.unwrap();
+
+    let intermediate_key = find_intermediate_address(&pool::id(), &user.keypair.pubkey(), &pool.token_mints[1]).0;
+    assert_eq!(
+        pool.context.banks_client.get_account(intermediate_key).await.unwrap(),
+        None
+    );
+    let user_account = pool
+        .context
+        .banks_client
+        .get_account(user.keypair.pubkey())
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(user_account.lamports, LAMPORTS_PER_SOL);
+}
+
+#[tokio::test]
+async fn test_routed_swap_checks_minimum_output() {
+    let (mut pool, user) = funded_pool().await;
+    assert_eq!(
+        routed_swap(&mut pool, &user, 2 * INPUT_AMOUNT).await,
+        Err(InstructionError::Custom(PoolError::MinimumOutputNotMet as u32))
+    );
+    assert_eq!(pool.balances(&user.tokens).await, [INPUT_AMOUNT; TOKEN_COUNT]);
+}
+
+#[tokio::test]
+async fn test_routed_swap_requires_matching_intermediate_mint() {
+    let (mut pool, user) = funded_pool().await;
+    let ix = create_routed_swap_ix(
+        PoolInstruction::RoutedSwap {
+            exact_input_amount: INPUT_AMOUNT,
+            input_token_index: 0,
+            first_output_index: 1,
+            second_input_index: 2,
+            output_token_index: 3,
+            minimum_output_amount: 0,
+        },
+        &pool::id(),
+        &user.keypair.pubkey(),
+        &user.tokens[0],
+        &user.tokens[3],
+        &pool.token_mints[1],
+        &pool.route_leg(),
+        &pool.route_leg(),
+    )
+    .unwrap();
+    assert_eq!(
+        pool.execute_transaction(ix, &[&user.keypair]).await,
+        Err(InstructionError::Custom(PoolError::InvalidMintAccount as u32))
+    );
+}
@@ -0,0 +1,472 @@
+#![cfg(feature = "test-bpf")]
+
+mod helpers;
+
+use helpers::{bank::*, AmountT};
+
+use borsh::{BorshDeserialize, BorshSerialize};
+use pool::{
+    client::{
+        amm::{AmmQuote, AmmQuoteParams, AmmSwapParams, PoolAmm},
+        route_cache::RouteCache,
+        route_plan::{RoutePlan, ROUTE_PLAN_VERSION},
+        router::{Router, RouterConfig, DIRECT_SWAP_COMPUTE_UNITS, MAX_SPLIT_ROUTES, ROUTED_SWAP_COMPUTE_UNITS},
+        venue::Venue,
+        ClientError, ClientResult,
+    },
+    decimal::DecimalU64,
+    instruction::{DeFiInstruction, PoolInstruction},
+    pool_fee::PoolFee,
+    state::PoolState,
+    TOKEN_COUNT,
+};
+use solana_program_test::*;
+use solana_sdk::{account::Account, instruction::Instruction, pubkey::Pubkey, signature::Signer};
+use std::collections::HashMap;
+
+const POOL_BALANCE: AmountT = 1_000_000_000;
+const INPUT_AMOUNT: AmountT = 1_000_000;
+
+async fn funded_pool() -> BankPool {
+    let mut pool = BankPool::new(&BankPoolParams::default()).await.unwrap();
+    let lp = pool.create_user(&[POOL_BALANCE; TOKEN_COUNT]);
+    pool.execute_defi_instruction(
+        DeFiInstruction::Add {
+            input_amounts: [POOL_BALANCE; TOKEN_COUNT],
+            minimum_mint_amount: 0,
+        },
+        &lp,
+    )
+    .await
+    .unwrap();
+    pool
+}
+
+//a pool made up from `state` and the real pool's accounts, which is enough to quote but not to swap in it
+fn made_up_pool(
+    accounts: &mut HashMap<Pubkey, Account>,
+    real_pool: &Pubkey,
+    state: &PoolState<TOKEN_COUNT>,
+) -> (Pubkey, PoolAmm) {
+    let mut account = accounts[real_pool].clone();
+    account.data = state.try_to_vec().unwrap();
+    //the pool authority has to be a valid program address of the made up pool's key
+    let key = std::iter::repeat_with(Pubkey::new_unique)
+        .find(|key| Pubkey::create_program_address(&[&key.to_bytes(), &[state.nonce]], &pool::id()).is_ok())
+        .unwrap();
+    accounts.insert(key, account.clone());
+    let mut amm = PoolAmm::from_keyed_account(&key, &account).unwrap();
+    amm.update(accounts).unwrap();
+    (key, amm)
+}
+
+#[tokio::test]
+async fn test_direct_route() {
+    let mut pool = funded_pool().await;
+    let router = Router::new(pool::id(), vec![pool.amm().await]);
+    let mints = pool.token_mints;
+
+    let route = router.find_best_route(&mints[0], &mints[1], INPUT_AMOUNT).unwrap();
+    assert_eq!(route.hops.len(), 1);
+    assert_eq!(route.hops[0].leg.pool, pool.pool);
+    assert_eq!((route.hops[0].input_index, route.hops[0].output_index), (0, 1));
+    assert_eq!(route.in_amount(), INPUT_AMOUNT);
+
+    let mut balances = [0; TOKEN_COUNT];
+    balances[0] = INPUT_AMOUNT;
+    let user = pool.create_user(&balances);
+    let ixs = route
+        .instructions(
+            &user.keypair.pubkey(),
+            &user.tokens[0],
+            &user.tokens[1],
+            route.out_amount(),
+        )
+        .unwrap();
+    pool.execute_instructions(&ixs, &[&user.keypair]).await.unwrap();
+    assert_eq!(pool.balance(&user.tokens[0]).await, 0);
+    assert_eq!(pool.balance(&user.tokens[1]).await, route.out_amount());
+
+    let unknown_mint = Pubkey::new_unique();
+    assert!(matches!(
+        router.find_best_route(&mints[0], &unknown_mint, INPUT_AMOUNT),
+        Err(ClientError::NoRoute(mint_in, mint_out)) if mint_in == mints[0] && mint_out == unknown_mint
+    ));
+}
+
+#[tokio::test]
+async fn test_two_hop_route() {
+    let mut pool = funded_pool().await;
+    let amm = pool.amm().await;
+    let mints = pool.token_mints;
+
+    //a second pool that only shares token 1 with the first one
+    let mut accounts = pool.accounts(&amm.get_accounts_to_update()).await;
+    let mut state = PoolState::<TOKEN_COUNT>::deserialize(&mut accounts[&pool.pool].data.as_slice()).unwrap();
+    for (i, mint) in state.token_mint_keys.iter_mut().enumerate() {
+        if i != 1 {
+            *mint = Pubkey::new_unique();
+        }
+    }
+    let other_mints = state.token_mint_keys;
+    let (other_pool, other_amm) = made_up_pool(&mut accounts, &pool.pool, &state);
+    let router = Router::new(pool::id(), vec![amm, other_amm]);
+
+    let route = router
+        .find_best_route(&other_mints[0], &mints[2], INPUT_AMOUNT)
+        .unwrap();
+    assert_eq!(route.hops.len(), 2);
+    let (first, second) = (&route.hops[0], &route.hops[1]);
+    assert_eq!(first.leg.pool, other_pool);
+    assert_eq!(first.output_mint, mints[1]);
+    assert_eq!(second.leg.pool, pool.pool);
+    assert_eq!(second.in_amount, first.out_amount);
+    assert_eq!((second.input_index, second.output_index), (1, 2));
+
+    let user = Pubkey::new_unique();
+    let ixs = route
+        .instructions(&user, &Pubkey::new_unique(), &Pubkey::new_unique(), 0)
+        .unwrap();
+    assert_eq!(ixs.len(), 1);
+    assert_eq!(
+        ixs[0].data,
+        PoolInstruction::<TOKEN_COUNT>::RoutedSwap {
+            exact_input_amount: INPUT_AMOUNT,
+            input_token_index: 0,
+            first_output_index: 1,
+            second_input_index: 1,
+            output_token_index: 2,
+            minimum_output_amount: 0,
+        }
+        .try_to_vec()
+        .unwrap()
+    );
+
+    //swaps within the first pool don't take a detour
+    assert_eq!(
+        router
+            .find_best_route(&mints[0], &mints[2], INPUT_AMOUNT)
+            .unwrap()
+            .hops
+            .len(),
+        1
+    );
+}
+
+#[tokio::test]
+async fn test_gas_aware_scoring() {
+    let mut pool = funded_pool().await;
+    let amm = pool.amm().await;
+    let mints = pool.token_mints;
+
+    //a direct pool from token 0 to a new token with a higher fee than the two hop route through token 1 pays in total
+    let mut accounts = pool.accounts(&amm.get_accounts_to_update()).await;
+    let state = |accounts: &HashMap<Pubkey, Account>| {
+        PoolState::<TOKEN_COUNT>::deserialize(&mut accounts[&pool.pool].data.as_slice()).unwrap()
+    };
+    let output_mint = Pubkey::new_unique();
+    let (mut direct_state, mut second_state) = (state(&accounts), state(&accounts));
+    for i in 0..TOKEN_COUNT {
+        direct_state.token_mint_keys[i] = Pubkey::new_unique();
+        second_state.token_mint_keys[i] = Pubkey::new_unique();
+    }
+    direct_state.token_mint_keys[0] = mints[0];
+    direct_state.token_mint_keys[2] = output_mint;
+    direct_state.lp_fee = PoolFee::new(DecimalU64::new(20, 6).unwrap()).unwrap();
+    second_state.token_mint_keys[1] = mints[1];
+    second_state.token_mint_keys[2] = output_mint;
+    let (direct_pool, direct_amm) = made_up_pool(&mut accounts, &pool.pool, &direct_state);
+    let (_, second_amm) = made_up_pool(&mut accounts, &pool.pool, &second_state);
+    let mut router = Router::new(pool::id(), vec![amm, direct_amm, second_amm]);
+
+    //by output alone the two hop route wins by a few units
+    let two_hop = router.find_best_route(&mints[0], &output_mint, INPUT_AMOUNT).unwrap();
+    assert_eq!(two_hop.hops.len(), 2);
+
+    let config = RouterConfig {
+        hop_penalty_bps: 1,
+        ..RouterConfig::default()
+    };
+    router.set_config(config);
+    let route = router.find_best_route(&mints[0], &output_mint, INPUT_AMOUNT).unwrap();
+    assert_eq!(route.hops.len(), 1);
+    assert_eq!(route.hops[0].leg.pool, direct_pool);
+    assert!(route.out_amount() < two_hop.out_amount());
+
+    //a lamport per compute unit, which the two hop route needs more of
+    let mut config = RouterConfig {
+        compute_unit_price: 1_000_000,
+        ..RouterConfig::default()
+    };
+    assert_eq!(two_hop.compute_units(&config), ROUTED_SWAP_COMPUTE_UNITS);
+    assert_eq!(route.compute_units(&config), DIRECT_SWAP_COMPUTE_UNITS);
+    assert_eq!(two_hop.priority_fee(&config), ROUTED_SWAP_COMPUTE_UNITS as u64);
+    //fees aren't scored without a price for the output token
+    router.set_config(config.clone());
+    assert_eq!(
+        router.find_best_route(&mints[0], &output_mint, INPUT_AMOUNT).unwrap(),
+        two_hop
+    );
+    config.lamport_prices.insert(output_mint, 0.001);
+    router.set_config(config.clone());
+    assert_eq!(
+        router.find_best_route(&mints[0], &output_mint, INPUT_AMOUNT).unwrap(),
+        route
+    );
+    config.fee_weight = 0.0;
+    router.set_config(config);
+    assert_eq!(
+        router.find_best_route(&mints[0], &output_mint, INPUT_AMOUNT).unwrap(),
+        two_hop
+    );
+}
+
+#[tokio::test]
+async fn test_split_route() {
+    let mut pool = funded_pool().await;
+    let amm = pool.amm().await;
+    let mints = pool.token_mints;
+
+    //two more pools just like the first one
+    let mut accounts = pool.accounts(&amm.get_accounts_to_update()).await;
+    let state = PoolState::<TOKEN_COUNT>::deserialize(&mut accounts[&pool.pool].data.as_slice()).unwrap();
+    let (_, second_amm) = made_up_pool(&mut accounts, &pool.pool, &state);
+    let (_, third_amm) = made_up_pool(&mut accounts, &pool.pool, &state);
+    let router = Router::new(pool::id(), vec![amm, second_amm, third_amm]);
+
+    //an order large enough for price impact to matter is split evenly
+    let amount = POOL_BALANCE;
+    let single = router.find_best_route(&mints[0], &mints[1], amount).unwrap();
+    let split = router.find_best_split(&mints[0], &mints[1], amount).unwrap();
+    assert_eq!(split.routes.len(), MAX_SPLIT_ROUTES);
+    assert_eq!(split.in_amount(), amount);
+    assert!(split.out_amount() > single.out_amount());
+    for route in split.routes.iter() {
+        assert!(route.in_amount() >= amount / 4 && route.in_amount() <= amount / 2);
+    }
+
+    let user = Pubkey::new_unique();
+    let minimum_output_amount = split.out_amount() - 1;
+    let ixs = split
+        .instructions(
+            &user,
+            &Pubkey::new_unique(),
+            &Pubkey::new_unique(),
+            minimum_output_amount,
+        )
+        .unwrap();
+    assert_eq!(ixs.len(), MAX_SPLIT_ROUTES);
+
+    //too small to split
+    let split = router.find_best_split(&mints[0], &mints[1], 10).unwrap();
+    assert_eq!(split.routes.len(), 1);
+}
+
+#[tokio::test]
+async fn test_route_cache() {
+    let mut pool = funded_pool().await;
+    let amm = pool.amm().await;
+    let mints = pool.token_mints;
+    let keys = amm.get_accounts_to_update();
+    let accounts = pool.accounts(&keys).await;
+    let mut cache = RouteCache::new(Router::new(pool::id(), vec![amm]), accounts);
+
+    let route = cache.find_best_route(&mints[0], &mints[1], INPUT_AMOUNT).unwrap();
+    assert_eq!(cache.cached_routes(), 1);
+    assert_eq!(
+        cache.find_best_route(&mints[0], &mints[1], INPUT_AMOUNT).unwrap(),
+        route
+    );
+    cache.apply(&Pubkey::new_unique(), Account::default()).unwrap();
+    assert_eq!(cache.cached_routes(), 1);
+
+    let mut balances = [0; TOKEN_COUNT];
+    balances[0] = INPUT_AMOUNT;
+    let user = pool.create_user(&balances);
+    let ixs = route
+        .instructions(&user.keypair.pubkey(), &user.tokens[0], &user.tokens[1], 0)
+        .unwrap();
+    pool.execute_instructions(&ixs, &[&user.keypair]).await.unwrap();
+
+    //the swap changed the pool's token accounts
+    for (key, account) in pool.accounts(&keys).await {
+        cache.apply(&key, account).unwrap();
+    }
+    assert_eq!(cache.cached_routes(), 0);
+    let requoted = cache.find_best_route(&mints[0], &mints[1], INPUT_AMOUNT).unwrap();
+    assert!(requoted.out_amount() < route.out_amount());
+    let fresh = Router::new(pool::id(), vec![pool.amm().await])
+        .find_best_route(&mints[0], &mints[1], INPUT_AMOUNT)
+        .unwrap();
+    assert_eq!(requoted, fresh);
+}
+
+//another program's pool that pays `rate` times the input in either direction
+struct FixedRateVenue {
+    program_id: Pubkey,
+    key: Pubkey,
+    mints: [Pubkey; 2],
+    rate: u64,
+}
+
+impl Venue for FixedRateVenue {
+    fn key(&self) -> Pubkey {
+        self.key
+    }
+
+    fn label(&self) -> String {
+        "Fixed Rate".to_string()
+    }
+
+    fn get_reserve_mints(&self) -> Vec<Pubkey> {
+        self.mints.to_vec()
+    }
+
+    fn get_accounts_to_update(&self) -> Vec<Pubkey> {
+        vec![]
+    }
+
+    fn update(&mut self, _accounts: &HashMap<Pubkey, Account>) -> ClientResult<()> {
+        Ok(())
+    }
+
+    fn quote(&self, params: &AmmQuoteParams) -> ClientResult<AmmQuote> {
+        Ok(AmmQuote {
+            in_amount: params.amount,
+            out_amount: params.amount * self.rate,
+            fee_amount: 0,
+            fee_mint: params.output_mint,
+        })
+    }
+
+    fn build_swap_ix(&self, params: &AmmSwapParams) -> ClientResult<Instruction> {
+        Ok(Instruction {
+            program_id: self.program_id,
+            accounts: vec![],
+            data: [params.in_amount.to_le_bytes(), params.out_amount.to_le_bytes()].concat(),
+        })
+    }
+}
+
+#[tokio::test]
+async fn test_venue_route() {
+    let mut pool = funded_pool().await;
+    let mints = pool.token_mints;
+    let mut router = Router::new(pool::id(), vec![pool.amm().await]);
+    let venue_program_id = Pubkey::new_unique();
+    let external_mint = Pubkey::new_unique();
+    router.add_venue(Box::new(FixedRateVenue {
+        program_id: venue_program_id,
+        key: Pubkey::new_unique(),
+        mints: [mints[1], external_mint],
+        rate: 1,
+    }));
+
+    //through our pool into the venue
+    let route = router
+        .find_best_venue_route(&mints[0], &external_mint, INPUT_AMOUNT)
+        .unwrap();
+    assert_eq!(route.hops.len(), 2);
+    let (first, second) = (&route.hops[0], &route.hops[1]);
+    assert_eq!((first.venue, first.output_mint), (pool.pool, mints[1]));
+    let our_route = router.find_best_route(&mints[0], &mints[1], INPUT_AMOUNT).unwrap();
+    assert_eq!(first.out_amount, our_route.out_amount());
+    assert_eq!(second.out_amount, first.out_amount);
+
+    let (user, intermediate) = (Pubkey::new_unique(), Pubkey::new_unique());
+    let (input, output) = (Pubkey::new_unique(), Pubkey::new_unique());
+    assert!(router
+        .venue_route_instructions(&route, &user, &input, None, &output, 0)
+        .is_err());
+    let ixs = router
+        .venue_route_instructions(&route, &user, &input, Some(&intermediate), &output, 5)
+        .unwrap();
+    assert_eq!(ixs.len(), 2);
+    assert_eq!(ixs[0].program_id, pool::id());
+    assert_eq!(ixs[1].program_id, venue_program_id);
+    //the second swap takes exactly the first one's output
+    assert_eq!(
+        ixs[1].data,
+        [first.out_amount.to_le_bytes(), 5u64.to_le_bytes()].concat()
+    );
+
+    //a venue that pays more wins the direct swap
+    router.add_venue(Box::new(FixedRateVenue {
+        program_id: venue_program_id,
+        key: Pubkey::new_unique(),
+        mints: [mints[0], mints[1]],
+        rate: 2,
+    }));
+    let route = router
+        .find_best_venue_route(&mints[0], &mints[1], INPUT_AMOUNT)
+        .unwrap();
+    assert_eq!(route.hops.len(), 1);
+    assert_eq!(route.out_amount(), 2 * INPUT_AMOUNT);
+    assert_eq!(route.hops[0].label, "Fixed Rate");
+}
+
+#[tokio::test]
+async fn test_price_impact() {
+    let mut pool = funded_pool().await;
+    let mints = pool.token_mints;
+    let router = Router::new(pool::id(), vec![pool.amm().await]);
+
+    let small = router.find_best_route(&mints[0], &mints[1], INPUT_AMOUNT).unwrap();
+    let large = router.find_best_route(&mints[0], &mints[1], POOL_BALANCE).unwrap();
+    let small_impact = router.price_impact(&small).unwrap();
+    let large_impact = router.price_impact(&large).unwrap();
+    assert!((0.0..0.0001).contains(&small_impact));
+    assert!(large_impact > 10.0 * small_impact && large_impact < 1.0);
+
+    //after a swap in the same direction the route pays less
+    let mut balances = [0; TOKEN_COUNT];
+    balances[0] = POOL_BALANCE;
+    let user = pool.create_user(&balances);
+    let ixs = large
+        .instructions(&user.keypair.pubkey(), &user.tokens[0], &user.tokens[1], 0)
+        .unwrap();
+    pool.execute_instructions(&ixs, &[&user.keypair]).await.unwrap();
+    let router = Router::new(pool::id(), vec![pool.amm().await]);
+    let requoted = router.requote(&small).unwrap();
+    assert_eq!(requoted.in_amount(), INPUT_AMOUNT);
+    assert!(requoted.out_amount() < small.out_amount());
+}
+
+#[tokio::test]
+async fn test_route_plan() {
+    let mut pool = funded_pool().await;
+    let mints = pool.token_mints;
+    let router = Router::new(pool::id(), vec![pool.amm().await]);
+    let route = router.find_best_route(&mints[0], &mints[1], INPUT_AMOUNT).unwrap();
+    let plan = RoutePlan::new(&route, route.out_amount() - 1, 100);
+    assert_eq!(plan.pools(), vec![pool.pool]);
+    assert!(!plan.is_expired(100) && plan.is_expired(101));
+
+    let bytes = plan.to_bytes();
+    assert_eq!(bytes[0], ROUTE_PLAN_VERSION);
+    assert_eq!(RoutePlan::from_bytes(&bytes).unwrap(), plan);
+    let json = plan.to_json();
+    assert!(json.contains(&format!("\"pool\":\"{}\"", pool.pool)));
+    assert_eq!(RoutePlan::from_json(&json).unwrap(), plan);
+    assert_eq!(plan.to_route(&router).unwrap(), route);
+
+    //other versions are rejected, whatever their fields
+    let mut future_bytes = bytes.clone();
+    future_bytes[0] = ROUTE_PLAN_VERSION + 1;
+    assert!(matches!(
+        RoutePlan::from_bytes(&future_bytes),
+        Err(ClientError::InvalidRoutePlan(_))
+    ));
+    let future_json = format!("{{\"version\":{},\"route\":[]}}", ROUTE_PLAN_VERSION + 1);
+    assert!(matches!(
+        RoutePlan::from_json(&future_json),
+        Err(ClientError::InvalidRoutePlan(reason)) if reason.contains("version")
+    ));
+
+    let mut mismatched = plan.clone();
+    mismatched.hops[0].output_index = 2;
+    assert!(matches!(
+        mismatched.to_route(&router),
+        Err(ClientError::InvalidRoutePlan(_))
+    ));
+}
@@ -0,0 +1,170 @@
+#![cfg(feature = "test-bpf")]
+
+mod helpers;
+
+use helpers::{bank::*, AmountT};
+
+use pool::{error::PoolError, instruction::DeFiInstruction, TOKEN_COUNT};
+use solana_program::program_option::COption;
+use solana_program_test::*;
+use solana_sdk::{
+    instruction::InstructionError,
+    signature::{Keypair, Signer},
+};
+use spl_token::{error::TokenError, state::Account as TokenState};
+
+const EXPIRY: i64 = 3600;
+
+fn swap(input_index: usize, amount: AmountT) -> DeFiInstruction<TOKEN_COUNT> {
+    let mut exact_input_amounts = [0; TOKEN_COUNT];
+    exact_input_amounts[input_index] = amount;
+    DeFiInstruction::SwapExactInput {
+        exact_input_amounts,
+        output_token_index: 1 - input_index as u8,
+        minimum_output_amount: 0,
+    }
+}
+
+fn limits(token_0_limit: AmountT) -> [AmountT; TOKEN_COUNT] {
+    let mut limits = [0; TOKEN_COUNT];
+    limits[0] = token_0_limit;
+    limits
+}
+
+async fn funded_pool() -> (BankPool, BankUser) {
+    let mut pool = BankPool::new(&BankPoolParams::default()).await.unwrap();
+    let seed = pool.create_user(&[100_000_000; TOKEN_COUNT]);
+    pool.execute_defi_instruction(
+        DeFiInstruction::Add {
+            input_amounts: [100_000_000; TOKEN_COUNT],
+            minimum_mint_amount: 0,
+        },
+        &seed,
+    )
+    .await
+    .unwrap();
+    let user = pool.create_user(&[10_000_000; TOKEN_COUNT]);
+    (pool, user)
+}
+
+#[tokio::test]
+async fn test_session_swaps() {
+    let (mut pool, user) = funded_pool().await;
+    let user_key = user.keypair.pubkey();
+    let session_key = Keypair::new();
+    let expiry_ts = pool.now().await + EXPIRY;
+    pool.authorize_session(&user, &session_key.pubkey(), expiry_ts, limits(3_000_000))
+        .await
+        .unwrap();
+    let session = pool.session(&user_key).await.unwrap();
+    assert_eq!(session.session_key, session_key.pubkey());
+    assert_eq!(session.expiry_ts, expiry_ts);
+
+    let output_before = pool.balance(&user.tokens[1]).await;
+    pool.execute_session_swap(swap(0, 2_000_000), &user, &session_key)
+        .await
+        .unwrap();
+    assert_eq!(pool.balance(&user.tokens[0]).await, 8_000_000);
+    assert!(pool.balance(&user.tokens[1]).await > output_before);
+    assert_eq!(
+        pool.session(&user_key).await.unwrap().remaining_limits,
+        limits(1_000_000)
+    );
+
+    //the limits are per token
+    assert_eq!(
+        pool.execute_session_swap(swap(0, 2_000_000), &user, &session_key).await,
+        Err(InstructionError::Custom(PoolError::SessionLimitExceeded as u32))
+    );
+    assert_eq!(
+        pool.execute_session_swap(swap(1, 1_000_000), &user, &session_key).await,
+        Err(InstructionError::Custom(PoolError::SessionLimitExceeded as u32))
+    );
+    pool.execute_session_swap(swap(0, 1_000_000), &user, &session_key)
+        .await
+        .unwrap();
+
+    //the user can still swap itself
+    pool.execute_defi_instruction(swap(0, 1_000_000), &user).await.unwrap();
+
+    //and the session ends at its expiry
+    pool.authorize_session(&user, &session_key.pubkey(), expiry_ts, limits(3_000_000))
+        .await
+        .unwrap();
+    pool.advance_clock(EXPIRY).await;
+    assert_eq!(
+        pool.execute_session_swap(swap(0, 1_000_000), &user, &session_key).await,
+        Err(InstructionError::Custom(PoolError::SessionExpired as u32))
+    );
+}
+
+#[tokio::test]
+async fn test_session_key_only_swaps_for_the_user() {
+    let (mut pool, user) = funded_pool().await;
+    let session_key = Keypair::new();
+    let expiry_ts = pool.now().await + EXPIRY;
+    pool.authorize_session(&user, &session_key.pubkey(), expiry_ts, limits(3_000_000))
+        .await
+        .unwrap();
+
+    //another key can't use the session
+    assert_eq!(
+        pool.execute_session_swap(swap(0, 1_000_000), &user, &Keypair::new())
+            .await,
+        Err(InstructionError::MissingRequiredSignature)
+    );
+
+    //nor can the session key pay out to its own account
+    let thief = pool.create_user(&[0; TOKEN_COUNT]);
+    let mut ix = pool.defi_ix(swap(0, 1_000_000), &user);
+    ix.accounts[5 + TOKEN_COUNT + 1].pubkey = thief.tokens[1];
+    pool::instruction::sign_swap_with_session::<TOKEN_COUNT>(
+        &mut ix,
+        &pool.pool,
+        &user.keypair.pubkey(),
+        &session_key.pubkey(),
+    );
+    assert_eq!(
+        pool.execute_transaction(ix, &[&session_key]).await,
+        Err(InstructionError::Custom(TokenError::OwnerMismatch as u32))
+    );
+
+    //or swap without a session
+    let mut ix = pool.defi_ix(swap(0, 1_000_000), &user);
+    ix.accounts[4 + TOKEN_COUNT].is_signer = false;
+    assert_eq!(
+        pool.execute_transaction(ix, &[]).await,
+        Err(InstructionError::NotEnoughAccountKeys)
+    );
+}
+
+#[tokio::test]
+async fn test_revoke_session() {
+    let (mut pool, user) = funded_pool().await;
+    let user_key = user.keypair.pubkey();
+    let session_key = Keypair::new();
+    let expiry_ts = pool.now().await + EXPIRY;
+    pool.authorize_session(&user, &session_key.pubkey(), expiry_ts, limits(3_000_000))
+        .await
+        .unwrap();
+    let session_address = pool::instruction::find_session_address(&pool::id(), &pool.pool, &user_key).0;
+    let token_state: TokenState = pool.get_packed(&user.tokens[0]).await;
+    assert_eq!(token_state.delegate, COption::Some(session_address));
+    assert_eq!(token_state.delegated_amount, 3_000_000);
+    //tokens without a limit aren't delegated
+    assert_eq!(
+        pool.get_packed::<TokenState>(&user.tokens[1]).await.delegate,
+        COption::None
+    );
+
+    pool.revoke_session(&user).await.unwrap();
+    assert_eq!(pool.session(&user_key).await, None);
+    assert_eq!(
+        pool.get_packed::<TokenState>(&user.tokens[0]).await.delegate,
+        COption::None
+    );
+    assert_eq!(
+        pool.execute_session_swap(swap(0, 1_000_000), &user, &session_key).await,
+        Err(InstructionError::UninitializedAccount)
+    );
+}
@@ -0,0 +1,229 @@
+#![cfg(feature = "test-bpf")]
+
+mod helpers;
+
+use helpers::{bank::*, AmountT};
+
+use pool::{
+    common::create_array,
+    error::PoolError,
+    instruction::{create_redeem_sunset_ix, DeFiInstruction, GovernanceInstruction},
+    processor::ENACT_DELAY,
+    TOKEN_COUNT,
+};
+use solana_program_test::*;
+use solana_sdk::{
+    instruction::{Instruction, InstructionError},
+    pubkey::Pubkey,
+    signature::Signer,
+};
+use spl_token::instruction::transfer;
+
+const POOL_BALANCE: AmountT = 1_000_000_000;
+
+async fn add(pool: &mut BankPool, amount: AmountT) -> BankUser {
+    let user = pool.create_user(&[amount; TOKEN_COUNT]);
+    pool.execute_defi_instruction(
+        DeFiInstruction::Add {
+            input_amounts: [amount; TOKEN_COUNT],
+            minimum_mint_amount: 0,
+        },
+        &user,
+    )
+    .await
+    .unwrap();
+    user
+}
+
+fn swap() -> DeFiInstruction<TOKEN_COUNT> {
+    let mut exact_input_amounts = [0; TOKEN_COUNT];
+    exact_input_amounts[0] = POOL_BALANCE / 100;
+    DeFiInstruction::SwapExactInput {
+        exact_input_amounts,
+        output_token_index: 1,
+        minimum_output_amount: 0,
+    }
+}
+
+async fn set_sunset(pool: &mut BankPool, deadline: i64) -> Result<(), InstructionError> {
+    pool.execute_governance_instruction(GovernanceInstruction::SetSunset { deadline }, None)
+        .await
+}
+
+fn redeem_sunset_ix(pool: &BankPool, lp_account: &Pubkey, user: &BankUser) -> Instruction {
+    create_redeem_sunset_ix(
+        &pool::id(),
+        &pool.pool,
+        &pool.authority,
+        &pool.token_accounts,
+        &pool.lp_mint,
+        &spl_token::id(),
+        lp_account,
+        &user.keypair.pubkey(),
+        &user.tokens,
+    )
+    .unwrap()
+}
+
+async fn redeem_sunset(pool: &mut BankPool, user: &BankUser) -> Result<(), InstructionError> {
+    let ix = redeem_sunset_ix(pool, &user.lp, user);
+    pool.execute_transaction(ix, &[&user.keypair]).await
+}
+
+fn share(balances: &[AmountT; TOKEN_COUNT], lp_amount: AmountT, lp_supply: AmountT) -> [AmountT; TOKEN_COUNT] {
+    create_array(|i| (balances[i] as u128 * lp_amount as u128 / lp_supply as u128) as AmountT)
+}
+
+#[tokio::test]
+async fn test_sunset_only_allows_removes_until_its_deadline() {
+    let mut pool = BankPool::new(&BankPoolParams::default()).await.unwrap();
+    let user = add(&mut pool, POOL_BALANCE).await;
+    let now = pool.now().await;
+    assert_eq!(
+        set_sunset(&mut pool, now + ENACT_DELAY - 1).await,
+        Err(InstructionError::Custom(PoolError::InsufficientDelay as u32))
+    );
+    set_sunset(&mut pool, now + ENACT_DELAY).await.unwrap();
+
+    assert_eq!(
+        pool.execute_defi_instruction(swap(), &user).await,
+        Err(InstructionError::Custom(PoolError::PoolIsSunset as u32))
+    );
+    assert_eq!(
+        pool.execute_defi_instruction(
+            DeFiInstruction::Add {
+                input_amounts: [0; TOKEN_COUNT],
+                minimum_mint_amount: 0,
+            },
+            &user,
+        )
+        .await,
+        Err(InstructionError::Custom(PoolError::PoolIsSunset as u32))
+    );
+    let remove = |exact_burn_amount| DeFiInstruction::RemoveUniform {
+        exact_burn_amount,
+        minimum_output_amounts: [0; TOKEN_COUNT],
+    };
+    pool.execute_defi_instruction(remove(POOL_BALANCE / 2), &user)
+        .await
+        .unwrap();
+
+    //governance can call it off until the deadline
+    set_sunset(&mut pool, 0).await.unwrap();
+    pool.execute_defi_instruction(swap(), &user).await.unwrap();
+    set_sunset(&mut pool, now + ENACT_DELAY).await.unwrap();
+
+    pool.set_now(now + ENACT_DELAY).await;
+    assert_eq!(
+        pool.execute_defi_instruction(remove(1), &user).await,
+        Err(InstructionError::Custom(PoolError::PoolIsSunset as u32))
+    );
+    assert_eq!(
+        set_sunset(&mut pool, 0).await,
+        Err(InstructionError::Custom(PoolError::PoolIsSunset as u32))
+    );
+}
+
+#[tokio::test]
+async fn test_redeem_sunset_pays_every_lp_token_the_same() {
+    let mut pool = BankPool::new(&BankPoolParams::default()).await.unwrap();
+    let first = add(&mut pool, POOL_BALANCE).await;
+    let second = add(&mut pool, POOL_BALANCE / 2).await;
+    let (first_lp, second_lp) = (pool.balance(&first.lp).await, pool.balance(&second.lp).await);
+    assert_eq!(
+        redeem_sunset(&mut pool, &first).await,
+        Err(InstructionError::Custom(PoolError::InvalidEnact as u32))
+    );
+    let deadline = pool.now().await + ENACT_DELAY;
+    set_sunset(&mut pool, deadline).await.unwrap();
+    assert_eq!(
+        redeem_sunset(&mut pool, &first).await,
+        Err(InstructionError::Custom(PoolError::InsufficientDelay as u32))
+    );
+
+    pool.set_now(deadline).await;
+    let (lp_supply, pool_balances) = (pool.lp_total_supply().await, pool.pool_balances().await);
+    redeem_sunset(&mut pool, &first).await.unwrap();
+    let first_payout = share(&pool_balances, first_lp, lp_supply);
+    assert_eq!(pool.balances(&first.tokens).await, first_payout);
+    assert_eq!(pool.balance(&first.lp).await, 0);
+    //there's nothing left to redeem
+    assert_eq!(
+        redeem_sunset(&mut pool, &first).await,
+        Err(InstructionError::Custom(PoolError::InsufficientUserBalance as u32))
+    );
+
+    redeem_sunset(&mut pool, &second).await.unwrap();
+    let remaining_balances = create_array(|i| pool_balances[i] - first_payout[i]);
+    assert_eq!(
+        pool.balances(&second.tokens).await,
+        share(&remaining_balances, second_lp, lp_supply - first_lp)
+    );
+    assert_eq!(pool.lp_total_supply().await, lp_supply - first_lp - second_lp);
+}
+
+#[tokio::test]
+async fn test_redeem_sunset_after_transfer_pays_once() {
+    let mut pool = BankPool::new(&BankPoolParams::default()).await.unwrap();
+    let first = add(&mut pool, POOL_BALANCE).await;
+    let second = add(&mut pool, POOL_BALANCE).await;
+    let (first_lp, second_lp) = (pool.balance(&first.lp).await, pool.balance(&second.lp).await);
+    let deadline = pool.now().await + ENACT_DELAY;
+    set_sunset(&mut pool, deadline).await.unwrap();
+    pool.set_now(deadline).await;
+    let (lp_supply, pool_balances) = (pool.lp_total_supply().await, pool.pool_balances().await);
+
+    //the first holder moves half of its lp tokens to a fresh holder, each is paid for what it holds
+    let fresh = pool.create_user(&[0; TOKEN_COUNT]);
+    let moved = first_lp / 2;
+    let ix = transfer(
+        &spl_token::id(),
+        &first.lp,
+        &fresh.lp,
+        &first.keypair.pubkey(),
+        &[],
+        moved,
+    )
+    .unwrap();
+    pool.execute_transaction(ix, &[&first.keypair]).await.unwrap();
+    redeem_sunset(&mut pool, &first).await.unwrap();
+    redeem_sunset(&mut pool, &fresh).await.unwrap();
+
+    //redeemed lp tokens are burned, so neither account can be paid again
+    assert_eq!(
+        redeem_sunset(&mut pool, &fresh).await,
+        Err(InstructionError::Custom(PoolError::InsufficientUserBalance as u32))
+    );
+    assert_eq!(
+        redeem_sunset(&mut pool, &first).await,
+        Err(InstructionError::Custom(PoolError::InsufficientUserBalance as u32))
+    );
+    let first_payout = pool.balances(&first.tokens).await;
+    let fresh_payout = pool.balances(&fresh.tokens).await;
+    for i in 0..TOKEN_COUNT {
+        assert!(first_payout[i] + fresh_payout[i] <= share(&pool_balances, first_lp, lp_supply)[i]);
+    }
+
+    //so the holders who redeem last aren't diluted
+    redeem_sunset(&mut pool, &second).await.unwrap();
+    let second_payout = pool.balances(&second.tokens).await;
+    for i in 0..TOKEN_COUNT {
+        assert!(second_payout[i] >= share(&pool_balances, second_lp, lp_supply)[i]);
+    }
+}
+
+#[tokio::test]
+async fn test_redeem_sunset_requires_lp_owner() {
+    let mut pool = BankPool::new(&BankPoolParams::default()).await.unwrap();
+    let first = add(&mut pool, POOL_BALANCE).await;
+    let second = add(&mut pool, POOL_BALANCE).await;
+    let deadline = pool.now().await + ENACT_DELAY;
+    set_sunset(&mut pool, deadline).await.unwrap();
+    pool.set_now(deadline).await;
+
+    let ix = redeem_sunset_ix(&pool, &first.lp, &second);
+    assert_eq!(
+        pool.execute_transaction(ix, &[&second.keypair]).await,
+        Err(InstructionError::Custom(PoolError::InvalidUserAuthority as u32))
+    );
+}
@@ -0,0 +1,198 @@
+#![cfg(feature = "test-bpf")]
+
+mod helpers;
+
+use helpers::{bank::*, AmountT};
+
+use pool::{
+    error::PoolError,
+    instruction::{DeFiInstruction, GovernanceInstruction},
+    state::{Role, TokenFlags},
+    TOKEN_COUNT,
+};
+use solana_program_test::*;
+use solana_sdk::{
+    instruction::InstructionError,
+    signature::{Keypair, Signer},
+};
+
+const INPUT_DISABLED: InstructionError = InstructionError::Custom(PoolError::TokenInputDisabled as u32);
+const OUTPUT_DISABLED: InstructionError = InstructionError::Custom(PoolError::TokenOutputDisabled as u32);
+
+fn single(index: usize, amount: AmountT) -> [AmountT; TOKEN_COUNT] {
+    let mut amounts = [0; TOKEN_COUNT];
+    amounts[index] = amount;
+    amounts
+}
+
+fn swap(input_index: usize, output_index: usize) -> DeFiInstruction<TOKEN_COUNT> {
+    DeFiInstruction::SwapExactInput {
+        exact_input_amounts: single(input_index, 1_000_000),
+        output_token_index: output_index as u8,
+        minimum_output_amount: 0,
+    }
+}
+
+fn set_token_flags(token_index: u8, input_disabled: bool, output_disabled: bool) -> GovernanceInstruction<TOKEN_COUNT> {
+    GovernanceInstruction::SetTokenFlags {
+        token_index,
+        flags: TokenFlags {
+            input_disabled,
+            output_disabled,
+        },
+    }
+}
+
+async fn funded_pool() -> (BankPool, BankUser) {
+    let mut pool = BankPool::new(&BankPoolParams::default()).await.unwrap();
+    let user = pool.create_user(&[200_000_000; TOKEN_COUNT]);
+    pool.execute_defi_instruction(
+        DeFiInstruction::Add {
+            input_amounts: [100_000_000; TOKEN_COUNT],
+            minimum_mint_amount: 0,
+        },
+        &user,
+    )
+    .await
+    .unwrap();
+    (pool, user)
+}
+
+#[tokio::test]
+async fn test_disabled_input() {
+    let (mut pool, user) = funded_pool().await;
+    pool.execute_governance_instruction(set_token_flags(0, true, false), None)
+        .await
+        .unwrap();
+
+    assert_eq!(
+        pool.execute_defi_instruction(swap(0, 1), &user).await,
+        Err(INPUT_DISABLED)
+    );
+    assert_eq!(
+        pool.execute_defi_instruction(
+            DeFiInstruction::Add {
+                input_amounts: [1_000_000; TOKEN_COUNT],
+                minimum_mint_amount: 0,
+            },
+            &user
+        )
+        .await,
+        Err(INPUT_DISABLED)
+    );
+    assert_eq!(
+        pool.execute_defi_instruction(
+            DeFiInstruction::SwapExactOutput {
+                maximum_input_amount: AmountT::MAX,
+                input_token_index: 0,
+                exact_output_amounts: single(1, 1_000_000),
+            },
+            &user
+        )
+        .await,
+        Err(INPUT_DISABLED)
+    );
+
+    //the token can still be swapped out of the pool, and the other tokens added
+    pool.execute_defi_instruction(swap(1, 0), &user).await.unwrap();
+    pool.execute_defi_instruction(
+        DeFiInstruction::Add {
+            input_amounts: single(1, 1_000_000),
+            minimum_mint_amount: 0,
+        },
+        &user,
+    )
+    .await
+    .unwrap();
+
+    //enabling it again
+    pool.execute_governance_instruction(set_token_flags(0, false, false), None)
+        .await
+        .unwrap();
+    pool.execute_defi_instruction(swap(0, 1), &user).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_disabled_output() {
+    let (mut pool, user) = funded_pool().await;
+    pool.execute_governance_instruction(set_token_flags(1, false, true), None)
+        .await
+        .unwrap();
+
+    assert_eq!(
+        pool.execute_defi_instruction(swap(0, 1), &user).await,
+        Err(OUTPUT_DISABLED)
+    );
+    assert_eq!(
+        pool.execute_defi_instruction(
+            DeFiInstruction::RemoveExactBurn {
+                exact_burn_amount: 1_000_000,
+                output_token_index: 1,
+                minimum_output_amount: 0,
+            },
+            &user
+        )
+        .await,
+        Err(OUTPUT_DISABLED)
+    );
+    assert_eq!(
+        pool.execute_defi_instruction(
+            DeFiInstruction::RemoveExactOutput {
+                maximum_burn_amount: AmountT::MAX,
+                exact_output_amounts: single(1, 1_000_000),
+            },
+            &user
+        )
+        .await,
+        Err(OUTPUT_DISABLED)
+    );
+
+    pool.execute_defi_instruction(swap(1, 0), &user).await.unwrap();
+    //uniform removes always give all tokens
+    let before = pool.balance(&user.tokens[1]).await;
+    pool.execute_defi_instruction(
+        DeFiInstruction::RemoveUniform {
+            exact_burn_amount: 1_000_000,
+            minimum_output_amounts: [0; TOKEN_COUNT],
+        },
+        &user,
+    )
+    .await
+    .unwrap();
+    assert!(pool.balance(&user.tokens[1]).await > before);
+}
+
+#[tokio::test]
+async fn test_pause_admin_sets_token_flags() {
+    let (mut pool, user) = funded_pool().await;
+    let pause_admin = Keypair::new();
+    pool.execute_governance_instruction(
+        GovernanceInstruction::SetRole {
+            role: Role::PauseAdmin,
+            key: pause_admin.pubkey(),
+        },
+        None,
+    )
+    .await
+    .unwrap();
+
+    pool.execute_governance_instruction_as(set_token_flags(0, true, true), None, &pause_admin)
+        .await
+        .unwrap();
+    assert_eq!(
+        pool.state().await.token_flags[0],
+        TokenFlags {
+            input_disabled: true,
+            output_disabled: true
+        }
+    );
+    assert_eq!(
+        pool.execute_defi_instruction(swap(0, 1), &user).await,
+        Err(INPUT_DISABLED)
+    );
+    assert_eq!(
+        pool.execute_governance_instruction_as(set_token_flags(TOKEN_COUNT as u8, true, false), None, &pause_admin)
+            .await,
+        Err(InstructionError::InvalidInstructionData)
+    );
+}
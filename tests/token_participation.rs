@@ -0,0 +1,140 @@
+#![cfg(feature = "test-bpf")]
+
+mod helpers;
+
+use helpers::{bank::*, AmountT};
+
+use pool::{
+    error::PoolError,
+    instruction::{DeFiInstruction, GovernanceInstruction},
+    state::TokenParticipation,
+    TOKEN_COUNT,
+};
+use solana_program_test::*;
+use solana_sdk::instruction::InstructionError;
+
+const DEPOSIT_ONLY: InstructionError = InstructionError::Custom(PoolError::TokenDepositOnly as u32);
+const WITHDRAW_ONLY: InstructionError = InstructionError::Custom(PoolError::TokenWithdrawOnly as u32);
+//the token that the pool onboards resp. offboards
+const PHASED: usize = TOKEN_COUNT - 1;
+
+fn single(index: usize, amount: AmountT) -> [AmountT; TOKEN_COUNT] {
+    let mut amounts = [0; TOKEN_COUNT];
+    amounts[index] = amount;
+    amounts
+}
+
+fn swap(input_index: usize, output_index: usize) -> DeFiInstruction<TOKEN_COUNT> {
+    DeFiInstruction::SwapExactInput {
+        exact_input_amounts: single(input_index, 1_000_000),
+        output_token_index: output_index as u8,
+        minimum_output_amount: 0,
+    }
+}
+
+fn add(index: usize) -> DeFiInstruction<TOKEN_COUNT> {
+    DeFiInstruction::Add {
+        input_amounts: single(index, 1_000_000),
+        minimum_mint_amount: 0,
+    }
+}
+
+fn remove_exact_burn(output_index: usize) -> DeFiInstruction<TOKEN_COUNT> {
+    DeFiInstruction::RemoveExactBurn {
+        exact_burn_amount: 1_000_000,
+        output_token_index: output_index as u8,
+        minimum_output_amount: 0,
+    }
+}
+
+async fn funded_pool() -> (BankPool, BankUser) {
+    let mut pool = BankPool::new(&BankPoolParams::default()).await.unwrap();
+    let user = pool.create_user(&[200_000_000; TOKEN_COUNT]);
+    pool.execute_defi_instruction(
+        DeFiInstruction::Add {
+            input_amounts: [100_000_000; TOKEN_COUNT],
+            minimum_mint_amount: 0,
+        },
+        &user,
+    )
+    .await
+    .unwrap();
+    (pool, user)
+}
+
+#[tokio::test]
+async fn test_deposit_only_token_is_only_added() {
+    let (mut pool, user) = funded_pool().await;
+    pool.set_token_participation(PHASED as u8, TokenParticipation::DepositOnly)
+        .await
+        .unwrap();
+    assert_eq!(
+        pool.state().await.token_participation[PHASED],
+        TokenParticipation::DepositOnly
+    );
+
+    for defi_instruction in vec![swap(0, PHASED), swap(PHASED, 0), remove_exact_burn(PHASED)] {
+        assert_eq!(
+            pool.execute_defi_instruction(defi_instruction, &user).await,
+            Err(DEPOSIT_ONLY)
+        );
+    }
+    //the other tokens trade as usual while the onboarded one's balance builds up
+    pool.execute_defi_instruction(add(PHASED), &user).await.unwrap();
+    if TOKEN_COUNT > 2 {
+        pool.execute_defi_instruction(swap(0, 1), &user).await.unwrap();
+    }
+    pool.execute_defi_instruction(
+        DeFiInstruction::RemoveUniform {
+            exact_burn_amount: 1_000_000,
+            minimum_output_amounts: [0; TOKEN_COUNT],
+        },
+        &user,
+    )
+    .await
+    .unwrap();
+
+    pool.set_token_participation(PHASED as u8, TokenParticipation::Active)
+        .await
+        .unwrap();
+    pool.execute_defi_instruction(swap(0, PHASED), &user).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_withdraw_only_token_is_only_given_out() {
+    let (mut pool, user) = funded_pool().await;
+    pool.set_token_participation(PHASED as u8, TokenParticipation::WithdrawOnly)
+        .await
+        .unwrap();
+
+    for defi_instruction in vec![add(PHASED), swap(PHASED, 0)] {
+        assert_eq!(
+            pool.execute_defi_instruction(defi_instruction, &user).await,
+            Err(WITHDRAW_ONLY)
+        );
+    }
+    pool.execute_defi_instruction(swap(0, PHASED), &user).await.unwrap();
+    pool.execute_defi_instruction(remove_exact_burn(PHASED), &user)
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn test_set_token_participation_validation() {
+    let mut pool = BankPool::new(&BankPoolParams::default()).await.unwrap();
+    assert_eq!(
+        pool.state().await.token_participation,
+        [TokenParticipation::Active; TOKEN_COUNT]
+    );
+    assert_eq!(
+        pool.execute_governance_instruction(
+            GovernanceInstruction::SetTokenParticipation {
+                token_index: TOKEN_COUNT as u8,
+                participation: TokenParticipation::DepositOnly,
+            },
+            None,
+        )
+        .await,
+        Err(InstructionError::InvalidInstructionData)
+    );
+}
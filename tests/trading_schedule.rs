@@ -0,0 +1,176 @@
+#![cfg(feature = "test-bpf")]
+
+mod helpers;
+
+use helpers::{bank::*, AmountT};
+
+use pool::{
+    error::PoolError,
+    instruction::DeFiInstruction,
+    state::{MaintenanceWindow, TradingHours, MAX_MAINTENANCE_WINDOWS},
+    TOKEN_COUNT,
+};
+use solana_program::clock::UnixTimestamp;
+use solana_program_test::*;
+use solana_sdk::instruction::InstructionError;
+
+const POOL_BALANCE: AmountT = 1_000_000_000;
+const HOUR: UnixTimestamp = 3600;
+
+fn swap() -> DeFiInstruction<TOKEN_COUNT> {
+    let mut exact_input_amounts = [0; TOKEN_COUNT];
+    exact_input_amounts[0] = 1_000_000;
+    DeFiInstruction::SwapExactInput {
+        exact_input_amounts,
+        output_token_index: 1,
+        minimum_output_amount: 0,
+    }
+}
+
+fn remove_uniform(exact_burn_amount: AmountT) -> DeFiInstruction<TOKEN_COUNT> {
+    DeFiInstruction::RemoveUniform {
+        exact_burn_amount,
+        minimum_output_amounts: [0; TOKEN_COUNT],
+    }
+}
+
+async fn funded_pool() -> (BankPool, BankUser) {
+    let mut pool = BankPool::new(&BankPoolParams::default()).await.unwrap();
+    let user = pool.create_user(&[POOL_BALANCE; TOKEN_COUNT]);
+    pool.execute_defi_instruction(
+        DeFiInstruction::Add {
+            input_amounts: [POOL_BALANCE / 2; TOKEN_COUNT],
+            minimum_mint_amount: 0,
+        },
+        &user,
+    )
+    .await
+    .unwrap();
+    (pool, user)
+}
+
+//hours that open `offset` from now and stay open for `duration`
+fn hours_from(now: UnixTimestamp, offset: UnixTimestamp, duration: UnixTimestamp) -> TradingHours {
+    let secs_of_day = |ts: UnixTimestamp| ts.rem_euclid(24 * HOUR) as u32;
+    TradingHours {
+        open_secs: secs_of_day(now + offset),
+        close_secs: secs_of_day(now + offset + duration),
+    }
+}
+
+#[tokio::test]
+async fn test_swaps_only_within_trading_hours() {
+    let (mut pool, user) = funded_pool().await;
+    let now = pool.now().await;
+    pool.set_trading_schedule(hours_from(now, HOUR, 2 * HOUR), &[])
+        .await
+        .unwrap();
+    assert_eq!(
+        pool.execute_defi_instruction(swap(), &user).await,
+        Err(InstructionError::Custom(PoolError::OutsideTradingHours as u32))
+    );
+    //users can always exit
+    let lp_balance = pool.balance(&user.lp).await;
+    pool.execute_defi_instruction(remove_uniform(lp_balance / 2), &user)
+        .await
+        .unwrap();
+
+    pool.advance_clock(HOUR).await;
+    pool.execute_defi_instruction(swap(), &user).await.unwrap();
+    pool.advance_clock(2 * HOUR).await;
+    assert_eq!(
+        pool.execute_defi_instruction(swap(), &user).await,
+        Err(InstructionError::Custom(PoolError::OutsideTradingHours as u32))
+    );
+    //the same hours on the next day
+    pool.advance_clock(21 * HOUR).await;
+    pool.execute_defi_instruction(swap(), &user).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_trading_hours_past_midnight() {
+    let hours = TradingHours {
+        open_secs: 22 * HOUR as u32,
+        close_secs: 2 * HOUR as u32,
+    };
+    assert!(hours.is_open(23 * HOUR));
+    assert!(hours.is_open(24 * HOUR + HOUR));
+    assert!(!hours.is_open(12 * HOUR));
+
+    let (mut pool, user) = funded_pool().await;
+    let now = pool.now().await;
+    //open around the clock but for the next hour
+    pool.set_trading_schedule(hours_from(now, HOUR, 23 * HOUR), &[])
+        .await
+        .unwrap();
+    assert_eq!(
+        pool.execute_defi_instruction(swap(), &user).await,
+        Err(InstructionError::Custom(PoolError::OutsideTradingHours as u32))
+    );
+    pool.advance_clock(HOUR).await;
+    pool.execute_defi_instruction(swap(), &user).await.unwrap();
+
+    pool.set_trading_schedule(TradingHours::default(), &[]).await.unwrap();
+    pool.execute_defi_instruction(swap(), &user).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_maintenance_windows() {
+    let (mut pool, user) = funded_pool().await;
+    let now = pool.now().await;
+    let window = MaintenanceWindow {
+        start_ts: now + HOUR,
+        end_ts: now + 2 * HOUR,
+    };
+    pool.set_trading_schedule(TradingHours::default(), &[window])
+        .await
+        .unwrap();
+    let state = pool.state().await;
+    assert_eq!(state.maintenance_windows[0], window);
+    assert_eq!(state.next_maintenance(now), Some(window));
+
+    pool.execute_defi_instruction(swap(), &user).await.unwrap();
+    pool.advance_clock(HOUR).await;
+    assert_eq!(
+        pool.execute_defi_instruction(swap(), &user).await,
+        Err(InstructionError::Custom(PoolError::PoolInMaintenance as u32))
+    );
+    let lp_balance = pool.balance(&user.lp).await;
+    pool.execute_defi_instruction(remove_uniform(lp_balance / 2), &user)
+        .await
+        .unwrap();
+    pool.advance_clock(HOUR).await;
+    pool.execute_defi_instruction(swap(), &user).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_invalid_trading_schedule() {
+    let (mut pool, _) = funded_pool().await;
+    let now = pool.now().await;
+    assert_eq!(
+        pool.set_trading_schedule(
+            TradingHours {
+                open_secs: 24 * HOUR as u32,
+                close_secs: 0,
+            },
+            &[],
+        )
+        .await,
+        Err(InstructionError::Custom(PoolError::InvalidTradingSchedule as u32))
+    );
+    let backwards = MaintenanceWindow {
+        start_ts: now + HOUR,
+        end_ts: now,
+    };
+    assert_eq!(
+        pool.set_trading_schedule(TradingHours::default(), &[backwards]).await,
+        Err(InstructionError::Custom(PoolError::InvalidTradingSchedule as u32))
+    );
+    let windows = [MaintenanceWindow {
+        start_ts: now + HOUR,
+        end_ts: now + 2 * HOUR,
+    }; MAX_MAINTENANCE_WINDOWS];
+    pool.set_trading_schedule(TradingHours::default(), &windows)
+        .await
+        .unwrap();
+}
@@ -0,0 +1,277 @@
+#![cfg(feature = "test-bpf")]
+
+mod helpers;
+
+use helpers::{bank::*, AmountT};
+
+use pool::{
+    error::PoolError,
+    instruction::{
+        create_routed_swap_ix, find_allowed_user_address, find_route_authority_address, find_user_gate_account,
+        DeFiInstruction, PoolInstruction, RouteLeg,
+    },
+    state::UserGate,
+    TOKEN_COUNT,
+};
+use solana_program::pubkey::Pubkey;
+use solana_program_test::*;
+use solana_sdk::{
+    account::Account, instruction::InstructionError, native_token::LAMPORTS_PER_SOL, signature::Signer, system_program,
+};
+use spl_associated_token_account::get_associated_token_address;
+
+const NOT_ALLOWED: InstructionError = InstructionError::Custom(PoolError::UserNotAllowed as u32);
+const DEPOSITOR_NOT_ALLOWED: InstructionError = InstructionError::Custom(PoolError::DepositorNotAllowed as u32);
+
+fn add(amount: AmountT) -> DeFiInstruction<TOKEN_COUNT> {
+    DeFiInstruction::Add {
+        input_amounts: [amount; TOKEN_COUNT],
+        minimum_mint_amount: 0,
+    }
+}
+
+fn swap(amount: AmountT) -> DeFiInstruction<TOKEN_COUNT> {
+    let mut exact_input_amounts = [0; TOKEN_COUNT];
+    exact_input_amounts[0] = amount;
+    DeFiInstruction::SwapExactInput {
+        exact_input_amounts,
+        output_token_index: 1,
+        minimum_output_amount: 0,
+    }
+}
+
+//a pool with liquidity from a user that added it before the gate went up
+async fn funded_pool() -> (BankPool, BankUser) {
+    let mut pool = BankPool::new(&BankPoolParams::default()).await.unwrap();
+    let seed = pool.create_user(&[100_000_000; TOKEN_COUNT]);
+    pool.execute_defi_instruction(add(100_000_000), &seed).await.unwrap();
+    (pool, seed)
+}
+
+#[tokio::test]
+async fn test_allowlist() {
+    let (mut pool, seed) = funded_pool().await;
+    pool.set_user_gate(UserGate::Allowlist).await.unwrap();
+    assert_eq!(pool.state().await.user_gate, UserGate::Allowlist);
+    let user = pool.create_user(&[10_000_000; TOKEN_COUNT]);
+
+    assert_eq!(
+        pool.execute_defi_instruction(add(1_000_000), &user).await,
+        Err(NOT_ALLOWED)
+    );
+    assert_eq!(
+        pool.execute_defi_instruction(swap(1_000_000), &user).await,
+        Err(NOT_ALLOWED)
+    );
+
+    let user_key = user.keypair.pubkey();
+    pool.set_user_allowed(&user_key, true).await.unwrap();
+    //allowing it again changes nothing
+    pool.set_user_allowed(&user_key, true).await.unwrap();
+    pool.execute_defi_instruction(add(1_000_000), &user).await.unwrap();
+    pool.execute_defi_instruction(swap(1_000_000), &user).await.unwrap();
+
+    //the allowed user account of another user doesn't let anyone in
+    let other = pool.create_user(&[10_000_000; TOKEN_COUNT]);
+    let mut ix = pool.defi_ix(swap(1_000_000), &other);
+    let gate_account = ix.accounts.last_mut().unwrap();
+    assert_eq!(
+        gate_account.pubkey,
+        find_allowed_user_address(&pool::id(), &pool.pool, &other.keypair.pubkey()).0
+    );
+    gate_account.pubkey = find_allowed_user_address(&pool::id(), &pool.pool, &user_key).0;
+    assert_eq!(pool.execute_transaction(ix, &[&other.keypair]).await, Err(NOT_ALLOWED));
+
+    //disallowed users keep their uniform removes
+    pool.set_user_allowed(&user_key, false).await.unwrap();
+    assert_eq!(
+        pool.context
+            .banks_client
+            .get_account(find_allowed_user_address(&pool::id(), &pool.pool, &user_key).0)
+            .await
+            .unwrap(),
+        None
+    );
+    assert_eq!(
+        pool.execute_defi_instruction(swap(1_000_000), &user).await,
+        Err(NOT_ALLOWED)
+    );
+    let lp_balance = pool.balance(&user.lp).await;
+    pool.execute_defi_instruction(
+        DeFiInstruction::RemoveUniform {
+            exact_burn_amount: lp_balance,
+            minimum_output_amounts: [0; TOKEN_COUNT],
+        },
+        &user,
+    )
+    .await
+    .unwrap();
+
+    //only the governance manages the allowlist
+    assert_eq!(
+        pool.execute_governance_instruction_as(
+            pool::instruction::GovernanceInstruction::SetUserGate {
+                user_gate: UserGate::Open
+            },
+            None,
+            &seed.keypair
+        )
+        .await,
+        Err(InstructionError::Custom(PoolError::InvalidGovernanceAccount as u32))
+    );
+
+    //opening the pool lets everyone in again
+    pool.set_user_gate(UserGate::Open).await.unwrap();
+    pool.execute_defi_instruction(swap(1_000_000), &other).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_gating_token() {
+    let (mut pool, _) = funded_pool().await;
+    let mint = Pubkey::new_unique();
+    pool.set_user_gate(UserGate::GatingToken { mint }).await.unwrap();
+    let user = pool.create_user(&[10_000_000; TOKEN_COUNT]);
+    let user_key = user.keypair.pubkey();
+    let gate_account = get_associated_token_address(&user_key, &mint);
+    assert_eq!(
+        find_user_gate_account(&pool::id(), &pool.pool, &UserGate::GatingToken { mint }, &user_key),
+        Some(gate_account)
+    );
+
+    //the account has to exist and hold some of the gating token
+    assert_eq!(
+        pool.execute_defi_instruction(swap(1_000_000), &user).await,
+        Err(NOT_ALLOWED)
+    );
+    pool.context
+        .set_account(&gate_account, &token_account(&mint, &user_key, 0).into());
+    assert_eq!(
+        pool.execute_defi_instruction(swap(1_000_000), &user).await,
+        Err(NOT_ALLOWED)
+    );
+    pool.context
+        .set_account(&gate_account, &token_account(&mint, &user_key, 1).into());
+    pool.execute_defi_instruction(swap(1_000_000), &user).await.unwrap();
+    pool.execute_defi_instruction(add(1_000_000), &user).await.unwrap();
+
+    //held by the user, not just anyone
+    let other = pool.create_user(&[10_000_000; TOKEN_COUNT]);
+    let other_gate_account = get_associated_token_address(&other.keypair.pubkey(), &mint);
+    pool.context
+        .set_account(&other_gate_account, &token_account(&mint, &user_key, 1).into());
+    assert_eq!(
+        pool.execute_defi_instruction(swap(1_000_000), &other).await,
+        Err(NOT_ALLOWED)
+    );
+    //of the gating mint
+    pool.context.set_account(
+        &other_gate_account,
+        &token_account(&Pubkey::new_unique(), &other.keypair.pubkey(), 1).into(),
+    );
+    assert_eq!(
+        pool.execute_defi_instruction(swap(1_000_000), &other).await,
+        Err(NOT_ALLOWED)
+    );
+}
+
+#[tokio::test]
+async fn test_gated_pool_as_second_route_leg() {
+    let (mut pool, _) = funded_pool().await;
+    pool.set_user_gate(UserGate::Allowlist).await.unwrap();
+    let user = pool.create_user(&[10_000_000; TOKEN_COUNT]);
+    let user_key = user.keypair.pubkey();
+    pool.set_user_allowed(&user_key, true).await.unwrap();
+    //pays for the intermediate token account
+    let user_account = Account::new(LAMPORTS_PER_SOL, 0, &system_program::id());
+    pool.context.set_account(&user_key, &user_account.into());
+
+    let gated_leg = |swapper: &Pubkey| {
+        let mut leg: RouteLeg<TOKEN_COUNT> = pool.route_leg();
+        leg.swap_accounts
+            .push(find_allowed_user_address(&pool::id(), &pool.pool, swapper).0);
+        leg
+    };
+    //the second leg swaps for the route authority, which isn't allowed
+    let ix = create_routed_swap_ix(
+        PoolInstruction::RoutedSwap {
+            exact_input_amount: 1_000_000,
+            input_token_index: 0,
+            first_output_index: 1,
+            second_input_index: 1,
+            output_token_index: 2,
+            minimum_output_amount: 0,
+        },
+        &pool::id(),
+        &user_key,
+        &user.tokens[0],
+        &user.tokens[2],
+        &pool.token_mints[1],
+        &gated_leg(&user_key),
+        &gated_leg(&find_route_authority_address(&pool::id()).0),
+    )
+    .unwrap();
+    assert_eq!(pool.execute_transaction(ix, &[&user.keypair]).await, Err(NOT_ALLOWED));
+}
+
+#[tokio::test]
+async fn test_depositor_allowlist() {
+    let (mut pool, seed) = funded_pool().await;
+    pool.set_depositor_gate(UserGate::Allowlist).await.unwrap();
+    assert_eq!(pool.state().await.depositor_gate, UserGate::Allowlist);
+    let remove_exact_burn = || DeFiInstruction::RemoveExactBurn {
+        exact_burn_amount: 1_000_000,
+        output_token_index: 0,
+        minimum_output_amount: 0,
+    };
+
+    //swaps stay public
+    let trader = pool.create_user(&[10_000_000; TOKEN_COUNT]);
+    pool.execute_defi_instruction(swap(1_000_000), &trader).await.unwrap();
+    assert_eq!(
+        pool.execute_defi_instruction(add(1_000_000), &trader).await,
+        Err(DEPOSITOR_NOT_ALLOWED)
+    );
+    //liquidity that was added before can only be removed uniformly
+    assert_eq!(
+        pool.execute_defi_instruction(remove_exact_burn(), &seed).await,
+        Err(DEPOSITOR_NOT_ALLOWED)
+    );
+    pool.execute_defi_instruction(
+        DeFiInstruction::RemoveUniform {
+            exact_burn_amount: 1_000_000,
+            minimum_output_amounts: [0; TOKEN_COUNT],
+        },
+        &seed,
+    )
+    .await
+    .unwrap();
+
+    //the depositor allowlist is separate from the user allowlist
+    let depositor = pool.create_user(&[10_000_000; TOKEN_COUNT]);
+    let depositor_key = depositor.keypair.pubkey();
+    pool.set_user_allowed(&depositor_key, true).await.unwrap();
+    assert_eq!(
+        pool.execute_defi_instruction(add(1_000_000), &depositor).await,
+        Err(DEPOSITOR_NOT_ALLOWED)
+    );
+    pool.set_depositor_allowed(&depositor_key, true).await.unwrap();
+    pool.execute_defi_instruction(add(1_000_000), &depositor).await.unwrap();
+    pool.execute_defi_instruction(remove_exact_burn(), &depositor)
+        .await
+        .unwrap();
+
+    //with both gates up, depositors have to pass both
+    pool.set_user_gate(UserGate::Allowlist).await.unwrap();
+    pool.execute_defi_instruction(add(1_000_000), &depositor).await.unwrap();
+    pool.set_user_allowed(&depositor_key, false).await.unwrap();
+    assert_eq!(
+        pool.execute_defi_instruction(add(1_000_000), &depositor).await,
+        Err(NOT_ALLOWED)
+    );
+    pool.set_depositor_allowed(&depositor_key, false).await.unwrap();
+    pool.set_user_gate(UserGate::Open).await.unwrap();
+    assert_eq!(
+        pool.execute_defi_instruction(add(1_000_000), &depositor).await,
+        Err(DEPOSITOR_NOT_ALLOWED)
+    );
+}
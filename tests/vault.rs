@@ -0,0 +1,335 @@
+#![cfg(feature = "test-bpf")]
+
+mod helpers;
+
+use helpers::{bank::*, AmountT};
+
+use borsh::BorshDeserialize;
+use pool::{
+    error::PoolError,
+    instruction::DeFiInstruction,
+    vault::{
+        create_compound_ix, create_deposit_ix, create_init_ix, create_withdraw_ix, find_vault_address,
+        process_instruction, VaultState,
+    },
+    TOKEN_COUNT,
+};
+use solana_program_test::*;
+use solana_sdk::{
+    instruction::{AccountMeta, InstructionError},
+    pubkey::Pubkey,
+    signature::Signer,
+};
+use spl_token::state::Mint;
+
+const POOL_BALANCE: AmountT = 1_000_000_000;
+const DEPOSIT: AmountT = 100_000_000;
+
+struct BankVault {
+    program_id: Pubkey,
+    key: Pubkey,
+    share_mint: Pubkey,
+    lp_account: Pubkey,
+    token_accounts: [Pubkey; TOKEN_COUNT],
+}
+
+impl BankVault {
+    //the user's accounts of a defi instruction, with the vault's lp token account in place of the user's and
+    // optionally the vault as user transfer authority of its token accounts (compounding) or the user's (withdrawals)
+    fn defi_accounts(
+        &self,
+        pool: &BankPool,
+        defi_instruction: DeFiInstruction<TOKEN_COUNT>,
+        user: &BankUser,
+        vault_authority: bool,
+        vault_tokens: bool,
+    ) -> Vec<AccountMeta> {
+        let mut accounts = pool.defi_ix(defi_instruction, user).accounts;
+        let user_tokens_start = 5 + TOKEN_COUNT;
+        if vault_authority {
+            accounts[4 + TOKEN_COUNT] = AccountMeta::new_readonly(self.key, false);
+        }
+        if vault_tokens {
+            for (i, token_account) in self.token_accounts.iter().enumerate() {
+                accounts[user_tokens_start + i] = AccountMeta::new(*token_account, false);
+            }
+        }
+        accounts[user_tokens_start + TOKEN_COUNT + 1] = AccountMeta::new(self.lp_account, false);
+        accounts
+    }
+
+    async fn state(&self, pool: &mut BankPool) -> VaultState<TOKEN_COUNT> {
+        let account = pool.context.banks_client.get_account(self.key).await.unwrap().unwrap();
+        assert_eq!(account.owner, self.program_id);
+        VaultState::deserialize(&mut account.data.as_slice()).unwrap()
+    }
+
+    async fn share_supply(&self, pool: &mut BankPool) -> AmountT {
+        pool.get_packed::<Mint>(&self.share_mint).await.supply
+    }
+
+    fn create_share_account(&self, pool: &mut BankPool, user: &BankUser) -> Pubkey {
+        let share_account = Pubkey::new_unique();
+        pool.set_token_account(&share_account, &self.share_mint, &user.keypair.pubkey(), 0);
+        share_account
+    }
+
+    async fn deposit(
+        &self,
+        pool: &mut BankPool,
+        user: &BankUser,
+        share_account: &Pubkey,
+        minimum_share_amount: AmountT,
+    ) -> Result<(), InstructionError> {
+        let input_amounts = [DEPOSIT; TOKEN_COUNT];
+        let add = DeFiInstruction::Add {
+            input_amounts,
+            minimum_mint_amount: 0,
+        };
+        let ix = create_deposit_ix(
+            &self.program_id,
+            &pool::id(),
+            &pool.pool,
+            &self.share_mint,
+            share_account,
+            &self.defi_accounts(pool, add, user, false, false),
+            input_amounts,
+            minimum_share_amount,
+        )
+        .unwrap();
+        pool.execute_transaction(ix, &[&user.keypair]).await
+    }
+
+    async fn withdraw(
+        &self,
+        pool: &mut BankPool,
+        user: &BankUser,
+        share_account: &Pubkey,
+        share_amount: AmountT,
+        vault_authority: bool,
+    ) -> Result<(), InstructionError> {
+        let remove = DeFiInstruction::RemoveUniform {
+            exact_burn_amount: 0,
+            minimum_output_amounts: [0; TOKEN_COUNT],
+        };
+        let remove_accounts = self.defi_accounts(pool, remove, user, vault_authority, false);
+        let ix = create_withdraw_ix(
+            &self.program_id,
+            &pool::id(),
+            &pool.pool,
+            &self.share_mint,
+            share_account,
+            &user.keypair.pubkey(),
+            &remove_accounts,
+            share_amount,
+            [0; TOKEN_COUNT],
+        )
+        .unwrap();
+        pool.execute_transaction(ix, &[&user.keypair]).await
+    }
+
+    async fn compound(&self, pool: &mut BankPool, anyone: &BankUser) -> Result<(), InstructionError> {
+        let add = DeFiInstruction::Add {
+            input_amounts: [0; TOKEN_COUNT],
+            minimum_mint_amount: 0,
+        };
+        let ix = create_compound_ix::<TOKEN_COUNT>(
+            &self.program_id,
+            &pool::id(),
+            &pool.pool,
+            &self.lp_account,
+            &pool.context.payer.pubkey(),
+            &self.defi_accounts(pool, add, anyone, true, true),
+        )
+        .unwrap();
+        pool.execute_transaction(ix, &[]).await
+    }
+}
+
+async fn funded_pool_with_vault() -> (BankPool, BankVault) {
+    let program_id = Pubkey::new_unique();
+    let mut pool = BankPool::new_with(&BankPoolParams::default(), |program_test| {
+        program_test.add_program("vault", program_id, processor!(process_instruction::<TOKEN_COUNT>));
+    })
+    .await
+    .unwrap();
+    let lp = pool.create_user(&[POOL_BALANCE; TOKEN_COUNT]);
+    pool.execute_defi_instruction(
+        DeFiInstruction::Add {
+            input_amounts: [POOL_BALANCE; TOKEN_COUNT],
+            minimum_mint_amount: 0,
+        },
+        &lp,
+    )
+    .await
+    .unwrap();
+
+    let key = find_vault_address(&program_id, &pool.pool).0;
+    let vault = BankVault {
+        program_id,
+        key,
+        share_mint: Pubkey::new_unique(),
+        lp_account: Pubkey::new_unique(),
+        token_accounts: [(); TOKEN_COUNT].map(|_| Pubkey::new_unique()),
+    };
+    pool.set_mint_account(&vault.share_mint, 6, &key);
+    let lp_mint = pool.lp_mint;
+    pool.set_token_account(&vault.lp_account, &lp_mint, &key, 0);
+    let token_mints = pool.token_mints;
+    for (token_account, mint) in vault.token_accounts.iter().zip(token_mints.iter()) {
+        pool.set_token_account(token_account, mint, &key, 0);
+    }
+    (pool, vault)
+}
+
+async fn swap(pool: &mut BankPool) {
+    let mut input_amounts = [0; TOKEN_COUNT];
+    input_amounts[0] = POOL_BALANCE / 10;
+    let trader = pool.create_user(&input_amounts);
+    pool.execute_defi_instruction(
+        DeFiInstruction::SwapExactInput {
+            exact_input_amounts: input_amounts,
+            output_token_index: 1,
+            minimum_output_amount: 0,
+        },
+        &trader,
+    )
+    .await
+    .unwrap();
+}
+
+#[tokio::test]
+async fn test_vault_init() {
+    let (mut pool, vault) = funded_pool_with_vault().await;
+    let init_ix = |pool: &BankPool, share_mint: &Pubkey| {
+        create_init_ix(
+            &vault.program_id,
+            &pool::id(),
+            &pool.pool,
+            share_mint,
+            &vault.lp_account,
+            &vault.token_accounts,
+            &pool.context.payer.pubkey(),
+        )
+        .unwrap()
+    };
+
+    //the vault has to be the share mint's authority
+    let foreign_mint = Pubkey::new_unique();
+    pool.set_mint_account(&foreign_mint, 6, &Pubkey::new_unique());
+    let ix = init_ix(&pool, &foreign_mint);
+    assert_eq!(
+        pool.execute_transaction(ix, &[]).await,
+        Err(InstructionError::Custom(PoolError::InvalidMintAccount as u32))
+    );
+
+    let ix = init_ix(&pool, &vault.share_mint);
+    pool.execute_transaction(ix, &[]).await.unwrap();
+    let state = vault.state(&mut pool).await;
+    assert_eq!(state.pool, pool.pool);
+    assert_eq!(state.share_mint, vault.share_mint);
+    assert_eq!(state.lp_account, vault.lp_account);
+    assert_eq!(state.token_accounts, vault.token_accounts);
+    assert_eq!(state.lp_fees_earned, 0);
+
+    let ix = init_ix(&pool, &vault.share_mint);
+    assert_eq!(
+        pool.execute_transaction(ix, &[]).await,
+        Err(InstructionError::AccountAlreadyInitialized)
+    );
+}
+
+#[tokio::test]
+async fn test_vault_compounds() {
+    let (mut pool, vault) = funded_pool_with_vault().await;
+    let ix = create_init_ix(
+        &vault.program_id,
+        &pool::id(),
+        &pool.pool,
+        &vault.share_mint,
+        &vault.lp_account,
+        &vault.token_accounts,
+        &pool.context.payer.pubkey(),
+    )
+    .unwrap();
+    pool.execute_transaction(ix, &[]).await.unwrap();
+
+    let alice = pool.create_user(&[DEPOSIT; TOKEN_COUNT]);
+    let bob = pool.create_user(&[DEPOSIT; TOKEN_COUNT]);
+    let alice_shares = vault.create_share_account(&mut pool, &alice);
+    let bob_shares = vault.create_share_account(&mut pool, &bob);
+    assert_eq!(
+        vault.deposit(&mut pool, &alice, &alice_shares, AmountT::MAX).await,
+        Err(InstructionError::Custom(PoolError::MinimumMintNotMet as u32))
+    );
+    vault.deposit(&mut pool, &alice, &alice_shares, 0).await.unwrap();
+    //the first deposit gets a share per lp token
+    let alice_share_amount = pool.balance(&alice_shares).await;
+    assert_eq!(alice_share_amount, pool.balance(&vault.lp_account).await);
+    assert_eq!(pool.balances(&alice.tokens).await, [0; TOKEN_COUNT]);
+
+    //fees make the lp tokens worth more, while a share stays worth an lp token
+    swap(&mut pool).await;
+    vault.compound(&mut pool, &bob).await.unwrap();
+    assert_eq!(vault.state(&mut pool).await.lp_fees_earned, 0);
+    swap(&mut pool).await;
+    vault.compound(&mut pool, &bob).await.unwrap();
+    let lp_fees_earned = vault.state(&mut pool).await.lp_fees_earned;
+    assert!(lp_fees_earned > 0);
+    let lp_before = pool.balance(&vault.lp_account).await;
+    vault.deposit(&mut pool, &bob, &bob_shares, 0).await.unwrap();
+    let bob_share_amount = pool.balance(&bob_shares).await;
+    assert_eq!(bob_share_amount, pool.balance(&vault.lp_account).await - lp_before);
+
+    //tokens that end up in the vault's token accounts are added to the pool for the share holders, which makes a
+    // share worth more than an lp token
+    let lp_before = pool.balance(&vault.lp_account).await;
+    let token_mint = pool.token_mints[0];
+    pool.set_token_account(&vault.token_accounts[0], &token_mint, &vault.key, DEPOSIT / 10);
+    vault.compound(&mut pool, &bob).await.unwrap();
+    assert!(pool.balance(&vault.lp_account).await > lp_before);
+    assert!(pool.balance(&vault.lp_account).await > vault.share_supply(&mut pool).await);
+    assert_eq!(pool.balances(&vault.token_accounts).await, [0; TOKEN_COUNT]);
+    assert!(vault.state(&mut pool).await.lp_fees_earned >= lp_fees_earned);
+
+    //withdrawals have to go through the vault as user transfer authority
+    assert_eq!(
+        vault
+            .withdraw(&mut pool, &alice, &alice_shares, alice_share_amount, false)
+            .await,
+        Err(InstructionError::InvalidArgument)
+    );
+    assert_eq!(
+        vault
+            .withdraw(&mut pool, &alice, &alice_shares, alice_share_amount + 1, true)
+            .await,
+        Err(InstructionError::Custom(
+            spl_token::error::TokenError::InsufficientFunds as u32
+        ))
+    );
+    vault
+        .withdraw(&mut pool, &alice, &alice_shares, alice_share_amount, true)
+        .await
+        .unwrap();
+    assert_eq!(pool.balance(&alice_shares).await, 0);
+    let alice_total: AmountT = pool.balances(&alice.tokens).await.iter().sum();
+    assert!(
+        alice_total > TOKEN_COUNT as AmountT * DEPOSIT,
+        "alice got {}",
+        alice_total
+    );
+
+    //the last share holder's withdrawal empties the vault
+    vault
+        .withdraw(&mut pool, &bob, &bob_shares, bob_share_amount, true)
+        .await
+        .unwrap();
+    assert_eq!(vault.share_supply(&mut pool).await, 0);
+    assert_eq!(pool.balance(&vault.lp_account).await, 0);
+    let bob_total: AmountT = pool.balances(&bob.tokens).await.iter().sum();
+    assert!(
+        bob_total > TOKEN_COUNT as AmountT * DEPOSIT * 99 / 100,
+        "bob got {}",
+        bob_total
+    );
+}
@@ -0,0 +1,144 @@
+#![cfg(feature = "test-bpf")]
+
+mod helpers;
+
+use helpers::{bank::*, AmountT};
+
+use pool::{
+    instruction::{find_virtual_price_address, DeFiInstruction, GovernanceInstruction},
+    oracle::{RateSource, RATE_ONE},
+    state::VIRTUAL_PRICE_EXPO,
+    TOKEN_COUNT,
+};
+use solana_program_test::*;
+use solana_sdk::{account::Account, pubkey::Pubkey, system_program};
+use spl_token::state::Account as TokenState;
+
+const POOL_BALANCE: AmountT = 1_000_000_000;
+const ONE: u64 = 1_000_000_000_000;
+
+async fn funded_pool() -> (BankPool, BankUser) {
+    let mut pool = BankPool::new(&BankPoolParams::default()).await.unwrap();
+    let user = pool.create_user(&[POOL_BALANCE; TOKEN_COUNT]);
+    pool.execute_defi_instruction(
+        DeFiInstruction::Add {
+            input_amounts: [POOL_BALANCE / 2; TOKEN_COUNT],
+            minimum_mint_amount: 0,
+        },
+        &user,
+    )
+    .await
+    .unwrap();
+    (pool, user)
+}
+
+#[tokio::test]
+async fn test_publish_virtual_price() {
+    let (mut pool, user) = funded_pool().await;
+    assert_eq!(VIRTUAL_PRICE_EXPO, -12);
+
+    pool.publish_virtual_price().await.unwrap();
+    let published = pool.virtual_price().await;
+    assert_eq!(published.pool, pool.pool);
+    assert_eq!(published.expo, VIRTUAL_PRICE_EXPO);
+    assert_eq!(published.price, ONE);
+    assert_eq!(published.conf, 0);
+    assert!(!published.is_paused);
+    assert_eq!(published.lp_supply, pool.lp_total_supply().await);
+    assert_eq!(published.publish_time, pool.now().await);
+
+    //fees raise the virtual price, republishing overwrites the account
+    for _ in 0..10 {
+        pool.execute_defi_instruction(
+            DeFiInstruction::SwapExactInput {
+                exact_input_amounts: {
+                    let mut amounts = [0; TOKEN_COUNT];
+                    amounts[0] = POOL_BALANCE / 100;
+                    amounts
+                },
+                output_token_index: 1,
+                minimum_output_amount: 0,
+            },
+            &user,
+        )
+        .await
+        .unwrap();
+    }
+    pool.advance_clock(60).await;
+    pool.publish_virtual_price().await.unwrap();
+    let republished = pool.virtual_price().await;
+    assert!(republished.price > ONE);
+    //only the rounding of the depth that the pool recorded after the last swap
+    assert!(republished.conf < ONE / 1_000_000);
+    assert!(republished.publish_slot > published.publish_slot);
+    assert_eq!(republished.publish_time, published.publish_time + 60);
+
+    //tokens sent to the pool directly raise the price too, but only the price at the pool's recorded depth accounts for
+    // them after the next instruction, which the confidence reflects
+    let token_account = pool.token_accounts[0];
+    let mut token_state = pool.get_packed::<TokenState>(&token_account).await;
+    token_state.amount += POOL_BALANCE / 10;
+    pool.set_packed(&token_account, token_state);
+    pool.publish_virtual_price().await.unwrap();
+    let donated = pool.virtual_price().await;
+    assert!(donated.price > republished.price);
+    assert_eq!(donated.conf, donated.price - republished.price + republished.conf);
+
+    pool.execute_governance_instruction(GovernanceInstruction::SetPaused { paused: true }, None)
+        .await
+        .unwrap();
+    pool.publish_virtual_price().await.unwrap();
+    assert!(pool.virtual_price().await.is_paused);
+}
+
+#[tokio::test]
+async fn test_publish_virtual_price_to_prefunded_address() {
+    let (mut pool, _) = funded_pool().await;
+    //lamports sent to the address in advance must not keep the account from being created
+    let key = find_virtual_price_address(&pool::id(), &pool.pool).0;
+    let prefunded = Account {
+        lamports: 1_000,
+        owner: system_program::id(),
+        ..Account::default()
+    };
+    pool.context.set_account(&key, &prefunded.into());
+
+    pool.publish_virtual_price().await.unwrap();
+    assert_eq!(pool.virtual_price().await.price, ONE);
+}
+
+#[tokio::test]
+async fn test_rate_changes_dont_count_as_uncertainty() {
+    let (mut pool, user) = funded_pool().await;
+
+    //the pool's first token accrues 10% of yield since the last instruction
+    pool.set_rate_source(
+        0,
+        RateSource::Fixed {
+            rate: RATE_ONE / 10 * 11,
+        },
+        Pubkey::default(),
+    )
+    .await
+    .unwrap();
+    pool.publish_virtual_price().await.unwrap();
+    let published = pool.virtual_price().await;
+    assert!(published.price > ONE);
+    assert!(published.conf < ONE / 1_000_000, "{}", published.conf);
+
+    //uniform removes don't value the balances and leave the depth at the rates it was recorded at
+    let exact_burn_amount = pool.balance(&user.lp).await / 10;
+    pool.execute_defi_instruction(
+        DeFiInstruction::RemoveUniform {
+            exact_burn_amount,
+            minimum_output_amounts: [0; TOKEN_COUNT],
+        },
+        &user,
+    )
+    .await
+    .unwrap();
+    assert_eq!(pool.state().await.depth_rates, [RATE_ONE; TOKEN_COUNT]);
+    pool.publish_virtual_price().await.unwrap();
+    let republished = pool.virtual_price().await;
+    assert!(republished.conf < ONE / 1_000_000, "{}", republished.conf);
+}
@@ -0,0 +1,159 @@
+#![cfg(feature = "test-bpf")]
+
+mod helpers;
+
+use helpers::{bank::*, AmountT};
+
+use pool::{
+    error::PoolError,
+    instruction::DeFiInstruction,
+    state::InvariantKind,
+    weighted::{MIN_WEIGHT, WEIGHT_ONE},
+    TOKEN_COUNT,
+};
+use solana_program_test::*;
+use solana_sdk::instruction::InstructionError;
+
+//pool balances per unit of weight
+const BALANCE_PER_WEIGHT: AmountT = 1_000_000;
+const SWAP_AMOUNT: AmountT = 1_000_000;
+
+fn pool_error(error: PoolError) -> InstructionError {
+    InstructionError::Custom(error as u32)
+}
+
+//80% of the pool's value in its first token, the rest split evenly
+fn weights() -> [u32; TOKEN_COUNT] {
+    let other_weight = WEIGHT_ONE / 5 / (TOKEN_COUNT as u32 - 1);
+    let mut weights = [other_weight; TOKEN_COUNT];
+    weights[0] = WEIGHT_ONE - other_weight * (TOKEN_COUNT as u32 - 1);
+    weights
+}
+
+//a weighted pool whose first token is worth twice as much as the others, funded with balances of the weights' values
+async fn weighted_pool() -> (BankPool, BankUser) {
+    let weights = weights();
+    let mut pool = BankPool::new_weighted(&BankPoolParams::default(), weights)
+        .await
+        .unwrap();
+    let mut input_amounts = [0; TOKEN_COUNT];
+    for i in 0..TOKEN_COUNT {
+        input_amounts[i] = weights[i] as AmountT * BALANCE_PER_WEIGHT;
+    }
+    input_amounts[0] /= 2;
+    let user = pool.create_user(&[WEIGHT_ONE as AmountT * BALANCE_PER_WEIGHT; TOKEN_COUNT]);
+    pool.execute_defi_instruction(
+        DeFiInstruction::Add {
+            input_amounts,
+            minimum_mint_amount: 0,
+        },
+        &user,
+    )
+    .await
+    .unwrap();
+    (pool, user)
+}
+
+#[tokio::test]
+async fn test_init_weighted_validates_weights() {
+    let params = BankPoolParams::default();
+    let mut invalid_weights = weights();
+    invalid_weights[0] -= 1;
+    let mut too_light = weights();
+    too_light[0] += too_light[1] - (MIN_WEIGHT - 1);
+    too_light[1] = MIN_WEIGHT - 1;
+    for token_weights in vec![invalid_weights, too_light] {
+        let mut pool = BankPool::new_uninitialized(&params).await;
+        let init_ix = pool.init_weighted_ix(&params, token_weights);
+        assert_eq!(
+            pool.execute_transaction(init_ix, &[]).await,
+            Err(pool_error(PoolError::InvalidTokenWeights))
+        );
+    }
+
+    let mut pool = BankPool::new_weighted(&params, weights()).await.unwrap();
+    let state = pool.state().await;
+    assert_eq!(state.invariant_kind, InvariantKind::WeightedProduct);
+    assert_eq!(state.token_weights, weights());
+
+    let state = BankPool::new(&params).await.unwrap().state().await;
+    assert_eq!(state.invariant_kind, InvariantKind::StableSwap);
+    assert_eq!(state.token_weights, [0; TOKEN_COUNT]);
+}
+
+#[tokio::test]
+async fn test_weighted_swaps_price_tokens_by_their_weights() {
+    let (mut pool, user) = weighted_pool().await;
+
+    let mut exact_input_amounts = [0; TOKEN_COUNT];
+    exact_input_amounts[1] = SWAP_AMOUNT;
+    let output_before = pool.balance(&user.tokens[0]).await;
+    pool.execute_defi_instruction(
+        DeFiInstruction::SwapExactInput {
+            exact_input_amounts,
+            output_token_index: 0,
+            minimum_output_amount: 0,
+        },
+        &user,
+    )
+    .await
+    .unwrap();
+    //the first token is worth two of the second
+    let output = pool.balance(&user.tokens[0]).await - output_before;
+    assert!(output < SWAP_AMOUNT / 2, "{}", output);
+    assert!(output > SWAP_AMOUNT / 2 * 99 / 100, "{}", output);
+
+    //and the other way round
+    let mut exact_output_amounts = [0; TOKEN_COUNT];
+    exact_output_amounts[1] = SWAP_AMOUNT;
+    let input_before = pool.balance(&user.tokens[0]).await;
+    pool.execute_defi_instruction(
+        DeFiInstruction::SwapExactOutput {
+            maximum_input_amount: SWAP_AMOUNT,
+            input_token_index: 0,
+            exact_output_amounts,
+        },
+        &user,
+    )
+    .await
+    .unwrap();
+    let input = input_before - pool.balance(&user.tokens[0]).await;
+    assert!(input > SWAP_AMOUNT / 2, "{}", input);
+    assert!(input < SWAP_AMOUNT / 2 * 101 / 100, "{}", input);
+}
+
+#[tokio::test]
+async fn test_weighted_adds_and_removes_dont_profit() {
+    let (mut pool, user) = weighted_pool().await;
+    let balances_before = pool.balances(&user.tokens).await;
+    let lp_before = pool.balance(&user.lp).await;
+
+    let mut input_amounts = [0; TOKEN_COUNT];
+    input_amounts[1] = SWAP_AMOUNT;
+    pool.execute_defi_instruction(
+        DeFiInstruction::Add {
+            input_amounts,
+            minimum_mint_amount: 0,
+        },
+        &user,
+    )
+    .await
+    .unwrap();
+    let minted = pool.balance(&user.lp).await - lp_before;
+    assert!(minted > 0);
+    pool.execute_defi_instruction(
+        DeFiInstruction::RemoveExactBurn {
+            exact_burn_amount: minted,
+            output_token_index: 1,
+            minimum_output_amount: 0,
+        },
+        &user,
+    )
+    .await
+    .unwrap();
+
+    let balances_after = pool.balances(&user.tokens).await;
+    assert!(balances_after[1] < balances_before[1]);
+    assert!(balances_after[1] > balances_before[1] - SWAP_AMOUNT / 100);
+    assert_eq!(pool.balance(&user.lp).await, lp_before);
+}
@@ -0,0 +1,177 @@
+#![cfg(feature = "test-bpf")]
+
+mod helpers;
+
+use helpers::{bank::*, AmountT};
+
+use pool::{
+    error::PoolError,
+    instruction::DeFiInstruction,
+    state::{MAX_WITHDRAWAL_COOLDOWN, WITHDRAWAL_ANNOUNCEMENT_VALIDITY},
+    TOKEN_COUNT,
+};
+use solana_program::clock::UnixTimestamp;
+use solana_program_test::*;
+use solana_sdk::{instruction::InstructionError, signature::Signer};
+
+const POOL_BALANCE: AmountT = 1_000_000_000;
+const COOLDOWN: UnixTimestamp = 600;
+
+fn remove_exact_burn(exact_burn_amount: AmountT) -> DeFiInstruction<TOKEN_COUNT> {
+    DeFiInstruction::RemoveExactBurn {
+        exact_burn_amount,
+        output_token_index: 0,
+        minimum_output_amount: 0,
+    }
+}
+
+//a pool whose removes above a tenth of the user's lp tokens need an announcement
+async fn cooldown_pool() -> (BankPool, BankUser, AmountT) {
+    let mut pool = BankPool::new(&BankPoolParams::default()).await.unwrap();
+    let user = pool.create_user(&[POOL_BALANCE; TOKEN_COUNT]);
+    pool.execute_defi_instruction(
+        DeFiInstruction::Add {
+            input_amounts: [POOL_BALANCE / 2; TOKEN_COUNT],
+            minimum_mint_amount: 0,
+        },
+        &user,
+    )
+    .await
+    .unwrap();
+    let threshold = pool.balance(&user.lp).await / 10;
+    pool.set_withdrawal_cooldown(threshold, COOLDOWN).await.unwrap();
+    (pool, user, threshold)
+}
+
+#[tokio::test]
+async fn test_small_and_uniform_removes_need_no_announcement() {
+    let (mut pool, user, threshold) = cooldown_pool().await;
+    pool.execute_defi_instruction(remove_exact_burn(threshold), &user)
+        .await
+        .unwrap();
+    pool.execute_defi_instruction(
+        DeFiInstruction::RemoveUniform {
+            exact_burn_amount: 2 * threshold,
+            minimum_output_amounts: [0; TOKEN_COUNT],
+        },
+        &user,
+    )
+    .await
+    .unwrap();
+    assert_eq!(
+        pool.execute_defi_instruction(remove_exact_burn(threshold + 1), &user)
+            .await,
+        Err(InstructionError::Custom(PoolError::WithdrawalNotAnnounced as u32))
+    );
+}
+
+#[tokio::test]
+async fn test_small_removes_of_a_slot_add_up() {
+    let (mut pool, user, threshold) = cooldown_pool().await;
+    //two removes of the threshold in one transaction burn twice what the pool lets go unannounced
+    let ix = pool.defi_ix(remove_exact_burn(threshold), &user);
+    assert_eq!(
+        pool.execute_instructions(&[ix.clone(), ix.clone()], &[&user.keypair])
+            .await,
+        Err(InstructionError::Custom(PoolError::WithdrawalNotAnnounced as u32))
+    );
+    pool.execute_transaction(ix.clone(), &[&user.keypair]).await.unwrap();
+    assert_eq!(pool.state().await.unannounced_burn_amount, threshold);
+
+    //the running total starts over with the next slot
+    pool.advance_slots(1).await;
+    pool.execute_transaction(ix, &[&user.keypair]).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_announced_withdrawal() {
+    let (mut pool, user, threshold) = cooldown_pool().await;
+    pool.announce_withdrawal(&user, 3 * threshold).await.unwrap();
+    let announcement = pool.withdrawal_announcement(&user.keypair.pubkey()).await.unwrap();
+    assert_eq!(announcement.lp_amount, 3 * threshold);
+
+    assert_eq!(
+        pool.execute_defi_instruction(remove_exact_burn(2 * threshold), &user)
+            .await,
+        Err(InstructionError::Custom(PoolError::WithdrawalCooldownActive as u32))
+    );
+    pool.advance_clock(COOLDOWN).await;
+    //simulations are checked alike but don't spend the announcement
+    pool.execute_defi_instruction(
+        DeFiInstruction::Simulate(Box::new(remove_exact_burn(2 * threshold))),
+        &user,
+    )
+    .await
+    .unwrap();
+    pool.execute_defi_instruction(remove_exact_burn(2 * threshold), &user)
+        .await
+        .unwrap();
+    let announcement = pool.withdrawal_announcement(&user.keypair.pubkey()).await.unwrap();
+    assert_eq!(announcement.lp_amount, threshold);
+    //removes can't burn more than what's left of the announcement
+    assert_eq!(
+        pool.execute_defi_instruction(remove_exact_burn(2 * threshold), &user)
+            .await,
+        Err(InstructionError::Custom(PoolError::WithdrawalNotAnnounced as u32))
+    );
+
+    //announcing again restarts the cooldown
+    pool.announce_withdrawal(&user, 3 * threshold).await.unwrap();
+    assert_eq!(
+        pool.execute_defi_instruction(remove_exact_burn(2 * threshold), &user)
+            .await,
+        Err(InstructionError::Custom(PoolError::WithdrawalCooldownActive as u32))
+    );
+
+    //and announcing 0 withdraws the announcement
+    pool.announce_withdrawal(&user, 0).await.unwrap();
+    assert_eq!(pool.withdrawal_announcement(&user.keypair.pubkey()).await, None);
+}
+
+#[tokio::test]
+async fn test_announcement_expires() {
+    let (mut pool, user, threshold) = cooldown_pool().await;
+    pool.announce_withdrawal(&user, 2 * threshold).await.unwrap();
+    pool.advance_clock(COOLDOWN + WITHDRAWAL_ANNOUNCEMENT_VALIDITY).await;
+    assert_eq!(
+        pool.execute_defi_instruction(remove_exact_burn(2 * threshold), &user)
+            .await,
+        Err(InstructionError::Custom(PoolError::WithdrawalNotAnnounced as u32))
+    );
+}
+
+#[tokio::test]
+async fn test_exact_output_removes_are_covered() {
+    let (mut pool, user, threshold) = cooldown_pool().await;
+    let mut exact_output_amounts = [0; TOKEN_COUNT];
+    exact_output_amounts[0] = POOL_BALANCE / 4;
+    let remove = DeFiInstruction::RemoveExactOutput {
+        maximum_burn_amount: AmountT::MAX,
+        exact_output_amounts,
+    };
+    assert_eq!(
+        pool.execute_defi_instruction(remove.clone(), &user).await,
+        Err(InstructionError::Custom(PoolError::WithdrawalNotAnnounced as u32))
+    );
+    pool.announce_withdrawal(&user, 5 * threshold).await.unwrap();
+    pool.advance_clock(COOLDOWN).await;
+    pool.execute_defi_instruction(remove, &user).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_set_withdrawal_cooldown() {
+    let (mut pool, user, threshold) = cooldown_pool().await;
+    let state = pool.state().await;
+    assert_eq!(state.withdrawal_cooldown_threshold, threshold);
+    assert_eq!(state.withdrawal_cooldown, COOLDOWN);
+    assert_eq!(
+        pool.set_withdrawal_cooldown(threshold, MAX_WITHDRAWAL_COOLDOWN + 1)
+            .await,
+        Err(InstructionError::InvalidInstructionData)
+    );
+
+    pool.set_withdrawal_cooldown(0, 0).await.unwrap();
+    pool.execute_defi_instruction(remove_exact_burn(2 * threshold), &user)
+        .await
+        .unwrap();
+}
@@ -0,0 +1,273 @@
+#![cfg(feature = "test-bpf")]
+
+mod helpers;
+
+use helpers::{bank::*, AmountT};
+
+use borsh::{BorshDeserialize, BorshSerialize};
+use pool::{
+    error::PoolError,
+    instruction::{DeFiInstruction, GovernanceInstruction, YieldAdapterInstruction},
+    state::YIELD_DELEGATE_SEED,
+    TOKEN_COUNT,
+};
+use solana_program::{
+    account_info::AccountInfo,
+    entrypoint::ProgramResult,
+    program::{invoke_signed, set_return_data},
+    program_error::ProgramError,
+    program_option::COption,
+    program_pack::Pack,
+};
+use solana_program_test::*;
+use solana_sdk::{
+    instruction::{AccountMeta, InstructionError},
+    pubkey::Pubkey,
+};
+use spl_token::{instruction::transfer, state::Account as TokenState};
+
+const POOL_BALANCE: AmountT = 1_000_000_000;
+const BUFFER: AmountT = POOL_BALANCE / 4;
+//the token that the pool deploys
+const DEPLOYED: usize = 0;
+const MARKET_SEED: &[u8] = b"market";
+
+fn pool_error(error: PoolError) -> InstructionError {
+    InstructionError::Custom(error as u32)
+}
+
+//a yield market that keeps what the pool deploys in a token account owned by its own address and reports the account's
+// balance as the pool's position, so whatever else ends up in the account is yield
+fn process_market(program_id: &Pubkey, accounts: &[AccountInfo], data: &[u8]) -> ProgramResult {
+    let (pool, yield_delegate, pool_token_account, token_program, market, market_authority) = match accounts {
+        [pool, yield_delegate, pool_token_account, token_program, market, market_authority] => (
+            pool,
+            yield_delegate,
+            pool_token_account,
+            token_program,
+            market,
+            market_authority,
+        ),
+        _ => return Err(ProgramError::NotEnoughAccountKeys),
+    };
+    match YieldAdapterInstruction::try_from_slice(data)? {
+        YieldAdapterInstruction::Deposit { amount } => {
+            let bump = Pubkey::find_program_address(&[YIELD_DELEGATE_SEED, &pool.key.to_bytes()], program_id).1;
+            invoke_signed(
+                &transfer(
+                    token_program.key,
+                    pool_token_account.key,
+                    market.key,
+                    yield_delegate.key,
+                    &[],
+                    amount,
+                )?,
+                &[
+                    pool_token_account.clone(),
+                    market.clone(),
+                    yield_delegate.clone(),
+                    token_program.clone(),
+                ],
+                &[&[YIELD_DELEGATE_SEED, &pool.key.to_bytes(), &[bump]]],
+            )?
+        }
+        YieldAdapterInstruction::Withdraw { amount } => {
+            let bump = Pubkey::find_program_address(&[MARKET_SEED], program_id).1;
+            invoke_signed(
+                &transfer(
+                    token_program.key,
+                    market.key,
+                    pool_token_account.key,
+                    market_authority.key,
+                    &[],
+                    amount,
+                )?,
+                &[
+                    market.clone(),
+                    pool_token_account.clone(),
+                    market_authority.clone(),
+                    token_program.clone(),
+                ],
+                &[&[MARKET_SEED, &[bump]]],
+            )?
+        }
+    }
+    let position = TokenState::unpack(&market.data.borrow())?.amount;
+    set_return_data(&position.try_to_vec()?);
+    Ok(())
+}
+
+struct Market {
+    program: Pubkey,
+    account: Pubkey,
+    authority: Pubkey,
+}
+
+impl Market {
+    fn accounts(&self) -> [AccountMeta; 2] {
+        [
+            AccountMeta::new(self.account, false),
+            AccountMeta::new_readonly(self.authority, false),
+        ]
+    }
+}
+
+fn swap(input_index: usize, output_index: usize, amount: AmountT) -> DeFiInstruction<TOKEN_COUNT> {
+    let mut exact_input_amounts = [0; TOKEN_COUNT];
+    exact_input_amounts[input_index] = amount;
+    DeFiInstruction::SwapExactInput {
+        exact_input_amounts,
+        output_token_index: output_index as u8,
+        minimum_output_amount: 0,
+    }
+}
+
+async fn pool_with_market() -> (BankPool, BankUser, Market) {
+    let program = Pubkey::new_unique();
+    let mut pool = BankPool::new_with(&BankPoolParams::default(), |program_test| {
+        program_test.add_program("yield_market", program, processor!(process_market));
+    })
+    .await
+    .unwrap();
+    let user = pool.create_user(&[2 * POOL_BALANCE; TOKEN_COUNT]);
+    pool.execute_defi_instruction(
+        DeFiInstruction::Add {
+            input_amounts: [POOL_BALANCE; TOKEN_COUNT],
+            minimum_mint_amount: 0,
+        },
+        &user,
+    )
+    .await
+    .unwrap();
+    let market = Market {
+        program,
+        account: Pubkey::new_unique(),
+        authority: Pubkey::find_program_address(&[MARKET_SEED], &program).0,
+    };
+    let mint = pool.token_mints[DEPLOYED];
+    pool.set_token_account(&market.account, &mint, &market.authority, 0);
+    pool.set_yield_adapter(DEPLOYED as u8, &market.program, BUFFER)
+        .await
+        .unwrap();
+    (pool, user, market)
+}
+
+#[tokio::test]
+async fn test_deploy_and_recall_yield() {
+    let (mut pool, user, market) = pool_with_market().await;
+    assert_eq!(
+        pool.deploy_yield(DEPLOYED as u8, &Pubkey::new_unique(), &market.accounts())
+            .await,
+        Err(pool_error(PoolError::InvalidYieldAdapter))
+    );
+    pool.deploy_yield(DEPLOYED as u8, &market.program, &market.accounts())
+        .await
+        .unwrap();
+    assert_eq!(pool.pool_balances().await[DEPLOYED], BUFFER);
+    assert_eq!(pool.balance(&market.account).await, POOL_BALANCE - BUFFER);
+    assert_eq!(
+        pool.state().await.yield_adapters[DEPLOYED].deployed,
+        POOL_BALANCE - BUFFER
+    );
+    //the adapter's delegate was only approved for the deposit
+    let pool_token_account = pool.token_accounts[DEPLOYED];
+    let account = pool
+        .context
+        .banks_client
+        .get_account(pool_token_account)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(TokenState::unpack(&account.data).unwrap().delegate, COption::None);
+
+    //the buffer covers small trades, larger ones need a recall first
+    pool.execute_defi_instruction(swap(1, DEPLOYED, BUFFER / 10), &user)
+        .await
+        .unwrap();
+    assert_eq!(
+        pool.execute_defi_instruction(swap(1, DEPLOYED, POOL_BALANCE / 2), &user)
+            .await,
+        Err(pool_error(PoolError::YieldBufferShort))
+    );
+    assert_eq!(
+        pool.set_yield_adapter(DEPLOYED as u8, &Pubkey::default(), 0).await,
+        Err(pool_error(PoolError::InvalidYieldAdapter))
+    );
+
+    //users can always exit, so recalls don't stop with the pool
+    pool.execute_governance_instruction(GovernanceInstruction::SetPaused { paused: true }, None)
+        .await
+        .unwrap();
+    assert_eq!(
+        pool.deploy_yield(DEPLOYED as u8, &market.program, &market.accounts())
+            .await,
+        Err(pool_error(PoolError::PoolIsPaused))
+    );
+    pool.recall_yield(
+        DEPLOYED as u8,
+        POOL_BALANCE - BUFFER,
+        &market.program,
+        &market.accounts(),
+    )
+    .await
+    .unwrap();
+    assert_eq!(pool.state().await.yield_adapters[DEPLOYED].deployed, 0);
+    pool.execute_governance_instruction(GovernanceInstruction::SetPaused { paused: false }, None)
+        .await
+        .unwrap();
+    pool.execute_defi_instruction(swap(1, DEPLOYED, POOL_BALANCE / 2), &user)
+        .await
+        .unwrap();
+    pool.set_yield_adapter(DEPLOYED as u8, &Pubkey::default(), 0)
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn test_yield_accrues_to_liquidity_providers() {
+    const YIELD: AmountT = POOL_BALANCE / 10;
+    let (mut pool, user, market) = pool_with_market().await;
+    pool.deploy_yield(DEPLOYED as u8, &market.program, &market.accounts())
+        .await
+        .unwrap();
+
+    //the market pays out yield, which the pool doesn't take the adapter's word for
+    let mint = pool.token_mints[DEPLOYED];
+    pool.set_token_account(&market.account, &mint, &market.authority, POOL_BALANCE - BUFFER + YIELD);
+    pool.deploy_yield(DEPLOYED as u8, &market.program, &market.accounts())
+        .await
+        .unwrap();
+    assert_eq!(
+        pool.state().await.yield_adapters[DEPLOYED].deployed,
+        POOL_BALANCE - BUFFER
+    );
+
+    //but counts it once it is recalled
+    pool.recall_yield(
+        DEPLOYED as u8,
+        POOL_BALANCE - BUFFER + YIELD,
+        &market.program,
+        &market.accounts(),
+    )
+    .await
+    .unwrap();
+    assert_eq!(pool.state().await.yield_adapters[DEPLOYED].deployed, 0);
+    assert_eq!(pool.pool_balances().await[DEPLOYED], POOL_BALANCE + YIELD);
+
+    //a uniform remove of a tenth of the pool pays out a tenth of the yield on top
+    let other = 1 - DEPLOYED;
+    let before = pool.balances(&user.tokens).await;
+    let lp_balance = pool.balance(&user.lp).await;
+    pool.execute_defi_instruction(
+        DeFiInstruction::RemoveUniform {
+            exact_burn_amount: lp_balance / 10,
+            minimum_output_amounts: [0; TOKEN_COUNT],
+        },
+        &user,
+    )
+    .await
+    .unwrap();
+    let after = pool.balances(&user.tokens).await;
+    let output_amounts = [after[DEPLOYED] - before[DEPLOYED], after[other] - before[other]];
+    assert!(output_amounts[0] > output_amounts[1] + YIELD / 20);
+}